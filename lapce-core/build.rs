@@ -0,0 +1,42 @@
+use std::{env, fs, path::Path, process::Command};
+
+/// The output of a command, if it ran and succeeded
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    let output = output.trim();
+    (!output.is_empty()).then(|| output.to_string())
+}
+
+// Capture how Lapce is built, so that it can be shown in the about dialog and
+// in bug reports
+fn main() {
+    if let Some(commit) = command_output("git", &["rev-parse", "--short=10", "HEAD"])
+    {
+        println!("cargo:rustc-env=LAPCE_GIT_COMMIT={commit}");
+    }
+    // The commit changes when HEAD moves to another branch, or the branch it
+    // is on moves, whose ref may have been packed. Only the files that exist
+    // are watched, as cargo would run this again on each build otherwise.
+    let git_dir = Path::new("../.git");
+    let mut watched = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    if let Ok(head) = fs::read_to_string(git_dir.join("HEAD")) {
+        if let Some(branch) = head.trim().strip_prefix("ref: ") {
+            watched.push(git_dir.join(branch));
+        }
+    }
+    for path in watched.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"])
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=LAPCE_RUSTC_VERSION={rustc_version}");
+
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=LAPCE_TARGET={target}");
+}
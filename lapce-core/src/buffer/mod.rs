@@ -17,17 +17,18 @@
     tree::{Node, NodeInfo},
     Cursor, Delta, DeltaBuilder, DeltaElement, Interval, Rope, RopeDelta, RopeInfo,
 };
-use lsp_types::Position;
+use lsp_types::{Position, TextEdit};
 
 use crate::{
     char_buffer::CharBuffer,
     cursor::CursorMode,
     editor::EditType,
     indent::{auto_detect_indent_style, IndentStyle},
+    line_ending::LineEnding,
     mode::Mode,
     paragraph::ParagraphCursor,
     selection::Selection,
-    syntax::{self, edit::SyntaxEdit, Syntax},
+    syntax::{self, edit::SyntaxEdit, util::matching_pair_direction, Syntax},
     word::WordCursor,
 };
 
@@ -94,6 +95,9 @@ pub struct Buffer {
     last_edit_type: EditType,
 
     indent_style: IndentStyle,
+    line_ending: LineEnding,
+    /// Whether the text has other line endings than `line_ending`
+    mixed_line_endings: bool,
 
     max_len: usize,
     max_len_line: usize,
@@ -107,8 +111,11 @@ fn to_string(&self) -> String {
 
 impl Buffer {
     pub fn new(text: &str) -> Self {
+        let (text, line_ending, mixed_line_endings) =
+            LineEnding::read(Rope::from(text));
         Self {
-            text: Rope::from(text),
+            deletes_from_union: Subset::new(text.len()),
+            text,
 
             rev_counter: 1,
             pristine_rev_id: 0,
@@ -128,13 +135,14 @@ pub fn new(text: &str) -> Self {
             undos: BTreeSet::new(),
             undo_group_id: 1,
             live_undos: vec![0],
-            deletes_from_union: Subset::new(text.len()),
             undone_groups: BTreeSet::new(),
             tombstones: Rope::default(),
 
             this_edit_type: EditType::Other,
             last_edit_type: EditType::Other,
             indent_style: IndentStyle::DEFAULT_INDENT,
+            line_ending,
+            mixed_line_endings,
 
             max_len: 0,
             max_len_line: 0,
@@ -151,10 +159,30 @@ pub fn set_pristine(&mut self) {
         self.pristine_rev_id = self.rev();
     }
 
+    /// Mark the buffer as having unsaved changes, like when its file changed
+    /// on disk and the buffer was kept as it is. No revision is the saved one
+    /// then, so undoing doesn't make the buffer pristine either.
+    pub fn set_dirty(&mut self) {
+        self.pristine_rev_id = u64::MAX;
+    }
+
     pub fn is_pristine(&self) -> bool {
         self.is_equivalent_revision(self.pristine_rev_id, self.rev())
     }
 
+    /// The text as it was when the buffer was last saved, or `None` if no
+    /// revision is the saved one
+    pub fn pristine_text(&self) -> Option<Rope> {
+        let rev_index = self.find_rev(self.pristine_rev_id)?;
+        let deletes_from_union = self.deletes_from_cur_union_for_index(rev_index);
+        let delta = Delta::synthesize(
+            &self.tombstones,
+            &self.deletes_from_union,
+            &deletes_from_union,
+        );
+        Some(delta.apply(&self.text))
+    }
+
     pub fn set_cursor_before(&mut self, cursor: CursorMode) {
         if let Some(rev) = self.revs.last_mut() {
             rev.cursor_before = Some(cursor);
@@ -253,6 +281,7 @@ pub fn line_len(&self, line: usize) -> usize {
     }
 
     pub fn init_content(&mut self, content: Rope) {
+        let (content, line_ending, mixed_line_endings) = LineEnding::read(content);
         if !content.is_empty() {
             let delta = Delta::simple_edit(Interval::new(0, 0), content, 0);
             let (new_rev, new_text, new_tombstones, new_deletes_from_union, _) =
@@ -265,6 +294,8 @@ pub fn init_content(&mut self, content: Rope) {
                 new_deletes_from_union,
             );
         }
+        self.line_ending = line_ending;
+        self.mixed_line_endings = mixed_line_endings;
         self.set_pristine();
     }
 
@@ -273,6 +304,11 @@ pub fn reload(
         content: Rope,
         set_pristine: bool,
     ) -> (RopeDelta, InvalLines, SyntaxEdit) {
+        // The CR line endings of the file are LF in the text
+        let content = match self.line_ending {
+            LineEnding::Cr => LineEnding::Lf.normalize(&content).unwrap_or(content),
+            _ => content,
+        };
         let delta = LineHashDiff::compute_delta(&self.text, &content);
         self.this_edit_type = EditType::Other;
         let (delta, inval_lines, edits) = self.add_delta(delta);
@@ -282,6 +318,40 @@ pub fn reload(
         (delta, inval_lines, edits)
     }
 
+    /// The line ending of the new lines, which is the one used the most in the
+    /// text when it was loaded
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Whether the text had other line endings than [`Buffer::line_ending`]
+    /// when it was loaded
+    pub fn mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    /// Replace all the line endings of the text with the given one, which new
+    /// lines use too. Converting to or from CR only changes how the file is
+    /// saved, as CR line endings are LF in the text.
+    pub fn convert_line_endings(
+        &mut self,
+        line_ending: LineEnding,
+    ) -> Option<(RopeDelta, InvalLines, SyntaxEdit)> {
+        let saved_differently =
+            (self.line_ending == LineEnding::Cr) != (line_ending == LineEnding::Cr);
+        self.line_ending = line_ending;
+        self.mixed_line_endings = false;
+        match line_ending.in_text().normalize(&self.text) {
+            Some(content) => Some(self.reload(content, false)),
+            None => {
+                if saved_differently {
+                    self.set_dirty();
+                }
+                None
+            }
+        }
+    }
+
     pub fn detect_indent(&mut self, syntax: Option<&Syntax>) {
         self.indent_style =
             auto_detect_indent_style(&self.text).unwrap_or_else(|| {
@@ -291,6 +361,12 @@ pub fn detect_indent(&mut self, syntax: Option<&Syntax>) {
             });
     }
 
+    /// Indent with the style, like one set by the user, instead of the
+    /// detected one
+    pub fn set_indent_style(&mut self, indent_style: IndentStyle) {
+        self.indent_style = indent_style;
+    }
+
     pub fn indent_unit(&self) -> &'static str {
         self.indent_style.as_str()
     }
@@ -329,6 +405,39 @@ pub fn edit(
         self.add_delta(delta)
     }
 
+    /// The regions replaced by the edits of a language server, like the ones
+    /// of a formatter, to apply them with [`Buffer::edit`] as a single undo
+    /// step. The edits are all relative to the current text, so they don't
+    /// shift each other, and the ones overlapping an edit before them are left
+    /// out since applying both would garble the text.
+    pub fn text_edit_regions<'a>(
+        &self,
+        edits: &'a [TextEdit],
+    ) -> Vec<(Selection, &'a str)> {
+        let mut regions: Vec<(usize, usize, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let start = self.offset_of_position(&edit.range.start);
+                let end = self.offset_of_position(&edit.range.end);
+                (start.min(end), start.max(end), edit.new_text.as_str())
+            })
+            .collect();
+        regions.sort_by_key(|(start, end, _)| (*start, *end));
+
+        let mut last_end = 0;
+        regions
+            .into_iter()
+            .filter(|(start, end, _)| {
+                let overlaps = *start < last_end;
+                if !overlaps {
+                    last_end = *end;
+                }
+                !overlaps
+            })
+            .map(|(start, end, text)| (Selection::region(start, end), text))
+            .collect()
+    }
+
     fn add_delta(
         &mut self,
         delta: RopeDelta,
@@ -769,6 +878,14 @@ pub fn offset_of_line_col(&self, line: usize, col: usize) -> usize {
         RopeText::new(&self.text).offset_of_line_col(line, col)
     }
 
+    pub fn offset_to_line_char_col(&self, offset: usize) -> (usize, usize) {
+        RopeText::new(&self.text).offset_to_line_char_col(offset)
+    }
+
+    pub fn offset_of_line_char_col(&self, line: usize, col: usize) -> usize {
+        RopeText::new(&self.text).offset_of_line_char_col(line, col)
+    }
+
     pub fn line_end_col(&self, line: usize, caret: bool) -> usize {
         RopeText::new(&self.text).line_end_col(line, caret)
     }
@@ -892,6 +1009,36 @@ pub fn previous_unmatched(
         }
     }
 
+    /// Find the bracket at `offset`, or else right before it, and the bracket matching it.
+    /// Returns the offsets of `(bracket, matching bracket)`, or `None` when there is no
+    /// bracket there or it is unbalanced.
+    /// With a syntax tree the brackets in strings and comments are left out, without
+    /// one the brackets are matched by counting them.
+    pub fn find_matching_bracket(
+        &self,
+        syntax: Option<&Syntax>,
+        offset: usize,
+    ) -> Option<(usize, usize)> {
+        let syntax = syntax.filter(|syntax| syntax.layers.try_tree().is_some());
+        let match_bracket = |offset: usize| {
+            let c = self.char_at_offset(offset)?;
+            matching_pair_direction(c)?;
+            let matching = if let Some(syntax) = syntax {
+                syntax.find_matching_pair(offset)?
+            } else {
+                WordCursor::new(&self.text, offset).match_pairs()?
+            };
+            Some((offset, matching))
+        };
+
+        match_bracket(offset).or_else(|| {
+            let prev_offset = self.prev_grapheme_offset(offset, 1, 0);
+            (prev_offset < offset)
+                .then(|| match_bracket(prev_offset))
+                .flatten()
+        })
+    }
+
     /// Get the content of the rope as a Cow string, for 'nice' ranges (small, and at the right
     /// offsets) this will be a reference to the rope's data. Otherwise, it allocates a new string.
     /// You should be somewhat wary of requesting large parts of the rope, as it will allocate
@@ -1005,6 +1152,26 @@ pub fn move_n_words_backward(
     pub fn move_word_backward_deletion(&self, offset: usize) -> usize {
         self.find_nth_word(offset, 1, |cursor| cursor.prev_deletion_boundary())
     }
+
+    pub fn move_n_word_parts_right(&self, offset: usize, count: usize) -> usize {
+        self.find_nth_word(offset, count, |cursor| cursor.next_subword_end())
+    }
+
+    pub fn move_n_word_parts_left(
+        &self,
+        offset: usize,
+        count: usize,
+        mode: Mode,
+    ) -> usize {
+        self.find_nth_word(offset, count, |cursor| cursor.prev_subword_start(mode))
+    }
+
+    /// Like [`Buffer::move_word_backward_deletion`], but also stopping at the start
+    /// of the subword.
+    pub fn move_word_part_backward_deletion(&self, offset: usize) -> usize {
+        self.move_word_backward_deletion(offset)
+            .max(self.move_n_word_parts_left(offset, 1, Mode::Insert))
+    }
 }
 
 fn shuffle_tombstones(
@@ -1258,6 +1425,74 @@ pub fn rope_diff(
     Some(changes)
 }
 
+/// Groups the changed lines of a [`rope_diff`] into hunks of adjacent
+/// changes, as `(left lines, right lines)`. A hunk that only inserts lines
+/// has an empty left range positioned where the lines are inserted, and
+/// likewise for a hunk that only removes lines.
+pub fn diff_hunks(changes: &[DiffLines]) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut hunks = Vec::new();
+    let mut current: Option<(Range<usize>, Range<usize>)> = None;
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for change in changes {
+        match change {
+            DiffLines::Both(l, r) | DiffLines::Skip(l, r) => {
+                if let Some(hunk) = current.take() {
+                    hunks.push(hunk);
+                }
+                left_line = l.end;
+                right_line = r.end;
+            }
+            DiffLines::Left(l) => {
+                let hunk = current.get_or_insert_with(|| {
+                    (left_line..left_line, right_line..right_line)
+                });
+                hunk.0.end = l.end;
+                left_line = l.end;
+            }
+            DiffLines::Right(r) => {
+                let hunk = current.get_or_insert_with(|| {
+                    (left_line..left_line, right_line..right_line)
+                });
+                hunk.1.end = r.end;
+                right_line = r.end;
+            }
+        }
+    }
+    if let Some(hunk) = current {
+        hunks.push(hunk);
+    }
+    hunks
+}
+
+/// The right side line where the next hunk after `line` starts, or the
+/// previous one before it when going backwards. With `wrap` it continues
+/// from the other end of the document when there is no hunk in that
+/// direction.
+pub fn change_line(
+    hunks: &[(Range<usize>, Range<usize>)],
+    line: usize,
+    forward: bool,
+    wrap: bool,
+) -> Option<usize> {
+    let mut starts = hunks.iter().map(|(_, right)| right.start);
+    if forward {
+        starts.clone().find(|start| *start > line).or_else(|| {
+            if wrap {
+                starts.next()
+            } else {
+                None
+            }
+        })
+    } else {
+        starts
+            .clone()
+            .rev()
+            .find(|start| *start < line)
+            .or_else(|| if wrap { starts.next_back() } else { None })
+    }
+}
+
 pub struct DeltaValueRegion<'a, N: NodeInfo + 'a> {
     pub old_offset: usize,
     pub new_offset: usize,
@@ -102,11 +102,10 @@ pub fn offset_to_line_col(&self, offset: usize) -> (usize, usize) {
     pub fn offset_of_line_col(&self, line: usize, col: usize) -> usize {
         let mut pos = 0;
         let mut offset = self.offset_of_line(line);
-        for c in self
-            .slice_to_cow(offset..self.offset_of_line(line + 1))
-            .chars()
-        {
-            if c == '\n' {
+        let line_content = self.slice_to_cow(offset..self.offset_of_line(line + 1));
+        let mut chars = line_content.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\n' || (c == '\r' && chars.peek() == Some(&'\n')) {
                 return offset;
             }
 
@@ -120,6 +119,27 @@ pub fn offset_of_line_col(&self, line: usize, col: usize) -> usize {
         offset
     }
 
+    /// The line of the offset and its column counted in characters, which is
+    /// where it shows on the line rather than its byte column
+    pub fn offset_to_line_char_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len());
+        let line = self.line_of_offset(offset);
+        let line_start = self.offset_of_line(line);
+        (line, self.slice_to_cow(line_start..offset).chars().count())
+    }
+
+    /// The offset of the column counted in characters, clamped to the end of the
+    /// line
+    pub fn offset_of_line_char_col(&self, line: usize, col: usize) -> usize {
+        let line_start = self.offset_of_line(line);
+        let line_end = self.line_end_offset(line, true);
+        self.slice_to_cow(line_start..line_end)
+            .char_indices()
+            .nth(col)
+            .map(|(i, _)| line_start + i)
+            .unwrap_or(line_end)
+    }
+
     pub fn line_end_col(&self, line: usize, caret: bool) -> usize {
         let line_start = self.offset_of_line(line);
         let offset = self.line_end_offset(line, caret);
@@ -341,6 +361,20 @@ fn test_line_content() {
         assert_eq!(text.line_content(5), "");
     }
 
+    #[test]
+    fn test_line_char_col() {
+        let text = Rope::from("héllo\r\nwörld");
+        let text = RopeText::new(&text);
+
+        assert_eq!(text.offset_to_line_char_col(3), (0, 2));
+        assert_eq!(text.offset_of_line_char_col(0, 2), 3);
+        assert_eq!(text.offset_to_line_char_col(11), (1, 2));
+        assert_eq!(text.offset_of_line_char_col(1, 2), 11);
+        // Clamped to the end of the line, before its line ending
+        assert_eq!(text.offset_of_line_char_col(0, 10), 6);
+        assert_eq!(text.offset_of_line_char_col(1, 10), 14);
+    }
+
     #[test]
     fn test_offset_of_line() {
         let text = Rope::from("");
@@ -16,6 +16,131 @@ fn is_pristine() {
         buffer.do_undo();
         assert!(buffer.is_pristine());
     }
+
+    #[test]
+    fn set_dirty() {
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("abc"));
+        buffer.set_dirty();
+        assert!(!buffer.is_pristine());
+
+        buffer.edit(&[(Selection::caret(0), "d")], EditType::InsertChars);
+        buffer.do_undo();
+        assert!(!buffer.is_pristine());
+
+        buffer.set_pristine();
+        assert!(buffer.is_pristine());
+    }
+
+    #[test]
+    fn convert_mixed_line_endings() {
+        use crate::line_ending::LineEnding;
+
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("a\r\nb\nc\r\nd"));
+        assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+        assert!(buffer.mixed_line_endings());
+
+        assert!(buffer.convert_line_endings(LineEnding::Lf).is_some());
+        assert_eq!(buffer.to_string(), "a\nb\nc\nd");
+        assert_eq!(buffer.line_ending(), LineEnding::Lf);
+        assert!(!buffer.mixed_line_endings());
+        // The conversion is an edit, which is saved like any other
+        assert!(!buffer.is_pristine());
+        assert!(buffer.convert_line_endings(LineEnding::Lf).is_none());
+    }
+
+    #[test]
+    fn convert_cr_line_endings() {
+        use crate::line_ending::LineEnding;
+
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("a\rb\r"));
+        assert_eq!(buffer.to_string(), "a\nb\n");
+        assert_eq!(buffer.num_lines(), 3);
+
+        // Only how the file is saved changes
+        assert!(buffer.convert_line_endings(LineEnding::Lf).is_none());
+        assert_eq!(buffer.line_ending(), LineEnding::Lf);
+        assert!(!buffer.is_pristine());
+    }
+
+    #[test]
+    fn apply_formatter_edits() {
+        use lsp_types::{Position, Range, TextEdit};
+
+        let edit = |line, start, end, text: &str| TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: text.to_string(),
+        };
+        let text = "fn main(){\nlet x=1;\nlet y=x+1;\n}\n";
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from(text));
+
+        // The edits are all relative to the text before any of them, so the
+        // earlier ones don't shift the offsets of the later ones, whatever
+        // order they come in
+        let edits = vec![
+            edit(2, 7, 8, " + "),
+            edit(2, 5, 6, " = "),
+            edit(2, 0, 0, "    "),
+            edit(1, 5, 6, " = "),
+            edit(1, 0, 0, "    "),
+            edit(0, 9, 9, " "),
+            // Overlaps the edit of `=`, so it's left out
+            edit(1, 5, 8, "=="),
+        ];
+        let regions = buffer.text_edit_regions(&edits);
+        assert_eq!(regions.len(), 6);
+        buffer.edit(&regions, EditType::Other);
+        assert_eq!(
+            buffer.to_string(),
+            "fn main() {\n    let x = 1;\n    let y = x + 1;\n}\n"
+        );
+
+        // The edits are undone as one step
+        buffer.do_undo();
+        assert_eq!(buffer.to_string(), text);
+        assert!(buffer.is_pristine());
+    }
+
+    #[test]
+    fn undo_save_edits_together() {
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("a=1 \nb"));
+        buffer.edit(&[(Selection::region(1, 2), " = ")], EditType::Save);
+        buffer.edit(&[(Selection::region(5, 6), "")], EditType::Save);
+        buffer.edit(&[(Selection::caret(7), "\n")], EditType::Save);
+        assert_eq!(buffer.to_string(), "a = 1\nb\n");
+
+        buffer.do_undo();
+        assert_eq!(buffer.to_string(), "a=1 \nb");
+        assert!(buffer.is_pristine());
+    }
+
+    #[test]
+    fn pristine_text() {
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("abc"));
+        buffer.edit(&[(Selection::caret(3), "d")], EditType::InsertChars);
+        assert_eq!(buffer.pristine_text().unwrap().to_string(), "abc");
+
+        buffer.set_pristine();
+        buffer.edit(&[(Selection::region(0, 1), "")], EditType::Delete);
+        buffer.edit(&[(Selection::caret(0), "e")], EditType::InsertChars);
+        assert_eq!(buffer.to_string(), "ebcd");
+        assert_eq!(buffer.pristine_text().unwrap().to_string(), "abcd");
+
+        // The saved revision can be one that was undone
+        buffer.do_undo();
+        buffer.do_undo();
+        buffer.do_undo();
+        assert_eq!(buffer.to_string(), "abc");
+        assert_eq!(buffer.pristine_text().unwrap().to_string(), "abcd");
+
+        buffer.set_dirty();
+        assert!(buffer.pristine_text().is_none());
+    }
 }
 
 mod motion {
@@ -166,3 +291,122 @@ fn v(buf: &Buffer, off: usize, n: usize, end: usize) {
         }
     }
 }
+
+mod diff {
+    use std::sync::{atomic::AtomicU64, Arc};
+
+    use lapce_xi_rope::Rope;
+
+    use crate::buffer::{change_line, diff_hunks, rope_diff};
+
+    fn hunks(
+        left: &str,
+        right: &str,
+    ) -> Vec<(std::ops::Range<usize>, std::ops::Range<usize>)> {
+        let changes = rope_diff(
+            Rope::from(left),
+            Rope::from(right),
+            0,
+            Arc::new(AtomicU64::new(0)),
+            3,
+        )
+        .unwrap();
+        diff_hunks(&changes)
+    }
+
+    #[test]
+    fn groups_adjacent_changes() {
+        assert_eq!(hunks("a\nb\nc\n", "a\nb\nc\n"), vec![]);
+        assert_eq!(hunks("a\nb\nc\n", "a\nx\nc\n"), vec![(1..2, 1..2)]);
+        assert_eq!(
+            hunks("a\nb\nc\nd\ne\n", "x\nb\nc\nd\ny\nz\n"),
+            vec![(0..1, 0..1), (4..5, 4..6)]
+        );
+    }
+
+    #[test]
+    fn insertions_and_removals_are_positioned() {
+        assert_eq!(hunks("a\nc\n", "a\nb\nc\n"), vec![(1..1, 1..2)]);
+        assert_eq!(hunks("a\nb\nc\n", "a\nc\n"), vec![(1..2, 1..1)]);
+        assert_eq!(hunks("", "a\n"), vec![(0..0, 0..1)]);
+    }
+
+    #[test]
+    fn moves_between_changes() {
+        let hunks =
+            hunks("a\nb\nc\nd\ne\nf\ng\nh\ni\n", "x\na\nb\nc\ny\ne\nf\ng\ni\n");
+        assert_eq!(hunks, vec![(0..0, 0..1), (3..4, 4..5), (7..8, 8..8)]);
+
+        assert_eq!(change_line(&hunks, 0, true, false), Some(4));
+        assert_eq!(change_line(&hunks, 4, true, false), Some(8));
+        assert_eq!(change_line(&hunks, 6, true, false), Some(8));
+        assert_eq!(change_line(&hunks, 8, true, false), None);
+        assert_eq!(change_line(&hunks, 8, true, true), Some(0));
+
+        assert_eq!(change_line(&hunks, 8, false, false), Some(4));
+        assert_eq!(change_line(&hunks, 5, false, false), Some(4));
+        assert_eq!(change_line(&hunks, 4, false, false), Some(0));
+        assert_eq!(change_line(&hunks, 0, false, false), None);
+        assert_eq!(change_line(&hunks, 0, false, true), Some(8));
+    }
+}
+
+mod brackets {
+    use super::*;
+
+    #[test]
+    fn matches_nested_brackets() {
+        let buffer = Buffer::new("fn f() { x[(y)]; }");
+        //                      ->012345678901234567<-
+        assert_eq!(buffer.find_matching_bracket(None, 7), Some((7, 17)));
+        assert_eq!(buffer.find_matching_bracket(None, 10), Some((10, 14)));
+        assert_eq!(buffer.find_matching_bracket(None, 11), Some((11, 13)));
+        assert_eq!(buffer.find_matching_bracket(None, 14), Some((14, 10)));
+        // Right after a bracket
+        assert_eq!(buffer.find_matching_bracket(None, 15), Some((14, 10)));
+        assert_eq!(buffer.find_matching_bracket(None, 9), None);
+    }
+
+    #[test]
+    fn unbalanced_brackets_have_no_match() {
+        let buffer = Buffer::new("a(b[c)");
+        assert_eq!(buffer.find_matching_bracket(None, 1), Some((1, 5)));
+        assert_eq!(buffer.find_matching_bracket(None, 3), None);
+        assert_eq!(buffer.find_matching_bracket(None, 0), None);
+    }
+
+    #[cfg(feature = "lang-rust")]
+    fn rust_syntax(buffer: &Buffer) -> crate::syntax::Syntax {
+        let mut syntax = crate::syntax::Syntax::from_language(
+            crate::language::LapceLanguage::Rust,
+        )
+        .unwrap();
+        syntax.parse(0, buffer.text().clone(), None);
+        syntax
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn matches_nested_brackets_with_syntax() {
+        let buffer = Buffer::new("fn f() { x[(y)]; }");
+        let syntax = rust_syntax(&buffer);
+        let syntax = Some(&syntax);
+        assert_eq!(buffer.find_matching_bracket(syntax, 7), Some((7, 17)));
+        assert_eq!(buffer.find_matching_bracket(syntax, 10), Some((10, 14)));
+        assert_eq!(buffer.find_matching_bracket(syntax, 11), Some((11, 13)));
+        assert_eq!(buffer.find_matching_bracket(syntax, 17), Some((17, 7)));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn ignores_brackets_in_strings_with_syntax() {
+        let buffer = Buffer::new("fn f() { g(\")\"); }");
+        assert_eq!(buffer.find_matching_bracket(None, 10), Some((10, 12)));
+
+        let syntax = rust_syntax(&buffer);
+        let syntax = Some(&syntax);
+        assert_eq!(buffer.find_matching_bracket(syntax, 10), Some((10, 14)));
+        assert_eq!(buffer.find_matching_bracket(syntax, 14), Some((14, 10)));
+        assert_eq!(buffer.find_matching_bracket(syntax, 12), None);
+    }
+}
@@ -42,6 +42,10 @@ pub enum EditCommand {
     DeleteWordForward,
     #[strum(serialize = "delete_word_backward")]
     DeleteWordBackward,
+    #[strum(serialize = "delete_word_part_forward")]
+    DeleteWordPartForward,
+    #[strum(serialize = "delete_word_part_backward")]
+    DeleteWordPartBackward,
     #[strum(serialize = "delete_to_beginning_of_line")]
     DeleteToBeginningOfLine,
     #[strum(serialize = "delete_to_end_of_line")]
@@ -129,6 +133,12 @@ pub enum MoveCommand {
     WordForward,
     #[strum(serialize = "word_end_forward")]
     WordEndForward,
+    #[strum(message = "Move Word Part Right")]
+    #[strum(serialize = "move_word_part_right")]
+    MoveWordPartRight,
+    #[strum(message = "Move Word Part Left")]
+    #[strum(serialize = "move_word_part_left")]
+    MoveWordPartLeft,
     #[strum(message = "Document Start")]
     #[strum(serialize = "document_start")]
     DocumentStart,
@@ -147,6 +157,9 @@ pub enum MoveCommand {
     GotoLineDefaultFirst,
     #[strum(serialize = "match_pairs")]
     MatchPairs,
+    #[strum(message = "Move to Matching Bracket")]
+    #[strum(serialize = "move_to_matching_bracket")]
+    MoveToMatchingBracket,
     #[strum(serialize = "next_unmatched_right_bracket")]
     NextUnmatchedRightBracket,
     #[strum(serialize = "previous_unmatched_left_bracket")]
@@ -187,7 +200,10 @@ pub fn to_movement(&self, count: Option<usize>) -> Movement {
             WordBackward => Movement::WordBackward,
             WordForward => Movement::WordForward,
             WordEndForward => Movement::WordEndForward,
+            MoveWordPartRight => Movement::WordPartRight,
+            MoveWordPartLeft => Movement::WordPartLeft,
             MatchPairs => Movement::MatchPairs,
+            MoveToMatchingBracket => Movement::MatchingBracket,
             NextUnmatchedRightBracket => Movement::NextUnmatched(')'),
             PreviousUnmatchedLeftBracket => Movement::PreviousUnmatched('('),
             NextUnmatchedRightCurlyBracket => Movement::NextUnmatched('}'),
@@ -216,6 +232,27 @@ pub enum FocusCommand {
     SplitHorizontal,
     #[strum(serialize = "split_exchange")]
     SplitExchange,
+    #[strum(message = "Equalize Splits")]
+    #[strum(serialize = "split_equalize")]
+    SplitEqualize,
+    #[strum(message = "Increase Split Size")]
+    #[strum(serialize = "split_increase_size")]
+    SplitIncreaseSize,
+    #[strum(message = "Decrease Split Size")]
+    #[strum(serialize = "split_decrease_size")]
+    SplitDecreaseSize,
+    #[strum(message = "Move Editor to Left Split")]
+    #[strum(serialize = "move_editor_to_split_left")]
+    MoveEditorToSplitLeft,
+    #[strum(message = "Move Editor to Right Split")]
+    #[strum(serialize = "move_editor_to_split_right")]
+    MoveEditorToSplitRight,
+    #[strum(message = "Move Editor to Split Above")]
+    #[strum(serialize = "move_editor_to_split_up")]
+    MoveEditorToSplitUp,
+    #[strum(message = "Move Editor to Split Below")]
+    #[strum(serialize = "move_editor_to_split_down")]
+    MoveEditorToSplitDown,
     #[strum(serialize = "split_close")]
     SplitClose,
     #[strum(serialize = "split_right")]
@@ -234,10 +271,18 @@ pub enum FocusCommand {
     SearchBackward,
     #[strum(serialize = "toggle_case_sensitive_search")]
     ToggleCaseSensitive,
+    #[strum(serialize = "toggle_whole_word_search")]
+    ToggleWholeWord,
+    #[strum(serialize = "toggle_in_selection_search")]
+    ToggleInSelection,
     #[strum(serialize = "global_search_refresh")]
     GlobalSearchRefresh,
     #[strum(serialize = "clear_search")]
     ClearSearch,
+    #[strum(serialize = "search_history_previous")]
+    SearchHistoryPrevious,
+    #[strum(serialize = "search_history_next")]
+    SearchHistoryNext,
     #[strum(serialize = "search_in_view")]
     SearchInView,
     #[strum(serialize = "list.select")]
@@ -252,6 +297,12 @@ pub enum FocusCommand {
     ListPreviousPage,
     #[strum(serialize = "list.expand")]
     ListExpand,
+    #[strum(serialize = "picker.complete_path")]
+    PickerCompletePath,
+    #[strum(serialize = "hover.next_page")]
+    HoverNextPage,
+    #[strum(serialize = "hover.previous_page")]
+    HoverPreviousPage,
     #[strum(serialize = "jump_to_next_snippet_placeholder")]
     JumpToNextSnippetPlaceholder,
     #[strum(serialize = "jump_to_prev_snippet_placeholder")]
@@ -283,6 +334,16 @@ pub enum FocusCommand {
     #[strum(message = "Go to Definition")]
     #[strum(serialize = "goto_definition")]
     GotoDefinition,
+    #[strum(message = "Peek Definition")]
+    #[strum(serialize = "peek_definition")]
+    PeekDefinition,
+    /// Open the definition shown in the peek popup in its editor
+    #[strum(serialize = "peek.open")]
+    PeekOpen,
+    #[strum(serialize = "peek.next")]
+    PeekNext,
+    #[strum(serialize = "peek.previous")]
+    PeekPrevious,
     #[strum(message = "Go to Type Definition")]
     #[strum(serialize = "goto_type_definition")]
     GotoTypeDefinition,
@@ -298,6 +359,12 @@ pub enum FocusCommand {
     NextError,
     #[strum(serialize = "previous_error")]
     PreviousError,
+    #[strum(message = "Go to Next Diagnostic in File")]
+    #[strum(serialize = "next_diagnostic")]
+    NextDiagnostic,
+    #[strum(message = "Go to Previous Diagnostic in File")]
+    #[strum(serialize = "previous_diagnostic")]
+    PreviousDiagnostic,
     #[strum(message = "Go to Next Difference")]
     #[strum(serialize = "next_diff")]
     NextDiff,
@@ -321,6 +388,9 @@ pub enum FocusCommand {
     InlineFindLeft,
     #[strum(serialize = "repeat_last_inline_find")]
     RepeatLastInlineFind,
+    #[strum(message = "Run Selection in Terminal")]
+    #[strum(serialize = "run_selection_in_terminal")]
+    RunSelectionInTerminal,
     #[strum(message = "Save")]
     #[strum(serialize = "save")]
     Save,
@@ -336,6 +406,9 @@ pub enum FocusCommand {
     Rename,
     #[strum(serialize = "confirm_rename")]
     ConfirmRename,
+    #[strum(message = "Confirm Rename With Preview")]
+    #[strum(serialize = "confirm_rename_with_preview")]
+    ConfirmRenameWithPreview,
     #[strum(serialize = "select_next_syntax_item")]
     SelectNextSyntaxItem,
     #[strum(serialize = "select_previous_syntax_item")]
@@ -382,8 +455,15 @@ pub enum MultiSelectionCommand {
     InsertCursorAbove,
     #[strum(serialize = "insert_cursor_below")]
     InsertCursorBelow,
+    #[strum(serialize = "select_block_up")]
+    SelectBlockUp,
+    #[strum(serialize = "select_block_down")]
+    SelectBlockDown,
     #[strum(serialize = "insert_cursor_end_of_line")]
     InsertCursorEndOfLine,
+    #[strum(message = "Split Selection into Lines")]
+    #[strum(serialize = "split_selection_into_lines")]
+    SplitSelectionIntoLines,
     #[strum(serialize = "select_current_line")]
     SelectCurrentLine,
     #[strum(serialize = "select_all_current")]
@@ -395,3 +475,95 @@ pub enum MultiSelectionCommand {
     #[strum(serialize = "select_all")]
     SelectAll,
 }
+
+#[derive(
+    Display,
+    EnumString,
+    EnumIter,
+    Clone,
+    PartialEq,
+    Eq,
+    Debug,
+    EnumMessage,
+    IntoStaticStr,
+)]
+pub enum SignatureCommand {
+    #[strum(message = "Next Signature Overload")]
+    #[strum(serialize = "signature.next_overload")]
+    NextOverload,
+    #[strum(message = "Previous Signature Overload")]
+    #[strum(serialize = "signature.prev_overload")]
+    PrevOverload,
+}
+
+#[derive(
+    Display,
+    EnumString,
+    EnumIter,
+    Clone,
+    PartialEq,
+    Eq,
+    Debug,
+    EnumMessage,
+    IntoStaticStr,
+)]
+pub enum SelectionRangeCommand {
+    #[strum(message = "Expand Selection")]
+    #[strum(serialize = "selection_range.expand")]
+    Expand,
+    #[strum(message = "Shrink Selection")]
+    #[strum(serialize = "selection_range.shrink")]
+    Shrink,
+}
+
+#[derive(
+    Display,
+    EnumString,
+    EnumIter,
+    Clone,
+    PartialEq,
+    Eq,
+    Debug,
+    EnumMessage,
+    IntoStaticStr,
+)]
+pub enum SourceControlCommand {
+    #[strum(message = "Stage Hunk")]
+    #[strum(serialize = "source_control.stage_hunk")]
+    StageHunk,
+    #[strum(message = "Unstage Hunk")]
+    #[strum(serialize = "source_control.unstage_hunk")]
+    UnstageHunk,
+    #[strum(message = "Go to Next Change")]
+    #[strum(serialize = "source_control.next_change")]
+    NextChange,
+    #[strum(message = "Go to Previous Change")]
+    #[strum(serialize = "source_control.prev_change")]
+    PrevChange,
+}
+
+#[derive(
+    Display,
+    EnumString,
+    EnumIter,
+    Clone,
+    PartialEq,
+    Eq,
+    Debug,
+    EnumMessage,
+    IntoStaticStr,
+)]
+pub enum FoldCommand {
+    #[strum(message = "Fold")]
+    #[strum(serialize = "fold.fold")]
+    Fold,
+    #[strum(message = "Unfold")]
+    #[strum(serialize = "fold.unfold")]
+    Unfold,
+    #[strum(message = "Fold All")]
+    #[strum(serialize = "fold.fold_all")]
+    FoldAll,
+    #[strum(message = "Unfold All")]
+    #[strum(serialize = "fold.unfold_all")]
+    UnfoldAll,
+}
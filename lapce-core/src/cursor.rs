@@ -188,24 +188,25 @@ pub fn apply_delta(&mut self, delta: &RopeDelta) {
     pub fn yank(&self, buffer: &Buffer) -> RegisterData {
         let (content, mode) = match &self.mode {
             CursorMode::Insert(selection) => {
+                // The rows of a block selection that miss short lines are kept
+                // empty, rather than copying the whole line like other carets.
+                let block_rows = selection.is_block() && !selection.is_caret();
                 let mut mode = VisualMode::Normal;
                 let mut content = "".to_string();
+                let mut ends_with_newline = true;
                 for region in selection.regions() {
-                    let region_content = if region.is_caret() {
+                    let region_content = if region.is_caret() && !block_rows {
                         mode = VisualMode::Linewise;
                         let line = buffer.line_of_offset(region.start);
                         buffer.line_content(line)
                     } else {
                         buffer.slice_to_cow(region.min()..region.max())
                     };
-                    if content.is_empty() {
-                        content = region_content.to_string();
-                    } else if content.ends_with('\n') {
-                        content += &region_content;
-                    } else {
+                    if !ends_with_newline {
                         content += "\n";
-                        content += &region_content;
                     }
+                    ends_with_newline = region_content.ends_with('\n');
+                    content += &region_content;
                 }
                 (content, mode)
             }
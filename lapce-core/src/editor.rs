@@ -64,13 +64,18 @@ pub enum EditType {
     MotionDelete,
     Undo,
     Redo,
+    /// The edits of the formatter and of the cleanup of the whitespace made
+    /// when saving, which are undone together
+    Save,
     Other,
 }
 
 impl EditType {
     /// Checks whether a new undo group should be created between two edits.
     pub fn breaks_undo_group(self, previous: EditType) -> bool {
-        !((self == EditType::InsertChars || self == EditType::Delete)
+        !((self == EditType::InsertChars
+            || self == EditType::Delete
+            || self == EditType::Save)
             && self == previous)
     }
 }
@@ -371,7 +376,7 @@ fn insert_new_line(
                 } else {
                     &line_indent
                 };
-                format!("\n{indent}")
+                format!("{}{indent}", buffer.line_ending().in_text().as_str())
             };
 
             let selection = Selection::region(region.min(), region.max());
@@ -388,7 +393,10 @@ fn insert_new_line(
                             let selection = Selection::caret(
                                 (region.max() as i32 + shift) as usize,
                             );
-                            let content = format!("\n{line_indent}");
+                            let content = format!(
+                                "{}{line_indent}",
+                                buffer.line_ending().in_text().as_str()
+                            );
                             extra_edits.push((selection, content));
                         }
                     }
@@ -1002,21 +1010,23 @@ pub fn do_edit<T: Clipboard>(
                 let data = cursor.yank(buffer);
                 clipboard.put_string(data.content);
 
-                let selection =
-                    if let CursorMode::Insert(mut selection) = cursor.mode.clone() {
-                        for region in selection.regions_mut() {
-                            if region.is_caret() {
-                                let line = buffer.line_of_offset(region.start);
-                                let start = buffer.offset_of_line(line);
-                                let end = buffer.offset_of_line(line + 1);
-                                region.start = start;
-                                region.end = end;
-                            }
+                let selection = if let CursorMode::Insert(mut selection) =
+                    cursor.mode.clone()
+                {
+                    let block_rows = selection.is_block() && !selection.is_caret();
+                    for region in selection.regions_mut() {
+                        if region.is_caret() && !block_rows {
+                            let line = buffer.line_of_offset(region.start);
+                            let start = buffer.offset_of_line(line);
+                            let end = buffer.offset_of_line(line + 1);
+                            region.start = start;
+                            region.end = end;
                         }
-                        selection
-                    } else {
-                        cursor.edit_selection(buffer)
-                    };
+                    }
+                    selection
+                } else {
+                    cursor.edit_selection(buffer)
+                };
 
                 let (delta, inval_lines, edits) =
                     buffer.edit(&[(&selection, "")], EditType::Cut);
@@ -1103,8 +1113,12 @@ pub fn do_edit<T: Clipboard>(
                         };
                         let indent = buffer.indent_unit();
                         let mut new_selection = Selection::new();
+                        // The carets of a block selection on short lines delete
+                        // nothing, so that it removes just its columns
+                        let block_rows = selection.is_block()
+                            && edit_type == EditType::DeleteSelection;
                         for region in selection.regions() {
-                            let new_region = if region.is_caret() {
+                            let new_region = if region.is_caret() && !block_rows {
                                 if indent.starts_with('\t') {
                                     let new_end = buffer.move_left(
                                         region.end,
@@ -1206,8 +1220,10 @@ pub fn do_edit<T: Clipboard>(
                             EditType::DeleteSelection
                         };
                         let mut new_selection = Selection::new();
+                        let block_rows = selection.is_block()
+                            && edit_type == EditType::DeleteSelection;
                         for region in selection.regions() {
-                            let new_region = if region.is_caret() {
+                            let new_region = if region.is_caret() && !block_rows {
                                 let new_end =
                                     buffer.move_right(region.end, Mode::Insert, 1);
                                 SelRegion::new(region.start, new_end, None)
@@ -1243,7 +1259,7 @@ pub fn do_edit<T: Clipboard>(
                 cursor.mode = CursorMode::Insert(selection);
                 vec![(delta, inval_lines, edits)]
             }
-            DeleteWordForward => {
+            DeleteWordForward | DeleteWordPartForward => {
                 let selection = match cursor.mode {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         cursor.edit_selection(buffer)
@@ -1253,7 +1269,11 @@ pub fn do_edit<T: Clipboard>(
                         let selection = cursor.edit_selection(buffer);
 
                         for region in selection.regions() {
-                            let end = buffer.move_word_forward(region.end);
+                            let end = if *cmd == DeleteWordPartForward {
+                                buffer.move_n_word_parts_right(region.end, 1)
+                            } else {
+                                buffer.move_word_forward(region.end)
+                            };
                             let new_region = SelRegion::new(region.start, end, None);
                             new_selection.add_region(new_region);
                         }
@@ -1268,7 +1288,7 @@ pub fn do_edit<T: Clipboard>(
                 cursor.update_selection(buffer, selection);
                 vec![(delta, inval_lines, edits)]
             }
-            DeleteWordBackward => {
+            DeleteWordBackward | DeleteWordPartBackward => {
                 let selection = match cursor.mode {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         cursor.edit_selection(buffer)
@@ -1278,7 +1298,11 @@ pub fn do_edit<T: Clipboard>(
                         let selection = cursor.edit_selection(buffer);
 
                         for region in selection.regions() {
-                            let end = buffer.move_word_backward_deletion(region.end);
+                            let end = if *cmd == DeleteWordPartBackward {
+                                buffer.move_word_part_backward_deletion(region.end)
+                            } else {
+                                buffer.move_word_backward_deletion(region.end)
+                            };
                             let new_region = SelRegion::new(region.start, end, None);
                             new_selection.add_region(new_region);
                         }
@@ -1526,11 +1550,38 @@ enum DuplicateDirection {
 mod test {
     use crate::{
         buffer::Buffer,
+        command::EditCommand,
         cursor::{Cursor, CursorMode},
         editor::{DuplicateDirection, Editor},
+        register::{Clipboard, Register},
         selection::{SelRegion, Selection},
     };
 
+    #[derive(Default)]
+    struct TestClipboard(Option<String>);
+
+    impl Clipboard for TestClipboard {
+        fn get_string(&self) -> Option<String> {
+            self.0.clone()
+        }
+
+        fn put_string(&mut self, s: impl AsRef<str>) {
+            self.0 = Some(s.as_ref().to_string());
+        }
+    }
+
+    fn do_edit(cursor: &mut Cursor, buffer: &mut Buffer, cmd: &EditCommand) {
+        Editor::do_edit(
+            cursor,
+            buffer,
+            cmd,
+            None,
+            &mut TestClipboard::default(),
+            false,
+            &mut Register::default(),
+        );
+    }
+
     #[test]
     fn test_insert_simple() {
         let mut buffer = Buffer::new("abc");
@@ -1541,6 +1592,16 @@ fn test_insert_simple() {
         assert_eq!("aebc", buffer.slice_to_cow(0..buffer.len()));
     }
 
+    #[test]
+    fn test_insert_new_line_with_line_ending() {
+        let mut buffer = Buffer::new("abc\r\ndef");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(3)), None, None);
+
+        Editor::insert_new_line(&mut buffer, &mut cursor, Selection::caret(3));
+        assert_eq!("abc\r\n\r\ndef", buffer.slice_to_cow(0..buffer.len()));
+    }
+
     #[test]
     fn test_insert_multiple_cursor() {
         let mut buffer = Buffer::new("abc\nefg\n");
@@ -1763,5 +1824,146 @@ fn check_multiple_cursor_match_insertion() {
         assert_eq!(cursor.mode, CursorMode::Insert(end_selection));
     }
 
+    #[test]
+    fn test_insert_block_selection() {
+        let mut buffer = Buffer::new("abcd\nefgh\nijkl\n");
+        let selection = Selection::block(&buffer, (0, 1), (2, 1));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+
+        Editor::insert(&mut cursor, &mut buffer, "x", None, true);
+        assert_eq!(
+            "axbcd\nexfgh\nixjkl\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        let selection = Selection::block(&buffer, (0, 2), (2, 4));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        assert_eq!("bc\nfg\njk", cursor.yank(&buffer).content);
+
+        Editor::insert(&mut cursor, &mut buffer, "-", None, true);
+        assert_eq!("ax-d\nex-h\nix-l\n", buffer.slice_to_cow(0..buffer.len()));
+        let mut end_selection = Selection::new();
+        end_selection.add_region(SelRegion::caret(3));
+        end_selection.add_region(SelRegion::caret(8));
+        end_selection.add_region(SelRegion::caret(13));
+        assert_eq!(cursor.mode, CursorMode::Insert(end_selection));
+    }
+
+    #[test]
+    fn test_block_selection_short_lines() {
+        let mut buffer = Buffer::new("abcd\nx\n\nijkl\n");
+        let selection = Selection::block(&buffer, (0, 2), (3, 4));
+        assert_eq!(selection.regions().len(), 2);
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        assert_eq!("cd\nkl", cursor.yank(&buffer).content);
+
+        do_edit(&mut cursor, &mut buffer, &EditCommand::DeleteBackward);
+        assert_eq!("ab\nx\n\nij\n", buffer.slice_to_cow(0..buffer.len()));
+
+        // The short lines only get carets, which are left alone by the deletion.
+        let selection = Selection::block(&buffer, (0, 1), (2, 2));
+        assert_eq!(selection.regions().len(), 3);
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        assert_eq!("b\n\n", cursor.yank(&buffer).content);
+        do_edit(&mut cursor, &mut buffer, &EditCommand::DeleteForward);
+        assert_eq!("a\nx\n\nij\n", buffer.slice_to_cow(0..buffer.len()));
+
+        let mut buffer = Buffer::new("abc\nx\nabc\n");
+        let selection = Selection::block(&buffer, (0, 3), (2, 3));
+        assert_eq!(selection.regions().len(), 2);
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        Editor::insert(&mut cursor, &mut buffer, "!", None, true);
+        assert_eq!("abc!\nx\nabc!\n", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_block_selection_char_columns() {
+        let mut buffer = Buffer::new("héllo\nworld\n");
+        let selection = Selection::block(&buffer, (0, 1), (1, 3));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        assert_eq!("él\nor", cursor.yank(&buffer).content);
+
+        do_edit(&mut cursor, &mut buffer, &EditCommand::DeleteBackward);
+        assert_eq!("hlo\nwld\n", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_delete_carets_with_selection() {
+        // Outside of a block selection, the carets still delete next to them
+        let mut buffer = Buffer::new("abcd\nefgh\n");
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::new(0, 2, None));
+        selection.add_region(SelRegion::caret(7));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+        do_edit(&mut cursor, &mut buffer, &EditCommand::DeleteBackward);
+        assert_eq!("cd\negh\n", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_split_selection_into_lines() {
+        let mut buffer = Buffer::new("let a = 1\n\nlet b = 2\nlet c = 3\n");
+        let selection = Selection::region(4, 20);
+        let split = selection.split_into_lines(&buffer, false);
+        assert_eq!(split.len(), 3);
+        assert_eq!(
+            split.regions(),
+            &[
+                SelRegion::caret(9),
+                SelRegion::caret(10),
+                SelRegion::caret(20)
+            ]
+        );
+        assert_eq!(selection.split_into_lines(&buffer, true).len(), 3);
+        // Selecting whole lines doesn't add a cursor on the next line
+        assert_eq!(
+            Selection::region(0, 21)
+                .split_into_lines(&buffer, true)
+                .len(),
+            3
+        );
+
+        let mut cursor = Cursor::new(CursorMode::Insert(split.clone()), None, None);
+        Editor::insert(&mut cursor, &mut buffer, ";", None, true);
+        assert_eq!(
+            "let a = 1;\n;\nlet b = 2;\nlet c = 3\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        do_edit(&mut cursor, &mut buffer, &EditCommand::Undo);
+        assert_eq!(
+            "let a = 1\n\nlet b = 2\nlet c = 3\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(cursor.mode, CursorMode::Insert(split));
+    }
+
+    #[test]
+    fn test_delete_word_part() {
+        let mut buffer = Buffer::new("let getHTTPResponse = some_snake;");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(19)), None, None);
+
+        do_edit(
+            &mut cursor,
+            &mut buffer,
+            &EditCommand::DeleteWordPartBackward,
+        );
+        assert_eq!(
+            "let getHTTP = some_snake;",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(14)), None, None);
+        do_edit(
+            &mut cursor,
+            &mut buffer,
+            &EditCommand::DeleteWordPartForward,
+        );
+        assert_eq!(
+            "let getHTTP = _snake;",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
+
     // TODO(dbuga): add tests duplicating selections (multiple line blocks)
 }
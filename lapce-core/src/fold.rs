@@ -0,0 +1,349 @@
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::{Buffer, InvalLines};
+
+/// A range of lines that can be folded. The first line stays visible to show
+/// the fold, the lines after it up to `end_line` are hidden.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FoldRange {
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl FoldRange {
+    pub fn new(start_line: usize, end_line: usize) -> Self {
+        Self {
+            start_line,
+            end_line,
+        }
+    }
+
+    /// Whether the range is on a single line, which leaves nothing to hide
+    pub fn is_empty(&self) -> bool {
+        self.end_line <= self.start_line
+    }
+
+    /// Whether the line is one of the lines of the range
+    pub fn contains(&self, line: usize) -> bool {
+        self.start_line <= line && line <= self.end_line
+    }
+
+    /// Whether the line is hidden when the range is folded
+    pub fn hides(&self, line: usize) -> bool {
+        self.start_line < line && line <= self.end_line
+    }
+}
+
+/// Find the ranges that can be folded from the indentation of the lines, for
+/// when the language server doesn't provide them. A line starts a range when
+/// the lines after it are indented more deeply, and the range ends at the last
+/// of them. Blank lines don't end a range, but the ones at its end are left
+/// out of it.
+pub fn indent_fold_ranges(buffer: &Buffer, tab_width: usize) -> Vec<FoldRange> {
+    let tab_width = tab_width.max(1);
+    let mut ranges = Vec::new();
+    // The lines that may start a range, with their indentation
+    let mut starts: Vec<(usize, usize)> = Vec::new();
+    let mut last_non_blank = 0;
+    for line in 0..buffer.num_lines() {
+        let indent = line_indent(&buffer.line_content(line), tab_width);
+        let Some(indent) = indent else { continue };
+        while let Some(&(start_line, start_indent)) = starts.last() {
+            if start_indent < indent {
+                break;
+            }
+            starts.pop();
+            if last_non_blank > start_line {
+                ranges.push(FoldRange::new(start_line, last_non_blank));
+            }
+        }
+        starts.push((line, indent));
+        last_non_blank = line;
+    }
+    for (start_line, _) in starts {
+        if last_non_blank > start_line {
+            ranges.push(FoldRange::new(start_line, last_non_blank));
+        }
+    }
+
+    ranges.sort_by_key(|range| (range.start_line, range.end_line));
+    ranges
+}
+
+/// The visual width of the indentation of a line, or `None` if it's blank
+fn line_indent(content: &str, tab_width: usize) -> Option<usize> {
+    let mut indent = 0;
+    for ch in content.chars() {
+        match ch {
+            ' ' => indent += 1,
+            '\t' => indent = (indent / tab_width + 1) * tab_width,
+            '\n' | '\r' => return None,
+            _ => return Some(indent),
+        }
+    }
+    None
+}
+
+/// The ranges of lines that are folded in a document. Folds can be nested, a
+/// line is hidden as long as one of them hides it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Folds {
+    /// Sorted by their start line, then by their end line
+    ranges: Vec<FoldRange>,
+}
+
+impl Folds {
+    pub fn new(ranges: impl IntoIterator<Item = FoldRange>) -> Self {
+        let mut folds = Self::default();
+        for range in ranges {
+            folds.fold(range);
+        }
+        folds
+    }
+
+    pub fn ranges(&self) -> &[FoldRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Fold a range of lines. Returns false if there's nothing to fold, because
+    /// the range is already folded or is on a single line.
+    pub fn fold(&mut self, range: FoldRange) -> bool {
+        if range.is_empty() {
+            return false;
+        }
+        let key = |range: &FoldRange| (range.start_line, range.end_line);
+        match self.ranges.binary_search_by_key(&key(&range), key) {
+            Ok(_) => false,
+            Err(i) => {
+                self.ranges.insert(i, range);
+                true
+            }
+        }
+    }
+
+    /// Fold the innermost of `ranges` around the line that isn't folded yet,
+    /// returning it.
+    pub fn fold_at(
+        &mut self,
+        ranges: &[FoldRange],
+        line: usize,
+    ) -> Option<FoldRange> {
+        let range = ranges
+            .iter()
+            .filter(|range| {
+                !range.is_empty()
+                    && range.contains(line)
+                    && !self.ranges.contains(range)
+            })
+            .max_by_key(|range| (range.start_line, usize::MAX - range.end_line))
+            .copied()?;
+        self.fold(range);
+        Some(range)
+    }
+
+    /// Fold all of `ranges`
+    pub fn fold_all(&mut self, ranges: &[FoldRange]) {
+        for range in ranges {
+            self.fold(*range);
+        }
+    }
+
+    /// Unfold the folds around the line, returning whether there were any
+    pub fn unfold_at(&mut self, line: usize) -> bool {
+        let len = self.ranges.len();
+        self.ranges.retain(|range| !range.contains(line));
+        self.ranges.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// Drop the folds that don't fit the buffer anymore, like the ones restored
+    /// for a file that was changed since. A fold is kept if it ends within the
+    /// buffer and its first line still starts a block indented after it.
+    pub fn retain_valid(&mut self, buffer: &Buffer, tab_width: usize) {
+        if self.ranges.is_empty() {
+            return;
+        }
+        let last_line = buffer.last_line();
+        let starts: Vec<usize> = indent_fold_ranges(buffer, tab_width)
+            .iter()
+            .map(|range| range.start_line)
+            .collect();
+        self.ranges.retain(|range| {
+            range.end_line <= last_line && starts.contains(&range.start_line)
+        });
+    }
+
+    /// Whether the line is hidden by a fold
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.ranges
+            .iter()
+            .take_while(|range| range.start_line < line)
+            .any(|range| range.hides(line))
+    }
+
+    /// The line displaying the folds that hide the line, or the line itself if
+    /// it isn't hidden
+    pub fn visible_line(&self, line: usize) -> usize {
+        let mut visible_line = line;
+        while let Some(range) =
+            self.ranges.iter().find(|range| range.hides(visible_line))
+        {
+            visible_line = range.start_line;
+        }
+        visible_line
+    }
+
+    /// Whether a fold starts at the line, which displays it
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.ranges
+            .binary_search_by_key(&line, |range| range.start_line)
+            .is_ok()
+    }
+
+    /// Update the folds after an edit of the lines. The ones after the edit
+    /// are moved with their lines, while editing the lines of a fold unfolds
+    /// it. Only an edit within the first line of a fold keeps it in place.
+    pub fn apply_edit(&mut self, inval_lines: &InvalLines) {
+        let start = inval_lines.start_line;
+        let old_end = start + inval_lines.inval_count;
+        let new_end = start + inval_lines.new_count;
+        let within_line = inval_lines.inval_count == 1 && inval_lines.new_count == 1;
+        self.ranges.retain_mut(|range| {
+            if range.end_line < start {
+                true
+            } else if range.start_line >= old_end {
+                range.start_line = range.start_line + new_end - old_end;
+                range.end_line = range.end_line + new_end - old_end;
+                true
+            } else {
+                within_line && range.start_line == start
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ranges(text: &str) -> Vec<(usize, usize)> {
+        indent_fold_ranges(&Buffer::new(text), 4)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_indent_fold_ranges() {
+        let text = "fn main() {\n    if a {\n        b();\n    }\n}\n";
+        // The closing lines stay visible, like the first line of the range
+        assert_eq!(ranges(text), vec![(0, 3), (1, 2)]);
+
+        let text = "def f():\n    x = 1\n\n    y = 2\n\n\nz = 3\n";
+        // The blank lines at the end of a range are left out
+        assert_eq!(ranges(text), vec![(0, 3)]);
+
+        let text = "a:\n\tb:\n\t\tc\n\td\n";
+        assert_eq!(ranges(text), vec![(0, 3), (1, 2)]);
+
+        assert_eq!(ranges("a\nb\n    c\n"), vec![(1, 2)]);
+        assert_eq!(ranges("a\nb\nc\n"), vec![]);
+    }
+
+    #[test]
+    fn test_fold_at() {
+        let buffer =
+            Buffer::new("fn main() {\n    if a {\n        b();\n    }\n}\n");
+        let ranges = indent_fold_ranges(&buffer, 4);
+        let mut folds = Folds::default();
+
+        // The innermost range is folded first, then the one around it
+        assert_eq!(folds.fold_at(&ranges, 2), Some(FoldRange::new(1, 2)));
+        assert_eq!(folds.fold_at(&ranges, 1), Some(FoldRange::new(0, 3)));
+        assert_eq!(folds.fold_at(&ranges, 1), None);
+        assert!(folds.is_folded(0));
+        assert!(folds.is_folded(1));
+        assert!(!folds.is_hidden(0));
+        assert!(folds.is_hidden(3));
+        assert!(!folds.is_hidden(4));
+        // The hidden lines are displayed by the outermost fold
+        assert_eq!(folds.visible_line(2), 0);
+        assert_eq!(folds.visible_line(4), 4);
+
+        assert!(folds.unfold_at(2));
+        assert!(folds.is_empty());
+        assert!(!folds.unfold_at(2));
+    }
+
+    #[test]
+    fn test_retain_valid() {
+        let buffer =
+            Buffer::new("fn main() {\n    if a {\n        b();\n    }\n}\n");
+        let mut folds = Folds::new([
+            FoldRange::new(0, 3),
+            FoldRange::new(1, 2),
+            // The closing line doesn't start a block anymore
+            FoldRange::new(3, 4),
+            // Past the end of the buffer
+            FoldRange::new(1, 8),
+        ]);
+        folds.retain_valid(&buffer, 4);
+        assert_eq!(
+            folds.ranges(),
+            &[FoldRange::new(0, 3), FoldRange::new(1, 2)]
+        );
+
+        folds.retain_valid(&Buffer::new("a\nb\nc\nd\n"), 4);
+        assert!(folds.is_empty());
+    }
+
+    #[test]
+    fn test_apply_edit() {
+        let mut folds = Folds::new([FoldRange::new(2, 4), FoldRange::new(8, 9)]);
+
+        // Lines added before the folds move them
+        folds.apply_edit(&InvalLines {
+            start_line: 0,
+            inval_count: 1,
+            new_count: 3,
+        });
+        assert_eq!(
+            folds.ranges(),
+            &[FoldRange::new(4, 6), FoldRange::new(10, 11)]
+        );
+
+        // Editing the first line of a fold keeps it
+        folds.apply_edit(&InvalLines {
+            start_line: 4,
+            inval_count: 1,
+            new_count: 1,
+        });
+        assert_eq!(
+            folds.ranges(),
+            &[FoldRange::new(4, 6), FoldRange::new(10, 11)]
+        );
+
+        // Editing the hidden lines of a fold unfolds it
+        folds.apply_edit(&InvalLines {
+            start_line: 5,
+            inval_count: 1,
+            new_count: 1,
+        });
+        assert_eq!(folds.ranges(), &[FoldRange::new(10, 11)]);
+
+        // So does an edit across a fold, which removes the lines after it
+        folds.apply_edit(&InvalLines {
+            start_line: 9,
+            inval_count: 3,
+            new_count: 1,
+        });
+        assert!(folds.is_empty());
+    }
+}
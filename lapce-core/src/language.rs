@@ -990,7 +990,11 @@ pub enum LapceLanguage {
 
 impl LapceLanguage {
     pub fn from_path(path: &Path) -> Option<LapceLanguage> {
-        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    pub fn from_extension(extension: &str) -> Option<LapceLanguage> {
+        let extension = extension.to_lowercase();
         // NOTE: This is a linear search.  It is assumed that this function
         // isn't called in any tight loop.
         for properties in LANGUAGES {
@@ -1046,6 +1050,16 @@ pub fn indent_unit(&self) -> &str {
         self.properties().indent
     }
 
+    /// Whether the language is markdown, where the spaces ending a line break
+    /// it
+    pub fn is_markdown(&self) -> bool {
+        #[cfg(feature = "lang-markdown")]
+        if matches!(self, LapceLanguage::Markdown) {
+            return true;
+        }
+        false
+    }
+
     pub(crate) fn new_highlight_config(
         &self,
     ) -> Result<HighlightConfiguration, HighlightIssue> {
@@ -8,9 +8,11 @@
 pub mod directory;
 pub mod editor;
 pub mod encoding;
+pub mod fold;
 pub mod indent;
 pub mod language;
 pub mod lens;
+pub mod line_ending;
 pub mod meta;
 pub mod mode;
 pub mod movement;
@@ -18,5 +20,8 @@
 pub mod register;
 pub mod selection;
 pub mod style;
+pub mod symbol;
 pub mod syntax;
+pub mod whitespace;
 pub mod word;
+pub mod wrap;
@@ -0,0 +1,167 @@
+use lapce_xi_rope::Rope;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// The character sequence ending the lines of a document
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Display, EnumIter, EnumString,
+)]
+#[strum(ascii_case_insensitive)]
+pub enum LineEnding {
+    #[default]
+    #[strum(serialize = "LF")]
+    Lf,
+    #[strum(serialize = "CRLF")]
+    CrLf,
+    #[strum(serialize = "CR")]
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// How the line ending is in the text of a buffer, whose lines are only split
+    /// at LF: CR line endings are read as LF, and written back when the file is
+    /// saved.
+    pub fn in_text(&self) -> LineEnding {
+        match self {
+            LineEnding::Cr => LineEnding::Lf,
+            line_ending => *line_ending,
+        }
+    }
+
+    /// The text of a file as it is in a buffer, with its line endings as LF when
+    /// it uses CR the most, and the line ending it uses the most and whether it
+    /// uses others too as detected by [`LineEnding::detect`]
+    pub fn read(text: Rope) -> (Rope, LineEnding, bool) {
+        let (line_ending, mixed) = LineEnding::detect(&text);
+        let text = match line_ending {
+            LineEnding::Cr => LineEnding::Lf.normalize(&text).unwrap_or(text),
+            _ => text,
+        };
+        (text, line_ending, mixed)
+    }
+
+    /// Detect the line ending used the most in the text, and whether other
+    /// line endings are used too. Text without line endings is taken as
+    /// [`LineEnding::Lf`].
+    pub fn detect(text: &Rope) -> (LineEnding, bool) {
+        let mut counts = [0usize; 3];
+        // A `\r\n` can be split across two chunks
+        let mut after_cr = false;
+        for chunk in text.iter_chunks(..text.len()) {
+            for b in chunk.bytes() {
+                match b {
+                    b'\n' if after_cr => {
+                        counts[LineEnding::Cr as usize] -= 1;
+                        counts[LineEnding::CrLf as usize] += 1;
+                    }
+                    b'\n' => counts[LineEnding::Lf as usize] += 1,
+                    b'\r' => counts[LineEnding::Cr as usize] += 1,
+                    _ => {}
+                }
+                after_cr = b == b'\r';
+            }
+        }
+
+        let used = counts.iter().filter(|count| **count > 0).count();
+        let line_ending = [LineEnding::Lf, LineEnding::CrLf, LineEnding::Cr]
+            .into_iter()
+            .rev()
+            .max_by_key(|line_ending| counts[*line_ending as usize])
+            .filter(|line_ending| counts[*line_ending as usize] > 0)
+            .unwrap_or_default();
+        (line_ending, used > 1)
+    }
+
+    /// The text with all of its line endings replaced with this one, `None` if
+    /// they all are this one already
+    pub fn normalize(&self, text: &Rope) -> Option<Rope> {
+        let content = text.slice_to_cow(..text.len());
+        let mut normalized = String::with_capacity(content.len());
+        let mut chars = content.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    normalized.push_str(self.as_str());
+                }
+                '\n' => normalized.push_str(self.as_str()),
+                c => normalized.push(c),
+            }
+        }
+
+        (normalized != content).then(|| Rope::from(normalized))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_xi_rope::Rope;
+
+    use super::LineEnding;
+
+    #[test]
+    fn test_detect() {
+        let detect = |text: &str| LineEnding::detect(&Rope::from(text));
+        assert_eq!(detect("abc"), (LineEnding::Lf, false));
+        assert_eq!(detect("a\nb\nc"), (LineEnding::Lf, false));
+        assert_eq!(detect("a\r\nb\r\nc"), (LineEnding::CrLf, false));
+        assert_eq!(detect("a\rb\rc"), (LineEnding::Cr, false));
+        // The dominant one is detected, and the others are reported
+        assert_eq!(detect("a\r\nb\nc\r\nd"), (LineEnding::CrLf, true));
+        assert_eq!(detect("a\nb\r\nc\nd\r"), (LineEnding::Lf, true));
+    }
+
+    #[test]
+    fn test_detect_across_chunks() {
+        // Big enough for the rope to be split into several chunks
+        let text = "abc\r\n".repeat(1000);
+        let rope = Rope::from(&text);
+        assert!(rope.iter_chunks(..rope.len()).count() > 1);
+        assert_eq!(LineEnding::detect(&rope), (LineEnding::CrLf, false));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let text = Rope::from("a\r\nb\nc\rd\r\n");
+        assert_eq!(
+            LineEnding::Lf.normalize(&text).map(|t| t.to_string()),
+            Some("a\nb\nc\nd\n".to_string())
+        );
+        assert_eq!(
+            LineEnding::CrLf.normalize(&text).map(|t| t.to_string()),
+            Some("a\r\nb\r\nc\r\nd\r\n".to_string())
+        );
+        assert_eq!(LineEnding::Lf.normalize(&Rope::from("a\nb\n")), None);
+    }
+
+    #[test]
+    fn test_read_cr() {
+        let (text, line_ending, mixed) = LineEnding::read(Rope::from("a\rb\rc\r\n"));
+        assert_eq!(text.to_string(), "a\nb\nc\n");
+        assert_eq!(line_ending, LineEnding::Cr);
+        assert!(mixed);
+        // Written back as they were read, but for the line endings it mixed
+        assert_eq!(
+            LineEnding::Cr.normalize(&text).map(|t| t.to_string()),
+            Some("a\rb\rc\r".to_string())
+        );
+
+        // Only the files which mostly use CR are changed
+        let (text, line_ending, _) = LineEnding::read(Rope::from("a\r\nb\rc\r\n"));
+        assert_eq!(text.to_string(), "a\r\nb\rc\r\n");
+        assert_eq!(line_ending, LineEnding::CrLf);
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(LineEnding::CrLf.to_string(), "CRLF");
+        assert_eq!("crlf".parse::<LineEnding>(), Ok(LineEnding::CrLf));
+    }
+}
@@ -44,3 +44,12 @@ fn release_type() -> &'static str {
         "Stable"
     }
 }
+
+/// The commit Lapce was built from, if it was built from a git checkout
+pub const GIT_COMMIT: Option<&str> = option_env!("LAPCE_GIT_COMMIT");
+
+/// The version of the compiler Lapce was built with
+pub const RUSTC_VERSION: &str = env!("LAPCE_RUSTC_VERSION");
+
+/// The target triple Lapce was built for
+pub const TARGET: &str = env!("LAPCE_TARGET");
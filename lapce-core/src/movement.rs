@@ -21,9 +21,12 @@ pub enum Movement {
     WordEndForward,
     WordForward,
     WordBackward,
+    WordPartRight,
+    WordPartLeft,
     NextUnmatched(char),
     PreviousUnmatched(char),
     MatchPairs,
+    MatchingBracket,
     ParagraphForward,
     ParagraphBackward,
 }
@@ -3,7 +3,7 @@
 use lapce_xi_rope::{RopeDelta, Transformer};
 use serde::{Deserialize, Serialize};
 
-use crate::cursor::ColPosition;
+use crate::{buffer::Buffer, cursor::ColPosition};
 
 /// Indicate whether a delta should be applied inside, outside non-caret selection or
 /// after a caret selection (see [`Selection::apply_delta`].
@@ -33,6 +33,9 @@ pub struct SelRegion {
 pub struct Selection {
     regions: Vec<SelRegion>,
     last_inserted: usize,
+    /// Whether the regions are the rows of a block selection
+    #[serde(default)]
+    block: bool,
 }
 
 impl AsRef<Selection> for Selection {
@@ -138,6 +141,7 @@ pub fn new() -> Selection {
         Selection {
             regions: Vec::new(),
             last_inserted: 0,
+            block: false,
         }
     }
 
@@ -146,6 +150,7 @@ pub fn caret(offset: usize) -> Selection {
         Selection {
             regions: vec![SelRegion::caret(offset)],
             last_inserted: 0,
+            block: false,
         }
     }
 
@@ -159,9 +164,75 @@ pub fn region(start: usize, end: usize) -> Selection {
                 horiz: None,
             }],
             last_inserted: 0,
+            block: false,
         }
     }
 
+    /// Creates a block (column) [`Selection`] for the rectangle between the `anchor`
+    /// and `head` positions, given as `(line, col)` with the columns counted in
+    /// characters.
+    ///
+    /// Every line of the rectangle gets a region from the anchor column to the head
+    /// column, clamped to the end of the line. Lines too short to reach the left
+    /// edge of the rectangle are skipped, except the anchor and head lines, so the
+    /// block can always be extended from its ends.
+    pub fn block(
+        buffer: &Buffer,
+        anchor: (usize, usize),
+        head: (usize, usize),
+    ) -> Selection {
+        let (anchor_line, anchor_col) = anchor;
+        let (head_line, head_col) = head;
+        let left = min(anchor_col, head_col);
+
+        let mut selection = Selection::new();
+        for line in min(anchor_line, head_line)..max(anchor_line, head_line) + 1 {
+            let (_, line_end) =
+                buffer.offset_to_line_char_col(buffer.line_end_offset(line, true));
+            if left > line_end && line != anchor_line && line != head_line {
+                continue;
+            }
+            let start = buffer.offset_of_line_char_col(line, anchor_col);
+            let end = buffer.offset_of_line_char_col(line, head_col);
+            selection.regions.push(SelRegion::new(start, end, None));
+            if line == head_line {
+                selection.last_inserted = selection.regions.len() - 1;
+            }
+        }
+        selection.block = true;
+        selection
+    }
+
+    /// Whether this [`Selection`] was made by [`Selection::block`], and wasn't
+    /// edited since
+    pub fn is_block(&self) -> bool {
+        self.block
+    }
+
+    /// Splits every region of this [`Selection`] into a caret on each of the
+    /// lines it spans, at the end of the line or at its start with `line_start`.
+    /// A region ending at the very start of a line, after a line break, leaves
+    /// that line out.
+    pub fn split_into_lines(&self, buffer: &Buffer, line_start: bool) -> Selection {
+        let mut selection = Selection::new();
+        for region in &self.regions {
+            let start_line = buffer.line_of_offset(region.min());
+            let (mut end_line, end_col) = buffer.offset_to_line_col(region.max());
+            if end_col == 0 && end_line > start_line {
+                end_line -= 1;
+            }
+            for line in start_line..end_line + 1 {
+                let offset = if line_start {
+                    buffer.offset_of_line(line)
+                } else {
+                    buffer.line_end_offset(line, true)
+                };
+                selection.add_region(SelRegion::caret(offset));
+            }
+        }
+        selection
+    }
+
     /// Returns whether this [`Selection`], contains the given `offset` position or not.
     ///
     /// **Example:**
@@ -404,6 +475,7 @@ pub fn delete_range(&mut self, start: usize, end: usize) {
     /// ]);
     /// ```
     pub fn add_region(&mut self, region: SelRegion) {
+        self.block = false;
         let mut ix = self.search(region.min());
         if ix == self.regions.len() {
             self.regions.push(region);
@@ -0,0 +1,246 @@
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse, Position, Range};
+
+use crate::buffer::InvalLines;
+
+/// The lines spanned by a document symbol, like a function or a class
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SymbolScope {
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Where the name of the symbol is
+    pub position: Position,
+}
+
+impl SymbolScope {
+    fn new(range: &Range, position: Position) -> Self {
+        Self {
+            start_line: range.start.line as usize,
+            end_line: range.end.line as usize,
+            position,
+        }
+    }
+
+    /// Whether the line is inside the scope, past its first line, so that the
+    /// first line is one of the headers of the line
+    fn encloses(&self, line: usize) -> bool {
+        self.start_line <= line && line < self.end_line
+    }
+}
+
+/// The scopes of the symbols of a document, which give the headers of the
+/// enclosing functions and classes of a line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolScopes {
+    /// Sorted by their start line, outer scopes before the ones they enclose
+    scopes: Vec<SymbolScope>,
+}
+
+impl SymbolScopes {
+    pub fn new(resp: &DocumentSymbolResponse) -> Self {
+        let mut scopes = Vec::new();
+        match resp {
+            DocumentSymbolResponse::Flat(symbols) => {
+                scopes.extend(symbols.iter().map(|symbol| {
+                    SymbolScope::new(
+                        &symbol.location.range,
+                        symbol.location.range.start,
+                    )
+                }));
+            }
+            DocumentSymbolResponse::Nested(symbols) => {
+                Self::push_nested(&mut scopes, symbols);
+            }
+        }
+        scopes.retain(|scope| scope.start_line < scope.end_line);
+        scopes.sort_by_key(|scope| (scope.start_line, usize::MAX - scope.end_line));
+        Self { scopes }
+    }
+
+    fn push_nested(scopes: &mut Vec<SymbolScope>, symbols: &[DocumentSymbol]) {
+        for symbol in symbols {
+            scopes.push(SymbolScope::new(
+                &symbol.range,
+                symbol.selection_range.start,
+            ));
+            if let Some(children) = symbol.children.as_ref() {
+                Self::push_nested(scopes, children);
+            }
+        }
+    }
+
+    /// Move the scopes with their lines after an edit, until the language server
+    /// gives the new ones. The scopes around the edit are stretched or shrunk.
+    pub fn apply_edit(&mut self, inval_lines: &InvalLines) {
+        let start = inval_lines.start_line;
+        let old_end = start + inval_lines.inval_count;
+        let new_end = start + inval_lines.new_count;
+        let shift = |line: usize| {
+            if line < start {
+                line
+            } else if line >= old_end {
+                line - old_end + new_end
+            } else {
+                line.min(new_end.saturating_sub(1).max(start))
+            }
+        };
+        for scope in self.scopes.iter_mut() {
+            scope.start_line = shift(scope.start_line);
+            scope.end_line = shift(scope.end_line);
+            scope.position.line = shift(scope.position.line as usize) as u32;
+        }
+        self.scopes
+            .retain(|scope| scope.start_line < scope.end_line);
+        self.scopes
+            .sort_by_key(|scope| (scope.start_line, usize::MAX - scope.end_line));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// The first lines of the scopes enclosing the line, outermost first. The
+    /// scopes starting on the line count, since the line is the header then.
+    pub fn headers(&self, line: usize) -> Vec<usize> {
+        let mut lines: Vec<usize> = self
+            .scopes
+            .iter()
+            .take_while(|scope| scope.start_line <= line)
+            .filter(|scope| scope.encloses(line))
+            .map(|scope| scope.start_line)
+            .collect();
+        lines.dedup();
+        lines
+    }
+
+    /// Where the name of the outermost symbol starting on the line is
+    pub fn position_of_line(&self, line: usize) -> Option<Position> {
+        self.scopes
+            .iter()
+            .find(|scope| scope.start_line == line)
+            .map(|scope| scope.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::{Location, SymbolInformation, SymbolKind, Url};
+
+    use super::*;
+
+    fn range(start_line: u32, end_line: u32) -> Range {
+        Range::new(Position::new(start_line, 0), Position::new(end_line, 1))
+    }
+
+    #[allow(deprecated)]
+    fn symbol(
+        name: &str,
+        start_line: u32,
+        end_line: u32,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            range: range(start_line, end_line),
+            selection_range: Range::new(
+                Position::new(start_line, 4),
+                Position::new(start_line, 4 + name.len() as u32),
+            ),
+            children: Some(children),
+        }
+    }
+
+    #[test]
+    fn test_nested_headers() {
+        // mod a {          0
+        //     struct B {   1
+        //         c: u8,   2
+        //     }            3
+        //     impl B {     4
+        //         fn d() { 5
+        //             e    6
+        //         }        7
+        //     }            8
+        // }                9
+        let scopes =
+            SymbolScopes::new(&DocumentSymbolResponse::Nested(vec![symbol(
+                "a",
+                0,
+                9,
+                vec![
+                    symbol("B", 1, 3, vec![symbol("c", 2, 2, vec![])]),
+                    symbol("B", 4, 8, vec![symbol("d", 5, 7, vec![])]),
+                ],
+            )]));
+
+        assert_eq!(scopes.headers(0), vec![0]);
+        assert_eq!(scopes.headers(2), vec![0, 1]);
+        // The last line of a scope doesn't have it as header anymore
+        assert_eq!(scopes.headers(3), vec![0]);
+        assert_eq!(scopes.headers(5), vec![0, 4, 5]);
+        assert_eq!(scopes.headers(6), vec![0, 4, 5]);
+        assert_eq!(scopes.headers(7), vec![0, 4]);
+        assert_eq!(scopes.headers(9), Vec::<usize>::new());
+        assert_eq!(scopes.headers(10), Vec::<usize>::new());
+
+        assert_eq!(scopes.position_of_line(5), Some(Position::new(5, 4)));
+        assert_eq!(scopes.position_of_line(2), None);
+    }
+
+    #[test]
+    fn test_headers_after_edit() {
+        let mut scopes = SymbolScopes::new(&DocumentSymbolResponse::Nested(vec![
+            symbol("a", 0, 4, vec![symbol("b", 1, 3, vec![])]),
+            symbol("c", 6, 8, vec![]),
+        ]));
+
+        // A new line inside of b stretches the scopes around it and moves the
+        // ones after it
+        scopes.apply_edit(&InvalLines {
+            start_line: 2,
+            inval_count: 1,
+            new_count: 2,
+        });
+        assert_eq!(scopes.headers(3), vec![0, 1]);
+        assert_eq!(scopes.headers(4), vec![0]);
+        assert_eq!(scopes.headers(8), vec![7]);
+        assert_eq!(scopes.position_of_line(7), Some(Position::new(7, 4)));
+
+        // Removing the lines of b leaves it on a single line
+        scopes.apply_edit(&InvalLines {
+            start_line: 1,
+            inval_count: 4,
+            new_count: 1,
+        });
+        assert_eq!(scopes.headers(1), vec![0]);
+        assert_eq!(scopes.headers(4), vec![4]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_flat_headers() {
+        let uri = Url::parse("file:///main.py").unwrap();
+        let info = |name: &str, start_line, end_line| SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::CLASS,
+            tags: None,
+            deprecated: None,
+            location: Location::new(uri.clone(), range(start_line, end_line)),
+            container_name: None,
+        };
+        // The symbols aren't in order, nor nested, in a flat response
+        let scopes = SymbolScopes::new(&DocumentSymbolResponse::Flat(vec![
+            info("method", 2, 4),
+            info("Class", 0, 6),
+            info("other", 8, 9),
+        ]));
+
+        assert_eq!(scopes.headers(3), vec![0, 2]);
+        assert_eq!(scopes.headers(5), vec![0]);
+        assert_eq!(scopes.headers(8), vec![8]);
+        assert_eq!(scopes.position_of_line(0), Some(Position::new(0, 0)));
+    }
+}
@@ -524,6 +524,35 @@ pub fn highlight_iter<'a>(
     // TODO: Folding
 }
 
+/// A named node of the kinds the sticky headers are made of, like a function
+/// or a type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxSymbol {
+    /// The kind of the node, like `function_item`
+    pub kind: &'static str,
+    pub name: String,
+    /// The name of the symbol it is in
+    pub container_name: Option<String>,
+    /// The offset of its name
+    pub offset: usize,
+}
+
+/// The node naming the one of a symbol
+fn symbol_name(node: Node) -> Option<Node> {
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(name);
+    }
+    // Like the functions of C, named in their declarator
+    if let Some(mut declarator) = node.child_by_field_name("declarator") {
+        while let Some(inner) = declarator.child_by_field_name("declarator") {
+            declarator = inner;
+        }
+        return Some(declarator);
+    }
+    // Like the impl blocks of Rust, named after their type
+    node.child_by_field_name("type")
+}
+
 #[derive(Clone)]
 pub struct Syntax {
     pub rev: u64,
@@ -691,6 +720,24 @@ pub fn find_matching_pair(&self, offset: usize) -> Option<usize> {
         None
     }
 
+    /// Whether the offset is in a string or a comment, which is when one of the
+    /// nodes around it is of such a kind
+    pub fn is_in_string_or_comment(&self, offset: usize) -> bool {
+        let tree = match self.layers.try_tree() {
+            Some(tree) => tree,
+            None => return false,
+        };
+        let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+        while let Some(n) = node {
+            let kind = n.kind();
+            if kind.contains("string") || kind.contains("comment") {
+                return true;
+            }
+            node = n.parent();
+        }
+        false
+    }
+
     pub fn parent_offset(&self, offset: usize) -> Option<usize> {
         let tree = self.layers.try_tree()?;
         let node = tree
@@ -741,7 +788,8 @@ fn find_tag_in_siblings(
         } else {
             node.next_sibling()
         } {
-            if sibling.kind() == tag {
+            // Missing nodes are inserted by the parser for unbalanced brackets
+            if sibling.kind() == tag && !sibling.is_missing() {
                 let offset = sibling.start_byte();
                 return Some(offset);
             }
@@ -780,6 +828,49 @@ pub fn sticky_headers(&self, offset: usize) -> Option<Vec<usize>> {
         Some(offsets)
     }
 
+    /// The symbols found in the syntax tree, for the documents no language
+    /// server gives the symbols of, in the order they appear in
+    pub fn symbols(&self) -> Vec<SyntaxSymbol> {
+        let mut symbols = Vec::new();
+        if let Some(tree) = self.layers.try_tree() {
+            self.push_symbols(tree.root_node(), None, &mut symbols);
+        }
+        symbols
+    }
+
+    fn push_symbols(
+        &self,
+        node: Node,
+        container_name: Option<&str>,
+        symbols: &mut Vec<SyntaxSymbol>,
+    ) {
+        let tags = self.language.sticky_header_tags();
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            let name = if tags.contains(&child.kind()) {
+                symbol_name(child)
+            } else {
+                None
+            };
+            match name {
+                Some(name) => {
+                    let text = self
+                        .text
+                        .slice_to_cow(name.start_byte()..name.end_byte())
+                        .to_string();
+                    symbols.push(SyntaxSymbol {
+                        kind: child.kind(),
+                        name: text.clone(),
+                        container_name: container_name.map(|n| n.to_string()),
+                        offset: name.start_byte(),
+                    });
+                    self.push_symbols(child, Some(&text), symbols);
+                }
+                None => self.push_symbols(child, container_name, symbols),
+            }
+        }
+    }
+
     pub fn find_enclosing_parentheses(
         &self,
         offset: usize,
@@ -856,6 +947,45 @@ pub fn find_enclosing_pair(&self, offset: usize) -> Option<(usize, usize)> {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_symbols() {
+        let text = "struct Foo;\nimpl Foo {\n    fn new() {}\n}\nfn main() {}\n";
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust).unwrap();
+        syntax.parse(0, Rope::from(text), None);
+        let symbols: Vec<_> = syntax
+            .symbols()
+            .into_iter()
+            .map(|s| (s.kind, s.name, s.container_name, s.offset))
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("struct_item", "Foo".to_string(), None, 7),
+                ("impl_item", "Foo".to_string(), None, 17),
+                (
+                    "function_item",
+                    "new".to_string(),
+                    Some("Foo".to_string()),
+                    30
+                ),
+                ("function_item", "main".to_string(), None, 44),
+            ]
+        );
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_is_in_string_or_comment() {
+        let text = "fn main() {\n    let s = \"a.b\"; // c.d\n    s.len();\n}\n";
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust).unwrap();
+        syntax.parse(0, Rope::from(text), None);
+        assert!(syntax.is_in_string_or_comment(text.find("a.b").unwrap() + 1));
+        assert!(syntax.is_in_string_or_comment(text.find("c.d").unwrap() + 1));
+        assert!(!syntax.is_in_string_or_comment(text.find("s.len").unwrap() + 1));
+        assert!(!syntax.is_in_string_or_comment(text.find("main").unwrap()));
+    }
+
     #[test]
     fn test_lens() {
         let lens = Syntax::lens_from_normal_lines(5, 25, 2, &[4]);
@@ -0,0 +1,195 @@
+use crate::{buffer::Buffer, selection::Selection};
+
+/// How the whitespace of a document is cleaned up when it's saved
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WhitespaceCleanup {
+    /// Whether the whitespace at the end of the lines is removed
+    pub trim_trailing: bool,
+    /// Whether a line ending is added at the end of the document if it
+    /// doesn't end with one
+    pub final_newline: bool,
+    /// Whether the lines with a cursor aren't trimmed, since the user may be
+    /// typing on them
+    pub skip_cursor_lines: bool,
+    /// Whether two or more spaces ending a line are kept, since they break
+    /// the line in markdown
+    pub keep_hard_breaks: bool,
+}
+
+impl WhitespaceCleanup {
+    /// The edits cleaning up the whitespace of the buffer, to apply with
+    /// [`Buffer::edit`] as a single undo step
+    pub fn edits(
+        &self,
+        buffer: &Buffer,
+        cursor_lines: &[usize],
+    ) -> Vec<(Selection, &'static str)> {
+        let mut edits = Vec::new();
+        if self.trim_trailing {
+            for line in 0..buffer.num_lines() {
+                if self.skip_cursor_lines && cursor_lines.contains(&line) {
+                    continue;
+                }
+                let content = buffer.line_content(line);
+                let content = content.trim_end_matches(['\n', '\r']);
+                let trimmed = content.trim_end();
+                if trimmed.len() == content.len()
+                    || (self.keep_hard_breaks
+                        && is_hard_break(trimmed, &content[trimmed.len()..]))
+                {
+                    continue;
+                }
+                let start = buffer.offset_of_line(line);
+                edits.push((
+                    Selection::region(start + trimmed.len(), start + content.len()),
+                    "",
+                ));
+            }
+        }
+
+        if self.final_newline {
+            let len = buffer.len();
+            if len > 0 && !matches!(buffer.text().byte_at(len - 1), b'\n' | b'\r') {
+                edits.push((
+                    Selection::caret(len),
+                    buffer.line_ending().in_text().as_str(),
+                ));
+            }
+        }
+
+        edits
+    }
+}
+
+/// Whether the whitespace ending a line of markdown is a hard line break, which
+/// is two or more spaces after some text
+fn is_hard_break(text: &str, whitespace: &str) -> bool {
+    !text.is_empty()
+        && whitespace.len() >= 2
+        && whitespace.bytes().all(|b| b == b' ')
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_xi_rope::Rope;
+
+    use super::WhitespaceCleanup;
+    use crate::{buffer::Buffer, editor::EditType};
+
+    fn clean_up(text: &str, cleanup: WhitespaceCleanup) -> String {
+        clean_up_with_cursors(text, cleanup, &[])
+    }
+
+    fn clean_up_with_cursors(
+        text: &str,
+        cleanup: WhitespaceCleanup,
+        cursor_lines: &[usize],
+    ) -> String {
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from(text));
+        let edits = cleanup.edits(&buffer, cursor_lines);
+        if !edits.is_empty() {
+            buffer.edit(&edits, EditType::Other);
+        }
+        buffer.to_string()
+    }
+
+    #[test]
+    fn test_trim_and_final_newline() {
+        let text = "fn main() {  \n\tlet a = 1;\t \n\n    \n    a\r\n}";
+        let cleanup = WhitespaceCleanup {
+            trim_trailing: true,
+            final_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            clean_up(text, cleanup),
+            "fn main() {\n\tlet a = 1;\n\n\n    a\r\n}\n"
+        );
+
+        // Each of them can be turned off
+        assert_eq!(
+            clean_up(
+                text,
+                WhitespaceCleanup {
+                    final_newline: false,
+                    ..cleanup
+                }
+            ),
+            "fn main() {\n\tlet a = 1;\n\n\n    a\r\n}"
+        );
+        assert_eq!(
+            clean_up(
+                text,
+                WhitespaceCleanup {
+                    trim_trailing: false,
+                    ..cleanup
+                }
+            ),
+            format!("{text}\n")
+        );
+
+        // A clean document isn't edited, nor is an empty one given a newline
+        assert_eq!(clean_up("a\nb\n", cleanup), "a\nb\n");
+        assert_eq!(clean_up("", cleanup), "");
+    }
+
+    #[test]
+    fn test_final_newline_matches_line_ending() {
+        let cleanup = WhitespaceCleanup {
+            final_newline: true,
+            ..Default::default()
+        };
+        assert_eq!(clean_up("a\r\nb", cleanup), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_skip_cursor_lines() {
+        let cleanup = WhitespaceCleanup {
+            trim_trailing: true,
+            skip_cursor_lines: true,
+            ..Default::default()
+        };
+        // Every line with a cursor is left as it is, not only the first one
+        let text = "a \nb \nc \nd \n";
+        assert_eq!(
+            clean_up_with_cursors(text, cleanup, &[1, 3]),
+            "a\nb \nc\nd \n"
+        );
+
+        // Unless the lines with a cursor are trimmed too
+        assert_eq!(
+            clean_up_with_cursors(
+                text,
+                WhitespaceCleanup {
+                    skip_cursor_lines: false,
+                    ..cleanup
+                },
+                &[1, 3]
+            ),
+            "a\nb\nc\nd\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_markdown_hard_breaks() {
+        let text = "line  \nbreak   \ntab\t\nsingle \n  \n";
+        let cleanup = WhitespaceCleanup {
+            trim_trailing: true,
+            keep_hard_breaks: true,
+            ..Default::default()
+        };
+        // Only the spaces after some text break the line
+        assert_eq!(clean_up(text, cleanup), "line  \nbreak   \ntab\nsingle\n\n");
+        assert_eq!(
+            clean_up(
+                text,
+                WhitespaceCleanup {
+                    keep_hard_breaks: false,
+                    ..cleanup
+                }
+            ),
+            "line\nbreak\ntab\nsingle\n\n"
+        );
+    }
+}
@@ -254,6 +254,79 @@ pub fn end_boundary(&mut self) -> Option<usize> {
         None
     }
 
+    /// Get the next end of a subword, and set the cursor position to the boundary found.
+    /// On top of the end of the word, a subword ends before an underscore and at the
+    /// camelCase humps inside the word.
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use lapce_xi_rope::Rope;
+    /// let rope = Rope::from("some_snake_case getHTTPResponse");
+    /// let mut cursor = WordCursor::new(&rope, 0);
+    /// assert_eq!(cursor.next_subword_end(), Some(4));
+    /// let mut cursor = WordCursor::new(&rope, 19);
+    /// assert_eq!(cursor.next_subword_end(), Some(23));
+    ///```
+    pub fn next_subword_end(&mut self) -> Option<usize> {
+        let start = self.inner.pos();
+        let end = self.end_boundary();
+        self.inner.set(start);
+
+        let mut prev = self.inner.next_codepoint()?;
+        let mut candidate = self.inner.pos();
+        while end.map_or(true, |end| candidate < end) {
+            let Some(ch) = self.inner.next_codepoint() else {
+                break;
+            };
+            if is_subword_end(prev, ch, self.inner.peek_next_codepoint()) {
+                break;
+            }
+            prev = ch;
+            candidate = self.inner.pos();
+        }
+        self.inner.set(candidate);
+        Some(candidate)
+    }
+
+    /// Get the previous start of a subword, and set the cursor position to the boundary found.
+    /// On top of the start of the word, a subword starts after an underscore and at
+    /// the camelCase humps inside the word.
+    /// **Example:**
+    ///
+    /// ```rust
+    /// # use lapce_core::word::WordCursor;
+    /// # use lapce_core::mode::Mode;
+    /// # use lapce_xi_rope::Rope;
+    /// let rope = Rope::from("some_snake_case getHTTPResponse");
+    /// let mut cursor = WordCursor::new(&rope, 15);
+    /// assert_eq!(cursor.prev_subword_start(Mode::Insert), Some(11));
+    /// let mut cursor = WordCursor::new(&rope, 23);
+    /// assert_eq!(cursor.prev_subword_start(Mode::Insert), Some(19));
+    ///```
+    pub fn prev_subword_start(&mut self, mode: Mode) -> Option<usize> {
+        let start = self.inner.pos();
+        let word_start = self.prev_boundary(mode)?;
+        self.inner.set(start);
+
+        let mut next = self.inner.peek_next_codepoint();
+        let mut ch = self.inner.prev_codepoint()?;
+        let mut candidate = self.inner.pos();
+        while candidate > word_start {
+            let Some(prev) = self.inner.prev_codepoint() else {
+                break;
+            };
+            if is_subword_start(prev, ch, next) {
+                break;
+            }
+            next = Some(ch);
+            ch = prev;
+            candidate = self.inner.pos();
+        }
+        self.inner.set(candidate);
+        Some(candidate)
+    }
+
     /// Get the first matching [`CharClassification::Other`] backward and set the cursor position to this location .
     /// **Example:**
     ///
@@ -468,6 +541,24 @@ pub fn get_char_property(codepoint: char) -> CharClassification {
     CharClassification::Other
 }
 
+/// Whether `ch`, between `prev` and `next`, starts a new camelCase hump: an
+/// uppercase letter after a lowercase one, or the last uppercase letter of an
+/// acronym followed by lowercase letters (`HTTPResponse`).
+fn is_hump(prev: char, ch: char, next: Option<char>) -> bool {
+    ((prev.is_lowercase() || prev.is_ascii_digit()) && ch.is_uppercase())
+        || (prev.is_uppercase()
+            && ch.is_uppercase()
+            && next.map_or(false, char::is_lowercase))
+}
+
+fn is_subword_start(prev: char, ch: char, next: Option<char>) -> bool {
+    (prev == '_' && ch.is_alphanumeric()) || is_hump(prev, ch, next)
+}
+
+fn is_subword_end(prev: char, ch: char, next: Option<char>) -> bool {
+    (prev.is_alphanumeric() && ch == '_') || is_hump(prev, ch, next)
+}
+
 fn classify_boundary(
     prev: CharClassification,
     next: CharClassification,
@@ -676,6 +767,62 @@ fn should_get_deletion_boundary_backward() {
         assert_eq!(&text[..position.unwrap()], "violet ");
     }
 
+    fn subword_stops(
+        text: &str,
+        offset: usize,
+        mut find_next: impl FnMut(&mut WordCursor) -> Option<usize>,
+    ) -> Vec<usize> {
+        let rope = Rope::from(text);
+        let mut cursor = WordCursor::new(&rope, offset);
+        let mut stops = Vec::new();
+        while let Some(position) = find_next(&mut cursor) {
+            stops.push(position);
+        }
+        stops
+    }
+
+    #[test]
+    fn should_stop_at_camel_case_humps() {
+        let text = "getHTTPResponseCode";
+        assert_eq!(
+            subword_stops(text, 0, |cursor| cursor.next_subword_end()),
+            vec![3, 7, 15, 19]
+        );
+        assert_eq!(
+            subword_stops(text, 19, |cursor| cursor
+                .prev_subword_start(Mode::Insert)),
+            vec![15, 7, 3, 0]
+        );
+    }
+
+    #[test]
+    fn should_stop_at_snake_case_underscores() {
+        let text = "some_snake_case";
+        assert_eq!(
+            subword_stops(text, 0, |cursor| cursor.next_subword_end()),
+            vec![4, 10, 15]
+        );
+        assert_eq!(
+            subword_stops(text, 15, |cursor| cursor
+                .prev_subword_start(Mode::Insert)),
+            vec![11, 5, 0]
+        );
+    }
+
+    #[test]
+    fn subword_stops_should_include_word_boundaries() {
+        let text = "let some_case = getHTTP;";
+        assert_eq!(
+            subword_stops(text, 0, |cursor| cursor.next_subword_end()),
+            vec![3, 8, 13, 15, 19, 23, 24]
+        );
+        assert_eq!(
+            subword_stops(text, 24, |cursor| cursor
+                .prev_subword_start(Mode::Insert)),
+            vec![23, 19, 16, 14, 9, 4, 0]
+        );
+    }
+
     #[test]
     fn find_pair_should_return_positions() {
         let text = "violet (are) blue";
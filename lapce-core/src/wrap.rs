@@ -0,0 +1,657 @@
+use crate::{
+    buffer::{Buffer, InvalLines},
+    fold::Folds,
+};
+
+/// How the editor soft wraps lines that are longer than the space available
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Lines are never wrapped, the editor scrolls horizontally instead
+    None,
+    /// Lines are wrapped at the width of the editor
+    Viewport,
+    /// Lines are wrapped at a fixed column, whatever the width of the editor
+    Column(usize),
+}
+
+impl WrapMode {
+    /// The width lines are wrapped at, given the width of the editor and the
+    /// width of a column.
+    pub fn width(&self, viewport_width: f64, char_width: f64) -> Option<f64> {
+        match *self {
+            WrapMode::None => None,
+            WrapMode::Viewport => Some(viewport_width.max(char_width)),
+            WrapMode::Column(column) => Some(column.max(1) as f64 * char_width),
+        }
+    }
+}
+
+/// A segment of a buffer line that is displayed on its own row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VisualLine {
+    pub line: usize,
+    /// The column in the line where the segment starts
+    pub start: usize,
+    /// The column in the line where the segment ends, excluding the line ending
+    pub end: usize,
+    /// The column of the line whose indentation the segment is displayed with.
+    /// Always zero for the first segment of a line.
+    pub indent: usize,
+    /// Whether this is the last segment of the line
+    pub last: bool,
+}
+
+impl VisualLine {
+    /// Whether the segment continues a line that was wrapped
+    pub fn is_continuation(&self) -> bool {
+        self.start > 0
+    }
+
+    /// Clamp a column of the line to the columns the segment displays. A
+    /// column on a wrap point belongs to the segment it starts.
+    pub fn clamp_col(&self, col: usize, line_end_col: usize) -> usize {
+        let end = if self.last {
+            line_end_col
+        } else {
+            // The column before the wrap point
+            self.end.saturating_sub(1).max(self.start)
+        };
+        col.max(self.start).min(end.max(self.start))
+    }
+}
+
+/// Where moving the cursor by visual lines goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisualMove {
+    /// There is no visual line above, so to the start of the line
+    LineStart,
+    /// There is no visual line below, so to the end of the line
+    LineEnd,
+    /// To the line and column
+    LineCol(usize, usize),
+}
+
+/// How a buffer line is wrapped
+#[derive(Clone, Debug, Default)]
+struct LineWrap {
+    /// The length of the line, excluding the line ending
+    len: usize,
+    /// The columns where the line is wrapped
+    wrap_points: Vec<usize>,
+    /// The column of the line whose indentation the rows after the first one
+    /// are displayed with
+    indent: usize,
+}
+
+static NOT_WRAPPED: LineWrap = LineWrap {
+    len: 0,
+    wrap_points: Vec::new(),
+    indent: 0,
+};
+
+/// The sums of the widths of the text are allowed to be off by this much
+const WIDTH_EPSILON: f64 = 1e-6;
+
+/// The rows the lines of a buffer are displayed on once they are soft wrapped
+/// and folded
+#[derive(Clone, Debug)]
+pub struct VisualLines {
+    width: Option<f64>,
+    /// How each buffer line is wrapped, `None` for the lines that were edited
+    /// since
+    lines: Vec<Option<LineWrap>>,
+    /// The first visual line of each buffer line, plus the total number of
+    /// visual lines at the end. A line hidden by a fold has no visual line, so
+    /// it starts where the next one does.
+    first_visual_line: Vec<usize>,
+    /// Whether the lines were edited since they were wrapped
+    edited: bool,
+}
+
+impl VisualLines {
+    /// Wrap the lines of `buffer` so that no row is wider than `width`, when
+    /// possible. Words are kept whole unless they don't fit on a row on their
+    /// own. With `wrap_indent`, the rows after the first one are indented like
+    /// the line they continue. The text is measured with `char_width`, and a
+    /// tab takes the width up to the next stop `tab_width` spaces apart.
+    pub fn new(
+        buffer: &Buffer,
+        width: f64,
+        tab_width: usize,
+        wrap_indent: bool,
+        char_width: &mut dyn FnMut(char) -> f64,
+    ) -> Self {
+        Self::with_folds(
+            buffer,
+            Some(width),
+            tab_width,
+            wrap_indent,
+            &Folds::default(),
+            char_width,
+        )
+    }
+
+    /// Wrap the lines of `buffer` like [`VisualLines::new`], unless `width` is
+    /// `None`, leaving out the lines hidden by `folds`
+    pub fn with_folds(
+        buffer: &Buffer,
+        width: Option<f64>,
+        tab_width: usize,
+        wrap_indent: bool,
+        folds: &Folds,
+        char_width: &mut dyn FnMut(char) -> f64,
+    ) -> Self {
+        let mut visual_lines = Self {
+            width,
+            lines: Vec::new(),
+            first_visual_line: Vec::new(),
+            edited: true,
+        };
+        visual_lines.rewrap(buffer, tab_width, wrap_indent, folds, char_width);
+        visual_lines
+    }
+
+    /// Keep track of the lines an edit of the buffer replaced, which are
+    /// wrapped again by [`VisualLines::rewrap`]
+    pub fn apply_edit(&mut self, inval_lines: &InvalLines) {
+        let start = inval_lines.start_line.min(self.lines.len());
+        let end = (start + inval_lines.inval_count).min(self.lines.len());
+        self.lines.splice(
+            start..end,
+            std::iter::repeat(None).take(inval_lines.new_count),
+        );
+        self.edited = true;
+    }
+
+    /// Whether the lines were edited since they were wrapped
+    pub fn is_edited(&self) -> bool {
+        self.edited
+    }
+
+    /// Wrap the lines of `buffer` that were edited since they were wrapped,
+    /// with the same width as the others, and leave out the lines hidden by
+    /// `folds`. Every line is wrapped again if the edits weren't all applied.
+    pub fn rewrap(
+        &mut self,
+        buffer: &Buffer,
+        tab_width: usize,
+        wrap_indent: bool,
+        folds: &Folds,
+        char_width: &mut dyn FnMut(char) -> f64,
+    ) {
+        let num_lines = buffer.num_lines();
+        if self.lines.len() != num_lines {
+            self.lines = vec![None; num_lines];
+        }
+
+        self.first_visual_line.clear();
+        self.first_visual_line.reserve(num_lines + 1);
+        let mut visual_line = 0;
+        for line in 0..num_lines {
+            self.first_visual_line.push(visual_line);
+            let wrap = self.lines[line].get_or_insert_with(|| {
+                let content = buffer.line_content(line);
+                let content = content.trim_end_matches(['\n', '\r']);
+                match self.width {
+                    Some(width) => {
+                        wrap_line(content, width, tab_width, wrap_indent, char_width)
+                    }
+                    None => LineWrap {
+                        len: content.len(),
+                        ..Default::default()
+                    },
+                }
+            });
+            if !folds.is_hidden(line) {
+                visual_line += wrap.wrap_points.len() + 1;
+            }
+        }
+        self.first_visual_line.push(visual_line);
+        self.edited = false;
+    }
+
+    /// The width the lines are wrapped at, `None` if they are only folded
+    pub fn width(&self) -> Option<f64> {
+        self.width
+    }
+
+    /// The total number of visual lines
+    pub fn len(&self) -> usize {
+        *self.first_visual_line.last().unwrap_or(&0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of buffer lines
+    pub fn num_lines(&self) -> usize {
+        self.first_visual_line.len().saturating_sub(1)
+    }
+
+    /// The first visual line of a buffer line
+    pub fn visual_line_of_line(&self, line: usize) -> usize {
+        let line = line.min(self.num_lines());
+        self.first_visual_line[line]
+    }
+
+    /// Whether the line is hidden by a fold, so it isn't displayed
+    pub fn is_hidden(&self, line: usize) -> bool {
+        line < self.num_lines()
+            && self.first_visual_line[line] == self.first_visual_line[line + 1]
+    }
+
+    /// The visual line a column of a buffer line is displayed on. The lines
+    /// hidden by a fold are on the last visual line before them, the one of the
+    /// fold.
+    pub fn visual_line_of_line_col(&self, line: usize, col: usize) -> usize {
+        if line >= self.num_lines() {
+            return self.len().saturating_sub(1);
+        }
+        if self.is_hidden(line) {
+            return self.first_visual_line[line].saturating_sub(1);
+        }
+        let wraps = &self.line_wrap(line).wrap_points;
+        let row = wraps.partition_point(|wrap| *wrap <= col);
+        self.first_visual_line[line] + row
+    }
+
+    /// The segment of a buffer line displayed on a visual line. Visual lines
+    /// past the end are treated as the last one.
+    pub fn visual_line(&self, visual_line: usize) -> VisualLine {
+        let visual_line = visual_line.min(self.len().saturating_sub(1));
+        let line = self
+            .first_visual_line
+            .partition_point(|first| *first <= visual_line)
+            .saturating_sub(1)
+            .min(self.num_lines().saturating_sub(1));
+        let row =
+            visual_line - self.first_visual_line.get(line).copied().unwrap_or(0);
+        let wrap = self.line_wrap(line);
+        let wraps = &wrap.wrap_points;
+        let start = if row == 0 { 0 } else { wraps[row - 1] };
+        let end = wraps.get(row).copied().unwrap_or(wrap.len);
+        VisualLine {
+            line,
+            start,
+            end,
+            indent: if row == 0 { 0 } else { wrap.indent },
+            last: row == wraps.len(),
+        }
+    }
+
+    /// Move `count` visual lines up or down from the column of the line.
+    /// `col_at` is the column of the line displayed on the visual line the move
+    /// lands on, at the horizontal position the move keeps, which is then kept
+    /// within the columns the visual line displays.
+    #[allow(clippy::too_many_arguments)]
+    pub fn move_visual_line(
+        &self,
+        buffer: &Buffer,
+        line: usize,
+        col: usize,
+        count: usize,
+        up: bool,
+        caret: bool,
+        col_at: impl FnOnce(&VisualLine) -> usize,
+    ) -> VisualMove {
+        let current = self.visual_line_of_line_col(line, col);
+        let last = self.len().saturating_sub(1);
+        if up && current == 0 {
+            return VisualMove::LineStart;
+        }
+        if !up && current >= last {
+            return VisualMove::LineEnd;
+        }
+
+        let target = if up {
+            current.saturating_sub(count)
+        } else {
+            (current + count).min(last)
+        };
+        let visual_line = self.visual_line(target);
+        let col = visual_line.clamp_col(
+            col_at(&visual_line),
+            buffer.line_end_col(visual_line.line, caret),
+        );
+        VisualMove::LineCol(visual_line.line, col)
+    }
+
+    fn line_wrap(&self, line: usize) -> &LineWrap {
+        self.lines
+            .get(line)
+            .and_then(Option::as_ref)
+            .unwrap_or(&NOT_WRAPPED)
+    }
+}
+
+/// The horizontal position after a char displayed at `x`, where a tab takes
+/// the width up to the next stop
+fn advance(
+    ch: char,
+    x: f64,
+    tab: f64,
+    char_width: &mut dyn FnMut(char) -> f64,
+) -> f64 {
+    if ch == '\t' && tab > 0.0 {
+        ((x + WIDTH_EPSILON) / tab).floor() * tab + tab
+    } else {
+        x + char_width(ch)
+    }
+}
+
+/// Find where a line of text is wrapped so that its rows are no wider than
+/// `width`
+fn wrap_line(
+    content: &str,
+    width: f64,
+    tab_width: usize,
+    wrap_indent: bool,
+    char_width: &mut dyn FnMut(char) -> f64,
+) -> LineWrap {
+    let tab = char_width(' ') * tab_width.max(1) as f64;
+
+    // The continuation indent is only kept if it leaves at least half the
+    // width for the text
+    let mut indent = (0, 0.0);
+    if wrap_indent {
+        let mut x = 0.0;
+        for (col, ch) in content.char_indices() {
+            if ch != ' ' && ch != '\t' {
+                indent = (col, x);
+                break;
+            }
+            x = advance(ch, x, tab, char_width);
+        }
+        if indent.1 * 2.0 > width {
+            indent = (0, 0.0);
+        }
+    }
+
+    let mut wrap_points = Vec::new();
+    let mut capacity = width;
+    let mut row_start = (0, 0.0);
+    let mut word_start = None;
+    let mut prev_whitespace = false;
+    let mut x = 0.0;
+    for (col, ch) in content.char_indices() {
+        let whitespace = ch.is_whitespace();
+        if !whitespace && prev_whitespace {
+            word_start = Some((col, x));
+        }
+        let next_x = advance(ch, x, tab, char_width);
+        // Whitespace is allowed to hang past the end of the row, so rows
+        // start with a word
+        while !whitespace
+            && col > row_start.0
+            && next_x - row_start.1 > capacity + WIDTH_EPSILON
+        {
+            row_start = match word_start.take() {
+                Some(start) if start.0 > row_start.0 => start,
+                _ => (col, x),
+            };
+            wrap_points.push(row_start.0);
+            capacity = width - indent.1;
+        }
+        prev_whitespace = whitespace;
+        x = next_x;
+    }
+
+    LineWrap {
+        len: content.len(),
+        indent: if wrap_points.is_empty() { 0 } else { indent.0 },
+        wrap_points,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{editor::EditType, fold::FoldRange, selection::Selection};
+
+    /// The width of a char in a monospace font, where the CJK chars take two
+    /// columns
+    fn char_width(ch: char) -> f64 {
+        if ch >= '\u{2e80}' {
+            2.0
+        } else {
+            1.0
+        }
+    }
+
+    fn rows(text: &str, width: usize, wrap_indent: bool) -> Vec<String> {
+        let buffer = Buffer::new(text);
+        let visual_lines =
+            VisualLines::new(&buffer, width as f64, 4, wrap_indent, &mut char_width);
+        (0..visual_lines.len())
+            .map(|i| {
+                let visual_line = visual_lines.visual_line(i);
+                let content = buffer.line_content(visual_line.line);
+                let indent = &content[..visual_line.indent];
+                format!("{indent}{}", &content[visual_line.start..visual_line.end])
+            })
+            .collect()
+    }
+
+    /// Move from the column of the line by the visual lines, keeping the
+    /// distance from the start of the row
+    fn move_by(
+        visual_lines: &VisualLines,
+        buffer: &Buffer,
+        (line, col): (usize, usize),
+        rows: isize,
+    ) -> VisualMove {
+        let current = visual_lines
+            .visual_line(visual_lines.visual_line_of_line_col(line, col));
+        visual_lines.move_visual_line(
+            buffer,
+            line,
+            col,
+            rows.unsigned_abs(),
+            rows < 0,
+            true,
+            |visual_line| visual_line.start + (col - current.start),
+        )
+    }
+
+    #[test]
+    fn test_wrap_mode_width() {
+        assert_eq!(WrapMode::None.width(120.0, 8.0), None);
+        assert_eq!(WrapMode::Viewport.width(120.0, 8.0), Some(120.0));
+        assert_eq!(WrapMode::Column(80).width(120.0, 2.0), Some(160.0));
+        assert_eq!(WrapMode::Column(80).width(40.0, 2.0), Some(160.0));
+    }
+
+    #[test]
+    fn test_wrap_column() {
+        assert_eq!(
+            rows("the quick brown fox jumps\nover", 10, false),
+            vec!["the quick ", "brown fox ", "jumps", "over"]
+        );
+        // A word longer than the row is broken
+        assert_eq!(
+            rows("abcdefghijklmnop qr", 6, false),
+            vec!["abcdef", "ghijkl", "mnop ", "qr"]
+        );
+        // Lines that fit aren't wrapped
+        assert_eq!(rows("0123456789\n", 10, false), vec!["0123456789", ""]);
+    }
+
+    #[test]
+    fn test_wrap_indent() {
+        assert_eq!(
+            rows("    let value = compute(first, second);", 20, true),
+            vec!["    let value = ", "    compute(first, ", "    second);"]
+        );
+        assert_eq!(
+            rows("    let value = compute(first, second);", 20, false),
+            vec!["    let value = ", "compute(first, ", "second);"]
+        );
+        // An indentation that takes more than half the row isn't kept
+        assert_eq!(
+            rows("            abc def", 16, true),
+            vec!["            abc ", "def"]
+        );
+    }
+
+    #[test]
+    fn test_wrap_tabs() {
+        // The tab takes the width up to the next tab stop
+        assert_eq!(rows("a\tbcdef", 6, false), vec!["a\t", "bcdef"]);
+    }
+
+    #[test]
+    fn test_wrap_wide_chars() {
+        // The rows are as wide as the chars are displayed, not as many chars
+        assert_eq!(rows("中文字符 abc", 5, false), vec!["中文", "字符 ", "abc"]);
+    }
+
+    #[test]
+    fn test_rewrap_edited_lines() {
+        let mut buffer = Buffer::new("aaaa bbbb\ncc\ndddd eeee");
+        let mut visual_lines =
+            VisualLines::new(&buffer, 5.0, 4, false, &mut char_width);
+        assert_eq!(visual_lines.len(), 5);
+        assert!(!visual_lines.is_edited());
+
+        let offset = buffer.line_end_offset(1, true);
+        let (_, inval_lines, _) =
+            buffer.edit(&[(Selection::caret(offset), " ffff\nhh")], EditType::Other);
+        visual_lines.apply_edit(&inval_lines);
+        assert!(visual_lines.is_edited());
+
+        let mut measured = String::new();
+        visual_lines.rewrap(&buffer, 4, false, &Folds::default(), &mut |ch| {
+            measured.push(ch);
+            char_width(ch)
+        });
+        // Only the edited lines are measured, each after the space the tab
+        // stops are measured with
+        assert_eq!(measured, " cc ffff hh");
+        assert!(!visual_lines.is_edited());
+        assert_eq!(visual_lines.len(), 7);
+        assert_eq!(visual_lines.visual_line_of_line_col(1, 3), 3);
+        assert_eq!(visual_lines.visual_line_of_line(2), 4);
+        assert_eq!(visual_lines.visual_line_of_line_col(3, 5), 6);
+    }
+
+    #[test]
+    fn test_visual_line_of_line_col() {
+        let buffer = Buffer::new("aaaa bbbb cccc\nshort\ndddd eeee");
+        let visual_lines = VisualLines::new(&buffer, 5.0, 4, false, &mut char_width);
+        assert_eq!(visual_lines.len(), 6);
+        assert_eq!(visual_lines.visual_line_of_line_col(0, 0), 0);
+        assert_eq!(visual_lines.visual_line_of_line_col(0, 4), 0);
+        // The wrap point belongs to the row it starts
+        assert_eq!(visual_lines.visual_line_of_line_col(0, 5), 1);
+        assert_eq!(visual_lines.visual_line_of_line_col(0, 14), 2);
+        assert_eq!(visual_lines.visual_line_of_line_col(1, 3), 3);
+        assert_eq!(visual_lines.visual_line_of_line_col(2, 9), 5);
+        assert_eq!(visual_lines.visual_line_of_line(2), 4);
+
+        assert_eq!(
+            visual_lines.visual_line(1),
+            VisualLine {
+                line: 0,
+                start: 5,
+                end: 10,
+                indent: 0,
+                last: false,
+            }
+        );
+        assert_eq!(
+            visual_lines.visual_line(3),
+            VisualLine {
+                line: 1,
+                start: 0,
+                end: 5,
+                indent: 0,
+                last: true,
+            }
+        );
+        // Past the end is the last visual line
+        assert_eq!(visual_lines.visual_line(10), visual_lines.visual_line(5));
+    }
+
+    #[test]
+    fn test_move_across_wrapped_lines() {
+        let buffer = Buffer::new("aaaa bb ccccc\nxy\n");
+        let visual_lines = VisualLines::new(&buffer, 5.0, 4, false, &mut char_width);
+        let move_by =
+            |line_col, rows| move_by(&visual_lines, &buffer, line_col, rows);
+
+        // Moving down by a visual line keeps the distance from the start of
+        // the row, within the columns the row displays
+        assert_eq!(move_by((0, 2), 1), VisualMove::LineCol(0, 7));
+        // The row below is shorter, so the cursor stops before its wrap point
+        assert_eq!(move_by((0, 4), 1), VisualMove::LineCol(0, 7));
+        assert_eq!(move_by((0, 7), 1), VisualMove::LineCol(0, 10));
+        assert_eq!(move_by((0, 12), 1), VisualMove::LineCol(1, 2));
+        assert_eq!(move_by((1, 2), 1), VisualMove::LineCol(2, 0));
+        assert_eq!(move_by((0, 2), 3), VisualMove::LineCol(1, 2));
+        assert_eq!(move_by((2, 0), 1), VisualMove::LineEnd);
+
+        assert_eq!(move_by((1, 1), -1), VisualMove::LineCol(0, 9));
+        assert_eq!(move_by((0, 9), -1), VisualMove::LineCol(0, 6));
+        assert_eq!(move_by((0, 12), -1), VisualMove::LineCol(0, 7));
+        assert_eq!(move_by((0, 3), -1), VisualMove::LineStart);
+    }
+
+    #[test]
+    fn test_folded_lines() {
+        let buffer = Buffer::new("fn a() {\n    b();\n    c();\n}\nd\n");
+        let folds = Folds::new([FoldRange::new(0, 2)]);
+        let visual_lines = VisualLines::with_folds(
+            &buffer,
+            None,
+            4,
+            false,
+            &folds,
+            &mut char_width,
+        );
+        assert_eq!(visual_lines.width(), None);
+        assert_eq!(visual_lines.len(), 4);
+        assert!(!visual_lines.is_hidden(0));
+        assert!(visual_lines.is_hidden(1));
+        assert!(visual_lines.is_hidden(2));
+        assert!(!visual_lines.is_hidden(3));
+
+        let lines: Vec<_> = (0..visual_lines.len())
+            .map(|i| visual_lines.visual_line(i).line)
+            .collect();
+        assert_eq!(lines, vec![0, 3, 4, 5]);
+        // The hidden lines are on the visual line of the fold
+        assert_eq!(visual_lines.visual_line_of_line_col(2, 4), 0);
+        assert_eq!(visual_lines.visual_line_of_line(3), 1);
+    }
+
+    #[test]
+    fn test_move_across_folded_lines() {
+        let buffer = Buffer::new("if {\n    b\n    c\n}\nlong line here\n");
+        let folds = Folds::new([FoldRange::new(0, 2), FoldRange::new(1, 2)]);
+        let visual_lines = VisualLines::with_folds(
+            &buffer,
+            Some(5.0),
+            4,
+            false,
+            &folds,
+            &mut char_width,
+        );
+        assert_eq!(visual_lines.len(), 6);
+
+        // Moving down from the fold skips the lines it hides, and moving up
+        // comes back to it
+        let move_by =
+            |line, rows| match move_by(&visual_lines, &buffer, (line, 0), rows) {
+                VisualMove::LineCol(line, _) => line,
+                movement => panic!("moved to {movement:?}"),
+            };
+        assert_eq!(move_by(0, 1), 3);
+        assert_eq!(move_by(3, -1), 0);
+        assert_eq!(move_by(3, 1), 4);
+        // The wrapped rows of a line are still there after a fold
+        assert_eq!(move_by(0, 3), 4);
+        assert_eq!(move_by(0, 4), 4);
+        assert_eq!(move_by(0, 5), 5);
+    }
+}
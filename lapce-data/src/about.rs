@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::{fmt::Write, path::Path, sync::Arc};
 
 use druid::{Command, Env, EventCtx, Modifiers, Target, WidgetId};
-use lapce_core::{command::FocusCommand, mode::Mode};
+use lapce_core::{command::FocusCommand, meta, mode::Mode};
 
 use crate::{
     command::{CommandExecuted, CommandKind, LapceCommand, LAPCE_COMMAND},
@@ -15,6 +15,156 @@ pub struct AboutData {
     pub active: bool,
 }
 
+/// How the running version of Lapce was built
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub release: &'static str,
+    pub commit: Option<&'static str>,
+    pub rustc: &'static str,
+    pub target: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let features = [
+            ("updater", cfg!(feature = "updater")),
+            ("portable", cfg!(feature = "portable")),
+        ];
+        Self {
+            version: *meta::VERSION,
+            release: *meta::RELEASE,
+            commit: meta::GIT_COMMIT,
+            rustc: meta::RUSTC_VERSION,
+            target: meta::TARGET,
+            features: features
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature)
+                .collect(),
+        }
+    }
+}
+
+/// What a bug report needs to know about the running Lapce, to be copied from
+/// the about dialog
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    pub build: BuildInfo,
+    pub os: String,
+    /// The name and the version of the plugins enabled in the workspace
+    pub plugins: Vec<(String, String)>,
+    /// The last lines of the log
+    pub log: Vec<String>,
+}
+
+impl Diagnostics {
+    /// How many lines of the log are included
+    const LOG_LINES: usize = 50;
+
+    pub fn new(data: &LapceTabData) -> Self {
+        let plugins = data
+            .plugin
+            .installed
+            .iter()
+            .filter(|(id, _)| {
+                !data.plugin.disabled.contains(*id)
+                    && !data.plugin.workspace_disabled.contains(*id)
+            })
+            .map(|(id, volt)| (id.to_string(), volt.version.clone()))
+            .collect();
+        let log = data
+            .log_file
+            .as_ref()
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|log| {
+                let lines: Vec<&str> = log.lines().collect();
+                lines[lines.len().saturating_sub(Self::LOG_LINES)..]
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            build: BuildInfo::current(),
+            os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            plugins,
+            log,
+        }
+    }
+
+    /// The diagnostics as markdown, to be pasted in an issue. The home
+    /// directory is replaced with `~` if it's given, so that the paths in the
+    /// log don't tell the user name.
+    pub fn format(&self, home: Option<&Path>) -> String {
+        let build = &self.build;
+        let mut text = String::new();
+        let _ = writeln!(text, "Lapce {} ({})", build.version, build.release);
+        let _ = writeln!(text, "Commit: {}", build.commit.unwrap_or("unknown"));
+        let _ = writeln!(text, "Compiler: {}", build.rustc);
+        let _ = writeln!(text, "Target: {}", build.target);
+        let features = if build.features.is_empty() {
+            "none".to_string()
+        } else {
+            build.features.join(", ")
+        };
+        let _ = writeln!(text, "Features: {features}");
+        let _ = writeln!(text, "OS: {}", self.os);
+
+        text.push_str("\nPlugins:\n");
+        if self.plugins.is_empty() {
+            text.push_str("none\n");
+        }
+        for (name, version) in &self.plugins {
+            let _ = writeln!(text, "- {name} {version}");
+        }
+
+        if !self.log.is_empty() {
+            text.push_str("\nLog:\n```\n");
+            for line in &self.log {
+                text.push_str(line);
+                text.push('\n');
+            }
+            text.push_str("```\n");
+        }
+
+        match home.and_then(|home| home.to_str()) {
+            Some(home) if !home.is_empty() => replace_home(&text, home),
+            _ => text,
+        }
+    }
+}
+
+/// Replace the home directory in the paths of the text with `~`, leaving the
+/// paths it's only part of a name in, like `/home/jane2` for `/home/jane`
+fn replace_home(text: &str, home: &str) -> String {
+    let home = home.trim_end_matches(std::path::is_separator);
+    if home.is_empty() {
+        return text.to_string();
+    }
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(home) {
+        let end = index + home.len();
+        let starts_path = rest[..index]
+            .chars()
+            .next_back()
+            .map_or(true, |c| !is_name_char(c) && !std::path::is_separator(c));
+        let ends_name = rest[end..]
+            .chars()
+            .next()
+            .map_or(true, |c| !is_name_char(c) || std::path::is_separator(c));
+        replaced.push_str(&rest[..index]);
+        replaced.push_str(if starts_path && ends_name { "~" } else { home });
+        rest = &rest[end..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
 pub struct AboutFocusData {
     about: Arc<AboutData>,
 }
@@ -80,3 +230,77 @@ pub fn new(data: &LapceTabData) -> Self {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{BuildInfo, Diagnostics};
+
+    fn diagnostics(plugins: Vec<(&str, &str)>, log: Vec<&str>) -> Diagnostics {
+        Diagnostics {
+            build: BuildInfo {
+                version: "0.2.5",
+                release: "Stable",
+                commit: Some("1a2b3c4d5e"),
+                rustc: "rustc 1.65.0 (897e37553 2022-11-02)",
+                target: "x86_64-unknown-linux-gnu",
+                features: vec!["updater"],
+            },
+            os: "linux x86_64".to_string(),
+            plugins: plugins
+                .into_iter()
+                .map(|(name, version)| (name.to_string(), version.to_string()))
+                .collect(),
+            log: log.into_iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_format_diagnostics() {
+        let diagnostics = diagnostics(
+            vec![("dzhou121.lapce-rust", "0.3.1")],
+            vec![
+                "[INFO] open /home/jane/code/main.rs",
+                "[INFO] open /home/jane2/notes.txt and /srv/home/jane/a",
+                "[INFO] cwd /home/jane",
+            ],
+        );
+        let expected = "\
+Lapce 0.2.5 (Stable)
+Commit: 1a2b3c4d5e
+Compiler: rustc 1.65.0 (897e37553 2022-11-02)
+Target: x86_64-unknown-linux-gnu
+Features: updater
+OS: linux x86_64
+
+Plugins:
+- dzhou121.lapce-rust 0.3.1
+
+Log:
+```
+[INFO] open ~/code/main.rs
+[INFO] open /home/jane2/notes.txt and /srv/home/jane/a
+[INFO] cwd ~
+```
+";
+        assert_eq!(diagnostics.format(Some(Path::new("/home/jane"))), expected);
+
+        // The paths are kept as they are without the home directory
+        assert!(diagnostics
+            .format(None)
+            .contains("[INFO] open /home/jane/code/main.rs"));
+    }
+
+    #[test]
+    fn test_format_empty_diagnostics() {
+        let mut diagnostics = diagnostics(Vec::new(), Vec::new());
+        diagnostics.build.commit = None;
+        diagnostics.build.features = Vec::new();
+        let text = diagnostics.format(None);
+        assert!(text.contains("Commit: unknown\n"));
+        assert!(text.contains("Features: none\n"));
+        assert!(text.ends_with("Plugins:\nnone\n"));
+        assert!(!text.contains("Log:"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 
 use druid::{Command, Env, EventCtx, Modifiers, Target, WidgetId};
 use lapce_core::{command::FocusCommand, mode::Mode};
@@ -9,11 +9,26 @@
     keypress::KeyPressFocus,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct AlertContentData {
     pub title: String,
     pub msg: String,
-    pub buttons: Vec<(String, WidgetId, LapceCommand)>,
+    /// Shown below the message in markdown, like the notes of a release
+    pub notes: Option<String>,
+    pub buttons: Vec<AlertButton>,
+    /// How long the alert is shown before it's dismissed, if it isn't kept
+    /// until the user dismisses it. The time doesn't run while the mouse is
+    /// over the alert.
+    pub timeout: Option<Duration>,
+}
+
+/// A button of an alert, which runs the command on the target and dismisses
+/// the alert
+#[derive(Clone, Debug)]
+pub struct AlertButton {
+    pub text: String,
+    pub target: WidgetId,
+    pub command: LapceCommand,
 }
 
 #[derive(Clone)]
@@ -21,6 +36,14 @@ pub struct AlertData {
     pub widget_id: WidgetId,
     pub active: bool,
     pub content: AlertContentData,
+    /// What the current alert is about, if a newer alert about the same thing
+    /// replaces it
+    key: Option<String>,
+    /// The alerts shown after the current one is dismissed, in the order they
+    /// came in, along with what they're about
+    pub queue: VecDeque<(Option<String>, AlertContentData)>,
+    /// Counts the alerts shown, to tell when the current one is replaced
+    pub shown: usize,
 }
 
 pub struct AlertFocusData {
@@ -38,13 +61,74 @@ pub fn new() -> Self {
         Self {
             widget_id: WidgetId::next(),
             active: false,
-            content: AlertContentData {
-                title: "".to_string(),
-                msg: "".to_string(),
-                buttons: Vec::new(),
-            },
+            content: AlertContentData::default(),
+            key: None,
+            queue: VecDeque::new(),
+            shown: 0,
         }
     }
+
+    /// Show the alert, or queue it if another one is shown already, so that
+    /// it isn't replaced before the user sees it
+    pub fn push(&mut self, content: AlertContentData) {
+        self.push_keyed(None, content);
+    }
+
+    /// Show the alert like [`AlertData::push`], unless an alert with the same
+    /// key is shown or queued, which is replaced by it instead. This keeps a
+    /// single alert about a thing, like a file changed several times on disk.
+    pub fn push_keyed(&mut self, key: Option<String>, content: AlertContentData) {
+        if key.is_some() {
+            if self.active && self.key == key {
+                self.show(key, content);
+                return;
+            }
+            if let Some(queued) =
+                self.queue.iter_mut().find(|(queued, _)| *queued == key)
+            {
+                queued.1 = content;
+                return;
+            }
+        }
+
+        if self.active {
+            self.queue.push_back((key, content));
+        } else {
+            self.show(key, content);
+        }
+    }
+
+    /// Dismiss the current alert and show the next one. Returns whether there
+    /// was one.
+    pub fn dismiss(&mut self) -> bool {
+        match self.queue.pop_front() {
+            Some((key, content)) => {
+                self.show(key, content);
+                true
+            }
+            None => {
+                self.active = false;
+                false
+            }
+        }
+    }
+
+    fn show(&mut self, key: Option<String>, content: AlertContentData) {
+        self.active = true;
+        self.key = key;
+        self.content = content;
+        self.shown += 1;
+    }
+
+    /// The command to submit when the button of the current alert is pressed
+    pub fn button_command(&self, index: usize) -> Option<Command> {
+        let button = self.content.buttons.get(index)?;
+        Some(Command::new(
+            LAPCE_COMMAND,
+            button.command.clone(),
+            Target::Widget(button.target),
+        ))
+    }
 }
 
 impl KeyPressFocus for AlertFocusData {
@@ -93,3 +177,112 @@ pub fn new(data: &LapceTabData) -> Self {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use druid::{Target, WidgetId};
+    use lapce_core::command::FocusCommand;
+
+    use super::{AlertButton, AlertContentData, AlertData};
+    use crate::command::{
+        CommandKind, LapceCommand, LapceWorkbenchCommand, LAPCE_COMMAND,
+    };
+
+    fn alert(title: &str) -> AlertContentData {
+        AlertContentData {
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_alert_queue() {
+        let mut data = AlertData::new();
+        data.push(alert("first"));
+        assert!(data.active);
+        assert_eq!(data.content.title, "first");
+
+        // The alerts coming in while one is shown wait for it to be dismissed
+        data.push(alert("second"));
+        data.push(alert("third"));
+        assert_eq!(data.content.title, "first");
+
+        assert!(data.dismiss());
+        assert_eq!(data.content.title, "second");
+        assert!(data.dismiss());
+        assert_eq!(data.content.title, "third");
+        assert!(!data.dismiss());
+        assert!(!data.active);
+        assert_eq!(data.shown, 3);
+
+        data.push(alert("fourth"));
+        assert!(data.active);
+        assert_eq!(data.content.title, "fourth");
+    }
+
+    #[test]
+    fn test_keyed_alerts_replace_each_other() {
+        let key = |path: &str| Some(path.to_string());
+        let mut data = AlertData::new();
+        data.push_keyed(key("a.rs"), alert("a.rs changed"));
+        data.push_keyed(key("b.rs"), alert("b.rs changed"));
+        data.push(alert("other"));
+
+        // The alert shown is replaced, and so is the queued one
+        data.push_keyed(key("a.rs"), alert("a.rs deleted"));
+        assert_eq!(data.content.title, "a.rs deleted");
+        data.push_keyed(key("b.rs"), alert("b.rs changed again"));
+        assert_eq!(data.queue.len(), 2);
+
+        assert!(data.dismiss());
+        assert_eq!(data.content.title, "b.rs changed again");
+        assert!(data.dismiss());
+        assert_eq!(data.content.title, "other");
+        assert!(!data.dismiss());
+
+        // Once dismissed, the alert about the same thing is shown again
+        data.push_keyed(key("a.rs"), alert("a.rs changed"));
+        assert!(data.active);
+        assert_eq!(data.content.title, "a.rs changed");
+    }
+
+    #[test]
+    fn test_button_command() {
+        let editor_id = WidgetId::next();
+        let tab_id = WidgetId::next();
+        let mut data = AlertData::new();
+        data.push(AlertContentData {
+            buttons: vec![
+                AlertButton {
+                    text: "Reload".to_string(),
+                    target: editor_id,
+                    command: LapceCommand {
+                        kind: CommandKind::Focus(FocusCommand::ForceExit),
+                        data: None,
+                    },
+                },
+                AlertButton {
+                    text: "Keep".to_string(),
+                    target: tab_id,
+                    command: LapceCommand {
+                        kind: CommandKind::Workbench(
+                            LapceWorkbenchCommand::OpenLink,
+                        ),
+                        data: Some(serde_json::json!("https://lapce.dev")),
+                    },
+                },
+            ],
+            ..alert("File changed on disk")
+        });
+
+        let command = data.button_command(1).unwrap();
+        assert_eq!(command.target(), Target::Widget(tab_id));
+        let command = command.get(LAPCE_COMMAND).unwrap();
+        assert_eq!(command.kind.str(), "open_link");
+        assert_eq!(command.data, Some(serde_json::json!("https://lapce.dev")));
+
+        let command = data.button_command(0).unwrap();
+        assert_eq!(command.target(), Target::Widget(editor_id));
+        assert!(data.button_command(2).is_none());
+    }
+}
@@ -1,4 +1,8 @@
-use lapce_core::buffer::Buffer;
+use lapce_core::{
+    buffer::Buffer,
+    cursor::{Cursor, CursorMode},
+    selection::{SelRegion, Selection},
+};
 
 /// The direction to snap. Left is used when moving left, Right when moving right.
 /// Nearest is used for mouse selection.
@@ -50,7 +54,7 @@ pub fn snap_to_soft_tab_line_col(
 
 /// Internal shared logic that performs the actual snapping. It can be passed
 /// either an column or offset within the line since it is only modified when it makes no
-/// difference which is used (since they're equal for spaces).
+/// difference which is used (since they're equal for whitespace).
 /// It returns the column or offset within the line (depending on what you passed in).
 fn snap_to_soft_tab_logic(
     buffer: &Buffer,
@@ -61,14 +65,15 @@ fn snap_to_soft_tab_logic(
 ) -> usize {
     assert!(tab_width >= 1);
 
-    // Number of spaces, ignoring incomplete soft tabs.
-    let space_count =
-        (count_spaces_from(buffer, start_line_offset) / tab_width) * tab_width;
-
-    // If we're past the soft tabs, we don't need to snap.
-    if offset_or_col >= space_count {
-        return offset_or_col;
-    }
+    // Only a soft tab that the cursor is strictly inside of needs snapping, its
+    // edges are fine as they are.
+    let soft_tab = soft_tabs(buffer, start_line_offset, tab_width)
+        .take_while(|(start, _)| *start < offset_or_col)
+        .find(|(_, end)| offset_or_col < *end);
+    let (start, end) = match soft_tab {
+        Some(soft_tab) => soft_tab,
+        None => return offset_or_col,
+    };
 
     let bias = match direction {
         SnapDirection::Left => 0,
@@ -76,20 +81,121 @@ fn snap_to_soft_tab_logic(
         SnapDirection::Nearest => tab_width / 2,
     };
 
-    ((offset_or_col + bias) / tab_width) * tab_width
+    if offset_or_col - start + bias >= tab_width {
+        end
+    } else {
+        start
+    }
 }
 
-/// Count the number of spaces found after a certain offset.
-fn count_spaces_from(buffer: &Buffer, from_offset: usize) -> usize {
-    let mut cursor = lapce_xi_rope::Cursor::new(buffer.text(), from_offset);
-    let mut space_count = 0usize;
-    while let Some(next) = cursor.next_codepoint() {
-        if next != ' ' {
-            break;
+/// The soft tabs in the leading indentation of the line starting at
+/// `start_line_offset`, as ranges of offsets within the line. A soft tab is
+/// `tab_width` spaces going from a tab stop to the next one, so spaces that
+/// follow a hard tab only make one once they reach a tab stop, and spaces that
+/// don't fill up to the next tab stop make none.
+fn soft_tabs(
+    buffer: &Buffer,
+    start_line_offset: usize,
+    tab_width: usize,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut cursor = lapce_xi_rope::Cursor::new(buffer.text(), start_line_offset);
+    // The visual column, where hard tabs go to the next tab stop
+    let mut col = 0;
+    let mut offset = 0;
+    let mut soft_tab_start = None;
+    std::iter::from_fn(move || loop {
+        match cursor.next_codepoint()? {
+            ' ' => {
+                if col % tab_width == 0 {
+                    soft_tab_start = Some(offset);
+                }
+                col += 1;
+                offset += 1;
+                if col % tab_width == 0 {
+                    if let Some(start) = soft_tab_start.take() {
+                        return Some((start, offset));
+                    }
+                }
+            }
+            '\t' => {
+                col = (col / tab_width + 1) * tab_width;
+                offset += 1;
+                soft_tab_start = None;
+            }
+            // The content of the line, where the indentation ends
+            _ => return None,
+        }
+    })
+}
+
+/// Where the text deleted by a backspace at `offset` starts, if the offset is in
+/// the leading indentation of its line, the boundary with the content of the
+/// line included. It's the previous tab stop if only spaces are in the way,
+/// which deletes a whole soft tab after a tab stop, or the hard tab right
+/// before the offset.
+pub fn soft_tab_backspace_start(
+    buffer: &Buffer,
+    offset: usize,
+    tab_width: usize,
+) -> Option<usize> {
+    assert!(tab_width >= 1);
+
+    let line = buffer.line_of_offset(offset);
+    let start_line_offset = buffer.offset_of_line(line);
+    let offset_within_line = offset - start_line_offset;
+    if offset_within_line == 0 {
+        return None;
+    }
+
+    let mut cursor = lapce_xi_rope::Cursor::new(buffer.text(), start_line_offset);
+    let mut col = 0;
+    let mut start = 0;
+    for i in 0..offset_within_line {
+        match cursor.next_codepoint()? {
+            ' ' => {
+                if col % tab_width == 0 {
+                    start = i;
+                }
+                col += 1;
+            }
+            '\t' => {
+                start = i;
+                col = (col / tab_width + 1) * tab_width;
+            }
+            // The offset is past the indentation
+            _ => return None,
         }
-        space_count += 1;
     }
-    space_count
+
+    Some(start_line_offset + start)
+}
+
+/// Make the carets of an insert mode `cursor` that are in the leading
+/// indentation select what a backspace deletes there, as given by
+/// [`soft_tab_backspace_start`]. The other carets are left as they are.
+pub fn select_soft_tab_backspace(
+    buffer: &Buffer,
+    cursor: &mut Cursor,
+    tab_width: usize,
+) {
+    let selection = match &cursor.mode {
+        CursorMode::Insert(selection) => selection,
+        CursorMode::Normal(_) | CursorMode::Visual { .. } => return,
+    };
+
+    let mut new_selection = Selection::new();
+    for region in selection.regions() {
+        let new_region = if region.is_caret() {
+            match soft_tab_backspace_start(buffer, region.end, tab_width) {
+                Some(start) => SelRegion::new(region.end, start, None),
+                None => *region,
+            }
+        } else {
+            *region
+        };
+        new_selection.add_region(new_region);
+    }
+    cursor.set_insert(new_selection);
 }
 
 #[cfg(test)]
@@ -97,19 +203,26 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_count_spaces_from() {
-        let buffer = Buffer::new("     abc\n   def\nghi\n");
-        assert_eq!(count_spaces_from(&buffer, 0), 5);
-        assert_eq!(count_spaces_from(&buffer, 1), 4);
-        assert_eq!(count_spaces_from(&buffer, 5), 0);
-        assert_eq!(count_spaces_from(&buffer, 6), 0);
-
-        assert_eq!(count_spaces_from(&buffer, 8), 0);
-        assert_eq!(count_spaces_from(&buffer, 9), 3);
-        assert_eq!(count_spaces_from(&buffer, 10), 2);
-
-        assert_eq!(count_spaces_from(&buffer, 16), 0);
-        assert_eq!(count_spaces_from(&buffer, 17), 0);
+    fn test_soft_tabs() {
+        let buffer = Buffer::new("     abc\n   def\nghi\n\t  \t    x\n  \t  \n");
+        let soft_tabs = |line: usize, tab_width: usize| {
+            soft_tabs(&buffer, buffer.offset_of_line(line), tab_width)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(soft_tabs(0, 4), vec![(0, 4)]);
+        assert_eq!(soft_tabs(0, 2), vec![(0, 2), (2, 4)]);
+        assert_eq!(soft_tabs(0, 8), vec![]);
+        assert_eq!(soft_tabs(1, 4), vec![]);
+        assert_eq!(soft_tabs(1, 2), vec![(0, 2)]);
+        assert_eq!(soft_tabs(2, 4), vec![]);
+
+        // Spaces following a hard tab are only a soft tab between tab stops
+        assert_eq!(soft_tabs(3, 4), vec![(4, 8)]);
+        assert_eq!(soft_tabs(3, 2), vec![(1, 3), (4, 6), (6, 8)]);
+        assert_eq!(soft_tabs(3, 8), vec![]);
+        assert_eq!(soft_tabs(4, 2), vec![(0, 2), (3, 5)]);
+        assert_eq!(soft_tabs(4, 4), vec![]);
     }
 
     #[test]
@@ -252,4 +365,108 @@ fn test_snap_to_soft_tab_line_col() {
             );
         }
     }
+
+    #[test]
+    fn test_snap_with_tab_widths() {
+        let buffer = Buffer::new("        abc\n\t    def\n  \t  ghi");
+        let snap = |offset: usize, tab_width: usize| {
+            (
+                snap_to_soft_tab(&buffer, offset, SnapDirection::Left, tab_width),
+                snap_to_soft_tab(&buffer, offset, SnapDirection::Nearest, tab_width),
+                snap_to_soft_tab(&buffer, offset, SnapDirection::Right, tab_width),
+            )
+        };
+
+        // Tab width 2
+        assert_eq!(snap(1, 2), (0, 2, 2));
+        assert_eq!(snap(5, 2), (4, 6, 6));
+        assert_eq!(snap(8, 2), (8, 8, 8));
+
+        // Tab width 4
+        assert_eq!(snap(1, 4), (0, 0, 4));
+        assert_eq!(snap(6, 4), (4, 8, 8));
+
+        // Tab width 8
+        assert_eq!(snap(3, 8), (0, 0, 8));
+        assert_eq!(snap(4, 8), (0, 8, 8));
+        assert_eq!(snap(9, 8), (9, 9, 9));
+
+        // Mixed whitespace: "\t    def" starts at 12, its soft tab follows the
+        // hard tab
+        assert_eq!(snap(13, 4), (13, 13, 13));
+        assert_eq!(snap(14, 4), (13, 13, 17));
+        assert_eq!(snap(15, 4), (13, 17, 17));
+        assert_eq!(snap(14, 8), (14, 14, 14));
+        assert_eq!(snap(14, 2), (13, 15, 15));
+
+        // "  \t  ghi" starts at 21, the spaces before the hard tab are a soft
+        // tab only with a width of 2, and the ones after it don't reach a tab stop
+        // with a width of 4
+        assert_eq!(snap(22, 2), (21, 23, 23));
+        assert_eq!(snap(22, 4), (22, 22, 22));
+        assert_eq!(snap(25, 4), (25, 25, 25));
+        assert_eq!(snap(25, 2), (24, 26, 26));
+    }
+
+    #[test]
+    fn test_soft_tab_backspace() {
+        let buffer = Buffer::new("        abc\n\t  def\n  \t x\n      y");
+        let start = |offset: usize, tab_width: usize| {
+            soft_tab_backspace_start(&buffer, offset, tab_width)
+        };
+
+        // A whole soft tab with tab widths of 2, 4 and 8
+        assert_eq!(start(8, 2), Some(6));
+        assert_eq!(start(8, 4), Some(4));
+        assert_eq!(start(8, 8), Some(0));
+        // Back to the previous tab stop from inside a soft tab
+        assert_eq!(start(6, 4), Some(4));
+        assert_eq!(start(3, 8), Some(0));
+        // Nothing to do at the start of the line, or past the indentation
+        assert_eq!(start(0, 4), None);
+        assert_eq!(start(9, 4), None);
+        assert_eq!(start(11, 4), None);
+
+        // Mixed whitespace: "\t  def" starts at 12. The spaces are deleted back to
+        // the tab stop the hard tab goes to, at the boundary with the content too
+        assert_eq!(start(15, 4), Some(13));
+        assert_eq!(start(14, 4), Some(13));
+        assert_eq!(start(13, 4), Some(12));
+        assert_eq!(start(15, 2), Some(13));
+        assert_eq!(start(16, 4), None);
+
+        // "  \t x" starts at 19, the hard tab is deleted by itself
+        assert_eq!(start(23, 4), Some(22));
+        assert_eq!(start(22, 4), Some(21));
+        assert_eq!(start(21, 4), Some(19));
+        assert_eq!(start(21, 2), Some(19));
+        assert_eq!(start(24, 4), None);
+
+        // "      y" starts at 25, with an incomplete soft tab at the end
+        assert_eq!(start(31, 4), Some(29));
+        assert_eq!(start(31, 2), Some(29));
+        assert_eq!(start(31, 8), Some(25));
+    }
+
+    #[test]
+    fn test_select_soft_tab_backspace() {
+        let buffer = Buffer::new("        abc\n\t  def");
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::caret(8));
+        selection.add_region(SelRegion::caret(10));
+        selection.add_region(SelRegion::caret(15));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+
+        select_soft_tab_backspace(&buffer, &mut cursor, 4);
+        let selection = match &cursor.mode {
+            CursorMode::Insert(selection) => selection,
+            _ => panic!("not in insert mode"),
+        };
+        let regions: Vec<(usize, usize)> = selection
+            .regions()
+            .iter()
+            .map(|region| (region.min(), region.max()))
+            .collect();
+        assert_eq!(regions, vec![(4, 8), (10, 10), (13, 15)]);
+    }
 }
@@ -0,0 +1,526 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use lapce_core::buffer::Buffer;
+use lapce_rpc::file::{FileLocation, FileSelection};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use url::Url;
+
+/// The urls opened as documents rather than resolved as paths
+static URL_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?i)(?:https?|file)://").unwrap());
+
+/// A file followed by the lines to select in it, like `src/main.rs:10-20`
+static LINE_RANGE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(.+):(\d+)-(\d+)$").unwrap());
+
+/// A file followed by a line and the offset of the cursor in it, like
+/// `src/main.rs:10+4`. Anything after the `+` is matched so that an offset that
+/// isn't a number is reported rather than taken as part of the file name
+static LINE_OFFSET_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(.+):(\d+)\+(.*)$").unwrap());
+
+/// A file followed by a line and a column, like `src/main.rs:10:5`
+static LINE_COLUMN_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(.+):(\d+):(\d+)$").unwrap());
+
+/// A file followed by a line, like `src/main.rs:10`
+static LINE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.+):(\d+)$").unwrap());
+
+/// A path given on the command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathObject {
+    File(FileLocation),
+    Directory(PathBuf),
+    /// A document at an http or https url, it's read through the proxy into a
+    /// scratch buffer since it can't be saved back to the url
+    Url(Url),
+}
+
+/// Split the paths into the folders, the files and the urls to open
+pub fn split_path_objects(
+    paths: Vec<PathObject>,
+) -> (Vec<PathBuf>, Vec<FileLocation>, Vec<Url>) {
+    let mut folders = Vec::new();
+    let mut files = Vec::new();
+    let mut urls = Vec::new();
+    for path in paths {
+        match path {
+            PathObject::File(file) => files.push(file),
+            PathObject::Directory(dir) => folders.push(dir),
+            PathObject::Url(url) => urls.push(url),
+        }
+    }
+    (folders, files, urls)
+}
+
+/// The urls sent to open along with the paths, the ones that aren't valid are
+/// skipped
+pub fn parse_urls(urls: &[String]) -> Vec<Url> {
+    urls.iter().filter_map(|url| Url::parse(url).ok()).collect()
+}
+
+/// Make the cli paths absolute, the current working directory is only looked up
+/// when there is a relative path to resolve against it. The globs the shell
+/// didn't expand are expanded into the files and directories they match
+pub fn resolve_paths(
+    paths: &[PathBuf],
+    current_dir: impl FnOnce() -> io::Result<PathBuf>,
+) -> Result<Vec<PathObject>> {
+    let pwd = if paths
+        .iter()
+        .any(|p| p.is_relative() && url_value(p).is_none())
+    {
+        let pwd = current_dir().map_err(|e| {
+            anyhow!("could not determine the current working directory: {e}")
+        })?;
+        Some(pwd)
+    } else {
+        None
+    };
+
+    let mut resolved = Vec::new();
+    for path in paths {
+        if let Some(url) = url_value(path) {
+            resolved.push(resolve_url(url)?);
+            continue;
+        }
+        let path = match pwd.as_ref() {
+            Some(pwd) => pwd.join(path),
+            None => path.to_path_buf(),
+        };
+        if !path.exists() && is_glob(&path) {
+            for path in expand_glob(&path)? {
+                resolved.push(resolve_path(&path)?);
+            }
+        } else {
+            resolved.push(resolve_path(&path)?);
+        }
+    }
+    Ok(resolved)
+}
+
+fn url_value(path: &Path) -> Option<&str> {
+    path.to_str().filter(|value| URL_REGEX.is_match(value))
+}
+
+/// The url isn't canonicalized, except for a `file` url which is opened as the
+/// path it points to
+fn resolve_url(value: &str) -> Result<PathObject> {
+    let url = Url::parse(value).map_err(|e| anyhow!("invalid url {value}: {e}"))?;
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| anyhow!("invalid url {value}: it isn't a local path"))?;
+        return resolve_path(&path);
+    }
+    Ok(PathObject::Url(url))
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_str()
+        .map(|p| p.contains(|c| matches!(c, '*' | '?' | '[')))
+        .unwrap_or(false)
+}
+
+/// The files and directories matched by the glob, sorted by their path. Only
+/// the directory before the first component with a glob in it is searched
+fn expand_glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let mut base = PathBuf::new();
+    let mut globbed = Vec::new();
+    for component in pattern.components() {
+        let part = Path::new(component.as_os_str());
+        if globbed.is_empty() && !is_glob(part) {
+            base.push(component);
+        } else {
+            globbed.push(part.to_str().unwrap_or_default().to_string());
+        }
+    }
+
+    let glob = globbed.join("/");
+    let matcher = GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| anyhow!("invalid pattern {}: {e}", pattern.display()))?
+        .compile_matcher();
+    // Without `**` the matches can't be deeper than the pattern
+    let depth = if glob.contains("**") {
+        None
+    } else {
+        Some(globbed.len())
+    };
+
+    let mut matched = Vec::new();
+    walk_glob(&base, &base, depth, &matcher, &mut matched);
+    if matched.is_empty() {
+        return Err(anyhow!("no files matched pattern {}", pattern.display()));
+    }
+    matched.sort();
+    Ok(matched)
+}
+
+fn walk_glob(
+    base: &Path,
+    dir: &Path,
+    depth: Option<usize>,
+    matcher: &GlobMatcher,
+    matched: &mut Vec<PathBuf>,
+) {
+    if depth == Some(0) {
+        return;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path
+            .strip_prefix(base)
+            .map(|relative| matcher.is_match(relative))
+            .unwrap_or(false)
+        {
+            matched.push(path.clone());
+        }
+        // The symlinks aren't followed so that a link to a parent isn't walked
+        // forever
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            walk_glob(base, &path, depth.map(|d| d - 1), matcher, matched);
+        }
+    }
+}
+
+fn resolve_path(path: &Path) -> Result<PathObject> {
+    // A file whose name looks like it ends with a position is opened as is
+    let (path, selection) = match path.canonicalize() {
+        Ok(path) => (path, None),
+        Err(e) => {
+            let (file, selection) = match split_selection(path)? {
+                Some(split) => split,
+                None => {
+                    return Err(anyhow!("could not open {}: {e}", path.display()))
+                }
+            };
+            let file = file
+                .canonicalize()
+                .map_err(|e| anyhow!("could not open {}: {e}", path.display()))?;
+            (file, Some(selection))
+        }
+    };
+
+    if path.is_dir() {
+        Ok(PathObject::Directory(path))
+    } else {
+        Ok(PathObject::File(FileLocation { path, selection }))
+    }
+}
+
+/// Split the position or the lines to select off the end of the path, the
+/// lines and columns are written from 1 and an inverted range is turned around.
+/// The offset after a `+` is the number of characters before the cursor in its
+/// line
+fn split_selection(path: &Path) -> Result<Option<(PathBuf, FileSelection)>> {
+    let value = match path.to_str() {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let number = |s: &str| s.parse::<usize>().ok().map(|n| n.saturating_sub(1));
+
+    let split = if let Some(caps) = LINE_RANGE_REGEX.captures(value) {
+        number(&caps[2]).zip(number(&caps[3])).map(|(first, last)| {
+            let selection = FileSelection::Lines {
+                start: first.min(last),
+                end: first.max(last),
+            };
+            (PathBuf::from(&caps[1]), selection)
+        })
+    } else if let Some(caps) = LINE_OFFSET_REGEX.captures(value) {
+        let column = caps[3].parse::<usize>().map_err(|_| {
+            anyhow!(
+                "invalid column offset `{}` in {value}, expected a number from 0",
+                &caps[3]
+            )
+        })?;
+        number(&caps[2]).map(|line| {
+            (
+                PathBuf::from(&caps[1]),
+                FileSelection::Position { line, column },
+            )
+        })
+    } else if let Some(caps) = LINE_COLUMN_REGEX.captures(value) {
+        number(&caps[2])
+            .zip(number(&caps[3]))
+            .map(|(line, column)| {
+                (
+                    PathBuf::from(&caps[1]),
+                    FileSelection::Position { line, column },
+                )
+            })
+    } else if let Some(caps) = LINE_REGEX.captures(value) {
+        number(&caps[2]).map(|line| {
+            let selection = FileSelection::Position { line, column: 0 };
+            (PathBuf::from(&caps[1]), selection)
+        })
+    } else {
+        None
+    };
+    Ok(split)
+}
+
+/// The region of the buffer the selection spans, the lines past the end of the
+/// buffer are clamped to it, and the columns past the end of the line to its end
+pub fn selection_region(
+    buffer: &Buffer,
+    selection: &FileSelection,
+) -> (usize, usize) {
+    match selection {
+        FileSelection::Lines { start, end } => (
+            buffer.offset_of_line(*start),
+            buffer.offset_of_line(end + 1),
+        ),
+        FileSelection::Position { line, column } => {
+            let line_start = buffer.offset_of_line(*line);
+            let line_end = buffer.line_end_offset(*line, true);
+            let offset = buffer
+                .slice_to_cow(line_start..line_end)
+                .char_indices()
+                .nth(*column)
+                .map(|(i, _)| line_start + i)
+                .unwrap_or(line_end);
+            (offset, offset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io, path::PathBuf};
+
+    use lapce_core::buffer::Buffer;
+    use lapce_rpc::file::{FileLocation, FileSelection};
+    use url::Url;
+
+    use super::{resolve_paths, selection_region, split_selection, PathObject};
+
+    #[test]
+    fn test_resolve_paths_in_deleted_cwd() {
+        let cwd = std::env::temp_dir()
+            .join(format!("lapce-deleted-cwd-{}", std::process::id()));
+        fs::create_dir_all(&cwd).unwrap();
+        fs::write(cwd.join("file.txt"), "").unwrap();
+        let absolute = cwd.join("file.txt").canonicalize().unwrap();
+        fs::remove_dir_all(&cwd).unwrap();
+
+        // The directory is gone by the time the relative path is resolved
+        let err = resolve_paths(&[PathBuf::from("file.txt")], || Ok(cwd.clone()))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("could not open "));
+
+        // The working directory itself can't be looked up
+        let err = resolve_paths(&[PathBuf::from("file.txt")], || {
+            Err(io::ErrorKind::NotFound.into())
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("could not determine the current working directory"));
+
+        // It isn't needed for absolute paths, which are still reported
+        let err = resolve_paths(&[absolute.clone()], || {
+            Err(io::ErrorKind::NotFound.into())
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("could not open {}", absolute.display())));
+    }
+
+    #[test]
+    fn test_resolve_line_ranges() {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-line-ranges-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let paths = resolve_paths(
+            &[
+                PathBuf::from("main.rs:10-20"),
+                PathBuf::from("main.rs:20-10"),
+                PathBuf::from("."),
+            ],
+            || Ok(dir.clone()),
+        )
+        .unwrap();
+        let lines = |start, end| {
+            PathObject::File(FileLocation {
+                path: dir.join("main.rs"),
+                selection: Some(FileSelection::Lines { start, end }),
+            })
+        };
+        assert_eq!(
+            paths,
+            vec![
+                lines(9, 19),
+                lines(9, 19),
+                PathObject::Directory(dir.clone()),
+            ]
+        );
+
+        let err =
+            resolve_paths(&[PathBuf::from("missing.rs:1-2")], || Ok(dir.clone()))
+                .unwrap_err();
+        assert!(err.to_string().contains("missing.rs:1-2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_file_named_like_a_line_range() {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-named-like-range-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes:1-2"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        // The file is opened rather than the range split off its name
+        let paths = resolve_paths(&[PathBuf::from("notes:1-2")], || Ok(dir.clone()))
+            .unwrap();
+        assert_eq!(paths, vec![PathObject::File(dir.join("notes:1-2").into())]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_globs() {
+        let dir =
+            std::env::temp_dir().join(format!("lapce-globs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/nested/mod.rs"), "").unwrap();
+        fs::write(dir.join("src/notes.txt"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file = |path: &str| PathObject::File(dir.join(path).into());
+
+        let paths =
+            resolve_paths(&[PathBuf::from("src/**/*.rs")], || Ok(dir.clone()))
+                .unwrap();
+        assert_eq!(paths, vec![file("src/main.rs"), file("src/nested/mod.rs")]);
+
+        // A single `*` doesn't cross directories, and matches them too
+        let paths =
+            resolve_paths(&[PathBuf::from("src/*")], || Ok(dir.clone())).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                file("src/main.rs"),
+                PathObject::Directory(dir.join("src/nested")),
+                file("src/notes.txt"),
+            ]
+        );
+
+        let err = resolve_paths(&[PathBuf::from("src/*.md")], || Ok(dir.clone()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "no files matched pattern {}",
+                dir.join("src/*.md").display()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let dir =
+            std::env::temp_dir().join(format!("lapce-urls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let file_url = Url::from_file_path(dir.join("main.rs")).unwrap();
+
+        // The working directory isn't needed for urls
+        let paths = resolve_paths(
+            &[
+                PathBuf::from("https://example.com/notes.md"),
+                PathBuf::from(file_url.as_str()),
+            ],
+            || Err(io::ErrorKind::NotFound.into()),
+        )
+        .unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathObject::Url(Url::parse("https://example.com/notes.md").unwrap()),
+                PathObject::File(dir.join("main.rs").into()),
+            ]
+        );
+
+        let err = resolve_paths(&[PathBuf::from("http://[::1")], || Ok(dir.clone()))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("invalid url http://[::1: "));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_selection() {
+        let split = |path: &str| split_selection(&PathBuf::from(path)).unwrap();
+        let position = |path: &str, line, column| {
+            Some((
+                PathBuf::from(path),
+                FileSelection::Position { line, column },
+            ))
+        };
+        assert_eq!(split("main.rs"), None);
+        assert_eq!(
+            split("C:\\src\\main.rs:0-3"),
+            Some((
+                PathBuf::from("C:\\src\\main.rs"),
+                FileSelection::Lines { start: 0, end: 2 }
+            ))
+        );
+        assert_eq!(split("main.rs:10"), position("main.rs", 9, 0));
+        assert_eq!(split("main.rs:10:5"), position("main.rs", 9, 4));
+        assert_eq!(split("main.rs:10+4"), position("main.rs", 9, 4));
+        // An offset of 0 is the first column
+        assert_eq!(split("main.rs:10+0"), position("main.rs", 9, 0));
+
+        for offset in ["-3", "four", ""] {
+            let err =
+                split_selection(&PathBuf::from(format!("main.rs:10+{offset}")))
+                    .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "invalid column offset `{offset}` in main.rs:10+{offset}, \
+                     expected a number from 0"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_selection_region() {
+        let buffer = Buffer::new("a\nbb\nccc\n");
+        let region = |start, end| {
+            selection_region(&buffer, &FileSelection::Lines { start, end })
+        };
+        assert_eq!(region(1, 1), (2, 5));
+        assert_eq!(region(0, 2), (0, 9));
+        // The end past the buffer is clamped to its end
+        assert_eq!(region(1, 40), (2, 9));
+        assert_eq!(region(40, 50), (9, 9));
+
+        let position = |line, column| {
+            selection_region(&buffer, &FileSelection::Position { line, column })
+        };
+        assert_eq!(position(2, 1), (6, 6));
+        // The column past the end of the line is clamped to it
+        assert_eq!(position(1, 10), (4, 4));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, fmt, path::PathBuf, sync::Arc};
 
 use anyhow::Result;
 use druid::{
@@ -8,56 +8,81 @@
 use lapce_core::{
     buffer::DiffLines,
     command::{
-        EditCommand, FocusCommand, MotionModeCommand, MoveCommand,
-        MultiSelectionCommand,
+        EditCommand, FocusCommand, FoldCommand, MotionModeCommand, MoveCommand,
+        MultiSelectionCommand, SelectionRangeCommand, SignatureCommand,
+        SourceControlCommand,
     },
+    line_ending::LineEnding,
+    symbol::SymbolScopes,
     syntax::Syntax,
+    whitespace::WhitespaceCleanup,
 };
 use lapce_rpc::{
     buffer::BufferId,
-    file::FileNodeItem,
-    plugin::{PluginId, VoltID, VoltInfo, VoltMetadata},
-    source_control::DiffInfo,
+    file::{FileLocation, FileNodeItem, FileOperation},
+    plugin::{
+        PluginDownloadProgress, PluginId, VoltCapabilities, VoltID, VoltInfo,
+        VoltMetadata,
+    },
+    proxy::PluginPaletteProvider,
+    source_control::{BlameHunk, DiffHunk, DiffInfo},
     style::Style,
     terminal::TermId,
 };
 use lapce_xi_rope::{spans::Spans, Rope};
 use lsp_types::{
-    CodeActionOrCommand, CodeActionResponse, CompletionItem, CompletionResponse,
-    InlayHint, Location, MessageType, Position, ProgressParams,
-    PublishDiagnosticsParams, SelectionRange, SignatureHelp, TextEdit, Url,
+    CodeActionOrCommand, CodeActionResponse, Color, ColorPresentation,
+    CompletionItem, CompletionResponse, DiagnosticSeverity, Documentation,
+    FoldingRange, InlayHint, Location, MessageType, Position, ProgressParams,
+    PublishDiagnosticsParams, Range, SelectionRange, SignatureHelp, TextEdit, Url,
     WorkspaceEdit,
 };
 use serde_json::Value;
 use strum::{self, EnumMessage, IntoEnumIterator};
 use strum_macros::{Display, EnumIter, EnumMessage, EnumString, IntoStaticStr};
+use thiserror::Error;
 
 use crate::{
     alert::AlertContentData,
+    config::MacroConfig,
     data::{
         EditorTabChild, LapceMainSplitData, LapceTabData, LapceWorkspace,
         SplitContent,
     },
     document::BufferContent,
     editor::{EditorLocation, EditorPosition, Line, LineCol},
+    format::FormattingError,
     images,
     keypress::{KeyMap, KeyPress},
     markdown::Content,
     menu::MenuKind,
     palette::{PaletteItem, PaletteType},
-    plugin::{PluginsInfo, VoltIconKind},
-    proxy::ProxyStatus,
+    panel::{PanelKind, PanelPosition},
+    peek::PeekEntry,
+    plugin::{PluginCommand, PluginsInfo, VoltIconKind},
+    problem::ProblemGrouping,
+    proxy::{ProxyStatus, WorkspaceEditReply},
     search::Match,
     selection_range::SelectionRangeDirection,
     settings::LapceSettingsKind,
-    split::{SplitDirection, SplitMoveDirection},
-    update::ReleaseInfo,
+    split::{SplitCommand, SplitDirection, SplitMoveDirection},
+    update::{ReleaseInfo, UpdateStatus},
 };
 
 pub const LAPCE_OPEN_FOLDER: Selector<FileInfo> = Selector::new("lapce.open-folder");
 pub const LAPCE_OPEN_FILE: Selector<FileInfo> = Selector::new("lapce.open-file");
 pub const LAPCE_SAVE_FILE_AS: Selector<FileInfo> =
     Selector::new("lapce.save-file-as");
+pub const LAPCE_IMPORT_KEYMAPS: Selector<FileInfo> =
+    Selector::new("lapce.import-keymaps");
+pub const LAPCE_EXPORT_STATE: Selector<FileInfo> =
+    Selector::new("lapce.export-state");
+pub const LAPCE_IMPORT_STATE: Selector<FileInfo> =
+    Selector::new("lapce.import-state");
+pub const LAPCE_IMPORT_STATE_REPLACE: Selector<FileInfo> =
+    Selector::new("lapce.import-state-replace");
+pub const LAPCE_INSTALL_PLUGIN_FROM_FILE: Selector<FileInfo> =
+    Selector::new("lapce.install-plugin-from-file");
 pub const LAPCE_COMMAND: Selector<LapceCommand> = Selector::new("lapce.new-command");
 pub const LAPCE_UI_COMMAND: Selector<LapceUICommand> =
     Selector::new("lapce.ui_command");
@@ -76,6 +101,10 @@ pub enum CommandKind {
     Focus(FocusCommand),
     MotionMode(MotionModeCommand),
     MultiSelection(MultiSelectionCommand),
+    Signature(SignatureCommand),
+    SelectionRange(SelectionRangeCommand),
+    SourceControl(SourceControlCommand),
+    Fold(FoldCommand),
 }
 
 impl CommandKind {
@@ -87,6 +116,10 @@ pub fn desc(&self) -> Option<&'static str> {
             CommandKind::Focus(cmd) => cmd.get_message(),
             CommandKind::MotionMode(cmd) => cmd.get_message(),
             CommandKind::MultiSelection(cmd) => cmd.get_message(),
+            CommandKind::Signature(cmd) => cmd.get_message(),
+            CommandKind::SelectionRange(cmd) => cmd.get_message(),
+            CommandKind::SourceControl(cmd) => cmd.get_message(),
+            CommandKind::Fold(cmd) => cmd.get_message(),
         }
     }
 
@@ -98,6 +131,10 @@ pub fn str(&self) -> &'static str {
             CommandKind::Focus(cmd) => cmd.into(),
             CommandKind::MotionMode(cmd) => cmd.into(),
             CommandKind::MultiSelection(cmd) => cmd.into(),
+            CommandKind::Signature(cmd) => cmd.into(),
+            CommandKind::SelectionRange(cmd) => cmd.into(),
+            CommandKind::SourceControl(cmd) => cmd.into(),
+            CommandKind::Fold(cmd) => cmd.into(),
         }
     }
 }
@@ -105,18 +142,34 @@ pub fn str(&self) -> &'static str {
 impl LapceCommand {
     pub const PALETTE: &'static str = "palette";
 
+    /// The command with the id, as written in the keymaps, or the error with
+    /// the ids of the commands closest to it
+    pub fn from_id_with_suggestions(id: &str) -> Result<Self, UnknownCommand> {
+        let commands = lapce_internal_commands();
+        commands.get(id).cloned().ok_or_else(|| {
+            UnknownCommand::new(id, commands.keys().map(|id| id.as_str()))
+        })
+    }
+
     pub fn is_palette_command(&self) -> bool {
         if let CommandKind::Workbench(cmd) = &self.kind {
             match cmd {
                 LapceWorkbenchCommand::Palette
                 | LapceWorkbenchCommand::PaletteLine
                 | LapceWorkbenchCommand::PaletteSymbol
+                | LapceWorkbenchCommand::PaletteAllBufferSymbols
                 | LapceWorkbenchCommand::PaletteCommand
                 | LapceWorkbenchCommand::ChangeFileLanguage
+                | LapceWorkbenchCommand::ChangeLineEnding
+                | LapceWorkbenchCommand::ReopenWithEncoding
+                | LapceWorkbenchCommand::ShowLocalHistory
                 | LapceWorkbenchCommand::ChangeColorTheme
                 | LapceWorkbenchCommand::ChangeIconTheme
+                | LapceWorkbenchCommand::ChangeSettingsProfile
                 | LapceWorkbenchCommand::ConnectSshHost
                 | LapceWorkbenchCommand::ConnectWsl
+                | LapceWorkbenchCommand::InstallPluginVersion
+                | LapceWorkbenchCommand::RenameTerminalTab
                 | LapceWorkbenchCommand::PaletteWorkspace => return true,
                 _ => {}
             }
@@ -126,6 +179,71 @@ pub fn is_palette_command(&self) -> bool {
     }
 }
 
+/// An id which isn't the one of any command, like a command renamed since
+/// the keymaps were written, with the closest ids to suggest instead
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("unknown command `{id}`{}", did_you_mean(.suggestions))]
+pub struct UnknownCommand {
+    pub id: String,
+    pub suggestions: Vec<String>,
+}
+
+impl UnknownCommand {
+    /// The number of ids suggested at most
+    const MAX_SUGGESTIONS: usize = 3;
+
+    pub fn new<'a>(id: &str, known: impl IntoIterator<Item = &'a str>) -> Self {
+        // Allow about one typo for every three characters
+        let max_distance = (id.chars().count() / 3).max(1);
+        let mut suggestions: Vec<(usize, &str)> = known
+            .into_iter()
+            .map(|known| (edit_distance(id, known), known))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        suggestions.sort();
+        Self {
+            id: id.to_string(),
+            suggestions: suggestions
+                .into_iter()
+                .take(Self::MAX_SUGGESTIONS)
+                .map(|(_, known)| known.to_string())
+                .collect(),
+        }
+    }
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [one] => format!(", did you mean `{one}`?"),
+        [rest @ .., last] => {
+            let rest = rest
+                .iter()
+                .map(|s| format!("`{s}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(", did you mean {rest} or `{last}`?")
+        }
+    }
+}
+
+/// The number of characters to insert, delete or replace to turn one string
+/// into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b.len() + 1);
+        row.push(i + 1);
+        for (j, cb) in b.iter().enumerate() {
+            let replace = prev[j] + usize::from(ca != *cb);
+            row.push(replace.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
 #[derive(PartialEq, Eq)]
 pub enum CommandExecuted {
     Yes,
@@ -183,9 +301,211 @@ pub fn lapce_internal_commands() -> IndexMap<String, LapceCommand> {
         commands.insert(c.to_string(), command);
     }
 
+    for c in SignatureCommand::iter() {
+        let command = LapceCommand {
+            kind: CommandKind::Signature(c.clone()),
+            data: None,
+        };
+        commands.insert(c.to_string(), command);
+    }
+
+    for c in SelectionRangeCommand::iter() {
+        let command = LapceCommand {
+            kind: CommandKind::SelectionRange(c.clone()),
+            data: None,
+        };
+        commands.insert(c.to_string(), command);
+    }
+
+    for c in SourceControlCommand::iter() {
+        let command = LapceCommand {
+            kind: CommandKind::SourceControl(c.clone()),
+            data: None,
+        };
+        commands.insert(c.to_string(), command);
+    }
+
+    for c in FoldCommand::iter() {
+        let command = LapceCommand {
+            kind: CommandKind::Fold(c.clone()),
+            data: None,
+        };
+        commands.insert(c.to_string(), command);
+    }
+
     commands
 }
 
+/// A command which runs a sequence of commands, defined in the settings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandMacro {
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<LapceCommand>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MacroError {
+    #[error("macro `{name}` runs an {error}")]
+    UnknownCommand { name: String, error: UnknownCommand },
+    #[error("macro `{name}` is recursive: {}", .cycle.join(" -> "))]
+    Recursive { name: String, cycle: Vec<String> },
+    #[error("step {step} of macro `{name}` failed: {error}")]
+    StepFailed {
+        name: String,
+        step: usize,
+        error: String,
+    },
+}
+
+impl CommandMacro {
+    /// The name shown in the palette
+    pub fn desc(&self) -> &str {
+        self.description.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Run the step at the index with `run_step`, returning the index of the
+    /// one to run after it, `None` after the last one
+    pub fn run_step<E, F>(
+        &self,
+        step: usize,
+        run_step: F,
+    ) -> Result<Option<usize>, MacroError>
+    where
+        E: fmt::Display,
+        F: FnOnce(&LapceCommand) -> Result<(), E>,
+    {
+        let command = match self.steps.get(step) {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        run_step(command).map_err(|error| MacroError::StepFailed {
+            name: self.name.clone(),
+            step: step + 1,
+            error: error.to_string(),
+        })?;
+        Ok(Some(step + 1).filter(|next| *next < self.steps.len()))
+    }
+}
+
+/// Where a step of a macro is run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroStepTarget {
+    /// By the tab, like the commands of the palette
+    Workbench,
+    /// By the focused widget, which has to be an editor for the edits and
+    /// the moves
+    Focus { needs_editor: bool },
+}
+
+impl MacroStepTarget {
+    /// Where the command is run as a step of a macro, or why it can't be
+    pub fn of(kind: &CommandKind) -> Result<Self, String> {
+        match kind {
+            CommandKind::Workbench(_) => Ok(MacroStepTarget::Workbench),
+            CommandKind::Edit(_) | CommandKind::Move(_) => {
+                Ok(MacroStepTarget::Focus { needs_editor: true })
+            }
+            CommandKind::Focus(_)
+            | CommandKind::SourceControl(_)
+            | CommandKind::Fold(_) => Ok(MacroStepTarget::Focus {
+                needs_editor: false,
+            }),
+            CommandKind::MotionMode(_)
+            | CommandKind::MultiSelection(_)
+            | CommandKind::Signature(_)
+            | CommandKind::SelectionRange(_) => Err(format!(
+                "`{}` can only be run from a key binding",
+                kind.str()
+            )),
+        }
+    }
+}
+
+/// The command macros of the settings, with the macros they run expanded into
+/// their steps
+#[derive(Clone, Debug, Default)]
+pub struct CommandMacros {
+    macros: IndexMap<String, CommandMacro>,
+}
+
+impl CommandMacros {
+    /// Resolve the steps of the macros to the commands. The macros which run
+    /// an unknown command, or which end up running themselves, are left out
+    /// and returned as errors.
+    pub fn new(
+        config: &HashMap<String, MacroConfig>,
+        commands: &IndexMap<String, LapceCommand>,
+    ) -> (Self, Vec<MacroError>) {
+        let mut names: Vec<&String> = config.keys().collect();
+        names.sort();
+
+        let mut macros = IndexMap::new();
+        let mut errors = Vec::new();
+        for name in names {
+            match Self::expand(name, config, commands, &mut Vec::new()) {
+                Ok(steps) => {
+                    macros.insert(
+                        name.to_string(),
+                        CommandMacro {
+                            name: name.to_string(),
+                            description: config[name].description.clone(),
+                            steps,
+                        },
+                    );
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        (Self { macros }, errors)
+    }
+
+    /// The commands run by the macro, with `stack` holding the macros being
+    /// expanded to find the ones running themselves
+    fn expand(
+        name: &str,
+        config: &HashMap<String, MacroConfig>,
+        commands: &IndexMap<String, LapceCommand>,
+        stack: &mut Vec<String>,
+    ) -> Result<Vec<LapceCommand>, MacroError> {
+        if let Some(start) = stack.iter().position(|n| n == name) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(MacroError::Recursive {
+                name: stack[0].clone(),
+                cycle,
+            });
+        }
+
+        stack.push(name.to_string());
+        let mut steps = Vec::new();
+        for step in config[name].steps.iter() {
+            if let Some(command) = commands.get(step) {
+                steps.push(command.clone());
+            } else if config.contains_key(step) {
+                steps.extend(Self::expand(step, config, commands, stack)?);
+            } else {
+                let known =
+                    commands.keys().chain(config.keys()).map(|id| id.as_str());
+                return Err(MacroError::UnknownCommand {
+                    name: name.to_string(),
+                    error: UnknownCommand::new(step, known),
+                });
+            }
+        }
+        stack.pop();
+        Ok(steps)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandMacro> {
+        self.macros.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandMacro> {
+        self.macros.values()
+    }
+}
+
 #[derive(
     Display,
     EnumString,
@@ -222,14 +542,30 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Reveal Active File in File Explorer")]
     RevealActiveFileInFileExplorer,
 
+    #[strum(serialize = "toggle_file_explorer_hidden_files")]
+    #[strum(message = "Toggle Hidden Files in File Explorer")]
+    ToggleFileExplorerHiddenFiles,
+
+    /// Apply the file operations given as the command's data
+    #[strum(serialize = "file_explorer_file_operations")]
+    FileExplorerFileOperations,
+
     #[strum(serialize = "change_color_theme")]
     #[strum(message = "Change Color Theme")]
     ChangeColorTheme,
 
+    #[strum(serialize = "cycle_color_theme")]
+    #[strum(message = "Cycle Color Theme")]
+    CycleColorTheme,
+
     #[strum(serialize = "change_icon_theme")]
     #[strum(message = "Change Icon Theme")]
     ChangeIconTheme,
 
+    #[strum(serialize = "change_settings_profile")]
+    #[strum(message = "Change Settings Profile")]
+    ChangeSettingsProfile,
+
     #[strum(serialize = "open_settings")]
     #[strum(message = "Open Settings")]
     OpenSettings,
@@ -286,6 +622,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Close Terminal Tab")]
     CloseTerminalTab,
 
+    #[strum(serialize = "rename_terminal_tab")]
+    #[strum(message = "Rename Terminal Tab")]
+    RenameTerminalTab,
+
     #[strum(serialize = "next_terminal_tab")]
     #[strum(message = "Next Terminal Tab")]
     NextTerminalTab,
@@ -294,6 +634,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Previous Terminal Tab")]
     PreviousTerminalTab,
 
+    #[strum(serialize = "clear_terminal_scrollback")]
+    #[strum(message = "Clear Terminal Scrollback")]
+    ClearTerminalScrollback,
+
     #[strum(serialize = "next_window_tab")]
     #[strum(message = "Go To Next Window Tab")]
     NextWindowTab,
@@ -343,6 +687,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.workspace_symbol")]
     PaletteWorkspaceSymbol,
 
+    #[strum(message = "Go to Symbol in Open Editors")]
+    #[strum(serialize = "palette.all_buffer_symbols")]
+    PaletteAllBufferSymbols,
+
     #[strum(message = "Command Palette")]
     #[strum(serialize = "palette.command")]
     PaletteCommand,
@@ -363,6 +711,19 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "show_panel")]
     ShowPanel,
 
+    /// Move the panel passed in parameter, or the focused one, to the left
+    #[strum(message = "Move Panel to the Left")]
+    #[strum(serialize = "move_panel_left")]
+    MovePanelLeft,
+
+    #[strum(message = "Move Panel to the Right")]
+    #[strum(serialize = "move_panel_right")]
+    MovePanelRight,
+
+    #[strum(message = "Move Panel to the Bottom")]
+    #[strum(serialize = "move_panel_bottom")]
+    MovePanelBottom,
+
     /// Toggles the panel passed in parameter.
     #[strum(serialize = "toggle_panel_focus")]
     TogglePanelFocus,
@@ -423,6 +784,37 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_visual")]
     ToggleSearchVisual,
 
+    #[strum(serialize = "toggle_rename_preview_visual")]
+    ToggleRenamePreviewVisual,
+
+    #[strum(message = "Apply Rename Preview")]
+    #[strum(serialize = "apply_rename_preview")]
+    ApplyRenamePreview,
+
+    #[strum(message = "Discard Rename Preview")]
+    #[strum(serialize = "discard_rename_preview")]
+    DiscardRenamePreview,
+
+    /// Run the code action of a diagnostic of the problem panel given as the
+    /// command's data
+    #[strum(serialize = "run_problem_code_action")]
+    RunProblemCodeAction,
+
+    /// Apply the presentation of a document color chosen in its menu, given as
+    /// the command's data
+    #[strum(serialize = "apply_color_presentation")]
+    ApplyColorPresentation,
+
+    /// Reload the open file given as the command's data with the content it
+    /// has on disk, after it changed outside of Lapce
+    #[strum(serialize = "reload_from_disk")]
+    ReloadFromDisk,
+
+    /// Keep the content of the open file given as the command's data, rather
+    /// than the one it has on disk after it changed outside of Lapce
+    #[strum(serialize = "keep_local_version")]
+    KeepLocalVersion,
+
     #[strum(serialize = "focus_editor")]
     FocusEditor,
 
@@ -459,10 +851,54 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Install current theme file")]
     InstallTheme,
 
+    #[strum(serialize = "export_keymaps")]
+    #[strum(message = "Export Keyboard Shortcuts")]
+    ExportKeymaps,
+
+    #[strum(serialize = "import_keymaps")]
+    #[strum(message = "Import Keyboard Shortcuts")]
+    ImportKeymaps,
+
+    #[strum(serialize = "export_state")]
+    #[strum(message = "Export Workspaces State")]
+    ExportState,
+
+    #[strum(serialize = "import_state")]
+    #[strum(message = "Import Workspaces State (Merge)")]
+    ImportState,
+
+    #[strum(serialize = "import_state_replace")]
+    #[strum(message = "Import Workspaces State (Replace)")]
+    ImportStateReplace,
+
+    #[strum(serialize = "install_plugin_version")]
+    #[strum(message = "Install Plugin Version")]
+    InstallPluginVersion,
+
+    #[strum(serialize = "install_plugin_from_file")]
+    #[strum(message = "Install Plugin from File")]
+    InstallPluginFromFile,
+
     #[strum(serialize = "change_file_language")]
     #[strum(message = "Change current file language")]
     ChangeFileLanguage,
 
+    #[strum(serialize = "toggle_large_file_mode")]
+    #[strum(message = "Toggle Large File Mode for current file")]
+    ToggleLargeFileMode,
+
+    #[strum(serialize = "change_line_ending")]
+    #[strum(message = "Change current file line ending")]
+    ChangeLineEnding,
+
+    #[strum(serialize = "reopen_with_encoding")]
+    #[strum(message = "Reopen current file with Encoding")]
+    ReopenWithEncoding,
+
+    #[strum(serialize = "show_local_history")]
+    #[strum(message = "Show Local History of Current File")]
+    ShowLocalHistory,
+
     #[strum(serialize = "next_editor_tab")]
     #[strum(message = "Next editor tab")]
     NextEditorTab,
@@ -471,17 +907,55 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Previous editor tab")]
     PreviousEditorTab,
 
+    /// Run the command macro named by the data
+    #[strum(serialize = "run_macro")]
+    RunMacro,
+
+    #[strum(serialize = "pin_tab")]
+    #[strum(message = "Pin Tab")]
+    PinTab,
+
+    #[strum(serialize = "unpin_tab")]
+    #[strum(message = "Unpin Tab")]
+    UnpinTab,
+
     #[strum(serialize = "toggle_inlay_hints")]
     #[strum(message = "Toggle Inlay Hints")]
     ToggleInlayHints,
 
+    /// Show the release notes of the latest release, before installing it
     #[strum(serialize = "restart_to_update")]
     RestartToUpdate,
 
+    #[strum(serialize = "install_update")]
+    InstallUpdate,
+
+    #[strum(serialize = "rollback_update")]
+    #[strum(message = "Rollback to Previous Version")]
+    RollbackUpdate,
+
+    #[strum(serialize = "open_link")]
+    OpenLink,
+
+    /// Run each line of the text in the active terminal, once the user agreed
+    /// to it since its shell can't take them at once
+    #[strum(serialize = "run_text_line_by_line")]
+    RunTextLineByLine,
+
+    /// Remember the answer of the user to the capabilities a volt asked for,
+    /// and start it with those granted
+    #[strum(serialize = "set_volt_permissions")]
+    SetVoltPermissions,
+
     #[strum(serialize = "show_about")]
     #[strum(message = "About Lapce")]
     ShowAbout,
 
+    /// Copy the versions, the plugins and the end of the log, for a bug report
+    #[strum(serialize = "copy_diagnostics")]
+    #[strum(message = "Copy Diagnostics")]
+    CopyDiagnostics,
+
     #[strum(message = "Save All Files")]
     #[strum(serialize = "save_all")]
     SaveAll,
@@ -535,6 +1009,11 @@ pub enum LapceUICommand {
         path: PathBuf,
         content: Rope,
     },
+    /// Informs the editor that the file has been deleted.
+    /// (Sent by the proxy on certain watcher events)
+    OpenFileDeleted {
+        path: PathBuf,
+    },
     ReloadBuffer {
         path: PathBuf,
         rev: u64,
@@ -568,9 +1047,12 @@ pub enum LapceUICommand {
         // If present, will update the case-sensitivity
         Option<bool>,
     ),
-    /// Informs the editor of the results from the global search, this is caused by the
-    /// `UpdateSearch{,WithCaseSensitivity}` commands
-    GlobalSearchResult(String, Arc<IndexMap<PathBuf, Vec<Match>>>),
+    /// Informs the editor of a batch of results from the global search, this is caused
+    /// by the `UpdateSearch{,WithCaseSensitivity}` commands
+    GlobalSearchResult {
+        search_id: u64,
+        matches: Arc<IndexMap<PathBuf, Vec<Match>>>,
+    },
     CancelFilePicker,
     /// Change the workspace to the given path/remote (or clear it)
     SetWorkspace(LapceWorkspace),
@@ -584,6 +1066,9 @@ pub enum LapceUICommand {
         /// Whether the changes are temporary, and thus whether we should update the config file
         preview: bool,
     },
+    /// Switch the workspace to the settings profile, or back to the base
+    /// settings
+    SetSettingsProfile(Option<String>),
     UpdateKeymap(KeyMap, Vec<KeyPress>),
     /// Open the URI in the respective program, such as urls for the browser, or paths in the file
     /// explorer
@@ -592,23 +1077,53 @@ pub enum LapceUICommand {
     OpenPaths {
         window_tab_id: Option<(WindowId, WidgetId)>,
         folders: Vec<PathBuf>,
-        files: Vec<PathBuf>,
+        files: Vec<FileLocation>,
+        urls: Vec<Url>,
     },
     /// Open a specific file in the editor; along with `same_tab` which decides which tabs to look
     /// at for whether the file is already open.
     OpenFile(PathBuf, bool),
+    /// Open a file given on the command line, selecting the part of it it was
+    /// given with
+    OpenFileLocation(FileLocation),
+    /// Read the document at the url through the proxy into a scratch buffer
+    OpenUrlDocument(Url),
+    UrlDocumentLoaded {
+        url: Url,
+        content: String,
+    },
     /// Open a specific file in the editor as a source control diff view
     OpenFileDiff {
         path: PathBuf,
         /// Ex: "head"
         history: String,
     },
+    /// Sent after the file was edited, for a local history snapshot of it to be
+    /// taken once the edits settled
+    LocalHistorySnapshot {
+        path: PathBuf,
+    },
+    /// Show the diff of the file with its local history snapshot in the editor
+    PreviewLocalHistory {
+        editor_id: WidgetId,
+        path: PathBuf,
+        time: u64,
+    },
+    /// Replace the content of the file with its local history snapshot
+    RestoreLocalHistory {
+        path: PathBuf,
+        time: u64,
+    },
     /// Shows a specific file in the user's file explorer
     RevealInFileExplorer(PathBuf),
     /// Cancel the completion request
     CancelCompletion {
         request_id: usize,
     },
+    /// Sent once a completion request waited for its debounce time
+    SendCompletionRequest {
+        request_id: usize,
+    },
     /// Receieved when the request for completion items has completed
     UpdateCompletion {
         request_id: usize,
@@ -624,6 +1139,14 @@ pub enum LapceUICommand {
         offset: usize,
         item: Box<CompletionItem>,
     },
+    /// Received when the highlighted completion item has been resolved for its
+    /// documentation, which is `None` if the resolve gave none.
+    UpdateCompletionDocumentation {
+        session_id: usize,
+        plugin_id: PluginId,
+        label: String,
+        documentation: Option<Documentation>,
+    },
     /// Completion 'internal' event that indicates that it should recompute the layouts for
     /// the completion documentation.
     RefreshCompletionDocumentation,
@@ -643,11 +1166,25 @@ pub enum LapceUICommand {
     },
     /// Received when the request for the plugin's description completed
     UpdateVoltReadme(Arc<Vec<Content>>),
+    /// Received when the inlay hints of the lines of the file were fetched
     UpdateInlayHints {
         path: PathBuf,
         rev: u64,
+        lines: std::ops::Range<usize>,
         hints: Spans<InlayHint>,
     },
+    /// Received when the colors of the file were fetched, for their swatches
+    UpdateDocumentColors {
+        path: PathBuf,
+        rev: u64,
+        colors: Spans<Color>,
+    },
+    /// Received when the document symbols of the file were fetched, for its sticky headers
+    UpdateSymbolScopes {
+        path: PathBuf,
+        rev: u64,
+        scopes: Arc<SymbolScopes>,
+    },
     /// Received when the request for code actions in the file completed
     UpdateCodeActions {
         path: PathBuf,
@@ -656,6 +1193,12 @@ pub enum LapceUICommand {
         offset: usize,
         resp: CodeActionResponse,
     },
+    /// Received when the git blame of the file was fetched
+    UpdateBlame {
+        path: PathBuf,
+        rev: u64,
+        hunks: Vec<BlameHunk>,
+    },
     /// Received when there was an error in getting code actions
     CodeActionsError {
         path: PathBuf,
@@ -666,8 +1209,39 @@ pub enum LapceUICommand {
     RunCommand(String, Vec<String>),
     /// Execute a code action from a specific plugin
     RunCodeAction(CodeActionOrCommand, PluginId),
-    /// Apply a workspace edit, which comes from an LSP
-    ApplyWorkspaceEdit(WorkspaceEdit),
+    /// Show the code actions of a diagnostic of the problem panel in a context
+    /// menu at the point in the window
+    ShowProblemCodeActions {
+        point: Point,
+        path: PathBuf,
+        plugin_id: PluginId,
+        resp: CodeActionResponse,
+    },
+    /// Open the color picker of the document color at the range, at the point
+    /// where its swatch was clicked
+    ShowColorPicker {
+        point: Point,
+        path: PathBuf,
+        range: Range,
+        color: lsp_types::Color,
+    },
+    /// Show the ways the LSP can write the picked color, in a menu at the point
+    /// where its swatch was clicked
+    ShowColorPresentations {
+        point: Point,
+        path: PathBuf,
+        range: Range,
+        presentations: Vec<ColorPresentation>,
+    },
+    /// Apply a workspace edit, which comes from an LSP, answering the plugin
+    /// waiting for it once it's applied
+    ApplyWorkspaceEdit(WorkspaceEdit, Option<Arc<WorkspaceEditReply>>),
+    /// Show the edits of a rename in the rename preview panel before applying
+    /// them
+    ShowRenamePreview {
+        new_name: String,
+        edit: WorkspaceEdit,
+    },
     /// Display a list of the current code actions at the given point
     ShowCodeActions(Option<Point>),
     /// Sets the information about the latest Lapce release
@@ -681,6 +1255,11 @@ pub enum LapceUICommand {
     /// Inform that the widget that their children have changed, typically so that they can call
     /// `ctx.children_changed()` on the next `event`
     ChildrenChanged,
+    /// Move the panel after the panels at the position, saving the new order
+    MovePanel {
+        kind: PanelKind,
+        position: PanelPosition,
+    },
     /// Changes the active file in the explorer panel to the current file
     EnsureEditorTabActiveVisible,
     /// Displays the (core) settings in the settings view
@@ -703,6 +1282,21 @@ pub enum LapceUICommand {
         run_id: String,
         items: im::Vector<PaletteItem>,
     },
+    /// Request the items of a plugin palette, once the input stopped changing
+    RequestPluginPaletteItems {
+        run_id: String,
+    },
+    /// Sent when a plugin took too long to reply with the items of its palette
+    PluginPaletteItemsTimeout {
+        run_id: String,
+    },
+    /// A plugin registered a palette
+    RegisterPluginPaletteProvider {
+        plugin_id: PluginId,
+        provider: PluginPaletteProvider,
+    },
+    /// The plugin was stopped or reloaded, so its registrations are stale
+    PluginServerStopped(PluginId),
     /// Event received to set the palette's items after they were filtered
     FilterPaletteItems {
         run_id: String,
@@ -711,8 +1305,7 @@ pub enum LapceUICommand {
     },
     /// Set the filter for the keymaps (in the settings), updating which keymaps are shown
     UpdateKeymapsFilter(String),
-    /// Reset a specific settings item to its default value by sending it to the widget  
-    /// (note: only handled by theme settings items currently)
+    /// Reset a specific settings item to its default value by sending it to the widget
     ResetSettingsItem,
     /// Reset a specific settings item to its default value by the path to it
     ResetSettingsFile {
@@ -725,8 +1318,7 @@ pub enum LapceUICommand {
         key: String,
         value: Value,
     },
-    /// Update the filter for the settings
-    /// TODO: currently unused!
+    /// Update the filter for the settings, which shows the settings matching it
     UpdateSettingsFilter(String),
     FilterKeymaps {
         pattern: String,
@@ -737,6 +1329,9 @@ pub enum LapceUICommand {
     },
     UpdatePickerPwd(PathBuf),
     UpdatePickerItems(PathBuf, HashMap<PathBuf, FileNodeItem>),
+    CompletePickerPath,
+    /// The entries of the directory the typed path was completed in
+    UpdatePickerCompletion(String, HashMap<PathBuf, FileNodeItem>),
     /// Event received when the directory of a folder has been read, so that we can include the new
     /// files in the explorer.
     UpdateExplorerItems {
@@ -755,6 +1350,9 @@ pub enum LapceUICommand {
     LoadPluginIcon(VoltID, VoltIconKind),
     VoltInstalled(VoltMetadata, Option<Vec<u8>>),
     VoltInstalling(VoltInfo, String),
+    /// The volt asks for these capabilities before it's started
+    VoltPermissionsRequested(VoltInfo, VoltCapabilities),
+    VoltDownloadProgress(PluginDownloadProgress),
     VoltRemoving(VoltMetadata, String),
     VoltInstallStatusClear(VoltID),
     VoltRemoved(VoltInfo, bool),
@@ -762,9 +1360,17 @@ pub enum LapceUICommand {
     DisableVolt(VoltInfo),
     EnableVoltWorkspace(VoltInfo),
     DisableVoltWorkspace(VoltInfo),
+    RunPluginCommand(PluginCommand),
+    /// Keep the volt at this version instead of offering upgrades
+    PinVolt(VoltID, String),
+    UnpinVolt(VoltID),
     RequestLayout,
     RequestPaint,
     ResetFade,
+    /// Scroll the hover by the height of its view, down or up
+    ScrollHoverPage {
+        down: bool,
+    },
     /// Close a window tab (which is distinct from the typical editor tab!)
     CloseTab,
     /// Close a window tab by its id
@@ -787,7 +1393,9 @@ pub enum LapceUICommand {
     PreviousEditorTab,
     /// Restart Lapce at the given path so that we can apply the update
     RestartToUpdate(PathBuf, ReleaseInfo),
-    UpdateStarted,
+    /// Restore the version kept by the last update and restart Lapce with it
+    RollbackUpdate(PathBuf),
+    UpdateProgress(UpdateStatus),
     UpdateFailed,
     /// Create a new Lapce window
     NewWindow(WindowId),
@@ -801,20 +1409,24 @@ pub enum LapceUICommand {
         path: PathBuf,
         rev: u64,
         /// The resulting edits
-        result: Result<Vec<TextEdit>>,
+        result: Result<Vec<TextEdit>, FormattingError>,
     },
     /// Event received when the formatting request has been completed, and the document should be
     /// saved with the given path using the edits.
     DocumentFormatAndSave {
         path: PathBuf,
         rev: u64,
-        result: Result<Vec<TextEdit>>,
+        result: Result<Vec<TextEdit>, FormattingError>,
         exit: Option<WidgetId>,
+        /// How the whitespace is cleaned up after the edits are applied
+        cleanup: WhitespaceCleanup,
     },
-    /// Save the document with the given path
+    /// Clean up the whitespace of the document with the given path and save
+    /// it
     DocumentSave {
         path: PathBuf,
         exit: Option<WidgetId>,
+        cleanup: WhitespaceCleanup,
     },
     /// Mark the document as saved/pristine, if the revision still matches
     BufferSave {
@@ -856,6 +1468,14 @@ pub enum LapceUICommand {
     /// Update the current progress information (from the proxy)
     WorkDoneProgress(ProgressParams),
     UpdateDiffInfo(DiffInfo),
+    StageHunk {
+        path: PathBuf,
+        hunk: DiffHunk,
+    },
+    UnstageHunk {
+        path: PathBuf,
+        hunk: DiffHunk,
+    },
     /// Scrolls the editor-view so that the rect is visible  
     EnsureRectVisible(Rect),
     /// Scrolls the editor-view so that the cursor is visible
@@ -891,6 +1511,12 @@ pub enum LapceUICommand {
     OpenPluginInfo(VoltInfo),
     SplitTerminal(bool, WidgetId),
     SplitTerminalClose(TermId, WidgetId),
+    /// Focus the terminal next to the one with the widget id
+    SplitTerminalMove(SplitMoveDirection, WidgetId),
+    SplitTerminalExchange(WidgetId),
+    /// Name the terminal tab with the split id, or go back to naming it after
+    /// its terminal with an empty name
+    RenameTerminalTab(WidgetId, String),
     SplitEditor(bool, WidgetId),
     SplitEditorMove(SplitMoveDirection, WidgetId),
     SplitEditorExchange(WidgetId),
@@ -898,6 +1524,8 @@ pub enum LapceUICommand {
     Split(bool),
     SplitClose,
     SplitExchange(SplitContent),
+    /// Resize the panes of the split holding the content
+    SplitResize(SplitContent, SplitCommand),
     SplitRemove(SplitContent),
     SplitMove(SplitMoveDirection),
     SplitAdd(usize, SplitContent, bool),
@@ -906,6 +1534,9 @@ pub enum LapceUICommand {
     EditorTabAdd(usize, EditorTabChild),
     EditorTabRemove(usize, bool, bool),
     EditorTabSwap(usize, usize),
+    /// Pin the tab at the index of the editor tab
+    PinTab(usize),
+    UnpinTab(usize),
     EditorContentChanged,
     JumpToPosition(Option<WidgetId>, Position, bool),
     JumpToLine(Option<WidgetId>, usize),
@@ -914,13 +1545,31 @@ pub enum LapceUICommand {
     JumpToLineLocation(Option<WidgetId>, EditorLocation<Line>),
     JumpToLineColLocation(Option<WidgetId>, EditorLocation<LineCol>, bool),
     ToggleProblem(PathBuf),
+    SetProblemGrouping(ProblemGrouping),
+    SetProblemMinSeverity(DiagnosticSeverity),
+    ToggleProblemSource(String),
     TerminalJumpToLine(i32),
+    /// Run the text in the active terminal, as if it was pasted and followed by
+    /// enter
+    TerminalRunText(String),
+    /// Run the step of the macro, once the ones before it were handled
+    RunMacroStep {
+        name: String,
+        step: usize,
+    },
     GoToLocation(Option<WidgetId>, EditorLocation, bool),
     GotoDefinition {
         editor_view_id: WidgetId,
         offset: usize,
         location: EditorLocation<Position>,
     },
+    /// Show the definitions found at the offset of the editor in the peek
+    /// popup
+    ShowPeek {
+        editor_view_id: WidgetId,
+        offset: usize,
+        entries: Vec<PeekEntry>,
+    },
     PrepareRename {
         path: PathBuf,
         rev: u64,
@@ -956,6 +1605,28 @@ pub enum LapceUICommand {
     TrashPath {
         path: PathBuf,
     },
+    /// Put the paths in the explorer's clipboard, to be moved by the next paste
+    ExplorerCut {
+        paths: Vec<PathBuf>,
+    },
+    /// Put the paths in the explorer's clipboard, to be copied by the next paste
+    ExplorerCopy {
+        paths: Vec<PathBuf>,
+    },
+    /// Move or copy the paths of the explorer's clipboard into the directory
+    ExplorerPaste {
+        dir: PathBuf,
+    },
+    /// Apply the operations as a whole and reload the explorer, asking to
+    /// permanently delete the paths that couldn't be moved to the trash
+    ExplorerFileOperations {
+        operations: Vec<FileOperation>,
+    },
+    /// Point the open documents at the files' new paths after the paths were
+    /// moved, the moves being `(from, to)` pairs
+    ExplorerPathsMoved {
+        moves: Vec<(PathBuf, PathBuf)>,
+    },
     /// Start duplicating a specific file in view at the given index
     ExplorerStartDuplicate {
         /// The index into the explorer's file listing
@@ -999,6 +1670,17 @@ pub enum LapceUICommand {
     CopyPath(PathBuf),
     CopyRelativePath(PathBuf),
     SetLanguage(String),
+    /// Convert the line endings of the active file
+    ConvertLineEndings(LineEnding),
+    /// Reopen the active file with the encoding
+    ReopenWithEncoding(String),
+    /// The file was read again with the encoding, for the document at the revision
+    ReopenedWithEncoding {
+        path: PathBuf,
+        rev: u64,
+        content: Rope,
+        encoding: String,
+    },
     ApplySelectionRange {
         buffer_id: BufferId,
         rev: u64,
@@ -1011,6 +1693,22 @@ pub enum LapceUICommand {
         ranges: Vec<SelectionRange>,
         direction: SelectionRangeDirection,
     },
+    /// The LSP selection ranges around `origin`, none if there's no LSP to ask,
+    /// for the expand selection command that asked for them
+    StoreSelectionRangeStack {
+        buffer_id: BufferId,
+        rev: u64,
+        origin: (usize, usize),
+        ranges: Option<SelectionRange>,
+    },
+    /// The LSP folding ranges of a document, none if there's no LSP to ask, for
+    /// the fold command that asked for them
+    StoreFoldingRanges {
+        buffer_id: BufferId,
+        rev: u64,
+        ranges: Option<Vec<FoldingRange>>,
+        cmd: FoldCommand,
+    },
 
     /// An item in a list was chosen
     /// This is typically targeted at the widget which contains the list
@@ -1044,6 +1742,10 @@ pub enum LapceUICommand {
 pub struct InitBufferContent<P: EditorPosition> {
     pub path: PathBuf,
     pub content: Rope,
+    /// The name of the encoding the file was read with
+    pub encoding: String,
+    /// Whether the file is opened in large file mode
+    pub large_file: bool,
     pub locations: Vec<(WidgetId, EditorLocation<P>)>,
     pub edits: Option<Rope>,
     pub cb: Option<InitBufferContentCb>,
@@ -1051,9 +1753,18 @@ pub struct InitBufferContent<P: EditorPosition> {
 
 impl<P: EditorPosition + Clone + Send + 'static> InitBufferContent<P> {
     pub fn execute(&self, ctx: &mut EventCtx, data: &mut LapceTabData) {
-        let doc = data.main_split.open_docs.get_mut(&self.path).unwrap();
+        // The document may have been closed while its content was loading
+        let doc = match data.main_split.open_docs.get_mut(&self.path) {
+            Some(doc) => doc,
+            None => {
+                ctx.set_handled();
+                return;
+            }
+        };
         let doc = Arc::make_mut(doc);
-        doc.init_content(self.content.to_owned());
+        doc.set_large_file(self.large_file);
+        doc.init_content(self.content.to_owned(), &data.config);
+        doc.set_encoding(self.encoding.clone());
 
         if let Some(rope) = &self.edits {
             doc.reload(rope.clone(), false);
@@ -1082,3 +1793,216 @@ pub fn execute(&self, ctx: &mut EventCtx, data: &mut LapceTabData) {
         ctx.set_handled();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{
+        edit_distance, lapce_internal_commands, CommandMacros, LapceCommand,
+        MacroError, MacroStepTarget, UnknownCommand,
+    };
+    use crate::config::MacroConfig;
+
+    fn macros(entries: &[(&str, &[&str])]) -> HashMap<String, MacroConfig> {
+        entries
+            .iter()
+            .map(|(name, steps)| {
+                (
+                    name.to_string(),
+                    MacroConfig {
+                        description: None,
+                        steps: steps.iter().map(|s| s.to_string()).collect(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_run_macro_steps_in_order() {
+        let config = macros(&[
+            ("format-and-save", &["format_document", "save"]),
+            ("check", &["format-and-save", "palette.command"]),
+        ]);
+        let (macros, errors) =
+            CommandMacros::new(&config, &lapce_internal_commands());
+        assert!(errors.is_empty());
+
+        // The steps are run one at a time, like the tab does when it's given
+        // the next one
+        let run = |fail_at: Option<&str>| {
+            let command_macro = macros.get("check").unwrap();
+            let mut ran = Vec::new();
+            let mut step = Some(0);
+            while let Some(current) = step {
+                step = command_macro.run_step(current, |command| {
+                    ran.push(command.kind.str());
+                    MacroStepTarget::of(&command.kind)?;
+                    if Some(command.kind.str()) == fail_at {
+                        Err("the file is read-only".to_string())
+                    } else {
+                        Ok(())
+                    }
+                })?;
+            }
+            Ok::<_, MacroError>(ran)
+        };
+
+        // The steps of the macros run by another one are run in their place
+        assert_eq!(
+            run(None).unwrap(),
+            vec!["format_document", "save", "palette.command"]
+        );
+
+        // The steps after the one failing aren't run
+        let err = run(Some("save")).unwrap_err();
+        assert_eq!(
+            err,
+            MacroError::StepFailed {
+                name: "check".to_string(),
+                step: 2,
+                error: "the file is read-only".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_macro_step_target() {
+        let commands = lapce_internal_commands();
+        let target = |id: &str| MacroStepTarget::of(&commands[id].kind);
+
+        assert_eq!(target("save_all"), Ok(MacroStepTarget::Workbench));
+        // Editing runs in the focused editor, in order with the other steps
+        assert_eq!(
+            target("delete_backward"),
+            Ok(MacroStepTarget::Focus { needs_editor: true })
+        );
+        assert_eq!(
+            target("down"),
+            Ok(MacroStepTarget::Focus { needs_editor: true })
+        );
+        assert_eq!(
+            target("split_vertical"),
+            Ok(MacroStepTarget::Focus {
+                needs_editor: false
+            })
+        );
+        assert_eq!(
+            target("select_all_current"),
+            Err("`select_all_current` can only be run from a key binding"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_reject_recursive_macros() {
+        let config = macros(&[
+            ("a", &["save", "b"]),
+            ("b", &["c"]),
+            ("c", &["a"]),
+            ("d", &["d"]),
+            ("e", &["b", "save"]),
+            ("f", &["save", "save"]),
+            ("g", &["f", "f"]),
+        ]);
+        let (macros, errors) =
+            CommandMacros::new(&config, &lapce_internal_commands());
+
+        // A macro running another one twice isn't recursive
+        assert_eq!(
+            macros.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+            vec!["f", "g"]
+        );
+        assert_eq!(macros.get("g").unwrap().steps.len(), 4);
+
+        let cycle = |cycle: &[&str]| MacroError::Recursive {
+            name: cycle[0].to_string(),
+            cycle: cycle.iter().map(|s| s.to_string()).collect(),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                cycle(&["a", "b", "c", "a"]),
+                cycle(&["b", "c", "a", "b"]),
+                cycle(&["c", "a", "b", "c"]),
+                cycle(&["d", "d"]),
+                // The macro running a recursive one names the cycle it's in
+                MacroError::Recursive {
+                    name: "e".to_string(),
+                    cycle: vec!["b", "c", "a", "b"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect(),
+                },
+            ]
+        );
+        assert_eq!(errors[3].to_string(), "macro `d` is recursive: d -> d");
+    }
+
+    #[test]
+    fn test_reject_unknown_commands() {
+        let config = macros(&[
+            ("a", &["save", "not_a_command"]),
+            ("b", &["a"]),
+            ("format-and-save", &["format_document", "save"]),
+            ("c", &["fromat-and-save"]),
+        ]);
+        let (macros, errors) =
+            CommandMacros::new(&config, &lapce_internal_commands());
+        assert!(macros.get("a").is_none());
+        assert!(macros.get("b").is_none());
+        assert_eq!(
+            errors[0],
+            MacroError::UnknownCommand {
+                name: "a".to_string(),
+                error: UnknownCommand {
+                    id: "not_a_command".to_string(),
+                    suggestions: Vec::new(),
+                },
+            }
+        );
+        // The macros are suggested along with the commands
+        assert_eq!(
+            errors[2].to_string(),
+            "macro `c` runs an unknown command `fromat-and-save`, \
+             did you mean `format-and-save`?"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("save", "save"), 0);
+        assert_eq!(edit_distance("save_fil", "save_file"), 1);
+        assert_eq!(edit_distance("sve_all", "save_all"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest_commands() {
+        let known = ["save", "save_file", "save_all", "search", "close"];
+        let error = UnknownCommand::new("save_fil", known);
+        assert_eq!(error.suggestions, vec!["save_file", "save_all"]);
+        assert_eq!(
+            error.to_string(),
+            "unknown command `save_fil`, did you mean `save_file` or `save_all`?"
+        );
+
+        let error = UnknownCommand::new("xyz", known);
+        assert!(error.suggestions.is_empty());
+        assert_eq!(error.to_string(), "unknown command `xyz`");
+    }
+
+    #[test]
+    fn test_command_from_id() {
+        let command = LapceCommand::from_id_with_suggestions("save_all").unwrap();
+        assert_eq!(command.kind.str(), "save_all");
+
+        let error = LapceCommand::from_id_with_suggestions("sve_all").unwrap_err();
+        assert_eq!(error.suggestions[0], "save_all");
+        let error =
+            LapceCommand::from_id_with_suggestions("toggle_inlay_hint").unwrap_err();
+        assert_eq!(error.suggestions[0], "toggle_inlay_hints");
+    }
+}
@@ -1,17 +1,31 @@
-use std::{borrow::Cow, fmt::Display, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    borrow::Cow, collections::HashMap, fmt::Display, path::PathBuf, str::FromStr,
+    sync::Arc, time::Duration,
+};
 
 use anyhow::Error;
 use core::fmt;
-use druid::{EventCtx, Size, WidgetId};
+use druid::{EventCtx, ExtEventSink, Size, Target, WidgetId};
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-use lapce_core::command::FocusCommand;
-use lapce_rpc::{buffer::BufferId, plugin::PluginId};
-use lsp_types::{CompletionItem, CompletionResponse, CompletionTextEdit, Position};
+use lapce_core::{
+    command::FocusCommand,
+    selection::{SelRegion, Selection},
+};
+use lapce_rpc::{buffer::BufferId, plugin::PluginId, proxy::ProxyResponse};
+use lapce_xi_rope::{RopeDelta, Transformer};
+use lsp_types::{
+    CompletionItem, CompletionResponse, CompletionTextEdit, Documentation,
+    InsertTextFormat, Position,
+};
 use once_cell::sync::Lazy;
 use regex::Regex;
 
 use crate::{
-    config::LapceConfig, data::LapceEditorData, document::Document, list::ListData,
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceConfig,
+    data::LapceEditorData,
+    document::Document,
+    list::ListData,
     proxy::LapceProxy,
 };
 
@@ -36,6 +50,9 @@ fn extract_elements(
             } else if let Some((ele, end)) = Self::extract_tabstop(s, pos) {
                 elements.push(ele);
                 pos = end;
+            } else if let Some((ele, end)) = Self::extract_choice(s, pos) {
+                elements.push(ele);
+                pos = end;
             } else if let Some((ele, end)) = Self::extract_placeholder(s, pos) {
                 elements.push(ele);
                 pos = end;
@@ -98,6 +115,36 @@ fn extract_tabstop(str: &str, pos: usize) -> Option<(SnippetElement, usize)> {
         None
     }
 
+    #[inline]
+    fn extract_choice(s: &str, pos: usize) -> Option<(SnippetElement, usize)> {
+        // Regex for `${num|...|}` pattern, where `...` are the choices separated by
+        // commas (for example `${1|one,two,three|}`)
+        static REGEX: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r#"^\$\{(\d+)\|((?:[^|\\]|\\.)*)\|\}"#).unwrap()
+        });
+
+        let caps = REGEX.captures(&s[pos..])?;
+
+        let tab = caps.get(1)?.as_str().parse::<usize>().ok()?;
+
+        let mut choices = Vec::new();
+        let mut choice = String::new();
+        let mut chars_iter = caps.get(2)?.as_str().chars();
+        while let Some(char) = chars_iter.next() {
+            match char {
+                '\\' => choice.extend(chars_iter.next()),
+                ',' => choices.push(std::mem::take(&mut choice)),
+                _ => choice.push(char),
+            }
+        }
+        choices.push(choice);
+
+        Some((
+            SnippetElement::Choice(tab, choices),
+            pos + caps.get(0).unwrap().end(),
+        ))
+    }
+
     #[inline]
     fn extract_placeholder(s: &str, pos: usize) -> Option<(SnippetElement, usize)> {
         // Regex for `${num:text}` pattern, where text can be empty (for example `${1:first}`
@@ -169,20 +216,74 @@ pub fn text(&self) -> String {
 
     #[inline]
     fn write_text_to<Buffer: fmt::Write>(&self, buf: &mut Buffer) -> fmt::Result {
+        let mirrors = self.mirrors();
         for snippet_element in self.elements.iter() {
-            snippet_element.write_text_to(buf)?
+            snippet_element.write_mirrored_text_to(buf, &mirrors)?
         }
         fmt::Result::Ok(())
     }
 
+    /// The text of the first placeholder of each index, which the tabstops with
+    /// the same index mirror
+    fn mirrors(&self) -> HashMap<usize, String> {
+        fn collect(
+            elements: &[SnippetElement],
+            mirrors: &mut HashMap<usize, String>,
+        ) {
+            for el in elements {
+                match el {
+                    SnippetElement::PlaceHolder(tab, els) => {
+                        mirrors.entry(*tab).or_insert_with(|| {
+                            els.iter().map(|e| e.text()).collect()
+                        });
+                        collect(els, mirrors);
+                    }
+                    SnippetElement::Choice(tab, choices) => {
+                        mirrors.entry(*tab).or_insert_with(|| {
+                            choices.first().cloned().unwrap_or_default()
+                        });
+                    }
+                    SnippetElement::Text(_) | SnippetElement::Tabstop(_) => {}
+                }
+            }
+        }
+
+        let mut mirrors = HashMap::new();
+        collect(&self.elements, &mut mirrors);
+        mirrors
+    }
+
+    /// The choices of each `${1|one,two|}` tabstop
+    pub fn choices(&self) -> Vec<(usize, Vec<String>)> {
+        fn collect(
+            elements: &[SnippetElement],
+            choices: &mut Vec<(usize, Vec<String>)>,
+        ) {
+            for el in elements {
+                match el {
+                    SnippetElement::PlaceHolder(_, els) => collect(els, choices),
+                    SnippetElement::Choice(tab, values) => {
+                        choices.push((*tab, values.clone()))
+                    }
+                    SnippetElement::Text(_) | SnippetElement::Tabstop(_) => {}
+                }
+            }
+        }
+
+        let mut choices = Vec::new();
+        collect(&self.elements, &mut choices);
+        choices
+    }
+
     #[inline]
     pub fn tabs(&self, pos: usize) -> Vec<(usize, (usize, usize))> {
-        Self::elements_tabs(&self.elements, pos)
+        Self::elements_tabs(&self.elements, pos, &self.mirrors())
     }
 
     pub fn elements_tabs(
         elements: &[SnippetElement],
         start: usize,
+        mirrors: &HashMap<usize, String>,
     ) -> Vec<(usize, (usize, usize))> {
         let mut tabs = Vec::new();
         let mut pos = start;
@@ -192,14 +293,22 @@ pub fn elements_tabs(
                     pos += t.len();
                 }
                 SnippetElement::PlaceHolder(tab, els) => {
-                    let placeholder_tabs = Self::elements_tabs(els, pos);
-                    let end = pos + els.iter().map(|e| e.len()).sum::<usize>();
+                    let placeholder_tabs = Self::elements_tabs(els, pos, mirrors);
+                    let end = pos
+                        + els.iter().map(|e| e.mirrored_len(mirrors)).sum::<usize>();
                     tabs.push((*tab, (pos, end)));
                     tabs.extend_from_slice(&placeholder_tabs);
                     pos = end;
                 }
+                SnippetElement::Choice(tab, _) => {
+                    let end = pos + el.len();
+                    tabs.push((*tab, (pos, end)));
+                    pos = end;
+                }
                 SnippetElement::Tabstop(tab) => {
-                    tabs.push((*tab, (pos, pos)));
+                    let end = pos + el.mirrored_len(mirrors);
+                    tabs.push((*tab, (pos, end)));
+                    pos = end;
                 }
             }
         }
@@ -230,17 +339,29 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 pub enum SnippetElement {
     Text(String),
     PlaceHolder(usize, Vec<SnippetElement>),
+    /// A tabstop offering a list of values, the first one is inserted
+    Choice(usize, Vec<String>),
     Tabstop(usize),
 }
 
 impl SnippetElement {
     pub fn len(&self) -> usize {
+        self.mirrored_len(&HashMap::new())
+    }
+
+    /// The length once the tabstops are replaced by the placeholder they mirror
+    fn mirrored_len(&self, mirrors: &HashMap<usize, String>) -> usize {
         match &self {
             SnippetElement::Text(text) => text.len(),
             SnippetElement::PlaceHolder(_, elements) => {
-                elements.iter().map(|e| e.len()).sum()
+                elements.iter().map(|e| e.mirrored_len(mirrors)).sum()
+            }
+            SnippetElement::Choice(_, choices) => {
+                choices.first().map(|c| c.len()).unwrap_or(0)
+            }
+            SnippetElement::Tabstop(tab) => {
+                mirrors.get(tab).map(|t| t.len()).unwrap_or(0)
             }
-            SnippetElement::Tabstop(_) => 0,
         }
     }
 
@@ -258,16 +379,29 @@ pub fn text(&self) -> String {
     }
 
     fn write_text_to<Buffer: fmt::Write>(&self, buf: &mut Buffer) -> fmt::Result {
+        self.write_mirrored_text_to(buf, &HashMap::new())
+    }
+
+    fn write_mirrored_text_to<Buffer: fmt::Write>(
+        &self,
+        buf: &mut Buffer,
+        mirrors: &HashMap<usize, String>,
+    ) -> fmt::Result {
         match self {
             SnippetElement::Text(text) => buf.write_str(text),
             SnippetElement::PlaceHolder(_, elements) => {
                 for child_snippet_elm in elements {
                     // call ourselves recursively
-                    child_snippet_elm.write_text_to(buf)?;
+                    child_snippet_elm.write_mirrored_text_to(buf, mirrors)?;
                 }
                 fmt::Result::Ok(())
             }
-            SnippetElement::Tabstop(_) => fmt::Result::Ok(()),
+            SnippetElement::Choice(_, choices) => {
+                buf.write_str(choices.first().map(|c| c.as_str()).unwrap_or(""))
+            }
+            SnippetElement::Tabstop(tab) => {
+                buf.write_str(mirrors.get(tab).map(|t| t.as_str()).unwrap_or(""))
+            }
         }
     }
 }
@@ -285,23 +419,203 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 }
                 f.write_str("}")
             }
+            SnippetElement::Choice(tab, choices) => {
+                // Trying to write to the provided buffer in the form "${tab|a,b|}"
+                write!(f, "${{{tab}|")?;
+                for (i, choice) in choices.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    for char in choice.chars() {
+                        if matches!(char, ',' | '|' | '\\') {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{char}")?;
+                    }
+                }
+                f.write_str("|}")
+            }
             SnippetElement::Tabstop(tab) => write!(f, "${tab}"),
         }
     }
 }
 
+/// A tabstop of an inserted snippet, with the regions of all the placeholders
+/// sharing its index, which mirror each other
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnippetTabstop {
+    pub index: usize,
+    pub regions: Vec<(usize, usize)>,
+    /// The values offered for a `${1|one,two|}` tabstop
+    pub choices: Option<Vec<String>>,
+}
+
+/// The snippet whose tabstops are being filled in, after it was inserted by a
+/// completion
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveSnippet {
+    /// In the order they're jumped to, by index with `$0` last
+    tabstops: Vec<SnippetTabstop>,
+    current: usize,
+}
+
+impl ActiveSnippet {
+    /// The tabstops of `snippet`, once its text is inserted at `offset`
+    pub fn new(snippet: &Snippet, offset: usize) -> Self {
+        let mut tabstops: Vec<SnippetTabstop> = Vec::new();
+        for (index, region) in snippet.tabs(offset) {
+            match tabstops.iter_mut().find(|t| t.index == index) {
+                Some(tabstop) => tabstop.regions.push(region),
+                None => tabstops.push(SnippetTabstop {
+                    index,
+                    regions: vec![region],
+                    choices: None,
+                }),
+            }
+        }
+        for (index, choices) in snippet.choices() {
+            if let Some(tabstop) = tabstops.iter_mut().find(|t| t.index == index) {
+                tabstop.choices.get_or_insert(choices);
+            }
+        }
+
+        // The cursor ends up at the end of a snippet without a `$0`
+        if !tabstops.iter().any(|t| t.index == 0) {
+            let end = offset + snippet.text().len();
+            tabstops.push(SnippetTabstop {
+                index: 0,
+                regions: vec![(end, end)],
+                choices: None,
+            });
+        }
+        tabstops.sort_by_key(|t| if t.index == 0 { usize::MAX } else { t.index });
+
+        Self {
+            tabstops,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &SnippetTabstop {
+        &self.tabstops[self.current]
+    }
+
+    /// Whether the last tabstop is reached, the snippet is then done with
+    pub fn is_finished(&self) -> bool {
+        self.current + 1 >= self.tabstops.len()
+    }
+
+    /// Move to the next tabstop, return false if there is none
+    pub fn next(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.current += 1;
+        true
+    }
+
+    /// Move to the previous tabstop, return false if there is none
+    pub fn prev(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.current -= 1;
+        true
+    }
+
+    /// Select all the regions of the current tabstop, so that what's typed goes
+    /// to its mirrors too
+    pub fn selection(&self) -> Selection {
+        let mut selection = Selection::new();
+        for (start, end) in self.current().regions.iter() {
+            selection.add_region(SelRegion::new(*start, *end, None));
+        }
+        selection
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tabstops.iter().flat_map(|t| t.regions.iter().copied())
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.regions()
+            .any(|(start, end)| start <= offset && offset <= end)
+    }
+
+    /// Whether all the tabstops ended up at the same offset, which is what's
+    /// left once the text of the snippet is deleted, by an undo for example
+    pub fn is_collapsed(&self) -> bool {
+        let mut regions = self.regions();
+        match regions.next() {
+            Some(first) => first.0 == first.1 && regions.all(|r| r == first),
+            None => true,
+        }
+    }
+
+    pub fn apply_delta(&mut self, delta: &RopeDelta) {
+        let mut transformer = Transformer::new(delta);
+        for tabstop in self.tabstops.iter_mut() {
+            for (start, end) in tabstop.regions.iter_mut() {
+                *start = transformer.transform(*start, false);
+                *end = transformer.transform(*end, true);
+            }
+        }
+    }
+
+    /// Replace the current tabstop by the ones of a snippet inserted in it
+    pub fn nest(&mut self, snippet: ActiveSnippet) {
+        self.tabstops
+            .splice(self.current..self.current + 1, snippet.tabstops);
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum CompletionStatus {
     Inactive,
     Started,
 }
 
+/// A request for completion items waiting to be sent
+#[derive(Clone)]
+struct CompletionQuery {
+    request_id: usize,
+    path: PathBuf,
+    input: String,
+    position: Position,
+}
+
+impl CompletionQuery {
+    fn send(self, proxy: &LapceProxy) {
+        proxy.proxy_rpc.completion(
+            self.request_id,
+            self.path,
+            self.input,
+            self.position,
+        );
+    }
+}
+
 #[derive(Clone)]
 pub struct CompletionData {
     pub id: WidgetId,
     pub scroll_id: WidgetId,
     pub documentation_scroll_id: WidgetId,
+    /// The id of the latest request, every request gets the next one
     pub request_id: usize,
+    /// The id of the first request of the current completion, the responses to
+    /// earlier requests are for another position
+    pub session_id: usize,
+    /// The id of the latest request whose response was received, for each
+    /// input. The responses to earlier requests arriving after it are stale.
+    received_ids: im::HashMap<String, usize>,
+    /// The request waiting for the input to stay the same long enough
+    pending: Option<CompletionQuery>,
+    /// Whether the items are the choices of a snippet tabstop rather than from
+    /// the language servers
+    pub is_snippet_choice: bool,
+    /// The items whose documentation was asked for with a resolve request, by
+    /// plugin and label, so that it's asked only once
+    resolve_requested: im::HashSet<(PluginId, String)>,
     pub status: CompletionStatus,
     pub offset: usize,
     pub buffer_id: BufferId,
@@ -325,6 +639,11 @@ pub fn new(config: Arc<LapceConfig>) -> Self {
             scroll_id: WidgetId::next(),
             documentation_scroll_id: WidgetId::next(),
             request_id: 0,
+            session_id: 0,
+            received_ids: im::HashMap::new(),
+            pending: None,
+            is_snippet_choice: false,
+            resolve_requested: im::HashSet::new(),
             offset: 0,
             status: CompletionStatus::Inactive,
             buffer_id: BufferId(0),
@@ -348,11 +667,7 @@ pub fn is_empty(&self) -> bool {
     }
 
     pub fn current_items(&self) -> &im::Vector<ScoredCompletionItem> {
-        if self.input.is_empty() {
-            self.all_items()
-        } else {
-            &self.completion_list.items
-        }
+        &self.completion_list.items
     }
 
     pub fn all_items(&self) -> &im::Vector<ScoredCompletionItem> {
@@ -372,17 +687,141 @@ pub fn current(&self) -> Option<&str> {
         self.current_item().map(|item| item.item.label.as_str())
     }
 
+    /// The highlighted item, if typing the char accepts it, which is when the
+    /// char is one of the commit characters of the item. The snippets aren't
+    /// accepted this way, since the char would be typed in their tabstop.
+    pub fn commit_item(&self, c: &str) -> Option<&ScoredCompletionItem> {
+        if !self.completion_list.config.completion.commit_on_type
+            || self.status == CompletionStatus::Inactive
+            || self.is_snippet_choice
+        {
+            return None;
+        }
+        let item = self.current_item()?;
+        let commits = item
+            .item
+            .commit_characters
+            .as_ref()
+            .map(|chars| chars.iter().any(|char| char == c))
+            .unwrap_or(false);
+        let is_snippet =
+            item.item.insert_text_format == Some(InsertTextFormat::SNIPPET);
+        (commits && !is_snippet).then_some(item)
+    }
+
+    /// Start a new completion, the responses to the requests made before are
+    /// then ignored
+    pub fn start_session(&mut self) {
+        self.session_id = self.request_id + 1;
+        self.received_ids.clear();
+        self.resolve_requested.clear();
+    }
+
+    /// Request the items for `input`, once it stayed the same for `debounce`.
+    /// A request still waiting is superseded by the new one.
     pub fn request(
         &mut self,
         proxy: Arc<LapceProxy>,
+        event_sink: ExtEventSink,
         path: PathBuf,
         input: String,
         position: Position,
+        debounce: Duration,
     ) {
+        let query = self.queue(path, input, position, !debounce.is_zero());
+        if let Some(query) = query {
+            query.send(&proxy);
+            return;
+        }
+
+        let request_id = self.request_id;
+        std::thread::spawn(move || {
+            std::thread::sleep(debounce);
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::SendCompletionRequest { request_id },
+                Target::Widget(proxy.tab_id),
+            );
+        });
+    }
+
+    /// Give the new request its id, and return it if it's to be sent right away
+    fn queue(
+        &mut self,
+        path: PathBuf,
+        input: String,
+        position: Position,
+        debounced: bool,
+    ) -> Option<CompletionQuery> {
+        if let Some(superseded) = self.pending.take() {
+            // It was never sent, so it's requested again if its input comes back
+            self.input_items.remove(&superseded.input);
+        }
+
+        self.request_id += 1;
         self.input_items.insert(input.clone(), im::Vector::new());
-        proxy
-            .proxy_rpc
-            .completion(self.request_id, path, input, position);
+        let query = CompletionQuery {
+            request_id: self.request_id,
+            path,
+            input,
+            position,
+        };
+        if debounced {
+            self.pending = Some(query);
+            None
+        } else {
+            Some(query)
+        }
+    }
+
+    fn take_pending(&mut self, request_id: usize) -> Option<CompletionQuery> {
+        if self.pending.as_ref()?.request_id == request_id {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    /// Send the request waiting for its debounce time, unless it was superseded
+    pub fn send_pending(&mut self, request_id: usize, proxy: &LapceProxy) {
+        if let Some(query) = self.take_pending(request_id) {
+            query.send(proxy);
+        }
+    }
+
+    /// List the choices of the snippet tabstop at `offset`
+    pub fn show_choices(
+        &mut self,
+        buffer_id: BufferId,
+        offset: usize,
+        choices: &[String],
+    ) {
+        self.start_session();
+        self.status = CompletionStatus::Started;
+        self.pending = None;
+        self.is_snippet_choice = true;
+        self.buffer_id = buffer_id;
+        self.offset = offset;
+        self.input = "".to_string();
+        self.input_items.clear();
+        self.input_items.insert(
+            "".to_string(),
+            choices
+                .iter()
+                .map(|choice| ScoredCompletionItem {
+                    item: CompletionItem {
+                        label: choice.clone(),
+                        ..Default::default()
+                    },
+                    plugin_id: PluginId(0),
+                    score: 0,
+                    label_score: 0,
+                    indices: Vec::new(),
+                })
+                .collect(),
+        );
+        self.completion_list.selected_index = 0;
+        self.filter_items();
     }
 
     pub fn cancel(&mut self) {
@@ -390,6 +829,10 @@ pub fn cancel(&mut self) {
             return;
         }
         self.status = CompletionStatus::Inactive;
+        self.pending = None;
+        self.is_snippet_choice = false;
+        self.received_ids.clear();
+        self.resolve_requested.clear();
         self.input = "".to_string();
         self.input_items.clear();
         self.completion_list.clear_items();
@@ -411,10 +854,17 @@ pub fn receive(
         resp: CompletionResponse,
         plugin_id: PluginId,
     ) {
-        if self.status == CompletionStatus::Inactive || self.request_id != request_id
+        if self.status == CompletionStatus::Inactive
+            || request_id < self.session_id
+            || self
+                .received_ids
+                .get(&input)
+                .map(|id| request_id < *id)
+                .unwrap_or(false)
         {
             return;
         }
+        self.received_ids.insert(input.clone(), request_id);
 
         let items = match resp {
             CompletionResponse::Array(items) => items,
@@ -439,15 +889,113 @@ pub fn receive(
         }
     }
 
-    pub fn filter_items(&mut self) {
-        if self.input.is_empty() {
-            self.completion_list.items = self.all_items().clone();
+    /// The highlighted item, if it came without documentation which it's yet to
+    /// be resolved for. Only the highlighted item is resolved, not to flood the
+    /// language server with requests.
+    fn item_to_resolve(&self) -> Option<&ScoredCompletionItem> {
+        let item = self.current_item()?;
+        if self.is_snippet_choice
+            || item.item.documentation.is_some()
+            || item.item.data.is_none()
+            || self
+                .resolve_requested
+                .contains(&(item.plugin_id, item.item.label.clone()))
+        {
+            return None;
+        }
+        Some(item)
+    }
+
+    /// Request the documentation of the highlighted item, if it's missing
+    pub fn resolve_current_item(
+        &mut self,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let item = match self.item_to_resolve() {
+            Some(item) => item.clone(),
+            None => return,
+        };
+        self.resolve_requested
+            .insert((item.plugin_id, item.item.label.clone()));
+
+        let session_id = self.session_id;
+        let plugin_id = item.plugin_id;
+        let label = item.item.label.clone();
+        let tab_id = proxy.tab_id;
+        proxy
+            .proxy_rpc
+            .completion_resolve(plugin_id, item.item, move |result| {
+                let documentation =
+                    if let Ok(ProxyResponse::CompletionResolveResponse { item }) =
+                        result
+                    {
+                        item.documentation
+                    } else {
+                        None
+                    };
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateCompletionDocumentation {
+                        session_id,
+                        plugin_id,
+                        label,
+                        documentation,
+                    },
+                    Target::Widget(tab_id),
+                );
+            });
+    }
+
+    /// Receive the documentation of a resolved item. Without any, the item stays
+    /// as it is, and isn't resolved again.
+    pub fn receive_documentation(
+        &mut self,
+        session_id: usize,
+        plugin_id: PluginId,
+        label: &str,
+        documentation: Option<Documentation>,
+    ) {
+        if self.status == CompletionStatus::Inactive || session_id != self.session_id
+        {
             return;
         }
+        let documentation = match documentation {
+            Some(documentation) => documentation,
+            None => return,
+        };
+
+        let items = self
+            .input_items
+            .iter_mut()
+            .flat_map(|(_, items)| items.iter_mut())
+            .chain(self.completion_list.items.iter_mut());
+        for item in items {
+            if item.plugin_id == plugin_id && item.item.label == label {
+                item.item.documentation = Some(documentation.clone());
+            }
+        }
+    }
 
-        let mut items: im::Vector<ScoredCompletionItem> = self
+    /// List the items matching the input, without the kinds hidden by the
+    /// settings. The items matching as well are ordered by their kind.
+    pub fn filter_items(&mut self) {
+        let config = self.completion_list.config.clone();
+        let completion = &config.completion;
+        let items = self
             .all_items()
             .iter()
+            .filter(|i| !completion.is_kind_hidden(i.item.kind));
+        if self.input.is_empty() {
+            // The language servers sort the items themselves, which is kept for
+            // the items of the same rank
+            let mut items = items.cloned().collect::<Vec<_>>();
+            items.sort_by_key(|i| completion.kind_rank(i.item.kind));
+            self.completion_list.items = items.into();
+            return;
+        }
+
+        let mut items: im::Vector<ScoredCompletionItem> = items
             .filter_map(|i| {
                 let filter_text =
                     i.item.filter_text.as_ref().unwrap_or(&i.item.label);
@@ -484,6 +1032,11 @@ pub fn filter_items(&mut self) {
         items.sort_by(|a, b| {
             b.score
                 .cmp(&a.score)
+                .then_with(|| {
+                    completion
+                        .kind_rank(a.item.kind)
+                        .cmp(&completion.kind_rank(b.item.kind))
+                })
                 .then_with(|| b.label_score.cmp(&a.label_score))
                 .then_with(|| a.item.label.len().cmp(&b.item.label.len()))
         });
@@ -517,8 +1070,10 @@ pub fn update_document_completion(
             let doc = Arc::make_mut(doc);
 
             // It isn't enabled at all, so we just clear it (in case it was
-            // enabled, and then disalbled) which is cheap with no existing completion lens
-            if !config.editor.enable_completion_lens {
+            // enabled, and then disalbled) which is cheap with no existing completion lens.
+            // The choices of a snippet replace the selected text rather than
+            // complete it, so there's no lens for them either.
+            if !config.editor.enable_completion_lens || self.is_snippet_choice {
                 doc.clear_completion();
                 return;
             }
@@ -624,6 +1179,8 @@ pub struct ScoredCompletionItem {
 
 #[cfg(test)]
 mod tests {
+    use lsp_types::CompletionItemKind;
+
     use super::*;
 
     #[test]
@@ -756,6 +1313,150 @@ fn test_snippet() {
         );
     }
 
+    #[test]
+    fn test_snippet_choice_and_mirrors() {
+        use SnippetElement::*;
+
+        let s = "${1|one,two\\,three,fo\\|ur|} and ${2:x ${3|a,b|}}";
+        let parsed = Snippet::from_str(s).unwrap();
+        assert_eq!(
+            Snippet {
+                elements: vec![
+                    Choice(
+                        1,
+                        vec!["one".into(), "two,three".into(), "fo|ur".into()]
+                    ),
+                    Text(" and ".into()),
+                    PlaceHolder(
+                        2,
+                        vec![
+                            Text("x ".into()),
+                            Choice(3, vec!["a".into(), "b".into()])
+                        ]
+                    )
+                ]
+            },
+            parsed
+        );
+        assert_eq!(s, parsed.to_string());
+        assert_eq!("one and x a", parsed.text());
+        assert_eq!(
+            vec![(1, (0, 3)), (2, (8, 11)), (3, (10, 11))],
+            parsed.tabs(0)
+        );
+        assert_eq!(
+            vec![
+                (1, vec!["one".into(), "two,three".into(), "fo|ur".into()]),
+                (3, vec!["a".into(), "b".into()])
+            ],
+            parsed.choices()
+        );
+
+        // The tabstops sharing the index of a placeholder get its text
+        let s = "for (${1:i} = 0; $1 < ${2:n}; $1++) {\n\t$0\n}";
+        let parsed = Snippet::from_str(s).unwrap();
+        assert_eq!(s, parsed.to_string());
+        assert_eq!("for (i = 0; i < n; i++) {\n\t\n}", parsed.text());
+        assert_eq!(
+            vec![
+                (1, (5, 6)),
+                (1, (12, 13)),
+                (2, (16, 17)),
+                (1, (19, 20)),
+                (0, (27, 27))
+            ],
+            parsed.tabs(0)
+        );
+    }
+
+    #[test]
+    fn test_active_snippet() {
+        use lapce_core::{buffer::Buffer, editor::EditType};
+
+        fn current_texts(snippet: &ActiveSnippet, buffer: &Buffer) -> Vec<String> {
+            snippet
+                .current()
+                .regions
+                .iter()
+                .map(|(start, end)| buffer.slice_to_cow(*start..*end).to_string())
+                .collect()
+        }
+
+        let snippet =
+            Snippet::from_str("for (${1:i} = 0; $1 < ${2|n,len|}; $1++) {$0}")
+                .unwrap();
+        let mut buffer = Buffer::new("");
+        buffer.edit(
+            &[(Selection::caret(0), snippet.text().as_str())],
+            EditType::Completion,
+        );
+        let mut active = ActiveSnippet::new(&snippet, 0);
+        assert_eq!(1, active.current().index);
+        assert_eq!(vec!["i", "i", "i"], current_texts(&active, &buffer));
+
+        // What's typed in a tabstop goes to its mirrors too
+        let (delta, _, _) =
+            buffer.edit(&[(active.selection(), "idx")], EditType::InsertChars);
+        active.apply_delta(&delta);
+        assert_eq!(
+            "for (idx = 0; idx < n; idx++) {}",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(vec!["idx", "idx", "idx"], current_texts(&active, &buffer));
+
+        assert!(active.next());
+        assert_eq!(2, active.current().index);
+        assert_eq!(
+            Some(vec!["n".to_string(), "len".to_string()]),
+            active.current().choices
+        );
+        assert_eq!(vec!["n"], current_texts(&active, &buffer));
+        assert!(!active.is_finished());
+
+        assert!(active.next());
+        assert_eq!(0, active.current().index);
+        assert_eq!(vec![(31, 31)], active.current().regions);
+        assert!(active.is_finished());
+        assert!(!active.next());
+
+        assert!(active.prev());
+        assert!(active.prev());
+        assert_eq!(1, active.current().index);
+        assert!(!active.prev());
+
+        // Undoing what was typed brings the placeholders back
+        let (delta, _, _, _) = buffer.do_undo().unwrap();
+        active.apply_delta(&delta);
+        assert_eq!(
+            "for (i = 0; i < n; i++) {}",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(vec!["i", "i", "i"], current_texts(&active, &buffer));
+        assert!(!active.is_collapsed());
+
+        // Undoing the snippet leaves nothing of it
+        let (delta, _, _, _) = buffer.do_undo().unwrap();
+        active.apply_delta(&delta);
+        assert!(buffer.is_empty());
+        assert!(active.is_collapsed());
+    }
+
+    #[test]
+    fn test_nested_active_snippet() {
+        let mut active =
+            ActiveSnippet::new(&Snippet::from_str("${1:a}, ${2:b}").unwrap(), 0);
+        // A completion in the first tabstop inserts another snippet there
+        let nested = ActiveSnippet::new(&Snippet::from_str("f($1)").unwrap(), 0);
+        active.nest(nested);
+
+        let mut indexes = vec![active.current().index];
+        while active.next() {
+            indexes.push(active.current().index);
+        }
+        // The nested `$1` and the end of the nested snippet, then the rest
+        assert_eq!(vec![1, 0, 2, 0], indexes);
+    }
+
     #[test]
     fn test_extract_tabstop() {
         fn vec_of_tab_elms(s: &str) -> Vec<(usize, usize)> {
@@ -1198,4 +1899,240 @@ fn test_extract_text() {
             Snippet::extract_text(s, end + 1, &['$', '{', '}', '\\'], &[])
         );
     }
+
+    #[test]
+    fn test_rapid_queries() {
+        let item = |label: &str| CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        };
+        let resp = |label: &str| CompletionResponse::Array(vec![item(label)]);
+        let shown = |completion: &CompletionData| {
+            completion
+                .current_items()
+                .iter()
+                .map(|i| i.item.label.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut completion = CompletionData::new(Arc::new(LapceConfig::default()));
+        completion.status = CompletionStatus::Started;
+        completion.start_session();
+        completion.update_input("pri".to_string());
+
+        // Only the last of the debounced queries is sent
+        let path = PathBuf::from("main.rs");
+        for input in ["p", "pr", "pri"] {
+            let query = completion.queue(
+                path.clone(),
+                input.to_string(),
+                Position::default(),
+                true,
+            );
+            assert!(query.is_none());
+        }
+        assert!(completion.take_pending(1).is_none());
+        assert!(completion.take_pending(2).is_none());
+        let query = completion.take_pending(3).unwrap();
+        assert_eq!(query.input, "pri");
+        assert!(!completion.input_items.contains_key("p"));
+        assert!(!completion.input_items.contains_key("pr"));
+
+        // The responses arrive out of order
+        let plugin_id = PluginId(0);
+        completion.receive(3, "pri".to_string(), resp("println"), plugin_id);
+        completion.receive(1, "pri".to_string(), resp("print"), plugin_id);
+        completion.receive(2, "pri".to_string(), resp("primitive"), plugin_id);
+        assert_eq!(shown(&completion), vec!["println".to_string()]);
+
+        // The responses for a previous completion are ignored
+        completion.start_session();
+        completion.receive(3, "pri".to_string(), resp("print"), plugin_id);
+        assert_eq!(shown(&completion), vec!["println".to_string()]);
+    }
+
+    #[test]
+    fn test_resolve_documentation() {
+        let item = |label: &str, documentation: Option<&str>| CompletionItem {
+            label: label.to_string(),
+            documentation: documentation
+                .map(|doc| Documentation::String(doc.to_string())),
+            data: Some(serde_json::Value::Null),
+            ..Default::default()
+        };
+        let resolving = |completion: &CompletionData| {
+            completion
+                .item_to_resolve()
+                .map(|item| item.item.label.clone())
+        };
+
+        let mut completion = CompletionData::new(Arc::new(LapceConfig::default()));
+        completion.status = CompletionStatus::Started;
+        completion.start_session();
+        let plugin_id = PluginId(0);
+        completion.receive(
+            1,
+            "".to_string(),
+            CompletionResponse::Array(vec![
+                item("print", Some("Prints to the standard output")),
+                item("println", None),
+                item("eprintln", None),
+            ]),
+            plugin_id,
+        );
+
+        // Items with documentation aren't resolved
+        assert_eq!(None, resolving(&completion));
+
+        // Only the highlighted item is resolved, once
+        completion.completion_list.selected_index = 1;
+        assert_eq!(Some("println".to_string()), resolving(&completion));
+        completion
+            .resolve_requested
+            .insert((plugin_id, "println".to_string()));
+        assert_eq!(None, resolving(&completion));
+
+        let markdown = Documentation::MarkupContent(lsp_types::MarkupContent {
+            kind: lsp_types::MarkupKind::Markdown,
+            value: "Prints with a **newline**".to_string(),
+        });
+        completion.receive_documentation(
+            completion.session_id,
+            plugin_id,
+            "println",
+            Some(markdown.clone()),
+        );
+        assert_eq!(
+            Some(&markdown),
+            completion
+                .current_item()
+                .unwrap()
+                .item
+                .documentation
+                .as_ref()
+        );
+
+        // The resolve gave no documentation
+        completion.completion_list.selected_index = 2;
+        assert_eq!(Some("eprintln".to_string()), resolving(&completion));
+        completion
+            .resolve_requested
+            .insert((plugin_id, "eprintln".to_string()));
+        completion.receive_documentation(
+            completion.session_id,
+            plugin_id,
+            "eprintln",
+            None,
+        );
+        assert_eq!(None, completion.current_item().unwrap().item.documentation);
+        assert_eq!(None, resolving(&completion));
+
+        // Documentation for a previous completion is ignored
+        completion.receive_documentation(
+            completion.session_id - 1,
+            plugin_id,
+            "eprintln",
+            Some(markdown),
+        );
+        assert_eq!(None, completion.current_item().unwrap().item.documentation);
+    }
+
+    fn kind_item(label: &str, kind: Option<CompletionItemKind>) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn shown_labels(completion: &CompletionData) -> Vec<String> {
+        completion
+            .current_items()
+            .iter()
+            .map(|i| i.item.label.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_hidden_kinds() {
+        let mut config = LapceConfig::default();
+        config.completion.hidden_kinds = vec!["Snippet".to_string()];
+        let mut completion = CompletionData::new(Arc::new(config));
+        completion.status = CompletionStatus::Started;
+        completion.start_session();
+        completion.receive(
+            1,
+            "".to_string(),
+            CompletionResponse::Array(vec![
+                kind_item("for", Some(CompletionItemKind::SNIPPET)),
+                kind_item("format", Some(CompletionItemKind::FUNCTION)),
+                kind_item("fork", None),
+                kind_item("for_each", Some(CompletionItemKind::METHOD)),
+            ]),
+            PluginId(0),
+        );
+        assert_eq!(
+            shown_labels(&completion),
+            vec!["format", "fork", "for_each"]
+        );
+
+        // The snippet is hidden even when it matches the input best
+        completion.update_input("for".to_string());
+        let shown = shown_labels(&completion);
+        assert_eq!(shown.len(), 3);
+        assert!(!shown.contains(&"for".to_string()));
+
+        // The choices of a snippet tabstop have no kind to hide
+        completion.show_choices(BufferId(0), 0, &["a".to_string()]);
+        assert_eq!(shown_labels(&completion), vec!["a"]);
+    }
+
+    #[test]
+    fn test_kind_order() {
+        let items = vec![
+            kind_item("len_a", Some(CompletionItemKind::METHOD)),
+            kind_item("len_b", Some(CompletionItemKind::FIELD)),
+            kind_item("len_c", Some(CompletionItemKind::KEYWORD)),
+            kind_item("len_d", Some(CompletionItemKind::FIELD)),
+            kind_item("len_e", None),
+        ];
+        let mut config = LapceConfig::default();
+        config.completion.kind_order = vec![
+            "field".to_string(),
+            "enum_member".to_string(),
+            "method".to_string(),
+        ];
+        let mut completion = CompletionData::new(Arc::new(config));
+        completion.status = CompletionStatus::Started;
+        completion.start_session();
+        completion.receive(
+            1,
+            "".to_string(),
+            CompletionResponse::Array(items),
+            PluginId(0),
+        );
+
+        // The order of the server is kept within a kind, and the kinds left out
+        // of the setting come last
+        assert_eq!(
+            shown_labels(&completion),
+            vec!["len_b", "len_d", "len_a", "len_c", "len_e"]
+        );
+
+        // The items matching the input as well are ordered by kind
+        completion.update_input("len".to_string());
+        let shown = shown_labels(&completion);
+        let first = |labels: &[&str]| {
+            let mut shown = shown[..labels.len()].to_vec();
+            shown.sort();
+            assert_eq!(shown, labels);
+        };
+        first(&["len_b", "len_d"]);
+        assert_eq!(shown[2], "len_a");
+        assert_eq!(shown.len(), 5);
+
+        // but an item matching better still comes first
+        completion.update_input("len_c".to_string());
+        assert_eq!(shown_labels(&completion)[0], "len_c");
+    }
 }
@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    str::FromStr,
     sync::{atomic::AtomicBool, Arc},
 };
 
@@ -9,10 +10,10 @@
     Color, ExtEventSink, FontFamily, Size, Target,
 };
 use indexmap::IndexMap;
-use lapce_core::directory::Directory;
+use lapce_core::{directory::Directory, language::LapceLanguage, wrap::WrapMode};
 use lapce_proxy::plugin::wasi::find_all_volts;
 use lapce_rpc::plugin::VoltID;
-use lsp_types::{CompletionItemKind, SymbolKind};
+use lsp_types::{CompletionItemKind, DiagnosticSeverity, InlayHintKind, SymbolKind};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -21,8 +22,12 @@
 use toml_edit::easy as toml;
 
 use crate::{
+    alert::AlertContentData,
     command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config_schema::setting_schema,
+    container::TabOverflow,
     data::{LapceWorkspace, LapceWorkspaceType},
+    local_history::LocalHistorySnapshot,
     svg::SvgStore,
 };
 
@@ -33,7 +38,7 @@
 const DEFAULT_ICON_THEME: &str = include_str!("../../defaults/icon-theme.toml");
 
 static DEFAULT_CONFIG: Lazy<config::Config> = Lazy::new(LapceConfig::default_config);
-static DEFAULT_LAPCE_CONFIG: Lazy<LapceConfig> =
+pub(crate) static DEFAULT_LAPCE_CONFIG: Lazy<LapceConfig> =
     Lazy::new(LapceConfig::default_lapce_config);
 
 pub struct LapceTheme {}
@@ -105,6 +110,10 @@ impl LapceTheme {
     pub const SOURCE_CONTROL_REMOVED: &str = "source_control.removed";
     pub const SOURCE_CONTROL_MODIFIED: &str = "source_control.modified";
 
+    pub const EXPLORER_FILE_ADDED: &str = "explorer.file.added";
+    pub const EXPLORER_FILE_DELETED: &str = "explorer.file.deleted";
+    pub const EXPLORER_FILE_MODIFIED: &str = "explorer.file.modified";
+
     pub const TERMINAL_CURSOR: &str = "terminal.cursor";
     pub const TERMINAL_BACKGROUND: &str = "terminal.background";
     pub const TERMINAL_FOREGROUND: &str = "terminal.foreground";
@@ -195,6 +204,66 @@ pub enum LoadThemeError {
     Read(std::io::Error),
 }
 
+/// The settings that are unset by default, so they aren't in the default
+/// settings, with the type of their value
+const OPTIONAL_SETTINGS: &[(&str, &str)] = &[
+    ("color-theme.high-contrast", "boolean"),
+    ("icon-theme.use-editor-color", "boolean"),
+];
+
+/// The settings a language can override, with values of their types, as
+/// the default settings have no languages
+const LANGUAGE_SETTINGS: &str = r#"
+tab-width = 4
+insert-spaces = true
+format-on-save = false
+trim-trailing-whitespace = false
+insert-final-newline = false
+rulers = []
+"#;
+
+/// The tables whose keys are chosen by the user, like the colors of a theme
+const OPEN_SETTINGS_TABLES: &[&str] = &[
+    "color-theme.syntax",
+    "color-theme.ui",
+    "icon-theme.ui",
+    "icon-theme.foldername",
+    "icon-theme.filename",
+    "icon-theme.extension",
+];
+
+/// A setting of a settings file that is left out when the file is loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The path of the setting, like `editor.font-size`, which is empty when
+    /// the file isn't valid TOML
+    pub key: String,
+    pub kind: ConfigErrorKind,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}: {}", self.key, self.kind)
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    #[error("the file is not valid TOML: {0}")]
+    Parse(String),
+    #[error("unknown setting")]
+    UnknownKey,
+    #[error("expected a {expected}, found a {found}")]
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
 pub struct LapceIcons {}
 
 impl LapceIcons {
@@ -334,6 +403,26 @@ pub struct CoreConfig {
         desc = "Enable customised titlebar and disable OS native one (Linux, BSD, Windows)"
     )]
     pub custom_titlebar: bool,
+    #[field_names(
+        desc = "The title of the window. The tokens {file}, {path}, {workspace}, {dirty} and {branch} are replaced with the active editor and workspace, and {separator} separates the parts which aren't empty."
+    )]
+    pub window_title: String,
+    #[field_names(
+        desc = "How long to wait for the next key of a keymap with multiple keys, like `ctrl+k ctrl+s`, in milliseconds. If 0, it waits until the next key is pressed."
+    )]
+    pub key_sequence_timeout: u64,
+    #[field_names(
+        desc = "How long to wait for more file changes before updating the explorer and the source control, in milliseconds"
+    )]
+    pub file_watcher_debounce_ms: u64,
+    #[field_names(
+        desc = "The releases to update to, \"stable\" or \"nightly\". If empty, the ones of the running version."
+    )]
+    pub update_channel: String,
+    #[field_names(
+        desc = "Replace the home directory with ~ in the diagnostics copied from the about dialog, so that they don't tell the user name"
+    )]
+    pub redact_diagnostics: bool,
 }
 
 #[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
@@ -353,6 +442,10 @@ pub struct EditorConfig {
     pub tab_width: usize,
     #[field_names(desc = "If opened editors are shown in a tab")]
     pub show_tab: bool,
+    #[field_names(
+        desc = "What to do with the tabs which don't fit in the tab bar.\nOptions: scroll, dropdown."
+    )]
+    pub tab_overflow: String,
     #[field_names(desc = "If navigation breadcrumbs are shown for the file")]
     pub show_bread_crumbs: bool,
     #[field_names(desc = "If the editor can scroll beyond the last line")]
@@ -365,6 +458,10 @@ pub struct EditorConfig {
         desc = "Show code context like functions and classes at the top of editor when scroll"
     )]
     pub sticky_header: bool,
+    #[field_names(
+        desc = "The maximum number of lines shown at the top of the editor for the code context. If 0, there's no limit."
+    )]
+    pub sticky_header_max_lines: usize,
     #[field_names(
         desc = "If the editor should show the documentation of the current completion item"
     )]
@@ -385,6 +482,10 @@ pub struct EditorConfig {
         desc = "How long (in ms) it should take before the hover information appears"
     )]
     pub hover_delay: u64,
+    #[field_names(
+        desc = "The maximum height of the hover information, after which it can be scrolled"
+    )]
+    pub hover_max_height: usize,
     #[field_names(
         desc = "If modal mode should have relative line numbers (though, not in insert mode)"
     )]
@@ -393,6 +494,26 @@ pub struct EditorConfig {
         desc = "Whether it should format the document on save (if there is an available formatter)"
     )]
     pub format_on_save: bool,
+    #[field_names(
+        desc = "How long (in ms) saving waits for the formatter, after which the document is saved without formatting"
+    )]
+    pub format_on_save_timeout: u64,
+    #[field_names(
+        desc = "Whether the whitespace at the end of lines is removed on save"
+    )]
+    pub trim_trailing_whitespace: bool,
+    #[field_names(
+        desc = "Whether trimming the trailing whitespace on save trims the lines of the cursors too, which are otherwise left as they are since the user may be typing on them"
+    )]
+    pub trim_cursor_line_whitespace: bool,
+    #[field_names(
+        desc = "Whether trimming the trailing whitespace on save keeps two or more spaces ending a line of markdown, which break the line"
+    )]
+    pub keep_markdown_hard_breaks: bool,
+    #[field_names(
+        desc = "Whether a line ending is added at the end of the file on save, if it doesn't end with one"
+    )]
+    pub insert_final_newline: bool,
 
     #[field_names(desc = "If matching brackets are highlighted")]
     pub highlight_matching_brackets: bool,
@@ -402,6 +523,12 @@ pub struct EditorConfig {
 
     #[field_names(desc = "If inlay hints should be displayed")]
     pub enable_inlay_hints: bool,
+    #[field_names(desc = "If the inlay hints of types should be displayed")]
+    pub inlay_hint_types: bool,
+    #[field_names(
+        desc = "If the inlay hints of parameter names should be displayed"
+    )]
+    pub inlay_hint_parameters: bool,
 
     #[field_names(
         desc = "Set the inlay hint font family. If empty, it uses the editor font family."
@@ -425,6 +552,10 @@ pub struct EditorConfig {
         desc = "Set the error lens font size. If 0 it uses the inlay hint font size."
     )]
     pub error_lens_font_size: usize,
+    #[field_names(
+        desc = "The least severe diagnostics that going to the next or previous diagnostic of a file stops at.\nOptions: error, warning, information, hint."
+    )]
+    pub diagnostic_navigation_severity: String,
     #[field_names(
         desc = "If the editor should display the completion item as phantom text"
     )]
@@ -449,6 +580,14 @@ pub struct EditorConfig {
         desc = "Whether the multiple cursor selection only selects whole words."
     )]
     pub multicursor_whole_words: bool,
+    #[field_names(
+        desc = "Whether selecting the next occurrence for multiple cursors continues from the start of the document once the end is reached."
+    )]
+    pub multicursor_wrap_around: bool,
+    #[field_names(
+        desc = "Whether splitting a selection into lines puts the cursors at the start of the lines instead of their end."
+    )]
+    pub split_selection_to_line_start: bool,
     #[field_names(
         desc = "How the editor should render whitespace characters.\nOptions: none, all, boundary, trailing."
     )]
@@ -467,6 +606,34 @@ pub struct EditorConfig {
         desc = "If enabled the cursor treats leading soft tabs as if they are hard tabs."
     )]
     pub atomic_soft_tabs: bool,
+    #[field_names(
+        desc = "If atomic soft tabs are enabled, backspace in the leading indentation deletes the spaces back to the previous tab stop, a whole soft tab when there are enough of them."
+    )]
+    pub atomic_soft_tabs_backspace: bool,
+    #[field_names(
+        desc = "If atomic soft tabs are enabled, the left and right arrows move over a whole soft tab."
+    )]
+    pub atomic_soft_tabs_arrows: bool,
+    #[field_names(
+        desc = "The columns at which vertical rulers are drawn, like [80, 100]"
+    )]
+    pub rulers: Vec<usize>,
+    #[field_names(
+        desc = "How long lines are soft wrapped.\nOptions: none, viewport, wrap-column."
+    )]
+    pub wrap_mode: String,
+    #[field_names(
+        desc = "The column lines are wrapped at when the wrap mode is wrap-column"
+    )]
+    pub wrap_column: usize,
+    #[field_names(
+        desc = "If the rows of a wrapped line are indented like the line itself"
+    )]
+    pub wrap_indent: bool,
+    #[field_names(
+        desc = "Make the word movements and word deletions of insert mode stop at camelCase humps and underscores inside words."
+    )]
+    pub subword_navigation: bool,
     #[field_names(desc = "Use double click to open interact with file explorer")]
     pub double_click: bool,
     #[field_names(desc = "Move the focus as you type in the global search box")]
@@ -503,6 +670,16 @@ pub fn inlay_hint_font_family(&self) -> FontFamily {
         }
     }
 
+    /// Whether the inlay hints of the kind are displayed, when inlay hints are.
+    /// The hints without a kind are always displayed.
+    pub fn inlay_hint_kind_enabled(&self, kind: Option<InlayHintKind>) -> bool {
+        match kind {
+            Some(InlayHintKind::TYPE) => self.inlay_hint_types,
+            Some(InlayHintKind::PARAMETER) => self.inlay_hint_parameters,
+            _ => true,
+        }
+    }
+
     pub fn inlay_hint_font_size(&self) -> usize {
         if self.inlay_hint_font_size < 5
             || self.inlay_hint_font_size > self.font_size
@@ -544,6 +721,58 @@ pub fn completion_lens_font_size(&self) -> usize {
             self.completion_lens_font_size
         }
     }
+
+    pub fn tab_overflow(&self) -> TabOverflow {
+        match self.tab_overflow.as_str() {
+            "dropdown" => TabOverflow::Dropdown,
+            _ => TabOverflow::Scroll,
+        }
+    }
+
+    pub fn diagnostic_navigation_severity(&self) -> DiagnosticSeverity {
+        match self.diagnostic_navigation_severity.as_str() {
+            "error" => DiagnosticSeverity::ERROR,
+            "information" => DiagnosticSeverity::INFORMATION,
+            "hint" => DiagnosticSeverity::HINT,
+            _ => DiagnosticSeverity::WARNING,
+        }
+    }
+
+    pub fn wrap_mode(&self) -> WrapMode {
+        match self.wrap_mode.as_str() {
+            "viewport" => WrapMode::Viewport,
+            "wrap-column" => WrapMode::Column(self.wrap_column),
+            _ => WrapMode::None,
+        }
+    }
+}
+
+/// The editor settings of a language, like `[language.go]`, which override
+/// the ones of `[editor]` when they are set
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct LanguageConfig {
+    pub tab_width: Option<usize>,
+    /// Whether to indent with spaces rather than tabs, instead of detecting
+    /// it from the document
+    pub insert_spaces: Option<bool>,
+    pub format_on_save: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub rulers: Option<Vec<usize>>,
+}
+
+/// A command that runs other commands in sequence, like
+/// `[macros.format-and-save]`
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct MacroConfig {
+    /// Shown in the palette instead of the name
+    pub description: Option<String>,
+    /// The names of the commands to run, as in the keymaps, which can be the
+    /// names of other macros
+    #[serde(default)]
+    pub steps: Vec<String>,
 }
 
 #[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
@@ -683,6 +912,268 @@ pub struct TerminalConfig {
     pub line_height: usize,
     #[field_names(desc = "Set the terminal Shell")]
     pub shell: String,
+    #[field_names(
+        desc = "Whether the terminals of a workspace are opened again when the workspace is reopened. They start in the directory their shell last reported with OSC 7, or else the one they were started in."
+    )]
+    pub restore_sessions: bool,
+    #[field_names(
+        desc = "The number of lines kept in the scrollback of a terminal, the oldest lines are dropped first. If 0, the scrollback is unbounded."
+    )]
+    pub scrollback_lines: usize,
+    #[field_names(
+        desc = "Whether the file paths and URLs in the terminal output are opened with Ctrl+click (Cmd+click on macOS) instead of a plain click."
+    )]
+    pub open_links_with_modifier: bool,
+    #[field_names(
+        desc = "Whether the lines of a multi-line selection run in the terminal are run one by one, instead of being pasted as a whole."
+    )]
+    pub run_selection_line_by_line: bool,
+}
+
+#[derive(FieldNames, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PaletteConfig {
+    #[field_names(
+        desc = "Remember how often and how recently commands were run across restarts, to rank them higher in the palette"
+    )]
+    pub remember_usage: bool,
+    #[field_names(
+        desc = "Whether the palette respects the case of the input. \"smart\" only respects it if the input has an uppercase character.\nOptions: smart, sensitive, insensitive."
+    )]
+    pub case_sensitivity: String,
+    #[field_names(
+        desc = "The weight of the bonus for matching right after a separator, like / or _"
+    )]
+    pub separator_bonus: f64,
+    #[field_names(
+        desc = "The weight of the bonus for matching the first character of the input"
+    )]
+    pub first_char_bonus: f64,
+    #[field_names(
+        desc = "The weight of the bonus for matching consecutive characters"
+    )]
+    pub consecutive_bonus: f64,
+}
+
+impl PaletteConfig {
+    /// `None` is smart case, which only respects case if the input has an
+    /// uppercase character
+    pub fn case_sensitive(&self) -> Option<bool> {
+        match self.case_sensitivity.as_str() {
+            "sensitive" => Some(true),
+            "insensitive" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PaletteConfig {
+    fn default() -> Self {
+        Self {
+            remember_usage: true,
+            case_sensitivity: "smart".to_string(),
+            separator_bonus: 1.0,
+            first_char_bonus: 1.0,
+            consecutive_bonus: 1.0,
+        }
+    }
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CompletionConfig {
+    #[field_names(
+        desc = "How long the input has to stay the same before completion items are requested for it, in milliseconds. If 0, they're requested on every key stroke."
+    )]
+    pub debounce_ms: u64,
+    #[field_names(
+        desc = "The kinds of the items hidden from the completion list, like \"snippet\" or \"keyword\""
+    )]
+    pub hidden_kinds: Vec<String>,
+    #[field_names(
+        desc = "The kinds of the items listed first when they match the input as well as the others, in this order. The kinds left out come after them."
+    )]
+    pub kind_order: Vec<String>,
+    #[field_names(
+        desc = "Whether typing one of the commit characters of the highlighted item, like ( for a function, accepts the item before the character is typed"
+    )]
+    pub commit_on_type: bool,
+}
+
+impl CompletionConfig {
+    /// Whether the items of the kind are left out of the completion list, the
+    /// items without a kind never are
+    pub fn is_kind_hidden(&self, kind: Option<CompletionItemKind>) -> bool {
+        kind.map(|kind| find_kind(&self.hidden_kinds, kind).is_some())
+            .unwrap_or(false)
+    }
+
+    /// Where the items of the kind come among the items which match the input
+    /// as well, the lowest first
+    pub fn kind_rank(&self, kind: Option<CompletionItemKind>) -> usize {
+        kind.and_then(|kind| find_kind(&self.kind_order, kind))
+            .unwrap_or(self.kind_order.len())
+    }
+}
+
+/// The index of the kind in the list of kind names, which are compared
+/// ignoring the case and whether they're written with `-` or `_`
+fn find_kind(names: &[String], kind: CompletionItemKind) -> Option<usize> {
+    let kind = completion_kind_name(kind)?;
+    names
+        .iter()
+        .position(|name| name.to_lowercase().replace('_', "-") == kind)
+}
+
+/// The name of the completion item kind in the settings, like `enum-member`
+pub fn completion_kind_name(kind: CompletionItemKind) -> Option<&'static str> {
+    let name = match kind {
+        CompletionItemKind::TEXT => "text",
+        CompletionItemKind::METHOD => "method",
+        CompletionItemKind::FUNCTION => "function",
+        CompletionItemKind::CONSTRUCTOR => "constructor",
+        CompletionItemKind::FIELD => "field",
+        CompletionItemKind::VARIABLE => "variable",
+        CompletionItemKind::CLASS => "class",
+        CompletionItemKind::INTERFACE => "interface",
+        CompletionItemKind::MODULE => "module",
+        CompletionItemKind::PROPERTY => "property",
+        CompletionItemKind::UNIT => "unit",
+        CompletionItemKind::VALUE => "value",
+        CompletionItemKind::ENUM => "enum",
+        CompletionItemKind::KEYWORD => "keyword",
+        CompletionItemKind::SNIPPET => "snippet",
+        CompletionItemKind::COLOR => "color",
+        CompletionItemKind::FILE => "file",
+        CompletionItemKind::REFERENCE => "reference",
+        CompletionItemKind::FOLDER => "folder",
+        CompletionItemKind::ENUM_MEMBER => "enum-member",
+        CompletionItemKind::CONSTANT => "constant",
+        CompletionItemKind::STRUCT => "struct",
+        CompletionItemKind::EVENT => "event",
+        CompletionItemKind::OPERATOR => "operator",
+        CompletionItemKind::TYPE_PARAMETER => "type-parameter",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SourceControlConfig {
+    #[field_names(
+        desc = "Show the git blame of the current line at the end of the line"
+    )]
+    pub inline_blame: bool,
+    #[field_names(
+        desc = "Whether going to the next or previous change continues from the other end of the document"
+    )]
+    pub wrap_change_navigation: bool,
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExplorerConfig {
+    #[field_names(
+        desc = "Globs of the files hidden from the file explorer. The ones without a / are matched against the file names, the others against the paths relative to the workspace."
+    )]
+    pub exclude: Vec<String>,
+    #[field_names(
+        desc = "Hide the files matched by .gitignore and the other ignore files from the file explorer"
+    )]
+    pub hide_gitignored: bool,
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct DocumentConfig {
+    #[field_names(
+        desc = "The size in MB above which files are opened in large file mode, without syntax highlighting, soft wrapping, folding and language servers. If 0, large file mode is never used."
+    )]
+    pub large_file_threshold_mb: usize,
+    #[field_names(
+        desc = "Reload the files changed outside of Lapce which have no unsaved changes, instead of asking"
+    )]
+    pub auto_reload: bool,
+}
+
+impl DocumentConfig {
+    /// The size in bytes above which files are opened in large file mode
+    pub fn large_file_threshold(&self) -> Option<usize> {
+        (self.large_file_threshold_mb > 0)
+            .then(|| self.large_file_threshold_mb.saturating_mul(1024 * 1024))
+    }
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct FindConfig {
+    #[field_names(
+        desc = "How many of the recent search strings are remembered for each workspace. If 0, they aren't remembered."
+    )]
+    pub history_size: usize,
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemoteConfig {
+    #[field_names(
+        desc = "The Lapce executable on the remote hosts to run as the proxy. If empty, Lapce installs the proxy itself."
+    )]
+    pub lapce_path: String,
+}
+
+impl RemoteConfig {
+    pub fn lapce_path(&self) -> Option<&str> {
+        (!self.lapce_path.is_empty()).then_some(self.lapce_path.as_str())
+    }
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct HistoryConfig {
+    #[field_names(desc = "The local history of file snapshots")]
+    pub local: LocalHistoryConfig,
+}
+
+#[derive(FieldNames, Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct LocalHistoryConfig {
+    #[field_names(desc = "Take snapshots of files on save and after edits")]
+    pub enabled: bool,
+    #[field_names(desc = "How many snapshots are kept for each file")]
+    pub max_entries: usize,
+    #[field_names(
+        desc = "How many days snapshots are kept for. If 0, they're kept regardless of their age."
+    )]
+    pub max_age_days: u64,
+    #[field_names(desc = "Files larger than this, in KiB, don't get snapshots")]
+    pub max_file_size: usize,
+    #[field_names(
+        desc = "How long the buffer has to stay unchanged after an edit before a snapshot of it is taken, in seconds"
+    )]
+    pub debounce_secs: u64,
+}
+
+impl LocalHistoryConfig {
+    /// The snapshot of the content of the file, if one should be taken
+    pub fn snapshot(
+        &self,
+        path: PathBuf,
+        content: String,
+        time: u64,
+    ) -> Option<LocalHistorySnapshot> {
+        if !self.enabled || content.len() > self.max_file_size * 1024 {
+            return None;
+        }
+        Some(LocalHistorySnapshot {
+            path,
+            time,
+            content,
+            max_entries: self.max_entries,
+            max_age: self.max_age_days * 60 * 60 * 24,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -805,24 +1296,34 @@ pub struct IconThemeConfig {
 }
 
 impl IconThemeConfig {
+    /// The icon of the file, by its name or else by its extension. The
+    /// extensions made of several parts are tried first, like `d.ts` before
+    /// `ts`, and the case of the extension only matters when both cases are in
+    /// the table. The settings add to the table of the icon theme, under
+    /// `[icon-theme.extension]`, with the icons relative to the theme or
+    /// absolute.
     pub fn resolve_path_to_icon(&self, path: &Path) -> Option<PathBuf> {
-        if let Some((_, icon)) = self.filename.get_key_value(
-            path.file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default(),
-        ) {
-            Some(self.path.join(icon))
-        } else if let Some((_, icon)) = self.extension.get_key_value(
-            path.extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default(),
-        ) {
-            Some(self.path.join(icon))
-        } else {
-            None
+        let file_name = path.file_name()?.to_str()?;
+        if let Some(icon) = self.filename.get(file_name) {
+            return Some(self.path.join(icon));
+        }
+
+        let extensions = file_name
+            .match_indices('.')
+            .map(|(i, _)| &file_name[i + 1..])
+            .filter(|extension| !extension.is_empty());
+        for extension in extensions {
+            let icon = self.extension.get(extension).or_else(|| {
+                self.extension
+                    .iter()
+                    .find(|(e, _)| e.eq_ignore_ascii_case(extension))
+                    .map(|(_, icon)| icon)
+            });
+            if let Some(icon) = icon {
+                return Some(self.path.join(icon));
+            }
         }
+        None
     }
 }
 
@@ -910,7 +1411,7 @@ pub struct DropdownInfo {
     pub items: im::Vector<String>,
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub struct LapceConfig {
     #[serde(skip)]
@@ -919,8 +1420,30 @@ pub struct LapceConfig {
     pub ui: UIConfig,
     pub editor: EditorConfig,
     pub terminal: TerminalConfig,
+    pub palette: PaletteConfig,
+    pub completion: CompletionConfig,
+    pub source_control: SourceControlConfig,
+    pub explorer: ExplorerConfig,
+    pub document: DocumentConfig,
+    pub find: FindConfig,
+    pub remote: RemoteConfig,
+    pub history: HistoryConfig,
     pub color_theme: ColorThemeConfig,
     pub icon_theme: IconThemeConfig,
+    /// The settings of languages by their id, like `go` or `python`
+    #[serde(default)]
+    pub language: HashMap<String, LanguageConfig>,
+    /// The command macros by their names, like `format-and-save`
+    #[serde(default)]
+    pub macros: HashMap<String, MacroConfig>,
+    /// The settings profiles by their names, like `[profile.writing.editor]`,
+    /// which are layered over the other settings when they're active
+    #[serde(default)]
+    pub profile: HashMap<String, serde_json::Value>,
+    /// The profile the settings were loaded with, which is the one of the
+    /// workspace
+    #[serde(skip)]
+    pub active_profile: Option<String>,
     #[serde(flatten)]
     pub plugins: HashMap<String, HashMap<String, serde_json::Value>>,
     #[serde(skip)]
@@ -965,7 +1488,44 @@ pub fn get_dropdown_info(&self, kind: &str, key: &str) -> Option<DropdownInfo> {
                     .unwrap_or(0),
                 items: self.icon_theme_list.clone(),
             }),
-            _ => None,
+            _ => {
+                let options = setting_schema(kind, key)?.options?;
+                let value = self.setting_value(kind, key)?;
+                Some(DropdownInfo {
+                    active_index: options
+                        .iter()
+                        .position(|option| Some(*option) == value.as_str())
+                        .unwrap_or(0),
+                    items: options.iter().map(|option| option.to_string()).collect(),
+                })
+            }
+        }
+    }
+
+    /// The values of the settings of a section of the settings file by their
+    /// keys, like the ones of `editor`
+    pub fn section_values(
+        &self,
+        kind: &str,
+    ) -> serde_json::Map<String, serde_json::Value> {
+        let value = match kind {
+            "core" => serde_json::to_value(&self.core),
+            "ui" => serde_json::to_value(&self.ui),
+            "editor" => serde_json::to_value(&self.editor),
+            "terminal" => serde_json::to_value(&self.terminal),
+            "palette" => serde_json::to_value(&self.palette),
+            "completion" => serde_json::to_value(&self.completion),
+            "source-control" => serde_json::to_value(&self.source_control),
+            "explorer" => serde_json::to_value(&self.explorer),
+            "document" => serde_json::to_value(&self.document),
+            "find" => serde_json::to_value(&self.find),
+            "remote" => serde_json::to_value(&self.remote),
+            "history.local" => serde_json::to_value(&self.history.local),
+            _ => return serde_json::Map::default(),
+        };
+        match value {
+            Ok(serde_json::Value::Object(values)) => values,
+            _ => serde_json::Map::default(),
         }
     }
 }
@@ -973,6 +1533,7 @@ pub fn get_dropdown_info(&self, kind: &str, key: &str) -> Option<DropdownInfo> {
 pub struct ConfigWatcher {
     event_sink: ExtEventSink,
     delay_handler: Arc<AtomicBool>,
+    config_dir: Option<PathBuf>,
 }
 
 impl ConfigWatcher {
@@ -980,13 +1541,30 @@ pub fn new(event_sink: ExtEventSink) -> Self {
         Self {
             event_sink,
             delay_handler: Arc::new(AtomicBool::new(false)),
+            config_dir: Directory::config_directory(),
+        }
+    }
+
+    /// The config directory is watched rather than the settings and keymaps
+    /// files, since editors often replace the files when saving them, which
+    /// ends the watching of the replaced file
+    fn is_config_path(&self, path: &Path) -> bool {
+        if path.parent() != self.config_dir.as_deref() {
+            return true;
         }
+        matches!(
+            path.file_name().and_then(|name| name.to_str()),
+            Some("settings.toml" | "keymaps.toml")
+        )
     }
 }
 
 impl notify::EventHandler for ConfigWatcher {
     fn handle_event(&mut self, event: notify::Result<notify::Event>) {
         if let Ok(event) = event {
+            if !event.paths.iter().any(|path| self.is_config_path(path)) {
+                return;
+            }
             match event.kind {
                 notify::EventKind::Create(_)
                 | notify::EventKind::Modify(_)
@@ -1024,11 +1602,16 @@ fn handle_event(&mut self, event: notify::Result<notify::Event>) {
 }
 
 impl LapceConfig {
-    pub fn load(workspace: &LapceWorkspace, disabled_volts: &[VoltID]) -> Self {
-        let config = Self::merge_config(workspace, None, None);
+    pub fn load(
+        workspace: &LapceWorkspace,
+        disabled_volts: &[VoltID],
+        profile: Option<&str>,
+    ) -> Self {
+        let config = Self::merge_config(workspace, profile, None, None);
         let mut lapce_config: LapceConfig = config
             .try_deserialize()
             .unwrap_or_else(|_| DEFAULT_LAPCE_CONFIG.clone());
+        lapce_config.active_profile = profile.map(|profile| profile.to_string());
 
         lapce_config.available_color_themes =
             Self::load_color_themes(disabled_volts);
@@ -1087,6 +1670,7 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
         if color_theme_config.is_some() || icon_theme_config.is_some() {
             if let Ok(new) = Self::merge_config(
                 workspace,
+                self.active_profile.as_deref(),
                 color_theme_config.cloned(),
                 icon_theme_config.cloned(),
             )
@@ -1108,11 +1692,15 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
         self.resolve_colors(Some(&default_lapce_config));
         self.default_color_theme = default_lapce_config.color_theme.clone();
         self.default_icon_theme = default_lapce_config.icon_theme.clone();
+        self.svg_store
+            .write()
+            .set_theme(&self.color_theme.name, &self.icon_theme.name);
         self.update_id();
     }
 
     fn merge_config(
         workspace: &LapceWorkspace,
+        profile: Option<&str>,
         color_theme_config: Option<config::Config>,
         icon_theme_config: Option<config::Config>,
     ) -> config::Config {
@@ -1133,32 +1721,167 @@ fn merge_config(
                 .unwrap_or_else(|_| config.clone());
         }
 
-        if let Some(path) = Self::settings_file() {
+        let settings: Vec<toml::value::Table> = Self::settings_files(workspace)
+            .iter()
+            .filter_map(|path| Self::valid_settings(path))
+            .collect();
+        Self::layer_settings(config, &settings, profile)
+    }
+
+    /// Layer the settings files over the config in order, and then the
+    /// settings the profile sets in each of them, so that the profile
+    /// overrides the other settings of all the files. The languages are
+    /// resolved over the result, so they override the profile in turn.
+    fn layer_settings(
+        mut config: config::Config,
+        settings: &[toml::value::Table],
+        profile: Option<&str>,
+    ) -> config::Config {
+        let profiles: Vec<&toml::value::Table> = profile
+            .map(|profile| {
+                settings
+                    .iter()
+                    .filter_map(|settings| profile_settings(settings, profile))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for settings in settings.iter().chain(profiles) {
+            let Ok(settings) =
+                toml::to_string(&toml::Value::Table(settings.clone()))
+            else {
+                continue;
+            };
             config = config::Config::builder()
                 .add_source(config.clone())
-                .add_source(config::File::from(path.as_path()).required(false))
+                .add_source(config::File::from_str(
+                    &settings,
+                    config::FileFormat::Toml,
+                ))
                 .build()
                 .unwrap_or_else(|_| config.clone());
         }
 
+        config
+    }
+
+    /// The names of the settings profiles, sorted, with the ones which are
+    /// only different by their case, like in two settings files, counted once
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profile.keys().cloned().collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        names
+    }
+
+    /// The settings files of the user and of the workspace, in the order
+    /// they override each other
+    fn settings_files(workspace: &LapceWorkspace) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Self::settings_file().into_iter().collect();
         match workspace.kind {
             LapceWorkspaceType::Local => {
                 if let Some(path) = workspace.path.as_ref() {
-                    let path = path.join("./.lapce/settings.toml");
-                    config = config::Config::builder()
-                        .add_source(config.clone())
-                        .add_source(
-                            config::File::from(path.as_path()).required(false),
-                        )
-                        .build()
-                        .unwrap_or_else(|_| config.clone());
+                    files.push(path.join("./.lapce/settings.toml"));
                 }
             }
             LapceWorkspaceType::RemoteSSH(_) => {}
             LapceWorkspaceType::RemoteWSL => {}
         }
+        files
+    }
 
-        config
+    /// The settings of the file without the invalid ones, which would
+    /// otherwise make the whole config fail to load
+    fn valid_settings(path: &Path) -> Option<toml::value::Table> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let (settings, _) = Self::validated_settings(&content);
+        Some(settings)
+    }
+
+    /// Check the settings against the default ones, returning the settings
+    /// that are unknown or whose value has the wrong type
+    pub fn validate(settings: &str) -> Vec<ConfigError> {
+        Self::validated_settings(settings).1
+    }
+
+    fn validated_settings(settings: &str) -> (toml::value::Table, Vec<ConfigError>) {
+        let mut errors = Vec::new();
+        let mut table: toml::value::Table = match toml::from_str(settings) {
+            Ok(table) => table,
+            Err(err) => {
+                errors.push(ConfigError {
+                    key: String::new(),
+                    kind: ConfigErrorKind::Parse(err.to_string()),
+                });
+                return (toml::value::Table::new(), errors);
+            }
+        };
+        let defaults = Self::settings_defaults(&table);
+        validate_settings_table("", &mut table, &defaults, &mut errors);
+        (table, errors)
+    }
+
+    /// The default settings the settings are checked against, with the
+    /// languages and the profiles they set, as there are none by default
+    fn settings_defaults(settings: &toml::value::Table) -> toml::value::Table {
+        let mut defaults: toml::value::Table =
+            toml::from_str(DEFAULT_SETTINGS).unwrap_or_default();
+        if let Some(toml::Value::Table(languages)) = settings.get("language") {
+            let language_defaults: toml::value::Table =
+                toml::from_str(LANGUAGE_SETTINGS).unwrap_or_default();
+            let languages = languages
+                .keys()
+                .map(|id| {
+                    (id.clone(), toml::Value::Table(language_defaults.clone()))
+                })
+                .collect();
+            defaults.insert("language".to_string(), toml::Value::Table(languages));
+        }
+        if let Some(toml::Value::Table(profiles)) = settings.get("profile") {
+            let profiles = profiles
+                .iter()
+                .map(|(name, profile)| {
+                    let mut profile_defaults = match profile {
+                        toml::Value::Table(profile) => {
+                            Self::settings_defaults(profile)
+                        }
+                        _ => toml::value::Table::new(),
+                    };
+                    // a profile can't have profiles of its own
+                    profile_defaults.remove("profile");
+                    (name.clone(), toml::Value::Table(profile_defaults))
+                })
+                .collect();
+            defaults.insert("profile".to_string(), toml::Value::Table(profiles));
+        }
+        defaults
+    }
+
+    /// The alert about the settings that were left out when loading the
+    /// settings files, as they are invalid
+    pub fn settings_alert(workspace: &LapceWorkspace) -> Option<AlertContentData> {
+        let mut msg = String::new();
+        for path in Self::settings_files(workspace) {
+            let errors = match std::fs::read_to_string(&path) {
+                Ok(content) => Self::validate(&content),
+                Err(_) => continue,
+            };
+            if errors.is_empty() {
+                continue;
+            }
+            msg.push_str(&format!("{}\n", path.display()));
+            for error in errors {
+                msg.push_str(&format!("  {error}\n"));
+            }
+        }
+        if msg.is_empty() {
+            return None;
+        }
+        Some(AlertContentData {
+            title: "Some settings are invalid and were not applied".to_string(),
+            msg: msg.trim_end().to_string(),
+            ..Default::default()
+        })
     }
 
     fn resolve_colors(&mut self, default_config: Option<&LapceConfig>) {
@@ -1494,6 +2217,17 @@ pub fn set_color_theme(
         }
     }
 
+    /// The color theme after the current one, in the order of the theme list,
+    /// wrapping around to the first one
+    pub fn next_color_theme(&self) -> Option<&str> {
+        let next = self
+            .color_theme_list
+            .index_of(&self.color_theme.name)
+            .map(|i| (i + 1) % self.color_theme_list.len())
+            .unwrap_or(0);
+        self.color_theme_list.get(next).map(|theme| theme.as_str())
+    }
+
     pub fn set_icon_theme(
         &mut self,
         workspace: &LapceWorkspace,
@@ -1701,16 +2435,77 @@ fn text_size_internal(
         text_layout.size()
     }
 
+    /// The overrides of the editor settings for the language, whose id is
+    /// matched like the language names of code blocks, so `Go` and `go` match
+    fn language_config(
+        &self,
+        language: Option<LapceLanguage>,
+    ) -> Option<&LanguageConfig> {
+        let language = language?;
+        self.language
+            .iter()
+            .find(|(id, _)| LapceLanguage::from_str(id).ok() == Some(language))
+            .map(|(_, config)| config)
+    }
+
+    /// The tab width of the language, which is the editor one unless the
+    /// language sets its own
+    pub fn tab_width_for(&self, language: Option<LapceLanguage>) -> usize {
+        self.language_config(language)
+            .and_then(|config| config.tab_width)
+            .unwrap_or(self.editor.tab_width)
+    }
+
+    /// Whether the language is indented with spaces, `None` when the
+    /// indentation is detected from the document
+    pub fn insert_spaces_for(
+        &self,
+        language: Option<LapceLanguage>,
+    ) -> Option<bool> {
+        self.language_config(language)
+            .and_then(|config| config.insert_spaces)
+    }
+
+    pub fn format_on_save_for(&self, language: Option<LapceLanguage>) -> bool {
+        self.language_config(language)
+            .and_then(|config| config.format_on_save)
+            .unwrap_or(self.editor.format_on_save)
+    }
+
+    pub fn trim_trailing_whitespace_for(
+        &self,
+        language: Option<LapceLanguage>,
+    ) -> bool {
+        self.language_config(language)
+            .and_then(|config| config.trim_trailing_whitespace)
+            .unwrap_or(self.editor.trim_trailing_whitespace)
+    }
+
+    pub fn insert_final_newline_for(&self, language: Option<LapceLanguage>) -> bool {
+        self.language_config(language)
+            .and_then(|config| config.insert_final_newline)
+            .unwrap_or(self.editor.insert_final_newline)
+    }
+
+    pub fn rulers_for(&self, language: Option<LapceLanguage>) -> &[usize] {
+        self.language_config(language)
+            .and_then(|config| config.rulers.as_deref())
+            .unwrap_or(&self.editor.rulers)
+    }
+
+    /// The width of a tab in the language, in pixels
     pub fn tab_width(
         &self,
         text: &mut PietText,
+        language: Option<LapceLanguage>,
         font_family: FontFamily,
         font_size: usize,
     ) -> f64 {
+        let tab_width = self.tab_width_for(language) as f64;
         {
             let info = self.tab_layout_info.read();
             if let Some(width) = info.get(&(font_family.clone(), font_size)) {
-                return self.editor.tab_width as f64 * *width;
+                return tab_width * *width;
             };
         }
 
@@ -1726,7 +2521,7 @@ pub fn tab_width(
         self.tab_layout_info
             .write()
             .insert((font_family, font_size), width);
-        self.editor.tab_width as f64 * width
+        tab_width * width
     }
 
     pub fn logo_svg(&self) -> Svg {
@@ -1745,6 +2540,20 @@ pub fn ui_svg(&self, icon: &'static str) -> Svg {
         })
     }
 
+    /// The icon of the icon theme with the color to paint it in. The icons
+    /// painted in a single color take the icon color of the color theme, unless
+    /// the icon theme sets whether its icons take it.
+    fn themed_svg(&self, path: &Path) -> Option<(Svg, Option<&Color>)> {
+        let color = self.get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE);
+        let (svg, tint) = self.svg_store.write().tinted(path, color)?;
+        let color = match self.icon_theme.use_editor_color {
+            Some(true) => Some(color),
+            Some(false) => None,
+            None => tint.map(|_| color),
+        };
+        Some((svg, color))
+    }
+
     pub fn folder_svg(&self, path: &Path) -> Option<(Svg, Option<&Color>)> {
         self.icon_theme
             .foldername
@@ -1754,32 +2563,16 @@ pub fn folder_svg(&self, path: &Path) -> Option<(Svg, Option<&Color>)> {
                     .to_str()
                     .unwrap_or_default(),
             )
-            .and_then(|(_, path)| {
-                let path = self.icon_theme.path.join(path);
-                self.svg_store.write().get_svg_on_disk(&path)
-            })
-            .map(|svg| {
-                let color = if self.icon_theme.use_editor_color.unwrap_or(false) {
-                    Some(self.get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE))
-                } else {
-                    None
-                };
-                (svg, color)
-            })
+            .and_then(|(_, path)| self.themed_svg(&self.icon_theme.path.join(path)))
     }
 
     pub fn file_svg(&self, path: &Path) -> (Svg, Option<&Color>) {
         let svg = self
             .icon_theme
             .resolve_path_to_icon(path)
-            .and_then(|p| self.svg_store.write().get_svg_on_disk(&p));
+            .and_then(|p| self.themed_svg(&p));
         if let Some(svg) = svg {
-            let color = if self.icon_theme.use_editor_color.unwrap_or(false) {
-                Some(self.get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE))
-            } else {
-                None
-            };
-            (svg, color)
+            svg
         } else {
             (
                 self.ui_svg(LapceIcons::FILE),
@@ -1874,3 +2667,473 @@ pub fn completion_svg(
         ))
     }
 }
+
+/// The settings the profile sets in the settings, whose name is matched
+/// ignoring the case
+fn profile_settings<'a>(
+    settings: &'a toml::value::Table,
+    profile: &str,
+) -> Option<&'a toml::value::Table> {
+    settings
+        .get("profile")?
+        .as_table()?
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(profile))?
+        .1
+        .as_table()
+}
+
+/// Check the settings of the table against the default ones, removing the
+/// invalid settings
+fn validate_settings_table(
+    prefix: &str,
+    table: &mut toml::value::Table,
+    defaults: &toml::value::Table,
+    errors: &mut Vec<ConfigError>,
+) {
+    let mut invalid = Vec::new();
+    for (name, value) in table.iter_mut() {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        let valid = match defaults.get(name) {
+            Some(toml::Value::Table(default_table)) => match value {
+                toml::Value::Table(table) => {
+                    validate_settings_table(&key, table, default_table, errors);
+                    true
+                }
+                _ => check_setting_type(key, value, "table", errors),
+            },
+            Some(default) => {
+                check_setting_type(key, value, default.type_str(), errors)
+            }
+            None => {
+                if let Some((_, expected)) = OPTIONAL_SETTINGS
+                    .iter()
+                    .find(|(optional, _)| *optional == key)
+                {
+                    check_setting_type(key, value, *expected, errors)
+                } else if OPEN_SETTINGS_TABLES.contains(&prefix) {
+                    check_setting_type(key, value, "string", errors)
+                } else if prefix.is_empty() && value.is_table() {
+                    // the tables Lapce doesn't know are the settings of plugins
+                    true
+                } else {
+                    errors.push(ConfigError {
+                        key,
+                        kind: ConfigErrorKind::UnknownKey,
+                    });
+                    false
+                }
+            }
+        };
+        if !valid {
+            invalid.push(name.clone());
+        }
+    }
+    for name in invalid {
+        table.remove(&name);
+    }
+}
+
+fn check_setting_type(
+    key: String,
+    value: &toml::Value,
+    expected: &'static str,
+    errors: &mut Vec<ConfigError>,
+) -> bool {
+    let found = value.type_str();
+    // whole numbers are fine for the settings that are floats
+    if found == expected || (expected == "float" && found == "integer") {
+        return true;
+    }
+    errors.push(ConfigError {
+        key,
+        kind: ConfigErrorKind::WrongType { expected, found },
+    });
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
+
+    use lapce_core::language::LapceLanguage;
+
+    use super::{
+        ConfigError, ConfigErrorKind, DocumentConfig, IconThemeConfig,
+        LanguageConfig, LapceConfig, LapceTheme, DEFAULT_CONFIG, DEFAULT_DARK_THEME,
+        DEFAULT_LAPCE_CONFIG, DEFAULT_LIGHT_THEME,
+    };
+    use crate::data::LapceWorkspace;
+
+    fn unknown(key: &str) -> ConfigError {
+        ConfigError {
+            key: key.to_string(),
+            kind: ConfigErrorKind::UnknownKey,
+        }
+    }
+
+    fn wrong_type(
+        key: &str,
+        expected: &'static str,
+        found: &'static str,
+    ) -> ConfigError {
+        ConfigError {
+            key: key.to_string(),
+            kind: ConfigErrorKind::WrongType { expected, found },
+        }
+    }
+
+    #[test]
+    fn test_validate_valid_settings() {
+        let settings = r##"
+            [core]
+            modal = true
+
+            [editor]
+            font-size = 14
+            line-height = 2
+
+            [palette]
+            case-sensitivity = "sensitive"
+
+            [color-theme.ui]
+            "editor.background" = "#000000"
+
+            [some-plugin]
+            anything = [1, 2]
+        "##;
+        assert_eq!(LapceConfig::validate(settings), Vec::new());
+    }
+
+    #[test]
+    fn test_validate_invalid_settings() {
+        let settings = r#"
+            modal = true
+
+            [core]
+            modal = "yes"
+            colour-theme = "Lapce Light"
+
+            [editor]
+            font-size = 14.5
+            line-height = 1.5
+
+            [ui]
+            font-family = 12
+
+            [color-theme.ui]
+            "editor.background" = 0
+
+            [history]
+            local = true
+        "#;
+        let mut errors = LapceConfig::validate(settings);
+        errors.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            errors,
+            vec![
+                wrong_type("color-theme.ui.editor.background", "string", "integer"),
+                unknown("core.colour-theme"),
+                wrong_type("core.modal", "boolean", "string"),
+                wrong_type("editor.font-size", "integer", "float"),
+                wrong_type("history.local", "table", "boolean"),
+                unknown("modal"),
+                wrong_type("ui.font-family", "string", "integer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_malformed_settings() {
+        let errors = LapceConfig::validate("[editor\nfont-size = 14");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].key, "");
+        assert!(matches!(errors[0].kind, ConfigErrorKind::Parse(_)));
+
+        let errors = LapceConfig::validate("[editor]\nfont-size = ");
+        assert!(matches!(
+            errors.as_slice(),
+            [ConfigError {
+                kind: ConfigErrorKind::Parse(_),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_invalid_settings_are_left_out() {
+        let (settings, errors) = LapceConfig::validated_settings(
+            "[editor]\nfont-size = \"big\"\ntab-width = 2",
+        );
+        assert_eq!(
+            errors,
+            vec![wrong_type("editor.font-size", "integer", "string")]
+        );
+        let editor = settings.get("editor").and_then(|e| e.as_table()).unwrap();
+        assert!(editor.get("font-size").is_none());
+        assert_eq!(
+            editor.get("tab-width").and_then(|w| w.as_integer()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_validate_language_settings() {
+        let settings = r#"
+            [language.go]
+            tab-width = "x"
+            insert-spaces = false
+            tabwidth = 8
+        "#;
+        let mut errors = LapceConfig::validate(settings);
+        errors.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            errors,
+            vec![
+                wrong_type("language.go.tab-width", "integer", "string"),
+                unknown("language.go.tabwidth"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_language_overrides() {
+        // The languages are behind features, so this is only checked in
+        // builds which have them
+        let (Ok(go), Ok(python)) = (
+            LapceLanguage::from_str("go"),
+            LapceLanguage::from_str("python"),
+        ) else {
+            return;
+        };
+
+        let mut config = LapceConfig::default();
+        config.editor.tab_width = 4;
+        config.editor.format_on_save = true;
+        config.editor.trim_trailing_whitespace = true;
+        config.editor.rulers = vec![100];
+        config.language.insert(
+            "Go".to_string(),
+            LanguageConfig {
+                tab_width: Some(8),
+                insert_spaces: Some(false),
+                trim_trailing_whitespace: Some(false),
+                insert_final_newline: Some(true),
+                ..Default::default()
+            },
+        );
+
+        // The language sets its own
+        assert_eq!(config.tab_width_for(Some(go)), 8);
+        assert_eq!(config.insert_spaces_for(Some(go)), Some(false));
+        assert!(!config.trim_trailing_whitespace_for(Some(go)));
+        assert!(config.insert_final_newline_for(Some(go)));
+        // The language leaves out the key, so it inherits the editor one
+        assert!(config.format_on_save_for(Some(go)));
+        assert_eq!(config.rulers_for(Some(go)), &[100]);
+        // Languages without overrides, or documents without a language,
+        // use the editor settings
+        for language in [Some(python), None] {
+            assert_eq!(config.tab_width_for(language), 4);
+            assert_eq!(config.insert_spaces_for(language), None);
+            assert!(config.format_on_save_for(language));
+            assert!(config.trim_trailing_whitespace_for(language));
+            assert!(!config.insert_final_newline_for(language));
+            assert_eq!(config.rulers_for(language), &[100]);
+        }
+    }
+
+    #[test]
+    fn test_validate_profile_settings() {
+        let settings = r#"
+            [profile.rust.editor]
+            font-size = "big"
+            tab-width = 2
+
+            [profile.rust]
+            unknown = 1
+
+            [profile.writing.language.markdown]
+            tab-width = 2
+            tabwidth = 2
+
+            [profile.writing.profile.nested.editor]
+            font-size = 20
+        "#;
+        let mut errors = LapceConfig::validate(settings);
+        errors.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            errors,
+            vec![
+                wrong_type("profile.rust.editor.font-size", "integer", "string"),
+                unknown("profile.rust.unknown"),
+                unknown("profile.writing.language.markdown.tabwidth"),
+                unknown("profile.writing.profile"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_precedence() {
+        let user = r#"
+            [editor]
+            font-size = 14
+            tab-width = 4
+            font-family = "Fira Code"
+
+            [language.go]
+            tab-width = 8
+
+            [profile.writing.editor]
+            font-size = 18
+            tab-width = 2
+        "#;
+        let workspace = r#"
+            [editor]
+            font-size = 15
+
+            [profile.Writing.ui]
+            font-size = 16
+        "#;
+        let settings = [user, workspace]
+            .map(|settings| LapceConfig::validated_settings(settings).0);
+        let load = |profile: Option<&str>| -> LapceConfig {
+            LapceConfig::layer_settings(DEFAULT_CONFIG.clone(), &settings, profile)
+                .try_deserialize()
+                .unwrap()
+        };
+
+        // The workspace settings override the user ones
+        let config = load(None);
+        assert_eq!(config.editor.font_size, 15);
+        assert_eq!(config.editor.tab_width, 4);
+        assert_eq!(config.ui.font_size, DEFAULT_LAPCE_CONFIG.ui.font_size);
+        assert_eq!(config.profile_names().len(), 1);
+
+        // The profile overrides the settings of both files, whatever the case
+        // of its name, and leaves the others as they are
+        let config = load(Some("writing"));
+        assert_eq!(config.editor.font_size, 18);
+        assert_eq!(config.editor.tab_width, 2);
+        assert_eq!(config.ui.font_size, 16);
+        assert_eq!(config.editor.font_family, "Fira Code");
+        // and the language overrides the profile
+        assert_eq!(config.language["go"].tab_width, Some(8));
+        if let Ok(go) = LapceLanguage::from_str("go") {
+            assert_eq!(config.tab_width_for(Some(go)), 8);
+        }
+        assert_eq!(config.tab_width_for(None), 2);
+
+        // A profile which isn't set anymore is the same as none
+        let config = load(Some("rust"));
+        assert_eq!(config.editor.font_size, 15);
+        assert_eq!(config.editor.tab_width, 4);
+    }
+
+    #[test]
+    fn test_switch_color_theme() {
+        let workspace = LapceWorkspace::default();
+        let mut config = LapceConfig::default();
+        for theme in [DEFAULT_LIGHT_THEME, DEFAULT_DARK_THEME] {
+            let (name, theme) =
+                LapceConfig::load_color_theme_from_str(theme).unwrap();
+            config
+                .available_color_themes
+                .insert(name.to_lowercase(), (name.clone(), theme));
+            config.color_theme_list.push_back(name);
+        }
+
+        config.set_color_theme(&workspace, "Lapce Light", true);
+        assert_eq!(config.color_theme.name, "Lapce Light");
+        let light_background = config
+            .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND)
+            .as_rgba_u32();
+        assert_eq!(config.next_color_theme(), Some("Lapce Dark"));
+
+        config.set_color_theme(&workspace, "Lapce Dark", true);
+        assert_eq!(config.color_theme.name, "Lapce Dark");
+        assert_ne!(
+            config
+                .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND)
+                .as_rgba_u32(),
+            light_background
+        );
+        // The cycle wraps around to the first theme
+        assert_eq!(config.next_color_theme(), Some("Lapce Light"));
+    }
+
+    #[test]
+    fn test_large_file_threshold() {
+        let config = DocumentConfig {
+            large_file_threshold_mb: 50,
+            ..Default::default()
+        };
+        assert_eq!(config.large_file_threshold(), Some(50 * 1024 * 1024));
+        // 0 turns the large file mode off
+        let config = DocumentConfig {
+            large_file_threshold_mb: 0,
+            ..Default::default()
+        };
+        assert_eq!(config.large_file_threshold(), None);
+
+        let settings = r#"
+            [document]
+            large-file-threshold-mb = 10
+        "#;
+        assert_eq!(LapceConfig::validate(settings), Vec::new());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_file_icon_by_extension() {
+        let table = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let theme = IconThemeConfig {
+            path: PathBuf::from("/themes/material"),
+            filename: table(&[("Cargo.toml", "cargo.svg")]),
+            extension: table(&[
+                ("rs", "rust.svg"),
+                ("ts", "typescript.svg"),
+                ("d.ts", "typescript-def.svg"),
+                ("md", "/home/user/icons/markdown.svg"),
+            ]),
+            ..Default::default()
+        };
+        let icon = |path: &str| theme.resolve_path_to_icon(Path::new(path));
+        assert_eq!(
+            icon("/src/main.rs"),
+            Some(PathBuf::from("/themes/material/rust.svg"))
+        );
+        assert_eq!(
+            icon("/Cargo.toml"),
+            Some(PathBuf::from("/themes/material/cargo.svg"))
+        );
+        // The longest extension first, ignoring the case
+        assert_eq!(
+            icon("/types/index.D.TS"),
+            Some(PathBuf::from("/themes/material/typescript-def.svg"))
+        );
+        assert_eq!(
+            icon("/src/index.ts"),
+            Some(PathBuf::from("/themes/material/typescript.svg"))
+        );
+        // The icons of the settings can be anywhere
+        assert_eq!(
+            icon("/README.md"),
+            Some(PathBuf::from("/home/user/icons/markdown.svg"))
+        );
+        assert_eq!(icon("/Makefile"), None);
+        assert_eq!(icon("/notes.txt"), None);
+    }
+}
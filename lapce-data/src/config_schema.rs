@@ -0,0 +1,405 @@
+use once_cell::sync::Lazy;
+use serde_json::Value;
+
+use crate::{
+    config::{
+        CompletionConfig, CoreConfig, DocumentConfig, EditorConfig, ExplorerConfig,
+        FindConfig, LapceConfig, LocalHistoryConfig, PaletteConfig, RemoteConfig,
+        SourceControlConfig, TerminalConfig, UIConfig, DEFAULT_LAPCE_CONFIG,
+    },
+    settings::SettingsValueKind,
+};
+
+/// The sections of the settings file which are edited in the settings UI, the
+/// nested ones with the keys of their parents, like `history.local`
+pub const SETTINGS_SECTIONS: [&str; 12] = [
+    "core",
+    "ui",
+    "editor",
+    "terminal",
+    "palette",
+    "completion",
+    "source-control",
+    "explorer",
+    "document",
+    "find",
+    "remote",
+    "history.local",
+];
+
+/// The sections shown together in the workbench settings, which are the ones
+/// without a page of their own
+pub fn workbench_sections() -> &'static [&'static str] {
+    &SETTINGS_SECTIONS[4..]
+}
+
+/// The values of the settings which can only be one of a few, by the section
+/// and the key of the setting
+const SETTINGS_OPTIONS: &[(&str, &str, &[&str])] = &[
+    ("core", "update-channel", &["", "stable", "nightly"]),
+    ("editor", "tab-overflow", &["scroll", "dropdown"]),
+    (
+        "editor",
+        "diagnostic-navigation-severity",
+        &["error", "warning", "information", "hint"],
+    ),
+    (
+        "editor",
+        "render-whitespace",
+        &["none", "all", "boundary", "trailing"],
+    ),
+    ("editor", "wrap-mode", &["none", "viewport", "wrap-column"]),
+    (
+        "palette",
+        "case-sensitivity",
+        &["smart", "sensitive", "insensitive"],
+    ),
+];
+
+static SETTINGS_SCHEMA: Lazy<Vec<SettingSchema>> = Lazy::new(build_schema);
+
+/// The description of a setting, which the settings UI renders with the
+/// editor for its kind of value
+#[derive(Clone, Debug)]
+pub struct SettingSchema {
+    /// The section of the settings file the setting is in, like `editor`
+    pub kind: &'static str,
+    /// The key of the setting in its section, in kebab case
+    pub key: String,
+    pub description: &'static str,
+    /// `None` for the settings which can only be edited in the settings file,
+    /// like the lists
+    pub value_kind: Option<SettingsValueKind>,
+    /// The values the setting can take, chosen from a dropdown
+    pub options: Option<&'static [&'static str]>,
+    pub default: Value,
+}
+
+impl SettingSchema {
+    /// Whether the setting is found by the search, which is when each word of
+    /// it is in the key or the description of the setting, ignoring the case
+    pub fn matches(&self, search: &str) -> bool {
+        setting_matches(search, self.kind, &self.key, self.description)
+    }
+
+    /// Whether the value is different from the default of the setting
+    pub fn is_modified(&self, value: &Value) -> bool {
+        setting_is_modified(value, &self.default)
+    }
+}
+
+/// The schema of all the settings of the settings UI, in the order of the
+/// sections and then of the fields of their config
+pub fn settings_schema() -> &'static [SettingSchema] {
+    &SETTINGS_SCHEMA
+}
+
+/// The schema of the setting in the section, if it's in the settings UI
+pub fn setting_schema(kind: &str, key: &str) -> Option<&'static SettingSchema> {
+    SETTINGS_SCHEMA
+        .iter()
+        .find(|schema| schema.kind == kind && schema.key == key)
+}
+
+/// Whether the search finds the setting, also used for the settings of the
+/// plugins which aren't in the schema
+pub fn setting_matches(
+    search: &str,
+    kind: &str,
+    key: &str,
+    description: &str,
+) -> bool {
+    let text = format!("{kind}.{key} {} {description}", key.replace('-', " "))
+        .to_lowercase();
+    search
+        .split_whitespace()
+        .all(|word| text.contains(&word.to_lowercase()))
+}
+
+/// Whether the value of a setting is different from its default, the numbers
+/// being compared whether they're integers or floats
+pub fn setting_is_modified(value: &Value, default: &Value) -> bool {
+    match (value.as_f64(), default.as_f64()) {
+        (Some(value), Some(default)) => value != default,
+        _ => value != default,
+    }
+}
+
+fn build_schema() -> Vec<SettingSchema> {
+    let mut schema = Vec::new();
+    for kind in SETTINGS_SECTIONS {
+        let mut defaults = DEFAULT_LAPCE_CONFIG.section_values(kind);
+        for (field, description) in section_fields(kind) {
+            let key = field.replace('_', "-");
+            let default = defaults.remove(&key).unwrap_or(Value::Null);
+            let value_kind = match &default {
+                Value::Bool(_) => Some(SettingsValueKind::Bool),
+                Value::Number(n) if n.is_f64() => Some(SettingsValueKind::Float),
+                Value::Number(_) => Some(SettingsValueKind::Integer),
+                Value::String(_) => Some(SettingsValueKind::String),
+                _ => None,
+            };
+            let options = SETTINGS_OPTIONS
+                .iter()
+                .find(|(k, o_key, _)| *k == kind && *o_key == key)
+                .map(|(_, _, options)| *options);
+            schema.push(SettingSchema {
+                kind,
+                key,
+                description,
+                value_kind,
+                options,
+                default,
+            });
+        }
+    }
+    schema
+}
+
+/// The fields of the config of the section with their descriptions
+fn section_fields(kind: &str) -> Vec<(&'static str, &'static str)> {
+    let (fields, descs) = match kind {
+        "core" => (&CoreConfig::FIELDS[..], &CoreConfig::DESCS[..]),
+        "ui" => (&UIConfig::FIELDS[..], &UIConfig::DESCS[..]),
+        "editor" => (&EditorConfig::FIELDS[..], &EditorConfig::DESCS[..]),
+        "terminal" => (&TerminalConfig::FIELDS[..], &TerminalConfig::DESCS[..]),
+        "palette" => (&PaletteConfig::FIELDS[..], &PaletteConfig::DESCS[..]),
+        "completion" => {
+            (&CompletionConfig::FIELDS[..], &CompletionConfig::DESCS[..])
+        }
+        "source-control" => (
+            &SourceControlConfig::FIELDS[..],
+            &SourceControlConfig::DESCS[..],
+        ),
+        "explorer" => (&ExplorerConfig::FIELDS[..], &ExplorerConfig::DESCS[..]),
+        "document" => (&DocumentConfig::FIELDS[..], &DocumentConfig::DESCS[..]),
+        "find" => (&FindConfig::FIELDS[..], &FindConfig::DESCS[..]),
+        "remote" => (&RemoteConfig::FIELDS[..], &RemoteConfig::DESCS[..]),
+        "history.local" => (
+            &LocalHistoryConfig::FIELDS[..],
+            &LocalHistoryConfig::DESCS[..],
+        ),
+        _ => return Vec::new(),
+    };
+    fields.iter().copied().zip(descs.iter().copied()).collect()
+}
+
+impl LapceConfig {
+    /// The current value of a setting of the settings UI, or of a plugin when
+    /// the section is the name of the plugin and the user has set it
+    pub fn setting_value(&self, kind: &str, key: &str) -> Option<Value> {
+        if SETTINGS_SECTIONS.contains(&kind) {
+            self.section_values(kind).remove(key)
+        } else {
+            self.plugins.get(kind)?.get(key).cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use serde_json::Value;
+    use toml_edit::easy as toml;
+
+    use super::{
+        setting_schema, settings_schema, SETTINGS_OPTIONS, SETTINGS_SECTIONS,
+    };
+    use crate::{config::DEFAULT_LAPCE_CONFIG, settings::SettingsValueKind};
+
+    /// The sections of the settings, found from the fields of the config. The
+    /// tables with only tables in them, like `history`, aren't sections, but
+    /// their tables are.
+    fn config_sections() -> BTreeSet<String> {
+        fn push(sections: &mut BTreeSet<String>, section: String, value: &Value) {
+            let table = match value.as_object() {
+                Some(table) => table,
+                None => return,
+            };
+            if table.values().any(|value| !value.is_object()) {
+                sections.insert(section);
+                return;
+            }
+            for (key, value) in table {
+                push(sections, format!("{section}.{key}"), value);
+            }
+        }
+
+        let config = serde_json::to_value(&*DEFAULT_LAPCE_CONFIG).unwrap();
+        let mut sections = BTreeSet::new();
+        for (key, value) in config.as_object().unwrap() {
+            // The themes have settings pages of their own
+            if key != "color-theme" && key != "icon-theme" {
+                push(&mut sections, key.clone(), value);
+            }
+        }
+        sections
+    }
+
+    #[test]
+    fn test_schema_covers_every_section() {
+        let sections: BTreeSet<String> =
+            SETTINGS_SECTIONS.iter().map(|s| s.to_string()).collect();
+        assert_eq!(sections, config_sections());
+    }
+
+    #[test]
+    fn test_schema_covers_every_field() {
+        let defaults: toml::value::Table =
+            toml::from_str(include_str!("../../defaults/settings.toml")).unwrap();
+        for kind in SETTINGS_SECTIONS {
+            let schema_keys: BTreeSet<String> = settings_schema()
+                .iter()
+                .filter(|schema| schema.kind == kind)
+                .map(|schema| schema.key.clone())
+                .collect();
+            // Each field of the config has a schema entry, and each entry is a
+            // field of the config
+            let config_keys: BTreeSet<String> = DEFAULT_LAPCE_CONFIG
+                .section_values(kind)
+                .keys()
+                .cloned()
+                .collect();
+            assert_eq!(schema_keys, config_keys, "[{kind}]");
+            // and a setting of the default settings
+            let default_table = kind
+                .split('.')
+                .fold(&defaults, |table, key| table[key].as_table().unwrap());
+            let default_keys: BTreeSet<String> =
+                default_table.keys().cloned().collect();
+            assert_eq!(schema_keys, default_keys, "[{kind}]");
+        }
+    }
+
+    #[test]
+    fn test_schema_entries() {
+        for schema in settings_schema() {
+            let name = format!("{}.{}", schema.kind, schema.key);
+            assert!(!schema.description.is_empty(), "{name}");
+            assert!(!schema.default.is_null(), "{name}");
+            if let Some(options) = schema.options {
+                assert_eq!(
+                    schema.value_kind,
+                    Some(SettingsValueKind::String),
+                    "{name}"
+                );
+                assert!(
+                    options.contains(&schema.default.as_str().unwrap()),
+                    "{name}"
+                );
+            }
+        }
+        // No options for a setting which doesn't exist
+        for (kind, key, _) in SETTINGS_OPTIONS {
+            assert!(setting_schema(kind, key).is_some(), "{kind}.{key}");
+        }
+        // and the same options as the settings file schema
+        assert_eq!(
+            json_schema_options(),
+            SETTINGS_OPTIONS
+                .iter()
+                .map(|(kind, key, options)| {
+                    (
+                        format!("{kind}.{key}"),
+                        options.iter().map(|o| o.to_string()).collect(),
+                    )
+                })
+                .collect()
+        );
+
+        let schema = setting_schema("editor", "line-height").unwrap();
+        assert_eq!(schema.value_kind, Some(SettingsValueKind::Float));
+        let schema = setting_schema("editor", "font-size").unwrap();
+        assert_eq!(schema.value_kind, Some(SettingsValueKind::Integer));
+        assert_eq!(schema.default, serde_json::json!(13));
+        let schema = setting_schema("editor", "wrap-mode").unwrap();
+        assert_eq!(schema.options.unwrap().len(), 3);
+        // The lists are only edited in the settings file
+        let schema = setting_schema("editor", "rulers").unwrap();
+        assert_eq!(schema.value_kind, None);
+        assert!(setting_schema("editor", "unknown").is_none());
+    }
+
+    /// The settings which can only be one of a few values in the schema of the
+    /// settings file, with the values
+    fn json_schema_options() -> BTreeSet<(String, Vec<String>)> {
+        fn push(
+            options: &mut BTreeSet<(String, Vec<String>)>,
+            definitions: &Value,
+            section: &str,
+            definition: &Value,
+        ) {
+            let properties = match definition["properties"].as_object() {
+                Some(properties) => properties,
+                None => return,
+            };
+            for (key, property) in properties {
+                let name = if section.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{section}.{key}")
+                };
+                if let Some(values) = property["enum"].as_array() {
+                    let values = values
+                        .iter()
+                        .map(|v| v.as_str().unwrap().to_string())
+                        .collect();
+                    options.insert((name, values));
+                } else if let Some(reference) = property["$ref"]
+                    .as_str()
+                    .and_then(|r| r.strip_prefix("#/definitions/"))
+                {
+                    push(options, definitions, &name, &definitions[reference]);
+                }
+            }
+        }
+
+        let schema: Value =
+            serde_json::from_str(include_str!("../../extra/schemas/settings.json"))
+                .unwrap();
+        let mut options = BTreeSet::new();
+        push(
+            &mut options,
+            &schema["definitions"],
+            "",
+            &schema["definitions"]["Settings"],
+        );
+        options
+    }
+
+    #[test]
+    fn test_search_settings() {
+        let found = |search: &str| {
+            settings_schema()
+                .iter()
+                .filter(|schema| schema.matches(search))
+                .map(|schema| format!("{}.{}", schema.kind, schema.key))
+                .collect::<Vec<_>>()
+        };
+        let font_size = found("Font Size");
+        for setting in ["ui.font-size", "editor.font-size", "terminal.font-size"] {
+            assert!(font_size.contains(&setting.to_string()), "{setting}");
+        }
+        assert!(!font_size.contains(&"editor.font-family".to_string()));
+        assert_eq!(found("terminal.shell"), vec!["terminal.shell"]);
+        // The description is searched too
+        assert!(
+            found("scrollback").contains(&"terminal.scrollback-lines".to_string())
+        );
+        assert!(found("no such setting").is_empty());
+        assert_eq!(found("").len(), settings_schema().len());
+    }
+
+    #[test]
+    fn test_modified_from_default() {
+        let schema = setting_schema("editor", "line-height").unwrap();
+        assert!(!schema.is_modified(&serde_json::json!(1.5)));
+        assert!(schema.is_modified(&serde_json::json!(2)));
+        let schema = setting_schema("editor", "font-size").unwrap();
+        assert!(!schema.is_modified(&serde_json::json!(13.0)));
+        let schema = setting_schema("core", "modal").unwrap();
+        assert!(schema.is_modified(&serde_json::json!(true)));
+        assert!(!schema.is_modified(&serde_json::json!(false)));
+    }
+}
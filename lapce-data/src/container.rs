@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use druid::{Point, Size};
 
 pub struct ChildState {
@@ -5,3 +7,74 @@ pub struct ChildState {
     pub size: Option<Size>,
     pub hidden: bool,
 }
+
+/// What the tab strip of an editor tab does with the tabs which don't fit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TabOverflow {
+    /// The tabs scroll horizontally
+    Scroll,
+    /// Only the tabs which fit are shown, and a dropdown lists all of them
+    Dropdown,
+}
+
+/// The tabs shown in the width, given the width of each tab. They are the first
+/// ones which fit, or the ones ending with the active tab if it's past them.
+/// The active tab is always shown, even if it's wider than the width.
+pub fn visible_tabs(widths: &[f64], available: f64, active: usize) -> Range<usize> {
+    if widths.is_empty() {
+        return 0..0;
+    }
+    let active = active.min(widths.len() - 1);
+
+    let mut end = 0;
+    let mut total = 0.0;
+    while end < widths.len() && total + widths[end] <= available {
+        total += widths[end];
+        end += 1;
+    }
+    if active < end {
+        return 0..end;
+    }
+
+    let mut start = active;
+    let mut total = widths[active];
+    while start > 0 && total + widths[start - 1] <= available {
+        total += widths[start - 1];
+        start -= 1;
+    }
+    start..active + 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::visible_tabs;
+
+    #[test]
+    fn test_all_tabs_fit() {
+        assert_eq!(visible_tabs(&[100.0, 100.0, 100.0], 300.0, 2), 0..3);
+        assert_eq!(visible_tabs(&[100.0, 100.0, 100.0], 1000.0, 0), 0..3);
+        assert_eq!(visible_tabs(&[], 300.0, 0), 0..0);
+    }
+
+    #[test]
+    fn test_overflowing_tabs() {
+        let widths = [100.0, 120.0, 80.0, 150.0, 100.0];
+        // The first tabs are shown while the active one is among them
+        assert_eq!(visible_tabs(&widths, 310.0, 0), 0..3);
+        assert_eq!(visible_tabs(&widths, 310.0, 2), 0..3);
+        // Otherwise the tabs shown end with the active one
+        assert_eq!(visible_tabs(&widths, 310.0, 3), 2..4);
+        assert_eq!(visible_tabs(&widths, 310.0, 4), 3..5);
+    }
+
+    #[test]
+    fn test_narrow_width() {
+        let widths = [100.0, 120.0, 80.0];
+        // The active tab is shown even if it doesn't fit
+        assert_eq!(visible_tabs(&widths, 50.0, 1), 1..2);
+        assert_eq!(visible_tabs(&widths, 0.0, 0), 0..1);
+        // An active index past the tabs, while one is being closed, shows
+        // the last tab
+        assert_eq!(visible_tabs(&widths, 200.0, 5), 2..3);
+    }
+}
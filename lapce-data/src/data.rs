@@ -5,85 +5,108 @@
     env,
     fmt::Display,
     io::{BufReader, Read, Write},
+    ops::Range,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use druid::{
     piet::PietText, theme, Command, Data, Env, EventCtx, ExtEventSink,
-    FileDialogOptions, Lens, Point, Rect, Size, Target, Vec2, WidgetId, WindowId,
+    FileDialogOptions, FileSpec, Lens, Modifiers, Point, Rect, Size, Target, Vec2,
+    WidgetId, WindowId,
 };
 use im::Vector;
 use itertools::Itertools;
 use lapce_core::{
-    command::{FocusCommand, MultiSelectionCommand},
+    command::{FocusCommand, MultiSelectionCommand, SourceControlCommand},
     cursor::{Cursor, CursorMode},
     directory::Directory,
     editor::EditType,
-    meta,
-    mode::{Mode, MotionMode},
+    fold::Folds,
+    language::LapceLanguage,
+    line_ending::LineEnding,
+    mode::{Mode, MotionMode, VisualMode},
     movement::Movement,
     register::Register,
     selection::Selection,
+    whitespace::WhitespaceCleanup,
+    wrap::WrapMode,
 };
 use lapce_rpc::{
     buffer::BufferId,
     core::{CoreMessage, CoreNotification},
-    plugin::{VoltID, VoltInfo},
-    proxy::ProxyResponse,
+    file::{moved_path, FileLocation, FileNodeItem, FileOperation, FileSelection},
+    plugin::{VoltCapabilities, VoltID, VoltInfo, VoltPermissions},
+    proxy::{ProxyNotification, ProxyResponse},
     source_control::FileDiff,
     terminal::TermId,
     RpcMessage,
 };
 use lapce_xi_rope::{Rope, RopeDelta};
-use lsp_types::{Diagnostic, DiagnosticSeverity, Position, ProgressToken, TextEdit};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, MessageType, Position, ProgressToken, TextEdit,
+    Url, WorkspaceEdit,
+};
 use notify::Watcher;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{
-    about::AboutData,
-    alert::{AlertContentData, AlertData},
+    about::{AboutData, Diagnostics},
+    alert::{AlertButton, AlertContentData, AlertData},
+    cli::{parse_urls, selection_region, split_path_objects, PathObject},
     command::{
         CommandKind, EnsureVisiblePosition, InitBufferContentCb, LapceCommand,
-        LapceUICommand, LapceWorkbenchCommand, LAPCE_COMMAND, LAPCE_OPEN_FILE,
+        LapceUICommand, LapceWorkbenchCommand, MacroStepTarget, LAPCE_COMMAND,
+        LAPCE_EXPORT_STATE, LAPCE_IMPORT_KEYMAPS, LAPCE_IMPORT_STATE,
+        LAPCE_IMPORT_STATE_REPLACE, LAPCE_INSTALL_PLUGIN_FROM_FILE, LAPCE_OPEN_FILE,
         LAPCE_OPEN_FOLDER, LAPCE_UI_COMMAND,
     },
-    completion::CompletionData,
+    completion::{ActiveSnippet, CompletionData},
     config::{ConfigWatcher, GetConfig, LapceConfig, LapceTheme},
     db::{
-        EditorInfo, EditorTabChildInfo, EditorTabInfo, LapceDb, SplitContentInfo,
-        SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
+        EditorInfo, EditorTabChildInfo, EditorTabInfo, ImportMode, LapceDb,
+        SplitContentInfo, SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
     },
     document::{BufferContent, Document, LocalBufferKind},
-    editor::{EditorLocation, EditorPosition, LapceEditorBufferData, Line, TabRect},
-    explorer::FileExplorerData,
-    find::Find,
+    document_color::{ColorPickerData, ColorPresentationEdit},
+    editor::{
+        apply_edit, workspace_edits, EditorLocation, EditorPosition,
+        LapceEditorBufferData, Line, LineCol, TabRect,
+    },
+    explorer::{ExplorerFilter, FileExplorerData},
+    find::{Find, FindHistory},
+    format::FormattingError,
     hover::HoverData,
     images::ImageCache,
-    keypress::KeyPressData,
-    palette::{PaletteData, PaletteType, PaletteViewData},
+    keypress::{KeyPressData, KeyPressFocus},
+    local_history,
+    palette::{PaletteData, PaletteFrecency, PaletteType, PaletteViewData},
     panel::{
-        PanelContainerPosition, PanelData, PanelKind, PanelOrder, PanelPosition,
+        PanelBadge, PanelBadgeKind, PanelContainerPosition, PanelData, PanelKind,
+        PanelOrder, PanelPosition,
     },
-    picker::FilePickerData,
+    peek::{PeekData, PEEK_LINES},
+    picker::{split_partial, FilePickerData, PickerKind},
     plugin::PluginData,
-    problem::ProblemData,
-    proxy::{LapceProxy, ProxyStatus, TermEvent},
+    problem::{problem_badge, ProblemCodeAction, ProblemData},
+    proxy::{LapceProxy, ProxyStatus, TermEvent, WorkspaceEditReply},
     rename::RenameData,
     search::SearchData,
+    selection_range::SelectionRangeStack,
     settings::LapceSettingsPanelData,
     signature::SignatureData,
     source_control::SourceControlData,
-    split::{SplitDirection, SplitMoveDirection},
+    split::{SplitCommand, SplitDirection, SplitMoveDirection},
     terminal::TerminalPanelData,
     title::TitleData,
-    update::ReleaseInfo,
+    update::{ReleaseInfo, UpdateStatus},
 };
 
 /// `LapceData` is the topmost structure in a tree of structures that holds
@@ -104,8 +127,8 @@ pub struct LapceData {
     pub panel_orders: PanelOrder,
     /// The latest release information
     pub latest_release: Arc<Option<ReleaseInfo>>,
-    /// whether the update is in progress
-    pub update_in_process: bool,
+    /// The step of the update in progress
+    pub update_status: Option<UpdateStatus>,
     /// log file path
     #[data(ignore)]
     pub log_file: Arc<Option<PathBuf>>,
@@ -118,7 +141,7 @@ impl LapceData {
     /// previously written to the Lapce database.
     pub fn load(
         event_sink: ExtEventSink,
-        paths: Vec<PathBuf>,
+        paths: Vec<PathObject>,
         log_file: Option<PathBuf>,
     ) -> Self {
         let _ = lapce_proxy::register_lapce_path();
@@ -126,15 +149,15 @@ pub fn load(
         let log_file = Arc::new(log_file);
         let db = Arc::new(LapceDb::new().unwrap());
         let mut windows = im::HashMap::new();
-        let config = LapceConfig::load(&LapceWorkspace::default(), &[]);
+        let config = LapceConfig::load(&LapceWorkspace::default(), &[], None);
         let keypress = Arc::new(KeyPressData::new(&config, event_sink.clone()));
         let panel_orders = db
             .get_panel_orders()
+            .map(Self::add_missing_panels)
             .unwrap_or_else(|_| Self::default_panel_orders());
         let latest_release = Arc::new(None);
 
-        let dirs: Vec<&PathBuf> = paths.iter().filter(|p| p.is_dir()).collect();
-        let files: Vec<&PathBuf> = paths.iter().filter(|p| p.is_file()).collect();
+        let (dirs, files, urls) = split_path_objects(paths);
         if !dirs.is_empty() {
             let (size, mut pos) = db
                 .get_last_window_info()
@@ -162,7 +185,7 @@ pub fn load(
                         active_tab: 0,
                         workspaces: vec![LapceWorkspace {
                             kind: workspace_type,
-                            path: Some(dir.to_path_buf()),
+                            path: Some(dir),
                             last_open: 0,
                         }],
                     },
@@ -232,17 +255,26 @@ pub fn load(
             for file in files {
                 let _ = event_sink.submit_command(
                     LAPCE_UI_COMMAND,
-                    LapceUICommand::OpenFile(file.to_path_buf(), false),
+                    LapceUICommand::OpenFileLocation(file),
+                    Target::Window(*window_id),
+                );
+            }
+            for url in urls {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenUrlDocument(url),
                     Target::Window(*window_id),
                 );
             }
         }
 
         #[cfg(feature = "updater")]
+        if let Some(channel) =
+            crate::update::UpdateChannel::from_config(&config.core.update_channel)
         {
             let local_event_sink = event_sink.clone();
             std::thread::spawn(move || loop {
-                if let Ok(release) = crate::update::get_latest_release() {
+                if let Ok(release) = crate::update::get_latest_release(channel) {
                     let _ = local_event_sink.submit_command(
                         LAPCE_UI_COMMAND,
                         LapceUICommand::UpdateLatestRelease(release),
@@ -271,7 +303,7 @@ pub fn load(
             db,
             panel_orders,
             latest_release,
-            update_in_process: false,
+            update_status: None,
             log_file,
         }
     }
@@ -288,12 +320,30 @@ pub fn default_panel_orders() -> PanelOrder {
         );
         order.insert(
             PanelPosition::BottomLeft,
-            im::vector![PanelKind::Terminal, PanelKind::Search, PanelKind::Problem,],
+            im::vector![
+                PanelKind::Terminal,
+                PanelKind::Search,
+                PanelKind::Problem,
+                PanelKind::RenamePreview,
+            ],
         );
 
         order
     }
 
+    /// Put the panels that didn't exist yet when the order was saved where they
+    /// are by default
+    fn add_missing_panels(mut order: PanelOrder) -> PanelOrder {
+        for (position, kinds) in Self::default_panel_orders() {
+            for kind in kinds {
+                if !order.values().any(|kinds| kinds.contains(&kind)) {
+                    order.entry(position).or_default().push_back(kind);
+                }
+            }
+        }
+        order
+    }
+
     pub fn reload_env(&self, env: &mut Env) {
         env.set(theme::SCROLLBAR_WIDTH, 10.0);
         env.set(theme::SCROLLBAR_EDGE_WIDTH, 0.0);
@@ -324,6 +374,7 @@ fn listen_local_socket(event_sink: ExtEventSink) -> Result<()> {
                         window_tab_id,
                         folders,
                         files,
+                        urls,
                     }) = msg
                     {
                         let window_tab_id =
@@ -339,6 +390,7 @@ fn listen_local_socket(event_sink: ExtEventSink) -> Result<()> {
                                 window_tab_id,
                                 folders,
                                 files,
+                                urls: parse_urls(&urls),
                             },
                             Target::Global,
                         );
@@ -355,18 +407,18 @@ fn listen_local_socket(event_sink: ExtEventSink) -> Result<()> {
         Ok(())
     }
 
-    pub fn try_open_in_existing_process(paths: &[PathBuf]) -> Result<()> {
+    pub fn try_open_in_existing_process(paths: &[PathObject]) -> Result<()> {
         let local_socket = Directory::local_socket()
             .ok_or_else(|| anyhow!("can't get local socket folder"))?;
         let mut socket =
             interprocess::local_socket::LocalSocketStream::connect(local_socket)?;
-        let folders: Vec<_> = paths.iter().filter(|p| p.is_dir()).cloned().collect();
-        let files: Vec<_> = paths.iter().filter(|p| p.is_file()).cloned().collect();
+        let (folders, files, urls) = split_path_objects(paths.to_vec());
         let msg: CoreMessage =
             RpcMessage::Notification(CoreNotification::OpenPaths {
                 window_tab_id: None,
                 folders,
                 files,
+                urls: urls.into_iter().map(String::from).collect(),
             });
         lapce_rpc::stdio::write_msg(&mut socket, msg)?;
 
@@ -432,7 +484,7 @@ pub struct LapceWindowData {
     pub pos: Point,
     pub panel_orders: PanelOrder,
     pub latest_release: Arc<Option<ReleaseInfo>>,
-    pub update_in_progress: bool,
+    pub update_status: Option<UpdateStatus>,
     #[data(ignore)]
     pub log_file: Arc<Option<PathBuf>>,
 }
@@ -442,7 +494,7 @@ impl LapceWindowData {
     pub fn new(
         keypress: Arc<KeyPressData>,
         latest_release: Arc<Option<ReleaseInfo>>,
-        update_in_progress: bool,
+        update_status: Option<UpdateStatus>,
         log_file: Arc<Option<PathBuf>>,
         current_panels: Option<PanelData>,
         panel_orders: PanelOrder,
@@ -465,7 +517,7 @@ pub fn new(
                 db.clone(),
                 keypress.clone(),
                 latest_release.clone(),
-                update_in_progress,
+                update_status,
                 log_file.clone(),
                 current_panels.clone(),
                 panel_orders.clone(),
@@ -488,7 +540,7 @@ pub fn new(
                 db.clone(),
                 keypress.clone(),
                 latest_release.clone(),
-                update_in_progress,
+                update_status,
                 log_file.clone(),
                 current_panels,
                 panel_orders.clone(),
@@ -506,6 +558,7 @@ pub fn new(
                 last_open: 0,
             },
             &[],
+            None,
         ));
         let _ = event_sink.submit_command(
             LAPCE_UI_COMMAND,
@@ -515,15 +568,13 @@ pub fn new(
 
         let mut watcher =
             notify::recommended_watcher(ConfigWatcher::new(event_sink)).unwrap();
-        if let Some(path) = LapceConfig::settings_file() {
-            let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
+        // The directory of the settings and keymaps files
+        if let Some(path) = Directory::config_directory() {
+            let _ = watcher.watch(&path, notify::RecursiveMode::NonRecursive);
         }
         if let Some(path) = Directory::themes_directory() {
             let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
         }
-        if let Some(path) = LapceConfig::keymaps_file() {
-            let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
-        }
         if let Some(path) = Directory::plugins_directory() {
             let _ = watcher.watch(&path, notify::RecursiveMode::Recursive);
         }
@@ -543,7 +594,7 @@ pub fn new(
             maximised: info.maximised,
             panel_orders,
             latest_release,
-            update_in_progress,
+            update_status,
             log_file,
         }
     }
@@ -596,6 +647,7 @@ pub enum FocusArea {
     Palette,
     Editor,
     Rename,
+    Peek,
     Panel(PanelKind),
     FilePicker,
     BranchPicker,
@@ -605,6 +657,8 @@ pub enum FocusArea {
 pub enum DragContent {
     EditorTab(WidgetId, usize, EditorTabChild, Box<TabRect>),
     Panel(PanelKind, Rect),
+    /// Files and directories dragged in the file explorer
+    ExplorerPaths(Vec<PathBuf>),
 }
 
 #[derive(Clone, Lens, Data)]
@@ -620,6 +674,8 @@ pub struct LapceTabData {
     pub signature: Arc<SignatureData>,
     pub hover: Arc<HoverData>,
     pub rename: Arc<RenameData>,
+    pub color_picker: Arc<ColorPickerData>,
+    pub peek: Arc<PeekData>,
     pub terminal: Arc<TerminalPanelData>,
     pub palette: Arc<PaletteData>,
     pub find: Arc<Find>,
@@ -653,7 +709,7 @@ pub struct LapceTabData {
     pub progresses: Arc<Vec<WorkProgress>>,
     pub drag: Arc<Option<(Vec2, Vec2, DragContent)>>,
     pub latest_release: Arc<Option<ReleaseInfo>>,
-    pub update_in_progress: bool,
+    pub update_status: Option<UpdateStatus>,
     #[data(ignore)]
     pub log_file: Arc<Option<PathBuf>>,
 }
@@ -673,7 +729,7 @@ pub fn new(
         db: Arc<LapceDb>,
         keypress: Arc<KeyPressData>,
         latest_release: Arc<Option<ReleaseInfo>>,
-        update_in_progress: bool,
+        update_status: Option<UpdateStatus>,
         log_file: Arc<Option<PathBuf>>,
         current_panels: Option<PanelData>,
         panel_orders: PanelOrder,
@@ -686,7 +742,12 @@ pub fn new(
         let mut all_disabled_volts = disabled_volts.clone();
         all_disabled_volts.extend_from_slice(&workspace_disabled_volts);
 
-        let config = Arc::new(LapceConfig::load(&workspace, &all_disabled_volts));
+        let profile = db.get_workspace_profile(&workspace).ok();
+        let config = Arc::new(LapceConfig::load(
+            &workspace,
+            &all_disabled_volts,
+            profile.as_deref(),
+        ));
         let workspace_info = if workspace.path.is_some() {
             db.get_workspace_info(&workspace).ok()
         } else {
@@ -706,16 +767,24 @@ pub fn new(
             tab_id,
             workspace.clone(),
             all_disabled_volts,
-            config.plugins.clone(),
+            &config,
             term_sender.clone(),
             event_sink.clone(),
         ));
         let title = Arc::new(TitleData::new(config.clone()));
-        let palette = Arc::new(PaletteData::new(config.clone(), proxy.clone()));
+        let frecency = if config.palette.remember_usage {
+            db.get_palette_frecency().unwrap_or_default()
+        } else {
+            PaletteFrecency::default()
+        };
+        let palette =
+            Arc::new(PaletteData::new(config.clone(), proxy.clone(), frecency));
         let completion = Arc::new(CompletionData::new(config.clone()));
         let hover = Arc::new(HoverData::new());
         let signature = Arc::new(SignatureData::new());
         let rename = Arc::new(RenameData::new());
+        let color_picker = Arc::new(ColorPickerData::new());
+        let peek = Arc::new(PeekData::new());
         let source_control = Arc::new(SourceControlData::new());
         let settings = Arc::new(LapceSettingsPanelData::new());
         let about = Arc::new(AboutData::new());
@@ -724,11 +793,13 @@ pub fn new(
             tab_id,
             disabled_volts,
             workspace_disabled_volts,
+            db.get_pinned_volts().unwrap_or_default(),
             event_sink.clone(),
         ));
         let file_explorer = Arc::new(FileExplorerData::new(
             tab_id,
             workspace.clone(),
+            ExplorerFilter::new(&config.explorer),
             proxy.clone(),
             event_sink.clone(),
         ));
@@ -818,14 +889,35 @@ pub fn new(
             &config,
             event_sink.clone(),
         );
-
-        let terminal = Arc::new(TerminalPanelData::new(
-            Arc::new(workspace.clone()),
-            proxy.clone(),
+        let peek_editor = LapceEditorData::new(
+            Some(peek.view_id),
+            Some(peek.editor_id),
+            None,
+            BufferContent::Local(LocalBufferKind::Empty),
             &config,
-            event_sink.clone(),
+        );
+        main_split
+            .editors
+            .insert(peek_editor.view_id, Arc::new(peek_editor));
+
+        let restored_terminal = workspace_info
+            .as_ref()
+            .and_then(|info| info.terminal.as_ref())
+            .filter(|_| config.terminal.restore_sessions)
+            .and_then(|info| {
+                info.to_data(&workspace, proxy.clone(), &config, event_sink.clone())
+            });
+        let terminal = Arc::new(restored_terminal.unwrap_or_else(|| {
+            TerminalPanelData::new(
+                Arc::new(workspace.clone()),
+                proxy.clone(),
+                &config,
+                event_sink.clone(),
+            )
+        }));
+        let problem = Arc::new(ProblemData::new(
+            db.get_problem_grouping().unwrap_or_default(),
         ));
-        let problem = Arc::new(ProblemData::new());
         let panel = workspace_info
             .map(|i| {
                 let mut panel = i.panel;
@@ -849,6 +941,8 @@ pub fn new(
             signature,
             hover,
             rename,
+            color_picker,
+            peek,
             terminal,
             plugin,
             problem,
@@ -876,7 +970,7 @@ pub fn new(
             progresses: Arc::new(Vec::new()),
             drag: Arc::new(None),
             latest_release,
-            update_in_progress,
+            update_status,
             log_file,
         };
         tab.start_update_process(event_sink);
@@ -892,6 +986,11 @@ pub fn workspace_info(&self) -> WorkspaceInfo {
         WorkspaceInfo {
             split: main_split_data.split_info(self),
             panel: (*self.panel).clone(),
+            terminal: if self.config.terminal.restore_sessions {
+                Some(self.terminal.panel_info())
+            } else {
+                None
+            },
         }
     }
 
@@ -914,6 +1013,19 @@ pub fn start_update_process(&mut self, event_sink: ExtEventSink) {
             });
         }
 
+        if let Some(receiver) = Arc::make_mut(&mut self.palette)
+            .plugin_timer_receiver
+            .take()
+        {
+            let widget_id = self.palette.widget_id;
+            let event_sink = event_sink.clone();
+            thread::spawn(move || {
+                PaletteViewData::plugin_timer_process(
+                    receiver, widget_id, event_sink,
+                );
+            });
+        }
+
         if let Some(receiver) = Arc::make_mut(&mut self.palette).receiver.take() {
             let widget_id = self.palette.widget_id;
             thread::spawn(move || {
@@ -962,6 +1074,7 @@ pub fn editor_view_content(
             signature: self.signature.clone(),
             hover: self.hover.clone(),
             rename: self.rename.clone(),
+            peek: self.peek.clone(),
             focus_area: self.focus_area.clone(),
             source_control: self.source_control.clone(),
             proxy: self.proxy.clone(),
@@ -1037,6 +1150,7 @@ pub fn update_from_editor_buffer_data(
         self.signature = editor_buffer_data.signature.clone();
         self.hover = editor_buffer_data.hover.clone();
         self.rename = editor_buffer_data.rename.clone();
+        self.peek = editor_buffer_data.peek.clone();
         self.main_split = editor_buffer_data.main_split.clone();
         self.find = editor_buffer_data.find.clone();
         if !editor_buffer_data.editor.same(editor) {
@@ -1097,8 +1211,14 @@ pub fn completion_origin(
             BufferContent::File(_) | BufferContent::Scratch(..) => {
                 let doc = self.main_split.editor_doc(editor.view_id);
                 let offset = self.completion.offset;
-                let (point_above, point_below) =
-                    doc.points_of_offset(text, offset, &editor.view, config);
+                let wrap_width = editor.wrap_width(text, config);
+                let (point_above, point_below) = doc.points_of_offset(
+                    text,
+                    offset,
+                    &editor.view,
+                    wrap_width,
+                    config,
+                );
 
                 let mut origin = *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
@@ -1142,8 +1262,14 @@ pub fn signature_origin(
             BufferContent::File(_) | BufferContent::Scratch(_, _) => {
                 let doc = self.main_split.editor_doc(editor.view_id);
                 let offset = self.signature.offset;
-                let (point_above, _point_below) =
-                    doc.points_of_offset(text, offset, &editor.view, config);
+                let wrap_width = editor.wrap_width(text, config);
+                let (point_above, _point_below) = doc.points_of_offset(
+                    text,
+                    offset,
+                    &editor.view,
+                    wrap_width,
+                    config,
+                );
 
                 let mut origin = *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
@@ -1194,8 +1320,14 @@ pub fn rename_origin(
             BufferContent::File(_) | BufferContent::Scratch(..) => {
                 let doc = self.main_split.editor_doc(editor.view_id);
                 let offset = self.rename.start;
-                let (point_above, point_below) =
-                    doc.points_of_offset(text, offset, &editor.view, config);
+                let wrap_width = editor.wrap_width(text, config);
+                let (point_above, point_below) = doc.points_of_offset(
+                    text,
+                    offset,
+                    &editor.view,
+                    wrap_width,
+                    config,
+                );
 
                 let mut origin = *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
@@ -1218,6 +1350,36 @@ pub fn rename_origin(
         }
     }
 
+    /// The rect of the peek popup, across the editor it was shown from right
+    /// below the line the definitions were peeked at, or above the line when
+    /// there isn't enough room below it
+    pub fn peek_rect(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        config: &LapceConfig,
+    ) -> Option<Rect> {
+        let editor = self.main_split.editors.get(&self.peek.from_editor)?;
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let offset = self.peek.offset.min(doc.buffer().len());
+        let wrap_width = editor.wrap_width(text, config);
+        let (point_above, point_below) =
+            doc.points_of_offset(text, offset, &editor.view, wrap_width, config);
+
+        let origin =
+            *editor.window_origin.borrow() - self.window_origin.borrow().to_vec2();
+        let size = Size::new(
+            editor.size.borrow().width,
+            (config.ui.header_height() + config.editor.line_height() * PEEK_LINES)
+                as f64,
+        );
+        let mut y = origin.y + point_below.y;
+        if y + size.height + 1.0 > tab_size.height {
+            y = (origin.y + point_above.y - size.height).max(0.0);
+        }
+        Some(size.to_rect().with_origin(Point::new(origin.x, y)))
+    }
+
     pub fn hover_origin(
         &self,
         text: &mut PietText,
@@ -1242,19 +1404,17 @@ pub fn hover_origin(
                     - self.window_origin.borrow().to_vec2()
             }
             BufferContent::File(_) | BufferContent::Scratch(..) => {
-                let doc = self.main_split.editor_doc(editor.view_id);
-                let offset = self.hover.offset;
-                let (point, _) =
-                    doc.points_of_offset(text, offset, &editor.view, config);
-                let x = point.x;
-                let y = point.y;
+                let line_origin = self.hover_line_origin(text, editor, config);
                 let hover_size = *self.hover.content_size.borrow();
-                let mut origin = *editor.window_origin.borrow()
-                    - self.window_origin.borrow().to_vec2()
-                    + Vec2::new(x, y - hover_size.height);
-                if origin.y < 0.0 {
-                    origin.y += hover_size.height + line_height;
-                }
+                let mut origin = Point::new(
+                    line_origin.x,
+                    HoverData::origin_y(
+                        line_origin.y,
+                        line_origin.y + line_height,
+                        hover_size.height,
+                        tab_size.height,
+                    ),
+                );
                 if origin.x + hover_size.width + 1.0 > tab_size.width {
                     origin.x = tab_size.width - hover_size.width - 1.0;
                 }
@@ -1267,6 +1427,70 @@ pub fn hover_origin(
         }
     }
 
+    /// The height the hover can take in the tab, which depends on the room there
+    /// is around the line it's for
+    pub fn hover_max_height(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        config: &LapceConfig,
+    ) -> f64 {
+        let max_height = config.editor.hover_max_height as f64;
+        let editor = self.main_split.editors.get(&self.hover.editor_view_id);
+        match editor {
+            Some(editor)
+                if matches!(
+                    editor.content,
+                    BufferContent::File(_) | BufferContent::Scratch(..)
+                ) =>
+            {
+                let line_height = config.editor.line_height() as f64;
+                let line_origin = self.hover_line_origin(text, editor, config);
+                HoverData::available_height(
+                    line_origin.y,
+                    line_origin.y + line_height,
+                    tab_size.height,
+                    max_height,
+                )
+            }
+            _ => max_height.min(tab_size.height),
+        }
+    }
+
+    /// The top left of the hovered position in the editor, relative to the tab
+    fn hover_line_origin(
+        &self,
+        text: &mut PietText,
+        editor: &LapceEditorData,
+        config: &LapceConfig,
+    ) -> Point {
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let wrap_width = editor.wrap_width(text, config);
+        let (point, _) = doc.points_of_offset(
+            text,
+            self.hover.offset,
+            &editor.view,
+            wrap_width,
+            config,
+        );
+        *editor.window_origin.borrow() - self.window_origin.borrow().to_vec2()
+            + point.to_vec2()
+    }
+
+    /// Save a local history snapshot of the open file, unless it's disabled or
+    /// the file is too large
+    pub fn save_local_history(&self, path: &Path) {
+        if let Some(doc) = self.main_split.open_docs.get(path) {
+            if let Some(snapshot) = self.config.history.local.snapshot(
+                path.to_path_buf(),
+                doc.buffer().to_string(),
+                local_history::now(),
+            ) {
+                self.db.save_local_history(snapshot);
+            }
+        }
+    }
+
     pub fn palette_view_data(&self) -> PaletteViewData {
         PaletteViewData {
             palette: self.palette.clone(),
@@ -1282,18 +1506,302 @@ pub fn palette_view_data(&self) -> PaletteViewData {
         }
     }
 
+    /// Grant the volt the capabilities it asks for as the user answered once
+    /// already, or ask the user with an alert listing them
+    pub fn request_volt_permissions(
+        &self,
+        ctx: &mut EventCtx,
+        volt: &VoltInfo,
+        requested: VoltCapabilities,
+    ) {
+        let volt_id = volt.id();
+        if let Ok(permissions) = self.db.get_volt_permissions(&volt_id) {
+            if permissions.requested.covers(&requested) {
+                self.proxy
+                    .proxy_rpc
+                    .grant_volt_permissions(volt_id, permissions.granted);
+                return;
+            }
+        }
+
+        let answer = |text: &str, granted: VoltCapabilities| AlertButton {
+            text: text.to_string(),
+            target: self.id,
+            command: LapceCommand {
+                kind: CommandKind::Workbench(
+                    LapceWorkbenchCommand::SetVoltPermissions,
+                ),
+                data: Some(serde_json::json!((
+                    &volt_id,
+                    VoltPermissions {
+                        requested: requested.clone(),
+                        granted,
+                    }
+                ))),
+            },
+        };
+        let msg = requested
+            .describe()
+            .iter()
+            .map(|line| format!("• {line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowAlert(AlertContentData {
+                title: format!(
+                    "Do you want to allow the plugin {} to:",
+                    volt.display_name
+                ),
+                msg,
+                buttons: vec![
+                    answer("Allow", requested.clone()),
+                    answer("Deny", VoltCapabilities::default()),
+                ],
+                ..Default::default()
+            }),
+            Target::Widget(self.id),
+        ));
+    }
+
+    /// Run the text in the active terminal, showing the terminal panel. The
+    /// user is asked before running each of its lines when the shell can't
+    /// take them at once.
+    pub fn run_text_in_terminal(
+        &mut self,
+        ctx: &mut EventCtx,
+        text: &str,
+        line_by_line: bool,
+    ) {
+        let terminal = match self.terminal.active_terminal() {
+            Some(terminal) => terminal,
+            None => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "There is no terminal to run the text in".to_string(),
+                        msg: "Open a terminal and run it again.".to_string(),
+                        ..Default::default()
+                    }),
+                    Target::Widget(self.id),
+                ));
+                return;
+            }
+        };
+        if !terminal.run_text(text, line_by_line) {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowAlert(AlertContentData {
+                    title: "Do you want to run each line separately?".to_string(),
+                    msg: "The shell of the terminal can't take several lines at \
+                          once, so each of them would be run as it comes in."
+                        .to_string(),
+                    buttons: vec![AlertButton {
+                        text: "Run Each Line".to_string(),
+                        target: self.id,
+                        command: LapceCommand {
+                            kind: CommandKind::Workbench(
+                                LapceWorkbenchCommand::RunTextLineByLine,
+                            ),
+                            data: Some(serde_json::json!(text)),
+                        },
+                    }],
+                    ..Default::default()
+                }),
+                Target::Widget(self.id),
+            ));
+            return;
+        }
+        if !self.panel.is_panel_visible(&PanelKind::Terminal) {
+            Arc::make_mut(&mut self.panel).show_panel(&PanelKind::Terminal);
+        }
+    }
+
+    /// Show what importing the keymap file would change, and let the user apply it
+    pub fn preview_keymap_import(&self, ctx: &mut EventCtx, path: &Path) {
+        let import = std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| KeyPressData::import_keymaps(&content));
+        let import = match import {
+            Ok(import) => import,
+            Err(err) => {
+                self.show_keymap_import_error(ctx, err);
+                return;
+            }
+        };
+
+        let content = if import.is_empty() {
+            AlertContentData {
+                title: "The keyboard shortcuts are already imported".to_string(),
+                timeout: Some(Duration::from_secs(5)),
+                ..Default::default()
+            }
+        } else {
+            AlertContentData {
+                title: format!(
+                    "Do you want to import the keyboard shortcuts from {}?",
+                    path.display()
+                ),
+                msg: import.summary(),
+                buttons: vec![AlertButton {
+                    text: "Import".to_string(),
+                    target: self.id,
+                    command: LapceCommand {
+                        kind: CommandKind::Workbench(
+                            LapceWorkbenchCommand::ImportKeymaps,
+                        ),
+                        data: Some(serde_json::json!(path)),
+                    },
+                }],
+                ..Default::default()
+            }
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowAlert(content),
+            Target::Widget(self.id),
+        ));
+    }
+
+    /// Restore the open buffers in the remote proxy when it's connected again,
+    /// or tell that it couldn't be
+    pub fn update_proxy_status(&mut self, ctx: &mut EventCtx, status: ProxyStatus) {
+        match (*self.proxy_status, status) {
+            (ProxyStatus::Reconnecting { .. }, ProxyStatus::Connected) => {
+                let buffers = self
+                    .main_split
+                    .open_docs
+                    .values()
+                    .filter(|doc| doc.loaded())
+                    .filter_map(|doc| match doc.content() {
+                        BufferContent::File(path) => {
+                            Some(ProxyNotification::RestoreBuffer {
+                                buffer_id: doc.id(),
+                                path: path.clone(),
+                                content: doc.buffer().to_string(),
+                                rev: doc.rev(),
+                                encoding: doc.encoding().to_string(),
+                                large_file: doc.large_file(),
+                            })
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                self.proxy.restore_buffers(buffers);
+            }
+            (ProxyStatus::Reconnecting { .. }, ProxyStatus::Disconnected) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "Lost the connection to the remote".to_string(),
+                        msg: "Reconnect to the remote to keep working".to_string(),
+                        ..Default::default()
+                    }),
+                    Target::Widget(self.id),
+                ));
+            }
+            _ => {}
+        }
+        self.proxy_status = Arc::new(status);
+    }
+
+    /// Write the state of the workspaces to an archive, or import one
+    pub fn transfer_state(
+        &self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        import: Option<ImportMode>,
+    ) {
+        if import.is_none() {
+            // Saved first for the archive to have the current workspace
+            let _ = self.db.save_workspace(self);
+        }
+        let (title, result) = match import {
+            Some(mode) => (
+                "Failed to import the workspaces state",
+                // The window is reopened with the imported state
+                self.db.import_from(path, mode).map(|_| {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ReloadWindow,
+                        Target::Auto,
+                    ));
+                }),
+            ),
+            None => (
+                "Failed to export the workspaces state",
+                self.db.export_to(path),
+            ),
+        };
+        if let Err(err) = result {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowAlert(AlertContentData {
+                    title: title.to_string(),
+                    msg: err.to_string(),
+                    ..Default::default()
+                }),
+                Target::Widget(self.id),
+            ));
+        }
+    }
+
+    fn show_keymap_import_error(&self, ctx: &mut EventCtx, err: anyhow::Error) {
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowAlert(AlertContentData {
+                title: "Failed to transfer the keyboard shortcuts".to_string(),
+                msg: err.to_string(),
+                ..Default::default()
+            }),
+            Target::Widget(self.id),
+        ));
+    }
+
     pub fn run_workbench_command(
         &mut self,
         ctx: &mut EventCtx,
         command: &LapceWorkbenchCommand,
         data: Option<Value>,
         _count: Option<usize>,
-        _env: &Env,
+        env: &Env,
     ) {
         match command {
             LapceWorkbenchCommand::RestartToUpdate => {
+                if let Some(release) = &*self.latest_release {
+                    if release.is_update() {
+                        let notes = release.body.trim();
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ShowAlert(AlertContentData {
+                                title: format!(
+                                    "Do you want to update to Lapce {}?",
+                                    release.version
+                                ),
+                                msg: "Lapce will restart once the update is installed."
+                                    .to_string(),
+                                notes: (!notes.is_empty())
+                                    .then(|| notes.to_string()),
+                                buttons: vec![AlertButton {
+                                    text: "Restart to Update".to_string(),
+                                    target: self.id,
+                                    command: LapceCommand {
+                                        kind: CommandKind::Workbench(
+                                            LapceWorkbenchCommand::InstallUpdate,
+                                        ),
+                                        data: None,
+                                    },
+                                }],
+                                ..Default::default()
+                            }),
+                            Target::Widget(self.id),
+                        ));
+                    }
+                }
+            }
+            LapceWorkbenchCommand::InstallUpdate => {
                 if let Some(release) = (*self.latest_release).clone() {
-                    if release.version != *meta::VERSION {
+                    if release.is_update() && self.update_status.is_none() {
                         if let Ok(process_path) = env::current_exe() {
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
@@ -1307,6 +1815,29 @@ pub fn run_workbench_command(
                     }
                 }
             }
+            LapceWorkbenchCommand::RollbackUpdate => {
+                if let Ok(process_path) = env::current_exe() {
+                    if crate::update::can_rollback(&process_path) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::RollbackUpdate(process_path),
+                            Target::Global,
+                        ));
+                    } else {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ShowAlert(AlertContentData {
+                                title: "There's no previous version to roll back to"
+                                    .to_string(),
+                                msg: "The previous version is kept when Lapce updates itself."
+                                    .to_string(),
+                                ..Default::default()
+                            }),
+                            Target::Widget(self.id),
+                        ));
+                    }
+                }
+            }
             LapceWorkbenchCommand::CloseFolder => {
                 if self.workspace.path.is_some() {
                     let mut workspace = (*self.workspace).clone();
@@ -1320,51 +1851,53 @@ pub fn run_workbench_command(
             }
             LapceWorkbenchCommand::OpenFolder => {
                 if !self.workspace.kind.is_remote() {
-                    let options = FileDialogOptions::new()
+                    let mut options = FileDialogOptions::new()
                         .select_directories()
                         .accept_command(LAPCE_OPEN_FOLDER);
+                    if let Some(dir) = FilePickerData::start_dir(
+                        &self.db,
+                        PickerKind::OpenFolder,
+                        &self.workspace,
+                    ) {
+                        options = options.force_starting_directory(dir);
+                    }
                     ctx.submit_command(
                         druid::commands::SHOW_OPEN_PANEL.with(options),
                     );
                 } else {
-                    let picker = Arc::make_mut(&mut self.picker);
-                    picker.active = true;
-                    if let Some(node) = picker.root.get_file_node(&picker.pwd) {
-                        if !node.read {
-                            let tab_id = self.id;
-                            let event_sink = ctx.get_external_handle();
-                            FilePickerData::read_dir(
-                                &node.path_buf,
-                                tab_id,
-                                &self.proxy,
-                                event_sink,
-                            );
-                        }
-                    }
+                    self.show_file_picker(ctx, PickerKind::OpenFolder);
                 }
             }
             LapceWorkbenchCommand::OpenFile => {
                 if !self.workspace.kind.is_remote() {
-                    let options =
+                    let mut options =
                         FileDialogOptions::new().accept_command(LAPCE_OPEN_FILE);
+                    if let Some(dir) = FilePickerData::start_dir(
+                        &self.db,
+                        PickerKind::OpenFile,
+                        &self.workspace,
+                    ) {
+                        options = options.force_starting_directory(dir);
+                    }
                     ctx.submit_command(
                         druid::commands::SHOW_OPEN_PANEL.with(options),
                     );
                 } else {
-                    let picker = Arc::make_mut(&mut self.picker);
-                    picker.active = true;
-                    if let Some(node) = picker.root.get_file_node(&picker.pwd) {
-                        if !node.read {
-                            let tab_id = self.id;
-                            let event_sink = ctx.get_external_handle();
-                            FilePickerData::read_dir(
-                                &node.path_buf,
-                                tab_id,
-                                &self.proxy,
-                                event_sink,
-                            );
-                        }
-                    }
+                    self.show_file_picker(ctx, PickerKind::OpenFile);
+                }
+            }
+            LapceWorkbenchCommand::ToggleFileExplorerHiddenFiles => {
+                Arc::make_mut(&mut self.file_explorer).toggle_hidden_files();
+            }
+            LapceWorkbenchCommand::FileExplorerFileOperations => {
+                if let Some(operations) = data.and_then(|d| {
+                    serde_json::from_value::<Vec<FileOperation>>(d).ok()
+                }) {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ExplorerFileOperations { operations },
+                        Target::Widget(self.id),
+                    ));
                 }
             }
             LapceWorkbenchCommand::RevealActiveFileInFileExplorer => {
@@ -1408,6 +1941,18 @@ pub fn run_workbench_command(
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::CycleColorTheme => {
+                if let Some(theme) = self.config.next_color_theme() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::SetColorTheme {
+                            theme: theme.to_string(),
+                            preview: false,
+                        },
+                        Target::Auto,
+                    ));
+                }
+            }
             LapceWorkbenchCommand::ChangeIconTheme => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1415,6 +1960,13 @@ pub fn run_workbench_command(
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::ChangeSettingsProfile => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::SettingsProfile)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
             LapceWorkbenchCommand::NewFile => {
                 self.main_split.new_file(ctx, &self.config);
             }
@@ -1522,6 +2074,13 @@ pub fn run_workbench_command(
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::PaletteAllBufferSymbols => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::AllBufferSymbols)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
             LapceWorkbenchCommand::PaletteCommand => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1626,6 +2185,45 @@ pub fn run_workbench_command(
             LapceWorkbenchCommand::ToggleProblemVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleRenamePreviewVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::RenamePreview);
+            }
+            LapceWorkbenchCommand::ApplyRenamePreview => {
+                if Arc::make_mut(&mut self.rename)
+                    .apply_preview(ctx, &mut self.main_split)
+                {
+                    self.hide_panel(ctx, PanelKind::RenamePreview);
+                }
+            }
+            LapceWorkbenchCommand::DiscardRenamePreview => {
+                Arc::make_mut(&mut self.rename)
+                    .discard_preview(&mut self.main_split);
+                self.hide_panel(ctx, PanelKind::RenamePreview);
+            }
+            LapceWorkbenchCommand::RunProblemCodeAction => {
+                if let Some(action) = data.and_then(|d| {
+                    serde_json::from_value::<ProblemCodeAction>(d).ok()
+                }) {
+                    self.main_split.submit_to_file_editor(
+                        ctx,
+                        &action.path,
+                        move || {
+                            LapceUICommand::RunCodeAction(
+                                action.action.clone(),
+                                action.plugin_id,
+                            )
+                        },
+                        &self.config,
+                    );
+                }
+            }
+            LapceWorkbenchCommand::ApplyColorPresentation => {
+                if let Some(edit) = data.and_then(|d| {
+                    serde_json::from_value::<ColorPresentationEdit>(d).ok()
+                }) {
+                    apply_edit(&mut self.main_split, &edit.path, &edit.edits);
+                }
+            }
             LapceWorkbenchCommand::ToggleTerminalVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Terminal);
             }
@@ -1685,6 +2283,46 @@ pub fn run_workbench_command(
                     }
                 }
             }
+            LapceWorkbenchCommand::MovePanelLeft
+            | LapceWorkbenchCommand::MovePanelRight
+            | LapceWorkbenchCommand::MovePanelBottom => {
+                let container = match command {
+                    LapceWorkbenchCommand::MovePanelLeft => {
+                        PanelContainerPosition::Left
+                    }
+                    LapceWorkbenchCommand::MovePanelRight => {
+                        PanelContainerPosition::Right
+                    }
+                    _ => PanelContainerPosition::Bottom,
+                };
+                let kind = match data {
+                    Some(data) => serde_json::from_value::<PanelKind>(data).ok(),
+                    None => match self.focus_area {
+                        FocusArea::Panel(kind) => Some(kind),
+                        _ => None,
+                    },
+                };
+                if let Some(kind) = kind {
+                    // A panel already in the container stays where it is
+                    let moved = self.panel.panel_position(&kind).map_or(
+                        false,
+                        |(_, position)| {
+                            position != container.first()
+                                && position != container.second()
+                        },
+                    );
+                    if moved {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::MovePanel {
+                                kind,
+                                position: container.first(),
+                            },
+                            Target::Widget(self.id),
+                        ));
+                    }
+                }
+            }
             LapceWorkbenchCommand::SourceControlInit => {
                 self.proxy.proxy_rpc.git_init();
             }
@@ -1853,6 +2491,113 @@ pub fn run_workbench_command(
             LapceWorkbenchCommand::InstallTheme => {
                 self.main_split.install_theme(ctx, &self.config);
             }
+            LapceWorkbenchCommand::ExportKeymaps => {
+                match KeyPressData::export_keymaps() {
+                    Ok(content) => {
+                        self.main_split.export_keymaps(ctx, &self.config, content)
+                    }
+                    Err(err) => self.show_keymap_import_error(ctx, err),
+                }
+            }
+            LapceWorkbenchCommand::ReloadFromDisk => {
+                let path =
+                    data.and_then(|d| serde_json::from_value::<PathBuf>(d).ok());
+                if let Some(doc) =
+                    path.and_then(|path| self.main_split.open_docs.get_mut(&path))
+                {
+                    Arc::make_mut(doc).reload_from_disk();
+                }
+            }
+            LapceWorkbenchCommand::KeepLocalVersion => {
+                let path =
+                    data.and_then(|d| serde_json::from_value::<PathBuf>(d).ok());
+                if let Some(doc) =
+                    path.and_then(|path| self.main_split.open_docs.get_mut(&path))
+                {
+                    Arc::make_mut(doc).keep_local_version();
+                }
+            }
+            LapceWorkbenchCommand::OpenLink => {
+                // The link is given once opening it was confirmed
+                let link =
+                    data.and_then(|d| serde_json::from_value::<String>(d).ok());
+                if let Some(link) = link {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::OpenURI(link),
+                        Target::Widget(self.id),
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::RunTextLineByLine => {
+                let text =
+                    data.and_then(|d| serde_json::from_value::<String>(d).ok());
+                if let Some(text) = text {
+                    self.run_text_in_terminal(ctx, &text, true);
+                }
+            }
+            LapceWorkbenchCommand::SetVoltPermissions => {
+                let answer = data.and_then(|d| {
+                    serde_json::from_value::<(VoltID, VoltPermissions)>(d).ok()
+                });
+                if let Some((volt_id, permissions)) = answer {
+                    let _ = self.db.save_volt_permissions(&volt_id, &permissions);
+                    self.proxy
+                        .proxy_rpc
+                        .grant_volt_permissions(volt_id, permissions.granted);
+                }
+            }
+            LapceWorkbenchCommand::ImportKeymaps => {
+                // The path is only given once the changes were looked at
+                let path =
+                    data.and_then(|d| serde_json::from_value::<PathBuf>(d).ok());
+                if let Some(path) = path {
+                    let result = std::fs::read_to_string(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|content| KeyPressData::import_keymaps(&content))
+                        .and_then(|import| {
+                            KeyPressData::apply_keymap_import(&import)
+                        });
+                    if let Err(err) = result {
+                        self.show_keymap_import_error(ctx, err);
+                    }
+                } else {
+                    let options = FileDialogOptions::new()
+                        .accept_command(LAPCE_IMPORT_KEYMAPS);
+                    ctx.submit_command(
+                        druid::commands::SHOW_OPEN_PANEL.with(options),
+                    );
+                }
+            }
+            LapceWorkbenchCommand::ExportState => {
+                let options = FileDialogOptions::new()
+                    .default_name("lapce-state.json.gz")
+                    .accept_command(LAPCE_EXPORT_STATE);
+                ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options));
+            }
+            LapceWorkbenchCommand::ImportState => {
+                let options =
+                    FileDialogOptions::new().accept_command(LAPCE_IMPORT_STATE);
+                ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+            }
+            LapceWorkbenchCommand::ImportStateReplace => {
+                let options = FileDialogOptions::new()
+                    .accept_command(LAPCE_IMPORT_STATE_REPLACE);
+                ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+            }
+            LapceWorkbenchCommand::InstallPluginVersion => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::PluginVersion)),
+                    Target::Auto,
+                ))
+            }
+            LapceWorkbenchCommand::InstallPluginFromFile => {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("Volt", &["volt"])])
+                    .accept_command(LAPCE_INSTALL_PLUGIN_FROM_FILE);
+                ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+            }
             LapceWorkbenchCommand::ChangeFileLanguage => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1860,6 +2605,36 @@ pub fn run_workbench_command(
                     Target::Auto,
                 ))
             }
+            LapceWorkbenchCommand::ToggleLargeFileMode => {
+                if let Some(editor) = self.main_split.active_editor().cloned() {
+                    if let BufferContent::File(path) = &editor.content {
+                        if let Some(doc) = self.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).toggle_large_file();
+                        }
+                    }
+                }
+            }
+            LapceWorkbenchCommand::ChangeLineEnding => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::LineEnding)),
+                    Target::Auto,
+                ))
+            }
+            LapceWorkbenchCommand::ReopenWithEncoding => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::Encoding)),
+                    Target::Auto,
+                ))
+            }
+            LapceWorkbenchCommand::ShowLocalHistory => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::LocalHistory)),
+                    Target::Auto,
+                ))
+            }
             LapceWorkbenchCommand::NextEditorTab => {
                 if let Some(active) = *self.main_split.active_tab {
                     ctx.submit_command(Command::new(
@@ -1878,6 +2653,29 @@ pub fn run_workbench_command(
                     ));
                 }
             }
+            LapceWorkbenchCommand::RunMacro => {
+                if let Some(name) = data.as_ref().and_then(|data| data.as_str()) {
+                    self.run_macro_step(ctx, name, 0, env);
+                }
+            }
+            LapceWorkbenchCommand::PinTab | LapceWorkbenchCommand::UnpinTab => {
+                if let Some(active) = *self.main_split.active_tab {
+                    if let Some(editor_tab) =
+                        self.main_split.editor_tabs.get(&active)
+                    {
+                        let index = editor_tab.active;
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            if *command == LapceWorkbenchCommand::PinTab {
+                                LapceUICommand::PinTab(index)
+                            } else {
+                                LapceUICommand::UnpinTab(index)
+                            },
+                            Target::Widget(active),
+                        ));
+                    }
+                }
+            }
             LapceWorkbenchCommand::ToggleInlayHints => {
                 let config = Arc::make_mut(&mut self.config);
                 config.editor.enable_inlay_hints = !config.editor.enable_inlay_hints;
@@ -1899,6 +2697,23 @@ pub fn run_workbench_command(
                     Arc::make_mut(&mut self.panel).show_panel(&PanelKind::Terminal);
                 }
             }
+            LapceWorkbenchCommand::RenameTerminalTab => {
+                let split_id = data
+                    .and_then(|d| serde_json::from_value::<usize>(d).ok())
+                    .map(WidgetId::from_usize)
+                    .or_else(|| {
+                        self.terminal.active_terminal_split().map(|s| s.split_id)
+                    });
+                if let Some(split_id) = split_id {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RunPalette(Some(
+                            PaletteType::TerminalTabName(split_id),
+                        )),
+                        Target::Auto,
+                    ));
+                }
+            }
             LapceWorkbenchCommand::NextTerminalTab => {
                 let terminal_panel = Arc::make_mut(&mut self.terminal);
                 if terminal_panel.active >= terminal_panel.tabs_order.len() - 1 {
@@ -1965,6 +2780,16 @@ pub fn run_workbench_command(
                     ));
                 }
             }
+            LapceWorkbenchCommand::ClearTerminalScrollback => {
+                if let Some(terminal) = self.terminal.active_terminal() {
+                    terminal.raw.lock().clear_scrollback();
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RequestPaint,
+                        Target::Widget(self.id),
+                    ));
+                }
+            }
             LapceWorkbenchCommand::ShowAbout => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1972,6 +2797,27 @@ pub fn run_workbench_command(
                     Target::Widget(self.id),
                 ));
             }
+            LapceWorkbenchCommand::CopyDiagnostics => {
+                let home = directories::BaseDirs::new()
+                    .filter(|_| self.config.core.redact_diagnostics)
+                    .map(|dirs| dirs.home_dir().to_path_buf());
+                let text = Diagnostics::new(self).format(home.as_deref());
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::PutToClipboard(text),
+                    Target::Widget(self.id),
+                ));
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "The diagnostics are copied to the clipboard"
+                            .to_string(),
+                        timeout: Some(Duration::from_secs(3)),
+                        ..Default::default()
+                    }),
+                    Target::Widget(self.id),
+                ));
+            }
             LapceWorkbenchCommand::SaveAll => {
                 let mut paths = HashSet::new();
                 for (_, editor) in self.main_split.editors.iter() {
@@ -2044,22 +2890,23 @@ pub fn run_command(
                     env,
                 );
             }
-            CommandKind::Focus(_) | CommandKind::Edit(_) | CommandKind::Move(_) => {
-                let widget_id = if *self.focus != self.palette.input_editor {
-                    *self.focus
-                } else if let Some(active_tab) = self.main_split.active_tab.as_ref()
-                {
-                    self.main_split
-                        .editor_tabs
-                        .get(active_tab)
-                        .unwrap()
-                        .active_child()
-                        .map(|c| c.widget_id())
-                        .unwrap_or(*self.focus)
-                } else {
-                    *self.focus
-                };
-
+            CommandKind::SourceControl(
+                SourceControlCommand::StageHunk | SourceControlCommand::UnstageHunk,
+            ) => {
+                Arc::make_mut(&mut self.source_control).run_command(
+                    ctx,
+                    command,
+                    count,
+                    Modifiers::empty(),
+                    env,
+                );
+            }
+            CommandKind::Focus(_)
+            | CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::SourceControl(_)
+            | CommandKind::Fold(_) => {
+                let widget_id = self.focus_command_target();
                 ctx.submit_command(Command::new(
                     LAPCE_COMMAND,
                     command.clone(),
@@ -2070,6 +2917,79 @@ pub fn run_command(
         }
     }
 
+    /// Run the step of the macro like any other command, then queue the next
+    /// one behind the commands it submitted, so that a step run by the focused
+    /// widget is done before the next one starts. The macro is stopped at the
+    /// first step which can't be run.
+    pub fn run_macro_step(
+        &mut self,
+        ctx: &mut EventCtx,
+        name: &str,
+        step: usize,
+        env: &Env,
+    ) {
+        let command_macro = match self.keypress.macros.get(name) {
+            Some(command_macro) => command_macro.clone(),
+            None => return,
+        };
+        let result = command_macro.run_step(step, |command| {
+            if let MacroStepTarget::Focus { needs_editor: true } =
+                MacroStepTarget::of(&command.kind)?
+            {
+                let target = self.focus_command_target();
+                if !self.main_split.editors.contains_key(&target) {
+                    return Err(format!(
+                        "there is no editor to run `{}` in",
+                        command.kind.str()
+                    ));
+                }
+            }
+            self.run_command(ctx, command, None, env);
+            Ok(())
+        });
+        match result {
+            Ok(Some(next)) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunMacroStep {
+                        name: name.to_string(),
+                        step: next,
+                    },
+                    Target::Widget(self.id),
+                ));
+            }
+            Ok(None) => {}
+            Err(err) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "The macro was stopped".to_string(),
+                        msg: err.to_string(),
+                        ..Default::default()
+                    }),
+                    Target::Widget(self.id),
+                ));
+            }
+        }
+    }
+
+    /// The widget the focus, edit and move commands are run in
+    fn focus_command_target(&self) -> WidgetId {
+        if *self.focus != self.palette.input_editor {
+            *self.focus
+        } else if let Some(active_tab) = self.main_split.active_tab.as_ref() {
+            self.main_split
+                .editor_tabs
+                .get(active_tab)
+                .unwrap()
+                .active_child()
+                .map(|c| c.widget_id())
+                .unwrap_or(*self.focus)
+        } else {
+            *self.focus
+        }
+    }
+
     pub fn terminal_update_process(
         tab_id: WidgetId,
         _palette_widget_id: WidgetId,
@@ -2143,6 +3063,29 @@ fn hide_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         }
     }
 
+    /// The badge over the icon of the panel, for the panels that count
+    /// something
+    pub fn panel_badge(&self, kind: &PanelKind) -> Option<PanelBadge> {
+        match kind {
+            PanelKind::Problem => problem_badge(
+                self.main_split.error_count,
+                self.main_split.warning_count,
+            ),
+            PanelKind::SourceControl => PanelBadge::new(
+                self.source_control.file_diffs.len(),
+                PanelBadgeKind::Info,
+            ),
+            PanelKind::Search => PanelBadge::new(
+                self.search.matches.values().map(|m| m.len()).sum(),
+                PanelBadgeKind::Info,
+            ),
+            PanelKind::FileExplorer
+            | PanelKind::Plugin
+            | PanelKind::Terminal
+            | PanelKind::RenamePreview => None,
+        }
+    }
+
     pub fn show_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         Arc::make_mut(&mut self.panel).show_panel(&kind);
         let focus_id = match kind {
@@ -2152,6 +3095,7 @@ pub fn show_panel(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
             PanelKind::Terminal => self.terminal.widget_id,
             PanelKind::Search => self.search.active,
             PanelKind::Problem => self.problem.widget_id,
+            PanelKind::RenamePreview => self.rename.preview_widget_id,
         };
         if let PanelKind::Search = kind {
             ctx.submit_command(Command::new(
@@ -2182,7 +3126,10 @@ fn toggle_panel_visual(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
 
     fn toggle_panel_focus(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         let should_hide = match kind {
-            PanelKind::FileExplorer | PanelKind::Plugin | PanelKind::Problem => {
+            PanelKind::FileExplorer
+            | PanelKind::Plugin
+            | PanelKind::Problem
+            | PanelKind::RenamePreview => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.panel.is_panel_visible(&kind)
@@ -2232,6 +3179,76 @@ pub fn toggle_container_visual(
         }
     }
 
+    /// Show the in-app picker in the directory `kind` was last picked from
+    fn show_file_picker(&mut self, ctx: &mut EventCtx, kind: PickerKind) {
+        let start_dir = FilePickerData::start_dir(&self.db, kind, &self.workspace);
+        let picker = Arc::make_mut(&mut self.picker);
+        picker.active = true;
+        if let Some(dir) = start_dir {
+            if dir != picker.pwd {
+                picker.init_root(&dir);
+                picker.index = 0;
+                self.set_picker_pwd(dir);
+            }
+        }
+        if let Some(node) = self.picker.root.get_file_node(&self.picker.pwd) {
+            if !node.read {
+                let tab_id = self.id;
+                let event_sink = ctx.get_external_handle();
+                FilePickerData::read_start_dir(
+                    &node.path_buf,
+                    &self.picker.home,
+                    tab_id,
+                    &self.proxy,
+                    event_sink,
+                );
+            }
+        }
+    }
+
+    /// List the directory of the typed path to complete it
+    pub fn complete_picker_path(&mut self, ctx: &mut EventCtx) {
+        let input = self
+            .main_split
+            .local_docs
+            .get(&LocalBufferKind::FilePicker)
+            .unwrap()
+            .buffer()
+            .to_string();
+        let (dir, _) = split_partial(&input);
+        if dir.is_empty() {
+            return;
+        }
+        let event_sink = ctx.get_external_handle();
+        let tab_id = self.id;
+        self.proxy
+            .proxy_rpc
+            .read_dir(PathBuf::from(dir), move |result| {
+                if let Ok(ProxyResponse::ReadDirResponse { items }) = result {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdatePickerCompletion(input, items),
+                        Target::Widget(tab_id),
+                    );
+                }
+            });
+    }
+
+    pub fn update_picker_completion(
+        &mut self,
+        ctx: &mut EventCtx,
+        input: &str,
+        items: &HashMap<PathBuf, FileNodeItem>,
+    ) {
+        let case_insensitive = !self.workspace.kind.is_remote()
+            && cfg!(any(target_os = "windows", target_os = "macos"));
+        let picker = Arc::make_mut(&mut self.picker);
+        if let Some(completed) = picker.complete(input, items, case_insensitive) {
+            self.set_picker_pwd(PathBuf::from(completed));
+            self.read_picker_pwd(ctx);
+        }
+    }
+
     pub fn read_picker_pwd(&mut self, ctx: &mut EventCtx) {
         let path = self.picker.pwd.clone();
         let event_sink = ctx.get_external_handle();
@@ -2300,7 +3317,7 @@ fn with_mut<V, F: FnOnce(&mut LapceTabData) -> V>(
         tab.keypress = data.keypress.clone();
         tab.latest_release = data.latest_release.clone();
         tab.log_file = data.log_file.clone();
-        tab.update_in_progress = data.update_in_progress;
+        tab.update_status = data.update_status;
         tab.log_file = data.log_file.clone();
         tab.multiple_tab = data.tabs.len() > 1;
         if !tab.panel.order.same(&data.panel_orders) {
@@ -2338,7 +3355,7 @@ fn with_mut<V, F: FnOnce(&mut LapceWindowData) -> V>(
         let mut win = data.windows.get(&self.0).unwrap().clone();
         win.keypress = data.keypress.clone();
         win.latest_release = data.latest_release.clone();
-        win.update_in_progress = data.update_in_process;
+        win.update_status = data.update_status;
         win.panel_orders = data.panel_orders.clone();
         let result = f(&mut win);
         data.keypress = win.keypress.clone();
@@ -2422,10 +3439,31 @@ pub struct SplitData {
     pub widget_id: WidgetId,
     pub children: Vec<SplitContent>,
     pub direction: SplitDirection,
+    /// The shares of the children, only kept in sync by the split widget
+    pub ratios: Vec<f64>,
     pub layout_rect: Rc<RefCell<Rect>>,
 }
 
 impl SplitData {
+    /// The flex of the child at `index`, even when no shares are known
+    pub fn ratio(&self, index: usize) -> f64 {
+        if self.ratios.len() == self.children.len() {
+            self.ratios[index]
+        } else {
+            1.0
+        }
+    }
+
+    /// The share of the split the child at `index` takes
+    pub fn share(&self, index: usize) -> f64 {
+        let total: f64 = (0..self.children.len()).map(|i| self.ratio(i)).sum();
+        if total > 0.0 {
+            self.ratio(index) / total
+        } else {
+            0.0
+        }
+    }
+
     pub fn split_info(&self, data: &LapceTabData) -> SplitInfo {
         let info = SplitInfo {
             direction: self.direction,
@@ -2434,11 +3472,116 @@ pub fn split_info(&self, data: &LapceTabData) -> SplitInfo {
                 .iter()
                 .map(|child| child.content_info(data))
                 .collect(),
+            ratios: if self.ratios.len() == self.children.len() {
+                self.ratios.clone()
+            } else {
+                Vec::new()
+            },
         };
         info
     }
 }
 
+/// What's left of a split after one of its children was removed from it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitRemoval {
+    /// The index the child had in the split
+    pub index: usize,
+    /// The child to focus, when the removed child was the active editor tab
+    pub focus: Option<WidgetId>,
+    /// Whether the split has no children left
+    pub emptied: bool,
+    /// The parent split and the index in it where the only child left took
+    /// the place of the split, which was pruned
+    pub collapsed: Option<(WidgetId, usize, SplitContent)>,
+}
+
+/// The editor tab that borders the editor tab on the side of `direction`
+pub fn adjacent_editor_tab(
+    editor_tabs: &im::HashMap<WidgetId, Arc<LapceEditorTabData>>,
+    editor_tab_id: WidgetId,
+    direction: SplitMoveDirection,
+) -> Option<WidgetId> {
+    let rect = *editor_tabs.get(&editor_tab_id)?.layout_rect.borrow();
+    editor_tabs
+        .values()
+        .find(|e| {
+            e.widget_id != editor_tab_id
+                && direction.is_adjacent(rect, *e.layout_rect.borrow())
+        })
+        .map(|e| e.widget_id)
+}
+
+/// Remove the child from the split, dropping the editor tab or the split it
+/// holds. A split left with a single child is replaced by that child in its
+/// parent split.
+pub fn remove_split_child(
+    editor_tabs: &mut im::HashMap<WidgetId, Arc<LapceEditorTabData>>,
+    splits: &mut im::HashMap<WidgetId, Arc<SplitData>>,
+    active_tab: Option<WidgetId>,
+    split_id: WidgetId,
+    content: &SplitContent,
+) -> Option<SplitRemoval> {
+    let split_data = Arc::make_mut(splits.get_mut(&split_id)?);
+    let index = split_data.children.iter().position(|c| c == content)?;
+    let is_active = match split_data.children.remove(index) {
+        SplitContent::EditorTab(tab_id) => {
+            editor_tabs.remove(&tab_id);
+            active_tab == Some(tab_id)
+        }
+        SplitContent::Split(child_split_id) => {
+            splits.remove(&child_split_id);
+            false
+        }
+    };
+
+    let split_data = splits.get(&split_id)?.clone();
+    let mut removal = SplitRemoval {
+        index,
+        focus: None,
+        emptied: split_data.children.is_empty(),
+        collapsed: None,
+    };
+    if removal.emptied {
+        return Some(removal);
+    }
+
+    if is_active {
+        let focus_index = index.min(split_data.children.len() - 1);
+        removal.focus = Some(split_data.children[focus_index].widget_id());
+    }
+
+    if let ([split_content], Some(parent_split_id)) =
+        (split_data.children.as_slice(), split_data.parent_split)
+    {
+        let split_content = *split_content;
+        let parent_split = Arc::make_mut(splits.get_mut(&parent_split_id)?);
+        if let Some(index) = parent_split
+            .children
+            .iter()
+            .position(|c| c == &SplitContent::Split(split_id))
+        {
+            parent_split.children[index] = split_content;
+            match split_content {
+                SplitContent::EditorTab(editor_tab_id) => {
+                    if let Some(editor_tab) = editor_tabs.get_mut(&editor_tab_id) {
+                        Arc::make_mut(editor_tab).split = parent_split_id;
+                    }
+                }
+                SplitContent::Split(id) => {
+                    if let Some(split) = splits.get_mut(&id) {
+                        Arc::make_mut(split).parent_split = Some(parent_split_id);
+                    }
+                }
+            }
+            splits.remove(&split_id);
+            removal.collapsed = Some((parent_split_id, index, split_content));
+        }
+    }
+
+    Some(removal)
+}
+
 // #[derive(Clone, Debug)]
 // pub enum EditorKind {
 //     PalettePreview,
@@ -2469,6 +3612,8 @@ pub struct LapceMainSplitData {
     pub db: Arc<LapceDb>,
     pub locations: Arc<Vec<EditorLocation>>,
     pub current_location: usize,
+    /// The recent strings of the search input of the workspace
+    pub find_history: Arc<FindHistory>,
 }
 
 impl LapceMainSplitData {
@@ -2513,7 +3658,17 @@ pub fn document_format(
         &mut self,
         path: &Path,
         rev: u64,
-        edits: &Result<Vec<TextEdit>>,
+        edits: &Result<Vec<TextEdit>, FormattingError>,
+    ) {
+        self.apply_formatting(path, rev, edits, EditType::Other);
+    }
+
+    fn apply_formatting(
+        &mut self,
+        path: &Path,
+        rev: u64,
+        edits: &Result<Vec<TextEdit>, FormattingError>,
+        edit_type: EditType,
     ) {
         let doc = self.open_docs.get(path).unwrap();
         if doc.rev() != rev {
@@ -2522,20 +3677,10 @@ pub fn document_format(
 
         if let Ok(edits) = edits {
             if !edits.is_empty() {
-                let doc = self.open_docs.get_mut(path).unwrap();
-
-                let edits = edits
-                    .iter()
-                    .map(|edit| {
-                        let start =
-                            doc.buffer().offset_of_position(&edit.range.start);
-                        let end = doc.buffer().offset_of_position(&edit.range.end);
-                        let selection = Selection::region(start, end);
-                        (selection, edit.new_text.as_str())
-                    })
-                    .collect::<Vec<(Selection, &str)>>();
-
-                self.edit(path, &edits, EditType::Other);
+                // The edits are applied at once, so that they're undone in a
+                // single step
+                let edits = doc.buffer().text_edit_regions(edits);
+                self.edit(path, &edits, edit_type);
             }
         }
     }
@@ -2545,13 +3690,69 @@ pub fn document_format_and_save(
         ctx: &mut EventCtx,
         path: &Path,
         rev: u64,
-        result: &Result<Vec<TextEdit>>,
+        result: &Result<Vec<TextEdit>, FormattingError>,
         exit_widget_id: Option<WidgetId>,
+        cleanup: &WhitespaceCleanup,
     ) {
-        self.document_format(path, rev, result);
+        if let Err(err) = result {
+            if err.should_warn() {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "The file was saved without formatting".to_string(),
+                        msg: format!(
+                            "{}: {err}",
+                            path.file_name().unwrap_or_default().to_string_lossy()
+                        ),
+                        timeout: Some(Duration::from_secs(5)),
+                        ..Default::default()
+                    }),
+                    Target::Widget(*self.tab_id),
+                ));
+            }
+        }
+        // The formatting and the cleanup are undone together
+        self.apply_formatting(path, rev, result, EditType::Save);
+        self.clean_up_whitespace(path, cleanup);
         self.document_save(ctx, path, exit_widget_id);
     }
 
+    /// Trim the trailing whitespace and end the document with a line ending
+    /// right before saving it, leaving the lines of the cursors of every
+    /// editor of the document as they are if asked to
+    pub fn clean_up_whitespace(&mut self, path: &Path, cleanup: &WhitespaceCleanup) {
+        let Some(doc) = self.open_docs.get(path) else {
+            return;
+        };
+        let cursor_lines = self.cursor_lines(path);
+        let edits = cleanup.edits(doc.buffer(), &cursor_lines);
+        if !edits.is_empty() {
+            self.edit(path, &edits, EditType::Save);
+        }
+    }
+
+    /// The lines with a cursor in the editors of the document
+    fn cursor_lines(&self, path: &Path) -> Vec<usize> {
+        let Some(doc) = self.open_docs.get(path) else {
+            return Vec::new();
+        };
+        let content = BufferContent::File(path.to_path_buf());
+        self.editors
+            .values()
+            .filter(|editor| editor.content == content)
+            .flat_map(|editor| match &editor.cursor.mode {
+                CursorMode::Normal(offset) => vec![*offset],
+                CursorMode::Visual { end, .. } => vec![*end],
+                CursorMode::Insert(selection) => selection
+                    .regions()
+                    .iter()
+                    .map(|region| region.end)
+                    .collect(),
+            })
+            .map(|offset| doc.buffer().line_of_offset(offset))
+            .collect()
+    }
+
     pub fn document_save(
         &mut self,
         ctx: &mut EventCtx,
@@ -2560,12 +3761,14 @@ pub fn document_save(
     ) {
         let doc = self.open_docs.get(path).unwrap();
         let rev = doc.rev();
+        let cr_line_endings = doc.buffer().line_ending() == LineEnding::Cr;
         let event_sink = ctx.get_external_handle();
         let path = PathBuf::from(path);
         let tab_id = *self.tab_id;
         self.proxy.proxy_rpc.save(
             rev,
             path.clone(),
+            cr_line_endings,
             Box::new(move |result| {
                 if let Ok(ProxyResponse::SaveResponse {}) = result {
                     let _ = event_sink.submit_command(
@@ -2582,29 +3785,42 @@ pub fn document_save(
         );
     }
 
-    pub fn diagnostics_items(
-        &self,
-        severity: DiagnosticSeverity,
-    ) -> Vec<(&PathBuf, Vec<&EditorDiagnostic>)> {
-        self.diagnostics
-            .iter()
+    /// Whether an editor shows the file
+    pub fn is_doc_shown(&self, path: &Path) -> bool {
+        let content = BufferContent::File(path.to_path_buf());
+        self.editors
+            .values()
+            .any(|editor| editor.content == content)
+    }
+
+    /// Close the document of a file which was only loaded to be looked at,
+    /// like in the peek popup, and tell the proxy it isn't open anymore
+    pub fn close_doc(&mut self, path: &Path) {
+        if self.open_docs.remove(path).is_some() {
+            self.proxy.proxy_rpc.close_buffer(path.to_path_buf());
+        }
+    }
+
+    /// The diagnostics of every file, taken from the open document when there
+    /// is one since those are kept in sync with its edits
+    pub fn file_diagnostics(
+        &self,
+    ) -> impl Iterator<Item = (&PathBuf, &[EditorDiagnostic])> {
+        self.diagnostics.iter().map(|(path, diagnostics)| {
+            let diagnostics = match self.open_docs.get(path) {
+                Some(doc) => doc.diagnostics.as_deref().map(|d| d.as_slice()),
+                None => Some(diagnostics.as_slice()),
+            };
+            (path, diagnostics.unwrap_or(&[]))
+        })
+    }
+
+    pub fn diagnostics_items(
+        &self,
+        severity: DiagnosticSeverity,
+    ) -> Vec<(&PathBuf, Vec<&EditorDiagnostic>)> {
+        self.file_diagnostics()
             .filter_map(|(path, diagnostic)| {
-                if let Some(doc) = self.open_docs.get(path) {
-                    return match doc.diagnostics.as_ref() {
-                        Some(d) => {
-                            let diagnostics: Vec<&EditorDiagnostic> = d
-                                .iter()
-                                .filter(|d| d.diagnostic.severity == Some(severity))
-                                .collect();
-                            if !diagnostics.is_empty() {
-                                Some((path, diagnostics))
-                            } else {
-                                None
-                            }
-                        }
-                        None => None,
-                    };
-                }
                 let diagnostics: Vec<&EditorDiagnostic> = diagnostic
                     .iter()
                     .filter(|d| d.diagnostic.severity == Some(severity))
@@ -2669,6 +3885,7 @@ pub fn get_active_tab_mut(
                 split: *self.split_id,
                 active: 0,
                 children: Vector::new(),
+                pinned: im::HashSet::new(),
                 layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
                 content_is_hot: Rc::new(RefCell::new(false)),
             };
@@ -2711,6 +3928,7 @@ fn new_editor_tab(
             split: split_id,
             active: 0,
             children: Vector::new(),
+            pinned: im::HashSet::new(),
             layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
             content_is_hot: Rc::new(RefCell::new(false)),
         };
@@ -2739,20 +3957,8 @@ fn editor_tab_new_settings(
     ) -> WidgetId {
         let editor_tab = self.editor_tabs.get_mut(&editor_tab_id).unwrap();
         let editor_tab = Arc::make_mut(editor_tab);
-        let editor = LapceEditorData::new(
-            None,
-            None,
-            None,
-            BufferContent::Local(LocalBufferKind::Keymap),
-            config,
-        );
-        let keymap_input_view_id = editor.view_id;
-        self.editors.insert(editor.view_id, Arc::new(editor));
-        let child = EditorTabChild::Settings {
-            settings_widget_id: WidgetId::next(),
-            editor_tab_id,
-            keymap_input_view_id,
-        };
+        let child =
+            EditorTabChild::new_settings(&mut self.editors, editor_tab_id, config);
         editor_tab.children.push_back(child.clone());
         child.widget_id()
     }
@@ -2983,6 +4189,32 @@ pub fn jump_to_position(
                 history: None,
             };
             self.jump_to_location(ctx, editor_view_id, same_tab, location, config);
+        } else if let BufferContent::Scratch(..) = &editor.content {
+            // A scratch buffer has no file to open, its editor is moved to the
+            // position instead
+            let view_id = editor.view_id;
+            let offset = self
+                .editor_doc(view_id)
+                .buffer()
+                .offset_of_position(&position);
+            let editor = Arc::make_mut(self.editors.get_mut(&view_id).unwrap());
+            editor.cursor = if config.core.modal {
+                Cursor::new(CursorMode::Normal(offset), None, None)
+            } else {
+                Cursor::new(CursorMode::Insert(Selection::caret(offset)), None, None)
+            };
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::EnsureCursorVisible(Some(
+                    EnsureVisiblePosition::CenterOfWindow,
+                )),
+                Target::Widget(view_id),
+            ));
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(view_id),
+            ));
         }
     }
 
@@ -3059,20 +4291,11 @@ pub fn open_settings(
                 if let Some(widget_id) = existing {
                     widget_id
                 } else {
-                    let editor = LapceEditorData::new(
-                        None,
-                        None,
-                        None,
-                        BufferContent::Local(LocalBufferKind::Keymap),
+                    let child = EditorTabChild::new_settings(
+                        &mut self.editors,
+                        editor_tab.widget_id,
                         config,
                     );
-                    let keymap_input_view_id = editor.view_id;
-                    self.editors.insert(editor.view_id, Arc::new(editor));
-                    let child = EditorTabChild::Settings {
-                        settings_widget_id: WidgetId::next(),
-                        editor_tab_id: editor_tab.widget_id,
-                        keymap_input_view_id,
-                    };
                     editor_tab
                         .children
                         .insert(editor_tab.active + 1, child.clone());
@@ -3186,6 +4409,87 @@ pub fn jump_to_location_cb<
         editor_view_id
     }
 
+    /// Send the command to an editor tab showing the file, opening the file in
+    /// one when none of them does. The command is sent through the event sink so
+    /// that it reaches an editor that was only just created
+    pub fn submit_to_file_editor(
+        &mut self,
+        ctx: &mut EventCtx,
+        path: &Path,
+        command: impl Fn() -> LapceUICommand + Send + 'static,
+        config: &LapceConfig,
+    ) {
+        let event_sink = ctx.get_external_handle();
+        let content = BufferContent::File(path.to_path_buf());
+        if let Some(editor) = self
+            .editors
+            .values()
+            .find(|e| e.tab_id.is_some() && e.content == content)
+        {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                command(),
+                Target::Widget(editor.view_id),
+            );
+            return;
+        }
+
+        let location = EditorLocation {
+            path: path.to_path_buf(),
+            position: None::<usize>,
+            scroll_offset: None,
+            history: None,
+        };
+        self.jump_to_location_cb(
+            ctx,
+            None,
+            false,
+            location,
+            config,
+            Some(
+                move |_: &mut EventCtx, main_split: &mut LapceMainSplitData| {
+                    if let Some(view_id) = *main_split.active {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            command(),
+                            Target::Widget(view_id),
+                        );
+                    }
+                },
+            ),
+        );
+    }
+
+    /// Apply an edit a plugin asked for, from an editor of the first file it
+    /// edits, or from the active editor when it only creates or renames files
+    pub fn apply_plugin_workspace_edit(
+        &mut self,
+        ctx: &mut EventCtx,
+        edit: WorkspaceEdit,
+        reply: Option<Arc<WorkspaceEditReply>>,
+        config: &LapceConfig,
+    ) {
+        let path = workspace_edits(&edit).and_then(|edits| {
+            edits.keys().filter_map(|url| url.to_file_path().ok()).min()
+        });
+        if let Some(path) = path {
+            self.submit_to_file_editor(
+                ctx,
+                &path,
+                move || {
+                    LapceUICommand::ApplyWorkspaceEdit(edit.clone(), reply.clone())
+                },
+                config,
+            );
+        } else if let Some(view_id) = *self.active {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ApplyWorkspaceEdit(edit, reply),
+                Target::Widget(view_id),
+            ));
+        }
+    }
+
     pub fn can_jump_location_backward(&self) -> bool {
         self.current_location >= 1
     }
@@ -3266,6 +4570,22 @@ pub fn install_theme(&mut self, ctx: &mut EventCtx, _config: &LapceConfig) {
         }
     }
 
+    pub fn export_keymaps(
+        &mut self,
+        ctx: &mut EventCtx,
+        config: &LapceConfig,
+        content: String,
+    ) {
+        let id = self.new_file(ctx, config);
+        let doc = self.scratch_docs.get_mut(&id).unwrap();
+        let doc = Arc::make_mut(doc);
+
+        #[cfg(feature = "lang-toml")]
+        doc.set_language(lapce_core::language::LapceLanguage::Toml);
+
+        doc.reload(Rope::from(content), true);
+    }
+
     pub fn export_theme(&mut self, ctx: &mut EventCtx, config: &LapceConfig) {
         let id = self.new_file(ctx, config);
         let doc = self.scratch_docs.get_mut(&id).unwrap();
@@ -3383,13 +4703,14 @@ pub fn go_to_location_cb<
                 doc.scroll_offset =
                     Vec2::new(info.scroll_offset.0, info.scroll_offset.1);
                 doc.cursor_offset = info.cursor_offset;
+                *doc.folds_mut() = Folds::new(info.folds);
             }
 
             let cb: Option<InitBufferContentCb> = cb.map(|cb| Box::new(cb) as _);
 
             // Since we don't have document loaded, we'll have to retrieve it from the proxy
             // So, the document is not immediately filled with content!
-            doc.retrieve_file(vec![(editor_view_id, location)], None, cb);
+            doc.retrieve_file(vec![(editor_view_id, location)], None, cb, config);
             self.open_docs.insert(path.clone(), Arc::new(doc));
         } else {
             let doc = self.open_docs.get_mut(&path).unwrap().clone();
@@ -3507,6 +4828,176 @@ pub fn jump_to_line(
             self.jump_to_location(ctx, editor_view_id, true, location, config);
         }
     }
+
+    /// Open a file given on the command line, selecting the part of it that was
+    /// asked for once it's loaded
+    pub fn open_file_location(
+        &mut self,
+        ctx: &mut EventCtx,
+        file: &FileLocation,
+        config: &LapceConfig,
+    ) {
+        let selection = match file.selection.clone() {
+            Some(selection) => selection,
+            None => {
+                self.jump_to_location(
+                    ctx,
+                    None,
+                    false,
+                    EditorLocation {
+                        path: file.path.clone(),
+                        position: None::<usize>,
+                        scroll_offset: None,
+                        history: None,
+                    },
+                    config,
+                );
+                return;
+            }
+        };
+
+        let line = match selection {
+            FileSelection::Lines { start, .. } => start,
+            FileSelection::Position { line, .. } => line,
+        };
+        let view_id = self
+            .get_editor_or_new(
+                ctx,
+                None,
+                false,
+                Some(file.path.clone()),
+                false,
+                config,
+            )
+            .view_id;
+        let modal = config.core.modal;
+        self.jump_to_location_cb(
+            ctx,
+            Some(view_id),
+            false,
+            EditorLocation {
+                path: file.path.clone(),
+                position: Some(LineCol { line, column: 0 }),
+                scroll_offset: None,
+                history: None,
+            },
+            config,
+            Some(
+                move |ctx: &mut EventCtx, main_split: &mut LapceMainSplitData| {
+                    main_split
+                        .select_file_selection(ctx, view_id, &selection, modal);
+                },
+            ),
+        );
+    }
+
+    fn select_file_selection(
+        &mut self,
+        ctx: &mut EventCtx,
+        view_id: WidgetId,
+        selection: &FileSelection,
+        modal: bool,
+    ) {
+        let doc = self.editor_doc(view_id);
+        let (start, end) = selection_region(doc.buffer(), selection);
+        let editor = match self.editors.get_mut(&view_id) {
+            Some(editor) => Arc::make_mut(editor),
+            None => return,
+        };
+        editor.cursor = if start == end {
+            if modal {
+                Cursor::new(CursorMode::Normal(start), None, None)
+            } else {
+                Cursor::new(CursorMode::Insert(Selection::caret(start)), None, None)
+            }
+        } else if modal {
+            // The end of a linewise selection is in its last line
+            let end = end.saturating_sub(1).max(start);
+            Cursor::new(
+                CursorMode::Visual {
+                    start,
+                    end,
+                    mode: VisualMode::Linewise,
+                },
+                None,
+                None,
+            )
+        } else {
+            Cursor::new(
+                CursorMode::Insert(Selection::region(start, end)),
+                None,
+                None,
+            )
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::EnsureCursorVisible(Some(
+                EnsureVisiblePosition::CenterOfWindow,
+            )),
+            Target::Widget(view_id),
+        ));
+    }
+
+    /// Read the document at the url through the proxy, it's shown once it's
+    /// loaded
+    pub fn open_url_document(&self, ctx: &mut EventCtx, url: Url) {
+        let tab_id = *self.tab_id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy
+            .proxy_rpc
+            .read_url(url.to_string(), move |result| {
+                let command = match result {
+                    Ok(ProxyResponse::ReadUrlResponse { content }) => {
+                        LapceUICommand::UrlDocumentLoaded { url, content }
+                    }
+                    Ok(_) => return,
+                    Err(err) => LapceUICommand::NewMessage {
+                        kind: MessageType::ERROR,
+                        title: "Could not open url".to_string(),
+                        message: format!("{url}: {}", err.message),
+                    },
+                };
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(tab_id),
+                );
+            });
+    }
+
+    /// Show the document read from the url in a scratch buffer named after it,
+    /// so that it's never written back
+    pub fn show_url_document(
+        &mut self,
+        ctx: &mut EventCtx,
+        url: &Url,
+        content: String,
+        config: &LapceConfig,
+    ) {
+        let tab_id = *self.tab_id;
+        let proxy = self.proxy.clone();
+        let buffer_id = BufferId::next();
+        let content_kind = BufferContent::Scratch(buffer_id, url.to_string());
+        let mut doc = Document::new(
+            content_kind.clone(),
+            tab_id,
+            ctx.get_external_handle(),
+            proxy,
+        );
+        if let Some(language) = LapceLanguage::from_path(Path::new(url.path())) {
+            doc.set_language(language);
+        }
+        doc.reload(Rope::from(content), true);
+        self.scratch_docs.insert(buffer_id, Arc::new(doc));
+
+        let editor = self.get_editor_or_new(ctx, None, true, None, true, config);
+        editor.content = content_kind;
+        editor.cursor = if config.core.modal {
+            Cursor::new(CursorMode::Normal(0), None, None)
+        } else {
+            Cursor::new(CursorMode::Insert(Selection::caret(0)), None, None)
+        };
+    }
 }
 
 impl LapceMainSplitData {
@@ -3560,6 +5051,12 @@ pub fn new(
         );
         editors.insert(editor.view_id, Arc::new(editor));
 
+        let find_history = if config.find.history_size > 0 {
+            db.get_find_history(&workspace).unwrap_or_default()
+        } else {
+            FindHistory::default()
+        };
+
         let mut main_split_data = Self {
             tab_id: Arc::new(tab_id),
             split_id,
@@ -3583,6 +5080,7 @@ pub fn new(
             db,
             locations: Arc::new(Vec::new()),
             current_location: 0,
+            find_history: Arc::new(find_history),
         };
 
         if let Some(info) = workspace_info {
@@ -3600,8 +5098,15 @@ pub fn new(
                 let unsaved_buffer = unsaved_buffers
                     .get(&path.to_str().unwrap().to_string())
                     .map(Rope::from);
-                Arc::make_mut(main_split_data.open_docs.get_mut(&path).unwrap())
-                    .retrieve_file(locations.clone(), unsaved_buffer, None);
+                let info = main_split_data
+                    .db
+                    .get_buffer_info(&main_split_data.workspace, &path);
+                let doc =
+                    Arc::make_mut(main_split_data.open_docs.get_mut(&path).unwrap());
+                if let Ok(info) = info {
+                    *doc.folds_mut() = Folds::new(info.folds);
+                }
+                doc.retrieve_file(locations.clone(), unsaved_buffer, None, config);
             }
         } else {
             main_split_data.splits.insert(
@@ -3611,6 +5116,7 @@ pub fn new(
                     widget_id: *main_split_data.split_id,
                     children: Vec::new(),
                     direction: SplitDirection::Vertical,
+                    ratios: Vec::new(),
                     layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
                 }),
             );
@@ -3755,6 +5261,50 @@ pub fn save_as_success(
         }
     }
 
+    /// Point the documents and editors of the files under the moved paths at
+    /// the files' new location, the moves being `(from, to)` pairs
+    pub fn move_paths(&mut self, moves: &[(PathBuf, PathBuf)]) {
+        let new_path = |path: &Path| {
+            moves
+                .iter()
+                .find_map(|(from, to)| moved_path(from, to, path))
+        };
+
+        let moved_docs = self
+            .open_docs
+            .keys()
+            .filter_map(|path| Some((path.clone(), new_path(path)?)))
+            .collect::<Vec<_>>();
+        for (path, new_path) in moved_docs {
+            if let Some(mut doc) = self.open_docs.remove(&path) {
+                // The proxy and the language servers get the document again at
+                // its new path, with the unsaved changes it has
+                self.proxy.proxy_rpc.close_buffer(path);
+                if doc.loaded() {
+                    self.proxy.proxy_rpc.restore_buffer(
+                        doc.id(),
+                        new_path.clone(),
+                        doc.buffer().to_string(),
+                        doc.rev(),
+                        doc.encoding().to_string(),
+                        doc.large_file(),
+                    );
+                }
+                Arc::make_mut(&mut doc)
+                    .set_content(BufferContent::File(new_path.clone()));
+                self.open_docs.insert(new_path, doc);
+            }
+        }
+
+        for (_, editor) in self.editors.iter_mut() {
+            if let BufferContent::File(path) = &editor.content {
+                if let Some(new_path) = new_path(path) {
+                    Arc::make_mut(editor).content = BufferContent::File(new_path);
+                }
+            }
+        }
+    }
+
     pub fn save_as(
         &mut self,
         ctx: &mut EventCtx,
@@ -3775,6 +5325,7 @@ pub fn save_as(
                     path.to_path_buf(),
                     doc.rev(),
                     doc.buffer().to_string(),
+                    doc.buffer().line_ending() == LineEnding::Cr,
                     Box::new(move |result| {
                         if let Ok(_r) = result {
                             let _ = event_sink.submit_command(
@@ -3841,27 +5392,28 @@ pub fn editor_close(
                             msg: "Your changes will be lost if you don't save them."
                                 .to_string(),
                             buttons: vec![
-                                (
-                                    "Save".to_string(),
-                                    view_id,
-                                    LapceCommand {
+                                AlertButton {
+                                    text: "Save".to_string(),
+                                    target: view_id,
+                                    command: LapceCommand {
                                         kind: CommandKind::Focus(
                                             FocusCommand::SaveAndExit,
                                         ),
                                         data: None,
                                     },
-                                ),
-                                (
-                                    "Don't Save".to_string(),
-                                    view_id,
-                                    LapceCommand {
+                                },
+                                AlertButton {
+                                    text: "Don't Save".to_string(),
+                                    target: view_id,
+                                    command: LapceCommand {
                                         kind: CommandKind::Focus(
                                             FocusCommand::ForceExit,
                                         ),
                                         data: None,
                                     },
-                                ),
+                                },
                             ],
+                            ..Default::default()
                         }),
                         Target::Widget(*self.tab_id),
                     ));
@@ -3937,6 +5489,7 @@ pub fn split(
                 widget_id: WidgetId::next(),
                 children,
                 direction,
+                ratios: Vec::new(),
                 layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
             };
             let new_split_id = new_split.widget_id;
@@ -3977,6 +5530,103 @@ pub fn split_exchange(&mut self, ctx: &mut EventCtx, content: SplitContent) {
         }
     }
 
+    pub fn split_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        content: SplitContent,
+        command: SplitCommand,
+    ) {
+        if let SplitCommand::MoveEditorToSplit { direction } = command {
+            if let SplitContent::EditorTab(editor_tab_id) = content {
+                self.move_editor_to_split(ctx, editor_tab_id, direction);
+            }
+        } else if let Some(split_id) = content.split_id(self) {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::SplitResize(content, command),
+                Target::Widget(split_id),
+            ));
+        }
+    }
+
+    /// Move the active child of the editor tab into the editor tab next to it
+    /// in `direction`, or into a new one split off when there's none. The
+    /// editor tab left empty gets removed along with its split.
+    fn move_editor_to_split(
+        &mut self,
+        ctx: &mut EventCtx,
+        editor_tab_id: WidgetId,
+        direction: SplitMoveDirection,
+    ) {
+        let editor_tab = match self.editor_tabs.get(&editor_tab_id) {
+            Some(editor_tab) => editor_tab.clone(),
+            None => return,
+        };
+        let from_index = editor_tab.active;
+        let mut child = match editor_tab.children.get(from_index) {
+            Some(child) => child.clone(),
+            None => return,
+        };
+        let target_id =
+            adjacent_editor_tab(&self.editor_tabs, editor_tab_id, direction);
+
+        if let Some(target_id) = target_id {
+            child.set_editor_tab(self, target_id);
+            let target =
+                Arc::make_mut(self.editor_tabs.get_mut(&target_id).unwrap());
+            let index = target.insert_after_active(child.clone());
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::EditorTabAdd(index, child.clone()),
+                Target::Widget(target_id),
+            ));
+        } else {
+            // splitting off the only child would just leave it where it was
+            if editor_tab.children.len() == 1 {
+                return;
+            }
+            let new_editor_tab_id = WidgetId::next();
+            child.set_editor_tab(self, new_editor_tab_id);
+            let (split_direction, shift_current) = direction.split_direction();
+            let mut new_editor_tab = LapceEditorTabData {
+                widget_id: new_editor_tab_id,
+                split: editor_tab.split,
+                active: 0,
+                children: vec![child.clone()].into(),
+                pinned: im::HashSet::new(),
+                layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
+                content_is_hot: Rc::new(RefCell::new(false)),
+            };
+            let new_split_id = self.split(
+                ctx,
+                editor_tab.split,
+                SplitContent::EditorTab(editor_tab_id),
+                SplitContent::EditorTab(new_editor_tab_id),
+                split_direction,
+                shift_current,
+                true,
+            );
+            new_editor_tab.split = new_split_id;
+            if editor_tab.split != new_split_id {
+                Arc::make_mut(self.editor_tabs.get_mut(&editor_tab_id).unwrap())
+                    .split = new_split_id;
+            }
+            self.editor_tabs
+                .insert(new_editor_tab_id, Arc::new(new_editor_tab));
+        }
+
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::EditorTabRemove(from_index, false, false),
+            Target::Widget(editor_tab_id),
+        ));
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(child.widget_id()),
+        ));
+    }
+
     pub fn split_move(
         &mut self,
         ctx: &mut EventCtx,
@@ -3987,70 +5637,14 @@ pub fn split_move(
             SplitContent::EditorTab(widget_id) => {
                 let editor_tab = self.editor_tabs.get(&widget_id).unwrap();
                 let rect = editor_tab.layout_rect.borrow();
-                match direction {
-                    SplitMoveDirection::Up => {
-                        for (_, e) in self.editor_tabs.iter() {
-                            let current_rect = e.layout_rect.borrow();
-                            if current_rect.y1 == rect.y0
-                                && current_rect.x0 <= rect.x0
-                                && rect.x0 < current_rect.x1
-                            {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::Focus,
-                                    Target::Widget(e.children[e.active].widget_id()),
-                                ));
-                                return;
-                            }
-                        }
-                    }
-                    SplitMoveDirection::Down => {
-                        for (_, e) in self.editor_tabs.iter() {
-                            let current_rect = e.layout_rect.borrow();
-                            if current_rect.y0 == rect.y1
-                                && current_rect.x0 <= rect.x0
-                                && rect.x0 < current_rect.x1
-                            {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::Focus,
-                                    Target::Widget(e.children[e.active].widget_id()),
-                                ));
-                                return;
-                            }
-                        }
-                    }
-                    SplitMoveDirection::Right => {
-                        for (_, e) in self.editor_tabs.iter() {
-                            let current_rect = e.layout_rect.borrow();
-                            if current_rect.x0 == rect.x1
-                                && current_rect.y0 <= rect.y0
-                                && rect.y0 < current_rect.y1
-                            {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::Focus,
-                                    Target::Widget(e.children[e.active].widget_id()),
-                                ));
-                                return;
-                            }
-                        }
-                    }
-                    SplitMoveDirection::Left => {
-                        for (_, e) in self.editor_tabs.iter() {
-                            let current_rect = e.layout_rect.borrow();
-                            if current_rect.x1 == rect.x0
-                                && current_rect.y0 <= rect.y0
-                                && rect.y0 < current_rect.y1
-                            {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::Focus,
-                                    Target::Widget(e.children[e.active].widget_id()),
-                                ));
-                                return;
-                            }
-                        }
+                for (_, e) in self.editor_tabs.iter() {
+                    if direction.is_adjacent(*rect, *e.layout_rect.borrow()) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::Focus,
+                            Target::Widget(e.children[e.active].widget_id()),
+                        ));
+                        return;
                     }
                 }
             }
@@ -4082,6 +5676,7 @@ fn split_plugin(
                 volt_name,
             }]
             .into(),
+            pinned: im::HashSet::new(),
             layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
             content_is_hot: Rc::new(RefCell::new(false)),
         };
@@ -4116,27 +5711,18 @@ pub fn split_settings(
         let editor_tab = self.editor_tabs.get(&editor_tab_id).unwrap();
         let split_id = editor_tab.split;
 
-        let editor = LapceEditorData::new(
-            None,
-            None,
-            None,
-            BufferContent::Local(LocalBufferKind::Keymap),
-            config,
-        );
-        let keymap_input_view_id = editor.view_id;
-        self.editors.insert(editor.view_id, Arc::new(editor));
-
         let new_editor_tab_id = WidgetId::next();
         let mut new_editor_tab = LapceEditorTabData {
             widget_id: new_editor_tab_id,
             split: split_id,
             active: 0,
-            children: vec![EditorTabChild::Settings {
-                settings_widget_id: WidgetId::next(),
-                editor_tab_id: new_editor_tab_id,
-                keymap_input_view_id,
-            }]
+            children: vec![EditorTabChild::new_settings(
+                &mut self.editors,
+                new_editor_tab_id,
+                config,
+            )]
             .into(),
+            pinned: im::HashSet::new(),
             layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
             content_is_hot: Rc::new(RefCell::new(false)),
         };
@@ -4218,6 +5804,7 @@ pub fn split_editor(
                     new_editor.find_view_id,
                 )]
                 .into(),
+                pinned: im::HashSet::new(),
                 layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
                 content_is_hot: Rc::new(RefCell::new(false)),
             };
@@ -4266,6 +5853,8 @@ pub enum EditorTabChild {
         settings_widget_id: WidgetId,
         editor_tab_id: WidgetId,
         keymap_input_view_id: WidgetId,
+        /// The input searching the settings
+        settings_input_view_id: WidgetId,
     },
     Plugin {
         widget_id: WidgetId,
@@ -4276,6 +5865,35 @@ pub enum EditorTabChild {
 }
 
 impl EditorTabChild {
+    /// A settings tab, with its own inputs filtering the keymaps and the
+    /// settings
+    pub fn new_settings(
+        editors: &mut im::HashMap<WidgetId, Arc<LapceEditorData>>,
+        editor_tab_id: WidgetId,
+        config: &LapceConfig,
+    ) -> Self {
+        let mut new_input = |kind: LocalBufferKind| {
+            let editor = LapceEditorData::new(
+                None,
+                None,
+                None,
+                BufferContent::Local(kind),
+                config,
+            );
+            let view_id = editor.view_id;
+            editors.insert(view_id, Arc::new(editor));
+            view_id
+        };
+        let keymap_input_view_id = new_input(LocalBufferKind::Keymap);
+        let settings_input_view_id = new_input(LocalBufferKind::Settings);
+        EditorTabChild::Settings {
+            settings_widget_id: WidgetId::next(),
+            editor_tab_id,
+            keymap_input_view_id,
+            settings_input_view_id,
+        }
+    }
+
     pub fn widget_id(&self) -> WidgetId {
         match &self {
             EditorTabChild::Editor(widget_id, _, _) => *widget_id,
@@ -4304,12 +5922,12 @@ pub fn child_info(&self, data: &LapceTabData) -> EditorTabChildInfo {
 
     pub fn set_editor_tab(
         &mut self,
-        data: &mut LapceTabData,
+        main_split: &mut LapceMainSplitData,
         editor_tab_widget_id: WidgetId,
     ) {
         match self {
             EditorTabChild::Editor(view_id, _, _) => {
-                let editor_data = data.main_split.editors.get_mut(view_id).unwrap();
+                let editor_data = main_split.editors.get_mut(view_id).unwrap();
                 let editor_data = Arc::make_mut(editor_data);
                 editor_data.tab_id = Some(editor_tab_widget_id);
             }
@@ -4330,6 +5948,9 @@ pub struct LapceEditorTabData {
     pub split: WidgetId,
     pub active: usize,
     pub children: Vector<EditorTabChild>,
+    /// The children pinned, which are kept at the start of the tabs and aren't
+    /// closed along with the others
+    pub pinned: im::HashSet<WidgetId>,
     pub layout_rect: Rc<RefCell<Rect>>,
     pub content_is_hot: Rc<RefCell<bool>>,
 }
@@ -4344,6 +5965,7 @@ pub fn tab_info(&self, data: &LapceTabData) -> EditorTabInfo {
                 .iter()
                 .map(|child| child.child_info(data))
                 .collect(),
+            pinned: self.pinned_indices(),
         };
         info
     }
@@ -4351,6 +5973,109 @@ pub fn tab_info(&self, data: &LapceTabData) -> EditorTabInfo {
     pub fn active_child(&self) -> Option<&EditorTabChild> {
         self.children.get(self.active)
     }
+
+    /// Insert the child after the active one and make it the active one,
+    /// returning its index
+    pub fn insert_after_active(&mut self, child: EditorTabChild) -> usize {
+        let index = (self.active + 1).min(self.children.len());
+        self.children.insert(index, child);
+        self.active = index;
+        index
+    }
+
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.children
+            .get(index)
+            .map(|child| self.pinned.contains(&child.widget_id()))
+            .unwrap_or(false)
+    }
+
+    /// The indices of the pinned children, in the order of the tabs
+    pub fn pinned_indices(&self) -> Vec<usize> {
+        (0..self.children.len())
+            .filter(|i| self.is_pinned(*i))
+            .collect()
+    }
+
+    /// Pin the children at the indices, like when the tabs are restored
+    pub fn pin_indices(&mut self, indices: &[usize]) {
+        self.pinned = indices
+            .iter()
+            .filter_map(|i| self.children.get(*i))
+            .map(|child| child.widget_id())
+            .collect();
+    }
+
+    /// Where a child that isn't pinned goes when it's put at the index, which is
+    /// after the pinned ones
+    pub fn unpinned_index(&self, index: usize) -> usize {
+        index.max(self.pinned_indices().len())
+    }
+
+    /// Move the child, keeping the same child active. The pinned children stay
+    /// at the start of the tabs, so the child only moves among the ones pinned
+    /// like it. Returns where it's moved to.
+    pub fn move_child(&mut self, from: usize, to: usize) -> usize {
+        if from >= self.children.len() {
+            return from;
+        }
+        let to = to.min(self.children.len() - 1);
+        let to = if self.is_pinned(from) {
+            to.min(self.pinned_indices().len().saturating_sub(1))
+        } else {
+            self.unpinned_index(to)
+        };
+        let child = self.children.remove(from);
+        self.children.insert(to, child);
+        if self.active == from {
+            self.active = to;
+        } else if from < self.active && self.active <= to {
+            self.active -= 1;
+        } else if to <= self.active && self.active < from {
+            self.active += 1;
+        }
+        to
+    }
+
+    /// Pin the child and move it after the other pinned ones. Returns where it's
+    /// moved to.
+    pub fn pin(&mut self, index: usize) -> usize {
+        if index >= self.children.len() || self.is_pinned(index) {
+            return index;
+        }
+        let to = self.pinned_indices().len();
+        self.pinned.insert(self.children[index].widget_id());
+        self.move_child(index, to);
+        to
+    }
+
+    /// Unpin the child and move it before the other unpinned ones. Returns
+    /// where it's moved to.
+    pub fn unpin(&mut self, index: usize) -> usize {
+        if !self.is_pinned(index) {
+            return index;
+        }
+        self.pinned.remove(&self.children[index].widget_id());
+        let to = self.pinned_indices().len();
+        self.move_child(index, to);
+        to
+    }
+
+    /// The children to close when closing the tabs in the range, except the one
+    /// to keep, which leaves the pinned ones open. The last ones come first, so
+    /// that closing them doesn't move the others.
+    pub fn closable_children(
+        &self,
+        range: Range<usize>,
+        keep: Option<usize>,
+    ) -> Vec<WidgetId> {
+        range
+            .rev()
+            .filter(|i| Some(*i) != keep && !self.is_pinned(*i))
+            .filter_map(|i| self.children.get(i))
+            .map(|child| child.widget_id())
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -4398,11 +6123,12 @@ pub struct LapceEditorData {
     pub size: Rc<RefCell<Size>>,
     pub sticky_header: Rc<RefCell<StickyHeaderInfo>>,
     pub window_origin: Rc<RefCell<Point>>,
-    pub snippet: Option<Vec<(usize, (usize, usize))>>,
+    pub snippet: Option<ActiveSnippet>,
     pub last_movement_new: Movement,
     pub last_inline_find: Option<(InlineFindDirection, String)>,
     pub inline_find: Option<InlineFindDirection>,
     pub motion_mode: Option<MotionMode>,
+    pub selection_range_stack: SelectionRangeStack,
 }
 
 impl LapceEditorData {
@@ -4447,6 +6173,7 @@ pub fn new(
             inline_find: None,
             last_inline_find: None,
             motion_mode: None,
+            selection_range_stack: SelectionRangeStack::default(),
         }
     }
 
@@ -4466,31 +6193,43 @@ pub fn is_code_lens(&self) -> bool {
         matches!(self.view, EditorView::Lens)
     }
 
-    pub fn add_snippet_placeholders(
-        &mut self,
-        new_placeholders: Vec<(usize, (usize, usize))>,
-    ) {
-        if self.snippet.is_none() {
-            if new_placeholders.len() > 1 {
-                self.snippet = Some(new_placeholders);
-            }
-            return;
+    /// The width the lines are soft wrapped at, or `None` if they aren't
+    /// wrapped. Only files and scratch buffers in the normal view are wrapped.
+    pub fn wrap_width(
+        &self,
+        text: &mut PietText,
+        config: &LapceConfig,
+    ) -> Option<f64> {
+        if !self.view.is_normal() || self.compare.is_some() {
+            return None;
+        }
+        if !matches!(
+            self.content,
+            BufferContent::File(_) | BufferContent::Scratch(..)
+        ) {
+            return None;
         }
 
-        let placeholders = self.snippet.as_mut().unwrap();
+        let wrap_mode = config.editor.wrap_mode();
+        let viewport_width = self.size.borrow().width;
+        if wrap_mode == WrapMode::Viewport && viewport_width <= 0.0 {
+            // The editor hasn't been laid out yet
+            return None;
+        }
+        // Leave a column for the cursor at the end of the line
+        let char_width = config.editor_char_width(text);
+        wrap_mode.width(viewport_width - char_width, char_width)
+    }
 
-        let mut current = 0;
-        let offset = self.cursor.offset();
-        for (i, (_, (start, end))) in placeholders.iter().enumerate() {
-            if *start <= offset && offset <= *end {
-                current = i;
-                break;
+    pub fn add_snippet(&mut self, new_snippet: ActiveSnippet) {
+        match self.snippet.as_mut() {
+            Some(snippet) => snippet.nest(new_snippet),
+            None => {
+                if !new_snippet.is_finished() {
+                    self.snippet = Some(new_snippet);
+                }
             }
         }
-
-        let v = placeholders.split_off(current);
-        placeholders.extend_from_slice(&new_placeholders);
-        placeholders.extend_from_slice(&v[1..]);
     }
 
     pub fn editor_info(&self, data: &LapceTabData) -> EditorInfo {
@@ -4603,6 +6342,55 @@ fn default() -> Self {
     }
 }
 
+/// The home directory of the user, looked up once since the paths are shown
+/// while painting
+static HOME_DIR: Lazy<Option<PathBuf>> =
+    Lazy::new(|| directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()));
+
+impl LapceWorkspace {
+    /// The home directory the paths of the workspace are in, which isn't known
+    /// for a remote workspace
+    pub fn home_dir(&self) -> Option<&'static Path> {
+        if self.kind.is_remote() {
+            None
+        } else {
+            HOME_DIR.as_deref()
+        }
+    }
+
+    /// The path as shown to the user, relative to the workspace when it's in
+    /// it, or else to the home directory
+    pub fn display_path(&self, path: &Path) -> PathBuf {
+        let roots: Vec<&Path> = self.path.iter().map(|p| p.as_path()).collect();
+        display_path(path, &roots, self.home_dir())
+    }
+}
+
+/// The path relative to the root of the workspace it's in. With several roots,
+/// the name of the root comes first, to tell the files of each apart. A path
+/// outside of the workspace is shown from the home directory as `~` if it's in
+/// it, or else as it is.
+pub fn display_path(path: &Path, roots: &[&Path], home: Option<&Path>) -> PathBuf {
+    // A root inside another one is the one the path belongs to
+    let root = roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count());
+    if let Some(root) = root {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        return match root.file_name() {
+            Some(name) if relative.as_os_str().is_empty() => PathBuf::from(name),
+            Some(name) if roots.len() > 1 => Path::new(name).join(relative),
+            _ => relative.to_path_buf(),
+        };
+    }
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(relative) if relative.as_os_str().is_empty() => PathBuf::from("~"),
+        Some(relative) => Path::new("~").join(relative),
+        None => path.to_path_buf(),
+    }
+}
+
 impl std::fmt::Display for LapceWorkspace {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -4613,3 +6401,515 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::RefCell,
+        path::{Path, PathBuf},
+        rc::Rc,
+        sync::Arc,
+    };
+
+    use druid::{Rect, WidgetId};
+    use lapce_rpc::plugin::VoltID;
+
+    use super::{
+        adjacent_editor_tab, display_path, remove_split_child, EditorTabChild,
+        LapceEditorTabData, SplitContent, SplitData, SplitRemoval,
+    };
+    use crate::{
+        db::{EditorTabChildInfo, EditorTabInfo},
+        split::{SplitDirection, SplitMoveDirection},
+    };
+
+    fn plugin(name: &str) -> EditorTabChild {
+        EditorTabChild::Plugin {
+            widget_id: WidgetId::next(),
+            volt_id: VoltID {
+                author: "lapce".to_string(),
+                name: name.to_string(),
+            },
+            volt_name: name.to_string(),
+            editor_tab_id: WidgetId::next(),
+        }
+    }
+
+    fn editor_tab(names: &[&str]) -> LapceEditorTabData {
+        LapceEditorTabData {
+            widget_id: WidgetId::next(),
+            split: WidgetId::next(),
+            active: 0,
+            children: names.iter().map(|name| plugin(name)).collect(),
+            pinned: im::HashSet::new(),
+            layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
+            content_is_hot: Rc::new(RefCell::new(false)),
+        }
+    }
+
+    fn names(editor_tab: &LapceEditorTabData) -> Vec<String> {
+        editor_tab
+            .children
+            .iter()
+            .map(|child| match child {
+                EditorTabChild::Plugin { volt_name, .. } => volt_name.to_string(),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    fn split(
+        parent_split: Option<WidgetId>,
+        children: &[SplitContent],
+    ) -> SplitData {
+        SplitData {
+            parent_split,
+            widget_id: WidgetId::next(),
+            children: children.to_vec(),
+            direction: SplitDirection::Vertical,
+            ratios: Vec::new(),
+            layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
+        }
+    }
+
+    #[test]
+    fn test_split_share() {
+        let children: Vec<_> = (0..4)
+            .map(|_| SplitContent::EditorTab(WidgetId::next()))
+            .collect();
+        let mut split = split(None, &children);
+        // The children share the split evenly until they're resized
+        assert_eq!(split.share(1), 0.25);
+
+        split.ratios = vec![0.5, 0.25, 0.125, 0.125];
+        assert_eq!(split.share(0), 0.5);
+        assert_eq!(split.share(3), 0.125);
+    }
+
+    #[test]
+    fn test_adjacent_editor_tab() {
+        let left = editor_tab(&["a"]);
+        *left.layout_rect.borrow_mut() = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let right = editor_tab(&["b"]);
+        *right.layout_rect.borrow_mut() = Rect::new(100.0, 0.0, 200.0, 100.0);
+        let (left_id, right_id) = (left.widget_id, right.widget_id);
+        let editor_tabs: im::HashMap<_, _> = [left, right]
+            .into_iter()
+            .map(|editor_tab| (editor_tab.widget_id, Arc::new(editor_tab)))
+            .collect();
+
+        let adjacent =
+            |id, direction| adjacent_editor_tab(&editor_tabs, id, direction);
+        assert_eq!(adjacent(left_id, SplitMoveDirection::Right), Some(right_id));
+        assert_eq!(adjacent(right_id, SplitMoveDirection::Left), Some(left_id));
+        assert_eq!(adjacent(left_id, SplitMoveDirection::Left), None);
+        assert_eq!(adjacent(left_id, SplitMoveDirection::Down), None);
+        assert_eq!(adjacent(WidgetId::next(), SplitMoveDirection::Left), None);
+    }
+
+    #[test]
+    fn test_insert_after_active() {
+        let mut editor_tab = editor_tab(&["a", "b", "c"]);
+        assert_eq!(editor_tab.insert_after_active(plugin("d")), 1);
+        assert_eq!(names(&editor_tab), vec!["a", "d", "b", "c"]);
+        assert_eq!(editor_tab.active, 1);
+
+        editor_tab.active = 3;
+        assert_eq!(editor_tab.insert_after_active(plugin("e")), 4);
+        assert_eq!(names(&editor_tab), vec!["a", "d", "b", "c", "e"]);
+        assert_eq!(editor_tab.active, 4);
+    }
+
+    /// A root split holding the editor tab `a` and a nested split, which holds
+    /// the editor tabs `b` and `c`
+    #[allow(clippy::type_complexity)]
+    fn split_tree() -> (
+        im::HashMap<WidgetId, Arc<LapceEditorTabData>>,
+        im::HashMap<WidgetId, Arc<SplitData>>,
+        WidgetId,
+        WidgetId,
+        [WidgetId; 3],
+    ) {
+        let root_id = WidgetId::next();
+        let nested_id = WidgetId::next();
+        let [a, b, c] = [WidgetId::next(), WidgetId::next(), WidgetId::next()];
+
+        let mut root = split(
+            None,
+            &[SplitContent::EditorTab(a), SplitContent::Split(nested_id)],
+        );
+        root.widget_id = root_id;
+        let mut nested = split(
+            Some(root_id),
+            &[SplitContent::EditorTab(b), SplitContent::EditorTab(c)],
+        );
+        nested.widget_id = nested_id;
+        let splits = [root, nested]
+            .into_iter()
+            .map(|split| (split.widget_id, Arc::new(split)))
+            .collect();
+
+        let editor_tabs = [(a, root_id), (b, nested_id), (c, nested_id)]
+            .into_iter()
+            .map(|(id, split)| {
+                let mut tab = editor_tab(&["file"]);
+                tab.widget_id = id;
+                tab.split = split;
+                (id, Arc::new(tab))
+            })
+            .collect();
+
+        (editor_tabs, splits, root_id, nested_id, [a, b, c])
+    }
+
+    #[test]
+    fn test_remove_split_child_prunes_split() {
+        let (mut editor_tabs, mut splits, root_id, nested_id, [a, b, c]) =
+            split_tree();
+
+        // The nested split is left with `b` only, which takes its place
+        let removal = remove_split_child(
+            &mut editor_tabs,
+            &mut splits,
+            Some(c),
+            nested_id,
+            &SplitContent::EditorTab(c),
+        );
+        assert_eq!(
+            removal,
+            Some(SplitRemoval {
+                index: 1,
+                focus: Some(b),
+                emptied: false,
+                collapsed: Some((root_id, 1, SplitContent::EditorTab(b))),
+            })
+        );
+        assert!(!editor_tabs.contains_key(&c));
+        assert!(!splits.contains_key(&nested_id));
+        assert_eq!(
+            splits[&root_id].children,
+            vec![SplitContent::EditorTab(a), SplitContent::EditorTab(b)]
+        );
+        assert_eq!(editor_tabs[&b].split, root_id);
+
+        // The root split isn't pruned, even with a single child
+        let removal = remove_split_child(
+            &mut editor_tabs,
+            &mut splits,
+            Some(b),
+            root_id,
+            &SplitContent::EditorTab(a),
+        );
+        assert_eq!(
+            removal,
+            Some(SplitRemoval {
+                index: 0,
+                focus: None,
+                emptied: false,
+                collapsed: None,
+            })
+        );
+        assert_eq!(splits[&root_id].children, vec![SplitContent::EditorTab(b)]);
+
+        let removal = remove_split_child(
+            &mut editor_tabs,
+            &mut splits,
+            Some(b),
+            root_id,
+            &SplitContent::EditorTab(b),
+        );
+        assert_eq!(
+            removal,
+            Some(SplitRemoval {
+                index: 0,
+                focus: None,
+                emptied: true,
+                collapsed: None,
+            })
+        );
+        assert!(editor_tabs.is_empty());
+        assert!(splits[&root_id].children.is_empty());
+    }
+
+    #[test]
+    fn test_remove_split_child_drops_nested_split() {
+        let (mut editor_tabs, mut splits, root_id, nested_id, [a, ..]) =
+            split_tree();
+
+        assert_eq!(
+            remove_split_child(
+                &mut editor_tabs,
+                &mut splits,
+                None,
+                root_id,
+                &SplitContent::Split(nested_id),
+            ),
+            Some(SplitRemoval {
+                index: 1,
+                focus: None,
+                emptied: false,
+                collapsed: None,
+            })
+        );
+        assert!(!splits.contains_key(&nested_id));
+        assert_eq!(splits[&root_id].children, vec![SplitContent::EditorTab(a)]);
+
+        // A child that isn't in the split leaves it as it is
+        assert_eq!(
+            remove_split_child(
+                &mut editor_tabs,
+                &mut splits,
+                None,
+                root_id,
+                &SplitContent::Split(nested_id),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pin_tabs() {
+        let mut editor_tab = editor_tab(&["a", "b", "c", "d"]);
+        editor_tab.active = 2;
+
+        // A pinned tab goes after the other pinned ones
+        assert_eq!(editor_tab.pin(2), 0);
+        assert_eq!(editor_tab.pin(3), 1);
+        assert_eq!(names(&editor_tab), vec!["c", "d", "a", "b"]);
+        assert_eq!(editor_tab.pinned_indices(), vec![0, 1]);
+        // The active tab moves along with its child
+        assert_eq!(editor_tab.active, 0);
+
+        // An unpinned tab goes before the other unpinned ones
+        assert_eq!(editor_tab.unpin(0), 1);
+        assert_eq!(names(&editor_tab), vec!["d", "c", "a", "b"]);
+        assert_eq!(editor_tab.pinned_indices(), vec![0]);
+        assert_eq!(editor_tab.active, 1);
+        assert_eq!(editor_tab.unpin(2), 2);
+    }
+
+    #[test]
+    fn test_move_tabs_around_pinned_ones() {
+        let mut editor_tab = editor_tab(&["a", "b", "c", "d"]);
+        editor_tab.pin(2);
+        editor_tab.pin(3);
+        assert_eq!(names(&editor_tab), vec!["c", "d", "a", "b"]);
+
+        // A tab that isn't pinned stops after the pinned ones
+        assert_eq!(editor_tab.move_child(3, 0), 2);
+        assert_eq!(names(&editor_tab), vec!["c", "d", "b", "a"]);
+        assert_eq!(editor_tab.move_child(2, 10), 3);
+        assert_eq!(names(&editor_tab), vec!["c", "d", "a", "b"]);
+
+        // A pinned tab stays among the pinned ones
+        assert_eq!(editor_tab.move_child(0, 3), 1);
+        assert_eq!(names(&editor_tab), vec!["d", "c", "a", "b"]);
+        assert_eq!(editor_tab.pinned_indices(), vec![0, 1]);
+        assert_eq!(editor_tab.unpinned_index(0), 2);
+        assert_eq!(editor_tab.unpinned_index(3), 3);
+    }
+
+    #[test]
+    fn test_close_all_leaves_pinned_tabs() {
+        let mut editor_tab = editor_tab(&["a", "b", "c", "d", "e"]);
+        editor_tab.pin(1);
+        editor_tab.pin(4);
+        assert_eq!(names(&editor_tab), vec!["b", "e", "a", "c", "d"]);
+
+        let ids = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|i| editor_tab.children[*i].widget_id())
+                .collect::<Vec<_>>()
+        };
+        // The last ones come first, so closing them doesn't move the others
+        assert_eq!(editor_tab.closable_children(0..5, None), ids(&[4, 3, 2]));
+        assert_eq!(editor_tab.closable_children(0..5, Some(3)), ids(&[4, 2]));
+        assert_eq!(editor_tab.closable_children(0..3, None), ids(&[2]));
+        assert!(editor_tab.closable_children(0..2, None).is_empty());
+    }
+
+    #[test]
+    fn test_restore_pinned_tabs() {
+        let mut editor_tab = editor_tab(&["a", "b", "c", "d"]);
+        editor_tab.pin(3);
+        editor_tab.pin(2);
+        editor_tab.pin(3);
+        assert_eq!(names(&editor_tab), vec!["d", "b", "c", "a"]);
+
+        let info = EditorTabInfo {
+            active: editor_tab.active,
+            is_focus: false,
+            children: editor_tab
+                .children
+                .iter()
+                .map(|child| match child {
+                    EditorTabChild::Plugin {
+                        volt_id, volt_name, ..
+                    } => EditorTabChildInfo::Plugin {
+                        volt_id: volt_id.clone(),
+                        volt_name: volt_name.to_string(),
+                    },
+                    _ => EditorTabChildInfo::Settings,
+                })
+                .collect(),
+            pinned: editor_tab.pinned_indices(),
+        };
+        let info: EditorTabInfo =
+            serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+
+        // The restored children get new ids
+        let restored =
+            info.to_tab_data(editor_tab.split, |child, editor_tab_id| match child {
+                EditorTabChildInfo::Plugin { volt_id, volt_name } => {
+                    EditorTabChild::Plugin {
+                        widget_id: WidgetId::next(),
+                        volt_id: volt_id.clone(),
+                        volt_name: volt_name.to_string(),
+                        editor_tab_id,
+                    }
+                }
+                _ => plugin(""),
+            });
+        assert_eq!(names(&restored), vec!["d", "b", "c", "a"]);
+        assert_eq!(restored.pinned_indices(), vec![0, 1, 2]);
+        assert_eq!(restored.active, editor_tab.active);
+
+        // The tabs saved before pinning existed have none pinned
+        let info: EditorTabInfo = serde_json::from_str(
+            r#"{"active":0,"is_focus":false,"children":["Settings"]}"#,
+        )
+        .unwrap();
+        assert!(info.pinned.is_empty());
+    }
+
+    #[cfg(not(windows))]
+    const SEPARATOR: &str = "/";
+    #[cfg(windows)]
+    const SEPARATOR: &str = "\\";
+
+    /// The path with the separator of the platform, from one written with `/`
+    fn native(path: &str) -> PathBuf {
+        let path = path.replace('/', SEPARATOR);
+        if cfg!(windows) && path.starts_with(SEPARATOR) {
+            PathBuf::from(format!("C:{path}"))
+        } else {
+            PathBuf::from(path)
+        }
+    }
+
+    #[test]
+    fn test_display_path_in_workspace() {
+        let root = native("/home/user/project");
+        let roots = [root.as_path()];
+        let home = native("/home/user");
+        let path = native("/home/user/project/src/main.rs");
+        assert_eq!(
+            display_path(&path, &roots, Some(&home)),
+            native("src/main.rs")
+        );
+        // The root itself is shown by its name
+        assert_eq!(display_path(&root, &roots, Some(&home)), native("project"));
+        // A sibling folder sharing the start of the name isn't in the root
+        let path = native("/home/user/project-old/main.rs");
+        assert_eq!(
+            display_path(&path, &roots, Some(&home)),
+            native("~/project-old/main.rs")
+        );
+    }
+
+    #[test]
+    fn test_display_path_with_several_roots() {
+        let app = native("/work/app");
+        let lib = native("/work/app/lib");
+        let docs = native("/srv/docs");
+        let roots = [app.as_path(), lib.as_path(), docs.as_path()];
+        assert_eq!(
+            display_path(&native("/srv/docs/index.md"), &roots, None),
+            native("docs/index.md")
+        );
+        // The innermost root the path is in is the one it's shown from
+        assert_eq!(
+            display_path(&native("/work/app/lib/src/lib.rs"), &roots, None),
+            native("lib/src/lib.rs")
+        );
+        assert_eq!(
+            display_path(&native("/work/app/main.rs"), &roots, None),
+            native("app/main.rs")
+        );
+    }
+
+    #[test]
+    fn test_display_path_outside_workspace() {
+        let root = native("/home/user/project");
+        let roots = [root.as_path()];
+        let home = native("/home/user");
+        assert_eq!(
+            display_path(
+                &native("/home/user/.config/lapce/settings.toml"),
+                &roots,
+                Some(&home)
+            ),
+            native("~/.config/lapce/settings.toml")
+        );
+        assert_eq!(display_path(&home, &roots, Some(&home)), native("~"));
+        assert_eq!(
+            display_path(&native("/etc/hosts"), &roots, Some(&home)),
+            native("/etc/hosts")
+        );
+        // Without a workspace nor a home directory, the path is kept
+        assert_eq!(
+            display_path(&native("/home/user/notes.md"), &[], None),
+            native("/home/user/notes.md")
+        );
+        assert_eq!(
+            display_path(Path::new("relative.rs"), &[], Some(&home)),
+            PathBuf::from("relative.rs")
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_display_path_with_both_separators() {
+        let root = PathBuf::from(r"C:\Users\jane\project");
+        let roots = [root.as_path()];
+        let home = PathBuf::from("C:/Users/jane");
+        assert_eq!(
+            display_path(
+                Path::new("C:/Users/jane/project/src/main.rs"),
+                &roots,
+                Some(&home)
+            ),
+            PathBuf::from(r"src\main.rs")
+        );
+        assert_eq!(
+            display_path(Path::new(r"C:\Users\jane\notes.md"), &roots, Some(&home)),
+            PathBuf::from(r"~\notes.md")
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_display_path_with_both_separators() {
+        let root = PathBuf::from("/home/jane/project");
+        let roots = [root.as_path()];
+        let home = PathBuf::from("/home/jane");
+        // A backslash is a part of the file name, not a separator
+        assert_eq!(
+            display_path(
+                Path::new(r"/home/jane/project\src\main.rs"),
+                &roots,
+                Some(&home)
+            ),
+            PathBuf::from(r"~/project\src\main.rs")
+        );
+        assert_eq!(
+            display_path(
+                Path::new("/home/jane/project/src/main.rs"),
+                &roots,
+                Some(&home)
+            ),
+            PathBuf::from("src/main.rs")
+        );
+    }
+}
@@ -1,16 +1,22 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    fs,
     path::{Path, PathBuf},
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{anyhow, Result};
 use crossbeam_channel::{unbounded, Sender};
 use druid::{ExtEventSink, Point, Rect, Size, Vec2, WidgetId};
-use lapce_core::directory::Directory;
-use lapce_rpc::plugin::VoltID;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use lapce_core::{directory::Directory, fold::FoldRange};
+use lapce_rpc::plugin::{VoltID, VoltPermissions};
 use lapce_xi_rope::Rope;
 use serde::{Deserialize, Serialize};
 
@@ -21,23 +27,67 @@
         LapceMainSplitData, LapceTabData, LapceWindowData, LapceWorkspace,
         SplitContent, SplitData,
     },
-    document::{BufferContent, Document, LocalBufferKind},
+    document::{BufferContent, Document},
     editor::EditorLocation,
+    find::FindHistory,
+    local_history::{self, LocalHistorySnapshot},
+    palette::PaletteFrecency,
     panel::{PanelData, PanelOrder},
+    picker::PickerKind,
+    problem::ProblemGrouping,
+    proxy::LapceProxy,
     split::SplitDirection,
+    terminal::{TerminalPanelData, TerminalSession, TerminalSplitData},
+    terminal_layout::TerminalLayoutInfo,
 };
 
+/// The version of the way the state is stored, which each migration bumps
+pub const DB_VERSION: u64 = MIGRATIONS.len() as u64 + 1;
+const DB_VERSION_KEY: &str = "db_version";
+const LOCAL_HISTORY_PREFIX: &str = "local_history:";
+/// How long the palette usages wait for more of them before being written
+const PALETTE_USAGE_DELAY: Duration = Duration::from_secs(2);
+
+/// The migrations of the db from each version to the next one, in order.
+/// Version 1 is the db from before the version was stored.
+const MIGRATIONS: [fn(&sled::Db) -> Result<()>; 1] = [migrate_buffer_info_keys];
+
 pub enum SaveEvent {
     Workspace(LapceWorkspace, WorkspaceInfo),
     Tabs(TabsInfo),
     Buffer(BufferInfo),
     RecentWorkspace(LapceWorkspace),
+    LocalHistory(LocalHistorySnapshot),
+    FindHistory(LapceWorkspace, FindHistory),
+    /// A run of a palette command, at the time in seconds since the unix epoch
+    PaletteUsage(String, u64),
 }
 
 #[derive(Clone)]
 pub struct LapceDb {
     save_tx: Sender<SaveEvent>,
     sled_db: Option<sled::Db>,
+    /// Set once state was imported, for the state of the windows not to be
+    /// saved over it until the window is reopened with it
+    imported: Arc<AtomicBool>,
+}
+
+/// How the state of an archive is imported into the db
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keep the current state, the state of the archive replaces it where they
+    /// both have some
+    Merge,
+    /// Drop the current state for the one of the archive
+    Replace,
+}
+
+/// The whole db exported to a file, to be imported on another machine
+#[derive(Serialize, Deserialize)]
+struct DbArchive {
+    /// The [`DB_VERSION`] of the db the archive was exported from
+    version: u64,
+    entries: BTreeMap<String, String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -88,6 +138,9 @@ pub struct EditorTabInfo {
     pub active: usize,
     pub is_focus: bool,
     pub children: Vec<EditorTabChildInfo>,
+    /// The indices of the pinned children
+    #[serde(default)]
+    pub pinned: Vec<usize>,
 }
 
 impl EditorTabInfo {
@@ -100,36 +153,52 @@ pub fn to_data(
         config: &LapceConfig,
         event_sink: ExtEventSink,
     ) -> LapceEditorTabData {
+        let editor_tab_data = self.to_tab_data(split, |child, editor_tab_id| {
+            child.to_data(
+                data,
+                editor_tab_id,
+                editor_positions,
+                tab_id,
+                config,
+                event_sink.clone(),
+            )
+        });
+        if self.is_focus {
+            data.active = Arc::new(Some(
+                editor_tab_data.children[editor_tab_data.active].widget_id(),
+            ));
+            data.active_tab = Arc::new(Some(editor_tab_data.widget_id));
+        }
+        data.editor_tabs
+            .insert(editor_tab_data.widget_id, Arc::new(editor_tab_data.clone()));
+        editor_tab_data
+    }
+
+    /// The editor tab with the children `restore_child` gives for the ones saved,
+    /// given the id of the tab, which pins the ones that were pinned again
+    pub fn to_tab_data<F>(
+        &self,
+        split: WidgetId,
+        mut restore_child: F,
+    ) -> LapceEditorTabData
+    where
+        F: FnMut(&EditorTabChildInfo, WidgetId) -> EditorTabChild,
+    {
         let editor_tab_id = WidgetId::next();
-        let editor_tab_data = LapceEditorTabData {
+        let mut editor_tab_data = LapceEditorTabData {
             widget_id: editor_tab_id,
             split,
             active: self.active,
             children: self
                 .children
                 .iter()
-                .map(|child| {
-                    child.to_data(
-                        data,
-                        editor_tab_id,
-                        editor_positions,
-                        tab_id,
-                        config,
-                        event_sink.clone(),
-                    )
-                })
+                .map(|child| restore_child(child, editor_tab_id))
                 .collect(),
+            pinned: im::HashSet::new(),
             layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
             content_is_hot: Rc::new(RefCell::new(false)),
         };
-        if self.is_focus {
-            data.active = Arc::new(Some(
-                editor_tab_data.children[editor_tab_data.active].widget_id(),
-            ));
-            data.active_tab = Arc::new(Some(editor_tab_data.widget_id));
-        }
-        data.editor_tabs
-            .insert(editor_tab_id, Arc::new(editor_tab_data.clone()));
+        editor_tab_data.pin_indices(&self.pinned);
         editor_tab_data
     }
 }
@@ -167,23 +236,11 @@ pub fn to_data(
                     editor_data.find_view_id,
                 )
             }
-            EditorTabChildInfo::Settings => {
-                let editor = LapceEditorData::new(
-                    None,
-                    None,
-                    None,
-                    BufferContent::Local(LocalBufferKind::Keymap),
-                    config,
-                );
-                let keymap_input_view_id = editor.view_id;
-                data.editors.insert(editor.view_id, Arc::new(editor));
-
-                EditorTabChild::Settings {
-                    settings_widget_id: WidgetId::next(),
-                    editor_tab_id,
-                    keymap_input_view_id,
-                }
-            }
+            EditorTabChildInfo::Settings => EditorTabChild::new_settings(
+                &mut data.editors,
+                editor_tab_id,
+                config,
+            ),
             EditorTabChildInfo::Plugin { volt_id, volt_name } => {
                 EditorTabChild::Plugin {
                     widget_id: WidgetId::next(),
@@ -200,6 +257,9 @@ pub fn to_data(
 pub struct SplitInfo {
     pub children: Vec<SplitContentInfo>,
     pub direction: SplitDirection,
+    /// The shares of the children, empty when they're even
+    #[serde(default)]
+    pub ratios: Vec<f64>,
 }
 
 impl SplitInfo {
@@ -231,6 +291,7 @@ pub fn to_data(
                     )
                 })
                 .collect(),
+            ratios: self.ratios.clone(),
             layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
         };
         data.splits.insert(split_id, Arc::new(split_data.clone()));
@@ -242,6 +303,63 @@ pub fn to_data(
 pub struct WorkspaceInfo {
     pub split: SplitInfo,
     pub panel: PanelData,
+    /// Only saved when `terminal.restore-sessions` is enabled
+    #[serde(default)]
+    pub terminal: Option<TerminalPanelInfo>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerminalTabInfo {
+    /// The terminals in the order they are laid out in the tab
+    pub terminals: Vec<TerminalSession>,
+    pub active: usize,
+    /// How the terminals are split, they are side by side without it
+    #[serde(default)]
+    pub layout: Option<TerminalLayoutInfo>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerminalPanelInfo {
+    pub tabs: Vec<TerminalTabInfo>,
+    pub active: usize,
+}
+
+impl TerminalPanelInfo {
+    /// Open the saved terminals again, returns `None` if there were none
+    pub fn to_data(
+        &self,
+        workspace: &LapceWorkspace,
+        proxy: Arc<LapceProxy>,
+        config: &LapceConfig,
+        event_sink: ExtEventSink,
+    ) -> Option<TerminalPanelData> {
+        let mut tabs = im::HashMap::new();
+        let mut tabs_order = Vec::new();
+        for info in &self.tabs {
+            if let Some(split) = TerminalSplitData::from_tab_info(
+                info,
+                workspace,
+                proxy.clone(),
+                config,
+                event_sink.clone(),
+            ) {
+                tabs_order.push(split.split_id);
+                tabs.insert(split.split_id, split);
+            }
+        }
+        if tabs_order.is_empty() {
+            return None;
+        }
+
+        Some(TerminalPanelData {
+            widget_id: WidgetId::next(),
+            active: self.active.min(tabs_order.len() - 1),
+            tabs,
+            tabs_order: Arc::new(tabs_order),
+        })
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -264,6 +382,9 @@ pub struct BufferInfo {
     pub path: PathBuf,
     pub scroll_offset: (f64, f64),
     pub cursor_offset: usize,
+    /// The ranges of lines that were folded
+    #[serde(default)]
+    pub folds: Vec<FoldRange>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -351,17 +472,37 @@ pub fn new() -> Result<Self> {
             .join("lapce.db");
         let (save_tx, save_rx) = unbounded();
 
-        let sled_db = sled::Config::default()
-            .path(path)
-            .flush_every_ms(None)
-            .open()
+        let sled_db = Self::open(&path)
+            .map_err(|e| log::error!("Failed to open the db at {path:?}: {e}"))
             .ok();
 
-        let db = Self { save_tx, sled_db };
+        let db = Self {
+            save_tx,
+            sled_db,
+            imported: Arc::new(AtomicBool::new(false)),
+        };
         let local_db = db.clone();
         std::thread::spawn(move || -> Result<()> {
+            // The palette usages are written together once no command was run
+            // for a moment, as one is recorded on every run
+            let mut usages = Vec::new();
+            let mut usages_deadline = None;
             loop {
-                let event = save_rx.recv()?;
+                let event = match usages_deadline {
+                    Some(deadline) => match save_rx.recv_deadline(deadline) {
+                        Ok(event) => event,
+                        Err(e) => {
+                            let _ = local_db
+                                .insert_palette_usages(&std::mem::take(&mut usages));
+                            usages_deadline = None;
+                            if e.is_disconnected() {
+                                return Ok(());
+                            }
+                            continue;
+                        }
+                    },
+                    None => save_rx.recv()?,
+                };
                 match event {
                     SaveEvent::Workspace(workspace, info) => {
                         let _ = local_db.insert_workspace(&workspace, &info);
@@ -375,18 +516,148 @@ pub fn new() -> Result<Self> {
                     SaveEvent::RecentWorkspace(workspace) => {
                         let _ = local_db.insert_recent_workspace(workspace);
                     }
+                    SaveEvent::LocalHistory(snapshot) => {
+                        let _ = local_db.insert_local_history(&snapshot);
+                    }
+                    SaveEvent::FindHistory(workspace, history) => {
+                        let _ = local_db.insert_find_history(&workspace, &history);
+                    }
+                    SaveEvent::PaletteUsage(command, time) => {
+                        usages.push((command, time));
+                        usages_deadline = Some(Instant::now() + PALETTE_USAGE_DELAY);
+                    }
                 }
             }
         });
         Ok(db)
     }
 
+    /// Open the db and migrate it to the current version. A db that can't be
+    /// read or migrated is moved aside, and a fresh one is created instead.
+    /// Only a db locked by another instance of Lapce is left where it is.
+    fn open(path: &Path) -> Result<sled::Db> {
+        let sled_db = match open_sled(path) {
+            Ok(sled_db) => sled_db,
+            Err(e) if is_locked(&e) => return Err(e),
+            Err(e) => {
+                log::error!("Failed to read the db at {path:?}: {e}");
+                quarantine(path)?;
+                return Self::open_fresh(path);
+            }
+        };
+        if let Err(e) = Self::migrate(&sled_db, path) {
+            log::error!("Failed to migrate the db at {path:?}: {e}");
+            drop(sled_db);
+            quarantine(path)?;
+            return Self::open_fresh(path);
+        }
+        Ok(sled_db)
+    }
+
+    fn open_fresh(path: &Path) -> Result<sled::Db> {
+        let sled_db = open_sled(path)?;
+        set_db_version(&sled_db, DB_VERSION)?;
+        Ok(sled_db)
+    }
+
+    /// Apply the migrations the db is missing, after backing it up next to it.
+    /// The dbs of newer versions are left as they are.
+    pub fn migrate(sled_db: &sled::Db, path: &Path) -> Result<()> {
+        let version = match sled_db.get(DB_VERSION_KEY)? {
+            Some(version) => std::str::from_utf8(&version)?.parse::<u64>()?,
+            // A new db has nothing to migrate
+            None if sled_db.is_empty() => DB_VERSION,
+            None => 1,
+        };
+        if version == 0 {
+            return Err(anyhow!("invalid db version 0"));
+        }
+        if version >= DB_VERSION {
+            if version > DB_VERSION {
+                log::warn!("The db at {path:?} is of the newer version {version}");
+            }
+            return set_db_version(sled_db, version);
+        }
+
+        let backup = sibling_path(path, &format!("v{version}.bak"));
+        if backup.exists() {
+            fs::remove_dir_all(&backup)?;
+        }
+        let backup_db = open_sled(&backup)?;
+        backup_db.import(sled_db.export());
+        backup_db.flush()?;
+
+        apply_migrations(sled_db, version)
+    }
+
+    /// Write the db to a compressed archive file, without the local history
+    /// which is only of use on this machine
+    pub fn export_to(&self, path: &Path) -> Result<()> {
+        let archive = DbArchive {
+            version: DB_VERSION,
+            entries: db_entries(self.get_db()?)?,
+        };
+
+        let mut encoder =
+            GzEncoder::new(fs::File::create(path)?, Compression::default());
+        serde_json::to_writer(&mut encoder, &archive)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Import an archive made by [`LapceDb::export_to`], migrating it first if
+    /// it is from an older version. The state of the windows isn't saved after
+    /// that, until [`LapceDb::resume_saving`] once the window is reopened with
+    /// the imported one. The local history is kept when replacing the state.
+    pub fn import_from(&self, path: &Path, mode: ImportMode) -> Result<()> {
+        let archive: DbArchive =
+            serde_json::from_reader(GzDecoder::new(fs::File::open(path)?))?;
+        let entries = migrate_archive(archive)?;
+
+        let sled_db = self.get_db()?;
+        // Applied as a whole, so that a failed import leaves the db as it was
+        let mut batch = sled::Batch::default();
+        if mode == ImportMode::Replace {
+            for key in sled_db.iter().keys() {
+                let key = key?;
+                if !key.starts_with(LOCAL_HISTORY_PREFIX.as_bytes()) {
+                    batch.remove(key);
+                }
+            }
+        }
+        for (key, value) in entries {
+            batch.insert(key.as_str(), value.as_str());
+        }
+        batch.insert(DB_VERSION_KEY, DB_VERSION.to_string().as_str());
+        sled_db.apply_batch(batch)?;
+        sled_db.flush()?;
+
+        self.imported.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
     fn get_db(&self) -> Result<&sled::Db> {
         self.sled_db
             .as_ref()
             .ok_or_else(|| anyhow!("didn't open sled db"))
     }
 
+    /// The db to save the state of the windows in, unless state was imported
+    fn get_state_db(&self) -> Result<&sled::Db> {
+        if self.imported.load(Ordering::Relaxed) {
+            return Err(anyhow!(
+                "state was imported, it is saved once the window is reopened"
+            ));
+        }
+        self.get_db()
+    }
+
+    /// Save the state of the windows again, once the window was reopened with
+    /// the imported state
+    pub fn resume_saving(&self) {
+        self.imported.store(false, Ordering::Relaxed);
+    }
+
     pub fn save_app(&self, data: &LapceData) -> Result<()> {
         for (_, window) in data.windows.iter() {
             for (_, tab) in window.tabs.iter() {
@@ -490,7 +761,7 @@ pub fn get_buffer_info(
         workspace: &LapceWorkspace,
         path: &Path,
     ) -> Result<BufferInfo> {
-        let key = format!("{}:{}", workspace, path.to_str().unwrap_or(""));
+        let key = buffer_info_key(workspace, path);
         let sled_db = self.get_db()?;
         let info = sled_db
             .get(key.as_str())?
@@ -501,17 +772,88 @@ pub fn get_buffer_info(
     }
 
     fn insert_buffer(&self, info: &BufferInfo) -> Result<()> {
-        let key = format!("{}:{}", info.workspace, info.path.to_str().unwrap_or(""));
+        let key = buffer_info_key(&info.workspace, &info.path);
         let info = serde_json::to_string(info)?;
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
         sled_db.insert(key.as_str(), info.as_str())?;
         sled_db.flush()?;
         Ok(())
     }
 
+    pub fn save_local_history(&self, snapshot: LocalHistorySnapshot) {
+        let _ = self.save_tx.send(SaveEvent::LocalHistory(snapshot));
+    }
+
+    /// The times of the local history snapshots of the file, from the newest to
+    /// the oldest
+    pub fn get_local_history(&self, path: &Path) -> Result<Vec<u64>> {
+        let sled_db = self.get_db()?;
+        let prefix = local_history_prefix(path);
+        let mut times = Vec::new();
+        for key in sled_db.scan_prefix(&prefix).keys() {
+            let key = key?;
+            let key = std::str::from_utf8(&key)?;
+            // The keys of files whose path starts with this one are skipped
+            if let Some(time) = key
+                .strip_prefix(&prefix)
+                .and_then(|time| time.parse::<u64>().ok())
+            {
+                times.push(time);
+            }
+        }
+        times.reverse();
+        Ok(times)
+    }
+
+    pub fn get_local_history_content(
+        &self,
+        path: &Path,
+        time: u64,
+    ) -> Result<String> {
+        let sled_db = self.get_db()?;
+        let content = sled_db
+            .get(local_history_key(path, time))?
+            .ok_or_else(|| anyhow!("can't find local history snapshot"))?;
+        let content = std::str::from_utf8(&content)?;
+        Ok(content.to_string())
+    }
+
+    fn insert_local_history(&self, snapshot: &LocalHistorySnapshot) -> Result<()> {
+        let sled_db = self.get_db()?;
+        let mut times = self.get_local_history(&snapshot.path)?;
+        if let Some(latest) = times.first() {
+            // Nothing changed since the last snapshot
+            if self.get_local_history_content(&snapshot.path, *latest)?
+                == snapshot.content
+            {
+                return Ok(());
+            }
+        }
+
+        sled_db.insert(
+            local_history_key(&snapshot.path, snapshot.time),
+            snapshot.content.as_str(),
+        )?;
+        // A snapshot taken in the same second replaced the previous one
+        if times.first() != Some(&snapshot.time) {
+            times.insert(0, snapshot.time);
+        }
+        times.reverse();
+        for time in local_history::pruned_snapshots(
+            &times,
+            snapshot.time,
+            snapshot.max_entries,
+            snapshot.max_age,
+        ) {
+            sled_db.remove(local_history_key(&snapshot.path, time))?;
+        }
+        sled_db.flush()?;
+        Ok(())
+    }
+
     fn insert_tabs(&self, info: &TabsInfo) -> Result<()> {
         let tabs_info = serde_json::to_string(info)?;
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
         sled_db.insert(b"tabs", tabs_info.as_str())?;
         sled_db.flush()?;
         Ok(())
@@ -535,6 +877,24 @@ pub fn get_disabled_volts(&self) -> Result<Vec<VoltID>> {
         Ok(volts)
     }
 
+    pub fn save_pinned_volts(&self, volts: Vec<(&VoltID, &String)>) -> Result<()> {
+        let sled_db = self.get_db()?;
+        let volts = serde_json::to_string(&volts)?;
+        sled_db.insert(b"pinned_volts", volts.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_pinned_volts(&self) -> Result<Vec<(VoltID, String)>> {
+        let sled_db = self.get_db()?;
+        let volts = sled_db
+            .get("pinned_volts")?
+            .ok_or_else(|| anyhow!("can't find pinned volts"))?;
+        let volts = std::str::from_utf8(&volts)?;
+        let volts: Vec<(VoltID, String)> = serde_json::from_str(volts)?;
+        Ok(volts)
+    }
+
     pub fn save_workspace_disabled_volts(
         &self,
         workspace: &LapceWorkspace,
@@ -560,6 +920,62 @@ pub fn get_workspace_disabled_volts(
         Ok(volts)
     }
 
+    /// Save the settings profile of the workspace, or forget it when the
+    /// workspace goes back to the base settings
+    pub fn save_workspace_profile(
+        &self,
+        workspace: &LapceWorkspace,
+        profile: Option<&str>,
+    ) -> Result<()> {
+        let sled_db = self.get_db()?;
+        let key = format!("settings_profile:{workspace}");
+        match profile {
+            Some(profile) => {
+                sled_db.insert(key, profile)?;
+            }
+            None => {
+                sled_db.remove(key)?;
+            }
+        }
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_workspace_profile(
+        &self,
+        workspace: &LapceWorkspace,
+    ) -> Result<String> {
+        let sled_db = self.get_db()?;
+        let profile = sled_db
+            .get(format!("settings_profile:{workspace}"))?
+            .ok_or_else(|| anyhow!("can't find settings profile"))?;
+        let profile = std::str::from_utf8(&profile)?;
+        Ok(profile.to_string())
+    }
+
+    pub fn save_volt_permissions(
+        &self,
+        volt_id: &VoltID,
+        permissions: &VoltPermissions,
+    ) -> Result<()> {
+        let sled_db = self.get_db()?;
+        let permissions = serde_json::to_string(permissions)?;
+        sled_db
+            .insert(format!("volt_permissions:{volt_id}"), permissions.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_volt_permissions(&self, volt_id: &VoltID) -> Result<VoltPermissions> {
+        let sled_db = self.get_db()?;
+        let permissions = sled_db
+            .get(format!("volt_permissions:{volt_id}"))?
+            .ok_or_else(|| anyhow!("can't find volt permissions"))?;
+        let permissions = std::str::from_utf8(&permissions)?;
+        let permissions: VoltPermissions = serde_json::from_str(permissions)?;
+        Ok(permissions)
+    }
+
     pub fn save_last_window(&self, window: &LapceWindowData) {
         let info = window.info();
         let _ = self.insert_last_window_info(info);
@@ -567,7 +983,7 @@ pub fn save_last_window(&self, window: &LapceWindowData) {
 
     fn insert_last_window_info(&self, info: WindowInfo) -> Result<()> {
         let info = serde_json::to_string(&info)?;
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
         sled_db.insert("last_window", info.as_str())?;
         sled_db.flush()?;
         Ok(())
@@ -601,6 +1017,110 @@ pub fn save_panel_orders(&self, order: &PanelOrder) -> Result<()> {
         Ok(())
     }
 
+    pub fn get_problem_grouping(&self) -> Result<ProblemGrouping> {
+        let sled_db = self.get_db()?;
+        let grouping = sled_db
+            .get("problem_grouping")?
+            .ok_or_else(|| anyhow!("can't find problem grouping"))?;
+        let grouping = std::str::from_utf8(&grouping)?;
+        let grouping: ProblemGrouping = serde_json::from_str(grouping)?;
+        Ok(grouping)
+    }
+
+    pub fn save_problem_grouping(&self, grouping: ProblemGrouping) -> Result<()> {
+        let info = serde_json::to_string(&grouping)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("problem_grouping", info.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_picker_dirs(&self) -> Result<HashMap<PickerKind, PathBuf>> {
+        let sled_db = self.get_db()?;
+        let dirs = sled_db
+            .get("picker_dirs")?
+            .ok_or_else(|| anyhow!("can't find picker dirs"))?;
+        let dirs = std::str::from_utf8(&dirs)?;
+        let dirs: HashMap<PickerKind, PathBuf> = serde_json::from_str(dirs)?;
+        Ok(dirs)
+    }
+
+    pub fn save_picker_dir(&self, kind: PickerKind, dir: &Path) -> Result<()> {
+        let mut dirs = self.get_picker_dirs().unwrap_or_default();
+        dirs.insert(kind, dir.to_path_buf());
+        let info = serde_json::to_string(&dirs)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("picker_dirs", info.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_palette_frecency(&self) -> Result<PaletteFrecency> {
+        let sled_db = self.get_db()?;
+        let frecency = sled_db
+            .get("palette_frecency")?
+            .ok_or_else(|| anyhow!("can't find palette frecency"))?;
+        let frecency = std::str::from_utf8(&frecency)?;
+        let frecency: PaletteFrecency = serde_json::from_str(frecency)?;
+        Ok(frecency)
+    }
+
+    pub fn save_palette_usage(&self, command: &str, time: u64) {
+        let _ = self
+            .save_tx
+            .send(SaveEvent::PaletteUsage(command.to_string(), time));
+    }
+
+    /// The usages are added to the stored ones, which the other windows may
+    /// have added to since this one loaded them
+    fn insert_palette_usages(&self, usages: &[(String, u64)]) -> Result<()> {
+        let mut frecency = self.get_palette_frecency().unwrap_or_default();
+        for (command, time) in usages {
+            frecency.record(command, *time);
+        }
+        let info = serde_json::to_string(&frecency)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("palette_frecency", info.as_str())?;
+        sled_db.flush()?;
+        Ok(())
+    }
+
+    pub fn get_find_history(
+        &self,
+        workspace: &LapceWorkspace,
+    ) -> Result<FindHistory> {
+        let sled_db = self.get_db()?;
+        let history = sled_db
+            .get(format!("find_history:{workspace}"))?
+            .ok_or_else(|| anyhow!("can't find find history"))?;
+        let history = std::str::from_utf8(&history)?;
+        let history: FindHistory = serde_json::from_str(history)?;
+        Ok(history)
+    }
+
+    pub fn save_find_history(
+        &self,
+        workspace: &LapceWorkspace,
+        history: &FindHistory,
+    ) {
+        let _ = self
+            .save_tx
+            .send(SaveEvent::FindHistory(workspace.clone(), history.clone()));
+    }
+
+    /// The history isn't flushed, as it is saved on every search, but with the
+    /// workspace when it is closed
+    fn insert_find_history(
+        &self,
+        workspace: &LapceWorkspace,
+        history: &FindHistory,
+    ) -> Result<()> {
+        let history = serde_json::to_string(history)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert(format!("find_history:{workspace}"), history.as_str())?;
+        Ok(())
+    }
+
     fn insert_workspace(
         &self,
         workspace: &LapceWorkspace,
@@ -608,7 +1128,7 @@ fn insert_workspace(
     ) -> Result<()> {
         let workspace = workspace.to_string();
         let workspace_info = serde_json::to_string(info)?;
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
         sled_db.insert(workspace.as_str(), workspace_info.as_str())?;
         sled_db.flush()?;
         Ok(())
@@ -621,14 +1141,20 @@ pub fn save_workspace(&self, data: &LapceTabData) -> Result<()> {
         // Buffer for auto save on quit
         let main_split = &data.main_split;
 
+        self.insert_find_history(&workspace, &main_split.find_history)?;
         self.insert_workspace(&workspace, &workspace_info)?;
         self.insert_unsaved_buffer(main_split)?;
+        for doc in main_split.open_docs.values() {
+            if let Some(info) = Self::buffer_info(&workspace, doc) {
+                self.insert_buffer(&info)?;
+            }
+        }
 
         Ok(())
     }
 
     fn insert_unsaved_buffer(&self, main_split: &LapceMainSplitData) -> Result<()> {
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
         // Vec of all unsaved buffers of format path_buff, file_content
         let mut unsaved_buffers = Vec::new();
 
@@ -659,14 +1185,25 @@ pub fn save_workspace_async(&self, data: &LapceTabData) -> Result<()> {
     }
 
     pub fn save_doc_position(&self, workspace: &LapceWorkspace, doc: &Document) {
-        if let BufferContent::File(path) = doc.content() {
-            let info = BufferInfo {
+        if let Some(info) = Self::buffer_info(workspace, doc) {
+            let _ = self.save_tx.send(SaveEvent::Buffer(info));
+        }
+    }
+
+    /// The position in the document and its folds, to restore when it's opened again
+    fn buffer_info(
+        workspace: &LapceWorkspace,
+        doc: &Document,
+    ) -> Option<BufferInfo> {
+        match doc.content() {
+            BufferContent::File(path) => Some(BufferInfo {
                 workspace: workspace.clone(),
                 path: path.clone(),
                 scroll_offset: (doc.scroll_offset.x, doc.scroll_offset.y),
                 cursor_offset: doc.cursor_offset,
-            };
-            let _ = self.save_tx.send(SaveEvent::Buffer(info));
+                folds: doc.folds().ranges().to_vec(),
+            }),
+            _ => None,
         }
     }
 
@@ -721,7 +1258,7 @@ pub fn update_recent_workspace(&self, workspace: LapceWorkspace) -> Result<()> {
     }
 
     fn insert_recent_workspace(&self, workspace: LapceWorkspace) -> Result<()> {
-        let sled_db = self.get_db()?;
+        let sled_db = self.get_state_db()?;
 
         let mut workspaces = self.recent_workspaces().unwrap_or_default();
 
@@ -753,3 +1290,436 @@ fn insert_recent_workspace(&self, workspace: LapceWorkspace) -> Result<()> {
         Ok(())
     }
 }
+
+fn open_sled(path: &Path) -> Result<sled::Db> {
+    Ok(sled::Config::default()
+        .path(path)
+        .flush_every_ms(None)
+        .open()?)
+}
+
+/// Apply the migrations from the version to the current one
+fn apply_migrations(sled_db: &sled::Db, version: u64) -> Result<()> {
+    for from in version..DB_VERSION {
+        MIGRATIONS[from as usize - 1](sled_db)?;
+        set_db_version(sled_db, from + 1)?;
+    }
+    Ok(())
+}
+
+/// The entries of the archive at the current version, migrated in a temporary
+/// db when the archive is older
+fn migrate_archive(archive: DbArchive) -> Result<BTreeMap<String, String>> {
+    if archive.version == 0 || archive.version > DB_VERSION {
+        return Err(anyhow!(
+            "the archive is of version {}, this version of Lapce supports up to {}",
+            archive.version,
+            DB_VERSION
+        ));
+    }
+    if archive.version == DB_VERSION {
+        return Ok(archive.entries);
+    }
+
+    let sled_db = sled::Config::default().temporary(true).open()?;
+    for (key, value) in &archive.entries {
+        sled_db.insert(key.as_str(), value.as_str())?;
+    }
+    apply_migrations(&sled_db, archive.version)?;
+    db_entries(&sled_db)
+}
+
+/// All the entries of the db but its version and the local history, the ones
+/// which aren't UTF-8 are left out
+fn db_entries(sled_db: &sled::Db) -> Result<BTreeMap<String, String>> {
+    let mut entries = BTreeMap::new();
+    for entry in sled_db.iter() {
+        let (key, value) = entry?;
+        if key == DB_VERSION_KEY.as_bytes()
+            || key.starts_with(LOCAL_HISTORY_PREFIX.as_bytes())
+        {
+            continue;
+        }
+        match (
+            String::from_utf8(key.to_vec()),
+            String::from_utf8(value.to_vec()),
+        ) {
+            (Ok(key), Ok(value)) => {
+                entries.insert(key, value);
+            }
+            _ => {
+                log::warn!("Skipped the db entry {key:?} which isn't UTF-8");
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn set_db_version(sled_db: &sled::Db, version: u64) -> Result<()> {
+    sled_db.insert(DB_VERSION_KEY, version.to_string().as_str())?;
+    sled_db.flush()?;
+    Ok(())
+}
+
+/// The path next to the db with a suffix added to its name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("lapce.db");
+    path.with_file_name(format!("{name}.{suffix}"))
+}
+
+/// Whether the db couldn't be opened because another instance of Lapce holds
+/// its lock, which sled reports as an io error
+fn is_locked(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<sled::Error>() {
+        Some(sled::Error::Io(e)) => {
+            e.kind() == std::io::ErrorKind::WouldBlock
+                || e.to_string().contains("could not acquire lock")
+        }
+        _ => false,
+    }
+}
+
+/// Move an unreadable db aside, so that it can be looked into
+fn quarantine(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let corrupt = sibling_path(path, &format!("corrupt-{time}"));
+    fs::rename(path, &corrupt)?;
+    log::warn!("Moved the unreadable db at {path:?} to {corrupt:?}");
+    Ok(())
+}
+
+/// Version 1 to 2: the infos of the files were keyed by `{workspace}:{path}`,
+/// mixed up with the keys of the workspaces, they are under `buffer:` now
+fn migrate_buffer_info_keys(sled_db: &sled::Db) -> Result<()> {
+    let mut moved = Vec::new();
+    for entry in sled_db.iter() {
+        let (key, value) = entry?;
+        let Ok(info) = serde_json::from_slice::<BufferInfo>(&value) else {
+            continue;
+        };
+        let old_key =
+            format!("{}:{}", info.workspace, info.path.to_str().unwrap_or(""));
+        if key.as_ref() == old_key.as_bytes() {
+            moved.push((key, buffer_info_key(&info.workspace, &info.path), value));
+        }
+    }
+    for (old_key, key, value) in moved {
+        sled_db.insert(key.as_str(), value)?;
+        sled_db.remove(old_key)?;
+    }
+    sled_db.flush()?;
+    Ok(())
+}
+
+fn buffer_info_key(workspace: &LapceWorkspace, path: &Path) -> String {
+    format!("buffer:{}:{}", workspace, path.to_str().unwrap_or(""))
+}
+
+fn local_history_prefix(path: &Path) -> String {
+    format!("{LOCAL_HISTORY_PREFIX}{}:", path.to_str().unwrap_or(""))
+}
+
+/// The time is padded for the snapshots of a file to be sorted by it
+fn local_history_key(path: &Path, time: u64) -> String {
+    format!("{}{time:020}", local_history_prefix(path))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crossbeam_channel::unbounded;
+    use flate2::{write::GzEncoder, Compression};
+    use lapce_core::fold::FoldRange;
+
+    use super::{
+        buffer_info_key, db_entries, is_locked, open_sled, BufferInfo, DbArchive,
+        ImportMode, LapceDb, DB_VERSION, DB_VERSION_KEY,
+    };
+    use crate::data::LapceWorkspace;
+
+    fn db_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-db-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("lapce.db")
+    }
+
+    fn lapce_db(path: &Path, entries: &[(&str, &str)]) -> LapceDb {
+        let sled_db = LapceDb::open(path).unwrap();
+        for (key, value) in entries {
+            sled_db.insert(key, *value).unwrap();
+        }
+        LapceDb {
+            save_tx: unbounded().0,
+            sled_db: Some(sled_db),
+            imported: Default::default(),
+        }
+    }
+
+    fn write_archive(path: &Path, version: u64, entries: &[(&str, &str)]) {
+        let archive = DbArchive {
+            version,
+            entries: entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let mut encoder =
+            GzEncoder::new(fs::File::create(path).unwrap(), Compression::default());
+        serde_json::to_writer(&mut encoder, &archive).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    fn db_version(sled_db: &sled::Db) -> u64 {
+        let version = sled_db.get(DB_VERSION_KEY).unwrap().unwrap();
+        std::str::from_utf8(&version).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        let path = db_path("v1");
+        let workspace = LapceWorkspace {
+            path: Some(PathBuf::from("/project")),
+            ..Default::default()
+        };
+        let info = BufferInfo {
+            workspace: workspace.clone(),
+            path: PathBuf::from("/project/main.rs"),
+            scroll_offset: (0.0, 20.0),
+            cursor_offset: 12,
+            folds: Vec::new(),
+        };
+        let info = serde_json::to_string(&info).unwrap();
+        let old_key = format!("{workspace}:/project/main.rs");
+        {
+            // A db from before the version was stored
+            let sled_db = open_sled(&path).unwrap();
+            sled_db.insert(old_key.as_str(), info.as_str()).unwrap();
+            sled_db.insert("recent_workspaces", "[]").unwrap();
+            sled_db.flush().unwrap();
+        }
+
+        let sled_db = LapceDb::open(&path).unwrap();
+        assert_eq!(db_version(&sled_db), DB_VERSION);
+        let key = buffer_info_key(&workspace, &PathBuf::from("/project/main.rs"));
+        assert_eq!(sled_db.get(key).unwrap().unwrap(), info.as_bytes());
+        assert!(sled_db.get(old_key.as_str()).unwrap().is_none());
+        assert_eq!(sled_db.get("recent_workspaces").unwrap().unwrap(), b"[]");
+
+        // The db was backed up as it was before the migration
+        let backup = open_sled(&path.with_file_name("lapce.db.v1.bak")).unwrap();
+        assert_eq!(
+            backup.get(old_key.as_str()).unwrap().unwrap(),
+            info.as_bytes()
+        );
+        assert!(backup.get(DB_VERSION_KEY).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_buffer_info_folds() {
+        let db = lapce_db(&db_path("folds"), &[]);
+        let workspace = LapceWorkspace {
+            path: Some(PathBuf::from("/project")),
+            ..Default::default()
+        };
+        let path = PathBuf::from("/project/main.rs");
+        let info = BufferInfo {
+            workspace: workspace.clone(),
+            path: path.clone(),
+            scroll_offset: (0.0, 0.0),
+            cursor_offset: 0,
+            folds: vec![FoldRange::new(2, 5), FoldRange::new(3, 4)],
+        };
+        db.insert_buffer(&info).unwrap();
+        let saved = db.get_buffer_info(&workspace, &path).unwrap();
+        assert_eq!(saved.folds, info.folds);
+
+        // The buffers saved before the folds were have none
+        let mut value = serde_json::to_value(&info).unwrap();
+        value.as_object_mut().unwrap().remove("folds");
+        let info: BufferInfo = serde_json::from_value(value).unwrap();
+        assert!(info.folds.is_empty());
+    }
+
+    #[test]
+    fn test_palette_usages_are_added_to_stored_ones() {
+        let db = lapce_db(&db_path("palette"), &[]);
+        // Like two windows which loaded the usages before either ran a command
+        db.insert_palette_usages(&[("palette.folder".to_string(), 0)])
+            .unwrap();
+        db.insert_palette_usages(&[
+            ("palette.folder".to_string(), 0),
+            ("palette.file".to_string(), 0),
+        ])
+        .unwrap();
+        let frecency = db.get_palette_frecency().unwrap();
+        assert_eq!(frecency.score("palette.folder", 0), 2.0);
+        assert_eq!(frecency.score("palette.file", 0), 1.0);
+    }
+
+    #[test]
+    fn test_new_db_is_current() {
+        let path = db_path("new");
+        let sled_db = LapceDb::open(&path).unwrap();
+        assert_eq!(db_version(&sled_db), DB_VERSION);
+        assert!(!path.with_file_name("lapce.db.v1.bak").exists());
+    }
+
+    /// Where the db was moved aside to, if it was
+    fn quarantined(path: &Path) -> Option<PathBuf> {
+        fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|p| {
+                p.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("lapce.db.corrupt-")
+            })
+    }
+
+    #[test]
+    fn test_recover_corrupt_db() {
+        let path = db_path("corrupt");
+        fs::write(&path, b"not a db").unwrap();
+
+        let sled_db = LapceDb::open(&path).unwrap();
+        assert_eq!(db_version(&sled_db), DB_VERSION);
+        assert_eq!(fs::read(quarantined(&path).unwrap()).unwrap(), b"not a db");
+    }
+
+    #[test]
+    fn test_locked_db_is_kept() {
+        let path = db_path("locked");
+        // Like another instance of Lapce using the db
+        let sled_db = LapceDb::open(&path).unwrap();
+        sled_db.insert("recent_workspaces", "[1]").unwrap();
+        sled_db.flush().unwrap();
+
+        assert!(is_locked(&LapceDb::open(&path).unwrap_err()));
+        assert!(quarantined(&path).is_none());
+        assert_eq!(sled_db.get("recent_workspaces").unwrap().unwrap(), b"[1]");
+    }
+
+    #[test]
+    fn test_recover_invalid_version() {
+        let path = db_path("invalid-version");
+        {
+            let sled_db = open_sled(&path).unwrap();
+            sled_db.insert(DB_VERSION_KEY, "two").unwrap();
+            sled_db.insert("recent_workspaces", "[1]").unwrap();
+            sled_db.flush().unwrap();
+        }
+
+        let sled_db = LapceDb::open(&path).unwrap();
+        assert_eq!(db_version(&sled_db), DB_VERSION);
+        assert!(sled_db.get("recent_workspaces").unwrap().is_none());
+        let old_db = open_sled(&quarantined(&path).unwrap()).unwrap();
+        assert_eq!(old_db.get(DB_VERSION_KEY).unwrap().unwrap(), b"two");
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let entries = [
+            ("recent_workspaces", "[1]"),
+            ("buffer:Local:/project:/project/main.rs", "{}"),
+            (
+                "local_history:/project/main.rs:00000000000000000001",
+                "fn main",
+            ),
+        ];
+        let exported = lapce_db(&db_path("export"), &entries);
+        // Left out rather than failing the export
+        exported.get_db().unwrap().insert(b"\xff", "{}").unwrap();
+        let archive = db_path("archive").with_file_name("lapce-state.json.gz");
+        exported.export_to(&archive).unwrap();
+        let exported = db_entries(exported.get_db().unwrap()).unwrap();
+        // The local history stays on this machine
+        assert_eq!(
+            exported.keys().collect::<Vec<_>>(),
+            vec![
+                "buffer:Local:/project:/project/main.rs",
+                "recent_workspaces"
+            ]
+        );
+
+        // Merging keeps what isn't in the archive
+        let merged = lapce_db(
+            &db_path("merge"),
+            &[("recent_workspaces", "[2]"), ("panel_orders", "{}")],
+        );
+        merged.import_from(&archive, ImportMode::Merge).unwrap();
+        let sled_db = merged.get_db().unwrap();
+        assert_eq!(sled_db.get("recent_workspaces").unwrap().unwrap(), b"[1]");
+        assert_eq!(sled_db.get("panel_orders").unwrap().unwrap(), b"{}");
+        assert_eq!(db_version(sled_db), DB_VERSION);
+        // The imported state isn't saved over until the window is reopened
+        assert!(merged.get_state_db().is_err());
+        merged.resume_saving();
+        assert!(merged.get_state_db().is_ok());
+
+        let history = "local_history:/other.rs:00000000000000000001";
+        let replaced = lapce_db(
+            &db_path("replace"),
+            &[("panel_orders", "{}"), (history, "fn other")],
+        );
+        replaced.import_from(&archive, ImportMode::Replace).unwrap();
+        let sled_db = replaced.get_db().unwrap();
+        assert_eq!(db_entries(sled_db).unwrap(), exported);
+        assert_eq!(sled_db.get(history).unwrap().unwrap(), b"fn other");
+        assert_eq!(db_version(sled_db), DB_VERSION);
+    }
+
+    #[test]
+    fn test_import_migrates_archive() {
+        let archive = db_path("old-archive").with_file_name("lapce-state.json.gz");
+        let workspace = LapceWorkspace {
+            path: Some(PathBuf::from("/project")),
+            ..Default::default()
+        };
+        let info = serde_json::to_string(&BufferInfo {
+            workspace: workspace.clone(),
+            path: PathBuf::from("/project/main.rs"),
+            scroll_offset: (0.0, 0.0),
+            cursor_offset: 3,
+            folds: Vec::new(),
+        })
+        .unwrap();
+        let old_key = format!("{workspace}:/project/main.rs");
+        write_archive(&archive, 1, &[(old_key.as_str(), info.as_str())]);
+
+        let db = lapce_db(&db_path("import-old"), &[]);
+        db.import_from(&archive, ImportMode::Merge).unwrap();
+        let sled_db = db.get_db().unwrap();
+        let key = buffer_info_key(&workspace, &PathBuf::from("/project/main.rs"));
+        assert_eq!(sled_db.get(key).unwrap().unwrap(), info.as_bytes());
+        assert!(sled_db.get(old_key.as_str()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_import_rejects_newer_archive() {
+        let archive = db_path("new-archive").with_file_name("lapce-state.json.gz");
+        write_archive(&archive, DB_VERSION + 1, &[("recent_workspaces", "[1]")]);
+
+        let db = lapce_db(&db_path("import-new"), &[("recent_workspaces", "[2]")]);
+        assert!(db.import_from(&archive, ImportMode::Replace).is_err());
+        let sled_db = db.get_db().unwrap();
+        assert_eq!(sled_db.get("recent_workspaces").unwrap().unwrap(), b"[2]");
+        assert!(db.get_state_db().is_ok());
+    }
+}
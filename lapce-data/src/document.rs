@@ -1,40 +1,49 @@
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
+    time::Instant,
 };
 
+use crossbeam_channel::Receiver;
 use druid::{
     piet::{
-        PietText, PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder,
+        HitTestPoint, PietText, PietTextLayout, Text, TextAttribute, TextLayout,
+        TextLayoutBuilder,
     },
     Color, ExtEventSink, FontFamily, Point, Size, Target, Vec2, WidgetId,
 };
 use itertools::Itertools;
 use lapce_core::{
-    buffer::{Buffer, DiffLines, InvalLines},
+    buffer::{diff_hunks, rope_diff, Buffer, DiffLines, InvalLines},
     char_buffer::CharBuffer,
-    command::{EditCommand, MultiSelectionCommand},
+    command::{EditCommand, FocusCommand, MultiSelectionCommand},
     cursor::{ColPosition, Cursor, CursorMode},
     editor::{EditType, Editor},
+    fold::{indent_fold_ranges, FoldRange, Folds},
+    indent::IndentStyle,
     language::LapceLanguage,
+    line_ending::LineEnding,
     mode::{Mode, MotionMode},
     movement::{LinePosition, Movement},
     register::{Clipboard, Register, RegisterData},
     selection::{SelRegion, Selection},
     style::line_styles,
+    symbol::SymbolScopes,
     syntax::{
         edit::SyntaxEdit, highlight::HighlightIssue, util::matching_pair_direction,
         Syntax,
     },
     word::WordCursor,
+    wrap::{VisualLine, VisualLines, VisualMove},
 };
 use lapce_rpc::{
     buffer::BufferId,
     plugin::PluginId,
     proxy::ProxyResponse,
+    source_control::BlameHunk,
     style::{LineStyle, LineStyles, Style},
 };
 use lapce_xi_rope::{
@@ -42,19 +51,25 @@
     Interval, Rope, RopeDelta, Transformer,
 };
 use lsp_types::{
-    CodeActionOrCommand, CodeActionResponse, DiagnosticSeverity, InlayHint,
-    InlayHintLabel, MessageType, ShowMessageParams,
+    CodeActionOrCommand, CodeActionResponse, DiagnosticSeverity, FoldingRange,
+    InlayHint, InlayHintLabel, MessageType, Position, Range, ShowMessageParams,
 };
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
 use crate::{
+    alert::{AlertButton, AlertContentData},
     atomic_soft_tabs::{snap_to_soft_tab, snap_to_soft_tab_line_col, SnapDirection},
-    command::{InitBufferContentCb, LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandKind, InitBufferContentCb, LapceCommand, LapceUICommand,
+        LapceWorkbenchCommand, LAPCE_UI_COMMAND,
+    },
     config::{LapceConfig, LapceTheme},
     data::{EditorDiagnostic, EditorView},
+    document_color::{document_color_spans, swatch_color, COLOR_SWATCH},
     editor::{EditorLocation, EditorPosition},
-    find::{Find, FindProgress},
+    find::{Find, FindCount, FindProgress},
+    format::FormattingCancel,
     history,
     history::DocumentHistory,
     proxy::LapceProxy,
@@ -275,8 +290,97 @@ pub enum PhantomTextKind {
     Completion,
     /// Inlay hints supplied by an LSP/PSP (like type annotations)
     InlayHint,
+    /// Swatches of the colors found by an LSP/PSP
+    ColorSwatch,
     /// Error lens
     Diagnostic,
+    /// Inline git blame of the cursor's line
+    Blame,
+}
+
+/// The git blame of a document, for the inline blame of the cursor's line
+#[derive(Clone)]
+pub struct DocumentBlame {
+    /// The revision of the buffer that was blamed
+    pub rev: u64,
+    /// `None` while the blame is being fetched
+    pub hunks: Option<Arc<Vec<BlameHunk>>>,
+}
+
+/// How the file of a document changed outside of Lapce, until the user chooses
+/// between the two versions
+#[derive(Clone)]
+pub enum DiskChange {
+    /// The file has other content than the document was loaded from
+    Modified(Rope),
+    Deleted,
+}
+
+/// The alert asking whether to reload an open file which was modified outside
+/// of Lapce or keep the version of the editor, or whether to save it again if
+/// it was deleted, with the editor given
+pub fn disk_change_alert(
+    tab_id: WidgetId,
+    path: &Path,
+    deleted: bool,
+    editor: Option<WidgetId>,
+) -> AlertContentData {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let command = |command| LapceCommand {
+        kind: CommandKind::Workbench(command),
+        data: Some(serde_json::json!(path)),
+    };
+    if deleted {
+        AlertContentData {
+            title: format!("{name} was deleted on disk"),
+            msg: "Save it to put it back, or keep it open without the file."
+                .to_string(),
+            buttons: editor
+                .map(|view_id| AlertButton {
+                    text: "Save".to_string(),
+                    target: view_id,
+                    command: LapceCommand {
+                        kind: CommandKind::Focus(FocusCommand::Save),
+                        data: None,
+                    },
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    } else {
+        AlertContentData {
+            title: format!("{name} changed on disk"),
+            msg: "Reload it with the changes, or keep the version in the editor."
+                .to_string(),
+            buttons: vec![
+                AlertButton {
+                    text: "Reload".to_string(),
+                    target: tab_id,
+                    command: command(LapceWorkbenchCommand::ReloadFromDisk),
+                },
+                AlertButton {
+                    text: "Keep".to_string(),
+                    target: tab_id,
+                    command: command(LapceWorkbenchCommand::KeepLocalVersion),
+                },
+            ],
+            ..Default::default()
+        }
+    }
+}
+
+fn inline_blame_text(hunk: &BlameHunk) -> String {
+    if hunk.uncommitted {
+        return "You, uncommitted".to_string();
+    }
+    let date = chrono::NaiveDateTime::from_timestamp_opt(hunk.time, 0)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    format!("{}, {date} • {}", hunk.author, hunk.summary)
 }
 
 /// Information about the phantom text on a specific line.  
@@ -334,6 +438,16 @@ pub fn before_col(&self, col: usize) -> usize {
         last
     }
 
+    /// The phantom text at the column of the text combined with it
+    pub fn phantom_at(&self, col: usize) -> Option<&PhantomText> {
+        self.offset_size_iter()
+            .find(|(col_shift, size, hint_col, _)| {
+                let start = hint_col + col_shift;
+                col >= start && col < start + size
+            })
+            .map(|(_, _, _, phantom)| phantom)
+    }
+
     /// Insert the hints at their positions in the text
     pub fn combine_with_text(&self, text: String) -> String {
         let mut text = text;
@@ -375,6 +489,17 @@ pub fn offset_size_iter(
     }
 }
 
+/// The soft wrapped lines of a document, along with what they were wrapped for
+struct WrappedLines {
+    /// The wrap width, tab width, wrap indent setting and the id of the config
+    /// the text was measured with
+    settings: (Option<f64>, usize, bool, u64),
+    /// The revision of the buffer the edits were applied up to
+    rev: u64,
+    folds: Folds,
+    lines: Arc<VisualLines>,
+}
+
 /// A [`Document`] is a core structure for files in the editor. All editors are merely views into a
 /// specific document, which is what allows views to be synchronized without any effort.  
 /// This builds on top of the held [`Buffer`], providing syntax/semantic highlighting, phantom
@@ -402,11 +527,26 @@ pub struct Document {
     pub text_layouts: Rc<RefCell<TextLayoutCache>>,
     /// A cache for the sticky headers which maps a line to the lines it should show in the header.
     pub sticky_headers: Rc<RefCell<HashMap<usize, Option<Vec<usize>>>>>,
+    /// The soft wrapped lines of the document, which are wrapped again as the lines
+    /// are edited
+    visual_lines: Rc<RefCell<Option<WrappedLines>>>,
+    /// The ranges of lines that are folded
+    folds: Folds,
+    /// The ranges of lines that can be folded, along with the revision they were found for
+    folding_ranges: Option<(u64, Arc<Vec<FoldRange>>)>,
     /// Whether we've started loading the buffer's content, used for file loading since that
     /// has to be done through a request to the proxy.
     load_started: Rc<RefCell<bool>>,
     /// Whether the buffer's content has been loaded/initialized into the buffer.
     loaded: bool,
+    /// The name of the encoding the file was read with, which it is saved with too
+    encoding: String,
+    /// Whether the document is in large file mode, without syntax highlighting
+    /// and the features of the language servers
+    large_file: bool,
+    /// How the file changed outside of Lapce, while the user hasn't chosen
+    /// which version to keep, or wasn't saved again after being deleted
+    disk_change: Option<DiskChange>,
     /// Stores information about different versions of the document from source control.
     histories: im::HashMap<String, DocumentHistory>,
     /// The cursor's offset into the document, which is synced to the [`LapceEditorData::cursor`]
@@ -418,6 +558,14 @@ pub struct Document {
     pub code_actions: im::HashMap<usize, (PluginId, CodeActionResponse)>,
     /// Inlay hints for the document
     pub inlay_hints: Option<Spans<InlayHint>>,
+    /// The lines the inlay hints are fetched for, around the ones an editor
+    /// last showed
+    inlay_hint_lines: std::ops::Range<usize>,
+    /// The colors the LSP found in the document, at their ranges, which get a
+    /// swatch before them
+    document_colors: Option<Spans<lsp_types::Color>>,
+    /// The scopes of the document symbols from the LSP, which give the sticky headers
+    symbol_scopes: Option<Arc<SymbolScopes>>,
     /// The diagnostics for the document
     pub diagnostics: Option<Arc<Vec<EditorDiagnostic>>>,
     /// Current completion text which should be rendered at the `completion_pos`, as phantom text
@@ -428,6 +576,18 @@ pub struct Document {
     ime_text: Option<Arc<str>>,
     /// (line, col, shift) position that the IME text should be displayed at.
     ime_pos: (usize, usize, usize),
+    /// The git blame of the file, only valid for the revision it was fetched for
+    pub blame: Option<DocumentBlame>,
+    /// The line the inline blame is shown at the end of
+    blame_line: Option<usize>,
+    /// When the buffer was last edited, for the local history snapshot to wait
+    /// for the edits to settle
+    pub last_edit: Option<Instant>,
+    /// If a local history snapshot is waiting for the edits to settle
+    pub local_history_pending: bool,
+    /// Cancels the wait for the formatter before saving, since its edits are
+    /// of no use once the document is edited
+    formatting: Option<FormattingCancel>,
     /// Information about specific ranges that are used to do smarter selections, supplied by an LSP
     pub syntax_selection_range: Option<SyntaxSelectionRanges>,
     /// Information about the file-specific find box
@@ -447,14 +607,7 @@ pub fn new(
     ) -> Self {
         // Only files have syntax highlighing automatically,
         // though scratch buffer can have it be set manually by the user.
-        let syntax = match &content {
-            BufferContent::File(path) => {
-                Self::syntax_to_option(&proxy, Syntax::init(path))
-            }
-            BufferContent::Local(_) => None,
-            BufferContent::SettingsValue(..) => None,
-            BufferContent::Scratch(..) => None,
-        };
+        let syntax = Self::syntax_to_option(&proxy, content_syntax(&content, false));
         // Since scratch specifies its own id, we have to use that as our buffer id.
         let id = match &content {
             BufferContent::Scratch(id, _) => *id,
@@ -470,19 +623,33 @@ pub fn new(
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
+            visual_lines: Rc::new(RefCell::new(None)),
+            folds: Folds::default(),
+            folding_ranges: None,
             semantic_styles: None,
             load_started: Rc::new(RefCell::new(false)),
             histories: im::HashMap::new(),
             loaded: false,
+            encoding: "UTF-8".to_string(),
+            large_file: false,
+            disk_change: None,
             cursor_offset: 0,
             scroll_offset: Vec2::ZERO,
             code_actions: im::HashMap::new(),
             inlay_hints: None,
+            inlay_hint_lines: 0..INLAY_HINT_LINES,
+            document_colors: None,
+            symbol_scopes: None,
             diagnostics: None,
             completion: None,
             completion_pos: (0, 0),
             ime_text: None,
             ime_pos: (0, 0, 0),
+            blame: None,
+            blame_line: None,
+            last_edit: None,
+            local_history_pending: false,
+            formatting: None,
             find: Rc::new(RefCell::new(Find::new(0))),
             find_progress: Rc::new(RefCell::new(FindProgress::Ready)),
             event_sink,
@@ -525,14 +692,10 @@ pub fn loaded(&self) -> bool {
 
     pub fn set_content(&mut self, content: BufferContent) {
         self.content = content;
-        self.syntax = match &self.content {
-            BufferContent::File(path) => {
-                Self::syntax_to_option(&self.proxy, Syntax::init(path))
-            }
-            BufferContent::Local(_) => None,
-            BufferContent::SettingsValue(..) => None,
-            BufferContent::Scratch(..) => None,
-        };
+        self.syntax = Self::syntax_to_option(
+            &self.proxy,
+            content_syntax(&self.content, self.large_file),
+        );
         self.on_update(None);
     }
 
@@ -546,13 +709,30 @@ pub fn rev(&self) -> u64 {
     }
 
     //// Initialize the content with some text, this marks the document as loaded.
-    pub fn init_content(&mut self, content: Rope) {
+    pub fn init_content(&mut self, content: Rope, config: &LapceConfig) {
         self.buffer.init_content(content);
-        self.buffer.detect_indent(self.syntax.as_ref());
+        self.detect_indent(config);
+        // The folds restored from the last session may not fit the file anymore
+        self.folds
+            .retain_valid(&self.buffer, config.tab_width_for(self.language()));
         self.loaded = true;
         self.on_update(None);
     }
 
+    /// Detect the indentation of the document, unless the config sets the
+    /// one of its language
+    pub fn detect_indent(&mut self, config: &LapceConfig) {
+        let language = self.language();
+        match config.insert_spaces_for(language) {
+            Some(true) => {
+                let width = config.tab_width_for(language).min(u8::MAX as usize);
+                self.buffer.set_indent_style(IndentStyle::Spaces(width as u8));
+            }
+            Some(false) => self.buffer.set_indent_style(IndentStyle::Tabs),
+            None => self.buffer.detect_indent(self.syntax.as_ref()),
+        }
+    }
+
     /// Set the syntax highlighting this document should use.
     pub fn set_language(&mut self, language: LapceLanguage) {
         self.syntax =
@@ -614,6 +794,29 @@ pub fn set_completion(&mut self, completion: String, line: usize, col: usize) {
         self.completion_pos = (line, col);
     }
 
+    pub fn set_blame(&mut self, rev: u64, hunks: Vec<BlameHunk>) {
+        self.clear_text_layout_cache();
+        self.blame = Some(DocumentBlame {
+            rev,
+            hunks: Some(Arc::new(hunks)),
+        });
+    }
+
+    /// Drops the blame so that it's fetched again, for when new commits were
+    /// made
+    pub fn clear_blame(&mut self) {
+        if self.blame.take().is_some() {
+            self.clear_text_layout_cache();
+        }
+    }
+
+    pub fn set_blame_line(&mut self, line: Option<usize>) {
+        if self.blame_line != line {
+            self.clear_text_layout_cache();
+            self.blame_line = line;
+        }
+    }
+
     pub fn clear_completion(&mut self) {
         if self.completion.is_some() {
             self.clear_text_layout_cache();
@@ -663,14 +866,67 @@ fn update_completion(&mut self, delta: &RopeDelta) {
     pub fn reload(&mut self, content: Rope, set_pristine: bool) {
         self.code_actions.clear();
         self.inlay_hints = None;
+        self.document_colors = None;
         let delta = self.buffer.reload(content, set_pristine);
         self.apply_deltas(&[delta]);
     }
 
-    pub fn handle_file_changed(&mut self, content: Rope) {
-        if self.buffer.is_pristine() {
+    /// Reload the document with the content its file has on disk now, if it
+    /// has no unsaved changes and reloading is allowed. Otherwise the content is
+    /// kept until the user chooses which version to keep, and `true` is returned
+    /// for them to be asked.
+    pub fn handle_file_changed(&mut self, content: Rope, auto_reload: bool) -> bool {
+        if content.len() == self.buffer.len()
+            && content.to_string() == self.buffer.text().to_string()
+        {
+            // The file has the content of the document now, like when the
+            // changes were undone on disk too
+            self.disk_change = None;
+            self.buffer.set_pristine();
+            return false;
+        }
+        if auto_reload && self.buffer.is_pristine() {
+            self.disk_change = None;
             self.reload(content, true);
+            return false;
         }
+        self.disk_change = Some(DiskChange::Modified(content));
+        true
+    }
+
+    /// Mark the document as deleted on disk, until it's saved
+    pub fn handle_file_deleted(&mut self) {
+        self.disk_change = Some(DiskChange::Deleted);
+    }
+
+    pub fn is_deleted_on_disk(&self) -> bool {
+        matches!(self.disk_change, Some(DiskChange::Deleted))
+    }
+
+    /// Replace the content of the document with the one its file has on disk,
+    /// dropping the unsaved changes
+    pub fn reload_from_disk(&mut self) {
+        if let Some(DiskChange::Modified(content)) = self.disk_change.take() {
+            self.reload(content, true);
+        }
+    }
+
+    /// Keep the content of the document rather than the one its file has on
+    /// disk, which is overwritten when the document is saved
+    pub fn keep_local_version(&mut self) {
+        if let Some(DiskChange::Modified(_)) = self.disk_change {
+            self.disk_change = None;
+            // The document isn't the same as the file anymore
+            if self.buffer.is_pristine() {
+                self.buffer.set_dirty();
+            }
+        }
+    }
+
+    /// Mark the document as saved, with its file having its content on disk
+    pub fn set_saved(&mut self) {
+        self.disk_change = None;
+        self.buffer.set_pristine();
     }
 
     /// Retrieve a file from the poxy, initialized to a specific starting location.  
@@ -680,6 +936,7 @@ pub fn retrieve_file<P: EditorPosition + Send + 'static>(
         locations: Vec<(WidgetId, EditorLocation<P>)>,
         unsaved_buffer: Option<Rope>,
         cb: Option<InitBufferContentCb>,
+        config: &LapceConfig,
     ) {
         if self.loaded || *self.load_started.borrow() {
             return;
@@ -692,23 +949,35 @@ pub fn retrieve_file<P: EditorPosition + Send + 'static>(
             let path = path.clone();
             let event_sink = self.event_sink.clone();
             let proxy = self.proxy.clone();
+            let large_file_threshold = config.document.large_file_threshold();
             std::thread::spawn(move || {
-                proxy.proxy_rpc.new_buffer(id, path.clone(), move |result| {
-                    if let Ok(ProxyResponse::NewBufferResponse { content }) = result
-                    {
-                        let _ = event_sink.submit_command(
-                            LAPCE_UI_COMMAND,
-                            P::init_buffer_content_cmd(
-                                path,
-                                Rope::from(content),
-                                locations,
-                                unsaved_buffer,
-                                cb,
-                            ),
-                            Target::Widget(tab_id),
-                        );
-                    };
-                })
+                proxy.proxy_rpc.new_buffer(
+                    id,
+                    path.clone(),
+                    large_file_threshold,
+                    move |result| {
+                        if let Ok(ProxyResponse::NewBufferResponse {
+                            content,
+                            encoding,
+                            large_file,
+                        }) = result
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                P::init_buffer_content_cmd(
+                                    path,
+                                    Rope::from(content),
+                                    encoding,
+                                    large_file,
+                                    locations,
+                                    unsaved_buffer,
+                                    cb,
+                                ),
+                                Target::Widget(tab_id),
+                            );
+                        };
+                    },
+                )
             });
         }
 
@@ -725,6 +994,110 @@ pub fn retrieve_history(&mut self, version: &str) {
         self.histories.insert(version.to_string(), history);
     }
 
+    /// Convert all the line endings of the document, which new lines use too
+    pub fn convert_line_endings(&mut self, line_ending: LineEnding) {
+        if let Some(delta) = self.buffer.convert_line_endings(line_ending) {
+            self.apply_deltas(&[delta]);
+        }
+    }
+
+    /// Whether the document is in large file mode
+    pub fn large_file(&self) -> bool {
+        self.large_file
+    }
+
+    /// Turn the large file mode on or off. Turning it on drops the syntax
+    /// highlighting and the styles and hints from the language servers, turning
+    /// it off brings them back.
+    pub fn set_large_file(&mut self, large_file: bool) {
+        if self.large_file == large_file {
+            return;
+        }
+        self.large_file = large_file;
+        if large_file {
+            self.syntax = None;
+            self.semantic_styles = None;
+            self.inlay_hints = None;
+            self.document_colors = None;
+            self.symbol_scopes = None;
+            self.clear_style_cache();
+            self.clear_sticky_headers_cache();
+        } else {
+            if let BufferContent::File(_) = &self.content {
+                self.syntax = Self::syntax_to_option(
+                    &self.proxy,
+                    content_syntax(&self.content, large_file),
+                );
+            }
+            self.clear_style_cache();
+            self.trigger_syntax_change(None);
+            self.get_semantic_styles();
+            self.get_inlay_hints();
+            self.get_document_colors();
+            self.get_symbol_scopes();
+        }
+    }
+
+    /// Switch the large file mode of the document, which the proxy is told
+    /// about so that the language servers are attached when it is turned off
+    pub fn toggle_large_file(&mut self) {
+        let large_file = !self.large_file;
+        if let BufferContent::File(path) = &self.content {
+            self.proxy
+                .proxy_rpc
+                .set_large_file_mode(path.clone(), large_file);
+        }
+        self.set_large_file(large_file);
+    }
+
+    pub fn encoding(&self) -> &str {
+        &self.encoding
+    }
+
+    pub fn set_encoding(&mut self, encoding: String) {
+        self.encoding = encoding;
+    }
+
+    /// Read the file again with the encoding, replacing the unsaved changes.
+    /// The file is saved with the encoding from then on.
+    pub fn reopen_with_encoding(&self, encoding: &str) {
+        let BufferContent::File(path) = &self.content else { return };
+        let path = path.clone();
+        let rev = self.rev();
+        let tab_id = self.tab_id;
+        let event_sink = self.event_sink.clone();
+        let proxy = self.proxy.clone();
+        let encoding = encoding.to_string();
+        std::thread::spawn(move || {
+            proxy.proxy_rpc.reopen_with_encoding(
+                path.clone(),
+                encoding,
+                move |result| match result {
+                    Ok(ProxyResponse::NewBufferResponse {
+                        content,
+                        encoding,
+                        ..
+                    }) => {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ReopenedWithEncoding {
+                                path,
+                                rev,
+                                content: Rope::from(content),
+                                encoding,
+                            },
+                            Target::Widget(tab_id),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        log::error!("Failed to reopen {path:?}: {}", err.message);
+                    }
+                },
+            );
+        });
+    }
+
     pub fn reload_history(&self, version: &str) {
         if let Some(history) = self.histories.get(version) {
             history.retrieve(self);
@@ -732,6 +1105,11 @@ pub fn reload_history(&self, version: &str) {
     }
 
     pub fn load_history(&mut self, version: &str, content: Rope) {
+        // Compared with the text, which has the CR line endings as LF
+        let content = match self.buffer.line_ending() {
+            LineEnding::Cr => LineEnding::Lf.normalize(&content).unwrap_or(content),
+            _ => content,
+        };
         let mut history = DocumentHistory::new(version.to_string());
         history.load_content(content, self);
         self.histories.insert(version.to_string(), history);
@@ -868,7 +1246,7 @@ pub fn update_history_styles(
 
     /// Request semantic styles for the buffer from the LSP through the proxy.
     fn get_semantic_styles(&self) {
-        if !self.loaded() {
+        if !self.loaded() || self.large_file {
             return;
         }
 
@@ -930,9 +1308,33 @@ fn get_semantic_styles(&self) {
         }
     }
 
-    /// Request inlay hints for the buffer from the LSP through the proxy.
+    /// The lines the inlay hints are fetched for
+    pub fn inlay_hint_lines(&self) -> &std::ops::Range<usize> {
+        &self.inlay_hint_lines
+    }
+
+    /// The lines to fetch the inlay hints of for an editor showing the visible
+    /// lines, if they weren't fetched already
+    pub fn inlay_hint_lines_to_fetch(
+        &self,
+        visible: std::ops::Range<usize>,
+    ) -> Option<std::ops::Range<usize>> {
+        if !self.content().is_file() {
+            return None;
+        }
+        lines_to_fetch(&self.inlay_hint_lines, visible, self.buffer.num_lines())
+    }
+
+    /// Fetch the inlay hints of the lines instead of the ones fetched before
+    pub fn fetch_inlay_hints(&mut self, lines: std::ops::Range<usize>) {
+        self.inlay_hint_lines = lines;
+        self.get_inlay_hints();
+    }
+
+    /// Request inlay hints for the lines an editor shows from the LSP through
+    /// the proxy.
     pub fn get_inlay_hints(&self) {
-        if !self.loaded() {
+        if !self.loaded() || self.large_file {
             return;
         }
 
@@ -945,32 +1347,86 @@ pub fn get_inlay_hints(&self) {
             let path = path.clone();
             let buffer = self.buffer().clone();
             let rev = buffer.rev();
-            let len = buffer.len();
+            let lines = self.inlay_hint_lines.clone();
+            let range = Range {
+                start: buffer.offset_to_position(buffer.offset_of_line(lines.start)),
+                end: buffer.offset_to_position(buffer.offset_of_line(lines.end)),
+            };
+            let event_sink = self.event_sink.clone();
+            self.proxy.proxy_rpc.get_inlay_hints(
+                path.clone(),
+                range,
+                move |result| {
+                    if let Ok(ProxyResponse::GetInlayHints { hints }) = result {
+                        let hints = inlay_hint_spans(&buffer, hints);
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateInlayHints {
+                                path,
+                                rev,
+                                lines,
+                                hints,
+                            },
+                            Target::Widget(tab_id),
+                        );
+                    }
+                },
+            );
+        }
+    }
+
+    /// Request the colors of the document from the LSP through the proxy, for
+    /// their swatches.
+    pub fn get_document_colors(&self) {
+        if !self.loaded() || self.large_file {
+            return;
+        }
+
+        if let BufferContent::File(path) = self.content() {
+            let tab_id = self.tab_id;
+            let path = path.clone();
+            let buffer = self.buffer().clone();
+            let rev = buffer.rev();
             let event_sink = self.event_sink.clone();
             self.proxy
                 .proxy_rpc
-                .get_inlay_hints(path.clone(), move |result| {
-                    if let Ok(ProxyResponse::GetInlayHints { mut hints }) = result {
-                        // Sort the inlay hints by their position, as the LSP does not guarantee that it will
-                        // provide them in the order that they are in within the file
-                        // as well, Spans does not iterate in the order that they appear
-                        hints.sort_by(|left, right| {
-                            left.position.cmp(&right.position)
-                        });
+                .get_document_colors(path.clone(), move |result| {
+                    if let Ok(ProxyResponse::GetDocumentColors { colors }) = result {
+                        let colors = document_color_spans(&buffer, colors);
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateDocumentColors {
+                                path,
+                                rev,
+                                colors,
+                            },
+                            Target::Widget(tab_id),
+                        );
+                    }
+                });
+        }
+    }
 
-                        let mut hints_span = SpansBuilder::new(len);
-                        for hint in hints {
-                            let offset =
-                                buffer.offset_of_position(&hint.position).min(len);
-                            hints_span.add_span(
-                                Interval::new(offset, (offset + 1).min(len)),
-                                hint,
-                            );
-                        }
-                        let hints = hints_span.build();
+    /// Request the document symbols from the LSP through the proxy, for the scopes
+    /// of the sticky headers.
+    pub fn get_symbol_scopes(&self) {
+        if !self.loaded() || self.large_file {
+            return;
+        }
+
+        if let BufferContent::File(path) = self.content() {
+            let tab_id = self.tab_id;
+            let path = path.clone();
+            let rev = self.rev();
+            let event_sink = self.event_sink.clone();
+            self.proxy
+                .proxy_rpc
+                .get_document_symbols(path.clone(), move |result| {
+                    if let Ok(ProxyResponse::GetDocumentSymbols { resp }) = result {
+                        let scopes = Arc::new(SymbolScopes::new(&resp));
                         let _ = event_sink.submit_command(
                             LAPCE_UI_COMMAND,
-                            LapceUICommand::UpdateInlayHints { path, rev, hints },
+                            LapceUICommand::UpdateSymbolScopes { path, rev, scopes },
                             Target::Widget(tab_id),
                         );
                     }
@@ -983,12 +1439,31 @@ pub fn get_inlay_hints(&self) {
         self.find.borrow_mut().unset();
         *self.find_progress.borrow_mut() = FindProgress::Started;
         self.get_inlay_hints();
+        self.get_document_colors();
+        self.get_symbol_scopes();
         self.clear_style_cache();
         self.trigger_syntax_change(edits);
         self.get_semantic_styles();
         self.clear_sticky_headers_cache();
         self.trigger_head_change();
         self.notify_special();
+        self.schedule_local_history();
+    }
+
+    /// Request a local history snapshot of the file once the edits settled
+    fn schedule_local_history(&mut self) {
+        if let BufferContent::File(path) = &self.content {
+            self.last_edit = Some(Instant::now());
+            if self.local_history_pending {
+                return;
+            }
+            self.local_history_pending = true;
+            let _ = self.event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::LocalHistorySnapshot { path: path.clone() },
+                Target::Widget(self.tab_id),
+            );
+        }
     }
 
     /// Notify special buffer content's about their content potentially changing.
@@ -1098,6 +1573,35 @@ pub fn set_inlay_hints(&mut self, hints: Spans<InlayHint>) {
         self.clear_text_layout_cache();
     }
 
+    /// Update the colors of the document with new ones
+    pub fn set_document_colors(&mut self, colors: Spans<lsp_types::Color>) {
+        self.document_colors = Some(colors);
+        self.clear_text_layout_cache();
+    }
+
+    /// The range of the color whose swatch is at the offset, with the color
+    pub fn document_color_at(
+        &self,
+        offset: usize,
+    ) -> Option<(Range, lsp_types::Color)> {
+        let (interval, color) = self
+            .document_colors
+            .as_ref()?
+            .iter_chunks(offset..offset + 1)
+            .find(|(interval, _)| interval.start == offset)?;
+        let range = Range {
+            start: self.buffer.offset_to_position(interval.start),
+            end: self.buffer.offset_to_position(interval.end),
+        };
+        Some((range, *color))
+    }
+
+    /// Update the scopes of the document symbols with new ones
+    pub fn set_symbol_scopes(&mut self, scopes: Arc<SymbolScopes>) {
+        self.symbol_scopes = Some(scopes);
+        self.clear_sticky_headers_cache();
+    }
+
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
@@ -1110,6 +1614,57 @@ pub fn syntax(&self) -> Option<&Syntax> {
         self.syntax.as_ref()
     }
 
+    pub fn language(&self) -> Option<LapceLanguage> {
+        self.syntax.as_ref().map(|syntax| syntax.language)
+    }
+
+    /// Start waiting for the formatter, cancelling the earlier wait, and get
+    /// the receiver which tells when this one is cancelled
+    pub fn start_formatting(&mut self) -> Receiver<()> {
+        self.cancel_formatting();
+        let (cancel, cancelled) = FormattingCancel::new();
+        self.formatting = Some(cancel);
+        cancelled
+    }
+
+    /// Stop waiting for the formatter, so that the document is saved right
+    /// away without being formatted
+    pub fn cancel_formatting(&mut self) {
+        if let Some(cancel) = self.formatting.take() {
+            cancel.cancel();
+        }
+    }
+
+    /// The ranges changed since the document was last saved, which are
+    /// formatted when the language server can only format ranges. It's the
+    /// whole document if no revision is the saved one.
+    pub fn changed_ranges(&self) -> Vec<Range> {
+        let to_range = |lines: &std::ops::Range<usize>| {
+            Range::new(
+                Position::new(lines.start as u32, 0),
+                Position::new(lines.end as u32, 0),
+            )
+        };
+        let changes = self.buffer.pristine_text().and_then(|pristine| {
+            rope_diff(
+                pristine,
+                self.buffer.text().clone(),
+                self.rev(),
+                self.buffer.atomic_rev(),
+                0,
+            )
+        });
+        match changes {
+            Some(changes) => diff_hunks(&changes)
+                .iter()
+                .map(|(_, lines)| lines)
+                .filter(|lines| !lines.is_empty())
+                .map(to_range)
+                .collect(),
+            None => vec![to_range(&(0..self.buffer.num_lines()))],
+        }
+    }
+
     /// Update the styles after an edit, so the highlights are at the correct positions.  
     /// This does not do a reparse of the document itself.
     fn update_styles(&mut self, delta: &RopeDelta) {
@@ -1134,6 +1689,14 @@ fn update_inlay_hints(&mut self, delta: &RopeDelta) {
         }
     }
 
+    /// Update the document colors so their ranges are correct after an edit,
+    /// until the colors are fetched again.
+    fn update_document_colors(&mut self, delta: &RopeDelta) {
+        if let Some(colors) = self.document_colors.as_mut() {
+            colors.apply_shape(delta);
+        }
+    }
+
     pub fn set_ime_pos(&mut self, line: usize, col: usize, shift: usize) {
         self.ime_pos = (line, col, shift);
     }
@@ -1177,21 +1740,17 @@ pub fn line_phantom_text(
             .map(|hints| hints.iter_chunks(start_offset..end_offset))
             .into_iter()
             .flatten()
-            .filter(|(interval, _)| {
-                interval.start >= start_offset && interval.start < end_offset
+            .filter(|(interval, inlay_hint)| {
+                interval.start >= start_offset
+                    && interval.start < end_offset
+                    && config.editor.inlay_hint_kind_enabled(inlay_hint.kind)
             })
             .map(|(interval, inlay_hint)| {
                 let (_, col) = self.buffer.offset_to_line_col(interval.start);
-                let text = match &inlay_hint.label {
-                    InlayHintLabel::String(label) => label.to_string(),
-                    InlayHintLabel::LabelParts(parts) => {
-                        parts.iter().map(|p| &p.value).join("")
-                    }
-                };
                 PhantomText {
                     kind: PhantomTextKind::InlayHint,
                     col,
-                    text,
+                    text: inlay_hint_text(inlay_hint),
                     fg: Some(
                         config
                             .get_color_unchecked(LapceTheme::INLAY_HINT_FOREGROUND)
@@ -1212,6 +1771,31 @@ pub fn line_phantom_text(
         // overall.
         let mut text: SmallVec<[PhantomText; 6]> = hints.collect();
 
+        // A swatch in the color before each color of the line
+        let swatches = self
+            .document_colors
+            .as_ref()
+            .map(|colors| colors.iter_chunks(start_offset..end_offset))
+            .into_iter()
+            .flatten()
+            .filter(|(interval, _)| {
+                interval.start >= start_offset && interval.start < end_offset
+            })
+            .map(|(interval, color)| {
+                let (_, col) = self.buffer.offset_to_line_col(interval.start);
+                PhantomText {
+                    kind: PhantomTextKind::ColorSwatch,
+                    col,
+                    text: COLOR_SWATCH.to_string(),
+                    font_size: None,
+                    font_family: None,
+                    fg: Some(swatch_color(color)),
+                    bg: None,
+                    under_line: None,
+                }
+            });
+        text.extend(swatches);
+
         // The max severity is used to determine the color given to the background of the line
         let mut max_severity = None;
         // If error lens is enabled, and the diagnostics field is filled, then get the diagnostics
@@ -1278,6 +1862,36 @@ pub fn line_phantom_text(
 
         text.append(&mut diag_text);
 
+        // The blame is only shown if it was fetched for the current content
+        let blame_text = config
+            .source_control
+            .inline_blame
+            .then_some(())
+            .filter(|_| self.blame_line == Some(line))
+            .and(self.blame.as_ref())
+            .filter(|blame| blame.rev == self.rev())
+            .and_then(|blame| blame.hunks.as_ref())
+            .and_then(|hunks| hunks.iter().find(|hunk| hunk.lines.contains(&line)))
+            .map(|hunk| {
+                // After the last character, not after the line ending
+                let col = self.buffer.line_end_col(line, true);
+                PhantomText {
+                    kind: PhantomTextKind::Blame,
+                    col,
+                    text: format!("    {}", inline_blame_text(hunk)),
+                    fg: Some(
+                        config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone(),
+                    ),
+                    font_size: Some(config.editor.inlay_hint_font_size()),
+                    font_family: Some(config.editor.inlay_hint_font_family()),
+                    bg: None,
+                    under_line: None,
+                }
+            });
+        if let Some(blame_text) = blame_text {
+            text.push(blame_text);
+        }
+
         let (completion_line, completion_col) = self.completion_pos;
         let completion_text = config
             .editor
@@ -1337,12 +1951,24 @@ pub fn line_phantom_text(
     }
 
     fn apply_deltas(&mut self, deltas: &[(RopeDelta, InvalLines, SyntaxEdit)]) {
+        self.cancel_formatting();
         let rev = self.rev() - deltas.len() as u64;
-        for (i, (delta, _, _)) in deltas.iter().enumerate() {
+        for (i, (delta, inval_lines, _)) in deltas.iter().enumerate() {
             self.update_styles(delta);
             self.update_inlay_hints(delta);
+            self.update_document_colors(delta);
             self.update_diagnostics(delta);
             self.update_completion(delta);
+            self.folds.apply_edit(inval_lines);
+            if let Some(wrapped) = self.visual_lines.borrow_mut().as_mut() {
+                if wrapped.rev == rev + i as u64 {
+                    Arc::make_mut(&mut wrapped.lines).apply_edit(inval_lines);
+                    wrapped.rev += 1;
+                }
+            }
+            if let Some(scopes) = self.symbol_scopes.as_mut() {
+                Arc::make_mut(scopes).apply_edit(inval_lines);
+            }
             if let BufferContent::File(path) = &self.content {
                 self.proxy.proxy_rpc.update(
                     path.clone(),
@@ -1424,6 +2050,7 @@ pub fn do_multi_selection(
         cursor: &mut Cursor,
         cmd: &MultiSelectionCommand,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) {
         use MultiSelectionCommand::*;
@@ -1449,6 +2076,7 @@ pub fn do_multi_selection(
                         &Movement::Up,
                         Mode::Insert,
                         view,
+                        wrap_width,
                         config,
                     );
                     if new_offset != offset {
@@ -1470,6 +2098,7 @@ pub fn do_multi_selection(
                         &Movement::Down,
                         Mode::Insert,
                         view,
+                        wrap_width,
                         config,
                     );
                     if new_offset != offset {
@@ -1480,6 +2109,39 @@ pub fn do_multi_selection(
                     cursor.set_insert(selection);
                 }
             }
+            SelectBlockUp | SelectBlockDown => {
+                if let CursorMode::Insert(selection) = cursor.mode.clone() {
+                    let (Some(first), Some(last), Some(head)) = (
+                        selection.first(),
+                        selection.last(),
+                        selection.last_inserted(),
+                    ) else {
+                        return;
+                    };
+                    // The head of a block is the row that was extended last, the
+                    // anchor is the row at the other end.
+                    let anchor = if selection.len() > 1 && head == first {
+                        last.start
+                    } else {
+                        first.start
+                    };
+                    let (head_line, head_col) =
+                        self.buffer.offset_to_line_char_col(head.end);
+                    let new_line = if *cmd == SelectBlockUp {
+                        head_line.saturating_sub(1)
+                    } else {
+                        (head_line + 1).min(self.buffer.last_line())
+                    };
+                    if new_line == head_line {
+                        return;
+                    }
+                    cursor.set_insert(Selection::block(
+                        &self.buffer,
+                        self.buffer.offset_to_line_char_col(anchor),
+                        (new_line, head_col),
+                    ));
+                }
+            }
             InsertCursorEndOfLine => {
                 if let CursorMode::Insert(selection) = cursor.mode.clone() {
                     let mut new_selection = Selection::new();
@@ -1501,6 +2163,14 @@ pub fn do_multi_selection(
                     cursor.set_insert(new_selection);
                 }
             }
+            SplitSelectionIntoLines => {
+                if let CursorMode::Insert(selection) = cursor.mode.clone() {
+                    cursor.set_insert(selection.split_into_lines(
+                        &self.buffer,
+                        config.editor.split_selection_to_line_start,
+                    ));
+                }
+            }
             SelectCurrentLine => {
                 if let CursorMode::Insert(selection) = cursor.mode.clone() {
                     let mut new_selection = Selection::new();
@@ -1535,9 +2205,12 @@ pub fn do_multi_selection(
                         find.set_case_sensitive(case_sensitive);
                         find.set_find(&search_str, false, search_whole_word);
                         let mut offset = 0;
-                        while let Some((start, end)) =
-                            find.next(self.buffer.text(), offset, false, false)
-                        {
+                        while let Some((start, end)) = find.next_unselected(
+                            self.buffer.text(),
+                            offset,
+                            &selection,
+                            false,
+                        ) {
                             offset = end;
                             selection.add_region(SelRegion::new(start, end, None));
                         }
@@ -1571,26 +2244,14 @@ pub fn do_multi_selection(
                             let mut find = Find::new(0);
                             find.set_case_sensitive(case_sensitive);
                             find.set_find(&search_str, false, search_whole_word);
-                            let mut offset = r.max();
-                            let mut seen = HashSet::new();
-                            while let Some((start, end)) =
-                                find.next(self.buffer.text(), offset, false, true)
-                            {
-                                if !selection
-                                    .regions()
-                                    .iter()
-                                    .any(|r| r.min() == start && r.max() == end)
-                                {
-                                    selection.add_region(SelRegion::new(
-                                        start, end, None,
-                                    ));
-                                    break;
-                                }
-                                if seen.contains(&end) {
-                                    break;
-                                }
-                                offset = end;
-                                seen.insert(offset);
+                            if let Some((start, end)) = find.next_unselected(
+                                self.buffer.text(),
+                                r.max(),
+                                &selection,
+                                config.editor.multicursor_wrap_around,
+                            ) {
+                                selection
+                                    .add_region(SelRegion::new(start, end, None));
                             }
                         }
                     }
@@ -1613,26 +2274,15 @@ pub fn do_multi_selection(
                             let mut find = Find::new(0);
                             find.set_case_sensitive(case_sensitive);
                             find.set_find(&search_str, false, false);
-                            let mut offset = r.max();
-                            let mut seen = HashSet::new();
-                            while let Some((start, end)) =
-                                find.next(self.buffer.text(), offset, false, true)
-                            {
-                                if !selection
-                                    .regions()
-                                    .iter()
-                                    .any(|r| r.min() == start && r.max() == end)
-                                {
-                                    selection.replace_last_inserted_region(
-                                        SelRegion::new(start, end, None),
-                                    );
-                                    break;
-                                }
-                                if seen.contains(&end) {
-                                    break;
-                                }
-                                offset = end;
-                                seen.insert(offset);
+                            if let Some((start, end)) = find.next_unselected(
+                                self.buffer.text(),
+                                r.max(),
+                                &selection,
+                                config.editor.multicursor_wrap_around,
+                            ) {
+                                selection.replace_last_inserted_region(
+                                    SelRegion::new(start, end, None),
+                                );
                             }
                         }
                     }
@@ -1703,18 +2353,19 @@ fn line_style(&self, line: usize) -> Arc<Vec<LineStyle>> {
         self.line_styles.borrow().get(&line).cloned().unwrap()
     }
 
-    /// Get the (line, col) of a particular point within the editor.
-    /// The boolean indicates whether the point is within the text bounds.  
-    /// Points outside of vertical bounds will return the last line.
-    /// Points outside of horizontal bounds will return the last column on the line.
-    pub fn line_col_of_point(
+    /// Hit test a point within the editor against the text of its line, with the
+    /// phantom text, and get the line with the segment of it that was hit when
+    /// the lines are wrapped.
+    fn hit_test_point(
         &self,
         text: &mut PietText,
-        mode: Mode,
         point: Point,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
-    ) -> ((usize, usize), bool) {
+    ) -> (usize, Option<VisualLine>, HitTestPoint) {
+        // The segment of the line the point is on, when the lines are wrapped
+        let mut visual_line = None;
         let (line, font_size) = match view {
             EditorView::Diff(version) => {
                 let changes = self
@@ -1784,15 +2435,26 @@ pub fn line_col_of_point(
                     )
                 }
             }
-            EditorView::Normal => (
-                (point.y / config.editor.line_height() as f64).floor() as usize,
-                config.editor.font_size,
-            ),
+            EditorView::Normal => {
+                let line =
+                    (point.y / config.editor.line_height() as f64).floor() as usize;
+                match self.visual_lines(text, wrap_width, config) {
+                    Some(visual_lines) => {
+                        let segment = visual_lines.visual_line(line);
+                        visual_line = Some(segment);
+                        (segment.line, config.editor.font_size)
+                    }
+                    None => (line, config.editor.font_size),
+                }
+            }
         };
 
         let line = line.min(self.buffer.last_line());
 
         let mut x_shift = 0.0;
+        if let Some(visual_line) = visual_line.as_ref() {
+            x_shift = self.visual_line_x_shift(text, visual_line, font_size, config);
+        }
         if font_size < config.editor.font_size {
             let line_content = self.buffer.line_content(line);
             let mut col = 0usize;
@@ -1827,6 +2489,24 @@ pub fn line_col_of_point(
         let hit_point = text_layout
             .text
             .hit_test_point(Point::new(point.x - x_shift, 0.0));
+        (line, visual_line, hit_point)
+    }
+
+    /// Get the (line, col) of a particular point within the editor.
+    /// The boolean indicates whether the point is within the text bounds.  
+    /// Points outside of vertical bounds will return the last line.
+    /// Points outside of horizontal bounds will return the last column on the line.
+    pub fn line_col_of_point(
+        &self,
+        text: &mut PietText,
+        mode: Mode,
+        point: Point,
+        view: &EditorView,
+        wrap_width: Option<f64>,
+        config: &LapceConfig,
+    ) -> ((usize, usize), bool) {
+        let (line, visual_line, hit_point) =
+            self.hit_test_point(text, point, view, wrap_width, config);
         // We have to unapply the phantom text shifting in order to get back to the column in
         // the actual buffer
         let phantom_text = self.line_phantom_text(config, line);
@@ -1835,20 +2515,47 @@ pub fn line_col_of_point(
         // right end will just go to the end of the line.
         let max_col = self.buffer.line_end_col(line, mode != Mode::Normal);
         let mut col = col.min(max_col);
+        if let Some(visual_line) = visual_line.as_ref() {
+            // Keep the column on the row of the wrapped line that was hit
+            col = visual_line.clamp_col(col, max_col);
+        }
 
-        if config.editor.atomic_soft_tabs && config.editor.tab_width > 1 {
+        let tab_width = config.tab_width_for(self.language());
+        if config.editor.atomic_soft_tabs && tab_width > 1 {
             col = snap_to_soft_tab_line_col(
                 &self.buffer,
                 line,
                 col,
                 SnapDirection::Nearest,
-                config.editor.tab_width,
+                tab_width,
             );
         }
 
         ((line, col), hit_point.is_inside)
     }
 
+    /// The range and the color of the document color whose swatch is at the
+    /// point within the editor, if there's one
+    pub fn color_of_point(
+        &self,
+        text: &mut PietText,
+        point: Point,
+        view: &EditorView,
+        wrap_width: Option<f64>,
+        config: &LapceConfig,
+    ) -> Option<(Range, lsp_types::Color)> {
+        let (line, _, hit_point) =
+            self.hit_test_point(text, point, view, wrap_width, config);
+        if !hit_point.is_inside {
+            return None;
+        }
+        let phantom_text = self.line_phantom_text(config, line);
+        let swatch = phantom_text
+            .phantom_at(hit_point.idx)
+            .filter(|phantom| phantom.kind == PhantomTextKind::ColorSwatch)?;
+        self.document_color_at(self.buffer.offset_of_line_col(line, swatch.col))
+    }
+
     /// Get the offset of a particular point within the editor.  
     /// The boolean indicates whether the point is inside the text or not
     /// Points outside of vertical bounds will return the last line.
@@ -1859,10 +2566,11 @@ pub fn offset_of_point(
         mode: Mode,
         point: Point,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> (usize, bool) {
         let ((line, col), is_inside) =
-            self.line_col_of_point(text, mode, point, view, config);
+            self.line_col_of_point(text, mode, point, view, wrap_width, config);
         (self.buffer.offset_of_line_col(line, col), is_inside)
     }
 
@@ -1872,10 +2580,11 @@ pub fn points_of_offset(
         text: &mut PietText,
         offset: usize,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> (Point, Point) {
         let (line, col) = self.buffer.offset_to_line_col(offset);
-        self.points_of_line_col(text, line, col, view, config)
+        self.points_of_line_col(text, line, col, view, wrap_width, config)
     }
 
     /// Get the (point above, point below) of a particular (line, col) within the editor.
@@ -1885,8 +2594,11 @@ pub fn points_of_line_col(
         line: usize,
         col: usize,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> (Point, Point) {
+        // The segment of the line the column is on, when the lines are wrapped
+        let mut visual_line = None;
         let (y, line_height, font_size) = match view {
             EditorView::Diff(version) => {
                 let changes = self
@@ -1940,11 +2652,21 @@ pub fn points_of_line_col(
                     )
                 }
             }
-            EditorView::Normal => (
-                config.editor.line_height() * line,
-                config.editor.line_height(),
-                config.editor.font_size,
-            ),
+            EditorView::Normal => {
+                let line = match self.visual_lines(text, wrap_width, config) {
+                    Some(visual_lines) => {
+                        let row = visual_lines.visual_line_of_line_col(line, col);
+                        visual_line = Some(visual_lines.visual_line(row));
+                        row
+                    }
+                    None => line,
+                };
+                (
+                    config.editor.line_height() * line,
+                    config.editor.line_height(),
+                    config.editor.font_size,
+                )
+            }
         };
 
         let line = line.min(self.buffer.last_line());
@@ -1953,6 +2675,9 @@ pub fn points_of_line_col(
         let col = phantom_text.col_after(col, false);
 
         let mut x_shift = 0.0;
+        if let Some(visual_line) = visual_line.as_ref() {
+            x_shift = self.visual_line_x_shift(text, visual_line, font_size, config);
+        }
         if font_size < config.editor.font_size {
             let line_content = self.buffer.line_content(line);
             let mut col = 0usize;
@@ -2065,6 +2790,140 @@ pub fn line_point_of_line_col(
         text_layout.text.hit_test_text_position(col).point
     }
 
+    /// The lines of the document once they're soft wrapped at `wrap_width` and
+    /// folded, or `None` if they're neither wrapped nor folded. Only the lines edited
+    /// since they were wrapped are wrapped again, unless the settings changed. Large
+    /// files are never wrapped or folded.
+    pub fn visual_lines(
+        &self,
+        text: &mut PietText,
+        wrap_width: Option<f64>,
+        config: &LapceConfig,
+    ) -> Option<Arc<VisualLines>> {
+        if !has_visual_lines(self.large_file, wrap_width, &self.folds) {
+            return None;
+        }
+        let tab_width = config.tab_width_for(self.language());
+        let wrap_indent = config.editor.wrap_indent;
+        let settings = (wrap_width, tab_width, wrap_indent, config.id);
+        // The text is measured by the width of each char in the editor font
+        let mut char_widths = HashMap::new();
+        let mut char_width = |ch: char| {
+            *char_widths.entry(ch).or_insert_with(|| {
+                config.editor_text_width(text, ch.encode_utf8(&mut [0; 4]))
+            })
+        };
+
+        let mut cache = self.visual_lines.borrow_mut();
+        if let Some(wrapped) = cache.as_mut().filter(|wrapped| {
+            wrapped.settings == settings && wrapped.rev == self.buffer.rev()
+        }) {
+            if wrapped.folds != self.folds || wrapped.lines.is_edited() {
+                Arc::make_mut(&mut wrapped.lines).rewrap(
+                    &self.buffer,
+                    tab_width,
+                    wrap_indent,
+                    &self.folds,
+                    &mut char_width,
+                );
+                wrapped.folds = self.folds.clone();
+            }
+            return Some(wrapped.lines.clone());
+        }
+
+        let lines = Arc::new(VisualLines::with_folds(
+            &self.buffer,
+            wrap_width,
+            tab_width,
+            wrap_indent,
+            &self.folds,
+            &mut char_width,
+        ));
+        *cache = Some(WrappedLines {
+            settings,
+            rev: self.buffer.rev(),
+            folds: self.folds.clone(),
+            lines: lines.clone(),
+        });
+        Some(lines)
+    }
+
+    /// The ranges of lines that are folded
+    pub fn folds(&self) -> &Folds {
+        &self.folds
+    }
+
+    pub fn folds_mut(&mut self) -> &mut Folds {
+        &mut self.folds
+    }
+
+    /// The ranges of lines that can be folded, if they were found for the current
+    /// revision
+    pub fn folding_ranges(&self) -> Option<Arc<Vec<FoldRange>>> {
+        self.folding_ranges
+            .as_ref()
+            .filter(|(rev, _)| *rev == self.rev())
+            .map(|(_, ranges)| ranges.clone())
+    }
+
+    /// Keep the ranges of lines that can be folded at the revision, the ones of the
+    /// language server if it provides them, otherwise the ones found from the
+    /// indentation of the lines.
+    pub fn set_folding_ranges(
+        &mut self,
+        rev: u64,
+        ranges: Option<Vec<FoldingRange>>,
+        config: &LapceConfig,
+    ) {
+        if rev != self.rev() {
+            return;
+        }
+
+        let ranges = match ranges {
+            Some(ranges) if !ranges.is_empty() => ranges
+                .iter()
+                .map(|range| {
+                    FoldRange::new(
+                        range.start_line as usize,
+                        range.end_line as usize,
+                    )
+                })
+                .collect(),
+            _ => indent_fold_ranges(
+                &self.buffer,
+                config.tab_width_for(self.language()),
+            ),
+        };
+        self.folding_ranges = Some((rev, Arc::new(ranges)));
+    }
+
+    /// How far the text layout of the line is shifted horizontally to display the
+    /// segment of the visual line, which puts the segment continuing a wrapped line
+    /// at the line's indentation.
+    pub fn visual_line_x_shift(
+        &self,
+        text: &mut PietText,
+        visual_line: &VisualLine,
+        font_size: usize,
+        config: &LapceConfig,
+    ) -> f64 {
+        if !visual_line.is_continuation() {
+            return 0.0;
+        }
+
+        let line = visual_line.line;
+        let phantom_text = self.line_phantom_text(config, line);
+        let start = phantom_text.col_after(visual_line.start, false);
+        let indent = phantom_text.col_after(visual_line.indent, false);
+        let start_x = self
+            .line_point_of_line_col(text, line, start, font_size, config)
+            .x;
+        let indent_x = self
+            .line_point_of_line_col(text, line, indent, font_size, config)
+            .x;
+        indent_x - start_x
+    }
+
     /// Get the text layout for the given line.  
     /// If the text layout is not cached, it will be created and cached.
     pub fn get_text_layout(
@@ -2153,8 +3012,12 @@ fn new_text_layout(
         let phantom_text = self.line_phantom_text(config, line);
         let line_content = phantom_text.combine_with_text(line_content);
 
-        let tab_width =
-            config.tab_width(text, config.editor.font_family(), font_size);
+        let tab_width = config.tab_width(
+            text,
+            self.language(),
+            config.editor.font_family(),
+            font_size,
+        );
 
         // Inputs use the UI font instead of the editor font
         let font_family = if self.content.is_input() {
@@ -2399,6 +3262,79 @@ pub fn line_horiz_col(
         }
     }
 
+    /// Move the offset `count` visual lines up or down, when the lines are soft wrapped.
+    /// The horizontal position is relative to the start of the visual line, so that
+    /// the cursor keeps its place on screen while moving through a wrapped line.
+    #[allow(clippy::too_many_arguments)]
+    fn move_visual_line(
+        &self,
+        text: &mut PietText,
+        visual_lines: &VisualLines,
+        offset: usize,
+        horiz: Option<&ColPosition>,
+        count: usize,
+        up: bool,
+        mode: Mode,
+        config: &LapceConfig,
+    ) -> (usize, Option<ColPosition>) {
+        let (line, col) = self.buffer.offset_to_line_col(offset);
+        let font_size = config.editor.font_size;
+        let caret = mode != Mode::Normal;
+        let mut horiz = horiz.cloned();
+        let movement = visual_lines.move_visual_line(
+            &self.buffer,
+            line,
+            col,
+            count,
+            up,
+            caret,
+            |visual_line| {
+                let horiz = horiz.get_or_insert_with(|| {
+                    let current = visual_lines.visual_line(
+                        visual_lines.visual_line_of_line_col(line, col),
+                    );
+                    ColPosition::Col(
+                        self.line_point_of_offset(text, offset, font_size, config).x
+                            + self.visual_line_x_shift(
+                                text, &current, font_size, config,
+                            ),
+                    )
+                });
+                let line_horiz = match horiz {
+                    ColPosition::Col(x) => ColPosition::Col(
+                        *x - self.visual_line_x_shift(
+                            text,
+                            visual_line,
+                            font_size,
+                            config,
+                        ),
+                    ),
+                    horiz => horiz.clone(),
+                };
+                self.line_horiz_col(
+                    text,
+                    visual_line.line,
+                    font_size,
+                    &line_horiz,
+                    caret,
+                    config,
+                )
+            },
+        );
+        match movement {
+            VisualMove::LineStart => {
+                (self.buffer.offset_of_line(line), Some(ColPosition::Start))
+            }
+            VisualMove::LineEnd => (
+                self.buffer.offset_line_end(offset, caret),
+                Some(ColPosition::End),
+            ),
+            VisualMove::LineCol(line, col) => {
+                (self.buffer.offset_of_line_col(line, col), horiz)
+            }
+        }
+    }
+
     /// Move a selection region by a given movement.  
     /// Much of the time, this will just be a matter of moving the cursor, but
     /// some movements may depend on the current selection.
@@ -2412,6 +3348,7 @@ fn move_region(
         movement: &Movement,
         mode: Mode,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> SelRegion {
         let (count, region) = if count >= 1 && !modify && !region.is_caret() {
@@ -2446,6 +3383,7 @@ fn move_region(
             movement,
             mode,
             view,
+            wrap_width,
             config,
         );
         let start = match modify {
@@ -2464,6 +3402,7 @@ pub fn move_cursor(
         count: usize,
         modify: bool,
         view: &EditorView,
+        wrap_width: Option<f64>,
         register: &mut Register,
         config: &LapceConfig,
     ) {
@@ -2477,6 +3416,7 @@ pub fn move_cursor(
                     movement,
                     Mode::Normal,
                     view,
+                    wrap_width,
                     config,
                 );
                 if let Some(motion_mode) = cursor.motion_mode.clone() {
@@ -2488,6 +3428,7 @@ pub fn move_cursor(
                         &Movement::Right,
                         Mode::Insert,
                         view,
+                        wrap_width,
                         config,
                     );
                     let (start, end) = match movement {
@@ -2528,6 +3469,7 @@ pub fn move_cursor(
                     movement,
                     Mode::Visual,
                     view,
+                    wrap_width,
                     config,
                 );
                 cursor.mode = CursorMode::Visual {
@@ -2547,6 +3489,7 @@ pub fn move_cursor(
                     movement,
                     Mode::Insert,
                     view,
+                    wrap_width,
                     config,
                 );
                 cursor.set_insert(selection);
@@ -2565,12 +3508,14 @@ pub fn move_selection(
         movement: &Movement,
         mode: Mode,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> Selection {
         let mut new_selection = Selection::new();
         for region in selection.regions() {
             new_selection.add_region(self.move_region(
-                text, region, count, modify, movement, mode, view, config,
+                text, region, count, modify, movement, mode, view, wrap_width,
+                config,
             ));
         }
         new_selection
@@ -2586,18 +3531,42 @@ pub fn move_offset(
         movement: &Movement,
         mode: Mode,
         view: &EditorView,
+        wrap_width: Option<f64>,
         config: &LapceConfig,
     ) -> (usize, Option<ColPosition>) {
+        match movement {
+            Movement::Up | Movement::Down if view.is_normal() => {
+                if let Some(visual_lines) =
+                    self.visual_lines(text, wrap_width, config)
+                {
+                    return self.move_visual_line(
+                        text,
+                        &visual_lines,
+                        offset,
+                        horiz,
+                        count,
+                        matches!(movement, Movement::Up),
+                        mode,
+                        config,
+                    );
+                }
+            }
+            _ => {}
+        }
+
         match movement {
             Movement::Left => {
                 let mut new_offset = self.buffer.move_left(offset, mode, count);
 
-                if config.editor.atomic_soft_tabs && config.editor.tab_width > 1 {
+                if config.editor.atomic_soft_tabs
+                    && config.editor.atomic_soft_tabs_arrows
+                    && config.tab_width_for(self.language()) > 1
+                {
                     new_offset = snap_to_soft_tab(
                         &self.buffer,
                         new_offset,
                         SnapDirection::Left,
-                        config.editor.tab_width,
+                        config.tab_width_for(self.language()),
                     );
                 }
 
@@ -2606,12 +3575,15 @@ pub fn move_offset(
             Movement::Right => {
                 let mut new_offset = self.buffer.move_right(offset, mode, count);
 
-                if config.editor.atomic_soft_tabs && config.editor.tab_width > 1 {
+                if config.editor.atomic_soft_tabs
+                    && config.editor.atomic_soft_tabs_arrows
+                    && config.tab_width_for(self.language()) > 1
+                {
                     new_offset = snap_to_soft_tab(
                         &self.buffer,
                         new_offset,
                         SnapDirection::Right,
-                        config.editor.tab_width,
+                        config.tab_width_for(self.language()),
                     );
                 }
 
@@ -2853,6 +3825,28 @@ pub fn move_offset(
                     .unwrap();
                 (new_offset, None)
             }
+            Movement::WordPartRight => {
+                let new_offset = self.buffer.move_n_word_parts_right(offset, count);
+                (new_offset, None)
+            }
+            Movement::WordPartLeft => {
+                let new_offset =
+                    self.buffer.move_n_word_parts_left(offset, count, mode);
+                (new_offset, None)
+            }
+            Movement::WordEndForward
+                if mode == Mode::Insert && config.editor.subword_navigation =>
+            {
+                let new_offset = self.buffer.move_n_word_parts_right(offset, count);
+                (new_offset, None)
+            }
+            Movement::WordBackward
+                if mode == Mode::Insert && config.editor.subword_navigation =>
+            {
+                let new_offset =
+                    self.buffer.move_n_word_parts_left(offset, count, mode);
+                (new_offset, None)
+            }
             Movement::WordEndForward => {
                 let new_offset = self.buffer.move_n_wordends_forward(
                     offset,
@@ -2896,6 +3890,14 @@ pub fn move_offset(
                     (new_offset, None)
                 }
             }
+            Movement::MatchingBracket => {
+                let new_offset = self
+                    .buffer
+                    .find_matching_bracket(self.syntax(), offset)
+                    .map(|(_, matching)| matching)
+                    .unwrap_or(offset);
+                (new_offset, None)
+            }
             Movement::MatchPairs => {
                 if let Some(syntax) = self.syntax.as_ref() {
                     let new_offset =
@@ -2964,25 +3966,9 @@ pub fn code_action_size(
     }
 
     pub fn reset_find(&self, current_find: &Find) {
-        {
-            let find = self.find.borrow();
-            if find.search_string == current_find.search_string
-                && find.case_matching == current_find.case_matching
-                && find.regex.as_ref().map(|r| r.as_str())
-                    == current_find.regex.as_ref().map(|r| r.as_str())
-                && find.whole_words == current_find.whole_words
-            {
-                return;
-            }
+        if self.find.borrow_mut().take_query(current_find) {
+            *self.find_progress.borrow_mut() = FindProgress::Started;
         }
-
-        let mut find = self.find.borrow_mut();
-        find.unset();
-        find.search_string = current_find.search_string.clone();
-        find.case_matching = current_find.case_matching;
-        find.regex = current_find.regex.clone();
-        find.whole_words = current_find.whole_words;
-        *self.find_progress.borrow_mut() = FindProgress::Started;
     }
 
     pub fn update_find(
@@ -3057,11 +4043,44 @@ pub fn update_find(
         }
     }
 
+    /// The number of matches of the find, and the index of the one at the
+    /// offset. It's estimated while only parts of the document were searched,
+    /// like the visible lines of a large file.
+    pub fn find_count(&self, offset: usize) -> FindCount {
+        let find = self.find.borrow();
+        if let FindProgress::InProgress(searched) = &*self.find_progress.borrow() {
+            let searched_len = searched
+                .regions()
+                .iter()
+                .map(|region| region.max() - region.min())
+                .sum::<usize>();
+            if searched_len < self.buffer.len() {
+                return FindCount::estimate(
+                    find.occurrences().len(),
+                    searched_len,
+                    self.buffer.len(),
+                );
+            }
+        }
+        find.count(offset)
+    }
+
     /// Get the sticky headers for a particular line, creating them if necessary.
+    /// They come from the document symbols when the LSP provides them, otherwise
+    /// from the syntax tree.
     pub fn sticky_headers(&self, line: usize) -> Option<Vec<usize>> {
         if let Some(lines) = self.sticky_headers.borrow().get(&line) {
             return lines.clone();
         }
+        if let Some(scopes) = self
+            .symbol_scopes
+            .as_ref()
+            .filter(|scopes| !scopes.is_empty())
+        {
+            let lines = Some(scopes.headers(line));
+            self.sticky_headers.borrow_mut().insert(line, lines.clone());
+            return lines;
+        }
         let offset = self.buffer.offset_of_line(line + 1);
         let lines = self.syntax.as_ref()?.sticky_headers(offset).map(|offsets| {
             offsets
@@ -3082,6 +4101,17 @@ pub fn sticky_headers(&self, line: usize) -> Option<Vec<usize>> {
         lines
     }
 
+    /// Where the symbol of a sticky header line is, to jump to it
+    pub fn sticky_header_position(&self, line: usize) -> Position {
+        self.symbol_scopes
+            .as_ref()
+            .and_then(|scopes| scopes.position_of_line(line))
+            .unwrap_or_else(|| {
+                let offset = self.buffer.first_non_blank_character_on_line(line);
+                self.buffer.offset_to_position(offset)
+            })
+    }
+
     pub fn change_syntax_selection(
         &mut self,
         direction: SelectionRangeDirection,
@@ -3103,30 +4133,246 @@ pub fn change_syntax_selection(
     }
 
     pub fn find_enclosing_brackets(&self, offset: usize) -> Option<(usize, usize)> {
-        let char_at_cursor = match self.buffer().char_at_offset(offset) {
-            Some(c) => c,
-            None => return None,
-        };
-
-        if let Some(syntax) = self.syntax() {
-            if matching_pair_direction(char_at_cursor).is_some() {
-                if let Some(new_offset) = syntax.find_matching_pair(offset) {
-                    if offset > new_offset {
-                        return Some((new_offset, offset));
-                    }
-                    return Some((offset, new_offset));
-                }
-            } else {
-                return syntax.find_enclosing_pair(offset);
-            }
+        if let Some((bracket, matching)) =
+            self.buffer.find_matching_bracket(self.syntax(), offset)
+        {
+            return Some((bracket.min(matching), bracket.max(matching)));
         }
 
-        let mut cursor = WordCursor::new(self.buffer.text(), offset);
+        let char_at_cursor = self.buffer().char_at_offset(offset)?;
+        // An unbalanced bracket isn't highlighted
         if matching_pair_direction(char_at_cursor).is_some() {
-            let new_offset = cursor.match_pairs().unwrap_or(offset);
-            Some((offset, new_offset))
-        } else {
-            cursor.find_enclosing_pair()
+            return None;
         }
+
+        if let Some(syntax) = self.syntax() {
+            return syntax.find_enclosing_pair(offset);
+        }
+        WordCursor::new(self.buffer.text(), offset).find_enclosing_pair()
+    }
+}
+
+/// The lines whose inlay hints are fetched until an editor shows others
+const INLAY_HINT_LINES: usize = 100;
+
+/// The lines to fetch for the visible lines, if they aren't all in the lines
+/// fetched already. As many lines above and below them are fetched too, so that
+/// scrolling a bit doesn't need another request.
+fn lines_to_fetch(
+    fetched: &std::ops::Range<usize>,
+    visible: std::ops::Range<usize>,
+    num_lines: usize,
+) -> Option<std::ops::Range<usize>> {
+    let visible = visible.start.min(num_lines)..visible.end.min(num_lines);
+    if fetched.start <= visible.start && visible.end <= fetched.end {
+        return None;
+    }
+    let margin = visible.len();
+    Some(visible.start.saturating_sub(margin)..(visible.end + margin).min(num_lines))
+}
+
+/// Place the inlay hints at their positions in the buffer, ordered by position.
+/// The hints past the end of the buffer are placed at its end.
+fn inlay_hint_spans(buffer: &Buffer, mut hints: Vec<InlayHint>) -> Spans<InlayHint> {
+    // Sort the inlay hints by their position, as the LSP does not guarantee that
+    // it will provide them in the order that they are in within the file
+    // as well, Spans does not iterate in the order that they appear
+    hints.sort_by(|left, right| left.position.cmp(&right.position));
+
+    let len = buffer.len();
+    let mut hints_span = SpansBuilder::new(len);
+    for hint in hints {
+        let offset = buffer.offset_of_position(&hint.position).min(len);
+        hints_span.add_span(Interval::new(offset, (offset + 1).min(len)), hint);
+    }
+    hints_span.build()
+}
+
+/// The syntax highlighting a document with the content gets, which only files get on
+/// their own, unless they are in large file mode
+fn content_syntax(
+    content: &BufferContent,
+    large_file: bool,
+) -> Result<Syntax, HighlightIssue> {
+    match content {
+        BufferContent::File(path) if !large_file => Syntax::init(path),
+        _ => Err(HighlightIssue::NotAvailable),
+    }
+}
+
+/// Whether the lines of a document are soft wrapped or folded. The ones of a file in
+/// large file mode never are, since each of its lines would have to be measured.
+fn has_visual_lines(
+    large_file: bool,
+    wrap_width: Option<f64>,
+    folds: &Folds,
+) -> bool {
+    !large_file && (wrap_width.is_some() || !folds.is_empty())
+}
+
+/// The text of an inlay hint, with the spaces the server asks for around it
+fn inlay_hint_text(hint: &InlayHint) -> String {
+    let label = match &hint.label {
+        InlayHintLabel::String(label) => label.to_string(),
+        InlayHintLabel::LabelParts(parts) => parts.iter().map(|p| &p.value).join(""),
+    };
+    let left = if hint.padding_left == Some(true) {
+        " "
+    } else {
+        ""
+    };
+    let right = if hint.padding_right == Some(true) {
+        " "
+    } else {
+        ""
+    };
+    format!("{left}{label}{right}")
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use druid::{Target, WidgetId};
+    use lapce_core::{
+        buffer::Buffer,
+        fold::{FoldRange, Folds},
+        syntax::{highlight::HighlightIssue, Syntax},
+    };
+    use lsp_types::{InlayHint, InlayHintKind};
+    use serde_json::json;
+
+    use super::{
+        content_syntax, disk_change_alert, has_visual_lines, inlay_hint_spans,
+        inlay_hint_text, lines_to_fetch, BufferContent, LocalBufferKind,
+    };
+    use crate::{alert::AlertData, command::LAPCE_COMMAND, config::EditorConfig};
+
+    #[test]
+    fn test_disk_change_alert() {
+        let tab_id = WidgetId::next();
+        let view_id = WidgetId::next();
+        let path = Path::new("/src/main.rs");
+
+        let mut alert = AlertData::new();
+        alert.push(disk_change_alert(tab_id, path, false, None));
+        assert_eq!(alert.content.title, "main.rs changed on disk");
+        let reload = alert.button_command(0).unwrap();
+        assert_eq!(reload.target(), Target::Widget(tab_id));
+        let reload = reload.get(LAPCE_COMMAND).unwrap();
+        assert_eq!(reload.kind.str(), "reload_from_disk");
+        let reload_path: PathBuf =
+            serde_json::from_value(reload.data.clone().unwrap()).unwrap();
+        assert_eq!(reload_path, path);
+        let keep = alert.button_command(1).unwrap();
+        assert_eq!(
+            keep.get(LAPCE_COMMAND).unwrap().kind.str(),
+            "keep_local_version"
+        );
+
+        // A deleted file is saved again from its editor
+        alert.push(disk_change_alert(tab_id, path, true, Some(view_id)));
+        assert!(alert.dismiss());
+        assert_eq!(alert.content.title, "main.rs was deleted on disk");
+        let save = alert.button_command(0).unwrap();
+        assert_eq!(save.target(), Target::Widget(view_id));
+        assert_eq!(save.get(LAPCE_COMMAND).unwrap().kind.str(), "save");
+        assert!(alert.button_command(1).is_none());
+
+        let deleted = disk_change_alert(tab_id, path, true, None);
+        assert!(deleted.buttons.is_empty());
+    }
+
+    #[test]
+    fn test_inlay_hint_lines_to_fetch() {
+        // The lines shown were fetched already
+        assert_eq!(lines_to_fetch(&(0..100), 20..60, 500), None);
+        // Scrolling past them fetches a screen of lines around the ones shown
+        assert_eq!(lines_to_fetch(&(0..100), 90..130, 500), Some(50..170));
+        assert_eq!(lines_to_fetch(&(50..170), 10..50, 500), Some(0..90));
+        // without going past the end of the file
+        assert_eq!(lines_to_fetch(&(0..100), 460..520, 500), Some(420..500));
+        assert_eq!(lines_to_fetch(&(420..500), 470..520, 500), None);
+    }
+
+    #[test]
+    fn test_inlay_hint_placement() {
+        let buffer = Buffer::new("let a = f(1, 2);\nlet b = a;\n");
+        let hint = |line: u32, character: u32, label: &str, kind: u8| {
+            serde_json::from_value::<InlayHint>(json!({
+                "position": { "line": line, "character": character },
+                "label": label,
+                "kind": kind,
+                "paddingLeft": kind == 1,
+                "paddingRight": kind == 2,
+            }))
+            .unwrap()
+        };
+        // The server doesn't send them in order
+        let hints = vec![
+            hint(1, 5, ": i32", 1),
+            hint(0, 13, "y:", 2),
+            hint(0, 5, ": i32", 1),
+            hint(0, 10, "x:", 2),
+        ];
+        let spans = inlay_hint_spans(&buffer, hints);
+        let mut placed: Vec<(usize, String)> = spans
+            .iter()
+            .map(|(interval, hint)| (interval.start, inlay_hint_text(hint)))
+            .collect();
+        placed.sort();
+        assert_eq!(
+            placed,
+            vec![
+                (5, " : i32".to_string()),
+                (10, "x: ".to_string()),
+                (13, "y: ".to_string()),
+                (22, " : i32".to_string()),
+            ]
+        );
+
+        let label_parts: InlayHint = serde_json::from_value(json!({
+            "position": { "line": 0, "character": 5 },
+            "label": [{ "value": ": Vec<" }, { "value": "u8" }, { "value": ">" }],
+        }))
+        .unwrap();
+        assert_eq!(inlay_hint_text(&label_parts), ": Vec<u8>");
+
+        let config = EditorConfig {
+            enable_inlay_hints: true,
+            inlay_hint_types: false,
+            inlay_hint_parameters: true,
+            ..Default::default()
+        };
+        assert!(!config.inlay_hint_kind_enabled(Some(InlayHintKind::TYPE)));
+        assert!(config.inlay_hint_kind_enabled(Some(InlayHintKind::PARAMETER)));
+        assert!(config.inlay_hint_kind_enabled(None));
+    }
+
+    #[test]
+    fn test_large_file_mode() {
+        let path = Path::new("/src/main.rs");
+        let file = BufferContent::File(path.to_path_buf());
+        // Large files aren't highlighted, whatever their language
+        assert_eq!(
+            content_syntax(&file, true).err(),
+            Some(HighlightIssue::NotAvailable)
+        );
+        assert_eq!(
+            content_syntax(&file, false).is_ok(),
+            Syntax::init(path).is_ok()
+        );
+        assert_eq!(
+            content_syntax(&BufferContent::Local(LocalBufferKind::Search), false)
+                .err(),
+            Some(HighlightIssue::NotAvailable)
+        );
+
+        // Nor are they wrapped or folded
+        let folds = Folds::new([FoldRange::new(0, 2)]);
+        assert!(has_visual_lines(false, Some(80.0), &Folds::default()));
+        assert!(has_visual_lines(false, None, &folds));
+        assert!(!has_visual_lines(false, None, &Folds::default()));
+        assert!(!has_visual_lines(true, Some(80.0), &folds));
     }
 }
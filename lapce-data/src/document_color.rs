@@ -0,0 +1,394 @@
+use std::path::{Path, PathBuf};
+
+use druid::{Color, ExtEventSink, Point, Target};
+use lapce_core::buffer::Buffer;
+use lapce_rpc::proxy::ProxyResponse;
+use lapce_xi_rope::{
+    spans::{Spans, SpansBuilder},
+    Interval,
+};
+use lsp_types::{ColorInformation, ColorPresentation, Range, TextEdit};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
+    menu::{escape_mnemonic, MenuItem, MenuKind},
+    proxy::LapceProxy,
+};
+
+/// The phantom text painted in the color before each color of a document
+pub const COLOR_SWATCH: &str = "■ ";
+
+/// Place the colors the LSP found in the document at their ranges in the
+/// buffer. The colors overlapping an earlier one are left out.
+pub fn document_color_spans(
+    buffer: &Buffer,
+    mut colors: Vec<ColorInformation>,
+) -> Spans<lsp_types::Color> {
+    // The LSP does not guarantee that it will provide them in the order that
+    // they are in within the file
+    colors.sort_by(|left, right| left.range.start.cmp(&right.range.start));
+
+    let len = buffer.len();
+    let mut spans = SpansBuilder::new(len);
+    let mut last_end = 0;
+    for info in colors {
+        let start = buffer.offset_of_position(&info.range.start).min(len);
+        let end = buffer.offset_of_position(&info.range.end).min(len);
+        if start < last_end || start >= end {
+            continue;
+        }
+        spans.add_span(Interval::new(start, end), info.color);
+        last_end = end;
+    }
+    spans.build()
+}
+
+/// The color of the swatch of a color from the LSP, whose components go from
+/// 0 to 1
+pub fn swatch_color(color: &lsp_types::Color) -> Color {
+    Color::rgba(
+        color.red as f64,
+        color.green as f64,
+        color.blue as f64,
+        color.alpha as f64,
+    )
+}
+
+/// The edits writing a color the way of the presentation, which replace the
+/// color at the range with the label when the presentation has no edit
+pub fn presentation_edits(
+    range: Range,
+    presentation: &ColorPresentation,
+) -> Vec<TextEdit> {
+    let edit = presentation.text_edit.clone().unwrap_or_else(|| TextEdit {
+        range,
+        new_text: presentation.label.clone(),
+    });
+    std::iter::once(edit)
+        .chain(presentation.additional_text_edits.iter().flatten().cloned())
+        .collect()
+}
+
+/// The edits of the presentation of a color chosen in its menu, for the
+/// command applying them to the file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ColorPresentationEdit {
+    pub path: PathBuf,
+    pub edits: Vec<TextEdit>,
+}
+
+/// The menu of the ways the LSP can write the color at the range of the file,
+/// like `#ff0000`, `rgb(255, 0, 0)` or `hsl(0, 100%, 50%)`
+pub fn color_presentation_menu(
+    path: &Path,
+    range: Range,
+    presentations: &[ColorPresentation],
+) -> Vec<MenuKind> {
+    let command = |edits: Option<ColorPresentationEdit>| LapceCommand {
+        kind: CommandKind::Workbench(LapceWorkbenchCommand::ApplyColorPresentation),
+        data: edits.and_then(|edits| serde_json::to_value(edits).ok()),
+    };
+    if presentations.is_empty() {
+        return vec![MenuKind::Item(MenuItem {
+            desc: Some("No Color Presentations Available".to_string()),
+            command: command(None),
+            enabled: false,
+        })];
+    }
+
+    presentations
+        .iter()
+        .map(|presentation| {
+            let edits = ColorPresentationEdit {
+                path: path.to_path_buf(),
+                edits: presentation_edits(range, presentation),
+            };
+            MenuKind::Item(MenuItem {
+                desc: Some(escape_mnemonic(&presentation.label)),
+                command: command(Some(edits)),
+                enabled: true,
+            })
+        })
+        .collect()
+}
+
+/// The color picker opened by clicking the swatch of a document color. Once a
+/// color is picked, the ways the LSP can write it are shown in a menu.
+#[derive(Clone)]
+pub struct ColorPickerData {
+    pub active: bool,
+    /// Where the swatch was clicked, in the window
+    pub point: Point,
+    pub path: PathBuf,
+    /// The range of the color in the file
+    pub range: Range,
+    /// The picked color, which is kept as HSV for the hue not to be lost when
+    /// the color is gray
+    pub hsv: Hsv,
+    /// The alpha of the color, which is kept as it is
+    pub alpha: f32,
+}
+
+impl ColorPickerData {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            point: Point::ZERO,
+            path: PathBuf::new(),
+            range: Range::default(),
+            hsv: Hsv::default(),
+            alpha: 1.0,
+        }
+    }
+
+    /// Open the picker with the color at the range of the file
+    pub fn open(
+        &mut self,
+        point: Point,
+        path: PathBuf,
+        range: Range,
+        color: &lsp_types::Color,
+    ) {
+        self.active = true;
+        self.point = point;
+        self.path = path;
+        self.range = range;
+        self.hsv = Hsv::from_color(color);
+        self.alpha = color.alpha;
+    }
+
+    /// The picked color, as the LSP takes it
+    pub fn color(&self) -> lsp_types::Color {
+        self.hsv.to_color(self.alpha)
+    }
+
+    /// Ask the LSP for the ways to write the picked color, to show them in a
+    /// menu where the swatch was clicked, and close the picker
+    pub fn request_presentations(
+        &mut self,
+        event_sink: ExtEventSink,
+        proxy: &LapceProxy,
+    ) {
+        self.active = false;
+        let point = self.point;
+        let path = self.path.clone();
+        let range = self.range;
+        let tab_id = proxy.tab_id;
+        proxy.proxy_rpc.get_color_presentations(
+            path.clone(),
+            self.color(),
+            range,
+            move |result| {
+                if let Ok(ProxyResponse::GetColorPresentations { presentations }) =
+                    result
+                {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ShowColorPresentations {
+                            point,
+                            path,
+                            range,
+                            presentations,
+                        },
+                        Target::Widget(tab_id),
+                    );
+                }
+            },
+        );
+    }
+}
+
+impl Default for ColorPickerData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A color as its hue, from 0 to 360, and its saturation and value, from 0
+/// to 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hsv {
+    pub hue: f64,
+    pub saturation: f64,
+    pub value: f64,
+}
+
+impl Hsv {
+    pub fn from_color(color: &lsp_types::Color) -> Self {
+        let (r, g, b) = (color.red as f64, color.green as f64, color.blue as f64);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        Self {
+            hue,
+            saturation: if max == 0.0 { 0.0 } else { delta / max },
+            value: max,
+        }
+    }
+
+    pub fn to_color(self, alpha: f32) -> lsp_types::Color {
+        let chroma = self.value * self.saturation;
+        let sector = (self.hue / 60.0).rem_euclid(6.0);
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = self.value - chroma;
+        lsp_types::Color {
+            red: (r + m) as f32,
+            green: (g + m) as f32,
+            blue: (b + m) as f32,
+            alpha,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use lapce_core::{buffer::Buffer, editor::EditType, selection::Selection};
+    use lsp_types::{ColorInformation, ColorPresentation, Position, Range};
+    use serde_json::json;
+
+    use super::{
+        color_presentation_menu, document_color_spans, presentation_edits,
+        ColorPresentationEdit, Hsv,
+    };
+    use crate::menu::MenuKind;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn color(range: Range, red: f32) -> ColorInformation {
+        serde_json::from_value(json!({
+            "range": range,
+            "color": { "red": red, "green": 0.0, "blue": 0.0, "alpha": 1.0 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_swatch_placement() {
+        let buffer = Buffer::new("a { color: #f00; }\nb { color: red; }\n");
+        // The server doesn't send them in order, and the last one overlaps
+        let colors = vec![
+            color(range(1, 11, 14), 0.5),
+            color(range(0, 11, 15), 1.0),
+            color(range(0, 12, 14), 0.0),
+        ];
+        let spans = document_color_spans(&buffer, colors);
+        let placed: Vec<(usize, usize, f32)> = spans
+            .iter()
+            .map(|(interval, color)| (interval.start, interval.end, color.red))
+            .collect();
+        assert_eq!(placed, vec![(11, 15, 1.0), (30, 33, 0.5)]);
+    }
+
+    #[test]
+    fn test_apply_presentation() {
+        let buffer = Buffer::new("a { color: #f00; }\n");
+        let range = range(0, 11, 15);
+        let presentations: Vec<ColorPresentation> = serde_json::from_value(json!([
+            { "label": "rgb(255, 0, 0)" },
+            {
+                "label": "hsl(0, 100%, 50%)",
+                "textEdit": {
+                    "range": range,
+                    "newText": "hsl(0deg 100% 50%)",
+                },
+                "additionalTextEdits": [{
+                    "range": { "start": { "line": 0, "character": 0 },
+                               "end": { "line": 0, "character": 0 } },
+                    "newText": "/* hsl */ ",
+                }],
+            },
+        ]))
+        .unwrap();
+
+        let apply = |buffer: &Buffer, presentation| {
+            let mut buffer = buffer.clone();
+            let edits = presentation_edits(range, presentation);
+            let edits: Vec<(Selection, &str)> = edits
+                .iter()
+                .map(|edit| {
+                    let selection = Selection::region(
+                        buffer.offset_of_position(&edit.range.start),
+                        buffer.offset_of_position(&edit.range.end),
+                    );
+                    (selection, edit.new_text.as_str())
+                })
+                .collect();
+            buffer.edit(&edits, EditType::Other);
+            buffer.text().to_string()
+        };
+        assert_eq!(
+            apply(&buffer, &presentations[0]),
+            "a { color: rgb(255, 0, 0); }\n"
+        );
+        assert_eq!(
+            apply(&buffer, &presentations[1]),
+            "/* hsl */ a { color: hsl(0deg 100% 50%); }\n"
+        );
+
+        let menu =
+            color_presentation_menu(Path::new("/a.css"), range, &presentations);
+        let MenuKind::Item(item) = &menu[0] else {
+            panic!("not an item");
+        };
+        assert_eq!(item.desc(), "rgb(255, 0, 0)");
+        let edit: ColorPresentationEdit =
+            serde_json::from_value(item.command.data.clone().unwrap()).unwrap();
+        assert_eq!(edit.path, Path::new("/a.css"));
+        assert_eq!(edit.edits[0].new_text, "rgb(255, 0, 0)");
+    }
+
+    #[test]
+    fn test_hsv() {
+        let rgb = |red, green, blue| lsp_types::Color {
+            red,
+            green,
+            blue,
+            alpha: 0.5,
+        };
+        let hsv = Hsv::from_color(&rgb(1.0, 0.5, 0.0));
+        assert_eq!(hsv.hue, 30.0);
+        assert_eq!(hsv.saturation, 1.0);
+        assert_eq!(hsv.value, 1.0);
+        assert_eq!(hsv.to_color(0.5), rgb(1.0, 0.5, 0.0));
+
+        let hsv = Hsv::from_color(&rgb(0.2, 0.4, 0.8));
+        assert_eq!(hsv.hue.round(), 220.0);
+        let color = hsv.to_color(0.5);
+        assert!((color.red - 0.2).abs() < 1e-6);
+        assert!((color.green - 0.4).abs() < 1e-6);
+        assert!((color.blue - 0.8).abs() < 1e-6);
+
+        // Gray, whatever the hue
+        let gray = Hsv {
+            hue: 120.0,
+            saturation: 0.0,
+            value: 0.5,
+        };
+        assert_eq!(gray.to_color(1.0).red, 0.5);
+        assert_eq!(gray.to_color(1.0).green, 0.5);
+    }
+}
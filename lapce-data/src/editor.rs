@@ -9,8 +9,7 @@
     time::Duration,
 };
 
-use anyhow::{anyhow, Result};
-use crossbeam_channel::{self, bounded};
+use anyhow::Result;
 use druid::{
     piet::{PietText, PietTextLayout, Svg},
     Color, Command, Env, EventCtx, ExtEventSink, FileDialogOptions, Modifiers,
@@ -19,45 +18,59 @@
 use indexmap::IndexMap;
 pub use lapce_core::syntax::Syntax;
 use lapce_core::{
-    buffer::{Buffer, DiffLines, InvalLines},
-    command::{EditCommand, FocusCommand, MotionModeCommand, MultiSelectionCommand},
+    buffer::{change_line, Buffer, DiffLines, InvalLines},
+    command::{
+        EditCommand, FocusCommand, FoldCommand, MotionModeCommand,
+        MultiSelectionCommand, SelectionRangeCommand, SignatureCommand,
+        SourceControlCommand,
+    },
+    cursor::CursorMode,
     editor::EditType,
-    mode::{Mode, MotionMode},
+    language::LapceLanguage,
+    mode::{Mode, MotionMode, VisualMode},
     selection::{InsertDrift, Selection},
     syntax::edit::SyntaxEdit,
+    whitespace::WhitespaceCleanup,
+    wrap::VisualLines,
 };
-use lapce_rpc::{plugin::PluginId, proxy::ProxyResponse};
+use lapce_rpc::{buffer::BufferId, plugin::PluginId, proxy::ProxyResponse};
 use lapce_xi_rope::{Rope, RopeDelta, Transformer};
 use lsp_types::{
     request::GotoTypeDefinitionResponse, CodeAction, CodeActionOrCommand,
     CodeActionResponse, CompletionItem, CompletionTextEdit, DiagnosticSeverity,
-    DocumentChangeOperation, DocumentChanges, GotoDefinitionResponse, Location,
-    OneOf, Position, ResourceOp, TextEdit, Url, WorkspaceEdit,
+    DocumentChangeOperation, DocumentChanges, FoldingRange, GotoDefinitionResponse,
+    Location, OneOf, Position, Range, ResourceOp, SelectionRange, TextEdit, Url,
+    WorkspaceEdit,
 };
 
 use crate::{
+    atomic_soft_tabs::select_soft_tab_backspace,
     command::{
         CommandExecuted, CommandKind, EnsureVisiblePosition, InitBufferContent,
         InitBufferContentCb, LapceCommand, LapceUICommand, LAPCE_COMMAND,
         LAPCE_SAVE_FILE_AS, LAPCE_UI_COMMAND,
     },
-    completion::{CompletionData, CompletionStatus, Snippet},
+    completion::{ActiveSnippet, CompletionData, CompletionStatus, Snippet},
     config::LapceConfig,
     data::{
         EditorDiagnostic, EditorView, FocusArea, InlineFindDirection,
         LapceEditorData, LapceMainSplitData, SplitContent,
     },
-    document::{BufferContent, Document, LocalBufferKind},
+    document::{BufferContent, Document, DocumentBlame, LocalBufferKind},
     find::Find,
+    format,
     hover::{HoverData, HoverStatus},
-    keypress::{KeyMap, KeyPressFocus},
+    keypress::{KeyMap, KeyPressFocus, LANGUAGE_CONTEXT},
     palette::PaletteData,
-    proxy::{path_from_url, LapceProxy},
+    peek::{peek_entries, PeekData},
+    picker::{FilePickerData, PickerKind},
+    problem::next_diagnostic,
+    proxy::{path_from_url, LapceProxy, WorkspaceEditReply},
     rename::RenameData,
-    selection_range::SelectionRangeDirection,
+    selection_range::{selection_range_offsets, SelectionRangeDirection},
     signature::{SignatureData, SignatureStatus},
     source_control::SourceControlData,
-    split::{SplitDirection, SplitMoveDirection},
+    split::{SplitCommand, SplitDirection, SplitMoveDirection, SPLIT_RESIZE_STEP},
 };
 
 pub struct LapceUI {}
@@ -78,6 +91,8 @@ pub trait EditorPosition: Sized {
     fn init_buffer_content_cmd(
         path: PathBuf,
         content: Rope,
+        encoding: String,
+        large_file: bool,
         locations: Vec<(WidgetId, EditorLocation<Self>)>,
         edits: Option<Rope>,
         cb: Option<InitBufferContentCb>,
@@ -93,6 +108,8 @@ fn to_utf8_offset(&self, _buffer: &Buffer) -> usize {
     fn init_buffer_content_cmd(
         path: PathBuf,
         content: Rope,
+        encoding: String,
+        large_file: bool,
         locations: Vec<(WidgetId, EditorLocation<Self>)>,
         unsaved_buffers: Option<Rope>,
         cb: Option<InitBufferContentCb>,
@@ -100,6 +117,8 @@ fn init_buffer_content_cmd(
         LapceUICommand::InitBufferContent(InitBufferContent {
             path,
             content,
+            encoding,
+            large_file,
             locations,
             edits: unsaved_buffers,
             cb,
@@ -120,6 +139,8 @@ fn to_utf8_offset(&self, buffer: &Buffer) -> usize {
     fn init_buffer_content_cmd(
         path: PathBuf,
         content: Rope,
+        encoding: String,
+        large_file: bool,
         locations: Vec<(WidgetId, EditorLocation<Self>)>,
         edits: Option<Rope>,
         cb: Option<InitBufferContentCb>,
@@ -127,6 +148,8 @@ fn init_buffer_content_cmd(
         LapceUICommand::InitBufferContentLine(InitBufferContent {
             path,
             content,
+            encoding,
+            large_file,
             locations,
             edits,
             cb,
@@ -135,20 +158,37 @@ fn init_buffer_content_cmd(
 }
 
 /// UTF8 line and column-offset
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LineCol {
     pub line: usize,
     pub column: usize,
 }
 
+impl LineCol {
+    /// Get the line and utf8 column of `offset` in the buffer.  
+    /// Offsets past the end of the buffer are clamped to the end.
+    pub fn from_offset(buffer: &Buffer, offset: usize) -> LineCol {
+        let (line, column) = buffer.offset_to_line_col(offset);
+        LineCol { line, column }
+    }
+
+    /// Get the utf8 offset of this position in the buffer.  
+    /// A column past the end of the line is clamped to the line ending.
+    pub fn to_offset(&self, buffer: &Buffer) -> usize {
+        buffer.offset_of_line_col(self.line, self.column)
+    }
+}
+
 impl EditorPosition for LineCol {
     fn to_utf8_offset(&self, buffer: &Buffer) -> usize {
-        buffer.offset_of_line_col(self.line, self.column)
+        self.to_offset(buffer)
     }
 
     fn init_buffer_content_cmd(
         path: PathBuf,
         content: Rope,
+        encoding: String,
+        large_file: bool,
         locations: Vec<(WidgetId, EditorLocation<Self>)>,
         edits: Option<Rope>,
         cb: Option<InitBufferContentCb>,
@@ -156,6 +196,8 @@ fn init_buffer_content_cmd(
         LapceUICommand::InitBufferContentLineCol(InitBufferContent {
             path,
             content,
+            encoding,
+            large_file,
             locations,
             edits,
             cb,
@@ -171,6 +213,8 @@ fn to_utf8_offset(&self, buffer: &Buffer) -> usize {
     fn init_buffer_content_cmd(
         path: PathBuf,
         content: Rope,
+        encoding: String,
+        large_file: bool,
         locations: Vec<(WidgetId, EditorLocation<Self>)>,
         edits: Option<Rope>,
         cb: Option<InitBufferContentCb>,
@@ -178,6 +222,8 @@ fn init_buffer_content_cmd(
         LapceUICommand::InitBufferContentLsp(InitBufferContent {
             path,
             content,
+            encoding,
+            large_file,
             locations,
             edits,
             cb,
@@ -221,6 +267,7 @@ pub struct LapceEditorBufferData {
     pub signature: Arc<SignatureData>,
     pub hover: Arc<HoverData>,
     pub rename: Arc<RenameData>,
+    pub peek: Arc<PeekData>,
     pub main_split: LapceMainSplitData,
     pub focus_area: FocusArea,
     pub source_control: Arc<SourceControlData>,
@@ -247,6 +294,21 @@ pub fn sync_buffer_position(&mut self, scroll_offset: Vec2) {
         }
     }
 
+    /// Remember the content of the search input in the find history of the
+    /// workspace
+    fn record_search(&mut self) {
+        let history_size = self.config.find.history_size;
+        if history_size == 0 {
+            return;
+        }
+        let pattern = self.doc.buffer().to_string();
+        let history = Arc::make_mut(&mut self.main_split.find_history);
+        history.push(&pattern, history_size);
+        self.main_split
+            .db
+            .save_find_history(&self.main_split.workspace, history);
+    }
+
     /// Jump to the next/previous column on the line which matches the given text
     fn inline_find(
         &mut self,
@@ -329,7 +391,7 @@ pub fn get_code_actions(&mut self, ctx: &mut EventCtx) {
 
                 self.proxy.proxy_rpc.get_code_actions(
                     path.clone(),
-                    position,
+                    Range::new(position, position),
                     diagnostics,
                     move |result| {
                         if let Ok(ProxyResponse::GetCodeActionsResponse {
@@ -365,6 +427,112 @@ pub fn get_code_actions(&mut self, ctx: &mut EventCtx) {
         }
     }
 
+    /// Moves the cursor to the next or previous change against the compared
+    /// version in a diff view, or against HEAD like the gutter markers
+    fn run_source_control_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        cmd: &SourceControlCommand,
+    ) -> CommandExecuted {
+        let forward = match cmd {
+            SourceControlCommand::NextChange => true,
+            SourceControlCommand::PrevChange => false,
+            SourceControlCommand::StageHunk | SourceControlCommand::UnstageHunk => {
+                return CommandExecuted::No
+            }
+        };
+
+        let version = self.editor.compare.as_deref().unwrap_or("head");
+        let buffer = self.doc.buffer();
+        let line = buffer.line_of_offset(self.editor.cursor.offset());
+        let line = self.doc.get_history(version).and_then(|history| {
+            change_line(
+                history.hunks(),
+                line,
+                forward,
+                self.config.source_control.wrap_change_navigation,
+            )
+        });
+        if let Some(line) = line {
+            let offset = buffer.offset_of_line(line.min(buffer.last_line()));
+            self.run_move_command(
+                ctx,
+                &lapce_core::movement::Movement::Offset(offset),
+                None,
+                Modifiers::empty(),
+            );
+        }
+        CommandExecuted::Yes
+    }
+
+    /// Shows the git blame at the end of the cursor's line, the blame of the
+    /// file is fetched again once its content changed
+    pub fn get_inline_blame(&mut self, ctx: &mut EventCtx) {
+        if !self.config.source_control.inline_blame || !self.doc.loaded() {
+            return;
+        }
+        let path = match self.doc.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+
+        let line = self
+            .doc
+            .buffer()
+            .line_of_offset(self.editor.cursor.offset());
+        Arc::make_mut(&mut self.doc).set_blame_line(Some(line));
+
+        let rev = self.doc.rev();
+        if let Some(blame) = self.doc.blame.as_ref() {
+            // Only one request is in flight at a time, a stale result asks
+            // again on the next cursor move
+            if blame.rev == rev || blame.hunks.is_none() {
+                return;
+            }
+        }
+        Arc::make_mut(&mut self.doc).blame =
+            Some(DocumentBlame { rev, hunks: None });
+
+        let event_sink = ctx.get_external_handle();
+        self.proxy.proxy_rpc.git_blame(path.clone(), move |result| {
+            // Files that can't be blamed get an empty blame so that they
+            // aren't asked for again
+            let hunks = match result {
+                Ok(ProxyResponse::GitBlameResponse { hunks }) => hunks,
+                _ => Vec::new(),
+            };
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::UpdateBlame { path, rev, hunks },
+                Target::Auto,
+            );
+        });
+    }
+
+    /// Open the color picker of the document color at the range, where its
+    /// swatch was clicked
+    fn open_color_picker(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        range: Range,
+        color: lsp_types::Color,
+    ) {
+        let BufferContent::File(path) = self.doc.content() else {
+            return;
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowColorPicker {
+                point: ctx.to_window(mouse_event.pos),
+                path: path.clone(),
+                range,
+                color,
+            },
+            Target::Widget(self.doc.tab_id),
+        ));
+    }
+
     /// Update the positions of cursors in other editors which are editing the same document  
     /// Ex: You type at the start of the document, the cursor in the other editor (like a split)
     /// should be moved forward.
@@ -406,11 +574,18 @@ fn has_rename(&self) -> bool {
         self.rename.active
     }
 
+    fn has_peek(&self) -> bool {
+        self.peek.active
+    }
+
     /// Perform a workspace edit, which are from the LSP (such as code actions, or symbol renaming)
+    /// Apply the edit, telling the plugin that asked for it with `reply`
+    /// whether it was once every file of it is edited
     pub fn apply_workspace_edit(
         &mut self,
         ctx: &mut EventCtx,
         edit: &WorkspaceEdit,
+        reply: Option<&Arc<WorkspaceEditReply>>,
     ) {
         // TODO: I think this probably has some issues if an operation created a file, and then the
         // workspace-edits after this are told to edit the created file. I think it would behave
@@ -435,7 +610,27 @@ pub fn apply_workspace_edit(
             if let Some(edits) = workspace_edits(edit) {
                 for (url, edits) in edits {
                     if url_matches_path(path, &url) {
-                        apply_edit(&mut self.main_split, path, &edits);
+                        if !apply_edit(&mut self.main_split, path, &edits) {
+                            if let Some(reply) = reply {
+                                reply.fail();
+                            }
+                        }
+                    } else if let Some(url_path) =
+                        url.to_file_path().ok().filter(|p| {
+                            self.main_split
+                                .open_docs
+                                .get(p)
+                                .map(|doc| doc.loaded())
+                                .unwrap_or(false)
+                        })
+                    {
+                        // Other files that are already loaded are edited in place,
+                        // without jumping to them
+                        if !apply_edit(&mut self.main_split, &url_path, &edits) {
+                            if let Some(reply) = reply {
+                                reply.fail();
+                            }
+                        }
                     } else if let Ok(url_path) = url.to_file_path() {
                         // If it is not for the file we have open then we assume that
                         // we may have to load it
@@ -455,9 +650,13 @@ pub fn apply_workspace_edit(
 
                         // Note: For some reason Rust is unsure about what type the arguments are if we don't specify them
                         // Perhaps this could be fixed by being very explicit about the lifetimes in the jump_to_location_cb fn?
+                        let pending = reply.map(|r| r.pending());
                         let callback = move |_: &mut EventCtx, main_split: &mut LapceMainSplitData| {
                             // The file has been loaded, so we want to apply the edits now.
-                            apply_edit(main_split, &url_path, &edits);
+                            let applied = apply_edit(main_split, &url_path, &edits);
+                            if let Some(pending) = &pending {
+                                pending.done(applied);
+                            }
                         };
                         self.main_split.jump_to_location_cb(
                             ctx,
@@ -469,10 +668,16 @@ pub fn apply_workspace_edit(
                         );
                     } else {
                         log::warn!("Text edits failed to apply to URL {url:?} because it was not found");
+                        if let Some(reply) = reply {
+                            reply.fail();
+                        }
                     }
                 }
             }
         }
+        if let Some(reply) = reply {
+            reply.applied();
+        }
     }
 
     pub fn run_code_action(
@@ -482,13 +687,27 @@ pub fn run_code_action(
         plugin_id: &PluginId,
     ) {
         match action {
-            CodeActionOrCommand::Command(_cmd) => {}
+            CodeActionOrCommand::Command(cmd) => {
+                self.proxy.proxy_rpc.execute_command(
+                    cmd.clone(),
+                    *plugin_id,
+                    |_| {},
+                );
+            }
             CodeActionOrCommand::CodeAction(action) => {
                 // If the action contains a workspace edit we can apply it right away
                 // otherwise we need to use 'codeAction/resolve'
                 // (see: https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#textDocument_codeAction)
                 if let Some(edit) = action.edit.as_ref() {
-                    self.apply_workspace_edit(ctx, edit);
+                    self.apply_workspace_edit(ctx, edit, None);
+                    // The command of an action is run after its edit is applied
+                    if let Some(cmd) = action.command.as_ref() {
+                        self.proxy.proxy_rpc.execute_command(
+                            cmd.clone(),
+                            *plugin_id,
+                            |_| {},
+                        );
+                    }
                 } else {
                     self.resolve_code_action(ctx, action, plugin_id)
                 }
@@ -505,16 +724,24 @@ fn resolve_code_action(
     ) {
         let event_sink = ctx.get_external_handle();
         let view_id = self.view_id;
+        let plugin_id = *plugin_id;
         self.proxy.proxy_rpc.code_action_resolve(
             action.clone(),
-            *plugin_id,
+            plugin_id,
             move |result| {
                 if let Ok(ProxyResponse::CodeActionResolveResponse { item }) = result
                 {
-                    if let Some(edit) = item.edit {
+                    // An action that resolves to a command only, without an edit,
+                    // is applied by running the command
+                    if item.edit.is_some() || item.command.is_some() {
+                        let mut action = *item;
+                        action.edit.get_or_insert_with(WorkspaceEdit::default);
                         let _ = event_sink.submit_command(
                             LAPCE_UI_COMMAND,
-                            LapceUICommand::ApplyWorkspaceEdit(edit),
+                            LapceUICommand::RunCodeAction(
+                                CodeActionOrCommand::CodeAction(action),
+                                plugin_id,
+                            ),
                             Target::Widget(view_id),
                         );
                     }
@@ -611,17 +838,10 @@ pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
                                 InsertDrift::Default,
                             );
 
-                            let start_offset = additional_edit
-                                .iter()
-                                .map(|(selection, _)| selection.min_offset())
-                                .min()
-                                .map(|offset| {
-                                    offset.min(start_offset).min(edit_start)
-                                })
-                                .unwrap_or(start_offset);
-
-                            let mut transformer = Transformer::new(&delta);
-                            let offset = transformer.transform(start_offset, false);
+                            let offset = completion_snippet_offset(
+                                &delta,
+                                start_offset.min(edit_start),
+                            );
                             let snippet_tabs = snippet.tabs(offset);
 
                             if snippet_tabs.is_empty() {
@@ -639,16 +859,11 @@ pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
                                 return Ok(());
                             }
 
-                            let mut selection =
-                                lapce_core::selection::Selection::new();
-                            let (_tab, (start, end)) = &snippet_tabs[0];
-                            let region = lapce_core::selection::SelRegion::new(
-                                *start, *end, None,
-                            );
-                            selection.add_region(region);
+                            let active_snippet =
+                                ActiveSnippet::new(&snippet, offset);
                             Arc::make_mut(&mut self.editor)
                                 .cursor
-                                .set_insert(selection);
+                                .set_insert(active_snippet.selection());
 
                             let doc = Arc::make_mut(&mut self.doc);
                             doc.buffer_mut().set_cursor_before(old_cursor);
@@ -657,7 +872,8 @@ pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
 
                             self.apply_deltas(&[(delta, inval_lines, edits)]);
                             Arc::make_mut(&mut self.editor)
-                                .add_snippet_placeholders(snippet_tabs);
+                                .add_snippet(active_snippet);
+                            self.show_snippet_choices();
                             return Ok(());
                         }
                         _ => {}
@@ -667,31 +883,76 @@ pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
             }
         }
 
-        let offset = self.editor.cursor.offset();
-        let start_offset = self.doc.buffer().prev_code_boundary(offset);
-        let end_offset = self.doc.buffer().next_code_boundary(offset);
-        let selection = Selection::region(start_offset, end_offset);
-
+        let (selection, text) = plain_completion_edit(
+            self.doc.buffer(),
+            self.editor.cursor.offset(),
+            item,
+        );
         self.completion_do_edit(
             &selection,
-            &[
-                &[(
-                    selection.clone(),
-                    item.insert_text.as_deref().unwrap_or(item.label.as_str()),
-                )][..],
-                &additional_edit[..],
-            ]
-            .concat(),
+            &[&[(selection.clone(), text)][..], &additional_edit[..]].concat(),
         );
         Ok(())
     }
 
+    /// Accept the highlighted completion item if the char being typed is one of
+    /// its commit characters, before the char is inserted
+    fn commit_completion_on_type(&mut self, c: &str) {
+        let item = match completion_to_commit(
+            &self.completion,
+            self.doc.syntax(),
+            self.editor.snippet.is_some(),
+            self.editor.cursor.offset(),
+            c,
+        ) {
+            Some(item) => item,
+            None => return,
+        };
+        self.cancel_completion();
+        let _ = self.apply_completion_item(&item);
+    }
+
+    /// List the choices of the current snippet tabstop, if it has some
+    fn show_snippet_choices(&mut self) {
+        let choices = self.editor.snippet.as_ref().and_then(|snippet| {
+            let tabstop = snippet.current();
+            let offset = tabstop.regions.first()?.0;
+            Some((offset, tabstop.choices.clone()?))
+        });
+        if let Some((offset, choices)) = choices {
+            let buffer_id = self.doc.id();
+            Arc::make_mut(&mut self.completion)
+                .show_choices(buffer_id, offset, &choices);
+        }
+    }
+
+    /// Replace the current snippet tabstop, and its mirrors, by the choice
+    fn apply_snippet_choice(&mut self, choice: &str) {
+        if let Some(snippet) = self.editor.snippet.as_ref() {
+            let selection = snippet.selection();
+            self.completion_do_edit(&selection, &[(selection.clone(), choice)]);
+        }
+    }
+
+    /// Forget the snippet once the cursor is out of its tabstops, or its text
+    /// is gone
+    fn check_snippet_cursor(&mut self) {
+        if let Some(snippet) = self.editor.snippet.as_ref() {
+            if !snippet.contains(self.editor.cursor.offset())
+                || snippet.is_collapsed()
+            {
+                Arc::make_mut(&mut self.editor).snippet = None;
+            }
+        }
+    }
+
     pub fn cancel_completion(&mut self) {
         if self.completion.status == CompletionStatus::Inactive {
             return;
         }
         let completion = Arc::make_mut(&mut self.completion);
         completion.cancel();
+        self.proxy.proxy_rpc.cancel_completion();
     }
 
     pub fn cancel_signature(&mut self) {
@@ -727,6 +988,20 @@ pub fn cancel_rename(&mut self, ctx: &mut EventCtx) {
         }
     }
 
+    /// Close the peek popup, giving the focus back to the editor it was shown
+    /// from when it had it
+    pub fn cancel_peek(&mut self, ctx: &mut EventCtx) {
+        let peek = Arc::make_mut(&mut self.peek);
+        peek.cancel(ctx, &mut self.main_split);
+        if self.focus_area == FocusArea::Peek {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(self.peek.from_editor),
+            ));
+        }
+    }
+
     /// Select the current completion item, getting the data (potentially needing an LSP request)
     /// and then apply it.
     pub fn completion_item_select(&mut self, ctx: &mut EventCtx) {
@@ -738,7 +1013,13 @@ pub fn completion_item_select(&mut self, ctx: &mut EventCtx) {
         };
 
         self.cancel_completion();
-        if item.item.data.is_some() {
+        if self.completion.is_snippet_choice {
+            self.apply_snippet_choice(&item.item.label);
+        } else if item.item.additional_text_edits.is_none()
+            && item.item.data.is_some()
+        {
+            // The server may defer computing edits such as auto-imports
+            // until the item is resolved
             let view_id = self.editor.view_id;
             let buffer_id = self.doc.id();
             let rev = self.doc.rev();
@@ -778,13 +1059,17 @@ pub fn completion_item_select(&mut self, ctx: &mut EventCtx) {
     /// Sends a request to the LSP for completion information
     fn update_completion(
         &mut self,
-        _ctx: &mut EventCtx,
+        ctx: &mut EventCtx,
         display_if_empty_input: bool,
     ) {
         if self.get_mode() != Mode::Insert {
             self.cancel_completion();
             return;
         }
+        if self.completion.is_snippet_choice {
+            // What's typed replaces the choice, it's completed like any other text
+            self.cancel_completion();
+        }
         if !self.doc.loaded() {
             return;
         }
@@ -807,13 +1092,14 @@ fn update_completion(
                 .slice_to_cow(start_offset - 1..start_offset)
                 .to_string()
         };
-        let completion = Arc::make_mut(&mut self.completion);
         if !display_if_empty_input && input.is_empty() && char != "." && char != ":"
         {
-            completion.cancel();
+            self.cancel_completion();
             return;
         }
 
+        let debounce = Duration::from_millis(self.config.completion.debounce_ms);
+        let completion = Arc::make_mut(&mut self.completion);
         if completion.status != CompletionStatus::Inactive
             && completion.offset == start_offset
             && completion.buffer_id == self.doc.id()
@@ -824,9 +1110,11 @@ fn update_completion(
                 let start_pos = self.doc.buffer().offset_to_position(start_offset);
                 completion.request(
                     self.proxy.clone(),
+                    ctx.get_external_handle(),
                     self.doc.content().path().unwrap().into(),
                     "".to_string(),
                     start_pos,
+                    debounce,
                 );
             }
 
@@ -834,36 +1122,48 @@ fn update_completion(
                 let position = self.doc.buffer().offset_to_position(offset);
                 completion.request(
                     self.proxy.clone(),
+                    ctx.get_external_handle(),
                     self.doc.content().path().unwrap().into(),
                     input,
                     position,
+                    debounce,
                 );
             }
 
             return;
         }
 
+        if completion.status != CompletionStatus::Inactive {
+            // The requests of the previous completion are no longer needed
+            self.proxy.proxy_rpc.cancel_completion();
+        }
         completion.buffer_id = self.doc.id();
         completion.offset = start_offset;
         completion.input = input.clone();
         completion.status = CompletionStatus::Started;
         completion.input_items.clear();
-        completion.request_id += 1;
+        completion.start_session();
+        // The first requests of a completion are sent right away, for the list to
+        // show up as soon as possible
         let start_pos = self.doc.buffer().offset_to_position(start_offset);
         completion.request(
             self.proxy.clone(),
+            ctx.get_external_handle(),
             self.doc.content().path().unwrap().into(),
             "".to_string(),
             start_pos,
+            Duration::ZERO,
         );
 
         if !input.is_empty() {
             let position = self.doc.buffer().offset_to_position(offset);
             completion.request(
                 self.proxy.clone(),
+                ctx.get_external_handle(),
                 self.doc.content().path().unwrap().into(),
                 input,
                 position,
+                Duration::ZERO,
             );
         }
     }
@@ -980,6 +1280,7 @@ pub fn update_hover(&mut self, ctx: &mut EventCtx, offset: usize) {
             hover.request_id,
             self.doc.clone(),
             diagnostics,
+            offset,
             start_pos,
             hover.id,
             event_sink,
@@ -988,22 +1289,11 @@ pub fn update_hover(&mut self, ctx: &mut EventCtx, offset: usize) {
     }
 
     fn update_snippet_offset(&mut self, delta: &RopeDelta) {
-        if let Some(snippet) = &self.editor.snippet {
-            let mut transformer = Transformer::new(delta);
-            Arc::make_mut(&mut self.editor).snippet = Some(
-                snippet
-                    .iter()
-                    .map(|(tab, (start, end))| {
-                        (
-                            *tab,
-                            (
-                                transformer.transform(*start, false),
-                                transformer.transform(*end, true),
-                            ),
-                        )
-                    })
-                    .collect(),
-            );
+        if self.editor.snippet.is_none() {
+            return;
+        }
+        if let Some(snippet) = Arc::make_mut(&mut self.editor).snippet.as_mut() {
+            snippet.apply_delta(delta);
         }
     }
 
@@ -1112,6 +1402,35 @@ fn next_error(&mut self, ctx: &mut EventCtx) {
         }
     }
 
+    /// Move the cursor to the next or previous diagnostic of the file, and show
+    /// its message in the hover
+    fn go_to_diagnostic(&mut self, ctx: &mut EventCtx, forward: bool) {
+        let diagnostics = match self.diagnostics() {
+            Some(diagnostics) => diagnostics.clone(),
+            None => return,
+        };
+        let offset = self.editor.cursor.offset();
+        let min_severity = self.config.editor.diagnostic_navigation_severity();
+        let diagnostic =
+            match next_diagnostic(&diagnostics, offset, min_severity, forward) {
+                Some(diagnostic) => diagnostic,
+                None => return,
+            };
+        let offset = diagnostic.range.0.min(self.doc.buffer().len());
+
+        let editor = Arc::make_mut(&mut self.editor);
+        editor.cursor.set_offset(offset, false, false);
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::EnsureCursorVisible(Some(
+                EnsureVisiblePosition::CenterOfWindow,
+            )),
+            Target::Widget(self.editor.view_id),
+        ));
+        self.cancel_hover();
+        self.update_hover(ctx, offset);
+    }
+
     fn jump_location_forward(&mut self, ctx: &mut EventCtx) -> Option<()> {
         if self.main_split.locations.is_empty() {
             return None;
@@ -1203,7 +1522,12 @@ fn scroll(
         let diff = if down { diff } else { -diff };
 
         let offset = self.editor.cursor.offset();
-        let (line, _col) = self.doc.buffer().offset_to_line_col(offset);
+        let (line, col) = self.doc.buffer().offset_to_line_col(offset);
+        // With soft wrapping the cursor moves, and the editor scrolls, by visual lines
+        let line = match self.visual_lines(ctx.text()) {
+            Some(visual_lines) => visual_lines.visual_line_of_line_col(line, col),
+            None => line,
+        };
         let top = self.editor.scroll_offset.y + diff;
         let bottom = top + self.editor.size.borrow().height;
 
@@ -1248,6 +1572,33 @@ fn scroll(
         ));
     }
 
+    /// The lines of the document once soft wrapped for this editor, or `None` if the
+    /// editor doesn't wrap them
+    pub fn visual_lines(&self, text: &mut PietText) -> Option<Arc<VisualLines>> {
+        if !self.editor.view.is_normal() {
+            return None;
+        }
+        let wrap_width = self.editor.wrap_width(text, &self.config);
+        self.doc.visual_lines(text, wrap_width, &self.config)
+    }
+
+    /// The lines of the buffer the editor shows at its scroll offset
+    pub fn visible_lines(&self, text: &mut PietText) -> std::ops::Range<usize> {
+        let line_height = self.config.editor.line_height() as f64;
+        let y = self.editor.scroll_offset.y;
+        let height = self.editor.size.borrow().height;
+        let start = (y / line_height).floor() as usize;
+        let end = ((y + height) / line_height).ceil() as usize + 1;
+        match self.visual_lines(text) {
+            Some(visual_lines) if !visual_lines.is_empty() => {
+                let last = visual_lines.len() - 1;
+                visual_lines.visual_line(start.min(last)).line
+                    ..visual_lines.visual_line(end.min(last)).line + 1
+            }
+            _ => start..end,
+        }
+    }
+
     pub fn current_code_actions(&self) -> Option<&(PluginId, CodeActionResponse)> {
         let offset = self.editor.cursor.offset();
         let prev_offset = self.doc.buffer().prev_code_boundary(offset);
@@ -1322,17 +1673,74 @@ pub fn offset_of_mouse(
         self.doc.buffer().offset_of_line_col(line, col)
     }
 
+    /// Select the block (column) between the `anchor` offset and `point`, which is
+    /// what dragging the mouse with Alt held does.
+    pub fn select_block(
+        &mut self,
+        text: &mut PietText,
+        anchor: usize,
+        point: Point,
+        config: &LapceConfig,
+    ) {
+        let wrap_width = self.editor.wrap_width(text, config);
+        let (offset, _) = self.doc.offset_of_point(
+            text,
+            self.get_mode(),
+            point,
+            &self.editor.view,
+            wrap_width,
+            config,
+        );
+        let buffer = self.doc.buffer();
+        let (line, mut col) = buffer.offset_to_line_char_col(offset);
+        if offset == buffer.line_end_offset(line, true) {
+            // Past the end of the line, the block keeps growing by whole columns.
+            let (end, _) = self.doc.points_of_offset(
+                text,
+                offset,
+                &self.editor.view,
+                wrap_width,
+                config,
+            );
+            if point.x > end.x {
+                let char_width = config.editor_char_width(text);
+                col += ((point.x - end.x) / char_width).round() as usize;
+            }
+        }
+
+        let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
+        match cursor.mode {
+            CursorMode::Insert(_) => {
+                let selection = Selection::block(
+                    buffer,
+                    buffer.offset_to_line_char_col(anchor),
+                    (line, col),
+                );
+                cursor.set_insert(selection);
+            }
+            CursorMode::Normal(_) | CursorMode::Visual { .. } => {
+                cursor.set_mode(CursorMode::Visual {
+                    start: anchor,
+                    end: offset,
+                    mode: VisualMode::Blockwise,
+                });
+            }
+        }
+    }
+
     pub fn single_click(
         &mut self,
         ctx: &mut EventCtx,
         mouse_event: &MouseEvent,
         config: &LapceConfig,
     ) {
+        let wrap_width = self.editor.wrap_width(ctx.text(), config);
         let (new_offset, _) = self.doc.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
             &self.editor.view,
+            wrap_width,
             config,
         );
         let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
@@ -1342,6 +1750,17 @@ pub fn single_click(
             mouse_event.mods.alt(),
         );
 
+        // Clicking the swatch of a color opens the picker of another one
+        if let Some((range, color)) = self.doc.color_of_point(
+            ctx.text(),
+            mouse_event.pos,
+            &self.editor.view,
+            wrap_width,
+            config,
+        ) {
+            self.open_color_picker(ctx, mouse_event, range, color);
+        }
+
         let mut go_to_definition = false;
         #[cfg(target_os = "macos")]
         if mouse_event.mods.meta() {
@@ -1373,11 +1792,13 @@ pub fn double_click(
         config: &LapceConfig,
     ) {
         ctx.set_active(true);
+        let wrap_width = self.editor.wrap_width(ctx.text(), config);
         let (mouse_offset, _) = self.doc.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
             &self.editor.view,
+            wrap_width,
             config,
         );
         let (start, end) = self.doc.buffer().select_word(mouse_offset);
@@ -1397,11 +1818,13 @@ pub fn triple_click(
         config: &LapceConfig,
     ) {
         ctx.set_active(true);
+        let wrap_width = self.editor.wrap_width(ctx.text(), config);
         let (mouse_offset, _) = self.doc.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
             &self.editor.view,
+            wrap_width,
             config,
         );
         let line = self.doc.buffer().line_of_offset(mouse_offset);
@@ -1440,56 +1863,57 @@ fn save(&mut self, ctx: &mut EventCtx, exit: bool, allow_formatting: bool) {
         }
 
         if let BufferContent::File(path) = self.doc.content() {
-            let format_on_save =
-                allow_formatting && self.config.editor.format_on_save;
+            let format_on_save = allow_formatting
+                && self.config.format_on_save_for(self.doc.language());
             let path = path.clone();
+            let cleanup = self.whitespace_cleanup();
+            let exit = if exit {
+                Some(self.editor.view_id)
+            } else {
+                None
+            };
+            let tab_id = *self.main_split.tab_id;
+            if !format_on_save {
+                // Saving without formatting doesn't wait for the formatter of
+                // an earlier save either
+                Arc::make_mut(&mut self.doc).cancel_formatting();
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::DocumentSave {
+                        path,
+                        exit,
+                        cleanup,
+                    },
+                    Target::Widget(tab_id),
+                ));
+                return;
+            }
+
             let proxy = self.proxy.clone();
             let rev = self.doc.rev();
+            let ranges = self.doc.changed_ranges();
+            let timeout =
+                Duration::from_millis(self.config.editor.format_on_save_timeout);
+            let cancel = Arc::make_mut(&mut self.doc).start_formatting();
             let event_sink = ctx.get_external_handle();
-            let view_id = self.editor.view_id;
-            let tab_id = self.main_split.tab_id.clone();
-            let (sender, receiver) = bounded(1);
             thread::spawn(move || {
-                proxy.proxy_rpc.get_document_formatting(
+                let result = format::request_formatting(
+                    &proxy,
                     path.clone(),
-                    Box::new(move |result| {
-                        let _ = sender.send(result);
-                    }),
+                    ranges,
+                    timeout,
+                    &cancel,
                 );
-
-                let result =
-                    receiver.recv_timeout(Duration::from_secs(1)).map_or_else(
-                        |e| Err(anyhow!("{}", e)),
-                        |v| {
-                            v.map_err(|e| anyhow!("{:?}", e)).and_then(|r| {
-                                if let ProxyResponse::GetDocumentFormatting {
-                                    edits,
-                                } = r
-                                {
-                                    Ok(edits)
-                                } else {
-                                    Err(anyhow!("wrong response"))
-                                }
-                            })
-                        },
-                    );
-
-                let exit = if exit { Some(view_id) } else { None };
-                let cmd = if format_on_save {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
                     LapceUICommand::DocumentFormatAndSave {
                         path,
                         rev,
                         result,
                         exit,
-                    }
-                } else {
-                    LapceUICommand::DocumentSave { path, exit }
-                };
-
-                let _ = event_sink.submit_command(
-                    LAPCE_UI_COMMAND,
-                    cmd,
-                    Target::Widget(*tab_id),
+                        cleanup,
+                    },
+                    Target::Widget(tab_id),
                 );
             });
         } else if let BufferContent::Scratch(..) = self.doc.content() {
@@ -1497,12 +1921,32 @@ fn save(&mut self, ctx: &mut EventCtx, exit: bool, allow_formatting: bool) {
             let view_id = self.editor.view_id;
             self.main_split.current_save_as =
                 Some(Arc::new((content, view_id, exit)));
-            let options =
+            let mut options =
                 FileDialogOptions::new().accept_command(LAPCE_SAVE_FILE_AS);
+            if let Some(dir) = FilePickerData::start_dir(
+                &self.main_split.db,
+                PickerKind::SaveAs,
+                &self.main_split.workspace,
+            ) {
+                options = options.force_starting_directory(dir);
+            }
             ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options));
         }
     }
 
+    /// How the settings of the language of the document ask for its
+    /// whitespace to be cleaned up when it's saved
+    fn whitespace_cleanup(&self) -> WhitespaceCleanup {
+        let language = self.doc.language();
+        WhitespaceCleanup {
+            trim_trailing: self.config.trim_trailing_whitespace_for(language),
+            final_newline: self.config.insert_final_newline_for(language),
+            skip_cursor_lines: !self.config.editor.trim_cursor_line_whitespace,
+            keep_hard_breaks: self.config.editor.keep_markdown_hard_breaks
+                && language.map(|l| l.is_markdown()).unwrap_or(false),
+        }
+    }
+
     fn run_move_command(
         &mut self,
         ctx: &mut EventCtx,
@@ -1524,6 +1968,7 @@ fn run_move_command(
         let register = Arc::make_mut(&mut self.main_split.register);
         let doc = Arc::make_mut(&mut self.doc);
         let view = self.editor.view.clone();
+        let wrap_width = self.editor.wrap_width(ctx.text(), &self.config);
         doc.move_cursor(
             ctx.text(),
             &mut Arc::make_mut(&mut self.editor).cursor,
@@ -1531,22 +1976,11 @@ fn run_move_command(
             count.unwrap_or(1),
             mods.shift(),
             &view,
+            wrap_width,
             register,
             &self.config,
         );
-        if let Some(snippet) = self.editor.snippet.as_ref() {
-            let offset = self.editor.cursor.offset();
-            let mut within_region = false;
-            for (_, (start, end)) in snippet {
-                if offset >= *start && offset <= *end {
-                    within_region = true;
-                    break;
-                }
-            }
-            if !within_region {
-                Arc::make_mut(&mut self.editor).snippet = None;
-            }
-        }
+        self.check_snippet_cursor();
         self.cancel_completion();
         self.update_signature();
         self.cancel_hover();
@@ -1570,6 +2004,29 @@ fn run_edit_command(
                 None
             };
 
+        let cmd = match cmd {
+            EditCommand::DeleteWordForward
+                if self.config.editor.subword_navigation =>
+            {
+                &EditCommand::DeleteWordPartForward
+            }
+            EditCommand::DeleteWordBackward
+                if self.config.editor.subword_navigation =>
+            {
+                &EditCommand::DeleteWordPartBackward
+            }
+            _ => cmd,
+        };
+
+        let tab_width = self.config.tab_width_for(doc.language());
+        if cmd == &EditCommand::DeleteBackward
+            && self.config.editor.atomic_soft_tabs
+            && self.config.editor.atomic_soft_tabs_backspace
+            && tab_width > 1
+        {
+            select_soft_tab_backspace(doc.buffer(), cursor, tab_width);
+        }
+
         let deltas = doc.do_edit(cursor, cmd, modal, register);
 
         if !deltas.is_empty() {
@@ -1584,8 +2041,14 @@ fn run_edit_command(
             self.cancel_completion();
         }
         self.apply_deltas(&deltas);
-        if let EditCommand::NormalMode = cmd {
-            Arc::make_mut(&mut self.editor).snippet = None;
+        match cmd {
+            EditCommand::NormalMode => {
+                Arc::make_mut(&mut self.editor).snippet = None;
+            }
+            // Undoing the insertion of the snippet deletes it, while undoing
+            // what was typed in its tabstops keeps it going
+            EditCommand::Undo | EditCommand::Redo => self.check_snippet_cursor(),
+            _ => {}
         }
 
         CommandExecuted::Yes
@@ -1623,6 +2086,9 @@ fn run_focus_command(
                 if self.is_rename() {
                     self.cancel_rename(ctx);
                 }
+                if self.has_peek() {
+                    self.cancel_peek(ctx);
+                }
             }
             SplitVertical => {
                 self.main_split.split_editor(
@@ -1646,6 +2112,58 @@ fn run_focus_command(
                         .split_exchange(ctx, SplitContent::EditorTab(*widget_id));
                 }
             }
+            SplitEqualize => {
+                if let Some(widget_id) = self.editor.tab_id.as_ref() {
+                    self.main_split.split_command(
+                        ctx,
+                        SplitContent::EditorTab(*widget_id),
+                        SplitCommand::Equalize,
+                    );
+                }
+            }
+            SplitIncreaseSize | SplitDecreaseSize => {
+                if let Some(widget_id) = self.editor.tab_id.as_ref() {
+                    let content = SplitContent::EditorTab(*widget_id);
+                    let share = content
+                        .split_id(&self.main_split)
+                        .and_then(|split_id| self.main_split.splits.get(&split_id))
+                        .and_then(|split| {
+                            let index =
+                                split.children.iter().position(|c| c == &content)?;
+                            Some(split.share(index))
+                        });
+                    if let Some(share) = share {
+                        let step = if *cmd == SplitIncreaseSize {
+                            SPLIT_RESIZE_STEP
+                        } else {
+                            -SPLIT_RESIZE_STEP
+                        };
+                        self.main_split.split_command(
+                            ctx,
+                            content,
+                            SplitCommand::SetRatio(share + step),
+                        );
+                    }
+                }
+            }
+            MoveEditorToSplitLeft
+            | MoveEditorToSplitRight
+            | MoveEditorToSplitUp
+            | MoveEditorToSplitDown => {
+                let direction = match cmd {
+                    MoveEditorToSplitLeft => SplitMoveDirection::Left,
+                    MoveEditorToSplitRight => SplitMoveDirection::Right,
+                    MoveEditorToSplitUp => SplitMoveDirection::Up,
+                    _ => SplitMoveDirection::Down,
+                };
+                if let Some(widget_id) = self.editor.tab_id.as_ref() {
+                    self.main_split.split_command(
+                        ctx,
+                        SplitContent::EditorTab(*widget_id),
+                        SplitCommand::MoveEditorToSplit { direction },
+                    );
+                }
+            }
             SplitLeft => {
                 if let Some(widget_id) = self.editor.tab_id.as_ref() {
                     self.main_split.split_move(
@@ -1714,6 +2232,7 @@ fn run_focus_command(
             }
             SearchForward => {
                 if self.editor.content.is_search() {
+                    self.record_search();
                     if let Some(parent_view_id) = self.editor.parent_view_id {
                         ctx.submit_command(Command::new(
                             LAPCE_COMMAND,
@@ -1747,6 +2266,7 @@ fn run_focus_command(
             }
             SearchBackward => {
                 if self.editor.content.is_search() {
+                    self.record_search();
                     if let Some(parent_view_id) = self.editor.parent_view_id {
                         ctx.submit_command(Command::new(
                             LAPCE_COMMAND,
@@ -1786,18 +2306,61 @@ fn run_focus_command(
                 ));
                 return CommandExecuted::No;
             }
-            GlobalSearchRefresh => {
+            ToggleWholeWord => {
                 let tab_id = *self.main_split.tab_id;
-                let pattern = self.doc.buffer().to_string();
+                let find = Arc::make_mut(&mut self.find);
+                find.toggle_whole_words();
+                let pattern = find.search_string.clone().unwrap_or_default();
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::UpdateSearch(pattern, None),
                     Target::Widget(tab_id),
                 ));
+                return CommandExecuted::No;
             }
-            ClearSearch => {
-                Arc::make_mut(&mut self.find).visual = false;
-                let view_id =
+            ToggleInSelection => {
+                let tab_id = *self.main_split.tab_id;
+                let find = Arc::make_mut(&mut self.find);
+                let in_selection = find.toggle_in_selection();
+                let pattern = find.search_string.clone().unwrap_or_default();
+
+                // The search is restricted to what is selected in the editor that
+                // is being searched at the time the toggle is turned on
+                if let Some(editor) = self.main_split.active_editor() {
+                    let doc = self.main_split.editor_doc(editor.view_id);
+                    let scope = match &editor.cursor.mode {
+                        _ if !in_selection => None,
+                        lapce_core::cursor::CursorMode::Insert(selection) => {
+                            Some(selection.clone())
+                        }
+                        lapce_core::cursor::CursorMode::Visual { .. } => {
+                            Some(editor.cursor.edit_selection(doc.buffer()))
+                        }
+                        lapce_core::cursor::CursorMode::Normal(_) => None,
+                    };
+                    doc.find.borrow_mut().set_scope(scope);
+                }
+
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateSearch(pattern, None),
+                    Target::Widget(tab_id),
+                ));
+                return CommandExecuted::No;
+            }
+            GlobalSearchRefresh => {
+                self.record_search();
+                let tab_id = *self.main_split.tab_id;
+                let pattern = self.doc.buffer().to_string();
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateSearch(pattern, None),
+                    Target::Widget(tab_id),
+                ));
+            }
+            ClearSearch => {
+                Arc::make_mut(&mut self.find).visual = false;
+                let view_id =
                     if let Some(parent_view_id) = self.editor.parent_view_id {
                         parent_view_id
                     } else if self.editor.content.is_search() {
@@ -1811,6 +2374,31 @@ fn run_focus_command(
                     Target::Widget(view_id),
                 ));
             }
+            SearchHistoryPrevious | SearchHistoryNext => {
+                if !self.editor.content.is_search() {
+                    return CommandExecuted::No;
+                }
+                let current = self.doc.buffer().to_string();
+                let history = Arc::make_mut(&mut self.main_split.find_history);
+                let entry = if matches!(cmd, SearchHistoryPrevious) {
+                    history.previous(&current)
+                } else {
+                    history.next()
+                };
+                if let Some(pattern) = entry {
+                    let tab_id = *self.main_split.tab_id;
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateSearchInput(pattern.clone()),
+                        Target::Widget(tab_id),
+                    ));
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateSearch(pattern, None),
+                        Target::Widget(tab_id),
+                    ));
+                }
+            }
             SearchInView => {
                 let start_line = ((self.editor.scroll_offset.y
                     / self.config.editor.line_height() as f64)
@@ -1857,6 +2445,13 @@ fn run_focus_command(
                     }
                 }
             }
+            PickerCompletePath => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::CompletePickerPath,
+                    Target::Widget(*self.main_split.tab_id),
+                ));
+            }
             ListSelect => {
                 if self.is_palette() {
                     ctx.submit_command(Command::new(
@@ -1963,60 +2558,31 @@ fn run_focus_command(
                 }
             }
             JumpToNextSnippetPlaceholder => {
-                if let Some(snippet) = self.editor.snippet.as_ref() {
-                    let mut current = 0;
-                    let offset = self.editor.cursor.offset();
-                    for (i, (_, (start, end))) in snippet.iter().enumerate() {
-                        if *start <= offset && offset <= *end {
-                            current = i;
-                            break;
+                if self.editor.snippet.is_some() {
+                    let editor = Arc::make_mut(&mut self.editor);
+                    if let Some(snippet) = editor.snippet.as_mut() {
+                        snippet.next();
+                        editor.cursor.set_insert(snippet.selection());
+                        if snippet.is_finished() {
+                            editor.snippet = None;
                         }
                     }
-
-                    let last_placeholder = current + 1 >= snippet.len() - 1;
-
-                    if let Some((_, (start, end))) = snippet.get(current + 1) {
-                        let mut selection = lapce_core::selection::Selection::new();
-                        let region = lapce_core::selection::SelRegion::new(
-                            *start, *end, None,
-                        );
-                        selection.add_region(region);
-                        Arc::make_mut(&mut self.editor).cursor.set_insert(selection);
-                    }
-
-                    if last_placeholder {
-                        Arc::make_mut(&mut self.editor).snippet = None;
-                    }
                     self.update_signature();
                     self.cancel_completion();
+                    self.show_snippet_choices();
                 }
             }
             JumpToPrevSnippetPlaceholder => {
-                if let Some(snippet) = self.editor.snippet.as_ref() {
-                    let mut current = 0;
-                    let offset = self.editor.cursor.offset();
-                    for (i, (_, (start, end))) in snippet.iter().enumerate() {
-                        if *start <= offset && offset <= *end {
-                            current = i;
-                            break;
-                        }
-                    }
-
-                    if current > 0 {
-                        if let Some((_, (start, end))) = snippet.get(current - 1) {
-                            let mut selection =
-                                lapce_core::selection::Selection::new();
-                            let region = lapce_core::selection::SelRegion::new(
-                                *start, *end, None,
-                            );
-                            selection.add_region(region);
-                            Arc::make_mut(&mut self.editor)
-                                .cursor
-                                .set_insert(selection);
+                if self.editor.snippet.is_some() {
+                    let editor = Arc::make_mut(&mut self.editor);
+                    if let Some(snippet) = editor.snippet.as_mut() {
+                        if snippet.prev() {
+                            editor.cursor.set_insert(snippet.selection());
                         }
-                        self.update_signature();
-                        self.cancel_completion();
                     }
+                    self.update_signature();
+                    self.cancel_completion();
+                    self.show_snippet_choices();
                 }
             }
             PageUp => {
@@ -2149,6 +2715,64 @@ fn run_focus_command(
                     );
                 }
             }
+            PeekDefinition => {
+                if let BufferContent::File(path) = self.doc.content() {
+                    let offset = self.editor.cursor.offset();
+                    let event_sink = ctx.get_external_handle();
+                    let position = self.doc.buffer().offset_to_position(offset);
+                    let editor_view_id = self.editor.view_id;
+                    self.proxy.proxy_rpc.get_definition(
+                        offset,
+                        path.clone(),
+                        position,
+                        move |result| {
+                            if let Ok(ProxyResponse::GetDefinitionResponse {
+                                definition,
+                                ..
+                            }) = result
+                            {
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ShowPeek {
+                                        editor_view_id,
+                                        offset,
+                                        entries: peek_entries(definition),
+                                    },
+                                    Target::Auto,
+                                );
+                            }
+                        },
+                    );
+                }
+            }
+            PeekOpen => {
+                if let Some(location) = self.peek.location(&self.main_split) {
+                    let view_id = self.main_split.jump_to_location(
+                        ctx,
+                        None,
+                        true,
+                        location,
+                        &self.config,
+                    );
+                    self.cancel_peek(ctx);
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::Focus,
+                        Target::Widget(view_id),
+                    ));
+                }
+            }
+            PeekNext | PeekPrevious => {
+                if self.has_peek() {
+                    let peek = Arc::make_mut(&mut self.peek);
+                    if matches!(cmd, PeekNext) {
+                        peek.next();
+                    } else {
+                        peek.previous();
+                    }
+                    peek.go_to_current(ctx, &mut self.main_split, &self.config);
+                }
+            }
             GotoTypeDefinition => {
                 if let BufferContent::File(path) = self.doc.content() {
                     let offset = self.editor.cursor.offset();
@@ -2265,6 +2889,9 @@ fn run_focus_command(
             NextError => {
                 self.next_error(ctx);
             }
+            NextDiagnostic | PreviousDiagnostic => {
+                self.go_to_diagnostic(ctx, matches!(cmd, NextDiagnostic));
+            }
             NextDiff => {
                 self.next_diff(ctx);
             }
@@ -2293,33 +2920,21 @@ fn run_focus_command(
                     let proxy = self.proxy.clone();
                     let rev = self.doc.rev();
                     let event_sink = ctx.get_external_handle();
-                    let (sender, receiver) = bounded(1);
                     let tab_id = self.main_split.tab_id.clone();
+                    // The whole document is formatted as a range by the servers
+                    // which can only format ranges
+                    let ranges = vec![Range::new(
+                        Position::new(0, 0),
+                        Position::new(self.doc.buffer().num_lines() as u32, 0),
+                    )];
                     thread::spawn(move || {
-                        proxy.proxy_rpc.get_document_formatting(
+                        let result = format::request_formatting(
+                            &proxy,
                             path.clone(),
-                            Box::new(move |result| {
-                                let _ = sender.send(result);
-                            }),
+                            ranges,
+                            Duration::from_secs(1),
+                            &crossbeam_channel::never(),
                         );
-
-                        let result = receiver
-                            .recv_timeout(Duration::from_secs(1))
-                            .map_or_else(
-                                |e| Err(anyhow!("{}", e)),
-                                |v| {
-                                    v.map_err(|e| anyhow!("{:?}", e)).and_then(|r| {
-                                        if let ProxyResponse::GetDocumentFormatting {
-                                            edits,
-                                        } = r
-                                        {
-                                            Ok(edits)
-                                        } else {
-                                            Err(anyhow!("wrong response"))
-                                        }
-                                    })
-                                },
-                            );
                         let _ = event_sink.submit_command(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::DocumentFormat { path, rev, result },
@@ -2402,6 +3017,49 @@ fn run_focus_command(
             SaveAndExit => {
                 self.save(ctx, true, true);
             }
+            RunSelectionInTerminal => {
+                if !matches!(
+                    self.editor.content,
+                    BufferContent::File(_) | BufferContent::Scratch(..)
+                ) {
+                    return CommandExecuted::No;
+                }
+                let buffer = self.doc.buffer();
+                let selection = match &self.editor.cursor.mode {
+                    lapce_core::cursor::CursorMode::Insert(selection)
+                        if !selection.is_caret() =>
+                    {
+                        Some(selection.clone())
+                    }
+                    lapce_core::cursor::CursorMode::Visual { .. } => {
+                        Some(self.editor.cursor.edit_selection(buffer))
+                    }
+                    _ => None,
+                };
+                // Without a selection, the line of the cursor is run
+                let text = match selection {
+                    Some(selection) => selection
+                        .regions()
+                        .iter()
+                        .map(|region| {
+                            buffer.slice_to_cow(region.min()..region.max())
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => buffer
+                        .line_content(
+                            buffer.line_of_offset(self.editor.cursor.offset()),
+                        )
+                        .to_string(),
+                };
+                if !text.trim().is_empty() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::TerminalRunText(text),
+                        Target::Widget(*self.main_split.tab_id),
+                    ));
+                }
+            }
             Save => {
                 self.save(ctx, false, true);
             }
@@ -2441,7 +3099,8 @@ fn run_focus_command(
                     );
                 }
             }
-            ConfirmRename => {
+            ConfirmRename | ConfirmRenameWithPreview => {
+                let preview = cmd == &ConfirmRenameWithPreview;
                 let new_name = self
                     .main_split
                     .local_docs
@@ -2454,17 +3113,32 @@ fn run_focus_command(
                 if !new_name.is_empty() {
                     let event_sink = ctx.get_external_handle();
                     let view_id = self.rename.from_editor;
+                    let tab_id = self.main_split.tab_id.clone();
+                    let name = new_name.to_string();
                     self.proxy.proxy_rpc.rename(
                         self.rename.path.clone(),
                         self.rename.position,
                         new_name.to_string(),
                         move |result| {
                             if let Ok(ProxyResponse::Rename { edit }) = result {
-                                let _ = event_sink.submit_command(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::ApplyWorkspaceEdit(edit),
-                                    Target::Widget(view_id),
-                                );
+                                let _ = if preview {
+                                    event_sink.submit_command(
+                                        LAPCE_UI_COMMAND,
+                                        LapceUICommand::ShowRenamePreview {
+                                            new_name: name,
+                                            edit,
+                                        },
+                                        Target::Widget(*tab_id),
+                                    )
+                                } else {
+                                    event_sink.submit_command(
+                                        LAPCE_UI_COMMAND,
+                                        LapceUICommand::ApplyWorkspaceEdit(
+                                            edit, None,
+                                        ),
+                                        Target::Widget(view_id),
+                                    )
+                                };
                             }
                         },
                     );
@@ -2483,6 +3157,18 @@ fn run_focus_command(
             }
             SelectPreviousSyntaxItem => self
                 .run_selection_range_command(ctx, SelectionRangeDirection::Previous),
+            HoverNextPage | HoverPreviousPage => {
+                if !self.has_hover() {
+                    return CommandExecuted::No;
+                }
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ScrollHoverPage {
+                        down: cmd == &HoverNextPage,
+                    },
+                    Target::Widget(self.hover.id),
+                ));
+            }
             _ => return CommandExecuted::No,
         }
         CommandExecuted::Yes
@@ -2549,6 +3235,230 @@ fn run_selection_range_command(
         }
     }
 
+    /// Expand the selection to the next range around it, or shrink it back to
+    /// what it was before the last expansion
+    fn run_selection_range_stack_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        cmd: &SelectionRangeCommand,
+    ) -> CommandExecuted {
+        let selected = self.selected_range();
+        let editor = Arc::make_mut(&mut self.editor);
+        let stack = &mut editor.selection_range_stack;
+        stack.sync(self.doc.id(), self.doc.rev(), selected);
+        match cmd {
+            SelectionRangeCommand::Expand => {
+                if stack.needs_ranges() {
+                    self.request_selection_ranges(ctx, selected);
+                    return CommandExecuted::Yes;
+                }
+
+                let buffer = self.doc.buffer();
+                let mode = editor.cursor.mode.clone();
+                if let Some((start, end)) = stack.expand(buffer, mode, selected) {
+                    editor.cursor.mode = if editor.cursor.is_insert() {
+                        CursorMode::Insert(Selection::region(start, end))
+                    } else {
+                        CursorMode::Visual {
+                            start,
+                            end: buffer.prev_grapheme_offset(end, 1, start),
+                            mode: VisualMode::Normal,
+                        }
+                    };
+                }
+            }
+            SelectionRangeCommand::Shrink => {
+                if let Some(mode) = stack.shrink() {
+                    editor.cursor.mode = mode;
+                }
+            }
+        }
+        CommandExecuted::Yes
+    }
+
+    fn selected_range(&self) -> (usize, usize) {
+        let selection = self.editor.cursor.edit_selection(self.doc.buffer());
+        (selection.min_offset(), selection.max_offset())
+    }
+
+    /// Ask the LSP for the ranges around the selection, to expand it once they
+    /// are stored
+    fn request_selection_ranges(
+        &mut self,
+        ctx: &mut EventCtx,
+        origin: (usize, usize),
+    ) {
+        let buffer_id = self.doc.id();
+        let rev = self.doc.rev();
+        let event_sink = ctx.get_external_handle();
+        let view_id = self.view_id;
+        let store = move |ranges: Option<SelectionRange>| {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::StoreSelectionRangeStack {
+                    buffer_id,
+                    rev,
+                    origin,
+                    ranges,
+                },
+                Target::Widget(view_id),
+            );
+        };
+
+        match self.doc.content() {
+            BufferContent::File(path) => {
+                let position = self.doc.buffer().offset_to_position(origin.0);
+                self.proxy.proxy_rpc.get_selection_range(
+                    path.to_owned(),
+                    vec![position],
+                    move |result| {
+                        let ranges = match result {
+                            Ok(ProxyResponse::GetSelectionRange { ranges }) => {
+                                ranges.into_iter().next()
+                            }
+                            _ => None,
+                        };
+                        store(ranges);
+                    },
+                );
+            }
+            _ => store(None),
+        }
+    }
+
+    /// Keep the LSP ranges asked for by an expansion of the selection, and
+    /// expand it if it's still the same
+    pub fn store_selection_ranges(
+        &mut self,
+        ctx: &mut EventCtx,
+        buffer_id: BufferId,
+        rev: u64,
+        origin: (usize, usize),
+        ranges: Option<&SelectionRange>,
+    ) {
+        if self.doc.id() != buffer_id
+            || self.doc.rev() != rev
+            || self.selected_range() != origin
+        {
+            return;
+        }
+
+        let ranges = ranges
+            .map(|ranges| selection_range_offsets(self.doc.buffer(), ranges))
+            .unwrap_or_default();
+        Arc::make_mut(&mut self.editor)
+            .selection_range_stack
+            .set_ranges(buffer_id, rev, origin, ranges);
+        self.run_selection_range_stack_command(ctx, &SelectionRangeCommand::Expand);
+    }
+
+    fn run_fold_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        cmd: &FoldCommand,
+    ) -> CommandExecuted {
+        if !matches!(
+            self.doc.content(),
+            BufferContent::File(_) | BufferContent::Scratch(..)
+        ) || self.doc.large_file()
+        {
+            return CommandExecuted::No;
+        }
+
+        let offset = self.editor.cursor.offset();
+        let line = self.doc.buffer().line_of_offset(offset);
+        match cmd {
+            FoldCommand::Fold | FoldCommand::FoldAll => {
+                let Some(ranges) = self.doc.folding_ranges() else {
+                    self.request_folding_ranges(ctx, cmd.clone());
+                    return CommandExecuted::Yes;
+                };
+                let folds = Arc::make_mut(&mut self.doc).folds_mut();
+                if cmd == &FoldCommand::Fold {
+                    folds.fold_at(&ranges, line);
+                } else {
+                    folds.fold_all(&ranges);
+                }
+
+                // The cursor can't stay on a line that is now hidden
+                let visible_line = self.doc.folds().visible_line(line);
+                if visible_line != line {
+                    let offset = self.doc.buffer().line_end_offset(
+                        visible_line,
+                        !self.editor.cursor.is_normal(),
+                    );
+                    Arc::make_mut(&mut self.editor)
+                        .cursor
+                        .set_offset(offset, false, false);
+                }
+            }
+            FoldCommand::Unfold => {
+                Arc::make_mut(&mut self.doc).folds_mut().unfold_at(line);
+            }
+            FoldCommand::UnfoldAll => {
+                Arc::make_mut(&mut self.doc).folds_mut().clear();
+            }
+        }
+        CommandExecuted::Yes
+    }
+
+    /// Ask the LSP for the ranges of lines that can be folded, to run the fold
+    /// command once they are stored
+    fn request_folding_ranges(&mut self, ctx: &mut EventCtx, cmd: FoldCommand) {
+        let buffer_id = self.doc.id();
+        let rev = self.doc.rev();
+        let event_sink = ctx.get_external_handle();
+        let view_id = self.view_id;
+        let store = move |ranges: Option<Vec<FoldingRange>>| {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::StoreFoldingRanges {
+                    buffer_id,
+                    rev,
+                    ranges,
+                    cmd,
+                },
+                Target::Widget(view_id),
+            );
+        };
+
+        match self.doc.content() {
+            BufferContent::File(path) => {
+                self.proxy.proxy_rpc.get_folding_range(
+                    path.to_owned(),
+                    move |result| {
+                        let ranges = match result {
+                            Ok(ProxyResponse::GetFoldingRange { ranges }) => {
+                                Some(ranges)
+                            }
+                            _ => None,
+                        };
+                        store(ranges);
+                    },
+                );
+            }
+            _ => store(None),
+        }
+    }
+
+    /// Keep the ranges of lines that can be folded asked for by a fold command,
+    /// and run it if the document is still the same
+    pub fn store_folding_ranges(
+        &mut self,
+        ctx: &mut EventCtx,
+        buffer_id: BufferId,
+        rev: u64,
+        ranges: Option<Vec<FoldingRange>>,
+        cmd: &FoldCommand,
+    ) {
+        if self.doc.id() != buffer_id || self.doc.rev() != rev {
+            return;
+        }
+
+        Arc::make_mut(&mut self.doc).set_folding_ranges(rev, ranges, &self.config);
+        self.run_fold_command(ctx, cmd);
+    }
+
     fn run_motion_mode_command(
         &mut self,
         _ctx: &mut EventCtx,
@@ -2573,13 +3483,33 @@ fn run_multi_selection_command(
         cmd: &MultiSelectionCommand,
     ) -> CommandExecuted {
         let view = self.editor.view.clone();
+        let wrap_width = self.editor.wrap_width(ctx.text(), &self.config);
         let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
-        self.doc
-            .do_multi_selection(ctx.text(), cursor, cmd, &view, &self.config);
+        self.doc.do_multi_selection(
+            ctx.text(),
+            cursor,
+            cmd,
+            &view,
+            wrap_width,
+            &self.config,
+        );
         self.cancel_signature();
         self.cancel_completion();
         CommandExecuted::Yes
     }
+
+    fn run_signature_command(&mut self, cmd: &SignatureCommand) -> CommandExecuted {
+        if !self.has_signature() {
+            return CommandExecuted::No;
+        }
+
+        let signature = Arc::make_mut(&mut self.signature);
+        match cmd {
+            SignatureCommand::NextOverload => signature.next_overload(),
+            SignatureCommand::PrevOverload => signature.prev_overload(),
+        }
+        CommandExecuted::Yes
+    }
 }
 
 impl KeyPressFocus for LapceEditorBufferData {
@@ -2627,20 +3557,39 @@ fn check_condition(&self, condition: &str) -> bool {
             "in_snippet" => self.editor.snippet.is_some(),
             "completion_focus" => self.has_completions(),
             "hover_focus" => self.has_hover(),
+            "signature_focus" => self.has_signature(),
             "list_focus" => self.has_completions() || self.is_palette(),
+            "picker_focus" => {
+                self.editor.content
+                    == BufferContent::Local(LocalBufferKind::FilePicker)
+            }
             "rename_focus" => self.has_rename(),
+            "peek_focus" => {
+                self.has_peek() && self.editor.view_id == self.peek.view_id
+            }
             "modal_focus" => {
                 (self.has_completions() && !self.config.core.modal)
                     || self.has_hover()
                     || self.is_palette()
                     || self.has_rename()
+                    || self.has_peek()
             }
             _ => false,
         }
     }
 
+    fn check_context_value(&self, key: &str, value: &str) -> bool {
+        match key {
+            LANGUAGE_CONTEXT => self.doc.syntax().map_or(false, |syntax| {
+                value.parse::<LapceLanguage>().ok() == Some(syntax.language)
+            }),
+            _ => false,
+        }
+    }
+
     fn receive_char(&mut self, ctx: &mut EventCtx, c: &str) {
         if self.get_mode() == Mode::Insert {
+            self.commit_completion_on_type(c);
             let doc = Arc::make_mut(&mut self.doc);
             let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
             let deltas = doc.do_insert(cursor, c, &self.config);
@@ -2683,6 +3632,14 @@ fn run_command(
             CommandKind::MultiSelection(cmd) => {
                 self.run_multi_selection_command(ctx, cmd)
             }
+            CommandKind::Signature(cmd) => self.run_signature_command(cmd),
+            CommandKind::SelectionRange(cmd) => {
+                self.run_selection_range_stack_command(ctx, cmd)
+            }
+            CommandKind::SourceControl(cmd) => {
+                self.run_source_control_command(ctx, cmd)
+            }
+            CommandKind::Fold(cmd) => self.run_fold_command(ctx, cmd),
             CommandKind::Workbench(_) => CommandExecuted::No,
         };
         let doc = self.doc.clone();
@@ -2791,7 +3748,9 @@ fn process_get_references(
     }
 }
 
-fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
+pub(crate) fn workspace_edits(
+    edit: &WorkspaceEdit,
+) -> Option<HashMap<Url, Vec<TextEdit>>> {
     if let Some(changes) = edit.changes.as_ref() {
         return Some(changes.clone());
     }
@@ -2833,7 +3792,7 @@ fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>>
     Some(edits)
 }
 
-fn workspace_operation(op: &ResourceOp) -> Option<LapceUICommand> {
+pub(crate) fn workspace_operation(op: &ResourceOp) -> Option<LapceUICommand> {
     Some(match op {
         ResourceOp::Create(p) => LapceUICommand::CreateFileOpen {
             path: p.uri.to_file_path().ok()?,
@@ -2866,10 +3825,15 @@ fn url_matches_path(path: &Path, url: &Url) -> bool {
     matches
 }
 
-fn apply_edit(main_split: &mut LapceMainSplitData, path: &Path, edits: &[TextEdit]) {
+/// Apply the edits to the open file, `false` when it isn't open
+pub(crate) fn apply_edit(
+    main_split: &mut LapceMainSplitData,
+    path: &Path,
+    edits: &[TextEdit],
+) -> bool {
     let doc = match main_split.open_docs.get(path) {
         Some(doc) => doc,
-        None => return,
+        None => return false,
     };
 
     let edits = edits
@@ -2884,6 +3848,7 @@ fn apply_edit(main_split: &mut LapceMainSplitData, path: &Path, edits: &[TextEdi
         .collect::<Vec<_>>();
 
     main_split.edit(path, &edits, lapce_core::editor::EditType::Other);
+    true
 }
 
 /// Checks if completion should be triggered if the received command
@@ -2898,6 +3863,8 @@ fn show_completion(
         | EditCommand::DeleteForward
         | EditCommand::DeleteWordBackward
         | EditCommand::DeleteWordForward
+        | EditCommand::DeleteWordPartBackward
+        | EditCommand::DeleteWordPartForward
         | EditCommand::DeleteForwardAndInsert => {
             let start = match deltas.get(0).and_then(|delta| delta.0.els.get(0)) {
                 Some(lapce_xi_rope::DeltaElement::Copy(_, start)) => *start,
@@ -2922,3 +3889,277 @@ fn show_completion(
 
     show_completion
 }
+
+/// Where the snippet of a completion starts once `delta` has been applied.
+///
+/// `edit_start` is the start of the completion's own edit, so additional text
+/// edits (e.g. auto-imports) inserted before it only shift it along instead of
+/// pulling the snippet's tab stops to the import.
+fn completion_snippet_offset(delta: &RopeDelta, edit_start: usize) -> usize {
+    let mut transformer = Transformer::new(delta);
+    transformer.transform(edit_start, false)
+}
+
+/// The edit of a completion item without a text edit of its own, replacing
+/// the word around the offset with the text of the item
+fn plain_completion_edit<'a>(
+    buffer: &Buffer,
+    offset: usize,
+    item: &'a CompletionItem,
+) -> (Selection, &'a str) {
+    let start_offset = buffer.prev_code_boundary(offset);
+    let end_offset = buffer.next_code_boundary(offset);
+    (
+        Selection::region(start_offset, end_offset),
+        item.insert_text.as_deref().unwrap_or(item.label.as_str()),
+    )
+}
+
+/// The completion item which typing the char at the offset accepts, which is
+/// never the case in a snippet being filled in, or in a string or a comment
+fn completion_to_commit(
+    completion: &CompletionData,
+    syntax: Option<&Syntax>,
+    in_snippet: bool,
+    offset: usize,
+    c: &str,
+) -> Option<CompletionItem> {
+    if in_snippet
+        || syntax
+            .map(|syntax| syntax.is_in_string_or_comment(offset))
+            .unwrap_or(false)
+    {
+        return None;
+    }
+    completion.commit_item(c).map(|item| item.item.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, sync::Arc};
+
+    use lapce_core::{
+        buffer::Buffer,
+        editor::EditType,
+        selection::{InsertDrift, Selection},
+    };
+    use lapce_rpc::plugin::PluginId;
+    use lapce_xi_rope::Rope;
+    use lsp_types::{
+        CompletionItem, CompletionItemKind, CompletionResponse, InsertTextFormat,
+    };
+
+    use super::{
+        completion_snippet_offset, completion_to_commit, plain_completion_edit,
+        LineCol, Syntax,
+    };
+    use crate::{
+        completion::{CompletionData, CompletionStatus, Snippet},
+        config::LapceConfig,
+    };
+
+    /// Applies a completion replacing `prefix` at the end of `text`, together
+    /// with an import inserted at `import_offset`, as a single edit.
+    fn complete_with_import(
+        text: &str,
+        prefix: &str,
+        snippet: &str,
+        import_offset: usize,
+        import: &str,
+    ) -> (Buffer, usize, Snippet) {
+        let mut buffer = Buffer::new(text);
+        let edit_start = text.rfind(prefix).unwrap();
+        let snippet = snippet.parse::<Snippet>().unwrap();
+        let snippet_text = snippet.text();
+        let (delta, _, _) = buffer.edit(
+            &[
+                (
+                    Selection::region(edit_start, edit_start + prefix.len()),
+                    snippet_text.as_str(),
+                ),
+                (Selection::caret(import_offset), import),
+            ],
+            EditType::Completion,
+        );
+        let offset = completion_snippet_offset(&delta, edit_start);
+        (buffer, offset, snippet)
+    }
+
+    /// The text covered by the first tab stop of `snippet` placed at `offset`.
+    fn first_tab_stop(buffer: &Buffer, snippet: &Snippet, offset: usize) -> String {
+        let (_, (start, end)) = snippet.tabs(offset)[0];
+        buffer.slice_to_cow(start..end).to_string()
+    }
+
+    #[test]
+    fn test_completion_with_import_above() {
+        let text = "fn main() {\n    HashM\n}\n";
+        let (buffer, offset, snippet) = complete_with_import(
+            text,
+            "HashM",
+            "HashMap::new(${1:capacity})",
+            0,
+            "use std::collections::HashMap;\n",
+        );
+        assert_eq!(
+            buffer.text().to_string(),
+            "use std::collections::HashMap;\nfn main() {\n    HashMap::new(capacity)\n}\n"
+        );
+        assert_eq!(offset, buffer.text().to_string().rfind("HashMap").unwrap());
+        assert_eq!(first_tab_stop(&buffer, &snippet, offset), "capacity");
+    }
+
+    #[test]
+    fn test_completion_with_import_below() {
+        let text = "mod a {\n    fn f() {\n        HashM\n    }\n}\n";
+        let (buffer, offset, snippet) = complete_with_import(
+            text,
+            "HashM",
+            "HashMap::new(${1:capacity})",
+            text.len(),
+            "use std::collections::HashMap;\n",
+        );
+        assert_eq!(
+            buffer.text().to_string(),
+            "mod a {\n    fn f() {\n        HashMap::new(capacity)\n    }\n}\nuse std::collections::HashMap;\n"
+        );
+        assert_eq!(offset, text.rfind("HashM").unwrap());
+        assert_eq!(first_tab_stop(&buffer, &snippet, offset), "capacity");
+    }
+
+    #[test]
+    fn test_line_col_from_offset() {
+        let buffer = Buffer::new("ab\nçdé\n");
+        assert_eq!(
+            LineCol::from_offset(&buffer, 0),
+            LineCol { line: 0, column: 0 }
+        );
+        assert_eq!(
+            LineCol::from_offset(&buffer, 2),
+            LineCol { line: 0, column: 2 }
+        );
+        assert_eq!(
+            LineCol::from_offset(&buffer, 3),
+            LineCol { line: 1, column: 0 }
+        );
+        // 'ç' is two bytes long
+        assert_eq!(
+            LineCol::from_offset(&buffer, 5),
+            LineCol { line: 1, column: 2 }
+        );
+        assert_eq!(
+            LineCol::from_offset(&buffer, 9),
+            LineCol { line: 2, column: 0 }
+        );
+        assert_eq!(
+            LineCol::from_offset(&buffer, 100),
+            LineCol { line: 2, column: 0 }
+        );
+    }
+
+    #[test]
+    fn test_line_col_to_offset() {
+        let buffer = Buffer::new("ab\r\nçdé\r\nf");
+        assert_eq!(LineCol { line: 0, column: 1 }.to_offset(&buffer), 1);
+        assert_eq!(LineCol { line: 1, column: 0 }.to_offset(&buffer), 4);
+        assert_eq!(LineCol { line: 1, column: 3 }.to_offset(&buffer), 7);
+        assert_eq!(LineCol { line: 2, column: 0 }.to_offset(&buffer), 11);
+        // a column past the end of the line stops before the line ending
+        assert_eq!(
+            LineCol {
+                line: 0,
+                column: 10
+            }
+            .to_offset(&buffer),
+            2
+        );
+    }
+
+    #[test]
+    fn test_line_col_round_trip() {
+        let buffer = Buffer::new("fn main() {\n    let ü = 1;\n}\n");
+        for offset in 0..buffer.len() {
+            if buffer.text().is_codepoint_boundary(offset) {
+                let line_col = LineCol::from_offset(&buffer, offset);
+                assert_eq!(line_col.to_offset(&buffer), offset);
+            }
+        }
+    }
+
+    fn completion_with(
+        commit_on_type: bool,
+        item: CompletionItem,
+    ) -> CompletionData {
+        let mut config = LapceConfig::default();
+        config.completion.commit_on_type = commit_on_type;
+        let mut completion = CompletionData::new(Arc::new(config));
+        completion.status = CompletionStatus::Started;
+        completion.start_session();
+        completion.receive(
+            1,
+            "".to_string(),
+            CompletionResponse::Array(vec![item]),
+            PluginId(0),
+        );
+        completion.update_input("pri".to_string());
+        completion
+    }
+
+    #[test]
+    fn test_commit_completion_on_type() {
+        let item = CompletionItem {
+            label: "println".to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            commit_characters: Some(vec!["(".to_string()]),
+            ..Default::default()
+        };
+        let completion = completion_with(true, item.clone());
+        let text = "fn main() { pri }";
+        let offset = text.rfind(" }").unwrap();
+        let commit = |in_snippet: bool, c: &str| {
+            completion_to_commit(&completion, None, in_snippet, offset, c)
+        };
+        // Only the commit characters of the item accept it
+        assert_eq!(commit(false, "."), None);
+        // and not while a snippet is being filled in
+        assert_eq!(commit(true, "("), None);
+        let committed = commit(false, "(").unwrap();
+        assert_eq!(committed, item);
+
+        // The item is inserted, then the char is typed after it
+        let mut buffer = Buffer::new(text);
+        let (selection, new_text) =
+            plain_completion_edit(&buffer, offset, &committed);
+        let (delta, _, _) =
+            buffer.edit(&[(selection.clone(), new_text)], EditType::Completion);
+        let offset = selection
+            .apply_delta(&delta, true, InsertDrift::Default)
+            .max_offset();
+        buffer.edit(&[(Selection::caret(offset), "(")], EditType::InsertChars);
+        assert_eq!(buffer.text().to_string(), "fn main() { println( }");
+
+        // Nothing is accepted when it's turned off
+        let off = completion_with(false, item.clone());
+        assert!(completion_to_commit(&off, None, false, offset, "(").is_none());
+
+        // nor in a string or a comment
+        let mut syntax = Syntax::init(Path::new("main.rs")).unwrap();
+        let text = "fn main() { pri; \"pri\"; // pri here\n}";
+        syntax.parse(0, Rope::from(text), None);
+        let commit = |offset: usize| {
+            completion_to_commit(&completion, Some(&syntax), false, offset, "(")
+        };
+        assert_eq!(commit(text.find("pri;").unwrap() + 3), Some(item.clone()));
+        assert_eq!(commit(text.find("pri\"").unwrap() + 3), None);
+        assert_eq!(commit(text.rfind("pri").unwrap() + 3), None);
+
+        // nor for a snippet, whose tabstop the char would be typed in
+        let snippet = CompletionItem {
+            insert_text: Some("println!($0)".to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..item
+        };
+        let completion = completion_with(true, snippet);
+        assert!(completion.commit_item("(").is_none());
+    }
+}
@@ -1,16 +1,27 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use druid::{Command, EventCtx, ExtEventSink, Target, WidgetId};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use indexmap::IndexMap;
 use lapce_core::{cursor::CursorMode, selection::Selection};
-use lapce_rpc::{file::FileNodeItem, proxy::ProxyResponse};
+use lapce_rpc::{
+    file::{FileNodeItem, FileOperation},
+    proxy::ProxyResponse,
+    source_control::FileDiff,
+};
 use lapce_xi_rope::Rope;
 
 use crate::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    alert::{AlertButton, AlertContentData},
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
+    config::{ExplorerConfig, LapceTheme},
     data::{LapceMainSplitData, LapceWorkspace},
     document::LocalBufferKind,
     proxy::LapceProxy,
@@ -61,6 +72,82 @@ pub fn list_index(&self) -> usize {
     }
 }
 
+/// Decides which files are hidden from the file explorer
+#[derive(Clone)]
+pub struct ExplorerFilter {
+    /// The exclude globs matched against the paths relative to the workspace
+    paths: GlobSet,
+    /// The exclude globs matched against the file names
+    names: GlobSet,
+    hide_ignored: bool,
+}
+
+impl ExplorerFilter {
+    pub fn new(config: &ExplorerConfig) -> Self {
+        let mut paths = GlobSetBuilder::new();
+        let mut names = GlobSetBuilder::new();
+        for pattern in config.exclude.iter() {
+            let pattern = pattern.trim_end_matches('/');
+            if let Ok(glob) =
+                GlobBuilder::new(pattern).literal_separator(true).build()
+            {
+                if pattern.contains('/') {
+                    paths.add(glob);
+                } else {
+                    names.add(glob);
+                }
+            }
+        }
+        Self {
+            paths: paths.build().unwrap_or_else(|_| GlobSet::empty()),
+            names: names.build().unwrap_or_else(|_| GlobSet::empty()),
+            hide_ignored: config.hide_gitignored,
+        }
+    }
+
+    /// If the path, relative to the workspace, is matched by an exclude glob
+    pub fn is_excluded(&self, relative_path: &Path) -> bool {
+        self.paths.is_match(relative_path)
+            || relative_path
+                .file_name()
+                .map(|name| self.names.is_match(name))
+                .unwrap_or(false)
+    }
+
+    pub fn is_hidden(&self, workspace: &Path, item: &FileNodeItem) -> bool {
+        if self.hide_ignored && item.ignored {
+            return true;
+        }
+        let relative_path = item
+            .path_buf
+            .strip_prefix(workspace)
+            .unwrap_or(&item.path_buf);
+        self.is_excluded(relative_path)
+    }
+}
+
+/// Where the paths dragged in the file explorer are dropped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplorerDrop {
+    /// The directory the paths are moved into
+    pub dir: PathBuf,
+    /// The index into the file list of the line the drop is shown on
+    pub list_index: usize,
+    /// Indentation level
+    pub indent_level: usize,
+    /// If the drop is shown as a line above the line at `list_index`, rather
+    /// than onto the directory at `list_index`
+    pub between: bool,
+}
+
+/// The paths cut or copied in the file explorer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplorerClipboard {
+    pub paths: Vec<PathBuf>,
+    /// If the paths are moved, rather than copied, by the next paste
+    pub cut: bool,
+}
+
 #[derive(Clone)]
 pub struct FileExplorerData {
     pub tab_id: WidgetId,
@@ -73,12 +160,22 @@ pub struct FileExplorerData {
     pub renaming_editor_view_id: WidgetId,
     pub proxy: Arc<LapceProxy>,
     pub event_sink: ExtEventSink,
+    pub filter: ExplorerFilter,
+    /// Temporarily shows the files hidden by the filter
+    pub show_hidden: bool,
+    /// The files and directories selected with clicks, which the bulk
+    /// operations act on
+    pub selection: HashSet<PathBuf>,
+    /// Where the range selected by a shift click starts
+    pub selection_anchor: Option<PathBuf>,
+    pub clipboard: Option<ExplorerClipboard>,
 }
 
 impl FileExplorerData {
     pub fn new(
         tab_id: WidgetId,
         workspace: LapceWorkspace,
+        filter: ExplorerFilter,
         proxy: Arc<LapceProxy>,
         event_sink: ExtEventSink,
     ) -> Self {
@@ -92,6 +189,8 @@ pub fn new(
                 open: false,
                 children: HashMap::new(),
                 children_open_count: 0,
+                ignored: false,
+                hidden: false,
             });
             let path = path.clone();
             Self::read_dir(&path, true, tab_id, &proxy, event_sink.clone());
@@ -106,12 +205,146 @@ pub fn new(
                 open: false,
                 children: HashMap::new(),
                 children_open_count: 0,
+                ignored: false,
+                hidden: false,
             }),
             active_selected: None,
             naming: None,
             renaming_editor_view_id: WidgetId::next(),
             proxy,
             event_sink,
+            filter,
+            show_hidden: false,
+            selection: HashSet::new(),
+            selection_anchor: None,
+            clipboard: None,
+        }
+    }
+
+    /// The paths of the file list in their order, without the workspace
+    pub fn visible_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Some(workspace) = self.workspace.as_ref() {
+            push_visible_paths(workspace, &mut paths);
+        }
+        paths
+    }
+
+    /// Update the selection for a click on the path, `toggle` being whether
+    /// ctrl (cmd on macOS) is held and `range` whether shift is held
+    pub fn select(&mut self, path: &Path, toggle: bool, range: bool) {
+        if range {
+            if let Some(anchor) = self.selection_anchor.as_ref() {
+                let paths = selection_range(&self.visible_paths(), anchor, path);
+                if !toggle {
+                    self.selection.clear();
+                }
+                self.selection.extend(paths);
+                return;
+            }
+        }
+
+        if !toggle {
+            self.selection.clear();
+            self.selection.insert(path.to_path_buf());
+        } else if !self.selection.remove(path) {
+            self.selection.insert(path.to_path_buf());
+        }
+        self.selection_anchor = Some(path.to_path_buf());
+    }
+
+    /// The paths an operation invoked on the path acts on, which are the
+    /// selected ones if the path is selected
+    pub fn operation_paths(&self, path: &Path) -> Vec<PathBuf> {
+        if self.selection.contains(path) {
+            top_level_paths(self.selection.iter().cloned().collect())
+        } else {
+            vec![path.to_path_buf()]
+        }
+    }
+
+    /// The operations moving the dragged paths into the directory, `None` when
+    /// the drop is invalid. The moves replacing an entry of the directory are
+    /// overwriting ones, to be confirmed by the user.
+    pub fn drop_operations(
+        &self,
+        paths: &[PathBuf],
+        dir: &Path,
+    ) -> Option<Vec<FileOperation>> {
+        let taken = self.dir_entries(dir);
+        let operations = drop_moves(paths, dir)?
+            .into_iter()
+            .map(|(from, to)| FileOperation::Move {
+                overwrite: taken.contains(&to),
+                from,
+                to,
+            })
+            .collect();
+        Some(operations)
+    }
+
+    /// The paths of the entries of the directory the explorer knows of
+    fn dir_entries(&self, dir: &Path) -> HashSet<PathBuf> {
+        self.workspace
+            .as_ref()
+            .and_then(|workspace| workspace.get_file_node(dir))
+            .map(|node| node.children.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The operations moving or copying the paths of the clipboard into the
+    /// directory, a cut is only pasted once
+    pub fn paste_operations(&mut self, dir: &Path) -> Vec<FileOperation> {
+        let clipboard = match self.clipboard.as_ref() {
+            Some(clipboard) => clipboard.clone(),
+            None => return Vec::new(),
+        };
+        if clipboard.cut {
+            self.clipboard = None;
+        }
+
+        let mut taken = self.dir_entries(dir);
+        clipboard
+            .paths
+            .into_iter()
+            .filter_map(|from| {
+                if clipboard.cut {
+                    // Moving a path into its own directory is a no-op
+                    if from.parent() == Some(dir) {
+                        return None;
+                    }
+                    let to = dir.join(from.file_name()?);
+                    Some(FileOperation::Move {
+                        from,
+                        to,
+                        overwrite: false,
+                    })
+                } else {
+                    let to = copy_destination(dir, &from, &taken)?;
+                    taken.insert(to.clone());
+                    Some(FileOperation::Copy { from, to })
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_filter(&mut self, filter: ExplorerFilter) {
+        self.filter = filter;
+        self.apply_filter();
+    }
+
+    pub fn toggle_hidden_files(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.apply_filter();
+    }
+
+    /// Update which files are hidden in the whole tree, keeping the expanded
+    /// directories expanded
+    fn apply_filter(&mut self) {
+        let filter = (!self.show_hidden).then_some(&self.filter);
+        if let Some(workspace) = self.workspace.as_mut() {
+            let path = workspace.path_buf.clone();
+            filter_item(workspace, &path, filter);
         }
     }
 
@@ -122,6 +355,7 @@ pub fn update_node_count(&mut self, path: &Path) -> Option<()> {
                 node.children_open_count = node
                     .children
                     .values()
+                    .filter(|item| !item.hidden)
                     .map(|item| item.children_open_count + 1)
                     .sum::<usize>();
             } else {
@@ -211,7 +445,9 @@ pub fn update_children(
             return None;
         }
 
-        let node = self.workspace.as_mut()?.get_file_node_mut(path)?;
+        let workspace = self.workspace.as_mut()?;
+        let workspace_path = workspace.path_buf.clone();
+        let node = workspace.get_file_node_mut(path)?;
 
         let removed_paths: Vec<PathBuf> = node
             .children
@@ -224,7 +460,8 @@ pub fn update_children(
         }
 
         for (path, child) in children.into_iter() {
-            if let Some(existing) = node.children.get(&path) {
+            if let Some(existing) = node.children.get_mut(&path) {
+                existing.ignored = child.ignored;
                 if existing.read {
                     Self::read_dir(
                         &path,
@@ -239,6 +476,14 @@ pub fn update_children(
             }
         }
 
+        let filter = (!self.show_hidden).then_some(&self.filter);
+        for child in node.children.values_mut() {
+            let hidden = filter
+                .map(|filter| filter.is_hidden(&workspace_path, child))
+                .unwrap_or(false);
+            child.hidden = hidden;
+        }
+
         node.read = true;
         if expand {
             node.open = true;
@@ -532,6 +777,252 @@ pub fn start_renaming(
     }
 }
 
+/// Update which descendants of the item are hidden, and how many of them are
+/// shown below their open directories
+fn filter_item(
+    item: &mut FileNodeItem,
+    workspace: &Path,
+    filter: Option<&ExplorerFilter>,
+) {
+    for child in item.children.values_mut() {
+        let hidden = filter
+            .map(|filter| filter.is_hidden(workspace, child))
+            .unwrap_or(false);
+        child.hidden = hidden;
+        filter_item(child, workspace, filter);
+    }
+    if item.is_dir {
+        item.children_open_count = if item.open {
+            item.children
+                .values()
+                .filter(|child| !child.hidden)
+                .map(|child| child.children_open_count + 1)
+                .sum()
+        } else {
+            0
+        };
+    }
+}
+
+fn file_operations_command(operations: Vec<FileOperation>) -> LapceCommand {
+    LapceCommand {
+        kind: CommandKind::Workbench(
+            LapceWorkbenchCommand::FileExplorerFileOperations,
+        ),
+        data: serde_json::to_value(operations).ok(),
+    }
+}
+
+/// The alert asking whether the moves can replace the entries at their
+/// destination
+pub fn replace_alert(
+    tab_id: WidgetId,
+    operations: Vec<FileOperation>,
+) -> AlertContentData {
+    let replaced = operations
+        .iter()
+        .filter_map(|operation| match operation {
+            FileOperation::Move {
+                to,
+                overwrite: true,
+                ..
+            } => to.file_name(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let title = match replaced.as_slice() {
+        [name] => format!("Do you want to replace {}?", name.to_string_lossy()),
+        _ => format!("Do you want to replace {} items?", replaced.len()),
+    };
+    AlertContentData {
+        title,
+        msg: "The destination already has entries with the same names.".to_string(),
+        buttons: vec![AlertButton {
+            text: "Replace".to_string(),
+            target: tab_id,
+            command: file_operations_command(operations),
+        }],
+        ..Default::default()
+    }
+}
+
+/// The alert asking to permanently delete the paths that couldn't be moved to
+/// the trash
+pub fn permanent_delete_alert(
+    tab_id: WidgetId,
+    paths: Vec<PathBuf>,
+) -> AlertContentData {
+    let (title, msg) = match paths.as_slice() {
+        [path] => (
+            format!(
+                "Do you want to permanently delete {}?",
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            ),
+            "It can't be moved to the trash, and deleting it can't be undone.",
+        ),
+        _ => (
+            format!("Do you want to permanently delete {} items?", paths.len()),
+            "They can't be moved to the trash, and deleting them can't be undone.",
+        ),
+    };
+    AlertContentData {
+        title,
+        msg: msg.to_string(),
+        buttons: vec![AlertButton {
+            text: "Delete Permanently".to_string(),
+            target: tab_id,
+            command: file_operations_command(
+                paths
+                    .into_iter()
+                    .map(|path| FileOperation::Delete { path })
+                    .collect(),
+            ),
+        }],
+        ..Default::default()
+    }
+}
+
+/// Where the paths dragged over the line at the index of the file list are
+/// dropped, `fraction` being how far down the line the pointer is
+pub fn drop_target(
+    workspace: &FileNodeItem,
+    list_index: usize,
+    fraction: f64,
+) -> Option<ExplorerDrop> {
+    let (indent_level, node) = match get_item_children(0, list_index, 0, workspace) {
+        (_, Some((indent_level, node))) if indent_level > 0 => (indent_level, node),
+        (_, Some(_)) => return None,
+        // Below the last line, the paths are dropped in the workspace
+        (_, None) => {
+            return Some(ExplorerDrop {
+                dir: workspace.path_buf.clone(),
+                list_index: workspace.children_open_count + 1,
+                indent_level: 1,
+                between: true,
+            })
+        }
+    };
+
+    if node.is_dir && (0.25..=0.75).contains(&fraction) {
+        return Some(ExplorerDrop {
+            dir: node.path_buf.clone(),
+            list_index,
+            indent_level,
+            between: false,
+        });
+    }
+
+    let parent = node.path_buf.parent()?.to_path_buf();
+    let drop = if fraction < 0.5 {
+        ExplorerDrop {
+            dir: parent,
+            list_index,
+            indent_level,
+            between: true,
+        }
+    } else if node.is_dir && node.open {
+        // Below an open directory is its first entry
+        ExplorerDrop {
+            dir: node.path_buf.clone(),
+            list_index: list_index + 1,
+            indent_level: indent_level + 1,
+            between: true,
+        }
+    } else {
+        ExplorerDrop {
+            dir: parent,
+            list_index: list_index + 1,
+            indent_level,
+            between: true,
+        }
+    };
+    Some(drop)
+}
+
+/// The `(from, to)` moves of the dragged paths into the directory, skipping
+/// the ones already in it. `None` when a directory would be dropped into itself
+/// or one of its descendants.
+pub fn drop_moves(paths: &[PathBuf], dir: &Path) -> Option<Vec<(PathBuf, PathBuf)>> {
+    if paths.iter().any(|path| dir.starts_with(path)) {
+        return None;
+    }
+    let moves = paths
+        .iter()
+        .filter(|path| path.parent() != Some(dir))
+        .filter_map(|from| Some((from.clone(), dir.join(from.file_name()?))))
+        .collect();
+    Some(moves)
+}
+
+fn push_visible_paths(item: &FileNodeItem, paths: &mut Vec<PathBuf>) {
+    if item.open {
+        for child in item.sorted_children() {
+            paths.push(child.path_buf.clone());
+            push_visible_paths(child, paths);
+        }
+    }
+}
+
+/// The paths between the anchor and the path, both included, in the order of
+/// the file list
+pub fn selection_range(
+    visible_paths: &[PathBuf],
+    anchor: &Path,
+    path: &Path,
+) -> Vec<PathBuf> {
+    let index = |path: &Path| visible_paths.iter().position(|p| p == path);
+    match (index(anchor), index(path)) {
+        (Some(start), Some(end)) => {
+            visible_paths[start.min(end)..=start.max(end)].to_vec()
+        }
+        (_, Some(end)) => vec![visible_paths[end].clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// The paths without the ones inside another of the paths, which are already
+/// acted on through their ancestor, sorted
+pub fn top_level_paths(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort();
+    let mut top_level: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if !top_level.iter().any(|parent| path.starts_with(parent)) {
+            top_level.push(path);
+        }
+    }
+    top_level
+}
+
+/// Where a copy of the path is pasted in the directory, adding " copy" to its
+/// name when the directory already has an entry with the name
+fn copy_destination(
+    dir: &Path,
+    from: &Path,
+    taken: &HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    let to = dir.join(from.file_name()?);
+    if !taken.contains(&to) && to != from {
+        return Some(to);
+    }
+    let stem = from.file_stem()?.to_string_lossy();
+    let extension = from
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| {
+            let suffix = if i == 1 {
+                " copy".to_string()
+            } else {
+                format!(" copy {i}")
+            };
+            dir.join(format!("{stem}{suffix}{extension}"))
+        })
+        .find(|to| !taken.contains(to))
+}
+
 /// Returns (current index, Option<(indentation level of item, item)>)
 pub fn get_item_children(
     i: usize,
@@ -584,3 +1075,350 @@ pub fn get_item_children_mut(
     }
     (i, None)
 }
+
+/// Get a FileDiff for the given FileNodeItem. If the given item is a folder
+/// that contains changes, returns a "fake" FileDiff that can be used to style
+/// the item accordingly.
+pub fn item_diff(
+    item: &FileNodeItem,
+    file_diffs: &IndexMap<PathBuf, (FileDiff, bool)>,
+) -> Option<FileDiff> {
+    if item.is_dir {
+        file_diffs
+            .keys()
+            .find(|path| path.as_path().starts_with(&item.path_buf))
+            .map(|path| FileDiff::Modified(path.clone()))
+    } else {
+        file_diffs.get(&item.path_buf).map(|d| d.0.clone())
+    }
+}
+
+/// The theme color of a file name in the explorer, given by its git status.
+/// The unchanged files that are ignored by git are dimmed.
+pub fn file_name_color(diff: Option<&FileDiff>, ignored: bool) -> &'static str {
+    match diff {
+        Some(FileDiff::Modified(_) | FileDiff::Renamed(_, _)) => {
+            LapceTheme::EXPLORER_FILE_MODIFIED
+        }
+        Some(FileDiff::Added(_)) => LapceTheme::EXPLORER_FILE_ADDED,
+        Some(FileDiff::Deleted(_)) => LapceTheme::EXPLORER_FILE_DELETED,
+        None if ignored => LapceTheme::PANEL_FOREGROUND_DIM,
+        None => LapceTheme::PANEL_FOREGROUND,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+    };
+
+    use indexmap::IndexMap;
+    use lapce_rpc::{file::FileNodeItem, source_control::FileDiff};
+
+    use super::{
+        copy_destination, drop_moves, drop_target, file_name_color, filter_item,
+        item_diff, push_visible_paths, selection_range, top_level_paths,
+        ExplorerDrop, ExplorerFilter,
+    };
+    use crate::config::{ExplorerConfig, LapceTheme};
+
+    fn filter(exclude: &[&str], hide_gitignored: bool) -> ExplorerFilter {
+        ExplorerFilter::new(&ExplorerConfig {
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            hide_gitignored,
+        })
+    }
+
+    fn node(
+        path: &str,
+        is_dir: bool,
+        ignored: bool,
+        children: Vec<FileNodeItem>,
+    ) -> FileNodeItem {
+        FileNodeItem {
+            path_buf: PathBuf::from(path),
+            is_dir,
+            read: is_dir,
+            open: is_dir,
+            children: children
+                .into_iter()
+                .map(|child| (child.path_buf.clone(), child))
+                .collect(),
+            children_open_count: 0,
+            ignored,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn excludes_names_and_relative_paths() {
+        let filter =
+            filter(&["**/node_modules", "target", "*.log", "docs/*.md"], false);
+        for path in [
+            "node_modules",
+            "web/node_modules",
+            "target",
+            "crates/a/target",
+            "a.log",
+            "logs/b.log",
+            "docs/intro.md",
+        ] {
+            assert!(filter.is_excluded(Path::new(path)), "{path}");
+        }
+        for path in [
+            "src",
+            "src/target.rs",
+            "docs/guide/intro.md",
+            "README.md",
+            "web/node_modules_old",
+        ] {
+            assert!(!filter.is_excluded(Path::new(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn hides_items_of_sample_tree() {
+        let mut root = node(
+            "/ws",
+            true,
+            false,
+            vec![
+                node(
+                    "/ws/src",
+                    true,
+                    false,
+                    vec![
+                        node("/ws/src/main.rs", false, false, vec![]),
+                        node("/ws/src/gen.rs", false, true, vec![]),
+                    ],
+                ),
+                node(
+                    "/ws/target",
+                    true,
+                    true,
+                    vec![node("/ws/target/debug", true, true, vec![])],
+                ),
+                node("/ws/node_modules", true, false, vec![]),
+                node("/ws/Cargo.toml", false, false, vec![]),
+            ],
+        );
+        let shown = |item: &FileNodeItem| {
+            item.sorted_children()
+                .iter()
+                .map(|child| child.path_buf.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let filter = filter(&["**/node_modules"], true);
+        filter_item(&mut root, Path::new("/ws"), Some(&filter));
+        assert_eq!(
+            shown(&root),
+            vec![PathBuf::from("/ws/src"), PathBuf::from("/ws/Cargo.toml")]
+        );
+        assert_eq!(root.children_open_count, 3);
+
+        // Showing the hidden files keeps the directories expanded
+        filter_item(&mut root, Path::new("/ws"), None);
+        assert_eq!(shown(&root).len(), 4);
+        assert_eq!(root.children_open_count, 7);
+        assert!(root.get_file_node(Path::new("/ws/target")).unwrap().open);
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn selects_range_in_list_order() {
+        let root = node(
+            "/ws",
+            true,
+            false,
+            vec![
+                node(
+                    "/ws/src",
+                    true,
+                    false,
+                    vec![
+                        node("/ws/src/lib.rs", false, false, vec![]),
+                        node("/ws/src/main.rs", false, false, vec![]),
+                    ],
+                ),
+                node("/ws/Cargo.toml", false, false, vec![]),
+                node("/ws/README.md", false, false, vec![]),
+            ],
+        );
+        let mut visible = Vec::new();
+        push_visible_paths(&root, &mut visible);
+        assert_eq!(
+            visible,
+            paths(&[
+                "/ws/src",
+                "/ws/src/lib.rs",
+                "/ws/src/main.rs",
+                "/ws/Cargo.toml",
+                "/ws/README.md",
+            ])
+        );
+
+        let range = |anchor: &str, path: &str| {
+            selection_range(&visible, Path::new(anchor), Path::new(path))
+        };
+        assert_eq!(
+            range("/ws/src/main.rs", "/ws/README.md"),
+            paths(&["/ws/src/main.rs", "/ws/Cargo.toml", "/ws/README.md"])
+        );
+        assert_eq!(
+            range("/ws/Cargo.toml", "/ws/src/lib.rs"),
+            paths(&["/ws/src/lib.rs", "/ws/src/main.rs", "/ws/Cargo.toml"])
+        );
+        assert_eq!(range("/ws/gone.rs", "/ws/src"), paths(&["/ws/src"]));
+    }
+
+    #[test]
+    fn operates_on_top_level_paths() {
+        assert_eq!(
+            top_level_paths(paths(&[
+                "/ws/src/main.rs",
+                "/ws/src",
+                "/ws/srcs/a.rs",
+                "/ws/Cargo.toml",
+            ])),
+            paths(&["/ws/Cargo.toml", "/ws/src", "/ws/srcs/a.rs"])
+        );
+    }
+
+    #[test]
+    fn drops_onto_and_between_items() {
+        let mut root = node(
+            "/ws",
+            true,
+            false,
+            vec![
+                node(
+                    "/ws/src",
+                    true,
+                    false,
+                    vec![node("/ws/src/main.rs", false, false, vec![])],
+                ),
+                node("/ws/Cargo.toml", false, false, vec![]),
+            ],
+        );
+        filter_item(&mut root, Path::new("/ws"), None);
+        let drop = |dir: &str, list_index, indent_level, between| {
+            Some(ExplorerDrop {
+                dir: PathBuf::from(dir),
+                list_index,
+                indent_level,
+                between,
+            })
+        };
+
+        assert_eq!(drop_target(&root, 1, 0.5), drop("/ws/src", 1, 1, false));
+        assert_eq!(drop_target(&root, 1, 0.1), drop("/ws", 1, 1, true));
+        assert_eq!(drop_target(&root, 1, 0.9), drop("/ws/src", 2, 2, true));
+        assert_eq!(drop_target(&root, 2, 0.5), drop("/ws/src", 3, 2, true));
+        assert_eq!(drop_target(&root, 3, 0.2), drop("/ws", 3, 1, true));
+        assert_eq!(drop_target(&root, 9, 0.5), drop("/ws", 4, 1, true));
+    }
+
+    #[test]
+    fn rejects_dropping_dir_into_itself() {
+        let dragged = paths(&["/ws/src", "/ws/Cargo.toml"]);
+        assert_eq!(drop_moves(&dragged, Path::new("/ws/src")), None);
+        assert_eq!(drop_moves(&dragged, Path::new("/ws/src/bin")), None);
+        assert_eq!(
+            drop_moves(&dragged, Path::new("/ws/docs")),
+            Some(vec![
+                (PathBuf::from("/ws/src"), PathBuf::from("/ws/docs/src")),
+                (
+                    PathBuf::from("/ws/Cargo.toml"),
+                    PathBuf::from("/ws/docs/Cargo.toml")
+                ),
+            ])
+        );
+        // Dropping in the directory the paths are already in moves nothing
+        assert_eq!(drop_moves(&dragged, Path::new("/ws")), Some(vec![]));
+    }
+
+    #[test]
+    fn names_copies_after_existing_entries() {
+        let dir = Path::new("/ws/src");
+        let mut taken = paths(&["/ws/src/main.rs", "/ws/src/lib.rs"])
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let copy = |from: &str, taken: &HashSet<PathBuf>| {
+            copy_destination(dir, Path::new(from), taken).unwrap()
+        };
+
+        assert_eq!(copy("/ws/build.rs", &taken), Path::new("/ws/src/build.rs"));
+        assert_eq!(
+            copy("/ws/src/main.rs", &taken),
+            Path::new("/ws/src/main copy.rs")
+        );
+        taken.insert(PathBuf::from("/ws/src/main copy.rs"));
+        assert_eq!(
+            copy("/ws/src/main.rs", &taken),
+            Path::new("/ws/src/main copy 2.rs")
+        );
+    }
+
+    #[test]
+    fn colors_names_by_git_status() {
+        let diffs: IndexMap<PathBuf, (FileDiff, bool)> = [
+            FileDiff::Modified(PathBuf::from("/ws/src/lib.rs")),
+            FileDiff::Added(PathBuf::from("/ws/src/new.rs")),
+            FileDiff::Deleted(PathBuf::from("/ws/old.rs")),
+            FileDiff::Renamed(
+                PathBuf::from("/ws/a.rs"),
+                PathBuf::from("/ws/build/b.rs"),
+            ),
+        ]
+        .into_iter()
+        .map(|diff| (diff.path().clone(), (diff, true)))
+        .collect();
+        let color = |path: &str, is_dir: bool, ignored: bool| {
+            let item = node(path, is_dir, ignored, Vec::new());
+            file_name_color(item_diff(&item, &diffs).as_ref(), item.ignored)
+        };
+
+        assert_eq!(
+            color("/ws/src/lib.rs", false, false),
+            LapceTheme::EXPLORER_FILE_MODIFIED
+        );
+        assert_eq!(
+            color("/ws/src/new.rs", false, false),
+            LapceTheme::EXPLORER_FILE_ADDED
+        );
+        assert_eq!(
+            color("/ws/old.rs", false, false),
+            LapceTheme::EXPLORER_FILE_DELETED
+        );
+        assert_eq!(
+            color("/ws/build/b.rs", false, false),
+            LapceTheme::EXPLORER_FILE_MODIFIED
+        );
+        // The folders with changes inside are shown as modified
+        assert_eq!(
+            color("/ws/src", true, false),
+            LapceTheme::EXPLORER_FILE_MODIFIED
+        );
+        assert_eq!(
+            color("/ws/src/main.rs", false, false),
+            LapceTheme::PANEL_FOREGROUND
+        );
+        assert_eq!(color("/ws/docs", true, false), LapceTheme::PANEL_FOREGROUND);
+
+        // The ignored files are dimmed, unless git still tracks changes in them
+        assert_eq!(
+            color("/ws/target", true, true),
+            LapceTheme::PANEL_FOREGROUND_DIM
+        );
+        assert_eq!(
+            color("/ws/build", true, true),
+            LapceTheme::EXPLORER_FILE_MODIFIED
+        );
+    }
+}
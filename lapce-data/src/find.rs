@@ -1,4 +1,8 @@
-use std::cmp::{max, min};
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    fmt,
+};
 
 use lapce_core::{
     selection::{InsertDrift, SelRegion, Selection},
@@ -51,6 +55,46 @@ pub struct FindStatus {
     lines: Vec<usize>,
 }
 
+/// The number of matches of the find, and which of them the cursor is on, as
+/// shown in the find box
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FindCount {
+    /// The index of the match the cursor is on
+    pub current: Option<usize>,
+    pub total: usize,
+    /// Whether the total is estimated from the part of the document searched
+    /// so far, in which case the index of the current match isn't known
+    pub estimated: bool,
+}
+
+impl FindCount {
+    /// Estimate the number of matches in the whole text from the ones found in
+    /// the part of it which was searched, assuming they're spread evenly
+    pub fn estimate(found: usize, searched_len: usize, text_len: usize) -> Self {
+        let total = if searched_len == 0 {
+            found
+        } else {
+            (found as u128 * text_len as u128 / searched_len as u128) as usize
+        };
+        Self {
+            current: None,
+            total: total.max(found),
+            estimated: true,
+        }
+    }
+}
+
+impl fmt::Display for FindCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let approx = if self.estimated { "~" } else { "" };
+        match self.current {
+            _ if self.total == 0 => write!(f, "No results"),
+            Some(current) => write!(f, "{} of {approx}{}", current + 1, self.total),
+            None => write!(f, "{approx}{} results", self.total),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Find {
     /// Uniquely identifies this search query.
@@ -73,6 +117,12 @@ pub struct Find {
     /// Query matches only whole words.
     pub whole_words: bool,
 
+    /// Query only matches inside of the `scope`.
+    pub in_selection: bool,
+
+    /// The selection that the search is restricted to when `in_selection` is set.
+    scope: Option<Selection>,
+
     /// The set of all known find occurrences (highlights).
     occurrences: Selection,
 }
@@ -86,6 +136,8 @@ pub fn new(id: usize) -> Find {
             case_matching: CaseMatching::CaseInsensitive,
             regex: None,
             whole_words: false,
+            in_selection: false,
+            scope: None,
             visual: false,
             occurrences: Selection::new(),
         }
@@ -99,6 +151,21 @@ pub fn occurrences(&self) -> &Selection {
         &self.occurrences
     }
 
+    /// The number of matches found, and the index of the one at the offset,
+    /// which is the last one starting before it when two matches touch
+    pub fn count(&self, offset: usize) -> FindCount {
+        let regions = self.occurrences.regions();
+        let after = regions.partition_point(|region| region.min() <= offset);
+        let current = after
+            .checked_sub(1)
+            .filter(|&index| regions[index].max() >= offset);
+        FindCount {
+            current,
+            total: regions.len(),
+            estimated: false,
+        }
+    }
+
     pub fn hls_dirty(&self) -> bool {
         self.hls_dirty
     }
@@ -127,6 +194,62 @@ pub fn toggle_case_sensitive(&mut self) -> bool {
         self.case_sensitive()
     }
 
+    /// Flips whether only whole words are matched and returns the new value.
+    pub fn toggle_whole_words(&mut self) -> bool {
+        self.whole_words = !self.whole_words;
+        self.whole_words
+    }
+
+    /// Flips whether the search is restricted to the selection and returns the
+    /// new value.
+    pub fn toggle_in_selection(&mut self) -> bool {
+        self.in_selection = !self.in_selection;
+        self.in_selection
+    }
+
+    /// Sets the selection that the search is restricted to when `in_selection` is
+    /// set. Carets are ignored, so a selection without any text selected doesn't
+    /// restrict the search.
+    pub fn set_scope(&mut self, scope: Option<Selection>) {
+        self.scope = scope.and_then(|scope| {
+            let mut selection = Selection::new();
+            for region in scope.regions().iter().filter(|r| !r.is_caret()) {
+                selection.add_region(SelRegion::new(
+                    region.min(),
+                    region.max(),
+                    None,
+                ));
+            }
+            (!selection.is_empty()).then_some(selection)
+        });
+        self.occurrences = Selection::new();
+        self.hls_dirty = true;
+    }
+
+    /// Takes the query, the toggles and the scope of the other find, which
+    /// unsets the occurrences. Returns `false` when they were already the same.
+    pub fn take_query(&mut self, other: &Find) -> bool {
+        if self.search_string == other.search_string
+            && self.case_matching == other.case_matching
+            && self.regex.as_ref().map(|r| r.as_str())
+                == other.regex.as_ref().map(|r| r.as_str())
+            && self.whole_words == other.whole_words
+            && self.in_selection == other.in_selection
+            && self.scope == other.scope
+        {
+            return false;
+        }
+
+        self.unset();
+        self.search_string = other.search_string.clone();
+        self.case_matching = other.case_matching;
+        self.regex = other.regex.clone();
+        self.whole_words = other.whole_words;
+        self.in_selection = other.in_selection;
+        self.scope = other.scope.clone();
+        true
+    }
+
     /// Returns `true` if the search query is a multi-line regex.
     pub(crate) fn is_multiline_regex(&self) -> bool {
         self.regex.is_some()
@@ -207,9 +330,7 @@ pub fn next(
             ) {
                 let end = find_cursor.pos();
 
-                if self.whole_words
-                    && !self.is_matching_whole_words(text, start, end)
-                {
+                if !self.is_valid_match(text, start, end) {
                     raw_lines = text.lines_raw(find_cursor.pos()..text.len());
                     continue;
                 }
@@ -231,9 +352,7 @@ pub fn next(
                 ) {
                     let end = find_cursor.pos();
 
-                    if self.whole_words
-                        && !self.is_matching_whole_words(text, start, end)
-                    {
+                    if !self.is_valid_match(text, start, end) {
                         raw_lines = text.lines_raw(find_cursor.pos()..offset);
                         continue;
                     }
@@ -253,9 +372,7 @@ pub fn next(
             ) {
                 let end = find_cursor.pos();
                 raw_lines = text.lines_raw(find_cursor.pos()..offset);
-                if self.whole_words
-                    && !self.is_matching_whole_words(text, start, end)
-                {
+                if !self.is_valid_match(text, start, end) {
                     continue;
                 }
                 if start < offset {
@@ -278,9 +395,7 @@ pub fn next(
                 ) {
                     let end = find_cursor.pos();
 
-                    if self.whole_words
-                        && !self.is_matching_whole_words(text, start, end)
-                    {
+                    if !self.is_valid_match(text, start, end) {
                         raw_lines = text.lines_raw(find_cursor.pos()..text.len());
                         continue;
                     }
@@ -298,6 +413,57 @@ pub fn next(
         None
     }
 
+    /// Returns the first match starting at or after `offset` that isn't already one
+    /// of the regions of `selection`. With `wrap`, the search continues from the
+    /// start of the text once the end is reached.
+    ///
+    /// Unlike [`Find::next`], a match that starts right where the previous one
+    /// ends is found, and matches overlapping a previous one are skipped.
+    pub fn next_unselected(
+        &self,
+        text: &Rope,
+        offset: usize,
+        selection: &Selection,
+        wrap: bool,
+    ) -> Option<(usize, usize)> {
+        let search_string = self.search_string.as_ref()?;
+        let mut ranges = vec![(offset, text.len())];
+        if wrap {
+            ranges.push((0, offset));
+        }
+
+        for (from, to) in ranges {
+            let mut raw_lines = text.lines_raw(from..text.len());
+            let mut find_cursor = Cursor::new(text, from);
+            while let Some(start) = find(
+                &mut find_cursor,
+                &mut raw_lines,
+                self.case_matching,
+                search_string,
+                self.regex.as_ref(),
+            ) {
+                if start >= to {
+                    break;
+                }
+                let end = find_cursor.pos();
+                raw_lines = text.lines_raw(end..text.len());
+
+                if !self.is_valid_match(text, start, end) {
+                    continue;
+                }
+                if selection
+                    .regions()
+                    .iter()
+                    .any(|r| r.min() == start && r.max() == end)
+                {
+                    continue;
+                }
+                return Some((start, end));
+            }
+        }
+        None
+    }
+
     /// Execute the search on the provided text in the range provided by `start` and `end`.
     pub fn update_find(
         &mut self,
@@ -346,7 +512,7 @@ pub fn update_find(
         ) {
             let end = find_cursor.pos();
 
-            if self.whole_words && !self.is_matching_whole_words(text, start, end) {
+            if !self.is_valid_match(text, start, end) {
                 raw_lines = text.lines_raw(find_cursor.pos()..to);
                 continue;
             }
@@ -400,6 +566,12 @@ pub fn update_highlights(&mut self, text: &Rope, delta: &RopeDelta) {
                 self.occurrences
                     .apply_delta(delta, false, InsertDrift::Default);
 
+            // the scope follows the edits, so that text typed at its edges is
+            // searched as well
+            if let Some(scope) = self.scope.as_mut() {
+                *scope = scope.apply_delta(delta, true, InsertDrift::Inside);
+            }
+
             // invalidate occurrences around insert positions
             for DeltaRegion {
                 new_offset, len, ..
@@ -522,6 +694,92 @@ pub fn next_occurrence(
         }
     }
 
+    /// Returns the text that replaces the match at `start..end`. When the search is a
+    /// regular expression, `$0`, `$1` and `${name}` in the template are expanded to
+    /// the groups captured by the match and `$$` is a literal `$`.
+    pub fn replacement(
+        &self,
+        text: &Rope,
+        start: usize,
+        end: usize,
+        template: &str,
+    ) -> String {
+        let regex = match self.regex.as_ref() {
+            Some(regex) => regex,
+            None => return template.to_string(),
+        };
+
+        // Run the regex on the same text it was found in, so that anchors and
+        // word boundaries at the edges of the match behave the same
+        let haystack_start = if self.is_multiline_regex() {
+            0
+        } else {
+            text.offset_of_line(text.line_of_offset(start))
+        };
+        let haystack_end = if self.is_multiline_regex() {
+            text.len()
+        } else {
+            text.offset_of_line(text.line_of_offset(end) + 1)
+        };
+        let haystack = text.slice_to_cow(haystack_start..haystack_end);
+
+        let mut replacement = String::new();
+        if let Some(captures) = regex
+            .captures_iter(&haystack)
+            .find(|c| c.get(0).map(|m| m.start() + haystack_start) == Some(start))
+        {
+            captures.expand(template, &mut replacement);
+        } else {
+            replacement.push_str(template);
+        }
+        replacement
+    }
+
+    /// Returns the edits that replace every match in the text with the `template`,
+    /// see [`Find::replacement`]. Applying them together keeps the replacement a
+    /// single undo step.
+    pub fn replace_with(
+        &self,
+        text: &Rope,
+        template: &str,
+    ) -> Vec<(Selection, String)> {
+        if self.search_string.is_none() {
+            return Vec::new();
+        }
+
+        // The occurrences are only found incrementally around what is visible, so
+        // the whole text is searched again
+        let mut find = self.clone();
+        find.occurrences = Selection::new();
+        find.update_find(text, 0, text.len(), false);
+
+        find.occurrences
+            .regions()
+            .iter()
+            .map(|region| {
+                (
+                    Selection::region(region.min(), region.max()),
+                    self.replacement(text, region.min(), region.max(), template),
+                )
+            })
+            .collect()
+    }
+
+    /// Checks if a match satisfies the whole words and in selection settings.
+    fn is_valid_match(&self, text: &Rope, start: usize, end: usize) -> bool {
+        if self.whole_words && !self.is_matching_whole_words(text, start, end) {
+            return false;
+        }
+
+        match self.scope.as_ref() {
+            Some(scope) if self.in_selection => scope
+                .regions_in_range(start, end)
+                .iter()
+                .any(|region| region.min() <= start && end <= region.max()),
+            _ => true,
+        }
+    }
+
     /// Checks if the start and end of a match is matching whole words.
     fn is_matching_whole_words(
         &self,
@@ -543,3 +801,367 @@ fn is_matching_whole_words(
         true
     }
 }
+
+/// The recent strings of the search input, recalled with up and down
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FindHistory {
+    /// The strings, the most recent one first
+    entries: VecDeque<String>,
+    /// The index of the string recalled into the input, if one is
+    #[serde(skip)]
+    position: Option<usize>,
+    /// What was in the input before recalling, which is brought back after
+    /// going down past the most recent string
+    #[serde(skip)]
+    draft: String,
+}
+
+impl FindHistory {
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.as_str())
+    }
+
+    /// Remember the string as the most recent one, moving it to the front if
+    /// it's remembered already, and forget the oldest ones past the capacity
+    pub fn push(&mut self, entry: &str, capacity: usize) {
+        self.position = None;
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != entry);
+        self.entries.push_front(entry.to_string());
+        self.entries.truncate(capacity);
+    }
+
+    /// The string older than the recalled one, or the most recent one which
+    /// isn't the `current` content of the input if none is recalled
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let start = match self.position {
+            Some(position) => position + 1,
+            None => self.entries.iter().position(|entry| entry != current)?,
+        };
+        let entry = self.entries.get(start)?.clone();
+        if self.position.is_none() {
+            self.draft = current.to_string();
+        }
+        self.position = Some(start);
+        Some(entry)
+    }
+
+    /// The string more recent than the recalled one, or what was in the input
+    /// before recalling past the most recent one
+    pub fn next(&mut self) -> Option<String> {
+        match self.position? {
+            0 => {
+                self.position = None;
+                Some(std::mem::take(&mut self.draft))
+            }
+            position => {
+                self.position = Some(position - 1);
+                self.entries.get(position - 1).cloned()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_core::selection::{SelRegion, Selection};
+    use lapce_xi_rope::{Interval, Rope, RopeDelta};
+
+    use super::{Find, FindCount, FindHistory};
+
+    fn occurrences(find: &Find) -> Vec<(usize, usize)> {
+        find.occurrences()
+            .regions()
+            .iter()
+            .map(|region| (region.min(), region.max()))
+            .collect()
+    }
+
+    /// Replace every match of `find` in `text` with the `template`
+    fn replace_all(find: &Find, text: &str, template: &str) -> String {
+        let edits = find.replace_with(&Rope::from(text), template);
+        let mut result = text.to_string();
+        for (selection, replacement) in edits.iter().rev() {
+            result.replace_range(
+                selection.min_offset()..selection.max_offset(),
+                replacement,
+            );
+        }
+        result
+    }
+
+    #[test]
+    fn replace_with_named_groups() {
+        let mut find = Find::new(0);
+        find.set_find(r"let (?P<name>\w+) = (\d+);", true, false);
+
+        let text = "let foo = 1;\nlet bar = 22;\n";
+        assert_eq!(
+            replace_all(&find, text, "const ${name}: u32 = $2; // $$0 was '$0'"),
+            "const foo: u32 = 1; // $0 was 'let foo = 1;'\n\
+             const bar: u32 = 22; // $0 was 'let bar = 22;'\n"
+        );
+    }
+
+    #[test]
+    fn replace_with_changing_length() {
+        let mut find = Find::new(0);
+        find.set_find(r"([a-z])(\d+)", true, false);
+
+        let text = "a1 b22 c333\nd4444";
+        assert_eq!(replace_all(&find, text, "$2$1$1"), "1aa 22bb 333cc\n4444dd");
+        assert_eq!(replace_all(&find, text, "$1"), "a b c\nd");
+
+        // The edits are at the offsets of the matches in the original text
+        let edits = find.replace_with(&Rope::from(text), "$1");
+        let ranges: Vec<_> = edits
+            .iter()
+            .map(|(selection, _)| (selection.min_offset(), selection.max_offset()))
+            .collect();
+        assert_eq!(ranges, vec![(0, 2), (3, 6), (7, 11), (12, 17)]);
+    }
+
+    #[test]
+    fn replace_with_literal_search_keeps_template() {
+        let mut find = Find::new(0);
+        find.set_find("a.b", false, false);
+
+        assert_eq!(replace_all(&find, "a.b axb a.b", "$1$$"), "$1$$ axb $1$$");
+    }
+
+    #[test]
+    fn toggle_whole_words_skips_partial_words() {
+        let text = Rope::from("foo food foo_bar (foo)");
+        let mut find = Find::new(0);
+        find.set_find("foo", false, false);
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find).len(), 4);
+
+        assert!(find.toggle_whole_words());
+        find.set_find("foo", false, find.whole_words);
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find), vec![(0, 3), (18, 21)]);
+        assert_eq!(find.next(&text, 0, false, false), Some((18, 21)));
+    }
+
+    #[test]
+    fn in_selection_restricts_matches_to_scope() {
+        let text = Rope::from("ab ab ab ab");
+        let mut find = Find::new(0);
+        find.set_find("ab", false, false);
+        find.set_scope(Some(Selection::region(2, 8)));
+
+        // The scope has no effect until the toggle is turned on
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find).len(), 4);
+
+        assert!(find.toggle_in_selection());
+        find.set_find("ab", false, false);
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find), vec![(3, 5), (6, 8)]);
+        assert_eq!(find.next(&text, 6, false, true), Some((3, 5)));
+
+        // A caret doesn't restrict the search
+        find.set_scope(Some(Selection::caret(4)));
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find).len(), 4);
+    }
+
+    #[test]
+    fn take_query_copies_scope() {
+        let text = Rope::from("ab ab ab ab");
+        let mut current = Find::new(0);
+        current.set_find("ab", false, false);
+        current.in_selection = true;
+        current.set_scope(Some(Selection::region(2, 8)));
+
+        let mut find = Find::new(0);
+        assert!(find.take_query(&current));
+        assert!(!find.take_query(&current));
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find), vec![(3, 5), (6, 8)]);
+
+        // Selecting other text changes the scope of the search
+        current.set_scope(Some(Selection::region(0, 5)));
+        assert!(find.take_query(&current));
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find), vec![(0, 2), (3, 5)]);
+    }
+
+    #[test]
+    fn in_selection_scope_follows_edits() {
+        let text = Rope::from("xx ab ab ab ab");
+        let mut find = Find::new(0);
+        find.set_find("ab", false, false);
+        find.in_selection = true;
+        find.set_scope(Some(Selection::region(6, 11)));
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(occurrences(&find), vec![(6, 8), (9, 11)]);
+
+        // Typing "ab " inside of the scope adds a match and moves the others
+        let delta = RopeDelta::simple_edit(
+            Interval::new(9, 9),
+            Rope::from("ab "),
+            text.len(),
+        );
+        let text = delta.apply(&text);
+        find.update_highlights(&text, &delta);
+        assert_eq!(occurrences(&find), vec![(6, 8), (9, 11), (12, 14)]);
+
+        // Deleting text before the scope shifts it
+        let delta =
+            RopeDelta::simple_edit(Interval::new(0, 1), Rope::from(""), text.len());
+        let text = delta.apply(&text);
+        find.update_highlights(&text, &delta);
+        assert_eq!(occurrences(&find), vec![(5, 7), (8, 10), (11, 13)]);
+    }
+
+    #[test]
+    fn next_unselected_finds_adjacent_matches() {
+        let text = Rope::from("ababab");
+        let mut find = Find::new(0);
+        find.set_find("ab", false, false);
+
+        let mut selection = Selection::region(0, 2);
+        let mut offset = 2;
+        while let Some((start, end)) =
+            find.next_unselected(&text, offset, &selection, false)
+        {
+            selection.add_region(SelRegion::new(start, end, None));
+            offset = end;
+        }
+        let regions: Vec<_> = selection
+            .regions()
+            .iter()
+            .map(|r| (r.min(), r.max()))
+            .collect();
+        assert_eq!(regions, vec![(0, 2), (2, 4), (4, 6)]);
+    }
+
+    #[test]
+    fn next_unselected_skips_overlapping_matches() {
+        let text = Rope::from("aaaaa");
+        let mut find = Find::new(0);
+        find.set_find("aa", false, false);
+
+        let selection = Selection::region(0, 2);
+        assert_eq!(
+            find.next_unselected(&text, 2, &selection, false),
+            Some((2, 4))
+        );
+
+        let mut selection = selection;
+        selection.add_region(SelRegion::new(2, 4, None));
+        assert_eq!(find.next_unselected(&text, 4, &selection, false), None);
+        assert_eq!(find.next_unselected(&text, 4, &selection, true), None);
+    }
+
+    #[test]
+    fn next_unselected_wraps_around() {
+        let text = Rope::from("foo bar foo");
+        let mut find = Find::new(0);
+        find.set_find("foo", false, false);
+
+        let selection = Selection::region(8, 11);
+        assert_eq!(find.next_unselected(&text, 11, &selection, false), None);
+        assert_eq!(
+            find.next_unselected(&text, 11, &selection, true),
+            Some((0, 3))
+        );
+    }
+
+    #[test]
+    fn history_dedups_and_caps() {
+        let mut history = FindHistory::default();
+        for entry in ["foo", "bar", "", "baz", "foo"] {
+            history.push(entry, 3);
+        }
+        // The repeated string moves to the front instead of being added twice,
+        // and the empty one isn't remembered
+        assert_eq!(history.entries().collect::<Vec<_>>(), ["foo", "baz", "bar"]);
+
+        history.push("qux", 3);
+        assert_eq!(history.entries().collect::<Vec<_>>(), ["qux", "foo", "baz"]);
+
+        history.push("foo", 0);
+        assert_eq!(history.entries().count(), 0);
+    }
+
+    #[test]
+    fn history_recalls_with_up_and_down() {
+        let mut history = FindHistory::default();
+        for entry in ["one", "two", "three"] {
+            history.push(entry, 10);
+        }
+        assert_eq!(history.next(), None);
+
+        assert_eq!(history.previous("thr").as_deref(), Some("three"));
+        assert_eq!(history.previous("three").as_deref(), Some("two"));
+        assert_eq!(history.previous("two").as_deref(), Some("one"));
+        // The oldest string stays recalled
+        assert_eq!(history.previous("one"), None);
+
+        assert_eq!(history.next().as_deref(), Some("two"));
+        assert_eq!(history.next().as_deref(), Some("three"));
+        // What was typed before recalling comes back
+        assert_eq!(history.next().as_deref(), Some("thr"));
+        assert_eq!(history.next(), None);
+
+        // The string just searched for isn't recalled first
+        assert_eq!(history.previous("three").as_deref(), Some("two"));
+        history.push("two", 10);
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn count_follows_navigation_with_wrap_around() {
+        let text = Rope::from("foo bar foo baz foo");
+        let mut find = Find::new(0);
+        find.set_find("foo", false, false);
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(find.count(5).to_string(), "3 results");
+
+        let mut offset = 16;
+        assert_eq!(find.count(offset).to_string(), "3 of 3");
+        // Going forward from the last match wraps around to the first one
+        offset = find.next(&text, offset, false, true).unwrap().0;
+        assert_eq!(find.count(offset).current, Some(0));
+        assert_eq!(find.count(offset).to_string(), "1 of 3");
+        offset = find.next(&text, offset, false, true).unwrap().0;
+        assert_eq!(find.count(offset).to_string(), "2 of 3");
+
+        // And going backward from the first one wraps around to the last one
+        let offset = find.next(&text, 0, true, true).unwrap().0;
+        assert_eq!(find.count(offset).to_string(), "3 of 3");
+
+        find.set_find("qux", false, false);
+        find.update_find(&text, 0, text.len(), false);
+        assert_eq!(find.count(0).to_string(), "No results");
+    }
+
+    #[test]
+    fn count_of_touching_matches() {
+        let text = Rope::from("aaaaaa");
+        let mut find = Find::new(0);
+        find.set_find("aa", false, false);
+        find.update_find(&text, 0, text.len(), false);
+        // The cursor at the end of a match and the start of the next one is on
+        // the next one
+        assert_eq!(find.count(2).current, Some(1));
+        assert_eq!(find.count(6).current, Some(2));
+    }
+
+    #[test]
+    fn count_estimate() {
+        let count = FindCount::estimate(12, 1_000, 100_000);
+        assert_eq!(count.total, 1_200);
+        assert_eq!(count.to_string(), "~1200 results");
+        assert_eq!(
+            FindCount::estimate(0, 1_000, 100_000).to_string(),
+            "No results"
+        );
+        assert_eq!(FindCount::estimate(3, 0, 100).total, 3);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, select, Receiver, Sender};
+use lapce_rpc::{proxy::ProxyResponse, RpcError};
+use lsp_types::{Range, TextEdit};
+use thiserror::Error;
+
+use crate::proxy::LapceProxy;
+
+/// Why formatting a document gave no edits to apply
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum FormattingError {
+    /// The formatter didn't answer in time, so the document is saved without
+    /// being formatted
+    #[error("the formatter didn't answer within {} ms", .0.as_millis())]
+    TimedOut(Duration),
+    /// The document was edited or saved again while waiting for the formatter
+    #[error("formatting was cancelled")]
+    Cancelled,
+    /// No language server of the document is capable of formatting it
+    #[error("there's no formatter for the document")]
+    NotSupported,
+    /// The formatter failed
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl FormattingError {
+    /// Whether the user should be told that the document was saved without
+    /// being formatted, which isn't worth it when there's no formatter or the
+    /// user cancelled it
+    pub fn should_warn(&self) -> bool {
+        matches!(self, FormattingError::TimedOut(_))
+    }
+}
+
+/// Stops the wait for a formatting request, which then ends with
+/// [`FormattingError::Cancelled`]
+#[derive(Clone, Debug)]
+pub struct FormattingCancel(Sender<()>);
+
+impl FormattingCancel {
+    pub fn new() -> (Self, Receiver<()>) {
+        let (sender, receiver) = bounded(1);
+        (Self(sender), receiver)
+    }
+
+    pub fn cancel(&self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+/// Request the edits formatting the document from the language servers, and
+/// if none of them is capable of formatting whole documents, the edits
+/// formatting the ranges instead, but not when formatting the document
+/// failed. Both requests are waited for until the timeout at most, in total.
+pub fn request_formatting(
+    proxy: &LapceProxy,
+    path: PathBuf,
+    ranges: Vec<Range>,
+    timeout: Duration,
+    cancel: &Receiver<()>,
+) -> Result<Vec<TextEdit>, FormattingError> {
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = bounded(1);
+    proxy.proxy_rpc.get_document_formatting(
+        path.clone(),
+        Box::new(move |result| {
+            let _ = sender.send(result);
+        }),
+    );
+
+    match wait_for_edits(&receiver, cancel, deadline, timeout) {
+        Err(FormattingError::NotSupported) if !ranges.is_empty() => {
+            let (sender, receiver) = bounded(1);
+            proxy.proxy_rpc.get_document_range_formatting(
+                path,
+                ranges,
+                Box::new(move |result| {
+                    let _ = sender.send(result);
+                }),
+            );
+            wait_for_edits(&receiver, cancel, deadline, timeout)
+        }
+        result => result,
+    }
+}
+
+/// Wait for the response of the formatter until the deadline, or until the
+/// wait is cancelled
+fn wait_for_edits(
+    receiver: &Receiver<Result<ProxyResponse, RpcError>>,
+    cancel: &Receiver<()>,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<Vec<TextEdit>, FormattingError> {
+    let response = select! {
+        recv(receiver) -> response => response
+            .map_err(|err| FormattingError::Failed(err.to_string()))?,
+        recv(cancel) -> _ => return Err(FormattingError::Cancelled),
+        default(deadline.saturating_duration_since(Instant::now())) => {
+            return Err(FormattingError::TimedOut(timeout));
+        }
+    };
+    match response {
+        Ok(ProxyResponse::GetDocumentFormatting { edits }) => Ok(edits),
+        Ok(_) => Err(FormattingError::Failed("wrong response".to_string())),
+        Err(err) if err.code == RpcError::NOT_CAPABLE => {
+            Err(FormattingError::NotSupported)
+        }
+        Err(err) => Err(FormattingError::Failed(err.message)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crossbeam_channel::{bounded, never};
+    use lapce_rpc::{proxy::ProxyResponse, RpcError};
+    use lsp_types::{Position, Range, TextEdit};
+
+    use super::{wait_for_edits, FormattingCancel, FormattingError};
+
+    #[test]
+    fn test_formatter_timeout() {
+        // The formatter never answers, as its sender is kept alive
+        let (_sender, receiver) = bounded(1);
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        let result =
+            wait_for_edits(&receiver, &never(), Instant::now() + timeout, timeout);
+        assert_eq!(result, Err(FormattingError::TimedOut(timeout)));
+        assert!(start.elapsed() >= timeout);
+        assert!(result.unwrap_err().should_warn());
+
+        // A deadline already passed by the first request gives up right away
+        let result = wait_for_edits(&receiver, &never(), Instant::now(), timeout);
+        assert_eq!(result, Err(FormattingError::TimedOut(timeout)));
+    }
+
+    #[test]
+    fn test_cancel_formatting() {
+        let (_sender, receiver) = bounded(1);
+        let (cancel, cancelled) = FormattingCancel::new();
+        cancel.cancel();
+        // Cancelling twice doesn't block
+        cancel.cancel();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let result =
+            wait_for_edits(&receiver, &cancelled, deadline, Duration::from_secs(60));
+        assert_eq!(result, Err(FormattingError::Cancelled));
+        assert!(!FormattingError::Cancelled.should_warn());
+    }
+
+    #[test]
+    fn test_formatter_edits() {
+        let (sender, receiver) = bounded(1);
+        let edits = vec![TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(0, 1)),
+            new_text: "  ".to_string(),
+        }];
+        sender
+            .send(Ok(ProxyResponse::GetDocumentFormatting {
+                edits: edits.clone(),
+            }))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let result =
+            wait_for_edits(&receiver, &never(), deadline, Duration::from_secs(60));
+        assert_eq!(result, Ok(edits));
+    }
+
+    #[test]
+    fn test_formatter_errors() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let wait = |code: i64| {
+            let (sender, receiver) = bounded(1);
+            sender
+                .send(Err(RpcError {
+                    code,
+                    message: "failed".to_string(),
+                }))
+                .unwrap();
+            wait_for_edits(&receiver, &never(), deadline, Duration::from_secs(60))
+        };
+        // Only a server lacking the capability has the ranges formatted instead
+        assert_eq!(
+            wait(RpcError::NOT_CAPABLE),
+            Err(FormattingError::NotSupported)
+        );
+        assert_eq!(wait(0), Err(FormattingError::Failed("failed".to_string())));
+    }
+}
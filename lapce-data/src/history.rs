@@ -12,7 +12,8 @@
 };
 use itertools::Itertools;
 use lapce_core::{
-    buffer::{rope_diff, Buffer, DiffLines},
+    buffer::{diff_hunks, rope_diff, Buffer, DiffLines},
+    language::LapceLanguage,
     style::line_styles,
     syntax::Syntax,
 };
@@ -32,9 +33,13 @@
 pub struct DocumentHistory {
     version: String,
     buffer: Option<Buffer>,
+    /// The language of the document, to lay out tabs like it does
+    language: Option<LapceLanguage>,
     styles: Arc<Spans<Style>>,
     line_styles: Rc<RefCell<LineStyles>>,
     changes: Arc<Vec<DiffLines>>,
+    /// The changes grouped into hunks, used to move between them
+    hunks: Arc<Vec<(Range<usize>, Range<usize>)>>,
     text_layouts: Rc<RefCell<TextLayoutCache>>,
     diff_extend_lines: usize,
 }
@@ -64,10 +69,12 @@ pub fn new(version: String) -> Self {
         Self {
             version,
             buffer: None,
+            language: None,
             styles: Arc::new(Spans::default()),
             line_styles: Rc::new(RefCell::new(LineStyles::new())),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             changes: Arc::new(Vec::new()),
+            hunks: Arc::new(Vec::new()),
             diff_extend_lines: DEFAULT_DIFF_EXTEND_LINES,
         }
     }
@@ -76,6 +83,7 @@ pub fn load_content(&mut self, content: Rope, doc: &Document) {
         let mut buffer = Buffer::new("");
         buffer.init_content(content);
         self.buffer = Some(buffer);
+        self.language = doc.language();
         self.trigger_update_change(doc, DEFAULT_DIFF_EXTEND_LINES);
         self.retrieve_history_styles(doc);
     }
@@ -127,8 +135,12 @@ fn new_text_layout(
         let line_content = self.buffer.as_ref().unwrap().line_content(line);
         let font_family = config.editor.font_family();
         let font_size = config.editor.font_size;
-        let tab_width =
-            config.tab_width(text, config.editor.font_family(), font_size);
+        let tab_width = config.tab_width(
+            text,
+            self.language,
+            config.editor.font_family(),
+            font_size,
+        );
         let mut layout_builder = text
             .new_text_layout(line_content.to_string())
             .font(font_family, font_size as f64)
@@ -412,6 +424,10 @@ pub fn changes(&self) -> &[DiffLines] {
         &self.changes
     }
 
+    pub fn hunks(&self) -> &[(Range<usize>, Range<usize>)] {
+        &self.hunks
+    }
+
     pub fn diff_extend_lines(&self) -> usize {
         self.diff_extend_lines
     }
@@ -421,6 +437,7 @@ pub fn update_changes(
         changes: Arc<Vec<DiffLines>>,
         diff_extend_lines: usize,
     ) {
+        self.hunks = Arc::new(diff_hunks(&changes));
         self.changes = changes;
         self.diff_extend_lines = diff_extend_lines;
     }
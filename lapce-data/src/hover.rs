@@ -2,15 +2,18 @@
 
 use druid::{ExtEventSink, Size, Target, WidgetId};
 use lapce_rpc::{buffer::BufferId, proxy::ProxyResponse};
-use lsp_types::{HoverContents, MarkedString, MarkupKind, Position};
+use lsp_types::{
+    Diagnostic, Documentation, HoverContents, Location, MarkedString, Position, Url,
+};
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{LapceConfig, LapceTheme},
     data::EditorDiagnostic,
     document::{BufferContent, Document},
-    markdown::{from_marked_string, parse_markdown, Content},
-    proxy::LapceProxy,
+    editor::EditorLocation,
+    markdown::{from_marked_string, parse_documentation, parse_markdown, Content},
+    proxy::{path_from_url, LapceProxy},
     rich_text::{RichText, RichTextBuilder},
 };
 
@@ -35,8 +38,9 @@ pub struct HoverData {
     pub buffer_id: BufferId,
     /// A counter to keep track of the active requests
     pub request_id: usize,
-    /// Stores the size of the hover box
-    pub size: Size,
+    /// The width of the hover box, its height is limited by the
+    /// `editor.hover-max-height` setting and the room around the hovered line
+    pub width: f64,
     /// Stores the actual size of the hover content
     pub content_size: Rc<RefCell<Size>>,
     /// The hover items that are currently loaded
@@ -56,7 +60,7 @@ pub fn new() -> Self {
             buffer_id: BufferId(0),
             request_id: 0,
             // TODO: make this configurable by themes
-            size: Size::new(600.0, 300.0),
+            width: 600.0,
             content_size: Rc::new(RefCell::new(Size::ZERO)),
 
             items: Arc::new(Vec::new()),
@@ -77,8 +81,41 @@ pub fn len(&self) -> usize {
         self.items.len()
     }
 
+    /// Whether there is neither documentation nor a diagnostic to show
     pub fn is_empty(&self) -> bool {
-        self.items.is_empty()
+        self.items.is_empty() && self.diagnostic_content.is_none()
+    }
+
+    /// The height the hover can take, given the top and bottom of the line it's
+    /// for and the height of the tab. It's the room on the roomier side of the
+    /// line, up to `max_height`.
+    pub fn available_height(
+        line_top: f64,
+        line_bottom: f64,
+        tab_height: f64,
+        max_height: f64,
+    ) -> f64 {
+        (tab_height - line_bottom)
+            .max(line_top)
+            .max(0.0)
+            .min(max_height)
+    }
+
+    /// The vertical origin of a hover of `height` for the line between
+    /// `line_top` and `line_bottom`. It goes below the line, unless it doesn't fit
+    /// there and there's more room above.
+    pub fn origin_y(
+        line_top: f64,
+        line_bottom: f64,
+        height: f64,
+        tab_height: f64,
+    ) -> f64 {
+        let room_below = tab_height - line_bottom;
+        if height <= room_below || room_below >= line_top {
+            line_bottom
+        } else {
+            line_top - height
+        }
     }
 
     /// Cancel the current hover information, clearing out held data
@@ -91,7 +128,8 @@ pub fn cancel(&mut self) {
         Arc::make_mut(&mut self.items).clear();
     }
 
-    /// Send a request to update the hover at the given position and file
+    /// Send a request to update the hover at the given position and file, and
+    /// show the diagnostics at the offset meanwhile
     #[allow(clippy::too_many_arguments)]
     pub fn request(
         &mut self,
@@ -99,6 +137,7 @@ pub fn request(
         request_id: usize,
         doc: Arc<Document>,
         diagnostics: Option<Arc<Vec<EditorDiagnostic>>>,
+        offset: usize,
         position: Position,
         hover_widget_id: WidgetId,
         event_sink: ExtEventSink,
@@ -107,6 +146,7 @@ pub fn request(
         if let BufferContent::File(path) = doc.content() {
             // Clone config for use inside the proxy callback
             let p_config = config.clone();
+            let diagnostic_event_sink = event_sink.clone();
             // Get the information/documentation that should be shown on hover
             proxy.proxy_rpc.get_hover(
                 request_id,
@@ -127,7 +167,19 @@ pub fn request(
                     }
                 }),
             );
-            self.collect_diagnostics(position, diagnostics, config);
+            self.collect_diagnostics(offset, diagnostics, config);
+            // The diagnostics are shown without waiting for the server, which
+            // may have nothing to say about the position
+            if self.diagnostic_content.is_some() {
+                let _ = diagnostic_event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateHover {
+                        request_id,
+                        items: Arc::new(Vec::new()),
+                    },
+                    Target::Widget(hover_widget_id),
+                );
+            }
         }
     }
 
@@ -146,73 +198,119 @@ pub fn receive(&mut self, request_id: usize, items: Arc<Vec<Content>>) {
 
     fn collect_diagnostics(
         &mut self,
-        position: Position,
+        offset: usize,
         diagnostics: Option<Arc<Vec<EditorDiagnostic>>>,
         config: Arc<LapceConfig>,
     ) {
-        if let Some(diagnostics) = diagnostics {
-            let diagnostics = diagnostics
-                .iter()
-                .map(|diag| &diag.diagnostic)
-                .filter(|diag| {
-                    position >= diag.range.start && position < diag.range.end
-                });
-
-            // Get the dim foreground color for extra information about the error that is typically
-            // not significant
-            let dim_color =
-                config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone();
-
-            // Build up the text for all the diagnostics
-            let mut content = RichTextBuilder::new();
-            content.set_line_height(1.5);
-            for diagnostic in diagnostics {
-                content.push(&diagnostic.message);
-
-                // If there's a source of the message (ex: it came from rustc or rust-analyzer)
-                // then include that
-                if let Some(source) = &diagnostic.source {
-                    content.push(" ");
-                    content.push(source).text_color(dim_color.clone());
-
-                    // If there's an available error code then include that
-                    if let Some(code) = &diagnostic.code {
-                        // TODO: code description field has information like documentation on the
-                        // error code which could be useful to provide as a link
-
-                        // formatted as  diagsource(code)
-                        content.push("(").text_color(dim_color.clone());
-                        match code {
-                            lsp_types::NumberOrString::Number(v) => {
-                                content
-                                    .push(&v.to_string())
-                                    .text_color(dim_color.clone());
-                            }
-                            lsp_types::NumberOrString::String(v) => {
-                                content
-                                    .push(v.as_str())
-                                    .text_color(dim_color.clone());
-                            }
+        let diagnostics: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .flat_map(|diagnostics| diagnostics.iter())
+            .filter(|diag| {
+                let (start, end) = diag.range;
+                start <= offset && (offset < end || start == end)
+            })
+            .map(|diag| &diag.diagnostic)
+            .collect();
+        if diagnostics.is_empty() {
+            self.diagnostic_content = None;
+            return;
+        }
+
+        // Get the dim foreground color for extra information about the error that is typically
+        // not significant
+        let dim_color = config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone();
+        let link_color = config.get_color_unchecked(LapceTheme::EDITOR_LINK).clone();
+
+        // Build up the text for all the diagnostics
+        let mut content = RichTextBuilder::new();
+        content.set_line_height(1.5);
+        for diagnostic in diagnostics {
+            content.push(&diagnostic.message);
+
+            // If there's a source of the message (ex: it came from rustc or rust-analyzer)
+            // then include that
+            if let Some(source) = &diagnostic.source {
+                content.push(" ");
+                content.push(source).text_color(dim_color.clone());
+
+                // If there's an available error code then include that
+                if let Some(code) = &diagnostic.code {
+                    // TODO: code description field has information like documentation on the
+                    // error code which could be useful to provide as a link
+
+                    // formatted as  diagsource(code)
+                    content.push("(").text_color(dim_color.clone());
+                    match code {
+                        lsp_types::NumberOrString::Number(v) => {
+                            content
+                                .push(&v.to_string())
+                                .text_color(dim_color.clone());
+                        }
+                        lsp_types::NumberOrString::String(v) => {
+                            content.push(v.as_str()).text_color(dim_color.clone());
                         }
-                        content.push(")").text_color(dim_color.clone());
                     }
+                    content.push(")").text_color(dim_color.clone());
                 }
+            }
 
-                // TODO: The Related information field has data that can give better insight into
-                // the causes of the error
-                // (ex: The place where a variable was moved into when the 'main' error is at where
-                // you tried using it. This would work the best with some way to link to files)
-
-                content.push("\n");
+            // The related information gives better insight into the causes of
+            // the error, like the place where a variable was moved when the
+            // error is where it's used after that. Each of them links to its
+            // location.
+            for related in diagnostic.related_information.iter().flatten() {
+                let location = &related.location;
+                let path = path_from_url(&location.uri);
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.to_string_lossy().to_string());
+                content.push("\n  ");
+                content
+                    .push(&format!(
+                        "{file_name}:{}:{}",
+                        location.range.start.line + 1,
+                        location.range.start.character + 1
+                    ))
+                    .text_color(link_color.clone())
+                    .underline(true)
+                    .link_target(related_location_target(location));
+                content.push(": ");
+                content.push(&related.message);
             }
 
-            self.diagnostic_content = Some(content.build());
-        } else {
-            self.diagnostic_content = None;
+            content.push("\n");
         }
+
+        self.diagnostic_content = Some(content.build());
     }
 }
 
+/// The link target of a location related to a diagnostic, which is its uri
+/// with the line and character it starts at as the fragment
+fn related_location_target(location: &Location) -> String {
+    let mut url = location.uri.clone();
+    url.set_fragment(Some(&format!(
+        "{},{}",
+        location.range.start.line, location.range.start.character
+    )));
+    url.to_string()
+}
+
+/// The location a link to a location related to a diagnostic jumps to
+pub fn related_location(target: &str) -> Option<EditorLocation<Position>> {
+    let mut url = Url::parse(target).ok()?;
+    let (line, character) = url.fragment()?.split_once(',')?;
+    let position = Position::new(line.parse().ok()?, character.parse().ok()?);
+    url.set_fragment(None);
+    Some(EditorLocation {
+        path: path_from_url(&url),
+        position: Some(position),
+        scroll_offset: None,
+        history: None,
+    })
+}
+
 impl Default for HoverData {
     fn default() -> Self {
         Self::new()
@@ -240,14 +338,68 @@ fn parse_hover_resp(hover: lsp_types::Hover, config: &LapceConfig) -> Vec<Conten
                 .flatten()
                 .collect()
         }
-        HoverContents::Markup(content) => match content.kind {
-            MarkupKind::PlainText => {
-                let mut builder = RichTextBuilder::new();
-                builder.set_line_height(1.5);
-                builder.push(&content.value);
-                vec![Content::Text(builder.build())]
-            }
-            MarkupKind::Markdown => parse_markdown(&content.value, 1.5, config),
-        },
+        HoverContents::Markup(content) => {
+            parse_documentation(&Documentation::MarkupContent(content), config)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::{Location, Position, Range, Url};
+
+    use super::{related_location, related_location_target};
+    use crate::hover::HoverData;
+
+    #[test]
+    fn test_related_location_link() {
+        let location = Location::new(
+            Url::parse("file:///src/main.rs").unwrap(),
+            Range::new(Position::new(11, 4), Position::new(11, 9)),
+        );
+        let target = related_location_target(&location);
+        assert_eq!(target, "file:///src/main.rs#11,4");
+        let location = related_location(&target).unwrap();
+        assert_eq!(location.path, std::path::PathBuf::from("/src/main.rs"));
+        assert_eq!(location.position, Some(Position::new(11, 4)));
+
+        assert!(related_location("https://lapce.dev").is_none());
+        assert!(related_location("file:///src/main.rs#usage").is_none());
+    }
+
+    #[test]
+    fn test_hover_placement() {
+        let tab_height = 800.0;
+        let max_height = 300.0;
+
+        // Enough room below the line
+        let (top, bottom) = (100.0, 120.0);
+        let height =
+            HoverData::available_height(top, bottom, tab_height, max_height);
+        assert_eq!(height, 300.0);
+        assert_eq!(HoverData::origin_y(top, bottom, height, tab_height), 120.0);
+
+        // Not enough room below, so it flips above the line
+        let (top, bottom) = (700.0, 720.0);
+        let height =
+            HoverData::available_height(top, bottom, tab_height, max_height);
+        assert_eq!(height, 300.0);
+        assert_eq!(HoverData::origin_y(top, bottom, height, tab_height), 400.0);
+
+        // A short hover still fits below
+        assert_eq!(HoverData::origin_y(top, bottom, 50.0, tab_height), 720.0);
+
+        // Not enough room on either side, it takes the roomier one and scrolls
+        let tab_height = 400.0;
+        let (top, bottom) = (150.0, 170.0);
+        let height =
+            HoverData::available_height(top, bottom, tab_height, max_height);
+        assert_eq!(height, 230.0);
+        assert_eq!(HoverData::origin_y(top, bottom, height, tab_height), 170.0);
+        let (top, bottom) = (250.0, 270.0);
+        let height =
+            HoverData::available_height(top, bottom, tab_height, max_height);
+        assert_eq!(height, 250.0);
+        assert_eq!(HoverData::origin_y(top, bottom, height, tab_height), 0.0);
     }
 }
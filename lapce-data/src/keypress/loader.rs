@@ -90,7 +90,7 @@ pub fn finalize(
         (map, command_map)
     }
 
-    fn get_keymap(
+    pub(super) fn get_keymap(
         toml_keymap: &toml_edit::Table,
         modal: bool,
     ) -> Result<Option<KeyMap>> {
@@ -1,12 +1,16 @@
 #![allow(clippy::module_inception)]
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use druid::{
     piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
     Command, Env, EventCtx, ExtEventSink, KbKey, KeyEvent, Modifiers, PaintCtx,
-    Point, Rect, RenderContext, Size, Target,
+    Point, Rect, RenderContext, Size, Target, TimerToken,
 };
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 use indexmap::IndexMap;
@@ -15,20 +19,29 @@
 
 mod keypress;
 mod loader;
+mod portable;
+mod sequence;
 
 pub use keypress::KeyPress;
+pub use portable::KeymapImport;
+pub use sequence::KeyPressSequence;
 
 use keypress::Key;
 
 use crate::{
     command::{
-        lapce_internal_commands, CommandExecuted, CommandKind, LapceCommand,
-        LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
+        lapce_internal_commands, CommandExecuted, CommandKind, CommandMacros,
+        LapceCommand, LapceUICommand, LapceWorkbenchCommand, UnknownCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceConfig, LapceTheme},
     keypress::loader::KeyMapLoader,
 };
 
+/// The key of the `when` clause predicate that matches the language of the
+/// focused editor, like `editorLangId == markdown`
+pub const LANGUAGE_CONTEXT: &str = "editorLangId";
+
 const DEFAULT_KEYMAPS_COMMON: &str =
     include_str!("../../../defaults/keymaps-common.toml");
 const DEFAULT_KEYMAPS_MACOS: &str =
@@ -40,6 +53,8 @@
 enum KeymapMatch {
     Full(String),
     Multiple(Vec<String>),
+    /// The keys are a complete keymap, but also the prefix of longer ones
+    Ambiguous(Vec<String>),
     Prefix,
     None,
 }
@@ -85,6 +100,18 @@ pub enum Alignment {
 }
 
 impl KeyMap {
+    /// Whether the keymap only applies to editors of a specific language
+    fn is_language_specific(&self) -> bool {
+        self.when.as_deref().map_or(false, |when| {
+            when.split(['&', '|']).any(|condition| {
+                matches!(
+                    Predicate::parse(condition),
+                    Predicate::Value { key, .. } if key == LANGUAGE_CONTEXT
+                )
+            })
+        })
+    }
+
     /// Returns the first [`KeyPress`] of this [`KeyMap`] that can be converted into
     /// [`druid::HotKey`].
     pub fn hotkey(&self) -> Option<druid::HotKey> {
@@ -132,6 +159,10 @@ pub fn paint(
 pub trait KeyPressFocus {
     fn get_mode(&self) -> Mode;
     fn check_condition(&self, condition: &str) -> bool;
+    /// Check a `key == value` predicate of a `when` clause
+    fn check_context_value(&self, _key: &str, _value: &str) -> bool {
+        false
+    }
     fn run_command(
         &mut self,
         ctx: &mut EventCtx,
@@ -169,8 +200,12 @@ fn from(ev: &'a druid::MouseEvent) -> Self {
 
 #[derive(Clone)]
 pub struct KeyPressData {
-    pending_keypress: Vec<KeyPress>,
+    pending_keypress: KeyPressSequence,
+    sequence_timeout: Duration,
+    sequence_timer: TimerToken,
     pub commands: Arc<IndexMap<String, LapceCommand>>,
+    /// The command macros of the settings, which run like the commands
+    pub macros: Arc<CommandMacros>,
     pub keymaps: Arc<IndexMap<Vec<KeyPress>, Vec<KeyMap>>>,
     pub command_keymaps: Arc<IndexMap<String, Vec<KeyMap>>>,
 
@@ -189,9 +224,13 @@ impl KeyPressData {
     pub fn new(config: &LapceConfig, event_sink: ExtEventSink) -> Self {
         let (keymaps, command_keymaps) =
             Self::get_keymaps(config).unwrap_or((IndexMap::new(), IndexMap::new()));
+        let commands = lapce_internal_commands();
         let mut keypress = Self {
-            pending_keypress: Vec::new(),
-            commands: Arc::new(lapce_internal_commands()),
+            pending_keypress: KeyPressSequence::default(),
+            sequence_timeout: Self::sequence_timeout(config),
+            sequence_timer: TimerToken::INVALID,
+            macros: Arc::new(Self::get_macros(config, &commands)),
+            commands: Arc::new(commands),
             keymaps: Arc::new(keymaps),
             command_keymaps: Arc::new(command_keymaps),
             commands_with_keymap: Arc::new(Vec::new()),
@@ -207,6 +246,8 @@ pub fn new(config: &LapceConfig, event_sink: ExtEventSink) -> Self {
     }
 
     pub fn update_keymaps(&mut self, config: &LapceConfig) {
+        self.sequence_timeout = Self::sequence_timeout(config);
+        self.macros = Arc::new(Self::get_macros(config, &self.commands));
         if let Ok((new_keymaps, new_command_keymaps)) = Self::get_keymaps(config) {
             self.keymaps = Arc::new(new_keymaps);
             self.command_keymaps = Arc::new(new_command_keymaps);
@@ -214,10 +255,83 @@ pub fn update_keymaps(&mut self, config: &LapceConfig) {
         }
     }
 
+    fn get_macros(
+        config: &LapceConfig,
+        commands: &IndexMap<String, LapceCommand>,
+    ) -> CommandMacros {
+        let (macros, errors) = CommandMacros::new(&config.macros, commands);
+        for err in errors {
+            log::error!("Failed to load command macro: {err}");
+        }
+        macros
+    }
+
+    fn sequence_timeout(config: &LapceConfig) -> Duration {
+        Duration::from_millis(config.core.key_sequence_timeout)
+    }
+
+    /// The keys pressed so far of a keymap that takes more than one key
+    pub fn pending_sequence(&self) -> &KeyPressSequence {
+        &self.pending_keypress
+    }
+
+    /// The keymaps that the pending key sequence can still be completed to
+    pub fn pending_keymaps(&self) -> Vec<&KeyMap> {
+        if self.pending_keypress.is_empty() {
+            return Vec::new();
+        }
+
+        let keys: Vec<KeyPress> = self
+            .pending_keypress
+            .keys()
+            .iter()
+            .map(KeyPress::to_lowercase)
+            .collect();
+        self.keymaps
+            .get(&keys)
+            .map(|keymaps| {
+                keymaps
+                    .iter()
+                    .filter(|keymap| keymap.key.len() > keys.len())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The key to show next to the command in a menu, which can only show a
+    /// single key press. The keys of the modes other than insert mode are left
+    /// out, since they don't run the command while typing.
+    pub fn command_hotkey(&self, command: &str) -> Option<druid::HotKey> {
+        Self::menu_hotkey(self.command_keymaps.get(command)?)
+    }
+
+    fn menu_hotkey(keymaps: &[KeyMap]) -> Option<druid::HotKey> {
+        keymaps
+            .iter()
+            .filter(|keymap| {
+                keymap.key.len() == 1
+                    && (keymap.modes.is_empty()
+                        || keymap.modes.contains(Modes::INSERT))
+            })
+            .find_map(KeyMap::hotkey)
+    }
+
     fn load_commands(&mut self) {
         let mut commands_with_keymap = Vec::new();
         let mut commands_without_keymap = Vec::new();
-        for (_, keymaps) in self.command_keymaps.iter() {
+        for (command, keymaps) in self.command_keymaps.iter() {
+            if self.commands.get(command).is_none()
+                && self.macros.get(command).is_none()
+            {
+                let known = self
+                    .commands
+                    .keys()
+                    .map(|id| id.as_str())
+                    .chain(self.macros.iter().map(|m| m.name.as_str()));
+                let err = UnknownCommand::new(command, known);
+                log::warn!("Keymap runs an {err}");
+                continue;
+            }
             for keymap in keymaps.iter() {
                 if self.commands.get(&keymap.command).is_some() {
                     commands_with_keymap.push(keymap.clone());
@@ -263,10 +377,28 @@ fn run_command<T: KeyPressFocus>(
                 | CommandKind::Edit(_)
                 | CommandKind::Focus(_)
                 | CommandKind::MotionMode(_)
-                | CommandKind::MultiSelection(_) => {
+                | CommandKind::MultiSelection(_)
+                | CommandKind::Signature(_)
+                | CommandKind::SelectionRange(_)
+                | CommandKind::SourceControl(_)
+                | CommandKind::Fold(_) => {
                     focus.run_command(ctx, cmd, count, mods, env)
                 }
             }
+        } else if self.macros.get(command).is_some() {
+            if !focus.focus_only() {
+                ctx.submit_command(Command::new(
+                    LAPCE_COMMAND,
+                    LapceCommand {
+                        kind: CommandKind::Workbench(
+                            LapceWorkbenchCommand::RunMacro,
+                        ),
+                        data: Some(serde_json::json!(command)),
+                    },
+                    Target::Auto,
+                ));
+            }
+            CommandExecuted::Yes
         } else {
             CommandExecuted::No
         }
@@ -369,46 +501,62 @@ pub fn key_down<'a, T: KeyPressFocus>(
         };
         let mods = keypress.mods;
 
+        let now = Instant::now();
+        if self.pending_keypress.is_expired(now, self.sequence_timeout) {
+            self.finish_sequence(ctx, focus, env);
+        }
+
         let mode = focus.get_mode();
         if self.handle_count(focus, &keypress) {
             return false;
         }
 
-        self.pending_keypress.push(keypress.clone());
+        self.pending_keypress.push(keypress.clone(), now);
 
-        let keymatch = self.match_keymap(&self.pending_keypress, focus);
+        let keymatch =
+            Self::match_keymap(&self.keymaps, self.pending_keypress.keys(), focus);
         match keymatch {
             KeymapMatch::Full(command) => {
-                self.pending_keypress.clear();
+                self.pending_keypress.take();
                 let count = self.count.take();
                 self.run_command(ctx, &command, count, mods, focus, env);
                 return true;
             }
             KeymapMatch::Multiple(commands) => {
-                self.pending_keypress.clear();
+                self.pending_keypress.take();
                 let count = self.count.take();
-                for command in commands {
-                    if self.run_command(ctx, &command, count, mods, focus, env)
-                        == CommandExecuted::Yes
-                    {
-                        return true;
-                    }
-                }
-
+                self.run_commands(ctx, &commands, count, mods, focus, env);
                 return true;
             }
-            KeymapMatch::Prefix => {
+            KeymapMatch::Ambiguous(_) | KeymapMatch::Prefix => {
                 // Here pending_keypress contains only a prefix of some keymap, so let's keep
-                // collecting key presses.
+                // collecting key presses. A complete keymap that is also a prefix only
+                // runs once the sequence times out.
+                if !self.sequence_timeout.is_zero() {
+                    self.sequence_timer =
+                        ctx.request_timer(self.sequence_timeout, None);
+                }
                 return false;
             }
             KeymapMatch::None => {
-                self.pending_keypress.clear();
+                let mut keys = self.pending_keypress.take();
+                keys.pop();
+                if let KeymapMatch::Ambiguous(commands) =
+                    Self::match_keymap(&self.keymaps, &keys, focus)
+                {
+                    // The new key didn't continue the sequence, so the keymap it
+                    // completed so far wins, and the new key starts over
+                    let count = self.count.take();
+                    let mods = keys.last().map(|k| k.mods).unwrap_or(mods);
+                    self.run_commands(ctx, &commands, count, mods, focus, env);
+                    return self.key_down(ctx, event, focus, env);
+                }
+
                 if focus.get_mode() == Mode::Insert {
                     let mut keypress = keypress.clone();
                     keypress.mods.set(Modifiers::SHIFT, false);
                     if let KeymapMatch::Full(command) =
-                        self.match_keymap(&[keypress], focus)
+                        Self::match_keymap(&self.keymaps, &[keypress], focus)
                     {
                         if let Some(cmd) = self.commands.get(&command) {
                             if let CommandKind::Move(_) = cmd.kind {
@@ -449,15 +597,73 @@ pub fn key_down<'a, T: KeyPressFocus>(
         false
     }
 
-    fn match_keymap<T: KeyPressFocus>(
+    /// Give up on the pending key sequence once its timeout passed, running the
+    /// keymap it completes if it's also the prefix of longer ones. Returns
+    /// `false` if `token` isn't the timer of the pending sequence.
+    pub fn sequence_timer_fired<T: KeyPressFocus>(
+        &mut self,
+        ctx: &mut EventCtx,
+        token: TimerToken,
+        focus: &mut T,
+        env: &Env,
+    ) -> bool {
+        if token != self.sequence_timer {
+            return false;
+        }
+        self.sequence_timer = TimerToken::INVALID;
+
+        if self
+            .pending_keypress
+            .is_expired(Instant::now(), self.sequence_timeout)
+        {
+            self.finish_sequence(ctx, focus, env);
+        }
+        true
+    }
+
+    fn finish_sequence<T: KeyPressFocus>(
+        &mut self,
+        ctx: &mut EventCtx,
+        focus: &mut T,
+        env: &Env,
+    ) {
+        let keys = self.pending_keypress.take();
+        let count = self.count.take();
+        if let KeymapMatch::Ambiguous(commands) =
+            Self::match_keymap(&self.keymaps, &keys, focus)
+        {
+            let mods = keys.last().map(|k| k.mods).unwrap_or_else(Modifiers::empty);
+            self.run_commands(ctx, &commands, count, mods, focus, env);
+        }
+    }
+
+    /// Run the commands until one of them executes
+    fn run_commands<T: KeyPressFocus>(
         &self,
+        ctx: &mut EventCtx,
+        commands: &[String],
+        count: Option<usize>,
+        mods: Modifiers,
+        focus: &mut T,
+        env: &Env,
+    ) {
+        for command in commands {
+            if self.run_command(ctx, command, count, mods, focus, env)
+                == CommandExecuted::Yes
+            {
+                return;
+            }
+        }
+    }
+
+    fn match_keymap<T: KeyPressFocus>(
+        keymaps: &IndexMap<Vec<KeyPress>, Vec<KeyMap>>,
         keypresses: &[KeyPress],
         check: &T,
     ) -> KeymapMatch {
         let keypresses: Vec<KeyPress> =
             keypresses.iter().map(KeyPress::to_lowercase).collect();
-        let matches = self
-            .keymaps
+        let matches: Vec<&KeyMap> = keymaps
             .get(&keypresses)
             .map(|keymaps| {
                 keymaps
@@ -485,16 +691,24 @@ fn match_keymap<T: KeyPressFocus>(
             })
             .unwrap_or_else(Vec::new);
 
+        let mut full: Vec<&KeyMap> = matches
+            .iter()
+            .rev()
+            .filter(|m| m.key == keypresses)
+            .copied()
+            .collect();
+        // A keymap for the language of the editor wins over a generic one
+        full.sort_by_key(|m| !m.is_language_specific());
+        let full: Vec<String> = full.iter().map(|m| m.command.clone()).collect();
+
         if matches.is_empty() {
             KeymapMatch::None
         } else if matches.len() == 1 && matches[0].key == keypresses {
             KeymapMatch::Full(matches[0].command.clone())
-        } else if matches.len() > 1
-            && matches.iter().filter(|m| m.key != keypresses).count() == 0
-        {
-            KeymapMatch::Multiple(
-                matches.iter().rev().map(|m| m.command.clone()).collect(),
-            )
+        } else if full.len() == matches.len() {
+            KeymapMatch::Multiple(full)
+        } else if !full.is_empty() {
+            KeymapMatch::Ambiguous(full)
         } else {
             KeymapMatch::Prefix
         }
@@ -505,11 +719,13 @@ fn check_one_condition<T: KeyPressFocus>(
             condition: &str,
             check: &T,
         ) -> bool {
-            let trimmed = condition.trim();
-            if let Some(stripped) = trimmed.strip_prefix('!') {
-                !check.check_condition(stripped)
-            } else {
-                check.check_condition(trimmed)
+            match Predicate::parse(condition) {
+                Predicate::Value { key, value, equal } => {
+                    check.check_context_value(key, value) == equal
+                }
+                Predicate::Flag { name, negated } => {
+                    check.check_condition(name) != negated
+                }
             }
         }
 
@@ -674,6 +890,42 @@ pub fn file() -> Option<PathBuf> {
         LapceConfig::keymaps_file()
     }
 
+    /// The keymaps the user added or overrode in their keymaps file
+    pub fn user_keymaps() -> Result<Vec<KeyMap>> {
+        let path = Self::file().ok_or_else(|| anyhow!("no keymaps file"))?;
+        match std::fs::read_to_string(path) {
+            Ok(content) => portable::keymaps_from_str(&content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The keymaps of the user as a standalone file, that can be imported on
+    /// another machine
+    pub fn export_keymaps() -> Result<String> {
+        Ok(portable::keymaps_to_string(&Self::user_keymaps()?))
+    }
+
+    /// Compare the keymaps of a file exported by [`Self::export_keymaps`] with the
+    /// keymaps of the user
+    pub fn import_keymaps(content: &str) -> Result<KeymapImport> {
+        let imported = portable::keymaps_from_str(content)?;
+        Ok(KeymapImport::new(&Self::user_keymaps()?, &imported))
+    }
+
+    /// Merge the imported keymaps into the keymaps file of the user, keeping
+    /// the rest of the file as it is
+    pub fn apply_keymap_import(import: &KeymapImport) -> Result<()> {
+        let path = Self::file().ok_or_else(|| anyhow!("no keymaps file"))?;
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        std::fs::write(path, portable::apply_import_to_str(&content, import)?)?;
+        Ok(())
+    }
+
     #[allow(clippy::type_complexity)]
     fn get_keymaps(
         config: &LapceConfig,
@@ -775,14 +1027,62 @@ fn parse_first(condition: &'a str) -> Self {
     }
 }
 
+/// A single condition of a `when` clause
+#[derive(Debug, PartialEq, Eq)]
+enum Predicate<'a> {
+    /// `key == value`, or `key != value` when not `equal`
+    Value {
+        key: &'a str,
+        value: &'a str,
+        equal: bool,
+    },
+    /// A condition checked by the focus, like `editor_focus`, or `!editor_focus`
+    /// when `negated`
+    Flag { name: &'a str, negated: bool },
+}
+
+impl<'a> Predicate<'a> {
+    /// A `!` before a `key == value` negates the whole of it
+    fn parse(condition: &'a str) -> Self {
+        let condition = condition.trim();
+        let (negated, condition) = match condition.strip_prefix('!') {
+            Some(rest) if !rest.starts_with('=') => (true, rest.trim_start()),
+            _ => (false, condition),
+        };
+        if let Some((key, value)) = condition.split_once("==") {
+            Predicate::Value {
+                key: key.trim(),
+                value: value.trim(),
+                equal: !negated,
+            }
+        } else if let Some((key, value)) = condition.split_once("!=") {
+            Predicate::Value {
+                key: key.trim(),
+                value: value.trim(),
+                equal: negated,
+            }
+        } else {
+            Predicate::Flag {
+                name: condition,
+                negated,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use indexmap::IndexMap;
     use lapce_core::mode::Mode;
 
-    use crate::keypress::{Condition, KeyPressData, KeyPressFocus};
+    use crate::keypress::{
+        loader::KeyMapLoader, Condition, KeyPress, KeyPressData, KeyPressFocus,
+        KeymapMatch, Predicate, LANGUAGE_CONTEXT,
+    };
 
     struct MockFocus {
         accepted_conditions: &'static [&'static str],
+        language: &'static str,
     }
 
     impl KeyPressFocus for MockFocus {
@@ -790,6 +1090,10 @@ fn check_condition(&self, condition: &str) -> bool {
             self.accepted_conditions.contains(&condition)
         }
 
+        fn check_context_value(&self, key: &str, value: &str) -> bool {
+            key == LANGUAGE_CONTEXT && value == self.language
+        }
+
         fn get_mode(&self) -> Mode {
             unimplemented!()
         }
@@ -830,10 +1134,135 @@ fn test_parse() {
         );
     }
 
+    #[test]
+    fn test_parse_predicate() {
+        assert_eq!(
+            Predicate::parse(" editor_focus "),
+            Predicate::Flag {
+                name: "editor_focus",
+                negated: false
+            }
+        );
+        assert_eq!(
+            Predicate::parse("! editor_focus"),
+            Predicate::Flag {
+                name: "editor_focus",
+                negated: true
+            }
+        );
+        let value = |equal| Predicate::Value {
+            key: "a",
+            value: "b",
+            equal,
+        };
+        assert_eq!(Predicate::parse("a == b"), value(true));
+        assert_eq!(Predicate::parse("a != b"), value(false));
+        assert_eq!(Predicate::parse("!a == b"), value(false));
+        assert_eq!(Predicate::parse("!a != b"), value(true));
+    }
+
+    #[test]
+    fn test_ambiguous_sequence() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+k"
+command = "delete_to_end_of_line"
+
+[[keymaps]]
+key = "ctrl+k ctrl+s"
+command = "save_all"
+
+[[keymaps]]
+key = "ctrl+k ctrl+w"
+command = "close_all"
+when = "foo"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(keymaps, false).unwrap();
+        let (keymaps, _) = loader.finalize();
+
+        let focus = MockFocus {
+            accepted_conditions: &[],
+            language: "rust",
+        };
+        let matches = |keys: &str| {
+            KeyPressData::match_keymap(&keymaps, &KeyPress::parse(keys), &focus)
+        };
+
+        // A complete keymap that's also a prefix waits for the sequence to time out
+        assert_eq!(
+            matches("ctrl+k"),
+            KeymapMatch::Ambiguous(vec!["delete_to_end_of_line".to_string()])
+        );
+        assert_eq!(
+            matches("ctrl+k ctrl+s"),
+            KeymapMatch::Full("save_all".to_string())
+        );
+        assert_eq!(matches("ctrl+k ctrl+x"), KeymapMatch::None);
+
+        // The longer keymap doesn't apply, so there's nothing to wait for
+        assert_eq!(matches("ctrl+k ctrl+w"), KeymapMatch::None);
+        let keymaps: IndexMap<_, _> = keymaps
+            .into_iter()
+            .map(|(keys, keymaps)| {
+                let keymaps: Vec<_> = keymaps
+                    .into_iter()
+                    .filter(|keymap| keymap.command != "save_all")
+                    .collect();
+                (keys, keymaps)
+            })
+            .collect();
+        assert_eq!(
+            KeyPressData::match_keymap(&keymaps, &KeyPress::parse("ctrl+k"), &focus),
+            KeymapMatch::Full("delete_to_end_of_line".to_string())
+        );
+    }
+
+    #[test]
+    fn test_language_specific_keymap_wins() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+enter"
+command = "toggle_checkbox"
+when = "editor_focus && editorLangId == markdown"
+
+[[keymaps]]
+key = "ctrl+enter"
+command = "new_line_below"
+when = "editor_focus"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(keymaps, false).unwrap();
+        let (keymaps, _) = loader.finalize();
+        let keypresses = KeyPress::parse("ctrl+enter");
+
+        let markdown = MockFocus {
+            accepted_conditions: &["editor_focus"],
+            language: "markdown",
+        };
+        assert_eq!(
+            KeyPressData::match_keymap(&keymaps, &keypresses, &markdown),
+            KeymapMatch::Multiple(vec![
+                "toggle_checkbox".to_string(),
+                "new_line_below".to_string()
+            ])
+        );
+
+        let rust = MockFocus {
+            accepted_conditions: &["editor_focus"],
+            language: "rust",
+        };
+        assert_eq!(
+            KeyPressData::match_keymap(&keymaps, &keypresses, &rust),
+            KeymapMatch::Full("new_line_below".to_string())
+        );
+    }
+
     #[test]
     fn test_check_condition() {
         let focus = MockFocus {
             accepted_conditions: &["foo", "bar"],
+            language: "markdown",
         };
 
         let test_cases = [
@@ -850,6 +1279,11 @@ fn test_check_condition() {
             ("foo && bar || baz", true),
             ("foo && bar && baz", false),
             ("foo && bar && !baz", true),
+            ("editorLangId == markdown", true),
+            ("editorLangId == rust", false),
+            ("editorLangId != rust", true),
+            ("foo && editorLangId == markdown", true),
+            ("baz || editorLangId == rust", false),
         ];
 
         for (condition, should_accept) in test_cases.into_iter() {
@@ -860,4 +1294,54 @@ fn test_check_condition() {
             );
         }
     }
+
+    #[test]
+    fn test_menu_hotkey() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+k ctrl+0"
+command = "fold_all"
+
+[[keymaps]]
+key = "z c"
+command = "fold"
+mode = "n"
+
+[[keymaps]]
+key = "ctrl+shift+k"
+command = "fold"
+
+[[keymaps]]
+key = "u"
+command = "undo"
+mode = "n"
+
+[[keymaps]]
+key = "ctrl+z"
+command = "undo"
+mode = "i"
+
+[[keymaps]]
+key = "ctrl+z"
+command = "-undo"
+mode = "i"
+
+[[keymaps]]
+key = "ctrl+y"
+command = "redo"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(keymaps, true).unwrap();
+        let (_, command_keymaps) = loader.finalize();
+        let hotkey =
+            |command: &str| KeyPressData::menu_hotkey(&command_keymaps[command]);
+
+        assert_eq!(hotkey("fold"), KeyPress::parse("ctrl+shift+k")[0].hotkey());
+        // A sequence of keys can't be shown in a menu
+        assert_eq!(hotkey("fold_all"), None);
+        // The key of normal mode doesn't do anything while typing, and the key
+        // of insert mode was unbound
+        assert_eq!(hotkey("undo"), None);
+        assert_eq!(hotkey("redo"), KeyPress::parse("ctrl+y")[0].hotkey());
+    }
 }
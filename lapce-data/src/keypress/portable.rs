@@ -0,0 +1,317 @@
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+
+use crate::keypress::{loader::KeyMapLoader, KeyMap};
+
+/// Read the keymaps of a keymap file, keeping the ones for modal editing too
+pub fn keymaps_from_str(s: &str) -> Result<Vec<KeyMap>> {
+    let document: toml_edit::Document = s.parse()?;
+    let toml_keymaps = document
+        .get("keymaps")
+        .and_then(|v| v.as_array_of_tables())
+        .ok_or_else(|| anyhow!("no keymaps"))?;
+
+    let mut keymaps = Vec::new();
+    for toml_keymap in toml_keymaps {
+        match KeyMapLoader::get_keymap(toml_keymap, true) {
+            Ok(Some(keymap)) => keymaps.push(keymap),
+            Ok(None) => {}
+            Err(err) => log::error!("Could not parse keymap: {err}"),
+        }
+    }
+    Ok(keymaps)
+}
+
+/// Write the keymaps as a standalone keymap file
+pub fn keymaps_to_string(keymaps: &[KeyMap]) -> String {
+    let mut array = toml_edit::ArrayOfTables::new();
+    for keymap in keymaps {
+        array.push(keymap_table(keymap));
+    }
+
+    let mut document = toml_edit::Document::new();
+    document.insert("keymaps", toml_edit::Item::ArrayOfTables(array));
+    document.to_string()
+}
+
+/// Apply the import to the content of a keymap file, changing the keys of the
+/// overridden keymaps where they are and adding the new ones at the end, so
+/// that the comments and the entries that can't be read are kept
+pub fn apply_import_to_str(s: &str, import: &KeymapImport) -> Result<String> {
+    let mut document: toml_edit::Document = s.parse()?;
+    let array = document
+        .entry("keymaps")
+        .or_insert_with(|| toml_edit::Item::ArrayOfTables(Default::default()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| anyhow!("keymaps isn't an array of tables"))?;
+
+    for (old, new) in &import.overrides {
+        let table = array.iter_mut().find(|table| {
+            matches!(
+                KeyMapLoader::get_keymap(table, true),
+                Ok(Some(keymap)) if &keymap == old
+            )
+        });
+        match table {
+            Some(table) => {
+                table["key"] = toml_edit::value(keys_string(new));
+            }
+            None => array.push(keymap_table(new)),
+        }
+    }
+    for keymap in &import.additions {
+        array.push(keymap_table(keymap));
+    }
+    Ok(document.to_string())
+}
+
+fn keymap_table(keymap: &KeyMap) -> toml_edit::Table {
+    let mut table = toml_edit::Table::new();
+    table.insert("key", toml_edit::value(keys_string(keymap)));
+    table.insert("command", toml_edit::value(keymap.command.clone()));
+    if !keymap.modes.is_empty() {
+        table.insert("mode", toml_edit::value(keymap.modes.to_string()));
+    }
+    if let Some(when) = keymap.when.as_ref() {
+        table.insert("when", toml_edit::value(when.clone()));
+    }
+    table
+}
+
+fn keys_string(keymap: &KeyMap) -> String {
+    keymap.key.iter().map(|k| k.to_string()).join(" ")
+}
+
+/// What importing a keymap file changes in the keymaps of the user
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeymapImport {
+    /// Keymaps for commands the user had no keymap for
+    pub additions: Vec<KeyMap>,
+    /// The keymaps of the user that are replaced, and what they're replaced with
+    pub overrides: Vec<(KeyMap, KeyMap)>,
+    /// The keymaps of the user bound to the same keys as an imported keymap for
+    /// another command, and that imported keymap
+    pub conflicts: Vec<(KeyMap, KeyMap)>,
+}
+
+impl KeymapImport {
+    pub fn new(current: &[KeyMap], imported: &[KeyMap]) -> Self {
+        let mut import = Self::default();
+        let mut overridden = vec![false; current.len()];
+        for keymap in imported {
+            if current.contains(keymap) {
+                continue;
+            }
+
+            let replaced = current
+                .iter()
+                .enumerate()
+                .position(|(i, c)| !overridden[i] && is_same_binding(c, keymap));
+            match replaced {
+                Some(i) => {
+                    overridden[i] = true;
+                    import.overrides.push((current[i].clone(), keymap.clone()));
+                }
+                None => import.additions.push(keymap.clone()),
+            }
+
+            for (i, c) in current.iter().enumerate() {
+                if !overridden[i]
+                    && c.key == keymap.key
+                    && c.modes == keymap.modes
+                    && c.when == keymap.when
+                    && c.command != keymap.command
+                {
+                    import.conflicts.push((c.clone(), keymap.clone()));
+                }
+            }
+        }
+        import
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.additions.is_empty() && self.overrides.is_empty()
+    }
+
+    /// The keymaps of the user once the import is applied
+    pub fn apply(&self, current: &[KeyMap]) -> Vec<KeyMap> {
+        let mut keymaps: Vec<KeyMap> = current
+            .iter()
+            .map(|keymap| {
+                self.overrides
+                    .iter()
+                    .find(|(old, _)| old == keymap)
+                    .map(|(_, new)| new.clone())
+                    .unwrap_or_else(|| keymap.clone())
+            })
+            .collect();
+        keymaps.extend(self.additions.iter().cloned());
+        keymaps
+    }
+
+    /// A description of the changes, to show before the import is applied
+    pub fn summary(&self) -> String {
+        let keys = keys_string;
+        let mut lines = Vec::new();
+        for keymap in &self.additions {
+            lines.push(format!("Add {} for {}", keys(keymap), keymap.command));
+        }
+        for (old, new) in &self.overrides {
+            lines.push(format!(
+                "Change {} from {} to {}",
+                new.command,
+                keys(old),
+                keys(new)
+            ));
+        }
+        for (old, new) in &self.conflicts {
+            lines.push(format!("{} is also bound to {}", keys(new), old.command));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Whether both keymaps bind the same command in the same context, whatever the
+/// keys
+fn is_same_binding(a: &KeyMap, b: &KeyMap) -> bool {
+    a.command == b.command && a.modes == b.modes && a.when == b.when
+}
+
+#[cfg(test)]
+mod test {
+    use crate::keypress::portable::{
+        apply_import_to_str, keymaps_from_str, keymaps_to_string, KeymapImport,
+    };
+
+    const USER_KEYMAPS: &str = r#"
+[[keymaps]]
+key = "ctrl+k ctrl+s"
+command = "save_all"
+
+[[keymaps]]
+key = "ctrl+w l"
+command = "right"
+mode = "nv"
+
+[[keymaps]]
+key = "ctrl+shift+p"
+command = "-palette.command"
+
+[[keymaps]]
+key = "ctrl+enter"
+command = "toggle_checkbox"
+when = "editor_focus && editorLangId == markdown"
+    "#;
+
+    #[test]
+    fn test_round_trip() {
+        let keymaps = keymaps_from_str(USER_KEYMAPS).unwrap();
+        assert_eq!(keymaps.len(), 4);
+
+        let exported = keymaps_to_string(&keymaps);
+        let current = Vec::new();
+        let imported = keymaps_from_str(&exported).unwrap();
+        let import = KeymapImport::new(&current, &imported);
+        assert_eq!(import.additions.len(), 4);
+        assert!(import.overrides.is_empty());
+        assert!(import.conflicts.is_empty());
+
+        assert_eq!(import.apply(&current), keymaps);
+    }
+
+    #[test]
+    fn test_additions_and_overrides() {
+        let current = keymaps_from_str(USER_KEYMAPS).unwrap();
+        let imported = keymaps_from_str(
+            r#"
+[[keymaps]]
+key = "ctrl+k ctrl+s"
+command = "save_all"
+
+[[keymaps]]
+key = "ctrl+k s"
+command = "right"
+mode = "nv"
+
+[[keymaps]]
+key = "ctrl+k ctrl+s"
+command = "save"
+when = "editor_focus"
+
+[[keymaps]]
+key = "ctrl+enter"
+command = "new_line_below"
+when = "editor_focus && editorLangId == markdown"
+            "#,
+        )
+        .unwrap();
+
+        let import = KeymapImport::new(&current, &imported);
+        assert_eq!(
+            import.overrides,
+            vec![(current[1].clone(), imported[1].clone())]
+        );
+        assert_eq!(
+            import.additions,
+            vec![imported[2].clone(), imported[3].clone()]
+        );
+        assert_eq!(
+            import.conflicts,
+            vec![(current[3].clone(), imported[3].clone())]
+        );
+
+        let keymaps = import.apply(&current);
+        assert_eq!(
+            keymaps,
+            vec![
+                current[0].clone(),
+                imported[1].clone(),
+                current[2].clone(),
+                current[3].clone(),
+                imported[2].clone(),
+                imported[3].clone(),
+            ]
+        );
+        assert!(KeymapImport::new(&keymaps, &imported).is_empty());
+    }
+
+    #[test]
+    fn test_apply_import_in_place() {
+        let content = r#"
+# My keymaps
+[[keymaps]]
+key = "ctrl+w l" # like vim
+command = "right"
+mode = "nv"
+
+[[keymaps]]
+keys = "ctrl+q"
+command = "some_command_with_a_typo"
+"#;
+        let current = keymaps_from_str(content).unwrap();
+        let imported = keymaps_from_str(
+            r#"
+[[keymaps]]
+key = "ctrl+k s"
+command = "right"
+mode = "nv"
+
+[[keymaps]]
+key = "ctrl+k ctrl+s"
+command = "save_all"
+            "#,
+        )
+        .unwrap();
+        let import = KeymapImport::new(&current, &imported);
+
+        let applied = apply_import_to_str(content, &import).unwrap();
+        assert!(applied.contains("# My keymaps"));
+        assert!(applied.contains("some_command_with_a_typo"));
+        assert!(!applied.contains("ctrl+w l"));
+        assert_eq!(keymaps_from_str(&applied).unwrap(), import.apply(&current));
+
+        // A file without keymaps yet gets them
+        let applied = apply_import_to_str("", &import).unwrap();
+        assert_eq!(keymaps_from_str(&applied).unwrap(), imported);
+    }
+}
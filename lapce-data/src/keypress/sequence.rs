@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use crate::keypress::KeyPress;
+
+/// The keys pressed so far of a keymap that takes more than one key, like
+/// `ctrl+k ctrl+s`
+#[derive(Clone, Debug, Default)]
+pub struct KeyPressSequence {
+    keys: Vec<KeyPress>,
+    last_pressed: Option<Instant>,
+}
+
+impl KeyPressSequence {
+    pub fn keys(&self) -> &[KeyPress] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The keys pressed so far, followed by an ellipsis to show more are
+    /// expected, or `None` if no sequence is pending
+    pub fn label(&self) -> Option<String> {
+        if self.keys.is_empty() {
+            return None;
+        }
+
+        let keys: Vec<String> = self.keys.iter().map(KeyPress::to_string).collect();
+        Some(format!("{} …", keys.join(" ")))
+    }
+
+    pub(super) fn push(&mut self, keypress: KeyPress, now: Instant) {
+        self.keys.push(keypress);
+        self.last_pressed = Some(now);
+    }
+
+    pub(super) fn take(&mut self) -> Vec<KeyPress> {
+        self.last_pressed = None;
+        std::mem::take(&mut self.keys)
+    }
+
+    /// Whether no key was pressed for `timeout` since the last key of the
+    /// sequence, after which the sequence is given up on. A zero `timeout` never
+    /// expires.
+    pub fn is_expired(&self, now: Instant, timeout: Duration) -> bool {
+        if timeout.is_zero() {
+            return false;
+        }
+
+        match self.last_pressed {
+            Some(last_pressed) => {
+                now.saturating_duration_since(last_pressed) >= timeout
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::keypress::{sequence::KeyPressSequence, KeyPress};
+
+    #[test]
+    fn test_sequence_expiry() {
+        let timeout = Duration::from_millis(1000);
+        let start = Instant::now();
+
+        let mut sequence = KeyPressSequence::default();
+        assert!(!sequence.is_expired(start + timeout, timeout));
+
+        for keypress in KeyPress::parse("ctrl+k") {
+            sequence.push(keypress, start);
+        }
+        assert!(!sequence.is_expired(start + Duration::from_millis(999), timeout));
+        assert!(sequence.is_expired(start + timeout, timeout));
+
+        // Every key press restarts the timeout
+        for keypress in KeyPress::parse("ctrl+s") {
+            sequence.push(keypress, start + Duration::from_millis(600));
+        }
+        assert!(!sequence.is_expired(start + timeout, timeout));
+        assert!(sequence.is_expired(start + Duration::from_millis(1600), timeout));
+
+        assert_eq!(sequence.take(), KeyPress::parse("ctrl+k ctrl+s"));
+        assert!(sequence.is_empty());
+        assert!(!sequence.is_expired(start + Duration::from_millis(1600), timeout));
+
+        sequence.push(KeyPress::parse("ctrl+k").remove(0), start);
+        assert!(!sequence.is_expired(start + timeout, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_sequence_label() {
+        let mut sequence = KeyPressSequence::default();
+        assert_eq!(sequence.label(), None);
+
+        let now = Instant::now();
+        for keypress in KeyPress::parse("ctrl+k ctrl+s") {
+            sequence.push(keypress, now);
+        }
+        assert_eq!(sequence.label().as_deref(), Some("Ctrl+k Ctrl+s …"));
+
+        sequence.take();
+        assert_eq!(sequence.label(), None);
+    }
+}
@@ -3,30 +3,37 @@
 pub mod about;
 pub mod alert;
 pub mod atomic_soft_tabs;
+pub mod cli;
 pub mod command;
 pub mod completion;
 pub mod config;
+pub mod config_schema;
 pub mod container;
 pub mod data;
 pub mod db;
 pub mod document;
+pub mod document_color;
 pub mod dropdown;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod format;
 pub mod history;
 pub mod hover;
 pub mod images;
 pub mod keypress;
 pub mod list;
+pub mod local_history;
 pub mod markdown;
 pub mod menu;
 pub mod palette;
 pub mod panel;
+pub mod peek;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
 pub mod proxy;
+pub mod proxy_connection;
 pub mod rename;
 pub mod rich_text;
 pub mod search;
@@ -37,5 +44,7 @@
 pub mod split;
 mod svg;
 pub mod terminal;
+pub mod terminal_layout;
+pub mod terminal_link;
 pub mod title;
 pub mod update;
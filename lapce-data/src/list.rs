@@ -1,6 +1,8 @@
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
-use druid::{Command, Data, EventCtx, Target, WidgetId};
+use druid::{
+    piet::Svg, Color, Command, Data, EventCtx, FontWeight, Target, WidgetId,
+};
 use lapce_core::{command::FocusCommand, movement::Movement};
 
 use crate::{
@@ -8,8 +10,13 @@
         CommandExecuted, CommandKind, LapceCommand, LapceUICommand, LAPCE_UI_COMMAND,
     },
     config::{GetConfig, LapceConfig},
+    rich_text::{RichText, RichTextBuilder},
 };
 
+/// The number of items painted past each edge of the visible area, so that the ones
+/// scrolled into view are already there
+const OVERSCAN: usize = 2;
+
 // Note: when adding fields to this, make sure to think whether they need to be added to the `same`
 // implementation
 /// Note: all `T` are going to be required by the UI code to implement `ListPaint<D>`  
@@ -78,15 +85,27 @@ pub fn line_height(&self) -> usize {
     /// The maximum number of items in the list that can be displayed  
     /// This is limited by `max_displayed_items` *or* by the number of items
     pub fn max_display_count(&self) -> usize {
-        let mut count = 0;
-        for _ in self.items.iter() {
-            count += 1;
-            if count >= self.max_displayed_items {
-                return self.max_displayed_items;
-            }
-        }
+        self.items.len().min(self.max_displayed_items)
+    }
 
-        count
+    /// The items to paint for the area of the list between `y0` and `y1`, with a few
+    /// more on each side. Only these are touched, however many items there are.
+    pub fn visible_range(&self, y0: f64, y1: f64) -> Range<usize> {
+        let line_height = self.line_height().max(1) as f64;
+        let start = (y0.max(0.0) / line_height).floor() as usize;
+        let end = (y1.max(0.0) / line_height).ceil() as usize;
+        let start = start.saturating_sub(OVERSCAN).min(self.items.len());
+        let end = (end + OVERSCAN).min(self.items.len()).max(start);
+        start..end
+    }
+
+    /// The index of the item at `y` in the list, if there's one
+    pub fn index_of_y(&self, y: f64) -> Option<usize> {
+        if y < 0.0 {
+            return None;
+        }
+        let index = (y / self.line_height().max(1) as f64).floor() as usize;
+        (index < self.items.len()).then_some(index)
     }
 
     pub fn clear_items(&mut self) {
@@ -187,9 +206,11 @@ impl<T: Clone + PartialEq + 'static, D: Data> Data for ListData<T, D> {
     fn same(&self, other: &Self) -> bool {
         // We don't compare the held Config, because that should be updated whenever
         // the widget is used
+        // The items are compared by pointer first, so that large lists which didn't
+        // change aren't compared item by item
 
         self.parent == other.parent
-            && self.items == other.items
+            && (self.items.ptr_eq(&other.items) || self.items == other.items)
             && self.data.same(&other.data)
             && self.selected_index.same(&other.selected_index)
             && self.max_displayed_items.same(&other.max_displayed_items)
@@ -201,3 +222,267 @@ fn get_config(&self) -> &LapceConfig {
         &self.config
     }
 }
+
+/// The content of a row of a list, split in columns. The label follows the icon, and
+/// the detail is aligned to the right edge of the row.
+#[derive(Clone)]
+pub struct ListRow {
+    pub icon: Option<ListIcon>,
+    pub label: ListText,
+    /// Secondary text, like the path of a file, which is dimmed
+    pub detail: Option<ListText>,
+}
+
+#[derive(Clone)]
+pub struct ListIcon {
+    pub svg: Svg,
+    pub color: Option<Color>,
+}
+
+/// The text of a column, with the characters that matched the filter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListText {
+    pub text: String,
+    /// The byte offsets of the characters that matched the filter
+    pub indices: Vec<usize>,
+    /// The color of the text, instead of the one of its column
+    pub color: Option<Color>,
+}
+
+impl ListText {
+    pub fn new(text: String, indices: Vec<usize>) -> Self {
+        Self {
+            text,
+            indices,
+            color: None,
+        }
+    }
+
+    /// The styled text, with the characters that matched the filter in bold and in
+    /// `focus_color`
+    pub fn rich_text(&self, focus_color: &Color) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        let mut adder = builder.push(&self.text);
+        if let Some(color) = self.color.as_ref() {
+            adder.text_color(color.clone());
+        }
+        for &i in &self.indices {
+            let Some(c) = self.text.get(i..).and_then(|s| s.chars().next()) else {
+                continue;
+            };
+            let mut adder = builder.add_attributes_for_range(i..i + c.len_utf8());
+            adder.weight(FontWeight::BOLD);
+            if self.color.is_none() {
+                adder.text_color(focus_color.clone());
+            }
+        }
+        builder.build()
+    }
+
+    /// Shorten the text to fit in `max_width` by replacing its middle with an
+    /// ellipsis, which keeps both ends of a path visible. The matched characters
+    /// that are cut out aren't highlighted anymore.
+    pub fn truncate_middle(
+        &self,
+        max_width: f64,
+        width_of: impl Fn(&str) -> f64,
+    ) -> ListText {
+        const ELLIPSIS: char = '…';
+
+        if width_of(&self.text) <= max_width {
+            return self.clone();
+        }
+
+        // The text keeping the first `kept / 2` and the last `kept - kept / 2`
+        // characters, with the byte offset where each of these parts starts
+        let chars: Vec<(usize, char)> = self.text.char_indices().collect();
+        let shortened = |kept: usize| {
+            let head = kept / 2;
+            let tail = kept - head;
+            let head_end = chars.get(head).map(|(i, _)| *i).unwrap_or(0);
+            let tail_start = chars[chars.len() - tail..]
+                .first()
+                .map(|(i, _)| *i)
+                .unwrap_or(self.text.len());
+            let text = format!(
+                "{}{ELLIPSIS}{}",
+                &self.text[..head_end],
+                &self.text[tail_start..]
+            );
+            (text, head_end, tail_start)
+        };
+
+        // The most characters that can be kept, since the width grows with them
+        let (mut low, mut high) = (0, chars.len().saturating_sub(1));
+        while low < high {
+            let mid = (low + high + 1) / 2;
+            if width_of(&shortened(mid).0) <= max_width {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        let (text, head_end, tail_start) = shortened(low);
+        let shift = head_end + ELLIPSIS.len_utf8();
+        let indices = self
+            .indices
+            .iter()
+            .filter_map(|&i| {
+                if i < head_end {
+                    Some(i)
+                } else if i >= tail_start {
+                    Some(i - tail_start + shift)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        ListText {
+            text,
+            indices,
+            color: self.color.clone(),
+        }
+    }
+}
+
+/// Where the columns of a list row go
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListColumns {
+    pub label_x: f64,
+    /// Where the detail starts and the width it has, which ends at the right edge
+    /// of the row. `None` when the label leaves no room for it.
+    pub detail: Option<(f64, f64)>,
+}
+
+impl ListColumns {
+    /// The space at the edges of the row and between the columns
+    pub const PADDING: f64 = 5.0;
+    /// The detail isn't shown when it would be narrower than this
+    pub const MIN_DETAIL_WIDTH: f64 = 30.0;
+
+    /// Lay out a row of `width`, with the label after an icon of `icon_width` and
+    /// the detail aligned to the right. The detail is given the room left by the
+    /// label when it's too wide for it.
+    pub fn new(
+        width: f64,
+        icon_width: f64,
+        label_width: f64,
+        detail_width: Option<f64>,
+    ) -> Self {
+        let label_x = icon_width + Self::PADDING;
+        let detail = detail_width.and_then(|detail_width| {
+            let right = width - Self::PADDING;
+            let available = right - (label_x + label_width + Self::PADDING * 2.0);
+            if available < detail_width.min(Self::MIN_DETAIL_WIDTH) {
+                return None;
+            }
+            let detail_width = detail_width.min(available);
+            Some((right - detail_width, detail_width))
+        });
+        Self { label_x, detail }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use druid::WidgetId;
+
+    use super::{ListColumns, ListData, ListText};
+    use crate::config::LapceConfig;
+
+    fn list(len: usize) -> ListData<usize, ()> {
+        let mut list =
+            ListData::new(Arc::new(LapceConfig::default()), WidgetId::next(), ());
+        list.line_height = Some(20);
+        list.items = (0..len).collect();
+        list
+    }
+
+    #[test]
+    fn test_visible_range_is_bounded() {
+        let list = list(100_000);
+        // However many items there are, only the ones on screen and the overscan are painted
+        assert_eq!(list.visible_range(0.0, 300.0), 0..17);
+        assert_eq!(list.visible_range(1_000_010.0, 1_000_310.0), 49_998..50_018);
+        assert_eq!(
+            list.visible_range(1_999_700.0, 2_000_000.0),
+            99_983..100_000
+        );
+        for y in [0.0, 123_456.7, 1_500_000.0, 1_999_990.0] {
+            assert!(list.visible_range(y, y + 300.0).len() <= 300 / 20 + 1 + 2 * 2);
+        }
+        // Past the end of the list there's nothing to paint
+        assert!(list.visible_range(3_000_000.0, 3_000_300.0).is_empty());
+    }
+
+    #[test]
+    fn test_index_of_y() {
+        let list = list(3);
+        assert_eq!(list.index_of_y(0.0), Some(0));
+        assert_eq!(list.index_of_y(59.0), Some(2));
+        assert_eq!(list.index_of_y(60.0), None);
+        assert_eq!(list.index_of_y(-1.0), None);
+        assert_eq!(list.max_display_count(), 3);
+    }
+
+    /// The width of text where every character is 10 wide
+    fn width_of(text: &str) -> f64 {
+        text.chars().count() as f64 * 10.0
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        let text = ListText::new("src/editor/view.rs".to_string(), vec![0, 11, 17]);
+
+        // The text is kept as is when it fits
+        assert_eq!(text.truncate_middle(180.0, width_of), text);
+
+        // The end of the text is kept over its start
+        let truncated = text.truncate_middle(100.0, width_of);
+        assert_eq!(truncated.text, "src/…ew.rs");
+        // The matches that were cut out aren't highlighted, and the ones after the
+        // ellipsis move with the text
+        assert_eq!(truncated.indices, vec![0, 11]);
+
+        let truncated = text.truncate_middle(70.0, width_of);
+        assert_eq!(truncated.text, "src….rs");
+        assert_eq!(truncated.indices, vec![0, 8]);
+
+        // The ellipsis is left when nothing fits
+        assert_eq!(text.truncate_middle(0.0, width_of).text, "…");
+
+        // Multibyte characters aren't split
+        let text = ListText::new("äöü/ß.rs".to_string(), vec![2, 7]);
+        let truncated = text.truncate_middle(60.0, width_of);
+        assert_eq!(truncated.text, "äö….rs");
+        assert_eq!(truncated.indices, vec![2]);
+    }
+
+    #[test]
+    fn test_columns_alignment() {
+        // The detail is aligned to the right edge, past the icon and label
+        let columns = ListColumns::new(300.0, 20.0, 100.0, Some(80.0));
+        assert_eq!(columns.label_x, 25.0);
+        assert_eq!(columns.detail, Some((215.0, 80.0)));
+
+        // Without an icon, the label starts at the padding
+        let columns = ListColumns::new(300.0, 0.0, 100.0, None);
+        assert_eq!(columns.label_x, 5.0);
+        assert_eq!(columns.detail, None);
+
+        // A narrower row shrinks the detail to the room left by the label
+        let columns = ListColumns::new(200.0, 20.0, 100.0, Some(80.0));
+        assert_eq!(columns.detail, Some((135.0, 60.0)));
+
+        // Until there's too little room for it to be useful
+        let columns = ListColumns::new(160.0, 20.0, 100.0, Some(80.0));
+        assert_eq!(columns.detail, None);
+
+        // A short detail is shown as long as it fits whole
+        let columns = ListColumns::new(160.0, 20.0, 100.0, Some(20.0));
+        assert_eq!(columns.detail, Some((135.0, 20.0)));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::TimeZone;
+
+/// The history version a snapshot of the local history is loaded as, for the
+/// editor to show its diff with the buffer
+pub const LOCAL_HISTORY_VERSION: &str = "local";
+
+/// The content of a file at some point in time, kept independently of source
+/// control
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalHistorySnapshot {
+    pub path: PathBuf,
+    /// Seconds since the unix epoch
+    pub time: u64,
+    pub content: String,
+    /// How many snapshots of the file are kept
+    pub max_entries: usize,
+    /// How old in seconds snapshots can get before they're removed, 0 keeps them
+    /// regardless of their age
+    pub max_age: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The times of the snapshots that should be removed, out of the times of all
+/// the snapshots of a file sorted from the oldest to the newest
+pub fn pruned_snapshots(
+    times: &[u64],
+    now: u64,
+    max_entries: usize,
+    max_age: u64,
+) -> Vec<u64> {
+    let excess = times.len().saturating_sub(max_entries);
+    times
+        .iter()
+        .enumerate()
+        .filter(|(i, time)| {
+            *i < excess || (max_age > 0 && now.saturating_sub(**time) > max_age)
+        })
+        .map(|(_, time)| *time)
+        .collect()
+}
+
+/// How long ago the snapshot was taken, like "5 minutes ago"
+pub fn snapshot_age(now: u64, time: u64) -> String {
+    let secs = now.saturating_sub(time);
+    let (count, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+/// The local date and time the snapshot was taken at
+pub fn snapshot_date(time: u64) -> String {
+    chrono::Local
+        .timestamp_opt(time as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_excess_and_old_snapshots() {
+        let times = [100, 200, 300, 400, 500];
+
+        assert!(pruned_snapshots(&times, 500, 10, 0).is_empty());
+        assert_eq!(pruned_snapshots(&times, 500, 3, 0), vec![100, 200]);
+        assert_eq!(pruned_snapshots(&times, 500, 10, 250), vec![100, 200]);
+        assert_eq!(pruned_snapshots(&times, 500, 4, 350), vec![100]);
+        assert_eq!(pruned_snapshots(&times, 500, 0, 0), times.to_vec());
+    }
+
+    #[test]
+    fn describes_snapshot_age() {
+        assert_eq!(snapshot_age(1000, 1000), "just now");
+        assert_eq!(snapshot_age(1000, 1100), "just now");
+        assert_eq!(snapshot_age(1000, 880), "2 minutes ago");
+        assert_eq!(snapshot_age(3600, 0), "1 hour ago");
+        assert_eq!(snapshot_age(86400 * 3, 0), "3 days ago");
+    }
+}
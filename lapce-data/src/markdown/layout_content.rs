@@ -2,7 +2,7 @@
 
 use druid::{
     kurbo::Line,
-    piet::{InterpolationMode, PietText},
+    piet::{InterpolationMode, PietText, TextStorage},
     ArcStr, Color, Env, EventCtx, ExtEventSink, FontDescriptor, PaintCtx, Point,
     Rect, RenderContext, Size, TextLayout, UpdateCtx, Vec2,
 };
@@ -148,6 +148,37 @@ pub fn size(&self, images: &ImageCache, config: &LapceConfig) -> Size {
         }
     }
 
+    /// The target of the link under the point, which is relative to the origin
+    /// the content is drawn at
+    pub fn link_at(&self, point: Point) -> Option<&str> {
+        match self {
+            LayoutContent::Text(layout) => text_link_at(layout, point),
+            _ => None,
+        }
+    }
+
+    /// The vertical offset of the anchor, relative to the origin the content
+    /// is drawn at
+    pub fn anchor_y(&self, anchor: &str) -> Option<f64> {
+        let layout = match self {
+            LayoutContent::Text(layout) => layout,
+            _ => return None,
+        };
+        let text = layout.text()?;
+        let offset = text.anchor_offset(anchor)?;
+        let end = offset
+            + text.as_str()[offset..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+        let y = layout
+            .rects_for_range(offset..end)
+            .first()
+            .map(|rect| rect.y0)
+            .unwrap_or_else(|| layout.point_for_text_position(offset).y);
+        Some(y)
+    }
+
     /// `width` is only used for images currently, and should probably be the same as
     /// `set_wrap_width`'s call
     pub fn draw(
@@ -239,6 +270,23 @@ fn get_image_size(image: &Image, max_width: f64) -> Size {
 }
 
 /// Utility function to make constructing a vector of `LayoutContent` from a vector of `Content`
+/// The target of the link under the point of the text, which is relative to
+/// the origin the text is drawn at
+pub fn text_link_at(layout: &TextLayout<RichText>, point: Point) -> Option<&str> {
+    let text = layout.text()?;
+    let offset = layout.text_position_for_point(point);
+    // the position is the closest cursor position, which is past the
+    // character under the point when it's on its right half
+    let (range, target) = text
+        .link_at(offset)
+        .or_else(|| text.link_at(offset.checked_sub(1)?))?;
+    layout
+        .rects_for_range(range)
+        .iter()
+        .any(|rect| rect.contains(point))
+        .then_some(target)
+}
+
 pub fn layouts_from_contents<'a>(
     ctx: &mut EventCtx,
     data: &mut LapceTabData,
@@ -1,12 +1,12 @@
-use std::str::FromStr;
+use std::{ops::Range, str::FromStr};
 
-use druid::{FontStyle, FontWeight};
+use druid::{Color, FontStyle, FontWeight};
 use lapce_core::{
     language::LapceLanguage,
     syntax::{highlight::HighlightIssue, Syntax},
 };
 use lapce_xi_rope::Rope;
-use lsp_types::{Documentation, MarkedString, MarkupKind};
+use lsp_types::{Documentation, MarkedString, MarkupKind, Url};
 use pulldown_cmark::{CodeBlockKind, Tag};
 use smallvec::SmallVec;
 
@@ -15,7 +15,10 @@
     rich_text::{AttributesAdder, RichText, RichTextBuilder},
 };
 
+use self::table::MarkdownTable;
+
 pub mod layout_content;
+pub mod table;
 
 #[derive(Clone)]
 pub enum Content {
@@ -55,7 +58,7 @@ pub fn parse_markdown(
     line_height: f64,
     config: &LapceConfig,
 ) -> Vec<Content> {
-    use pulldown_cmark::{CowStr, Event, Options, Parser};
+    use pulldown_cmark::{Event, Options, Parser};
 
     let mut res = Vec::new();
 
@@ -67,6 +70,8 @@ pub fn parse_markdown(
     let mut pos = 0;
 
     let mut tag_stack: SmallVec<[(usize, Tag); 4]> = SmallVec::new();
+    // The table whose cells are being collected, laid out when it ends
+    let mut table: Option<MarkdownTable> = None;
 
     let parser = Parser::new_ext(
         text,
@@ -76,7 +81,11 @@ pub fn parse_markdown(
             | Options::ENABLE_TASKLISTS
             | Options::ENABLE_HEADING_ATTRIBUTES,
     );
-    let mut last_text = CowStr::from("");
+    // The text of the code block being parsed, highlighted as a whole when
+    // the block ends
+    let mut code_text = String::new();
+    // The text of the heading being parsed, which its anchor is made from
+    let mut heading_text = String::new();
     // Whether we should add a newline on the next entry
     // This is used so that we don't emit newlines at the very end of the generation
     let mut add_newline = false;
@@ -91,8 +100,26 @@ pub fn parse_markdown(
 
         match event {
             Event::Start(tag) => {
+                if let Some(table) = table.as_mut() {
+                    match tag {
+                        Tag::TableHead | Tag::TableRow => {
+                            table.start_row();
+                            continue;
+                        }
+                        Tag::TableCell => {
+                            table.start_cell();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                if let Tag::Table(alignments) = &tag {
+                    table = Some(MarkdownTable::new(alignments.clone()));
+                }
                 tag_stack.push((pos, tag));
             }
+            Event::End(Tag::TableHead | Tag::TableRow | Tag::TableCell)
+                if table.is_some() => {}
             Event::End(end_tag) => {
                 if let Some((start_offset, tag)) = tag_stack.pop() {
                     if end_tag != tag {
@@ -100,6 +127,17 @@ pub fn parse_markdown(
                         continue;
                     }
 
+                    if let (Tag::Table(_), Some(table)) = (&tag, table.take()) {
+                        let layout = table.layout();
+                        builder.push(&layout.text);
+                        builder
+                            .add_attributes_for_range(
+                                pos + layout.header.start..pos + layout.header.end,
+                            )
+                            .weight(FontWeight::BOLD);
+                        pos += layout.text.len();
+                    }
+
                     add_attribute_for_tag(
                         &tag,
                         builder.add_attributes_for_range(start_offset..pos),
@@ -123,9 +161,18 @@ pub fn parse_markdown(
                                 &mut builder,
                                 config,
                                 language,
-                                &last_text,
+                                &code_text,
                                 start_offset,
                             );
+                            code_text.clear();
+                            builder_dirty = true;
+                        }
+                        Tag::Heading(_, id, _) => {
+                            let anchor = id
+                                .map(|id| id.to_string())
+                                .unwrap_or_else(|| heading_anchor(&heading_text));
+                            builder.add_anchor(anchor, start_offset);
+                            heading_text.clear();
                             builder_dirty = true;
                         }
                         Tag::Image(_link_type, dest, title) => {
@@ -160,12 +207,34 @@ pub fn parse_markdown(
                         continue;
                     }
                 }
+                if let Some(table) = table.as_mut() {
+                    table.push_text(&text);
+                    continue;
+                }
+                if let Some((_, Tag::CodeBlock(_))) = tag_stack.last() {
+                    code_text.push_str(&text);
+                }
+                if in_heading(&tag_stack) {
+                    heading_text.push_str(&text);
+                }
                 builder.push(&text);
                 pos += text.len();
-                last_text = text;
                 builder_dirty = true;
             }
+            Event::Code(text) | Event::Html(text) if table.is_some() => {
+                if let Some(table) = table.as_mut() {
+                    table.push_text(&text);
+                }
+            }
+            Event::HardBreak | Event::SoftBreak if table.is_some() => {
+                if let Some(table) = table.as_mut() {
+                    table.push_text(" ");
+                }
+            }
             Event::Code(text) => {
+                if in_heading(&tag_stack) {
+                    heading_text.push_str(&text);
+                }
                 builder.push(&text).font_family(config.editor.font_family());
                 pos += text.len();
                 builder_dirty = true;
@@ -196,7 +265,12 @@ pub fn parse_markdown(
             }
             Event::Rule => {}
             Event::FootnoteReference(_text) => {}
-            Event::TaskListMarker(_text) => {}
+            Event::TaskListMarker(checked) => {
+                let marker = if checked { "☑ " } else { "☐ " };
+                builder.push(marker);
+                pos += marker.len();
+                builder_dirty = true;
+            }
         }
     }
 
@@ -215,6 +289,22 @@ pub fn highlight_as_code(
     text: &str,
     start_offset: usize,
 ) {
+    for (range, color) in code_highlights(config, language, text) {
+        builder
+            .add_attributes_for_range(
+                start_offset + range.start..start_offset + range.end,
+            )
+            .text_color(color);
+    }
+}
+
+/// The ranges of the code colored by the theme, none when the language is
+/// unknown or has no grammar
+fn code_highlights(
+    config: &LapceConfig,
+    language: Option<LapceLanguage>,
+    text: &str,
+) -> Vec<(Range<usize>, Color)> {
     let syntax = language
         .map(Syntax::from_language)
         .unwrap_or(Err(HighlightIssue::NotAvailable));
@@ -226,6 +316,7 @@ pub fn highlight_as_code(
         })
         .unwrap_or(None);
 
+    let mut highlights = Vec::new();
     if let Some(styles) = styles {
         for (range, style) in styles.iter() {
             if let Some(color) = style
@@ -233,14 +324,11 @@ pub fn highlight_as_code(
                 .as_ref()
                 .and_then(|fg| config.get_style_color(fg))
             {
-                builder
-                    .add_attributes_for_range(
-                        start_offset + range.start..start_offset + range.end,
-                    )
-                    .text_color(color.clone());
+                highlights.push((range.start..range.end, color.clone()));
             }
         }
     }
+    highlights
 }
 
 pub fn from_marked_string(text: MarkedString, config: &LapceConfig) -> Vec<Content> {
@@ -287,7 +375,7 @@ fn add_attribute_for_tag(
                     .clone(),
             );
         }
-        Tag::CodeBlock(_) => {
+        Tag::CodeBlock(_) | Tag::Table(_) => {
             attrs.font_family(config.editor.font_family());
         }
         Tag::Emphasis => {
@@ -297,11 +385,13 @@ fn add_attribute_for_tag(
             attrs.weight(FontWeight::BOLD);
         }
         // TODO: Strikethrough support
-        Tag::Link(_link_type, _target, _title) => {
-            // TODO: Link support
-            attrs.underline(true).text_color(
-                config.get_color_unchecked(LapceTheme::EDITOR_LINK).clone(),
-            );
+        Tag::Link(_link_type, target, _title) => {
+            attrs
+                .underline(true)
+                .text_color(
+                    config.get_color_unchecked(LapceTheme::EDITOR_LINK).clone(),
+                )
+                .link_target(target.to_string());
         }
         // All other tags are currently ignored
         _ => {}
@@ -316,13 +406,271 @@ fn should_add_newline_after_tag(tag: &Tag) -> bool {
     )
 }
 
+/// Whether the text being parsed is part of a heading
+fn in_heading(tag_stack: &[(usize, Tag)]) -> bool {
+    tag_stack
+        .iter()
+        .any(|(_, tag)| matches!(tag, Tag::Heading(..)))
+}
+
+/// The anchor of a heading without an explicit id, made like the ones of
+/// GitHub, so `## Getting Started` is linked to with `#getting-started`
+fn heading_anchor(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c.to_lowercase().next().unwrap_or(c))
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Where a link in the rendered markdown leads
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkTarget<'a> {
+    /// A heading of the same document, by its anchor
+    Anchor(&'a str),
+    /// A local file, which is only opened once the user confirms it
+    File(&'a str),
+    /// A web page or an email address, opened with the system opener
+    External(&'a str),
+    /// A uri of another scheme, which may be handled by any program, so it is
+    /// only opened once the user confirms it
+    Other(&'a str),
+}
+
+impl<'a> LinkTarget<'a> {
+    /// `None` for relative links, as there is nothing they are relative to
+    pub fn parse(target: &'a str) -> Option<Self> {
+        if let Some(anchor) = target.strip_prefix('#') {
+            return Some(LinkTarget::Anchor(anchor));
+        }
+        let url = Url::parse(target).ok()?;
+        // a windows path like `C:/file` is parsed with the drive as its scheme
+        match url.scheme() {
+            "file" => Some(LinkTarget::File(target)),
+            scheme if scheme.len() == 1 => Some(LinkTarget::File(target)),
+            "http" | "https" | "mailto" => Some(LinkTarget::External(target)),
+            _ => Some(LinkTarget::Other(target)),
+        }
+    }
+}
+
 /// Whether it should skip the text node after a specific tag  
 /// For example, images are skipped because it emits their title as a separate text node.  
 fn should_skip_text_in_tag(tag: &Tag) -> bool {
     matches!(tag, Tag::Image(..))
 }
 
-fn md_language_to_lapce_language(lang: &str) -> Option<LapceLanguage> {
-    // TODO: There are many other names commonly used that should be supported
-    LapceLanguage::from_str(lang).ok()
+/// The language of a fenced code block from its info string, like `rust`,
+/// `rs` or `rust,ignore`
+fn md_language_to_lapce_language(info: &str) -> Option<LapceLanguage> {
+    let lang = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .next()?
+        .trim_matches(|c| c == '{' || c == '}' || c == '.');
+    if lang.is_empty() {
+        return None;
+    }
+    LapceLanguage::from_str(lang)
+        .ok()
+        .or_else(|| LapceLanguage::from_extension(lang))
+}
+
+#[cfg(test)]
+mod test {
+    use druid::{piet::TextStorage, Color};
+    use lapce_core::language::LapceLanguage;
+    use lsp_types::{Documentation, MarkupContent, MarkupKind};
+
+    use crate::{
+        config::LapceConfig,
+        markdown::{
+            code_highlights, heading_anchor, md_language_to_lapce_language,
+            parse_documentation, parse_markdown, Content, LinkTarget,
+        },
+    };
+
+    fn texts(content: &[Content]) -> Vec<&str> {
+        content
+            .iter()
+            .filter_map(|content| match content {
+                Content::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_documentation() {
+        let config = LapceConfig::default();
+        let text = "Prints to the **standard** output.\n\n```\nlet x = 1;\n```";
+
+        // Plain text is shown as it is
+        let plain =
+            parse_documentation(&Documentation::String(text.into()), &config);
+        assert_eq!(vec![text], texts(&plain));
+        let plain = parse_documentation(
+            &Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::PlainText,
+                value: text.into(),
+            }),
+            &config,
+        );
+        assert_eq!(vec![text], texts(&plain));
+
+        // Markdown is rendered, code blocks included
+        let markdown = parse_documentation(
+            &Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: text.into(),
+            }),
+            &config,
+        );
+        assert_eq!(
+            vec!["Prints to the standard output.\nlet x = 1;\n"],
+            texts(&markdown)
+        );
+    }
+
+    #[test]
+    fn test_code_block_language() {
+        assert_eq!(md_language_to_lapce_language(""), None);
+        assert_eq!(md_language_to_lapce_language("not-a-language"), None);
+        // the grammars are only there with their features
+        if let Some(rust) = LapceLanguage::from_name("rust") {
+            assert_eq!(md_language_to_lapce_language("rust"), Some(rust));
+            assert_eq!(md_language_to_lapce_language("rs"), Some(rust));
+            assert_eq!(md_language_to_lapce_language("rust,ignore"), Some(rust));
+            assert_eq!(md_language_to_lapce_language("{.rust}"), Some(rust));
+        }
+    }
+
+    #[test]
+    fn test_highlight_code_block() {
+        let mut config = LapceConfig::default();
+        let keyword = Color::rgb8(0xff, 0, 0);
+        config
+            .color
+            .syntax
+            .insert("keyword".to_string(), keyword.clone());
+        let code = "fn main() {\n    let x = 1;\n}\n";
+
+        // unknown languages are left uncolored
+        assert!(code_highlights(
+            &config,
+            md_language_to_lapce_language("not-a-language"),
+            code
+        )
+        .is_empty());
+
+        if let Some(rust) = md_language_to_lapce_language("rust") {
+            let highlights = code_highlights(&config, Some(rust), code);
+            let keywords: Vec<&str> = highlights
+                .iter()
+                .filter(|(_, color)| color == &keyword)
+                .map(|(range, _)| &code[range.clone()])
+                .collect();
+            assert_eq!(keywords, vec!["fn", "let"]);
+        }
+
+        // the whole block is highlighted, not only its last line
+        let markdown = format!("```rust\n{code}```");
+        assert_eq!(vec![code], texts(&parse_markdown(&markdown, 1.5, &config)));
+    }
+
+    #[test]
+    fn test_parse_task_list() {
+        let config = LapceConfig::default();
+        let text = "- [ ] write tests\n- [x] fix bug";
+        assert_eq!(
+            vec!["☐ write tests\n☑ fix bug\n"],
+            texts(&parse_markdown(text, 1.5, &config))
+        );
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let config = LapceConfig::default();
+        let text = "| a | `b` |\n|---|--:|\n| 1 | 22 |\n| 3 |";
+        assert_eq!(
+            vec!["a │  b\n──┼───\n1 │ 22\n3"],
+            texts(&parse_markdown(text, 1.5, &config))
+        );
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let config = LapceConfig::default();
+        let text = concat!(
+            "# Usage Notes\n\n",
+            "See [docs](https://lapce.dev) and [below](#custom-id).\n\n",
+            "## Custom {#custom-id}"
+        );
+        let content = parse_markdown(text, 1.5, &config);
+        let text = match &content[..] {
+            [Content::Text(text)] => text,
+            _ => panic!("expected a single text"),
+        };
+        let rendered = text.as_str();
+
+        let docs = rendered.find("docs").unwrap();
+        assert_eq!(
+            text.link_at(docs + 1),
+            Some((docs..docs + 4, "https://lapce.dev"))
+        );
+        let below = rendered.find("below").unwrap();
+        assert_eq!(text.link_at(below), Some((below..below + 5, "#custom-id")));
+        assert_eq!(text.link_at(rendered.find("See").unwrap()), None);
+
+        assert_eq!(text.anchor_offset("usage-notes"), Some(0));
+        assert_eq!(
+            text.anchor_offset("custom-id"),
+            Some(rendered.find("Custom").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_heading_anchor() {
+        assert_eq!(heading_anchor("Getting Started"), "getting-started");
+        assert_eq!(heading_anchor(" `from_str` (v0.2) "), "from_str-v02");
+    }
+
+    #[test]
+    fn test_link_target() {
+        assert_eq!(
+            LinkTarget::parse("#usage"),
+            Some(LinkTarget::Anchor("usage"))
+        );
+        assert_eq!(
+            LinkTarget::parse("https://lapce.dev"),
+            Some(LinkTarget::External("https://lapce.dev"))
+        );
+        assert_eq!(
+            LinkTarget::parse("file:///etc/passwd"),
+            Some(LinkTarget::File("file:///etc/passwd"))
+        );
+        assert_eq!(
+            LinkTarget::parse("C:/Windows/notepad.exe"),
+            Some(LinkTarget::File("C:/Windows/notepad.exe"))
+        );
+        assert_eq!(
+            LinkTarget::parse("mailto:someone@lapce.dev"),
+            Some(LinkTarget::External("mailto:someone@lapce.dev"))
+        );
+        assert_eq!(
+            LinkTarget::parse("vscode://extension/run"),
+            Some(LinkTarget::Other("vscode://extension/run"))
+        );
+        assert_eq!(
+            LinkTarget::parse("javascript:alert(1)"),
+            Some(LinkTarget::Other("javascript:alert(1)"))
+        );
+        assert_eq!(LinkTarget::parse("docs/usage.md"), None);
+    }
 }
@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+use pulldown_cmark::Alignment;
+
+/// A pipe table being collected from the markdown events, so it can be laid
+/// out once all of its cells are known
+#[derive(Debug, Default)]
+pub struct MarkdownTable {
+    alignments: Vec<Alignment>,
+    /// The header row first, then the body rows
+    rows: Vec<Vec<String>>,
+}
+
+/// The text of a laid out table, with the columns padded to line up when
+/// drawn in a monospace font
+#[derive(Debug, PartialEq, Eq)]
+pub struct TableLayout {
+    pub text: String,
+    /// The range of the header row in `text`
+    pub header: Range<usize>,
+}
+
+impl MarkdownTable {
+    pub fn new(alignments: Vec<Alignment>) -> Self {
+        Self {
+            alignments,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn start_row(&mut self) {
+        self.rows.push(Vec::new());
+    }
+
+    pub fn start_cell(&mut self) {
+        if self.rows.is_empty() {
+            self.start_row();
+        }
+        if let Some(row) = self.rows.last_mut() {
+            row.push(String::new());
+        }
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        if let Some(cell) = self.rows.last_mut().and_then(|row| row.last_mut()) {
+            cell.push_str(text);
+        }
+    }
+
+    /// Lay out the rows, padding the ragged ones with empty cells
+    pub fn layout(&self) -> TableLayout {
+        let columns = self
+            .rows
+            .iter()
+            .map(|row| row.len())
+            .max()
+            .unwrap_or(0)
+            .max(self.alignments.len());
+        let mut widths = vec![0; columns];
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.trim().chars().count());
+            }
+        }
+
+        let mut text = String::new();
+        let mut header = 0..0;
+        for (row_index, row) in self.rows.iter().enumerate() {
+            if row_index == 1 {
+                let separator: Vec<String> =
+                    widths.iter().map(|width| "─".repeat(*width)).collect();
+                text.push_str(&separator.join("─┼─"));
+                text.push('\n');
+            }
+            let start = text.len();
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(i, width)| {
+                    let cell = row.get(i).map(|cell| cell.trim()).unwrap_or("");
+                    let alignment =
+                        self.alignments.get(i).unwrap_or(&Alignment::None);
+                    align_cell(cell, *width, alignment)
+                })
+                .collect();
+            // the padding of the cells missing from the end of a ragged row
+            let line = cells.join(" │ ");
+            text.push_str(line.trim_end_matches(|c| c == ' ' || c == '│'));
+            if row_index == 0 {
+                header = start..text.len();
+            }
+            text.push('\n');
+        }
+        if text.ends_with('\n') {
+            text.pop();
+        }
+
+        TableLayout { text, header }
+    }
+}
+
+fn align_cell(cell: &str, width: usize, alignment: &Alignment) -> String {
+    let padding = width.saturating_sub(cell.chars().count());
+    match alignment {
+        Alignment::Right => format!("{}{cell}", " ".repeat(padding)),
+        Alignment::Center => format!(
+            "{}{cell}{}",
+            " ".repeat(padding / 2),
+            " ".repeat(padding - padding / 2)
+        ),
+        Alignment::None | Alignment::Left => {
+            format!("{cell}{}", " ".repeat(padding))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pulldown_cmark::Alignment;
+
+    use super::{MarkdownTable, TableLayout};
+
+    fn table(alignments: Vec<Alignment>, rows: &[&[&str]]) -> MarkdownTable {
+        let mut table = MarkdownTable::new(alignments);
+        for row in rows {
+            table.start_row();
+            for cell in row.iter() {
+                table.start_cell();
+                table.push_text(cell);
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn test_table_layout() {
+        let table = table(
+            vec![Alignment::Left, Alignment::Center, Alignment::Right],
+            &[
+                &["Name", "Kind", "Size"],
+                &["lapce", "dir", "12"],
+                &["a", "file", "1024"],
+            ],
+        );
+        let header = "Name  │ Kind │ Size";
+        assert_eq!(
+            table.layout(),
+            TableLayout {
+                text: [
+                    header,
+                    "──────┼──────┼─────",
+                    "lapce │ dir  │   12",
+                    "a     │ file │ 1024",
+                ]
+                .join("\n"),
+                header: 0..header.len(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_ragged_table_layout() {
+        let table = table(
+            vec![Alignment::None, Alignment::None],
+            &[&["a", "b"], &["only one"], &["x", "y", "extra"]],
+        );
+        assert_eq!(
+            table.layout().text,
+            [
+                "a        │ b",
+                "─────────┼───┼──────",
+                "only one",
+                "x        │ y │ extra",
+            ]
+            .join("\n")
+        );
+    }
+}
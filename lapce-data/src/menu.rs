@@ -1,11 +1,61 @@
+use std::collections::HashSet;
+
 use crate::command::LapceCommand;
 
 #[derive(Debug)]
 pub enum MenuKind {
     Item(MenuItem),
+    /// An item which opens a nested menu
+    Submenu {
+        desc: String,
+        items: Vec<MenuKind>,
+    },
+    /// The title of a section of the menu, which can't be chosen
+    Header(String),
     Separator,
 }
 
+impl MenuKind {
+    /// Remove the separators at the edges of a menu or next to other ones, the
+    /// submenus without items and the headers of empty sections, which are left
+    /// behind when a menu is built out of optional items
+    pub fn tidy(items: Vec<MenuKind>) -> Vec<MenuKind> {
+        let mut tidied: Vec<MenuKind> = Vec::with_capacity(items.len());
+        for item in items {
+            let item = match item {
+                MenuKind::Submenu { desc, items } => {
+                    let items = Self::tidy(items);
+                    if items.is_empty() {
+                        continue;
+                    }
+                    MenuKind::Submenu { desc, items }
+                }
+                MenuKind::Separator => {
+                    Self::pop_header(&mut tidied);
+                    if matches!(tidied.last(), None | Some(MenuKind::Separator)) {
+                        continue;
+                    }
+                    MenuKind::Separator
+                }
+                item => item,
+            };
+            tidied.push(item);
+        }
+        Self::pop_header(&mut tidied);
+        if matches!(tidied.last(), Some(MenuKind::Separator)) {
+            tidied.pop();
+        }
+        tidied
+    }
+
+    /// Remove the header at the end of the items, since its section is empty
+    fn pop_header(items: &mut Vec<MenuKind>) {
+        if matches!(items.last(), Some(MenuKind::Header(_))) {
+            items.pop();
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MenuItem {
     pub desc: Option<String>,
@@ -23,3 +73,448 @@ pub fn desc(&self) -> &str {
         })
     }
 }
+
+/// Moving through a menu and its submenus with the keyboard, the way the
+/// native menus do: up and down go over the items that can be chosen, right
+/// opens the submenu under the cursor and left goes back to its parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuNavigation {
+    /// The index of the item under the cursor in the menu and in each opened
+    /// submenu
+    path: Vec<usize>,
+}
+
+impl MenuNavigation {
+    /// Start on the first item of the menu that can be chosen
+    pub fn new(items: &[MenuKind]) -> Self {
+        Self {
+            path: Self::first(items).into_iter().collect(),
+        }
+    }
+
+    /// The item under the cursor
+    pub fn selected<'a>(&self, items: &'a [MenuKind]) -> Option<&'a MenuKind> {
+        let (index, parents) = self.path.split_last()?;
+        Self::level(items, parents)?.get(*index)
+    }
+
+    /// How many submenus are opened
+    pub fn depth(&self) -> usize {
+        self.path.len().saturating_sub(1)
+    }
+
+    pub fn down(&mut self, items: &[MenuKind]) {
+        self.step(items, true);
+    }
+
+    pub fn up(&mut self, items: &[MenuKind]) {
+        self.step(items, false);
+    }
+
+    /// Open the submenu under the cursor, returning whether there was one
+    pub fn enter(&mut self, items: &[MenuKind]) -> bool {
+        let first = match self.selected(items) {
+            Some(MenuKind::Submenu { items, .. }) => Self::first(items),
+            _ => None,
+        };
+        if let Some(first) = first {
+            self.path.push(first);
+        }
+        first.is_some()
+    }
+
+    /// Close the innermost submenu, returning whether one was open
+    pub fn exit(&mut self) -> bool {
+        if self.path.len() > 1 {
+            self.path.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move to the next or previous item that can be chosen, wrapping around
+    /// the ends of the menu
+    fn step(&mut self, items: &[MenuKind], forward: bool) {
+        let Some((&index, parents)) = self.path.split_last() else {
+            return;
+        };
+        let Some(level) = Self::level(items, parents) else {
+            return;
+        };
+        let len = level.len();
+        let next = (1..len)
+            .map(|step| {
+                if forward {
+                    (index + step) % len
+                } else {
+                    (index + len - step) % len
+                }
+            })
+            .find(|i| Self::can_choose(&level[*i]));
+        if let (Some(next), Some(index)) = (next, self.path.last_mut()) {
+            *index = next;
+        }
+    }
+
+    /// The items of the submenu the parents lead to
+    fn level<'a>(
+        items: &'a [MenuKind],
+        parents: &[usize],
+    ) -> Option<&'a [MenuKind]> {
+        parents
+            .iter()
+            .try_fold(items, |items, index| match items.get(*index)? {
+                MenuKind::Submenu { items, .. } => Some(items.as_slice()),
+                _ => None,
+            })
+    }
+
+    fn first(items: &[MenuKind]) -> Option<usize> {
+        items.iter().position(Self::can_choose)
+    }
+
+    fn can_choose(item: &MenuKind) -> bool {
+        match item {
+            MenuKind::Item(item) => item.enabled,
+            MenuKind::Submenu { .. } => true,
+            MenuKind::Header(_) | MenuKind::Separator => false,
+        }
+    }
+}
+
+/// Escape the ampersands of a label that doesn't choose its mnemonic, like the
+/// title of a code action from a language server
+pub fn escape_mnemonic(text: &str) -> String {
+    text.replace('&', "&&")
+}
+
+/// The text of the label without the ampersands, and the index of the
+/// character after the `&` which chooses the mnemonic, if there's one
+fn parse_mnemonic(label: &str) -> (String, Option<usize>) {
+    let mut text = String::with_capacity(label.len());
+    let mut mnemonic = None;
+    let mut len = 0;
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '&' => match chars.next() {
+                Some('&') => '&',
+                Some(c) => {
+                    mnemonic.get_or_insert(len);
+                    c
+                }
+                None => break,
+            },
+            c => c,
+        };
+        text.push(c);
+        len += 1;
+    }
+    (text, mnemonic)
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// The letters of the items of a menu, which activate them when pressed and are
+/// underlined in their labels.
+///
+/// An item chooses its letter with a `&` before it in its label, and `&&` is an
+/// ampersand. If the letter is taken by an item before it, or if the item
+/// doesn't choose one, it gets the first free letter starting a word of its
+/// text, or else the first free letter of the text.
+#[derive(Debug)]
+pub struct Mnemonics {
+    texts: Vec<String>,
+    /// The index in the text of the letter of each item
+    keys: Vec<Option<usize>>,
+}
+
+impl Mnemonics {
+    pub fn new(items: &[MenuKind]) -> Self {
+        let mut texts = Vec::with_capacity(items.len());
+        let mut chosen = Vec::with_capacity(items.len());
+        for item in items {
+            let (text, mnemonic) = match item {
+                MenuKind::Item(item) => parse_mnemonic(item.desc()),
+                MenuKind::Submenu { desc, .. } => parse_mnemonic(desc),
+                // A header can't be chosen, so it doesn't get a letter
+                MenuKind::Header(desc) => (parse_mnemonic(desc).0, None),
+                MenuKind::Separator => (String::new(), None),
+            };
+            texts.push(text);
+            chosen.push(mnemonic);
+        }
+
+        let mut taken = HashSet::new();
+        let mut keys: Vec<Option<usize>> = texts
+            .iter()
+            .zip(chosen.iter())
+            .map(|(text, mnemonic)| {
+                let index = (*mnemonic)?;
+                let c = text.chars().nth(index)?;
+                (c.is_alphanumeric() && taken.insert(fold_case(c))).then_some(index)
+            })
+            .collect();
+
+        for (i, item) in items.iter().enumerate() {
+            if keys[i].is_some()
+                || !matches!(item, MenuKind::Item(_) | MenuKind::Submenu { .. })
+            {
+                continue;
+            }
+            let chars: Vec<char> = texts[i].chars().collect();
+            let word_starts = (0..chars.len())
+                .filter(|&j| j == 0 || !chars[j - 1].is_alphanumeric());
+            keys[i] = word_starts.chain(0..chars.len()).find(|&j| {
+                chars[j].is_alphanumeric() && taken.insert(fold_case(chars[j]))
+            });
+        }
+
+        Self { texts, keys }
+    }
+
+    /// The text of the item, without the ampersands of its label
+    pub fn text(&self, index: usize) -> &str {
+        &self.texts[index]
+    }
+
+    /// The label of the item for the native menu, with a `&` before its letter
+    pub fn label(&self, index: usize) -> String {
+        let key = self.keys[index];
+        let mut label = String::with_capacity(self.texts[index].len() + 1);
+        for (i, c) in self.texts[index].chars().enumerate() {
+            if key == Some(i) || c == '&' {
+                label.push('&');
+            }
+            label.push(c);
+        }
+        label
+    }
+
+    /// The letter which activates the item
+    pub fn key(&self, index: usize) -> Option<char> {
+        let key = self.keys[index]?;
+        self.texts[index].chars().nth(key).map(fold_case)
+    }
+
+    /// The item activated by pressing the key, regardless of its case
+    pub fn item(&self, key: char) -> Option<usize> {
+        let key = fold_case(key);
+        (0..self.keys.len()).find(|&i| self.key(i) == Some(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_core::command::FocusCommand;
+
+    use super::{MenuItem, MenuKind, MenuNavigation, Mnemonics};
+    use crate::command::{CommandKind, LapceCommand};
+
+    fn item(desc: &str) -> MenuKind {
+        MenuKind::Item(MenuItem {
+            desc: Some(desc.to_string()),
+            command: LapceCommand {
+                kind: CommandKind::Focus(FocusCommand::Rename),
+                data: None,
+            },
+            enabled: true,
+        })
+    }
+
+    fn submenu(desc: &str, items: Vec<MenuKind>) -> MenuKind {
+        MenuKind::Submenu {
+            desc: desc.to_string(),
+            items,
+        }
+    }
+
+    fn header(desc: &str) -> MenuKind {
+        MenuKind::Header(desc.to_string())
+    }
+
+    /// The menu as a string, with the submenus in brackets
+    fn describe(items: &[MenuKind]) -> String {
+        items
+            .iter()
+            .map(|item| match item {
+                MenuKind::Item(item) => item.desc().to_string(),
+                MenuKind::Submenu { desc, items } => {
+                    format!("{desc} [{}]", describe(items))
+                }
+                MenuKind::Header(desc) => format!("#{desc}"),
+                MenuKind::Separator => "-".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_tidy_separators() {
+        let menu = MenuKind::tidy(vec![
+            MenuKind::Separator,
+            item("Cut"),
+            MenuKind::Separator,
+            MenuKind::Separator,
+            item("Paste"),
+            MenuKind::Separator,
+        ]);
+        assert_eq!(describe(&menu), "Cut, -, Paste");
+
+        assert!(MenuKind::tidy(vec![MenuKind::Separator]).is_empty());
+    }
+
+    #[test]
+    fn test_tidy_nested() {
+        let menu = MenuKind::tidy(vec![
+            header("Quick Fix"),
+            item("Remove unused import"),
+            MenuKind::Separator,
+            submenu(
+                "Refactor",
+                vec![
+                    header("Extract"),
+                    item("Extract function"),
+                    MenuKind::Separator,
+                    header("Inline"),
+                ],
+            ),
+            submenu("Source Action", vec![MenuKind::Separator]),
+            MenuKind::Separator,
+            header("Other"),
+        ]);
+        // The submenu left empty is removed, and so are the headers of the
+        // empty sections along with the separators before them
+        assert_eq!(
+            describe(&menu),
+            "#Quick Fix, Remove unused import, -, \
+             Refactor [#Extract, Extract function]"
+        );
+    }
+
+    #[test]
+    fn test_navigate_submenus() {
+        let mut disabled = item("Paste");
+        if let MenuKind::Item(item) = &mut disabled {
+            item.enabled = false;
+        }
+        let menu = vec![
+            header("Edit"),
+            item("Cut"),
+            disabled,
+            MenuKind::Separator,
+            submenu(
+                "Folding",
+                vec![item("Fold"), MenuKind::Separator, item("Unfold All")],
+            ),
+            item("Command Palette"),
+        ];
+        let desc = |nav: &MenuNavigation| {
+            describe(std::slice::from_ref(nav.selected(&menu).unwrap()))
+        };
+
+        // The header, the disabled item and the separator are passed over
+        let mut nav = MenuNavigation::new(&menu);
+        assert_eq!(desc(&nav), "Cut");
+        assert!(!nav.enter(&menu));
+        nav.down(&menu);
+        assert_eq!(desc(&nav), "Folding [Fold, -, Unfold All]");
+
+        // Into the submenu, where up and down wrap around its own items
+        assert!(nav.enter(&menu));
+        assert_eq!(nav.depth(), 1);
+        assert_eq!(desc(&nav), "Fold");
+        nav.down(&menu);
+        assert_eq!(desc(&nav), "Unfold All");
+        nav.down(&menu);
+        assert_eq!(desc(&nav), "Fold");
+        nav.up(&menu);
+        assert_eq!(desc(&nav), "Unfold All");
+
+        // And back out of it, to the item which opened it
+        assert!(nav.exit());
+        assert_eq!(nav.depth(), 0);
+        assert_eq!(desc(&nav), "Folding [Fold, -, Unfold All]");
+        assert!(!nav.exit());
+        nav.down(&menu);
+        assert_eq!(desc(&nav), "Command Palette");
+        nav.down(&menu);
+        assert_eq!(desc(&nav), "Cut");
+        nav.up(&menu);
+        assert_eq!(desc(&nav), "Command Palette");
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let menu = vec![
+            item("Go to Definition"),
+            item("Go to Type Definition"),
+            MenuKind::Separator,
+            submenu("&Refactor", vec![item("Rename")]),
+            item("Cut"),
+            item("Copy"),
+            item("Paste"),
+            header("Selection"),
+            item("Save && Close"),
+        ];
+        let mnemonics = Mnemonics::new(&menu);
+        let labels = (0..menu.len())
+            .map(|i| mnemonics.label(i))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                "&Go to Definition",
+                "Go &to Type Definition",
+                "",
+                "&Refactor",
+                "&Cut",
+                "C&opy",
+                "&Paste",
+                "Selection",
+                "&Save && Close",
+            ]
+        );
+        assert_eq!(mnemonics.text(3), "Refactor");
+        assert_eq!(mnemonics.text(8), "Save & Close");
+
+        // The letters are activated regardless of their case
+        assert_eq!(mnemonics.item('R'), Some(3));
+        assert_eq!(mnemonics.item('o'), Some(5));
+        assert_eq!(mnemonics.item('T'), Some(1));
+        assert_eq!(mnemonics.item('x'), None);
+        // The header doesn't take the letter it starts with
+        assert_eq!(mnemonics.key(7), None);
+    }
+
+    #[test]
+    fn test_conflicting_mnemonics() {
+        let menu = vec![
+            item("&Cut"),
+            item("&Copy"),
+            item("&Cut Line"),
+            item("&Close"),
+            item("&&&x"),
+        ];
+        let mnemonics = Mnemonics::new(&menu);
+        let labels = (0..menu.len())
+            .map(|i| mnemonics.label(i))
+            .collect::<Vec<_>>();
+        // The letters chosen first keep them, and the others get the first free
+        // letters of their texts
+        assert_eq!(labels, vec!["&Cut", "C&opy", "Cut &Line", "Clo&se", "&&&x"]);
+        assert_eq!(mnemonics.item('u'), None);
+        assert_eq!(mnemonics.item('x'), Some(4));
+
+        // The order of the items decides the letters, so a menu always gets the
+        // same ones
+        let again = Mnemonics::new(&menu);
+        assert_eq!(
+            (0..menu.len()).map(|i| again.label(i)).collect::<Vec<_>>(),
+            labels
+        );
+    }
+}
@@ -2,47 +2,65 @@
     cell::RefCell,
     cmp::Ordering,
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use alacritty_terminal::{grid::Dimensions, term::cell::Flags};
 use anyhow::Result;
-use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+use crossbeam_channel::{
+    unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError,
+};
 use druid::{
     Command, Data, Env, EventCtx, ExtEventSink, Lens, Modifiers, Target, WidgetId,
 };
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use fuzzy_matcher::{
+    skim::{SkimMatcherV2, SkimScoreConfig},
+    FuzzyMatcher,
+};
 use indexmap::IndexMap;
 use itertools::Itertools;
 use lapce_core::{
     command::{EditCommand, FocusCommand},
     language::LapceLanguage,
+    line_ending::LineEnding,
     mode::Mode,
 };
-use lapce_rpc::{proxy::ProxyResponse, source_control::FileDiff};
-use lsp_types::{DocumentSymbolResponse, Position, Range, SymbolKind};
+use lapce_rpc::{
+    plugin::PluginId,
+    proxy::{PluginPaletteItem, PluginPaletteProvider, ProxyResponse},
+    source_control::FileDiff,
+};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolResponse, Position, Range, SymbolInformation,
+    SymbolKind,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use uuid::Uuid;
 
 use crate::{
     command::{
-        CommandExecuted, CommandKind, LapceCommand, LapceUICommand, LAPCE_COMMAND,
-        LAPCE_UI_COMMAND,
+        CommandExecuted, CommandKind, LapceCommand, LapceUICommand,
+        LapceWorkbenchCommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
-    config::LapceConfig,
+    config::{LapceConfig, PaletteConfig},
     data::{
         FocusArea, LapceMainSplitData, LapceTabData, LapceWorkspace,
         LapceWorkspaceType, SshHost,
     },
     db::LapceDb,
-    document::BufferContent,
+    document::{BufferContent, Document},
     editor::EditorLocation,
     find::Find,
     keypress::{KeyMap, KeyPressData, KeyPressFocus},
     list::ListData,
+    local_history,
     panel::PanelKind,
+    plugin::PluginCommand,
     proxy::{path_from_url, LapceProxy},
     source_control::SourceControlData,
     terminal::TerminalPanelData,
@@ -60,10 +78,55 @@ pub enum PaletteType {
     Reference,
     ColorTheme,
     IconTheme,
+    /// The settings profiles to switch the workspace to
+    SettingsProfile,
     SshHost,
+    /// Takes an `author.name@version` to install
+    PluginVersion,
     Language,
+    /// The encodings to reopen the active file with
+    Encoding,
+    /// The line endings to convert the active file to
+    LineEnding,
+    /// The local history snapshots of the active file
+    LocalHistory,
+    AllBufferSymbols,
+    /// The palette a plugin registered
+    Plugin(PluginId),
+    /// Takes the new name of the terminal tab with the split id
+    TerminalTabName(WidgetId),
 }
 
+/// How long the input has to stay the same before the items of a plugin palette
+/// are requested
+const PLUGIN_PALETTE_DEBOUNCE: Duration = Duration::from_millis(150);
+/// How long a plugin has to reply with the items of its palette
+const PLUGIN_PALETTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The name of the item of the settings profile palette going back to the
+/// settings without a profile
+const BASE_SETTINGS_PROFILE: &str = "Default";
+
+/// The encodings a file can be reopened with, by their WHATWG names
+const ENCODINGS: &[&str] = &[
+    "UTF-8",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-2",
+    "ISO-8859-15",
+    "windows-1250",
+    "windows-1251",
+    "KOI8-R",
+    "Shift_JIS",
+    "EUC-JP",
+    "ISO-2022-JP",
+    "GBK",
+    "gb18030",
+    "Big5",
+    "EUC-KR",
+];
+
 impl PaletteType {
     fn string(&self) -> String {
         match &self {
@@ -77,8 +140,16 @@ fn string(&self) -> String {
             | PaletteType::Reference
             | PaletteType::ColorTheme
             | PaletteType::IconTheme
+            | PaletteType::SettingsProfile
             | PaletteType::SshHost
-            | PaletteType::Language => "".to_string(),
+            | PaletteType::PluginVersion
+            | PaletteType::Language
+            | PaletteType::Encoding
+            | PaletteType::LineEnding
+            | PaletteType::LocalHistory
+            | PaletteType::AllBufferSymbols
+            | PaletteType::Plugin(_)
+            | PaletteType::TerminalTabName(_) => "".to_string(),
         }
     }
 
@@ -90,6 +161,8 @@ pub fn has_preview(&self) -> bool {
                 | PaletteType::WorkspaceSymbol
                 | PaletteType::GlobalSearch
                 | PaletteType::Reference
+                | PaletteType::AllBufferSymbols
+                | PaletteType::LocalHistory
         )
     }
 
@@ -99,9 +172,17 @@ fn get_palette_type(current_type: &PaletteType, input: &str) -> PaletteType {
         match current_type {
             PaletteType::Reference
             | PaletteType::SshHost
+            | PaletteType::PluginVersion
             | PaletteType::ColorTheme
             | PaletteType::IconTheme
-            | PaletteType::Language => {
+            | PaletteType::SettingsProfile
+            | PaletteType::Language
+            | PaletteType::Encoding
+            | PaletteType::LineEnding
+            | PaletteType::LocalHistory
+            | PaletteType::AllBufferSymbols
+            | PaletteType::Plugin(_)
+            | PaletteType::TerminalTabName(_) => {
                 return current_type.clone();
             }
             _ => (),
@@ -134,6 +215,12 @@ pub enum PaletteStatus {
     Done,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PluginPaletteStatus {
+    Loading,
+    Error(String),
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum PaletteItemContent {
     File(PathBuf, PathBuf, Option<FileDiff>),
@@ -152,13 +239,42 @@ pub enum PaletteItemContent {
         container_name: Option<String>,
         location: EditorLocation<Position>,
     },
+    /// A symbol from one of the open buffers
+    BufferSymbol {
+        kind: SymbolKind,
+        name: String,
+        container_name: Option<String>,
+        /// The path of a scratch buffer is its name
+        location: EditorLocation<Position>,
+        /// The editor showing the scratch buffer the symbol is in, which has
+        /// no file to open
+        scratch_editor: Option<WidgetId>,
+    },
     ReferenceLocation(PathBuf, EditorLocation<Position>),
     Workspace(LapceWorkspace),
     SshHost(SshHost),
     Command(LapceCommand),
+    /// A command macro of the settings, with its name and description
+    Macro(String, String),
     ColorTheme(String),
     IconTheme(String),
+    /// A settings profile, or the base settings without any
+    SettingsProfile(Option<String>),
     Language(String),
+    Encoding(String),
+    LineEnding(LineEnding),
+    /// A local history snapshot of the file, with the time it was taken at
+    LocalHistory(PathBuf, u64),
+    /// An item of the palette of a plugin
+    PluginItem {
+        plugin_id: PluginId,
+        provider: String,
+        item: PluginPaletteItem,
+    },
+    /// Opens the palette of a plugin, with its title
+    PluginPalette(PluginId, String),
+    /// Shown in place of the items of a plugin palette while they are requested
+    PluginStatus(PluginPaletteStatus),
 }
 
 impl PaletteItemContent {
@@ -206,6 +322,44 @@ fn select(
                     Target::Auto,
                 ));
             }
+            PaletteItemContent::BufferSymbol {
+                location,
+                scratch_editor: Some(editor_view_id),
+                ..
+            } => {
+                // The preview editor can only show files
+                if !preview {
+                    if let Some(position) = location.position {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::JumpToPosition(
+                                Some(*editor_view_id),
+                                position,
+                                false,
+                            ),
+                            Target::Auto,
+                        ));
+                    }
+                }
+            }
+            PaletteItemContent::BufferSymbol { location, .. } => {
+                // Not restricting the jump to the active editor tab means that the
+                // editor which already has the buffer open is focused
+                let (editor_id, same_tab) = if preview {
+                    (Some(preview_editor_id), true)
+                } else {
+                    (None, false)
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLspLocation(
+                        editor_id,
+                        location.clone(),
+                        same_tab,
+                    ),
+                    Target::Auto,
+                ));
+            }
             PaletteItemContent::Line(line, _) => {
                 let editor_id = if preview {
                     Some(preview_editor_id)
@@ -263,6 +417,15 @@ fn select(
                     Target::Auto,
                 ));
             }
+            PaletteItemContent::SettingsProfile(profile) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::SetSettingsProfile(profile.clone()),
+                        Target::Auto,
+                    ))
+                }
+            }
             PaletteItemContent::Language(name) => {
                 if !preview {
                     let name = name.to_string();
@@ -273,6 +436,42 @@ fn select(
                     ))
                 }
             }
+            PaletteItemContent::Encoding(encoding) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ReopenWithEncoding(encoding.to_string()),
+                        Target::Auto,
+                    ))
+                }
+            }
+            PaletteItemContent::LineEnding(line_ending) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ConvertLineEndings(*line_ending),
+                        Target::Auto,
+                    ))
+                }
+            }
+            PaletteItemContent::LocalHistory(path, time) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    if preview {
+                        LapceUICommand::PreviewLocalHistory {
+                            editor_id: preview_editor_id,
+                            path: path.clone(),
+                            time: *time,
+                        }
+                    } else {
+                        LapceUICommand::RestoreLocalHistory {
+                            path: path.clone(),
+                            time: *time,
+                        }
+                    },
+                    Target::Auto,
+                ));
+            }
             PaletteItemContent::Command(command) => {
                 if !preview {
                     ctx.submit_command(Command::new(
@@ -305,6 +504,35 @@ fn select(
                     ));
                 }
             }
+            // Sent to the plugin by `PaletteViewData::select`, which has the proxy
+            PaletteItemContent::PluginItem { .. } => {}
+            PaletteItemContent::PluginPalette(plugin_id, _) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RunPalette(Some(PaletteType::Plugin(
+                            *plugin_id,
+                        ))),
+                        Target::Auto,
+                    ));
+                }
+                return false;
+            }
+            PaletteItemContent::PluginStatus(_) => return false,
+            PaletteItemContent::Macro(name, _) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_COMMAND,
+                        LapceCommand {
+                            kind: CommandKind::Workbench(
+                                LapceWorkbenchCommand::RunMacro,
+                            ),
+                            data: Some(serde_json::json!(name)),
+                        },
+                        Target::Auto,
+                    ));
+                }
+            }
         }
         true
     }
@@ -314,10 +542,265 @@ fn select(
 pub struct PaletteItem {
     pub content: PaletteItemContent,
     pub filter_text: String,
+    /// The base score of the item, the fuzzy match score is added to it when
+    /// the items are filtered
     pub score: i64,
     pub indices: Vec<usize>,
 }
 
+/// The time in seconds it takes for the usage score of a command to halve
+const FRECENCY_HALF_LIFE: f64 = 60.0 * 60.0 * 24.0 * 7.0;
+/// Usage scores that decayed below this are forgotten
+const FRECENCY_MIN_SCORE: f64 = 0.05;
+/// How much the usage score weighs against the fuzzy match score
+const FRECENCY_WEIGHT: f64 = 8.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct PaletteUsage {
+    score: f64,
+    /// Seconds since the unix epoch
+    last_used: u64,
+}
+
+impl PaletteUsage {
+    fn score_at(&self, now: u64) -> f64 {
+        let elapsed = now.saturating_sub(self.last_used) as f64;
+        self.score * 0.5_f64.powf(elapsed / FRECENCY_HALF_LIFE)
+    }
+}
+
+/// Tracks how frequently and how recently the palette commands were run.  
+/// Every run of a command adds one to its usage score, and the score halves every
+/// [`FRECENCY_HALF_LIFE`] seconds, so that commands which aren't used anymore
+/// eventually sink back down.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaletteFrecency {
+    commands: HashMap<String, PaletteUsage>,
+}
+
+impl PaletteFrecency {
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    pub fn record(&mut self, command: &str, now: u64) {
+        let score = self.score(command, now) + 1.0;
+        self.commands.insert(
+            command.to_string(),
+            PaletteUsage {
+                score,
+                last_used: now,
+            },
+        );
+        self.commands
+            .retain(|_, usage| usage.score_at(now) >= FRECENCY_MIN_SCORE);
+    }
+
+    pub fn score(&self, command: &str, now: u64) -> f64 {
+        self.commands
+            .get(command)
+            .map(|usage| usage.score_at(now))
+            .unwrap_or(0.0)
+    }
+
+    /// The bonus that is added to the fuzzy match score of the command
+    pub fn bonus(&self, command: &str, now: u64) -> i64 {
+        (self.score(command, now).ln_1p() * FRECENCY_WEIGHT).round() as i64
+    }
+
+    /// The used commands, with the highest score first
+    pub fn commands(&self, now: u64) -> Vec<&str> {
+        self.commands
+            .iter()
+            .map(|(command, usage)| (command.as_str(), usage.score_at(now)))
+            .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal))
+            .map(|(command, _)| command)
+            .collect()
+    }
+}
+
+/// The input and the unfiltered items which are sent to the filtering thread
+type PaletteFilterBatch = (String, String, im::Vector<PaletteItem>, PaletteConfig);
+
+/// The symbols collected from the open buffers and from the index of the
+/// project
+#[derive(Default)]
+struct BufferSymbols {
+    /// The index of the item of each symbol, by its file, name and line, which
+    /// is used to skip the duplicates that are reported more than once
+    seen: HashMap<(PathBuf, String, u32), usize>,
+    items: im::Vector<PaletteItem>,
+}
+
+impl BufferSymbols {
+    fn extend(&mut self, path: &Path, resp: DocumentSymbolResponse) {
+        match resp {
+            DocumentSymbolResponse::Flat(symbols) => {
+                for s in symbols {
+                    self.push(
+                        path,
+                        s.kind,
+                        s.name,
+                        s.container_name,
+                        s.location.range.start,
+                    );
+                }
+            }
+            DocumentSymbolResponse::Nested(symbols) => {
+                self.extend_nested(path, symbols, None);
+            }
+        }
+    }
+
+    fn extend_nested(
+        &mut self,
+        path: &Path,
+        symbols: Vec<DocumentSymbol>,
+        container_name: Option<&str>,
+    ) {
+        for s in symbols {
+            if let Some(children) = s.children {
+                self.extend_nested(path, children, Some(&s.name));
+            }
+            self.push(
+                path,
+                s.kind,
+                s.name,
+                container_name.map(|n| n.to_string()),
+                s.selection_range.start,
+            );
+        }
+    }
+
+    /// The symbols of the project index are only added where an open buffer
+    /// has none, as the buffer is more up to date
+    fn extend_index(&mut self, symbols: Vec<SymbolInformation>) {
+        for s in symbols {
+            let path = path_from_url(&s.location.uri);
+            let key = (path.clone(), s.name.clone(), s.location.range.start.line);
+            if self.seen.contains_key(&key) {
+                continue;
+            }
+            self.seen.insert(key, self.items.len());
+            self.items.push_back(symbol_item(
+                EditorLocation {
+                    path,
+                    position: Some(s.location.range.start),
+                    scroll_offset: None,
+                    history: None,
+                },
+                None,
+                s.kind,
+                s.name,
+                s.container_name,
+            ));
+        }
+    }
+
+    /// The symbols of a scratch buffer, which no language server knows of, are
+    /// the ones of its syntax tree
+    fn extend_scratch(&mut self, doc: &Document, editor_view_id: WidgetId) {
+        let symbols = match doc.syntax() {
+            Some(syntax) => syntax.symbols(),
+            None => return,
+        };
+        let name = doc.content().file_name();
+        for s in symbols {
+            self.items.push_back(symbol_item(
+                EditorLocation {
+                    path: PathBuf::from(name),
+                    position: Some(doc.buffer().offset_to_position(s.offset)),
+                    scroll_offset: None,
+                    history: None,
+                },
+                Some(editor_view_id),
+                syntax_symbol_kind(s.kind),
+                s.name,
+                s.container_name,
+            ));
+        }
+    }
+
+    /// A symbol of an open buffer replaces the one of the project index at the
+    /// same place
+    fn push(
+        &mut self,
+        path: &Path,
+        kind: SymbolKind,
+        name: String,
+        container_name: Option<String>,
+        position: Position,
+    ) {
+        let key = (path.to_path_buf(), name.clone(), position.line);
+        let item = symbol_item(
+            EditorLocation {
+                path: path.to_path_buf(),
+                position: Some(position),
+                scroll_offset: None,
+                history: None,
+            },
+            None,
+            kind,
+            name,
+            container_name,
+        );
+        match self.seen.get(&key) {
+            Some(index) => {
+                self.items.set(*index, item);
+            }
+            None => {
+                self.seen.insert(key, self.items.len());
+                self.items.push_back(item);
+            }
+        }
+    }
+}
+
+fn symbol_item(
+    location: EditorLocation<Position>,
+    scratch_editor: Option<WidgetId>,
+    kind: SymbolKind,
+    name: String,
+    container_name: Option<String>,
+) -> PaletteItem {
+    let mut filter_text = name.clone();
+    if let Some(container_name) = container_name.as_ref() {
+        filter_text += container_name;
+    }
+    PaletteItem {
+        content: PaletteItemContent::BufferSymbol {
+            kind,
+            name,
+            container_name,
+            location,
+            scratch_editor,
+        },
+        filter_text,
+        score: 0,
+        indices: Vec::new(),
+    }
+}
+
+/// The kind of symbol of a node of the syntax tree, from the kind of the node
+fn syntax_symbol_kind(kind: &str) -> SymbolKind {
+    if kind.contains("function") || kind.contains("method") {
+        SymbolKind::FUNCTION
+    } else if kind.contains("class") {
+        SymbolKind::CLASS
+    } else if kind.contains("enum") {
+        SymbolKind::ENUM
+    } else if kind.contains("interface") || kind.contains("trait") {
+        SymbolKind::INTERFACE
+    } else if kind.contains("mod") {
+        SymbolKind::MODULE
+    } else {
+        SymbolKind::STRUCT
+    }
+}
+
 pub struct PaletteViewLens;
 
 #[derive(Clone, Data)]
@@ -383,8 +866,11 @@ pub struct PaletteData {
     pub list_data: ListData<PaletteItem, PaletteListData>,
     pub proxy: Arc<LapceProxy>,
     pub palette_type: PaletteType,
-    pub sender: Sender<(String, String, im::Vector<PaletteItem>)>,
-    pub receiver: Option<Receiver<(String, String, im::Vector<PaletteItem>)>>,
+    pub sender: Sender<PaletteFilterBatch>,
+    pub receiver: Option<Receiver<PaletteFilterBatch>>,
+    /// The timers of the requests to the plugin palettes
+    pub plugin_timer: Sender<PluginTimerEvent>,
+    pub plugin_timer_receiver: Option<Receiver<PluginTimerEvent>>,
     pub run_id: String,
     pub input: String,
     pub cursor: usize,
@@ -393,7 +879,9 @@ pub struct PaletteData {
     pub total_items: im::Vector<PaletteItem>,
     pub preview_editor: WidgetId,
     pub input_editor: WidgetId,
-    pub executed_commands: Rc<RefCell<HashMap<String, Instant>>>,
+    pub frecency: Rc<RefCell<PaletteFrecency>>,
+    /// The palettes registered by plugins
+    pub plugin_providers: im::HashMap<PluginId, PluginPaletteProvider>,
 }
 
 impl KeyPressFocus for PaletteViewData {
@@ -455,8 +943,13 @@ fn run_command(
 }
 
 impl PaletteData {
-    pub fn new(config: Arc<LapceConfig>, proxy: Arc<LapceProxy>) -> Self {
+    pub fn new(
+        config: Arc<LapceConfig>,
+        proxy: Arc<LapceProxy>,
+        frecency: PaletteFrecency,
+    ) -> Self {
         let (sender, receiver) = unbounded();
+        let (plugin_timer, plugin_timer_receiver) = unbounded();
         let widget_id = WidgetId::next();
         let scroll_id = WidgetId::next();
         let preview_editor = WidgetId::next();
@@ -481,6 +974,8 @@ pub fn new(config: Arc<LapceConfig>, proxy: Arc<LapceProxy>) -> Self {
             palette_type: PaletteType::File,
             sender,
             receiver: Some(receiver),
+            plugin_timer,
+            plugin_timer_receiver: Some(plugin_timer_receiver),
             run_id: Uuid::new_v4().to_string(),
             input: "".to_string(),
             cursor: 0,
@@ -488,7 +983,8 @@ pub fn new(config: Arc<LapceConfig>, proxy: Arc<LapceProxy>) -> Self {
             total_items: im::Vector::new(),
             preview_editor,
             input_editor: WidgetId::next(),
-            executed_commands: Rc::new(RefCell::new(HashMap::new())),
+            frecency: Rc::new(RefCell::new(frecency)),
+            plugin_providers: im::HashMap::new(),
         }
     }
 
@@ -520,8 +1016,16 @@ pub fn get_input(&self) -> &str {
             | PaletteType::Reference
             | PaletteType::ColorTheme
             | PaletteType::IconTheme
+            | PaletteType::SettingsProfile
             | PaletteType::Language
-            | PaletteType::SshHost => &self.input,
+            | PaletteType::Encoding
+            | PaletteType::LineEnding
+            | PaletteType::LocalHistory
+            | PaletteType::SshHost
+            | PaletteType::PluginVersion
+            | PaletteType::AllBufferSymbols
+            | PaletteType::Plugin(_)
+            | PaletteType::TerminalTabName(_) => &self.input,
             PaletteType::Line
             | PaletteType::DocumentSymbol
             | PaletteType::WorkspaceSymbol
@@ -530,6 +1034,166 @@ pub fn get_input(&self) -> &str {
             | PaletteType::GlobalSearch => &self.input[1..],
         }
     }
+
+    /// Request the items matching the input from the plugin of the palette, an
+    /// error is shown instead if the plugin doesn't reply in time
+    pub fn request_plugin_items(&self, ctx: &mut EventCtx) {
+        let plugin_id = match &self.palette_type {
+            PaletteType::Plugin(plugin_id) => *plugin_id,
+            _ => return,
+        };
+        let run_id = self.run_id.clone();
+        let widget_id = self.widget_id;
+        let event_sink = ctx.get_external_handle();
+
+        let provider = match self.plugin_providers.get(&plugin_id) {
+            Some(provider) => provider.name.clone(),
+            None => {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdatePaletteItems {
+                        run_id,
+                        items: plugin_status_items(PluginPaletteStatus::Error(
+                            "The plugin has no palette".to_string(),
+                        )),
+                    },
+                    Target::Widget(widget_id),
+                );
+                return;
+            }
+        };
+
+        {
+            let run_id = run_id.clone();
+            self.proxy.proxy_rpc.get_plugin_palette_items(
+                plugin_id,
+                provider.clone(),
+                self.get_input().to_string(),
+                move |result| {
+                    let items = match result {
+                        Ok(ProxyResponse::GetPluginPaletteItemsResponse {
+                            items,
+                        }) => items
+                            .into_iter()
+                            .map(|item| PaletteItem {
+                                filter_text: item.label.clone(),
+                                content: PaletteItemContent::PluginItem {
+                                    plugin_id,
+                                    provider: provider.clone(),
+                                    item,
+                                },
+                                score: 0,
+                                indices: Vec::new(),
+                            })
+                            .collect(),
+                        Ok(_) => return,
+                        Err(err) => plugin_status_items(PluginPaletteStatus::Error(
+                            err.message,
+                        )),
+                    };
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdatePaletteItems { run_id, items },
+                        Target::Widget(widget_id),
+                    );
+                },
+            );
+        }
+
+        let _ = self
+            .plugin_timer
+            .send(PluginTimerEvent::Start(PluginTimer::Timeout(run_id)));
+    }
+
+    /// Replace the loading status with an error if the plugin still hasn't
+    /// replied
+    pub fn plugin_items_timed_out(&mut self, run_id: &str) {
+        if self.run_id != run_id {
+            return;
+        }
+
+        let loading = PaletteItemContent::PluginStatus(PluginPaletteStatus::Loading);
+        if self.total_items.iter().any(|item| item.content == loading) {
+            self.total_items = plugin_status_items(PluginPaletteStatus::Error(
+                "The plugin didn't reply in time".to_string(),
+            ));
+            self.list_data.items = self.total_items.clone();
+        }
+    }
+}
+
+/// A timer of the requests to the plugin palettes, for the palette run with
+/// the id
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PluginTimer {
+    /// Request the items once the input stopped changing for a bit
+    Request(String),
+    /// Report that the plugin didn't reply in time
+    Timeout(String),
+}
+
+pub enum PluginTimerEvent {
+    /// Start the timer, replacing the pending one of the same kind
+    Start(PluginTimer),
+    /// Stop the pending timers, like when the palette is closed
+    Cancel,
+}
+
+/// Runs the timers of the plugin palettes on a single thread, instead of one
+/// for every key typed, until the sender is dropped. `fire` is called with the
+/// timers that ran out.
+pub fn run_plugin_timers(
+    receiver: Receiver<PluginTimerEvent>,
+    debounce: Duration,
+    timeout: Duration,
+    mut fire: impl FnMut(PluginTimer),
+) {
+    let mut request: Option<(Instant, String)> = None;
+    let mut reply: Option<(Instant, String)> = None;
+    loop {
+        let deadline = request.iter().chain(reply.iter()).map(|(d, _)| *d).min();
+        let event = match deadline {
+            Some(deadline) => match receiver.recv_deadline(deadline) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            },
+            None => match receiver.recv() {
+                Ok(event) => Some(event),
+                Err(_) => return,
+            },
+        };
+        let now = Instant::now();
+        match event {
+            Some(PluginTimerEvent::Start(PluginTimer::Request(run_id))) => {
+                request = Some((now + debounce, run_id));
+            }
+            Some(PluginTimerEvent::Start(PluginTimer::Timeout(run_id))) => {
+                reply = Some((now + timeout, run_id));
+            }
+            Some(PluginTimerEvent::Cancel) => {
+                request = None;
+                reply = None;
+            }
+            None => {
+                if request.as_ref().map_or(false, |(d, _)| *d <= now) {
+                    fire(PluginTimer::Request(request.take().unwrap().1));
+                }
+                if reply.as_ref().map_or(false, |(d, _)| *d <= now) {
+                    fire(PluginTimer::Timeout(reply.take().unwrap().1));
+                }
+            }
+        }
+    }
+}
+
+fn plugin_status_items(status: PluginPaletteStatus) -> im::Vector<PaletteItem> {
+    im::vector![PaletteItem {
+        content: PaletteItemContent::PluginStatus(status),
+        filter_text: String::new(),
+        score: 0,
+        indices: Vec::new(),
+    }]
 }
 
 impl PaletteViewData {
@@ -545,6 +1209,7 @@ pub fn cancel(&mut self, ctx: &mut EventCtx) {
             _ => {}
         }
         let palette = Arc::make_mut(&mut self.palette);
+        let _ = palette.plugin_timer.send(PluginTimerEvent::Cancel);
         palette.status = PaletteStatus::Inactive;
         palette.input = "".to_string();
         palette.cursor = 0;
@@ -575,14 +1240,7 @@ pub fn run_references(
         let items = locations
             .iter()
             .map(|l| {
-                let full_path = l.path.clone();
-                let mut path = l.path.clone();
-                if let Some(workspace_path) = self.workspace.path.as_ref() {
-                    path = path
-                        .strip_prefix(workspace_path)
-                        .unwrap_or(&full_path)
-                        .to_path_buf();
-                }
+                let path = self.workspace.display_path(&l.path);
                 let filter_text = path.to_str().unwrap_or("").to_string();
                 PaletteItem {
                     content: PaletteItemContent::ReferenceLocation(path, l.clone()),
@@ -659,12 +1317,19 @@ pub fn run(
             PaletteType::SshHost => {
                 self.get_ssh_hosts(ctx);
             }
+            PaletteType::PluginVersion | PaletteType::TerminalTabName(_) => {}
             PaletteType::GlobalSearch => {
                 self.get_global_search(ctx);
             }
             PaletteType::Command => {
                 self.get_commands(ctx);
             }
+            PaletteType::AllBufferSymbols => {
+                self.get_all_buffer_symbols(ctx);
+            }
+            PaletteType::Plugin(_) => {
+                self.get_plugin_items(ctx);
+            }
             PaletteType::ColorTheme => {
                 let config = self.config.clone();
                 self.get_color_themes(ctx, &config);
@@ -675,6 +1340,21 @@ pub fn run(
                 self.get_icon_themes(ctx, &config);
                 self.preselect_matching(ctx, &config.icon_theme.name);
             }
+            PaletteType::SettingsProfile => {
+                self.get_settings_profiles(ctx);
+                let active = self.config.active_profile.as_deref();
+                let name = self
+                    .config
+                    .profile_names()
+                    .into_iter()
+                    .find(|name| {
+                        active.map_or(false, |active| {
+                            name.eq_ignore_ascii_case(active)
+                        })
+                    })
+                    .unwrap_or_else(|| BASE_SETTINGS_PROFILE.to_string());
+                self.preselect_matching(ctx, &name);
+            }
             PaletteType::Language => {
                 self.get_languages(ctx);
                 if let Some(editor) = self.main_split.active_editor() {
@@ -685,6 +1365,26 @@ pub fn run(
                     }
                 }
             }
+            PaletteType::Encoding => {
+                self.get_encodings(ctx);
+                if let Some(editor) = self.main_split.active_editor() {
+                    let doc = self.main_split.content_doc(&editor.content);
+                    let encoding = doc.encoding().to_string();
+                    self.preselect_matching(ctx, &encoding);
+                }
+            }
+            PaletteType::LineEnding => {
+                self.get_line_endings(ctx);
+                if let Some(editor) = self.main_split.active_editor() {
+                    let doc = self.main_split.content_doc(&editor.content);
+                    let line_ending = doc.buffer().line_ending().to_string();
+                    self.preselect_matching(ctx, &line_ending);
+                }
+            }
+            PaletteType::LocalHistory => {
+                self.get_local_history(ctx);
+                self.palette.preview(ctx);
+            }
         }
 
         self.fill_list();
@@ -720,8 +1420,16 @@ pub fn delete_to_beginning_of_line(&mut self, ctx: &mut EventCtx) {
             | PaletteType::Reference
             | PaletteType::ColorTheme
             | PaletteType::IconTheme
+            | PaletteType::SettingsProfile
             | PaletteType::Language
-            | PaletteType::SshHost => 0,
+            | PaletteType::Encoding
+            | PaletteType::LineEnding
+            | PaletteType::LocalHistory
+            | PaletteType::SshHost
+            | PaletteType::PluginVersion
+            | PaletteType::AllBufferSymbols
+            | PaletteType::Plugin(_)
+            | PaletteType::TerminalTabName(_) => 0,
             PaletteType::Line
             | PaletteType::DocumentSymbol
             | PaletteType::WorkspaceSymbol
@@ -788,10 +1496,23 @@ pub fn select(&mut self, ctx: &mut EventCtx) {
         let palette = Arc::make_mut(&mut self.palette);
         if let Some(item) = palette.list_data.current_selected_item() {
             if let PaletteItemContent::Command(cmd) = &item.content {
-                palette
-                    .executed_commands
-                    .borrow_mut()
-                    .insert(cmd.kind.str().to_string(), Instant::now());
+                let now = PaletteFrecency::now();
+                palette.frecency.borrow_mut().record(cmd.kind.str(), now);
+                if self.config.palette.remember_usage {
+                    self.db.save_palette_usage(cmd.kind.str(), now);
+                }
+            }
+            if let PaletteItemContent::PluginItem {
+                plugin_id,
+                provider,
+                item,
+            } = &item.content
+            {
+                palette.proxy.proxy_rpc.select_plugin_palette_item(
+                    *plugin_id,
+                    provider.clone(),
+                    item.id.clone(),
+                );
             }
             if item.content.select(ctx, false, palette.preview_editor) {
                 self.cancel(ctx);
@@ -811,6 +1532,28 @@ pub fn select(&mut self, ctx: &mut EventCtx) {
                 ));
                 return;
             }
+            if let PaletteType::TerminalTabName(split_id) = self.palette.palette_type
+            {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RenameTerminalTab(
+                        split_id,
+                        self.palette.get_input().to_string(),
+                    ),
+                    Target::Auto,
+                ));
+            }
+            if self.palette.palette_type == PaletteType::PluginVersion {
+                if let Some(command) =
+                    PluginCommand::parse_install_version(self.palette.get_input())
+                {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RunPluginCommand(command),
+                        Target::Auto,
+                    ));
+                }
+            }
             self.cancel(ctx);
         }
     }
@@ -820,10 +1563,16 @@ pub fn update_input(&mut self, ctx: &mut EventCtx, input: String) {
 
         // WorkspaceSymbol requires sending the query to the lsp, so we refresh it when the input changes
         // If the input changed and the palette type is still/now workspace-symbol then we rerun it
+        // The same goes for plugin palettes, which send the query to the plugin
         let palette_type =
             PaletteType::get_palette_type(&palette.palette_type, &input);
-        if input != palette.input && palette_type == PaletteType::WorkspaceSymbol {
-            self.run(ctx, Some(PaletteType::WorkspaceSymbol), Some(input), false);
+        if input != palette.input
+            && matches!(
+                palette_type,
+                PaletteType::WorkspaceSymbol | PaletteType::Plugin(_)
+            )
+        {
+            self.run(ctx, Some(palette_type), Some(input), false);
             return;
         }
 
@@ -853,12 +1602,17 @@ pub fn update_palette(&mut self, ctx: &mut EventCtx) {
             self.palette.preview(ctx);
             Arc::make_mut(&mut self.palette).list_data.items =
                 self.palette.total_items.clone();
+        } else if let PaletteType::Plugin(_) = self.palette.palette_type {
+            // The plugin already matched its items against the input
+            Arc::make_mut(&mut self.palette).list_data.items =
+                self.palette.total_items.clone();
         } else {
             // Update the filtering with the input
             let _ = self.palette.sender.send((
                 self.palette.run_id.clone(),
                 self.palette.get_input().to_string(),
                 self.palette.total_items.clone(),
+                self.config.palette.clone(),
             ));
         }
     }
@@ -876,13 +1630,7 @@ fn get_files(&self, ctx: &mut EventCtx) {
                     .enumerate()
                     .map(|(_index, path)| {
                         let full_path = path.clone();
-                        let mut path = path.clone();
-                        if let Some(workspace_path) = workspace.path.as_ref() {
-                            path = path
-                                .strip_prefix(workspace_path)
-                                .unwrap_or(&full_path)
-                                .to_path_buf();
-                        }
+                        let path = workspace.display_path(path);
                         let file_diff =
                             file_diffs.get(&full_path).cloned().map(|t| t.0);
                         let filter_text = path.to_str().unwrap_or("").to_string();
@@ -983,6 +1731,24 @@ fn get_icon_themes(&mut self, _ctx: &mut EventCtx, config: &LapceConfig) {
             .collect();
     }
 
+    fn get_settings_profiles(&mut self, _ctx: &mut EventCtx) {
+        let profiles = self.config.profile_names();
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.total_items = std::iter::once(PaletteItem {
+            content: PaletteItemContent::SettingsProfile(None),
+            filter_text: BASE_SETTINGS_PROFILE.to_string(),
+            score: 0,
+            indices: vec![],
+        })
+        .chain(profiles.into_iter().map(|profile| PaletteItem {
+            filter_text: profile.clone(),
+            content: PaletteItemContent::SettingsProfile(Some(profile)),
+            score: 0,
+            indices: vec![],
+        }))
+        .collect();
+    }
+
     fn get_languages(&mut self, _ctx: &mut EventCtx) {
         let palette = Arc::make_mut(&mut self.palette);
         let mut langs = LapceLanguage::languages();
@@ -999,22 +1765,68 @@ fn get_languages(&mut self, _ctx: &mut EventCtx) {
             .collect();
     }
 
+    fn get_encodings(&mut self, _ctx: &mut EventCtx) {
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.total_items = ENCODINGS
+            .iter()
+            .map(|encoding| PaletteItem {
+                content: PaletteItemContent::Encoding(encoding.to_string()),
+                filter_text: encoding.to_string(),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+    }
+
+    fn get_line_endings(&mut self, _ctx: &mut EventCtx) {
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.total_items = LineEnding::iter()
+            .map(|line_ending| PaletteItem {
+                content: PaletteItemContent::LineEnding(line_ending),
+                filter_text: line_ending.to_string(),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+    }
+
+    fn get_local_history(&mut self, _ctx: &mut EventCtx) {
+        let path = match self.main_split.active_editor().map(|e| &e.content) {
+            Some(BufferContent::File(path)) => path.clone(),
+            _ => return,
+        };
+        let now = local_history::now();
+        let times = self.db.get_local_history(&path).unwrap_or_default();
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.total_items = times
+            .into_iter()
+            .map(|time| PaletteItem {
+                content: PaletteItemContent::LocalHistory(path.clone(), time),
+                filter_text: format!(
+                    "{} {}",
+                    local_history::snapshot_age(now, time),
+                    local_history::snapshot_date(time)
+                ),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+    }
+
     fn get_commands(&mut self, _ctx: &mut EventCtx) {
         const EXCLUDED_ITEMS: &[&str] = &["palette.command"];
 
-        let mut items: im::Vector<PaletteItem> = self
-            .palette
-            .executed_commands
-            .borrow()
-            .iter()
-            .sorted_by_key(|(_, i)| *i)
-            .rev()
-            .filter_map(|(key, _)| {
+        let now = PaletteFrecency::now();
+        let frecency = self.palette.frecency.borrow();
+        let mut items: im::Vector<PaletteItem> = frecency
+            .commands(now)
+            .into_iter()
+            .filter_map(|key| {
                 self.keypress.commands.get(key).and_then(|c| {
                     c.kind.desc().as_ref().map(|m| PaletteItem {
                         content: PaletteItemContent::Command(c.clone()),
                         filter_text: m.to_string(),
-                        score: 0,
+                        score: frecency.bonus(key, now),
                         indices: vec![],
                     })
                 })
@@ -1025,12 +1837,7 @@ fn get_commands(&mut self, _ctx: &mut EventCtx) {
                 return None;
             }
 
-            if self
-                .palette
-                .executed_commands
-                .borrow()
-                .contains_key(c.kind.str())
-            {
+            if frecency.score(c.kind.str(), now) > 0.0 {
                 return None;
             }
 
@@ -1041,11 +1848,51 @@ fn get_commands(&mut self, _ctx: &mut EventCtx) {
                 indices: vec![],
             })
         }));
+        drop(frecency);
+
+        items.extend(
+            self.keypress
+                .macros
+                .iter()
+                .map(|command_macro| PaletteItem {
+                    content: PaletteItemContent::Macro(
+                        command_macro.name.clone(),
+                        command_macro.desc().to_string(),
+                    ),
+                    filter_text: command_macro.desc().to_string(),
+                    score: 0,
+                    indices: vec![],
+                }),
+        );
+
+        items.extend(self.palette.plugin_providers.iter().map(
+            |(plugin_id, provider)| PaletteItem {
+                content: PaletteItemContent::PluginPalette(
+                    *plugin_id,
+                    provider.title.clone(),
+                ),
+                filter_text: provider.title.clone(),
+                score: 0,
+                indices: vec![],
+            },
+        ));
 
         let palette = Arc::make_mut(&mut self.palette);
         palette.total_items = items;
     }
 
+    /// Show that the items of the plugin palette are loading, they are only
+    /// requested once the input stopped changing for a bit
+    fn get_plugin_items(&mut self, ctx: &mut EventCtx) {
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.total_items = plugin_status_items(PluginPaletteStatus::Loading);
+
+        let run_id = palette.run_id.clone();
+        let _ = palette
+            .plugin_timer
+            .send(PluginTimerEvent::Start(PluginTimer::Request(run_id)));
+    }
+
     fn get_lines(&mut self, _ctx: &mut EventCtx) {
         if self.focus_area == FocusArea::Panel(PanelKind::Terminal) {
             if let Some(terminal) = self.terminal.active_terminal() {
@@ -1257,32 +2104,142 @@ fn get_workspace_symbols(&mut self, ctx: &mut EventCtx) {
         }
     }
 
+    /// Collect the symbols of every open buffer, and the ones of the project
+    /// index, the items are updated as each of the responses comes in. The
+    /// scratch buffers have their symbols found in their syntax tree.
+    fn get_all_buffer_symbols(&mut self, ctx: &mut EventCtx) {
+        let widget_id = self.palette.widget_id;
+        let run_id = self.palette.run_id.clone();
+        let collected = Arc::new(Mutex::new(BufferSymbols::default()));
+
+        {
+            let mut collected = collected.lock();
+            // A scratch buffer may be shown in several editors
+            let mut scratch_ids = HashSet::new();
+            for editor in self.main_split.editors.values() {
+                if let BufferContent::Scratch(id, _) = &editor.content {
+                    if !scratch_ids.insert(*id) {
+                        continue;
+                    }
+                    if let Some(doc) = self.main_split.scratch_docs.get(id) {
+                        collected.extend_scratch(doc, editor.view_id);
+                    }
+                }
+            }
+            if !collected.items.is_empty() {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdatePaletteItems {
+                        run_id: run_id.clone(),
+                        items: collected.items.clone(),
+                    },
+                    Target::Widget(widget_id),
+                ));
+            }
+        }
+
+        for path in self.main_split.open_docs.keys() {
+            let path = path.clone();
+            let run_id = run_id.clone();
+            let collected = collected.clone();
+            let event_sink = ctx.get_external_handle();
+            self.palette.proxy.proxy_rpc.get_document_symbols(
+                path.clone(),
+                move |result| {
+                    if let Ok(ProxyResponse::GetDocumentSymbols { resp }) = result {
+                        let items = {
+                            let mut collected = collected.lock();
+                            collected.extend(&path, resp);
+                            collected.items.clone()
+                        };
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdatePaletteItems { run_id, items },
+                            Target::Widget(widget_id),
+                        );
+                    }
+                },
+            );
+        }
+
+        let event_sink = ctx.get_external_handle();
+        self.palette.proxy.proxy_rpc.get_workspace_symbols(
+            String::new(),
+            move |result| {
+                if let Ok(ProxyResponse::GetWorkspaceSymbols { symbols }) = result {
+                    let items = {
+                        let mut collected = collected.lock();
+                        collected.extend_index(symbols);
+                        collected.items.clone()
+                    };
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdatePaletteItems { run_id, items },
+                        Target::Widget(widget_id),
+                    );
+                }
+            },
+        );
+    }
+
+    /// Send the commands of the timers of the plugin palettes as they run out
+    pub fn plugin_timer_process(
+        receiver: Receiver<PluginTimerEvent>,
+        widget_id: WidgetId,
+        event_sink: ExtEventSink,
+    ) {
+        run_plugin_timers(
+            receiver,
+            PLUGIN_PALETTE_DEBOUNCE,
+            PLUGIN_PALETTE_TIMEOUT,
+            |timer| {
+                let command = match timer {
+                    PluginTimer::Request(run_id) => {
+                        LapceUICommand::RequestPluginPaletteItems { run_id }
+                    }
+                    PluginTimer::Timeout(run_id) => {
+                        LapceUICommand::PluginPaletteItemsTimeout { run_id }
+                    }
+                };
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    command,
+                    Target::Widget(widget_id),
+                );
+            },
+        );
+    }
+
     pub fn update_process(
-        receiver: Receiver<(String, String, im::Vector<PaletteItem>)>,
+        receiver: Receiver<PaletteFilterBatch>,
         widget_id: WidgetId,
         event_sink: ExtEventSink,
     ) {
         fn receive_batch(
-            receiver: &Receiver<(String, String, im::Vector<PaletteItem>)>,
-        ) -> Result<(String, String, im::Vector<PaletteItem>)> {
-            let (mut run_id, mut input, mut items) = receiver.recv()?;
+            receiver: &Receiver<PaletteFilterBatch>,
+        ) -> Result<PaletteFilterBatch> {
+            let mut batch = receiver.recv()?;
             loop {
                 match receiver.try_recv() {
                     Ok(update) => {
-                        run_id = update.0;
-                        input = update.1;
-                        items = update.2;
+                        batch = update;
                     }
                     Err(TryRecvError::Empty) => break,
                     Err(TryRecvError::Disconnected) => break,
                 }
             }
-            Ok((run_id, input, items))
+            Ok(batch)
         }
 
-        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut match_config = PaletteConfig::default();
+        let mut matcher = Self::matcher(&match_config);
         loop {
-            if let Ok((run_id, input, items)) = receive_batch(&receiver) {
+            if let Ok((run_id, input, items, config)) = receive_batch(&receiver) {
+                if config != match_config {
+                    match_config = config;
+                    matcher = Self::matcher(&match_config);
+                }
+
                 let filtered_items =
                     Self::filter_items(&run_id, &input, items, &matcher);
 
@@ -1301,6 +2258,36 @@ fn receive_batch(
         }
     }
 
+    /// Create the fuzzy matcher, with the default skim bonuses scaled by the
+    /// configured weights
+    fn matcher(config: &PaletteConfig) -> SkimMatcherV2 {
+        fn weighted(bonus: i32, weight: f64) -> i32 {
+            (bonus as f64 * weight.max(0.0)).round() as i32
+        }
+
+        let default = SkimScoreConfig::default();
+        let score_config = SkimScoreConfig {
+            bonus_head: weighted(default.bonus_head, config.separator_bonus),
+            bonus_break: weighted(default.bonus_break, config.separator_bonus),
+            bonus_first_char_multiplier: weighted(
+                default.bonus_first_char_multiplier,
+                config.first_char_bonus,
+            ),
+            bonus_consecutive: weighted(
+                default.bonus_consecutive,
+                config.consecutive_bonus,
+            ),
+            ..default
+        };
+
+        let matcher = SkimMatcherV2::default().score_config(score_config);
+        match config.case_sensitive() {
+            None => matcher.smart_case(),
+            Some(true) => matcher.respect_case(),
+            Some(false) => matcher.ignore_case(),
+        }
+    }
+
     fn filter_items(
         _run_id: &str,
         input: &str,
@@ -1316,7 +2303,7 @@ fn filter_items(
                     matcher.fuzzy_indices(&i.filter_text, input)
                 {
                     let mut item = i.clone();
-                    item.score = score;
+                    item.score += score;
                     item.indices = indices;
                     Some(item)
                 } else {
@@ -1352,4 +2339,277 @@ fn filter_items_can_handle_large_number_of_items() {
         // Previous implementation of this function would crash the program
         let _view = PaletteViewData::filter_items("1", "s", items, &matcher);
     }
+
+    fn filtered_texts(
+        input: &str,
+        candidates: &[&str],
+        config: &PaletteConfig,
+    ) -> Vec<String> {
+        let items: im::Vector<PaletteItem> = candidates
+            .iter()
+            .map(|text| PaletteItem {
+                content: PaletteItemContent::ColorTheme(text.to_string()),
+                filter_text: text.to_string(),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+        let matcher = PaletteViewData::matcher(config);
+        PaletteViewData::filter_items("1", input, items, &matcher)
+            .into_iter()
+            .map(|item| item.filter_text)
+            .collect()
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    fn buffer_symbols_are_flattened_and_deduplicated() {
+        let range = |line| Range {
+            start: Position { line, character: 4 },
+            end: Position { line, character: 8 },
+        };
+        let symbol = |name: &str, line, children| DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            range: range(line),
+            selection_range: range(line),
+            children,
+        };
+        let resp = || {
+            DocumentSymbolResponse::Nested(vec![symbol(
+                "Foo",
+                0,
+                Some(vec![symbol("new", 1, None), symbol("bar", 5, None)]),
+            )])
+        };
+
+        let mut symbols = BufferSymbols::default();
+        symbols.extend(Path::new("a.rs"), resp());
+        symbols.extend(Path::new("a.rs"), resp());
+        symbols.extend(Path::new("b.rs"), resp());
+
+        let names: Vec<_> = symbols
+            .items
+            .iter()
+            .map(|item| item.filter_text.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["newFoo", "barFoo", "Foo", "newFoo", "barFoo", "Foo"]
+        );
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    fn buffer_symbols_replace_the_project_index() {
+        let url = |name: &str| {
+            lsp_types::Url::parse(&format!("file:///project/{name}")).unwrap()
+        };
+        let position = |line, character| Position { line, character };
+        let index_symbol = |name: &str, file: &str, line| SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::STRUCT,
+            tags: None,
+            deprecated: None,
+            location: lsp_types::Location {
+                uri: url(file),
+                range: Range {
+                    start: position(line, 0),
+                    end: position(line + 2, 1),
+                },
+            },
+            container_name: None,
+        };
+        let index = || {
+            vec![
+                index_symbol("Foo", "a.rs", 0),
+                index_symbol("Other", "c.rs", 3),
+            ]
+        };
+        let range = Range {
+            start: position(0, 7),
+            end: position(0, 10),
+        };
+        let resp = DocumentSymbolResponse::Nested(vec![DocumentSymbol {
+            name: "Foo".to_string(),
+            detail: None,
+            kind: SymbolKind::STRUCT,
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range: range,
+            children: None,
+        }]);
+
+        let mut symbols = BufferSymbols::default();
+        symbols.extend_index(index());
+        symbols.extend(&path_from_url(&url("a.rs")), resp);
+        symbols.extend_index(index());
+
+        let names: Vec<_> = symbols
+            .items
+            .iter()
+            .map(|item| item.filter_text.as_str())
+            .collect();
+        assert_eq!(names, vec!["Foo", "Other"]);
+        // The symbol is at its place in the buffer
+        match &symbols.items[0].content {
+            PaletteItemContent::BufferSymbol { location, .. } => {
+                assert_eq!(location.position, Some(position(0, 7)));
+            }
+            _ => panic!("not a buffer symbol"),
+        }
+    }
+
+    /// The timers fired for the events sent by `send`, which has to wait for
+    /// them to run out
+    fn fired_plugin_timers(
+        send: impl FnOnce(&Sender<PluginTimerEvent>),
+    ) -> Vec<PluginTimer> {
+        let (sender, receiver) = unbounded();
+        let (fired_sender, fired) = unbounded();
+        let timers = std::thread::spawn(move || {
+            run_plugin_timers(
+                receiver,
+                Duration::from_millis(20),
+                Duration::from_millis(60),
+                |timer| {
+                    let _ = fired_sender.send(timer);
+                },
+            )
+        });
+        send(&sender);
+        drop(sender);
+        timers.join().unwrap();
+        fired.try_iter().collect()
+    }
+
+    #[test]
+    fn plugin_timers_keep_the_last_request() {
+        let wait = || std::thread::sleep(Duration::from_millis(150));
+        let request = |run_id: &str| PluginTimer::Request(run_id.to_string());
+        let timeout = |run_id: &str| PluginTimer::Timeout(run_id.to_string());
+
+        // Typing replaces the request that is waiting
+        let fired = fired_plugin_timers(|sender| {
+            sender.send(PluginTimerEvent::Start(request("a"))).unwrap();
+            sender.send(PluginTimerEvent::Start(request("ab"))).unwrap();
+            wait();
+            sender.send(PluginTimerEvent::Start(timeout("ab"))).unwrap();
+            sender
+                .send(PluginTimerEvent::Start(timeout("abc")))
+                .unwrap();
+            wait();
+        });
+        assert_eq!(fired, vec![request("ab"), timeout("abc")]);
+
+        // Closing the palette stops them
+        let fired = fired_plugin_timers(|sender| {
+            sender.send(PluginTimerEvent::Start(request("a"))).unwrap();
+            sender.send(PluginTimerEvent::Start(timeout("a"))).unwrap();
+            sender.send(PluginTimerEvent::Cancel).unwrap();
+            wait();
+        });
+        assert!(fired.is_empty());
+    }
+
+    #[test]
+    fn frecency_decays_over_time() {
+        let half_life = FRECENCY_HALF_LIFE as u64;
+        let mut frecency = PaletteFrecency::default();
+        frecency.record("old", 0);
+        frecency.record("old", 0);
+        frecency.record("new", half_life * 2);
+
+        let now = half_life * 2;
+        assert_eq!(frecency.score("old", now), 0.5);
+        assert_eq!(frecency.score("new", now), 1.0);
+        assert_eq!(frecency.commands(now), vec!["new", "old"]);
+        assert!(frecency.bonus("new", now) > frecency.bonus("old", now));
+
+        // Stale commands are eventually forgotten
+        let now = half_life * 10;
+        frecency.record("new", now);
+        assert_eq!(frecency.commands(now), vec!["new"]);
+    }
+
+    #[test]
+    fn frecency_bonus_lifts_weaker_match() {
+        let mut frecency = PaletteFrecency::default();
+        for _ in 0..5 {
+            frecency.record("palette.folder", 0);
+        }
+
+        let items = |folder_score: i64| -> im::Vector<PaletteItem> {
+            ["Open File", "Open Folder"]
+                .into_iter()
+                .map(|text| PaletteItem {
+                    content: PaletteItemContent::ColorTheme(text.to_string()),
+                    filter_text: text.to_string(),
+                    score: if text == "Open Folder" {
+                        folder_score
+                    } else {
+                        0
+                    },
+                    indices: vec![],
+                })
+                .collect()
+        };
+        let matcher = PaletteViewData::matcher(&PaletteConfig::default());
+
+        let filtered =
+            PaletteViewData::filter_items("1", "open fle", items(0), &matcher);
+        assert_eq!(filtered[0].filter_text, "Open File");
+
+        let bonus = frecency.bonus("palette.folder", 0);
+        let filtered =
+            PaletteViewData::filter_items("1", "open fle", items(bonus), &matcher);
+        assert_eq!(filtered[0].filter_text, "Open Folder");
+    }
+
+    #[test]
+    fn filter_items_separator_bonus() {
+        let candidates = ["foobar", "foo/bar"];
+
+        let config = PaletteConfig::default();
+        assert_eq!(
+            filtered_texts("b", &candidates, &config),
+            vec!["foo/bar", "foobar"]
+        );
+
+        // Without the bonus both match equally, so the original order is kept
+        let config = PaletteConfig {
+            separator_bonus: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            filtered_texts("b", &candidates, &config),
+            vec!["foobar", "foo/bar"]
+        );
+    }
+
+    #[test]
+    fn filter_items_case_sensitivity() {
+        let candidates = ["readme", "README"];
+
+        // Smart case ignores case for a lowercase input only
+        let config = PaletteConfig::default();
+        assert_eq!(filtered_texts("read", &candidates, &config).len(), 2);
+        assert_eq!(filtered_texts("READ", &candidates, &config), vec!["README"]);
+
+        let config = PaletteConfig {
+            case_sensitivity: "sensitive".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(filtered_texts("read", &candidates, &config), vec!["readme"]);
+
+        let config = PaletteConfig {
+            case_sensitivity: "insensitive".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(filtered_texts("READ", &candidates, &config).len(), 2);
+    }
 }
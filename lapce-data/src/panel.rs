@@ -5,6 +5,13 @@
 
 pub type PanelOrder = im::HashMap<PanelPosition, im::Vector<PanelKind>>;
 
+/// The size a panel container is dragged under to hide it
+pub const PANEL_HIDE_SIZE: f64 = 90.0;
+/// The smallest size a panel container is resized to
+pub const PANEL_MIN_SIZE: f64 = 180.0;
+/// The smallest part of a panel container the panels split in it take
+const PANEL_MIN_SPLIT: f64 = 0.1;
+
 #[derive(Clone, Copy, PartialEq, Data, Serialize, Deserialize, Hash, Eq, Debug)]
 pub enum PanelKind {
     FileExplorer,
@@ -13,9 +20,10 @@ pub enum PanelKind {
     Terminal,
     Search,
     Problem,
+    RenamePreview,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PanelSize {
     pub left: f64,
     pub left_split: f64,
@@ -25,6 +33,43 @@ pub struct PanelSize {
     pub right_split: f64,
 }
 
+impl PanelSize {
+    /// The width of the side containers, or the height of the bottom one
+    pub fn container(&self, position: &PanelContainerPosition) -> f64 {
+        match position {
+            PanelContainerPosition::Left => self.left,
+            PanelContainerPosition::Bottom => self.bottom,
+            PanelContainerPosition::Right => self.right,
+        }
+    }
+
+    fn container_mut(&mut self, position: &PanelContainerPosition) -> &mut f64 {
+        match position {
+            PanelContainerPosition::Left => &mut self.left,
+            PanelContainerPosition::Bottom => &mut self.bottom,
+            PanelContainerPosition::Right => &mut self.right,
+        }
+    }
+
+    /// The part of the container the first of its two panels takes
+    pub fn split(&self, position: &PanelContainerPosition) -> f64 {
+        match position {
+            PanelContainerPosition::Left => self.left_split,
+            PanelContainerPosition::Bottom => self.bottom_split,
+            PanelContainerPosition::Right => self.right_split,
+        }
+    }
+
+    pub fn set_split(&mut self, position: &PanelContainerPosition, split: f64) {
+        let split = split.clamp(PANEL_MIN_SPLIT, 1.0 - PANEL_MIN_SPLIT);
+        match position {
+            PanelContainerPosition::Left => self.left_split = split,
+            PanelContainerPosition::Bottom => self.bottom_split = split,
+            PanelContainerPosition::Right => self.right_split = split,
+        }
+    }
+}
+
 impl PanelKind {
     pub fn svg_name(&self) -> &'static str {
         match &self {
@@ -34,18 +79,51 @@ pub fn svg_name(&self) -> &'static str {
             PanelKind::Terminal => LapceIcons::TERMINAL,
             PanelKind::Search => LapceIcons::SEARCH,
             PanelKind::Problem => LapceIcons::PROBLEM,
+            PanelKind::RenamePreview => LapceIcons::SCM_DIFF_RENAMED,
+        }
+    }
+}
+
+/// What the count of a panel badge is about, which gives the badge its color
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelBadgeKind {
+    Error,
+    Warning,
+    /// A count that doesn't need attention, like the changed files
+    Info,
+}
+
+/// The count shown over the icon of a panel, like the number of problems
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PanelBadge {
+    pub count: usize,
+    pub kind: PanelBadgeKind,
+}
+
+impl PanelBadge {
+    /// The badge of the count, or `None` when there's nothing to count
+    pub fn new(count: usize, kind: PanelBadgeKind) -> Option<Self> {
+        (count > 0).then_some(Self { count, kind })
+    }
+
+    /// The text of the badge, with the large counts cut short
+    pub fn text(&self) -> String {
+        if self.count > 99 {
+            "99+".to_string()
+        } else {
+            self.count.to_string()
         }
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PanelStyle {
     pub active: usize,
     pub shown: bool,
     pub maximized: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PanelData {
     pub active: PanelPosition,
     pub order: PanelOrder,
@@ -256,6 +334,66 @@ pub fn show_panel(&mut self, kind: &PanelKind) {
         }
     }
 
+    /// Move the panel after the panels at the position, where it's shown.
+    /// Returns whether it moved, which it doesn't when it's already there.
+    pub fn move_panel(&mut self, kind: &PanelKind, position: PanelPosition) -> bool {
+        let (index, from) = match self.panel_position(kind) {
+            Some(found) => found,
+            None => return false,
+        };
+        if from == position {
+            return false;
+        }
+
+        let mut from_len = 0;
+        if let Some(order) = self.order.get_mut(&from) {
+            order.remove(index);
+            from_len = order.len();
+        }
+        // The panel which was active where it was moved from stays active
+        if let Some(style) = self.style.get_mut(&from) {
+            if style.active > index {
+                style.active -= 1;
+            }
+            style.active = style.active.min(from_len.saturating_sub(1));
+        }
+        if from_len == 0 {
+            self.set_shown(&from, false);
+        }
+
+        let order = self.order.entry(position).or_insert_with(im::Vector::new);
+        order.push_back(*kind);
+        let active = order.len() - 1;
+        let style = self.style.entry(position).or_insert(PanelStyle {
+            active: 0,
+            shown: true,
+            maximized: false,
+        });
+        style.active = active;
+        style.shown = true;
+        true
+    }
+
+    /// Resize the container to the size its edge is dragged to, which hides
+    /// it when it's under [`PANEL_HIDE_SIZE`]. The container then keeps the
+    /// size it had before the drag, so that it's shown again with it.
+    /// Returns whether the container should be shown.
+    pub fn drag_container_size(
+        &mut self,
+        position: &PanelContainerPosition,
+        size: f64,
+        size_before: f64,
+        maximum: f64,
+    ) -> bool {
+        let shown = size >= PANEL_HIDE_SIZE;
+        *self.size.container_mut(position) = if shown {
+            size.max(PANEL_MIN_SIZE).min(maximum)
+        } else {
+            size_before
+        };
+        shown
+    }
+
     pub fn is_panel_visible(&self, kind: &PanelKind) -> bool {
         if let Some((index, position)) = self.panel_position(kind) {
             if let Some(style) = self.style.get(&position) {
@@ -269,9 +407,12 @@ pub fn is_panel_visible(&self, kind: &PanelKind) -> bool {
 #[derive(Clone, PartialEq, Eq)]
 pub enum PanelResizePosition {
     Left,
+    /// Between the two panels of the left container
     LeftSplit,
     Right,
+    RightSplit,
     Bottom,
+    BottomSplit,
 }
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
@@ -329,3 +470,104 @@ pub fn second(&self) -> PanelPosition {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        PanelContainerPosition, PanelData, PanelKind, PanelOrder, PanelPosition,
+        PANEL_MIN_SIZE,
+    };
+
+    fn panel_data() -> PanelData {
+        let mut order = PanelOrder::new();
+        order.insert(
+            PanelPosition::LeftTop,
+            im::vector![
+                PanelKind::FileExplorer,
+                PanelKind::Plugin,
+                PanelKind::SourceControl,
+            ],
+        );
+        order.insert(
+            PanelPosition::BottomLeft,
+            im::vector![PanelKind::Terminal, PanelKind::Search],
+        );
+        PanelData::new(order)
+    }
+
+    #[test]
+    fn test_layout_round_trip() {
+        let mut panel = panel_data();
+        panel.move_panel(&PanelKind::Search, PanelPosition::RightBottom);
+        panel.size.left = 320.0;
+        panel.size.set_split(&PanelContainerPosition::Bottom, 0.3);
+        panel.toggle_bottom_maximize();
+
+        let saved = serde_json::to_string(&panel).unwrap();
+        let loaded: PanelData = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, panel);
+        assert_eq!(
+            loaded.panel_position(&PanelKind::Search),
+            Some((0, PanelPosition::RightBottom))
+        );
+        assert_eq!(loaded.size.split(&PanelContainerPosition::Bottom), 0.3);
+    }
+
+    #[test]
+    fn test_move_panel() {
+        let mut panel = panel_data();
+        panel.show_panel(&PanelKind::SourceControl);
+
+        assert!(panel.move_panel(&PanelKind::FileExplorer, PanelPosition::RightTop));
+        assert_eq!(
+            panel.panel_position(&PanelKind::FileExplorer),
+            Some((0, PanelPosition::RightTop))
+        );
+        assert!(panel.is_panel_visible(&PanelKind::FileExplorer));
+        assert!(panel.is_container_shown(&PanelContainerPosition::Right));
+        // The panel which was active on the left stays active
+        assert!(panel.is_panel_visible(&PanelKind::SourceControl));
+
+        // Moving a panel after the others
+        assert!(panel.move_panel(&PanelKind::Terminal, PanelPosition::RightTop));
+        assert_eq!(
+            panel.order[&PanelPosition::RightTop],
+            im::vector![PanelKind::FileExplorer, PanelKind::Terminal]
+        );
+        assert!(panel.is_panel_visible(&PanelKind::Terminal));
+        assert!(!panel.is_panel_visible(&PanelKind::FileExplorer));
+        // and back, which leaves the other panel active
+        assert!(panel.move_panel(&PanelKind::Terminal, PanelPosition::BottomLeft));
+        assert!(panel.is_panel_visible(&PanelKind::FileExplorer));
+        assert_eq!(
+            panel.order[&PanelPosition::BottomLeft],
+            im::vector![PanelKind::Search, PanelKind::Terminal]
+        );
+
+        // The position a panel is already at
+        assert!(!panel.move_panel(&PanelKind::Search, PanelPosition::BottomLeft));
+
+        // The position left without panels is hidden
+        assert!(panel.move_panel(&PanelKind::FileExplorer, PanelPosition::LeftTop));
+        assert!(!panel.is_position_shown(&PanelPosition::RightTop));
+        assert!(!panel.is_container_shown(&PanelContainerPosition::Right));
+    }
+
+    #[test]
+    fn test_drag_container_size() {
+        let mut panel = panel_data();
+        let left = PanelContainerPosition::Left;
+        assert!(panel.drag_container_size(&left, 400.0, 250.0, 1000.0));
+        assert_eq!(panel.size.container(&left), 400.0);
+        assert!(panel.drag_container_size(&left, 120.0, 250.0, 1000.0));
+        assert_eq!(panel.size.container(&left), PANEL_MIN_SIZE);
+        assert!(panel.drag_container_size(&left, 2000.0, 250.0, 1000.0));
+        assert_eq!(panel.size.container(&left), 1000.0);
+        // Hidden, it keeps the size it had before the drag
+        assert!(!panel.drag_container_size(&left, 40.0, 250.0, 1000.0));
+        assert_eq!(panel.size.container(&left), 250.0);
+
+        panel.size.set_split(&left, 0.95);
+        assert_eq!(panel.size.split(&left), 0.9);
+    }
+}
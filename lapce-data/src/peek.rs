@@ -0,0 +1,307 @@
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use druid::{Command, EventCtx, Target, WidgetId};
+use lsp_types::{GotoDefinitionResponse, Location, Range};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceConfig,
+    data::LapceMainSplitData,
+    document::{BufferContent, LocalBufferKind},
+    editor::EditorLocation,
+    proxy::path_from_url,
+};
+
+/// The number of lines of the target shown in the peek popup
+pub const PEEK_LINES: usize = 12;
+
+/// A definition that can be shown in the peek popup
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeekEntry {
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+impl PeekEntry {
+    fn from_location(location: Location) -> Self {
+        Self {
+            path: path_from_url(&location.uri),
+            range: location.range,
+        }
+    }
+}
+
+/// The definitions of a goto definition response in the order the server gave
+/// them, without the ones it sent more than once. The links point at the name
+/// of their target, like the goto definition command does.
+pub fn peek_entries(response: GotoDefinitionResponse) -> Vec<PeekEntry> {
+    let entries: Vec<PeekEntry> = match response {
+        GotoDefinitionResponse::Scalar(location) => {
+            vec![PeekEntry::from_location(location)]
+        }
+        GotoDefinitionResponse::Array(locations) => locations
+            .into_iter()
+            .map(PeekEntry::from_location)
+            .collect(),
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| PeekEntry {
+                path: path_from_url(&link.target_uri),
+                range: link.target_selection_range,
+            })
+            .collect(),
+    };
+
+    let mut unique = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if !unique.contains(&entry) {
+            unique.push(entry);
+        }
+    }
+    unique
+}
+
+/// The popup showing a definition in an editor below the line it was asked
+/// from, where it can be edited without leaving the current file
+#[derive(Clone)]
+pub struct PeekData {
+    pub view_id: WidgetId,
+    pub editor_id: WidgetId,
+    pub active: bool,
+    /// The editor the definitions were peeked from
+    pub from_editor: WidgetId,
+    /// The offset the definitions were peeked at, below whose line the popup
+    /// is shown
+    pub offset: usize,
+    pub entries: Vec<PeekEntry>,
+    pub index: usize,
+    /// The files that weren't open and were only loaded for the peek
+    pub transient: HashSet<PathBuf>,
+}
+
+impl PeekData {
+    pub fn new() -> Self {
+        Self {
+            view_id: WidgetId::next(),
+            editor_id: WidgetId::next(),
+            active: false,
+            from_editor: WidgetId::next(),
+            offset: 0,
+            entries: Vec::new(),
+            index: 0,
+            transient: HashSet::new(),
+        }
+    }
+
+    pub fn current(&self) -> Option<&PeekEntry> {
+        self.entries.get(self.index)
+    }
+
+    /// Move to the next definition, going back to the first one after the last
+    pub fn next(&mut self) {
+        if !self.entries.is_empty() {
+            self.index = (self.index + 1) % self.entries.len();
+        }
+    }
+
+    /// Move to the previous definition, going to the last one before the first
+    pub fn previous(&mut self) {
+        if !self.entries.is_empty() {
+            self.index = (self.index + self.entries.len() - 1) % self.entries.len();
+        }
+    }
+
+    /// Show the definitions below the line of the offset in the editor, or
+    /// nothing when there are none
+    pub fn show(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+        from_editor: WidgetId,
+        offset: usize,
+        entries: Vec<PeekEntry>,
+        config: &LapceConfig,
+    ) {
+        if entries.is_empty() {
+            return;
+        }
+        self.active = true;
+        self.from_editor = from_editor;
+        self.offset = offset;
+        self.entries = entries;
+        self.index = 0;
+        self.go_to_current(ctx, main_split, config);
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(self.view_id),
+        ));
+    }
+
+    /// Show the current definition in the editor of the popup, loading its
+    /// file without opening it in an editor tab when it isn't open yet
+    pub fn go_to_current(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+        config: &LapceConfig,
+    ) {
+        let entry = match self.current() {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+        if !main_split.open_docs.contains_key(&entry.path) {
+            self.transient.insert(entry.path.clone());
+        }
+        main_split.go_to_location(
+            ctx,
+            Some(self.view_id),
+            true,
+            EditorLocation {
+                path: entry.path,
+                position: Some(entry.range.start),
+                scroll_offset: None,
+                history: None,
+            },
+            config,
+        );
+    }
+
+    /// The location of the cursor in the editor of the popup, to jump to it
+    pub fn location(
+        &self,
+        main_split: &LapceMainSplitData,
+    ) -> Option<EditorLocation> {
+        let editor = main_split.editors.get(&self.view_id)?;
+        match &editor.content {
+            BufferContent::File(path) => Some(EditorLocation {
+                path: path.clone(),
+                position: Some(editor.cursor.offset()),
+                scroll_offset: None,
+                history: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Close the popup. The files that were only loaded for it are closed,
+    /// except the ones edited in it, which are opened in an editor tab with
+    /// their unsaved changes.
+    pub fn cancel(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+    ) {
+        self.active = false;
+        if let Some(editor) = main_split.editors.get_mut(&self.view_id) {
+            let editor = Arc::make_mut(editor);
+            editor.content = BufferContent::Local(LocalBufferKind::Empty);
+        }
+
+        for path in std::mem::take(&mut self.transient) {
+            if main_split.is_doc_shown(&path) {
+                continue;
+            }
+            let edited = main_split
+                .open_docs
+                .get(&path)
+                .map(|doc| doc.loaded() && !doc.buffer().is_pristine())
+                .unwrap_or(false);
+            if edited {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenFile(path, false),
+                    Target::Auto,
+                ));
+            } else {
+                main_split.close_doc(&path);
+            }
+        }
+    }
+}
+
+impl Default for PeekData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use lsp_types::{
+        GotoDefinitionResponse, Location, LocationLink, Position, Range, Url,
+    };
+
+    use super::{peek_entries, PeekData, PeekEntry};
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn location(path: &str, range: Range) -> Location {
+        Location::new(Url::parse(&format!("file://{path}")).unwrap(), range)
+    }
+
+    fn entry(path: &str, range: Range) -> PeekEntry {
+        PeekEntry {
+            path: PathBuf::from(path),
+            range,
+        }
+    }
+
+    #[test]
+    fn test_peek_entries() {
+        let entries = peek_entries(GotoDefinitionResponse::Scalar(location(
+            "/a.rs",
+            range(1, 3, 7),
+        )));
+        assert_eq!(entries, vec![entry("/a.rs", range(1, 3, 7))]);
+
+        // Several definitions stay in the order of the server, once each
+        let entries = peek_entries(GotoDefinitionResponse::Array(vec![
+            location("/b.rs", range(4, 0, 3)),
+            location("/a.rs", range(1, 3, 7)),
+            location("/b.rs", range(4, 0, 3)),
+        ]));
+        assert_eq!(
+            entries,
+            vec![
+                entry("/b.rs", range(4, 0, 3)),
+                entry("/a.rs", range(1, 3, 7))
+            ]
+        );
+
+        let entries =
+            peek_entries(GotoDefinitionResponse::Link(vec![LocationLink {
+                origin_selection_range: None,
+                target_uri: Url::parse("file:///c.rs").unwrap(),
+                target_range: range(2, 0, 20),
+                target_selection_range: range(2, 7, 11),
+            }]));
+        assert_eq!(entries, vec![entry("/c.rs", range(2, 7, 11))]);
+
+        assert!(peek_entries(GotoDefinitionResponse::Array(Vec::new())).is_empty());
+    }
+
+    #[test]
+    fn test_peek_navigation() {
+        let mut peek = PeekData::new();
+        peek.next();
+        peek.previous();
+        assert_eq!(peek.current(), None);
+
+        peek.entries = vec![
+            entry("/a.rs", range(0, 0, 1)),
+            entry("/b.rs", range(1, 0, 1)),
+            entry("/c.rs", range(2, 0, 1)),
+        ];
+        peek.previous();
+        assert_eq!(peek.current(), Some(&entry("/c.rs", range(2, 0, 1))));
+        peek.next();
+        assert_eq!(peek.index, 0);
+        peek.next();
+        assert_eq!(peek.current(), Some(&entry("/b.rs", range(1, 0, 1))));
+    }
+}
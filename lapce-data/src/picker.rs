@@ -5,12 +5,45 @@
 
 use druid::{ExtEventSink, Target, WidgetId};
 use lapce_rpc::{file::FileNodeItem, proxy::ProxyResponse};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
+    data::LapceWorkspace,
+    db::LapceDb,
     proxy::LapceProxy,
 };
 
+/// What a file or folder is picked for, each remembering the directory
+/// it was last picked from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PickerKind {
+    OpenFile,
+    SaveAs,
+    OpenFolder,
+}
+
+/// The remembered directory while it still exists, otherwise the workspace
+fn fallback_dir(
+    remembered: Option<PathBuf>,
+    workspace: Option<&Path>,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    remembered
+        .filter(|dir| exists(dir))
+        .or_else(|| workspace.map(|p| p.to_path_buf()))
+}
+
+/// The last tab completion of the typed path
+#[derive(Clone, Default)]
+pub struct PickerCompletion {
+    /// The typed path the completion was made for
+    pub input: String,
+    /// The names of the entries the typed path could still complete to
+    pub candidates: Vec<String>,
+}
+
 #[derive(Clone)]
 pub struct FilePickerData {
     pub widget_id: WidgetId,
@@ -20,6 +53,7 @@ pub struct FilePickerData {
     pub home: PathBuf,
     pub pwd: PathBuf,
     pub index: usize,
+    pub completion: PickerCompletion,
 }
 
 impl FilePickerData {
@@ -31,6 +65,8 @@ pub fn new() -> Self {
             open: false,
             children: HashMap::new(),
             children_open_count: 0,
+            ignored: false,
+            hidden: false,
         };
         let home = PathBuf::from("/");
         let pwd = PathBuf::from("/");
@@ -42,22 +78,56 @@ pub fn new() -> Self {
             home,
             pwd,
             index: 0,
+            completion: PickerCompletion::default(),
+        }
+    }
+
+    /// The directory a picker of `kind` starts in, `None` leaving it to the
+    /// home directory
+    pub fn start_dir(
+        db: &LapceDb,
+        kind: PickerKind,
+        workspace: &LapceWorkspace,
+    ) -> Option<PathBuf> {
+        let remembered = db
+            .get_picker_dirs()
+            .ok()
+            .and_then(|mut dirs| dirs.remove(&kind));
+        if workspace.kind.is_remote() {
+            // remote directories are only known to be gone once they're read
+            remembered.or_else(|| workspace.path.clone())
+        } else {
+            fallback_dir(remembered, workspace.path.as_deref(), |dir| dir.is_dir())
+        }
+    }
+
+    /// Remember the directory of what was picked for the next `kind` picker
+    pub fn remember_dir(db: &LapceDb, kind: PickerKind, picked: &Path) {
+        if let Some(dir) = picked.parent() {
+            let _ = db.save_picker_dir(kind, dir);
         }
     }
 
     pub fn init_home(&mut self, home: &Path) {
         self.home = home.to_path_buf();
+        self.init_root(home);
+    }
+
+    /// Rebuild the tree down from the root to `dir` and make it the pwd
+    pub fn init_root(&mut self, dir: &Path) {
         let mut current_file_node = FileNodeItem {
-            path_buf: home.to_path_buf(),
+            path_buf: dir.to_path_buf(),
             is_dir: true,
             read: false,
             open: false,
             children: HashMap::new(),
             children_open_count: 0,
+            ignored: false,
+            hidden: false,
         };
-        let mut current_path = home.to_path_buf();
+        let mut current_path = dir.to_path_buf();
 
-        let mut ancestors = home.ancestors();
+        let mut ancestors = dir.ancestors();
         ancestors.next();
 
         for p in ancestors {
@@ -68,6 +138,8 @@ pub fn init_home(&mut self, home: &Path) {
                 open: true,
                 children: HashMap::new(),
                 children_open_count: 0,
+                ignored: false,
+                hidden: false,
             };
             file_node
                 .children
@@ -76,7 +148,7 @@ pub fn init_home(&mut self, home: &Path) {
             current_path = PathBuf::from(p);
         }
         self.root = current_file_node;
-        self.pwd = home.to_path_buf();
+        self.pwd = dir.to_path_buf();
     }
 
     pub fn read_dir(
@@ -98,6 +170,76 @@ pub fn read_dir(
             }
         });
     }
+
+    /// Complete the typed path against the entries of the directory it's in,
+    /// returning the completed path when it got longer
+    pub fn complete(
+        &mut self,
+        input: &str,
+        items: &HashMap<PathBuf, FileNodeItem>,
+        case_insensitive: bool,
+    ) -> Option<String> {
+        let (dir, partial) = split_partial(input);
+        let show_hidden = partial.starts_with('.');
+        let mut candidates: Vec<(&str, bool)> = items
+            .values()
+            .filter_map(|item| {
+                let name = item.path_buf.file_name()?.to_str()?;
+                Some((name, item.is_dir))
+            })
+            .filter(|(name, _)| show_hidden || !name.starts_with('.'))
+            .filter(|(name, _)| starts_with(name, partial, case_insensitive))
+            .collect();
+        candidates.sort();
+        if candidates.is_empty() {
+            self.completion = PickerCompletion::default();
+            return None;
+        }
+
+        let names: Vec<&str> = candidates.iter().map(|(name, _)| *name).collect();
+        let mut completed = common_prefix(&names, case_insensitive);
+        if let [(_, true)] = candidates.as_slice() {
+            completed.push(std::path::MAIN_SEPARATOR);
+        }
+        let completed = format!("{dir}{completed}");
+
+        self.completion = PickerCompletion {
+            input: completed.clone(),
+            candidates: if candidates.len() > 1 {
+                names.iter().map(|name| name.to_string()).collect()
+            } else {
+                Vec::new()
+            },
+        };
+        (completed.len() > input.len()).then_some(completed)
+    }
+
+    /// Read the directory the picker opens in, going back to the home
+    /// directory when it can't be read
+    pub fn read_start_dir(
+        path: &Path,
+        home: &Path,
+        tab_id: WidgetId,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let path = PathBuf::from(path);
+        let home = PathBuf::from(home);
+        proxy.proxy_rpc.read_dir(path.clone(), move |result| {
+            let command = match result {
+                Ok(ProxyResponse::ReadDirResponse { items }) => {
+                    LapceUICommand::UpdatePickerItems(path, items)
+                }
+                _ if path != home => LapceUICommand::HomeDir(home),
+                _ => return,
+            };
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                command,
+                Target::Widget(tab_id),
+            );
+        });
+    }
 }
 
 impl Default for FilePickerData {
@@ -105,3 +247,89 @@ fn default() -> Self {
         Self::new()
     }
 }
+
+/// Split the typed path into its directory, with the trailing separator,
+/// and the partial name after it
+pub fn split_partial(input: &str) -> (&str, &str) {
+    match input.rfind(|c| c == '/' || c == std::path::MAIN_SEPARATOR) {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    }
+}
+
+fn starts_with(name: &str, prefix: &str, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        name.to_lowercase().starts_with(&prefix.to_lowercase())
+    } else {
+        name.starts_with(prefix)
+    }
+}
+
+/// The longest prefix the names share, in the casing of the first name
+pub fn common_prefix(names: &[&str], case_insensitive: bool) -> String {
+    let first = match names.first() {
+        Some(first) => *first,
+        None => return String::new(),
+    };
+    let mut len = first.len();
+    for name in &names[1..] {
+        len = first
+            .char_indices()
+            .zip(name.chars())
+            .take_while(|((_, a), b)| {
+                if case_insensitive {
+                    a.to_lowercase().eq(b.to_lowercase())
+                } else {
+                    a == b
+                }
+            })
+            .last()
+            .map(|((i, a), _)| (i + a.len_utf8()).min(len))
+            .unwrap_or(0);
+    }
+    first[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_dir() {
+        let remembered = Some(PathBuf::from("/remembered"));
+        let workspace = Path::new("/workspace");
+
+        assert_eq!(
+            fallback_dir(remembered.clone(), Some(workspace), |_| true),
+            remembered
+        );
+        assert_eq!(
+            fallback_dir(remembered.clone(), Some(workspace), |_| false),
+            Some(workspace.to_path_buf())
+        );
+        assert_eq!(fallback_dir(remembered, None, |_| false), None);
+        assert_eq!(
+            fallback_dir(None, Some(workspace), |_| true),
+            Some(workspace.to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(common_prefix(&[], false), "");
+        assert_eq!(common_prefix(&["src"], false), "src");
+        assert_eq!(common_prefix(&["target", "tests", "test"], false), "t");
+        assert_eq!(common_prefix(&["tests", "test"], false), "test");
+        assert_eq!(common_prefix(&["abc", "xyz"], false), "");
+        assert_eq!(common_prefix(&["Docs", "docker"], false), "");
+        assert_eq!(common_prefix(&["Docs", "docker"], true), "Doc");
+        assert_eq!(common_prefix(&["héllo", "hélp"], false), "hél");
+    }
+
+    #[test]
+    fn test_split_partial() {
+        assert_eq!(split_partial("/home/user/Doc"), ("/home/user/", "Doc"));
+        assert_eq!(split_partial("/home/user/"), ("/home/user/", ""));
+        assert_eq!(split_partial("Doc"), ("", "Doc"));
+    }
+}
@@ -1,6 +1,11 @@
 pub mod plugin_install_status;
 
-use std::{collections::HashSet, str::FromStr, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
 use anyhow::Result;
 use druid::{
@@ -9,8 +14,15 @@
 };
 use indexmap::IndexMap;
 use lapce_core::directory::Directory;
-use lapce_proxy::plugin::{download_volt, volt_icon, wasi::find_all_volts};
+use lapce_proxy::plugin::{
+    download::{
+        cancel_volt_download, download_error_message, register_volt_download,
+    },
+    download_volt, install_volt_from_file, volt_icon,
+    wasi::find_all_volts,
+};
 use lapce_rpc::plugin::{VoltID, VoltInfo, VoltMetadata};
+use lsp_types::{MessageType, ShowMessageParams};
 use parking_lot::Mutex;
 use plugin_install_status::PluginInstallStatus;
 use serde::{Deserialize, Serialize};
@@ -135,6 +147,39 @@ pub fn failed(&mut self) {
     }
 }
 
+/// Installs a volt other than the latest one of the registry, the version it
+/// installs is pinned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PluginCommand {
+    InstallVersion {
+        id: VoltID,
+        version: String,
+    },
+    /// Install a volt archive from the disk, for when the registry can't be used
+    InstallFromFile(PathBuf),
+    /// Stop the volt and start it again from its directory, to try the changes
+    /// made to it while developing it
+    Reload(VoltID),
+}
+
+impl PluginCommand {
+    /// Parse an `author.name@version` input into an `InstallVersion`
+    pub fn parse_install_version(input: &str) -> Option<Self> {
+        let (id, version) = input.trim().split_once('@')?;
+        let (author, name) = id.split_once('.')?;
+        if author.is_empty() || name.is_empty() || version.is_empty() {
+            return None;
+        }
+        Some(PluginCommand::InstallVersion {
+            id: VoltID {
+                author: author.to_string(),
+                name: name.to_string(),
+            },
+            version: version.to_string(),
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct PluginData {
     pub widget_id: WidgetId,
@@ -149,6 +194,8 @@ pub struct PluginData {
     pub installed_icons: im::HashMap<VoltID, VoltIconKind>,
     pub disabled: HashSet<VoltID>,
     pub workspace_disabled: HashSet<VoltID>,
+    /// The volts that are kept at the given version instead of being upgraded
+    pub pinned: HashMap<VoltID, String>,
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -169,6 +216,7 @@ pub fn new(
         tab_id: WidgetId,
         disabled: Vec<VoltID>,
         workspace_disabled: Vec<VoltID>,
+        pinned: Vec<(VoltID, String)>,
         event_sink: ExtEventSink,
     ) -> Self {
         {
@@ -190,6 +238,7 @@ pub fn new(
             installed_icons: im::HashMap::new(),
             disabled: HashSet::from_iter(disabled.into_iter()),
             workspace_disabled: HashSet::from_iter(workspace_disabled.into_iter()),
+            pinned: HashMap::from_iter(pinned.into_iter()),
         }
     }
 
@@ -220,6 +269,10 @@ pub fn plugin_status(&self, id: &VoltID) -> PluginStatus {
         }
 
         if let Some(meta) = self.installed.get(id) {
+            if self.pinned.contains_key(id) {
+                return PluginStatus::Installed;
+            }
+
             if let Some(volt) = self
                 .installed_latest
                 .get(id)
@@ -366,14 +419,14 @@ pub fn install_volt(proxy: Arc<LapceProxy>, volt: VoltInfo) -> Result<()> {
         if volt.wasm {
             proxy.proxy_rpc.install_volt(volt);
         } else {
+            register_volt_download(&volt.id());
             std::thread::spawn(move || -> Result<()> {
-                let download_volt_result = download_volt(&volt);
+                let download_volt_result = download_volt(&volt, &proxy.core_rpc);
                 if let Err(err) = download_volt_result {
                     log::warn!("download_volt err: {err:?}");
-                    proxy.core_rpc.volt_installing(
-                        volt.clone(),
-                        "Could not download Plugin".to_string(),
-                    );
+                    proxy
+                        .core_rpc
+                        .volt_installing(volt.clone(), download_error_message(&err));
                     return Ok(());
                 }
 
@@ -386,6 +439,107 @@ pub fn install_volt(proxy: Arc<LapceProxy>, volt: VoltInfo) -> Result<()> {
         Ok(())
     }
 
+    /// Errors are reported in a message, as the volt may not be known yet
+    pub fn run_command(
+        tab_id: WidgetId,
+        proxy: Arc<LapceProxy>,
+        command: PluginCommand,
+        event_sink: ExtEventSink,
+    ) {
+        std::thread::spawn(move || {
+            let result = match command {
+                PluginCommand::InstallVersion { id, version } => {
+                    Self::install_version(
+                        tab_id,
+                        proxy.clone(),
+                        &id,
+                        &version,
+                        &event_sink,
+                    )
+                    .map_err(|err| {
+                        err.context(format!("Could not install {id}@{version}"))
+                    })
+                }
+                PluginCommand::InstallFromFile(path) => {
+                    Self::install_from_file(tab_id, &proxy, &path, &event_sink)
+                        .map_err(|err| {
+                            err.context(format!(
+                                "Could not install the plugin from {}",
+                                path.display()
+                            ))
+                        })
+                }
+                PluginCommand::Reload(id) => {
+                    proxy.proxy_rpc.reload_volt(id);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                log::warn!("{err:?}");
+                proxy.core_rpc.show_message(
+                    "Plugin".to_string(),
+                    ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: format!("{err:#}"),
+                    },
+                );
+            }
+        });
+    }
+
+    fn install_version(
+        tab_id: WidgetId,
+        proxy: Arc<LapceProxy>,
+        id: &VoltID,
+        version: &str,
+        event_sink: &ExtEventSink,
+    ) -> Result<()> {
+        let url = format!(
+            "https://plugins.lapce.dev/api/v1/plugins/{}/{}/{}",
+            id.author, id.name, version
+        );
+        let resp = reqwest::blocking::get(url)?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("can't find the version in the registry"));
+        }
+        let volt: VoltInfo = resp.json()?;
+
+        let _ = event_sink.submit_command(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::PinVolt(volt.id(), volt.version.clone()),
+            Target::Widget(tab_id),
+        );
+        Self::install_volt(proxy, volt)
+    }
+
+    fn install_from_file(
+        tab_id: WidgetId,
+        proxy: &LapceProxy,
+        path: &Path,
+        event_sink: &ExtEventSink,
+    ) -> Result<()> {
+        let meta = install_volt_from_file(path)?;
+        let _ = event_sink.submit_command(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::PinVolt(meta.id(), meta.version.clone()),
+            Target::Widget(tab_id),
+        );
+        if meta.wasm.is_some() {
+            // Restarts the volt if an other version of it was running
+            proxy.proxy_rpc.reload_volt(meta.id());
+        }
+        let icon = volt_icon(&meta);
+        proxy.core_rpc.volt_installed(meta, icon);
+        Ok(())
+    }
+
+    /// Abort the download of a volt being installed
+    pub fn cancel_volt_install(proxy: &LapceProxy, volt_id: VoltID) {
+        // Wasm volts are downloaded by the proxy, the others by us
+        proxy.proxy_rpc.cancel_volt_download(volt_id.clone());
+        cancel_volt_download(volt_id);
+    }
+
     pub fn remove_volt(proxy: Arc<LapceProxy>, meta: VoltMetadata) -> Result<()> {
         proxy.core_rpc.volt_removing(meta.clone(), "".to_string());
         if meta.wasm.is_some() {
@@ -457,3 +611,37 @@ pub enum PluginStatus {
     Upgrade(String),
     Disabled,
 }
+
+#[cfg(test)]
+mod test {
+    use lapce_rpc::plugin::VoltID;
+
+    use crate::plugin::PluginCommand;
+
+    #[test]
+    fn test_parse_install_version() {
+        assert_eq!(
+            PluginCommand::parse_install_version(" lapce.lapce-rust@0.3.1 "),
+            Some(PluginCommand::InstallVersion {
+                id: VoltID {
+                    author: "lapce".to_string(),
+                    name: "lapce-rust".to_string(),
+                },
+                version: "0.3.1".to_string(),
+            })
+        );
+        assert_eq!(
+            PluginCommand::parse_install_version("lapce.lapce-rust"),
+            None
+        );
+        assert_eq!(
+            PluginCommand::parse_install_version("lapce-rust@0.3.1"),
+            None
+        );
+        assert_eq!(PluginCommand::parse_install_version("lapce.@0.3.1"), None);
+        assert_eq!(
+            PluginCommand::parse_install_version("lapce.lapce-rust@"),
+            None
+        );
+    }
+}
@@ -9,6 +9,8 @@ pub struct PluginInstallStatus {
     error: String,
     plugin_name: String,
     install_type: PluginInstallType,
+    /// The downloaded part of the volt, between 0 and 1, when it's known
+    progress: Option<f64>,
 }
 
 impl PluginInstallStatus {
@@ -21,6 +23,7 @@ pub fn new(
             error,
             plugin_name: plugin_name.to_string(),
             install_type,
+            progress: None,
         }
     }
 
@@ -28,6 +31,14 @@ pub fn set_error(&mut self, error_string: &str) {
         self.error = error_string.to_string();
     }
 
+    pub fn set_progress(&mut self, progress: Option<f64>) {
+        self.progress = progress;
+    }
+
+    pub fn progress(&self) -> Option<f64> {
+        self.progress
+    }
+
     pub fn error_string(&self) -> &str {
         &self.error
     }
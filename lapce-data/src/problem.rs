@@ -1,30 +1,593 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
 
 use druid::WidgetId;
+use lapce_rpc::plugin::PluginId;
+use lsp_types::{
+    CodeActionKind, CodeActionOrCommand, CodeActionResponse, Diagnostic,
+    DiagnosticSeverity,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::{CommandKind, LapceCommand, LapceWorkbenchCommand},
+    data::EditorDiagnostic,
+    editor::workspace_edits,
+    menu::{escape_mnemonic, MenuItem, MenuKind},
+    panel::{PanelBadge, PanelBadgeKind},
+};
+
+/// The severities the problem panel knows about, from the most to the least
+/// severe
+pub const SEVERITIES: [DiagnosticSeverity; 4] = [
+    DiagnosticSeverity::ERROR,
+    DiagnosticSeverity::WARNING,
+    DiagnosticSeverity::INFORMATION,
+    DiagnosticSeverity::HINT,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemGrouping {
+    /// A header per file, with the diagnostics of every severity under it
+    ByFile,
+    /// A header per severity, with the files that have such diagnostics under it
+    BySeverity,
+}
+
+impl Default for ProblemGrouping {
+    fn default() -> Self {
+        Self::BySeverity
+    }
+}
+
+/// Which diagnostics the problem panel lists
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProblemFilter {
+    /// The least severe diagnostics that are still listed
+    pub min_severity: DiagnosticSeverity,
+    /// The diagnostic sources, like `clippy`, whose diagnostics aren't listed
+    pub hidden_sources: BTreeSet<String>,
+}
+
+impl Default for ProblemFilter {
+    fn default() -> Self {
+        Self {
+            min_severity: DiagnosticSeverity::WARNING,
+            hidden_sources: BTreeSet::new(),
+        }
+    }
+}
+
+impl ProblemFilter {
+    pub fn matches(&self, diagnostic: &Diagnostic) -> bool {
+        severity(diagnostic) <= self.min_severity
+            && !diagnostic
+                .source
+                .as_ref()
+                .map(|source| self.hidden_sources.contains(source))
+                .unwrap_or(false)
+    }
+}
+
+/// A group of the problem panel, with the files in it sorted by their path
+pub struct ProblemGroup<'a> {
+    /// The severity of the diagnostics in the group, or `None` when the panel
+    /// is grouped by file
+    pub severity: Option<DiagnosticSeverity>,
+    pub files: Vec<(&'a PathBuf, Vec<&'a EditorDiagnostic>)>,
+}
 
 #[derive(Clone)]
 pub struct ProblemData {
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
-    pub error_widget_id: WidgetId,
-    pub warning_widget_id: WidgetId,
+    pub content_widget_id: WidgetId,
     pub collapsed: HashMap<PathBuf, bool>,
+    pub grouping: ProblemGrouping,
+    pub filter: ProblemFilter,
 }
 
 impl ProblemData {
-    pub fn new() -> Self {
+    pub fn new(grouping: ProblemGrouping) -> Self {
         Self {
             widget_id: WidgetId::next(),
             split_id: WidgetId::next(),
-            error_widget_id: WidgetId::next(),
-            warning_widget_id: WidgetId::next(),
+            content_widget_id: WidgetId::next(),
             collapsed: HashMap::new(),
+            grouping,
+            filter: ProblemFilter::default(),
+        }
+    }
+
+    /// The diagnostics that pass the filter, grouped the way the panel is set
+    /// to group them
+    pub fn groups<'a>(
+        &self,
+        diagnostics: impl IntoIterator<Item = (&'a PathBuf, &'a [EditorDiagnostic])>,
+    ) -> Vec<ProblemGroup<'a>> {
+        let mut files: Vec<(&PathBuf, Vec<&EditorDiagnostic>)> = diagnostics
+            .into_iter()
+            .map(|(path, diagnostics)| {
+                let diagnostics = diagnostics
+                    .iter()
+                    .filter(|d| self.filter.matches(&d.diagnostic))
+                    .collect::<Vec<_>>();
+                (path, diagnostics)
+            })
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+
+        match self.grouping {
+            ProblemGrouping::ByFile => {
+                if files.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![ProblemGroup {
+                        severity: None,
+                        files,
+                    }]
+                }
+            }
+            ProblemGrouping::BySeverity => SEVERITIES
+                .iter()
+                .filter_map(|s| {
+                    let files = files
+                        .iter()
+                        .filter_map(|(path, diagnostics)| {
+                            let diagnostics = diagnostics
+                                .iter()
+                                .filter(|d| severity(&d.diagnostic) == *s)
+                                .copied()
+                                .collect::<Vec<_>>();
+                            (!diagnostics.is_empty()).then_some((*path, diagnostics))
+                        })
+                        .collect::<Vec<_>>();
+                    (!files.is_empty()).then_some(ProblemGroup {
+                        severity: Some(*s),
+                        files,
+                    })
+                })
+                .collect(),
         }
     }
 }
 
 impl Default for ProblemData {
     fn default() -> Self {
-        Self::new()
+        Self::new(ProblemGrouping::default())
+    }
+}
+
+/// A code action offered for a diagnostic of the problem panel, which is the
+/// data of [`LapceWorkbenchCommand::RunProblemCodeAction`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProblemCodeAction {
+    /// The file of the diagnostic
+    pub path: PathBuf,
+    pub plugin_id: PluginId,
+    pub action: CodeActionOrCommand,
+}
+
+/// The context menu items of the code actions a server returned for a
+/// diagnostic, noting how many files the actions that edit several of them
+/// touch. The refactorings and source actions are grouped in submenus.
+pub fn code_action_menu(
+    path: &Path,
+    plugin_id: PluginId,
+    resp: &CodeActionResponse,
+) -> Vec<MenuKind> {
+    if resp.is_empty() {
+        return vec![MenuKind::Item(MenuItem {
+            desc: Some("No Code Actions Available".to_string()),
+            command: LapceCommand {
+                kind: CommandKind::Workbench(
+                    LapceWorkbenchCommand::RunProblemCodeAction,
+                ),
+                data: None,
+            },
+            enabled: false,
+        })];
+    }
+
+    // Refactorings and source actions are kept in submenus, so that the fixes
+    // of the diagnostic are the first thing in the menu
+    let mut fixes = Vec::new();
+    let mut refactors = Vec::new();
+    let mut sources = Vec::new();
+    for action in resp.iter() {
+        let (title, files, kind) = match action {
+            CodeActionOrCommand::Command(c) => (&c.title, 0, None),
+            CodeActionOrCommand::CodeAction(a) => (
+                &a.title,
+                a.edit
+                    .as_ref()
+                    .and_then(workspace_edits)
+                    .map(|edits| edits.len())
+                    .unwrap_or(0),
+                a.kind.as_ref(),
+            ),
+        };
+        let title = escape_mnemonic(title);
+        let desc = if files > 1 {
+            format!("{title} ({files} files)")
+        } else {
+            title
+        };
+        let action = ProblemCodeAction {
+            path: path.to_path_buf(),
+            plugin_id,
+            action: action.clone(),
+        };
+        let item = MenuKind::Item(MenuItem {
+            desc: Some(desc),
+            command: LapceCommand {
+                kind: CommandKind::Workbench(
+                    LapceWorkbenchCommand::RunProblemCodeAction,
+                ),
+                data: serde_json::to_value(action).ok(),
+            },
+            enabled: true,
+        });
+        let kind = kind.map(|kind| kind.as_str()).unwrap_or("");
+        if kind.starts_with(CodeActionKind::REFACTOR.as_str()) {
+            refactors.push(item);
+        } else if kind.starts_with(CodeActionKind::SOURCE.as_str()) {
+            sources.push(item);
+        } else {
+            fixes.push(item);
+        }
+    }
+
+    fixes.push(MenuKind::Separator);
+    fixes.push(MenuKind::Submenu {
+        desc: "Refactor".to_string(),
+        items: refactors,
+    });
+    fixes.push(MenuKind::Submenu {
+        desc: "Source Action".to_string(),
+        items: sources,
+    });
+    MenuKind::tidy(fixes)
+}
+
+/// The severity of the diagnostic, which is an error when the server leaves it
+/// out
+pub fn severity(diagnostic: &Diagnostic) -> DiagnosticSeverity {
+    diagnostic.severity.unwrap_or(DiagnosticSeverity::ERROR)
+}
+
+/// The diagnostic of a file to go to from the offset: the first one starting
+/// after it, or the last one starting before it when going backward. It wraps
+/// around the end of the file, and skips the diagnostics less severe than
+/// `min_severity`.
+pub fn next_diagnostic(
+    diagnostics: &[EditorDiagnostic],
+    offset: usize,
+    min_severity: DiagnosticSeverity,
+    forward: bool,
+) -> Option<&EditorDiagnostic> {
+    let mut diagnostics: Vec<&EditorDiagnostic> = diagnostics
+        .iter()
+        .filter(|d| severity(&d.diagnostic) <= min_severity)
+        .collect();
+    diagnostics.sort_by_key(|d| d.range);
+
+    if forward {
+        diagnostics
+            .iter()
+            .find(|d| d.range.0 > offset)
+            .or_else(|| diagnostics.first())
+            .copied()
+    } else {
+        diagnostics
+            .iter()
+            .rev()
+            .find(|d| d.range.0 < offset)
+            .or_else(|| diagnostics.last())
+            .copied()
+    }
+}
+
+/// How many diagnostics there are of each of the [`SEVERITIES`], regardless of
+/// the filter
+pub fn severity_counts<'a>(
+    diagnostics: impl IntoIterator<Item = (&'a PathBuf, &'a [EditorDiagnostic])>,
+) -> [usize; 4] {
+    let mut counts = [0; 4];
+    for (_, diagnostics) in diagnostics {
+        for d in diagnostics {
+            let severity = severity(&d.diagnostic);
+            if let Some(i) = SEVERITIES.iter().position(|s| *s == severity) {
+                counts[i] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// The badge of the problem panel, counting the errors and the warnings
+pub fn problem_badge(
+    error_count: usize,
+    warning_count: usize,
+) -> Option<PanelBadge> {
+    let kind = if error_count > 0 {
+        PanelBadgeKind::Error
+    } else {
+        PanelBadgeKind::Warning
+    };
+    PanelBadge::new(error_count + warning_count, kind)
+}
+
+/// The sources the diagnostics come from, to pick the hidden ones from
+pub fn diagnostic_sources<'a>(
+    diagnostics: impl IntoIterator<Item = (&'a PathBuf, &'a [EditorDiagnostic])>,
+) -> BTreeSet<String> {
+    diagnostics
+        .into_iter()
+        .flat_map(|(_, diagnostics)| diagnostics.iter())
+        .filter_map(|d| d.diagnostic.source.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+
+    use super::*;
+
+    fn diagnostic(
+        severity: DiagnosticSeverity,
+        source: &str,
+        message: &str,
+    ) -> EditorDiagnostic {
+        EditorDiagnostic {
+            range: (0, 0),
+            diagnostic: Diagnostic {
+                range: Range::new(Position::new(0, 0), Position::new(0, 1)),
+                severity: Some(severity),
+                source: Some(source.to_string()),
+                message: message.to_string(),
+                ..Default::default()
+            },
+            lines: 1,
+        }
+    }
+
+    #[test]
+    fn navigates_diagnostics_of_a_file() {
+        let at = |severity, start, message| EditorDiagnostic {
+            range: (start, start + 3),
+            ..diagnostic(severity, "rustc", message)
+        };
+        // The server doesn't send them in the order of the file
+        let diagnostics = vec![
+            at(DiagnosticSeverity::ERROR, 40, "third"),
+            at(DiagnosticSeverity::WARNING, 10, "first"),
+            at(DiagnosticSeverity::HINT, 20, "hint"),
+            at(DiagnosticSeverity::ERROR, 30, "second"),
+        ];
+        let next = |offset, min_severity, forward| {
+            next_diagnostic(&diagnostics, offset, min_severity, forward)
+                .map(|d| d.diagnostic.message.as_str())
+        };
+
+        let warning = DiagnosticSeverity::WARNING;
+        assert_eq!(next(0, warning, true), Some("first"));
+        assert_eq!(next(10, warning, true), Some("second"));
+        assert_eq!(next(35, warning, true), Some("third"));
+        assert_eq!(next(30, warning, false), Some("first"));
+        assert_eq!(next(31, warning, false), Some("second"));
+
+        // Going past the last one wraps around to the first, and back
+        assert_eq!(next(40, warning, true), Some("first"));
+        assert_eq!(next(10, warning, false), Some("third"));
+
+        assert_eq!(next(10, DiagnosticSeverity::HINT, true), Some("hint"));
+        assert_eq!(next(10, DiagnosticSeverity::ERROR, false), Some("third"));
+        assert_eq!(
+            next_diagnostic(&[], 0, warning, true).map(|d| d.range),
+            None
+        );
+    }
+
+    #[test]
+    fn filters_mixed_diagnostics_down_to_errors() {
+        let main = PathBuf::from("/src/main.rs");
+        let lib = PathBuf::from("/src/lib.rs");
+        let main_diagnostics = vec![
+            diagnostic(DiagnosticSeverity::WARNING, "rustc", "unused variable"),
+            diagnostic(DiagnosticSeverity::ERROR, "rustc", "mismatched types"),
+            diagnostic(DiagnosticSeverity::ERROR, "clippy", "lint denied"),
+        ];
+        let lib_diagnostics = vec![
+            diagnostic(DiagnosticSeverity::HINT, "rustc", "consider borrowing"),
+            diagnostic(DiagnosticSeverity::WARNING, "clippy", "needless return"),
+        ];
+        let diagnostics = [
+            (&main, main_diagnostics.as_slice()),
+            (&lib, lib_diagnostics.as_slice()),
+        ];
+
+        let mut problem = ProblemData::new(ProblemGrouping::ByFile);
+        problem.filter.min_severity = DiagnosticSeverity::ERROR;
+        let groups = problem.groups(diagnostics);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].severity, None);
+        assert_eq!(groups[0].files.len(), 1);
+        assert_eq!(groups[0].files[0].0, &main);
+        let messages = groups[0].files[0]
+            .1
+            .iter()
+            .map(|d| d.diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["mismatched types", "lint denied"]);
+
+        problem.filter.hidden_sources.insert("clippy".to_string());
+        problem.grouping = ProblemGrouping::BySeverity;
+        let groups = problem.groups(diagnostics);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(groups[0].files[0].1.len(), 1);
+        assert_eq!(
+            groups[0].files[0].1[0].diagnostic.message,
+            "mismatched types"
+        );
+
+        assert_eq!(severity_counts(diagnostics), [2, 2, 0, 1]);
+    }
+
+    #[test]
+    fn problem_badge_counts_errors_and_warnings() {
+        let main = PathBuf::from("/src/main.rs");
+        let lib = PathBuf::from("/src/lib.rs");
+        let main_diagnostics = vec![
+            diagnostic(DiagnosticSeverity::ERROR, "rustc", "mismatched types"),
+            diagnostic(DiagnosticSeverity::WARNING, "rustc", "unused variable"),
+            diagnostic(DiagnosticSeverity::HINT, "rustc", "consider borrowing"),
+        ];
+        let lib_diagnostics = vec![
+            diagnostic(DiagnosticSeverity::WARNING, "clippy", "needless return"),
+            diagnostic(DiagnosticSeverity::INFORMATION, "rustc", "see here"),
+        ];
+        let diagnostics = [
+            (&main, main_diagnostics.as_slice()),
+            (&lib, lib_diagnostics.as_slice()),
+        ];
+        let problems = diagnostics
+            .iter()
+            .flat_map(|(_, diagnostics)| diagnostics.iter())
+            .filter(|d| {
+                matches!(
+                    severity(&d.diagnostic),
+                    DiagnosticSeverity::ERROR | DiagnosticSeverity::WARNING
+                )
+            })
+            .count();
+
+        let counts = severity_counts(diagnostics);
+        let badge = problem_badge(counts[0], counts[1]).unwrap();
+        assert_eq!(badge.count, problems);
+        assert_eq!(badge.count, 3);
+        assert_eq!(badge.kind, PanelBadgeKind::Error);
+
+        // Without errors the badge is for the warnings
+        let counts = severity_counts([(&lib, lib_diagnostics.as_slice())]);
+        let badge = problem_badge(counts[0], counts[1]).unwrap();
+        assert_eq!((badge.count, badge.kind), (1, PanelBadgeKind::Warning));
+
+        // and there's no badge without any of them
+        let hints = vec![diagnostic(DiagnosticSeverity::HINT, "rustc", "hint")];
+        let counts = severity_counts([(&main, hints.as_slice())]);
+        assert_eq!(problem_badge(counts[0], counts[1]), None);
+        assert_eq!(
+            PanelBadge::new(150, PanelBadgeKind::Info).unwrap().text(),
+            "99+"
+        );
+    }
+
+    #[test]
+    fn lists_code_actions_of_diagnostic() {
+        // The response of a server with a fix that edits two files and one that
+        // has to be resolved before it can be applied
+        let resp: CodeActionResponse = serde_json::from_value(serde_json::json!([
+            {
+                "title": "Import `HashMap`",
+                "kind": "quickfix",
+                "edit": {
+                    "changes": {
+                        "file:///src/main.rs": [{
+                            "range": {
+                                "start": { "line": 0, "character": 0 },
+                                "end": { "line": 0, "character": 0 },
+                            },
+                            "newText": "use crate::map::HashMap;\n",
+                        }],
+                        "file:///src/map.rs": [{
+                            "range": {
+                                "start": { "line": 0, "character": 0 },
+                                "end": { "line": 0, "character": 0 },
+                            },
+                            "newText": "pub ",
+                        }],
+                    },
+                },
+            },
+            {
+                "title": "Remove unused variable",
+                "kind": "quickfix",
+                "data": { "id": 7 },
+            },
+        ]))
+        .unwrap();
+
+        let path = PathBuf::from("/src/main.rs");
+        let menu = code_action_menu(&path, PluginId(1), &resp);
+        let items = menu
+            .iter()
+            .map(|item| match item {
+                MenuKind::Item(item) => item,
+                item => panic!("unexpected menu item {item:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].desc(), "Import `HashMap` (2 files)");
+        assert_eq!(items[1].desc(), "Remove unused variable");
+        assert!(items.iter().all(|item| item.enabled));
+
+        let action: ProblemCodeAction =
+            serde_json::from_value(items[1].command.data.clone().unwrap()).unwrap();
+        assert_eq!(action.path, path);
+        assert_eq!(action.plugin_id, PluginId(1));
+        match action.action {
+            CodeActionOrCommand::CodeAction(action) => {
+                assert!(action.edit.is_none());
+                assert!(action.data.is_some());
+            }
+            CodeActionOrCommand::Command(_) => panic!("expected a code action"),
+        }
+
+        let menu = code_action_menu(&path, PluginId(1), &Vec::new());
+        assert!(matches!(&menu[..], [MenuKind::Item(item)] if !item.enabled));
+    }
+
+    #[test]
+    fn groups_code_actions_by_kind() {
+        let resp: CodeActionResponse = serde_json::from_value(serde_json::json!([
+            { "title": "Extract into function", "kind": "refactor.extract" },
+            { "title": "Add missing match arms", "kind": "quickfix" },
+            { "title": "Inline variable", "kind": "refactor.inline" },
+            { "title": "Run test", "command": "rust-analyzer.runSingle" },
+        ]))
+        .unwrap();
+
+        let path = PathBuf::from("/src/main.rs");
+        let menu = code_action_menu(&path, PluginId(1), &resp);
+        let descs = |items: &[MenuKind]| {
+            items
+                .iter()
+                .map(|item| match item {
+                    MenuKind::Item(item) => item.desc().to_string(),
+                    MenuKind::Submenu { desc, .. } => format!("{desc} >"),
+                    MenuKind::Header(desc) => desc.clone(),
+                    MenuKind::Separator => "-".to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+        // The source action submenu has no items, so it's left out
+        assert_eq!(
+            descs(&menu),
+            vec!["Add missing match arms", "Run test", "-", "Refactor >"]
+        );
+        match &menu[3] {
+            MenuKind::Submenu { items, .. } => assert_eq!(
+                descs(items),
+                vec!["Extract into function", "Inline variable"]
+            ),
+            item => panic!("expected a submenu, got {item:?}"),
+        }
     }
 }
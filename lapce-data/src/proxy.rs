@@ -1,28 +1,32 @@
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 use std::{
-    collections::HashMap,
     io::{BufReader, Write},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, Command, Stdio},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
 };
 
 use anyhow::{anyhow, Result};
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
 use druid::{ExtEventSink, Target, WidgetId, WindowId};
 use flate2::read::GzDecoder;
 use lapce_core::{directory::Directory, meta};
 use lapce_proxy::dispatch::Dispatcher;
 use lapce_rpc::{
-    core::{CoreHandler, CoreNotification, CoreRequest, CoreRpcHandler},
+    core::{
+        CoreHandler, CoreNotification, CoreRequest, CoreResponse, CoreRpcHandler,
+    },
     plugin::VoltID,
-    proxy::{ProxyRpc, ProxyRpcHandler},
+    proxy::{ProxyNotification, ProxyRpcHandler},
     stdio::stdio_transport,
     terminal::TermId,
-    RequestId, RpcMessage,
+    RequestId,
 };
 use lapce_xi_rope::Rope;
 use lsp_types::{LogMessageParams, MessageType, Url};
@@ -31,8 +35,14 @@
 use thiserror::Error;
 
 use crate::{
+    alert::AlertContentData,
+    cli::parse_urls,
     command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceConfig,
     data::{LapceWorkspace, LapceWorkspaceType, SshHost},
+    proxy_connection::{
+        Backoff, FromProxy, ProxyConnection, ProxyTransport, ToProxy,
+    },
     terminal::RawTerminal,
 };
 
@@ -49,6 +59,11 @@ pub enum TermEvent {
 pub enum ProxyStatus {
     Connecting,
     Connected,
+    /// The connection dropped and the proxy is being restarted, `attempt`
+    /// counts from 1
+    Reconnecting {
+        attempt: usize,
+    },
     Disconnected,
 }
 
@@ -100,6 +115,7 @@ pub struct LapceProxy {
     pub core_rpc: CoreRpcHandler,
     term_tx: Sender<(TermId, TermEvent)>,
     event_sink: ExtEventSink,
+    restore_tx: Sender<Vec<ProxyNotification>>,
 }
 
 impl CoreHandler for LapceProxy {
@@ -110,6 +126,7 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                 window_tab_id,
                 folders,
                 files,
+                urls,
             } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -122,6 +139,7 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                         }),
                         folders,
                         files,
+                        urls: parse_urls(&urls),
                     },
                     Target::Global,
                 );
@@ -143,6 +161,13 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                     Target::Widget(self.tab_id),
                 );
             }
+            OpenFileDeleted { path } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenFileDeleted { path },
+                    Target::Widget(self.tab_id),
+                );
+            }
             ReloadBuffer { path, content, rev } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -161,6 +186,13 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                     Target::Widget(self.tab_id),
                 );
             }
+            WorkspaceSettingsChange {} => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ReloadConfig,
+                    Target::Auto,
+                );
+            }
             PublishDiagnostics { diagnostics } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -203,6 +235,26 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                     Target::Widget(self.tab_id),
                 );
             }
+            PluginPaletteProviderRegistered {
+                plugin_id,
+                provider,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RegisterPluginPaletteProvider {
+                        plugin_id,
+                        provider,
+                    },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            PluginServerStopped { plugin_id } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::PluginServerStopped(plugin_id),
+                    Target::Widget(self.tab_id),
+                );
+            }
             HomeDir { path } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -224,6 +276,20 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                     Target::Widget(self.tab_id),
                 );
             }
+            VoltPermissionsRequested { volt, requested } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::VoltPermissionsRequested(volt, requested),
+                    Target::Widget(self.tab_id),
+                );
+            }
+            VoltDownloadProgress { progress } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::VoltDownloadProgress(progress),
+                    Target::Widget(self.tab_id),
+                );
+            }
             VoltRemoving { volt, error } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -254,6 +320,16 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
                     .term_tx
                     .send((term_id, TermEvent::UpdateContent(content)));
             }
+            GlobalSearchMatches { search_id, matches } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::GlobalSearchResult {
+                        search_id,
+                        matches: Arc::new(matches),
+                    },
+                    Target::Widget(self.tab_id),
+                );
+            }
             CloseTerminal { term_id } => {
                 let _ = self.term_tx.send((term_id, TermEvent::CloseTerminal));
                 let _ = self.event_sink.submit_command(
@@ -302,7 +378,90 @@ fn handle_notification(&mut self, rpc: CoreNotification) {
         }
     }
 
-    fn handle_request(&mut self, _id: RequestId, _rpc: CoreRequest) {}
+    fn handle_request(&mut self, id: RequestId, rpc: CoreRequest) {
+        match rpc {
+            CoreRequest::ApplyWorkspaceEdit { edit } => {
+                let reply = WorkspaceEditReply::new(id, self.core_rpc.clone());
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ApplyWorkspaceEdit(edit, Some(Arc::new(reply))),
+                    Target::Widget(self.tab_id),
+                );
+            }
+        }
+    }
+}
+
+/// The answer to a plugin waiting for its workspace edit to be applied, sent
+/// once every editor it was given to is done with it. The edit is reported as
+/// applied only when an editor applied it and no part of it failed.
+pub struct WorkspaceEditReply {
+    id: RequestId,
+    core_rpc: CoreRpcHandler,
+    applied: AtomicBool,
+    failed: AtomicBool,
+}
+
+impl WorkspaceEditReply {
+    pub fn new(id: RequestId, core_rpc: CoreRpcHandler) -> Self {
+        Self {
+            id,
+            core_rpc,
+            applied: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn applied(&self) {
+        self.applied.store(true, Ordering::Relaxed);
+    }
+
+    /// Part of the edit couldn't be applied
+    pub fn fail(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
+
+    /// A part of the edit applied later, like once its file is loaded, which
+    /// fails the edit if it's dropped before being done
+    pub fn pending(self: &Arc<Self>) -> PendingWorkspaceEdit {
+        PendingWorkspaceEdit {
+            reply: self.clone(),
+            done: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Drop for WorkspaceEditReply {
+    fn drop(&mut self) {
+        let applied = self.applied.load(Ordering::Relaxed)
+            && !self.failed.load(Ordering::Relaxed);
+        self.core_rpc.handle_response(
+            self.id,
+            Ok(CoreResponse::ApplyWorkspaceEditResponse { applied }),
+        );
+    }
+}
+
+pub struct PendingWorkspaceEdit {
+    reply: Arc<WorkspaceEditReply>,
+    done: AtomicBool,
+}
+
+impl PendingWorkspaceEdit {
+    pub fn done(&self, applied: bool) {
+        if !applied {
+            self.reply.fail();
+        }
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for PendingWorkspaceEdit {
+    fn drop(&mut self) {
+        if !self.done.load(Ordering::Relaxed) {
+            self.reply.fail();
+        }
+    }
 }
 
 impl LapceProxy {
@@ -311,12 +470,13 @@ pub fn new(
         tab_id: WidgetId,
         workspace: LapceWorkspace,
         disabled_volts: Vec<VoltID>,
-        plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        config: &LapceConfig,
         term_tx: Sender<(TermId, TermEvent)>,
         event_sink: ExtEventSink,
     ) -> Self {
         let proxy_rpc = ProxyRpcHandler::new();
         let core_rpc = CoreRpcHandler::new();
+        let (restore_tx, restore_rx) = crossbeam_channel::unbounded();
 
         let proxy = Self {
             tab_id,
@@ -324,8 +484,19 @@ pub fn new(
             core_rpc,
             term_tx,
             event_sink: event_sink.clone(),
+            restore_tx,
         };
 
+        // Kept to initialize the remote proxy again when it's restarted
+        let initialize = ProxyNotification::Initialize {
+            workspace: workspace.path.clone(),
+            disabled_volts,
+            plugin_configurations: config.plugins.clone(),
+            window_id: window_id.to_usize(),
+            tab_id: tab_id.to_usize(),
+            file_watcher_debounce_ms: config.core.file_watcher_debounce_ms,
+        };
+        let remote_lapce_path = config.remote.lapce_path().map(String::from);
         let local_proxy = proxy.clone();
         thread::spawn(move || {
             let _ = event_sink.submit_command(
@@ -333,13 +504,23 @@ pub fn new(
                 LapceUICommand::ProxyUpdateStatus(ProxyStatus::Connecting),
                 Target::Widget(tab_id),
             );
-            let _ = local_proxy.start(
-                workspace.clone(),
-                disabled_volts,
-                plugin_configurations,
-                window_id.to_usize(),
-                tab_id.to_usize(),
+            let result = local_proxy.start(
+                workspace,
+                initialize,
+                remote_lapce_path,
+                restore_rx,
             );
+            if let Err(err) = result {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "Couldn't start the proxy".to_string(),
+                        msg: err.to_string(),
+                        ..Default::default()
+                    }),
+                    Target::Widget(tab_id),
+                );
+            }
             let _ = event_sink.submit_command(
                 LAPCE_UI_COMMAND,
                 LapceUICommand::ProxyUpdateStatus(ProxyStatus::Disconnected),
@@ -353,18 +534,11 @@ pub fn new(
     fn start(
         &self,
         workspace: LapceWorkspace,
-        disabled_volts: Vec<VoltID>,
-        plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
-        window_id: usize,
-        tab_id: usize,
+        initialize: ProxyNotification,
+        remote_lapce_path: Option<String>,
+        restore_rx: Receiver<Vec<ProxyNotification>>,
     ) -> Result<()> {
-        self.proxy_rpc.initialize(
-            workspace.path.clone(),
-            disabled_volts,
-            plugin_configurations,
-            window_id,
-            tab_id,
-        );
+        self.proxy_rpc.notification(initialize.clone());
         match workspace.kind {
             LapceWorkspaceType::Local => {
                 let proxy_rpc = self.proxy_rpc.clone();
@@ -376,8 +550,12 @@ fn start(
                     proxy_rpc.mainloop(&mut dispatcher);
                 });
             }
-            LapceWorkspaceType::RemoteSSH(ssh) => {
-                self.start_remote(SshRemote { ssh })?;
+            LapceWorkspaceType::RemoteSSH(host) => {
+                let transport = Self::install_remote(RemoteConnection {
+                    host,
+                    remote_lapce_path,
+                })?;
+                self.connect_remote(transport, initialize, restore_rx)?;
             }
             LapceWorkspaceType::RemoteWSL => {
                 let distro = WslDistro::all()?
@@ -385,7 +563,8 @@ fn start(
                     .find(|distro| distro.default)
                     .ok_or_else(|| anyhow!("no default distro found"))?
                     .name;
-                self.start_remote(WslRemote { distro })?;
+                let transport = Self::install_remote(WslRemote { distro })?;
+                self.connect_remote(transport, initialize, restore_rx)?;
             }
         }
 
@@ -395,7 +574,37 @@ fn start(
         Ok(())
     }
 
-    fn start_remote(&self, remote: impl Remote) -> Result<()> {
+    /// Start the proxy and keep restarting it when the connection drops
+    fn connect_remote(
+        &self,
+        mut transport: impl ProxyTransport,
+        initialize: ProxyNotification,
+        restore_rx: Receiver<Vec<ProxyNotification>>,
+    ) -> Result<()> {
+        let (writer_tx, reader_rx) = transport.connect()?;
+        let event_sink = self.event_sink.clone();
+        let tab_id = self.tab_id;
+        let connection = ProxyConnection::new(
+            transport,
+            self.proxy_rpc.clone(),
+            self.core_rpc.clone(),
+            initialize,
+            Backoff::default(),
+            move |status| {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ProxyUpdateStatus(status),
+                    Target::Widget(tab_id),
+                );
+            },
+            restore_rx,
+        );
+        thread::spawn(move || connection.run(writer_tx, reader_rx));
+        Ok(())
+    }
+
+    /// Install the proxy on the remote host if it isn't there yet
+    fn install_remote<R: Remote>(remote: R) -> Result<RemoteTransport<R>> {
         let proxy_version = match *meta::RELEASE {
             "Debug" | "Nightly" => "nightly".to_string(),
             _ => format!("v{}", *meta::VERSION),
@@ -426,13 +635,38 @@ fn start_remote(&self, remote: impl Remote) -> Result<()> {
         // shells retain similar syntax, although shells like Nushell might not
         // work (hopefully no one uses it as login shell)
         use HostPlatform::*;
-        let (platform, architecture) = self.host_specification(&remote).unwrap();
+        let (platform, architecture) = Self::host_specification(&remote)?;
 
         if platform == UnknownOS || architecture == HostArchitecture::UnknownArch {
             log::error!(target: "lapce_data::proxy::start_remote", "detected remote host: {platform}/{architecture}");
             return Err(anyhow!("Unknown OS and/or architecture"));
         }
 
+        // Fall back to the proxy Lapce installs when the configured one is
+        // missing or of another version, which may not speak the same protocol
+        if let Some(proxy_file) = remote.remote_lapce_path().map(String::from) {
+            if !remote.file_exists(platform, &proxy_file)? {
+                log::warn!(target: "lapce_data::proxy::start_remote", "remote lapce path {proxy_file} not found");
+            } else {
+                match remote.lapce_version(&proxy_file) {
+                    Ok(output) if is_lapce_version(&output, *meta::VERSION) => {
+                        return Ok(RemoteTransport {
+                            remote,
+                            platform,
+                            proxy_file,
+                            child: None,
+                        });
+                    }
+                    Ok(output) => {
+                        log::warn!(target: "lapce_data::proxy::start_remote", "remote lapce path {proxy_file} has version {}, not {}", output.trim(), *meta::VERSION);
+                    }
+                    Err(e) => {
+                        log::warn!(target: "lapce_data::proxy::start_remote", "can't get the version of remote lapce path {proxy_file}: {e}");
+                    }
+                }
+            }
+        }
+
         // ! Below paths have to be synced with what is
         // ! returned by Config::proxy_directory()
         let remote_proxy_path = match platform {
@@ -521,19 +755,7 @@ fn start_remote(&self, remote: impl Remote) -> Result<()> {
         log::debug!(target: "lapce_data::proxy::start_remote", "remote proxy path: {remote_proxy_path}");
 
         if !script_install.success() {
-            let cmd = match platform {
-                Windows => remote
-                    .command_builder()
-                    .args(["dir", &remote_proxy_file])
-                    .status()?,
-                _ => remote
-                    .command_builder()
-                    .arg("test")
-                    .arg("-e")
-                    .arg(&remote_proxy_file)
-                    .status()?,
-            };
-            if !cmd.success() {
+            if !remote.file_exists(platform, &remote_proxy_file)? {
                 let local_proxy_file = Directory::proxy_directory()
                     .ok_or_else(|| anyhow!("can't find proxy directory"))?
                     .join(&proxy_filename);
@@ -545,16 +767,17 @@ fn start_remote(&self, remote: impl Remote) -> Result<()> {
                 }
                 let url = format!("https://github.com/lapce/lapce/releases/download/{proxy_version}/{proxy_filename}.gz");
                 log::debug!(target: "lapce_data::proxy::start_remote", "proxy download URI: {url}");
-                let mut resp = reqwest::blocking::get(url).expect("request failed");
-                if resp.status().is_success() {
-                    let mut out = std::fs::File::create(&local_proxy_file)
-                        .expect("failed to create file");
-                    let mut gz = GzDecoder::new(&mut resp);
-                    std::io::copy(&mut gz, &mut out)
-                        .expect("failed to copy content");
-                } else {
+                let mut resp = reqwest::blocking::get(url)?;
+                if !resp.status().is_success() {
                     log::error!(target: "lapce_data::proxy::start_remote", "proxy download failed with: {}", resp.status());
+                    return Err(anyhow!(
+                        "can't download the proxy for {platform}/{architecture}: {}",
+                        resp.status()
+                    ));
                 }
+                let mut out = std::fs::File::create(&local_proxy_file)?;
+                let mut gz = GzDecoder::new(&mut resp);
+                std::io::copy(&mut gz, &mut out)?;
 
                 match platform {
                     // Windows creates all dirs in provided path
@@ -584,96 +807,16 @@ fn start_remote(&self, remote: impl Remote) -> Result<()> {
             }
         }
 
-        let mut child = match platform {
-            // Force cmd.exe usage to resolve %envvar% variables
-            Windows => remote
-                .command_builder()
-                .args(["cmd", "/c"])
-                .arg(&remote_proxy_file)
-                .arg("--proxy")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?,
-            _ => remote
-                .command_builder()
-                .arg(&remote_proxy_file)
-                .arg("--proxy")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?,
+        let transport = RemoteTransport {
+            remote,
+            platform,
+            proxy_file: remote_proxy_file,
+            child: None,
         };
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| anyhow!("can't find stdin"))?;
-        let stdout = BufReader::new(
-            child
-                .stdout
-                .take()
-                .ok_or_else(|| anyhow!("can't find stdout"))?,
-        );
-        log::debug!(target: "lapce_data::proxy::start_remote", "process id: {}", child.id());
-
-        let (writer_tx, writer_rx) = crossbeam_channel::unbounded();
-        let (reader_tx, reader_rx) = crossbeam_channel::unbounded();
-        stdio_transport(stdin, writer_rx, stdout, reader_tx);
-
-        let local_proxy_rpc = self.proxy_rpc.clone();
-        let local_writer_tx = writer_tx.clone();
-        thread::spawn(move || {
-            for msg in local_proxy_rpc.rx() {
-                match msg {
-                    ProxyRpc::Request(id, rpc) => {
-                        let _ = local_writer_tx.send(RpcMessage::Request(id, rpc));
-                    }
-                    ProxyRpc::Notification(rpc) => {
-                        let _ = local_writer_tx.send(RpcMessage::Notification(rpc));
-                    }
-                    ProxyRpc::Shutdown => {
-                        let _ = child.kill();
-                        let _ = child.wait();
-                        return;
-                    }
-                }
-            }
-        });
-
-        let core_rpc = self.core_rpc.clone();
-        let proxy_rpc = self.proxy_rpc.clone();
-        thread::spawn(move || {
-            for msg in reader_rx {
-                match msg {
-                    RpcMessage::Request(id, req) => {
-                        let writer_tx = writer_tx.clone();
-                        let core_rpc = core_rpc.clone();
-                        thread::spawn(move || match core_rpc.request(req) {
-                            Ok(resp) => {
-                                let _ =
-                                    writer_tx.send(RpcMessage::Response(id, resp));
-                            }
-                            Err(e) => {
-                                let _ = writer_tx.send(RpcMessage::Error(id, e));
-                            }
-                        });
-                    }
-                    RpcMessage::Notification(n) => {
-                        core_rpc.notification(n);
-                    }
-                    RpcMessage::Response(id, resp) => {
-                        proxy_rpc.handle_response(id, Ok(resp));
-                    }
-                    RpcMessage::Error(id, err) => {
-                        proxy_rpc.handle_response(id, Err(err));
-                    }
-                }
-            }
-        });
-
-        Ok(())
+        Ok(transport)
     }
 
     fn host_specification(
-        &self,
         remote: &impl Remote,
     ) -> Result<(HostPlatform, HostArchitecture)> {
         use HostArchitecture::*;
@@ -774,12 +917,24 @@ pub fn new_terminal(
         self.proxy_rpc.new_terminal(term_id, cwd, shell);
     }
 
+    /// Open the buffers in the remote proxy after it was restarted, with the
+    /// content the editor has for them
+    pub fn restore_buffers(&self, buffers: Vec<ProxyNotification>) {
+        let _ = self.restore_tx.send(buffers);
+    }
+
     pub fn stop(&self) {
         self.proxy_rpc.shutdown();
         self.core_rpc.shutdown();
     }
 }
 
+/// Whether the output of `lapce --version`, like `lapce 0.2.5`, is of the
+/// version
+fn is_lapce_version(output: &str, version: &str) -> bool {
+    output.split_whitespace().last() == Some(version)
+}
+
 fn new_command(program: &str) -> Command {
     #[allow(unused_mut)]
     let mut cmd = Command::new(program);
@@ -801,16 +956,51 @@ fn home_dir(&self) -> Result<String> {
         Ok(String::from_utf8(cmd.stdout)?)
     }
 
+    /// The proxy to run on the host instead of installing one
+    fn remote_lapce_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// What the Lapce executable on the host prints for `--version`
+    fn lapce_version(&self, path: &str) -> Result<String> {
+        let cmd = self.command_builder().arg(path).arg("--version").output()?;
+        if !cmd.status.success() {
+            return Err(anyhow!("{}", String::from_utf8_lossy(&cmd.stderr)));
+        }
+        Ok(String::from_utf8(cmd.stdout)?)
+    }
+
     fn upload_file(&self, local: impl AsRef<Path>, remote: &str) -> Result<()>;
 
     fn command_builder(&self) -> Command;
+
+    fn file_exists(&self, platform: HostPlatform, path: &str) -> Result<bool> {
+        let status = match platform {
+            HostPlatform::Windows => {
+                self.command_builder().args(["dir", path]).status()?
+            }
+            _ => self
+                .command_builder()
+                .arg("test")
+                .arg("-e")
+                .arg(path)
+                .status()?,
+        };
+        Ok(status.success())
+    }
 }
 
-struct SshRemote {
-    ssh: SshHost,
+/// How to run the proxy on a remote host over SSH, the messages to it are sent
+/// over the SSH channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteConnection {
+    pub host: SshHost,
+    /// The Lapce executable on the host to run as the proxy, instead of the one
+    /// Lapce installs
+    pub remote_lapce_path: Option<String>,
 }
 
-impl SshRemote {
+impl RemoteConnection {
     #[cfg(windows)]
     const SSH_ARGS: &'static [&'static str] = &[];
 
@@ -827,19 +1017,23 @@ impl SshRemote {
     ];
 }
 
-impl Remote for SshRemote {
+impl Remote for RemoteConnection {
+    fn remote_lapce_path(&self) -> Option<&str> {
+        self.remote_lapce_path.as_deref()
+    }
+
     fn upload_file(&self, local: impl AsRef<Path>, remote: &str) -> Result<()> {
         let mut cmd = new_command("scp");
 
         cmd.args(Self::SSH_ARGS);
 
-        if let Some(port) = self.ssh.port {
+        if let Some(port) = self.host.port {
             cmd.arg("-P").arg(port.to_string());
         }
 
         let output = cmd
             .arg(local.as_ref())
-            .arg(dbg!(format!("{}:{remote}", self.ssh.user_host())))
+            .arg(dbg!(format!("{}:{remote}", self.host.user_host())))
             .output()?;
 
         log::debug!(target: "lapce_data::proxy::upload_file", "{}", String::from_utf8_lossy(&output.stderr));
@@ -852,11 +1046,11 @@ fn command_builder(&self) -> Command {
         let mut cmd = new_command("ssh");
         cmd.args(Self::SSH_ARGS);
 
-        if let Some(port) = self.ssh.port {
+        if let Some(port) = self.host.port {
             cmd.arg("-p").arg(port.to_string());
         }
 
-        cmd.arg(self.ssh.user_host());
+        cmd.arg(self.host.user_host());
 
         if !std::env::var("LAPCE_DEBUG").unwrap_or_default().is_empty() {
             cmd.arg("-v");
@@ -933,6 +1127,67 @@ fn command_builder(&self) -> Command {
     }
 }
 
+/// Runs the installed proxy on the remote host, the connection to it drops when
+/// the process exits
+struct RemoteTransport<R: Remote> {
+    remote: R,
+    platform: HostPlatform,
+    proxy_file: String,
+    child: Option<Child>,
+}
+
+impl<R: Remote + Send + 'static> ProxyTransport for RemoteTransport<R> {
+    fn connect(&mut self) -> Result<(Sender<ToProxy>, Receiver<FromProxy>)> {
+        self.shutdown();
+
+        let mut child = match self.platform {
+            // Force cmd.exe usage to resolve %envvar% variables
+            HostPlatform::Windows => self
+                .remote
+                .command_builder()
+                .args(["cmd", "/c"])
+                .arg(&self.proxy_file)
+                .arg("--proxy")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?,
+            _ => self
+                .remote
+                .command_builder()
+                .arg(&self.proxy_file)
+                .arg("--proxy")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?,
+        };
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("can't find stdin"))?;
+        let stdout = BufReader::new(
+            child
+                .stdout
+                .take()
+                .ok_or_else(|| anyhow!("can't find stdout"))?,
+        );
+        log::debug!(target: "lapce_data::proxy::start_remote", "process id: {}", child.id());
+
+        let (writer_tx, writer_rx) = crossbeam_channel::unbounded();
+        let (reader_tx, reader_rx) = crossbeam_channel::unbounded();
+        stdio_transport(stdin, writer_rx, stdout, reader_tx);
+        self.child = Some(child);
+
+        Ok((writer_tx, reader_rx))
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
 // Rust-analyzer returns paths in the form of "file:///<drive>:/...", which gets parsed into URL
 // as "/<drive>://" which is then interpreted by PathBuf::new() as a UNIX-like path from root.
 // This function strips the additional / from the beginning, if the first segment is a drive letter.
@@ -979,3 +1234,224 @@ fn parse_os(os: &str) -> HostPlatform {
         _ => UnknownOS,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, thread, time::Duration};
+
+    use anyhow::Result;
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    #[cfg(unix)]
+    use lapce_core::meta;
+    use lapce_proxy::dispatch::Dispatcher;
+    use lapce_rpc::{
+        core::{CoreRpc, CoreRpcHandler},
+        proxy::{ProxyNotification, ProxyResponse, ProxyRpcHandler},
+        RpcMessage,
+    };
+
+    use super::is_lapce_version;
+    #[cfg(unix)]
+    use super::{HostPlatform, LapceProxy, Remote};
+    use crate::proxy_connection::{
+        Backoff, FromProxy, ProxyConnection, ProxyTransport, ToProxy,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Runs the commands on this machine, standing in for a remote host
+    #[cfg(unix)]
+    struct LocalRemote {
+        remote_lapce_path: Option<String>,
+    }
+
+    #[cfg(unix)]
+    impl Remote for LocalRemote {
+        fn remote_lapce_path(&self) -> Option<&str> {
+            self.remote_lapce_path.as_deref()
+        }
+
+        fn upload_file(
+            &self,
+            local: impl AsRef<std::path::Path>,
+            remote: &str,
+        ) -> Result<()> {
+            fs::copy(local, remote)?;
+            Ok(())
+        }
+
+        fn command_builder(&self) -> std::process::Command {
+            std::process::Command::new("env")
+        }
+    }
+
+    /// Runs the proxy in this process, the messages to it are passed over
+    /// channels like they are over the SSH channel
+    struct InProcessTransport;
+
+    impl ProxyTransport for InProcessTransport {
+        fn connect(&mut self) -> Result<(Sender<ToProxy>, Receiver<FromProxy>)> {
+            let core_rpc = CoreRpcHandler::new();
+            let proxy_rpc = ProxyRpcHandler::new();
+            let (writer_tx, writer_rx) = unbounded::<ToProxy>();
+            let (reader_tx, reader_rx) = unbounded::<FromProxy>();
+
+            let local_core_rpc = core_rpc.clone();
+            let local_proxy_rpc = proxy_rpc.clone();
+            thread::spawn(move || {
+                let mut dispatcher =
+                    Dispatcher::new(local_core_rpc, local_proxy_rpc.clone());
+                local_proxy_rpc.mainloop(&mut dispatcher);
+            });
+
+            let local_core_rpc = core_rpc.clone();
+            let local_reader_tx = reader_tx.clone();
+            thread::spawn(move || {
+                for msg in local_core_rpc.rx() {
+                    let msg = match msg {
+                        CoreRpc::Request(id, rpc) => RpcMessage::Request(id, rpc),
+                        CoreRpc::Notification(rpc) => RpcMessage::Notification(*rpc),
+                        CoreRpc::Shutdown => return,
+                    };
+                    let _ = local_reader_tx.send(msg);
+                }
+            });
+
+            thread::spawn(move || {
+                for msg in writer_rx {
+                    match msg {
+                        RpcMessage::Request(id, req) => {
+                            let reader_tx = reader_tx.clone();
+                            proxy_rpc.request_async(req, move |result| {
+                                let _ = reader_tx.send(match result {
+                                    Ok(resp) => RpcMessage::Response(id, resp),
+                                    Err(e) => RpcMessage::Error(id, e),
+                                });
+                            });
+                        }
+                        RpcMessage::Notification(n) => proxy_rpc.notification(n),
+                        RpcMessage::Response(id, resp) => {
+                            core_rpc.handle_response(id, Ok(resp));
+                        }
+                        RpcMessage::Error(id, err) => {
+                            core_rpc.handle_response(id, Err(err));
+                        }
+                    }
+                }
+                proxy_rpc.shutdown();
+            });
+
+            Ok((writer_tx, reader_rx))
+        }
+
+        fn shutdown(&mut self) {}
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-remote-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remote_lapce_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("path");
+        let write_lapce = |name: &str, version: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\necho \"lapce {version}\"\n"))
+                .unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let lapce_path = write_lapce("lapce", *meta::VERSION);
+
+        let transport = LapceProxy::install_remote(LocalRemote {
+            remote_lapce_path: Some(lapce_path.clone()),
+        })
+        .unwrap();
+        assert_eq!(transport.proxy_file, lapce_path);
+
+        // A missing one falls back to installing the proxy
+        let remote = LocalRemote {
+            remote_lapce_path: Some(
+                dir.join("missing").to_string_lossy().to_string(),
+            ),
+        };
+        let missing = remote.remote_lapce_path().unwrap();
+        assert!(!remote.file_exists(HostPlatform::Linux, missing).unwrap());
+
+        // So does one of another version
+        let old_path = write_lapce("lapce-old", "0.0.1");
+        let output = remote.lapce_version(&old_path).unwrap();
+        assert!(!is_lapce_version(&output, *meta::VERSION));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_is_lapce_version() {
+        assert!(is_lapce_version("lapce 0.2.5\n", "0.2.5"));
+        assert!(is_lapce_version(
+            "lapce nightly-2023-01-01",
+            "nightly-2023-01-01"
+        ));
+        assert!(!is_lapce_version("lapce 0.2.4\n", "0.2.5"));
+        assert!(!is_lapce_version("", "0.2.5"));
+    }
+
+    #[test]
+    fn test_remote_file_operations() {
+        let dir = temp_dir("files");
+        fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let proxy_rpc = ProxyRpcHandler::new();
+        let mut transport = InProcessTransport;
+        let (writer_tx, reader_rx) = transport.connect().unwrap();
+        let (_restore_tx, restore_rx) = unbounded();
+        let connection = ProxyConnection::new(
+            transport,
+            proxy_rpc.clone(),
+            CoreRpcHandler::new(),
+            ProxyNotification::Initialize {
+                workspace: Some(dir.clone()),
+                disabled_volts: Vec::new(),
+                plugin_configurations: Default::default(),
+                window_id: 0,
+                tab_id: 0,
+                file_watcher_debounce_ms: 0,
+            },
+            Backoff::default(),
+            |_| {},
+            restore_rx,
+        );
+        let connection = thread::spawn(move || connection.run(writer_tx, reader_rx));
+
+        let (tx, rx) = unbounded();
+        proxy_rpc.create_file(dir.join("lib.rs"), move |result| {
+            let _ = tx.send(result);
+        });
+        assert!(rx.recv_timeout(TIMEOUT).unwrap().is_ok());
+        assert!(dir.join("lib.rs").exists());
+
+        let (tx, rx) = unbounded();
+        proxy_rpc.read_dir(dir.clone(), move |result| {
+            let _ = tx.send(result);
+        });
+        match rx.recv_timeout(TIMEOUT).unwrap() {
+            Ok(ProxyResponse::ReadDirResponse { items }) => {
+                assert!(items.contains_key(&dir.join("main.rs")));
+                assert!(items.contains_key(&dir.join("lib.rs")));
+            }
+            _ => panic!("expected the content of the directory"),
+        }
+
+        proxy_rpc.shutdown();
+        connection.join().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
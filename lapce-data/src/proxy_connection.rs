@@ -0,0 +1,699 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crossbeam_channel::{select, Receiver, RecvTimeoutError, Sender};
+use lapce_rpc::{
+    core::{CoreNotification, CoreRequest, CoreResponse, CoreRpcHandler},
+    proxy::{
+        ProxyNotification, ProxyRequest, ProxyResponse, ProxyRpc, ProxyRpcHandler,
+    },
+    RequestId, RpcError, RpcMessage,
+};
+
+use crate::proxy::ProxyStatus;
+
+/// How long a restarted proxy waits for the editor to restore the open
+/// buffers, before the buffered messages are sent without them
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The messages sent to the proxy
+pub type ToProxy = RpcMessage<ProxyRequest, ProxyNotification, CoreResponse>;
+
+/// The messages received from the proxy
+pub type FromProxy = RpcMessage<CoreRequest, CoreNotification, ProxyResponse>;
+
+/// A way to start the proxy and talk to it, which is used again to restart it
+/// when the connection drops
+pub trait ProxyTransport: Send + 'static {
+    /// Start the proxy. The receiver is disconnected when the connection to
+    /// it drops.
+    fn connect(&mut self) -> Result<(Sender<ToProxy>, Receiver<FromProxy>)>;
+
+    /// Stop the proxy
+    fn shutdown(&mut self);
+}
+
+/// The delays between the attempts to reconnect, which double from `initial`
+/// up to `max`
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// The attempts after which the proxy is left disconnected
+    pub max_attempts: usize,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before the attempt, counted from 0
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let factor = u32::try_from(attempt)
+            .ok()
+            .and_then(|attempt| 1u32.checked_shl(attempt))
+            .unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+enum Served {
+    Shutdown,
+    /// The connection dropped, after the proxy answered at least once or not
+    Dropped {
+        answered: bool,
+    },
+}
+
+enum Reconnected {
+    Connected(Sender<ToProxy>, Receiver<FromProxy>),
+    GaveUp,
+    Shutdown,
+}
+
+/// Passes the messages between the editor and the proxy, and restarts the
+/// proxy when the connection drops.
+///
+/// While it reconnects, the idempotent requests are kept to be sent to the
+/// restarted proxy and the other ones fail, since whether the proxy handled
+/// them is unknown. The edits are dropped, the restarted proxy gets the whole
+/// content of the open buffers from the editor instead.
+pub struct ProxyConnection<T: ProxyTransport> {
+    transport: T,
+    proxy_rpc: ProxyRpcHandler,
+    core_rpc: CoreRpcHandler,
+    /// The notification initializing the proxy, which is sent again to a
+    /// restarted one
+    initialize: ProxyNotification,
+    backoff: Backoff,
+    update_status: Box<dyn Fn(ProxyStatus) + Send>,
+    /// The notifications restoring the open buffers, which the editor sends
+    /// when the proxy is connected again
+    restore_rx: Receiver<Vec<ProxyNotification>>,
+    /// The requests sent to the proxy which it hasn't answered yet
+    in_flight: HashMap<RequestId, ProxyRequest>,
+    /// The requests and notifications to send to the restarted proxy
+    buffered: Vec<ProxyRpc>,
+}
+
+impl<T: ProxyTransport> ProxyConnection<T> {
+    pub fn new(
+        transport: T,
+        proxy_rpc: ProxyRpcHandler,
+        core_rpc: CoreRpcHandler,
+        initialize: ProxyNotification,
+        backoff: Backoff,
+        update_status: impl Fn(ProxyStatus) + Send + 'static,
+        restore_rx: Receiver<Vec<ProxyNotification>>,
+    ) -> Self {
+        Self {
+            transport,
+            proxy_rpc,
+            core_rpc,
+            initialize,
+            backoff,
+            update_status: Box::new(update_status),
+            restore_rx,
+            in_flight: HashMap::new(),
+            buffered: Vec::new(),
+        }
+    }
+
+    /// Serve the connection made with the transport until the proxy is shut
+    /// down, or it can't be reconnected to
+    pub fn run(
+        mut self,
+        mut writer_tx: Sender<ToProxy>,
+        mut reader_rx: Receiver<FromProxy>,
+    ) {
+        let mut attempt = 0;
+        loop {
+            match self.serve(&writer_tx, &reader_rx) {
+                Served::Shutdown => break,
+                // A proxy which drops before answering anything counts as a
+                // failed attempt, so that it isn't restarted forever
+                Served::Dropped { answered } => {
+                    if answered {
+                        attempt = 0;
+                    }
+                }
+            }
+
+            self.keep_in_flight();
+            match self.reconnect(&mut attempt) {
+                Reconnected::Connected(new_writer_tx, new_reader_rx) => {
+                    writer_tx = new_writer_tx;
+                    reader_rx = new_reader_rx;
+                    if !self.resume(&writer_tx) {
+                        break;
+                    }
+                }
+                Reconnected::GaveUp => {
+                    self.give_up();
+                    break;
+                }
+                Reconnected::Shutdown => break,
+            }
+        }
+        self.transport.shutdown();
+    }
+
+    fn serve(
+        &mut self,
+        writer_tx: &Sender<ToProxy>,
+        reader_rx: &Receiver<FromProxy>,
+    ) -> Served {
+        let rpc_rx = self.proxy_rpc.rx().clone();
+        let mut answered = false;
+        loop {
+            select! {
+                recv(rpc_rx) -> msg => {
+                    match msg {
+                        Ok(ProxyRpc::Shutdown) | Err(_) => return Served::Shutdown,
+                        Ok(msg) => self.send(writer_tx, msg),
+                    }
+                }
+                recv(reader_rx) -> msg => {
+                    match msg {
+                        Ok(msg) => {
+                            answered = true;
+                            self.receive(writer_tx, msg);
+                        }
+                        Err(_) => return Served::Dropped { answered },
+                    }
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, writer_tx: &Sender<ToProxy>, msg: ProxyRpc) {
+        match msg {
+            ProxyRpc::Request(id, request) => {
+                self.in_flight.insert(id, request.clone());
+                let _ = writer_tx.send(RpcMessage::Request(id, request));
+            }
+            ProxyRpc::Notification(notification) => {
+                let _ = writer_tx.send(RpcMessage::Notification(notification));
+            }
+            ProxyRpc::Shutdown => {}
+        }
+    }
+
+    fn receive(&mut self, writer_tx: &Sender<ToProxy>, msg: FromProxy) {
+        match msg {
+            RpcMessage::Request(id, request) => {
+                let writer_tx = writer_tx.clone();
+                let core_rpc = self.core_rpc.clone();
+                thread::spawn(move || match core_rpc.request(request) {
+                    Ok(resp) => {
+                        let _ = writer_tx.send(RpcMessage::Response(id, resp));
+                    }
+                    Err(e) => {
+                        let _ = writer_tx.send(RpcMessage::Error(id, e));
+                    }
+                });
+            }
+            RpcMessage::Notification(notification) => {
+                self.core_rpc.notification(notification);
+            }
+            RpcMessage::Response(id, resp) => {
+                self.in_flight.remove(&id);
+                self.proxy_rpc.handle_response(id, Ok(resp));
+            }
+            RpcMessage::Error(id, err) => {
+                self.in_flight.remove(&id);
+                self.proxy_rpc.handle_response(id, Err(err));
+            }
+        }
+    }
+
+    /// Keep the idempotent requests the dropped proxy didn't answer to send
+    /// them again, in the order they were made, and fail the other ones
+    fn keep_in_flight(&mut self) {
+        let mut in_flight = self.in_flight.drain().collect::<Vec<_>>();
+        in_flight.sort_by_key(|(id, _)| *id);
+        let mut kept = Vec::new();
+        for (id, request) in in_flight {
+            if request.is_idempotent() {
+                kept.push(ProxyRpc::Request(id, request));
+            } else {
+                self.proxy_rpc.handle_response(id, Err(connection_lost()));
+            }
+        }
+        kept.append(&mut self.buffered);
+        self.buffered = kept;
+    }
+
+    fn reconnect(&mut self, attempt: &mut usize) -> Reconnected {
+        let rpc_rx = self.proxy_rpc.rx().clone();
+        while *attempt < self.backoff.max_attempts {
+            (self.update_status)(ProxyStatus::Reconnecting {
+                attempt: *attempt + 1,
+            });
+
+            let deadline = Instant::now() + self.backoff.delay(*attempt);
+            loop {
+                match rpc_rx.recv_deadline(deadline) {
+                    Ok(ProxyRpc::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                        return Reconnected::Shutdown;
+                    }
+                    Ok(msg) => self.buffer(msg),
+                    Err(RecvTimeoutError::Timeout) => break,
+                }
+            }
+
+            *attempt += 1;
+            match self.transport.connect() {
+                Ok((writer_tx, reader_rx)) => {
+                    return Reconnected::Connected(writer_tx, reader_rx);
+                }
+                Err(e) => {
+                    log::error!(target: "lapce_data::proxy_connection::reconnect", "{e}");
+                }
+            }
+        }
+        Reconnected::GaveUp
+    }
+
+    /// Keep a message made while reconnecting to send it to the restarted
+    /// proxy, or fail it right away
+    fn buffer(&mut self, msg: ProxyRpc) {
+        match msg {
+            ProxyRpc::Request(id, request) => {
+                if request.is_idempotent() {
+                    self.buffered.push(ProxyRpc::Request(id, request));
+                } else {
+                    self.proxy_rpc.handle_response(id, Err(connection_lost()));
+                }
+            }
+            // The restored buffers already have the edits
+            ProxyRpc::Notification(ProxyNotification::Update { .. }) => {}
+            ProxyRpc::Notification(notification) => {
+                self.buffered.push(ProxyRpc::Notification(notification));
+            }
+            ProxyRpc::Shutdown => {}
+        }
+    }
+
+    /// Initialize the restarted proxy and restore the open buffers in it,
+    /// before sending it the buffered messages which may need them, and return
+    /// false if the proxy was shut down in the meantime
+    fn resume(&mut self, writer_tx: &Sender<ToProxy>) -> bool {
+        let _ = writer_tx.send(RpcMessage::Notification(self.initialize.clone()));
+
+        // Drop what the editor sent too late for an earlier reconnection
+        while self.restore_rx.try_recv().is_ok() {}
+        (self.update_status)(ProxyStatus::Connected);
+        let mut serving = true;
+        match self.restore_rx.recv_timeout(RESTORE_TIMEOUT) {
+            Ok(restores) => {
+                let mut restored = HashMap::new();
+                for notification in restores {
+                    if let ProxyNotification::RestoreBuffer { path, rev, .. } =
+                        &notification
+                    {
+                        restored.insert(path.clone(), *rev);
+                    }
+                    let _ = writer_tx.send(RpcMessage::Notification(notification));
+                }
+                serving = self.drop_restored_updates(&restored);
+            }
+            Err(_) => {
+                log::error!(target: "lapce_data::proxy_connection::resume", "the open buffers weren't restored");
+            }
+        }
+
+        for msg in std::mem::take(&mut self.buffered) {
+            self.send(writer_tx, msg);
+        }
+        serving
+    }
+
+    /// Buffer the messages the editor sent before restoring the open buffers,
+    /// without the edits which the restored content already has, and return
+    /// false if the proxy was shut down in the meantime
+    fn drop_restored_updates(&mut self, restored: &HashMap<PathBuf, u64>) -> bool {
+        while let Ok(msg) = self.proxy_rpc.rx().try_recv() {
+            match msg {
+                ProxyRpc::Notification(ProxyNotification::Update {
+                    path,
+                    rev,
+                    ..
+                }) if restored
+                    .get(&path)
+                    .map(|restored_rev| rev <= *restored_rev)
+                    .unwrap_or(false) => {}
+                ProxyRpc::Shutdown => return false,
+                msg => self.buffered.push(msg),
+            }
+        }
+        true
+    }
+
+    /// Fail the buffered requests and the ones made from now on, until the
+    /// proxy is shut down
+    fn give_up(&mut self) {
+        (self.update_status)(ProxyStatus::Disconnected);
+        for msg in std::mem::take(&mut self.buffered) {
+            if let ProxyRpc::Request(id, _) = msg {
+                self.proxy_rpc.handle_response(id, Err(connection_lost()));
+            }
+        }
+        for msg in self.proxy_rpc.rx() {
+            match msg {
+                ProxyRpc::Request(id, _) => {
+                    self.proxy_rpc.handle_response(id, Err(connection_lost()));
+                }
+                ProxyRpc::Notification(_) => {}
+                ProxyRpc::Shutdown => return,
+            }
+        }
+    }
+}
+
+fn connection_lost() -> RpcError {
+    RpcError {
+        code: 0,
+        message: "the connection to the proxy was lost".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{anyhow, Result};
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use lapce_rpc::{
+        buffer::BufferId,
+        core::CoreRpcHandler,
+        proxy::{ProxyNotification, ProxyRequest, ProxyResponse, ProxyRpcHandler},
+        RpcError, RpcMessage,
+    };
+    use lapce_xi_rope::{Interval, Rope, RopeDelta};
+
+    use super::{
+        Backoff, FromProxy, ProxyConnection, ProxyStatus, ProxyTransport, ToProxy,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Hands the proxy side of each connection to the test
+    struct MockTransport {
+        proxies: Sender<(Receiver<ToProxy>, Sender<FromProxy>)>,
+        /// The connections fail while it's set
+        down: Arc<AtomicBool>,
+    }
+
+    impl ProxyTransport for MockTransport {
+        fn connect(&mut self) -> Result<(Sender<ToProxy>, Receiver<FromProxy>)> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(anyhow!("the proxy is unreachable"));
+            }
+            let (writer_tx, writer_rx) = unbounded();
+            let (reader_tx, reader_rx) = unbounded();
+            self.proxies.send((writer_rx, reader_tx))?;
+            Ok((writer_tx, reader_rx))
+        }
+
+        fn shutdown(&mut self) {}
+    }
+
+    struct Harness {
+        proxy_rpc: ProxyRpcHandler,
+        proxies: Receiver<(Receiver<ToProxy>, Sender<FromProxy>)>,
+        statuses: Receiver<ProxyStatus>,
+        restore_tx: Sender<Vec<ProxyNotification>>,
+        down: Arc<AtomicBool>,
+        connection: thread::JoinHandle<()>,
+    }
+
+    fn start(backoff: Backoff) -> Harness {
+        let proxy_rpc = ProxyRpcHandler::new();
+        let (proxies_tx, proxies) = unbounded();
+        let (status_tx, statuses) = unbounded();
+        let (restore_tx, restore_rx) = unbounded();
+        let down = Arc::new(AtomicBool::new(false));
+        let mut transport = MockTransport {
+            proxies: proxies_tx,
+            down: down.clone(),
+        };
+        let (writer_tx, reader_rx) = transport.connect().unwrap();
+        let connection = ProxyConnection::new(
+            transport,
+            proxy_rpc.clone(),
+            CoreRpcHandler::new(),
+            ProxyNotification::Initialize {
+                workspace: None,
+                disabled_volts: Vec::new(),
+                plugin_configurations: HashMap::new(),
+                window_id: 0,
+                tab_id: 0,
+                file_watcher_debounce_ms: 0,
+            },
+            backoff,
+            move |status| {
+                let _ = status_tx.send(status);
+            },
+            restore_rx,
+        );
+        let connection = thread::spawn(move || connection.run(writer_tx, reader_rx));
+        Harness {
+            proxy_rpc,
+            proxies,
+            statuses,
+            restore_tx,
+            down,
+            connection,
+        }
+    }
+
+    fn request(
+        proxy_rpc: &ProxyRpcHandler,
+        request: ProxyRequest,
+    ) -> Receiver<Result<ProxyResponse, RpcError>> {
+        let (tx, rx) = unbounded();
+        proxy_rpc.request_async(request, move |result| {
+            let _ = tx.send(result);
+        });
+        rx
+    }
+
+    fn read_dir() -> ProxyRequest {
+        ProxyRequest::ReadDir {
+            path: PathBuf::from("src"),
+        }
+    }
+
+    fn create_file() -> ProxyRequest {
+        ProxyRequest::CreateFile {
+            path: PathBuf::from("new.rs"),
+        }
+    }
+
+    fn recv_request(proxy: &Receiver<ToProxy>) -> (u64, ProxyRequest) {
+        match proxy.recv_timeout(TIMEOUT).unwrap() {
+            RpcMessage::Request(id, request) => (id, request),
+            _ => panic!("expected a request"),
+        }
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(500),
+            max_attempts: 10,
+        };
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(1), Duration::from_millis(200));
+        assert_eq!(backoff.delay(2), Duration::from_millis(400));
+        assert_eq!(backoff.delay(3), Duration::from_millis(500));
+        assert_eq!(backoff.delay(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_replay_idempotent_requests() {
+        let harness = start(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+            max_attempts: 3,
+        });
+        let (proxy, reader_tx) = harness.proxies.recv_timeout(TIMEOUT).unwrap();
+
+        let read_dir_rx = request(&harness.proxy_rpc, read_dir());
+        let create_file_rx = request(&harness.proxy_rpc, create_file());
+        let (read_dir_id, _) = recv_request(&proxy);
+        recv_request(&proxy);
+
+        // Drop the connection before the proxy answers
+        drop(proxy);
+        drop(reader_tx);
+        let (proxy, reader_tx) = harness.proxies.recv_timeout(TIMEOUT).unwrap();
+
+        assert!(create_file_rx.recv_timeout(TIMEOUT).unwrap().is_err());
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Reconnecting { attempt: 1 }
+        ));
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Connected
+        ));
+        harness
+            .restore_tx
+            .send(vec![ProxyNotification::OpenFileChanged {
+                path: PathBuf::from("restored.rs"),
+            }])
+            .unwrap();
+
+        // The restarted proxy is initialized, then gets the restored buffers
+        // and the request it has to answer
+        assert!(matches!(
+            proxy.recv_timeout(TIMEOUT).unwrap(),
+            RpcMessage::Notification(ProxyNotification::Initialize { .. })
+        ));
+        assert!(matches!(
+            proxy.recv_timeout(TIMEOUT).unwrap(),
+            RpcMessage::Notification(ProxyNotification::OpenFileChanged { path })
+                if path == PathBuf::from("restored.rs")
+        ));
+        let (id, request) = recv_request(&proxy);
+        assert_eq!(id, read_dir_id);
+        assert!(matches!(request, ProxyRequest::ReadDir { .. }));
+
+        reader_tx
+            .send(RpcMessage::Response(
+                id,
+                ProxyResponse::ReadDirResponse {
+                    items: Default::default(),
+                },
+            ))
+            .unwrap();
+        assert!(read_dir_rx.recv_timeout(TIMEOUT).unwrap().is_ok());
+
+        harness.proxy_rpc.shutdown();
+        harness.connection.join().unwrap();
+    }
+
+    #[test]
+    fn test_drop_updates_the_restored_buffers_have() {
+        let harness = start(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+            max_attempts: 3,
+        });
+        let (proxy, reader_tx) = harness.proxies.recv_timeout(TIMEOUT).unwrap();
+        drop(proxy);
+        drop(reader_tx);
+        let (proxy, _reader_tx) = harness.proxies.recv_timeout(TIMEOUT).unwrap();
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Reconnecting { attempt: 1 }
+        ));
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Connected
+        ));
+
+        let path = PathBuf::from("restored.rs");
+        let update = |rev| {
+            let delta =
+                RopeDelta::simple_edit(Interval::new(0, 0), Rope::from("a"), 0);
+            harness.proxy_rpc.update(path.clone(), delta, rev);
+        };
+        // Made before the editor took the content of the restored buffer
+        update(3);
+        harness
+            .restore_tx
+            .send(vec![ProxyNotification::RestoreBuffer {
+                buffer_id: BufferId(1),
+                path: path.clone(),
+                content: "a".to_string(),
+                rev: 3,
+                encoding: "utf-8".to_string(),
+                large_file: false,
+            }])
+            .unwrap();
+        update(4);
+
+        assert!(matches!(
+            proxy.recv_timeout(TIMEOUT).unwrap(),
+            RpcMessage::Notification(ProxyNotification::Initialize { .. })
+        ));
+        assert!(matches!(
+            proxy.recv_timeout(TIMEOUT).unwrap(),
+            RpcMessage::Notification(ProxyNotification::RestoreBuffer {
+                rev: 3,
+                ..
+            })
+        ));
+        assert!(matches!(
+            proxy.recv_timeout(TIMEOUT).unwrap(),
+            RpcMessage::Notification(ProxyNotification::Update { rev: 4, .. })
+        ));
+
+        harness.proxy_rpc.shutdown();
+        harness.connection.join().unwrap();
+    }
+
+    #[test]
+    fn test_fail_requests_after_giving_up() {
+        let harness = start(Backoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_millis(50),
+            max_attempts: 2,
+        });
+        let (proxy, reader_tx) = harness.proxies.recv_timeout(TIMEOUT).unwrap();
+        harness.down.store(true, Ordering::SeqCst);
+        drop(proxy);
+        drop(reader_tx);
+
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Reconnecting { attempt: 1 }
+        ));
+        // Made while reconnecting
+        let create_file_rx = request(&harness.proxy_rpc, create_file());
+        let read_dir_rx = request(&harness.proxy_rpc, read_dir());
+        assert!(create_file_rx.recv_timeout(TIMEOUT).unwrap().is_err());
+
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Reconnecting { attempt: 2 }
+        ));
+        assert!(matches!(
+            harness.statuses.recv_timeout(TIMEOUT).unwrap(),
+            ProxyStatus::Disconnected
+        ));
+        assert!(read_dir_rx.recv_timeout(TIMEOUT).unwrap().is_err());
+
+        // Made after giving up
+        let read_dir_rx = request(&harness.proxy_rpc, read_dir());
+        assert!(read_dir_rx.recv_timeout(TIMEOUT).unwrap().is_err());
+
+        harness.proxy_rpc.shutdown();
+        harness.connection.join().unwrap();
+    }
+}
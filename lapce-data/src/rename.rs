@@ -1,16 +1,221 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::HashSet,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use druid::{Command, EventCtx, ExtEventSink, Target, WidgetId};
 use lapce_core::buffer::Buffer;
 use lapce_xi_rope::Rope;
-use lsp_types::{Position, PrepareRenameResponse};
+use lsp_types::{
+    DocumentChangeOperation, DocumentChanges, Position, PrepareRenameResponse,
+    ResourceOp, TextEdit, WorkspaceEdit,
+};
 
 use crate::{
+    alert::AlertContentData,
     command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceConfig,
     data::LapceMainSplitData,
-    document::LocalBufferKind,
+    document::{BufferContent, Document, LocalBufferKind},
+    editor::{apply_edit, workspace_edits, workspace_operation, EditorLocation},
 };
 
+/// An edit of a rename that is being previewed
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenamePreviewEdit {
+    pub edit: TextEdit,
+    pub selected: bool,
+}
+
+impl RenamePreviewEdit {
+    /// The line of the edit with the new text right after the text it replaces,
+    /// and the ranges of both in it
+    pub fn diff_line(
+        &self,
+        buffer: &Buffer,
+    ) -> (String, Range<usize>, Range<usize>) {
+        let start = buffer.offset_of_position(&self.edit.range.start);
+        let line = buffer.line_of_offset(start);
+        let line_start = buffer.first_non_blank_character_on_line(line).min(start);
+        let line_end = buffer.line_end_offset(line, true).max(start);
+        let end = buffer
+            .offset_of_position(&self.edit.range.end)
+            .clamp(start, line_end);
+        let new_text = self.edit.new_text.lines().next().unwrap_or("");
+
+        let mut text = buffer.slice_to_cow(line_start..start).to_string();
+        let old = buffer.slice_to_cow(start..end);
+        let old_range = text.len()..text.len() + old.len();
+        text.push_str(&old);
+        let new_range = text.len()..text.len() + new_text.len();
+        text.push_str(new_text);
+        text.push_str(&buffer.slice_to_cow(end..line_end));
+        (text, old_range, new_range)
+    }
+}
+
+/// The edits of a rename that are in the same file
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenamePreviewFile {
+    pub path: PathBuf,
+    pub edits: Vec<RenamePreviewEdit>,
+}
+
+impl RenamePreviewFile {
+    pub fn is_selected(&self) -> bool {
+        self.edits.iter().any(|e| e.selected)
+    }
+}
+
+/// A line of the rename preview list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenamePreviewItem {
+    /// The header with the name of the file at this index
+    File(usize),
+    /// The edit at the second index, of the file at the first one
+    Edit(usize, usize),
+}
+
+/// The edits of a rename, grouped per file, which are shown before being
+/// applied so that some of them can be left out
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenamePreview {
+    pub new_name: String,
+    pub files: Vec<RenamePreviewFile>,
+    /// The file operations of the rename, like renaming the file of a module,
+    /// which are always applied
+    pub operations: Vec<ResourceOp>,
+    /// The files that weren't open and were only loaded for the preview
+    pub transient: HashSet<PathBuf>,
+}
+
+impl RenamePreview {
+    pub fn new(new_name: String, edit: &WorkspaceEdit) -> Self {
+        let mut files: Vec<RenamePreviewFile> = workspace_edits(edit)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(url, edits)| {
+                let path = url.to_file_path().ok()?;
+                let mut edits: Vec<RenamePreviewEdit> = edits
+                    .into_iter()
+                    .map(|edit| RenamePreviewEdit {
+                        edit,
+                        selected: true,
+                    })
+                    .collect();
+                edits.sort_by_key(|e| {
+                    (e.edit.range.start.line, e.edit.range.start.character)
+                });
+                Some(RenamePreviewFile { path, edits })
+            })
+            .filter(|file| !file.edits.is_empty())
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let operations = match edit.document_changes.as_ref() {
+            Some(DocumentChanges::Operations(ops)) => ops
+                .iter()
+                .filter_map(|op| match op {
+                    DocumentChangeOperation::Op(op) => Some(op.clone()),
+                    DocumentChangeOperation::Edit(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            new_name,
+            files,
+            operations,
+            transient: HashSet::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.operations.is_empty()
+    }
+
+    /// The number of lines of the list, a header for each file then a line per
+    /// edit
+    pub fn lines(&self) -> usize {
+        self.files.iter().map(|f| f.edits.len() + 1).sum()
+    }
+
+    pub fn item_at_line(&self, line: usize) -> Option<RenamePreviewItem> {
+        let mut start = 0;
+        for (i, file) in self.files.iter().enumerate() {
+            if line == start {
+                return Some(RenamePreviewItem::File(i));
+            }
+            if line <= start + file.edits.len() {
+                return Some(RenamePreviewItem::Edit(i, line - start - 1));
+            }
+            start += file.edits.len() + 1;
+        }
+        None
+    }
+
+    /// Select all the edits of the file, or deselect them if they all were
+    pub fn toggle_file(&mut self, file: usize) {
+        if let Some(file) = self.files.get_mut(file) {
+            let selected = !file.edits.iter().all(|e| e.selected);
+            for edit in file.edits.iter_mut() {
+                edit.selected = selected;
+            }
+        }
+    }
+
+    pub fn toggle_edit(&mut self, file: usize, edit: usize) {
+        if let Some(edit) = self
+            .files
+            .get_mut(file)
+            .and_then(|file| file.edits.get_mut(edit))
+        {
+            edit.selected = !edit.selected;
+        }
+    }
+
+    /// The number of selected edits, and of all the edits
+    pub fn selected_count(&self) -> (usize, usize) {
+        self.files
+            .iter()
+            .flat_map(|f| f.edits.iter())
+            .fold((0, 0), |(selected, total), e| {
+                (selected + usize::from(e.selected), total + 1)
+            })
+    }
+
+    /// The files with selected edits which aren't loaded yet, and so can't be
+    /// edited
+    pub fn unloaded_files(&self, is_loaded: impl Fn(&Path) -> bool) -> Vec<&Path> {
+        self.files
+            .iter()
+            .filter(|file| file.is_selected() && !is_loaded(&file.path))
+            .map(|file| file.path.as_path())
+            .collect()
+    }
+
+    /// The selected edits of the files that have any
+    pub fn selected_edits(&self) -> Vec<(&Path, Vec<TextEdit>)> {
+        self.files
+            .iter()
+            .filter(|file| file.is_selected())
+            .map(|file| {
+                (
+                    file.path.as_path(),
+                    file.edits
+                        .iter()
+                        .filter(|e| e.selected)
+                        .map(|e| e.edit.clone())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone)]
 pub struct RenameData {
     pub view_id: WidgetId,
@@ -28,6 +233,12 @@ pub struct RenameData {
     pub end: usize,
     pub placeholder: String,
     pub mouse_within: bool,
+
+    pub preview_widget_id: WidgetId,
+    pub preview_split_id: WidgetId,
+    pub preview_content_id: WidgetId,
+    /// The rename waiting to be confirmed in the rename preview panel
+    pub preview: Option<RenamePreview>,
 }
 
 impl RenameData {
@@ -45,6 +256,10 @@ pub fn new() -> Self {
             end: 0,
             placeholder: "".to_string(),
             mouse_within: false,
+            preview_widget_id: WidgetId::next(),
+            preview_split_id: WidgetId::next(),
+            preview_content_id: WidgetId::next(),
+            preview: None,
         }
     }
 
@@ -69,6 +284,108 @@ pub fn cancel(&mut self) {
         self.active = false;
     }
 
+    /// Keep the edits of a rename for the rename preview panel, loading the
+    /// files that aren't open so that the edited lines can be shown
+    pub fn show_preview(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+        new_name: String,
+        edit: &WorkspaceEdit,
+        config: &LapceConfig,
+    ) {
+        self.discard_preview(main_split);
+        let mut preview = RenamePreview::new(new_name, edit);
+        for file in preview.files.iter() {
+            if main_split.open_docs.contains_key(&file.path) {
+                continue;
+            }
+
+            let mut doc = Document::new(
+                BufferContent::File(file.path.clone()),
+                *main_split.tab_id,
+                ctx.get_external_handle(),
+                main_split.proxy.clone(),
+            );
+            doc.retrieve_file(
+                Vec::<(WidgetId, EditorLocation)>::new(),
+                None,
+                None,
+                config,
+            );
+            main_split
+                .open_docs
+                .insert(file.path.clone(), Arc::new(doc));
+            preview.transient.insert(file.path.clone());
+        }
+        self.preview = Some(preview);
+    }
+
+    /// Apply the selected edits of the previewed rename, as a single undo step
+    /// in each file. The files that were only loaded for the preview are saved
+    /// and closed right away, since no editor shows their changes. Nothing is
+    /// applied while some of the files are still loading, `false` then.
+    pub fn apply_preview(
+        &mut self,
+        ctx: &mut EventCtx,
+        main_split: &mut LapceMainSplitData,
+    ) -> bool {
+        let preview = match self.preview.take() {
+            Some(preview) => preview,
+            None => return true,
+        };
+
+        let unloaded = preview.unloaded_files(|path| {
+            main_split
+                .open_docs
+                .get(path)
+                .map(|doc| doc.loaded())
+                .unwrap_or(false)
+        });
+        if !unloaded.is_empty() {
+            let msg = unloaded
+                .iter()
+                .map(|path| format!("• {}", path.display()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowAlert(AlertContentData {
+                    title: "The rename can't be applied while its files are loading"
+                        .to_string(),
+                    msg,
+                    ..Default::default()
+                }),
+                Target::Widget(*main_split.tab_id),
+            ));
+            self.preview = Some(preview);
+            return false;
+        }
+
+        preview
+            .operations
+            .iter()
+            .filter_map(workspace_operation)
+            .map(|cmd| Command::new(LAPCE_UI_COMMAND, cmd, Target::Auto))
+            .for_each(|cmd| ctx.submit_command(cmd));
+
+        for (path, edits) in preview.selected_edits() {
+            apply_edit(main_split, path, &edits);
+            if preview.transient.contains(path) && !main_split.is_doc_shown(path) {
+                main_split.document_save(ctx, path, None);
+            }
+        }
+        close_transient(main_split, &preview);
+        true
+    }
+
+    /// Forget the previewed rename, closing the files only loaded for it
+    pub fn discard_preview(&mut self, main_split: &mut LapceMainSplitData) {
+        if let Some(preview) = self.preview.take() {
+            close_transient(main_split, &preview);
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn handle_prepare_rename(
         &mut self,
@@ -154,8 +471,107 @@ pub fn prepare_rename(
     }
 }
 
+/// Close the files only loaded for the preview which no editor shows. The
+/// saved ones are closed too, the proxy saves them before closing them.
+fn close_transient(main_split: &mut LapceMainSplitData, preview: &RenamePreview) {
+    for path in preview.transient.iter() {
+        if !main_split.is_doc_shown(path) {
+            main_split.close_doc(path);
+        }
+    }
+}
+
 impl Default for RenameData {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use lapce_core::buffer::Buffer;
+    use lsp_types::{Position, Range, TextEdit, Url, WorkspaceEdit};
+
+    use super::{RenamePreview, RenamePreviewItem};
+
+    fn edit(line: u32, start: u32, end: u32) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: "total".to_string(),
+        }
+    }
+
+    fn preview() -> RenamePreview {
+        let mut changes = HashMap::new();
+        changes.insert(
+            Url::parse("file:///b.rs").unwrap(),
+            vec![edit(3, 8, 11), edit(1, 8, 11)],
+        );
+        changes.insert(Url::parse("file:///a.rs").unwrap(), vec![edit(0, 0, 3)]);
+        RenamePreview::new("total".to_string(), &WorkspaceEdit::new(changes))
+    }
+
+    #[test]
+    fn test_group_edits_per_file() {
+        let preview = preview();
+        let paths: Vec<&PathBuf> = preview.files.iter().map(|f| &f.path).collect();
+        assert_eq!(
+            paths,
+            vec![&PathBuf::from("/a.rs"), &PathBuf::from("/b.rs")]
+        );
+        assert_eq!(preview.files[1].edits[0].edit, edit(1, 8, 11));
+        assert_eq!(preview.lines(), 5);
+        assert_eq!(preview.selected_count(), (3, 3));
+
+        assert_eq!(preview.item_at_line(0), Some(RenamePreviewItem::File(0)));
+        assert_eq!(preview.item_at_line(1), Some(RenamePreviewItem::Edit(0, 0)));
+        assert_eq!(preview.item_at_line(2), Some(RenamePreviewItem::File(1)));
+        assert_eq!(preview.item_at_line(4), Some(RenamePreviewItem::Edit(1, 1)));
+        assert_eq!(preview.item_at_line(5), None);
+    }
+
+    #[test]
+    fn test_deselect_edits() {
+        let mut preview = preview();
+        preview.toggle_edit(1, 0);
+        assert_eq!(preview.selected_count(), (2, 3));
+        let selected = preview.selected_edits();
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[1].1, vec![edit(3, 8, 11)]);
+
+        // Partially selected files are selected as a whole first
+        preview.toggle_file(1);
+        assert_eq!(preview.selected_count(), (3, 3));
+        preview.toggle_file(1);
+        assert_eq!(preview.selected_count(), (1, 3));
+        let selected = preview.selected_edits();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, PathBuf::from("/a.rs").as_path());
+    }
+
+    #[test]
+    fn test_unloaded_files() {
+        let mut preview = preview();
+        let loaded = |path: &Path| path == Path::new("/a.rs");
+        assert_eq!(preview.unloaded_files(loaded), vec![Path::new("/b.rs")]);
+
+        // The files without selected edits aren't edited, loaded or not
+        preview.toggle_file(1);
+        assert!(preview.unloaded_files(loaded).is_empty());
+    }
+
+    #[test]
+    fn test_diff_line() {
+        let buffer = Buffer::new("fn main() {\n    let sum = 1;\n}\n");
+        let preview = preview();
+        let (text, old, new) = preview.files[1].edits[0].diff_line(&buffer);
+        assert_eq!(text, "let sumtotal = 1;");
+        assert_eq!(&text[old], "sum");
+        assert_eq!(&text[new], "total");
+    }
+}
@@ -17,6 +17,10 @@ pub struct RichText {
     buffer: ArcStr,
     attrs: Arc<AttributeSpans>,
     line_height: f64,
+    /// The ranges of the links, with the targets they lead to
+    link_targets: Arc<Vec<(Range<usize>, String)>>,
+    /// The anchors that links within the text can jump to, with their offsets
+    anchors: Arc<Vec<(String, usize)>>,
 }
 
 impl RichText {
@@ -31,6 +35,8 @@ pub fn new_with_attributes(buffer: ArcStr, attributes: AttributeSpans) -> Self {
             buffer,
             attrs: Arc::new(attributes),
             line_height: 0.0,
+            link_targets: Arc::new(Vec::new()),
+            anchors: Arc::new(Vec::new()),
         }
     }
 
@@ -67,6 +73,22 @@ pub fn add_attribute(
         let range = druid::piet::util::resolve_range(range, self.buffer.len());
         Arc::make_mut(&mut self.attrs).add(range, attr);
     }
+
+    /// The target of the link covering the offset, with the range of the link
+    pub fn link_at(&self, offset: usize) -> Option<(Range<usize>, &str)> {
+        self.link_targets
+            .iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(range, target)| (range.clone(), target.as_str()))
+    }
+
+    /// The offset of the anchor, such as the one of a heading
+    pub fn anchor_offset(&self, anchor: &str) -> Option<usize> {
+        self.anchors
+            .iter()
+            .find(|(name, _)| name == anchor)
+            .map(|(_, offset)| *offset)
+    }
 }
 
 impl PietTextStorage for RichText {
@@ -104,6 +126,8 @@ pub struct RichTextBuilder {
     buffer: String,
     attrs: AttributeSpans,
     links: Vec<Link>,
+    link_targets: Vec<(Range<usize>, String)>,
+    anchors: Vec<(String, usize)>,
     line_height: f64,
 }
 
@@ -127,6 +151,11 @@ pub fn set_line_height(&mut self, line_height: f64) {
         self.line_height = line_height;
     }
 
+    /// Add an anchor at the offset, which links to `#anchor` jump to
+    pub fn add_anchor(&mut self, anchor: String, offset: usize) {
+        self.anchors.push((anchor, offset));
+    }
+
     /// Glue for usage of the write! macro.
     ///
     /// This method should generally not be invoked manually, but rather through the write! macro itself.
@@ -160,6 +189,8 @@ pub fn build(self) -> RichText {
             buffer: self.buffer.into(),
             attrs: self.attrs.into(),
             line_height: self.line_height,
+            link_targets: Arc::new(self.link_targets),
+            anchors: Arc::new(self.anchors),
         }
     }
 }
@@ -230,4 +261,47 @@ pub fn link(&mut self, command: impl Into<Command>) -> &mut Self {
             .push(Link::new(self.range.clone(), command.into()));
         self
     }
+
+    /// Set the target that the range links to, like a url or an `#anchor`
+    pub fn link_target(&mut self, target: impl Into<String>) -> &mut Self {
+        self.rich_text_builder
+            .link_targets
+            .push((self.range.clone(), target.into()));
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RichTextBuilder;
+
+    #[test]
+    fn test_link_at() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("see ");
+        builder.push("the docs").link_target("https://lapce.dev");
+        builder.push(" or ");
+        builder.push("below").link_target("#usage");
+        let text = builder.build();
+
+        assert_eq!(text.link_at(0), None);
+        assert_eq!(text.link_at(4), Some((4..12, "https://lapce.dev")));
+        assert_eq!(text.link_at(11), Some((4..12, "https://lapce.dev")));
+        assert_eq!(text.link_at(12), None);
+        assert_eq!(text.link_at(16), Some((16..21, "#usage")));
+        assert_eq!(text.link_at(21), None);
+    }
+
+    #[test]
+    fn test_anchor_offset() {
+        let mut builder = RichTextBuilder::new();
+        builder.push("Install\nUsage");
+        builder.add_anchor("install".to_string(), 0);
+        builder.add_anchor("usage".to_string(), 8);
+        let text = builder.build();
+
+        assert_eq!(text.anchor_offset("usage"), Some(8));
+        assert_eq!(text.anchor_offset("install"), Some(0));
+        assert_eq!(text.anchor_offset("missing"), None);
+    }
 }
@@ -10,6 +10,8 @@ pub struct SearchData {
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
     pub editor_view_id: WidgetId,
+    /// The id of the latest search, the matches of older searches are ignored
+    pub search_id: u64,
     pub matches: Arc<IndexMap<PathBuf, Vec<Match>>>,
 }
 
@@ -21,9 +23,41 @@ pub fn new() -> Self {
             widget_id: WidgetId::next(),
             split_id: WidgetId::next(),
             editor_view_id,
+            search_id: 0,
             matches: Arc::new(IndexMap::new()),
         }
     }
+
+    /// Clear the matches for a new search and return its id
+    pub fn new_search(&mut self) -> u64 {
+        self.search_id += 1;
+        self.matches = Arc::new(IndexMap::new());
+        self.search_id
+    }
+
+    /// Add a batch of matches that the search streamed in, returns `false` if the
+    /// batch belongs to an older search and was ignored
+    pub fn extend_matches(
+        &mut self,
+        search_id: u64,
+        batch: &IndexMap<PathBuf, Vec<Match>>,
+    ) -> bool {
+        if search_id != self.search_id {
+            return false;
+        }
+        if batch.is_empty() {
+            return true;
+        }
+
+        let matches = Arc::make_mut(&mut self.matches);
+        for (path, file_matches) in batch {
+            matches
+                .entry(path.clone())
+                .or_default()
+                .extend(file_matches.iter().cloned());
+        }
+        true
+    }
 }
 
 impl Default for SearchData {
@@ -31,3 +65,38 @@ fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use indexmap::IndexMap;
+
+    use super::SearchData;
+
+    fn batch(path: &str, line: usize) -> IndexMap<PathBuf, Vec<super::Match>> {
+        let mut batch = IndexMap::new();
+        batch.insert(PathBuf::from(path), vec![(line, (0, 1), "a".to_string())]);
+        batch
+    }
+
+    #[test]
+    fn extend_matches_ignores_older_searches() {
+        let mut search = SearchData::new();
+        let old_id = search.new_search();
+        assert!(search.extend_matches(old_id, &batch("a.rs", 1)));
+
+        let id = search.new_search();
+        assert!(search.matches.is_empty());
+        assert!(!search.extend_matches(old_id, &batch("b.rs", 2)));
+        assert!(search.extend_matches(id, &batch("a.rs", 3)));
+        assert!(search.extend_matches(id, &batch("a.rs", 4)));
+
+        assert_eq!(search.matches.len(), 1);
+        let lines: Vec<usize> = search.matches[&PathBuf::from("a.rs")]
+            .iter()
+            .map(|m| m.0)
+            .collect();
+        assert_eq!(lines, vec![3, 4]);
+    }
+}
@@ -1,3 +1,4 @@
+use lapce_core::{buffer::Buffer, cursor::CursorMode};
 use lapce_rpc::buffer::BufferId;
 use lsp_types::{Range, SelectionRange};
 
@@ -91,12 +92,189 @@ fn count(&self) -> usize {
     }
 }
 
+/// The selections "expand selection" went through in an editor, so that "shrink
+/// selection" walks back down exactly the same way
+#[derive(Clone, Debug, Default)]
+pub struct SelectionRangeStack {
+    buffer_id: Option<BufferId>,
+    rev: u64,
+    /// The selection the ranges were asked for, and the ranges around it, from
+    /// the innermost. Empty ranges mean there's no LSP to ask.
+    ranges: Option<((usize, usize), Vec<(usize, usize)>)>,
+    /// The cursor before each expansion, and the range it expanded to
+    stack: Vec<(CursorMode, (usize, usize))>,
+}
+
+impl SelectionRangeStack {
+    /// Forget everything after an edit, and the expansions that no longer hold
+    /// the selection, which is kept when the cursor only moved within it
+    pub fn sync(&mut self, buffer_id: BufferId, rev: u64, selected: (usize, usize)) {
+        if self.buffer_id != Some(buffer_id) || self.rev != rev {
+            *self = Self {
+                buffer_id: Some(buffer_id),
+                rev,
+                ..Default::default()
+            };
+            return;
+        }
+
+        while let Some((_, (start, end))) = self.stack.last() {
+            if *start <= selected.0 && selected.1 <= *end {
+                break;
+            }
+            self.stack.pop();
+        }
+        if self.stack.is_empty()
+            && self
+                .ranges
+                .as_ref()
+                .map(|(origin, _)| *origin != selected)
+                .unwrap_or(false)
+        {
+            self.ranges = None;
+        }
+    }
+
+    /// Whether the ranges around the selection should be asked to the LSP
+    /// before expanding
+    pub fn needs_ranges(&self) -> bool {
+        self.ranges.is_none()
+    }
+
+    pub fn set_ranges(
+        &mut self,
+        buffer_id: BufferId,
+        rev: u64,
+        origin: (usize, usize),
+        ranges: Vec<(usize, usize)>,
+    ) {
+        self.sync(buffer_id, rev, origin);
+        self.ranges = Some((origin, ranges));
+    }
+
+    /// The range to select around `selected`, which is remembered with the
+    /// cursor `mode` to shrink back to it
+    pub fn expand(
+        &mut self,
+        buffer: &Buffer,
+        mode: CursorMode,
+        selected: (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let (start, end) = selected;
+        let is_larger = |(s, e): &(usize, usize)| {
+            *s <= start && end <= *e && (*s, *e) != (start, end)
+        };
+        let range = self
+            .ranges
+            .as_ref()
+            .and_then(|(_, ranges)| ranges.iter().copied().find(is_larger))
+            .or_else(|| expand_heuristic(buffer, start, end))?;
+        self.stack.push((mode, range));
+        Some(range)
+    }
+
+    /// The cursor before the last expansion
+    pub fn shrink(&mut self) -> Option<CursorMode> {
+        self.stack.pop().map(|(mode, _)| mode)
+    }
+}
+
+/// The offsets of the ranges, from the innermost
+pub fn selection_range_offsets(
+    buffer: &Buffer,
+    range: &SelectionRange,
+) -> Vec<(usize, usize)> {
+    let mut offsets = Vec::new();
+    let mut range = Some(range);
+    while let Some(r) = range {
+        offsets.push((
+            buffer.offset_of_position(&r.range.start),
+            buffer.offset_of_position(&r.range.end),
+        ));
+        range = r.parent.as_deref();
+    }
+    offsets
+}
+
+/// Expand the selection without the LSP, to the smallest of the word around
+/// it, the inside of the brackets around it, those brackets, the line and the
+/// whole buffer
+pub fn expand_heuristic(
+    buffer: &Buffer,
+    start: usize,
+    end: usize,
+) -> Option<(usize, usize)> {
+    let mut candidates = vec![buffer.select_word(start), (0, buffer.len())];
+    if let Some((open, close)) = enclosing_brackets(buffer, start, end) {
+        candidates.push((open + 1, close));
+        candidates.push((open, close + 1));
+    }
+    let line = buffer.line_of_offset(start);
+    if buffer.line_of_offset(end) == line {
+        candidates.push((
+            buffer.first_non_blank_character_on_line(line),
+            buffer.line_end_offset(line, true),
+        ));
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(s, e)| *s <= start && end <= *e && (*s, *e) != (start, end))
+        .min_by_key(|(s, e)| e - s)
+}
+
+/// The offsets of the closest pair of brackets around `start..end`
+fn enclosing_brackets(
+    buffer: &Buffer,
+    start: usize,
+    end: usize,
+) -> Option<(usize, usize)> {
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+    let is_open = |c: char| PAIRS.iter().any(|(o, _)| *o == c);
+    let is_close = |c: char| PAIRS.iter().any(|(_, cl)| *cl == c);
+
+    let before = buffer.slice_to_cow(0..start);
+    let mut depth = 0;
+    let (open, open_char) = before.char_indices().rev().find(|(_, c)| {
+        if is_close(*c) {
+            depth += 1;
+        } else if is_open(*c) {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+
+    let mut depth = 0;
+    let (close, close_char) = buffer.char_indices_iter(end..).find(|(_, c)| {
+        if is_open(*c) {
+            depth += 1;
+        } else if is_close(*c) {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+
+    // The offsets of the iterator are from `end`
+    PAIRS
+        .contains(&(open_char, close_char))
+        .then_some((open, end + close))
+}
+
 #[cfg(test)]
 mod test {
+    use lapce_core::{buffer::Buffer, cursor::CursorMode, selection::Selection};
     use lapce_rpc::buffer::BufferId;
     use lsp_types::{Position, Range, SelectionRange};
 
-    use crate::selection_range::SyntaxSelectionRanges;
+    use crate::selection_range::{
+        expand_heuristic, SelectionRangeStack, SyntaxSelectionRanges,
+    };
 
     #[test]
     fn should_get_next_selection_range() {
@@ -179,4 +357,96 @@ fn should_get_next_selection_range() {
         assert_eq!(range, Some(range_zero));
         assert_eq!(syntax_selection.current_selection, Some(0));
     }
+
+    #[test]
+    fn test_expand_heuristic() {
+        let buffer = Buffer::new("fn main() {\n    let v = foo(bar, baz);\n}\n");
+        let text = buffer.text().to_string();
+        let bar = text.find("bar").unwrap();
+        let slice = |(s, e): (usize, usize)| text[s..e].to_string();
+
+        let word = expand_heuristic(&buffer, bar + 1, bar + 1).unwrap();
+        assert_eq!(slice(word), "bar");
+        let inner = expand_heuristic(&buffer, word.0, word.1).unwrap();
+        assert_eq!(slice(inner), "bar, baz");
+        let outer = expand_heuristic(&buffer, inner.0, inner.1).unwrap();
+        assert_eq!(slice(outer), "(bar, baz)");
+        let line = expand_heuristic(&buffer, outer.0, outer.1).unwrap();
+        assert_eq!(slice(line), "let v = foo(bar, baz);");
+        let block = expand_heuristic(&buffer, line.0, line.1).unwrap();
+        assert_eq!(slice(block), "\n    let v = foo(bar, baz);\n");
+        let all = expand_heuristic(&buffer, block.0 - 1, block.1 + 1).unwrap();
+        assert_eq!(all, (0, buffer.len()));
+        assert_eq!(expand_heuristic(&buffer, 0, buffer.len()), None);
+    }
+
+    #[test]
+    fn test_expand_then_shrink() {
+        let buffer = Buffer::new("let v = foo(bar, baz);");
+        let bar = 12;
+        let caret = CursorMode::Insert(Selection::caret(bar + 1));
+
+        let mut stack = SelectionRangeStack::default();
+        stack.sync(BufferId(0), 0, (bar + 1, bar + 1));
+        assert!(stack.needs_ranges());
+        // The LSP knows of the word and the arguments, the rest is guessed
+        stack.set_ranges(
+            BufferId(0),
+            0,
+            (bar + 1, bar + 1),
+            vec![(bar, bar + 3), (bar, bar + 8)],
+        );
+        assert!(!stack.needs_ranges());
+
+        let mut mode = caret.clone();
+        let mut selected = (bar + 1, bar + 1);
+        let mut expansions = Vec::new();
+        for _ in 0..3 {
+            stack.sync(BufferId(0), 0, selected);
+            let range = stack.expand(&buffer, mode.clone(), selected).unwrap();
+            expansions.push(range);
+            mode = CursorMode::Insert(Selection::region(range.0, range.1));
+            selected = range;
+        }
+        assert_eq!(
+            expansions,
+            vec![(bar, bar + 3), (bar, bar + 8), (bar - 1, bar + 9)]
+        );
+
+        // Moving within the selection keeps the expansions
+        stack.sync(BufferId(0), 0, (bar + 5, bar + 5));
+        assert_eq!(
+            stack.shrink(),
+            Some(CursorMode::Insert(Selection::region(bar, bar + 8)))
+        );
+        assert_eq!(
+            stack.shrink(),
+            Some(CursorMode::Insert(Selection::region(bar, bar + 3)))
+        );
+        assert_eq!(stack.shrink(), Some(caret));
+        assert_eq!(stack.shrink(), None);
+    }
+
+    #[test]
+    fn test_stack_invalidation() {
+        let buffer = Buffer::new("foo(bar)");
+        let mut stack = SelectionRangeStack::default();
+        stack.set_ranges(BufferId(0), 0, (5, 5), Vec::new());
+        let mode = CursorMode::Insert(Selection::caret(5));
+        assert_eq!(stack.expand(&buffer, mode.clone(), (5, 5)), Some((4, 7)));
+        assert_eq!(stack.expand(&buffer, mode, (4, 7)), Some((3, 8)));
+
+        // Moving out of the selection forgets the expansions
+        stack.sync(BufferId(0), 0, (1, 1));
+        assert_eq!(stack.shrink(), None);
+        assert!(stack.needs_ranges());
+
+        // So does an edit
+        stack.set_ranges(BufferId(0), 0, (5, 5), Vec::new());
+        let mode = CursorMode::Insert(Selection::caret(5));
+        assert!(stack.expand(&buffer, mode, (5, 5)).is_some());
+        stack.sync(BufferId(0), 1, (4, 7));
+        assert_eq!(stack.shrink(), None);
+        assert!(stack.needs_ranges());
+    }
 }
@@ -31,6 +31,9 @@ pub enum LapceSettingsKind {
     UI,
     Editor,
     Terminal,
+    /// The settings of the palette, completion, explorer and the other parts
+    /// without a page of their own
+    Workbench,
     Theme,
     Keymap,
     Plugin(VoltID),
@@ -51,6 +54,10 @@ pub struct LapceSettingsPanelData {
     /// Mapping of setting key to dropdown data for that key
     pub dropdown_data:
         im::HashMap<String, im::HashMap<String, DropdownData<String, ()>>>,
+
+    /// The search typed above the settings, which shows the matching settings
+    /// of all the sections when it isn't empty
+    pub filter_pattern: String,
 }
 
 impl KeyPressFocus for LapceSettingsPanelData {
@@ -101,6 +108,7 @@ pub fn new() -> Self {
             settings_split_id: WidgetId::next(),
 
             dropdown_data: im::HashMap::new(),
+            filter_pattern: String::new(),
         }
     }
 }
@@ -29,13 +29,14 @@ pub struct SignatureData {
     /// Size of the signature view
     pub size: Size,
 
-    // TODO: Allow switching between the signatures.
-    // (Since we request an update for the signature often, the LSP should swap
-    // the active parameter if it gets a better idea of the type, which will cover most cases)
     pub signatures: Vec<SignatureInformation>,
     pub current_signature: usize,
     /// The currently parameter the user is editing
     pub active_parameter: Option<usize>,
+    /// Whether the user picked the current signature themselves, in which case
+    /// it's kept when the signatures are requested again while typing, rather
+    /// than the one the LSP thinks is active
+    overload_chosen: bool,
 }
 impl SignatureData {
     pub fn new() -> Self {
@@ -52,6 +53,7 @@ pub fn new() -> Self {
             signatures: Vec::new(),
             current_signature: 0,
             active_parameter: None,
+            overload_chosen: false,
         }
     }
 
@@ -65,6 +67,51 @@ pub fn current(&self) -> Option<&SignatureInformation> {
         self.signatures.get(self.current_signature)
     }
 
+    /// The parameter of the current signature the user is editing. The active
+    /// parameter of the signature itself takes precedence over the one of the
+    /// response.
+    pub fn current_parameter(&self) -> Option<usize> {
+        self.current()
+            .and_then(|sig| sig.active_parameter)
+            .map(|idx| idx as usize)
+            .or(self.active_parameter)
+    }
+
+    /// The position of the current signature among the overloads, and their
+    /// count, when there's more than one of them
+    pub fn overload_position(&self) -> Option<(usize, usize)> {
+        if self.signatures.len() > 1 {
+            Some((self.current_signature + 1, self.signatures.len()))
+        } else {
+            None
+        }
+    }
+
+    /// Switch to the next overload, wrapping around after the last one
+    pub fn next_overload(&mut self) {
+        if self.signatures.is_empty() {
+            return;
+        }
+
+        self.current_signature =
+            (self.current_signature + 1) % self.signatures.len();
+        self.overload_chosen = true;
+    }
+
+    /// Switch to the previous overload, wrapping around before the first one
+    pub fn prev_overload(&mut self) {
+        if self.signatures.is_empty() {
+            return;
+        }
+
+        self.current_signature = if self.current_signature == 0 {
+            self.signatures.len() - 1
+        } else {
+            self.current_signature - 1
+        };
+        self.overload_chosen = true;
+    }
+
     pub fn request(
         &self,
         proxy: Arc<LapceProxy>,
@@ -83,6 +130,7 @@ pub fn cancel(&mut self) {
         self.signatures.clear();
         self.current_signature = 0;
         self.active_parameter = None;
+        self.overload_chosen = false;
         self.status = SignatureStatus::Inactive;
     }
 
@@ -108,8 +156,13 @@ pub fn receive(
         // few parameters
         let active_parameter = resp.active_parameter.map(|idx| idx as usize);
 
+        // Keep the overload the user picked, as long as the overloads are still
+        // the same ones
+        if !self.overload_chosen || self.signatures.len() != signatures.len() {
+            self.current_signature = active_sig_idx;
+            self.overload_chosen = false;
+        }
         self.signatures = signatures;
-        self.current_signature = active_sig_idx;
         self.active_parameter = active_parameter;
 
         // Updating of the text layouts for the UI is done in `SignatureContainer::update_signature`
@@ -121,3 +174,59 @@ fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use lapce_rpc::plugin::PluginId;
+    use lsp_types::{SignatureHelp, SignatureInformation};
+
+    use crate::signature::{SignatureData, SignatureStatus};
+
+    fn signature_help(active_signature: u32) -> SignatureHelp {
+        let signatures = ["fn f(a: u8)", "fn f(a: u8, b: u8)", "fn f(a: &str)"]
+            .iter()
+            .map(|label| SignatureInformation {
+                label: label.to_string(),
+                documentation: None,
+                parameters: None,
+                active_parameter: None,
+            })
+            .collect();
+        SignatureHelp {
+            signatures,
+            active_signature: Some(active_signature),
+            active_parameter: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_cycle_overloads() {
+        let mut signature = SignatureData::new();
+        signature.status = SignatureStatus::Started;
+        signature.request_id = 1;
+        signature.receive(1, signature_help(1), PluginId(0));
+        assert_eq!(signature.overload_position(), Some((2, 3)));
+        assert_eq!(signature.current_parameter(), Some(1));
+
+        signature.next_overload();
+        assert_eq!(signature.overload_position(), Some((3, 3)));
+        signature.next_overload();
+        assert_eq!(signature.overload_position(), Some((1, 3)));
+        signature.prev_overload();
+        assert_eq!(signature.overload_position(), Some((3, 3)));
+        assert_eq!(signature.current().unwrap().label, "fn f(a: &str)");
+
+        // Requesting the signatures again, like after typing a comma, keeps the
+        // overload the user picked
+        signature.request_id = 2;
+        signature.receive(2, signature_help(1), PluginId(0));
+        assert_eq!(signature.overload_position(), Some((3, 3)));
+
+        signature.cancel();
+        assert_eq!(signature.overload_position(), None);
+        signature.status = SignatureStatus::Started;
+        signature.request_id = 3;
+        signature.receive(3, signature_help(1), PluginId(0));
+        assert_eq!(signature.overload_position(), Some((2, 3)));
+    }
+}
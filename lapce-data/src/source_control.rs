@@ -1,13 +1,16 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use druid::{Command, Env, EventCtx, Modifiers, Target, WidgetId};
 use indexmap::IndexMap;
 use lapce_core::{
-    command::{FocusCommand, MoveCommand},
+    command::{FocusCommand, MoveCommand, SourceControlCommand},
     mode::Mode,
     movement::Movement,
 };
-use lapce_rpc::source_control::FileDiff;
+use lapce_rpc::source_control::{DiffHunk, FileDiff};
 
 use crate::{
     command::{CommandExecuted, CommandKind, LapceUICommand, LAPCE_UI_COMMAND},
@@ -18,6 +21,14 @@
 pub const SOURCE_CONTROL_BUFFER: &str = "[Source Control Buffer]";
 pub const SEARCH_BUFFER: &str = "[Search Buffer]";
 
+/// A row of the changes list, either a file or one of the hunks of an
+/// expanded file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceControlItem {
+    File(usize),
+    Hunk(usize, usize),
+}
+
 #[derive(Clone)]
 pub struct SourceControlData {
     pub active: WidgetId,
@@ -30,6 +41,8 @@ pub struct SourceControlData {
     pub commit_button_id: WidgetId,
     // VCS modified files & whether they should be included in the next commit
     pub file_diffs: IndexMap<PathBuf, (FileDiff, bool)>,
+    pub hunks: HashMap<PathBuf, Vec<DiffHunk>>,
+    pub expanded_files: HashSet<PathBuf>,
     pub branch: String,
     pub branches: im::Vector<String>,
 }
@@ -48,10 +61,96 @@ pub fn new() -> Self {
             split_id: WidgetId::next(),
             split_direction: SplitDirection::Horizontal,
             file_diffs: IndexMap::new(),
+            hunks: HashMap::new(),
+            expanded_files: HashSet::new(),
             branch: "".to_string(),
             branches: im::Vector::new(),
         }
     }
+
+    pub fn file_hunks(&self, path: &Path) -> &[DiffHunk] {
+        self.hunks.get(path).map(|h| h.as_slice()).unwrap_or(&[])
+    }
+
+    /// The rows of the changes list, with the hunks of the expanded files
+    /// listed under them
+    pub fn items(&self) -> Vec<SourceControlItem> {
+        let mut items = Vec::new();
+        for (i, path) in self.file_diffs.keys().enumerate() {
+            items.push(SourceControlItem::File(i));
+            if self.expanded_files.contains(path) {
+                items.extend(
+                    (0..self.file_hunks(path).len())
+                        .map(|j| SourceControlItem::Hunk(i, j)),
+                );
+            }
+        }
+        items
+    }
+
+    pub fn item_path(&self, item: SourceControlItem) -> Option<&PathBuf> {
+        match item {
+            SourceControlItem::File(i) | SourceControlItem::Hunk(i, _) => {
+                self.file_diffs.get_index(i).map(|(path, _)| path)
+            }
+        }
+    }
+
+    pub fn item_hunk(
+        &self,
+        item: SourceControlItem,
+    ) -> Option<(&PathBuf, &DiffHunk)> {
+        match item {
+            SourceControlItem::File(_) => None,
+            SourceControlItem::Hunk(_, j) => {
+                let path = self.item_path(item)?;
+                Some((path, self.file_hunks(path).get(j)?))
+            }
+        }
+    }
+
+    pub fn toggle_expanded(&mut self, path: &Path) {
+        if !self.expanded_files.remove(path) {
+            self.expanded_files.insert(path.to_path_buf());
+        }
+    }
+
+    /// Stages or unstages the hunk given in the command data, or the hunk
+    /// of the current row
+    fn run_source_control_command(
+        &self,
+        ctx: &mut EventCtx,
+        cmd: &SourceControlCommand,
+        data: Option<&serde_json::Value>,
+    ) -> CommandExecuted {
+        let (path, hunk) = match data {
+            Some(data) => {
+                match serde_json::from_value::<(PathBuf, DiffHunk)>(data.clone()) {
+                    Ok(target) => target,
+                    Err(_) => return CommandExecuted::No,
+                }
+            }
+            None => match self
+                .items()
+                .get(self.file_list_index)
+                .and_then(|item| self.item_hunk(*item))
+            {
+                Some((path, hunk)) => (path.clone(), hunk.clone()),
+                None => return CommandExecuted::No,
+            },
+        };
+        let command = match cmd {
+            SourceControlCommand::StageHunk if !hunk.staged => {
+                LapceUICommand::StageHunk { path, hunk }
+            }
+            SourceControlCommand::UnstageHunk if hunk.staged => {
+                LapceUICommand::UnstageHunk { path, hunk }
+            }
+            _ => return CommandExecuted::No,
+        };
+        ctx.submit_command(Command::new(LAPCE_UI_COMMAND, command, Target::Auto));
+        CommandExecuted::Yes
+    }
 }
 
 impl Default for SourceControlData {
@@ -163,7 +262,7 @@ fn run_command(
                 FocusCommand::ListPrevious => {
                     self.file_list_index = Movement::Up.update_index(
                         self.file_list_index,
-                        self.file_diffs.len(),
+                        self.items().len(),
                         1,
                         true,
                     );
@@ -171,28 +270,39 @@ fn run_command(
                 FocusCommand::ListNext => {
                     self.file_list_index = Movement::Down.update_index(
                         self.file_list_index,
-                        self.file_diffs.len(),
+                        self.items().len(),
                         1,
                         true,
                     );
                 }
                 FocusCommand::ListExpand => {
-                    if !self.file_diffs.is_empty() {
-                        self.file_diffs[self.file_list_index].1 =
-                            !self.file_diffs[self.file_list_index].1;
+                    match self.items().get(self.file_list_index) {
+                        Some(SourceControlItem::File(i)) => {
+                            self.file_diffs[*i].1 = !self.file_diffs[*i].1;
+                        }
+                        Some(item) => {
+                            // Toggles whether the hunk is staged
+                            let cmd = match self.item_hunk(*item) {
+                                Some((_, hunk)) if hunk.staged => {
+                                    SourceControlCommand::UnstageHunk
+                                }
+                                _ => SourceControlCommand::StageHunk,
+                            };
+                            return self.run_source_control_command(ctx, &cmd, None);
+                        }
+                        None => {}
                     }
                 }
                 FocusCommand::ListSelect => {
-                    if !self.file_diffs.is_empty() {
+                    if let Some(path) = self
+                        .items()
+                        .get(self.file_list_index)
+                        .and_then(|item| self.item_path(*item))
+                    {
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::OpenFileDiff {
-                                path: self
-                                    .file_diffs
-                                    .get_index(self.file_list_index)
-                                    .unwrap()
-                                    .0
-                                    .clone(),
+                                path: path.clone(),
                                 history: "head".to_string(),
                             },
                             Target::Auto,
@@ -205,7 +315,7 @@ fn run_command(
                 MoveCommand::Up => {
                     self.file_list_index = Movement::Up.update_index(
                         self.file_list_index,
-                        self.file_diffs.len(),
+                        self.items().len(),
                         1,
                         true,
                     );
@@ -213,13 +323,20 @@ fn run_command(
                 MoveCommand::Down => {
                     self.file_list_index = Movement::Down.update_index(
                         self.file_list_index,
-                        self.file_diffs.len(),
+                        self.items().len(),
                         1,
                         true,
                     );
                 }
                 _ => return CommandExecuted::No,
             },
+            CommandKind::SourceControl(cmd) => {
+                return self.run_source_control_command(
+                    ctx,
+                    cmd,
+                    command.data.as_ref(),
+                );
+            }
             _ => return CommandExecuted::No,
         }
         CommandExecuted::Yes
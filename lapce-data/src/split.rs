@@ -1,7 +1,66 @@
 use druid::{Rect, Size};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+/// The smallest share of a split a pane can be resized to
+pub const MIN_SPLIT_RATIO: f64 = 0.05;
+
+/// How much of its split a pane grows or shrinks by with the resize commands
+pub const SPLIT_RESIZE_STEP: f64 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitCommand {
+    /// Move the active editor into the split next to its own in the
+    /// direction, splitting one off when there's none
+    MoveEditorToSplit { direction: SplitMoveDirection },
+    /// Give the pane this share of its split, the other panes keeping
+    /// their proportions of the rest
+    SetRatio(f64),
+    /// Give every pane of the split the same share
+    Equalize,
+}
+
+impl SplitCommand {
+    /// The shares of the panes after running the command on the pane at `index`
+    pub fn apply(self, ratios: &[f64], index: usize) -> Vec<f64> {
+        let len = ratios.len();
+        if len <= 1 || index >= len {
+            return vec![1.0; len];
+        }
+        match self {
+            SplitCommand::SetRatio(ratio) => {
+                let others = (len - 1) as f64;
+                let ratio =
+                    ratio.clamp(MIN_SPLIT_RATIO, 1.0 - MIN_SPLIT_RATIO * others);
+                let others_total: f64 = ratios
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != index)
+                    .map(|(_, r)| r.max(0.0))
+                    .sum();
+                // every other pane keeps at least the minimum, and the space
+                // above that is shared out as the panes were before
+                let spare = 1.0 - ratio - MIN_SPLIT_RATIO * others;
+                ratios
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| {
+                        if i == index {
+                            ratio
+                        } else if others_total > 0.0 {
+                            MIN_SPLIT_RATIO + spare * r.max(0.0) / others_total
+                        } else {
+                            MIN_SPLIT_RATIO + spare / others
+                        }
+                    })
+                    .collect()
+            }
+            SplitCommand::Equalize => vec![1.0 / len as f64; len],
+            SplitCommand::MoveEditorToSplit { .. } => ratios.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SplitMoveDirection {
     Up,
     Down,
@@ -9,6 +68,37 @@ pub enum SplitMoveDirection {
     Left,
 }
 
+impl SplitMoveDirection {
+    /// The direction of the split a pane moved this way ends up in, and
+    /// whether it goes before the pane it was moved from
+    pub fn split_direction(self) -> (SplitDirection, bool) {
+        match self {
+            SplitMoveDirection::Up => (SplitDirection::Horizontal, true),
+            SplitMoveDirection::Down => (SplitDirection::Horizontal, false),
+            SplitMoveDirection::Right => (SplitDirection::Vertical, false),
+            SplitMoveDirection::Left => (SplitDirection::Vertical, true),
+        }
+    }
+
+    /// Whether `other` borders `rect` on this side
+    pub fn is_adjacent(self, rect: Rect, other: Rect) -> bool {
+        match self {
+            SplitMoveDirection::Up => {
+                other.y1 == rect.y0 && other.x0 <= rect.x0 && rect.x0 < other.x1
+            }
+            SplitMoveDirection::Down => {
+                other.y0 == rect.y1 && other.x0 <= rect.x0 && rect.x0 < other.x1
+            }
+            SplitMoveDirection::Right => {
+                other.x0 == rect.x1 && other.y0 <= rect.y0 && rect.y0 < other.y1
+            }
+            SplitMoveDirection::Left => {
+                other.x1 == rect.x0 && other.y0 <= rect.y0 && rect.y0 < other.y1
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SplitDirection {
     Vertical,
@@ -37,3 +127,89 @@ pub fn start(self, rect: Rect) -> f64 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ratios(ratios: &[f64], expected: &[f64]) {
+        assert_eq!(ratios.len(), expected.len());
+        for (ratio, expected) in ratios.iter().zip(expected) {
+            assert!(
+                (ratio - expected).abs() < 1e-9,
+                "{ratios:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_equalize() {
+        let ratios = SplitCommand::Equalize.apply(&[0.6, 0.3, 0.1], 2);
+        assert_ratios(&ratios, &[1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn test_set_ratio() {
+        let ratios = SplitCommand::SetRatio(0.7).apply(&[0.5, 0.5], 0);
+        assert_ratios(&ratios, &[0.7, 0.3]);
+
+        // the others keep their proportions above the minimum
+        let ratios = SplitCommand::SetRatio(0.5).apply(&[0.2, 0.2, 0.6], 2);
+        assert_ratios(&ratios, &[0.25, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn test_set_ratio_clamped() {
+        let ratios = SplitCommand::SetRatio(1.0).apply(&[0.5, 0.5], 1);
+        assert_ratios(&ratios, &[MIN_SPLIT_RATIO, 1.0 - MIN_SPLIT_RATIO]);
+
+        let ratios = SplitCommand::SetRatio(0.0).apply(&[0.5, 0.5], 1);
+        assert_ratios(&ratios, &[1.0 - MIN_SPLIT_RATIO, MIN_SPLIT_RATIO]);
+
+        let ratios = SplitCommand::SetRatio(0.95).apply(&[0.1, 0.1, 0.8], 2);
+        assert_ratios(&ratios, &[MIN_SPLIT_RATIO, MIN_SPLIT_RATIO, 0.9]);
+    }
+
+    #[test]
+    fn test_is_adjacent() {
+        // a 2x2 grid of panes, 100 wide and high each
+        let top_left = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let top_right = Rect::new(100.0, 0.0, 200.0, 100.0);
+        let bottom_left = Rect::new(0.0, 100.0, 100.0, 200.0);
+        let bottom_right = Rect::new(100.0, 100.0, 200.0, 200.0);
+
+        assert!(SplitMoveDirection::Right.is_adjacent(top_left, top_right));
+        assert!(SplitMoveDirection::Left.is_adjacent(top_right, top_left));
+        assert!(SplitMoveDirection::Down.is_adjacent(top_left, bottom_left));
+        assert!(SplitMoveDirection::Up.is_adjacent(bottom_right, top_right));
+
+        assert!(!SplitMoveDirection::Left.is_adjacent(top_left, top_right));
+        assert!(!SplitMoveDirection::Right.is_adjacent(top_left, bottom_right));
+        assert!(!SplitMoveDirection::Down.is_adjacent(top_left, bottom_right));
+
+        // a pane spanning the whole bottom borders both top panes
+        let bottom = Rect::new(0.0, 100.0, 200.0, 200.0);
+        assert!(SplitMoveDirection::Down.is_adjacent(top_right, bottom));
+        assert!(SplitMoveDirection::Up.is_adjacent(bottom, top_left));
+    }
+
+    #[test]
+    fn test_move_split_direction() {
+        assert_eq!(
+            SplitMoveDirection::Left.split_direction(),
+            (SplitDirection::Vertical, true)
+        );
+        assert_eq!(
+            SplitMoveDirection::Right.split_direction(),
+            (SplitDirection::Vertical, false)
+        );
+        assert_eq!(
+            SplitMoveDirection::Up.split_direction(),
+            (SplitDirection::Horizontal, true)
+        );
+        assert_eq!(
+            SplitMoveDirection::Down.split_direction(),
+            (SplitDirection::Horizontal, false)
+        );
+    }
+}
@@ -1,21 +1,38 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use druid::piet::Svg;
+use druid::{piet::Svg, Color};
 use include_dir::{include_dir, Dir};
+use once_cell::sync::Lazy;
+use regex::Regex;
 
 use crate::config::LOGO;
 
 const CODICONS_ICONS_DIR: Dir = include_dir!("../icons/codicons");
 const LAPCE_ICONS_DIR: Dir = include_dir!("../icons/lapce");
 
+/// The colors an svg paints with, in its attributes or its styles
+static SVG_COLOR_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:fill|stroke|stop-color)\s*[=:]\s*["']?\s*([^"';\s]+)"#).unwrap()
+});
+
+/// An icon read from the disk, with whether it's painted in a single color,
+/// which can then be replaced by the icon color of the theme
+#[derive(Clone)]
+struct SvgOnDisk {
+    svg: Svg,
+    monochrome: bool,
+}
+
 pub struct SvgStore {
     svgs: HashMap<String, Svg>,
-    svgs_on_disk: HashMap<PathBuf, Option<Svg>>,
+    svgs_on_disk: HashMap<PathBuf, Option<SvgOnDisk>>,
+    /// The color and icon themes the icons on disk were read for
+    theme: Option<(String, String)>,
 }
 
 impl Default for SvgStore {
@@ -32,6 +49,7 @@ fn new() -> Self {
         Self {
             svgs,
             svgs_on_disk: HashMap::new(),
+            theme: None,
         }
     }
 
@@ -56,13 +74,124 @@ pub fn get_default_svg(&mut self, name: &str) -> Svg {
     }
 
     pub fn get_svg_on_disk(&mut self, path: &Path) -> Option<Svg> {
+        self.svg_on_disk(path).map(|svg| svg.svg)
+    }
+
+    /// The icon on disk with the color to paint it in, which is the given one
+    /// for the icons painted in a single color, and `None` for the others so
+    /// that they keep their own colors
+    pub fn tinted(
+        &mut self,
+        path: &Path,
+        color: &Color,
+    ) -> Option<(Svg, Option<Color>)> {
+        let SvgOnDisk { svg, monochrome } = self.svg_on_disk(path)?;
+        Some((svg, monochrome.then(|| color.clone())))
+    }
+
+    /// Forget the icons read from the disk when the theme changes, so that the
+    /// icons of the new theme are read again
+    pub fn set_theme(&mut self, color_theme: &str, icon_theme: &str) {
+        let theme = (color_theme.to_string(), icon_theme.to_string());
+        if self.theme.as_ref() != Some(&theme) {
+            self.svgs_on_disk.clear();
+            self.theme = Some(theme);
+        }
+    }
+
+    fn svg_on_disk(&mut self, path: &Path) -> Option<SvgOnDisk> {
         if !self.svgs_on_disk.contains_key(path) {
-            let svg = fs::read_to_string(path)
-                .ok()
-                .and_then(|content| Svg::from_str(&content).ok());
+            let svg = fs::read_to_string(path).ok().and_then(|content| {
+                let svg = Svg::from_str(&content).ok()?;
+                Some(SvgOnDisk {
+                    svg,
+                    monochrome: is_monochrome(&content),
+                })
+            });
             self.svgs_on_disk.insert(path.to_path_buf(), svg);
         }
 
         self.svgs_on_disk.get(path).unwrap().clone()
     }
 }
+
+/// Whether the svg paints with a single color at most, not counting `none` and
+/// `currentColor`. The gradients and patterns aren't single colors.
+fn is_monochrome(content: &str) -> bool {
+    let mut colors = HashSet::new();
+    for captures in SVG_COLOR_REGEX.captures_iter(content) {
+        let color = captures[1].to_lowercase();
+        if color.starts_with("url(") {
+            return false;
+        }
+        if color != "none" && color != "currentcolor" {
+            colors.insert(color);
+        }
+    }
+    colors.len() <= 1
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use druid::Color;
+
+    use super::{is_monochrome, SvgStore};
+
+    const MONOCHROME: &str = concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16">"#,
+        r##"<path fill="#424242" d="M1 1h14v14H1z"/></svg>"##,
+    );
+    const COLORED: &str = concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16">"#,
+        r##"<path fill="#e44d26" d="M1 1h7v7H1z"/>"##,
+        r##"<path style="fill:#ffffff" d="M8 8h7v7H8z"/></svg>"##,
+    );
+
+    #[test]
+    fn test_monochrome() {
+        assert!(is_monochrome(MONOCHROME));
+        assert!(!is_monochrome(COLORED));
+        assert!(is_monochrome(
+            r#"<path fill="none" stroke="currentColor" d="M1 1"/>"#
+        ));
+        assert!(!is_monochrome(r##"<path fill="url(#a)" d="M1 1"/>"##));
+    }
+
+    #[test]
+    fn test_svg_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("lapce-svg-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rust.svg");
+        fs::write(&path, MONOCHROME).unwrap();
+
+        let color = Color::rgb8(0x12, 0x34, 0x56);
+        let tint = |store: &mut SvgStore| {
+            let (_, tint) = store.tinted(&path, &color).unwrap();
+            tint.map(|color| color.as_rgba_u32())
+        };
+        let mut store = SvgStore::default();
+        store.set_theme("Lapce Dark", "Lapce Codicons");
+        assert_eq!(tint(&mut store), Some(color.as_rgba_u32()));
+
+        // The icon read the first time is returned again, even once the file
+        // has changed
+        fs::write(&path, COLORED).unwrap();
+        assert_eq!(tint(&mut store), Some(color.as_rgba_u32()));
+        store.set_theme("Lapce Dark", "Lapce Codicons");
+        assert_eq!(tint(&mut store), Some(color.as_rgba_u32()));
+
+        // until the theme changes
+        store.set_theme("Lapce Light", "Lapce Codicons");
+        assert_eq!(tint(&mut store), None);
+
+        fs::write(&path, MONOCHROME).unwrap();
+        store.set_theme("Lapce Light", "Material Icons");
+        assert_eq!(tint(&mut store), Some(color.as_rgba_u32()));
+
+        assert!(store.tinted(&dir.join("missing.svg"), &color).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{cell::RefCell, path::PathBuf, rc::Rc, sync::Arc};
 
 use alacritty_terminal::{
     ansi,
@@ -12,7 +12,7 @@
 };
 use druid::{
     keyboard_types::Key, Color, Command, Env, EventCtx, ExtEventSink, KeyEvent,
-    Modifiers, Target, WidgetId,
+    Modifiers, Rect, Target, WidgetId,
 };
 use hashbrown::HashMap;
 use lapce_core::{
@@ -23,6 +23,7 @@
 };
 use lapce_rpc::terminal::TermId;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     command::{
@@ -30,11 +31,14 @@
     },
     config::{LapceConfig, LapceTheme},
     data::LapceWorkspace,
+    db::{TerminalPanelInfo, TerminalTabInfo},
     document::SystemClipboard,
     find::Find,
     keypress::KeyPressFocus,
     proxy::LapceProxy,
     split::SplitMoveDirection,
+    terminal_layout::TerminalLayout,
+    terminal_link::TerminalLinkTarget,
 };
 
 pub type TermConfig = alacritty_terminal::config::Config;
@@ -92,6 +96,18 @@ pub fn active_terminal_split_mut(&mut self) -> Option<&mut TerminalSplitData> {
             .and_then(|id| self.tabs.get_mut(id))
     }
 
+    /// The tab the terminal with the widget id is in, along with the id of the
+    /// terminal and the split of the tab it's laid out in
+    pub fn locate_terminal(
+        &self,
+        widget_id: WidgetId,
+    ) -> Option<(WidgetId, TermId, WidgetId)> {
+        self.tabs.values().find_map(|tab| {
+            let (term_id, split_id) = tab.parent_split(widget_id)?;
+            Some((tab.split_id, term_id, split_id))
+        })
+    }
+
     pub fn new_tab(
         &mut self,
         workspace: Arc<LapceWorkspace>,
@@ -107,6 +123,42 @@ pub fn new_tab(
         self.tabs.insert(new_term_tab_id, new_term_split);
         self.active = active_index;
     }
+
+    /// The sessions of the terminals, to open them again when the workspace is
+    /// reopened
+    pub fn panel_info(&self) -> TerminalPanelInfo {
+        TerminalPanelInfo {
+            tabs: self
+                .tabs_order
+                .iter()
+                .filter_map(|id| self.tabs.get(id))
+                .map(|split| split.tab_info())
+                .collect(),
+            active: self.active,
+        }
+    }
+}
+
+/// The state of a terminal that is kept across restarts
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerminalSession {
+    pub cwd: Option<PathBuf>,
+    pub shell: String,
+    pub title: String,
+}
+
+impl TerminalSession {
+    /// The directory to start the terminal in. If the saved directory doesn't exist
+    /// anymore, the terminal is started in the workspace root instead.
+    pub fn cwd(&self, workspace: &LapceWorkspace) -> Option<PathBuf> {
+        match &self.cwd {
+            // The directories of remote workspaces can't be checked from here
+            Some(cwd) if workspace.kind.is_remote() || cwd.is_dir() => {
+                Some(cwd.clone())
+            }
+            _ => workspace.path.clone(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -115,6 +167,11 @@ pub struct TerminalSplitData {
     pub active_term_id: TermId,
     pub split_id: WidgetId,
     pub terminals: im::HashMap<TermId, Arc<LapceTerminalData>>,
+    /// How the terminals are laid out in the split of the tab
+    pub layout: Arc<TerminalLayout>,
+    /// The name the user gave the tab, which is shown instead of the title of
+    /// the active terminal
+    pub name: Option<String>,
     pub indexed_colors: Arc<HashMap<u8, Color>>,
 }
 
@@ -139,7 +196,69 @@ pub fn new(
             active: widget_id,
             split_id,
             terminals,
+            layout: Arc::new(TerminalLayout::new(split_id, &[term_id])),
+            name: None,
+            indexed_colors: Arc::new(Self::get_indexed_colors()),
+        }
+    }
+
+    /// Open the terminals of a tab that was saved in a previous run, returns `None`
+    /// if the tab had no terminals.
+    pub fn from_tab_info(
+        info: &TerminalTabInfo,
+        workspace: &LapceWorkspace,
+        proxy: Arc<LapceProxy>,
+        config: &LapceConfig,
+        event_sink: ExtEventSink,
+    ) -> Option<Self> {
+        let split_id = WidgetId::next();
+        let mut terminals = im::HashMap::new();
+        let mut terminals_order = Vec::new();
+        for session in &info.terminals {
+            let session = TerminalSession {
+                cwd: session.cwd(workspace),
+                ..session.clone()
+            };
+            let terminal_data = Arc::new(LapceTerminalData::with_session(
+                &session,
+                split_id,
+                event_sink.clone(),
+                proxy.clone(),
+                config,
+            ));
+            terminals_order.push(terminal_data.term_id);
+            terminals.insert(terminal_data.term_id, terminal_data);
+        }
+
+        let active_term_id = terminals_order
+            .get(info.active)
+            .or_else(|| terminals_order.first())
+            .copied()?;
+        let layout = match &info.layout {
+            Some(layout) => layout.to_layout(split_id, &terminals_order),
+            None => TerminalLayout::new(split_id, &terminals_order),
+        };
+        Some(Self {
+            active_term_id,
+            active: terminals.get(&active_term_id)?.widget_id,
+            split_id,
+            terminals,
+            layout: Arc::new(layout),
+            name: info.name.clone(),
             indexed_colors: Arc::new(Self::get_indexed_colors()),
+        })
+    }
+
+    fn tab_info(&self) -> TerminalTabInfo {
+        let terminals: Vec<_> = self.ordered_terminals().collect();
+        TerminalTabInfo {
+            active: terminals
+                .iter()
+                .position(|t| t.term_id == self.active_term_id)
+                .unwrap_or(0),
+            terminals: terminals.iter().map(|t| t.session()).collect(),
+            layout: Some(self.layout.info()),
+            name: self.name.clone(),
         }
     }
 
@@ -147,6 +266,76 @@ pub fn active_terminal(&self) -> Option<&Arc<LapceTerminalData>> {
         self.terminals.get(&self.active_term_id)
     }
 
+    /// The name of the tab, or the title of its active terminal if the user
+    /// didn't name it
+    pub fn title(&self) -> Option<&str> {
+        match &self.name {
+            Some(name) => Some(name),
+            None => self.active_terminal().map(|t| t.title.as_str()),
+        }
+    }
+
+    /// Remove the terminal from the tab, returns the widget of the terminal
+    /// laid out after it, or before it if it was the last one, to focus next
+    pub fn remove_terminal(&mut self, term_id: TermId) -> Option<WidgetId> {
+        let mut order = self.layout.terminals();
+        let next = match order.iter().position(|id| id == &term_id) {
+            Some(index) => {
+                order.remove(index);
+                order.get(index.min(order.len().saturating_sub(1)))
+            }
+            None => None,
+        }
+        .and_then(|id| self.terminals.get(id))
+        .map(|t| t.widget_id);
+        self.terminals.remove(&term_id);
+        Arc::make_mut(&mut self.layout).remove(term_id);
+        next
+    }
+
+    /// The terminal next to the terminal in the direction, which is found by
+    /// the rects the terminals are laid out in like the editor tab to focus
+    pub fn neighbour(
+        &self,
+        term_id: TermId,
+        direction: SplitMoveDirection,
+    ) -> Option<&Arc<LapceTerminalData>> {
+        let rect = *self.terminals.get(&term_id)?.layout_rect.borrow();
+        self.terminals.values().find(|t| {
+            t.term_id != term_id
+                && direction.is_adjacent(rect, *t.layout_rect.borrow())
+        })
+    }
+
+    /// The split of the tab the terminal with the widget id is laid out in,
+    /// which is nested in the split of the tab once it was split the other way
+    pub fn parent_split(&self, widget_id: WidgetId) -> Option<(TermId, WidgetId)> {
+        let terminal = self.terminals.values().find(|t| t.widget_id == widget_id)?;
+        let (split_id, _) = self.layout.parent(terminal.term_id)?;
+        Some((terminal.term_id, split_id))
+    }
+
+    /// The split and the widget to take out of it when the terminal is closed,
+    /// see [`TerminalLayout::close_target`]
+    pub fn close_target(&self, term_id: TermId) -> Option<(WidgetId, WidgetId)> {
+        let (split_id, index) = self.layout.close_target(term_id)?;
+        let widget_id = match self.layout.children(split_id)?.get(index)? {
+            TerminalLayout::Terminal(id) => self.terminals.get(id)?.widget_id,
+            TerminalLayout::Split { widget_id, .. } => *widget_id,
+        };
+        Some((split_id, widget_id))
+    }
+
+    /// The terminals in the order they are laid out in the split
+    pub fn ordered_terminals(
+        &self,
+    ) -> impl Iterator<Item = &Arc<LapceTerminalData>> + '_ {
+        self.layout
+            .terminals()
+            .into_iter()
+            .filter_map(|id| self.terminals.get(&id))
+    }
+
     pub fn get_indexed_colors() -> HashMap<u8, Color> {
         let mut indexed_colors = HashMap::new();
         // Build colors.
@@ -498,28 +687,30 @@ fn run_command(
                         scroll_lines,
                     ));
                 }
-                FocusCommand::SplitVertical => {
-                    ctx.submit_command(Command::new(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::SplitTerminal(true, self.terminal.widget_id),
-                        Target::Widget(self.terminal.split_id),
-                    ));
-                }
-                FocusCommand::SplitLeft => {
+                FocusCommand::SplitVertical | FocusCommand::SplitHorizontal => {
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
-                        LapceUICommand::SplitEditorMove(
-                            SplitMoveDirection::Left,
+                        LapceUICommand::SplitTerminal(
+                            matches!(cmd, FocusCommand::SplitVertical),
                             self.terminal.widget_id,
                         ),
                         Target::Widget(self.terminal.split_id),
                     ));
                 }
-                FocusCommand::SplitRight => {
+                FocusCommand::SplitLeft
+                | FocusCommand::SplitRight
+                | FocusCommand::SplitUp
+                | FocusCommand::SplitDown => {
+                    let direction = match cmd {
+                        FocusCommand::SplitLeft => SplitMoveDirection::Left,
+                        FocusCommand::SplitRight => SplitMoveDirection::Right,
+                        FocusCommand::SplitUp => SplitMoveDirection::Up,
+                        _ => SplitMoveDirection::Down,
+                    };
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
-                        LapceUICommand::SplitEditorMove(
-                            SplitMoveDirection::Right,
+                        LapceUICommand::SplitTerminalMove(
+                            direction,
                             self.terminal.widget_id,
                         ),
                         Target::Widget(self.terminal.split_id),
@@ -528,7 +719,9 @@ fn run_command(
                 FocusCommand::SplitExchange => {
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
-                        LapceUICommand::SplitEditorExchange(self.terminal.widget_id),
+                        LapceUICommand::SplitTerminalExchange(
+                            self.terminal.widget_id,
+                        ),
                         Target::Widget(self.terminal.split_id),
                     ));
                 }
@@ -576,14 +769,149 @@ pub struct RawTerminal {
     pub parser: ansi::Processor,
     pub term: Term<EventProxy>,
     pub scroll_delta: f64,
+    cwd_reports: CwdReports,
+    /// The directory the shell last reported to be in
+    pub cwd: Option<PathBuf>,
 }
 
 impl RawTerminal {
     pub fn update_content(&mut self, content: Vec<u8>) {
         for byte in content {
+            if let Some(cwd) = self.cwd_reports.advance(byte) {
+                self.cwd = Some(cwd);
+            }
             self.parser.advance(&mut self.term, byte);
         }
     }
+
+    /// Drop the lines that scrolled out of view, keeping what is on screen
+    pub fn clear_scrollback(&mut self) {
+        clear_scrollback(&mut self.term);
+    }
+}
+
+fn clear_scrollback<T>(term: &mut Term<T>) {
+    term.grid_mut().clear_history();
+}
+
+/// The longest OSC sequence that is looked at, the longer ones aren't
+/// directories
+const MAX_OSC_LEN: usize = 4096;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum CwdReportState {
+    #[default]
+    Ground,
+    Escape,
+    Osc,
+    /// An escape in an OSC, which ends it when followed by a backslash
+    OscEscape,
+}
+
+/// Picks the directories the shell reports to be in out of its output, which
+/// alacritty ignores. They are sent as `ESC ] 7 ; file://host/path`, ended by
+/// a BEL or by `ESC \`.
+#[derive(Default)]
+struct CwdReports {
+    state: CwdReportState,
+    osc: Vec<u8>,
+}
+
+impl CwdReports {
+    /// The directory once the byte ends a report of one
+    fn advance(&mut self, byte: u8) -> Option<PathBuf> {
+        use CwdReportState::*;
+        match (self.state, byte) {
+            (Osc, 0x07) | (OscEscape, b'\\') => {
+                self.state = Ground;
+                return osc_cwd(&std::mem::take(&mut self.osc));
+            }
+            (Osc, 0x1b) => self.state = OscEscape,
+            (Osc, _) if self.osc.len() < MAX_OSC_LEN => self.osc.push(byte),
+            (Osc, _) => self.state = Ground,
+            (Escape, b']') => {
+                self.osc.clear();
+                self.state = Osc;
+            }
+            (_, 0x1b) => self.state = Escape,
+            _ => self.state = Ground,
+        }
+        None
+    }
+}
+
+/// The directory of an OSC 7 sequence, from its `file://` URL. The host is
+/// skipped as the directory is on the one the shell runs on.
+fn osc_cwd(osc: &[u8]) -> Option<PathBuf> {
+    let url = std::str::from_utf8(osc).ok()?.strip_prefix("7;")?;
+    let url = url.strip_prefix("file://")?;
+    let path = &url[url.find('/')?..];
+    let path = percent_decode(path)?;
+    // Like `/C:/Users`, on Windows
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &path[1..],
+        _ => path.as_str(),
+    };
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' && after.len() >= 2 {
+            let hex = std::str::from_utf8(&after[..2]).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The input to write to a terminal to run the `text`, with its lines ended by
+/// carriage returns like the enter key does. Multiple lines are pasted as a
+/// whole when the shell supports bracketed paste, so that it doesn't run each
+/// of them as they come in. `None` when it doesn't, unless `line_by_line`,
+/// since each line would then be run.
+fn run_text_input(
+    text: &str,
+    bracketed_paste: bool,
+    line_by_line: bool,
+) -> Option<String> {
+    let text = text.trim_end_matches(['\r', '\n']);
+    let lines = text.lines().collect::<Vec<_>>();
+    if lines.len() > 1 && !line_by_line {
+        if !bracketed_paste {
+            return None;
+        }
+        // The end of the paste can't be faked by the text
+        let text = lines.join("\r").replace('\x1b', "");
+        Some(format!("\x1b[200~{text}\x1b[201~\r"))
+    } else {
+        let mut input = lines.join("\r");
+        input.push('\r');
+        Some(input)
+    }
+}
+
+/// The terminal config that keeps `scrollback_lines` lines of history, where 0 is
+/// unbounded
+fn term_config(scrollback_lines: usize) -> TermConfig {
+    let history = if scrollback_lines == 0 {
+        log::warn!(
+            "terminal scrollback is unbounded, its memory usage will keep growing"
+        );
+        u32::MAX
+    } else {
+        u32::try_from(scrollback_lines).unwrap_or(u32::MAX)
+    };
+
+    let mut config = TermConfig::default();
+    config.scrolling.set_history(history);
+    config
 }
 
 impl RawTerminal {
@@ -591,8 +919,9 @@ pub fn new(
         term_id: TermId,
         proxy: Arc<LapceProxy>,
         event_sink: ExtEventSink,
+        scrollback_lines: usize,
     ) -> Self {
-        let config = TermConfig::default();
+        let config = term_config(scrollback_lines);
         let event_proxy = EventProxy {
             proxy,
             event_sink,
@@ -607,6 +936,8 @@ pub fn new(
             parser,
             term,
             scroll_delta: 0.0,
+            cwd_reports: CwdReports::default(),
+            cwd: None,
         }
     }
 }
@@ -618,10 +949,15 @@ pub struct LapceTerminalData {
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
     pub title: String,
+    /// The directory the terminal was started in
+    pub cwd: Option<PathBuf>,
+    pub shell: String,
     pub mode: Mode,
     pub visual_mode: VisualMode,
     pub raw: Arc<Mutex<RawTerminal>>,
     pub proxy: Arc<LapceProxy>,
+    /// The rect of the terminal in the window
+    pub layout_rect: Rc<RefCell<Rect>>,
 }
 
 impl LapceTerminalData {
@@ -632,7 +968,31 @@ pub fn new(
         proxy: Arc<LapceProxy>,
         config: &LapceConfig,
     ) -> Self {
-        let cwd = workspace.path.as_ref().cloned();
+        let shell = config.terminal.shell.clone();
+
+        // TODO: replace with profile name, once we implement terminal profiles
+        let title = if !shell.is_empty() {
+            shell.clone()
+        } else {
+            String::from("(no title)")
+        };
+
+        let session = TerminalSession {
+            cwd: workspace.path.as_ref().cloned(),
+            shell,
+            title,
+        };
+        Self::with_session(&session, split_id, event_sink, proxy, config)
+    }
+
+    /// Start a terminal with the directory, shell and title of the `session`
+    pub fn with_session(
+        session: &TerminalSession,
+        split_id: WidgetId,
+        event_sink: ExtEventSink,
+        proxy: Arc<LapceProxy>,
+        config: &LapceConfig,
+    ) -> Self {
         let widget_id = WidgetId::next();
         let view_id = WidgetId::next();
         let term_id = TermId::next();
@@ -640,18 +1000,13 @@ pub fn new(
             term_id,
             proxy.clone(),
             event_sink,
+            config.terminal.scrollback_lines,
         )));
 
         let local_proxy = proxy.clone();
         let local_raw = raw.clone();
-        let shell = config.terminal.shell.clone();
-
-        // TODO: replace with profile name, once we implement terminal profiles
-        let title = if !shell.is_empty() {
-            shell.clone()
-        } else {
-            String::from("(no title)")
-        };
+        let cwd = session.cwd.clone();
+        let shell = session.shell.clone();
 
         std::thread::spawn(move || {
             local_proxy.new_terminal(term_id, cwd, shell, local_raw);
@@ -662,11 +1017,57 @@ pub fn new(
             widget_id,
             view_id,
             split_id,
-            title,
+            title: session.title.clone(),
+            cwd: session.cwd.clone(),
+            shell: session.shell.clone(),
             mode: Mode::Terminal,
             visual_mode: VisualMode::Normal,
             raw,
             proxy,
+            layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
+        }
+    }
+
+    /// The directory the shell reported to be in, or else the one it was
+    /// started in
+    pub fn current_dir(&self) -> Option<PathBuf> {
+        self.raw.lock().cwd.clone().or_else(|| self.cwd.clone())
+    }
+
+    pub fn session(&self) -> TerminalSession {
+        TerminalSession {
+            cwd: self.current_dir(),
+            shell: self.shell.clone(),
+            title: self.title.clone(),
+        }
+    }
+
+    /// The command opening a link clicked in the output, with the relative
+    /// paths resolved against the directory the shell is in
+    pub fn link_command(
+        &self,
+        target: &TerminalLinkTarget,
+        workspace: &LapceWorkspace,
+    ) -> LapceUICommand {
+        let cwd = self.current_dir().or_else(|| workspace.path.clone());
+        target.open_command(cwd.as_deref(), workspace.home_dir())
+    }
+
+    /// Run the text in the terminal, as if it was pasted and followed by
+    /// enter. `false` when nothing was written since the shell would run each
+    /// of its lines, which wasn't asked with `line_by_line`.
+    pub fn run_text(&self, text: &str, line_by_line: bool) -> bool {
+        let bracketed_paste = {
+            let mut raw = self.raw.lock();
+            raw.term.scroll_display(Scroll::Bottom);
+            raw.term.mode().contains(TermMode::BRACKETED_PASTE)
+        };
+        match run_text_input(text, bracketed_paste, line_by_line) {
+            Some(input) => {
+                self.proxy.proxy_rpc.terminal_write(self.term_id, &input);
+                true
+            }
+            None => false,
         }
     }
 
@@ -952,9 +1353,119 @@ fn send_event(&self, event: alacritty_terminal::event::Event) {
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
+    use alacritty_terminal::{
+        ansi,
+        event::VoidListener,
+        grid::Dimensions,
+        index::{Column, Line},
+        term::test::TermSize,
+        Term,
+    };
     use druid::{KbKey, KeyEvent, Modifiers};
 
-    use crate::terminal::LapceTerminalData;
+    use crate::{
+        data::{LapceWorkspace, LapceWorkspaceType},
+        terminal::{
+            clear_scrollback, run_text_input, term_config, CwdReports,
+            LapceTerminalData, TerminalSession,
+        },
+    };
+
+    fn cwd_reports(output: &[&str]) -> Vec<PathBuf> {
+        let mut reports = CwdReports::default();
+        output
+            .iter()
+            .flat_map(|chunk| chunk.bytes())
+            .filter_map(|byte| reports.advance(byte))
+            .collect()
+    }
+
+    #[test]
+    fn test_cwd_reports() {
+        assert_eq!(
+            cwd_reports(&["$ cd src\r\n\x1b]7;file://host/home/jane/src\x07$ "]),
+            vec![PathBuf::from("/home/jane/src")]
+        );
+        // Split across the chunks of output, and ended by `ESC \`
+        assert_eq!(
+            cwd_reports(&[
+                "\x1b]7;file:///home/jane/my%20",
+                "dir\x1b\\\x1b]0;title\x07",
+            ]),
+            vec![PathBuf::from("/home/jane/my dir")]
+        );
+        // The other sequences aren't directories
+        assert!(
+            cwd_reports(&["\x1b]0;/home/jane\x07\x1b[1m\x1b]8;;\x07"]).is_empty()
+        );
+        assert_eq!(
+            cwd_reports(&["\x1b]7;file://pc/C:/Users/jane\x07"]),
+            vec![PathBuf::from("C:/Users/jane")]
+        );
+    }
+
+    fn session(cwd: Option<PathBuf>) -> TerminalSession {
+        TerminalSession {
+            cwd,
+            shell: String::new(),
+            title: "(no title)".to_string(),
+        }
+    }
+
+    #[test]
+    fn session_cwd_falls_back_to_workspace_root() {
+        let existing = std::env::temp_dir();
+        let missing = existing.join("lapce-terminal-session-missing-dir");
+        let root = PathBuf::from("/workspace/root");
+        let workspace = LapceWorkspace {
+            kind: LapceWorkspaceType::Local,
+            path: Some(root.clone()),
+            last_open: 0,
+        };
+
+        assert_eq!(
+            session(Some(existing.clone())).cwd(&workspace),
+            Some(existing)
+        );
+        assert_eq!(
+            session(Some(missing.clone())).cwd(&workspace),
+            Some(root.clone())
+        );
+        assert_eq!(session(None).cwd(&workspace), Some(root));
+
+        // Directories of remote workspaces are kept as they are
+        let remote = LapceWorkspace {
+            kind: LapceWorkspaceType::RemoteWSL,
+            ..workspace
+        };
+        assert_eq!(session(Some(missing.clone())).cwd(&remote), Some(missing));
+    }
+
+    #[test]
+    fn scrollback_is_bounded_and_can_be_cleared() {
+        let mut term =
+            Term::new(&term_config(100), &TermSize::new(10, 5), VoidListener);
+        let mut parser = ansi::Processor::new();
+        let mut print = |term: &mut Term<VoidListener>, lines: usize| {
+            for byte in "line\r\n".repeat(lines).bytes() {
+                parser.advance(term, byte);
+            }
+        };
+
+        print(&mut term, 300);
+        assert_eq!(term.grid().history_size(), 100);
+
+        // Resizing keeps the configured limit
+        term.resize(TermSize::new(20, 8));
+        print(&mut term, 300);
+        assert_eq!(term.grid().history_size(), 100);
+
+        clear_scrollback(&mut term);
+        assert_eq!(term.grid().history_size(), 0);
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, 'l');
+    }
 
     #[test]
     fn test_arrow_without_modifier() {
@@ -988,4 +1499,38 @@ fn test_arrow_with_two_modifiers() {
             ))
         );
     }
+
+    #[test]
+    fn test_run_text_input() {
+        // A single line is run as it is, with a trailing new line replaced
+        assert_eq!(
+            run_text_input("cargo test\n", true, false).as_deref(),
+            Some("cargo test\r")
+        );
+        assert_eq!(run_text_input("ls", false, false).as_deref(), Some("ls\r"));
+
+        // Multiple lines are pasted as a whole, with their line endings as enter
+        let text = "for i in 1 2; do\r\n  echo $i\ndone\n";
+        assert_eq!(
+            run_text_input(text, true, false).as_deref(),
+            Some("\x1b[200~for i in 1 2; do\r  echo $i\rdone\x1b[201~\r")
+        );
+        // unless each of them is to be run
+        assert_eq!(
+            run_text_input(text, true, true).as_deref(),
+            Some("for i in 1 2; do\r  echo $i\rdone\r")
+        );
+        assert_eq!(
+            run_text_input(text, false, true).as_deref(),
+            Some("for i in 1 2; do\r  echo $i\rdone\r")
+        );
+        // and nothing is run when the shell doesn't support bracketed paste
+        assert_eq!(run_text_input(text, false, false), None);
+
+        // The text can't end the paste early
+        assert_eq!(
+            run_text_input("a\n\x1b[201~rm -rf", true, false).as_deref(),
+            Some("\x1b[200~a\r[201~rm -rf\x1b[201~\r")
+        );
+    }
 }
@@ -0,0 +1,470 @@
+use druid::WidgetId;
+use lapce_rpc::terminal::TermId;
+use serde::{Deserialize, Serialize};
+
+use crate::split::SplitDirection;
+
+/// How the terminals of a tab are laid out, which is a tree of splits like the
+/// one of the editors, with the terminals as its leaves. The root is the split
+/// of the tab itself, so it has the id of the tab and is never removed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TerminalLayout {
+    Terminal(TermId),
+    Split {
+        widget_id: WidgetId,
+        direction: SplitDirection,
+        children: Vec<TerminalLayout>,
+    },
+}
+
+impl TerminalLayout {
+    /// The layout of a tab with the terminals side by side
+    pub fn new(split_id: WidgetId, terminals: &[TermId]) -> Self {
+        TerminalLayout::Split {
+            widget_id: split_id,
+            direction: SplitDirection::Vertical,
+            children: terminals
+                .iter()
+                .map(|term_id| TerminalLayout::Terminal(*term_id))
+                .collect(),
+        }
+    }
+
+    /// The terminals in the order they are laid out in, the ones of the first
+    /// child of a split before the ones of the next
+    pub fn terminals(&self) -> Vec<TermId> {
+        let mut terminals = Vec::new();
+        self.collect_terminals(&mut terminals);
+        terminals
+    }
+
+    fn collect_terminals(&self, terminals: &mut Vec<TermId>) {
+        match self {
+            TerminalLayout::Terminal(term_id) => terminals.push(*term_id),
+            TerminalLayout::Split { children, .. } => {
+                for child in children {
+                    child.collect_terminals(terminals);
+                }
+            }
+        }
+    }
+
+    /// The split the terminal is laid out in, with the index of the terminal
+    /// in it
+    pub fn parent(&self, term_id: TermId) -> Option<(WidgetId, usize)> {
+        let path = self.path(term_id)?;
+        path.last().map(|(split_id, index, _)| (*split_id, *index))
+    }
+
+    /// The splits from the root to the terminal, with the index of the child
+    /// leading to the terminal in each and the number of their children
+    fn path(&self, term_id: TermId) -> Option<Vec<(WidgetId, usize, usize)>> {
+        match self {
+            TerminalLayout::Terminal(id) => (*id == term_id).then(Vec::new),
+            TerminalLayout::Split {
+                widget_id,
+                children,
+                ..
+            } => children.iter().enumerate().find_map(|(i, child)| {
+                let mut path = child.path(term_id)?;
+                path.insert(0, (*widget_id, i, children.len()));
+                Some(path)
+            }),
+        }
+    }
+
+    /// The children of the split with the id
+    pub fn children(&self, split_id: WidgetId) -> Option<&[TerminalLayout]> {
+        match self {
+            TerminalLayout::Terminal(_) => None,
+            TerminalLayout::Split {
+                widget_id,
+                children,
+                ..
+            } => {
+                if *widget_id == split_id {
+                    return Some(children);
+                }
+                children.iter().find_map(|child| child.children(split_id))
+            }
+        }
+    }
+
+    fn parent_mut(&mut self, term_id: TermId) -> Option<&mut TerminalLayout> {
+        let is_parent = matches!(
+            self,
+            TerminalLayout::Split { children, .. }
+                if children.contains(&TerminalLayout::Terminal(term_id))
+        );
+        if is_parent {
+            return Some(self);
+        }
+        match self {
+            TerminalLayout::Terminal(_) => None,
+            TerminalLayout::Split { children, .. } => children
+                .iter_mut()
+                .find_map(|child| child.parent_mut(term_id)),
+        }
+    }
+
+    /// Split the new terminal off the terminal, after it in the direction. It
+    /// goes in the split of the terminal when the split goes the same way or
+    /// has no other child, otherwise both of them are put in a new split in
+    /// place of the terminal. Returns the split the new terminal is put in.
+    pub fn split(
+        &mut self,
+        term_id: TermId,
+        new_term_id: TermId,
+        direction: SplitDirection,
+    ) -> Option<WidgetId> {
+        let parent = self.parent_mut(term_id)?;
+        let (widget_id, split_direction, children) = match parent {
+            TerminalLayout::Split {
+                widget_id,
+                direction: split_direction,
+                children,
+            } => (*widget_id, split_direction, children),
+            TerminalLayout::Terminal(_) => return None,
+        };
+        let index = children
+            .iter()
+            .position(|child| child == &TerminalLayout::Terminal(term_id))?;
+
+        if *split_direction == direction || children.len() == 1 {
+            *split_direction = direction;
+            children.insert(index + 1, TerminalLayout::Terminal(new_term_id));
+            Some(widget_id)
+        } else {
+            let new_split_id = WidgetId::next();
+            children[index] = TerminalLayout::Split {
+                widget_id: new_split_id,
+                direction,
+                children: vec![
+                    TerminalLayout::Terminal(term_id),
+                    TerminalLayout::Terminal(new_term_id),
+                ],
+            };
+            Some(new_split_id)
+        }
+    }
+
+    /// The split and the index of the child of it to take out of the widgets
+    /// when the terminal is closed, which is the topmost split the terminal is
+    /// the only one in, or the terminal itself
+    pub fn close_target(&self, term_id: TermId) -> Option<(WidgetId, usize)> {
+        let path = self.path(term_id)?;
+        let mut target = path.len().checked_sub(1)?;
+        while target > 0 && path[target].2 == 1 {
+            target -= 1;
+        }
+        let (split_id, index, _) = path[target];
+        Some((split_id, index))
+    }
+
+    /// Remove the terminal, along with the splits it leaves empty but the
+    /// root, the way [`TerminalLayout::close_target`] takes them out of the
+    /// widgets. Returns whether the terminal was in the layout.
+    pub fn remove(&mut self, term_id: TermId) -> bool {
+        let children = match self {
+            TerminalLayout::Split { children, .. } => children,
+            TerminalLayout::Terminal(_) => return false,
+        };
+        if let Some(index) = children
+            .iter()
+            .position(|child| child == &TerminalLayout::Terminal(term_id))
+        {
+            children.remove(index);
+            return true;
+        }
+        for index in 0..children.len() {
+            if children[index].remove(term_id) {
+                if matches!(
+                    &children[index],
+                    TerminalLayout::Split { children, .. } if children.is_empty()
+                ) {
+                    children.remove(index);
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Swap the terminal with the child after it in its split, returns the
+    /// split and the index of the terminal before they were swapped
+    pub fn exchange(&mut self, term_id: TermId) -> Option<(WidgetId, usize)> {
+        let (widget_id, children) = match self.parent_mut(term_id)? {
+            TerminalLayout::Split {
+                widget_id,
+                children,
+                ..
+            } => (*widget_id, children),
+            TerminalLayout::Terminal(_) => return None,
+        };
+        let index = children
+            .iter()
+            .position(|child| child == &TerminalLayout::Terminal(term_id))?;
+        if index + 1 >= children.len() {
+            return None;
+        }
+        children.swap(index, index + 1);
+        Some((widget_id, index))
+    }
+
+    /// The layout as it's saved, see [`TerminalLayoutInfo`]
+    pub fn info(&self) -> TerminalLayoutInfo {
+        self.info_from(&mut 0)
+    }
+
+    fn info_from(&self, next: &mut usize) -> TerminalLayoutInfo {
+        match self {
+            TerminalLayout::Terminal(_) => {
+                *next += 1;
+                TerminalLayoutInfo::Terminal(*next - 1)
+            }
+            TerminalLayout::Split {
+                direction,
+                children,
+                ..
+            } => TerminalLayoutInfo::Split {
+                direction: *direction,
+                children: children
+                    .iter()
+                    .map(|child| child.info_from(next))
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// The layout of the terminals of a tab as it's saved, with the terminals
+/// given by their index in the order they are laid out in
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerminalLayoutInfo {
+    Terminal(usize),
+    Split {
+        direction: SplitDirection,
+        children: Vec<TerminalLayoutInfo>,
+    },
+}
+
+impl TerminalLayoutInfo {
+    /// The layout of the terminals opened again in the tab with the split id.
+    /// The splits left empty by the terminals that are missing are dropped,
+    /// and the terminals the layout doesn't have are added to the root.
+    pub fn to_layout(
+        &self,
+        split_id: WidgetId,
+        terminals: &[TermId],
+    ) -> TerminalLayout {
+        let (direction, children) = match self {
+            TerminalLayoutInfo::Split {
+                direction,
+                children,
+            } => (*direction, children.iter().collect()),
+            TerminalLayoutInfo::Terminal(_) => {
+                (SplitDirection::Vertical, vec![self])
+            }
+        };
+        let mut layout = TerminalLayout::Split {
+            widget_id: split_id,
+            direction,
+            children: children
+                .into_iter()
+                .filter_map(|child| child.to_child_layout(terminals))
+                .collect(),
+        };
+
+        let laid_out = layout.terminals();
+        if let TerminalLayout::Split { children, .. } = &mut layout {
+            children.extend(
+                terminals
+                    .iter()
+                    .filter(|term_id| !laid_out.contains(term_id))
+                    .map(|term_id| TerminalLayout::Terminal(*term_id)),
+            );
+        }
+        layout
+    }
+
+    fn to_child_layout(&self, terminals: &[TermId]) -> Option<TerminalLayout> {
+        match self {
+            TerminalLayoutInfo::Terminal(index) => terminals
+                .get(*index)
+                .map(|id| TerminalLayout::Terminal(*id)),
+            TerminalLayoutInfo::Split {
+                direction,
+                children,
+            } => {
+                let children: Vec<_> = children
+                    .iter()
+                    .filter_map(|child| child.to_child_layout(terminals))
+                    .collect();
+                (!children.is_empty()).then(|| TerminalLayout::Split {
+                    widget_id: WidgetId::next(),
+                    direction: *direction,
+                    children,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use druid::WidgetId;
+    use lapce_rpc::terminal::TermId;
+
+    use super::{TerminalLayout, TerminalLayoutInfo};
+    use crate::split::SplitDirection;
+
+    /// A tab split into a 2x2 grid of terminals, in the order top left, top
+    /// right, bottom left and bottom right
+    fn grid() -> (WidgetId, TerminalLayout, [TermId; 4]) {
+        let split_id = WidgetId::next();
+        let terms = [
+            TermId::next(),
+            TermId::next(),
+            TermId::next(),
+            TermId::next(),
+        ];
+        let mut layout = TerminalLayout::new(split_id, &terms[..1]);
+        assert_eq!(
+            layout.split(terms[0], terms[1], SplitDirection::Vertical),
+            Some(split_id)
+        );
+        let left = layout
+            .split(terms[0], terms[2], SplitDirection::Horizontal)
+            .unwrap();
+        let right = layout
+            .split(terms[1], terms[3], SplitDirection::Horizontal)
+            .unwrap();
+        assert_ne!(left, split_id);
+        assert_ne!(right, left);
+        (split_id, layout, terms)
+    }
+
+    #[test]
+    fn test_terminal_grid() {
+        let (split_id, layout, [a, b, c, d]) = grid();
+        assert_eq!(layout.terminals(), vec![a, c, b, d]);
+        assert_eq!(layout.parent(b).map(|(_, index)| index), Some(0));
+        assert_eq!(layout.parent(d).map(|(_, index)| index), Some(1));
+        assert_ne!(layout.parent(a).unwrap().0, split_id);
+        assert_eq!(layout.parent(a).map(|(_, index)| index), Some(0));
+        assert_eq!(layout.parent(c).map(|(_, index)| index), Some(1));
+    }
+
+    #[test]
+    fn test_split_direction_of_single_child() {
+        let split_id = WidgetId::next();
+        let (a, b) = (TermId::next(), TermId::next());
+        let mut layout = TerminalLayout::new(split_id, &[a]);
+        // The split of the tab turns around rather than nesting another one
+        assert_eq!(
+            layout.split(a, b, SplitDirection::Horizontal),
+            Some(split_id)
+        );
+        assert_eq!(
+            layout,
+            TerminalLayout::Split {
+                widget_id: split_id,
+                direction: SplitDirection::Horizontal,
+                children: vec![
+                    TerminalLayout::Terminal(a),
+                    TerminalLayout::Terminal(b)
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_close_terminals() {
+        let (split_id, mut layout, [a, b, c, d]) = grid();
+        let (left, _) = layout.parent(a).unwrap();
+        assert_eq!(layout.close_target(c), Some((left, 1)));
+        assert!(layout.remove(c));
+        assert!(!layout.remove(c));
+
+        // The split left with only the terminal goes along with it
+        assert_eq!(layout.close_target(a), Some((split_id, 0)));
+        assert!(layout.remove(a));
+        assert_eq!(layout.terminals(), vec![b, d]);
+        assert_eq!(layout.parent(d).map(|(_, index)| index), Some(1));
+
+        // The split of the tab stays, even when it's empty
+        let (right, _) = layout.parent(b).unwrap();
+        assert_eq!(layout.close_target(b), Some((right, 0)));
+        layout.remove(b);
+        assert_eq!(layout.close_target(d), Some((split_id, 0)));
+        layout.remove(d);
+        assert_eq!(
+            layout,
+            TerminalLayout::Split {
+                widget_id: split_id,
+                direction: SplitDirection::Vertical,
+                children: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_exchange_terminals() {
+        let (_, mut layout, [a, b, c, d]) = grid();
+        let (left, _) = layout.parent(a).unwrap();
+        assert_eq!(layout.exchange(a), Some((left, 0)));
+        assert_eq!(layout.terminals(), vec![c, a, b, d]);
+        // The last child of a split has nothing to swap with
+        assert_eq!(layout.exchange(a), None);
+    }
+
+    #[test]
+    fn test_layout_info() {
+        let (_, layout, _) = grid();
+        let info = layout.info();
+        assert_eq!(
+            info,
+            TerminalLayoutInfo::Split {
+                direction: SplitDirection::Vertical,
+                children: vec![
+                    TerminalLayoutInfo::Split {
+                        direction: SplitDirection::Horizontal,
+                        children: vec![
+                            TerminalLayoutInfo::Terminal(0),
+                            TerminalLayoutInfo::Terminal(1)
+                        ],
+                    },
+                    TerminalLayoutInfo::Split {
+                        direction: SplitDirection::Horizontal,
+                        children: vec![
+                            TerminalLayoutInfo::Terminal(2),
+                            TerminalLayoutInfo::Terminal(3)
+                        ],
+                    },
+                ],
+            }
+        );
+
+        // The terminals are opened again with new ids, in the order they were
+        // laid out in
+        let split_id = WidgetId::next();
+        let terms = [
+            TermId::next(),
+            TermId::next(),
+            TermId::next(),
+            TermId::next(),
+        ];
+        let restored = info.to_layout(split_id, &terms);
+        assert_eq!(restored.terminals(), terms.to_vec());
+        assert_eq!(restored.info(), info);
+        assert_eq!(restored.parent(terms[2]).map(|(_, index)| index), Some(0));
+
+        // The split of the missing terminals is dropped
+        let restored = info.to_layout(split_id, &terms[..2]);
+        assert_eq!(restored.terminals(), terms[..2].to_vec());
+        assert!(matches!(
+            restored,
+            TerminalLayout::Split { children, .. } if children.len() == 1
+        ));
+    }
+}
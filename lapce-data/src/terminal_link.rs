@@ -0,0 +1,379 @@
+use std::path::{Component, Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use url::Url;
+
+use crate::{
+    command::LapceUICommand,
+    editor::{EditorLocation, LineCol},
+};
+
+static URL_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`]+"#).unwrap());
+
+/// A path, optionally followed by a line and column like `src/main.rs:42:10`,
+/// or `src/main.rs(42,10)` as some compilers print them
+static PATH_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)
+        (?P<path>
+            (?:[A-Za-z]:[\\/]|~[\\/]|\.{1,2}[\\/]|[\\/])?
+            (?:[\w.@+\-]+[\\/])*
+            [\w.@+\-]*\w
+        )
+        (?:
+            :(?P<line>\d+)(?::(?P<column>\d+))?
+            |
+            \((?P<pline>\d+)(?:,\s*(?P<pcolumn>\d+))?\)
+        )?",
+    )
+    .unwrap()
+});
+
+/// What a link in the terminal output points to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TerminalLinkTarget {
+    /// A web page or any other uri, opened with the system opener
+    Url(String),
+    /// A file as written in the output, which may be relative to the working
+    /// directory of the terminal, with the line and column after it, from 1
+    File {
+        path: String,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+}
+
+impl TerminalLinkTarget {
+    /// The command opening the link, with the relative paths resolved against
+    /// the working directory of the terminal, and `~` against the home
+    /// directory when it's known
+    pub fn open_command(
+        &self,
+        cwd: Option<&Path>,
+        home: Option<&Path>,
+    ) -> LapceUICommand {
+        match self {
+            TerminalLinkTarget::Url(url) => {
+                let path = url
+                    .starts_with("file:")
+                    .then(|| Url::parse(url).ok()?.to_file_path().ok())
+                    .flatten();
+                match path {
+                    Some(path) => LapceUICommand::OpenFile(path, false),
+                    None => LapceUICommand::OpenURI(url.clone()),
+                }
+            }
+            TerminalLinkTarget::File { path, line, column } => {
+                LapceUICommand::JumpToLineColLocation(
+                    None,
+                    EditorLocation {
+                        path: resolve_path(path, cwd, home),
+                        position: line.map(|line| LineCol {
+                            line: line.saturating_sub(1),
+                            column: column.unwrap_or(1).saturating_sub(1),
+                        }),
+                        scroll_offset: None,
+                        history: None,
+                    },
+                    false,
+                )
+            }
+        }
+    }
+}
+
+/// A file path or url in a line of the terminal output, which is opened when
+/// it's clicked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalLink {
+    /// The column the link starts at
+    pub start: usize,
+    /// The column after the end of the link
+    pub end: usize,
+    pub target: TerminalLinkTarget,
+}
+
+impl TerminalLink {
+    /// The links of a line of the terminal, whose text has a char for each
+    /// column
+    pub fn find_all(line: &str) -> Vec<TerminalLink> {
+        let column = |offset: usize| line[..offset].chars().count();
+
+        let mut links: Vec<TerminalLink> = URL_REGEX
+            .find_iter(line)
+            .map(|m| {
+                let url = trim_url(m.as_str());
+                TerminalLink {
+                    start: column(m.start()),
+                    end: column(m.start() + url.len()),
+                    target: TerminalLinkTarget::Url(url.to_string()),
+                }
+            })
+            .collect();
+
+        let mut urls = links
+            .iter()
+            .map(|link| (link.start, link.end))
+            .collect::<Vec<_>>();
+        for captures in PATH_REGEX.captures_iter(line) {
+            let m = captures.get(0).unwrap();
+            let (start, end) = (column(m.start()), column(m.end()));
+            if urls.iter().any(|&(s, e)| start < e && s < end) {
+                continue;
+            }
+            let path = &captures["path"];
+            let number = |name: &str| {
+                captures
+                    .name(name)
+                    .and_then(|number| number.as_str().parse::<usize>().ok())
+            };
+            let line = number("line").or_else(|| number("pline"));
+            let column = number("column").or_else(|| number("pcolumn"));
+            if !is_file_path(path, line.is_some()) {
+                continue;
+            }
+            urls.push((start, end));
+            links.push(TerminalLink {
+                start,
+                end,
+                target: TerminalLinkTarget::File {
+                    path: path.to_string(),
+                    line,
+                    column,
+                },
+            });
+        }
+
+        links.sort_by_key(|link| link.start);
+        links
+    }
+
+    /// The link at the column of the line, if there's one
+    pub fn at(line: &str, column: usize) -> Option<TerminalLink> {
+        Self::find_all(line)
+            .into_iter()
+            .find(|link| link.start <= column && column < link.end)
+    }
+
+    /// The parts of the link on each row, as the row after the first one of
+    /// the line and the columns in it, when the line wraps at `columns`
+    pub fn rows(&self, columns: usize) -> Vec<(usize, usize, usize)> {
+        if columns == 0 || self.start >= self.end {
+            return Vec::new();
+        }
+        (self.start / columns..=(self.end - 1) / columns)
+            .map(|row| {
+                let start = self.start.max(row * columns) - row * columns;
+                let end = self.end.min((row + 1) * columns) - row * columns;
+                (row, start, end)
+            })
+            .collect()
+    }
+}
+
+/// Drop the punctuation ending a sentence after the url, and the closing
+/// brackets which don't belong to it, like the one around `(https://lapce.dev)`
+fn trim_url(mut url: &str) -> &str {
+    loop {
+        let trimmed = match url.chars().last() {
+            Some('.' | ',' | ';' | ':' | '!' | '?') => true,
+            Some(')') => url.matches('(').count() < url.matches(')').count(),
+            Some(']') => url.matches('[').count() < url.matches(']').count(),
+            _ => false,
+        };
+        if !trimmed {
+            return url;
+        }
+        url = &url[..url.len() - 1];
+    }
+}
+
+/// Whether the text matched as a path is likely to be one, which is a file
+/// name with an extension and a letter in it, that comes with a directory or a
+/// line number, so that words, versions and fractions aren't made links
+fn is_file_path(path: &str, has_line: bool) -> bool {
+    let (dir, name) = match path.rfind(['/', '\\']) {
+        Some(index) => (true, &path[index + 1..]),
+        None => (false, path),
+    };
+    (dir || has_line)
+        && name.contains('.')
+        && name.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// Make the path of a link absolute, with the `.` and `..` in it removed so
+/// that it's the same as the path of the file when it's opened otherwise
+pub fn resolve_path(path: &str, cwd: Option<&Path>, home: Option<&Path>) -> PathBuf {
+    let path = Path::new(path);
+    let path = match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => match cwd {
+            Some(cwd) if path.is_relative() => cwd.join(path),
+            _ => path.to_path_buf(),
+        },
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    resolved.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{resolve_path, TerminalLink, TerminalLinkTarget};
+
+    fn file(
+        path: &str,
+        line: Option<usize>,
+        column: Option<usize>,
+    ) -> TerminalLinkTarget {
+        TerminalLinkTarget::File {
+            path: path.to_string(),
+            line,
+            column,
+        }
+    }
+
+    fn targets(line: &str) -> Vec<TerminalLinkTarget> {
+        TerminalLink::find_all(line)
+            .into_iter()
+            .map(|link| link.target)
+            .collect()
+    }
+
+    #[test]
+    fn test_find_file_links() {
+        let line = "  --> src/main.rs:42:10";
+        let links = TerminalLink::find_all(line);
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].start, links[0].end), (6, 23));
+        assert_eq!(links[0].target, file("src/main.rs", Some(42), Some(10)));
+
+        assert_eq!(
+            targets("lapce-data/src/find.rs:12: let a = 1;"),
+            vec![file("lapce-data/src/find.rs", Some(12), None)]
+        );
+        assert_eq!(
+            targets("Program.cs(7,15): error CS1002"),
+            vec![file("Program.cs", Some(7), Some(15))]
+        );
+        assert_eq!(
+            targets("see ./README.md and ~/notes/todo.txt."),
+            vec![
+                file("./README.md", None, None),
+                file("~/notes/todo.txt", None, None)
+            ]
+        );
+        assert_eq!(
+            targets(r"C:\code\lapce\src\main.rs:3:1"),
+            vec![file(r"C:\code\lapce\src\main.rs", Some(3), Some(1))]
+        );
+    }
+
+    #[test]
+    fn test_skip_words_and_numbers() {
+        assert!(targets("error: aborting due to 2 previous errors").is_empty());
+        assert!(targets("Compiling lapce v0.2.5 in 1.5s, and/or").is_empty());
+        assert!(targets("ratio 1.5:3").is_empty());
+        // A file name without a directory needs a line number
+        assert!(targets("main.rs").is_empty());
+        assert_eq!(targets("main.rs:7"), vec![file("main.rs", Some(7), None)]);
+    }
+
+    #[test]
+    fn test_find_url_links() {
+        let line = concat!(
+            "docs at (https://lapce.dev/docs?a=1#b). ",
+            "or https://en.wikipedia.org/wiki/Rust_(language),"
+        );
+        assert_eq!(
+            targets(line),
+            vec![
+                TerminalLinkTarget::Url("https://lapce.dev/docs?a=1#b".to_string()),
+                TerminalLinkTarget::Url(
+                    "https://en.wikipedia.org/wiki/Rust_(language)".to_string()
+                ),
+            ]
+        );
+
+        // The path of the url isn't a link of its own
+        let links = TerminalLink::find_all("GET http://localhost:3000/src/app.js");
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].start, links[0].end), (4, 36));
+    }
+
+    #[test]
+    fn test_link_at_column() {
+        // The columns count chars, not bytes
+        let line = "→ src/lib.rs:1 ✓";
+        assert_eq!(TerminalLink::at(line, 1), None);
+        assert_eq!(
+            TerminalLink::at(line, 2).map(|link| link.target),
+            Some(file("src/lib.rs", Some(1), None))
+        );
+        assert!(TerminalLink::at(line, 13).is_some());
+        assert_eq!(TerminalLink::at(line, 14), None);
+    }
+
+    #[test]
+    fn test_wrapped_link_rows() {
+        // A line wrapped at 10 columns, with the link split over three rows
+        let line = "see src/terminal.rs:42 now";
+        let link = TerminalLink::at(line, 12).unwrap();
+        assert_eq!(link.target, file("src/terminal.rs", Some(42), None));
+        assert_eq!(link.rows(10), vec![(0, 4, 10), (1, 0, 10), (2, 0, 2)]);
+
+        let link = TerminalLink::at(line, 4).unwrap();
+        assert_eq!(link.rows(80), vec![(0, 4, 22)]);
+        assert!(link.rows(0).is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_resolve_path() {
+        let cwd = Path::new("/home/user/lapce");
+        let home = Path::new("/home/user");
+        assert_eq!(
+            resolve_path("src/main.rs", Some(cwd), Some(home)),
+            PathBuf::from("/home/user/lapce/src/main.rs")
+        );
+        assert_eq!(
+            resolve_path("./lapce-ui/../README.md", Some(cwd), Some(home)),
+            PathBuf::from("/home/user/lapce/README.md")
+        );
+        assert_eq!(
+            resolve_path("../other/lib.rs", Some(cwd), Some(home)),
+            PathBuf::from("/home/user/other/lib.rs")
+        );
+        assert_eq!(
+            resolve_path("~/notes.md", Some(cwd), Some(home)),
+            PathBuf::from("/home/user/notes.md")
+        );
+        assert_eq!(
+            resolve_path("/etc/hosts.conf", Some(cwd), Some(home)),
+            PathBuf::from("/etc/hosts.conf")
+        );
+        // Without a working directory the path is kept as it is
+        assert_eq!(
+            resolve_path("src/main.rs", None, None),
+            PathBuf::from("src/main.rs")
+        );
+    }
+}
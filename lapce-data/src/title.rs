@@ -6,10 +6,151 @@
 use crate::{
     command::{CommandExecuted, CommandKind, LapceCommand},
     config::LapceConfig,
+    data::{LapceTabData, LapceWorkspace, LapceWorkspaceType},
+    document::BufferContent,
     keypress::KeyPressFocus,
     list::ListData,
 };
 
+/// The name of the workspace, with the remote it's on
+pub fn workspace_title(workspace: &LapceWorkspace) -> Option<String> {
+    let p = workspace.path.as_ref()?;
+    let dir = p.file_name().unwrap_or(p.as_os_str()).to_string_lossy();
+    Some(match &workspace.kind {
+        LapceWorkspaceType::Local => format!("{dir}"),
+        LapceWorkspaceType::RemoteSSH(ssh) => format!("{dir} [{ssh}]"),
+        LapceWorkspaceType::RemoteWSL => format!("{dir} [wsl]"),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TitlePart {
+    Text(String),
+    /// The file name of the active editor
+    File,
+    /// The full path of the file of the active editor
+    Path,
+    Workspace,
+    /// A `*` when the active editor has unsaved changes
+    Dirty,
+    /// The current git branch
+    Branch,
+    /// Separates the parts of the title which are left out when they're empty
+    Separator,
+}
+
+/// What the tokens of a [`TitleTemplate`] are replaced with
+#[derive(Clone, Debug, Default)]
+pub struct TitleValues {
+    pub file: String,
+    pub path: String,
+    pub workspace: String,
+    pub dirty: bool,
+    pub branch: String,
+}
+
+impl TitleValues {
+    /// The values of the active editor and the workspace of the tab
+    pub fn new(data: &LapceTabData) -> Self {
+        let mut values = Self {
+            workspace: workspace_title(&data.workspace).unwrap_or_default(),
+            branch: data.source_control.branch.clone(),
+            ..Default::default()
+        };
+        if let Some(editor) = data.main_split.active_editor() {
+            if let BufferContent::File(_) | BufferContent::Scratch(..) =
+                &editor.content
+            {
+                let doc = data.main_split.content_doc(&editor.content);
+                values.file = editor.content.file_name().to_string();
+                if let BufferContent::File(path) = &editor.content {
+                    values.path = path.to_string_lossy().into_owned();
+                }
+                values.dirty = !doc.buffer().is_pristine();
+            }
+        }
+        values
+    }
+}
+
+/// The window title, given by a template like
+/// `{dirty}{file}{separator}{workspace}{separator}Lapce`.
+///
+/// The tokens are `{file}`, `{path}`, `{workspace}`, `{dirty}`, `{branch}` and
+/// `{separator}`, and anything else in braces is kept as it is. The separators
+/// are shown as ` - ` between the parts around them which aren't empty, so that
+/// a title without a file doesn't start with one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TitleTemplate {
+    parts: Vec<TitlePart>,
+}
+
+impl TitleTemplate {
+    pub const SEPARATOR: &'static str = " - ";
+
+    pub fn parse(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let token = rest.find('}').and_then(|end| {
+                let part = match &rest[1..end] {
+                    "file" => TitlePart::File,
+                    "path" => TitlePart::Path,
+                    "workspace" => TitlePart::Workspace,
+                    "dirty" => TitlePart::Dirty,
+                    "branch" => TitlePart::Branch,
+                    "separator" => TitlePart::Separator,
+                    _ => return None,
+                };
+                Some((part, end))
+            });
+            match token {
+                Some((part, end)) => {
+                    if !text.is_empty() {
+                        parts.push(TitlePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(TitlePart::Text(text));
+        }
+        Self { parts }
+    }
+
+    pub fn render(&self, values: &TitleValues) -> String {
+        let mut segments = vec![String::new()];
+        for part in self.parts.iter() {
+            let segment = segments.last_mut().unwrap();
+            match part {
+                TitlePart::Text(text) => segment.push_str(text),
+                TitlePart::File => segment.push_str(&values.file),
+                TitlePart::Path => segment.push_str(&values.path),
+                TitlePart::Workspace => segment.push_str(&values.workspace),
+                TitlePart::Dirty => {
+                    if values.dirty {
+                        segment.push('*');
+                    }
+                }
+                TitlePart::Branch => segment.push_str(&values.branch),
+                TitlePart::Separator => segments.push(String::new()),
+            }
+        }
+        segments.retain(|segment| !segment.trim().is_empty());
+        segments.join(Self::SEPARATOR)
+    }
+}
+
 #[derive(Clone)]
 pub struct TitleData {
     pub widget_id: WidgetId,
@@ -75,3 +216,60 @@ fn receive_char(&mut self, _ctx: &mut EventCtx, _c: &str) {
         // Currently, this does not have any sort of input (such as for filtering)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{TitleTemplate, TitleValues};
+
+    fn values() -> TitleValues {
+        TitleValues {
+            file: "main.rs".to_string(),
+            path: "/code/lapce/src/main.rs".to_string(),
+            workspace: "lapce".to_string(),
+            dirty: true,
+            branch: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_title_tokens() {
+        let template = TitleTemplate::parse("{dirty}{file} — {workspace} — Lapce");
+        assert_eq!(template.render(&values()), "*main.rs — lapce — Lapce");
+
+        let template = TitleTemplate::parse("{path} ({branch})");
+        assert_eq!(template.render(&values()), "/code/lapce/src/main.rs ()");
+        let values = TitleValues {
+            branch: "master".to_string(),
+            dirty: false,
+            ..values()
+        };
+        assert_eq!(template.render(&values), "/code/lapce/src/main.rs (master)");
+    }
+
+    #[test]
+    fn test_title_unknown_tokens() {
+        let template = TitleTemplate::parse("{name} {file} {file {} }{");
+        assert_eq!(template.render(&values()), "{name} main.rs {file {} }{");
+    }
+
+    #[test]
+    fn test_title_separators() {
+        let template = TitleTemplate::parse(
+            "{dirty}{file}{separator}{workspace}{separator}Lapce",
+        );
+        assert_eq!(template.render(&values()), "*main.rs - lapce - Lapce");
+
+        // The separators of the missing parts are left out
+        let values = TitleValues {
+            file: String::new(),
+            path: String::new(),
+            dirty: false,
+            ..values()
+        };
+        assert_eq!(template.render(&values), "lapce - Lapce");
+        assert_eq!(template.render(&TitleValues::default()), "Lapce");
+
+        let template = TitleTemplate::parse("{branch}{separator}{workspace}");
+        assert_eq!(template.render(&values), "lapce");
+    }
+}
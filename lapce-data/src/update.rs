@@ -1,33 +1,145 @@
-use std::path::{Path, PathBuf};
+use std::{
+    cmp::Ordering,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Result};
+use druid::Data;
 use lapce_core::{directory::Directory, meta};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Clone, Deserialize)]
 pub struct ReleaseInfo {
     pub tag_name: String,
     pub target_commitish: String,
     pub assets: Vec<ReleaseAsset>,
+    /// The release notes, in markdown
+    #[serde(default)]
+    pub body: String,
     #[serde(skip)]
     pub version: String,
 }
 
+impl ReleaseInfo {
+    /// Whether the release is newer than the running version
+    pub fn is_update(&self) -> bool {
+        is_newer(&self.version, *meta::VERSION)
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct ReleaseAsset {
     pub name: String,
     pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+    /// The checksum of the file, like `sha256:<hex>`
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+/// The releases checked for updates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateChannel {
+    Stable,
+    Nightly,
+}
+
+impl UpdateChannel {
+    /// The channel set in the config, or the one of the running version if it
+    /// isn't set. Debug builds don't have one.
+    pub fn from_config(channel: &str) -> Option<Self> {
+        match channel {
+            "stable" => Some(Self::Stable),
+            "nightly" => Some(Self::Nightly),
+            _ => match *meta::RELEASE {
+                "Debug" => None,
+                "Nightly" => Some(Self::Nightly),
+                _ => Some(Self::Stable),
+            },
+        }
+    }
 }
 
-pub fn get_latest_release() -> Result<ReleaseInfo> {
-    let url = match *meta::RELEASE {
-        "Debug" => {
-            return Err(anyhow!("no release for debug"));
+/// The steps of an update, shown in place of the update menu item
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Data)]
+pub enum UpdateStatus {
+    Downloading { downloaded: u64, total: u64 },
+    Verifying,
+    Installing,
+}
+
+impl UpdateStatus {
+    pub fn desc(&self) -> String {
+        match self {
+            UpdateStatus::Downloading { downloaded, total } if *total > 0 => {
+                format!("Downloading update ({}%)", downloaded * 100 / total)
+            }
+            UpdateStatus::Downloading { .. } => "Downloading update".to_string(),
+            UpdateStatus::Verifying => "Verifying update".to_string(),
+            UpdateStatus::Installing => "Installing update".to_string(),
         }
-        "Nightly" => {
+    }
+}
+
+/// The numbers of a version like `0.2.5` or `v0.3.0-rc.1`, and its
+/// pre-release part
+fn parse_version(version: &str) -> Option<(Vec<u64>, Option<&str>)> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let numbers = numbers
+        .split('.')
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    Some((numbers, pre))
+}
+
+/// Compare two release versions, if they can be compared. Nightly versions are
+/// named after their commit, so they can't.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let (a_numbers, a_pre) = parse_version(a)?;
+    let (b_numbers, b_pre) = parse_version(b)?;
+    let len = a_numbers.len().max(b_numbers.len());
+    let number = |numbers: &[u64], i: usize| numbers.get(i).copied().unwrap_or(0);
+    let ordering = (0..len)
+        .map(|i| number(&a_numbers, i).cmp(&number(&b_numbers, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal);
+    // A pre-release comes before the release of the same version
+    Some(ordering.then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => a.cmp(b),
+    }))
+}
+
+/// Whether the latest release is an update of the current version. A release
+/// which can't be compared, like a nightly one or one of another channel, is an
+/// update if it isn't the current version.
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    if current == "debug" {
+        return false;
+    }
+    match compare_versions(latest, current) {
+        Some(ordering) => ordering.is_gt(),
+        None => latest != current,
+    }
+}
+
+pub fn get_latest_release(channel: UpdateChannel) -> Result<ReleaseInfo> {
+    let url = match channel {
+        UpdateChannel::Nightly => {
             "https://api.github.com/repos/lapce/lapce/releases/tags/nightly"
         }
-        _ => "https://api.github.com/repos/lapce/lapce/releases/latest",
+        UpdateChannel::Stable => {
+            "https://api.github.com/repos/lapce/lapce/releases/latest"
+        }
     };
 
     let resp = reqwest::blocking::ClientBuilder::new()
@@ -48,7 +160,12 @@ pub fn get_latest_release() -> Result<ReleaseInfo> {
     Ok(release)
 }
 
-pub fn download_release(release: &ReleaseInfo) -> Result<PathBuf> {
+/// Download the release for this platform into the updates directory, and
+/// verify it against the size and the checksum of the release asset
+pub fn download_release(
+    release: &ReleaseInfo,
+    mut progress: impl FnMut(UpdateStatus),
+) -> Result<PathBuf> {
     let dir =
         Directory::updates_directory().ok_or_else(|| anyhow!("no directory"))?;
     let name = match std::env::consts::OS {
@@ -62,44 +179,292 @@ pub fn download_release(release: &ReleaseInfo) -> Result<PathBuf> {
     };
     let file_path = dir.join(name);
 
-    for asset in &release.assets {
-        if asset.name == name {
-            let mut resp = reqwest::blocking::get(&asset.browser_download_url)?;
-            if !resp.status().is_success() {
-                return Err(anyhow!("download file error {}", resp.text()?));
-            }
-            let mut out = std::fs::File::create(&file_path)?;
-            resp.copy_to(&mut out)?;
-            return Ok(file_path);
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| anyhow!("can't download release"))?;
+    let mut resp = reqwest::blocking::get(&asset.browser_download_url)?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("download file error {}", resp.text()?));
+    }
+    let total = match asset.size {
+        0 => resp.content_length().unwrap_or(0),
+        size => size,
+    };
+    let mut out = std::fs::File::create(&file_path)?;
+    let mut buf = vec![0; 64 * 1024];
+    let mut downloaded = 0;
+    progress(UpdateStatus::Downloading { downloaded, total });
+    loop {
+        let n = resp.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        downloaded += n as u64;
+        progress(UpdateStatus::Downloading { downloaded, total });
+    }
+    out.flush()?;
+
+    progress(UpdateStatus::Verifying);
+    verify(&file_path, asset)?;
+    Ok(file_path)
+}
+
+/// Check that the downloaded file is the whole release asset, unchanged
+fn verify(path: &Path, asset: &ReleaseAsset) -> Result<()> {
+    let bytes = std::fs::read(path)?;
+    if asset.size > 0 && bytes.len() as u64 != asset.size {
+        return Err(anyhow!(
+            "downloaded {} bytes of {} for {}",
+            bytes.len(),
+            asset.size,
+            asset.name
+        ));
+    }
+    if let Some(expected) = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+    {
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes);
+        let checksum = format!("{:x}", hasher.finalize());
+        if !checksum.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!("checksum mismatch for {}", asset.name));
+        }
+    }
+    Ok(())
+}
+
+/// Where the previous version is kept after an update
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Where the new version is copied before it replaces the installed one, next
+/// to it so that it can be renamed in place
+fn staged_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    path.with_file_name(name)
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Copy the new binary next to the installed one, keeping its permissions
+#[cfg(any(
+    test,
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    all(target_os = "windows", feature = "portable")
+))]
+fn stage(src: &Path, path: &Path) -> Result<PathBuf> {
+    let staged = staged_path(path);
+    if staged.exists() {
+        remove_path(&staged)?;
+    }
+    std::fs::copy(src, &staged)?;
+    Ok(staged)
+}
+
+/// Replace the installed version with the staged one, keeping the installed
+/// one as the backup. Both are renamed, so the installed path always holds a
+/// whole version, and the installed one is put back if the staged one can't
+/// be moved in.
+pub fn swap(staged: &Path, path: &Path) -> Result<()> {
+    let backup = backup_path(path);
+    if backup.exists() {
+        remove_path(&backup)?;
+    }
+    if path.exists() {
+        std::fs::rename(path, &backup)?;
+    }
+    if let Err(err) = std::fs::rename(staged, path) {
+        let _ = std::fs::rename(&backup, path);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Put the backup back in place of the installed version, which becomes the
+/// backup in turn
+pub fn restore(path: &Path) -> Result<()> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(anyhow!("no previous version to restore"));
+    }
+    let staged = staged_path(path);
+    if staged.exists() {
+        remove_path(&staged)?;
+    }
+    std::fs::rename(&backup, &staged)?;
+    swap(&staged, path)
+}
+
+/// An update which the new version hasn't confirmed yet by launching
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PendingUpdate {
+    pub version: String,
+    /// The installed binary or app bundle, whose backup is the previous version
+    pub path: PathBuf,
+    /// Whether the new version has been started, so that it failed to launch
+    /// if it's started again before confirming the update
+    pub launched: bool,
+}
+
+const PENDING_UPDATE: &str = "pending-update.json";
+
+impl PendingUpdate {
+    fn load(dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join(PENDING_UPDATE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join(PENDING_UPDATE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn remove(dir: &Path) {
+        let _ = std::fs::remove_file(dir.join(PENDING_UPDATE));
+    }
+}
+
+/// Check whether the version starting now is an update which failed to launch
+/// before. Returns the update to roll back, if so.
+fn check_health(dir: &Path, version: &str) -> Option<PendingUpdate> {
+    let mut pending = PendingUpdate::load(dir)?;
+    if pending.version != version {
+        // The update isn't the running version anymore
+        PendingUpdate::remove(dir);
+        return None;
+    }
+    if pending.launched {
+        PendingUpdate::remove(dir);
+        return Some(pending);
+    }
+    pending.launched = true;
+    if pending.save(dir).is_err() {
+        PendingUpdate::remove(dir);
+    }
+    None
+}
+
+/// Remember the update until the new version confirms it has launched, so
+/// that it's rolled back if it fails to
+pub fn mark_pending(release: &ReleaseInfo, path: &Path) -> Result<()> {
+    // There's nothing to roll back to without a backup, like after running the
+    // installer on Windows
+    if !backup_path(path).exists() {
+        return Ok(());
+    }
+    let dir =
+        Directory::updates_directory().ok_or_else(|| anyhow!("no directory"))?;
+    PendingUpdate {
+        version: release.version.clone(),
+        path: path.to_path_buf(),
+        launched: false,
+    }
+    .save(&dir)
+}
+
+/// Run on startup. If the previous launch of a new version didn't confirm the
+/// update, the previous version is restored and started instead.
+pub fn health_check() {
+    let dir = match Directory::updates_directory() {
+        Some(dir) => dir,
+        None => return,
+    };
+    if let Some(pending) = check_health(&dir, *meta::VERSION) {
+        eprintln!(
+            "Lapce {} failed to launch, rolling back to the previous version",
+            pending.version
+        );
+        if let Err(err) = restore(&pending.path) {
+            eprintln!("Failed to roll back: {err}");
+            return;
+        }
+        if let Err(err) = restart(&pending.path) {
+            eprintln!("Failed to restart: {err}");
         }
     }
+}
 
-    Err(anyhow!("can't download release"))
+/// Called once the new version is up, which passes the health check
+pub fn confirm_update() {
+    if let Some(dir) = Directory::updates_directory() {
+        PendingUpdate::remove(&dir);
+    }
+}
+
+/// Whether a previous version was kept by an update
+pub fn can_rollback(process_path: &Path) -> bool {
+    backup_path(&installed_path(process_path)).exists()
+}
+
+/// Restore the version kept by the last update. Returns the path to restart.
+pub fn rollback(process_path: &Path) -> Result<PathBuf> {
+    let path = installed_path(process_path);
+    restore(&path)?;
+    confirm_update();
+    Ok(path)
 }
 
 #[cfg(target_os = "macos")]
-pub fn extract(src: &Path, process_path: &Path) -> Result<PathBuf> {
-    let info = dmg::Attach::new(src).with()?;
-    let dest = process_path.parent().ok_or_else(|| anyhow!("no parent"))?;
-    let dest = if dest.file_name().and_then(|s| s.to_str()) == Some("MacOS") {
+fn installed_path(process_path: &Path) -> PathBuf {
+    app_directory(process_path).join("Lapce.app")
+}
+
+#[cfg(not(target_os = "macos"))]
+fn installed_path(process_path: &Path) -> PathBuf {
+    process_path.to_path_buf()
+}
+
+/// The directory of the app bundle
+#[cfg(target_os = "macos")]
+fn app_directory(process_path: &Path) -> &Path {
+    let dest = process_path.parent().unwrap_or(process_path);
+    if dest.file_name().and_then(|s| s.to_str()) == Some("MacOS") {
         dest.parent().unwrap().parent().unwrap().parent().unwrap()
     } else {
         dest
-    };
-    let _ = std::fs::remove_dir_all(dest.join("Lapce.app"));
-    fs_extra::copy_items(
-        &[info.mount_point.join("Lapce.app")],
-        dest,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn extract(src: &Path, process_path: &Path) -> Result<PathBuf> {
+    let info = dmg::Attach::new(src).with()?;
+    let dest = installed_path(process_path);
+    let staged = staged_path(&dest);
+    if staged.exists() {
+        std::fs::remove_dir_all(&staged)?;
+    }
+    std::fs::create_dir(&staged)?;
+    fs_extra::dir::copy(
+        info.mount_point.join("Lapce.app"),
+        &staged,
         &fs_extra::dir::CopyOptions {
             overwrite: true,
             skip_exist: false,
             buffer_size: 64000,
             copy_inside: true,
-            content_only: false,
+            content_only: true,
             depth: 0,
         },
     )?;
-    Ok(dest.join("Lapce.app"))
+    swap(&staged, &dest)?;
+    Ok(dest)
 }
 
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
@@ -109,8 +474,8 @@ pub fn extract(src: &Path, process_path: &Path) -> Result<PathBuf> {
     let mut archive = tar::Archive::new(tar);
     let parent = src.parent().ok_or_else(|| anyhow::anyhow!("no parent"))?;
     archive.unpack(parent)?;
-    std::fs::remove_file(process_path)?;
-    std::fs::copy(parent.join("Lapce").join("lapce"), process_path)?;
+    let staged = stage(&parent.join("Lapce").join("lapce"), process_path)?;
+    swap(&staged, process_path)?;
     Ok(process_path.to_path_buf())
 }
 
@@ -119,19 +484,15 @@ pub fn extract(src: &Path, process_path: &Path) -> Result<PathBuf> {
     let parent = src
         .parent()
         .ok_or_else(|| anyhow::anyhow!("src has no parent"))?;
-    let dst_parent = process_path
-        .parent()
-        .ok_or_else(|| anyhow::anyhow!("process_path has no parent"))?;
 
     {
         let mut archive = zip::ZipArchive::new(std::fs::File::open(src)?)?;
         archive.extract(parent)?;
     }
 
-    // TODO(dbuga): instead of replacing the exe, run the msi installer for non-portable
-    // TODO(dbuga): there's a very slight chance the user might end up with a backup file without a working .exe
-    std::fs::rename(process_path, dst_parent.join("lapce.exe.bak"))?;
-    std::fs::copy(parent.join("lapce.exe"), process_path)?;
+    // The running exe can be renamed, but not replaced
+    let staged = stage(&parent.join("lapce.exe"), process_path)?;
+    swap(&staged, process_path)?;
 
     Ok(process_path.to_path_buf())
 }
@@ -204,20 +565,156 @@ pub fn restart(path: &Path) -> Result<()> {
     Ok(())
 }
 
-#[cfg(all(target_os = "windows", feature = "portable"))]
-pub fn cleanup() {
-    // Clean up backup exe after an update
-    if let Ok(process_path) = std::env::current_exe() {
-        if let Some(dst_parent) = process_path.parent() {
-            let _ = std::fs::remove_file(dst_parent.join("lapce.exe.bak"));
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-update-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("0.2.5", "0.2.4"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("0.10.0", "0.9.9"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("v0.2.5", "0.2.5"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("0.3", "0.3.0"), Some(Ordering::Equal));
+        assert_eq!(
+            compare_versions("0.3.0-rc.1", "0.3.0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_versions("0.3.0-rc.2", "0.3.0-rc.1"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(compare_versions("nightly-1a2b3c4", "0.2.5"), None);
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.6", "0.2.5"));
+        assert!(!is_newer("0.2.5", "0.2.5"));
+        // An older stable release isn't an update
+        assert!(!is_newer("0.2.4", "0.2.5"));
+        // Nightly versions only tell whether they're the same build
+        assert!(is_newer("nightly-1a2b3c4", "nightly-5d6e7f8"));
+        assert!(!is_newer("nightly-1a2b3c4", "nightly-1a2b3c4"));
+        assert!(is_newer("0.2.5", "nightly-1a2b3c4"));
+        assert!(!is_newer("0.2.6", "debug"));
+    }
+
+    #[test]
+    fn test_swap_and_restore() {
+        let dir = temp_dir("swap");
+        let path = dir.join("lapce");
+        fs::write(&path, "old").unwrap();
+        let download = dir.join("download");
+        fs::write(&download, "new").unwrap();
+
+        let staged = stage(&download, &path).unwrap();
+        swap(&staged, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "old");
+        assert!(!staged.exists());
+
+        // Restoring swaps the versions, so the update can be restored in turn
+        restore(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "new");
+
+        fs::remove_file(backup_path(&path)).unwrap();
+        assert!(restore(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_swap_directory() {
+        let dir = temp_dir("bundle");
+        let path = dir.join("Lapce.app");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("version"), "old").unwrap();
+        let staged = staged_path(&path);
+        fs::create_dir(&staged).unwrap();
+        fs::write(staged.join("version"), "new").unwrap();
+
+        swap(&staged, &path).unwrap();
+        assert_eq!(fs::read_to_string(path.join("version")).unwrap(), "new");
+        restore(&path).unwrap();
+        assert_eq!(fs::read_to_string(path.join("version")).unwrap(), "old");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_health_check() {
+        let dir = temp_dir("health");
+        let pending = PendingUpdate {
+            version: "0.2.6".to_string(),
+            path: dir.join("lapce"),
+            launched: false,
+        };
+        pending.save(&dir).unwrap();
+
+        // The first launch of the new version marks it as launched, and the
+        // update is confirmed once it's up
+        assert_eq!(check_health(&dir, "0.2.6"), None);
+        assert!(PendingUpdate::load(&dir).unwrap().launched);
+
+        // Starting again without confirming means the launch failed
+        assert_eq!(
+            check_health(&dir, "0.2.6"),
+            Some(PendingUpdate {
+                launched: true,
+                ..pending
+            })
+        );
+        assert_eq!(PendingUpdate::load(&dir), None);
+
+        // An update which isn't the running version is forgotten
+        PendingUpdate {
+            version: "0.2.7".to_string(),
+            path: dir.join("lapce"),
+            launched: true,
         }
+        .save(&dir)
+        .unwrap();
+        assert_eq!(check_health(&dir, "0.2.6"), None);
+        assert_eq!(PendingUpdate::load(&dir), None);
+
+        let _ = fs::remove_dir_all(&dir);
     }
-}
 
-#[cfg(any(
-    not(target_os = "windows"),
-    all(target_os = "windows", not(feature = "portable"))
-))]
-pub fn cleanup() {
-    // Nothing to do yet
+    #[test]
+    fn test_verify() {
+        let dir = temp_dir("verify");
+        let path = dir.join("Lapce-linux.tar.gz");
+        fs::write(&path, "lapce").unwrap();
+        let asset = |size, digest: Option<&str>| ReleaseAsset {
+            name: "Lapce-linux.tar.gz".to_string(),
+            browser_download_url: String::new(),
+            size,
+            digest: digest.map(|d| d.to_string()),
+        };
+        let wrong = format!("sha256:{}", "0".repeat(64));
+
+        assert!(verify(&path, &asset(5, None)).is_ok());
+        assert!(verify(&path, &asset(0, None)).is_ok());
+        assert!(verify(&path, &asset(4, None)).is_err());
+        assert!(verify(&path, &asset(5, Some(&wrong))).is_err());
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"lapce");
+        let checksum = format!("sha256:{:x}", hasher.finalize());
+        assert!(verify(&path, &asset(5, Some(&checksum))).is_ok());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
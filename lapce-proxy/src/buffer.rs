@@ -1,16 +1,20 @@
 use std::{
     borrow::Cow,
+    collections::hash_map::DefaultHasher,
     ffi::OsString,
     fs,
     fs::File,
-    io::{Read, Write},
+    hash::{Hash, Hasher},
+    io::Write,
     path::{Path, PathBuf},
     time::SystemTime,
 };
 
 use anyhow::{anyhow, Result};
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
 use lapce_core::{
     buffer::rope_text::CharIndicesJoin, encoding::offset_utf8_to_utf16,
+    line_ending::LineEnding,
 };
 use lapce_rpc::buffer::BufferId;
 use lapce_xi_rope::{interval::IntervalBounds, rope::Rope, RopeDelta};
@@ -24,11 +28,28 @@ pub struct Buffer {
     pub path: PathBuf,
     pub rev: u64,
     pub mod_time: Option<SystemTime>,
+    /// The hash of the content of the file when it was last read or saved,
+    /// `None` if it isn't on disk
+    pub disk_hash: Option<u64>,
+    /// The encoding the file was read with, which it is saved with too
+    pub encoding: FileEncoding,
+    /// Whether the file has CR line endings, which are LF in the rope for its
+    /// lines to be split, and are written back when it's saved
+    pub cr_line_endings: bool,
+    /// Whether the file is in large file mode, so it isn't sent to the language
+    /// servers
+    pub large_file: bool,
 }
 
 impl Buffer {
     pub fn new(id: BufferId, path: PathBuf) -> Buffer {
-        let rope = Rope::from(load_file(&path).unwrap_or_default());
+        let (content, encoding, disk_hash) = load_file_with_encoding(&path, None)
+            .map(|(content, encoding)| {
+                let hash = content_hash(&content);
+                (content, encoding, Some(hash))
+            })
+            .unwrap_or_else(|_| (String::new(), FileEncoding::default(), None));
+        let (rope, line_ending, _) = LineEnding::read(Rope::from(content));
         let rev = u64::from(!rope.is_empty());
         let language_id = language_id_from_path(&path).unwrap_or("");
         let mod_time = get_mod_time(&path);
@@ -39,9 +60,18 @@ pub fn new(id: BufferId, path: PathBuf) -> Buffer {
             language_id,
             rev,
             mod_time,
+            disk_hash,
+            encoding,
+            cr_line_endings: line_ending == LineEnding::Cr,
+            large_file: false,
         }
     }
 
+    /// Whether the content is bigger than the large file threshold, if any
+    pub fn is_large(&self, threshold: Option<usize>) -> bool {
+        threshold.map_or(false, |threshold| self.rope.len() > threshold)
+    }
+
     pub fn save(&mut self, rev: u64) -> Result<()> {
         if self.rev != rev {
             return Err(anyhow!("not the right rev"));
@@ -56,9 +86,22 @@ pub fn save(&mut self, rev: u64) -> Result<()> {
         );
         let tmp_path = &self.path.with_extension(tmp_extension);
 
+        // The content is encoded before the file is created, so that nothing
+        // is written when the encoding can't represent it
+        let content = self.file_content();
+        let encoded = if self.encoding == FileEncoding::default() {
+            None
+        } else {
+            Some(self.encoding.encode(&content.to_string())?)
+        };
         let mut f = File::create(tmp_path)?;
-        for chunk in self.rope.iter_chunks(..self.rope.len()) {
-            f.write_all(chunk.as_bytes())?;
+        match encoded {
+            Some(bytes) => f.write_all(&bytes)?,
+            None => {
+                for chunk in content.iter_chunks(..content.len()) {
+                    f.write_all(chunk.as_bytes())?;
+                }
+            }
         }
 
         if let Ok(metadata) = fs::metadata(&self.path) {
@@ -68,9 +111,59 @@ pub fn save(&mut self, rev: u64) -> Result<()> {
 
         fs::rename(tmp_path, &self.path)?;
         self.mod_time = get_mod_time(&self.path);
+        self.disk_hash = Some(content_hash(&content.to_string()));
         Ok(())
     }
 
+    /// The content as it is in the file, with its CR line endings back
+    pub fn file_content(&self) -> Rope {
+        if self.cr_line_endings {
+            if let Some(content) = LineEnding::Cr.normalize(&self.rope) {
+                return content;
+            }
+        }
+        self.rope.clone()
+    }
+
+    /// How the file changed on disk since it was last read or saved, if it did.
+    /// A file which is touched, or written with the same content, isn't
+    /// changed.
+    pub fn disk_change(&mut self) -> Option<DiskChange> {
+        if !self.path.exists() {
+            // Only a file which was on disk can be deleted
+            self.mod_time = None;
+            return self.disk_hash.take().map(|_| DiskChange::Deleted);
+        }
+
+        let mod_time = get_mod_time(&self.path);
+        if mod_time == self.mod_time {
+            return None;
+        }
+        self.mod_time = mod_time;
+        let (content, _) =
+            load_file_with_encoding(&self.path, Some(self.encoding.encoding))
+                .ok()?;
+        let hash = content_hash(&content);
+        if self.disk_hash == Some(hash) {
+            return None;
+        }
+        self.disk_hash = Some(hash);
+        Some(DiskChange::Modified(content))
+    }
+
+    /// Read the file again with the given encoding, which it is saved with from
+    /// now on. The content is returned rather than replacing the rope, as the
+    /// editor sends it back as an edit.
+    pub fn reopen_with_encoding(
+        &mut self,
+        encoding: &'static Encoding,
+    ) -> Result<String> {
+        let (content, encoding) =
+            load_file_with_encoding(&self.path, Some(encoding))?;
+        self.encoding = encoding;
+        Ok(content)
+    }
+
     pub fn update(
         &mut self,
         delta: &RopeDelta,
@@ -145,8 +238,146 @@ pub fn is_empty(&self) -> bool {
     }
 }
 
-pub fn load_file(path: &Path) -> Result<String> {
-    Ok(read_path_to_string_lossy(path)?)
+/// The encoding of a file, and whether it starts with a byte order mark
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+impl FileEncoding {
+    /// Detect the encoding from the byte order mark, or else guess it from the
+    /// content: UTF-8 when it's valid, then Shift-JIS, and Latin-1 as the last
+    /// resort since any bytes are valid in it
+    pub fn detect(bytes: &[u8]) -> Self {
+        if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+            return Self {
+                encoding,
+                bom: true,
+            };
+        }
+
+        let encoding = if std::str::from_utf8(bytes).is_ok() {
+            UTF_8
+        } else if is_shift_jis(bytes) {
+            SHIFT_JIS
+        } else {
+            WINDOWS_1252
+        };
+        Self {
+            encoding,
+            bom: false,
+        }
+    }
+
+    /// Use the given encoding, keeping the byte order mark if the file has one
+    /// for it
+    pub fn with_encoding(bytes: &[u8], encoding: &'static Encoding) -> Self {
+        let bom = Encoding::for_bom(bytes).map(|(bom_encoding, _)| bom_encoding)
+            == Some(encoding);
+        Self { encoding, bom }
+    }
+
+    /// Decode the bytes of the file, the invalid sequences are replaced with
+    /// `U+FFFD` rather than failing
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let bytes = if self.bom {
+            let bom_len = Encoding::for_bom(bytes).map(|(_, len)| len).unwrap_or(0);
+            &bytes[bom_len..]
+        } else {
+            bytes
+        };
+        let (content, _) = self.encoding.decode_without_bom_handling(bytes);
+        content.into_owned()
+    }
+
+    /// Encode the text to be written to the file, failing when the encoding
+    /// can't represent one of its characters rather than replacing it
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(text.len());
+        // encoding_rs only decodes UTF-16, it encodes it as UTF-8
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let little_endian = self.encoding == UTF_16LE;
+            let to_bytes = |unit: u16| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            };
+            if self.bom {
+                bytes.extend_from_slice(&to_bytes(0xFEFF));
+            }
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&to_bytes(unit));
+            }
+            return Ok(bytes);
+        }
+
+        if self.bom && self.encoding == UTF_8 {
+            bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        let (encoded, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            let mut buf = [0; 4];
+            let c = text
+                .chars()
+                .find(|c| self.encoding.encode(c.encode_utf8(&mut buf)).2)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            return Err(anyhow!(
+                "{} can't represent the character {c:?} (U+{:04X}), reopen the \
+                 file with another encoding to save it",
+                self.name(),
+                c as u32
+            ));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+}
+
+/// Whether the bytes look like Shift-JIS text. An accented letter of Latin-1
+/// followed by a letter is often a valid kanji too, so the text has to have
+/// some kana or Japanese punctuation, whose bytes are rare in Latin-1.
+fn is_shift_jis(bytes: &[u8]) -> bool {
+    SHIFT_JIS
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map_or(false, |content| {
+            // Half-width katakana are single bytes shared with the accented
+            // letters of Latin-1, so they are taken as a sign it isn't Shift-JIS
+            let half_width_katakana =
+                |c: char| ('\u{FF61}'..='\u{FF9F}').contains(&c);
+            let kana_or_punctuation =
+                |c: char| ('\u{3000}'..='\u{30FF}').contains(&c);
+            !content.chars().any(half_width_katakana)
+                && content.chars().any(kana_or_punctuation)
+        })
+}
+
+/// Read the file and decode it with the given encoding, or the detected one
+pub fn load_file_with_encoding(
+    path: &Path,
+    encoding: Option<&'static Encoding>,
+) -> Result<(String, FileEncoding)> {
+    let bytes = fs::read(path)?;
+    let encoding = match encoding {
+        Some(encoding) => FileEncoding::with_encoding(&bytes, encoding),
+        None => FileEncoding::detect(&bytes),
+    };
+    Ok((encoding.decode(&bytes), encoding))
 }
 
 pub fn read_path_to_string_lossy<P: AsRef<Path>>(
@@ -294,6 +525,19 @@ fn get_document_content_changes(
     }
 }
 
+/// How the file of an open buffer changed on disk
+#[derive(Debug, PartialEq, Eq)]
+pub enum DiskChange {
+    Modified(String),
+    Deleted,
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Returns the modification timestamp for the file at a given path,
 /// if present.
 pub fn get_mod_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
@@ -302,3 +546,204 @@ pub fn get_mod_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
         .and_then(|meta| meta.modified())
         .ok()
 }
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, time::SystemTime};
+
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+    use lapce_core::line_ending::LineEnding;
+    use lapce_rpc::buffer::BufferId;
+    use lapce_xi_rope::{Interval, Rope, RopeDelta};
+
+    use super::{Buffer, DiskChange, FileEncoding};
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-encoding-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn detect_encoding_from_bom() {
+        let bytes = b"\xEF\xBB\xBFfn main() {}";
+        let encoding = FileEncoding::detect(bytes);
+        assert_eq!(encoding.encoding, UTF_8);
+        assert!(encoding.bom);
+        assert_eq!(encoding.decode(bytes), "fn main() {}");
+
+        let bytes = b"\xFF\xFEa\x00\xE9\x00";
+        let encoding = FileEncoding::detect(bytes);
+        assert_eq!(encoding.encoding, UTF_16LE);
+        assert!(encoding.bom);
+        assert_eq!(encoding.decode(bytes), "a\u{e9}");
+        assert_eq!(encoding.encode("a\u{e9}").unwrap(), bytes);
+    }
+
+    #[test]
+    fn detect_encoding_without_bom() {
+        let encoding = FileEncoding::detect("fn main() {}".as_bytes());
+        assert_eq!(encoding, FileEncoding::default());
+
+        let bytes = b"\x93\xFA\x96\x7B\x8C\xEA\x82\xC5\x82\xB7\x81\x42";
+        let encoding = FileEncoding::detect(bytes);
+        assert_eq!(encoding.encoding, SHIFT_JIS);
+        assert_eq!(encoding.decode(bytes), "日本語です。");
+
+        // Accented letters followed by letters are valid Shift-JIS kanji
+        let bytes = b"\xE9lan \xE9tait";
+        let encoding = FileEncoding::detect(bytes);
+        assert_eq!(encoding.encoding, WINDOWS_1252);
+        assert_eq!(encoding.decode(bytes), "élan était");
+
+        let bytes = b"caf\xE9, na\xEFve";
+        let encoding = FileEncoding::detect(bytes);
+        assert_eq!(encoding.encoding, WINDOWS_1252);
+        assert!(!encoding.bom);
+        assert_eq!(encoding.decode(bytes), "café, naïve");
+    }
+
+    #[test]
+    fn replace_invalid_sequences() {
+        let encoding = FileEncoding::with_encoding(b"caf\xE9", UTF_8);
+        assert_eq!(encoding.decode(b"caf\xE9"), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn save_with_original_encoding() {
+        let path = temp_file("latin1.txt", b"caf\xE9\n");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+        assert_eq!(buffer.encoding.encoding, WINDOWS_1252);
+        assert_eq!(buffer.get_document(), "café\n");
+
+        buffer.rope = Rope::from("café crème\n");
+        buffer.save(buffer.rev).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"caf\xE9 cr\xE8me\n");
+
+        // Reopening with another encoding saves with that one from then on
+        let content = buffer.reopen_with_encoding(UTF_8).unwrap();
+        assert_eq!(content, "caf\u{FFFD} cr\u{FFFD}me\n");
+        buffer.rope = Rope::from("café\n");
+        buffer.save(buffer.rev).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), "café\n".as_bytes());
+    }
+
+    #[test]
+    fn refuse_to_save_unmappable_characters() {
+        let path = temp_file("latin1-euro.txt", b"caf\xE9\n");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+        assert_eq!(buffer.encoding.encoding, WINDOWS_1252);
+
+        buffer.rope = Rope::from("café \u{2603}\n");
+        let err = buffer.save(buffer.rev).unwrap_err();
+        assert!(err.to_string().contains("U+2603"));
+        assert_eq!(fs::read(&path).unwrap(), b"caf\xE9\n");
+    }
+
+    #[test]
+    fn save_converted_line_endings() {
+        let path = temp_file("mixed.txt", b"a\r\nb\nc\r\n");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+
+        let mut doc = lapce_core::buffer::Buffer::new("");
+        doc.init_content(buffer.rope.clone());
+        assert_eq!(doc.line_ending(), LineEnding::CrLf);
+        assert!(doc.mixed_line_endings());
+
+        let (delta, _, _) = doc.convert_line_endings(LineEnding::Lf).unwrap();
+        buffer.update(&delta, buffer.rev + 1);
+        buffer.save(buffer.rev).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"a\nb\nc\n");
+    }
+
+    #[test]
+    fn keep_cr_line_endings() {
+        let path = temp_file("cr.txt", b"a\rb\rc\r");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+        assert_eq!(buffer.rope.to_string(), "a\nb\nc\n");
+        assert_eq!(buffer.line_of_offset(buffer.len()), 3);
+
+        // The editor reads the file the same way
+        let mut doc = lapce_core::buffer::Buffer::new("");
+        doc.init_content(buffer.file_content());
+        assert_eq!(doc.to_string(), buffer.rope.to_string());
+        assert_eq!(doc.line_ending(), LineEnding::Cr);
+
+        let delta = RopeDelta::simple_edit(
+            Interval::new(buffer.len(), buffer.len()),
+            Rope::from("d\n"),
+            buffer.len(),
+        );
+        buffer.update(&delta, buffer.rev + 1);
+        buffer.save(buffer.rev).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"a\rb\rc\rd\r");
+        assert_eq!(buffer.disk_change(), None);
+    }
+
+    #[test]
+    fn detect_large_file() {
+        let path = temp_file("large.log", "line\n".repeat(1000).as_bytes());
+        let buffer = Buffer::new(BufferId(0), path);
+        assert!(buffer.is_large(Some(4096)));
+        assert!(!buffer.is_large(Some(5000)));
+        assert!(!buffer.is_large(None));
+    }
+
+    #[test]
+    fn detect_external_modification() {
+        let path = temp_file("external.txt", b"fn main() {}\n");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+        assert_eq!(buffer.disk_change(), None);
+
+        // The modification time is moved back, so that the writes below don't
+        // depend on the precision of the one of the file system
+        fs::write(&path, "fn main() {}\n").unwrap();
+        buffer.mod_time = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(buffer.disk_change(), None);
+
+        fs::write(&path, "fn main() { run() }\n").unwrap();
+        buffer.mod_time = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            buffer.disk_change(),
+            Some(DiskChange::Modified("fn main() { run() }\n".to_string()))
+        );
+        // The change is only reported once
+        assert_eq!(buffer.disk_change(), None);
+
+        // Saving the buffer isn't a change made outside of it
+        buffer.rope = Rope::from("fn main() { run(); }\n");
+        buffer.save(buffer.rev).unwrap();
+        buffer.mod_time = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(buffer.disk_change(), None);
+    }
+
+    #[test]
+    fn detect_external_deletion() {
+        let path = temp_file("deleted.txt", b"deleted\n");
+        let mut buffer = Buffer::new(BufferId(0), path.clone());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(buffer.disk_change(), Some(DiskChange::Deleted));
+        assert_eq!(buffer.disk_change(), None);
+
+        // Creating the file again is a modification of the deleted buffer
+        fs::write(&path, "restored\n").unwrap();
+        assert_eq!(
+            buffer.disk_change(),
+            Some(DiskChange::Modified("restored\n".to_string()))
+        );
+
+        // Saving a deleted buffer puts it back on disk
+        fs::remove_file(&path).unwrap();
+        assert_eq!(buffer.disk_change(), Some(DiskChange::Deleted));
+        buffer.save(buffer.rev).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"deleted\n");
+        assert_eq!(buffer.disk_change(), None);
+
+        // A new file which was never saved isn't deleted
+        let mut buffer = Buffer::new(BufferId(0), path.with_file_name("new.txt"));
+        assert_eq!(buffer.disk_change(), None);
+    }
+}
@@ -7,36 +7,42 @@
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use alacritty_terminal::{event::WindowSize, event_loop::Msg};
 use anyhow::{anyhow, Context, Result};
-use crossbeam_channel::Sender;
+use crossbeam_channel::{SendTimeoutError, Sender};
+use encoding_rs::Encoding;
 use git2::{build::CheckoutBuilder, DiffOptions, Repository};
 use grep_matcher::Matcher;
 use grep_regex::RegexMatcherBuilder;
 use grep_searcher::{sinks::UTF8, SearcherBuilder};
 use indexmap::IndexMap;
+use lapce_core::buffer::{diff_hunks, rope_diff};
 use lapce_rpc::{
     core::{CoreNotification, CoreRpcHandler},
-    file::FileNodeItem,
+    file::{moved_path, FileLocation, FileNodeItem, FileOperation},
     proxy::{
         ProxyHandler, ProxyNotification, ProxyRequest, ProxyResponse,
-        ProxyRpcHandler,
+        ProxyRpcHandler, SearchMatches,
     },
-    source_control::{DiffInfo, FileDiff},
+    source_control::{BlameHunk, DiffHunk, DiffInfo, FileDiff},
     style::{LineStyle, SemanticStyles},
     terminal::TermId,
     RequestId, RpcError,
 };
 use lapce_xi_rope::Rope;
-use lsp_types::{Position, Range, TextDocumentItem, Url};
+use lsp_types::{TextDocumentItem, Url};
 use parking_lot::Mutex;
 
 use crate::{
-    buffer::{get_mod_time, load_file, Buffer},
-    plugin::{catalog::PluginCatalog, remove_volt, PluginCatalogRpcHandler},
+    buffer::{Buffer, DiskChange},
+    plugin::{
+        catalog::PluginCatalog,
+        download::{cancel_volt_download, register_volt_download},
+        remove_volt, PluginCatalogRpcHandler,
+    },
     terminal::Terminal,
     watcher::{FileWatcher, Notify, WatchToken},
 };
@@ -67,15 +73,19 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 plugin_configurations,
                 window_id,
                 tab_id,
+                file_watcher_debounce_ms,
             } => {
                 self.window_id = window_id;
                 self.tab_id = tab_id;
                 self.workspace = workspace;
-                self.file_watcher.notify(FileWatchNotifier::new(
-                    self.workspace.clone(),
-                    self.core_rpc.clone(),
-                    self.proxy_rpc.clone(),
-                ));
+                self.file_watcher.notify(
+                    FileWatchNotifier::new(
+                        self.workspace.clone(),
+                        self.core_rpc.clone(),
+                        self.proxy_rpc.clone(),
+                    ),
+                    Duration::from_millis(file_watcher_debounce_ms),
+                );
                 if let Some(workspace) = self.workspace.as_ref() {
                     self.file_watcher
                         .watch(workspace, true, WORKSPACE_EVENT_TOKEN);
@@ -105,16 +115,75 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 self.core_rpc.notification(CoreNotification::OpenPaths {
                     window_tab_id: Some((self.window_id, self.tab_id)),
                     folders,
-                    files,
+                    files: files.into_iter().map(FileLocation::from).collect(),
+                    urls: Vec::new(),
                 });
             }
+            RestoreBuffer {
+                buffer_id,
+                path,
+                content,
+                rev,
+                encoding,
+                large_file,
+            } => {
+                let mut buffer = Buffer::new(buffer_id, path.clone());
+                if let Some(encoding) = Encoding::for_label(encoding.as_bytes()) {
+                    buffer.encoding.encoding = encoding;
+                }
+                buffer.rope = Rope::from(content);
+                buffer.rev = rev;
+                buffer.large_file = large_file;
+                if !large_file {
+                    self.catalog_rpc.did_open_document(
+                        &path,
+                        buffer.language_id.to_string(),
+                        buffer.rev as i32,
+                        buffer.rope.to_string(),
+                    );
+                }
+                self.file_watcher.watch(&path, false, OPEN_FILE_EVENT_TOKEN);
+                self.buffers.insert(path, buffer);
+            }
             OpenFileChanged { path } => {
-                if let Some(buffer) = self.buffers.get(&path) {
-                    if get_mod_time(&buffer.path) == buffer.mod_time {
-                        return;
+                if let Some(buffer) = self.buffers.get_mut(&path) {
+                    if path.exists() {
+                        // A file replaced by renaming another one over it, like
+                        // editors save files, isn't watched anymore
+                        self.file_watcher.unwatch(&path, OPEN_FILE_EVENT_TOKEN);
+                        self.file_watcher.watch(&path, false, OPEN_FILE_EVENT_TOKEN);
+                    }
+                    match buffer.disk_change() {
+                        Some(DiskChange::Modified(content)) => {
+                            self.core_rpc.open_file_changed(path, content);
+                        }
+                        Some(DiskChange::Deleted) => {
+                            self.core_rpc.open_file_deleted(path);
+                        }
+                        None => {}
+                    }
+                }
+            }
+            CloseBuffer { path } => {
+                if let Some(buffer) = self.buffers.remove(&path) {
+                    self.file_watcher.unwatch(&path, OPEN_FILE_EVENT_TOKEN);
+                    if !buffer.large_file {
+                        self.catalog_rpc.did_close_document(&path);
                     }
-                    if let Ok(content) = load_file(&buffer.path) {
-                        self.core_rpc.open_file_changed(path, content);
+                }
+            }
+            SetLargeFileMode { path, large_file } => {
+                // The language servers can't be detached from a file, so they
+                // stay attached when the mode is turned on again
+                if let Some(buffer) = self.buffers.get_mut(&path) {
+                    if buffer.large_file && !large_file {
+                        buffer.large_file = false;
+                        self.catalog_rpc.did_open_document(
+                            &path,
+                            buffer.language_id.to_string(),
+                            buffer.rev as i32,
+                            buffer.rope.to_string(),
+                        );
                     }
                 }
             }
@@ -127,6 +196,9 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 self.catalog_rpc
                     .completion(request_id, &path, input, position);
             }
+            CancelCompletion {} => {
+                self.catalog_rpc.cancel_completion();
+            }
             SignatureHelp {
                 request_id,
                 path,
@@ -143,16 +215,22 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 self.proxy_rpc.shutdown();
             }
             Update { path, delta, rev } => {
-                let buffer = self.buffers.get_mut(&path).unwrap();
+                // The buffer isn't open when the proxy was restarted and the
+                // editor couldn't restore it
+                let Some(buffer) = self.buffers.get_mut(&path) else {
+                    return;
+                };
                 let old_text = buffer.rope.clone();
                 buffer.update(&delta, rev);
-                self.catalog_rpc.did_change_text_document(
-                    &path,
-                    rev,
-                    delta,
-                    old_text,
-                    buffer.rope.clone(),
-                );
+                if !buffer.large_file {
+                    self.catalog_rpc.did_change_text_document(
+                        &path,
+                        rev,
+                        delta,
+                        old_text,
+                        buffer.rope.clone(),
+                    );
+                }
             }
             UpdatePluginConfigs { configs } => {
                 let _ = self.catalog_rpc.update_plugin_configs(configs);
@@ -200,11 +278,15 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 }
             }
             InstallVolt { volt } => {
+                register_volt_download(&volt.id());
                 let catalog_rpc = self.catalog_rpc.clone();
                 let _ = catalog_rpc.install_volt(volt);
             }
-            ReloadVolt { volt } => {
-                let _ = self.catalog_rpc.reload_volt(volt);
+            ReloadVolt { volt_id } => {
+                let _ = self.catalog_rpc.reload_volt(volt_id);
+            }
+            CancelVoltDownload { volt_id } => {
+                cancel_volt_download(volt_id);
             }
             RemoveVolt { volt } => {
                 let catalog_rpc = self.catalog_rpc.clone();
@@ -219,6 +301,9 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
             EnableVolt { volt } => {
                 let _ = self.catalog_rpc.enable_volt(volt);
             }
+            GrantVoltPermissions { volt_id, granted } => {
+                let _ = self.catalog_rpc.grant_volt_permissions(volt_id, granted);
+            }
             GitCommit { message, diffs } => {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_commit(workspace, &message, diffs) {
@@ -254,6 +339,30 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     }
                 }
             }
+            GitStageHunk { path, hunk } => {
+                if let Some(workspace) = self.workspace.as_ref() {
+                    match git_stage_hunk(workspace, &path, &hunk) {
+                        Ok(()) => (),
+                        Err(e) => eprintln!("{e:?}"),
+                    }
+                }
+            }
+            GitUnstageHunk { path, hunk } => {
+                if let Some(workspace) = self.workspace.as_ref() {
+                    match git_unstage_hunk(workspace, &path, &hunk) {
+                        Ok(()) => (),
+                        Err(e) => eprintln!("{e:?}"),
+                    }
+                }
+            }
+            SelectPluginPaletteItem {
+                plugin_id,
+                provider,
+                id,
+            } => {
+                self.catalog_rpc
+                    .select_palette_item(plugin_id, provider, id);
+            }
             GitInit {} => {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_init(workspace) {
@@ -268,22 +377,64 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
     fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
         use ProxyRequest::*;
         match rpc {
-            NewBuffer { buffer_id, path } => {
-                let buffer = Buffer::new(buffer_id, path.clone());
-                let content = buffer.rope.to_string();
-                self.catalog_rpc.did_open_document(
-                    &path,
-                    buffer.language_id.to_string(),
-                    buffer.rev as i32,
-                    content.clone(),
-                );
+            NewBuffer {
+                buffer_id,
+                path,
+                large_file_threshold,
+            } => {
+                let mut buffer = Buffer::new(buffer_id, path.clone());
+                buffer.large_file = buffer.is_large(large_file_threshold);
+                // With the CR line endings of the file, for the editor to read it
+                // the same way
+                let content = buffer.file_content().to_string();
+                let encoding = buffer.encoding.name().to_string();
+                let large_file = buffer.large_file;
+                if !large_file {
+                    self.catalog_rpc.did_open_document(
+                        &path,
+                        buffer.language_id.to_string(),
+                        buffer.rev as i32,
+                        buffer.get_document(),
+                    );
+                }
                 self.file_watcher.watch(&path, false, OPEN_FILE_EVENT_TOKEN);
                 self.buffers.insert(path, buffer);
                 self.respond_rpc(
                     id,
-                    Ok(ProxyResponse::NewBufferResponse { content }),
+                    Ok(ProxyResponse::NewBufferResponse {
+                        content,
+                        encoding,
+                        large_file,
+                    }),
                 );
             }
+            ReopenWithEncoding { path, encoding } => {
+                let result = match (
+                    self.buffers.get_mut(&path),
+                    Encoding::for_label(encoding.as_bytes()),
+                ) {
+                    (Some(buffer), Some(encoding)) => buffer
+                        .reopen_with_encoding(encoding)
+                        .map(|content| ProxyResponse::NewBufferResponse {
+                            content,
+                            encoding: buffer.encoding.name().to_string(),
+                            large_file: buffer.large_file,
+                        })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        }),
+                    (None, _) => Err(RpcError {
+                        code: 0,
+                        message: "buffer not found".to_string(),
+                    }),
+                    (_, None) => Err(RpcError {
+                        code: 0,
+                        message: format!("unknown encoding {encoding}"),
+                    }),
+                };
+                self.respond_rpc(id, result);
+            }
             BufferHead { path } => {
                 let result = if let Some(workspace) = self.workspace.as_ref() {
                     let result = file_get_head(workspace, &path);
@@ -307,11 +458,13 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 self.respond_rpc(id, result);
             }
             GlobalSearch {
+                search_id,
                 pattern,
                 case_sensitive,
             } => {
-                static WORKER_ID: AtomicU64 = AtomicU64::new(0);
-                let our_id = WORKER_ID.fetch_add(1, Ordering::SeqCst) + 1;
+                // Starting a new search expires the one that is still running
+                static CURRENT_SEARCH_ID: AtomicU64 = AtomicU64::new(0);
+                CURRENT_SEARCH_ID.store(search_id, Ordering::SeqCst);
 
                 let workspace = self.workspace.clone();
                 let buffers = self
@@ -321,27 +474,40 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     .cloned()
                     .collect::<Vec<PathBuf>>();
                 let proxy_rpc = self.proxy_rpc.clone();
+                let core_rpc = self.core_rpc.clone();
 
                 // Perform the search on another thread to avoid blocking the proxy thread
                 thread::spawn(move || {
-                    proxy_rpc.handle_response(
-                        id,
-                        search_in_path(
-                            our_id,
-                            &WORKER_ID,
-                            workspace
-                                .iter()
-                                .flat_map(|w| ignore::Walk::new(w).flatten())
-                                .chain(
-                                    buffers.iter().flat_map(|p| {
-                                        ignore::Walk::new(p).flatten()
-                                    }),
-                                )
-                                .map(|p| p.into_path()),
-                            &pattern,
-                            case_sensitive,
-                        ),
+                    let (batch_tx, batch_rx) =
+                        crossbeam_channel::bounded(SEARCH_BATCH_CAPACITY);
+                    let forwarder = thread::spawn(move || {
+                        for matches in batch_rx {
+                            core_rpc.global_search_matches(search_id, matches);
+                        }
+                    });
+
+                    let result = search_in_path(
+                        search_id,
+                        &CURRENT_SEARCH_ID,
+                        workspace
+                            .iter()
+                            .flat_map(|w| ignore::Walk::new(w).flatten())
+                            .chain(
+                                buffers
+                                    .iter()
+                                    .flat_map(|p| ignore::Walk::new(p).flatten()),
+                            )
+                            .map(|p| p.into_path()),
+                        &pattern,
+                        case_sensitive,
+                        &batch_tx,
                     );
+
+                    // Wait for the batches to be sent, so that they arrive before
+                    // the response that ends the search
+                    drop(batch_tx);
+                    let _ = forwarder.join();
+                    proxy_rpc.handle_response(id, result);
                 });
             }
             CompletionResolve {
@@ -362,6 +528,24 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
+            GetPluginPaletteItems {
+                plugin_id,
+                provider,
+                query,
+            } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.palette_items(
+                    plugin_id,
+                    provider,
+                    query,
+                    move |result| {
+                        let result = result.map(|items| {
+                            ProxyResponse::GetPluginPaletteItemsResponse { items }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             GetHover {
                 request_id,
                 path,
@@ -401,6 +585,34 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     }
                 }
             }
+            GitBlame { path } => {
+                let workspace = match self.workspace.clone() {
+                    Some(workspace) => workspace,
+                    None => {
+                        self.respond_rpc(
+                            id,
+                            Err(RpcError {
+                                code: 0,
+                                message: "no workspace set".to_string(),
+                            }),
+                        );
+                        return;
+                    }
+                };
+                // Blame what the editor has, so that edited lines show up as
+                // uncommitted
+                let content = self.buffers.get(&path).map(|b| b.rope.to_string());
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = git_blame(&workspace, &path, content.as_deref())
+                        .map(|hunks| ProxyResponse::GitBlameResponse { hunks })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
             GetDefinition {
                 request_id,
                 path,
@@ -441,13 +653,12 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
-            GetInlayHints { path } => {
+            GetInlayHints { path, range } => {
                 let proxy_rpc = self.proxy_rpc.clone();
-                let buffer = self.buffers.get(&path).unwrap();
-                let range = Range {
-                    start: Position::new(0, 0),
-                    end: buffer.offset_to_position(buffer.len()),
-                };
+                if !self.buffers.contains_key(&path) {
+                    self.respond_rpc(id, Err(buffer_not_found()));
+                    return;
+                }
                 self.catalog_rpc
                     .get_inlay_hints(&path, range, move |_, result| {
                         let result = result
@@ -455,8 +666,35 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         proxy_rpc.handle_response(id, result);
                     });
             }
+            GetDocumentColors { path } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc
+                    .get_document_colors(&path, move |_, result| {
+                        let result = result.map(|colors| {
+                            ProxyResponse::GetDocumentColors { colors }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    });
+            }
+            GetColorPresentations { path, color, range } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_color_presentations(
+                    &path,
+                    color,
+                    range,
+                    move |_, result| {
+                        let result = result.map(|presentations| {
+                            ProxyResponse::GetColorPresentations { presentations }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             GetSemanticTokens { path } => {
-                let buffer = self.buffers.get(&path).unwrap();
+                let Some(buffer) = self.buffers.get(&path) else {
+                    self.respond_rpc(id, Err(buffer_not_found()));
+                    return;
+                };
                 let text = buffer.rope.clone();
                 let rev = buffer.rev;
                 let len = buffer.len();
@@ -504,13 +742,13 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
             }
             GetCodeActions {
                 path,
-                position,
+                range,
                 diagnostics,
             } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.get_code_actions(
                     &path,
-                    position,
+                    range,
                     diagnostics,
                     move |plugin_id, result| {
                         let result = result.map(|resp| {
@@ -549,6 +787,47 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         proxy_rpc.handle_response(id, result);
                     });
             }
+            GetDocumentRangeFormatting { path, ranges } => {
+                if ranges.is_empty() {
+                    self.respond_rpc(
+                        id,
+                        Ok(ProxyResponse::GetDocumentFormatting {
+                            edits: Vec::new(),
+                        }),
+                    );
+                    return;
+                }
+                // The number of ranges left to format with the edits of the
+                // others, until they're all answered together, or taken by
+                // the first error
+                let pending = Arc::new(Mutex::new(Some((ranges.len(), Vec::new()))));
+                for range in ranges {
+                    let proxy_rpc = self.proxy_rpc.clone();
+                    let pending = pending.clone();
+                    self.catalog_rpc.get_document_range_formatting(
+                        &path,
+                        range,
+                        move |_, result| {
+                            let mut pending = pending.lock();
+                            let result = match (pending.take(), result) {
+                                (Some((left, mut edits)), Ok(mut range_edits)) => {
+                                    edits.append(&mut range_edits);
+                                    if left > 1 {
+                                        *pending = Some((left - 1, edits));
+                                        return;
+                                    }
+                                    Ok(ProxyResponse::GetDocumentFormatting {
+                                        edits,
+                                    })
+                                }
+                                (Some(_), Err(err)) => Err(err),
+                                (None, _) => return,
+                            };
+                            proxy_rpc.handle_response(id, result);
+                        },
+                    );
+                }
+            }
             PrepareRename { path, position } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.prepare_rename(
@@ -638,31 +917,20 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
             ReadDir { path } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 thread::spawn(move || {
-                    let result = fs::read_dir(path)
-                        .map(|entries| {
-                            let items = entries
-                                .into_iter()
-                                .filter_map(|entry| {
-                                    entry
-                                        .map(|e| {
-                                            (
-                                                e.path(),
-                                                FileNodeItem {
-                                                    path_buf: e.path(),
-                                                    is_dir: e.path().is_dir(),
-                                                    open: false,
-                                                    read: false,
-                                                    children: HashMap::new(),
-                                                    children_open_count: 0,
-                                                },
-                                            )
-                                        })
-                                        .ok()
-                                })
-                                .collect::<HashMap<PathBuf, FileNodeItem>>();
-
-                            ProxyResponse::ReadDirResponse { items }
-                        })
+                    let result = read_dir(&path)
+                        .map(|items| ProxyResponse::ReadDirResponse { items })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
+            ReadUrl { url } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = read_url(&url)
+                        .map(|content| ProxyResponse::ReadUrlResponse { content })
                         .map_err(|e| RpcError {
                             code: 0,
                             message: e.to_string(),
@@ -670,13 +938,23 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     proxy_rpc.handle_response(id, result);
                 });
             }
-            Save { rev, path } => {
-                let buffer = self.buffers.get_mut(&path).unwrap();
+            Save {
+                rev,
+                path,
+                cr_line_endings,
+            } => {
+                let Some(buffer) = self.buffers.get_mut(&path) else {
+                    self.respond_rpc(id, Err(buffer_not_found()));
+                    return;
+                };
+                buffer.cr_line_endings = cr_line_endings;
                 let result = buffer
                     .save(rev)
                     .map(|_r| {
-                        self.catalog_rpc
-                            .did_save_text_document(&path, buffer.rope.clone());
+                        if !buffer.large_file {
+                            self.catalog_rpc
+                                .did_save_text_document(&path, buffer.rope.clone());
+                        }
                         ProxyResponse::SaveResponse {}
                     })
                     .map_err(|e| RpcError {
@@ -690,10 +968,12 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 path,
                 rev,
                 content,
+                cr_line_endings,
             } => {
                 let mut buffer = Buffer::new(buffer_id, path.clone());
                 buffer.rope = Rope::from(content);
                 buffer.rev = rev;
+                buffer.cr_line_endings = cr_line_endings;
                 let result = buffer
                     .save(rev)
                     .map(|_| ProxyResponse::Success {})
@@ -788,6 +1068,22 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 };
                 self.respond_rpc(id, result);
             }
+            FileOperations { operations } => {
+                let result = apply_file_operations(&operations)
+                    .map(|untrashed| {
+                        for operation in operations.iter() {
+                            if let FileOperation::Move { from, to, .. } = operation {
+                                self.move_buffers(from, to);
+                            }
+                        }
+                        ProxyResponse::FileOperationsResponse { untrashed }
+                    })
+                    .map_err(|e| RpcError {
+                        code: 0,
+                        message: e.to_string(),
+                    });
+                self.respond_rpc(id, result);
+            }
             GetSelectionRange { positions, path } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.get_selection_range(
@@ -801,6 +1097,17 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
+            GetFoldingRange { path } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_folding_range(
+                    path.as_path(),
+                    move |_, result| {
+                        let result = result
+                            .map(|ranges| ProxyResponse::GetFoldingRange { ranges });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             CodeActionResolve {
                 action_item,
                 plugin_id,
@@ -819,6 +1126,18 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
+            ExecuteCommand { plugin_id, command } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.execute_command(
+                    command,
+                    plugin_id,
+                    move |result| {
+                        let result =
+                            result.map(|_| ProxyResponse::ExecuteCommandResponse {});
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
         }
     }
 }
@@ -846,13 +1165,46 @@ pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
     fn respond_rpc(&self, id: RequestId, result: Result<ProxyResponse, RpcError>) {
         self.proxy_rpc.handle_response(id, result);
     }
+
+    /// Keep the buffers of the files moved from `from` to `to` open at their
+    /// new path, so that they're saved there
+    fn move_buffers(&mut self, from: &Path, to: &Path) {
+        let moved = self
+            .buffers
+            .keys()
+            .filter_map(|path| Some((path.clone(), moved_path(from, to, path)?)))
+            .collect::<Vec<_>>();
+        for (old_path, new_path) in moved {
+            if let Some(mut buffer) = self.buffers.remove(&old_path) {
+                self.file_watcher.unwatch(&old_path, OPEN_FILE_EVENT_TOKEN);
+                self.file_watcher
+                    .watch(&new_path, false, OPEN_FILE_EVENT_TOKEN);
+                if !buffer.large_file {
+                    self.catalog_rpc.did_open_document(
+                        &new_path,
+                        buffer.language_id.to_string(),
+                        buffer.rev as i32,
+                        buffer.rope.to_string(),
+                    );
+                }
+                buffer.path = new_path.clone();
+                self.buffers.insert(new_path, buffer);
+            }
+        }
+    }
+}
+
+/// What a change in the workspace needs to update
+#[derive(Clone, Copy, Default)]
+struct WorkspaceChange {
+    explorer: bool,
+    settings: bool,
 }
 
 struct FileWatchNotifier {
     core_rpc: CoreRpcHandler,
     proxy_rpc: ProxyRpcHandler,
     workspace: Option<PathBuf>,
-    workspace_fs_change_handler: Arc<Mutex<Option<Sender<bool>>>>,
     last_diff: Arc<Mutex<DiffInfo>>,
 }
 
@@ -872,7 +1224,6 @@ fn new(
             workspace,
             core_rpc,
             proxy_rpc,
-            workspace_fs_change_handler: Arc::new(Mutex::new(None)),
             last_diff: Arc::new(Mutex::new(DiffInfo::default())),
         };
 
@@ -891,17 +1242,28 @@ fn new(
     }
 
     fn handle_fs_events(&self, events: Vec<(WatchToken, notify::Event)>) {
+        let mut workspace_change: Option<WorkspaceChange> = None;
         for (token, event) in events {
             match token {
                 OPEN_FILE_EVENT_TOKEN => self.handle_open_file_fs_event(event),
-                WORKSPACE_EVENT_TOKEN => self.handle_workspace_fs_event(event),
+                WORKSPACE_EVENT_TOKEN => {
+                    if let Some(change) = self.workspace_change(&event) {
+                        let batch =
+                            workspace_change.get_or_insert_with(Default::default);
+                        batch.explorer |= change.explorer;
+                        batch.settings |= change.settings;
+                    }
+                }
                 _ => {}
             }
         }
+        if let Some(change) = workspace_change {
+            self.refresh_workspace(change);
+        }
     }
 
     fn handle_open_file_fs_event(&self, event: notify::Event) {
-        if event.kind.is_modify() {
+        if changes_open_file(&event.kind) {
             for path in event.paths {
                 self.proxy_rpc
                     .notification(ProxyNotification::OpenFileChanged { path });
@@ -909,69 +1271,49 @@ fn handle_open_file_fs_event(&self, event: notify::Event) {
         }
     }
 
-    fn handle_workspace_fs_event(&self, event: notify::Event) {
-        let explorer_change = match &event.kind {
+    /// What the event changed in the workspace, `None` when it's nothing that
+    /// has to be refreshed
+    fn workspace_change(&self, event: &notify::Event) -> Option<WorkspaceChange> {
+        let explorer = match &event.kind {
             notify::EventKind::Create(_)
             | notify::EventKind::Remove(_)
             | notify::EventKind::Modify(notify::event::ModifyKind::Name(_)) => true,
             notify::EventKind::Modify(_) => false,
-            _ => return,
+            _ => return None,
         };
+        let settings = self.workspace.as_ref().map_or(false, |workspace| {
+            let settings = workspace.join(".lapce").join("settings.toml");
+            event.paths.contains(&settings)
+        });
+        Some(WorkspaceChange { explorer, settings })
+    }
 
-        let mut handler = self.workspace_fs_change_handler.lock();
-        if let Some(sender) = handler.as_mut() {
-            if explorer_change {
-                // only send the value if we need to update file explorer as well
-                let _ = sender.send(explorer_change);
-            }
-            return;
+    /// Refresh the explorer, the settings and the source control once for the
+    /// whole batch of events
+    fn refresh_workspace(&self, change: WorkspaceChange) {
+        if change.explorer {
+            self.core_rpc.workspace_file_change();
         }
-        let (sender, receiver) = crossbeam_channel::unbounded();
-        if explorer_change {
-            // only send the value if we need to update file explorer as well
-            let _ = sender.send(explorer_change);
+        if change.settings {
+            self.core_rpc.workspace_settings_change();
         }
-
-        let local_handler = self.workspace_fs_change_handler.clone();
-        let core_rpc = self.core_rpc.clone();
-        let workspace = self.workspace.clone().unwrap();
-        let last_diff = self.last_diff.clone();
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(500));
-
-            {
-                local_handler.lock().take();
-            }
-
-            let mut explorer_change = false;
-            for e in receiver {
-                if e {
-                    explorer_change = true;
-                    break;
-                }
-            }
-            if explorer_change {
-                core_rpc.workspace_file_change();
+        if let Some(diff) = self.workspace.as_deref().and_then(git_diff_new) {
+            let mut last_diff = self.last_diff.lock();
+            if diff != *last_diff {
+                self.core_rpc.diff_info(diff.clone());
+                *last_diff = diff;
             }
-            if let Some(diff) = git_diff_new(&workspace) {
-                let mut last_diff = last_diff.lock();
-                if diff != *last_diff {
-                    core_rpc.diff_info(diff.clone());
-                    *last_diff = diff;
-                }
-            }
-        });
-        *handler = Some(sender);
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct DiffHunk {
-    pub old_start: u32,
-    pub old_lines: u32,
-    pub new_start: u32,
-    pub new_lines: u32,
-    pub header: String,
+/// The error of a request about a buffer which isn't open, like after the proxy
+/// was restarted when the editor couldn't restore it
+fn buffer_not_found() -> RpcError {
+    RpcError {
+        code: 0,
+        message: "buffer not found".to_string(),
+    }
 }
 
 fn git_init(workspace_path: &Path) -> Result<()> {
@@ -1067,6 +1409,192 @@ fn git_discard_workspace_changes(workspace_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Stages a single hunk of the working tree changes by writing the index
+/// content with the hunk's lines taken from the working tree.
+fn git_stage_hunk(
+    workspace_path: &Path,
+    path: &Path,
+    hunk: &DiffHunk,
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let mut index = repo.index()?;
+    let entry = index
+        .get_path(path.strip_prefix(workspace_path)?, 0)
+        .ok_or_else(|| anyhow!("{} is not in the index", path.display()))?;
+    let staged = git_blob_text(&repo, entry.id)?;
+    let worktree = fs::read_to_string(path)?;
+    if !text_hunks(&staged, &worktree, false).contains(hunk) {
+        return Err(anyhow!("hunk {hunk:?} of {} is outdated", path.display()));
+    }
+
+    let content =
+        replace_hunk_lines(&staged, hunk.old.clone(), &worktree, hunk.new.clone());
+    index.add_frombuffer(&entry, content.as_bytes())?;
+    index.write()?;
+    Ok(())
+}
+
+/// Unstages a single hunk of the staged changes by writing the index
+/// content with the hunk's lines taken back from HEAD.
+fn git_unstage_hunk(
+    workspace_path: &Path,
+    path: &Path,
+    hunk: &DiffHunk,
+) -> Result<()> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative_path = path.strip_prefix(workspace_path)?;
+    let mut index = repo.index()?;
+    let entry = index
+        .get_path(relative_path, 0)
+        .ok_or_else(|| anyhow!("{} is not in the index", path.display()))?;
+    let staged = git_blob_text(&repo, entry.id)?;
+    let head = git_blob_text(
+        &repo,
+        repo.head()?.peel_to_tree()?.get_path(relative_path)?.id(),
+    )?;
+    if !text_hunks(&head, &staged, true).contains(hunk) {
+        return Err(anyhow!("hunk {hunk:?} of {} is outdated", path.display()));
+    }
+
+    let content =
+        replace_hunk_lines(&staged, hunk.new.clone(), &head, hunk.old.clone());
+    index.add_frombuffer(&entry, content.as_bytes())?;
+    index.write()?;
+    Ok(())
+}
+
+fn git_blame(
+    workspace_path: &Path,
+    path: &Path,
+    content: Option<&str>,
+) -> Result<Vec<BlameHunk>> {
+    let repo = Repository::discover(workspace_path)?;
+    let relative_path = path.strip_prefix(workspace_path)?;
+    let content = match content {
+        Some(content) => content.to_string(),
+        None => fs::read_to_string(path)?,
+    };
+
+    // A file that isn't in HEAD yet is uncommitted as a whole
+    if repo
+        .head()?
+        .peel_to_tree()?
+        .get_path(relative_path)
+        .is_err()
+    {
+        return Ok(vec![BlameHunk {
+            lines: 0..content.lines().count().max(1),
+            author: String::new(),
+            time: 0,
+            summary: String::new(),
+            uncommitted: true,
+        }]);
+    }
+
+    let blame = repo
+        .blame_file(relative_path, None)?
+        .blame_buffer(content.as_bytes())?;
+    let mut hunks = Vec::new();
+    for hunk in blame.iter() {
+        // Blame lines are one based
+        let start = hunk.final_start_line().saturating_sub(1);
+        let lines = start..start + hunk.lines_in_hunk();
+        if hunk.final_commit_id().is_zero() {
+            hunks.push(BlameHunk {
+                lines,
+                author: String::new(),
+                time: 0,
+                summary: String::new(),
+                uncommitted: true,
+            });
+            continue;
+        }
+
+        let commit = repo.find_commit(hunk.final_commit_id())?;
+        let signature = hunk.final_signature();
+        hunks.push(BlameHunk {
+            lines,
+            author: signature.name().unwrap_or("").to_string(),
+            time: signature.when().seconds(),
+            summary: commit.summary().unwrap_or("").to_string(),
+            uncommitted: false,
+        });
+    }
+    Ok(hunks)
+}
+
+fn git_blob_text(repo: &Repository, id: git2::Oid) -> Result<String> {
+    let blob = repo.find_blob(id)?;
+    Ok(std::str::from_utf8(blob.content())?.to_string())
+}
+
+/// Computes the hunks between two versions of a file with the same line
+/// diff as the editor gutter.
+fn text_hunks(old: &str, new: &str, staged: bool) -> Vec<DiffHunk> {
+    let changes = rope_diff(
+        Rope::from(old),
+        Rope::from(new),
+        0,
+        Arc::new(AtomicU64::new(0)),
+        3,
+    )
+    .unwrap_or_default();
+    diff_hunks(&changes)
+        .into_iter()
+        .map(|(old, new)| DiffHunk { old, new, staged })
+        .collect()
+}
+
+/// Replaces `target_lines` of `target` with `source_lines` of `source`.
+///
+/// The line diff ignores line endings, so the inserted lines take the line
+/// ending `target` already uses. That keeps CRLF files intact and keeps
+/// content that `core.autocrlf` normalized in the index normalized.
+fn replace_hunk_lines(
+    target: &str,
+    target_lines: std::ops::Range<usize>,
+    source: &str,
+    source_lines: std::ops::Range<usize>,
+) -> String {
+    let ending = if target.contains("\r\n") {
+        Some("\r\n")
+    } else if target.contains('\n') {
+        Some("\n")
+    } else {
+        None
+    };
+
+    let target: Vec<&str> = target.split_inclusive('\n').collect();
+    let source: Vec<&str> = source.split_inclusive('\n').collect();
+    let lines = target[..target_lines.start]
+        .iter()
+        .map(|line| (*line, false))
+        .chain(source[source_lines].iter().map(|line| (*line, true)))
+        .chain(target[target_lines.end..].iter().map(|line| (*line, false)))
+        .collect::<Vec<_>>();
+
+    let mut content = String::new();
+    for (i, (line, from_source)) in lines.iter().enumerate() {
+        let text = line
+            .strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(line);
+        let line_ending = &line[text.len()..];
+        content.push_str(text);
+        if line_ending.is_empty() {
+            // A last line without a line ending that isn't last anymore
+            if i + 1 < lines.len() {
+                content.push_str(ending.unwrap_or("\n"));
+            }
+        } else if *from_source {
+            content.push_str(ending.unwrap_or(line_ending));
+        } else {
+            content.push_str(line_ending);
+        }
+    }
+    content
+}
+
 fn git_delta_format(
     workspace_path: &Path,
     delta: &git2::DiffDelta,
@@ -1102,11 +1630,17 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
     }
 
     let mut deltas = Vec::new();
+    let mut hunks: HashMap<PathBuf, Vec<DiffHunk>> = HashMap::new();
     let mut diff_options = DiffOptions::new();
     let diff = repo
         .diff_index_to_workdir(None, Some(diff_options.include_untracked(true)))
         .ok()?;
     for delta in diff.deltas() {
+        if let Some((path, file_hunks)) =
+            git_delta_hunks(&repo, workspace_path, &delta, false)
+        {
+            hunks.entry(path).or_default().extend(file_hunks);
+        }
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
             deltas.push(delta);
         }
@@ -1121,6 +1655,11 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         )
         .ok()?;
     for delta in cached_diff.deltas() {
+        if let Some((path, file_hunks)) =
+            git_delta_hunks(&repo, workspace_path, &delta, true)
+        {
+            hunks.entry(path).or_default().extend(file_hunks);
+        }
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
             deltas.push(delta);
         }
@@ -1170,9 +1709,31 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         head: name,
         branches,
         diffs: file_diffs,
+        hunks,
     })
 }
 
+/// The hunks of a modified file, staged ones are read from the odb on both
+/// sides while unstaged ones read the new side from the working tree.
+fn git_delta_hunks(
+    repo: &Repository,
+    workspace_path: &Path,
+    delta: &git2::DiffDelta,
+    staged: bool,
+) -> Option<(PathBuf, Vec<DiffHunk>)> {
+    if delta.status() != git2::Delta::Modified {
+        return None;
+    }
+    let path = workspace_path.join(delta.new_file().path()?);
+    let old = git_blob_text(repo, delta.old_file().id()).ok()?;
+    let new = if staged {
+        git_blob_text(repo, delta.new_file().id()).ok()?
+    } else {
+        fs::read_to_string(&path).ok()?
+    };
+    Some((path, text_hunks(&old, &new, staged)))
+}
+
 fn file_get_head(workspace_path: &Path, path: &Path) -> Result<(String, String)> {
     let repo = Repository::discover(workspace_path)?;
     let head = repo.head()?;
@@ -1227,14 +1788,255 @@ fn git_get_remote_file_url(workspace_path: &Path, file: &Path) -> Result<String>
     Ok(url)
 }
 
+fn read_url(url: &str) -> Result<String> {
+    let content = reqwest::blocking::get(url)?.error_for_status()?.text()?;
+    Ok(content)
+}
+
+/// The number of batches of search matches that can be waiting to be sent to the
+/// core before the search pauses
+const SEARCH_BATCH_CAPACITY: usize = 4;
+/// How long the matches are collected before they are sent to the core as a batch
+const SEARCH_BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The entries of the directory, which are marked as ignored when they're
+/// matched by the ignore files of the directory or of the ones above it
+fn read_dir(path: &Path) -> std::io::Result<HashMap<PathBuf, FileNodeItem>> {
+    let not_ignored = ignore::WalkBuilder::new(path)
+        .max_depth(Some(1))
+        .hidden(false)
+        .require_git(false)
+        .build()
+        .flatten()
+        .map(|entry| entry.into_path())
+        .collect::<HashSet<PathBuf>>();
+    let items = fs::read_dir(path)?
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let item = FileNodeItem {
+                path_buf: path.clone(),
+                is_dir: path.is_dir(),
+                open: false,
+                read: false,
+                children: HashMap::new(),
+                children_open_count: 0,
+                ignored: !not_ignored.contains(&path),
+                hidden: false,
+            };
+            (path, item)
+        })
+        .collect();
+    Ok(items)
+}
+
+/// Check that the operation can be applied, before any operation of its batch
+/// is applied
+fn check_file_operation(operation: &FileOperation) -> Result<()> {
+    match operation {
+        FileOperation::Move { from, to, .. } | FileOperation::Copy { from, to } => {
+            let overwrite = matches!(
+                operation,
+                FileOperation::Move {
+                    overwrite: true,
+                    ..
+                }
+            );
+            if !from.exists() {
+                return Err(anyhow!("{from:?} doesn't exist"));
+            }
+            if to.exists() && !overwrite {
+                return Err(anyhow!("{to:?} already exists"));
+            }
+            if overwrite && from.starts_with(to) {
+                return Err(anyhow!("can't replace {to:?} by its own content"));
+            }
+            if from.is_dir() {
+                let from = from.canonicalize()?;
+                let parent = to
+                    .parent()
+                    .and_then(|parent| parent.canonicalize().ok())
+                    .ok_or_else(|| anyhow!("{to:?} has no parent directory"))?;
+                if parent.starts_with(&from) {
+                    return Err(anyhow!("can't move or copy {from:?} into itself"));
+                }
+            }
+        }
+        FileOperation::Trash { path } | FileOperation::Delete { path } => {
+            if !path.exists() {
+                return Err(anyhow!("{path:?} doesn't exist"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Move the path, putting the entry it replaces aside to be restored if the
+/// batch is undone. Returns where the replaced entry was put.
+fn move_path(from: &Path, to: &Path, overwrite: bool) -> Result<Option<PathBuf>> {
+    let replaced = if overwrite && to.exists() {
+        let name = to.file_name().unwrap_or_default().to_string_lossy();
+        let replaced = to.with_file_name(format!(
+            ".{name}.lapce-replaced-{}",
+            std::process::id()
+        ));
+        fs::rename(to, &replaced).with_context(|| format!("replacing {to:?}"))?;
+        Some(replaced)
+    } else {
+        None
+    };
+    if let Err(err) = fs::rename(from, to) {
+        if let Some(replaced) = replaced.as_ref() {
+            let _ = fs::rename(replaced, to);
+        }
+        return Err(err).with_context(|| format!("moving {from:?}"));
+    }
+    Ok(replaced)
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Apply the operations as a whole, if one of them fails the ones applied
+/// before it are undone. The moves and copies are applied first because,
+/// unlike the deletions, they can be undone.
+/// Returns the paths that couldn't be moved to the trash, which are left in
+/// place for the user to decide whether to delete them permanently.
+fn apply_file_operations(operations: &[FileOperation]) -> Result<Vec<PathBuf>> {
+    let mut destinations = HashSet::new();
+    for operation in operations {
+        check_file_operation(operation)?;
+        if let FileOperation::Move { to, .. } | FileOperation::Copy { to, .. } =
+            operation
+        {
+            if !destinations.insert(to) {
+                return Err(anyhow!("{to:?} is the destination of two operations"));
+            }
+        }
+    }
+
+    let (reversible, irreversible): (Vec<_>, Vec<_>) =
+        operations.iter().partition(|operation| {
+            matches!(
+                operation,
+                FileOperation::Move { .. } | FileOperation::Copy { .. }
+            )
+        });
+
+    // The operations applied so far, with where the entry replaced by a move was
+    // put aside
+    let mut applied: Vec<(&FileOperation, Option<PathBuf>)> = Vec::new();
+    let mut untrashed = Vec::new();
+    for operation in reversible.into_iter().chain(irreversible) {
+        let result = match operation {
+            FileOperation::Move {
+                from,
+                to,
+                overwrite,
+            } => move_path(from, to, *overwrite),
+            FileOperation::Copy { from, to } => copy_path(from, to)
+                .map(|_| None)
+                .with_context(|| format!("copying {from:?}")),
+            FileOperation::Trash { path } => {
+                if trash::delete(path).is_err() {
+                    untrashed.push(path.clone());
+                }
+                Ok(None)
+            }
+            FileOperation::Delete { path } => remove_path(path)
+                .map(|_| None)
+                .with_context(|| format!("deleting {path:?}")),
+        };
+        match result {
+            Ok(replaced) => applied.push((operation, replaced)),
+            Err(err) => {
+                for (operation, replaced) in applied.into_iter().rev() {
+                    let undone = match operation {
+                        FileOperation::Move { from, to, .. } => fs::rename(to, from)
+                            .and_then(|_| match replaced {
+                                Some(replaced) => fs::rename(replaced, to),
+                                None => Ok(()),
+                            }),
+                        FileOperation::Copy { to, .. } => remove_path(to),
+                        FileOperation::Trash { .. }
+                        | FileOperation::Delete { .. } => Ok(()),
+                    };
+                    if let Err(undo_err) = undone {
+                        log::error!("Failed to undo {operation:?}: {undo_err}");
+                    }
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    for replaced in applied.into_iter().filter_map(|(_, replaced)| replaced) {
+        if let Err(err) = remove_path(&replaced) {
+            log::warn!("Failed to remove replaced {replaced:?}: {err}");
+        }
+    }
+    Ok(untrashed)
+}
+
+fn expired_search() -> RpcError {
+    RpcError {
+        code: 0,
+        message: "expired search job".to_string(),
+    }
+}
+
+/// Send a batch of matches, waiting while the channel is full. The wait gives up
+/// as soon as the search expires so a cancelled search doesn't linger.
+fn send_search_batch(
+    id: u64,
+    current_id: &AtomicU64,
+    batch_tx: &Sender<SearchMatches>,
+    mut batch: SearchMatches,
+) -> Result<(), RpcError> {
+    loop {
+        if current_id.load(Ordering::SeqCst) != id {
+            return Err(expired_search());
+        }
+        match batch_tx.send_timeout(batch, Duration::from_millis(10)) {
+            Ok(()) => return Ok(()),
+            Err(SendTimeoutError::Timeout(b)) => batch = b,
+            Err(SendTimeoutError::Disconnected(_)) => return Err(expired_search()),
+        }
+    }
+}
+
+/// Search the files for the pattern. The matches are sent in batches through
+/// `batch_tx` while the search is running, and the ones found after the last
+/// batch are in the response.
 fn search_in_path(
     id: u64,
     current_id: &AtomicU64,
     paths: impl Iterator<Item = PathBuf>,
     pattern: &str,
     case_sensitive: bool,
+    batch_tx: &Sender<SearchMatches>,
 ) -> Result<ProxyResponse, RpcError> {
     let mut matches = IndexMap::new();
+    // The first matches are sent right away, so that they can be shown quickly
+    let mut last_batch: Option<Instant> = None;
     let pattern = regex::escape(pattern);
     let matcher = RegexMatcherBuilder::new()
         .case_insensitive(!case_sensitive)
@@ -1247,10 +2049,7 @@ fn search_in_path(
 
     for path in paths {
         if current_id.load(Ordering::SeqCst) != id {
-            return Err(RpcError {
-                code: 0,
-                message: "expired search job".to_string(),
-            });
+            return Err(expired_search());
         }
 
         if path.is_file() {
@@ -1298,7 +2097,424 @@ fn search_in_path(
                 matches.insert(path.clone(), line_matches);
             }
         }
+
+        if !matches.is_empty()
+            && last_batch
+                .map(|last| last.elapsed() >= SEARCH_BATCH_INTERVAL)
+                .unwrap_or(true)
+        {
+            send_search_batch(
+                id,
+                current_id,
+                batch_tx,
+                std::mem::take(&mut matches),
+            )?;
+            last_batch = Some(Instant::now());
+        }
+    }
+
+    if current_id.load(Ordering::SeqCst) != id {
+        return Err(expired_search());
     }
 
     Ok(ProxyResponse::GlobalSearchResponse { matches })
 }
+
+/// Whether the event may have changed the content of an open file. A file
+/// saved by writing a new one and renaming it over the old one comes as a
+/// creation once the events of the burst are merged.
+fn changes_open_file(kind: &notify::EventKind) -> bool {
+    kind.is_create() || kind.is_modify() || kind.is_remove()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use git2::{Repository, Signature};
+    use lapce_rpc::{
+        file::FileOperation, proxy::ProxyResponse, source_control::DiffHunk,
+    };
+    use notify::{
+        event::{AccessKind, CreateKind, DataChange, ModifyKind, RemoveKind},
+        EventKind,
+    };
+
+    use super::{
+        apply_file_operations, changes_open_file, git_blame, git_blob_text,
+        git_stage_hunk, git_unstage_hunk, read_dir, replace_hunk_lines,
+        search_in_path, text_hunks,
+    };
+
+    fn search_files(name: &str, contents: &[&str]) -> Vec<PathBuf> {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-search-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let path = dir.join(format!("{i}.txt"));
+                fs::write(&path, content).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    #[test]
+    fn search_streams_matches_from_slow_producer() {
+        let paths = search_files("stream", &["a needle", "no match", "needle b"]);
+        let current_id = Arc::new(AtomicU64::new(1));
+        let done = Arc::new(AtomicBool::new(false));
+        let (batch_tx, batch_rx) = crossbeam_channel::bounded(4);
+
+        let search = {
+            let current_id = current_id.clone();
+            let done = done.clone();
+            thread::spawn(move || {
+                // Every file after the first one takes a while to be found
+                let slow_paths = paths.into_iter().enumerate().map(|(i, path)| {
+                    if i > 0 {
+                        thread::sleep(Duration::from_millis(300));
+                    }
+                    path
+                });
+                let result = search_in_path(
+                    1,
+                    &current_id,
+                    slow_paths,
+                    "needle",
+                    false,
+                    &batch_tx,
+                );
+                done.store(true, Ordering::SeqCst);
+                result
+            })
+        };
+
+        let first = batch_rx.recv_timeout(Duration::from_millis(250)).unwrap();
+        assert!(!done.load(Ordering::SeqCst));
+        assert_eq!(first.len(), 1);
+        let (path, matches) = first.first().unwrap();
+        assert!(path.ends_with("0.txt"));
+        assert_eq!(matches[0].1, (2, 8));
+
+        let result = search.join().unwrap();
+        let mut rest: Vec<PathBuf> = batch_rx
+            .try_iter()
+            .flat_map(|batch| batch.into_keys())
+            .collect();
+        if let Ok(ProxyResponse::GlobalSearchResponse { matches }) = result {
+            rest.extend(matches.into_keys());
+        } else {
+            panic!("search failed");
+        }
+        assert_eq!(rest.len(), 1);
+        assert!(rest[0].ends_with("2.txt"));
+    }
+
+    #[test]
+    fn cancelled_search_stops_while_waiting_to_send() {
+        let paths = search_files("cancel", &["needle"; 8]);
+        let current_id = Arc::new(AtomicU64::new(1));
+        // Nothing receives the batches, so the search has to wait once the
+        // channel is full
+        let (batch_tx, _batch_rx) = crossbeam_channel::bounded(1);
+
+        let search = {
+            let current_id = current_id.clone();
+            thread::spawn(move || {
+                let slow_paths = paths.into_iter().map(|path| {
+                    thread::sleep(Duration::from_millis(60));
+                    path
+                });
+                search_in_path(
+                    1,
+                    &current_id,
+                    slow_paths,
+                    "needle",
+                    false,
+                    &batch_tx,
+                )
+            })
+        };
+
+        thread::sleep(Duration::from_millis(300));
+        current_id.store(2, Ordering::SeqCst);
+        assert!(search.join().unwrap().is_err());
+    }
+
+    fn hunk_repo(name: &str, head: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-hunk-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, head).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("file.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("lapce", "lapce@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        (dir, path)
+    }
+
+    fn index_text(dir: &Path) -> String {
+        let repo = Repository::open(dir).unwrap();
+        let entry = repo
+            .index()
+            .unwrap()
+            .get_path(Path::new("file.txt"), 0)
+            .unwrap();
+        git_blob_text(&repo, entry.id).unwrap()
+    }
+
+    #[test]
+    fn replace_hunk_lines_keeps_line_endings() {
+        assert_eq!(
+            replace_hunk_lines("a\r\nb\r\nc\r\n", 1..2, "a\nx\ny\nc\n", 1..3),
+            "a\r\nx\r\ny\r\nc\r\n"
+        );
+        assert_eq!(
+            replace_hunk_lines("a\nb", 2..2, "a\nb\nc\n", 2..3),
+            "a\nb\nc\n"
+        );
+        assert_eq!(
+            replace_hunk_lines("a\nb\nc\n", 1..2, "a\nc\n", 1..1),
+            "a\nc\n"
+        );
+    }
+
+    #[test]
+    fn stage_and_unstage_hunks_round_trip() {
+        let head = "1\r\n2\r\n3\r\n4\r\n5\r\n6\r\n7\r\n8\r\n9\r\n";
+        let (dir, path) = hunk_repo("round-trip", head);
+        let worktree = "1\r\nx\r\n3\r\n4\r\n5\r\n6\r\n7\r\ny\r\n9\r\n";
+        fs::write(&path, worktree).unwrap();
+
+        let hunks = text_hunks(head, worktree, false);
+        assert_eq!(hunks.len(), 2);
+        git_stage_hunk(&dir, &path, &hunks[0]).unwrap();
+        let staged = index_text(&dir);
+        assert_eq!(staged, "1\r\nx\r\n3\r\n4\r\n5\r\n6\r\n7\r\n8\r\n9\r\n");
+        assert_eq!(text_hunks(&staged, worktree, false), vec![hunks[1].clone()]);
+
+        // Change the working tree over the staged region as well
+        let worktree = "1\r\nz\r\n3\r\n4\r\n5\r\n6\r\n7\r\ny\r\n9\r\n";
+        fs::write(&path, worktree).unwrap();
+        git_stage_hunk(&dir, &path, &text_hunks(&staged, worktree, false)[1])
+            .unwrap();
+        let staged = index_text(&dir);
+        assert_eq!(staged, "1\r\nx\r\n3\r\n4\r\n5\r\n6\r\n7\r\ny\r\n9\r\n");
+
+        let staged_hunks = text_hunks(head, &staged, true);
+        assert_eq!(staged_hunks.len(), 2);
+        // An outdated hunk is refused
+        let outdated = DiffHunk {
+            old: 0..1,
+            new: 0..1,
+            staged: true,
+        };
+        assert!(git_unstage_hunk(&dir, &path, &outdated).is_err());
+        for hunk in staged_hunks.iter().rev() {
+            git_unstage_hunk(&dir, &path, hunk).unwrap();
+        }
+        assert_eq!(index_text(&dir), head);
+        assert_eq!(fs::read_to_string(&path).unwrap(), worktree);
+    }
+
+    #[test]
+    fn blame_marks_edited_lines_uncommitted() {
+        let (dir, path) = hunk_repo("blame", "a\nb\nc\n");
+        let hunks = git_blame(&dir, &path, Some("a\nx\nc\n")).unwrap();
+        let uncommitted = hunks
+            .iter()
+            .filter(|hunk| hunk.uncommitted)
+            .map(|hunk| hunk.lines.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(uncommitted, vec![1..2]);
+        let committed = hunks.iter().find(|hunk| hunk.lines.contains(&0)).unwrap();
+        assert_eq!(committed.author, "lapce");
+        assert_eq!(committed.summary, "init");
+
+        let new_file = dir.join("new.txt");
+        fs::write(&new_file, "a\nb\n").unwrap();
+        let hunks = git_blame(&dir, &new_file, None).unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert!(hunks[0].uncommitted);
+        assert_eq!(hunks[0].lines, 0..2);
+    }
+
+    #[test]
+    fn read_dir_composes_nested_ignore_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("lapce-read-dir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let sub = dir.join("sub");
+        fs::create_dir_all(sub.join("gen")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join(".gitignore"), "target\n*.log\n").unwrap();
+        fs::write(sub.join(".gitignore"), "!keep.log\ngen/\n").unwrap();
+        for file in [
+            "a.log",
+            "main.rs",
+            "sub/b.log",
+            "sub/keep.log",
+            "sub/lib.rs",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let ignored = |path: &Path| {
+            let items = read_dir(path).unwrap();
+            let mut ignored = items
+                .values()
+                .filter(|item| item.ignored)
+                .map(|item| {
+                    item.path_buf
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect::<Vec<_>>();
+            ignored.sort();
+            ignored
+        };
+        assert_eq!(ignored(&dir), vec!["a.log", "target"]);
+        assert_eq!(ignored(&sub), vec!["b.log", "gen"]);
+    }
+
+    fn file_operations_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lapce-file-operations-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("a/b/c.txt"), "c").unwrap();
+        dir
+    }
+
+    #[test]
+    fn file_operations_reject_moving_dir_into_descendant() {
+        let dir = file_operations_dir("descendant");
+        let a = dir.join("a");
+
+        for operation in [
+            FileOperation::Move {
+                from: a.clone(),
+                to: a.join("b/a"),
+                overwrite: false,
+            },
+            FileOperation::Move {
+                from: a.clone(),
+                to: a.join("a"),
+                overwrite: false,
+            },
+            FileOperation::Copy {
+                from: a.clone(),
+                to: a.join("b/a"),
+            },
+            // Replacing the directory by one of its own descendants
+            FileOperation::Move {
+                from: a.join("b"),
+                to: a.clone(),
+                overwrite: true,
+            },
+        ] {
+            assert!(apply_file_operations(&[operation]).is_err());
+        }
+        assert!(dir.join("a/b/c.txt").exists());
+        assert!(!dir.join("a/a").exists());
+        assert!(!dir.join("a/b/a").exists());
+
+        // A sibling whose name starts with the same characters isn't a descendant
+        apply_file_operations(&[FileOperation::Copy {
+            from: a.clone(),
+            to: dir.join("ab"),
+        }])
+        .unwrap();
+        assert!(dir.join("ab/b/c.txt").exists());
+    }
+
+    #[test]
+    fn file_operations_roll_back_when_one_fails() {
+        let dir = file_operations_dir("roll-back");
+        fs::write(dir.join("d.txt"), "d").unwrap();
+
+        // The second move is valid when the batch is checked, but fails once
+        // the first one moved its source away
+        let result = apply_file_operations(&[
+            FileOperation::Copy {
+                from: dir.join("d.txt"),
+                to: dir.join("e.txt"),
+            },
+            FileOperation::Move {
+                from: dir.join("a"),
+                to: dir.join("moved"),
+                overwrite: false,
+            },
+            FileOperation::Move {
+                from: dir.join("a/b/c.txt"),
+                to: dir.join("c.txt"),
+                overwrite: false,
+            },
+        ]);
+        assert!(result.is_err());
+        assert!(dir.join("a/b/c.txt").exists());
+        assert!(dir.join("d.txt").exists());
+        assert!(!dir.join("e.txt").exists());
+        assert!(!dir.join("moved").exists());
+        assert!(!dir.join("c.txt").exists());
+    }
+
+    #[test]
+    fn file_operations_restore_replaced_path_when_rolling_back() {
+        let dir = file_operations_dir("overwrite");
+        fs::create_dir_all(dir.join("x")).unwrap();
+        fs::write(dir.join("x/c.txt"), "x").unwrap();
+
+        let replace = FileOperation::Move {
+            from: dir.join("a/b/c.txt"),
+            to: dir.join("x/c.txt"),
+            overwrite: true,
+        };
+        let failing = FileOperation::Move {
+            from: dir.join("a/b/c.txt"),
+            to: dir.join("y.txt"),
+            overwrite: false,
+        };
+        assert!(apply_file_operations(&[replace.clone(), failing]).is_err());
+        assert_eq!(fs::read_to_string(dir.join("a/b/c.txt")).unwrap(), "c");
+        assert_eq!(fs::read_to_string(dir.join("x/c.txt")).unwrap(), "x");
+        assert_eq!(fs::read_dir(dir.join("x")).unwrap().count(), 1);
+
+        apply_file_operations(&[replace]).unwrap();
+        assert!(!dir.join("a/b/c.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("x/c.txt")).unwrap(), "c");
+        assert_eq!(fs::read_dir(dir.join("x")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_changes_open_file() {
+        assert!(changes_open_file(&EventKind::Create(CreateKind::File)));
+        assert!(changes_open_file(&EventKind::Modify(ModifyKind::Data(
+            DataChange::Content
+        ))));
+        assert!(changes_open_file(&EventKind::Remove(RemoveKind::File)));
+        assert!(!changes_open_file(&EventKind::Access(AccessKind::Read)));
+    }
+}
@@ -8,15 +8,17 @@
     thread,
 };
 
+use lapce_core::directory::Directory;
 use lapce_rpc::{
-    plugin::{PluginId, VoltID, VoltMetadata},
+    plugin::{PluginId, VoltCapabilities, VoltID, VoltMetadata},
     proxy::ProxyResponse,
     style::LineStyle,
     RpcError,
 };
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
-    notification::DidOpenTextDocument, DidOpenTextDocumentParams, SemanticTokens,
+    notification::{DidCloseTextDocument, DidOpenTextDocument},
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, SemanticTokens,
     TextDocumentIdentifier, TextDocumentItem, VersionedTextDocumentIdentifier,
 };
 use parking_lot::Mutex;
@@ -28,7 +30,19 @@
     wasi::{load_all_volts, start_volt},
     PluginCatalogNotification, PluginCatalogRpcHandler,
 };
-use crate::plugin::{install_volt, wasi::enable_volt};
+use crate::plugin::{
+    install_volt,
+    wasi::{enable_volt, load_volt},
+};
+
+/// Starts a volt in its sandbox, which the tests replace with a mock host
+type VoltStarter = fn(
+    Option<PathBuf>,
+    Option<HashMap<String, Value>>,
+    PluginCatalogRpcHandler,
+    VoltMetadata,
+    VoltCapabilities,
+) -> anyhow::Result<()>;
 
 pub struct PluginCatalog {
     workspace: Option<PathBuf>,
@@ -36,7 +50,13 @@ pub struct PluginCatalog {
     plugins: HashMap<PluginId, PluginServerRpcHandler>,
     plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
     unactivated_volts: HashMap<VoltID, VoltMetadata>,
+    /// The volts waiting for the user to grant them the capabilities they
+    /// asked for before they're started
+    pending_volts: HashMap<VoltID, VoltMetadata>,
+    /// The volts which were started, to reload them from their directory
+    started_volts: HashMap<VoltID, VoltMetadata>,
     open_files: HashMap<PathBuf, String>,
+    volt_starter: VoltStarter,
 }
 
 impl PluginCatalog {
@@ -52,7 +72,10 @@ pub fn new(
             plugin_configurations,
             plugins: HashMap::new(),
             unactivated_volts: HashMap::new(),
+            pending_volts: HashMap::new(),
+            started_volts: HashMap::new(),
             open_files: HashMap::new(),
+            volt_starter: start_volt,
         };
 
         thread::spawn(move || {
@@ -108,7 +131,7 @@ pub fn handle_server_request(
                 f(
                     lapce_rpc::plugin::PluginId(0),
                     Err(RpcError {
-                        code: 0,
+                        code: RpcError::NOT_CAPABLE,
                         message: "no available plugin could make a callback, because the plugins list is empty".to_string(),
                     }),
                 );
@@ -153,16 +176,91 @@ pub fn handle_server_notification(
 
     fn start_unactivated_volts(&mut self, to_be_activated: Vec<VoltID>) {
         for id in to_be_activated.iter() {
-            let workspace = self.workspace.clone();
             if let Some(meta) = self.unactivated_volts.remove(id) {
-                let configurations =
-                    self.plugin_configurations.get(&meta.name).cloned();
-                let plugin_rpc = self.plugin_rpc.clone();
-                thread::spawn(move || {
-                    let _ = start_volt(workspace, configurations, plugin_rpc, meta);
-                });
+                self.start_volt(meta);
+            }
+        }
+    }
+
+    /// Ask the user to grant the volt the capabilities it requests, which is
+    /// started once they're answered for. The user is only asked once, as the
+    /// answer is remembered by the editor.
+    fn start_volt(&mut self, meta: VoltMetadata) {
+        let requested = meta.requested_capabilities();
+        if requested.is_empty() {
+            self.start_volt_with(meta, requested);
+        } else {
+            self.plugin_rpc
+                .core_rpc
+                .volt_permissions_requested(meta.info(), requested);
+            self.pending_volts.insert(meta.id(), meta);
+        }
+    }
+
+    fn start_volt_with(&mut self, meta: VoltMetadata, granted: VoltCapabilities) {
+        self.started_volts.insert(meta.id(), meta.clone());
+        let workspace = self.workspace.clone();
+        let configurations = self.plugin_configurations.get(&meta.name).cloned();
+        let plugin_rpc = self.plugin_rpc.clone();
+        let volt_starter = self.volt_starter;
+        thread::spawn(move || {
+            let _ =
+                volt_starter(workspace, configurations, plugin_rpc, meta, granted);
+        });
+    }
+
+    /// Stop the plugins of the volt, including the language servers it
+    /// started, with the requests they didn't answer yet cancelled. Returns
+    /// whether any was running.
+    fn stop_volt_plugins(&mut self, volt_id: &VoltID) -> bool {
+        let ids: Vec<PluginId> = self
+            .plugins
+            .iter()
+            .filter(|(_, plugin)| &plugin.volt_id == volt_id)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids.iter() {
+            if let Some(plugin) = self.plugins.remove(id) {
+                plugin.cancel_all_requests();
+                plugin.shutdown();
+                // What the plugin registered in the editor goes away with it
+                self.plugin_rpc.core_rpc.plugin_server_stopped(*id);
             }
         }
+        !ids.is_empty()
+    }
+
+    /// Stop the volt and start it again from the manifest and the wasm in its
+    /// directory, without waiting for its activation if it was started already
+    fn reload_volt(&mut self, volt_id: VoltID) {
+        let started = self.started_volts.remove(&volt_id);
+        let pending = self.pending_volts.remove(&volt_id);
+        let unactivated = self.unactivated_volts.remove(&volt_id);
+        let running = self.stop_volt_plugins(&volt_id);
+        let activated = running || started.is_some() || pending.is_some();
+
+        let old_meta = started.or(pending).or(unactivated);
+        let plugin_dir = || {
+            Directory::plugins_directory().map(|dir| dir.join(volt_id.to_string()))
+        };
+        let dir = old_meta
+            .as_ref()
+            .and_then(|meta| meta.dir.clone())
+            .or_else(plugin_dir);
+        let meta = match dir.map(|dir| load_volt(&dir)) {
+            Some(Ok(meta)) => meta,
+            _ => match old_meta {
+                Some(meta) => meta,
+                None => return,
+            },
+        };
+
+        if activated {
+            self.start_volt(meta);
+        } else {
+            self.unactivated_volts.insert(meta.id(), meta);
+            self.check_unactivated_volts();
+        }
     }
 
     fn check_unactivated_volts(&mut self) {
@@ -255,6 +353,31 @@ pub fn handle_did_open_text_document(&mut self, document: TextDocumentItem) {
         }
     }
 
+    pub fn handle_did_close_text_document(
+        &mut self,
+        text_document: TextDocumentIdentifier,
+    ) {
+        let path = match text_document.uri.to_file_path() {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        let language_id = match self.open_files.remove(&path) {
+            Some(language_id) => language_id,
+            None => return,
+        };
+        for (_, plugin) in self.plugins.iter() {
+            plugin.server_notification(
+                DidCloseTextDocument::METHOD,
+                DidCloseTextDocumentParams {
+                    text_document: text_document.clone(),
+                },
+                Some(language_id.clone()),
+                Some(path.clone()),
+                true,
+            );
+        }
+    }
+
     pub fn handle_did_save_text_document(
         &mut self,
         language_id: String,
@@ -349,36 +472,33 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
                 self.plugins.insert(plugin.plugin_id, plugin);
             }
             InstallVolt(volt) => {
-                let workspace = self.workspace.clone();
-                let configurations =
-                    self.plugin_configurations.get(&volt.name).cloned();
                 let catalog_rpc = self.plugin_rpc.clone();
                 let _ = catalog_rpc.stop_volt(volt.clone());
                 thread::spawn(move || {
-                    let _ =
-                        install_volt(catalog_rpc, workspace, configurations, volt);
+                    let _ = install_volt(catalog_rpc, volt);
                 });
             }
-            ReloadVolt(volt) => {
-                let volt_id = volt.id();
-                let ids: Vec<PluginId> = self.plugins.keys().cloned().collect();
-                for id in ids {
-                    if self.plugins.get(&id).unwrap().volt_id == volt_id {
-                        let plugin = self.plugins.remove(&id).unwrap();
-                        plugin.shutdown();
-                    }
+            StartVolt(volt) => {
+                self.start_volt(volt);
+            }
+            GrantVoltPermissions(volt_id, granted) => {
+                if let Some(meta) = self.pending_volts.remove(&volt_id) {
+                    self.start_volt_with(meta, granted);
+                }
+            }
+            ReloadVolt(volt_id) => {
+                self.reload_volt(volt_id);
+            }
+            CancelRequests(method) => {
+                for (_, plugin) in self.plugins.iter() {
+                    plugin.cancel_requests(method);
                 }
-                let _ = self.plugin_rpc.unactivated_volts(vec![volt]);
             }
             StopVolt(volt) => {
                 let volt_id = volt.id();
-                let ids: Vec<PluginId> = self.plugins.keys().cloned().collect();
-                for id in ids {
-                    if self.plugins.get(&id).unwrap().volt_id == volt_id {
-                        let plugin = self.plugins.remove(&id).unwrap();
-                        plugin.shutdown();
-                    }
-                }
+                self.pending_volts.remove(&volt_id);
+                self.started_volts.remove(&volt_id);
+                self.stop_volt_plugins(&volt_id);
             }
             EnableVolt(volt) => {
                 let volt_id = volt.id();
@@ -400,3 +520,232 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap, path::PathBuf, sync::Arc, thread, time::Duration,
+    };
+
+    use crossbeam_channel::{Receiver, Sender};
+    use jsonrpc_lite::{Id, Params};
+    use lapce_rpc::{
+        core::{CoreNotification, CoreRpc, CoreRpcHandler},
+        plugin::{VoltCapabilities, VoltMetadata},
+        proxy::{ProxyRpc, ProxyRpcHandler},
+        style::LineStyle,
+        RpcError,
+    };
+    use lapce_xi_rope::{Rope, RopeDelta};
+    use lsp_types::{
+        SemanticTokens, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        VersionedTextDocumentIdentifier,
+    };
+    use once_cell::sync::Lazy;
+    use parking_lot::Mutex;
+    use serde_json::Value;
+
+    use super::PluginCatalog;
+    use crate::plugin::{
+        psp::{
+            PluginHandlerNotification, PluginServerHandler, PluginServerRpcHandler,
+            RpcCallback,
+        },
+        wasi::load_volt,
+        PluginCatalogNotification, PluginCatalogRpc, PluginCatalogRpcHandler,
+    };
+
+    /// Tells each time a mock plugin is activated
+    static ACTIVATED: Lazy<(Sender<()>, Receiver<()>)> =
+        Lazy::new(crossbeam_channel::unbounded);
+
+    /// A plugin host which never answers, and only tells when it's activated
+    struct MockHost;
+
+    impl PluginServerHandler for MockHost {
+        fn document_supported(
+            &mut self,
+            _language_id: Option<&str>,
+            _path: Option<&std::path::Path>,
+        ) -> bool {
+            true
+        }
+
+        fn method_registered(&mut self, _method: &'static str) -> bool {
+            true
+        }
+
+        fn handle_host_notification(&mut self, _method: String, _params: Params) {}
+
+        fn handle_host_request(
+            &mut self,
+            _id: Id,
+            _method: String,
+            _params: Params,
+            _chan: Sender<Result<Value, RpcError>>,
+        ) {
+        }
+
+        fn handle_handler_notification(
+            &mut self,
+            notification: PluginHandlerNotification,
+        ) {
+            if let PluginHandlerNotification::Initialize = notification {
+                let _ = ACTIVATED.0.send(());
+            }
+        }
+
+        fn handle_did_save_text_document(
+            &self,
+            _language_id: String,
+            _path: PathBuf,
+            _text_document: TextDocumentIdentifier,
+            _text: Rope,
+        ) {
+        }
+
+        fn handle_did_change_text_document(
+            &mut self,
+            _language_id: String,
+            _document: VersionedTextDocumentIdentifier,
+            _delta: RopeDelta,
+            _text: Rope,
+            _new_text: Rope,
+            _change: Arc<
+                Mutex<(
+                    Option<TextDocumentContentChangeEvent>,
+                    Option<TextDocumentContentChangeEvent>,
+                )>,
+            >,
+        ) {
+        }
+
+        fn format_semantic_tokens(
+            &self,
+            _tokens: SemanticTokens,
+            _text: Rope,
+            _f: Box<dyn RpcCallback<Vec<LineStyle>, RpcError>>,
+        ) {
+        }
+    }
+
+    fn start_mock_volt(
+        _workspace: Option<PathBuf>,
+        _configurations: Option<HashMap<String, Value>>,
+        plugin_rpc: PluginCatalogRpcHandler,
+        meta: VoltMetadata,
+        _granted: VoltCapabilities,
+    ) -> anyhow::Result<()> {
+        let (io_tx, _io_rx) = crossbeam_channel::unbounded();
+        let rpc = PluginServerRpcHandler::new(meta.id(), io_tx);
+        let local_rpc = rpc.clone();
+        thread::spawn(move || {
+            local_rpc.mainloop(&mut MockHost);
+        });
+        plugin_rpc.plugin_server_loaded(rpc)?;
+        Ok(())
+    }
+
+    fn next_notification(
+        rx: &Receiver<PluginCatalogRpc>,
+    ) -> PluginCatalogNotification {
+        loop {
+            let rpc = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+            if let PluginCatalogRpc::Handler(notification) = rpc {
+                return notification;
+            }
+        }
+    }
+
+    fn wait_for_activation() {
+        ACTIVATED
+            .1
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the plugin wasn't activated");
+    }
+
+    #[test]
+    fn test_reload_volt() {
+        let core_rpc = CoreRpcHandler::new();
+        // Nothing is open in the editor
+        let proxy_rpc = ProxyRpcHandler::new();
+        let local_proxy_rpc = proxy_rpc.clone();
+        thread::spawn(move || {
+            for msg in local_proxy_rpc.rx() {
+                if let ProxyRpc::Request(id, _) = msg {
+                    local_proxy_rpc.handle_response(
+                        id,
+                        Err(RpcError {
+                            code: 0,
+                            message: "no editor".to_string(),
+                        }),
+                    );
+                }
+            }
+        });
+        let plugin_rpc = PluginCatalogRpcHandler::new(core_rpc.clone(), proxy_rpc);
+        let catalog_rx = plugin_rpc.plugin_rx.lock().take().unwrap();
+        let mut catalog = PluginCatalog {
+            workspace: None,
+            plugin_rpc,
+            plugins: HashMap::new(),
+            plugin_configurations: HashMap::new(),
+            unactivated_volts: HashMap::new(),
+            pending_volts: HashMap::new(),
+            started_volts: HashMap::new(),
+            open_files: HashMap::new(),
+            volt_starter: start_mock_volt,
+        };
+
+        let dir = std::env::current_dir()
+            .unwrap()
+            .join("src")
+            .join("plugin")
+            .join("wasi")
+            .join("plugins")
+            .join("some_author.test-plugin-reload");
+        let meta = load_volt(&dir).unwrap();
+        let volt_id = meta.id();
+
+        // It asks for no capabilities, so it's started without asking the user
+        catalog.handle_notification(PluginCatalogNotification::StartVolt(meta));
+        catalog.handle_notification(next_notification(&catalog_rx));
+        wait_for_activation();
+        assert_eq!(catalog.plugins.len(), 1);
+        let old_plugin = catalog.plugins.values().next().unwrap().clone();
+
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        old_plugin.server_request_async(
+            "test/neverAnswered",
+            Value::Null,
+            None,
+            None,
+            false,
+            move |result| {
+                let _ = tx.send(result);
+            },
+        );
+
+        catalog.handle_notification(PluginCatalogNotification::ReloadVolt(volt_id));
+        // The request isn't left waiting for the stopped plugin
+        let result = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(result.unwrap_err().message, "request cancelled");
+        assert!(catalog.plugins.is_empty());
+        // The editor drops what the plugin registered
+        let stopped = core_rpc.rx().try_iter().any(|rpc| match rpc {
+            CoreRpc::Notification(notification) => matches!(
+                *notification,
+                CoreNotification::PluginServerStopped { plugin_id }
+                    if plugin_id == old_plugin.plugin_id
+            ),
+            _ => false,
+        });
+        assert!(stopped);
+
+        // It was running, so it's activated again right away
+        catalog.handle_notification(next_notification(&catalog_rx));
+        wait_for_activation();
+        assert_eq!(catalog.plugins.len(), 1);
+        assert!(!catalog.plugins.contains_key(&old_plugin.plugin_id));
+    }
+}
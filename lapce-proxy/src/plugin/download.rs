@@ -0,0 +1,413 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use anyhow::Result;
+use lapce_rpc::{
+    core::CoreRpcHandler,
+    plugin::{PluginDownloadProgress, VoltID, VoltMetadata},
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+/// The archive of a volt is downloaded in chunks of this size, the progress is
+/// reported after each of them
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The volts whose download should be aborted
+static CANCELLED_DOWNLOADS: Lazy<Mutex<HashSet<VoltID>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Abort the download of the volt, or drop it if it hasn't started yet
+pub fn cancel_volt_download(volt_id: VoltID) {
+    CANCELLED_DOWNLOADS.lock().insert(volt_id);
+}
+
+/// Forget about an earlier cancellation of the volt when it's asked to be
+/// installed. This is done in the order the install and cancel requests arrive,
+/// so that a cancel sent before the download thread gets to run still drops it
+pub fn register_volt_download(volt_id: &VoltID) {
+    CANCELLED_DOWNLOADS.lock().remove(volt_id);
+}
+
+/// Run the download of the volt unless it was cancelled before it started, and
+/// forget about its cancellation once it's over
+pub(super) fn run_download<T>(
+    volt_id: &VoltID,
+    reporter: &impl VoltDownloadReporter,
+    download: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let result = if reporter.is_cancelled(volt_id) {
+        Err(VoltDownloadError::Cancelled.into())
+    } else {
+        download()
+    };
+    CANCELLED_DOWNLOADS.lock().remove(volt_id);
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoltDownloadError {
+    Cancelled,
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The manifest of the archive isn't for the volt that was asked for
+    ManifestMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The id in the manifest can't be used as the directory of the volt
+    InvalidManifestId(String),
+}
+
+impl fmt::Display for VoltDownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoltDownloadError::Cancelled => write!(f, "download cancelled"),
+            VoltDownloadError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum mismatch, expected {expected} but got {actual}")
+            }
+            VoltDownloadError::ManifestMismatch { expected, actual } => {
+                write!(f, "manifest mismatch, expected {expected} but got {actual}")
+            }
+            VoltDownloadError::InvalidManifestId(id) => {
+                write!(f, "invalid volt id {id} in the manifest")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VoltDownloadError {}
+
+/// The message shown in the plugin panel when a volt couldn't be downloaded
+pub fn download_error_message(err: &anyhow::Error) -> String {
+    match err.downcast_ref::<VoltDownloadError>() {
+        Some(VoltDownloadError::Cancelled) => "Installation cancelled",
+        Some(VoltDownloadError::ChecksumMismatch { .. }) => {
+            "Plugin checksum mismatch"
+        }
+        Some(VoltDownloadError::ManifestMismatch { .. })
+        | Some(VoltDownloadError::InvalidManifestId(_)) => "Invalid plugin manifest",
+        None => "Could not download Plugin",
+    }
+    .to_string()
+}
+
+/// Where the progress of a download goes
+pub trait VoltDownloadReporter {
+    fn progress(&self, progress: PluginDownloadProgress);
+
+    fn is_cancelled(&self, volt_id: &VoltID) -> bool {
+        CANCELLED_DOWNLOADS.lock().contains(volt_id)
+    }
+}
+
+impl VoltDownloadReporter for CoreRpcHandler {
+    fn progress(&self, progress: PluginDownloadProgress) {
+        self.volt_download_progress(progress);
+    }
+}
+
+/// Copy the archive of the volt from `reader` to `writer` and return its sha256
+/// checksum, in hex
+pub(super) fn stream_download(
+    volt_id: &VoltID,
+    mut reader: impl Read,
+    total: Option<u64>,
+    mut writer: impl Write,
+    reporter: &impl VoltDownloadReporter,
+) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; DOWNLOAD_CHUNK_SIZE];
+    let mut downloaded = 0;
+    loop {
+        if reporter.is_cancelled(volt_id) {
+            return Err(VoltDownloadError::Cancelled.into());
+        }
+
+        let read = read_chunk(&mut reader, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        writer.write_all(&buf[..read])?;
+        downloaded += read as u64;
+        reporter.progress(PluginDownloadProgress {
+            volt_id: volt_id.clone(),
+            downloaded,
+            total,
+        });
+    }
+    writer.flush()?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Fill `buf` unless the end of `reader` is reached first
+fn read_chunk(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+/// Volts without a known checksum are accepted as is
+pub(super) fn verify_checksum(expected: Option<&str>, actual: &str) -> Result<()> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(actual) => {
+            Err(VoltDownloadError::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            }
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that the id of the unpacked volt is usable as its directory, and that
+/// it's the volt, and version, that was asked for
+pub(super) fn verify_manifest(
+    meta: &VoltMetadata,
+    expected_id: Option<&VoltID>,
+    expected_version: Option<&str>,
+) -> Result<()> {
+    let id = meta.id();
+    let is_valid_segment = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with('.')
+            && !s.contains(|c: char| c == '/' || c == '\\' || c.is_control())
+    };
+    if !is_valid_segment(&id.author)
+        || !is_valid_segment(&id.name)
+        || meta.version.is_empty()
+    {
+        return Err(VoltDownloadError::InvalidManifestId(format!(
+            "{id}@{}",
+            meta.version
+        ))
+        .into());
+    }
+
+    let mismatch = |expected: String| VoltDownloadError::ManifestMismatch {
+        expected,
+        actual: format!("{id}@{}", meta.version),
+    };
+    if let Some(expected_id) = expected_id {
+        if expected_id != &id {
+            return Err(mismatch(expected_id.to_string()).into());
+        }
+    }
+    if let Some(expected_version) = expected_version {
+        if expected_version != meta.version {
+            return Err(mismatch(format!("{id}@{expected_version}")).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor};
+
+    use lapce_rpc::plugin::{PluginDownloadProgress, VoltID, VoltMetadata};
+
+    use super::{
+        cancel_volt_download, download_error_message, register_volt_download,
+        run_download, stream_download, verify_checksum, verify_manifest,
+        VoltDownloadError, VoltDownloadReporter, DOWNLOAD_CHUNK_SIZE,
+    };
+
+    /// Stands in for the proxy, recording the progress it's sent and cancelling
+    /// the download after `cancel_after` reports
+    #[derive(Default)]
+    struct MockProxy {
+        reports: RefCell<Vec<PluginDownloadProgress>>,
+        cancel_after: Option<usize>,
+    }
+
+    impl VoltDownloadReporter for MockProxy {
+        fn progress(&self, progress: PluginDownloadProgress) {
+            self.reports.borrow_mut().push(progress);
+        }
+
+        fn is_cancelled(&self, _volt_id: &VoltID) -> bool {
+            self.cancel_after
+                .map(|n| self.reports.borrow().len() >= n)
+                .unwrap_or(false)
+        }
+    }
+
+    /// Checks the cancellations the proxy records
+    struct Proxy;
+
+    impl VoltDownloadReporter for Proxy {
+        fn progress(&self, _progress: PluginDownloadProgress) {}
+    }
+
+    fn volt_id() -> VoltID {
+        VoltID {
+            author: "author".to_string(),
+            name: "plugin".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_staged_progress() {
+        let archive = vec![7u8; DOWNLOAD_CHUNK_SIZE * 2 + 100];
+        let total = Some(archive.len() as u64);
+        let proxy = MockProxy::default();
+        let mut written = Vec::new();
+
+        let checksum = stream_download(
+            &volt_id(),
+            Cursor::new(&archive),
+            total,
+            &mut written,
+            &proxy,
+        )
+        .unwrap();
+        assert_eq!(written, archive);
+
+        let reports = proxy.reports.borrow();
+        let downloaded: Vec<u64> = reports.iter().map(|p| p.downloaded).collect();
+        let chunk = DOWNLOAD_CHUNK_SIZE as u64;
+        assert_eq!(downloaded, vec![chunk, chunk * 2, chunk * 2 + 100]);
+        assert!(reports.iter().all(|p| p.total == total));
+        assert_eq!(reports.last().unwrap().fraction(), Some(1.0));
+
+        assert!(verify_checksum(Some(checksum.as_str()), &checksum).is_ok());
+        assert!(
+            verify_checksum(Some(checksum.to_uppercase().as_str()), &checksum)
+                .is_ok()
+        );
+        assert!(verify_checksum(None, &checksum).is_ok());
+    }
+
+    #[test]
+    fn test_cancelled_download() {
+        let archive = vec![7u8; DOWNLOAD_CHUNK_SIZE * 3];
+        let proxy = MockProxy {
+            cancel_after: Some(1),
+            ..Default::default()
+        };
+        let mut written = Vec::new();
+
+        let err = stream_download(
+            &volt_id(),
+            Cursor::new(&archive),
+            None,
+            &mut written,
+            &proxy,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VoltDownloadError>(),
+            Some(&VoltDownloadError::Cancelled)
+        );
+        assert_eq!(written.len(), DOWNLOAD_CHUNK_SIZE);
+        assert_eq!(proxy.reports.borrow()[0].fraction(), None);
+    }
+
+    #[test]
+    fn test_cancel_before_download() {
+        let volt_id = VoltID {
+            author: "author".to_string(),
+            name: "early-cancel".to_string(),
+        };
+        register_volt_download(&volt_id);
+        cancel_volt_download(volt_id.clone());
+
+        let mut started = false;
+        let err = run_download(&volt_id, &Proxy, || {
+            started = true;
+            Ok(())
+        })
+        .unwrap_err();
+        assert!(!started);
+        assert_eq!(
+            err.downcast_ref::<VoltDownloadError>(),
+            Some(&VoltDownloadError::Cancelled)
+        );
+
+        // The cancel only applied to the install it was sent for
+        register_volt_download(&volt_id);
+        assert!(run_download(&volt_id, &Proxy, || Ok(())).is_ok());
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let err = verify_checksum(Some("abc"), "def").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VoltDownloadError>(),
+            Some(&VoltDownloadError::ChecksumMismatch {
+                expected: "abc".to_string(),
+                actual: "def".to_string(),
+            })
+        );
+        assert_eq!(download_error_message(&err), "Plugin checksum mismatch");
+    }
+
+    #[test]
+    fn test_manifest_validation() {
+        let meta = |author: &str, name: &str, version: &str| VoltMetadata {
+            name: name.to_string(),
+            version: version.to_string(),
+            display_name: "Plugin".to_string(),
+            author: author.to_string(),
+            description: "".to_string(),
+            icon: None,
+            repository: None,
+            wasm: None,
+            color_themes: None,
+            icon_themes: None,
+            dir: None,
+            activation: None,
+            config: None,
+            capabilities: None,
+        };
+
+        let volt = meta("author", "plugin", "0.2.0");
+        assert!(verify_manifest(&volt, None, None).is_ok());
+        assert!(verify_manifest(&volt, Some(&volt_id()), Some("0.2.0")).is_ok());
+
+        let err =
+            verify_manifest(&volt, Some(&volt_id()), Some("0.1.0")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VoltDownloadError>(),
+            Some(&VoltDownloadError::ManifestMismatch {
+                expected: "author.plugin@0.1.0".to_string(),
+                actual: "author.plugin@0.2.0".to_string(),
+            })
+        );
+        assert_eq!(download_error_message(&err), "Invalid plugin manifest");
+
+        let other = meta("someone", "plugin", "0.2.0");
+        assert!(verify_manifest(&other, Some(&volt_id()), None).is_err());
+
+        for (author, name, version) in [
+            ("", "plugin", "0.2.0"),
+            ("author", "../plugin", "0.2.0"),
+            ("author", ".plugin", "0.2.0"),
+            ("author", "plugin", ""),
+        ] {
+            let err = verify_manifest(&meta(author, name, version), None, None)
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<VoltDownloadError>(),
+                Some(VoltDownloadError::InvalidManifestId(_))
+            ));
+        }
+    }
+}
@@ -23,9 +23,12 @@
 use parking_lot::Mutex;
 use serde_json::{json, Value};
 
-use super::psp::{
-    handle_plugin_server_message, PluginHandlerNotification, PluginHostHandler,
-    PluginServerHandler, PluginServerRpcHandler, RpcCallback,
+use super::{
+    permission::PluginPermissions,
+    psp::{
+        handle_plugin_server_message, PluginHandlerNotification, PluginHostHandler,
+        PluginServerHandler, PluginServerRpcHandler, RpcCallback,
+    },
 };
 use crate::{buffer::Buffer, plugin::PluginCatalogRpcHandler};
 
@@ -164,6 +167,7 @@ fn new(
         server_uri: Url,
         args: Vec<String>,
         options: Option<Value>,
+        permissions: PluginPermissions,
     ) -> Result<Self> {
         let server = match server_uri.scheme() {
             "file" => {
@@ -253,6 +257,9 @@ fn new(
             document_selector,
             server_rpc.clone(),
             plugin_rpc.clone(),
+            // The requests of the server are held to what the user granted the
+            // plugin which started it
+            permissions,
         );
 
         Ok(Self {
@@ -276,6 +283,7 @@ pub fn start(
         server_uri: Url,
         args: Vec<String>,
         options: Option<Value>,
+        permissions: PluginPermissions,
     ) -> Result<()> {
         let mut lsp = Self::new(
             plugin_rpc,
@@ -287,6 +295,7 @@ pub fn start(
             server_uri,
             args,
             options,
+            permissions,
         )?;
         let rpc = lsp.server_rpc.clone();
         thread::spawn(move || {
@@ -342,6 +351,9 @@ fn initialize(&mut self) {
                 inlay_hint: Some(InlayHintClientCapabilities {
                     ..Default::default()
                 }),
+                color_provider: Some(DocumentColorClientCapabilities {
+                    ..Default::default()
+                }),
                 code_action: Some(CodeActionClientCapabilities {
                     data_support: Some(true),
                     resolve_support: Some(CodeActionCapabilityResolveSupport {
@@ -383,6 +395,10 @@ fn initialize(&mut self) {
                 definition: Some(GotoCapability {
                     ..Default::default()
                 }),
+                folding_range: Some(FoldingRangeClientCapabilities {
+                    line_folding_only: Some(true),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             window: Some(WindowClientCapabilities {
@@ -399,6 +415,7 @@ fn initialize(&mut self) {
                     ..Default::default()
                 }),
                 configuration: Some(false),
+                apply_edit: Some(true),
                 ..Default::default()
             }),
 
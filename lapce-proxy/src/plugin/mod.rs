@@ -1,11 +1,14 @@
 pub mod catalog;
+pub mod download;
 pub mod lsp;
+pub mod permission;
 pub mod psp;
 pub mod wasi;
 
 use std::{
     collections::HashMap,
-    fs,
+    fs::{self, File},
+    io::{BufWriter, Read},
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
@@ -21,31 +24,38 @@
 use lapce_core::directory::Directory;
 use lapce_rpc::{
     core::CoreRpcHandler,
-    plugin::{PluginId, VoltInfo, VoltMetadata},
-    proxy::ProxyRpcHandler,
+    plugin::{PluginId, VoltCapabilities, VoltID, VoltInfo, VoltMetadata},
+    proxy::{
+        PaletteItemsParams, PluginPaletteItem, ProxyRpcHandler,
+        SelectPaletteItemParams, PALETTE_ITEMS_METHOD, SELECT_PALETTE_ITEM_METHOD,
+    },
     style::LineStyle,
     RequestId, RpcError,
 };
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
     request::{
-        CodeActionRequest, CodeActionResolveRequest, Completion,
-        DocumentSymbolRequest, Formatting, GotoDefinition, GotoTypeDefinition,
+        CodeActionRequest, CodeActionResolveRequest, ColorPresentationRequest,
+        Completion, DocumentColor, DocumentSymbolRequest, ExecuteCommand,
+        FoldingRangeRequest, Formatting, GotoDefinition, GotoTypeDefinition,
         GotoTypeDefinitionParams, GotoTypeDefinitionResponse, HoverRequest,
-        InlayHintRequest, PrepareRenameRequest, References, Rename, Request,
-        ResolveCompletionItem, SelectionRangeRequest, SemanticTokensFullRequest,
-        SignatureHelpRequest, WorkspaceSymbol,
+        InlayHintRequest, PrepareRenameRequest, RangeFormatting, References, Rename,
+        Request, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullRequest, SignatureHelpRequest, WorkspaceSymbol,
     },
-    CodeAction, CodeActionContext, CodeActionParams, CodeActionResponse,
+    CodeAction, CodeActionContext, CodeActionParams, CodeActionResponse, Color,
+    ColorInformation, ColorPresentation, ColorPresentationParams, Command,
     CompletionItem, CompletionParams, CompletionResponse, Diagnostic,
-    DocumentFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
-    FormattingOptions, GotoDefinitionParams, GotoDefinitionResponse, Hover,
-    HoverParams, InlayHint, InlayHintParams, Location, PartialResultParams,
-    Position, PrepareRenameResponse, Range, ReferenceContext, ReferenceParams,
-    RenameParams, SelectionRange, SelectionRangeParams, SemanticTokens,
-    SemanticTokensParams, SignatureHelp, SignatureHelpParams, SymbolInformation,
-    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, TextEdit,
-    Url, VersionedTextDocumentIdentifier, WorkDoneProgressParams, WorkspaceEdit,
+    DocumentColorParams, DocumentFormattingParams, DocumentRangeFormattingParams,
+    DocumentSymbolParams, DocumentSymbolResponse, ExecuteCommandParams,
+    FoldingRange, FoldingRangeParams, FormattingOptions, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverParams, InlayHint, InlayHintParams,
+    Location, PartialResultParams, Position, PrepareRenameResponse, Range,
+    ReferenceContext, ReferenceParams, RenameParams, SelectionRange,
+    SelectionRangeParams, SemanticTokens, SemanticTokensParams, SignatureHelp,
+    SignatureHelpParams, SymbolInformation, TextDocumentIdentifier,
+    TextDocumentItem, TextDocumentPositionParams, TextEdit, Url,
+    VersionedTextDocumentIdentifier, WorkDoneProgressParams, WorkspaceEdit,
     WorkspaceSymbolParams,
 };
 use parking_lot::Mutex;
@@ -55,8 +65,12 @@
 
 use self::{
     catalog::PluginCatalog,
+    download::{
+        download_error_message, run_download, stream_download, verify_checksum,
+        verify_manifest, VoltDownloadReporter,
+    },
     psp::{ClonableCallback, PluginServerRpcHandler, RpcCallback},
-    wasi::{load_volt, start_volt},
+    wasi::load_volt,
 };
 use crate::buffer::language_id_from_path;
 
@@ -88,6 +102,9 @@ pub enum PluginCatalogRpc {
     DidOpenTextDocument {
         document: TextDocumentItem,
     },
+    DidCloseTextDocument {
+        text_document: TextDocumentIdentifier,
+    },
     DidChangeTextDocument {
         language_id: String,
         document: VersionedTextDocumentIdentifier,
@@ -113,7 +130,14 @@ pub enum PluginCatalogNotification {
     InstallVolt(VoltInfo),
     StopVolt(VoltInfo),
     EnableVolt(VoltInfo),
-    ReloadVolt(VoltMetadata),
+    ReloadVolt(VoltID),
+    /// Start the volt without waiting for it to be activated, like when it was
+    /// just installed
+    StartVolt(VoltMetadata),
+    /// The capabilities the user granted the volt waiting for them
+    GrantVoltPermissions(VoltID, VoltCapabilities),
+    /// Cancel the requests of this method all the plugins are working on
+    CancelRequests(&'static str),
     Shutdown,
 }
 
@@ -199,6 +223,9 @@ pub fn mainloop(&self, plugin: &mut PluginCatalog) {
                 PluginCatalogRpc::DidOpenTextDocument { document } => {
                     plugin.handle_did_open_text_document(document);
                 }
+                PluginCatalogRpc::DidCloseTextDocument { text_document } => {
+                    plugin.handle_did_close_text_document(text_document);
+                }
                 PluginCatalogRpc::DidSaveTextDocument {
                     language_id,
                     path,
@@ -263,6 +290,9 @@ fn send_request_to_all_plugins<P, Resp>(
         let got_success = Arc::new(AtomicBool::new(false));
         let request_sent = Arc::new(AtomicUsize::new(0));
         let err_received = Arc::new(AtomicUsize::new(0));
+        // The error answered once every plugin failed, which is one of a
+        // plugin capable of the request if there is one
+        let failure: Arc<Mutex<Option<RpcError>>> = Arc::new(Mutex::new(None));
         self.send_request(
             None,
             Some(request_sent.clone()),
@@ -288,12 +318,28 @@ fn send_request_to_all_plugins<P, Resp>(
                     }
                     Err(e) => Err(e),
                 };
-                if result.is_ok() {
-                    cb(plugin_id, result)
-                } else {
-                    let rx = err_received.fetch_add(1, Ordering::Relaxed) + 1;
-                    if request_sent.load(Ordering::Acquire) == rx {
-                        cb(plugin_id, result)
+                match result {
+                    Ok(_) => cb(plugin_id, result),
+                    Err(err) => {
+                        let err = {
+                            let mut failure = failure.lock();
+                            if failure
+                                .as_ref()
+                                .map_or(true, |f| f.code == RpcError::NOT_CAPABLE)
+                            {
+                                *failure = Some(err);
+                            }
+                            let rx =
+                                err_received.fetch_add(1, Ordering::Relaxed) + 1;
+                            if request_sent.load(Ordering::Acquire) == rx {
+                                failure.take()
+                            } else {
+                                None
+                            }
+                        };
+                        if let Some(err) = err {
+                            cb(plugin_id, Err(err))
+                        }
                     }
                 }
             },
@@ -474,7 +520,7 @@ pub fn get_references(
     pub fn get_code_actions(
         &self,
         path: &Path,
-        position: Position,
+        range: Range,
         diagnostics: Vec<Diagnostic>,
         cb: impl FnOnce(PluginId, Result<CodeActionResponse, RpcError>)
             + Clone
@@ -485,10 +531,7 @@ pub fn get_code_actions(
         let method = CodeActionRequest::METHOD;
         let params = CodeActionParams {
             text_document: TextDocumentIdentifier { uri },
-            range: Range {
-                start: position,
-                end: position,
-            },
+            range,
             context: CodeActionContext {
                 diagnostics,
                 only: None,
@@ -534,6 +577,62 @@ pub fn get_inlay_hints(
         );
     }
 
+    pub fn get_document_colors(
+        &self,
+        path: &Path,
+        cb: impl FnOnce(PluginId, Result<Vec<ColorInformation>, RpcError>)
+            + Clone
+            + Send
+            + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = DocumentColor::METHOD;
+        let params = DocumentColorParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_color_presentations(
+        &self,
+        path: &Path,
+        color: Color,
+        range: Range,
+        cb: impl FnOnce(PluginId, Result<Vec<ColorPresentation>, RpcError>)
+            + Clone
+            + Send
+            + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = ColorPresentationRequest::METHOD;
+        let params = ColorPresentationParams {
+            text_document: TextDocumentIdentifier { uri },
+            color,
+            range,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
     pub fn get_document_symbols(
         &self,
         path: &Path,
@@ -607,6 +706,38 @@ pub fn get_document_formatting(
         );
     }
 
+    pub fn get_document_range_formatting(
+        &self,
+        path: &Path,
+        range: Range,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+            + Clone
+            + Send
+            + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = RangeFormatting::METHOD;
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
     pub fn prepare_rename(
         &self,
         path: &Path,
@@ -718,6 +849,32 @@ pub fn get_selection_range(
         );
     }
 
+    pub fn get_folding_range(
+        &self,
+        path: &Path,
+        cb: impl FnOnce(PluginId, Result<Vec<FoldingRange>, RpcError>)
+            + Clone
+            + Send
+            + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = FoldingRangeRequest::METHOD;
+        let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: Default::default(),
+        };
+        let language_id =
+            Some(language_id_from_path(path).unwrap_or("").to_string());
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
     pub fn hover(
         &self,
         path: &Path,
@@ -786,6 +943,13 @@ pub fn completion(
         );
     }
 
+    /// Let the plugins drop the completion requests they are working on
+    pub fn cancel_completion(&self) {
+        let _ = self.catalog_notification(
+            PluginCatalogNotification::CancelRequests(Completion::METHOD),
+        );
+    }
+
     pub fn completion_resolve(
         &self,
         plugin_id: PluginId,
@@ -822,6 +986,54 @@ pub fn completion_resolve(
         );
     }
 
+    pub fn palette_items(
+        &self,
+        plugin_id: PluginId,
+        provider: String,
+        query: String,
+        cb: impl FnOnce(Result<Vec<PluginPaletteItem>, RpcError>)
+            + Send
+            + Clone
+            + 'static,
+    ) {
+        self.send_request(
+            Some(plugin_id),
+            None,
+            PALETTE_ITEMS_METHOD,
+            PaletteItemsParams { provider, query },
+            None,
+            None,
+            move |_, result| {
+                let result = result.and_then(|value| {
+                    serde_json::from_value::<Vec<PluginPaletteItem>>(value).map_err(
+                        |_| RpcError {
+                            code: 0,
+                            message: "palette items deserialize error".to_string(),
+                        },
+                    )
+                });
+                cb(result)
+            },
+        );
+    }
+
+    pub fn select_palette_item(
+        &self,
+        plugin_id: PluginId,
+        provider: String,
+        id: String,
+    ) {
+        self.send_request(
+            Some(plugin_id),
+            None,
+            SELECT_PALETTE_ITEM_METHOD,
+            SelectPaletteItemParams { provider, id },
+            None,
+            None,
+            |_, _| {},
+        );
+    }
+
     pub fn signature_help(
         &self,
         request_id: usize,
@@ -897,6 +1109,29 @@ pub fn action_resolve(
         );
     }
 
+    pub fn execute_command(
+        &self,
+        command: Command,
+        plugin_id: PluginId,
+        cb: impl FnOnce(Result<Value, RpcError>) + Send + Clone + 'static,
+    ) {
+        let method = ExecuteCommand::METHOD;
+        let params = ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.send_request(
+            Some(plugin_id),
+            None,
+            method,
+            params,
+            None,
+            None,
+            move |_, result| cb(result),
+        );
+    }
+
     pub fn did_open_document(
         &self,
         path: &Path,
@@ -914,6 +1149,14 @@ pub fn did_open_document(
         });
     }
 
+    pub fn did_close_document(&self, path: &Path) {
+        let _ = self.plugin_tx.send(PluginCatalogRpc::DidCloseTextDocument {
+            text_document: TextDocumentIdentifier::new(
+                Url::from_file_path(path).unwrap(),
+            ),
+        });
+    }
+
     pub fn unactivated_volts(&self, volts: Vec<VoltMetadata>) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::UnactivatedVolts(volts))
     }
@@ -944,13 +1187,27 @@ pub fn stop_volt(&self, volt: VoltInfo) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::StopVolt(volt))
     }
 
-    pub fn reload_volt(&self, volt: VoltMetadata) -> Result<()> {
-        self.catalog_notification(PluginCatalogNotification::ReloadVolt(volt))
+    pub fn reload_volt(&self, volt_id: VoltID) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::ReloadVolt(volt_id))
     }
 
     pub fn enable_volt(&self, volt: VoltInfo) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::EnableVolt(volt))
     }
+
+    pub fn start_volt(&self, volt: VoltMetadata) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::StartVolt(volt))
+    }
+
+    pub fn grant_volt_permissions(
+        &self,
+        volt_id: VoltID,
+        granted: VoltCapabilities,
+    ) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::GrantVoltPermissions(
+            volt_id, granted,
+        ))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -981,7 +1238,21 @@ pub fn volt_icon(volt: &VoltMetadata) -> Option<Vec<u8>> {
     std::fs::read(icon).ok()
 }
 
-pub fn download_volt(volt: &VoltInfo) -> Result<VoltMetadata> {
+/// Download and unpack the volt into the plugins directory, reporting the
+/// progress of the download to `reporter`
+pub fn download_volt(
+    volt: &VoltInfo,
+    reporter: &impl VoltDownloadReporter,
+) -> Result<VoltMetadata> {
+    let id = volt.id();
+    run_download(&id, reporter, || download_volt_archive(volt, &id, reporter))
+}
+
+fn download_volt_archive(
+    volt: &VoltInfo,
+    id: &VoltID,
+    reporter: &impl VoltDownloadReporter,
+) -> Result<VoltMetadata> {
     let url = format!(
         "https://plugins.lapce.dev/api/v1/plugins/{}/{}/{}/download",
         volt.author, volt.name, volt.version
@@ -1005,45 +1276,104 @@ pub fn download_volt(volt: &VoltInfo) -> Result<VoltMetadata> {
         .get("content-type")
         .and_then(|v| v.to_str().ok())
         == Some("application/zstd");
+    let total = resp.content_length();
 
-    let id = volt.id();
-    let plugin_dir = Directory::plugins_directory()
-        .ok_or_else(|| anyhow!("can't get plugin directory"))?
-        .join(id.to_string());
+    let plugins_dir = Directory::plugins_directory()
+        .ok_or_else(|| anyhow!("can't get plugin directory"))?;
+    let plugin_dir = plugins_dir.join(id.to_string());
+    // The archive is kept next to the plugins until it's unpacked, so that a
+    // cancelled or corrupted download leaves the installed version untouched
+    let archive_path = plugins_dir.join(format!(".{id}.download"));
+
+    let result = stream_download(
+        id,
+        &mut resp,
+        total,
+        BufWriter::new(File::create(&archive_path)?),
+        reporter,
+    )
+    .and_then(|checksum| verify_checksum(volt.checksum.as_deref(), &checksum))
+    .and_then(|_| unpack_volt(&archive_path, &plugin_dir, is_zstd))
+    .and_then(|meta| {
+        if let Err(err) = verify_manifest(&meta, Some(id), Some(&volt.version)) {
+            let _ = fs::remove_dir_all(&plugin_dir);
+            return Err(err);
+        }
+        Ok(meta)
+    });
+    let _ = fs::remove_file(&archive_path);
+    result
+}
+
+/// Install a volt archive from the disk instead of the registry, the volt is
+/// only known once its manifest is read
+pub fn install_volt_from_file(path: &Path) -> Result<VoltMetadata> {
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    let is_zstd = match magic {
+        [0x28, 0xb5, 0x2f, 0xfd] => true,
+        [0x1f, 0x8b, _, _] => false,
+        _ => return Err(anyhow!("{} is not a volt archive", path.display())),
+    };
+
+    let plugins_dir = Directory::plugins_directory()
+        .ok_or_else(|| anyhow!("can't get plugin directory"))?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?
+        .to_string_lossy();
+    let unpack_dir = plugins_dir.join(format!(".{file_name}.unpack"));
+    let meta = unpack_volt(path, &unpack_dir, is_zstd)?;
+    if let Err(err) = verify_manifest(&meta, None, None) {
+        let _ = fs::remove_dir_all(&unpack_dir);
+        return Err(err);
+    }
+
+    let plugin_dir = plugins_dir.join(meta.id().to_string());
     let _ = fs::remove_dir_all(&plugin_dir);
-    fs::create_dir_all(&plugin_dir)?;
+    if let Err(err) = fs::rename(&unpack_dir, &plugin_dir) {
+        let _ = fs::remove_dir_all(&unpack_dir);
+        return Err(err.into());
+    }
+    // Loaded again for the paths in the manifest to point to where it ended up
+    load_volt(&plugin_dir)
+}
+
+fn unpack_volt(
+    archive_path: &Path,
+    plugin_dir: &Path,
+    is_zstd: bool,
+) -> Result<VoltMetadata> {
+    let _ = fs::remove_dir_all(plugin_dir);
+    fs::create_dir_all(plugin_dir)?;
 
-    if is_zstd {
-        let tar = zstd::Decoder::new(&mut resp).unwrap();
-        let mut archive = Archive::new(tar);
-        archive.unpack(&plugin_dir)?;
+    let file = File::open(archive_path)?;
+    let unpacked = if is_zstd {
+        zstd::Decoder::new(file).and_then(|tar| Archive::new(tar).unpack(plugin_dir))
     } else {
-        let tar = GzDecoder::new(&mut resp);
-        let mut archive = Archive::new(tar);
-        archive.unpack(&plugin_dir)?;
+        Archive::new(GzDecoder::new(file)).unpack(plugin_dir)
+    };
+    if let Err(err) = unpacked {
+        let _ = fs::remove_dir_all(plugin_dir);
+        return Err(err.into());
     }
 
-    let meta = load_volt(&plugin_dir)?;
-    Ok(meta)
+    load_volt(plugin_dir)
 }
 
 pub fn install_volt(
     catalog_rpc: PluginCatalogRpcHandler,
-    workspace: Option<PathBuf>,
-    configurations: Option<HashMap<String, serde_json::Value>>,
     volt: VoltInfo,
 ) -> Result<()> {
-    let download_volt_result = download_volt(&volt);
-    if download_volt_result.is_err() {
+    let download_volt_result = download_volt(&volt, &catalog_rpc.core_rpc);
+    if let Err(err) = download_volt_result.as_ref() {
         catalog_rpc
             .core_rpc
-            .volt_installing(volt, "Could not download Plugin".to_string());
+            .volt_installing(volt, download_error_message(err));
     }
     let meta = download_volt_result?;
-    let local_catalog_rpc = catalog_rpc.clone();
-    let local_meta = meta.clone();
 
-    let _ = start_volt(workspace, configurations, local_catalog_rpc, local_meta);
+    let _ = catalog_rpc.start_volt(meta.clone());
     let icon = volt_icon(&meta);
     catalog_rpc.core_rpc.volt_installed(meta, icon);
     Ok(())
@@ -0,0 +1,337 @@
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+use lapce_rpc::plugin::VoltCapabilities;
+use lsp_types::{
+    DocumentChangeOperation, DocumentChanges, ResourceOp, WorkspaceEdit,
+};
+
+/// The code of the error answering a request the plugin wasn't allowed to make
+pub const PERMISSION_DENIED_CODE: i64 = -32001;
+
+/// A plugin asked the proxy for something it wasn't granted the capability for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PermissionError {
+    /// Starting the program, which could be its language server
+    Process(String),
+    /// Writing the file, like with a workspace edit
+    Write(PathBuf),
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionError::Process(program) => {
+                write!(f, "permission denied: can't start the process {program}")
+            }
+            PermissionError::Write(path) => {
+                write!(f, "permission denied: can't write to {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PermissionError {}
+
+/// The capabilities the user granted a plugin, which are checked before the
+/// proxy does anything on its behalf
+#[derive(Clone, Debug, Default)]
+pub struct PluginPermissions {
+    granted: VoltCapabilities,
+    workspace: Option<PathBuf>,
+}
+
+impl PluginPermissions {
+    pub fn new(granted: VoltCapabilities, workspace: Option<PathBuf>) -> Self {
+        Self { granted, workspace }
+    }
+
+    pub fn check_process(&self, program: &str) -> Result<(), PermissionError> {
+        if self.granted.process {
+            Ok(())
+        } else {
+            Err(PermissionError::Process(program.to_string()))
+        }
+    }
+
+    pub fn check_write(&self, path: &Path) -> Result<(), PermissionError> {
+        let any = self
+            .granted
+            .fs_write
+            .iter()
+            .any(|scope| scope == VoltCapabilities::ANY);
+        // A path like `workspace/../.bashrc` is outside of the workspace
+        let normalized = normalize(path);
+        if any
+            || (normalized.is_absolute()
+                && self
+                    .dirs(&self.granted.fs_write)
+                    .any(|dir| normalized.starts_with(dir)))
+        {
+            Ok(())
+        } else {
+            Err(PermissionError::Write(path.to_path_buf()))
+        }
+    }
+
+    /// Check that all the files the edit changes, creates, renames or deletes
+    /// can be written to
+    pub fn check_workspace_edit(
+        &self,
+        edit: &WorkspaceEdit,
+    ) -> Result<(), PermissionError> {
+        let mut uris = Vec::new();
+        if let Some(changes) = edit.changes.as_ref() {
+            uris.extend(changes.keys());
+        }
+        match edit.document_changes.as_ref() {
+            Some(DocumentChanges::Edits(edits)) => {
+                uris.extend(edits.iter().map(|edit| &edit.text_document.uri));
+            }
+            Some(DocumentChanges::Operations(operations)) => {
+                for operation in operations {
+                    match operation {
+                        DocumentChangeOperation::Edit(edit) => {
+                            uris.push(&edit.text_document.uri);
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Create(op)) => {
+                            uris.push(&op.uri);
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Rename(op)) => {
+                            uris.push(&op.old_uri);
+                            uris.push(&op.new_uri);
+                        }
+                        DocumentChangeOperation::Op(ResourceOp::Delete(op)) => {
+                            uris.push(&op.uri);
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+
+        for uri in uris {
+            match uri.to_file_path() {
+                Ok(path) => self.check_write(&path)?,
+                Err(_) => {
+                    return Err(PermissionError::Write(PathBuf::from(
+                        uri.to_string(),
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The hosts given to the http extension of the sandbox, which refuses the
+    /// requests to any other host
+    pub fn allowed_hosts(&self) -> Vec<String> {
+        if self
+            .granted
+            .network
+            .iter()
+            .any(|host| host == VoltCapabilities::ANY)
+        {
+            return vec!["insecure:allow-all".to_string()];
+        }
+        // The extension compares the hosts of these urls with the one of the
+        // request, whatever the scheme is
+        self.granted
+            .network
+            .iter()
+            .map(|host| format!("https://{host}"))
+            .collect()
+    }
+
+    /// The directories opened in the sandbox at their own paths with the
+    /// rights to change them, which are those of the write scope
+    pub fn writable_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in self.dirs(&self.granted.fs_write) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    /// The directories opened in the sandbox at their own paths with only the
+    /// rights to read them, which are those of the read scope that aren't in
+    /// the write scope already
+    pub fn read_only_dirs(&self) -> Vec<PathBuf> {
+        let writable = self.writable_dirs();
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in self.dirs(&self.granted.fs_read) {
+            if !dirs.contains(&dir)
+                && !writable.iter().any(|writable| dir.starts_with(writable))
+            {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    /// The directories of the scope, without `*` which isn't a directory
+    fn dirs<'a>(
+        &'a self,
+        scope: &'a [String],
+    ) -> impl Iterator<Item = PathBuf> + 'a {
+        scope.iter().filter_map(|scope| {
+            if scope == VoltCapabilities::WORKSPACE {
+                self.workspace.as_deref().map(normalize)
+            } else {
+                let path = PathBuf::from(scope);
+                path.is_absolute().then(|| normalize(&path))
+            }
+        })
+    }
+}
+
+/// The path without its `.` and `..` components, which are resolved without
+/// looking at the file system. A `..` above the root stays at the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use lapce_rpc::plugin::VoltCapabilities;
+    use lsp_types::{Url, WorkspaceEdit};
+
+    use super::{normalize, PermissionError, PluginPermissions};
+
+    #[test]
+    fn test_allowed_hosts() {
+        // The sandbox isn't given any host to make requests to
+        let permissions = PluginPermissions::new(VoltCapabilities::default(), None);
+        assert!(permissions.allowed_hosts().is_empty());
+
+        let permissions = PluginPermissions::new(
+            VoltCapabilities {
+                network: vec!["api.github.com".to_string()],
+                ..Default::default()
+            },
+            None,
+        );
+        assert_eq!(
+            permissions.allowed_hosts(),
+            vec!["https://api.github.com".to_string()]
+        );
+
+        let permissions = PluginPermissions::new(VoltCapabilities::all(), None);
+        assert_eq!(
+            permissions.allowed_hosts(),
+            vec!["insecure:allow-all".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_process() {
+        let permissions = PluginPermissions::new(VoltCapabilities::default(), None);
+        assert_eq!(
+            permissions.check_process("rust-analyzer"),
+            Err(PermissionError::Process("rust-analyzer".to_string()))
+        );
+        let permissions = PluginPermissions::new(
+            VoltCapabilities {
+                process: true,
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(permissions.check_process("rust-analyzer").is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_scope() {
+        let workspace = PathBuf::from("/home/user/project");
+        let permissions = PluginPermissions::new(
+            VoltCapabilities {
+                fs_read: vec!["workspace".to_string(), "/opt/sdk".to_string()],
+                fs_write: vec!["workspace".to_string()],
+                ..Default::default()
+            },
+            Some(workspace.clone()),
+        );
+        assert!(permissions
+            .check_write(&workspace.join("src/main.rs"))
+            .is_ok());
+        assert!(permissions
+            .check_write(&PathBuf::from("/opt/sdk/lib.rs"))
+            .is_err());
+        // The paths going out of the workspace aren't in it
+        assert_eq!(
+            permissions.check_write(&workspace.join("../../.bashrc")),
+            Err(PermissionError::Write(workspace.join("../../.bashrc")))
+        );
+        assert!(permissions
+            .check_write(&workspace.join("src/../../project/./a.rs"))
+            .is_ok());
+        assert!(permissions
+            .check_write(&PathBuf::from("relative/a.rs"))
+            .is_err());
+        // The read scope is opened in the sandbox without the rights to write
+        assert_eq!(permissions.writable_dirs(), vec![workspace.clone()]);
+        assert_eq!(
+            permissions.read_only_dirs(),
+            vec![PathBuf::from("/opt/sdk")]
+        );
+
+        let edit = |path: &str| WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                Url::from_file_path(path).unwrap(),
+                Vec::new(),
+            )])),
+            ..Default::default()
+        };
+        assert!(permissions
+            .check_workspace_edit(&edit("/home/user/project/a.rs"))
+            .is_ok());
+        assert_eq!(
+            permissions.check_workspace_edit(&edit("/home/user/.bashrc")),
+            Err(PermissionError::Write(PathBuf::from("/home/user/.bashrc")))
+        );
+
+        // Without a workspace, its scope is empty
+        let permissions = PluginPermissions::new(
+            VoltCapabilities {
+                fs_write: vec!["workspace".to_string()],
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(permissions.check_write(&workspace.join("a.rs")).is_err());
+        assert!(permissions.writable_dirs().is_empty());
+        assert!(permissions.read_only_dirs().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), PathBuf::from("/a/c"));
+        assert_eq!(normalize(Path::new("/a/../../b")), PathBuf::from("/b"));
+        assert_eq!(normalize(Path::new("a/../../b")), PathBuf::from("../b"));
+    }
+}
@@ -15,33 +15,40 @@
 use lapce_core::{buffer::rope_text::RopeText, encoding::offset_utf16_to_utf8};
 use lapce_rpc::{
     plugin::{PluginId, VoltID},
+    proxy::{
+        PluginPaletteProvider, PALETTE_ITEMS_METHOD,
+        REGISTER_PALETTE_PROVIDER_METHOD, SELECT_PALETTE_ITEM_METHOD,
+    },
     style::{LineStyle, Style},
     RpcError,
 };
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
     notification::{
-        DidChangeTextDocument, DidOpenTextDocument, DidSaveTextDocument,
-        Initialized, LogMessage, Notification, Progress, PublishDiagnostics,
-        ShowMessage,
+        Cancel, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        DidSaveTextDocument, Initialized, LogMessage, Notification, Progress,
+        PublishDiagnostics, ShowMessage,
     },
     request::{
-        CodeActionRequest, CodeActionResolveRequest, Completion,
-        DocumentSymbolRequest, Formatting, GotoDefinition, GotoTypeDefinition,
-        HoverRequest, Initialize, InlayHintRequest, PrepareRenameRequest,
-        References, RegisterCapability, Rename, ResolveCompletionItem,
-        SelectionRangeRequest, SemanticTokensFullRequest, SignatureHelpRequest,
-        WorkDoneProgressCreate, WorkspaceSymbol,
+        ApplyWorkspaceEdit, CodeActionRequest, CodeActionResolveRequest,
+        ColorPresentationRequest, Completion, DocumentColor, DocumentSymbolRequest,
+        ExecuteCommand, FoldingRangeRequest, Formatting, GotoDefinition,
+        GotoTypeDefinition, HoverRequest, Initialize, InlayHintRequest,
+        PrepareRenameRequest, RangeFormatting, References, RegisterCapability,
+        Rename, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullRequest, SignatureHelpRequest, WorkDoneProgressCreate,
+        WorkspaceSymbol,
     },
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, CancelParams,
     CodeActionProviderCapability, DidChangeTextDocumentParams,
     DidSaveTextDocumentParams, DocumentSelector, HoverProviderCapability,
-    LogMessageParams, OneOf, ProgressParams, PublishDiagnosticsParams, Range,
-    Registration, RegistrationParams, SemanticTokens, SemanticTokensLegend,
-    SemanticTokensServerCapabilities, ServerCapabilities, ShowMessageParams,
-    TextDocumentContentChangeEvent, TextDocumentIdentifier,
-    TextDocumentSaveRegistrationOptions, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextDocumentSyncSaveOptions,
-    VersionedTextDocumentIdentifier,
+    LogMessageParams, MessageType, NumberOrString, OneOf, ProgressParams,
+    PublishDiagnosticsParams, Range, Registration, RegistrationParams,
+    SemanticTokens, SemanticTokensLegend, SemanticTokensServerCapabilities,
+    ServerCapabilities, ShowMessageParams, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentSaveRegistrationOptions,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncSaveOptions,
+    VersionedTextDocumentIdentifier, WorkspaceEdit,
 };
 use parking_lot::Mutex;
 use psp_types::{
@@ -53,9 +60,13 @@
 
 use super::{
     lsp::{DocumentFilter, LspClient},
+    permission::{PermissionError, PluginPermissions, PERMISSION_DENIED_CODE},
     PluginCatalogRpcHandler,
 };
 
+/// The LSP error code of a request cancelled by the client
+const REQUEST_CANCELLED: i64 = -32800;
+
 pub enum ResponseHandler<Resp, Error> {
     Chan(Sender<Result<Resp, Error>>),
     Callback(Box<dyn RpcCallback<Resp, Error>>),
@@ -160,7 +171,11 @@ pub struct PluginServerRpcHandler {
     rpc_rx: Receiver<PluginServerRpc>,
     io_tx: Sender<JsonRpc>,
     id: Arc<AtomicU64>,
-    server_pending: Arc<Mutex<HashMap<Id, ResponseHandler<Value, RpcError>>>>,
+    /// The requests sent to the server that are waiting for a response, with
+    /// their method
+    #[allow(clippy::type_complexity)]
+    server_pending:
+        Arc<Mutex<HashMap<Id, (&'static str, ResponseHandler<Value, RpcError>)>>>,
 }
 
 pub trait PluginServerHandler {
@@ -238,13 +253,13 @@ fn initialize(&self) {
     fn send_server_request(
         &self,
         id: Id,
-        method: &str,
+        method: &'static str,
         params: Params,
         rh: ResponseHandler<Value, RpcError>,
     ) {
         {
             let mut pending = self.server_pending.lock();
-            pending.insert(id.clone(), rh);
+            pending.insert(id.clone(), (method, rh));
         }
         let msg = JsonRpc::request_with_params(id, method, params);
         self.send_server_rpc(msg);
@@ -359,11 +374,56 @@ fn server_request_common<P: Serialize>(
     }
 
     pub fn handle_server_response(&self, id: Id, result: Result<Value, RpcError>) {
-        if let Some(handler) = { self.server_pending.lock().remove(&id) } {
+        if let Some((_, handler)) = { self.server_pending.lock().remove(&id) } {
             handler.invoke(result);
         }
     }
 
+    /// Let the server drop the work of the pending requests of `method`, which
+    /// are answered with a cancelled error right away
+    pub fn cancel_requests(&self, method: &str) {
+        self.cancel_pending_requests(|m| m == method);
+    }
+
+    /// Cancel all the pending requests, like when the plugin is stopped, so
+    /// that nothing waits for their answers
+    pub fn cancel_all_requests(&self) {
+        self.cancel_pending_requests(|_| true);
+    }
+
+    fn cancel_pending_requests(&self, cancel: impl Fn(&str) -> bool) {
+        let cancelled: Vec<(Id, ResponseHandler<Value, RpcError>)> = {
+            let mut pending = self.server_pending.lock();
+            let ids: Vec<Id> = pending
+                .iter()
+                .filter(|(_, (m, _))| cancel(m))
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| {
+                    let (_, rh) = pending.remove(&id)?;
+                    Some((id, rh))
+                })
+                .collect()
+        };
+
+        for (id, rh) in cancelled {
+            if let Id::Num(id) = id {
+                let params = CancelParams {
+                    id: NumberOrString::Number(id as i32),
+                };
+                self.send_server_notification(
+                    Cancel::METHOD,
+                    Params::from(serde_json::to_value(params).unwrap()),
+                );
+            }
+            rh.invoke(Err(RpcError {
+                code: REQUEST_CANCELLED,
+                message: "request cancelled".to_string(),
+            }));
+        }
+    }
+
     pub fn shutdown(&self) {
         self.handle_rpc(PluginServerRpc::Handler(
             PluginHandlerNotification::Shutdown,
@@ -392,7 +452,7 @@ pub fn mainloop<H>(&self, handler: &mut H)
                         self.send_server_request(id, method, params, rh);
                     } else {
                         rh.invoke(Err(RpcError {
-                            code: 0,
+                            code: RpcError::NOT_CAPABLE,
                             message: "server not capable".to_string(),
                         }));
                     }
@@ -545,6 +605,9 @@ pub struct PluginHostHandler {
     pub server_rpc: PluginServerRpcHandler,
     pub server_capabilities: ServerCapabilities,
     server_registrations: ServerRegistrations,
+    /// The name of the palette the plugin registered, if any
+    palette_provider: Option<String>,
+    permissions: PluginPermissions,
 }
 
 impl PluginHostHandler {
@@ -556,6 +619,7 @@ pub fn new(
         document_selector: DocumentSelector,
         server_rpc: PluginServerRpcHandler,
         catalog_rpc: PluginCatalogRpcHandler,
+        permissions: PluginPermissions,
     ) -> Self {
         let document_selector = document_selector
             .iter()
@@ -571,6 +635,8 @@ pub fn new(
             server_rpc,
             server_capabilities: ServerCapabilities::default(),
             server_registrations: ServerRegistrations::default(),
+            palette_provider: None,
+            permissions,
         }
     }
 
@@ -614,7 +680,7 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
                 .as_ref()
                 .and_then(|c| c.resolve_provider)
                 .unwrap_or(false),
-            DidOpenTextDocument::METHOD => {
+            DidOpenTextDocument::METHOD | DidCloseTextDocument::METHOD => {
                 match &self.server_capabilities.text_document_sync {
                     Some(TextDocumentSyncCapability::Kind(kind)) => {
                         kind != &TextDocumentSyncKind::NONE
@@ -693,12 +759,24 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
                     OneOf::Right(_) => true,
                 })
                 .unwrap_or(false),
+            RangeFormatting::METHOD => self
+                .server_capabilities
+                .document_range_formatting_provider
+                .as_ref()
+                .map(|f| match f {
+                    OneOf::Left(is_capable) => *is_capable,
+                    OneOf::Right(_) => true,
+                })
+                .unwrap_or(false),
             SemanticTokensFullRequest::METHOD => {
                 self.server_capabilities.semantic_tokens_provider.is_some()
             }
             InlayHintRequest::METHOD => {
                 self.server_capabilities.inlay_hint_provider.is_some()
             }
+            DocumentColor::METHOD | ColorPresentationRequest::METHOD => {
+                self.server_capabilities.color_provider.is_some()
+            }
             DocumentSymbolRequest::METHOD => {
                 self.server_capabilities.document_symbol_provider.is_some()
             }
@@ -712,9 +790,18 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
             SelectionRangeRequest::METHOD => {
                 self.server_capabilities.selection_range_provider.is_some()
             }
+            FoldingRangeRequest::METHOD => {
+                self.server_capabilities.folding_range_provider.is_some()
+            }
             CodeActionResolveRequest::METHOD => {
                 self.server_capabilities.code_action_provider.is_some()
             }
+            ExecuteCommand::METHOD => {
+                self.server_capabilities.execute_command_provider.is_some()
+            }
+            PALETTE_ITEMS_METHOD | SELECT_PALETTE_ITEM_METHOD => {
+                self.palette_provider.is_some()
+            }
             _ => false,
         }
     }
@@ -801,11 +888,44 @@ pub fn handle_request(
         params: Params,
         chan: Sender<Result<Value, RpcError>>,
     ) {
+        if method == ApplyWorkspaceEdit::METHOD {
+            // The editor is waited for on another thread, since applying the
+            // edit may need the plugin to answer
+            match self.workspace_edit(params) {
+                Ok(edit) => {
+                    let core_rpc = self.catalog_rpc.core_rpc.clone();
+                    thread::spawn(move || {
+                        let result = core_rpc
+                            .apply_workspace_edit(edit)
+                            .map_err(|e| anyhow!(e.message))
+                            .and_then(|applied| {
+                                Ok(serde_json::to_value(
+                                    ApplyWorkspaceEditResponse {
+                                        applied,
+                                        failure_reason: None,
+                                        failed_change: None,
+                                    },
+                                )?)
+                            });
+                        let _ = chan.send(result.map_err(request_error));
+                    });
+                }
+                Err(e) => {
+                    let _ = chan.send(Err(request_error(e)));
+                }
+            }
+            return;
+        }
         let result = self.process_request(method, params);
-        let _ = chan.send(result.map_err(|e| RpcError {
-            code: 0,
-            message: e.to_string(),
-        }));
+        let _ = chan.send(result.map_err(request_error));
+    }
+
+    /// The edit a plugin asked to apply, if it is allowed to
+    fn workspace_edit(&self, params: Params) -> Result<WorkspaceEdit> {
+        let params: ApplyWorkspaceEditParams =
+            serde_json::from_value(serde_json::to_value(params)?)?;
+        self.permissions.check_workspace_edit(&params.edit)?;
+        Ok(params.edit)
     }
 
     pub fn process_request(
@@ -824,6 +944,7 @@ pub fn process_request(
             ExecuteProcess::METHOD => {
                 let params: ExecuteProcessParams =
                     serde_json::from_value(serde_json::to_value(params)?)?;
+                self.permissions.check_process(&params.program)?;
                 let output = std::process::Command::new(params.program)
                     .args(params.args)
                     .output()?;
@@ -844,11 +965,25 @@ pub fn handle_notification(
             StartLspServer::METHOD => {
                 let params: StartLspServerParams =
                     serde_json::from_value(serde_json::to_value(params)?)?;
+                // There's no answer to tell the plugin, so the user is told
+                if let Err(err) =
+                    self.permissions.check_process(params.server_uri.as_str())
+                {
+                    self.catalog_rpc.core_rpc.show_message(
+                        format!("Plugin: {}", self.volt_display_name),
+                        ShowMessageParams {
+                            typ: MessageType::ERROR,
+                            message: err.to_string(),
+                        },
+                    );
+                    return Err(err.into());
+                }
                 let workspace = self.workspace.clone();
                 let pwd = self.pwd.clone();
                 let catalog_rpc = self.catalog_rpc.clone();
                 let volt_id = self.volt_id.clone();
                 let volt_display_name = self.volt_display_name.clone();
+                let permissions = self.permissions.clone();
                 thread::spawn(move || {
                     let _ = LspClient::start(
                         catalog_rpc,
@@ -860,6 +995,7 @@ pub fn handle_notification(
                         params.server_uri,
                         params.server_args,
                         params.options,
+                        permissions,
                     );
                 });
             }
@@ -884,6 +1020,17 @@ pub fn handle_notification(
                     serde_json::from_value(serde_json::to_value(params)?)?;
                 self.catalog_rpc.core_rpc.log_message(message);
             }
+            REGISTER_PALETTE_PROVIDER_METHOD => {
+                let provider: PluginPaletteProvider =
+                    serde_json::from_value(serde_json::to_value(params)?)?;
+                self.palette_provider = Some(provider.name.clone());
+                self.catalog_rpc
+                    .core_rpc
+                    .plugin_palette_provider_registered(
+                        self.server_rpc.plugin_id,
+                        provider,
+                    );
+            }
             _ => {
                 eprintln!("host notificaton {method} not handled");
             }
@@ -1010,6 +1157,19 @@ pub fn format_semantic_tokens(
     }
 }
 
+/// The error answered to a request of the plugin, telling it apart when it
+/// wasn't allowed
+fn request_error(e: anyhow::Error) -> RpcError {
+    RpcError {
+        code: if e.is::<PermissionError>() {
+            PERMISSION_DENIED_CODE
+        } else {
+            0
+        },
+        message: e.to_string(),
+    }
+}
+
 fn get_document_content_change(
     text: &Rope,
     delta: &RopeDelta,
@@ -16,7 +16,7 @@
 use jsonrpc_lite::{Id, Params};
 use lapce_core::directory::Directory;
 use lapce_rpc::{
-    plugin::{PluginId, VoltID, VoltInfo, VoltMetadata},
+    plugin::{PluginId, VoltCapabilities, VoltID, VoltInfo, VoltMetadata},
     style::LineStyle,
     RpcError,
 };
@@ -29,10 +29,12 @@
 use parking_lot::Mutex;
 use psp_types::Request;
 use toml_edit::easy as toml;
+use wasi_common::{DirCaps, FileCaps};
 use wasi_experimental_http_wasmtime::{HttpCtx, HttpState};
 use wasmtime_wasi::WasiCtxBuilder;
 
 use super::{
+    permission::PluginPermissions,
     psp::{
         handle_plugin_server_message, PluginHandlerNotification, PluginHostHandler,
         PluginServerHandler, RpcCallback,
@@ -287,7 +289,8 @@ pub fn find_all_volts() -> Vec<VoltMetadata> {
 ///         icon_themes: None,
 ///         dir: parent_path.canonicalize().ok(),
 ///         activation: None,
-///         config: None
+///         config: None,
+///         capabilities: None
 ///     }
 /// );
 /// let _ = std::fs::remove_file(parent_path.join("volt.toml"));
@@ -344,7 +347,9 @@ pub fn start_volt(
     configurations: Option<HashMap<String, serde_json::Value>>,
     plugin_rpc: PluginCatalogRpcHandler,
     meta: VoltMetadata,
+    granted: VoltCapabilities,
 ) -> Result<()> {
+    let permissions = PluginPermissions::new(granted, workspace.clone());
     let engine = wasmtime::Engine::default();
     let module = wasmtime::Module::from_file(
         &engine,
@@ -354,10 +359,7 @@ pub fn start_volt(
     )?;
     let mut linker = wasmtime::Linker::new(&engine);
     wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
-    HttpState::new()?.add_to_linker(&mut linker, |_| HttpCtx {
-        allowed_hosts: Some(vec!["insecure:allow-all".to_string()]),
-        max_concurrent_requests: Some(100),
-    })?;
+    add_http_to_linker(&mut linker, &permissions)?;
 
     let volt_path = meta
         .dir
@@ -390,7 +392,7 @@ pub fn start_volt(
     let stdin = Arc::new(RwLock::new(WasiPipe::new()));
     let stdout = Arc::new(RwLock::new(WasiPipe::new()));
     let stderr = Arc::new(RwLock::new(WasiPipe::new()));
-    let wasi = WasiCtxBuilder::new()
+    let mut wasi = WasiCtxBuilder::new()
         .inherit_env()?
         .env("VOLT_OS", std::env::consts::OS)?
         .env("VOLT_ARCH", std::env::consts::ARCH)?
@@ -416,8 +418,35 @@ pub fn start_volt(
                 wasmtime_wasi::ambient_authority(),
             )?,
             "/",
-        )?
-        .build();
+        )?;
+    let writable_dirs = permissions.writable_dirs();
+    for dir in &writable_dirs {
+        wasi = wasi.preopened_dir(
+            wasmtime_wasi::Dir::open_ambient_dir(
+                dir,
+                wasmtime_wasi::ambient_authority(),
+            )?,
+            dir,
+        )?;
+    }
+    let mut wasi = wasi.build();
+    // The builder opens the directories with all the rights, so the ones which
+    // can only be read are opened after them, at the next descriptors
+    let mut fd = 4 + writable_dirs.len() as u32;
+    for dir in permissions.read_only_dirs() {
+        let cap_dir = wasmtime_wasi::Dir::open_ambient_dir(
+            &dir,
+            wasmtime_wasi::ambient_authority(),
+        )?;
+        wasi.insert_dir(
+            fd,
+            Box::new(wasmtime_wasi::dir::Dir::from_cap_std(cap_dir)),
+            read_only_dir_caps(),
+            read_only_file_caps(),
+            dir,
+        );
+        fd += 1;
+    }
     let mut store = wasmtime::Store::new(&engine, wasi);
 
     let (io_tx, io_rx) = crossbeam_channel::unbounded();
@@ -467,6 +496,7 @@ pub fn start_volt(
             Vec::new(),
             rpc.clone(),
             plugin_rpc.clone(),
+            permissions,
         ),
         configurations,
     };
@@ -481,6 +511,46 @@ pub fn start_volt(
     Ok(())
 }
 
+/// The rights to look into a directory of the sandbox without changing it
+fn read_only_dir_caps() -> DirCaps {
+    DirCaps::OPEN
+        | DirCaps::READDIR
+        | DirCaps::READLINK
+        | DirCaps::PATH_FILESTAT_GET
+        | DirCaps::FILESTAT_GET
+}
+
+/// The rights to read the files opened in a read only directory
+fn read_only_file_caps() -> FileCaps {
+    FileCaps::READ
+        | FileCaps::SEEK
+        | FileCaps::TELL
+        | FileCaps::ADVISE
+        | FileCaps::FILESTAT_GET
+        | FileCaps::POLL_READWRITE
+}
+
+/// Add the http extension of the sandbox to the linker, which only makes the
+/// requests to the hosts the plugin was granted
+fn add_http_to_linker<T>(
+    linker: &mut wasmtime::Linker<T>,
+    permissions: &PluginPermissions,
+) -> Result<()> {
+    let allowed_hosts = permissions.allowed_hosts();
+    HttpState::new()?
+        .add_to_linker(linker, move |_| http_ctx(allowed_hosts.clone()))?;
+    Ok(())
+}
+
+/// The context of the http extension of the sandbox, which refuses the
+/// requests to the hosts that aren't allowed with a permission error
+fn http_ctx(allowed_hosts: Vec<String>) -> HttpCtx {
+    HttpCtx {
+        allowed_hosts: Some(allowed_hosts),
+        max_concurrent_requests: Some(100),
+    }
+}
+
 fn wasi_read_string(stdout: &Arc<RwLock<WasiPipe>>) -> Result<String> {
     let mut buf = String::new();
     stdout.write().unwrap().read_to_string(&mut buf)?;
@@ -1,8 +1,12 @@
+use super::add_http_to_linker;
 use super::load_volt;
 use super::unflatten_map;
-use lapce_rpc::plugin::VoltMetadata;
+use crate::plugin::permission::PluginPermissions;
+use lapce_rpc::plugin::{VoltCapabilities, VoltMetadata};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
 
 #[test]
 fn test_unflatten_map() {
@@ -24,6 +28,78 @@ fn test_unflatten_map() {
     );
 }
 
+/// Make a GET request to the url from a plugin which was granted the
+/// capabilities, returning the error code of the http extension
+fn plugin_http_get(permissions: &PluginPermissions, url: &str) -> i32 {
+    let wat = format!(
+        r#"
+        (module
+          (import "wasi_experimental_http" "req"
+            (func $req (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+                       (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "GET")
+          (data (i32.const 16) "{url}")
+          (func (export "run") (result i32)
+            (call $req
+              (i32.const 16) (i32.const {len})
+              (i32.const 0) (i32.const 3)
+              (i32.const 0) (i32.const 0)
+              (i32.const 0) (i32.const 0)
+              (i32.const 1024) (i32.const 1028))))
+        "#,
+        len = url.len()
+    );
+    let engine = wasmtime::Engine::default();
+    let module = wasmtime::Module::new(&engine, wat).unwrap();
+    let mut linker = wasmtime::Linker::new(&engine);
+    add_http_to_linker(&mut linker, permissions).unwrap();
+    let mut store = wasmtime::Store::new(&engine, ());
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let run = instance
+        .get_typed_func::<(), i32, _>(&mut store, "run")
+        .unwrap();
+    run.call(&mut store, ()).unwrap()
+}
+
+#[test]
+fn test_http_without_network_capability() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!(
+        "http://127.0.0.1:{}/",
+        listener.local_addr().unwrap().port()
+    );
+
+    // The request is refused before anything is sent
+    let permissions = PluginPermissions::new(VoltCapabilities::default(), None);
+    assert_ne!(plugin_http_get(&permissions, &url), 0);
+    assert_eq!(
+        listener.accept().unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+
+    // The granted host gets it
+    let permissions = PluginPermissions::new(
+        VoltCapabilities {
+            network: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        },
+        None,
+    );
+    listener.set_nonblocking(false).unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 1024];
+        let _ = stream.read(&mut buf).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .unwrap();
+    });
+    assert_eq!(plugin_http_get(&permissions, &url), 0);
+    server.join().unwrap();
+}
+
 #[test]
 fn test_load_volt() {
     let lapce_proxy_dir = std::env::current_dir()
@@ -144,7 +220,8 @@ fn test_load_volt() {
             icon_themes: Some(icon_themes_pathes),
             dir: parent_path.canonicalize().ok(),
             activation: None,
-            config: None
+            config: None,
+            capabilities: None
         }
     );
 
@@ -204,7 +281,8 @@ fn test_load_volt() {
             icon_themes: Some(icon_themes_pathes),
             dir: parent_path.canonicalize().ok(),
             activation: None,
-            config: None
+            config: None,
+            capabilities: None
         }
     );
 
@@ -230,7 +308,8 @@ fn test_load_volt() {
             icon_themes: Some(Vec::new()),
             dir: parent_path.canonicalize().ok(),
             activation: None,
-            config: None
+            config: None,
+            capabilities: None
         }
     );
 }
@@ -1,7 +1,8 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::{unbounded, Receiver};
@@ -55,6 +56,10 @@ pub trait Notify: Send {
 
 pub type PathFilter = dyn Fn(&Path) -> bool + Send + 'static;
 
+/// The longest a batch of events is held back while they keep coming, so that
+/// a long running operation still shows its progress
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(1);
+
 impl FileWatcher {
     pub fn new() -> Self {
         let (tx_event, rx_event) = unbounded();
@@ -70,28 +75,11 @@ pub fn new() -> Self {
         }
     }
 
-    pub fn notify<T: Notify + 'static>(&mut self, peer: T) {
+    /// Deliver the events to `peer` in batches: the events coming less than
+    /// `debounce` apart are coalesced and delivered at once.
+    pub fn notify<T: Notify + 'static>(&mut self, peer: T, debounce: Duration) {
         let rx_event = self.rx_event.take().unwrap();
-        let state = self.state.clone();
-        std::thread::spawn(move || {
-            while let Ok(Ok(event)) = rx_event.recv() {
-                let mut events = Vec::new();
-                {
-                    let mut state = state.lock();
-                    let WatcherState {
-                        ref mut watchees, ..
-                    } = *state;
-
-                    watchees
-                        .iter()
-                        .filter(|w| w.wants_event(&event))
-                        .map(|w| w.token)
-                        .for_each(|t| events.push((t, event.clone())));
-                }
-
-                peer.notify(events);
-            }
-        });
+        spawn_notifier(rx_event, self.state.clone(), peer, debounce);
     }
 
     /// Begin watching `path`. As `Event`s (documented in the
@@ -260,6 +248,94 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     }
 }
 
+fn spawn_notifier<T: Notify + 'static>(
+    rx_event: Receiver<Result<Event, notify::Error>>,
+    state: Arc<Mutex<WatcherState>>,
+    peer: T,
+    debounce: Duration,
+) {
+    std::thread::spawn(move || {
+        while let Some(batch) = next_batch(&rx_event, debounce) {
+            let mut events = Vec::new();
+            {
+                let mut state = state.lock();
+                let WatcherState {
+                    ref mut watchees, ..
+                } = *state;
+
+                for event in coalesce_events(batch) {
+                    watchees
+                        .iter()
+                        .filter(|w| w.wants_event(&event))
+                        .map(|w| w.token)
+                        .for_each(|t| events.push((t, event.clone())));
+                }
+            }
+
+            if !events.is_empty() {
+                peer.notify(events);
+            }
+        }
+    });
+}
+
+/// Wait for an event, then collect the ones following it until none comes for
+/// `debounce`. `None` when the watcher is gone.
+fn next_batch(
+    rx_event: &Receiver<Result<Event, notify::Error>>,
+    debounce: Duration,
+) -> Option<Vec<Event>> {
+    let mut batch = loop {
+        match rx_event.recv() {
+            Ok(Ok(event)) => break vec![event],
+            Ok(Err(_)) => {}
+            Err(_) => return None,
+        }
+    };
+
+    let deadline = Instant::now() + MAX_BATCH_DELAY;
+    loop {
+        let timeout =
+            debounce.min(deadline.saturating_duration_since(Instant::now()));
+        match rx_event.recv_timeout(timeout) {
+            Ok(Ok(event)) => batch.push(event),
+            Ok(Err(_)) => {}
+            Err(_) => return Some(batch),
+        }
+    }
+}
+
+/// Merge the events of the same paths, in the order the paths first changed.
+/// A creation, removal or rename takes over the changes of the content, since
+/// it's what the explorer has to know.
+fn coalesce_events(events: Vec<Event>) -> Vec<Event> {
+    let mut coalesced: Vec<Event> = Vec::new();
+    let mut indexes: HashMap<Vec<PathBuf>, usize> = HashMap::new();
+    for event in events {
+        match indexes.get(&event.paths) {
+            Some(&i) => {
+                if is_structural(&event.kind) || !is_structural(&coalesced[i].kind) {
+                    coalesced[i] = event;
+                }
+            }
+            None => {
+                indexes.insert(event.paths.clone(), coalesced.len());
+                coalesced.push(event);
+            }
+        }
+    }
+    coalesced
+}
+
+fn is_structural(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_))
+    )
+}
+
 fn mode_from_bool(is_recursive: bool) -> RecursiveMode {
     if is_recursive {
         RecursiveMode::Recursive
@@ -267,3 +343,67 @@ fn mode_from_bool(is_recursive: bool) -> RecursiveMode {
         RecursiveMode::NonRecursive
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{path::PathBuf, sync::Arc, time::Duration};
+
+    use crossbeam_channel::{unbounded, Sender};
+    use notify::{
+        event::{CreateKind, DataChange, ModifyKind, RemoveKind},
+        Event, EventKind,
+    };
+    use parking_lot::Mutex;
+
+    use super::{spawn_notifier, Notify, WatchToken, Watchee, WatcherState};
+
+    struct Peer(Sender<Vec<(WatchToken, Event)>>);
+
+    impl Notify for Peer {
+        fn notify(&self, events: Vec<(WatchToken, Event)>) {
+            let _ = self.0.send(events);
+        }
+    }
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(PathBuf::from(path))
+    }
+
+    #[test]
+    fn coalesce_burst_of_events() {
+        let (tx_event, rx_event) = unbounded();
+        let state = Arc::new(Mutex::new(WatcherState::default()));
+        state.lock().watchees.push(Watchee {
+            path: PathBuf::from("/workspace"),
+            recursive: true,
+            token: WatchToken(2),
+            filter: None,
+        });
+
+        for i in 0..1000 {
+            let path = format!("/workspace/node_modules/{}", i % 100);
+            let created = event(EventKind::Create(CreateKind::File), &path);
+            let modified = event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                &path,
+            );
+            tx_event.send(Ok(created)).unwrap();
+            tx_event.send(Ok(modified)).unwrap();
+        }
+        let removed = event(
+            EventKind::Remove(RemoveKind::File),
+            "/workspace/node_modules/0",
+        );
+        tx_event.send(Ok(removed)).unwrap();
+
+        let (tx, rx) = unbounded();
+        spawn_notifier(rx_event, state, Peer(tx), Duration::from_millis(50));
+
+        let events = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(events.len(), 100);
+        assert!(events.iter().all(|(token, _)| *token == WatchToken(2)));
+        assert!(events[0].1.kind.is_remove());
+        assert!(events[1..].iter().all(|(_, event)| event.kind.is_create()));
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
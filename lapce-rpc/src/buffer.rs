@@ -15,6 +15,8 @@ pub fn next() -> Self {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewBufferResponse {
     pub content: String,
+    pub encoding: String,
+    pub large_file: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,14 +10,17 @@
 use crossbeam_channel::{Receiver, Sender};
 use lsp_types::{
     CompletionResponse, LogMessageParams, ProgressParams, PublishDiagnosticsParams,
-    ShowMessageParams, SignatureHelp,
+    ShowMessageParams, SignatureHelp, WorkspaceEdit,
 };
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    file::FileNodeItem,
-    plugin::{PluginId, VoltInfo, VoltMetadata},
+    file::{FileLocation, FileNodeItem},
+    plugin::{
+        PluginDownloadProgress, PluginId, VoltCapabilities, VoltInfo, VoltMetadata,
+    },
+    proxy::{PluginPaletteProvider, SearchMatches},
     source_control::DiffInfo,
     terminal::TermId,
     RequestId, RpcError, RpcMessage,
@@ -38,6 +41,9 @@ pub enum CoreNotification {
         path: PathBuf,
         content: String,
     },
+    OpenFileDeleted {
+        path: PathBuf,
+    },
     CompletionResponse {
         request_id: usize,
         input: String,
@@ -57,9 +63,13 @@ pub enum CoreNotification {
     OpenPaths {
         window_tab_id: Option<(usize, usize)>,
         folders: Vec<PathBuf>,
-        files: Vec<PathBuf>,
+        files: Vec<FileLocation>,
+        /// The http and https urls of the documents to read
+        urls: Vec<String>,
     },
     WorkspaceFileChange {},
+    /// The settings file of the workspace changed
+    WorkspaceSettingsChange {},
     PublishDiagnostics {
         diagnostics: PublishDiagnosticsParams,
     },
@@ -70,6 +80,14 @@ pub enum CoreNotification {
         title: String,
         message: ShowMessageParams,
     },
+    PluginPaletteProviderRegistered {
+        plugin_id: PluginId,
+        provider: PluginPaletteProvider,
+    },
+    /// The plugin was stopped, so what it registered is gone
+    PluginServerStopped {
+        plugin_id: PluginId,
+    },
     LogMessage {
         message: LogMessageParams,
     },
@@ -84,6 +102,15 @@ pub enum CoreNotification {
         volt: VoltInfo,
         error: String,
     },
+    /// The volt asks for capabilities, and is started once the user answered
+    /// whether it's granted them
+    VoltPermissionsRequested {
+        volt: VoltInfo,
+        requested: VoltCapabilities,
+    },
+    VoltDownloadProgress {
+        progress: PluginDownloadProgress,
+    },
     VoltRemoving {
         volt: VoltMetadata,
         error: String,
@@ -108,6 +135,11 @@ pub enum CoreNotification {
     CloseTerminal {
         term_id: TermId,
     },
+    /// A batch of the matches of a global search that is still running
+    GlobalSearchMatches {
+        search_id: u64,
+        matches: SearchMatches,
+    },
     Log {
         level: String,
         message: String,
@@ -115,12 +147,20 @@ pub enum CoreNotification {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum CoreRequest {}
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreRequest {
+    /// Edits a plugin asked for, usually while running one of its commands,
+    /// which waits to know if they were applied
+    ApplyWorkspaceEdit { edit: WorkspaceEdit },
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "method", content = "params")]
-pub enum CoreResponse {}
+pub enum CoreResponse {
+    ApplyWorkspaceEditResponse { applied: bool },
+}
 
 pub type CoreMessage = RpcMessage<CoreRequest, CoreNotification, CoreResponse>;
 
@@ -215,6 +255,10 @@ pub fn workspace_file_change(&self) {
         self.notification(CoreNotification::WorkspaceFileChange {});
     }
 
+    pub fn workspace_settings_change(&self) {
+        self.notification(CoreNotification::WorkspaceSettingsChange {});
+    }
+
     pub fn diff_info(&self, diff: DiffInfo) {
         self.notification(CoreNotification::DiffInfo { diff });
     }
@@ -223,6 +267,10 @@ pub fn open_file_changed(&self, path: PathBuf, content: String) {
         self.notification(CoreNotification::OpenFileChanged { path, content });
     }
 
+    pub fn open_file_deleted(&self, path: PathBuf) {
+        self.notification(CoreNotification::OpenFileDeleted { path });
+    }
+
     pub fn completion_response(
         &self,
         request_id: usize,
@@ -259,6 +307,21 @@ pub fn volt_installing(&self, volt: VoltInfo, error: String) {
         self.notification(CoreNotification::VoltInstalling { volt, error });
     }
 
+    pub fn volt_permissions_requested(
+        &self,
+        volt: VoltInfo,
+        requested: VoltCapabilities,
+    ) {
+        self.notification(CoreNotification::VoltPermissionsRequested {
+            volt,
+            requested,
+        });
+    }
+
+    pub fn volt_download_progress(&self, progress: PluginDownloadProgress) {
+        self.notification(CoreNotification::VoltDownloadProgress { progress });
+    }
+
     pub fn volt_removing(&self, volt: VoltMetadata, error: String) {
         self.notification(CoreNotification::VoltRemoving { volt, error });
     }
@@ -289,10 +352,36 @@ pub fn show_message(&self, title: String, message: ShowMessageParams) {
         self.notification(CoreNotification::ShowMessage { title, message });
     }
 
+    pub fn plugin_server_stopped(&self, plugin_id: PluginId) {
+        self.notification(CoreNotification::PluginServerStopped { plugin_id });
+    }
+
+    pub fn plugin_palette_provider_registered(
+        &self,
+        plugin_id: PluginId,
+        provider: PluginPaletteProvider,
+    ) {
+        self.notification(CoreNotification::PluginPaletteProviderRegistered {
+            plugin_id,
+            provider,
+        });
+    }
+
     pub fn log_message(&self, message: LogMessageParams) {
         self.notification(CoreNotification::LogMessage { message });
     }
 
+    /// Apply the edit in the editor, waiting until it was, `false` when any
+    /// part of it couldn't be
+    pub fn apply_workspace_edit(
+        &self,
+        edit: WorkspaceEdit,
+    ) -> Result<bool, RpcError> {
+        match self.request(CoreRequest::ApplyWorkspaceEdit { edit })? {
+            CoreResponse::ApplyWorkspaceEditResponse { applied } => Ok(applied),
+        }
+    }
+
     pub fn close_terminal(&self, term_id: TermId) {
         self.notification(CoreNotification::CloseTerminal { term_id });
     }
@@ -304,6 +393,13 @@ pub fn update_terminal(&self, term_id: TermId, content: Vec<u8>) {
     pub fn home_dir(&self, path: PathBuf) {
         self.notification(CoreNotification::HomeDir { path });
     }
+
+    pub fn global_search_matches(&self, search_id: u64, matches: SearchMatches) {
+        self.notification(CoreNotification::GlobalSearchMatches {
+            search_id,
+            matches,
+        });
+    }
 }
 
 impl Default for CoreRpcHandler {
@@ -14,6 +14,73 @@ pub struct FileNodeItem {
     pub open: bool,
     pub children: HashMap<PathBuf, FileNodeItem>,
     pub children_open_count: usize,
+    /// If the file is matched by the ignore files, like `.gitignore`, of its
+    /// directory and the directories above it
+    pub ignored: bool,
+    /// If the file is hidden from the file explorer, hidden files are skipped by
+    /// [`FileNodeItem::sorted_children`] and aren't counted in
+    /// `children_open_count`
+    pub hidden: bool,
+}
+
+/// A filesystem operation of a [`ProxyRequest::FileOperations`] batch
+///
+/// [`ProxyRequest::FileOperations`]: crate::proxy::ProxyRequest::FileOperations
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperation {
+    /// Move a file or a directory to a path that doesn't exist yet, or replace
+    /// what's at the path when `overwrite` is set
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+        overwrite: bool,
+    },
+    /// Copy a file, or a directory with its content, to a path that doesn't
+    /// exist yet
+    Copy { from: PathBuf, to: PathBuf },
+    /// Move a file or a directory to the trash
+    Trash { path: PathBuf },
+    /// Permanently delete a file or a directory
+    Delete { path: PathBuf },
+}
+
+/// A file to open, with the part of it to select
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileLocation {
+    pub path: PathBuf,
+    pub selection: Option<FileSelection>,
+}
+
+impl From<PathBuf> for FileLocation {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            selection: None,
+        }
+    }
+}
+
+/// The part of a file to select when it's opened, the lines and columns are
+/// zero based
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSelection {
+    /// The lines from `start` to `end`, both included
+    Lines { start: usize, end: usize },
+    /// The cursor, with its column counted in characters
+    Position { line: usize, column: usize },
+}
+
+/// Where the path is after its ancestor, or itself, was moved from `from` to
+/// `to`
+pub fn moved_path(from: &Path, to: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(from).ok()?;
+    if relative.as_os_str().is_empty() {
+        Some(to.to_path_buf())
+    } else {
+        Some(to.join(relative))
+    }
 }
 
 impl PartialOrd for FileNodeItem {
@@ -42,16 +109,23 @@ fn cmp(&self, other: &Self) -> Ordering {
 }
 
 impl FileNodeItem {
+    /// The children that aren't hidden, sorted
     pub fn sorted_children(&self) -> Vec<&FileNodeItem> {
-        let mut children = self.children.values().collect::<Vec<&FileNodeItem>>();
+        let mut children = self
+            .children
+            .values()
+            .filter(|item| !item.hidden)
+            .collect::<Vec<&FileNodeItem>>();
         children.sort();
         children
     }
 
+    /// The children that aren't hidden, sorted
     pub fn sorted_children_mut(&mut self) -> Vec<&mut FileNodeItem> {
         let mut children = self
             .children
             .values_mut()
+            .filter(|item| !item.hidden)
             .collect::<Vec<&mut FileNodeItem>>();
         children.sort();
         children
@@ -74,6 +148,8 @@ pub fn sorted_children_mut(&mut self) -> Vec<&mut FileNodeItem> {
     /// #    open: false,
     /// #    children: HashMap::new(),
     /// #    children_open_count: 0,
+    /// #    ignored: false,
+    /// #    hidden: false,
     ///};
     /// let mut iter = node_item.ancestors_rev(Path::new("/pre/fix/foo/bar")).unwrap();
     /// assert_eq!(Some(Path::new("/pre/fix/foo")), iter.next());
@@ -127,6 +203,8 @@ pub fn add_child(&mut self, path: &Path, is_dir: bool) -> Option<()> {
                 open: false,
                 children: HashMap::new(),
                 children_open_count: 0,
+                ignored: false,
+                hidden: false,
             },
         );
         for p in path.ancestors() {
@@ -158,6 +236,7 @@ pub fn update_node_count(&mut self, path: &Path) -> Option<()> {
             node.children_open_count = if node.open {
                 node.children
                     .values()
+                    .filter(|item| !item.hidden)
                     .map(|item| item.children_open_count + 1)
                     .sum::<usize>()
             } else {
@@ -29,3 +29,9 @@ pub struct RpcError {
     pub code: i64,
     pub message: String,
 }
+
+impl RpcError {
+    /// The code of the error answering a request which no language server is
+    /// capable of, which is the JSON-RPC code of an unknown method
+    pub const NOT_CAPABLE: i64 = -32601;
+}
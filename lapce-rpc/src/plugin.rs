@@ -34,6 +34,9 @@ pub struct VoltInfo {
     pub repository: Option<String>,
     pub wasm: bool,
     pub updated_at_ts: i64,
+    /// The sha256 checksum of the archive of the volt, in hex
+    #[serde(default)]
+    pub checksum: Option<String>,
 }
 
 impl VoltInfo {
@@ -71,6 +74,7 @@ pub struct VoltMetadata {
     pub dir: Option<PathBuf>,
     pub activation: Option<VoltActivation>,
     pub config: Option<HashMap<String, VoltConfig>>,
+    pub capabilities: Option<VoltCapabilities>,
 }
 
 impl VoltMetadata {
@@ -88,6 +92,155 @@ pub fn info(&self) -> VoltInfo {
             repository: self.repository.clone(),
             wasm: self.wasm.is_some(),
             updated_at_ts: 0,
+            checksum: None,
+        }
+    }
+
+    /// The capabilities the volt asks for, which are the ones of
+    /// [`VoltCapabilities::legacy`] for the volts written before they were
+    /// declared
+    pub fn requested_capabilities(&self) -> VoltCapabilities {
+        self.capabilities
+            .clone()
+            .unwrap_or_else(VoltCapabilities::legacy)
+    }
+}
+
+/// What a volt asks to be allowed to do, declared in the `[capabilities]`
+/// table of its `volt.toml`. The user is asked to grant them before the volt
+/// is started, and the proxy refuses what wasn't granted.
+#[derive(Deserialize, Clone, Debug, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct VoltCapabilities {
+    /// The directories the volt reads files in besides its own, which are
+    /// `workspace`, absolute paths, or `*` for any
+    #[serde(default)]
+    pub fs_read: Vec<String>,
+    /// The directories the volt writes files in, like with workspace edits
+    #[serde(default)]
+    pub fs_write: Vec<String>,
+    /// The hosts the volt makes http requests to, or `*` for any
+    #[serde(default)]
+    pub network: Vec<String>,
+    /// Whether the volt starts processes, like its language server
+    #[serde(default)]
+    pub process: bool,
+}
+
+impl VoltCapabilities {
+    pub const ANY: &'static str = "*";
+    pub const WORKSPACE: &'static str = "workspace";
+
+    pub fn all() -> Self {
+        Self {
+            fs_read: vec![Self::ANY.to_string()],
+            fs_write: vec![Self::ANY.to_string()],
+            network: vec![Self::ANY.to_string()],
+            process: true,
+        }
+    }
+
+    /// What the volts written before the capabilities were declared need,
+    /// which is to work on the workspace with a language server they may
+    /// download first. The files outside of the workspace stay out of reach.
+    pub fn legacy() -> Self {
+        Self {
+            fs_read: vec![Self::WORKSPACE.to_string()],
+            fs_write: vec![Self::WORKSPACE.to_string()],
+            network: vec![Self::ANY.to_string()],
+            process: true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fs_read.is_empty()
+            && self.fs_write.is_empty()
+            && self.network.is_empty()
+            && !self.process
+    }
+
+    /// Whether everything the other capabilities allow is allowed by these
+    pub fn covers(&self, other: &VoltCapabilities) -> bool {
+        let covers = |scope: &[String], other: &[String]| {
+            scope.iter().any(|s| s == Self::ANY)
+                || other.iter().all(|o| scope.contains(o))
+        };
+        covers(&self.fs_read, &other.fs_read)
+            && covers(&self.fs_write, &other.fs_write)
+            && covers(&self.network, &other.network)
+            && (self.process || !other.process)
+    }
+
+    /// A line for each capability, to list them when the user is asked to
+    /// grant them
+    pub fn describe(&self) -> Vec<String> {
+        let scope = |scope: &[String], any: &str| {
+            if scope.iter().any(|s| s == Self::ANY) {
+                return any.to_string();
+            }
+            scope
+                .iter()
+                .map(|s| {
+                    if s == Self::WORKSPACE {
+                        "the workspace".to_string()
+                    } else {
+                        s.to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut lines = Vec::new();
+        if !self.fs_read.is_empty() {
+            lines.push(format!(
+                "Read files in {}",
+                scope(&self.fs_read, "any directory")
+            ));
+        }
+        if !self.fs_write.is_empty() {
+            lines.push(format!(
+                "Write files in {}",
+                scope(&self.fs_write, "any directory")
+            ));
+        }
+        if !self.network.is_empty() {
+            lines.push(format!(
+                "Make network requests to {}",
+                scope(&self.network, "any host")
+            ));
+        }
+        if self.process {
+            lines.push("Start processes on your computer".to_string());
+        }
+        lines
+    }
+}
+
+/// The answer of the user to the capabilities a volt asked for, which isn't
+/// asked again until the volt asks for more
+#[derive(Deserialize, Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct VoltPermissions {
+    pub requested: VoltCapabilities,
+    pub granted: VoltCapabilities,
+}
+
+/// How much of the archive of a volt was downloaded
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginDownloadProgress {
+    pub volt_id: VoltID,
+    pub downloaded: u64,
+    /// The size of the archive, if the server sent it
+    pub total: Option<u64>,
+}
+
+impl PluginDownloadProgress {
+    /// The downloaded part of the archive, between 0 and 1
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(total) if total > 0 => {
+                Some((self.downloaded as f64 / total as f64).min(1.0))
+            }
+            _ => None,
         }
     }
 }
@@ -142,7 +295,7 @@ fn from(volt: &VoltInfo) -> Self {
 
 #[cfg(test)]
 mod tests {
-    use super::{VoltID, VoltInfo, VoltMetadata};
+    use super::{VoltCapabilities, VoltID, VoltInfo, VoltMetadata};
 
     #[test]
     fn test_volt_metadata_id() {
@@ -160,6 +313,7 @@ fn test_volt_metadata_id() {
             dir: std::env::current_dir().unwrap().canonicalize().ok(),
             activation: None,
             config: None,
+            capabilities: None,
         };
         let volt_id = VoltID {
             author: "Author".to_string(),
@@ -182,6 +336,34 @@ fn test_volt_metadata_id() {
         assert_eq!(<VoltMetadata as Into<VoltID>>::into(volt_metadata), volt_id);
     }
 
+    #[test]
+    fn test_legacy_volt_capabilities() {
+        let volt_metadata = VoltMetadata {
+            name: "plugin".to_string(),
+            version: "0.1".to_string(),
+            display_name: "Plugin".to_string(),
+            author: "Author".to_string(),
+            description: "Useful plugin".to_string(),
+            icon: None,
+            repository: None,
+            wasm: None,
+            color_themes: None,
+            icon_themes: None,
+            dir: None,
+            activation: None,
+            config: None,
+            capabilities: None,
+        };
+        // A volt which doesn't declare its capabilities isn't given the files
+        // outside of the workspace
+        let requested = volt_metadata.requested_capabilities();
+        assert_eq!(requested, VoltCapabilities::legacy());
+        assert!(!requested.covers(&VoltCapabilities::all()));
+        assert!(!requested
+            .fs_write
+            .contains(&VoltCapabilities::ANY.to_string()));
+    }
+
     #[test]
     fn test_volt_metadata_info() {
         let volt_metadata = VoltMetadata {
@@ -198,6 +380,7 @@ fn test_volt_metadata_info() {
             dir: std::env::current_dir().unwrap().canonicalize().ok(),
             activation: None,
             config: None,
+            capabilities: None,
         };
         let volt_info = VoltInfo {
             name: "plugin".to_string(),
@@ -208,6 +391,7 @@ fn test_volt_metadata_info() {
             repository: None,
             wasm: false,
             updated_at_ts: 0,
+            checksum: None,
         };
         assert_eq!(volt_metadata.info(), volt_info);
     }
@@ -223,6 +407,7 @@ fn test_volt_info_id() {
             repository: None,
             wasm: false,
             updated_at_ts: 0,
+            checksum: None,
         };
         let volt_id = VoltID {
             author: "Author".to_string(),
@@ -234,4 +419,47 @@ fn test_volt_info_id() {
         assert_eq!(<&VoltInfo as Into<VoltID>>::into(&volt_info), volt_id);
         assert_eq!(<VoltInfo as Into<VoltID>>::into(volt_info), volt_id);
     }
+
+    #[test]
+    fn test_volt_capabilities_cover() {
+        let requested = VoltCapabilities {
+            fs_read: vec!["workspace".to_string()],
+            network: vec!["github.com".to_string()],
+            ..Default::default()
+        };
+        assert!(requested.covers(&requested));
+        assert!(requested.covers(&VoltCapabilities::default()));
+        assert!(VoltCapabilities::all().covers(&requested));
+        // A volt asking for more is asked about again
+        assert!(!requested.covers(&VoltCapabilities {
+            network: vec!["github.com".to_string(), "example.com".to_string()],
+            ..Default::default()
+        }));
+        assert!(!requested.covers(&VoltCapabilities {
+            process: true,
+            ..Default::default()
+        }));
+        // Denying everything doesn't cover what was asked for
+        assert!(!VoltCapabilities::default().covers(&requested));
+    }
+
+    #[test]
+    fn test_describe_volt_capabilities() {
+        let capabilities = VoltCapabilities {
+            fs_read: vec!["workspace".to_string(), "/opt/sdk".to_string()],
+            fs_write: Vec::new(),
+            network: vec!["*".to_string()],
+            process: true,
+        };
+        assert_eq!(
+            capabilities.describe(),
+            vec![
+                "Read files in the workspace, /opt/sdk".to_string(),
+                "Make network requests to any host".to_string(),
+                "Start processes on your computer".to_string(),
+            ]
+        );
+        assert!(VoltCapabilities::default().describe().is_empty());
+        assert!(VoltCapabilities::default().is_empty());
+    }
 }
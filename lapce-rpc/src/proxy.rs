@@ -12,9 +12,10 @@
 use indexmap::IndexMap;
 use lapce_xi_rope::RopeDelta;
 use lsp_types::{
-    request::GotoTypeDefinitionResponse, CodeAction, CodeActionResponse,
-    CompletionItem, Diagnostic, DocumentSymbolResponse, GotoDefinitionResponse,
-    Hover, InlayHint, Location, Position, PrepareRenameResponse, SelectionRange,
+    request::GotoTypeDefinitionResponse, CodeAction, CodeActionResponse, Color,
+    ColorInformation, ColorPresentation, Command, CompletionItem, Diagnostic,
+    DocumentSymbolResponse, FoldingRange, GotoDefinitionResponse, Hover, InlayHint,
+    Location, Position, PrepareRenameResponse, Range, SelectionRange,
     SymbolInformation, TextDocumentItem, TextEdit, WorkspaceEdit,
 };
 use parking_lot::Mutex;
@@ -22,9 +23,9 @@
 
 use crate::{
     buffer::BufferId,
-    file::FileNodeItem,
-    plugin::{PluginId, VoltInfo, VoltMetadata},
-    source_control::FileDiff,
+    file::{FileNodeItem, FileOperation},
+    plugin::{PluginId, VoltCapabilities, VoltInfo, VoltMetadata},
+    source_control::{BlameHunk, DiffHunk, FileDiff},
     style::SemanticStyles,
     terminal::TermId,
     RequestId, RpcError, RpcMessage,
@@ -44,11 +45,14 @@ pub enum ProxyRequest {
     NewBuffer {
         buffer_id: BufferId,
         path: PathBuf,
+        /// The size in bytes above which the file is opened in large file mode
+        large_file_threshold: Option<usize>,
     },
     BufferHead {
         path: PathBuf,
     },
     GlobalSearch {
+        search_id: u64,
         pattern: String,
         case_sensitive: bool,
     },
@@ -60,6 +64,17 @@ pub enum ProxyRequest {
         plugin_id: PluginId,
         action_item: Box<CodeAction>,
     },
+    /// Run a command of the plugin, like the one of a code action, which may
+    /// come back with edits through `workspace/applyEdit`
+    ExecuteCommand {
+        plugin_id: PluginId,
+        command: Command,
+    },
+    GetPluginPaletteItems {
+        plugin_id: PluginId,
+        provider: String,
+        query: String,
+    },
     GetHover {
         request_id: usize,
         path: PathBuf,
@@ -73,9 +88,15 @@ pub enum ProxyRequest {
         path: PathBuf,
         positions: Vec<Position>,
     },
+    GetFoldingRange {
+        path: PathBuf,
+    },
     GitGetRemoteFileUrl {
         file: PathBuf,
     },
+    GitBlame {
+        path: PathBuf,
+    },
     GetReferences {
         path: PathBuf,
         position: Position,
@@ -92,6 +113,15 @@ pub enum ProxyRequest {
     },
     GetInlayHints {
         path: PathBuf,
+        range: Range,
+    },
+    GetDocumentColors {
+        path: PathBuf,
+    },
+    GetColorPresentations {
+        path: PathBuf,
+        color: Color,
+        range: Range,
     },
     GetSemanticTokens {
         path: PathBuf,
@@ -107,7 +137,7 @@ pub enum ProxyRequest {
     },
     GetCodeActions {
         path: PathBuf,
-        position: Position,
+        range: Range,
         diagnostics: Vec<Diagnostic>,
     },
     GetDocumentSymbols {
@@ -120,6 +150,12 @@ pub enum ProxyRequest {
     GetDocumentFormatting {
         path: PathBuf,
     },
+    /// Format only the ranges of the document, like the lines changed since
+    /// the last commit, for the servers which can't format whole documents
+    GetDocumentRangeFormatting {
+        path: PathBuf,
+        ranges: Vec<Range>,
+    },
     GetOpenFilesContent {},
     GetFiles {
         path: String,
@@ -127,15 +163,27 @@ pub enum ProxyRequest {
     ReadDir {
         path: PathBuf,
     },
+    /// Read the document at an http or https url
+    ReadUrl {
+        url: String,
+    },
     Save {
         rev: u64,
         path: PathBuf,
+        /// Whether the LF line endings are written as CR, which the file uses
+        cr_line_endings: bool,
     },
     SaveBufferAs {
         buffer_id: BufferId,
         path: PathBuf,
         rev: u64,
         content: String,
+        cr_line_endings: bool,
+    },
+    /// Read the open file again with the encoding, which it is saved with too
+    ReopenWithEncoding {
+        path: PathBuf,
+        encoding: String,
     },
     CreateFile {
         path: PathBuf,
@@ -154,6 +202,62 @@ pub enum ProxyRequest {
         from: PathBuf,
         to: PathBuf,
     },
+    /// Apply the operations as a whole, when one of them fails the ones applied
+    /// before it are undone
+    FileOperations {
+        operations: Vec<FileOperation>,
+    },
+}
+
+impl ProxyRequest {
+    /// Whether sending the request again has the same effect as sending it
+    /// once, so it can be replayed to a proxy which was restarted before
+    /// answering it
+    pub fn is_idempotent(&self) -> bool {
+        use ProxyRequest::*;
+        match self {
+            NewBuffer { .. }
+            | BufferHead { .. }
+            | GlobalSearch { .. }
+            | CompletionResolve { .. }
+            | CodeActionResolve { .. }
+            | GetPluginPaletteItems { .. }
+            | GetHover { .. }
+            | GetSignature { .. }
+            | GetSelectionRange { .. }
+            | GetFoldingRange { .. }
+            | GitGetRemoteFileUrl { .. }
+            | GitBlame { .. }
+            | GetReferences { .. }
+            | GetDefinition { .. }
+            | GetTypeDefinition { .. }
+            | GetInlayHints { .. }
+            | GetDocumentColors { .. }
+            | GetColorPresentations { .. }
+            | GetSemanticTokens { .. }
+            | PrepareRename { .. }
+            | Rename { .. }
+            | GetCodeActions { .. }
+            | GetDocumentSymbols { .. }
+            | GetWorkspaceSymbols { .. }
+            | GetDocumentFormatting { .. }
+            | GetDocumentRangeFormatting { .. }
+            | GetOpenFilesContent {}
+            | GetFiles { .. }
+            | ReadDir { .. }
+            | ReadUrl { .. }
+            | ReopenWithEncoding { .. } => true,
+            ExecuteCommand { .. }
+            | Save { .. }
+            | SaveBufferAs { .. }
+            | CreateFile { .. }
+            | CreateDirectory { .. }
+            | TrashPath { .. }
+            | DuplicatePath { .. }
+            | RenamePath { .. }
+            | FileOperations { .. } => false,
+        }
+    }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -165,10 +269,34 @@ pub enum ProxyNotification {
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
         window_id: usize,
         tab_id: usize,
+        /// How long the file events are waited for to be coalesced into a
+        /// single update
+        file_watcher_debounce_ms: u64,
     },
     OpenFileChanged {
         path: PathBuf,
     },
+    /// The editor no longer has the file open, so the proxy stops tracking it
+    /// and tells the language servers it was closed
+    CloseBuffer {
+        path: PathBuf,
+    },
+    /// Open a buffer with the content the editor has for it, which may have
+    /// unsaved changes, in a proxy which was restarted
+    RestoreBuffer {
+        buffer_id: BufferId,
+        path: PathBuf,
+        content: String,
+        rev: u64,
+        encoding: String,
+        large_file: bool,
+    },
+    /// Turn the large file mode of an open file on or off, the language servers
+    /// are attached to the file when it is turned off
+    SetLargeFileMode {
+        path: PathBuf,
+        large_file: bool,
+    },
     OpenPaths {
         folders: Vec<PathBuf>,
         files: Vec<PathBuf>,
@@ -180,6 +308,8 @@ pub enum ProxyNotification {
         input: String,
         position: Position,
     },
+    /// The completion requests being worked on are no longer needed
+    CancelCompletion {},
     SignatureHelp {
         request_id: usize,
         path: PathBuf,
@@ -204,8 +334,12 @@ pub enum ProxyNotification {
     RemoveVolt {
         volt: VoltMetadata,
     },
+    CancelVoltDownload {
+        volt_id: VoltID,
+    },
+    /// Stop the volt and start it again from its directory
     ReloadVolt {
-        volt: VoltMetadata,
+        volt_id: VoltID,
     },
     DisableVolt {
         volt: VoltInfo,
@@ -213,6 +347,12 @@ pub enum ProxyNotification {
     EnableVolt {
         volt: VoltInfo,
     },
+    /// The capabilities the user granted the volt, which the proxy waits for
+    /// to start it
+    GrantVoltPermissions {
+        volt_id: VoltID,
+        granted: VoltCapabilities,
+    },
     GitCommit {
         message: String,
         diffs: Vec<FileDiff>,
@@ -224,7 +364,20 @@ pub enum ProxyNotification {
         files: Vec<PathBuf>,
     },
     GitDiscardWorkspaceChanges {},
+    GitStageHunk {
+        path: PathBuf,
+        hunk: DiffHunk,
+    },
+    GitUnstageHunk {
+        path: PathBuf,
+        hunk: DiffHunk,
+    },
     GitInit {},
+    SelectPluginPaletteItem {
+        plugin_id: PluginId,
+        provider: String,
+        id: String,
+    },
     TerminalWrite {
         term_id: TermId,
         content: String,
@@ -246,8 +399,16 @@ pub enum ProxyResponse {
     GitGetRemoteFileUrl {
         file_url: String,
     },
+    GitBlameResponse {
+        hunks: Vec<BlameHunk>,
+    },
     NewBufferResponse {
         content: String,
+        /// The name of the encoding the file was read with
+        encoding: String,
+        /// Whether the file is opened in large file mode, without the language
+        /// servers
+        large_file: bool,
     },
     BufferHeadResponse {
         version: String,
@@ -256,12 +417,19 @@ pub enum ProxyResponse {
     ReadDirResponse {
         items: HashMap<PathBuf, FileNodeItem>,
     },
+    ReadUrlResponse {
+        content: String,
+    },
     CompletionResolveResponse {
         item: Box<CompletionItem>,
     },
     CodeActionResolveResponse {
         item: Box<CodeAction>,
     },
+    ExecuteCommandResponse {},
+    GetPluginPaletteItemsResponse {
+        items: Vec<PluginPaletteItem>,
+    },
     HoverResponse {
         request_id: usize,
         hover: Hover,
@@ -296,9 +464,18 @@ pub enum ProxyResponse {
     GetSelectionRange {
         ranges: Vec<SelectionRange>,
     },
+    GetFoldingRange {
+        ranges: Vec<FoldingRange>,
+    },
     GetInlayHints {
         hints: Vec<InlayHint>,
     },
+    GetDocumentColors {
+        colors: Vec<ColorInformation>,
+    },
+    GetColorPresentations {
+        presentations: Vec<ColorPresentation>,
+    },
     GetSemanticTokens {
         styles: SemanticStyles,
     },
@@ -311,21 +488,73 @@ pub enum ProxyResponse {
     GetOpenFilesContentResponse {
         items: Vec<TextDocumentItem>,
     },
+    /// The last of the matches of a global search, the ones found before were
+    /// sent with [`CoreNotification::GlobalSearchMatches`]
+    ///
+    /// [`CoreNotification::GlobalSearchMatches`]: crate::core::CoreNotification::GlobalSearchMatches
     GlobalSearchResponse {
-        #[allow(clippy::type_complexity)]
-        matches: IndexMap<PathBuf, Vec<(usize, (usize, usize), String)>>,
+        matches: SearchMatches,
     },
     Success {},
     SaveResponse {},
+    /// The paths of the [`FileOperation::Trash`] operations that couldn't be
+    /// moved to the trash, they're left in place
+    FileOperationsResponse {
+        untrashed: Vec<PathBuf>,
+    },
 }
 
 pub type ProxyMessage = RpcMessage<ProxyRequest, ProxyNotification, ProxyResponse>;
 
+/// The matches of a global search grouped by the file they were found in, each
+/// match is the line number, the range of the match in the line and the line
+pub type SearchMatches = IndexMap<PathBuf, Vec<(usize, (usize, usize), String)>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadDirResponse {
     pub items: HashMap<PathBuf, FileNodeItem>,
 }
 
+/// Sent by a plugin with a [`PluginPaletteProvider`] to contribute a palette
+pub const REGISTER_PALETTE_PROVIDER_METHOD: &str = "lapce/registerPaletteProvider";
+/// Sent to a plugin with [`PaletteItemsParams`], the plugin replies with the
+/// `Vec<PluginPaletteItem>` matching the query
+pub const PALETTE_ITEMS_METHOD: &str = "lapce/paletteItems";
+/// Sent to a plugin with [`SelectPaletteItemParams`] when one of its items is
+/// selected, the plugin replies with `null`
+pub const SELECT_PALETTE_ITEM_METHOD: &str = "lapce/selectPaletteItem";
+
+/// A palette contributed by a plugin, a plugin provides at most one palette and
+/// registering another one replaces it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginPaletteProvider {
+    /// The name the plugin knows the palette by, sent back in every request
+    pub name: String,
+    /// The title of the palette in the command palette
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginPaletteItem {
+    /// Sent back to the plugin when the item is selected
+    pub id: String,
+    pub label: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaletteItemsParams {
+    pub provider: String,
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectPaletteItemParams {
+    pub provider: String,
+    pub id: String,
+}
+
 pub trait ProxyCallback: Send + FnOnce(Result<ProxyResponse, RpcError>) {}
 
 impl<F: Send + FnOnce(Result<ProxyResponse, RpcError>)> ProxyCallback for F {}
@@ -452,14 +681,18 @@ pub fn install_volt(&self, volt: VoltInfo) {
         self.notification(ProxyNotification::InstallVolt { volt });
     }
 
-    pub fn reload_volt(&self, volt: VoltMetadata) {
-        self.notification(ProxyNotification::ReloadVolt { volt });
+    pub fn reload_volt(&self, volt_id: VoltID) {
+        self.notification(ProxyNotification::ReloadVolt { volt_id });
     }
 
     pub fn remove_volt(&self, volt: VoltMetadata) {
         self.notification(ProxyNotification::RemoveVolt { volt });
     }
 
+    pub fn cancel_volt_download(&self, volt_id: VoltID) {
+        self.notification(ProxyNotification::CancelVoltDownload { volt_id });
+    }
+
     pub fn disable_volt(&self, volt: VoltInfo) {
         self.notification(ProxyNotification::DisableVolt { volt });
     }
@@ -468,11 +701,48 @@ pub fn enable_volt(&self, volt: VoltInfo) {
         self.notification(ProxyNotification::EnableVolt { volt });
     }
 
+    pub fn grant_volt_permissions(
+        &self,
+        volt_id: VoltID,
+        granted: VoltCapabilities,
+    ) {
+        self.notification(ProxyNotification::GrantVoltPermissions {
+            volt_id,
+            granted,
+        });
+    }
+
+    pub fn close_buffer(&self, path: PathBuf) {
+        self.notification(ProxyNotification::CloseBuffer { path });
+    }
+
+    /// Open a buffer with the content the editor has for it, which may have
+    /// unsaved changes
+    pub fn restore_buffer(
+        &self,
+        buffer_id: BufferId,
+        path: PathBuf,
+        content: String,
+        rev: u64,
+        encoding: String,
+        large_file: bool,
+    ) {
+        self.notification(ProxyNotification::RestoreBuffer {
+            buffer_id,
+            path,
+            content,
+            rev,
+            encoding,
+            large_file,
+        });
+    }
+
     pub fn shutdown(&self) {
         self.notification(ProxyNotification::Shutdown {});
         let _ = self.tx.send(ProxyRpc::Shutdown);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         &self,
         workspace: Option<PathBuf>,
@@ -480,6 +750,7 @@ pub fn initialize(
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
         window_id: usize,
         tab_id: usize,
+        file_watcher_debounce_ms: u64,
     ) {
         self.notification(ProxyNotification::Initialize {
             workspace,
@@ -487,6 +758,7 @@ pub fn initialize(
             plugin_configurations,
             window_id,
             tab_id,
+            file_watcher_debounce_ms,
         });
     }
 
@@ -505,6 +777,10 @@ pub fn completion(
         });
     }
 
+    pub fn cancel_completion(&self) {
+        self.notification(ProxyNotification::CancelCompletion {});
+    }
+
     pub fn signature_help(
         &self,
         request_id: usize,
@@ -554,9 +830,21 @@ pub fn new_buffer(
         &self,
         buffer_id: BufferId,
         path: PathBuf,
+        large_file_threshold: Option<usize>,
         f: impl ProxyCallback + 'static,
     ) {
-        self.request_async(ProxyRequest::NewBuffer { buffer_id, path }, f);
+        self.request_async(
+            ProxyRequest::NewBuffer {
+                buffer_id,
+                path,
+                large_file_threshold,
+            },
+            f,
+        );
+    }
+
+    pub fn set_large_file_mode(&self, path: PathBuf, large_file: bool) {
+        self.notification(ProxyNotification::SetLargeFileMode { path, large_file });
     }
 
     pub fn get_buffer_head(
@@ -604,12 +892,21 @@ pub fn rename_path(
         self.request_async(ProxyRequest::RenamePath { from, to }, f);
     }
 
+    pub fn file_operations(
+        &self,
+        operations: Vec<FileOperation>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::FileOperations { operations }, f);
+    }
+
     pub fn save_buffer_as(
         &self,
         buffer_id: BufferId,
         path: PathBuf,
         rev: u64,
         content: String,
+        cr_line_endings: bool,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
@@ -618,19 +915,31 @@ pub fn save_buffer_as(
                 path,
                 rev,
                 content,
+                cr_line_endings,
             },
             f,
         );
     }
 
+    pub fn reopen_with_encoding(
+        &self,
+        path: PathBuf,
+        encoding: String,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ReopenWithEncoding { path, encoding }, f);
+    }
+
     pub fn global_search(
         &self,
+        search_id: u64,
         pattern: String,
         case_sensitive: bool,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
             ProxyRequest::GlobalSearch {
+                search_id,
                 pattern,
                 case_sensitive,
             },
@@ -638,8 +947,21 @@ pub fn global_search(
         );
     }
 
-    pub fn save(&self, rev: u64, path: PathBuf, f: impl ProxyCallback + 'static) {
-        self.request_async(ProxyRequest::Save { rev, path }, f);
+    pub fn save(
+        &self,
+        rev: u64,
+        path: PathBuf,
+        cr_line_endings: bool,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::Save {
+                rev,
+                path,
+                cr_line_endings,
+            },
+            f,
+        );
     }
 
     pub fn get_files(&self, f: impl ProxyCallback + 'static) {
@@ -659,6 +981,10 @@ pub fn read_dir(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::ReadDir { path }, f);
     }
 
+    pub fn read_url(&self, url: String, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::ReadUrl { url }, f);
+    }
+
     pub fn completion_resolve(
         &self,
         plugin_id: PluginId,
@@ -674,6 +1000,36 @@ pub fn completion_resolve(
         );
     }
 
+    pub fn get_plugin_palette_items(
+        &self,
+        plugin_id: PluginId,
+        provider: String,
+        query: String,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetPluginPaletteItems {
+                plugin_id,
+                provider,
+                query,
+            },
+            f,
+        );
+    }
+
+    pub fn select_plugin_palette_item(
+        &self,
+        plugin_id: PluginId,
+        provider: String,
+        id: String,
+    ) {
+        self.notification(ProxyNotification::SelectPluginPaletteItem {
+            plugin_id,
+            provider,
+            id,
+        });
+    }
+
     pub fn code_action_resolve(
         &self,
         action_item: CodeAction,
@@ -689,6 +1045,15 @@ pub fn code_action_resolve(
         );
     }
 
+    pub fn execute_command(
+        &self,
+        command: Command,
+        plugin_id: PluginId,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ExecuteCommand { plugin_id, command }, f);
+    }
+
     pub fn get_hover(
         &self,
         request_id: usize,
@@ -752,14 +1117,14 @@ pub fn get_references(
     pub fn get_code_actions(
         &self,
         path: PathBuf,
-        position: Position,
+        range: Range,
         diagnostics: Vec<Diagnostic>,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
             ProxyRequest::GetCodeActions {
                 path,
-                position,
+                range,
                 diagnostics,
             },
             f,
@@ -774,6 +1139,18 @@ pub fn get_document_formatting(
         self.request_async(ProxyRequest::GetDocumentFormatting { path }, f);
     }
 
+    pub fn get_document_range_formatting(
+        &self,
+        path: PathBuf,
+        ranges: Vec<Range>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetDocumentRangeFormatting { path, ranges },
+            f,
+        );
+    }
+
     pub fn get_semantic_tokens(
         &self,
         path: PathBuf,
@@ -815,6 +1192,10 @@ pub fn git_get_remote_file_url(
         self.request_async(ProxyRequest::GitGetRemoteFileUrl { file }, f);
     }
 
+    pub fn git_blame(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GitBlame { path }, f);
+    }
+
     pub fn rename(
         &self,
         path: PathBuf,
@@ -832,8 +1213,34 @@ pub fn rename(
         );
     }
 
-    pub fn get_inlay_hints(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
-        self.request_async(ProxyRequest::GetInlayHints { path }, f);
+    pub fn get_inlay_hints(
+        &self,
+        path: PathBuf,
+        range: Range,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetInlayHints { path, range }, f);
+    }
+
+    pub fn get_document_colors(
+        &self,
+        path: PathBuf,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetDocumentColors { path }, f);
+    }
+
+    pub fn get_color_presentations(
+        &self,
+        path: PathBuf,
+        color: Color,
+        range: Range,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetColorPresentations { path, color, range },
+            f,
+        );
     }
 
     pub fn update(&self, path: PathBuf, delta: RopeDelta, rev: u64) {
@@ -855,6 +1262,14 @@ pub fn git_discard_workspace_changes(&self) {
         self.notification(ProxyNotification::GitDiscardWorkspaceChanges {});
     }
 
+    pub fn git_stage_hunk(&self, path: PathBuf, hunk: DiffHunk) {
+        self.notification(ProxyNotification::GitStageHunk { path, hunk });
+    }
+
+    pub fn git_unstage_hunk(&self, path: PathBuf, hunk: DiffHunk) {
+        self.notification(ProxyNotification::GitUnstageHunk { path, hunk });
+    }
+
     pub fn get_selection_range(
         &self,
         path: PathBuf,
@@ -863,6 +1278,10 @@ pub fn get_selection_range(
     ) {
         self.request_async(ProxyRequest::GetSelectionRange { path, positions }, f);
     }
+
+    pub fn get_folding_range(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GetFoldingRange { path }, f);
+    }
 }
 
 impl Default for ProxyRpcHandler {
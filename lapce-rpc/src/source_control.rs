@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, ops::Range, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -7,6 +7,31 @@ pub struct DiffInfo {
     pub head: String,
     pub branches: Vec<String>,
     pub diffs: Vec<FileDiff>,
+    pub hunks: HashMap<PathBuf, Vec<DiffHunk>>,
+}
+
+/// A group of adjacent changed lines of a modified file. Staged hunks
+/// compare HEAD with the index, unstaged ones compare the index with the
+/// working tree, so `old` and `new` are zero based line ranges on those
+/// sides. One of them is empty for a pure insertion or removal.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct DiffHunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    pub staged: bool,
+}
+
+/// The commit that last changed a range of lines of a file, the lines are
+/// zero based
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlameHunk {
+    pub lines: Range<usize>,
+    pub author: String,
+    /// Seconds since the epoch of the commit's author time
+    pub time: i64,
+    pub summary: String,
+    /// The lines differ from HEAD, the other fields are empty
+    pub uncommitted: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -8,9 +8,10 @@
 };
 use lapce_core::{command::FocusCommand, meta};
 use lapce_data::{
-    about::AboutFocusData,
+    about::{AboutFocusData, BuildInfo},
     command::{
-        CommandKind, LapceCommand, LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceIcons, LapceTheme},
     data::LapceTabData,
@@ -133,7 +134,7 @@ pub fn new(data: &LapceTabData) -> Self {
             mouse_pos: None,
             widget_id: data.about.widget_id,
             width: 320.0,
-            height: 384.0,
+            height: 500.0,
             padding: 40.0,
             svg_size: 80.0,
             close_rect: None,
@@ -346,16 +347,69 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
             .unwrap();
 
         ctx.draw_text(&version_layout, Point::new(self.padding, y));
-        y += version_layout.size().height + title_padding;
+        y += version_layout.size().height + line_gap / 2.0;
+
+        let build = BuildInfo::current();
+        let mut build_lines =
+            vec![build.rustc.to_string(), build.target.to_string()];
+        if let Some(commit) = build.commit {
+            build_lines.insert(0, format!("Commit: {commit}"));
+        }
+        for line in build_lines {
+            let build_layout = ctx
+                .text()
+                .new_text_layout(line)
+                .font(
+                    data.config.ui.font_family(),
+                    (data.config.ui.font_size() - 1) as f64,
+                )
+                .alignment(TextAlignment::Center)
+                .max_width(self.width - self.padding * 2.0)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(&build_layout, Point::new(self.padding, y));
+            y += build_layout.size().height + line_gap / 2.0;
+        }
+        y += title_padding - line_gap / 2.0;
 
         self.commands.clear();
 
-        for (msg, link) in [
+        let links = [
             ("Website", AboutUri::LAPCE),
             ("GitHub", AboutUri::GITHUB),
             ("Discord", AboutUri::DISCORD),
             ("Matrix", AboutUri::MATRIX),
-        ] {
+        ]
+        .into_iter()
+        .map(|(msg, link)| {
+            (
+                msg,
+                Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenURI(link.to_string()),
+                    Target::Auto,
+                ),
+            )
+        })
+        .chain([(
+            "Copy Diagnostics",
+            Command::new(
+                LAPCE_COMMAND,
+                LapceCommand {
+                    kind: CommandKind::Workbench(
+                        LapceWorkbenchCommand::CopyDiagnostics,
+                    ),
+                    data: None,
+                },
+                Target::Widget(data.id),
+            ),
+        )]);
+        for (msg, command) in links {
             let row_item = ctx
                 .text()
                 .new_text_layout(msg)
@@ -384,14 +438,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 y,
             ));
 
-            self.commands.push((
-                site_rect,
-                Command::new(
-                    LAPCE_UI_COMMAND,
-                    LapceUICommand::OpenURI(link.to_string()),
-                    Target::Auto,
-                ),
-            ));
+            self.commands.push((site_rect, command));
 
             ctx.draw_text(&row_item, Point::new(self.padding, y));
 
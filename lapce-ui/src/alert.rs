@@ -1,10 +1,14 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use druid::{
     piet::{PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder},
-    BoxConstraints, Command, Env, Event, EventCtx, FontWeight, LayoutCtx, LifeCycle,
-    LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect, RenderContext, Size, Target,
-    TextAlignment, UpdateCtx, Widget, WidgetId, WidgetPod,
+    BoxConstraints, Command, Env, Event, EventCtx, FontDescriptor, FontWeight,
+    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, TextAlignment, TimerToken, UpdateCtx, Widget,
+    WidgetId, WidgetPod,
 };
 use lapce_core::command::FocusCommand;
 use lapce_data::{
@@ -14,6 +18,12 @@
     },
     config::{LapceIcons, LapceTheme},
     data::LapceTabData,
+    markdown::{
+        layout_content::{
+            layout_content_clean_up, layouts_from_contents, LayoutContent,
+        },
+        parse_markdown,
+    },
 };
 
 pub struct AlertBox {
@@ -106,6 +116,9 @@ pub struct AlertBoxContent {
     widget_id: WidgetId,
 
     width: f64,
+    /// The width of an alert with notes, which need more room
+    notes_width: f64,
+    max_notes_height: f64,
     padding: f64,
     svg_size: f64,
     button_height: f64,
@@ -117,8 +130,23 @@ pub struct AlertBoxContent {
     title_origin: Point,
     msg_layout: Option<PietTextLayout>,
     msg_origin: Point,
+    /// The notes of the alert, built when it's shown
+    notes_shown: usize,
+    notes_layout: Vec<LayoutContent>,
+    notes_rect: Rect,
+    /// The height of all the notes, which scroll when it's more than the
+    /// height of `notes_rect`
+    notes_height: f64,
+    notes_scroll: f64,
 
     mouse_down_point: Point,
+
+    /// The alert whose timeout is running
+    shown: usize,
+    timer: TimerToken,
+    deadline: Option<Instant>,
+    /// The time left before the alert is dismissed while the mouse is over it
+    remaining: Option<Duration>,
 }
 
 impl AlertBoxContent {
@@ -126,6 +154,8 @@ pub fn new(data: &LapceTabData) -> Self {
         Self {
             widget_id: data.alert.widget_id,
             width: 250.0,
+            notes_width: 450.0,
+            max_notes_height: 300.0,
             padding: 20.0,
             svg_size: 50.0,
             button_height: 30.0,
@@ -136,8 +166,70 @@ pub fn new(data: &LapceTabData) -> Self {
             title_origin: Point::ZERO,
             msg_layout: None,
             msg_origin: Point::ZERO,
+            notes_shown: 0,
+            notes_layout: Vec::new(),
+            notes_rect: Rect::ZERO,
+            notes_height: 0.0,
+            notes_scroll: 0.0,
             mouse_down_point: Point::ZERO,
+            shown: 0,
+            timer: TimerToken::INVALID,
+            deadline: None,
+            remaining: None,
+        }
+    }
+
+    /// Dismiss the alert once the time left runs out
+    fn start_timer(&mut self, timer: TimerToken, remaining: Duration) {
+        self.timer = timer;
+        self.deadline = Some(Instant::now() + remaining);
+        self.remaining = None;
+    }
+
+    /// Keep the time left while the mouse is over the alert
+    fn pause_timer(&mut self) {
+        if let Some(deadline) = self.deadline.take() {
+            self.timer = TimerToken::INVALID;
+            self.remaining =
+                Some(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    /// Lay out the notes of the alert, if it's not the one they were built for
+    fn update_notes(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
+        if self.notes_shown == data.alert.shown {
+            return;
+        }
+        self.notes_shown = data.alert.shown;
+        self.notes_scroll = 0.0;
+
+        let notes = data
+            .alert
+            .content
+            .notes
+            .as_deref()
+            .map(|notes| parse_markdown(notes, 1.5, &data.config))
+            .unwrap_or_default();
+        layout_content_clean_up(&mut self.notes_layout, data);
+        self.notes_layout = layouts_from_contents(ctx, data, notes.iter());
+
+        let font = FontDescriptor::new(data.config.ui.font_family())
+            .with_size((data.config.ui.font_size() - 1) as f64);
+        let text_color = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+        for layout in self.notes_layout.iter_mut() {
+            layout.set_font(font.clone());
+            layout.set_text_color(text_color.clone());
         }
+        ctx.request_layout();
+    }
+
+    /// Scroll the notes by the delta, keeping the last of them at the bottom
+    fn scroll_notes(&mut self, delta: f64) {
+        let max_scroll = (self.notes_height - self.notes_rect.height()).max(0.0);
+        self.notes_scroll = (self.notes_scroll + delta).clamp(0.0, max_scroll);
     }
 
     fn icon_hit_test(&self, mouse_event: &MouseEvent) -> bool {
@@ -165,6 +257,7 @@ fn event(
         data: &mut LapceTabData,
         env: &Env,
     ) {
+        self.update_notes(ctx, data);
         match event {
             Event::KeyDown(key_event) => {
                 let mut focus = AlertFocusData::new(data);
@@ -181,6 +274,13 @@ fn event(
             Event::MouseDown(mouse_event) => {
                 self.mouse_down_point = mouse_event.pos;
             }
+            Event::Wheel(wheel_event) => {
+                if self.notes_rect.contains(wheel_event.pos) {
+                    self.scroll_notes(wheel_event.wheel_delta.y);
+                    ctx.request_paint();
+                    ctx.set_handled();
+                }
+            }
             Event::MouseUp(mouse_event) => {
                 if self.cancel_rect.contains(self.mouse_down_point)
                     && self.cancel_rect.contains(mouse_event.pos)
@@ -201,11 +301,9 @@ fn event(
                     if rect.contains(self.mouse_down_point)
                         && rect.contains(mouse_event.pos)
                     {
-                        ctx.submit_command(Command::new(
-                            LAPCE_COMMAND,
-                            data.alert.content.buttons[i].2.clone(),
-                            Target::Widget(data.alert.content.buttons[i].1),
-                        ));
+                        if let Some(command) = data.alert.button_command(i) {
+                            ctx.submit_command(command);
+                        }
                         ctx.submit_command(Command::new(
                             LAPCE_COMMAND,
                             LapceCommand {
@@ -222,16 +320,31 @@ fn event(
             Event::Command(cmd) if cmd.is(LAPCE_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_COMMAND);
                 if let CommandKind::Focus(FocusCommand::ModalClose) = &command.kind {
-                    let alert = Arc::make_mut(&mut data.alert);
-                    alert.active = false;
-                    ctx.submit_command(Command::new(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::Focus,
-                        Target::Widget(*data.focus),
-                    ));
+                    // The next alert in the queue keeps the focus
+                    if !Arc::make_mut(&mut data.alert).dismiss() {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::Focus,
+                            Target::Widget(*data.focus),
+                        ));
+                    }
+                    self.update_notes(ctx, data);
                     ctx.set_handled();
                 }
             }
+            Event::Timer(token) if *token == self.timer => {
+                self.timer = TimerToken::INVALID;
+                self.deadline = None;
+                ctx.submit_command(Command::new(
+                    LAPCE_COMMAND,
+                    LapceCommand {
+                        kind: CommandKind::Focus(FocusCommand::ModalClose),
+                        data: None,
+                    },
+                    Target::Widget(self.widget_id),
+                ));
+                ctx.set_handled();
+            }
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
                 if let LapceUICommand::Focus = &command {
@@ -245,20 +358,40 @@ fn event(
 
     fn lifecycle(
         &mut self,
-        _ctx: &mut LifeCycleCtx,
-        _event: &LifeCycle,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
         _data: &LapceTabData,
         _env: &Env,
     ) {
+        if let LifeCycle::HotChanged(hot) = event {
+            if *hot {
+                self.pause_timer();
+            } else if let Some(remaining) = self.remaining {
+                let timer = ctx.request_timer(remaining);
+                self.start_timer(timer, remaining);
+            }
+        }
     }
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
+        ctx: &mut UpdateCtx,
         _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        if data.alert.shown != self.shown {
+            self.shown = data.alert.shown;
+            self.timer = TimerToken::INVALID;
+            self.deadline = None;
+            self.remaining = data.alert.content.timeout;
+            if let Some(remaining) = self.remaining {
+                if !ctx.is_hot() {
+                    let timer = ctx.request_timer(remaining);
+                    self.start_timer(timer, remaining);
+                }
+            }
+        }
     }
 
     fn layout(
@@ -266,13 +399,15 @@ fn layout(
         ctx: &mut LayoutCtx,
         _bc: &BoxConstraints,
         data: &LapceTabData,
-        _env: &Env,
+        env: &Env,
     ) -> Size {
+        let width = if self.notes_layout.is_empty() {
+            self.width
+        } else {
+            self.notes_width
+        };
         self.svg_rect = Rect::ZERO
-            .with_origin(Point::new(
-                self.width / 2.0,
-                self.padding + self.svg_size / 2.0,
-            ))
+            .with_origin(Point::new(width / 2.0, self.padding + self.svg_size / 2.0))
             .inflate(self.svg_size / 2.0, self.svg_size / 2.0);
 
         let title_layout = ctx
@@ -285,7 +420,7 @@ fn layout(
             .default_attribute(TextAttribute::Weight(FontWeight::BOLD))
             .alignment(TextAlignment::Center)
             .set_line_height(1.2)
-            .max_width(self.width - self.padding * 2.0)
+            .max_width(width - self.padding * 2.0)
             .text_color(
                 data.config
                     .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
@@ -306,7 +441,7 @@ fn layout(
             )
             .alignment(TextAlignment::Center)
             .set_line_height(1.2)
-            .max_width(self.width - self.padding * 2.0)
+            .max_width(width - self.padding * 2.0)
             .text_color(
                 data.config
                     .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
@@ -322,28 +457,44 @@ fn layout(
                 + self.padding / 2.0,
         );
 
-        let mut y = self.msg_origin.y + msg_layout.size().height + self.padding;
+        let mut y = self.msg_origin.y + msg_layout.size().height;
+        self.notes_height = 0.0;
+        for layout in self.notes_layout.iter_mut() {
+            layout.set_max_width(&data.images, width - self.padding * 2.0);
+            layout.rebuild_if_needed(ctx.text(), env);
+            self.notes_height += layout.size(&data.images, &data.config).height;
+        }
+        let notes_origin = Point::new(self.padding, y + self.padding / 2.0);
+        self.notes_rect = Size::new(
+            width - self.padding * 2.0,
+            self.notes_height.min(self.max_notes_height),
+        )
+        .to_rect()
+        .with_origin(notes_origin);
+        self.scroll_notes(0.0);
+        if !self.notes_layout.is_empty() {
+            y = self.notes_rect.y1;
+        }
+
+        y += self.padding;
         self.buttons.clear();
         for _ in data.alert.content.buttons.iter() {
             let rect = Rect::ZERO
-                .with_origin(Point::new(
-                    self.width / 2.0,
-                    y + self.button_height / 2.0,
-                ))
-                .inflate(self.width / 2.0 - self.padding, self.button_height / 2.0);
+                .with_origin(Point::new(width / 2.0, y + self.button_height / 2.0))
+                .inflate(width / 2.0 - self.padding, self.button_height / 2.0);
             self.buttons.push(rect);
             y += self.button_height + self.padding / 2.0;
         }
 
         y += self.padding / 2.0;
         self.cancel_rect = Rect::ZERO
-            .with_origin(Point::new(self.width / 2.0, y + self.button_height / 2.0))
-            .inflate(self.width / 2.0 - self.padding, self.button_height / 2.0);
+            .with_origin(Point::new(width / 2.0, y + self.button_height / 2.0))
+            .inflate(width / 2.0 - self.padding, self.button_height / 2.0);
 
         self.title_layout = Some(title_layout);
         self.msg_layout = Some(msg_layout);
 
-        Size::new(self.width, y + self.button_height + self.padding)
+        Size::new(width, y + self.button_height + self.padding)
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
@@ -373,7 +524,40 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
         ctx.draw_text(self.title_layout.as_ref().unwrap(), self.title_origin);
         ctx.draw_text(self.msg_layout.as_ref().unwrap(), self.msg_origin);
 
-        for (i, (text, _, _)) in data.alert.content.buttons.iter().enumerate() {
+        // Long notes scroll rather than pushing the buttons off screen
+        ctx.with_save(|ctx| {
+            ctx.clip(self.notes_rect);
+            let mut origin = self.notes_rect.origin() - (0.0, self.notes_scroll);
+            for layout in &self.notes_layout {
+                let height = layout.size(&data.images, &data.config).height;
+                if origin.y + height >= self.notes_rect.y0
+                    && origin.y <= self.notes_rect.y1
+                {
+                    layout.draw(ctx, &data.images, &data.config, origin);
+                }
+                origin.y += height;
+            }
+        });
+        if self.notes_height > self.notes_rect.height() {
+            let view = self.notes_rect.height();
+            let bar_height = view * view / self.notes_height;
+            let bar_y = self.notes_rect.y0
+                + (view - bar_height) * self.notes_scroll
+                    / (self.notes_height - view);
+            let bar = Rect::new(
+                self.notes_rect.x1 + 4.0,
+                bar_y,
+                self.notes_rect.x1 + 8.0,
+                bar_y + bar_height,
+            );
+            ctx.fill(
+                bar,
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_SCROLL_BAR),
+            );
+        }
+
+        for (i, button) in data.alert.content.buttons.iter().enumerate() {
             ctx.stroke(
                 self.buttons[i],
                 data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
@@ -381,7 +565,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
             );
             let text_layout = ctx
                 .text()
-                .new_text_layout(text.to_string())
+                .new_text_layout(button.text.clone())
                 .font(
                     data.config.ui.font_family(),
                     data.config.ui.font_size() as f64,
@@ -1,8 +1,9 @@
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
-use std::{path::PathBuf, process::Stdio, sync::Arc};
+use std::{io::BufRead, path::PathBuf, process::Stdio, sync::Arc};
 
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, ErrorKind, Parser};
 use druid::{
     AppDelegate, AppLauncher, Command, Env, Event, LocalizedString, Point, Region,
     Size, Target, Widget, WidgetExt, WidgetPod, WindowDesc, WindowHandle, WindowId,
@@ -14,6 +15,7 @@
 use lapce_core::command::{EditCommand, FocusCommand};
 use lapce_core::meta;
 use lapce_data::{
+    cli::resolve_paths,
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::LapceConfig,
     data::{
@@ -21,6 +23,7 @@
         LapceWorkspaceType,
     },
     db::{TabsInfo, WindowInfo},
+    update::UpdateStatus,
 };
 
 use crate::{
@@ -40,9 +43,31 @@ struct Cli {
     /// Don't return instantly when opened in terminal
     #[clap(short, long, action)]
     wait: bool,
+    /// Paths to open, pass `-` to read newline separated paths from stdin. A file
+    /// can be followed by the lines to select in it, like `src/main.rs:10-20`, or
+    /// by a position like `src/main.rs:10:5`, or `src/main.rs:10+4` to put the
+    /// cursor after the first 4 characters of the line. The documents at http and
+    /// https urls are read into scratch buffers
     paths: Vec<PathBuf>,
 }
 
+/// Read the newline separated paths piped into `lapce -`
+fn read_paths_from_stdin() -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(anyhow!("no paths were read from stdin"));
+    }
+    Ok(paths)
+}
+
 pub fn build_window(data: &mut LapceWindowData) -> impl Widget<LapceData> {
     LapceWindow::new(data).lens(LapceWindowLens(data.window_id))
 }
@@ -54,17 +79,36 @@ pub fn launch() {
         load_shell_env();
     }
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+
+    if cli.paths.iter().any(|p| p.as_os_str() == "-") {
+        match read_paths_from_stdin() {
+            Ok(stdin_paths) => {
+                cli.paths.retain(|p| p.as_os_str() != "-");
+                cli.paths.extend(stdin_paths);
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        }
+    }
 
     // small hack to unblock terminal if launched from it
     if !cli.wait {
-        let mut args = std::env::args().collect::<Vec<_>>();
-        args.push("--wait".to_string());
-        let mut cmd = std::process::Command::new(&args[0]);
+        let program = std::env::args_os().next().unwrap_or_default();
+        let mut cmd = std::process::Command::new(program);
         #[cfg(target_os = "windows")]
         cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd.arg("--wait");
+        if cli.new {
+            cmd.arg("--new");
+        }
+        // stdin has already been consumed, so hand the resolved paths over
         if let Err(why) = cmd
-            .args(&args[1..])
+            .arg("--")
+            .args(&cli.paths)
+            .stdin(Stdio::null())
             .stderr(Stdio::null())
             .stdout(Stdio::null())
             .spawn()
@@ -73,18 +117,16 @@ pub fn launch() {
         };
         return;
     }
-    let pwd = std::env::current_dir().unwrap_or_default();
-    let paths: Vec<PathBuf> = cli
-        .paths
-        .iter()
-        .map(|p| pwd.join(p).canonicalize().unwrap_or_default())
-        .collect();
+    let paths = match resolve_paths(&cli.paths, std::env::current_dir) {
+        Ok(paths) => paths,
+        Err(e) => Cli::command().error(ErrorKind::Io, e).exit(),
+    };
     if !cli.new && LapceData::try_open_in_existing_process(&paths).is_ok() {
         return;
     }
 
     #[cfg(feature = "updater")]
-    lapce_data::update::cleanup();
+    lapce_data::update::health_check();
 
     let mut log_dispatch = fern::Dispatch::new()
         .format(|out, message, record| {
@@ -525,7 +567,7 @@ fn new_window(
         let mut window_data = LapceWindowData::new(
             data.keypress.clone(),
             data.latest_release.clone(),
-            data.update_in_process,
+            data.update_status,
             data.log_file.clone(),
             current_panels,
             data.panel_orders.clone(),
@@ -629,7 +671,7 @@ fn command(
                 let mut window_data = LapceWindowData::new(
                     data.keypress.clone(),
                     data.latest_release.clone(),
-                    data.update_in_process,
+                    data.update_status,
                     data.log_file.clone(),
                     None,
                     data.panel_orders.clone(),
@@ -686,11 +728,11 @@ fn command(
                             Some(release.clone());
                         return druid::Handled::Yes;
                     }
-                    LapceUICommand::UpdateStarted => {
-                        data.update_in_process = true;
+                    LapceUICommand::UpdateProgress(status) => {
+                        data.update_status = Some(*status);
                     }
                     LapceUICommand::UpdateFailed => {
-                        data.update_in_process = false;
+                        data.update_status = None;
                     }
                     LapceUICommand::RestartToUpdate(process_path, release) => {
                         let _ = data.db.save_app(data);
@@ -698,16 +740,36 @@ fn command(
                         let release = release.clone();
                         let event_sink = ctx.get_external_handle();
                         std::thread::spawn(move || {
+                            let progress = |status| {
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::UpdateProgress(status),
+                                    Target::Global,
+                                );
+                            };
                             let do_update = || -> anyhow::Result<()> {
                                 log::info!("start to down new versoin");
-                                let src =
-                                    lapce_data::update::download_release(&release)?;
+                                // Only tell when the percentage changes, not
+                                // for every chunk
+                                let mut shown = None;
+                                let src = lapce_data::update::download_release(
+                                    &release,
+                                    |status| {
+                                        let desc = status.desc();
+                                        if shown.as_ref() != Some(&desc) {
+                                            shown = Some(desc);
+                                            progress(status);
+                                        }
+                                    },
+                                )?;
 
                                 log::info!("start to extract");
+                                progress(UpdateStatus::Installing);
                                 let path = lapce_data::update::extract(
                                     &src,
                                     &process_path,
                                 )?;
+                                lapce_data::update::mark_pending(&release, &path)?;
 
                                 log::info!("now restart {path:?}");
                                 lapce_data::update::restart(&path)?;
@@ -715,11 +777,6 @@ fn command(
                                 Ok(())
                             };
 
-                            let _ = event_sink.submit_command(
-                                LAPCE_UI_COMMAND,
-                                LapceUICommand::UpdateStarted,
-                                Target::Global,
-                            );
                             if let Err(err) = do_update() {
                                 log::error!("Failed to update: {err}");
                                 let _ = event_sink.submit_command(
@@ -731,10 +788,28 @@ fn command(
                         });
                         return druid::Handled::Yes;
                     }
+                    LapceUICommand::RollbackUpdate(process_path) => {
+                        let _ = data.db.save_app(data);
+                        let process_path = process_path.clone();
+                        std::thread::spawn(move || {
+                            let do_rollback = || -> anyhow::Result<()> {
+                                let path =
+                                    lapce_data::update::rollback(&process_path)?;
+                                log::info!("now restart {path:?}");
+                                lapce_data::update::restart(&path)?;
+                                Ok(())
+                            };
+                            if let Err(err) = do_rollback() {
+                                log::error!("Failed to roll back: {err}");
+                            }
+                        });
+                        return druid::Handled::Yes;
+                    }
                     LapceUICommand::OpenPaths {
                         window_tab_id,
                         folders,
                         files,
+                        urls,
                     } => {
                         if let Some((window_id, tab_id)) = window_tab_id {
                             if let Some(window_data) = data.windows.get(window_id) {
@@ -760,9 +835,17 @@ fn command(
                                     for file in files {
                                         ctx.submit_command(Command::new(
                                             LAPCE_UI_COMMAND,
-                                            LapceUICommand::OpenFile(
-                                                file.to_path_buf(),
-                                                false,
+                                            LapceUICommand::OpenFileLocation(
+                                                file.clone(),
+                                            ),
+                                            Target::Widget(*tab_id),
+                                        ));
+                                    }
+                                    for url in urls {
+                                        ctx.submit_command(Command::new(
+                                            LAPCE_UI_COMMAND,
+                                            LapceUICommand::OpenUrlDocument(
+                                                url.clone(),
                                             ),
                                             Target::Widget(*tab_id),
                                         ));
@@ -792,7 +875,14 @@ fn command(
                         for file in files {
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
-                                LapceUICommand::OpenFile(file.to_path_buf(), false),
+                                LapceUICommand::OpenFileLocation(file.clone()),
+                                Target::Window(*data.active_window),
+                            ));
+                        }
+                        for url in urls {
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::OpenUrlDocument(url.clone()),
                                 Target::Window(*data.active_window),
                             ));
                         }
@@ -827,5 +917,8 @@ fn window_added(
         _env: &Env,
         _ctx: &mut druid::DelegateCtx,
     ) {
+        // The new version launched, so it doesn't need to be rolled back
+        #[cfg(feature = "updater")]
+        lapce_data::update::confirm_update();
     }
 }
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use druid::{
+    kurbo::{Circle, Line},
+    piet::GradientStop,
+    BoxConstraints, Color, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    LinearGradient, PaintCtx, Point, Rect, RenderContext, Size, UnitPoint,
+    UpdateCtx, Widget,
+};
+use lapce_data::{
+    config::LapceTheme,
+    data::LapceTabData,
+    document_color::{swatch_color, Hsv},
+};
+
+const PADDING: f64 = 10.0;
+const WIDTH: f64 = 200.0;
+const SQUARE_HEIGHT: f64 = 150.0;
+const HUE_HEIGHT: f64 = 14.0;
+const PICKED_HEIGHT: f64 = 20.0;
+
+/// The part of the picker being dragged
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Drag {
+    /// The square of the saturation and value of the hue
+    Square,
+    Hue,
+}
+
+/// The picker of a document color: the saturation and value are picked in the
+/// square, the hue in the bar below it, and clicking the picked color at the
+/// bottom shows the ways to write it
+pub struct ColorPicker {
+    drag: Option<Drag>,
+}
+
+impl ColorPicker {
+    pub fn new() -> Self {
+        Self { drag: None }
+    }
+
+    fn square_rect() -> Rect {
+        Rect::from_origin_size((PADDING, PADDING), (WIDTH, SQUARE_HEIGHT))
+    }
+
+    fn hue_rect() -> Rect {
+        Rect::from_origin_size(
+            (PADDING, Self::square_rect().y1 + PADDING),
+            (WIDTH, HUE_HEIGHT),
+        )
+    }
+
+    fn picked_rect() -> Rect {
+        Rect::from_origin_size(
+            (PADDING, Self::hue_rect().y1 + PADDING),
+            (WIDTH, PICKED_HEIGHT),
+        )
+    }
+
+    /// Pick the color at the position in the dragged part
+    fn pick(&self, data: &mut LapceTabData, pos: Point) {
+        let ratio = |value: f64, start: f64, len: f64| {
+            ((value - start) / len).clamp(0.0, 1.0)
+        };
+        let picker = Arc::make_mut(&mut data.color_picker);
+        match self.drag {
+            Some(Drag::Square) => {
+                let rect = Self::square_rect();
+                picker.hsv.saturation = ratio(pos.x, rect.x0, rect.width());
+                picker.hsv.value = 1.0 - ratio(pos.y, rect.y0, rect.height());
+            }
+            Some(Drag::Hue) => {
+                let rect = Self::hue_rect();
+                picker.hsv.hue = ratio(pos.x, rect.x0, rect.width()) * 360.0;
+            }
+            None => {}
+        }
+    }
+}
+
+impl Default for ColorPicker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The color of the hue at full saturation and value
+fn hue_color(hue: f64) -> Color {
+    swatch_color(
+        &Hsv {
+            hue,
+            saturation: 1.0,
+            value: 1.0,
+        }
+        .to_color(1.0),
+    )
+}
+
+impl Widget<LapceTabData> for ColorPicker {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        if !data.color_picker.active {
+            return;
+        }
+        match event {
+            Event::MouseDown(mouse) if mouse.button.is_left() => {
+                if Self::square_rect().contains(mouse.pos) {
+                    self.drag = Some(Drag::Square);
+                } else if Self::hue_rect().contains(mouse.pos) {
+                    self.drag = Some(Drag::Hue);
+                } else if Self::picked_rect().contains(mouse.pos) {
+                    Arc::make_mut(&mut data.color_picker).request_presentations(
+                        ctx.get_external_handle(),
+                        &data.proxy,
+                    );
+                }
+                if self.drag.is_some() {
+                    ctx.set_active(true);
+                    self.pick(data, mouse.pos);
+                }
+                ctx.set_handled();
+            }
+            Event::MouseMove(mouse) => {
+                if ctx.is_active() {
+                    self.pick(data, mouse.pos);
+                }
+                if ctx.is_active() || ctx.is_hot() {
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseUp(_) => {
+                if ctx.is_active() {
+                    self.drag = None;
+                    ctx.set_active(false);
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !Arc::ptr_eq(&old_data.color_picker, &data.color_picker) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        bc.constrain(Size::new(
+            WIDTH + PADDING * 2.0,
+            Self::picked_rect().y1 + PADDING,
+        ))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let picker = &data.color_picker;
+        // The saturation goes from white to the hue, and the value to black
+        let square = Self::square_rect();
+        ctx.fill(
+            square,
+            &LinearGradient::new(
+                UnitPoint::LEFT,
+                UnitPoint::RIGHT,
+                (Color::WHITE, hue_color(picker.hsv.hue)),
+            ),
+        );
+        ctx.fill(
+            square,
+            &LinearGradient::new(
+                UnitPoint::TOP,
+                UnitPoint::BOTTOM,
+                (Color::TRANSPARENT, Color::BLACK),
+            ),
+        );
+        let picked = Point::new(
+            square.x0 + picker.hsv.saturation * square.width(),
+            square.y0 + (1.0 - picker.hsv.value) * square.height(),
+        );
+        ctx.stroke(Circle::new(picked, 5.0), &Color::BLACK, 2.0);
+        ctx.stroke(Circle::new(picked, 4.0), &Color::WHITE, 1.0);
+
+        let hue = Self::hue_rect();
+        let stops = (0..=6)
+            .map(|i| GradientStop {
+                pos: i as f32 / 6.0,
+                color: hue_color(i as f64 * 60.0),
+            })
+            .collect::<Vec<_>>();
+        ctx.fill(
+            hue,
+            &LinearGradient::new(UnitPoint::LEFT, UnitPoint::RIGHT, stops),
+        );
+        let x = hue.x0 + picker.hsv.hue / 360.0 * hue.width();
+        ctx.stroke(
+            Line::new(Point::new(x, hue.y0 - 2.0), Point::new(x, hue.y1 + 2.0)),
+            &Color::WHITE,
+            2.0,
+        );
+
+        let picked = Self::picked_rect();
+        ctx.fill(picked, &swatch_color(&picker.color()));
+        ctx.stroke(
+            picked,
+            data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+            1.0,
+        );
+    }
+}
@@ -1,10 +1,9 @@
 use std::sync::Arc;
 
 use druid::{
-    piet::{Text, TextAttribute, TextLayoutBuilder},
     theme, BoxConstraints, Command, Data, Env, Event, EventCtx, FontDescriptor,
-    FontFamily, FontWeight, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point,
-    Rect, RenderContext, Size, Target, UpdateCtx, Widget, WidgetId, WidgetPod,
+    FontFamily, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, UpdateCtx, Widget, WidgetId, WidgetPod,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
@@ -12,7 +11,7 @@
     config::LapceTheme,
     data::LapceTabData,
     document::BufferContent,
-    list::ListData,
+    list::{ListData, ListRow, ListText},
     markdown::{
         layout_content::{
             layout_content_clean_up, layouts_from_contents, LayoutContent,
@@ -22,7 +21,7 @@
 };
 
 use crate::{
-    list::{List, ListPaint},
+    list::{paint_list_row, List, ListPaint, ListRowStyle},
     scroll::{LapceIdentityWrapper, LapceScroll},
 };
 
@@ -101,6 +100,9 @@ fn update_documentation(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData)
         }
 
         let documentation = if data.config.editor.completion_show_documentation {
+            Arc::make_mut(&mut data.completion)
+                .resolve_current_item(&data.proxy, ctx.get_external_handle());
+
             let current_item = (!data.completion.is_empty())
                 .then(|| data.completion.current_item())
                 .flatten();
@@ -387,7 +389,7 @@ fn paint(
         &self,
         ctx: &mut PaintCtx,
         data: &ListData<Self, D>,
-        _env: &Env,
+        env: &Env,
         line: usize,
     ) {
         let size = ctx.size();
@@ -422,34 +424,34 @@ fn paint(
             ctx.draw_svg(&svg, rect, Some(&color));
         }
 
-        let focus_color = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
-        let content = self.item.label.as_str();
-
-        let mut text_layout = ctx
-            .text()
-            .new_text_layout(content.to_string())
-            .font(
-                FontFamily::new_unchecked(data.config.editor.font_family.clone()),
-                data.config.editor.font_size as f64,
-            )
-            .text_color(
-                data.config
+        let row = ListRow {
+            icon: None,
+            label: ListText::new(self.item.label.clone(), self.indices.clone()),
+            detail: self
+                .item
+                .detail
+                .as_ref()
+                .filter(|detail| !detail.is_empty())
+                .map(|detail| ListText::new(detail.clone(), Vec::new())),
+        };
+        paint_list_row(
+            ctx,
+            data,
+            env,
+            line,
+            &row,
+            &ListRowStyle {
+                icon_width: line_height,
+                font_family: FontFamily::new_unchecked(
+                    data.config.editor.font_family.clone(),
+                ),
+                font_size: data.config.editor.font_size as f64,
+                color: data
+                    .config
                     .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
                     .clone(),
-            );
-        for i in &self.indices {
-            let i = *i;
-            text_layout = text_layout.range_attribute(
-                i..i + 1,
-                TextAttribute::TextColor(focus_color.clone()),
-            );
-            text_layout = text_layout
-                .range_attribute(i..i + 1, TextAttribute::Weight(FontWeight::BOLD));
-        }
-        let text_layout = text_layout.build().unwrap();
-        let y = line_height * line as f64 + text_layout.y_offset(line_height);
-        let point = Point::new(line_height + 5.0, y);
-        ctx.draw_text(&text_layout, point);
+            },
+        );
     }
 }
 
@@ -1,7 +1,7 @@
 use std::{collections::HashMap, iter::Iterator, sync::Arc, time::Duration};
 
 use druid::{
-    kurbo::{BezPath, Line},
+    kurbo::{Affine, BezPath, Line},
     piet::{PietText, PietTextLayout, Text, TextLayout as _, TextLayoutBuilder},
     BoxConstraints, Color, Command, Env, Event, EventCtx, InternalLifeCycle,
     LayoutCtx, LifeCycle, LifeCycleCtx, Modifiers, MouseButton, MouseEvent,
@@ -10,9 +10,10 @@
 };
 use lapce_core::{
     buffer::DiffLines,
-    command::{EditCommand, FocusCommand},
+    command::{EditCommand, FocusCommand, FoldCommand},
     cursor::{ColPosition, CursorMode},
     mode::{Mode, VisualMode},
+    wrap::VisualLines,
 };
 use lapce_data::{
     command::{
@@ -65,6 +66,8 @@ pub struct LapceEditor {
     /// of hover info (if there is any)
     mouse_hover_timer: TimerToken,
     drag_timer: TimerToken,
+    /// Where an Alt+drag block selection started
+    block_anchor: Option<usize>,
 }
 
 impl LapceEditor {
@@ -77,6 +80,7 @@ pub fn new(view_id: WidgetId, editor_id: WidgetId) -> Self {
             mouse_mods: Modifiers::empty(),
             mouse_hover_timer: TimerToken::INVALID,
             drag_timer: TimerToken::INVALID,
+            block_anchor: None,
         }
     }
 
@@ -172,12 +176,22 @@ fn mouse_move(
             return;
         }
 
+        if ctx.is_active() && mods.alt() {
+            let anchor = *self
+                .block_anchor
+                .get_or_insert_with(|| editor_data.editor.cursor.offset());
+            editor_data.select_block(ctx.text(), anchor, mouse_pos, config);
+            return;
+        }
+
+        let wrap_width = editor_data.editor.wrap_width(ctx.text(), config);
         if ctx.is_active() {
             let (new_offset, _) = editor_data.doc.offset_of_point(
                 ctx.text(),
                 editor_data.get_mode(),
                 mouse_pos,
                 &editor_data.editor.view,
+                wrap_width,
                 config,
             );
             let editor = Arc::make_mut(&mut editor_data.editor);
@@ -190,6 +204,7 @@ fn mouse_move(
             Mode::Insert,
             mouse_pos,
             &editor_data.editor.view,
+            wrap_width,
             config,
         );
         let within_scroll = self.mouse_within_scroll(editor_data, mouse_pos);
@@ -217,8 +232,24 @@ fn mouse_down(
         ctx.set_handled();
         match mouse_event.button {
             MouseButton::Left => {
+                if let Some(line) =
+                    Self::sticky_header_line_at(&editor_data, mouse_event, env)
+                {
+                    // Clicking a sticky header jumps to its symbol
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::JumpToPosition(
+                            Some(self.view_id),
+                            editor_data.doc.sticky_header_position(line),
+                            true,
+                        ),
+                        Target::Auto,
+                    ));
+                    return editor_data;
+                }
                 self.left_click(ctx, mouse_event, &mut editor_data, &data.config);
                 editor_data.get_code_actions(ctx);
+                editor_data.get_inline_blame(ctx);
                 editor_data.cancel_completion();
                 // TODO: Don't cancel over here, because it would good to allow the user to
                 // select text inside the hover/signature data
@@ -229,6 +260,7 @@ fn mouse_down(
                 self.mouse_hover_timer = TimerToken::INVALID;
                 self.right_click(ctx, &mut editor_data, mouse_event, &data.config);
                 editor_data.get_code_actions(ctx);
+                editor_data.get_inline_blame(ctx);
                 editor_data.cancel_completion();
                 editor_data.cancel_signature();
                 editor_data.cancel_hover();
@@ -247,6 +279,25 @@ fn mouse_down(
         editor_data
     }
 
+    /// The line of the sticky header under the mouse, if there's one
+    fn sticky_header_line_at(
+        data: &LapceEditorBufferData,
+        mouse_event: &MouseEvent,
+        env: &Env,
+    ) -> Option<usize> {
+        if !data.config.editor.sticky_header || mouse_event.count != 1 {
+            return None;
+        }
+
+        let info = data.editor.sticky_header.borrow();
+        let y = mouse_event.pos.y - data.editor.scroll_offset.y;
+        if y < 0.0 || y >= info.height {
+            return None;
+        }
+        let i = (y / Self::line_height(data, env)).floor() as usize;
+        info.lines.get(i).copied()
+    }
+
     fn left_click(
         &mut self,
         ctx: &mut EventCtx,
@@ -275,11 +326,13 @@ fn right_click(
         mouse_event: &MouseEvent,
         config: &LapceConfig,
     ) {
+        let wrap_width = editor_data.editor.wrap_width(ctx.text(), config);
         let (offset, _) = editor_data.doc.offset_of_point(
             ctx.text(),
             editor_data.get_mode(),
             mouse_event.pos,
             &editor_data.editor.view,
+            wrap_width,
             config,
         );
 
@@ -292,6 +345,13 @@ fn right_click(
             editor_data.single_click(ctx, mouse_event, config);
         }
 
+        let item = |kind: CommandKind| {
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand { kind, data: None },
+                enabled: true,
+            })
+        };
         let menu_items = if let BufferContent::File(_) = editor_data.doc.content() {
             vec![
                 MenuKind::Item(MenuItem {
@@ -310,6 +370,7 @@ fn right_click(
                     },
                     enabled: true,
                 }),
+                item(CommandKind::Focus(FocusCommand::PeekDefinition)),
                 MenuKind::Separator,
                 MenuKind::Item(MenuItem {
                     desc: None,
@@ -319,6 +380,17 @@ fn right_click(
                     },
                     enabled: true,
                 }),
+                item(CommandKind::Focus(FocusCommand::FormatDocument)),
+                MenuKind::Submenu {
+                    desc: "Folding".to_string(),
+                    items: vec![
+                        item(CommandKind::Fold(FoldCommand::Fold)),
+                        item(CommandKind::Fold(FoldCommand::Unfold)),
+                        MenuKind::Separator,
+                        item(CommandKind::Fold(FoldCommand::FoldAll)),
+                        item(CommandKind::Fold(FoldCommand::UnfoldAll)),
+                    ],
+                },
                 MenuKind::Separator,
                 MenuKind::Item(MenuItem {
                     desc: None,
@@ -469,6 +541,24 @@ pub fn get_size(
                             (line_height * lines as f64).max(editor_size.height)
                         },
                     )
+                } else if let Some(visual_lines) = data.visual_lines(text) {
+                    // The lines only need to scroll horizontally when they are
+                    // wrapped at a column past the width of the editor, or when
+                    // they are only folded
+                    let lines = visual_lines.len();
+                    let lines_width = match visual_lines.width() {
+                        Some(wrap_width) => wrap_width + width,
+                        None => width * data.doc.buffer().max_len() as f64,
+                    };
+                    Size::new(
+                        lines_width.max(editor_size.width),
+                        if data.config.editor.scroll_beyond_last_line {
+                            (line_height * lines as f64 - line_height).max(0.0)
+                                + editor_size.height
+                        } else {
+                            (line_height * lines as f64).max(editor_size.height)
+                        },
+                    )
                 } else {
                     Size::new(
                         (width * data.doc.buffer().max_len() as f64)
@@ -849,6 +939,18 @@ fn paint_content(
         let line_height = Self::line_height(data, env);
         let screen_lines = match &data.editor.view {
             EditorView::Normal => {
+                if let Some(visual_lines) = data.visual_lines(ctx.text()) {
+                    Self::paint_wrapped_content(
+                        ctx,
+                        data,
+                        &visual_lines,
+                        font_size,
+                        is_focused,
+                        env,
+                    );
+                    return;
+                }
+
                 let rect = ctx.region().bounding_box();
                 let start_line = (rect.y0 / line_height).floor() as usize;
                 let end_line = (rect.y1 / line_height).ceil() as usize;
@@ -880,6 +982,7 @@ fn paint_content(
         };
 
         Self::paint_current_line(ctx, data, &screen_lines);
+        Self::paint_rulers(ctx, data);
         Self::paint_cursor_new(ctx, data, &screen_lines, is_focused, env);
         Self::paint_find(ctx, data, &screen_lines);
         Self::paint_text(ctx, data, &screen_lines);
@@ -917,6 +1020,8 @@ fn paint_content(
             {
                 let text = match data.palette.palette_type {
                     PaletteType::SshHost => Some("select or enter your ssh connection like [user@]host[:port]"),
+                    PaletteType::PluginVersion => Some("enter the plugin and version to install like author.name@version"),
+                    PaletteType::TerminalTabName(_) => Some("enter the name of the terminal tab, or nothing to name it after its terminal"),
                     _ => None,
                 };
                 if let Some(text) = text {
@@ -943,6 +1048,114 @@ fn paint_content(
         }
     }
 
+    /// Paint the content of the editor when its lines are soft wrapped. Each visual line
+    /// is painted like the line it's a segment of, shifted into place and clipped to the
+    /// segment.
+    fn paint_wrapped_content(
+        ctx: &mut PaintCtx,
+        data: &LapceEditorBufferData,
+        visual_lines: &VisualLines,
+        font_size: usize,
+        is_focused: bool,
+        env: &Env,
+    ) {
+        let line_padding = Self::line_padding(data, env);
+        let line_height = Self::line_height(data, env);
+        let self_size = ctx.size();
+        let rect = ctx.region().bounding_box();
+        let start = (rect.y0 / line_height).floor() as usize;
+        let end =
+            ((rect.y1 / line_height).ceil() as usize + 1).min(visual_lines.len());
+
+        let rows: Vec<_> = (start..end)
+            .map(|visual_line| {
+                let segment = visual_lines.visual_line(visual_line);
+                let first = visual_lines.visual_line_of_line(segment.line);
+                let mut info = HashMap::new();
+                info.insert(
+                    segment.line,
+                    LineInfo {
+                        font_size,
+                        x: 0.0,
+                        y: first as f64 * line_height + line_padding,
+                        line_height,
+                    },
+                );
+                let screen_lines = ScreenLines {
+                    lines: vec![segment.line],
+                    info,
+                };
+                let y = visual_line as f64 * line_height;
+                let y_shift = (visual_line - first) as f64 * line_height;
+                (segment, screen_lines, y, y_shift)
+            })
+            .collect();
+
+        for (_, screen_lines, y, y_shift) in &rows {
+            ctx.with_save(|ctx| {
+                ctx.clip(Rect::new(0.0, *y, self_size.width, y + line_height));
+                ctx.transform(Affine::translate((0.0, *y_shift)));
+                Self::paint_current_line(ctx, data, screen_lines);
+            });
+        }
+        Self::paint_rulers(ctx, data);
+
+        for (segment, screen_lines, y, y_shift) in &rows {
+            let x_shift = data.doc.visual_line_x_shift(
+                ctx.text(),
+                segment,
+                font_size,
+                &data.config,
+            );
+            let phantom_text =
+                data.doc.line_phantom_text(&data.config, segment.line);
+            let x0 = if segment.is_continuation() {
+                let start = phantom_text.col_after(segment.start, false);
+                data.doc
+                    .line_point_of_line_col(
+                        ctx.text(),
+                        segment.line,
+                        start,
+                        font_size,
+                        &data.config,
+                    )
+                    .x
+                    + x_shift
+            } else {
+                0.0
+            };
+            let x1 = if segment.last {
+                self_size.width
+            } else {
+                let end = phantom_text.col_after(segment.end, false);
+                data.doc
+                    .line_point_of_line_col(
+                        ctx.text(),
+                        segment.line,
+                        end,
+                        font_size,
+                        &data.config,
+                    )
+                    .x
+                    + x_shift
+            };
+
+            ctx.with_save(|ctx| {
+                ctx.clip(Rect::new(x0, *y, x1, y + line_height));
+                ctx.transform(Affine::translate((x_shift, *y_shift)));
+                Self::paint_cursor_new(ctx, data, screen_lines, is_focused, env);
+                Self::paint_find(ctx, data, screen_lines);
+                Self::paint_text(ctx, data, screen_lines);
+                Self::paint_diagnostics(ctx, data, screen_lines);
+                Self::paint_snippet(ctx, data, screen_lines);
+                Self::highlight_scope_and_brackets(ctx, data, screen_lines);
+            });
+        }
+
+        // Sticky headers aren't shown for wrapped lines, but their state is reset
+        Self::paint_sticky_headers(ctx, data, env);
+    }
+
     fn paint_text(
         ctx: &mut PaintCtx,
         data: &LapceEditorBufferData,
@@ -985,6 +1198,7 @@ fn paint_text(
 
         let tab_width = data.config.tab_width(
             ctx.text(),
+            data.doc.language(),
             data.config.editor.font_family(),
             data.config.editor.font_size,
         );
@@ -1218,6 +1432,30 @@ fn paint_current_line(
         }
     }
 
+    fn paint_rulers(ctx: &mut PaintCtx, data: &LapceEditorBufferData) {
+        if data.editor.content.is_input()
+            || data.editor.content.is_palette()
+            || data.editor.view != EditorView::Normal
+        {
+            return;
+        }
+        let rulers = data.config.rulers_for(data.doc.language());
+        if rulers.is_empty() {
+            return;
+        }
+        let char_width = data.config.editor_char_width(ctx.text());
+        let rect = ctx.region().bounding_box();
+        for col in rulers {
+            let x = *col as f64 * char_width;
+            ctx.stroke(
+                Line::new(Point::new(x, rect.y0), Point::new(x, rect.y1)),
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_INDENT_GUIDE),
+                1.0,
+            );
+        }
+    }
+
     fn paint_cursor_new(
         ctx: &mut PaintCtx,
         data: &LapceEditorBufferData,
@@ -1506,9 +1744,14 @@ fn paint_find(
 
         let cursor_offset = data.editor.cursor.offset();
 
-        // Update the find with the whole document, so the count will be accurate in the widget
-        data.doc
-            .update_find(&data.find, 0, data.doc.buffer().last_line());
+        // Update the find with the whole document, so the count will be accurate in the widget.
+        // Large files only get the visible lines searched, and the count is estimated.
+        if data.doc.large_file() {
+            data.doc.update_find(&data.find, start_line, end_line);
+        } else {
+            data.doc
+                .update_find(&data.find, 0, data.doc.buffer().last_line());
+        }
         if data.find.search_string.is_some() {
             for region in data
                 .doc
@@ -1598,7 +1841,7 @@ fn paint_sticky_headers(
         info.height = 0.0;
         info.last_y_diff = 0.0;
 
-        if !data.editor.view.is_normal() {
+        if !data.editor.view.is_normal() || data.visual_lines(ctx.text()).is_some() {
             return;
         }
 
@@ -1611,12 +1854,23 @@ fn paint_sticky_headers(
         let y_diff = y0 - start_line as f64 * line_height;
         let mut last_sticky_should_scroll = false;
 
+        // The headers of a line, limited to the outermost ones that fit
+        let max_lines = data.config.editor.sticky_header_max_lines;
+        let sticky_headers = |line: usize| {
+            data.doc.sticky_headers(line).map(|mut lines| {
+                if max_lines > 0 {
+                    lines.truncate(max_lines);
+                }
+                lines
+            })
+        };
+
         let mut sticky_lines = Vec::new();
-        if let Some(lines) = data.doc.sticky_headers(start_line) {
+        if let Some(lines) = sticky_headers(start_line) {
             let total_lines = lines.len();
             if total_lines > 0 {
                 let line = start_line + total_lines;
-                if let Some(new_lines) = data.doc.sticky_headers(line) {
+                if let Some(new_lines) = sticky_headers(line) {
                     if new_lines.len() > total_lines {
                         sticky_lines = new_lines;
                     } else {
@@ -1624,7 +1878,7 @@ fn paint_sticky_headers(
                         last_sticky_should_scroll = new_lines.len() < total_lines;
                         if new_lines.len() < total_lines {
                             if let Some(new_new_lines) =
-                                data.doc.sticky_headers(start_line + total_lines - 1)
+                                sticky_headers(start_line + total_lines - 1)
                             {
                                 if new_new_lines.len() < total_lines {
                                     sticky_lines.pop();
@@ -1721,11 +1975,11 @@ fn paint_snippet(
         screen_lines: &ScreenLines,
     ) {
         if let Some(snippet) = data.editor.snippet.as_ref() {
-            for (_, (start, end)) in snippet {
+            for (start, end) in snippet.regions() {
                 let (start_line, start_col) =
-                    data.doc.buffer().offset_to_line_col(*start.min(end));
+                    data.doc.buffer().offset_to_line_col(start.min(end));
                 let (end_line, end_col) =
-                    data.doc.buffer().offset_to_line_col(*start.max(end));
+                    data.doc.buffer().offset_to_line_col(start.max(end));
 
                 for line in &screen_lines.lines {
                     let line = *line;
@@ -2340,10 +2594,12 @@ fn event(
             }
             Event::MouseUp(_mouse_event) => {
                 self.mouse_mods = Modifiers::empty();
+                self.block_anchor = None;
                 ctx.set_active(false);
             }
             Event::MouseDown(mouse_event) => {
                 self.mouse_mods = mouse_event.mods;
+                self.block_anchor = None;
                 let doc = data.main_split.editor_doc(self.view_id);
                 let editor =
                     data.main_split.editors.get(&self.view_id).unwrap().clone();
@@ -2356,11 +2612,13 @@ fn event(
                         data.main_split.editors.get(&self.view_id).unwrap().clone();
                     let mut editor_data = data.editor_view_content(self.view_id);
                     let doc = editor_data.doc.clone();
+                    let wrap_width = editor.wrap_width(ctx.text(), &data.config);
                     let (offset, _) = doc.offset_of_point(
                         ctx.text(),
                         editor.cursor.get_mode(),
                         self.mouse_pos,
                         &editor.view,
+                        wrap_width,
                         &data.config,
                     );
                     editor_data.update_hover(ctx, offset);
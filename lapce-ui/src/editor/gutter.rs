@@ -52,9 +52,7 @@ fn event(
                         {
                             let line_height =
                                 data.config.editor.line_height() as f64;
-                            let offset = data.editor.cursor.offset();
-                            let (line, _) =
-                                data.doc.buffer().offset_to_line_col(offset);
+                            let line = Self::cursor_line(ctx.text(), &data);
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
                                 LapceUICommand::ShowCodeActions(Some(
@@ -568,14 +566,23 @@ fn paint_gutter_code_lens(
         }
     }
 
+    /// The line of the cursor, which is a visual line when the lines are wrapped
+    fn cursor_line(text: &mut PietText, data: &LapceEditorBufferData) -> usize {
+        let offset = data.editor.cursor.offset();
+        let (line, col) = data.doc.buffer().offset_to_line_col(offset);
+        match data.visual_lines(text) {
+            Some(visual_lines) => visual_lines.visual_line_of_line_col(line, col),
+            None => line,
+        }
+    }
+
     fn code_actions_rect(
         &self,
         text: &mut PietText,
         data: &LapceEditorBufferData,
     ) -> Rect {
         let line_height = data.config.editor.line_height() as f64;
-        let offset = data.editor.cursor.offset();
-        let (line, _) = data.doc.buffer().offset_to_line_col(offset);
+        let line = Self::cursor_line(text, data);
 
         let width = 16.0;
         let height = 16.0;
@@ -605,6 +612,40 @@ fn paint_code_actions_hint(
         }
     }
 
+    /// Paint the indicator of a folded line, where the code actions hint goes,
+    /// unless the hint is shown on that line
+    fn paint_fold_indicator(
+        &self,
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        y: f64,
+    ) {
+        let line_height = data.config.editor.line_height() as f64;
+        let char_width = data.config.editor_char_width(ctx.text());
+        let size = 16.0;
+        let rect = Size::new(size, size).to_rect().with_origin(Point::new(
+            self.width + char_width + 3.0,
+            (line_height - size) / 2.0 + y - data.editor.scroll_offset.y,
+        ));
+        let code_actions_shown = *data.main_split.active == Some(self.view_id)
+            && data
+                .current_code_actions()
+                .map(|(_, actions)| !actions.is_empty())
+                .unwrap_or(false);
+        if code_actions_shown
+            && rect.contains(self.code_actions_rect(ctx.text(), data).center())
+        {
+            return;
+        }
+
+        let svg = data.config.ui_svg(LapceIcons::ITEM_CLOSED);
+        ctx.draw_svg(
+            &svg,
+            rect,
+            Some(data.config.get_color_unchecked(LapceTheme::EDITOR_DIM)),
+        );
+    }
+
     fn paint_sticky_header(
         &self,
         ctx: &mut PaintCtx,
@@ -680,7 +721,22 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
             }
             let line_height = data.config.editor.line_height() as f64;
             let scroll_offset = data.editor.scroll_offset;
-            let start_line = (scroll_offset.y / line_height).floor() as usize;
+            let visual_lines = data.visual_lines(ctx.text());
+            // The line a visual line is part of, and the y of the top of a line
+            let line_of_visual_line = |visual_line: usize| match &visual_lines {
+                Some(visual_lines) => visual_lines.visual_line(visual_line).line,
+                None => visual_line,
+            };
+            let line_y = |line: usize| {
+                let visual_line = match &visual_lines {
+                    Some(visual_lines) => visual_lines.visual_line_of_line(line),
+                    None => line,
+                };
+                visual_line as f64 * line_height
+            };
+            let start_line = line_of_visual_line(
+                (scroll_offset.y / line_height).floor() as usize,
+            );
             let num_lines = (ctx.size().height / line_height).floor() as usize;
             let last_line = data.doc.buffer().last_line();
             let current_line = data
@@ -691,7 +747,10 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
 
             let line_label_length =
                 (last_line + 1).to_string().len() as f64 * char_width;
-            let last_displayed_line = (start_line + num_lines + 1).min(last_line);
+            let last_displayed_line = line_of_visual_line(
+                (scroll_offset.y / line_height).floor() as usize + num_lines + 1,
+            )
+            .min(last_line);
 
             let sequential_line_numbers = *data.main_split.active
                 != Some(data.view_id)
@@ -701,6 +760,14 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
             let font_family = data.config.editor.font_family();
 
             for line in start_line..last_displayed_line + 1 {
+                if visual_lines
+                    .as_ref()
+                    .map(|visual_lines| visual_lines.is_hidden(line))
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+
                 let line_no = if sequential_line_numbers || line == current_line {
                     line + 1
                 } else {
@@ -734,15 +801,21 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
                 let x = line_label_length - text_layout.size().width;
 
                 // Vertically centered
-                let y = line_height * line as f64 - scroll_offset.y
+                let y = line_y(line) - scroll_offset.y
                     + text_layout.y_offset(line_height);
 
                 ctx.draw_text(&text_layout, Point::new(x, y));
+
+                if data.doc.folds().is_folded(line) {
+                    self.paint_fold_indicator(data, ctx, line_y(line));
+                }
             }
 
             if let Some(history) = data.doc.get_history("head") {
-                let end_line =
-                    (scroll_offset.y + rect.height() / line_height).ceil() as usize;
+                let end_line = line_of_visual_line(
+                    ((scroll_offset.y + rect.height()) / line_height).ceil()
+                        as usize,
+                );
 
                 let mut line = 0;
                 let mut last_change = None;
@@ -786,8 +859,7 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
                     if let Some(color) = color.cloned() {
                         let removed_height = 10.0;
                         let x = self.width + char_width;
-                        let mut y =
-                            (line - len) as f64 * line_height - scroll_offset.y;
+                        let mut y = line_y(line - len) - scroll_offset.y;
                         if len == 0 {
                             y -= removed_height / 2.0;
                         }
@@ -810,7 +882,7 @@ fn paint_gutter(&self, data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
                                 if len == 0 {
                                     removed_height
                                 } else {
-                                    line_height * len as f64
+                                    line_y(line) - line_y(line - len)
                                 },
                             ),
                         );
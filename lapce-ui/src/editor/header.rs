@@ -170,12 +170,10 @@ pub fn paint_buffer(
                 if let Some(_compare) = data.editor.compare.as_ref() {
                     file_name += " (Working tree)";
                 }
-                if let Some(workspace_path) = workspace.path.as_ref() {
-                    path = path
-                        .strip_prefix(workspace_path)
-                        .unwrap_or(&path)
-                        .to_path_buf();
+                if data.doc.is_deleted_on_disk() {
+                    file_name += " (Deleted)";
                 }
+                path = workspace.display_path(&path);
                 let folder = path
                     .parent()
                     .and_then(|s| s.to_str())
@@ -106,6 +106,7 @@ pub fn remove_child(
             }
             editor_tab.children.remove(i)
         };
+        editor_tab.pinned.remove(&removed_child.widget_id());
         if focus && !editor_tab.children.is_empty() {
             ctx.submit_command(Command::new(
                 LAPCE_UI_COMMAND,
@@ -173,20 +174,7 @@ fn mouse_up(
                         match direction {
                             Some(direction) => {
                                 let (split_direction, shift_current) =
-                                    match direction {
-                                        SplitMoveDirection::Up => {
-                                            (SplitDirection::Horizontal, true)
-                                        }
-                                        SplitMoveDirection::Down => {
-                                            (SplitDirection::Horizontal, false)
-                                        }
-                                        SplitMoveDirection::Right => {
-                                            (SplitDirection::Vertical, false)
-                                        }
-                                        SplitMoveDirection::Left => {
-                                            (SplitDirection::Vertical, true)
-                                        }
-                                    };
+                                    direction.split_direction();
                                 let editor_tab = data
                                     .main_split
                                     .editor_tabs
@@ -196,12 +184,16 @@ fn mouse_up(
 
                                 let new_editor_tab_id = WidgetId::next();
                                 let mut child = child.clone();
-                                child.set_editor_tab(data, new_editor_tab_id);
+                                child.set_editor_tab(
+                                    &mut data.main_split,
+                                    new_editor_tab_id,
+                                );
                                 let mut new_editor_tab = LapceEditorTabData {
                                     widget_id: new_editor_tab_id,
                                     split: split_id,
                                     active: 0,
                                     children: vec![child.clone()].into(),
+                                    pinned: im::HashSet::new(),
                                     layout_rect: Rc::new(RefCell::new(Rect::ZERO)),
                                     content_is_hot: Rc::new(RefCell::new(false)),
                                 };
@@ -252,7 +244,10 @@ fn mouse_up(
                                     return;
                                 }
                                 let mut child = child.clone();
-                                child.set_editor_tab(data, self.widget_id);
+                                child.set_editor_tab(
+                                    &mut data.main_split,
+                                    self.widget_id,
+                                );
                                 let editor_tab = data
                                     .main_split
                                     .editor_tabs
@@ -289,7 +284,7 @@ fn mouse_up(
                         *Arc::make_mut(&mut data.drag) = None;
                     }
                 }
-                DragContent::Panel(..) => {}
+                DragContent::Panel(..) | DragContent::ExplorerPaths(..) => {}
             }
         }
     }
@@ -354,10 +349,35 @@ fn event(
                             .unwrap();
                         let editor_tab = Arc::make_mut(editor_tab);
 
+                        if *from_index >= self.children.len() {
+                            return;
+                        }
+                        let to_index = editor_tab.move_child(*from_index, *to_index);
                         let child = self.children.remove(*from_index);
-                        self.children.insert(*to_index, child);
-                        let child = editor_tab.children.remove(*from_index);
-                        editor_tab.children.insert(*to_index, child);
+                        self.children.insert(to_index, child);
+                        ctx.request_layout();
+                        return;
+                    }
+                    LapceUICommand::PinTab(index)
+                    | LapceUICommand::UnpinTab(index) => {
+                        let editor_tab = data
+                            .main_split
+                            .editor_tabs
+                            .get_mut(&self.widget_id)
+                            .unwrap();
+                        let editor_tab = Arc::make_mut(editor_tab);
+                        if *index >= self.children.len() {
+                            return;
+                        }
+
+                        let to_index =
+                            if matches!(command, LapceUICommand::PinTab(_)) {
+                                editor_tab.pin(*index)
+                            } else {
+                                editor_tab.unpin(*index)
+                            };
+                        let child = self.children.remove(*index);
+                        self.children.insert(to_index, child);
                         ctx.request_layout();
                         return;
                     }
@@ -674,6 +694,9 @@ fn paint(
             );
         }
         ctx.draw_svg(&self.svg, svg_rect, self.svg_color.as_ref());
+        if editor_tab.is_pinned(tab_idx) {
+            return;
+        }
         ctx.draw_text(
             &self.text_layout,
             Point::new(svg_rect.x1 + 5.0, self.text_layout.y_offset(size.height)),
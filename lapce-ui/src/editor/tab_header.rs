@@ -13,8 +13,10 @@
         CommandKind, LapceCommand, LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceIcons, LapceTheme},
-    data::{EditorTabChild, LapceTabData},
+    container::TabOverflow,
+    data::{EditorTabChild, LapceData, LapceTabData},
     document::BufferContent,
+    menu::escape_mnemonic,
 };
 
 use crate::{
@@ -29,6 +31,11 @@ pub struct LapceEditorTabHeader {
         LapceScroll<LapceTabData, LapceEditorTabHeaderContent>,
     >,
     icons: Vec<LapceIcon>,
+    /// The button listing all the tabs, when the tabs overflowing go in a
+    /// dropdown
+    dropdown_rect: Option<Rect>,
+    /// Whether the active tab should be scrolled into view after the next layout
+    pending_ensure_visible: bool,
     mouse_pos: Point,
     hover_rect: Option<Rect>,
     is_hot: bool,
@@ -44,6 +51,8 @@ pub fn new(widget_id: WidgetId) -> Self {
             widget_id,
             content: WidgetPod::new(content),
             icons: Vec::new(),
+            dropdown_rect: None,
+            pending_ensure_visible: false,
             mouse_pos: Point::ZERO,
             is_hot: false,
             hover_rect: None,
@@ -51,21 +60,54 @@ pub fn new(widget_id: WidgetId) -> Self {
     }
 
     fn icon_hit_test(&mut self, mouse_event: &MouseEvent) -> bool {
-        for icon in self.icons.iter() {
-            if icon.rect.contains(mouse_event.pos) {
-                self.hover_rect = Some(icon.rect);
+        for rect in self
+            .icons
+            .iter()
+            .map(|icon| icon.rect)
+            .chain(self.dropdown_rect)
+        {
+            if rect.contains(mouse_event.pos) {
+                self.hover_rect = Some(rect);
                 return true;
             }
         }
         false
     }
 
-    fn mouse_down(&self, ctx: &mut EventCtx, mouse_event: &MouseEvent) {
+    fn mouse_down(
+        &self,
+        ctx: &mut EventCtx,
+        data: &LapceTabData,
+        mouse_event: &MouseEvent,
+    ) {
         for icon in self.icons.iter() {
             if icon.rect.contains(mouse_event.pos) {
                 ctx.submit_command(icon.command.clone());
             }
         }
+        if let Some(rect) = self.dropdown_rect {
+            if rect.contains(mouse_event.pos) {
+                self.show_dropdown(ctx, data, Point::new(rect.x0, rect.y1));
+            }
+        }
+    }
+
+    /// List all the tabs in a menu, to switch to the ones which don't fit
+    fn show_dropdown(&self, ctx: &mut EventCtx, data: &LapceTabData, pos: Point) {
+        let editor_tab = data.main_split.editor_tabs.get(&self.widget_id).unwrap();
+        let mut menu = druid::Menu::<LapceData>::new("");
+        for (i, child) in editor_tab.children.iter().enumerate() {
+            let item =
+                druid::MenuItem::new(escape_mnemonic(&child_title(data, child)))
+                    .selected(i == editor_tab.active)
+                    .command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::Focus,
+                        Target::Widget(child.widget_id()),
+                    ));
+            menu = menu.entry(item);
+        }
+        ctx.show_context_menu::<LapceData>(menu, ctx.to_window(pos));
     }
 
     fn ensure_active_visible<F>(
@@ -111,14 +153,9 @@ fn paint_header(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
                                 text = s.to_string();
                             }
                         }
-                        let mut path = path.to_path_buf();
-                        if let Some(workspace_path) = data.workspace.path.as_ref() {
-                            path = path
-                                .strip_prefix(workspace_path)
-                                .unwrap_or(&path)
-                                .to_path_buf();
-                        }
-                        hint = path
+                        hint = data
+                            .workspace
+                            .display_path(path)
                             .parent()
                             .and_then(|s| s.to_str())
                             .unwrap_or("")
@@ -134,6 +171,9 @@ fn paint_header(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
                     if let Some(_compare) = editor_buffer.editor.compare.as_ref() {
                         text = format!("{text} (Working tree)");
                     }
+                    if editor_buffer.doc.is_deleted_on_disk() {
+                        text = format!("{text} (Deleted)");
+                    }
                 }
                 EditorTabChild::Settings { .. } => {
                     text = "Settings".to_string();
@@ -213,7 +253,7 @@ fn event(
                 }
             }
             Event::MouseDown(mouse_event) => {
-                self.mouse_down(ctx, mouse_event);
+                self.mouse_down(ctx, data, mouse_event);
             }
             _ => (),
         }
@@ -247,13 +287,12 @@ fn update(
             .editor_tabs
             .get(&self.widget_id)
             .unwrap();
-        if editor_tab.active != old_editor_tab.active {
-            let scroll_id = self.content.id();
-            self.ensure_active_visible(
-                data,
-                |d| ctx.request_timer(d, Some(scroll_id)),
-                env,
-            );
+        if editor_tab.active != old_editor_tab.active
+            || !editor_tab.children.ptr_eq(&old_editor_tab.children)
+        {
+            // The rects of the tabs are only known after the layout
+            self.pending_ensure_visible = true;
+            ctx.request_layout();
         }
         self.content.update(ctx, data, env);
     }
@@ -331,10 +370,23 @@ fn layout(
             self.icons.push(icon);
         }
 
+        self.dropdown_rect = None;
+        if data.config.editor.show_tab
+            && data.config.editor.tab_overflow() == TabOverflow::Dropdown
+        {
+            let x = size.width - 3.0 * (gap + icon_size);
+            self.dropdown_rect = Some(
+                Size::new(icon_size, icon_size)
+                    .to_rect()
+                    .with_origin(Point::new(x + gap, gap)),
+            );
+        }
+
+        let icons_len = self.icons.len() + usize::from(self.dropdown_rect.is_some());
         self.content.layout(
             ctx,
             &BoxConstraints::tight(Size::new(
-                size.width - self.icons.len() as f64 * (gap + icon_size),
+                size.width - icons_len as f64 * (gap + icon_size),
                 size.height,
             )),
             data,
@@ -353,6 +405,15 @@ fn layout(
                 0.0,
             ),
         );
+        if self.pending_ensure_visible {
+            self.pending_ensure_visible = false;
+            let scroll_id = self.content.id();
+            self.ensure_active_visible(
+                data,
+                |d| ctx.request_timer(d, Some(scroll_id)),
+                env,
+            );
+        }
         size
     }
 
@@ -430,10 +491,14 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         }
 
         let svg_padding = 4.0;
-        for icon in self.icons.iter() {
-            if icon.rect.contains(self.mouse_pos) {
+        let icons = self.icons.iter().map(|icon| (icon.icon, icon.rect)).chain(
+            self.dropdown_rect
+                .map(|rect| (LapceIcons::DROPDOWN_ARROW, rect)),
+        );
+        for (icon, rect) in icons {
+            if rect.contains(self.mouse_pos) {
                 ctx.fill(
-                    icon.rect,
+                    rect,
                     &data.config.get_hover_color(
                         data.config
                             .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND),
@@ -441,10 +506,10 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
                 );
             }
             {
-                let svg = data.config.ui_svg(icon.icon);
+                let svg = data.config.ui_svg(icon);
                 ctx.draw_svg(
                     &svg,
-                    icon.rect.inflate(-svg_padding, -svg_padding),
+                    rect.inflate(-svg_padding, -svg_padding),
                     Some(
                         data.config
                             .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
@@ -454,3 +519,20 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         }
     }
 }
+
+/// The name of the tab of the child, as listed in the dropdown
+fn child_title(data: &LapceTabData, child: &EditorTabChild) -> String {
+    match child {
+        EditorTabChild::Editor(view_id, _, _) => {
+            let editor_buffer = data.editor_view_content(*view_id);
+            let name = editor_buffer.editor.content.file_name().to_string();
+            if editor_buffer.doc.buffer().is_pristine() {
+                name
+            } else {
+                format!("*{name}")
+            }
+        }
+        EditorTabChild::Settings { .. } => "Settings".to_string(),
+        EditorTabChild::Plugin { volt_name, .. } => format!("Plugin: {volt_name}"),
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     collections::HashSet,
     iter::Iterator,
-    ops::Sub,
+    ops::{Range, Sub},
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
@@ -12,8 +12,8 @@
     menu::MenuEventCtx,
     piet::{Text, TextLayout as TextLayoutTrait, TextLayoutBuilder},
     BoxConstraints, Command, Env, Event, EventCtx, FontStyle, LayoutCtx, LifeCycle,
-    LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point, RenderContext, Size,
-    Target, UpdateCtx, Widget, WidgetId,
+    LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point, Rect, RenderContext,
+    Size, Target, UpdateCtx, Widget, WidgetId,
 };
 use im::HashMap;
 use lapce_core::{command::FocusCommand, meta};
@@ -22,6 +22,7 @@
         CommandKind, LapceCommand, LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceIcons, LapceTheme},
+    container::{visible_tabs, TabOverflow},
     data::{
         DragContent, EditorTabChild, LapceData, LapceEditorTabData, LapceTabData,
     },
@@ -43,6 +44,9 @@ enum MouseAction {
 pub struct LapceEditorTabHeaderContent {
     pub widget_id: WidgetId,
     pub rects: Vec<TabRect>,
+    /// The tabs shown, which are all of them unless the tabs overflowing go in
+    /// a dropdown. The others get empty rects.
+    pub visible: Range<usize>,
     mouse_pos: Option<Point>,
     mouse_down_target: Option<(MouseAction, usize)>,
     dedup_paths: HashMap<PathBuf, PathBuf>,
@@ -53,6 +57,7 @@ pub fn new(widget_id: WidgetId) -> Self {
         Self {
             widget_id,
             rects: Vec::new(),
+            visible: 0..0,
             mouse_pos: None,
             mouse_down_target: None,
             dedup_paths: HashMap::default(),
@@ -165,7 +170,9 @@ fn mouse_down(
                 );
             }
 
-            if mouse_event.button.is_middle() {
+            let editor_tab =
+                data.main_split.editor_tabs.get(&self.widget_id).unwrap();
+            if mouse_event.button.is_middle() && !editor_tab.is_pinned(tab_idx) {
                 self.mouse_down_target =
                     Some((MouseAction::CloseViaMiddleClick, tab_idx));
                 return;
@@ -273,6 +280,37 @@ fn mouse_up(
         }
     }
 
+    /// Move the visible tabs to the start of the strip and give the others
+    /// empty rects at its edges, so they're neither painted nor hit. Returns
+    /// where the visible tabs end.
+    fn hide_tabs(&mut self) -> f64 {
+        let start = self
+            .rects
+            .get(self.visible.start)
+            .map(|r| r.rect.x0)
+            .unwrap_or(0.0);
+        let end = self
+            .rects
+            .get(self.visible.end.saturating_sub(1))
+            .map(|r| r.rect.x1 - start)
+            .unwrap_or(0.0);
+        for (i, tab_rect) in self.rects.iter_mut().enumerate() {
+            if self.visible.contains(&i) {
+                tab_rect.rect =
+                    tab_rect.rect.with_origin((tab_rect.rect.x0 - start, 0.0));
+                tab_rect.close_rect = tab_rect.close_rect.with_origin((
+                    tab_rect.close_rect.x0 - start,
+                    tab_rect.close_rect.y0,
+                ));
+            } else {
+                let x = if i < self.visible.start { 0.0 } else { end };
+                tab_rect.rect = Rect::new(x, 0.0, x, tab_rect.rect.y1);
+                tab_rect.close_rect = Rect::new(x, 0.0, x, 0.0);
+            }
+        }
+        end
+    }
+
     fn after_last_tab_index(&self) -> usize {
         self.rects.len()
     }
@@ -331,8 +369,10 @@ fn handle_drag(
                 return;
             }
 
+            // The tab dropped from another one goes after the pinned tabs
+            let mouse_index = editor_tab.unpinned_index(mouse_index);
             let mut child = child;
-            child.set_editor_tab(data, editor_tab.widget_id);
+            child.set_editor_tab(&mut data.main_split, editor_tab.widget_id);
             let editor_tab = data
                 .main_split
                 .editor_tabs
@@ -457,7 +497,7 @@ fn layout(
         self.rects.clear();
         let mut x = 0.0;
 
-        for child in editor_tab.children.iter() {
+        for (i, child) in editor_tab.children.iter().enumerate() {
             let mut text = "".to_string();
             let mut svg = data.config.ui_svg(LapceIcons::FILE);
             let mut svg_color = Some(
@@ -546,16 +586,27 @@ fn layout(
                     + 1.; // +1. for tab separator
             let close_size = 24.0;
             let inflate = (height - close_size) / 2.0;
+            // A pinned tab only shows its icon, and can't be closed from it
+            let pinned = editor_tab.is_pinned(i);
+            let width = if pinned {
+                data.config.ui.icon_size() as f64 * 2.0 + 1.
+            } else {
+                width
+            };
             let tab_rect = TabRect {
                 svg,
                 svg_color: svg_color.cloned(),
                 rect: Size::new(width, height)
                     .to_rect()
                     .with_origin(Point::new(x, 0.0)),
-                close_rect: Size::new(height, height)
-                    .to_rect()
-                    .with_origin(Point::new(x + width - height, 0.0))
-                    .inflate(-inflate, -inflate),
+                close_rect: if pinned {
+                    Rect::new(x + width, 0.0, x + width, 0.0)
+                } else {
+                    Size::new(height, height)
+                        .to_rect()
+                        .with_origin(Point::new(x + width - height, 0.0))
+                        .inflate(-inflate, -inflate)
+                },
                 text_layout,
                 path_layout,
             };
@@ -563,6 +614,14 @@ fn layout(
             self.rects.push(tab_rect);
         }
 
+        self.visible = 0..self.rects.len();
+        if data.config.editor.tab_overflow() == TabOverflow::Dropdown {
+            let widths: Vec<f64> =
+                self.rects.iter().map(|r| r.rect.width()).collect();
+            self.visible = visible_tabs(&widths, bc.max().width, editor_tab.active);
+            x = self.hide_tabs();
+        }
+
         Size::new(bc.max().width.max(x), height)
     }
 
@@ -576,6 +635,9 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
         );
 
         for (tab_idx, tab_rect) in self.rects.iter().enumerate() {
+            if !self.visible.contains(&tab_idx) {
+                continue;
+            }
             tab_rect.paint(ctx, data, self.widget_id, tab_idx, size, self.mouse_pos);
         }
 
@@ -694,14 +756,35 @@ fn to_menu(&self) -> druid::Menu<LapceData> {
         let entry_close_tab = druid::MenuItem::new("Close Tab")
             .on_activate(self.create_close_tab_callback());
 
+        let len = self.editor_tab.children.len();
         let entry_close_tabs_other = druid::MenuItem::new("Close Other Tabs")
-            .on_activate(self.create_close_all_tabs_callback());
+            .on_activate(
+                self.create_close_tabs_callback(0..len, Some(self.tab_idx)),
+            );
 
         let entry_close_tabs_left = druid::MenuItem::new("Close Tabs to the Left")
-            .on_activate(self.create_close_tabs_left_callback());
+            .on_activate(self.create_close_tabs_callback(0..self.tab_idx, None));
 
         let entry_close_tabs_right = druid::MenuItem::new("Close Tabs to the Right")
-            .on_activate(self.create_close_tabs_right_callback());
+            .on_activate(
+                self.create_close_tabs_callback(self.tab_idx + 1..len, None),
+            );
+
+        let entry_close_tabs_all = druid::MenuItem::new("Close All Tabs")
+            .on_activate(self.create_close_tabs_callback(0..len, None));
+
+        let pinned = self.editor_tab.is_pinned(self.tab_idx);
+        let entry_pin =
+            druid::MenuItem::new(if pinned { "Unpin Tab" } else { "Pin Tab" })
+                .command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    if pinned {
+                        LapceUICommand::UnpinTab(self.tab_idx)
+                    } else {
+                        LapceUICommand::PinTab(self.tab_idx)
+                    },
+                    Target::Widget(self.editor_tab.widget_id),
+                ));
 
         let entry_reveal_file = self.tab_path.as_deref().map(|path| {
             druid::MenuItem::new("Reveal in File Tree")
@@ -714,6 +797,9 @@ fn to_menu(&self) -> druid::Menu<LapceData> {
             .entry(entry_close_tabs_other)
             .entry(entry_close_tabs_left)
             .entry(entry_close_tabs_right)
+            .entry(entry_close_tabs_all)
+            .separator()
+            .entry(entry_pin)
             .separator();
 
         if let Some(entry) = entry_reveal_file {
@@ -750,81 +836,26 @@ fn create_close_tab_callback(
         }
     }
 
-    fn create_close_all_tabs_callback(
-        &self,
-    ) -> impl FnMut(&mut MenuEventCtx, &mut LapceData, &Env) + 'static {
-        let editor_tab = self.editor_tab.clone();
-        let tab_idx = self.tab_idx;
-
-        move |ctx, _, _| {
-            editor_tab
-                .children
-                .iter()
-                .enumerate()
-                .rev()
-                .filter_map(
-                    |(idx, tab)| if idx != tab_idx { Some(tab) } else { None },
-                )
-                .for_each(|tab| {
-                    ctx.submit_command(Command::new(
-                        LAPCE_COMMAND,
-                        LapceCommand {
-                            kind: CommandKind::Focus(FocusCommand::SplitClose),
-                            data: None,
-                        },
-                        Target::Widget(tab.widget_id()),
-                    ))
-                })
-        }
-    }
-
-    fn create_close_tabs_left_callback(
+    /// Close the tabs in the range except the one to keep, leaving the pinned
+    /// ones open
+    fn create_close_tabs_callback(
         &self,
+        range: Range<usize>,
+        keep: Option<usize>,
     ) -> impl FnMut(&mut MenuEventCtx, &mut LapceData, &Env) + 'static {
-        let editor_tab = self.editor_tab.clone();
-        let tab_idx = self.tab_idx;
+        let children = self.editor_tab.closable_children(range, keep);
 
         move |ctx, _, _| {
-            editor_tab
-                .children
-                .iter()
-                .take(tab_idx)
-                .rev()
-                .for_each(|tab| {
-                    ctx.submit_command(Command::new(
-                        LAPCE_COMMAND,
-                        LapceCommand {
-                            kind: CommandKind::Focus(FocusCommand::SplitClose),
-                            data: None,
-                        },
-                        Target::Widget(tab.widget_id()),
-                    ));
-                });
-        }
-    }
-
-    fn create_close_tabs_right_callback(
-        &self,
-    ) -> impl FnMut(&mut MenuEventCtx, &mut LapceData, &Env) + 'static {
-        let editor_tab = self.editor_tab.clone();
-        let tab_idx = self.tab_idx;
-
-        move |ctx, _, _| {
-            editor_tab
-                .children
-                .iter()
-                .skip(tab_idx + 1)
-                .rev()
-                .for_each(|tab| {
-                    ctx.submit_command(Command::new(
-                        LAPCE_COMMAND,
-                        LapceCommand {
-                            kind: CommandKind::Focus(FocusCommand::SplitClose),
-                            data: None,
-                        },
-                        Target::Widget(tab.widget_id()),
-                    ))
-                })
+            for widget_id in children.iter() {
+                ctx.submit_command(Command::new(
+                    LAPCE_COMMAND,
+                    LapceCommand {
+                        kind: CommandKind::Focus(FocusCommand::SplitClose),
+                        data: None,
+                    },
+                    Target::Widget(*widget_id),
+                ));
+            }
         }
     }
 
@@ -60,11 +60,13 @@ pub fn editor_tab_child_widget(
             settings_widget_id,
             editor_tab_id,
             keymap_input_view_id,
+            settings_input_view_id,
         } => LapceSettingsPanel::new(
             data,
             *settings_widget_id,
             *editor_tab_id,
             *keymap_input_view_id,
+            *settings_input_view_id,
         )
         .boxed(),
         EditorTabChild::Plugin {
@@ -146,6 +148,12 @@ pub fn request_focus(
             ctx.request_focus();
         }
         data.focus = Arc::new(self.view_id);
+        if self.view_id == data.peek.view_id {
+            // The peek popup isn't in an editor tab, and the editor it was shown
+            // from stays the active one
+            data.focus_area = FocusArea::Peek;
+            return;
+        }
         let editor = data.main_split.editors.get(&self.view_id).unwrap().clone();
         if let Some(editor_tab_id) = editor.tab_id {
             let editor_tab =
@@ -214,8 +222,36 @@ pub fn handle_lapce_ui_command(
             LapceUICommand::RunCodeAction(action, plugin_id) => {
                 data.run_code_action(ctx, action, plugin_id);
             }
-            LapceUICommand::ApplyWorkspaceEdit(edit) => {
-                data.apply_workspace_edit(ctx, edit);
+            LapceUICommand::ApplyWorkspaceEdit(edit, reply) => {
+                data.apply_workspace_edit(ctx, edit, reply.as_ref());
+            }
+            LapceUICommand::StoreSelectionRangeStack {
+                buffer_id,
+                rev,
+                origin,
+                ranges,
+            } => {
+                data.store_selection_ranges(
+                    ctx,
+                    *buffer_id,
+                    *rev,
+                    *origin,
+                    ranges.as_ref(),
+                );
+            }
+            LapceUICommand::StoreFoldingRanges {
+                buffer_id,
+                rev,
+                ranges,
+                cmd,
+            } => {
+                data.store_folding_ranges(
+                    ctx,
+                    *buffer_id,
+                    *rev,
+                    ranges.clone(),
+                    cmd,
+                );
             }
             LapceUICommand::EnsureCursorVisible(position) => {
                 self.ensure_cursor_visible(ctx, data, panel, position.as_ref(), env);
@@ -537,6 +573,19 @@ fn cursor_region(data: &LapceEditorBufferData, text: &mut PietText) -> Rect {
             };
             line as f64 * line_height
         };
+        let (cursor_x, y) = if data.visual_lines(text).is_some() {
+            let wrap_width = data.editor.wrap_width(text, &data.config);
+            let (point, _) = data.doc.points_of_offset(
+                text,
+                offset,
+                &data.editor.view,
+                wrap_width,
+                &data.config,
+            );
+            (point.x, point.y)
+        } else {
+            (cursor_x, y)
+        };
 
         let surrounding_lines_height =
             (data.config.editor.cursor_surrounding_lines as f64 * line_height)
@@ -719,10 +768,23 @@ fn event(
                         self.editor.widget().editor.widget().inner().offset(),
                     );
                     editor_data.get_code_actions(ctx);
+                    editor_data.get_inline_blame(ctx);
 
                     data.keypress = keypress.clone();
                 }
             }
+            Event::Timer(token) => {
+                let mut keypress = data.keypress.clone();
+                if Arc::make_mut(&mut keypress).sequence_timer_fired(
+                    ctx,
+                    *token,
+                    &mut editor_data,
+                    env,
+                ) {
+                    ctx.set_handled();
+                    data.keypress = keypress;
+                }
+            }
             Event::Command(cmd) if cmd.is(LAPCE_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_COMMAND);
                 if editor_data.run_command(
@@ -768,6 +830,18 @@ fn event(
         if editor.scroll_offset != offset {
             Arc::make_mut(data.main_split.editors.get_mut(&self.view_id).unwrap())
                 .scroll_offset = offset;
+
+            // The inlay hints are fetched for the lines scrolled to
+            if let BufferContent::File(path) = &editor.content {
+                let lines = data
+                    .editor_view_content(self.view_id)
+                    .visible_lines(ctx.text());
+                if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                    if let Some(lines) = doc.inlay_hint_lines_to_fetch(lines) {
+                        Arc::make_mut(doc).fetch_inlay_hints(lines);
+                    }
+                }
+            }
         }
     }
 
@@ -852,10 +926,14 @@ fn lifecycle(
                 } else {
                     let editor_data = data.editor_view_content(self.view_id);
                     let offset = editor_data.editor.cursor.offset();
+                    let wrap_width = editor_data
+                        .editor
+                        .wrap_width(ctx.text(), &editor_data.config);
                     let (_, origin) = editor_data.doc.points_of_offset(
                         ctx.text(),
                         offset,
                         &editor_data.editor.view,
+                        wrap_width,
                         &editor_data.config,
                     );
                     self.ime.set_origin(
@@ -1019,10 +1097,14 @@ fn update(
         }
 
         if update_ime_origin {
+            let wrap_width = editor_data
+                .editor
+                .wrap_width(ctx.text(), &editor_data.config);
             let (_, origin) = editor_data.doc.points_of_offset(
                 ctx.text(),
                 offset,
                 &editor_data.editor.view,
+                wrap_width,
                 &editor_data.config,
             );
             self.ime.set_origin(
@@ -1,26 +1,38 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use druid::{
     menu::MenuEventCtx,
     piet::{Text, TextAttribute, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Env, Event, EventCtx, ExtEventSink, KbKey,
-    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size,
-    Target, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod, WindowId,
+    LayoutCtx, LifeCycle, LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point,
+    Rect, RenderContext, Size, Target, UpdateCtx, Vec2, Widget, WidgetExt, WidgetId,
+    WidgetPod, WindowId,
 };
 use itertools::Itertools;
 use lapce_core::{command::FocusCommand, meta};
 use lapce_data::{
     command::{
-        CommandKind, LapceCommand, LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceConfig, LapceIcons, LapceTheme},
-    data::{EditorTabChild, LapceData, LapceEditorData, LapceTabData},
+    data::{DragContent, EditorTabChild, LapceData, LapceEditorData, LapceTabData},
     document::{BufferContent, LocalBufferKind},
-    explorer::{FileExplorerData, Naming},
+    explorer::{
+        drop_moves, drop_target, file_name_color, item_diff, replace_alert,
+        ExplorerDrop, FileExplorerData, Naming,
+    },
     panel::PanelKind,
     proxy::LapceProxy,
 };
-use lapce_rpc::{file::FileNodeItem, source_control::FileDiff};
+use lapce_rpc::{
+    file::{FileNodeItem, FileOperation},
+    source_control::FileDiff,
+};
 
 use crate::{
     editor::view::LapceEditorView,
@@ -38,12 +50,13 @@ fn paint_single_file_node_item(
     level: usize,
     current: usize,
     active: Option<&Path>,
+    selected: bool,
     hovered: Option<usize>,
     config: &LapceConfig,
     toggle_rects: &mut HashMap<usize, Rect>,
     file_diff: Option<FileDiff>,
 ) {
-    let background = if Some(item.path_buf.as_ref()) == active {
+    let background = if selected || Some(item.path_buf.as_ref()) == active {
         Some(LapceTheme::PANEL_CURRENT_BACKGROUND)
     } else if Some(current) == hovered {
         Some(LapceTheme::PANEL_HOVERED_BACKGROUND)
@@ -63,17 +76,7 @@ fn paint_single_file_node_item(
         );
     }
 
-    let text_color = if let Some(diff) = file_diff {
-        match diff {
-            FileDiff::Modified(_) | FileDiff::Renamed(_, _) => {
-                LapceTheme::SOURCE_CONTROL_MODIFIED
-            }
-            FileDiff::Added(_) => LapceTheme::SOURCE_CONTROL_ADDED,
-            FileDiff::Deleted(_) => LapceTheme::SOURCE_CONTROL_REMOVED,
-        }
-    } else {
-        LapceTheme::PANEL_FOREGROUND
-    };
+    let text_color = file_name_color(file_diff.as_ref(), item.ignored);
 
     let font_size = config.ui.font_size() as f64;
 
@@ -200,10 +203,11 @@ pub fn paint_file_node_item(
                 level,
                 i,
                 active,
+                data.file_explorer.selection.contains(&item.path_buf),
                 hovered,
                 config,
                 toggle_rects,
-                get_item_diff(item, data),
+                item_diff(item, &data.source_control.file_diffs),
             );
         }
     }
@@ -282,27 +286,6 @@ pub fn get_item_children(
     (i, None)
 }
 
-/// Get a FileDiff for the given FileNodeItem. If the given item is a folder
-/// that contains changes, returns a "fake" FileDiff that can be used to style
-/// the item accordingly.
-fn get_item_diff<'data>(
-    item: &'data FileNodeItem,
-    data: &'data LapceTabData,
-) -> Option<FileDiff> {
-    if item.is_dir {
-        data.source_control
-            .file_diffs
-            .keys()
-            .find(|path| path.as_path().starts_with(&item.path_buf))
-            .map(|path| FileDiff::Modified(path.clone()))
-    } else {
-        data.source_control
-            .file_diffs
-            .get(&item.path_buf)
-            .map(|d| d.0.clone())
-    }
-}
-
 pub fn get_item_children_mut(
     i: usize,
     index: usize,
@@ -488,6 +471,11 @@ struct FileExplorerFileList {
     line_height: f64,
     hovered: Option<usize>,
     name_edit_input: NameEditInput,
+    /// Where the left button was pressed and the path pressed on, dragging
+    /// starts once the pointer moves far enough from it
+    pressed: Option<(Point, PathBuf)>,
+    /// Where the dragged paths would be dropped
+    drop: Option<ExplorerDrop>,
 }
 
 impl FileExplorerFileList {
@@ -496,6 +484,103 @@ pub fn new(input: NameEditInput) -> Self {
             line_height: 25.0,
             hovered: None,
             name_edit_input: input,
+            pressed: None,
+            drop: None,
+        }
+    }
+
+    /// Start dragging the paths pressed on once the pointer moved far enough,
+    /// and find where they would be dropped
+    fn update_drag(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        mouse_event: &MouseEvent,
+    ) {
+        if data.drag.is_none() {
+            if let Some((start, path)) = self.pressed.as_ref() {
+                if start.distance(mouse_event.pos) > 5.0 {
+                    let paths = data.file_explorer.operation_paths(path);
+                    *Arc::make_mut(&mut data.drag) = Some((
+                        Vec2::new(-10.0, -10.0),
+                        mouse_event.window_pos.to_vec2(),
+                        DragContent::ExplorerPaths(paths),
+                    ));
+                    self.pressed = None;
+                }
+            }
+        }
+
+        let drop = match data.drag.as_ref() {
+            Some((_, _, DragContent::ExplorerPaths(paths))) => {
+                let index = ((mouse_event.pos.y + self.line_height)
+                    / self.line_height) as usize;
+                let fraction = (mouse_event.pos.y / self.line_height).fract();
+                data.file_explorer
+                    .workspace
+                    .as_ref()
+                    .and_then(|workspace| drop_target(workspace, index, fraction))
+                    .filter(|drop| drop_moves(paths, &drop.dir).is_some())
+            }
+            _ => None,
+        };
+        if drop != self.drop {
+            self.drop = drop;
+            ctx.request_paint();
+        }
+    }
+
+    /// Move the dragged paths into the directory, asking first if they
+    /// replace entries of the directory
+    fn drop_paths(
+        &self,
+        ctx: &mut EventCtx,
+        data: &LapceTabData,
+        paths: &[PathBuf],
+        dir: &Path,
+    ) {
+        let operations = match data.file_explorer.drop_operations(paths, dir) {
+            Some(operations) if !operations.is_empty() => operations,
+            _ => return,
+        };
+        let overwrite = operations.iter().any(|operation| {
+            matches!(
+                operation,
+                FileOperation::Move {
+                    overwrite: true,
+                    ..
+                }
+            )
+        });
+        let command = if overwrite {
+            LapceUICommand::ShowAlert(replace_alert(data.id, operations))
+        } else {
+            LapceUICommand::ExplorerFileOperations { operations }
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            command,
+            Target::Widget(data.id),
+        ));
+    }
+
+    fn paint_drop(&self, ctx: &mut PaintCtx, data: &LapceTabData, width: f64) {
+        if let Some(drop) = self.drop.as_ref() {
+            let y = drop.list_index as f64 * self.line_height - self.line_height;
+            if drop.between {
+                let x = 1.0 + 15.0 * drop.indent_level as f64;
+                ctx.fill(
+                    Rect::new(x, y - 1.0, width, y + 1.0),
+                    data.config.get_color_unchecked(LapceTheme::EDITOR_CARET),
+                );
+            } else {
+                ctx.fill(
+                    Rect::new(0.0, y, width, y + self.line_height),
+                    data.config.get_color_unchecked(
+                        LapceTheme::EDITOR_DRAG_DROP_TAB_BACKGROUND,
+                    ),
+                );
+            }
         }
     }
 
@@ -660,6 +745,10 @@ fn event(
                     return;
                 }
 
+                if mouse_event.buttons.contains(MouseButton::Left) {
+                    self.update_drag(ctx, data, mouse_event);
+                }
+
                 if let Some(workspace) = data.file_explorer.workspace.as_ref() {
                     let y = mouse_event.pos.y;
                     if y <= self.line_height
@@ -691,6 +780,30 @@ fn event(
                 let index = ((mouse_event.pos.y + self.line_height)
                     / self.line_height) as usize;
 
+                if mouse_event.button.is_left() {
+                    let toggle = if cfg!(target_os = "macos") {
+                        mouse_event.mods.meta()
+                    } else {
+                        mouse_event.mods.ctrl()
+                    };
+                    let range = mouse_event.mods.shift();
+                    if let Some((_, node)) = file_explorer.get_node_by_index(index) {
+                        let path = node.path_buf.clone();
+                        ctx.request_paint();
+                        // Clicks extending the selection don't open anything
+                        if toggle || range {
+                            file_explorer.select(&path, toggle, range);
+                            return;
+                        }
+                        // Pressing a selected path keeps the selection so that it
+                        // can be dragged, it's reduced to the path on release
+                        if !file_explorer.selection.contains(&path) {
+                            file_explorer.select(&path, false, false);
+                        }
+                        self.pressed = Some((mouse_event.pos, path));
+                    }
+                }
+
                 if mouse_event.button.is_left()
                     && (!data.config.editor.double_click || mouse_event.count == 2)
                 {
@@ -799,6 +912,40 @@ fn event(
                             ));
                         menu = menu.entry(item);
 
+                        // The selected paths if the node is part of the selection
+                        let paths = file_explorer.operation_paths(&node.path_buf);
+
+                        menu = menu.separator();
+                        if !is_workspace {
+                            let item =
+                                druid::MenuItem::new("Cut").command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ExplorerCut {
+                                        paths: paths.clone(),
+                                    },
+                                    Target::Auto,
+                                ));
+                            menu = menu.entry(item);
+
+                            let item =
+                                druid::MenuItem::new("Copy").command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ExplorerCopy {
+                                        paths: paths.clone(),
+                                    },
+                                    Target::Auto,
+                                ));
+                            menu = menu.entry(item);
+                        }
+                        let item = druid::MenuItem::new("Paste")
+                            .enabled(file_explorer.clipboard.is_some())
+                            .command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ExplorerPaste { dir: base.clone() },
+                                Target::Auto,
+                            ));
+                        menu = menu.entry(item);
+
                         // Separator between non destructive and destructive actions
                         menu = menu.separator();
 
@@ -858,16 +1005,23 @@ fn event(
                                 menu = menu.entry(item);
                             }
 
-                            let trash_text = if node.is_dir {
-                                "Move Directory to Trash"
+                            let trash_text = if paths.len() > 1 {
+                                format!("Move {} Items to Trash", paths.len())
+                            } else if node.is_dir {
+                                "Move Directory to Trash".to_string()
                             } else {
-                                "Move File to Trash"
+                                "Move File to Trash".to_string()
                             };
                             let item = druid::MenuItem::new(trash_text).command(
                                 Command::new(
                                     LAPCE_UI_COMMAND,
-                                    LapceUICommand::TrashPath {
-                                        path: node.path_buf.clone(),
+                                    LapceUICommand::ExplorerFileOperations {
+                                        operations: paths
+                                            .into_iter()
+                                            .map(|path| FileOperation::Trash {
+                                                path,
+                                            })
+                                            .collect(),
                                     },
                                     Target::Auto,
                                 ),
@@ -911,6 +1065,25 @@ fn event(
                             ));
                         menu = menu.entry(item);
 
+                        let hidden_text = if data.file_explorer.show_hidden {
+                            "Hide Hidden Files"
+                        } else {
+                            "Show Hidden Files"
+                        };
+                        let item = druid::MenuItem::new(hidden_text).command(
+                            Command::new(
+                                LAPCE_COMMAND,
+                                LapceCommand {
+                                    kind: CommandKind::Workbench(
+                                        LapceWorkbenchCommand::ToggleFileExplorerHiddenFiles,
+                                    ),
+                                    data: None,
+                                },
+                                Target::Auto,
+                            ),
+                        );
+                        menu = menu.entry(item);
+
                         ctx.show_context_menu::<LapceData>(
                             menu,
                             ctx.to_window(mouse_event.pos),
@@ -918,6 +1091,22 @@ fn event(
                     }
                 }
             }
+            Event::MouseUp(mouse_event) if mouse_event.button.is_left() => {
+                if let Some((_, path)) = self.pressed.take() {
+                    // Released without dragging
+                    Arc::make_mut(&mut data.file_explorer)
+                        .select(&path, false, false);
+                    ctx.request_paint();
+                }
+                if let Some(drop) = self.drop.take() {
+                    if let Some((_, _, DragContent::ExplorerPaths(paths))) =
+                        data.drag.as_ref()
+                    {
+                        self.drop_paths(ctx, data, paths, &drop.dir);
+                    }
+                    ctx.request_paint();
+                }
+            }
             _ => {}
         }
     }
@@ -931,6 +1120,7 @@ fn lifecycle(
     ) {
         if let LifeCycle::HotChanged(false) = event {
             self.hovered = None;
+            self.drop = None;
         }
 
         self.name_edit_input.lifecycle(ctx, event, data, env);
@@ -957,6 +1147,11 @@ fn update(
             ctx.request_layout();
         }
 
+        // Repaint the names when the git status of the files changes
+        if data.source_control.file_diffs != old_data.source_control.file_diffs {
+            ctx.request_paint();
+        }
+
         if data.file_explorer.naming.is_some() {
             self.name_edit_input.update(ctx, data, env);
         }
@@ -1052,14 +1247,15 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
                     &mut HashMap::new(),
                 );
                 if i > max {
-                    return;
+                    break;
                 }
             }
 
             // If we didn't draw the name input then we'll have to draw it here
             if let Some(naming) = &data.file_explorer.naming {
-                if i == 0
-                    || (naming.list_index() >= min && naming.list_index() < max)
+                if i <= max
+                    && (i == 0
+                        || (naming.list_index() >= min && naming.list_index() < max))
                 {
                     draw_name_input(
                         ctx,
@@ -1073,6 +1269,8 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
                 }
             }
         }
+
+        self.paint_drop(ctx, data, width);
     }
 }
 
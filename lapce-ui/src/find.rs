@@ -86,7 +86,7 @@ pub fn new(
         Self {
             parent_view_id,
             input_width: 200.0,
-            result_width: 75.0,
+            result_width: 90.0,
             input: WidgetPod::new(input.boxed()),
             icons,
             mouse_pos: Point::ZERO,
@@ -229,33 +229,10 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         );
         self.input.paint(ctx, data, env);
 
-        let mut index = None;
-        let cursor_offset = buffer.editor.cursor.offset();
-
-        for i in 0..buffer.doc.find.borrow().occurrences().regions().len() {
-            let region = buffer.doc.find.borrow().occurrences().regions()[i];
-            if region.min() <= cursor_offset && cursor_offset <= region.max() {
-                index = Some(i);
-            }
-        }
-
+        let count = buffer.doc.find_count(buffer.editor.cursor.offset());
         let text_layout = ctx
             .text()
-            .new_text_layout(if !buffer.doc.find.borrow().occurrences().is_empty() {
-                match index {
-                    Some(index) => format!(
-                        "{}/{}",
-                        index + 1,
-                        buffer.doc.find.borrow().occurrences().len()
-                    ),
-                    None => format!(
-                        "{} results",
-                        buffer.doc.find.borrow().occurrences().len()
-                    ),
-                }
-            } else {
-                "No results".to_string()
-            })
+            .new_text_layout(count.to_string())
             .font(
                 data.config.ui.font_family(),
                 data.config.ui.font_size() as f64,
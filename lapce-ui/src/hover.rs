@@ -1,17 +1,28 @@
 use std::sync::Arc;
 
 use druid::{
-    kurbo::Line, theme, ArcStr, BoxConstraints, Command, Data, Env, Event, EventCtx,
-    FontDescriptor, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point,
-    RenderContext, Size, Target, TextLayout, UpdateCtx, Widget, WidgetId, WidgetPod,
+    kurbo::Line,
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    theme, ArcStr, BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx,
+    FontDescriptor, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect,
+    RenderContext, Size, Target, TextLayout, UpdateCtx, Vec2, Widget, WidgetId,
+    WidgetPod,
 };
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    alert::{AlertButton, AlertContentData},
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::LapceTabData,
-    hover::{HoverData, HoverStatus},
-    markdown::layout_content::{
-        layout_content_clean_up, layouts_from_contents, LayoutContent,
+    hover::{related_location, HoverData, HoverStatus},
+    markdown::{
+        layout_content::{
+            layout_content_clean_up, layouts_from_contents, text_link_at,
+            LayoutContent,
+        },
+        LinkTarget,
     },
     rich_text::RichText,
 };
@@ -26,11 +37,14 @@ pub struct HoverContainer {
         LapceIdentityWrapper<LapceScroll<LapceTabData, Hover>>,
     >,
     content_size: Size,
+    /// The target of the link under the mouse, shown at the bottom
+    hovered_link: Option<String>,
 }
 impl HoverContainer {
     pub fn new(data: &HoverData) -> Self {
+        // Scrollable both ways, as wide code blocks aren't wrapped
         let hover = LapceIdentityWrapper::wrap(
-            LapceScroll::new(Hover::new()).vertical(),
+            LapceScroll::new(Hover::new()),
             data.scroll_id,
         );
         Self {
@@ -38,7 +52,118 @@ pub fn new(data: &HoverData) -> Self {
             scroll_id: data.scroll_id,
             hover: WidgetPod::new(hover),
             content_size: Size::ZERO,
+            hovered_link: None,
+        }
+    }
+
+    /// The target of the link at the position in the container
+    fn link_at(&self, data: &LapceTabData, pos: Point) -> Option<String> {
+        if data.hover.status != HoverStatus::Done
+            || !self.content_size.to_rect().contains(pos)
+        {
+            return None;
         }
+        let scroll = self.hover.widget().inner();
+        scroll.child().link_at(data, pos + scroll.offset())
+    }
+
+    fn open_link(&mut self, ctx: &mut EventCtx, data: &LapceTabData, link: &str) {
+        // The locations related to a diagnostic are jumped to in the editor
+        if let Some(location) = related_location(link) {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::JumpToLspLocation(None, location, true),
+                Target::Widget(data.id),
+            ));
+            return;
+        }
+        match LinkTarget::parse(link) {
+            Some(LinkTarget::Anchor(anchor)) => {
+                let scroll = self.hover.widget_mut().inner_mut();
+                if let Some(y) = scroll.child().anchor_y(data, anchor) {
+                    let x = scroll.offset().x;
+                    if scroll.scroll_to(Point::new(x, y)) {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ResetFade,
+                            Target::Widget(self.scroll_id),
+                        ));
+                        ctx.request_paint();
+                    }
+                }
+            }
+            // Documentation could link to anything on the disk, or to a scheme
+            // handled by any program, which is then run by the system opener
+            Some(LinkTarget::File(link)) => {
+                confirm_link(
+                    ctx,
+                    data,
+                    "Do you want to open this local file?",
+                    link,
+                );
+            }
+            Some(LinkTarget::Other(link)) => {
+                confirm_link(
+                    ctx,
+                    data,
+                    "Do you want to open this link with the program handling it?",
+                    link,
+                );
+            }
+            Some(LinkTarget::External(link)) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::OpenURI(link.to_string()),
+                    Target::Widget(data.id),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    fn paint_hovered_link(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
+        let link = match &self.hovered_link {
+            Some(link) => link,
+            None => return,
+        };
+        let text_layout = ctx
+            .text()
+            .new_text_layout(link.clone())
+            .font(
+                data.config.ui.font_family(),
+                data.config.ui.font_size() as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let height = text_layout.size().height + Hover::STARTING_Y * 2.0;
+        let rect = Rect::new(
+            0.0,
+            self.content_size.height - height,
+            self.content_size.width,
+            self.content_size.height,
+        );
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::HOVER_BACKGROUND),
+        );
+        ctx.stroke(
+            Line::new(Point::new(rect.x0, rect.y0), Point::new(rect.x1, rect.y0)),
+            data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+            1.0,
+        );
+        ctx.with_save(|ctx| {
+            ctx.clip(rect);
+            ctx.draw_text(
+                &text_layout,
+                Point::new(Hover::STARTING_X, rect.y0 + Hover::STARTING_Y),
+            );
+        });
     }
 
     fn ensure_visible(
@@ -76,20 +201,62 @@ fn event(
         env: &Env,
     ) {
         match event {
+            Event::MouseMove(mouse_event) => {
+                let link = self.link_at(data, mouse_event.pos);
+                if link.is_some() {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                if link != self.hovered_link {
+                    self.hovered_link = link;
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseDown(mouse_event) if mouse_event.button.is_left() => {
+                if let Some(link) = self.link_at(data, mouse_event.pos) {
+                    self.open_link(ctx, data, &link);
+                    ctx.set_handled();
+                    return;
+                }
+            }
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
-                if let LapceUICommand::UpdateHover { request_id, items } = command {
-                    // TODO: Should we check whether it has actually changed?
-                    let hover = Arc::make_mut(&mut data.hover);
-                    hover.receive(*request_id, items.clone());
-
-                    self.hover
-                        .widget_mut()
-                        .inner_mut()
-                        .child_mut()
-                        .update_layouts(ctx, data);
-
-                    ctx.request_paint();
+                match command {
+                    LapceUICommand::UpdateHover { request_id, items } => {
+                        // TODO: Should we check whether it has actually changed?
+                        let hover = Arc::make_mut(&mut data.hover);
+                        hover.receive(*request_id, items.clone());
+                        self.hovered_link = None;
+
+                        self.hover
+                            .widget_mut()
+                            .inner_mut()
+                            .child_mut()
+                            .update_layouts(ctx, data);
+
+                        ctx.request_paint();
+                    }
+                    LapceUICommand::ScrollHoverPage { down } => {
+                        let line_height = data.config.editor.line_height() as f64;
+                        let page = (self.content_size.height - line_height)
+                            .max(line_height);
+                        let delta = if *down { page } else { -page };
+                        if self
+                            .hover
+                            .widget_mut()
+                            .inner_mut()
+                            .scroll_by(Vec2::new(0.0, delta))
+                        {
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ResetFade,
+                                Target::Widget(self.scroll_id),
+                            ));
+                            ctx.request_paint();
+                        }
+                    }
+                    _ => {}
                 }
             }
             _ => {}
@@ -104,6 +271,11 @@ fn lifecycle(
         data: &LapceTabData,
         env: &Env,
     ) {
+        if let LifeCycle::HotChanged(false) = event {
+            if self.hovered_link.take().is_some() {
+                ctx.request_paint();
+            }
+        }
         self.hover.lifecycle(ctx, event, data, env);
     }
 
@@ -159,16 +331,19 @@ fn update(
     fn layout(
         &mut self,
         ctx: &mut LayoutCtx,
-        _bc: &BoxConstraints,
+        bc: &BoxConstraints,
         data: &LapceTabData,
         env: &Env,
     ) -> Size {
-        let size = data.hover.size;
+        let size = Size::new(data.hover.width.min(bc.max().width), bc.max().height);
+        // The text is wrapped to the width of the hover, rather than to the
+        // unbounded width given by the scroll
+        self.hover.widget_mut().inner_mut().child_mut().max_width = size.width;
         let bc = BoxConstraints::new(Size::ZERO, size);
         self.content_size = self.hover.layout(ctx, &bc, data, env);
         *data.hover.content_size.borrow_mut() = self.content_size;
         self.hover.set_origin(ctx, data, env, Point::ZERO);
-        size
+        self.content_size
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
@@ -190,6 +365,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
                 );
             }
             self.hover.paint(ctx, data, env);
+            self.paint_hovered_link(ctx, data);
         }
     }
 }
@@ -198,6 +374,8 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
 struct Hover {
     active_layout: Vec<LayoutContent>,
     active_diagnostic_layout: TextLayout<RichText>,
+    /// The width of the hover, which the text is wrapped to
+    max_width: f64,
 }
 
 impl Hover {
@@ -212,6 +390,7 @@ fn new() -> Self {
                 layout.set_text(RichText::new(ArcStr::from("")));
                 layout
             },
+            max_width: 0.0,
         }
     }
 
@@ -246,6 +425,57 @@ fn update_layouts(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
 
         ctx.request_layout();
     }
+
+    /// The height of the diagnostic, with its padding and separator, above
+    /// the documentation
+    fn diagnostic_height(&self) -> f64 {
+        if self.active_diagnostic_layout.size().is_empty() {
+            0.0
+        } else {
+            self.active_diagnostic_layout.layout_metrics().size.height
+                + Hover::STARTING_Y * 3.0
+        }
+    }
+
+    /// The origins of the documentation contents, as they are painted
+    fn content_origins<'a>(
+        &'a self,
+        data: &'a LapceTabData,
+    ) -> impl Iterator<Item = (Point, &'a LayoutContent)> + 'a {
+        let mut origin = Point::new(
+            Hover::STARTING_X,
+            Hover::STARTING_Y + self.diagnostic_height(),
+        );
+        self.active_layout.iter().map(move |layout| {
+            let layout_origin = origin;
+            origin.y += layout.size(&data.images, &data.config).height;
+            (layout_origin, layout)
+        })
+    }
+
+    /// The target of the link at the point of the scrolled content
+    fn link_at(&self, data: &LapceTabData, point: Point) -> Option<String> {
+        if !self.active_diagnostic_layout.size().is_empty() {
+            let origin = Vec2::new(Hover::STARTING_X, Hover::STARTING_Y);
+            if let Some(target) =
+                text_link_at(&self.active_diagnostic_layout, point - origin)
+            {
+                return Some(target.to_string());
+            }
+        }
+        self.content_origins(data).find_map(|(origin, layout)| {
+            layout
+                .link_at(point - origin.to_vec2())
+                .map(|target| target.to_string())
+        })
+    }
+
+    /// The vertical offset of the anchor in the scrolled content
+    fn anchor_y(&self, data: &LapceTabData, anchor: &str) -> Option<f64> {
+        self.content_origins(data).find_map(|(origin, layout)| {
+            layout.anchor_y(anchor).map(|y| origin.y + y)
+        })
+    }
 }
 impl Widget<LapceTabData> for Hover {
     fn event(
@@ -281,12 +511,11 @@ fn update(
     fn layout(
         &mut self,
         ctx: &mut LayoutCtx,
-        bc: &BoxConstraints,
+        _bc: &BoxConstraints,
         data: &LapceTabData,
         env: &Env,
     ) -> Size {
-        let width = bc.max().width;
-        let max_width = width
+        let max_width = self.max_width
             - Hover::STARTING_X
             - env.get(theme::SCROLLBAR_WIDTH)
             - env.get(theme::SCROLLBAR_PAD);
@@ -334,14 +563,13 @@ fn layout(
             max_layout_width = diagnostic_size.width;
         }
 
-        let width = if max_layout_width < max_width {
-            max_layout_width
-                + Hover::STARTING_X
-                + env.get(theme::SCROLLBAR_WIDTH)
-                + env.get(theme::SCROLLBAR_PAD)
-        } else {
-            width
-        };
+        // Words too long to be wrapped, which long lines of code often have,
+        // make the hover wider than its view, and it's then scrolled
+        // horizontally
+        let width = max_layout_width
+            + Hover::STARTING_X
+            + env.get(theme::SCROLLBAR_WIDTH)
+            + env.get(theme::SCROLLBAR_PAD);
 
         Size::new(
             width,
@@ -409,3 +637,24 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         }
     }
 }
+
+/// Ask the user before opening the link with the system opener
+fn confirm_link(ctx: &mut EventCtx, data: &LapceTabData, title: &str, link: &str) {
+    ctx.submit_command(Command::new(
+        LAPCE_UI_COMMAND,
+        LapceUICommand::ShowAlert(AlertContentData {
+            title: title.to_string(),
+            msg: link.to_string(),
+            buttons: vec![AlertButton {
+                text: "Open".to_string(),
+                target: data.id,
+                command: LapceCommand {
+                    kind: CommandKind::Workbench(LapceWorkbenchCommand::OpenLink),
+                    data: Some(serde_json::json!(link)),
+                },
+            }],
+            ..Default::default()
+        }),
+        Target::Widget(data.id),
+    ));
+}
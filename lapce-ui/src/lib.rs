@@ -4,6 +4,7 @@
 pub mod alert;
 pub mod app;
 pub mod button;
+pub mod color_picker;
 pub mod completion;
 pub mod dropdown;
 pub mod editor;
@@ -17,9 +18,11 @@
 pub mod message;
 pub mod palette;
 pub mod panel;
+pub mod peek;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
+pub mod rename_preview;
 pub mod scroll;
 pub mod search;
 pub mod settings;
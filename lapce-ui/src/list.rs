@@ -1,15 +1,16 @@
 use std::marker::PhantomData;
 
 use druid::{
-    piet::{Text, TextLayoutBuilder},
-    BoxConstraints, Command, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target, UpdateCtx,
-    Widget, WidgetId, WidgetPod,
+    piet::{PietTextLayout, Text, TextLayout, TextLayoutBuilder},
+    text::TextStorage,
+    theme, BoxConstraints, Color, Command, Data, Env, Event, EventCtx, FontFamily,
+    LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size,
+    Target, UpdateCtx, Widget, WidgetId, WidgetPod,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::LapceTheme,
-    list::ListData,
+    list::{ListColumns, ListData, ListRow, ListText},
 };
 
 use crate::scroll::{LapceIdentityWrapper, LapceScroll};
@@ -172,13 +173,13 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &ListData<T, D>, env: &Env) {
 /// The actual list of entries
 struct ListContent<T: Clone + ListPaint<D> + 'static, D: Data> {
     /// The line the mouse was last down upon
-    mouse_down: usize,
+    mouse_down: Option<usize>,
     _marker: PhantomData<(*const T, *const D)>,
 }
 impl<T: Clone + ListPaint<D> + 'static, D: Data> ListContent<T, D> {
     pub fn new() -> ListContent<T, D> {
         ListContent {
-            mouse_down: 0,
+            mouse_down: None,
             _marker: PhantomData,
         }
     }
@@ -199,17 +200,14 @@ fn event(
                 ctx.set_handled();
             }
             Event::MouseDown(mouse_event) => {
-                let line =
-                    (mouse_event.pos.y / data.line_height() as f64).floor() as usize;
-                self.mouse_down = line;
+                self.mouse_down = data.index_of_y(mouse_event.pos.y);
                 ctx.set_handled();
             }
             Event::MouseUp(mouse_event) => {
-                // TODO: function for translating mouse pos to the line, so that we don't repeat
-                // this calculation; which makes it harder to change later
-                let line =
-                    (mouse_event.pos.y / data.line_height() as f64).floor() as usize;
-                if line == self.mouse_down {
+                let line = data.index_of_y(mouse_event.pos.y);
+                if let Some(line) =
+                    line.filter(|line| Some(*line) == self.mouse_down)
+                {
                     data.selected_index = line;
                     data.select(ctx);
                     ctx.set_handled();
@@ -258,15 +256,11 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &ListData<T, D>, env: &Env) {
 
         let line_height = data.line_height() as f64;
 
-        let start_line = (rect.y0 / line_height).floor() as usize;
-        let end_line = (rect.y1 / line_height).ceil() as usize;
-        let count = end_line - start_line;
-
-        // Get the items, skip over all items before the start line, and
-        // ignore all items after the end line
-        for (line, item) in
-            data.items.iter().enumerate().skip(start_line).take(count)
-        {
+        // Only the items in view are painted, which are looked up by their index
+        // rather than by iterating over the ones before them, so that the cost
+        // doesn't grow with the size of the list
+        for line in data.visible_range(rect.y0, rect.y1) {
+            let item = &data.items[line];
             if line == data.selected_index {
                 // Create a rect covering the entry at the selected index
                 let bg_rect = Rect::ZERO
@@ -300,6 +294,89 @@ fn paint(
     );
 }
 
+/// How the columns of a [`ListRow`] are painted
+pub struct ListRowStyle {
+    /// The width taken by the icon, which the label comes after
+    pub icon_width: f64,
+    pub font_family: FontFamily,
+    pub font_size: f64,
+    /// The color of the label, the detail is dimmed
+    pub color: Color,
+}
+
+/// Paint a row made of columns, with the icon and the label at the start and the
+/// detail aligned to the right edge. The middle of the detail is cut out when it's
+/// too long to fit next to the label.
+pub fn paint_list_row<T: Clone, D: Data>(
+    ctx: &mut PaintCtx,
+    data: &ListData<T, D>,
+    env: &Env,
+    line: usize,
+    row: &ListRow,
+    style: &ListRowStyle,
+) {
+    let line_height = data.line_height() as f64;
+    let y = line_height * line as f64;
+    let focus_color = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
+    let detail_color = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+
+    if let Some(icon) = row.icon.as_ref() {
+        let svg_size = data.config.ui.icon_size() as f64;
+        let rect = Size::new(svg_size, svg_size)
+            .to_rect()
+            .with_origin(Point::new(
+                (line_height - svg_size) / 2.0 + 5.0,
+                (line_height - svg_size) / 2.0 + y,
+            ));
+        ctx.draw_svg(&icon.svg, rect, icon.color.as_ref());
+    }
+
+    let text_layout =
+        |ctx: &mut PaintCtx, text: &ListText, color: &Color| -> PietTextLayout {
+            let text = text.rich_text(focus_color);
+            let builder = ctx
+                .text()
+                .new_text_layout(text.clone())
+                .font(style.font_family.clone(), style.font_size)
+                .text_color(color.clone());
+            text.add_attributes(builder, env).build().unwrap()
+        };
+
+    let label = text_layout(ctx, &row.label, &style.color);
+    let detail_width = row
+        .detail
+        .as_ref()
+        .map(|detail| text_layout(ctx, detail, detail_color).size().width);
+    let width = ctx.size().width
+        - env.get(theme::SCROLLBAR_WIDTH)
+        - env.get(theme::SCROLLBAR_PAD);
+    let columns =
+        ListColumns::new(width, style.icon_width, label.size().width, detail_width);
+
+    ctx.draw_text(
+        &label,
+        Point::new(columns.label_x, y + label.y_offset(line_height)),
+    );
+
+    if let (Some(detail), Some((detail_x, detail_width))) =
+        (row.detail.as_ref(), columns.detail)
+    {
+        let detail = detail.truncate_middle(detail_width, |text| {
+            ctx.text()
+                .new_text_layout(text.to_string())
+                .font(style.font_family.clone(), style.font_size)
+                .build()
+                .unwrap()
+                .size()
+                .width
+        });
+        let detail = text_layout(ctx, &detail, detail_color);
+        // The shortened detail can be a bit narrower than the room it was given
+        let x = detail_x + detail_width - detail.size().width;
+        ctx.draw_text(&detail, Point::new(x, y + detail.y_offset(line_height)));
+    }
+}
+
 // A simple implementation of ListPaint for entries which are just strings
 impl<D: Data> ListPaint<D> for String {
     fn paint(
@@ -1,21 +1,19 @@
 use std::{path::Path, sync::Arc};
 
 use druid::{
-    kurbo::Rect,
-    piet::{Svg, Text, TextAttribute, TextLayoutBuilder},
-    theme, BoxConstraints, Color, Command, Data, Env, Event, EventCtx, FontWeight,
-    LayoutCtx, LifeCycle, LifeCycleCtx, Modifiers, PaintCtx, Point, RenderContext,
-    Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
+    kurbo::Rect, piet::Svg, theme, BoxConstraints, Color, Command, Data, Env, Event,
+    EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, Modifiers, PaintCtx, Point,
+    RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_COMMAND, LAPCE_UI_COMMAND},
     config::{LapceConfig, LapceTheme},
     data::{LapceTabData, LapceWorkspaceType},
     keypress::{Alignment, KeyMap, KeyPressFocus},
-    list::ListData,
+    list::{ListData, ListIcon, ListRow, ListText},
     palette::{
         PaletteItem, PaletteItemContent, PaletteListData, PaletteStatus,
-        PaletteViewData,
+        PaletteType, PaletteViewData, PluginPaletteStatus,
     },
 };
 use lapce_rpc::source_control::FileDiff;
@@ -23,7 +21,7 @@
 
 use crate::{
     editor::view::LapceEditorView,
-    list::{List, ListPaint},
+    list::{paint_list_row, List, ListPaint, ListRowStyle},
 };
 
 pub struct Palette {
@@ -106,7 +104,14 @@ fn event(
                         if &palette.run_id == run_id {
                             palette.total_items = items.clone();
                             palette.preview(ctx);
-                            if palette.get_input() == "" {
+                            // The items of a plugin are already matched against
+                            // the input
+                            if palette.get_input() == ""
+                                || matches!(
+                                    palette.palette_type,
+                                    PaletteType::Plugin(_)
+                                )
+                            {
                                 palette.list_data.items =
                                     palette.total_items.clone();
                             } else {
@@ -114,10 +119,20 @@ fn event(
                                     palette.run_id.clone(),
                                     palette.get_input().to_string(),
                                     palette.total_items.clone(),
+                                    data.config.palette.clone(),
                                 ));
                             }
                         }
                     }
+                    LapceUICommand::RequestPluginPaletteItems { run_id } => {
+                        if &data.palette.run_id == run_id {
+                            data.palette.request_plugin_items(ctx);
+                        }
+                    }
+                    LapceUICommand::PluginPaletteItemsTimeout { run_id } => {
+                        Arc::make_mut(&mut data.palette)
+                            .plugin_items_timed_out(run_id);
+                    }
                     LapceUICommand::FilterPaletteItems {
                         run_id,
                         input,
@@ -578,6 +593,12 @@ fn paint(
                 name,
                 location,
                 ..
+            }
+            | PaletteItemContent::BufferSymbol {
+                kind,
+                name,
+                location,
+                ..
             } => file_paint_symbols(
                 &location.path,
                 &self.indices,
@@ -642,10 +663,32 @@ fn paint(
                 theme.to_string(),
                 self.indices.to_vec(),
             ),
+            PaletteItemContent::SettingsProfile(_) => {
+                PaletteItemPaintInfo::new_text(
+                    self.filter_text.clone(),
+                    self.indices.to_vec(),
+                )
+            }
             PaletteItemContent::Language(name) => PaletteItemPaintInfo::new_text(
                 name.to_string(),
                 self.indices.to_vec(),
             ),
+            PaletteItemContent::LineEnding(line_ending) => {
+                PaletteItemPaintInfo::new_text(
+                    line_ending.to_string(),
+                    self.indices.to_vec(),
+                )
+            }
+            PaletteItemContent::Encoding(encoding) => {
+                PaletteItemPaintInfo::new_text(
+                    encoding.to_string(),
+                    self.indices.to_vec(),
+                )
+            }
+            PaletteItemContent::LocalHistory(..) => PaletteItemPaintInfo::new_text(
+                self.filter_text.clone(),
+                self.indices.to_vec(),
+            ),
             PaletteItemContent::TerminalLine(_line, content) => {
                 PaletteItemPaintInfo::new_text(
                     content.clone(),
@@ -656,46 +699,88 @@ fn paint(
                 format!("{ssh}"),
                 self.indices.to_vec(),
             ),
+            PaletteItemContent::PluginItem { item, .. } => PaletteItemPaintInfo {
+                svg: None,
+                svg_color: None,
+                text: item.label.clone(),
+                text_color: None,
+                text_indices: self.indices.to_vec(),
+                hint: item.description.clone().unwrap_or_default(),
+                hint_indices: Vec::new(),
+                keymap: None,
+            },
+            PaletteItemContent::Macro(name, desc) => {
+                let keymap = data
+                    .data
+                    .keymaps
+                    .as_ref()
+                    .and_then(|keymaps| keymaps.get(name))
+                    .and_then(|keymaps| keymaps.get(0))
+                    .cloned();
+                PaletteItemPaintInfo {
+                    keymap,
+                    ..PaletteItemPaintInfo::new_text(
+                        desc.to_string(),
+                        self.indices.to_vec(),
+                    )
+                }
+            }
+            PaletteItemContent::PluginPalette(_, title) => {
+                PaletteItemPaintInfo::new_text(
+                    title.to_string(),
+                    self.indices.to_vec(),
+                )
+            }
+            PaletteItemContent::PluginStatus(status) => {
+                let (text, text_color) = match status {
+                    PluginPaletteStatus::Loading => ("Loading...".to_string(), None),
+                    PluginPaletteStatus::Error(err) => (
+                        err.to_string(),
+                        Some(
+                            data.config
+                                .get_color_unchecked(LapceTheme::LAPCE_ERROR)
+                                .clone(),
+                        ),
+                    ),
+                };
+                PaletteItemPaintInfo {
+                    text_color,
+                    ..PaletteItemPaintInfo::new_text(text, Vec::new())
+                }
+            }
         };
 
         let line_height = data.line_height() as f64;
 
-        if let Some(svg) = svg.as_ref() {
-            let svg_size = data.config.ui.icon_size() as f64;
-            let rect =
-                Size::new(svg_size, svg_size)
-                    .to_rect()
-                    .with_origin(Point::new(
-                        (line_height - svg_size) / 2.0 + 5.0,
-                        (line_height - svg_size) / 2.0 + line_height * line as f64,
-                    ));
-            ctx.draw_svg(svg, rect, svg_color.as_ref());
-        }
-
-        let svg_x = match &self.content {
+        let icon_width = match &self.content {
             &PaletteItemContent::Line(_, _) | &PaletteItemContent::Workspace(_) => {
                 0.0
             }
             _ => line_height,
         };
-
-        let focus_color = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
-
-        let full_text = if hint.is_empty() {
-            text.clone()
-        } else {
-            text.clone() + " " + &hint
+        let row = ListRow {
+            icon: svg.map(|svg| ListIcon {
+                svg,
+                color: svg_color,
+            }),
+            label: ListText {
+                text,
+                indices: text_indices,
+                color: text_color,
+            },
+            detail: (!hint.is_empty()).then(|| ListText::new(hint, hint_indices)),
         };
-        let mut text_layout = ctx
-            .text()
-            .new_text_layout(full_text.clone())
-            .font(
-                data.config.ui.font_family(),
-                data.config.ui.font_size() as f64,
-            )
-            .text_color(match text_color {
-                Some(ref color) => color.clone(),
-                None => data
+        paint_list_row(
+            ctx,
+            data,
+            env,
+            line,
+            &row,
+            &ListRowStyle {
+                icon_width,
+                font_family: data.config.ui.font_family(),
+                font_size: data.config.ui.font_size() as f64,
+                color: data
                     .config
                     .get_color_unchecked(if line == data.selected_index {
                         LapceTheme::PALETTE_CURRENT_FOREGROUND
@@ -703,69 +788,8 @@ fn paint(
                         LapceTheme::PALETTE_FOREGROUND
                     })
                     .clone(),
-            });
-        for &i_start in &text_indices {
-            let i_end = full_text
-                .char_indices()
-                .find(|(i, _)| *i == i_start)
-                .map(|(_, c)| c.len_utf8() + i_start);
-            let i_end = if let Some(i_end) = i_end {
-                i_end
-            } else {
-                // Log a warning, but continue as we don't want to crash on a bug
-                log::warn!(
-                    "Invalid text indices in palette: text: '{}', i_start: {}",
-                    text,
-                    i_start
-                );
-                continue;
-            };
-
-            text_layout = text_layout.range_attribute(
-                i_start..i_end,
-                TextAttribute::TextColor(match text_color {
-                    Some(ref color) => color.clone(),
-                    None => focus_color.clone(),
-                }),
-            );
-            text_layout = text_layout.range_attribute(
-                i_start..i_end,
-                TextAttribute::Weight(FontWeight::BOLD),
-            );
-        }
-
-        if !hint.is_empty() {
-            text_layout = text_layout
-                .range_attribute(
-                    text.len() + 1..full_text.len(),
-                    TextAttribute::FontSize(13.0),
-                )
-                .range_attribute(
-                    text.len() + 1..full_text.len(),
-                    TextAttribute::TextColor(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
-                            .clone(),
-                    ),
-                );
-            for i in &hint_indices {
-                let i = *i + text.len() + 1;
-                text_layout = text_layout.range_attribute(
-                    i..i + 1,
-                    TextAttribute::TextColor(focus_color.clone()),
-                );
-                text_layout = text_layout.range_attribute(
-                    i..i + 1,
-                    TextAttribute::Weight(FontWeight::BOLD),
-                );
-            }
-        }
-
-        let text_layout = text_layout.build().unwrap();
-        let x = svg_x + 5.0;
-        let y = line_height * line as f64 + text_layout.y_offset(line_height);
-        let point = Point::new(x, y);
-        ctx.draw_text(&text_layout, point);
+            },
+        );
 
         // TODO: make sure that the main text and the keymaps don't overlap
         // This isn't currently an issue for any added commands, but could become one
@@ -14,7 +14,9 @@
     },
     config::{LapceIcons, LapceTheme},
     data::{DragContent, LapceTabData},
-    panel::{PanelContainerPosition, PanelKind, PanelPosition},
+    panel::{
+        PanelBadge, PanelBadgeKind, PanelContainerPosition, PanelKind, PanelPosition,
+    },
 };
 
 use crate::{scroll::LapceScroll, split::LapceSplit, tab::LapceIcon};
@@ -205,7 +207,8 @@ fn event(
                 self.mouse_down = false;
                 if let Some(header) = self.header.as_ref() {
                     let rect = header.layout_rect();
-                    if rect.contains(mouse_event.pos) {
+                    if mouse_event.button.is_left() && rect.contains(mouse_event.pos)
+                    {
                         self.mouse_down = true
                     }
                 }
@@ -369,6 +372,10 @@ impl PanelSectionHeader {
     pub fn new(text: ReadOnlyString, kind: PanelKind) -> Self {
         Self { text, kind }
     }
+
+    pub fn set_text(&mut self, text: ReadOnlyString) {
+        self.text = text;
+    }
 }
 
 impl Widget<LapceTabData> for PanelSectionHeader {
@@ -466,6 +473,10 @@ pub struct PanelContainer {
     position: PanelContainerPosition,
     pub panels:
         HashMap<PanelKind, WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
+    /// The separator between the two panels when both are shown
+    split_rect: Option<Rect>,
+    /// Where the space the two panels share starts, and its length
+    split_span: (f64, f64),
 }
 
 impl PanelContainer {
@@ -490,9 +501,32 @@ pub fn new(position: PanelContainerPosition) -> Self {
             switcher1: WidgetPod::new(switcher1),
             position,
             panels: HashMap::new(),
+            split_rect: None,
+            split_span: (0.0, 0.0),
         }
     }
 
+    /// The separator between the two panels shown in the container, which is
+    /// dragged to resize them
+    pub fn split_rect(&self) -> Option<Rect> {
+        self.split_rect
+    }
+
+    /// The part of the container the first panel takes when the separator is
+    /// dragged to the point, which is relative to the container
+    pub fn split_at(&self, pos: Point) -> f64 {
+        let (start, length) = self.split_span;
+        if length <= 0.0 {
+            return 0.5;
+        }
+        let offset = if self.position.is_bottom() {
+            pos.x
+        } else {
+            pos.y
+        };
+        (offset - start) / length
+    }
+
     pub fn insert_panel(
         &mut self,
         kind: PanelKind,
@@ -661,21 +695,24 @@ fn layout(
             .active_panel_at_position(&self.position.second())
             .and_then(|(panel, shown)| if shown { Some(panel) } else { None });
 
+        self.split_rect = None;
         match (panel_first, panel_second) {
             (Some(panel_first), Some(panel_second)) => {
-                let split = match self.position {
-                    PanelContainerPosition::Left => data.panel.size.left_split,
-                    PanelContainerPosition::Bottom => data.panel.size.bottom_split,
-                    PanelContainerPosition::Right => data.panel.size.right_split,
-                };
+                let split = data.panel.size.split(&self.position);
                 let separator = 4.0;
                 if is_bottom {
-                    let size_fist = ((self_size.width
+                    let length = self_size.width
                         - switcher0_size
                         - switcher1_size
-                        - separator)
-                        * split)
-                        .round();
+                        - separator;
+                    let size_fist = (length * split).round();
+                    self.split_span = (switcher0_size, length);
+                    self.split_rect = Some(Rect::new(
+                        switcher0_size + size_fist,
+                        0.0,
+                        switcher0_size + size_fist + separator,
+                        self_size.height,
+                    ));
                     let size_second = self_size.width
                         - separator
                         - switcher0_size
@@ -714,12 +751,18 @@ fn layout(
                         Point::new(size_fist + switcher0_size + separator, 0.0),
                     );
                 } else {
-                    let size_fist = ((self_size.height
+                    let length = self_size.height
                         - switcher0_size
                         - switcher1_size
-                        - separator)
-                        * split)
-                        .round();
+                        - separator;
+                    let size_fist = (length * split).round();
+                    self.split_span = (switcher0_size, length);
+                    self.split_rect = Some(Rect::new(
+                        0.0,
+                        switcher0_size + size_fist,
+                        self_size.width,
+                        switcher0_size + size_fist + separator,
+                    ));
                     let size_second = self_size.height
                         - separator
                         - switcher0_size
@@ -981,9 +1024,17 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
     }
 }
 
+/// The icon of a panel in the switcher, with the badge counting what the panel
+/// shows
+struct PanelTab {
+    kind: PanelKind,
+    icon: LapceIcon,
+    badge: Option<PanelBadge>,
+}
+
 pub struct PanelSwitcher {
     position: PanelPosition,
-    icons: Vec<(PanelKind, LapceIcon)>,
+    icons: Vec<PanelTab>,
     mouse_pos: Point,
     on_icon: bool,
     clicked_icon: Option<usize>,
@@ -1021,8 +1072,8 @@ fn update_icons(&mut self, self_size: Size, data: &LapceTabData) {
         let icon_size = data.config.ui.font_size() as f64;
         self.maximise_toggle = None;
         if self.position.is_bottom() {
-            for (i, (_, icon)) in icons.iter_mut().enumerate() {
-                icon.rect = Rect::ZERO
+            for (i, tab) in icons.iter_mut().enumerate() {
+                tab.icon.rect = Rect::ZERO
                     .with_origin(Point::new(
                         self_size.width / 2.0,
                         (i as f64 + 0.5) * switcher_size,
@@ -1038,8 +1089,8 @@ fn update_icons(&mut self, self_size: Size, data: &LapceTabData) {
                     .inflate(icon_size / 2.0, icon_size / 2.0),
             );
         } else {
-            for (i, (_, icon)) in icons.iter_mut().enumerate() {
-                icon.rect = Rect::ZERO
+            for (i, tab) in icons.iter_mut().enumerate() {
+                tab.icon.rect = Rect::ZERO
                     .with_origin(Point::new(
                         (i as f64 + 0.5) * switcher_size,
                         self_size.height / 2.0,
@@ -1050,7 +1101,53 @@ fn update_icons(&mut self, self_size: Size, data: &LapceTabData) {
         self.icons = icons;
     }
 
-    fn panel_icon(kind: &PanelKind, data: &LapceTabData) -> (PanelKind, LapceIcon) {
+    /// Paint the badge as a pill centered on the top right corner of the icon
+    fn paint_badge(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        icon_rect: Rect,
+        badge: &PanelBadge,
+    ) {
+        let text_layout = ctx
+            .text()
+            .new_text_layout(badge.text())
+            .font(
+                data.config.ui.font_family(),
+                (data.config.ui.font_size() as f64 * 0.7).round(),
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let text_size = text_layout.size();
+        let height = text_size.height;
+        let width = (text_size.width + height * 0.5).max(height);
+        let rect = Rect::from_center_size(
+            Point::new(icon_rect.x1, icon_rect.y0),
+            Size::new(width, height),
+        );
+        let color = match badge.kind {
+            PanelBadgeKind::Error => LapceTheme::LAPCE_ERROR,
+            PanelBadgeKind::Warning => LapceTheme::LAPCE_WARN,
+            PanelBadgeKind::Info => LapceTheme::EDITOR_CARET,
+        };
+        ctx.fill(
+            rect.to_rounded_rect(height / 2.0),
+            data.config.get_color_unchecked(color),
+        );
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                rect.x0 + (width - text_size.width) / 2.0,
+                rect.y0 + text_layout.y_offset(height),
+            ),
+        );
+    }
+
+    fn panel_icon(kind: &PanelKind, data: &LapceTabData) -> PanelTab {
         let cmd = match kind {
             PanelKind::FileExplorer => {
                 LapceWorkbenchCommand::ToggleFileExplorerVisual
@@ -1062,10 +1159,13 @@ fn panel_icon(kind: &PanelKind, data: &LapceTabData) -> (PanelKind, LapceIcon) {
             PanelKind::Terminal => LapceWorkbenchCommand::ToggleTerminalVisual,
             PanelKind::Search => LapceWorkbenchCommand::ToggleSearchVisual,
             PanelKind::Problem => LapceWorkbenchCommand::ToggleProblemVisual,
+            PanelKind::RenamePreview => {
+                LapceWorkbenchCommand::ToggleRenamePreviewVisual
+            }
         };
-        (
-            *kind,
-            LapceIcon {
+        PanelTab {
+            kind: *kind,
+            icon: LapceIcon {
                 icon: kind.svg_name(),
                 rect: Rect::ZERO,
                 command: Command::new(
@@ -1077,7 +1177,8 @@ fn panel_icon(kind: &PanelKind, data: &LapceTabData) -> (PanelKind, LapceIcon) {
                     Target::Widget(data.id),
                 ),
             },
-        )
+            badge: data.panel_badge(kind),
+        }
     }
 }
 
@@ -1094,7 +1195,7 @@ fn event(
                 if ctx.is_active() {
                     if let Some(i) = self.clicked_icon {
                         ctx.set_active(false);
-                        let (kind, icon) = &self.icons[i];
+                        let PanelTab { kind, icon, .. } = &self.icons[i];
                         let offset =
                             mouse_event.pos.to_vec2() - icon.rect.origin().to_vec2();
                         *Arc::make_mut(&mut data.drag) = Some((
@@ -1106,8 +1207,8 @@ fn event(
                 }
                 self.mouse_pos = mouse_event.pos;
                 let icon_padding = Self::icon_padding(data);
-                for (_, icon) in self.icons.iter() {
-                    let rect = icon.rect.inflate(icon_padding, icon_padding);
+                for tab in self.icons.iter() {
+                    let rect = tab.icon.rect.inflate(icon_padding, icon_padding);
                     if rect.contains(self.mouse_pos) {
                         if !self.on_icon {
                             ctx.set_cursor(&Cursor::Pointer);
@@ -1140,8 +1241,8 @@ fn event(
             }
             Event::MouseDown(mouse_event) => {
                 let icon_padding = Self::icon_padding(data);
-                for (i, (_, icon)) in self.icons.iter().enumerate() {
-                    let rect = icon.rect.inflate(icon_padding, icon_padding);
+                for (i, tab) in self.icons.iter().enumerate() {
+                    let rect = tab.icon.rect.inflate(icon_padding, icon_padding);
                     if rect.contains(mouse_event.pos) {
                         self.clicked_icon = Some(i);
                         ctx.set_active(true);
@@ -1163,11 +1264,11 @@ fn event(
             Event::MouseUp(mouse_event) => {
                 ctx.set_active(false);
                 let icon_padding = Self::icon_padding(data);
-                for (i, (_, icon)) in self.icons.iter().enumerate() {
-                    let rect = icon.rect.inflate(icon_padding, icon_padding);
+                for (i, tab) in self.icons.iter().enumerate() {
+                    let rect = tab.icon.rect.inflate(icon_padding, icon_padding);
                     if rect.contains(mouse_event.pos) {
                         if self.clicked_icon == Some(i) {
-                            ctx.submit_command(icon.command.clone());
+                            ctx.submit_command(tab.icon.command.clone());
                         }
                         break;
                     }
@@ -1210,11 +1311,18 @@ fn lifecycle(
 
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
+        ctx: &mut UpdateCtx,
         _old_data: &LapceTabData,
-        _data: &LapceTabData,
+        data: &LapceTabData,
         _env: &Env,
     ) {
+        for tab in self.icons.iter_mut() {
+            let badge = data.panel_badge(&tab.kind);
+            if tab.badge != badge {
+                tab.badge = badge;
+                ctx.request_paint();
+            }
+        }
     }
 
     fn layout(
@@ -1360,7 +1468,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 active_kinds.push(panel);
             }
         }
-        for (kind, icon) in self.icons.iter() {
+        for PanelTab { kind, icon, badge } in self.icons.iter() {
             let mouse_rect = icon.rect.inflate(icon_padding, icon_padding);
             if mouse_rect.contains(self.mouse_pos) {
                 ctx.fill(
@@ -1431,6 +1539,9 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                         .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
                 ),
             );
+            if let Some(badge) = badge {
+                Self::paint_badge(ctx, data, icon.rect, badge);
+            }
         }
 
         if let Some(rect) = self.maximise_toggle {
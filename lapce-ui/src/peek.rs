@@ -0,0 +1,174 @@
+use druid::{
+    piet::{Text, TextAttribute, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget, WidgetExt,
+    WidgetPod,
+};
+use lapce_data::{config::LapceTheme, data::LapceTabData, peek::PeekData};
+
+use crate::editor::view::LapceEditorView;
+
+/// The popup showing a peeked definition in an editor, under a header with the
+/// location of the definition and its position among the others
+pub struct PeekContainer {
+    editor: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+}
+
+impl PeekContainer {
+    pub fn new(data: &PeekData) -> Self {
+        let editor =
+            LapceEditorView::new(data.view_id, data.editor_id, None).hide_header();
+        Self {
+            editor: WidgetPod::new(editor.boxed()),
+        }
+    }
+}
+
+impl Widget<LapceTabData> for PeekContainer {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        self.editor.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.editor.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        if old_data.peek.index != data.peek.index
+            || old_data.peek.entries != data.peek.entries
+        {
+            ctx.request_paint();
+        }
+        self.editor.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let size = bc.max();
+        let header_height = data.config.ui.header_height() as f64;
+        self.editor.layout(
+            ctx,
+            &BoxConstraints::tight(Size::new(
+                size.width,
+                (size.height - header_height).max(0.0),
+            )),
+            data,
+            env,
+        );
+        self.editor
+            .set_origin(ctx, data, env, Point::new(0.0, header_height));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        let header_height = data.config.ui.header_height() as f64;
+        let width = ctx.size().width;
+        ctx.fill(
+            Rect::new(0.0, 0.0, width, header_height),
+            data.config
+                .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+        );
+
+        if let Some(entry) = data.peek.current() {
+            let file_name = entry
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let folder = entry
+                .path
+                .parent()
+                .map(|folder| {
+                    data.workspace
+                        .path
+                        .as_ref()
+                        .and_then(|workspace| folder.strip_prefix(workspace).ok())
+                        .unwrap_or(folder)
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .unwrap_or_default();
+            let text =
+                format!("{file_name}  {folder}:{}", entry.range.start.line + 1);
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text.clone())
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::PANEL_FOREGROUND)
+                        .clone(),
+                )
+                .range_attribute(
+                    file_name.len()..text.len(),
+                    TextAttribute::TextColor(
+                        data.config
+                            .get_color_unchecked(LapceTheme::PANEL_FOREGROUND_DIM)
+                            .clone(),
+                    ),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(10.0, text_layout.y_offset(header_height)),
+            );
+
+            if data.peek.entries.len() > 1 {
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(format!(
+                        "{} of {}",
+                        data.peek.index + 1,
+                        data.peek.entries.len()
+                    ))
+                    .font(
+                        data.config.ui.font_family(),
+                        data.config.ui.font_size() as f64,
+                    )
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::PANEL_FOREGROUND_DIM)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(
+                        width - text_layout.size().width - 10.0,
+                        text_layout.y_offset(header_height),
+                    ),
+                );
+            }
+        }
+
+        self.editor.paint(ctx, data, env);
+    }
+}
@@ -11,7 +11,7 @@
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{LapceConfig, LapceIcons, LapceTheme},
     data::LapceTabData,
-    picker::FilePickerData,
+    picker::{FilePickerData, PickerKind},
 };
 use lapce_rpc::file::FileNodeItem;
 
@@ -391,6 +391,11 @@ fn mouse_down(
                     if let Some((i, t)) = self.last_left_click.as_ref() {
                         if *i == index && t.elapsed().as_millis() < 500 {
                             self.last_left_click = None;
+                            FilePickerData::remember_dir(
+                                &data.db,
+                                PickerKind::OpenFile,
+                                &node.path_buf,
+                            );
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
                                 LapceUICommand::OpenFile(
@@ -701,6 +706,11 @@ fn mouse_down(
                                 get_item_children(0, data.picker.index, item);
                             if let Some(node) = node {
                                 if node.is_dir {
+                                    FilePickerData::remember_dir(
+                                        &data.db,
+                                        PickerKind::OpenFolder,
+                                        &node.path_buf,
+                                    );
                                     let mut workspace = workspace.clone();
                                     workspace.path = Some(node.path_buf.clone());
                                     ctx.submit_command(Command::new(
@@ -709,6 +719,11 @@ fn mouse_down(
                                         Target::Auto,
                                     ));
                                 } else {
+                                    FilePickerData::remember_dir(
+                                        &data.db,
+                                        PickerKind::OpenFile,
+                                        &node.path_buf,
+                                    );
                                     ctx.submit_command(Command::new(
                                         LAPCE_UI_COMMAND,
                                         LapceUICommand::OpenFile(
@@ -877,5 +892,35 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
             let y = btn.rect.y0 + btn.text_layout.y_offset(btn_size.height);
             ctx.draw_text(&btn.text_layout, Point::new(x, y));
         }
+
+        let candidates = &data.picker.completion.candidates;
+        if let Some(leftmost) = self.buttons.last() {
+            if !candidates.is_empty() {
+                let gap = leftmost.rect.y0;
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(candidates.join("  "))
+                    .font(
+                        data.config.ui.font_family(),
+                        data.config.ui.font_size() as f64,
+                    )
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                let clip_rect =
+                    Rect::new(gap, 0.0, leftmost.rect.x0 - gap, size.height);
+                ctx.with_save(|ctx| {
+                    ctx.clip(clip_rect);
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(gap, text_layout.y_offset(size.height)),
+                    );
+                });
+            }
+        }
     }
 }
@@ -20,8 +20,8 @@
     },
     panel::PanelKind,
     plugin::{
-        plugin_install_status::PluginInstallType, PluginData, PluginLoadStatus,
-        PluginStatus, VoltIconKind,
+        plugin_install_status::PluginInstallType, PluginCommand, PluginData,
+        PluginLoadStatus, PluginStatus, VoltIconKind,
     },
     settings::LapceSettingsFocusData,
 };
@@ -47,6 +47,8 @@ pub struct Plugin {
     gap: f64,
     height: f64,
     last_idle_timer: TimerToken,
+    /// Where the installation progress was last painted, it follows the scroll
+    install_progress_y: f64,
 }
 
 impl Plugin {
@@ -59,6 +61,7 @@ pub fn new(installed: bool) -> Self {
             rects: HashMap::new(),
             gap: 10.0,
             last_idle_timer: TimerToken::INVALID,
+            install_progress_y: 0.0,
         }
     }
 
@@ -113,10 +116,12 @@ fn paint_install_progress_element(
         display_name: &str,
         install_type: &PluginInstallType,
         error_string: &str,
+        progress: Option<f64>,
         config: &LapceConfig,
         i: usize,
     ) {
         let rect = ctx.region().bounding_box();
+        self.install_progress_y = rect.y0;
 
         let y = self.line_height * i as f64 + rect.y0;
         let x = 0.0; //0.5 * self.line_height;
@@ -142,17 +147,21 @@ fn paint_install_progress_element(
             .with_origin(Point::new(x, y));
 
         // [INSTALLING / UNINSTALLING]
-        let mut status_text = "Installing...";
+        let mut status_text = "Installing...".to_string();
+        let mut progress_width = None;
         if !error_string.is_empty() {
-            status_text = error_string;
+            status_text = error_string.to_string();
             color_background = config.get_color_unchecked(LapceTheme::LAPCE_ERROR);
         } else if *install_type == PluginInstallType::Uninstallation {
-            status_text = "Removing...";
+            status_text = "Removing...".to_string();
+        } else if let Some(progress) = progress {
+            status_text = format!("Installing... {:.0}%", progress * 100.0);
+            progress_width = Some(full_percent_width * progress);
         }
 
         let status_text_layout = ctx
             .text()
-            .new_text_layout(status_text.to_string())
+            .new_text_layout(status_text)
             .font(config.ui.font_family(), config.ui.font_size() as f64)
             .default_attribute(TextAttribute::Style(druid::FontStyle::Italic))
             .text_color(
@@ -167,7 +176,21 @@ fn paint_install_progress_element(
         let x_state_text =
             full_percent_width - text_size.width - text_padding * 2.0 - 0.0;
 
-        ctx.fill(rect_background, color_background);
+        if let Some(progress_width) = progress_width {
+            // The rest of the download is dimmed
+            ctx.fill(
+                rect_background,
+                config.get_color_unchecked(LapceTheme::EDITOR_DIM),
+            );
+            ctx.fill(
+                Size::new(progress_width, self.line_height)
+                    .to_rect()
+                    .with_origin(Point::new(x, y)),
+                color_background,
+            );
+        } else {
+            ctx.fill(rect_background, color_background);
+        }
         ctx.draw_text(
             &text_layout,
             Point::new(x + text_padding, y + text_layout.y_offset(self.line_height)),
@@ -456,6 +479,7 @@ fn paint_installation_progress(
                 install_status.plugin_name(),
                 install_status.install_type(),
                 install_status.error_string(),
+                install_status.progress(),
                 &data.config,
                 i,
             );
@@ -490,7 +514,9 @@ fn paint_available(&mut self, ctx: &mut PaintCtx, data: &LapceTabData) {
                 let x = self.line_height;
                 let layout = ctx
                     .text()
-                    .new_text_layout("Failed to load plugin information.")
+                    .new_text_layout(
+                        "Plugin registry unreachable, installed plugins are still listed.",
+                    )
                     .font(
                         data.config.ui.font_family(),
                         data.config.ui.font_size() as f64,
@@ -577,6 +603,23 @@ fn event(
                 }
             }
             Event::MouseDown(mouse_event) => {
+                // Clicking on a download in progress cancels it
+                if self.installed
+                    && mouse_event.button.is_left()
+                    && mouse_event.pos.y >= self.install_progress_y
+                {
+                    let row = ((mouse_event.pos.y - self.install_progress_y)
+                        / self.line_height) as usize;
+                    if let Some((id, status)) = data.plugin.installing.get_index(row)
+                    {
+                        if *status.install_type() == PluginInstallType::Installation
+                            && status.error_string().is_empty()
+                        {
+                            PluginData::cancel_volt_install(&data.proxy, id.clone());
+                            return;
+                        }
+                    }
+                }
                 if mouse_event.button.is_left() {
                     let index = (mouse_event.pos.y
                         / (self.line_height * 3.0 + self.gap))
@@ -1251,11 +1294,44 @@ fn status_on_click(
             menu = menu.separator();
         }
 
-        let proxy = data.proxy.clone();
+        let tab_id = data.id;
         let local_meta = meta.clone();
+        let item = if data.plugin.pinned.contains_key(id) {
+            druid::MenuItem::new("Unpin Version").on_activate(
+                move |ctx, _data, _env| {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UnpinVolt(local_meta.id()),
+                        Target::Widget(tab_id),
+                    ));
+                },
+            )
+        } else {
+            druid::MenuItem::new("Pin Version").on_activate(
+                move |ctx, _data, _env| {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::PinVolt(
+                            local_meta.id(),
+                            local_meta.version.clone(),
+                        ),
+                        Target::Widget(tab_id),
+                    ));
+                },
+            )
+        };
+        menu = menu.entry(item);
+
+        let volt_id = meta.id();
         let item = druid::MenuItem::new("Reload Plugin").on_activate(
-            move |_ctx, _data, _env| {
-                proxy.proxy_rpc.reload_volt(local_meta.clone());
+            move |ctx, _data, _env| {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPluginCommand(PluginCommand::Reload(
+                        volt_id.clone(),
+                    )),
+                    Target::Widget(tab_id),
+                ));
             },
         );
         menu = menu.entry(item);
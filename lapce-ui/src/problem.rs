@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use druid::{
     piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
@@ -9,35 +9,31 @@
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{LapceIcons, LapceTheme},
-    data::{EditorDiagnostic, LapceTabData},
+    data::{EditorDiagnostic, LapceData, LapceTabData},
     editor::EditorLocation,
     panel::PanelKind,
-    problem::ProblemData,
+    problem::{
+        diagnostic_sources, severity, severity_counts, ProblemData, ProblemGrouping,
+        SEVERITIES,
+    },
     proxy::path_from_url,
 };
+use lapce_rpc::proxy::ProxyResponse;
 use lsp_types::DiagnosticSeverity;
 
-use crate::panel::{LapcePanel, PanelHeaderKind, PanelSizing};
+use crate::panel::{LapcePanel, PanelHeaderKind, PanelSectionHeader, PanelSizing};
 
 pub fn new_problem_panel(data: &ProblemData) -> LapcePanel {
     LapcePanel::new(
         PanelKind::Problem,
         data.widget_id,
         data.split_id,
-        vec![
-            (
-                data.error_widget_id,
-                PanelHeaderKind::Simple("Errors".into()),
-                ProblemContent::new(DiagnosticSeverity::ERROR).boxed(),
-                PanelSizing::Flex(true),
-            ),
-            (
-                data.warning_widget_id,
-                PanelHeaderKind::Simple("Warnings".into()),
-                ProblemContent::new(DiagnosticSeverity::WARNING).boxed(),
-                PanelSizing::Flex(true),
-            ),
-        ],
+        vec![(
+            data.content_widget_id,
+            PanelHeaderKind::Widget(ProblemHeader::new().boxed()),
+            ProblemContent::new().boxed(),
+            PanelSizing::Flex(true),
+        )],
     )
 }
 
@@ -45,16 +41,219 @@ fn is_collapsed(data: &LapceTabData, path: &Path) -> bool {
     data.problem.collapsed.get(path).copied().unwrap_or(false)
 }
 
+fn severity_name(severity: DiagnosticSeverity) -> &'static str {
+    match severity {
+        DiagnosticSeverity::ERROR => "Errors",
+        DiagnosticSeverity::WARNING => "Warnings",
+        DiagnosticSeverity::INFORMATION => "Information",
+        _ => "Hints",
+    }
+}
+
+fn severity_icon(severity: DiagnosticSeverity) -> &'static str {
+    match severity {
+        DiagnosticSeverity::ERROR => LapceIcons::ERROR,
+        DiagnosticSeverity::WARNING => LapceIcons::WARNING,
+        _ => LapceIcons::LIGHTBULB,
+    }
+}
+
+/// The section header of the panel, with the number of diagnostics of each
+/// severity and a context menu to pick how they're grouped and filtered
+struct ProblemHeader {
+    header: PanelSectionHeader,
+}
+
+impl ProblemHeader {
+    fn new() -> Self {
+        Self {
+            header: PanelSectionHeader::new("Problems".into(), PanelKind::Problem),
+        }
+    }
+
+    fn header_text(data: &LapceTabData) -> String {
+        let counts = severity_counts(data.main_split.file_diagnostics());
+        let counts = SEVERITIES
+            .iter()
+            .zip(counts)
+            .map(|(severity, count)| {
+                format!("{count} {}", severity_name(*severity).to_lowercase())
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Problems ({counts})")
+    }
+
+    fn show_menu(ctx: &mut EventCtx, mouse_event: &MouseEvent, data: &LapceTabData) {
+        let problem = &data.problem;
+        let mut menu = druid::Menu::<LapceData>::new("");
+
+        for (grouping, label) in [
+            (ProblemGrouping::ByFile, "Group by File"),
+            (ProblemGrouping::BySeverity, "Group by Severity"),
+        ] {
+            let item = druid::MenuItem::new(label)
+                .selected(problem.grouping == grouping)
+                .command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::SetProblemGrouping(grouping),
+                    Target::Widget(data.id),
+                ));
+            menu = menu.entry(item);
+        }
+
+        menu = menu.separator();
+        for (severity, label) in [
+            (DiagnosticSeverity::ERROR, "Show Errors"),
+            (DiagnosticSeverity::WARNING, "Show Warnings and Above"),
+            (
+                DiagnosticSeverity::INFORMATION,
+                "Show Information and Above",
+            ),
+            (DiagnosticSeverity::HINT, "Show All"),
+        ] {
+            let item = druid::MenuItem::new(label)
+                .selected(problem.filter.min_severity == severity)
+                .command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::SetProblemMinSeverity(severity),
+                    Target::Widget(data.id),
+                ));
+            menu = menu.entry(item);
+        }
+
+        let mut sources = diagnostic_sources(data.main_split.file_diagnostics());
+        sources.extend(problem.filter.hidden_sources.iter().cloned());
+        if !sources.is_empty() {
+            menu = menu.separator();
+        }
+        for source in sources {
+            let item = druid::MenuItem::new(format!("Show {source}"))
+                .selected(!problem.filter.hidden_sources.contains(&source))
+                .command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ToggleProblemSource(source),
+                    Target::Widget(data.id),
+                ));
+            menu = menu.entry(item);
+        }
+
+        ctx.show_context_menu::<LapceData>(menu, ctx.to_window(mouse_event.pos));
+    }
+}
+
+impl Widget<LapceTabData> for ProblemHeader {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        if let Event::MouseDown(mouse_event) = event {
+            if mouse_event.button.is_right() && ctx.is_hot() {
+                Self::show_menu(ctx, mouse_event, data);
+                ctx.set_handled();
+            }
+        }
+        self.header.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.header.set_text(Self::header_text(data).into());
+        }
+        self.header.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !data
+            .main_split
+            .diagnostics
+            .same(&old_data.main_split.diagnostics)
+        {
+            self.header.set_text(Self::header_text(data).into());
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        self.header.layout(ctx, bc, data, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        self.header.paint(ctx, data, env);
+    }
+}
+
+/// A line, or several for a diagnostic, of the problem panel
+enum ProblemRow<'a> {
+    Severity(DiagnosticSeverity, usize),
+    File(&'a PathBuf, usize),
+    Diagnostic(&'a PathBuf, &'a EditorDiagnostic, usize),
+}
+
+impl ProblemRow<'_> {
+    fn lines(&self) -> usize {
+        match self {
+            ProblemRow::Severity(..) | ProblemRow::File(..) => 1,
+            ProblemRow::Diagnostic(_, d, _) => d.lines,
+        }
+    }
+}
+
+/// The rows of the panel, leaving out the diagnostics of collapsed files
+fn problem_rows(data: &LapceTabData) -> Vec<ProblemRow<'_>> {
+    let mut rows = Vec::new();
+    for group in data.problem.groups(data.main_split.file_diagnostics()) {
+        let indent = if let Some(severity) = group.severity {
+            let count = group.files.iter().map(|(_, d)| d.len()).sum();
+            rows.push(ProblemRow::Severity(severity, count));
+            1
+        } else {
+            0
+        };
+        for (path, diagnostics) in group.files {
+            rows.push(ProblemRow::File(path, indent));
+            if is_collapsed(data, path) {
+                continue;
+            }
+            rows.extend(
+                diagnostics
+                    .into_iter()
+                    .map(|d| ProblemRow::Diagnostic(path, d, indent)),
+            );
+        }
+    }
+    rows
+}
+
 struct ProblemContent {
-    severity: DiagnosticSeverity,
     mouse_pos: Point,
     content_height: f64,
 }
 
 impl ProblemContent {
-    pub fn new(severity: DiagnosticSeverity) -> Self {
+    pub fn new() -> Self {
         Self {
-            severity,
             mouse_pos: Point::ZERO,
             content_height: 0.0,
         }
@@ -74,77 +273,39 @@ fn mouse_down(
         let line_height = data.config.editor.line_height() as f64;
         let click_line = (mouse_event.pos.y / line_height).floor() as usize;
 
-        let items = data.main_split.diagnostics_items(self.severity);
-
+        // Skip rows before the clicked one.
         let mut line_cursor = 0;
-
-        // Skip files before clicked section.
-        let mut current_file = None;
-        for (path, diagnostics) in items {
-            let diag_lines = if is_collapsed(data, path) {
-                // If section is collapsed count only header with file name.
-                1
-            } else {
-                // Total file lines and header with file name.
-                diagnostics.iter().map(|d| d.lines).sum::<usize>() + 1 /* file name header */
-            };
-            let line_range = line_cursor..(line_cursor + diag_lines);
-
-            // Did we reach the clicked section?
-            if line_range.contains(&click_line) {
-                // The current file is what we are looking for.
-                current_file = Some((path, diagnostics));
+        let mut clicked_row = None;
+        for row in problem_rows(data) {
+            let lines = row.lines();
+            if (line_cursor..(line_cursor + lines)).contains(&click_line) {
+                clicked_row = Some(row);
                 break;
             }
-
-            // No. Move line cursor.
-            line_cursor += diag_lines;
+            line_cursor += lines;
         }
 
-        // Check if we have clicked something interesting.
-        let (path, diagnostics) = if let Some(diag) = current_file {
-            diag
-        } else {
-            // The user clicked an empty area.
-            return;
-        };
-
-        // Handle click on header with file name.
-        if line_cursor == click_line {
-            ctx.submit_command(Command::new(
-                LAPCE_UI_COMMAND,
-                LapceUICommand::ToggleProblem(path.to_path_buf()),
-                Target::Widget(data.id),
-            ));
+        if mouse_event.button.is_right() {
+            if let Some(ProblemRow::Diagnostic(path, diagnostic, _)) = clicked_row {
+                Self::request_code_actions(ctx, mouse_event, data, path, diagnostic);
+            }
             return;
         }
 
-        assert!(
-            !is_collapsed(data, path),
-            "Can't click any element because the file is collapsed. This shouldn't happen, please report a bug."
-        );
-
-        // Skip header.
-        line_cursor += 1;
-
-        // Skip to clicked diagnostic.
-        let mut clicked_file_diagnostic = None;
-        for file_diagnostic in diagnostics {
-            let line_range = line_cursor..(line_cursor + file_diagnostic.lines);
-
-            // Is the current diagnostic the clicked one?
-            if line_range.contains(&click_line) {
-                // We found the diagnostic we are looking for.
-                clicked_file_diagnostic = Some(file_diagnostic);
-                break;
+        let (path, file_diagnostic) = match clicked_row {
+            Some(ProblemRow::File(path, _)) => {
+                // Handle click on header with file name.
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ToggleProblem(path.to_path_buf()),
+                    Target::Widget(data.id),
+                ));
+                return;
             }
-
-            // No. Move line cursor and consume diagnostic.
-            line_cursor += file_diagnostic.lines;
-        }
-
-        // Handle current diagnostic.
-        let file_diagnostic = clicked_file_diagnostic.expect("Editor diagnostic not found. We should have found something, please report a bug");
+            Some(ProblemRow::Diagnostic(path, diagnostic, _)) => (path, diagnostic),
+            // The user clicked a severity header or an empty area.
+            _ => return,
+        };
 
         assert!(
             line_cursor <= click_line,
@@ -183,7 +344,7 @@ fn mouse_down(
 
         let mut clicked_related = None;
         for related in related {
-            let lines = related.message.lines().count();
+            let lines = related.message.lines().count() + 1;
             let item_line_range = line_cursor..(line_cursor + lines);
 
             // Is the current line the clicked one?
@@ -216,6 +377,322 @@ fn mouse_down(
             ));
         }
     }
+
+    /// Ask the servers for the code actions of the diagnostic, to show them in
+    /// a context menu where the diagnostic was clicked
+    fn request_code_actions(
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &LapceTabData,
+        path: &Path,
+        diagnostic: &EditorDiagnostic,
+    ) {
+        let point = ctx.to_window(mouse_event.pos);
+        let path = path.to_path_buf();
+        let tab_id = data.id;
+        let event_sink = ctx.get_external_handle();
+        data.proxy.proxy_rpc.get_code_actions(
+            path.clone(),
+            diagnostic.diagnostic.range,
+            vec![diagnostic.diagnostic.clone()],
+            move |result| {
+                if let Ok(ProxyResponse::GetCodeActionsResponse {
+                    plugin_id,
+                    resp,
+                }) = result
+                {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ShowProblemCodeActions {
+                            point,
+                            path,
+                            plugin_id,
+                            resp,
+                        },
+                        Target::Widget(tab_id),
+                    );
+                }
+            },
+        );
+    }
+
+    fn paint_severity(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        severity: DiagnosticSeverity,
+        count: usize,
+        line: usize,
+    ) {
+        let line_height = data.config.editor.line_height() as f64;
+        let padding = (line_height - 14.0) / 2.0;
+        let rect = Size::new(line_height, line_height)
+            .to_rect()
+            .with_origin(Point::new(0.0, line_height * line as f64))
+            .inflate(-padding, -padding);
+        ctx.draw_svg(
+            &data.config.ui_svg(severity_icon(severity)),
+            rect,
+            Some(
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
+            ),
+        );
+
+        let text_layout = ctx
+            .text()
+            .new_text_layout(format!("{} ({count})", severity_name(severity)))
+            .font(
+                data.config.ui.font_family(),
+                data.config.ui.font_size() as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                line_height,
+                line_height * line as f64 + text_layout.y_offset(line_height),
+            ),
+        );
+    }
+
+    fn paint_file(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        path: &Path,
+        indent: f64,
+        line: usize,
+    ) {
+        let line_height = data.config.editor.line_height() as f64;
+        let ui_font_family = data.config.ui.font_family();
+        let ui_font_size = data.config.ui.font_size() as f64;
+
+        let svg_size = data.config.ui.icon_size() as f64;
+        let (svg, svg_color) = data.config.file_svg(path);
+        let rect = Size::new(svg_size, svg_size)
+            .to_rect()
+            .with_origin(Point::new(
+                indent + (line_height - svg_size) / 2.0,
+                line_height * line as f64 + (line_height - svg_size) / 2.0,
+            ));
+        ctx.draw_svg(&svg, rect, svg_color);
+
+        let text_layout = ctx
+            .text()
+            .new_text_layout(path.file_name().unwrap().to_str().unwrap().to_string())
+            .font(ui_font_family.clone(), ui_font_size)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                indent + line_height,
+                line_height * line as f64 + text_layout.y_offset(line_height),
+            ),
+        );
+
+        if is_collapsed(data, path) {
+            return;
+        }
+
+        let folder = data
+            .workspace
+            .display_path(path)
+            .parent()
+            .and_then(Path::to_str)
+            .unwrap_or("")
+            .to_string();
+
+        if !folder.is_empty() {
+            let x = indent + text_layout.size().width + line_height + 5.0;
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(folder)
+                .font(ui_font_family, ui_font_size)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    x,
+                    line_height * line as f64 + text_layout.y_offset(line_height),
+                ),
+            );
+        }
+    }
+
+    fn paint_diagnostic(
+        &self,
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        d: &EditorDiagnostic,
+        indent: f64,
+        line: usize,
+    ) {
+        let line_height = data.config.editor.line_height() as f64;
+        let padding = (line_height - 14.0) / 2.0;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+        let ui_font_family = data.config.ui.font_family();
+        let ui_font_size = data.config.ui.font_size() as f64;
+
+        let msg_lines = message_lines(d);
+        let mut current_line = line;
+
+        if ctx.is_hot()
+            && current_line <= mouse_line
+            && mouse_line < current_line + msg_lines
+        {
+            ctx.fill(
+                Size::new(size.width, line_height * msg_lines as f64)
+                    .to_rect()
+                    .with_origin(Point::new(0.0, line_height * current_line as f64)),
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+            );
+        }
+
+        let svg = data.config.ui_svg(severity_icon(severity(&d.diagnostic)));
+        let rect = Size::new(line_height, line_height)
+            .to_rect()
+            .with_origin(Point::new(
+                indent + line_height,
+                line_height * current_line as f64,
+            ))
+            .inflate(-padding, -padding);
+        ctx.draw_svg(
+            &svg,
+            rect,
+            Some(
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
+            ),
+        );
+
+        for line in d.diagnostic.message.lines() {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(line.to_string())
+                .font(ui_font_family.clone(), ui_font_size)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    indent + 2.0 * line_height,
+                    line_height * current_line as f64
+                        + text_layout.y_offset(line_height),
+                ),
+            );
+            current_line += 1;
+        }
+
+        for related in d.diagnostic.related_information.as_deref().unwrap_or(&[]) {
+            if ctx.is_hot() && mouse_line >= current_line {
+                let lines = related.message.lines().count() + 1;
+                if mouse_line < current_line + lines {
+                    ctx.fill(
+                        Size::new(size.width, line_height * lines as f64)
+                            .to_rect()
+                            .with_origin(Point::new(
+                                0.0,
+                                line_height * current_line as f64,
+                            )),
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                    );
+                }
+            }
+
+            let svg = data.config.ui_svg(LapceIcons::LINK);
+            let rect = Size::new(line_height, line_height)
+                .to_rect()
+                .with_origin(Point::new(
+                    indent + 2.0 * line_height,
+                    line_height * current_line as f64,
+                ))
+                .inflate(-padding, -padding);
+            ctx.draw_svg(
+                &svg,
+                rect,
+                Some(
+                    data.config
+                        .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
+                ),
+            );
+            let path = path_from_url(&related.location.uri);
+            let text = format!(
+                "{}[{}, {}]:",
+                path.file_name().and_then(|f| f.to_str()).unwrap_or(""),
+                related.location.range.start.line,
+                related.location.range.start.character,
+            );
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(ui_font_family.clone(), ui_font_size)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    indent + 3.0 * line_height,
+                    line_height * current_line as f64
+                        + text_layout.y_offset(line_height),
+                ),
+            );
+            for line in related.message.lines() {
+                current_line += 1;
+
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(line.to_string())
+                    .font(ui_font_family.clone(), ui_font_size)
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(
+                        indent + 3.0 * line_height,
+                        line_height * current_line as f64
+                            + text_layout.y_offset(line_height),
+                    ),
+                );
+            }
+            current_line += 1;
+        }
+    }
 }
 
 impl Widget<LapceTabData> for ProblemContent {
@@ -269,7 +746,10 @@ fn update(
             ctx.request_layout();
         }
 
-        if data.problem.collapsed != old_data.problem.collapsed {
+        if data.problem.collapsed != old_data.problem.collapsed
+            || data.problem.grouping != old_data.problem.grouping
+            || data.problem.filter != old_data.problem.filter
+        {
             ctx.request_layout();
         }
     }
@@ -281,16 +761,9 @@ fn layout(
         data: &LapceTabData,
         _env: &Env,
     ) -> Size {
-        let items = data.main_split.diagnostics_items(self.severity);
-        let lines = items
+        let lines = problem_rows(data)
             .iter()
-            .map(|(path, diagnostics)| {
-                if is_collapsed(data, path) {
-                    1
-                } else {
-                    diagnostics.iter().map(|d| d.lines).sum::<usize>() + 1 /* file name header */
-                }
-            })
+            .map(ProblemRow::lines)
             .sum::<usize>();
         let line_height = data.config.editor.line_height() as f64;
         self.content_height = line_height * lines as f64;
@@ -300,264 +773,36 @@ fn layout(
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
         let line_height = data.config.editor.line_height() as f64;
-        let padding = (line_height - 14.0) / 2.0;
-        let size = ctx.size();
-        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
 
         let rect = ctx.region().bounding_box();
         let min = (rect.y0 / line_height).floor() as usize;
         let max = (rect.y1 / line_height) as usize + 2;
 
-        let ui_font_family = data.config.ui.font_family();
-        let ui_font_size = data.config.ui.font_size() as f64;
-
-        let items = data.main_split.diagnostics_items(self.severity);
         let mut current_line = 0;
-        for (path, diagnostics) in items {
-            let diagnostics_len =
-                diagnostics.iter().map(|d| d.lines).sum::<usize>() + 1 /* file name header */;
-
-            if !is_collapsed(data, path) && diagnostics_len + current_line < min {
-                current_line += diagnostics_len + 1;
-                continue;
+        for row in problem_rows(data) {
+            if current_line > max {
+                break;
             }
-
-            let svg_size = data.config.ui.icon_size() as f64;
-            let (svg, svg_color) = data.config.file_svg(path);
-            let rect =
-                Size::new(svg_size, svg_size)
-                    .to_rect()
-                    .with_origin(Point::new(
-                        (line_height - svg_size) / 2.0,
-                        line_height * current_line as f64
-                            + (line_height - svg_size) / 2.0,
-                    ));
-            ctx.draw_svg(&svg, rect, svg_color);
-
-            let text_layout = ctx
-                .text()
-                .new_text_layout(
-                    path.file_name().unwrap().to_str().unwrap().to_string(),
-                )
-                .font(ui_font_family.clone(), ui_font_size)
-                .text_color(
-                    data.config
-                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                        .clone(),
-                )
-                .build()
-                .unwrap();
-            ctx.draw_text(
-                &text_layout,
-                Point::new(
-                    line_height,
-                    line_height * current_line as f64
-                        + text_layout.y_offset(line_height),
-                ),
-            );
-
-            if is_collapsed(data, path) {
-                current_line += 1;
+            let lines = row.lines();
+            if current_line + lines < min {
+                current_line += lines;
                 continue;
             }
 
-            let folder = data
-                .workspace
-                .path
-                .as_ref()
-                .and_then(|workspace_path| path.strip_prefix(workspace_path).ok())
-                .unwrap_or(path)
-                .parent()
-                .and_then(Path::to_str)
-                .unwrap_or("")
-                .to_string();
-
-            if !folder.is_empty() {
-                let x = text_layout.size().width + line_height + 5.0;
-
-                let text_layout = ctx
-                    .text()
-                    .new_text_layout(folder)
-                    .font(ui_font_family.clone(), ui_font_size)
-                    .text_color(
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
-                            .clone(),
-                    )
-                    .build()
-                    .unwrap();
-                ctx.draw_text(
-                    &text_layout,
-                    Point::new(
-                        x,
-                        line_height * current_line as f64
-                            + text_layout.y_offset(line_height),
-                    ),
-                );
-            }
-
-            for d in diagnostics {
-                if current_line > max {
-                    return;
+            match row {
+                ProblemRow::Severity(severity, count) => {
+                    Self::paint_severity(ctx, data, severity, count, current_line);
                 }
-                let msg_lines = message_lines(d);
-                let related_lines = related_line_count(d);
-                if current_line + 1 + msg_lines + related_lines < min {
-                    current_line += msg_lines + related_lines;
-                    continue;
+                ProblemRow::File(path, indent) => {
+                    let indent = line_height * indent as f64;
+                    Self::paint_file(ctx, data, path, indent, current_line);
                 }
-
-                if ctx.is_hot()
-                    && current_line < mouse_line
-                    && mouse_line < current_line + 1 + msg_lines
-                {
-                    ctx.fill(
-                        Size::new(size.width, line_height * msg_lines as f64)
-                            .to_rect()
-                            .with_origin(Point::new(
-                                0.0,
-                                line_height * (current_line + 1) as f64,
-                            )),
-                        data.config
-                            .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
-                    );
-                }
-
-                let svg = match self.severity {
-                    DiagnosticSeverity::ERROR => {
-                        data.config.ui_svg(LapceIcons::ERROR)
-                    }
-                    _ => data.config.ui_svg(LapceIcons::WARNING),
-                };
-                let rect = Size::new(line_height, line_height)
-                    .to_rect()
-                    .with_origin(Point::new(
-                        line_height,
-                        line_height * (current_line + 1) as f64,
-                    ))
-                    .inflate(-padding, -padding);
-                ctx.draw_svg(
-                    &svg,
-                    rect,
-                    Some(
-                        data.config
-                            .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
-                    ),
-                );
-
-                for line in d.diagnostic.message.lines() {
-                    current_line += 1;
-                    let text_layout = ctx
-                        .text()
-                        .new_text_layout(line.to_string())
-                        .font(ui_font_family.clone(), ui_font_size)
-                        .text_color(
-                            data.config
-                                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
-                                .clone(),
-                        )
-                        .build()
-                        .unwrap();
-                    ctx.draw_text(
-                        &text_layout,
-                        Point::new(
-                            2.0 * line_height,
-                            line_height * current_line as f64
-                                + text_layout.y_offset(line_height),
-                        ),
-                    );
-                }
-
-                for related in
-                    d.diagnostic.related_information.as_deref().unwrap_or(&[])
-                {
-                    current_line += 1;
-
-                    if ctx.is_hot() && mouse_line >= current_line {
-                        let lines = related.message.lines().count() + 1;
-                        if mouse_line < current_line + lines {
-                            ctx.fill(
-                                Size::new(size.width, line_height * lines as f64)
-                                    .to_rect()
-                                    .with_origin(Point::new(
-                                        0.0,
-                                        line_height * current_line as f64,
-                                    )),
-                                data.config.get_color_unchecked(
-                                    LapceTheme::EDITOR_CURRENT_LINE,
-                                ),
-                            );
-                        }
-                    }
-
-                    let svg = data.config.ui_svg(LapceIcons::LINK);
-                    let rect = Size::new(line_height, line_height)
-                        .to_rect()
-                        .with_origin(Point::new(
-                            2.0 * line_height,
-                            line_height * current_line as f64,
-                        ))
-                        .inflate(-padding, -padding);
-                    ctx.draw_svg(
-                        &svg,
-                        rect,
-                        Some(
-                            data.config
-                                .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
-                        ),
-                    );
-                    let path = path_from_url(&related.location.uri);
-                    let text = format!(
-                        "{}[{}, {}]:",
-                        path.file_name().and_then(|f| f.to_str()).unwrap_or(""),
-                        related.location.range.start.line,
-                        related.location.range.start.character,
-                    );
-                    let text_layout = ctx
-                        .text()
-                        .new_text_layout(text)
-                        .font(ui_font_family.clone(), ui_font_size)
-                        .text_color(
-                            data.config
-                                .get_color_unchecked(LapceTheme::EDITOR_DIM)
-                                .clone(),
-                        )
-                        .build()
-                        .unwrap();
-                    ctx.draw_text(
-                        &text_layout,
-                        Point::new(
-                            3.0 * line_height,
-                            line_height * current_line as f64
-                                + text_layout.y_offset(line_height),
-                        ),
-                    );
-                    for line in related.message.lines() {
-                        current_line += 1;
-
-                        let text_layout = ctx
-                            .text()
-                            .new_text_layout(line.to_string())
-                            .font(ui_font_family.clone(), ui_font_size)
-                            .text_color(
-                                data.config
-                                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
-                                    .clone(),
-                            )
-                            .build()
-                            .unwrap();
-                        ctx.draw_text(
-                            &text_layout,
-                            Point::new(
-                                3.0 * line_height,
-                                line_height * current_line as f64
-                                    + text_layout.y_offset(line_height),
-                            ),
-                        );
-                    }
+                ProblemRow::Diagnostic(_, d, indent) => {
+                    let indent = line_height * indent as f64;
+                    self.paint_diagnostic(ctx, data, d, indent, current_line);
                 }
             }
-            current_line += 1;
+            current_line += lines;
         }
     }
 }
@@ -565,12 +810,3 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
 fn message_lines(diagnostic: &EditorDiagnostic) -> usize {
     diagnostic.diagnostic.message.lines().count()
 }
-
-fn related_line_count(diagnostic: &EditorDiagnostic) -> usize {
-    diagnostic
-        .diagnostic
-        .related_information
-        .as_ref()
-        .map(|r| r.iter().map(|r| r.message.lines().count() + 1).sum())
-        .unwrap_or(0)
-}
@@ -0,0 +1,463 @@
+use std::{path::Path, sync::Arc};
+
+use druid::{
+    kurbo::Line,
+    piet::{Text, TextAttribute, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, LayoutCtx,
+    LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect, RenderContext, Size,
+    Target, UpdateCtx, Widget, WidgetExt,
+};
+use lapce_data::{
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
+    },
+    config::LapceTheme,
+    data::LapceTabData,
+    editor::EditorLocation,
+    panel::PanelKind,
+    rename::{RenameData, RenamePreviewItem},
+};
+
+use crate::panel::{LapcePanel, PanelHeaderKind, PanelSizing};
+
+pub fn new_rename_preview_panel(data: &RenameData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::RenamePreview,
+        data.preview_widget_id,
+        data.preview_split_id,
+        vec![(
+            data.preview_content_id,
+            PanelHeaderKind::Simple("Rename Preview".into()),
+            RenamePreviewContent::new().boxed(),
+            PanelSizing::Flex(false),
+        )],
+    )
+}
+
+/// The list of the edits of the previewed rename. The first line sums them up
+/// and has the buttons to apply or discard them, then each file has a header
+/// followed by a line per edit, and a checkbox to leave them out.
+struct RenamePreviewContent {
+    mouse_pos: Point,
+    content_height: f64,
+    apply_rect: Rect,
+    discard_rect: Rect,
+}
+
+impl RenamePreviewContent {
+    fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            content_height: 0.0,
+            apply_rect: Rect::ZERO,
+            discard_rect: Rect::ZERO,
+        }
+    }
+
+    fn mouse_down(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &mut LapceTabData,
+    ) {
+        if !ctx.is_hot() {
+            return;
+        }
+
+        for (rect, cmd) in [
+            (self.apply_rect, LapceWorkbenchCommand::ApplyRenamePreview),
+            (
+                self.discard_rect,
+                LapceWorkbenchCommand::DiscardRenamePreview,
+            ),
+        ] {
+            if rect.contains(mouse_event.pos) {
+                ctx.submit_command(Command::new(
+                    LAPCE_COMMAND,
+                    LapceCommand {
+                        kind: CommandKind::Workbench(cmd),
+                        data: None,
+                    },
+                    Target::Widget(data.id),
+                ));
+                return;
+            }
+        }
+
+        let line_height = data.config.editor.line_height() as f64;
+        let click_line = (mouse_event.pos.y / line_height).floor() as usize;
+        let item = match click_line
+            .checked_sub(1)
+            .and_then(|line| data.rename.preview.as_ref()?.item_at_line(line))
+        {
+            Some(item) => item,
+            None => return,
+        };
+
+        let on_checkbox = |indent: f64| {
+            mouse_event.pos.x >= indent * line_height
+                && mouse_event.pos.x < (indent + 1.0) * line_height
+        };
+        let rename = Arc::make_mut(&mut data.rename);
+        let preview = match rename.preview.as_mut() {
+            Some(preview) => preview,
+            None => return,
+        };
+        match item {
+            RenamePreviewItem::File(file) => preview.toggle_file(file),
+            RenamePreviewItem::Edit(file, edit) if on_checkbox(1.0) => {
+                preview.toggle_edit(file, edit)
+            }
+            RenamePreviewItem::Edit(file, edit) => {
+                let file = &preview.files[file];
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLspLocation(
+                        None,
+                        EditorLocation {
+                            path: file.path.clone(),
+                            position: Some(file.edits[edit].edit.range.start),
+                            scroll_offset: None,
+                            history: None,
+                        },
+                        false,
+                    ),
+                    Target::Widget(data.id),
+                ));
+            }
+        }
+    }
+
+    fn paint_checkbox(
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        origin: Point,
+        selected: bool,
+    ) {
+        let line_height = data.config.editor.line_height() as f64;
+        let size = (line_height / 2.0).round();
+        let rect = Size::new(size, size).to_rect().with_origin(Point::new(
+            origin.x + (line_height - size) / 2.0,
+            origin.y + (line_height - size) / 2.0,
+        ));
+        ctx.stroke(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+            1.0,
+        );
+        if selected {
+            ctx.fill(
+                rect.inflate(-2.5, -2.5),
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND),
+            );
+        }
+    }
+}
+
+impl Widget<LapceTabData> for RenamePreviewContent {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+                let line_height = data.config.editor.line_height() as f64;
+                if (mouse_event.pos.y >= line_height
+                    && mouse_event.pos.y < self.content_height)
+                    || self.apply_rect.contains(mouse_event.pos)
+                    || self.discard_rect.contains(mouse_event.pos)
+                {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if data.rename.preview != old_data.rename.preview {
+            ctx.request_layout();
+        } else if data.rename.preview.is_some()
+            && !data
+                .main_split
+                .open_docs
+                .same(&old_data.main_split.open_docs)
+        {
+            // The edited lines are read from the documents, which may have
+            // been loaded or changed since
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        let lines = data
+            .rename
+            .preview
+            .as_ref()
+            .map(|preview| preview.lines() + 1)
+            .unwrap_or(0);
+        let line_height = data.config.editor.line_height() as f64;
+        self.content_height = line_height * lines as f64;
+
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        self.apply_rect = Rect::ZERO;
+        self.discard_rect = Rect::ZERO;
+        let preview = match data.rename.preview.as_ref() {
+            Some(preview) => preview,
+            None => return,
+        };
+
+        let line_height = data.config.editor.line_height() as f64;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+
+        let ui_font_family = data.config.ui.font_family();
+        let ui_font_size = data.config.ui.font_size() as f64;
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+        let dim = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+            .clone();
+
+        let (selected, total) = preview.selected_count();
+        let text_layout = ctx
+            .text()
+            .new_text_layout(format!(
+                "Rename to {}: {selected} of {total} changes in {} files",
+                preview.new_name,
+                preview.files.len()
+            ))
+            .font(ui_font_family.clone(), ui_font_size)
+            .text_color(foreground.clone())
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(line_height / 2.0, text_layout.y_offset(line_height)),
+        );
+
+        let mut x = text_layout.size().width + line_height;
+        for (label, is_apply) in [("Apply", true), ("Discard", false)] {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(label)
+                .font(ui_font_family.clone(), ui_font_size)
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_LINK)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let rect = Size::new(text_layout.size().width, line_height)
+                .to_rect()
+                .with_origin(Point::new(x, 0.0));
+            ctx.draw_text(
+                &text_layout,
+                Point::new(x, text_layout.y_offset(line_height)),
+            );
+            if is_apply {
+                self.apply_rect = rect;
+            } else {
+                self.discard_rect = rect;
+            }
+            x = rect.x1 + line_height / 2.0;
+        }
+
+        let mut current_line = 1;
+        for file in preview.files.iter() {
+            if current_line > max {
+                return;
+            }
+            if current_line + file.edits.len() < min {
+                current_line += file.edits.len() + 1;
+                continue;
+            }
+
+            let y = line_height * current_line as f64;
+            Self::paint_checkbox(ctx, data, Point::new(0.0, y), file.is_selected());
+
+            let svg_size = data.config.ui.icon_size() as f64;
+            let (svg, svg_color) = data.config.file_svg(&file.path);
+            let rect =
+                Size::new(svg_size, svg_size)
+                    .to_rect()
+                    .with_origin(Point::new(
+                        line_height + (line_height - svg_size) / 2.0,
+                        y + (line_height - svg_size) / 2.0,
+                    ));
+            ctx.draw_svg(&svg, rect, svg_color);
+
+            let file_name = file
+                .path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .unwrap_or("")
+                .to_string();
+            let folder = data
+                .workspace
+                .display_path(&file.path)
+                .parent()
+                .and_then(Path::to_str)
+                .unwrap_or("")
+                .to_string();
+            let text = format!("{file_name} {folder}");
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text.clone())
+                .font(ui_font_family.clone(), ui_font_size)
+                .text_color(foreground.clone())
+                .range_attribute(
+                    file_name.len()..text.len(),
+                    TextAttribute::TextColor(dim.clone()),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(2.0 * line_height, y + text_layout.y_offset(line_height)),
+            );
+
+            let doc = data.main_split.open_docs.get(&file.path);
+            for edit in file.edits.iter() {
+                current_line += 1;
+                if current_line < min {
+                    continue;
+                }
+                if current_line > max {
+                    return;
+                }
+
+                let y = line_height * current_line as f64;
+                if ctx.is_hot() && mouse_line == current_line {
+                    ctx.fill(
+                        Size::new(size.width, line_height)
+                            .to_rect()
+                            .with_origin(Point::new(0.0, y)),
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+                    );
+                }
+                Self::paint_checkbox(
+                    ctx,
+                    data,
+                    Point::new(line_height, y),
+                    edit.selected,
+                );
+
+                let line_number = format!("{}: ", edit.edit.range.start.line + 1);
+                let (text, old, new) = match doc.filter(|doc| doc.loaded()) {
+                    Some(doc) => {
+                        let (text, old, new) = edit.diff_line(doc.buffer());
+                        let offset = line_number.len();
+                        (
+                            format!("{line_number}{text}"),
+                            old.start + offset..old.end + offset,
+                            new.start + offset..new.end + offset,
+                        )
+                    }
+                    // Only the new text can be shown until the file is loaded
+                    None => {
+                        let new = line_number.len()
+                            ..line_number.len() + edit.edit.new_text.len();
+                        (format!("{line_number}{}", edit.edit.new_text), 0..0, new)
+                    }
+                };
+                let old_color = data
+                    .config
+                    .get_color_unchecked(LapceTheme::SOURCE_CONTROL_REMOVED)
+                    .clone();
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(text)
+                    .font(ui_font_family.clone(), ui_font_size)
+                    .text_color(foreground.clone())
+                    .range_attribute(
+                        0..line_number.len(),
+                        TextAttribute::TextColor(dim.clone()),
+                    )
+                    .range_attribute(
+                        old.clone(),
+                        TextAttribute::TextColor(old_color.clone()),
+                    )
+                    .range_attribute(
+                        new,
+                        TextAttribute::TextColor(
+                            data.config
+                                .get_color_unchecked(
+                                    LapceTheme::SOURCE_CONTROL_ADDED,
+                                )
+                                .clone(),
+                        ),
+                    )
+                    .build()
+                    .unwrap();
+                let origin = Point::new(
+                    3.0 * line_height,
+                    y + text_layout.y_offset(line_height),
+                );
+                ctx.draw_text(&text_layout, origin);
+
+                // The replaced text is struck through
+                if !old.is_empty() {
+                    let x0 = text_layout.hit_test_text_position(old.start).point.x;
+                    let x1 = text_layout.hit_test_text_position(old.end).point.x;
+                    let y = y + line_height / 2.0;
+                    ctx.stroke(
+                        Line::new(
+                            Point::new(origin.x + x0, y),
+                            Point::new(origin.x + x1, y),
+                        ),
+                        &old_color,
+                        1.0,
+                    );
+                }
+            }
+            current_line += 1;
+        }
+    }
+}
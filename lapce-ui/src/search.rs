@@ -1,5 +1,3 @@
-use std::path::PathBuf;
-
 use druid::{
     piet::{Text, TextAttribute, TextLayout as PietTextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontWeight,
@@ -491,13 +489,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 ),
             );
 
-            let mut path: PathBuf = path.clone();
-            if let Some(workspace_path) = data.workspace.path.as_ref() {
-                path = path
-                    .strip_prefix(workspace_path)
-                    .unwrap_or(&path)
-                    .to_path_buf();
-            }
+            let path = data.workspace.display_path(path);
             let folder = path
                 .parent()
                 .and_then(|s| s.to_str())
@@ -23,8 +23,10 @@
         CommandExecuted, CommandKind, LapceUICommand, LAPCE_COMMAND,
         LAPCE_UI_COMMAND,
     },
-    config::{
-        CoreConfig, DropdownInfo, EditorConfig, LapceTheme, TerminalConfig, UIConfig,
+    config::{DropdownInfo, LapceTheme},
+    config_schema::{
+        setting_is_modified, setting_matches, settings_schema, workbench_sections,
+        SETTINGS_SECTIONS,
     },
     data::{FocusArea, LapceEditorData, LapceTabData},
     document::{BufferContent, Document},
@@ -32,8 +34,8 @@
     keypress::KeyPressFocus,
     settings::{LapceSettingsFocusData, LapceSettingsKind, SettingsValueKind},
 };
+use lapce_rpc::plugin::VoltID;
 use lapce_xi_rope::Rope;
-use serde::Serialize;
 
 use crate::{
     button::Button,
@@ -50,6 +52,12 @@ pub struct LapceSettingsPanel {
     content_rect: Rect,
     switcher_rect: Rect,
     switcher: WidgetPod<LapceTabData, LapceScroll<LapceTabData, SettingsSwitcher>>,
+    /// The input searching the settings
+    search: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    /// The settings of all the sections matching the search, shown instead of
+    /// the active section while searching
+    search_results:
+        WidgetPod<LapceTabData, LapceScroll<LapceTabData, LapceSettings>>,
     children: HashMap<
         LapceSettingsKind,
         WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
@@ -62,6 +70,7 @@ pub fn new(
         widget_id: WidgetId,
         editor_tab_id: WidgetId,
         keymap_input_view_id: WidgetId,
+        settings_input_view_id: WidgetId,
     ) -> Self {
         let mut children = HashMap::new();
         children.insert(
@@ -86,6 +95,12 @@ pub fn new(
                 LapceSettings::new_scroll(LapceSettingsKind::Terminal).boxed(),
             ),
         );
+        children.insert(
+            LapceSettingsKind::Workbench,
+            WidgetPod::new(
+                LapceSettings::new_scroll(LapceSettingsKind::Workbench).boxed(),
+            ),
+        );
         children.insert(
             LapceSettingsKind::Theme,
             WidgetPod::new(LapceScroll::new(ThemeSettings::new(data)).boxed()),
@@ -109,6 +124,12 @@ pub fn new(
         }
 
         let switcher = LapceScroll::new(SettingsSwitcher::new(widget_id));
+        let search =
+            LapceEditorView::new(settings_input_view_id, WidgetId::next(), None)
+                .hide_header()
+                .hide_gutter()
+                .set_placeholder("Search Settings".to_string())
+                .padding((10.0, 15.0));
 
         Self {
             widget_id,
@@ -116,6 +137,8 @@ pub fn new(
             content_rect: Rect::ZERO,
             switcher_rect: Rect::ZERO,
             switcher: WidgetPod::new(switcher),
+            search: WidgetPod::new(search.boxed()),
+            search_results: WidgetPod::new(LapceSettings::new_search_scroll()),
             children,
             active: LapceSettingsKind::Core,
         }
@@ -173,9 +196,17 @@ fn update_plugins(&mut self, ctx: &mut EventCtx, data: &LapceTabData) {
             }
         }
         if children_changed {
+            self.search_results.widget_mut().child_mut().clear();
             ctx.children_changed();
         }
     }
+
+    /// Whether the settings matching the search are shown, instead of the
+    /// active section
+    fn is_searching(&self, data: &LapceTabData) -> bool {
+        self.active != LapceSettingsKind::Keymap
+            && !data.settings.filter_pattern.is_empty()
+    }
 }
 
 impl Widget<LapceTabData> for LapceSettingsPanel {
@@ -283,10 +314,18 @@ fn event(
         }
 
         self.switcher.event(ctx, event, data, env);
+        if self.active != LapceSettingsKind::Keymap
+            || event.should_propagate_to_hidden()
+        {
+            self.search.event(ctx, event, data, env);
+        }
         if event.should_propagate_to_hidden() {
+            self.search_results.event(ctx, event, data, env);
             for child in self.children.values_mut() {
                 child.event(ctx, event, data, env);
             }
+        } else if self.is_searching(data) {
+            self.search_results.event(ctx, event, data, env);
         } else if let Some(child) = self.children.get_mut(&self.active) {
             child.event(ctx, event, data, env);
         }
@@ -300,6 +339,8 @@ fn lifecycle(
         env: &Env,
     ) {
         self.switcher.lifecycle(ctx, event, data, env);
+        self.search.lifecycle(ctx, event, data, env);
+        self.search_results.lifecycle(ctx, event, data, env);
         for child in self.children.values_mut() {
             child.lifecycle(ctx, event, data, env);
         }
@@ -308,11 +349,16 @@ fn lifecycle(
     fn update(
         &mut self,
         ctx: &mut UpdateCtx,
-        _old_data: &LapceTabData,
+        old_data: &LapceTabData,
         data: &LapceTabData,
         env: &Env,
     ) {
+        if self.is_searching(data) != self.is_searching(old_data) {
+            ctx.request_layout();
+        }
         self.switcher.update(ctx, data, env);
+        self.search.update(ctx, data, env);
+        self.search_results.update(ctx, data, env);
         for child in self.children.values_mut() {
             child.update(ctx, data, env);
         }
@@ -342,13 +388,34 @@ fn layout(
             .with_origin(Point::ZERO)
             .round();
 
-        let content_size = Size::new(
-            self_size.width - switcher_size.width - 20.0,
-            self_size.height,
-        );
-        let content_origin = Point::new(switcher_size.width + 20.0, 0.0);
+        let content_width = self_size.width - switcher_size.width - 20.0;
+        let content_x = switcher_size.width + 20.0;
+        let search_height = if self.active != LapceSettingsKind::Keymap {
+            let search_size = self.search.layout(
+                ctx,
+                &BoxConstraints::new(
+                    Size::new(content_width, 0.0),
+                    Size::new(content_width, self_size.height),
+                ),
+                data,
+                env,
+            );
+            self.search
+                .set_origin(ctx, data, env, Point::new(content_x, 0.0));
+            search_size.height
+        } else {
+            0.0
+        };
+
+        let content_size =
+            Size::new(content_width, (self_size.height - search_height).max(0.0));
+        let content_origin = Point::new(content_x, search_height);
         let content_bc = BoxConstraints::tight(content_size);
-        if let Some(child) = self.children.get_mut(&self.active) {
+        if self.is_searching(data) {
+            self.search_results.layout(ctx, &content_bc, data, env);
+            self.search_results
+                .set_origin(ctx, data, env, content_origin);
+        } else if let Some(child) = self.children.get_mut(&self.active) {
             child.layout(ctx, &content_bc, data, env);
             child.set_origin(ctx, data, env, content_origin);
         }
@@ -358,7 +425,12 @@ fn layout(
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         self.switcher.paint(ctx, data, env);
-        if let Some(child) = self.children.get_mut(&self.active) {
+        if self.active != LapceSettingsKind::Keymap {
+            self.search.paint(ctx, data, env);
+        }
+        if self.is_searching(data) {
+            self.search_results.paint(ctx, data, env);
+        } else if let Some(child) = self.children.get_mut(&self.active) {
             child.paint(ctx, data, env);
         }
 
@@ -375,8 +447,21 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
 
 struct LapceSettings {
     widget_id: WidgetId,
-    kind: LapceSettingsKind,
-    children: Vec<WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
+    /// The section shown, or `None` for the settings of all the sections and
+    /// plugins which match the search
+    kind: Option<LapceSettingsKind>,
+    /// The search the shown settings match
+    filter: String,
+    children: Vec<SettingsListItem>,
+}
+
+/// A setting of the list, with what the search is matched against
+struct SettingsListItem {
+    kind: String,
+    key: String,
+    desc: String,
+    shown: bool,
+    widget: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
 }
 
 impl LapceSettings {
@@ -385,103 +470,178 @@ pub fn new_scroll(
     ) -> LapceScroll<LapceTabData, LapceSettings> {
         LapceScroll::new(Self {
             widget_id: WidgetId::next(),
-            kind,
+            kind: Some(kind),
+            filter: String::new(),
             children: Vec::new(),
         })
     }
 
-    fn update_children(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
-        fn into_settings_map(
-            data: &impl Serialize,
-        ) -> serde_json::Map<String, serde_json::Value> {
-            match serde_json::to_value(data).unwrap() {
-                serde_json::Value::Object(h) => h,
-                _ => serde_json::Map::default(),
-            }
-        }
+    /// The settings matching the search typed above the settings
+    pub fn new_search_scroll() -> LapceScroll<LapceTabData, LapceSettings> {
+        LapceScroll::new(Self {
+            widget_id: WidgetId::next(),
+            kind: None,
+            filter: String::new(),
+            children: Vec::new(),
+        })
+    }
 
+    fn update_children(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
         self.children.clear();
 
-        let (kind, fields, descs, mut settings) = match &self.kind {
-            LapceSettingsKind::Core => (
-                "core",
-                &CoreConfig::FIELDS[..],
-                &CoreConfig::DESCS[..],
-                into_settings_map(&data.config.core),
-            ),
-            LapceSettingsKind::UI => (
-                "ui",
-                &UIConfig::FIELDS[..],
-                &UIConfig::DESCS[..],
-                into_settings_map(&data.config.ui),
-            ),
-            LapceSettingsKind::Editor => (
-                "editor",
-                &EditorConfig::FIELDS[..],
-                &EditorConfig::DESCS[..],
-                into_settings_map(&data.config.editor),
-            ),
-            LapceSettingsKind::Terminal => (
-                "terminal",
-                &TerminalConfig::FIELDS[..],
-                &TerminalConfig::DESCS[..],
-                into_settings_map(&data.config.terminal),
-            ),
-            LapceSettingsKind::Theme | LapceSettingsKind::Keymap => {
-                return;
+        match &self.kind {
+            Some(LapceSettingsKind::Core) => {
+                self.push_section_items(ctx, data, "core");
             }
-            LapceSettingsKind::Plugin(volt_id) => {
-                if let Some(volt) = data.plugin.installed.get(volt_id).cloned() {
-                    if let Some(config) = volt.config.as_ref() {
-                        for (key, config) in
-                            config.iter().sorted_by_key(|(key, _)| *key)
-                        {
-                            let mut value = config.default.clone();
-                            if let Some(plugin_config) =
-                                data.config.plugins.get(&volt.name)
-                            {
-                                if let Some(v) = plugin_config.get(key) {
-                                    value = v.clone();
-                                }
-                            }
-                            // TODO: let plugins define dropdowns
-                            let value = SettingsValue::from(value);
-                            self.children.push(create_settings_item(
-                                data,
-                                volt.name.clone(),
-                                key.to_string(),
-                                config.description.clone(),
-                                value,
-                                ctx.get_external_handle(),
-                            ));
-                        }
-                    }
+            Some(LapceSettingsKind::UI) => {
+                self.push_section_items(ctx, data, "ui");
+            }
+            Some(LapceSettingsKind::Editor) => {
+                self.push_section_items(ctx, data, "editor");
+            }
+            Some(LapceSettingsKind::Terminal) => {
+                self.push_section_items(ctx, data, "terminal");
+            }
+            Some(LapceSettingsKind::Workbench) => {
+                for section in workbench_sections() {
+                    self.push_section_items(ctx, data, section);
                 }
-                return;
             }
-        };
+            Some(LapceSettingsKind::Theme | LapceSettingsKind::Keymap) => {}
+            Some(LapceSettingsKind::Plugin(volt_id)) => {
+                let volt_id = volt_id.clone();
+                self.push_plugin_items(ctx, data, &volt_id);
+            }
+            None => {
+                for section in SETTINGS_SECTIONS {
+                    self.push_section_items(ctx, data, section);
+                }
+                let volt_ids: Vec<VoltID> = data
+                    .plugin
+                    .installed
+                    .iter()
+                    .filter(|(_, volt)| volt.config.is_some())
+                    .sorted_by_key(|(_, volt)| &volt.display_name)
+                    .map(|(volt_id, _)| volt_id.clone())
+                    .collect();
+                for volt_id in volt_ids {
+                    self.push_plugin_items(ctx, data, &volt_id);
+                }
+            }
+        }
 
-        for (field, desc) in fields.iter().zip(descs.iter()) {
-            // TODO(dbuga): we should generate kebab-case field names
-            let field = field.replace('_', "-");
+        if self.kind.is_none() {
+            self.filter = data.settings.filter_pattern.clone();
+        }
+        self.update_shown();
+    }
+
+    fn push_section_items(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        section: &str,
+    ) {
+        let mut values = data.config.section_values(section);
+        for schema in settings_schema().iter().filter(|s| s.kind == section) {
             let value = if let Some(dropdown) =
-                data.config.get_dropdown_info(kind, &field)
+                data.config.get_dropdown_info(section, &schema.key)
             {
                 SettingsValue::Dropdown(dropdown)
             } else {
-                let value = settings.remove(&field).unwrap();
-                SettingsValue::from(value)
+                values
+                    .remove(&schema.key)
+                    .map(SettingsValue::from)
+                    .unwrap_or(SettingsValue::Empty)
             };
-            self.children.push(create_settings_item(
+            self.push_item(
+                ctx,
                 data,
-                kind.to_string(),
-                field,
-                desc.to_string(),
+                section.to_string(),
+                schema.key.clone(),
+                schema.description.to_string(),
                 value,
-                ctx.get_external_handle(),
-            ));
+                schema.default.clone(),
+            );
+        }
+    }
+
+    fn push_plugin_items(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        volt_id: &VoltID,
+    ) {
+        let volt = match data.plugin.installed.get(volt_id).cloned() {
+            Some(volt) => volt,
+            None => return,
+        };
+        if let Some(config) = volt.config.as_ref() {
+            for (key, config) in config.iter().sorted_by_key(|(key, _)| *key) {
+                let value = data
+                    .config
+                    .setting_value(&volt.name, key)
+                    .unwrap_or_else(|| config.default.clone());
+                // TODO: let plugins define dropdowns
+                self.push_item(
+                    ctx,
+                    data,
+                    volt.name.clone(),
+                    key.to_string(),
+                    config.description.clone(),
+                    SettingsValue::from(value),
+                    config.default.clone(),
+                );
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_item(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        kind: String,
+        key: String,
+        desc: String,
+        value: SettingsValue,
+        default: serde_json::Value,
+    ) {
+        let mut info = SettingsItemInfo::new(
+            data,
+            kind.clone(),
+            key.clone(),
+            desc.clone(),
+            default,
+        );
+        if self.kind.is_none() {
+            info = info.with_kind_in_name();
+        }
+        let widget =
+            create_settings_item(data, info, value, ctx.get_external_handle());
+        self.children.push(SettingsListItem {
+            kind,
+            key,
+            desc,
+            shown: true,
+            widget,
+        });
+    }
+
+    /// Show only the settings matching the search, which are all of them
+    /// when it's empty
+    fn update_shown(&mut self) {
+        for child in self.children.iter_mut() {
+            child.shown =
+                setting_matches(&self.filter, &child.kind, &child.key, &child.desc);
         }
     }
+
+    /// Build the settings again the next time they're shown, like when the
+    /// plugins changed
+    fn clear(&mut self) {
+        self.children.clear();
+    }
 }
 
 impl Widget<LapceTabData> for LapceSettings {
@@ -497,7 +657,9 @@ fn event(
         env: &Env,
     ) {
         for child in self.children.iter_mut() {
-            child.event(ctx, event, data, env);
+            if child.shown || event.should_propagate_to_hidden() {
+                child.widget.event(ctx, event, data, env);
+            }
         }
         if self.children.is_empty() {
             self.update_children(ctx, data);
@@ -513,7 +675,7 @@ fn lifecycle(
         env: &Env,
     ) {
         for child in self.children.iter_mut() {
-            child.lifecycle(ctx, event, data, env);
+            child.widget.lifecycle(ctx, event, data, env);
         }
     }
 
@@ -524,8 +686,13 @@ fn update(
         data: &LapceTabData,
         env: &Env,
     ) {
+        if self.kind.is_none() && self.filter != data.settings.filter_pattern {
+            self.filter = data.settings.filter_pattern.clone();
+            self.update_shown();
+            ctx.request_layout();
+        }
         for child in self.children.iter_mut() {
-            child.update(ctx, data, env);
+            child.widget.update(ctx, data, env);
         }
     }
 
@@ -537,9 +704,9 @@ fn layout(
         env: &Env,
     ) -> Size {
         let mut y = 0.0;
-        for child in self.children.iter_mut() {
-            let size = child.layout(ctx, bc, data, env);
-            child.set_origin(ctx, data, env, Point::new(0.0, y));
+        for child in self.children.iter_mut().filter(|child| child.shown) {
+            let size = child.widget.layout(ctx, bc, data, env);
+            child.widget.set_origin(ctx, data, env, Point::new(0.0, y));
             y += size.height;
         }
 
@@ -547,8 +714,8 @@ fn layout(
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
-        for child in self.children.iter_mut() {
-            child.paint(ctx, data, env);
+        for child in self.children.iter_mut().filter(|child| child.shown) {
+            child.widget.paint(ctx, data, env);
         }
     }
 }
@@ -588,9 +755,7 @@ fn from(v: serde_json::Value) -> Self {
 /// Includes padding.
 fn create_settings_item(
     data: &mut LapceTabData,
-    kind: String,
-    key: String,
-    desc: String,
+    info: SettingsItemInfo,
     value: SettingsValue,
     event_sink: ExtEventSink,
 ) -> WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>> {
@@ -602,9 +767,7 @@ fn create_settings_item(
                 insets,
                 InputSettingsItem::new(
                     data,
-                    kind,
-                    key,
-                    desc,
+                    info,
                     event_sink,
                     n.to_string(),
                     SettingsValueKind::Float,
@@ -617,9 +780,7 @@ fn create_settings_item(
                 insets,
                 InputSettingsItem::new(
                     data,
-                    kind,
-                    key,
-                    desc,
+                    info,
                     event_sink,
                     n.to_string(),
                     SettingsValueKind::Integer,
@@ -632,9 +793,7 @@ fn create_settings_item(
                 insets,
                 InputSettingsItem::new(
                     data,
-                    kind,
-                    key,
-                    desc,
+                    info,
                     event_sink,
                     s,
                     SettingsValueKind::String,
@@ -643,28 +802,26 @@ fn create_settings_item(
             .boxed(),
         ),
         SettingsValue::Bool(checked) => WidgetPod::new(
-            LapcePadding::new(
-                insets,
-                CheckBoxSettingsItem::new(key, kind, desc, checked),
-            )
-            .boxed(),
+            LapcePadding::new(insets, CheckBoxSettingsItem::new(info, checked))
+                .boxed(),
         ),
         SettingsValue::Dropdown(dropdown) => WidgetPod::new(
             LapcePadding::new(
                 insets,
-                DropdownSettingsItem::new(data, key, kind, desc, dropdown),
+                DropdownSettingsItem::new(data, info, dropdown),
             )
             .boxed(),
         ),
         SettingsValue::Empty => WidgetPod::new(
-            LapcePadding::new(insets, EmptySettingsItem::new(key, kind, desc))
-                .boxed(),
+            LapcePadding::new(insets, EmptySettingsItem::new(info)).boxed(),
         ),
     }
 }
 
 /// Shared information between each setting item
 struct SettingsItemInfo {
+    /// The id of the setting item, which is sent the command resetting it
+    widget_id: WidgetId,
     width: f64,
     padding: f64,
 
@@ -672,6 +829,16 @@ struct SettingsItemInfo {
     key: String,
     kind: String,
     desc: String,
+    /// Whether the name starts with the section of the setting, like in the
+    /// search results
+    kind_in_name: bool,
+
+    /// The default value of the setting
+    default: serde_json::Value,
+    /// Whether the value is different from the default, which is shown with a
+    /// marker and a button resetting the setting
+    modified: bool,
+    reset: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
 
     name_text: Option<PietTextLayout>,
     desc_text: Option<PietTextLayout>,
@@ -684,17 +851,56 @@ impl SettingsItemInfo {
     /// The amount of time to wait for the next key press before storing settings.
     const SAVE_DELAY: Duration = Duration::from_millis(500);
 
-    fn new(key: String, kind: String, desc: String) -> Self {
-        SettingsItemInfo {
+    fn new(
+        data: &LapceTabData,
+        kind: String,
+        key: String,
+        desc: String,
+        default: serde_json::Value,
+    ) -> Self {
+        let widget_id = WidgetId::next();
+        let reset_kind = kind.clone();
+        let reset_key = key.clone();
+        let reset = Button::new(data, format!("Reset to {default}")).on_click(
+            move |ctx, data, _env| {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ResetSettingsFile {
+                        kind: reset_kind.clone(),
+                        key: reset_key.clone(),
+                    },
+                    Target::Widget(data.id),
+                ));
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ResetSettingsItem,
+                    Target::Widget(widget_id),
+                ));
+            },
+        );
+
+        let mut info = SettingsItemInfo {
+            widget_id,
             width: 0.0,
             padding: 10.0,
             key,
             kind,
             desc,
+            kind_in_name: false,
+            default,
+            modified: false,
+            reset: WidgetPod::new(reset.boxed()),
             name_text: None,
             desc_text: None,
             last_idle_timer: TimerToken::INVALID,
-        }
+        };
+        info.check_modified(data);
+        info
+    }
+
+    fn with_kind_in_name(mut self) -> Self {
+        self.kind_in_name = true;
+        self
     }
 
     /// Check if the last-idle-timer has been triggered, and thus it should probably update
@@ -702,6 +908,27 @@ fn idle_timer_triggered(&self, token: TimerToken) -> bool {
         token == self.last_idle_timer
     }
 
+    /// Check whether the current value of the setting is different from its
+    /// default, returning whether that changed
+    fn check_modified(&mut self, data: &LapceTabData) -> bool {
+        let modified = data
+            .config
+            .setting_value(&self.kind, &self.key)
+            .map(|value| setting_is_modified(&value, &self.default))
+            .unwrap_or(false);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    /// The default value as it's written in the input of the setting
+    fn default_text(&self) -> String {
+        match &self.default {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        }
+    }
+
     fn clear_text_layout_cache(&mut self) {
         self.name_text = None;
         self.desc_text = None;
@@ -715,11 +942,16 @@ pub fn name(
         data: &LapceTabData,
     ) -> &PietTextLayout {
         if self.name_text.is_none() {
-            let mut splits = self.key.rsplitn(2, '.');
+            let key = if self.kind_in_name {
+                format!("{}.{}", self.kind, self.key)
+            } else {
+                self.key.clone()
+            };
+            let mut splits = key.rsplitn(2, '.');
             let mut name_text = String::new();
 
             if let Some(name) = splits.next() {
-                name_text.reserve(self.key.len());
+                name_text.reserve(key.len());
                 if let Some(title) = splits.next() {
                     for (i, part) in title.split('.').enumerate() {
                         if i > 0 {
@@ -803,16 +1035,43 @@ fn update_settings(
         ));
     }
 
+    /// Pass the event to the reset button when it's shown
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        if self.modified {
+            self.reset.event(ctx, event, data, env);
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.reset.lifecycle(ctx, event, data, env);
+    }
+
     fn update(
         &mut self,
-        _ctx: &mut UpdateCtx,
+        ctx: &mut UpdateCtx,
         old_data: &LapceTabData,
         data: &LapceTabData,
-        _env: &Env,
+        env: &Env,
     ) {
         if data.config.id != old_data.config.id {
             self.clear_text_layout_cache();
+            if self.check_modified(data) {
+                ctx.request_layout();
+            }
         }
+        self.reset.update(ctx, data, env);
     }
 
     fn layout(
@@ -820,7 +1079,7 @@ fn layout(
         ctx: &mut LayoutCtx,
         bc: &BoxConstraints,
         data: &LapceTabData,
-        _env: &Env,
+        env: &Env,
         extra_width: f64,
     ) -> Size {
         let width = bc.max().width;
@@ -834,17 +1093,36 @@ fn layout(
         let mut height = name.height + desc.height + (self.padding * 3.0);
         height = height.round();
 
+        if self.modified {
+            let reset_size = self.reset.layout(
+                ctx,
+                &BoxConstraints::new(Size::ZERO, Size::new(300.0, name.height)),
+                data,
+                env,
+            );
+            self.reset.set_origin(
+                ctx,
+                data,
+                env,
+                Point::new(
+                    name.width + self.padding,
+                    self.padding + (name.height - reset_size.height) / 2.0,
+                ),
+            );
+        }
+
         Size::new(self.width, height)
     }
 
-    /// Paint the name of the setting and the description  
+    /// Paint the name of the setting and the description, with the marker and
+    /// the reset button when the setting is modified  
     /// `extra_width` decides how the description should be shifted to the right  
     /// Returns the y position of the description, so that you can relative to it.
     fn paint(
         &mut self,
         ctx: &mut PaintCtx,
         data: &LapceTabData,
-        _env: &Env,
+        env: &Env,
         extra_width: f64,
     ) -> f64 {
         let mut y = 0.0;
@@ -876,6 +1154,17 @@ fn paint(
         let desc = self.desc(text, data, extra_width);
         ctx.draw_text(desc, Point::new(extra_width, y));
 
+        if self.modified {
+            let x = 1.0 - padding;
+            ctx.stroke(
+                Line::new(Point::new(x, 0.0), Point::new(x, ctx.size().height)),
+                data.config
+                    .get_color_unchecked(LapceTheme::SOURCE_CONTROL_MODIFIED),
+                2.0,
+            );
+            self.reset.paint(ctx, data, env);
+        }
+
         desc_y
     }
 }
@@ -886,32 +1175,36 @@ struct EmptySettingsItem {
     info: SettingsItemInfo,
 }
 impl EmptySettingsItem {
-    fn new(key: String, kind: String, desc: String) -> Self {
-        EmptySettingsItem {
-            info: SettingsItemInfo::new(key, kind, desc),
-        }
+    fn new(info: SettingsItemInfo) -> Self {
+        EmptySettingsItem { info }
     }
 }
 impl Widget<LapceTabData> for EmptySettingsItem {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.info.widget_id)
+    }
+
     fn event(
         &mut self,
-        _ctx: &mut EventCtx,
-        _event: &Event,
-        _data: &mut LapceTabData,
-        _env: &Env,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
     ) {
+        self.info.event(ctx, event, data, env);
     }
 
     fn lifecycle(
         &mut self,
         ctx: &mut LifeCycleCtx,
         event: &LifeCycle,
-        _data: &LapceTabData,
-        _env: &Env,
+        data: &LapceTabData,
+        env: &Env,
     ) {
         if let LifeCycle::HotChanged(_) = event {
             ctx.request_paint();
         }
+        self.info.lifecycle(ctx, event, data, env);
     }
 
     fn update(
@@ -946,23 +1239,29 @@ struct CheckBoxSettingsItem {
 }
 
 impl CheckBoxSettingsItem {
-    fn new(key: String, kind: String, desc: String, checked: bool) -> Self {
+    fn new(info: SettingsItemInfo, checked: bool) -> Self {
         Self {
             checked,
             checkbox_width: 20.0,
-            info: SettingsItemInfo::new(key, kind, desc),
+            info,
         }
     }
 }
 
 impl Widget<LapceTabData> for CheckBoxSettingsItem {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.info.widget_id)
+    }
+
     fn event(
         &mut self,
         ctx: &mut EventCtx,
         event: &Event,
         data: &mut LapceTabData,
-        _env: &Env,
+        env: &Env,
     ) {
+        self.info.event(ctx, event, data, env);
+
         match event {
             Event::MouseMove(_) => ctx.set_handled(),
             Event::MouseDown(mouse_event) => {
@@ -988,6 +1287,15 @@ fn event(
                     serde_json::Value::Bool(self.checked),
                 );
             }
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::ResetSettingsItem = command {
+                    ctx.set_handled();
+                    self.checked = self.info.default.as_bool().unwrap_or(false);
+                    self.info.last_idle_timer = TimerToken::INVALID;
+                    ctx.request_paint();
+                }
+            }
             _ => {}
         }
     }
@@ -996,12 +1304,13 @@ fn lifecycle(
         &mut self,
         ctx: &mut LifeCycleCtx,
         event: &LifeCycle,
-        _data: &LapceTabData,
-        _env: &Env,
+        data: &LapceTabData,
+        env: &Env,
     ) {
         if let LifeCycle::HotChanged(_) = event {
             ctx.request_paint();
         }
+        self.info.lifecycle(ctx, event, data, env);
     }
 
     fn update(
@@ -1053,7 +1362,6 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
 }
 
 struct DropdownSettingsItem {
-    widget_id: WidgetId,
     dropdown: WidgetPod<DropdownData<String, ()>, DropdownSelector<String, ()>>,
 
     // We could have map of the key to a function to get the current items and selected item?
@@ -1062,33 +1370,24 @@ struct DropdownSettingsItem {
 impl DropdownSettingsItem {
     fn new(
         data: &mut LapceTabData,
-        key: String,
-        kind: String,
-        desc: String,
-        info: DropdownInfo,
+        info: SettingsItemInfo,
+        dropdown_info: DropdownInfo,
     ) -> Self {
-        let widget_id = WidgetId::next();
-
         let dropdown = WidgetPod::new(DropdownSelector::default());
 
         // Create our data and insert it into the settings data structure so we can access it later
         let mut dropdown_data =
-            DropdownData::new(data.config.clone(), widget_id, ());
-        dropdown_data.update_from_info(info);
+            DropdownData::new(data.config.clone(), info.widget_id, ());
+        dropdown_data.update_from_info(dropdown_info);
 
         let settings = Arc::make_mut(&mut data.settings);
         settings
             .dropdown_data
-            .entry(kind.clone())
+            .entry(info.kind.clone())
             .or_insert_with(im::HashMap::new)
-            .insert(key.clone(), dropdown_data);
+            .insert(info.key.clone(), dropdown_data);
 
-        Self {
-            widget_id,
-            dropdown,
-
-            info: SettingsItemInfo::new(key, kind, desc),
-        }
+        Self { dropdown, info }
     }
 
     /// Get the `DropdownData` for this widget, cloning it with the curent config
@@ -1104,7 +1403,7 @@ fn get_data_clone(&self, data: &LapceTabData) -> DropdownData<String, ()> {
 }
 impl Widget<LapceTabData> for DropdownSettingsItem {
     fn id(&self) -> Option<WidgetId> {
-        Some(self.widget_id)
+        Some(self.info.widget_id)
     }
 
     fn event(
@@ -1114,21 +1413,31 @@ fn event(
         data: &mut LapceTabData,
         env: &Env,
     ) {
+        self.info.event(ctx, event, data, env);
+
+        let mut reset = false;
         match event {
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
-                if let LapceUICommand::DropdownItemSelected = command {
-                    // We just update the settings whenever you select an item in the dropdown
-                    // since it is unlikely that you're changing it fast enough to to be an
-                    // issue
-                    let dropdown_data = self.get_data_clone(data);
-                    if let Some(value) = dropdown_data.get_active_item() {
-                        self.info.update_settings(
-                            data,
-                            ctx,
-                            serde_json::json!(value),
-                        );
+                match command {
+                    LapceUICommand::DropdownItemSelected => {
+                        // We just update the settings whenever you select an item
+                        // in the dropdown since it is unlikely that you're
+                        // changing it fast enough to to be an issue
+                        let dropdown_data = self.get_data_clone(data);
+                        if let Some(value) = dropdown_data.get_active_item() {
+                            self.info.update_settings(
+                                data,
+                                ctx,
+                                serde_json::json!(value),
+                            );
+                        }
                     }
+                    LapceUICommand::ResetSettingsItem => {
+                        ctx.set_handled();
+                        reset = true;
+                    }
+                    _ => {}
                 }
             }
             _ => {}
@@ -1142,6 +1451,13 @@ fn event(
             .get_mut(&self.info.key)
             .unwrap();
         dropdown_data.update_data(data.config.clone());
+        if reset {
+            let default = self.info.default_text();
+            if let Some(index) = dropdown_data.list.items.index_of(&default) {
+                dropdown_data.active_item_index = index;
+                dropdown_data.list.selected_index = index;
+            }
+        }
 
         self.dropdown.event(ctx, event, dropdown_data, env);
     }
@@ -1155,6 +1471,7 @@ fn lifecycle(
     ) {
         let dropdown = self.get_data_clone(data);
         self.dropdown.lifecycle(ctx, event, &dropdown, env);
+        self.info.lifecycle(ctx, event, data, env);
     }
 
     fn update(
@@ -1211,14 +1528,12 @@ struct InputSettingsItem {
 impl InputSettingsItem {
     fn new(
         data: &mut LapceTabData,
-        kind: String,
-        key: String,
-        desc: String,
+        info: SettingsItemInfo,
         event_sink: ExtEventSink,
         input: String,
         value_kind: SettingsValueKind,
     ) -> Self {
-        let name = format!("{kind}.{key}");
+        let name = format!("{}.{}", info.kind, info.key);
         let content = BufferContent::SettingsValue(name.clone());
 
         let mut doc =
@@ -1237,12 +1552,16 @@ fn new(
             view_id,
             input: WidgetPod::new(input),
             value_kind,
-            info: SettingsItemInfo::new(key, kind, desc),
+            info,
         }
     }
 }
 
 impl Widget<LapceTabData> for InputSettingsItem {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.info.widget_id)
+    }
+
     fn event(
         &mut self,
         ctx: &mut EventCtx,
@@ -1250,6 +1569,8 @@ fn event(
         data: &mut LapceTabData,
         env: &Env,
     ) {
+        self.info.event(ctx, event, data, env);
+
         // Don't alert the input to mouse-wheel events
         if !matches!(event, Event::Wheel(_)) {
             self.input.event(ctx, event, data, env);
@@ -1286,6 +1607,18 @@ fn event(
                     log::warn!("Setting Input editor view id referred to editor with non-settings-value BufferContent");
                 }
             }
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::ResetSettingsItem = command {
+                    ctx.set_handled();
+                    let name = format!("{}.{}", self.info.kind, self.info.key);
+                    if let Some(doc) = data.main_split.value_docs.get_mut(&name) {
+                        let doc = Arc::make_mut(doc);
+                        doc.reload(Rope::from(self.info.default_text()), true);
+                    }
+                    self.info.last_idle_timer = TimerToken::INVALID;
+                }
+            }
             _ => {}
         }
     }
@@ -1302,6 +1635,7 @@ fn lifecycle(
         }
 
         self.input.lifecycle(ctx, event, data, env);
+        self.info.lifecycle(ctx, event, data, env);
     }
 
     fn update(
@@ -2004,6 +2338,18 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
     }
 }
 
+/// The pages of the settings before the ones of the plugins, in the order
+/// they're listed in
+const SETTINGS_KINDS: [LapceSettingsKind; 7] = [
+    LapceSettingsKind::Core,
+    LapceSettingsKind::UI,
+    LapceSettingsKind::Editor,
+    LapceSettingsKind::Terminal,
+    LapceSettingsKind::Workbench,
+    LapceSettingsKind::Theme,
+    LapceSettingsKind::Keymap,
+];
+
 struct SettingsSwitcher {
     settings_widget_id: WidgetId,
     plugin_settings_expanded: bool,
@@ -2028,7 +2374,12 @@ fn new(settings_widget_id: WidgetId) -> Self {
     }
 
     fn num_items(&self, data: &LapceTabData) -> usize {
-        let mut n = if self.plugin_settings_disabled { 6 } else { 7 };
+        let kinds = SETTINGS_KINDS.len();
+        let mut n = if self.plugin_settings_disabled {
+            kinds
+        } else {
+            kinds + 1
+        };
         if self.plugin_settings_expanded {
             n += data
                 .plugin
@@ -2053,22 +2404,13 @@ pub fn set_active(&mut self, active: LapceSettingsKind, data: &LapceTabData) {
                 .enumerate()
             {
                 if active_volt_id == volt_id {
-                    self.active_index = Some(i + 6);
+                    self.active_index = Some(i + SETTINGS_KINDS.len());
                     return;
                 }
             }
         }
 
-        let kinds = [
-            LapceSettingsKind::Core,
-            LapceSettingsKind::UI,
-            LapceSettingsKind::Editor,
-            LapceSettingsKind::Terminal,
-            LapceSettingsKind::Theme,
-            LapceSettingsKind::Keymap,
-        ];
-
-        for (i, kind) in kinds.iter().enumerate() {
+        for (i, kind) in SETTINGS_KINDS.iter().enumerate() {
             if kind == &self.active {
                 self.active_index = Some(i);
                 return;
@@ -2097,20 +2439,21 @@ fn event(
                 if let Some(last_index) = self.last_mouse_down.take() {
                     let index = (mouse_event.pos.y / self.line_height) as usize;
                     if index < self.num_items(data) && index == last_index {
+                        let kinds = SETTINGS_KINDS.len();
                         match index {
-                            6 => {
+                            _ if index == kinds => {
                                 self.plugin_settings_expanded =
                                     !self.plugin_settings_expanded;
                                 ctx.request_layout();
                             }
-                            _ if index > 6 => {
+                            _ if index > kinds => {
                                 if let Some((volt_id, _)) = data
                                     .plugin
                                     .installed
                                     .iter()
                                     .filter(|(_, v)| v.config.is_some())
                                     .sorted_by_key(|(_, v)| &v.display_name)
-                                    .nth(index - 7)
+                                    .nth(index - kinds - 1)
                                 {
                                     ctx.submit_command(Command::new(
                                         LAPCE_UI_COMMAND,
@@ -2124,16 +2467,7 @@ fn event(
                                 }
                             }
                             _ => {
-                                if let Some(kind) = [
-                                    LapceSettingsKind::Core,
-                                    LapceSettingsKind::UI,
-                                    LapceSettingsKind::Editor,
-                                    LapceSettingsKind::Terminal,
-                                    LapceSettingsKind::Theme,
-                                    LapceSettingsKind::Keymap,
-                                ]
-                                .get(index)
-                                {
+                                if let Some(kind) = SETTINGS_KINDS.get(index) {
                                     ctx.submit_command(Command::new(
                                         LAPCE_UI_COMMAND,
                                         LapceUICommand::ShowSettingsKind(
@@ -2199,6 +2533,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
             "UI Settings",
             "Editor Settings",
             "Terminal Settings",
+            "Workbench Settings",
             "Theme Settings",
             "Keybindings",
             "Plugin Settings",
@@ -2218,8 +2553,9 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
             self.plugin_settings_disabled = false;
         }
 
+        let kinds = SETTINGS_KINDS.len();
         for (i, text) in settings_sections.iter().enumerate() {
-            let font_size = if i <= 6 {
+            let font_size = if i <= kinds {
                 data.config.ui.font_size() + 1
             } else {
                 data.config.ui.font_size()
@@ -2242,7 +2578,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 .build()
                 .unwrap();
 
-            let x = if i <= 6 { 20.0 } else { 40.0 };
+            let x = if i <= kinds { 20.0 } else { 40.0 };
             ctx.draw_text(
                 &text_layout,
                 Point::new(
@@ -2255,8 +2591,8 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
 
         let x = 2.0;
         let active = self.active_index.unwrap_or(0);
-        let active = if active > 5 { active + 1 } else { active };
-        if (active <= 6 || self.plugin_settings_expanded)
+        let active = if active >= kinds { active + 1 } else { active };
+        if (active <= kinds || self.plugin_settings_expanded)
             && !self.plugin_settings_disabled
         {
             let y0 = self.line_height * active as f64;
@@ -82,7 +82,8 @@ fn update_signature(&mut self, ctx: &mut EventCtx, data: &mut LapceTabData) {
 
             parse_signature(
                 signature,
-                data.signature.active_parameter,
+                data.signature.current_parameter(),
+                data.signature.overload_position(),
                 language,
                 &data.config,
             )
@@ -193,6 +194,7 @@ fn update(
                 || old_signature.status != data.signature.status
                 || old_signature.signatures != signature.signatures
                 || old_signature.current_signature != signature.current_signature
+                || old_signature.active_parameter != signature.active_parameter
             {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -445,6 +447,7 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
 fn parse_signature(
     sig: &SignatureInformation,
     active_parameter: Option<usize>,
+    overload: Option<(usize, usize)>,
     language: Option<LapceLanguage>,
     config: &LapceConfig,
 ) -> (RichText, Option<Vec<Content>>, Option<Vec<Content>>) {
@@ -523,6 +526,13 @@ fn parse_signature(
 
         // TODO: make this a code block of the current language
 
+        // Show which of the overloads is displayed, they can be cycled through
+        if let Some((current, count)) = overload {
+            builder.push(&format!("  ({current}/{count})")).text_color(
+                config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone(),
+            );
+        }
+
         (builder.build(), param_doc)
     };
 
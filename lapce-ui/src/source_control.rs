@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use druid::{
     kurbo::BezPath,
@@ -7,6 +7,7 @@
     LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point, Rect, RenderContext,
     Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId,
 };
+use lapce_core::command::SourceControlCommand;
 use lapce_data::{
     command::{
         CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
@@ -15,8 +16,9 @@
     config::{LapceIcons, LapceTheme},
     data::{FocusArea, LapceData, LapceTabData},
     panel::PanelKind,
+    source_control::SourceControlItem,
 };
-use lapce_rpc::source_control::FileDiff;
+use lapce_rpc::source_control::{DiffHunk, FileDiff};
 
 use crate::{
     button::Button,
@@ -89,6 +91,8 @@ struct SourceControlFileList {
     mouse_down: Option<usize>,
     current_line: Option<usize>,
     line_rects: Vec<Rect>,
+    /// The expand chevron of a file or the stage/unstage link of a hunk
+    action_rects: Vec<(usize, Rect)>,
     line_height: f64,
 }
 
@@ -100,6 +104,7 @@ pub fn new(widget_id: WidgetId) -> Self {
             mouse_down: None,
             current_line: None,
             line_rects: vec![],
+            action_rects: vec![],
             line_height: 25.0,
         }
     }
@@ -120,6 +125,28 @@ fn icon_hit_test(&self, mouse_event: &MouseEvent) -> Option<usize> {
         }
         None
     }
+
+    fn action_hit_test(&self, line: usize, mouse_event: &MouseEvent) -> bool {
+        self.action_rects
+            .iter()
+            .any(|(l, rect)| *l == line && rect.contains(mouse_event.pos))
+    }
+
+    fn hunk_command(path: &Path, hunk: &DiffHunk, target: Target) -> Command {
+        let cmd = if hunk.staged {
+            SourceControlCommand::UnstageHunk
+        } else {
+            SourceControlCommand::StageHunk
+        };
+        Command::new(
+            LAPCE_COMMAND,
+            LapceCommand {
+                kind: CommandKind::SourceControl(cmd),
+                data: Some(serde_json::json!((path, hunk))),
+            },
+            target,
+        )
+    }
 }
 
 impl Widget<LapceTabData> for SourceControlFileList {
@@ -153,17 +180,17 @@ fn event(
                 let y = mouse_event.pos.y;
                 if y > 0.0 {
                     let line = (y / self.line_height).floor() as usize;
-                    if line < data.source_control.file_diffs.len()
-                        && mouse_event.pos.x < self.line_height
+                    if let Some(SourceControlItem::File(i)) =
+                        data.source_control.items().get(line).copied()
                     {
-                        if let Some(mouse_down) = self.mouse_down {
-                            if mouse_down == line {
-                                let source_control =
-                                    Arc::make_mut(&mut data.source_control);
-                                source_control.file_diffs[line].1 =
-                                    !source_control.file_diffs[line].1;
-                                ctx.request_paint();
-                            }
+                        if mouse_event.pos.x < self.line_height
+                            && self.mouse_down == Some(line)
+                        {
+                            let source_control =
+                                Arc::make_mut(&mut data.source_control);
+                            source_control.file_diffs[i].1 =
+                                !source_control.file_diffs[i].1;
+                            ctx.request_paint();
                         }
                     }
                 }
@@ -183,24 +210,47 @@ fn event(
                         self.mouse_down = None;
                         let source_control = Arc::make_mut(&mut data.source_control);
 
-                        if target_line < source_control.file_diffs.len() {
+                        if let Some(item) =
+                            source_control.items().get(target_line).copied()
+                        {
                             source_control.file_list_index = target_line;
-                            if mouse_event.pos.x < self.line_height {
-                                self.mouse_down = Some(target_line);
-                            } else {
-                                ctx.submit_command(Command::new(
-                                    LAPCE_UI_COMMAND,
-                                    LapceUICommand::OpenFileDiff {
-                                        path: source_control
-                                            .file_diffs
-                                            .get_index(target_line)
-                                            .unwrap()
-                                            .0
-                                            .clone(),
-                                        history: "head".to_string(),
-                                    },
-                                    Target::Widget(data.id),
-                                ));
+                            let on_action =
+                                self.action_hit_test(target_line, mouse_event);
+                            match item {
+                                SourceControlItem::File(_)
+                                    if mouse_event.pos.x < self.line_height =>
+                                {
+                                    self.mouse_down = Some(target_line);
+                                }
+                                SourceControlItem::File(_) if on_action => {
+                                    let path = source_control
+                                        .item_path(item)
+                                        .unwrap()
+                                        .clone();
+                                    source_control.toggle_expanded(&path);
+                                }
+                                SourceControlItem::Hunk(_, _) if on_action => {
+                                    let (path, hunk) =
+                                        source_control.item_hunk(item).unwrap();
+                                    ctx.submit_command(Self::hunk_command(
+                                        path,
+                                        hunk,
+                                        Target::Widget(data.id),
+                                    ));
+                                }
+                                _ => {
+                                    ctx.submit_command(Command::new(
+                                        LAPCE_UI_COMMAND,
+                                        LapceUICommand::OpenFileDiff {
+                                            path: source_control
+                                                .item_path(item)
+                                                .unwrap()
+                                                .clone(),
+                                            history: "head".to_string(),
+                                        },
+                                        Target::Widget(data.id),
+                                    ));
+                                }
                             }
                         }
 
@@ -209,13 +259,35 @@ fn event(
                     }
                     MouseButton::Right => {
                         let source_control = data.source_control.clone();
+                        let target_item =
+                            match source_control.items().get(target_line).copied() {
+                                Some(item) => item,
+                                None => return,
+                            };
                         let (target_file_path, target_file_diff) = source_control
-                            .file_diffs
-                            .get_index(target_line)
-                            .map(|(path, (diff, _))| (path.clone(), diff.clone()))
+                            .item_path(target_item)
+                            .and_then(|path| {
+                                let (diff, _) =
+                                    source_control.file_diffs.get(path)?;
+                                Some((path.clone(), diff.clone()))
+                            })
                             .unwrap();
 
                         let mut menu = druid::Menu::<LapceData>::new("");
+                        if let Some((path, hunk)) =
+                            source_control.item_hunk(target_item)
+                        {
+                            let label = if hunk.staged {
+                                "Unstage Hunk"
+                            } else {
+                                "Stage Hunk"
+                            };
+                            menu = menu
+                                .entry(druid::MenuItem::new(label).command(
+                                    Self::hunk_command(path, hunk, Target::Auto),
+                                ))
+                                .separator();
+                        }
                         let mut item = druid::MenuItem::new("Open Changes").command(
                             Command::new(
                                 LAPCE_UI_COMMAND,
@@ -314,8 +386,7 @@ fn update(
         data: &LapceTabData,
         _env: &Env,
     ) {
-        if data.source_control.file_diffs.len()
-            != old_data.source_control.file_diffs.len()
+        if data.source_control.items().len() != old_data.source_control.items().len()
         {
             ctx.request_layout();
         }
@@ -328,7 +399,7 @@ fn layout(
         data: &LapceTabData,
         _env: &Env,
     ) -> Size {
-        let height = self.line_height * data.source_control.file_diffs.len() as f64;
+        let height = self.line_height * data.source_control.items().len() as f64;
         Size::new(bc.max().width, height)
     }
 
@@ -352,11 +423,14 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
         let rect = ctx.region().bounding_box();
         let start_line = (rect.y0 / self.line_height).floor() as usize;
         let end_line = (rect.y1 / self.line_height).ceil() as usize;
+        let items = data.source_control.items();
         self.line_rects = vec![];
+        self.action_rects = vec![];
         for line in start_line..end_line {
-            if line >= data.source_control.file_diffs.len() {
-                break;
-            }
+            let item = match items.get(line) {
+                Some(item) => *item,
+                None => break,
+            };
             let y = self.line_height * line as f64;
 
             let current_line = Size::new(ctx.size().width, self.line_height)
@@ -374,18 +448,23 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 }
             }
 
+            let i = match item {
+                SourceControlItem::File(i) => i,
+                SourceControlItem::Hunk(_, _) => {
+                    let (_, hunk) = data.source_control.item_hunk(item).unwrap();
+                    self.paint_hunk(ctx, data, hunk, line);
+                    continue;
+                }
+            };
             let (mut path, (diff, checked)) = data
                 .source_control
                 .file_diffs
-                .get_index(line)
+                .get_index(i)
                 .map(|d| (d.0.clone(), d.1))
                 .unwrap();
-            if let Some(workspace_path) = data.workspace.path.as_ref() {
-                path = path
-                    .strip_prefix(workspace_path)
-                    .unwrap_or(&path)
-                    .to_path_buf();
-            }
+            let has_hunks = !data.source_control.file_hunks(&path).is_empty();
+            let expanded = data.source_control.expanded_files.contains(&path);
+            path = data.workspace.display_path(&path);
 
             {
                 let width = 13.0;
@@ -518,6 +597,88 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                             + (self.line_height - svg_size) / 2.0,
                     ));
             ctx.draw_svg(&svg, rect, Some(color));
+
+            if has_hunks {
+                let svg = data.config.ui_svg(if expanded {
+                    LapceIcons::ITEM_OPENED
+                } else {
+                    LapceIcons::ITEM_CLOSED
+                });
+                let rect =
+                    rect.with_origin(Point::new(rect.x0 - svg_size - 10.0, rect.y0));
+                ctx.draw_svg(
+                    &svg,
+                    rect,
+                    Some(
+                        data.config
+                            .get_color_unchecked(LapceTheme::LAPCE_ICON_ACTIVE),
+                    ),
+                );
+                self.action_rects.push((line, rect.inflate(5.0, 5.0)));
+            }
         }
     }
 }
+
+impl SourceControlFileList {
+    fn paint_hunk(
+        &mut self,
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        hunk: &DiffHunk,
+        line: usize,
+    ) {
+        let y = self.line_height * line as f64;
+        let font_size = data.config.ui.font_size() as f64;
+
+        let header = format!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old.start + 1,
+            hunk.old.len(),
+            hunk.new.start + 1,
+            hunk.new.len()
+        );
+        let text_layout = ctx
+            .text()
+            .new_text_layout(header)
+            .font(data.config.ui.font_family(), font_size)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                self.line_height * 2.0,
+                y + text_layout.y_offset(self.line_height),
+            ),
+        );
+
+        let label = if hunk.staged { "Unstage" } else { "Stage" };
+        let text_layout = ctx
+            .text()
+            .new_text_layout(label)
+            .font(data.config.ui.font_family(), font_size)
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_LINK)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let x = ctx.size().width - text_layout.size().width - 10.0;
+        ctx.draw_text(
+            &text_layout,
+            Point::new(x, y + text_layout.y_offset(self.line_height)),
+        );
+        self.action_rects.push((
+            line,
+            Size::new(text_layout.size().width, self.line_height)
+                .to_rect()
+                .with_origin(Point::new(x, y)),
+        ));
+    }
+}
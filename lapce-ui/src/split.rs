@@ -13,10 +13,13 @@
         LAPCE_COMMAND, LAPCE_UI_COMMAND,
     },
     config::{LapceConfig, LapceTheme},
-    data::{FocusArea, LapceEditorData, LapceTabData, SplitContent, SplitData},
+    data::{
+        remove_split_child, FocusArea, LapceEditorData, LapceTabData, SplitContent,
+        SplitData,
+    },
     keypress::{Alignment, DefaultKeyPressHandler, KeyMap},
     panel::PanelKind,
-    split::{SplitDirection, SplitMoveDirection},
+    split::{SplitCommand, SplitDirection, SplitMoveDirection},
     terminal::LapceTerminalData,
 };
 use lapce_rpc::terminal::TermId;
@@ -37,9 +40,9 @@ struct LapceDynamicSplit {
 pub fn split_data_widget(split_data: &SplitData, data: &LapceTabData) -> LapceSplit {
     let mut split =
         LapceSplit::new(split_data.widget_id).direction(split_data.direction);
-    for child in split_data.children.iter() {
+    for (i, child) in split_data.children.iter().enumerate() {
         let child = split_content_widget(child, data);
-        split = split.with_flex_child(child, None, 1.0, true);
+        split = split.with_flex_child(child, None, split_data.ratio(i), true);
     }
     split
 }
@@ -63,11 +66,11 @@ pub fn split_content_widget(
             let split_data = data.main_split.splits.get(widget_id).unwrap();
             let mut split =
                 LapceSplit::new(*widget_id).direction(split_data.direction);
-            for content in split_data.children.iter() {
+            for (i, content) in split_data.children.iter().enumerate() {
                 split = split.with_flex_child(
                     split_content_widget(content, data),
                     None,
-                    1.0,
+                    split_data.ratio(i),
                     true,
                 );
             }
@@ -541,6 +544,47 @@ fn shift_start_of_child(&mut self, i: usize, start: f64, allow_shifting: bool) {
         //  Probably just when we reset the bar_hovered
     }
 
+    fn split_resize(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &LapceTabData,
+        content: &SplitContent,
+        command: SplitCommand,
+    ) {
+        let index = match data
+            .main_split
+            .splits
+            .get(&self.split_id)
+            .and_then(|split| split.children.iter().position(|c| c == content))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let params: Vec<f64> =
+            self.children.iter().map(|child| child.params).collect();
+        let ratios = command.apply(&params, index);
+        for (child, ratio) in self.children.iter_mut().zip(ratios) {
+            child.params = ratio;
+            child.update_resize_pos = true;
+        }
+        ctx.request_layout();
+    }
+
+    /// Write the shares of the children back to the split data, so they
+    /// are saved with the workspace
+    fn save_ratios(&self, data: &mut LapceTabData) {
+        if let Some(split_data) = data.main_split.splits.get_mut(&self.split_id) {
+            let total: f64 = self.children.iter().map(|child| child.params).sum();
+            if total > 0.0 {
+                Arc::make_mut(split_data).ratios = self
+                    .children
+                    .iter()
+                    .map(|child| child.params / total)
+                    .collect();
+            }
+        }
+    }
+
     fn has_non_flex_children(&self) -> bool {
         self.children.iter().any(|ch| !ch.flex)
     }
@@ -725,46 +769,107 @@ pub fn split_editor_move(
         }
     }
 
+    /// Split a new terminal off the terminal with the widget id. The command is
+    /// sent to the split of the tab, which passes it on to the split nested in
+    /// it that the terminal is laid out in.
     pub fn split_terminal(
         &mut self,
         ctx: &mut EventCtx,
         data: &mut LapceTabData,
-        _vertical: bool,
+        vertical: bool,
         widget_id: WidgetId,
     ) {
-        let mut index = 0;
-        for (i, child_id) in self.children_ids.iter().enumerate() {
-            if child_id == &widget_id {
-                index = i;
-                break;
-            }
+        let (tab_id, term_id, parent_id) =
+            match data.terminal.locate_terminal(widget_id) {
+                Some(location) => location,
+                None => return,
+            };
+        if parent_id != self.split_id {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::SplitTerminal(vertical, widget_id),
+                Target::Widget(parent_id),
+            ));
+            return;
         }
+        let index = match self.children_ids.iter().position(|id| id == &widget_id) {
+            Some(index) => index,
+            None => return,
+        };
 
         let terminal_data = Arc::new(LapceTerminalData::new(
             data.workspace.clone(),
-            self.split_id,
+            tab_id,
             ctx.get_external_handle(),
             data.proxy.clone(),
             &data.config,
         ));
         let terminal = LapceTerminalView::new(&terminal_data);
-        Arc::make_mut(&mut data.terminal)
-            .active_terminal_split_mut()
-            .unwrap()
+        let terminal_split =
+            match Arc::make_mut(&mut data.terminal).tabs.get_mut(&tab_id) {
+                Some(terminal_split) => terminal_split,
+                None => return,
+            };
+        let direction = if vertical {
+            SplitDirection::Vertical
+        } else {
+            SplitDirection::Horizontal
+        };
+        let split_id = match Arc::make_mut(&mut terminal_split.layout).split(
+            term_id,
+            terminal_data.term_id,
+            direction,
+        ) {
+            Some(split_id) => split_id,
+            None => return,
+        };
+        terminal_split
             .terminals
             .insert(terminal_data.term_id, terminal_data.clone());
 
-        self.insert_flex_child(
-            index + 1,
-            terminal.boxed(),
-            Some(terminal_data.widget_id),
-            1.0,
-            true,
-        );
-        self.even_flex_children();
+        if split_id == self.split_id {
+            self.direction = direction;
+            self.insert_flex_child(
+                index + 1,
+                terminal.boxed(),
+                Some(terminal_data.widget_id),
+                1.0,
+                true,
+            );
+            self.even_flex_children();
+        } else {
+            // The terminal goes the other way, so it's put in a new split
+            // along with the terminal it was split off
+            let child = self.children.remove(index);
+            self.children_ids.remove(index);
+            let params = child.params;
+            let mut split = LapceSplit::new(split_id).direction(direction);
+            split.children.push(ChildWidget {
+                params: 1.0,
+                update_resize_pos: true,
+                ..child
+            });
+            split.children_ids.push(widget_id);
+            let split = split.with_flex_child(
+                terminal.boxed(),
+                Some(terminal_data.widget_id),
+                1.0,
+                true,
+            );
+            self.insert_flex_child(
+                index,
+                split.boxed(),
+                Some(split_id),
+                params,
+                true,
+            );
+        }
         ctx.children_changed();
     }
 
+    /// Take the widget out of the split when the terminal is closed, which is
+    /// the terminal itself or the split nested in this one that only had the
+    /// terminal, see `TerminalSplitData::close_target`
     pub fn split_terminal_close(
         &mut self,
         ctx: &mut EventCtx,
@@ -772,55 +877,95 @@ pub fn split_terminal_close(
         term_id: TermId,
         widget_id: WidgetId,
     ) {
-        if self.children.is_empty() {
-            return;
+        let index = match self.children_ids.iter().position(|id| id == &widget_id) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let next = Arc::make_mut(&mut data.terminal)
+            .active_terminal_split_mut()
+            .and_then(|terminal_split| terminal_split.remove_terminal(term_id));
+        self.children.remove(index);
+        self.children_ids.remove(index);
+        if let Some(next) = next {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(next),
+            ));
         }
 
-        if self.children.len() == 1 {
-            Arc::make_mut(&mut data.terminal)
-                .active_terminal_split_mut()
-                .unwrap()
-                .terminals
-                .remove(&term_id);
-            self.children.remove(0);
-            self.children_ids.remove(0);
+        self.even_flex_children();
+        ctx.children_changed();
+    }
 
-            self.even_flex_children();
-            ctx.children_changed();
+    /// Focus the terminal next to the one with the widget id, which is found
+    /// the way the editor tab to focus is
+    pub fn split_terminal_move(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        direction: SplitMoveDirection,
+        widget_id: WidgetId,
+    ) {
+        let next = data.terminal.locate_terminal(widget_id).and_then(
+            |(tab_id, term_id, _)| {
+                let terminal_split = data.terminal.tabs.get(&tab_id)?;
+                terminal_split
+                    .neighbour(term_id, direction)
+                    .map(|t| t.widget_id)
+            },
+        );
+        if let Some(next) = next {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(next),
+            ));
+        }
+    }
+
+    /// Swap the terminal with the widget after it in the split it's laid out
+    /// in, which the command is passed on to like [`LapceSplit::split_terminal`]
+    pub fn split_terminal_exchange(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut LapceTabData,
+        widget_id: WidgetId,
+    ) {
+        let (tab_id, term_id, parent_id) =
+            match data.terminal.locate_terminal(widget_id) {
+                Some(location) => location,
+                None => return,
+            };
+        if parent_id != self.split_id {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::SplitTerminalExchange(widget_id),
+                Target::Widget(parent_id),
+            ));
             return;
         }
 
-        let mut index = 0;
-        for (i, child_id) in self.children_ids.iter().enumerate() {
-            if child_id == &widget_id {
-                index = i;
-                break;
+        let index = Arc::make_mut(&mut data.terminal)
+            .tabs
+            .get_mut(&tab_id)
+            .and_then(|terminal_split| {
+                Arc::make_mut(&mut terminal_split.layout).exchange(term_id)
+            })
+            .map(|(_, index)| index);
+        if let Some(index) = index {
+            if index + 1 < self.children.len() {
+                self.children.swap(index, index + 1);
+                self.children_ids.swap(index, index + 1);
+                ctx.request_layout();
             }
         }
-
-        let new_index = if index >= self.children.len() - 1 {
-            index - 1
-        } else {
-            index + 1
-        };
-        let _terminal_id = self.children_ids[index];
-        let new_terminal_id = self.children_ids[new_index];
         ctx.submit_command(Command::new(
             LAPCE_UI_COMMAND,
             LapceUICommand::Focus,
-            Target::Widget(new_terminal_id),
+            Target::Widget(widget_id),
         ));
-
-        Arc::make_mut(&mut data.terminal)
-            .active_terminal_split_mut()
-            .unwrap()
-            .terminals
-            .remove(&term_id);
-        self.children.remove(index);
-        self.children_ids.remove(index);
-
-        self.even_flex_children();
-        ctx.children_changed();
     }
 
     pub fn split_replace(
@@ -878,36 +1023,27 @@ pub fn split_remove(
         data: &mut LapceTabData,
         content: &SplitContent,
     ) {
-        let split_data = data.main_split.splits.get_mut(&self.split_id).unwrap();
-        let split_data = Arc::make_mut(split_data);
-
-        let mut index = 0;
-        for (i, c) in split_data.children.iter().enumerate() {
-            if c == content {
-                index = i;
-                break;
-            }
-        }
+        let removal = match remove_split_child(
+            &mut data.main_split.editor_tabs,
+            &mut data.main_split.splits,
+            *data.main_split.active_tab,
+            self.split_id,
+            content,
+        ) {
+            Some(removal) => removal,
+            None => return,
+        };
 
-        self.children.remove(index);
+        self.children.remove(removal.index);
         ctx.children_changed();
 
-        let removed_child = split_data.children.remove(index);
-        let is_active = match removed_child {
-            SplitContent::EditorTab(tab_id) => {
-                data.main_split.editor_tabs.remove(&tab_id);
-                *data.main_split.active_tab == Some(tab_id)
-            }
-            SplitContent::Split(split_id) => {
-                data.main_split.splits.remove(&split_id);
-                false
-            }
-        };
-
-        let split_data = data.main_split.splits.get_mut(&self.split_id).unwrap();
-        let split_children_len = split_data.children.len();
-        if split_children_len == 0 {
-            if let Some(parent_split) = split_data.parent_split {
+        if removal.emptied {
+            let parent_split = data
+                .main_split
+                .splits
+                .get(&self.split_id)
+                .and_then(|split_data| split_data.parent_split);
+            if let Some(parent_split) = parent_split {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::SplitRemove(SplitContent::Split(self.split_id)),
@@ -922,42 +1058,22 @@ pub fn split_remove(
                 data.main_split.active = Arc::new(None);
                 data.main_split.active_tab = Arc::new(None);
             }
-        } else {
-            if is_active {
-                let new_index = if index > split_children_len - 1 {
-                    split_children_len - 1
-                } else {
-                    index
-                };
-                ctx.submit_command(Command::new(
-                    LAPCE_UI_COMMAND,
-                    LapceUICommand::Focus,
-                    Target::Widget(split_data.children[new_index].widget_id()),
-                ));
-            }
-            if split_children_len == 1 {
-                let split_content = split_data.children[0];
-                if let Some(parent_split_id) = split_data.parent_split {
-                    let parent_split =
-                        data.main_split.splits.get_mut(&parent_split_id).unwrap();
-                    let parent_split = Arc::make_mut(parent_split);
-                    if let Some(index) = parent_split
-                        .children
-                        .iter()
-                        .position(|c| c == &SplitContent::Split(self.split_id))
-                    {
-                        parent_split.children[index] = split_content;
-                        split_content
-                            .set_split_id(&mut data.main_split, parent_split_id);
-                        ctx.submit_command(Command::new(
-                            LAPCE_UI_COMMAND,
-                            LapceUICommand::SplitReplace(index, split_content),
-                            Target::Widget(parent_split_id),
-                        ));
-                        data.main_split.splits.remove(&self.split_id);
-                    }
-                }
-            }
+            return;
+        }
+
+        if let Some(focus) = removal.focus {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::Focus,
+                Target::Widget(focus),
+            ));
+        }
+        if let Some((parent_split_id, index, split_content)) = removal.collapsed {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::SplitReplace(index, split_content),
+                Target::Widget(parent_split_id),
+            ));
         }
     }
 
@@ -1051,6 +1167,7 @@ fn event(
             Event::MouseUp(mouse_event) => {
                 if mouse_event.button.is_left() && ctx.is_active() {
                     ctx.set_active(false);
+                    self.save_ratios(data);
                 }
             }
             Event::MouseDown(mouse_event) => {
@@ -1109,6 +1226,7 @@ fn event(
                                 data.focus_area = FocusArea::Editor;
                             }
                         }
+                        return;
                     }
                     LapceUICommand::SplitAdd(usize, content, focus_new) => {
                         self.split_add(ctx, data, *usize, content, *focus_new);
@@ -1125,6 +1243,9 @@ fn event(
                     LapceUICommand::SplitExchange(content) => {
                         self.split_exchange(ctx, data, content);
                     }
+                    LapceUICommand::SplitResize(content, command) => {
+                        self.split_resize(ctx, data, content, *command);
+                    }
                     LapceUICommand::SplitEditor(vertical, widget_id) => {
                         self.split_editor(ctx, data, *vertical, *widget_id);
                     }
@@ -1143,8 +1264,15 @@ fn event(
                     LapceUICommand::SplitTerminalClose(term_id, widget_id) => {
                         self.split_terminal_close(ctx, data, *term_id, *widget_id);
                     }
-                    _ => (),
+                    LapceUICommand::SplitTerminalMove(direction, widget_id) => {
+                        self.split_terminal_move(ctx, data, *direction, *widget_id);
+                    }
+                    LapceUICommand::SplitTerminalExchange(widget_id) => {
+                        self.split_terminal_exchange(ctx, data, *widget_id);
+                    }
+                    _ => return,
                 }
+                self.save_ratios(data);
                 return;
             }
             _ => (),
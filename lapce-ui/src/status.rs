@@ -216,8 +216,10 @@ fn draw_document_details(
 
         // Language name / change button
 
-        let lang = match data.main_split.content_doc(&editor.content).syntax() {
+        let doc = data.main_split.content_doc(&editor.content);
+        let lang = match doc.syntax() {
             Some(v) => v.language.to_string(),
+            None if doc.large_file() => String::from("Large File"),
             None => String::from("Plain Text"), // TODO: remove after implementing plaintext in lapce-core/src/language.rs
         };
         let (point, text_layout, _) = self.paint_icon_with_label_from_right(
@@ -256,6 +258,50 @@ fn draw_document_details(
             ),
         ));
 
+        // Line ending / change button, with a hint when the file mixes them
+
+        let buffer = doc.buffer();
+        let line_ending = if buffer.mixed_line_endings() {
+            format!("{} (mixed)", buffer.line_ending())
+        } else {
+            buffer.line_ending().to_string()
+        };
+        let (point, text_layout, _) = self.paint_icon_with_label_from_right(
+            rect.x0 - 10.0,
+            size.height,
+            None,
+            line_ending,
+            ctx,
+            &data.config,
+        );
+        let rect = Rect::ZERO
+            .with_origin(Point::new(point.x - 2.0, 0.0))
+            .with_size(Size::new(
+                text_layout.layout.width() as f64 + 4.0,
+                size.height,
+            ));
+        if rect.contains(self.mouse_pos) {
+            ctx.fill(
+                rect,
+                data.config
+                    .get_color_unchecked(LapceTheme::PANEL_CURRENT_BACKGROUND),
+            );
+        }
+        ctx.draw_text(&text_layout, point);
+        self.clickable_items.push((
+            rect,
+            Command::new(
+                LAPCE_COMMAND,
+                LapceCommand {
+                    kind: CommandKind::Workbench(
+                        LapceWorkbenchCommand::ChangeLineEnding,
+                    ),
+                    data: None,
+                },
+                Target::Widget(data.id),
+            ),
+        ));
+
         // Document text/cursor details (line, column, character) / Go to line
 
         let mut string = "".to_string();
@@ -285,7 +331,7 @@ fn draw_document_details(
 
         if !string.is_empty() {
             let (point, text_layout, _) = self.paint_icon_with_label_from_right(
-                rect.x0 - 10.0, // give some space between document details and language name
+                rect.x0 - 10.0, // give some space between document details and line ending
                 size.height,
                 None,
                 string,
@@ -399,6 +445,12 @@ fn update(
         if !old_data.progresses.same(&data.progresses) {
             ctx.request_paint();
         }
+
+        if old_data.keypress.pending_sequence().keys()
+            != data.keypress.pending_sequence().keys()
+        {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -492,6 +544,28 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &druid::Env)
             left += text_size.width + 10.0;
         }
 
+        if let Some(label) = data.keypress.pending_sequence().label() {
+            let text_layout = ctx
+                .text()
+                .new_text_layout(label)
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::STATUS_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(left + 10.0, text_layout.y_offset(size.height)),
+            );
+            left += 10.0 + text_layout.size().width;
+        }
+
         let x = left + 5.0;
         let (new_left, error_svg, (error_point, error_text_layout)) = self
             .paint_icon_with_label(
@@ -1,4 +1,4 @@
-use std::{process, sync::Arc};
+use std::{process, sync::Arc, thread, time::Duration};
 
 use druid::{
     kurbo::Line,
@@ -12,14 +12,16 @@
 use lapce_core::{
     command::FocusCommand,
     cursor::{Cursor, CursorMode},
+    editor::EditType,
     language::LapceLanguage,
     selection::Selection,
 };
 use lapce_data::{
     command::{
         CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
-        LAPCE_COMMAND, LAPCE_OPEN_FILE, LAPCE_OPEN_FOLDER, LAPCE_SAVE_FILE_AS,
-        LAPCE_UI_COMMAND,
+        LAPCE_COMMAND, LAPCE_EXPORT_STATE, LAPCE_IMPORT_KEYMAPS, LAPCE_IMPORT_STATE,
+        LAPCE_IMPORT_STATE_REPLACE, LAPCE_INSTALL_PLUGIN_FROM_FILE, LAPCE_OPEN_FILE,
+        LAPCE_OPEN_FOLDER, LAPCE_SAVE_FILE_AS, LAPCE_UI_COMMAND,
     },
     completion::CompletionStatus,
     config::{LapceConfig, LapceIcons, LapceTheme},
@@ -28,30 +30,37 @@
         LapceTabData, LapceWindowData, LapceWorkspace, LapceWorkspaceType,
         WorkProgress,
     },
-    document::{BufferContent, LocalBufferKind},
+    db::ImportMode,
+    document::{disk_change_alert, BufferContent, LocalBufferKind},
+    document_color::color_presentation_menu,
     editor::EditorLocation,
+    explorer::{permanent_delete_alert, ExplorerClipboard},
     hover::HoverStatus,
-    keypress::{DefaultKeyPressHandler, KeyMap, KeyPressData},
-    menu::MenuKind,
+    keypress::{DefaultKeyPressHandler, KeyPressData},
+    local_history::LOCAL_HISTORY_VERSION,
+    menu::{MenuKind, Mnemonics},
     palette::PaletteStatus,
-    panel::{
-        PanelContainerPosition, PanelKind, PanelPosition, PanelResizePosition,
-        PanelStyle,
+    panel::{PanelContainerPosition, PanelKind, PanelPosition, PanelResizePosition},
+    picker::{FilePickerData, PickerCompletion, PickerKind},
+    plugin::{
+        plugin_install_status::{PluginInstallStatus, PluginInstallType},
+        PluginCommand, PluginData,
     },
-    plugin::plugin_install_status::{PluginInstallStatus, PluginInstallType},
+    problem::{code_action_menu, severity_counts},
     proxy::path_from_url,
     signature::SignatureStatus,
+    title::workspace_title,
 };
-use lapce_rpc::proxy::ProxyResponse;
+use lapce_rpc::{file::FileOperation, proxy::ProxyResponse};
 use lapce_xi_rope::Rope;
-use lsp_types::DiagnosticSeverity;
 
 use crate::{
-    about::AboutBox, alert::AlertBox, completion::CompletionContainer,
-    editor::view::LapceEditorView, explorer::FileExplorer, hover::HoverContainer,
-    message::LapceMessage, panel::PanelContainer, picker::FilePicker,
-    plugin::Plugin, problem::new_problem_panel, scroll::LapceScroll,
-    search::new_search_panel, signature::SignatureContainer,
+    about::AboutBox, alert::AlertBox, color_picker::ColorPicker,
+    completion::CompletionContainer, editor::view::LapceEditorView,
+    explorer::FileExplorer, hover::HoverContainer, message::LapceMessage,
+    panel::PanelContainer, peek::PeekContainer, picker::FilePicker, plugin::Plugin,
+    problem::new_problem_panel, rename_preview::new_rename_preview_panel,
+    scroll::LapceScroll, search::new_search_panel, signature::SignatureContainer,
     source_control::new_source_control_panel, split::split_data_widget,
     status::LapceStatus, terminal::TerminalPanel, title::Title,
 };
@@ -84,6 +93,8 @@ pub struct LapceTab {
     signature: WidgetPod<LapceTabData, SignatureContainer>,
     hover: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     rename: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    color_picker: WidgetPod<LapceTabData, ColorPicker>,
+    peek: WidgetPod<LapceTabData, PeekContainer>,
     status: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     picker: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     about: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
@@ -93,6 +104,8 @@ pub struct LapceTab {
     panel_bottom: WidgetPod<LapceTabData, PanelContainer>,
     panel_right: WidgetPod<LapceTabData, PanelContainer>,
     current_bar_hover: Option<PanelResizePosition>,
+    /// The size of the panel container when its edge started being dragged
+    resize_start_size: f64,
     width: f64,
     height: f64,
     title_height: f64,
@@ -100,14 +113,53 @@ pub struct LapceTab {
     mouse_pos: Point,
 }
 
-fn workspace_title(workspace: &LapceWorkspace) -> Option<String> {
-    let p = workspace.path.as_ref()?;
-    let dir = p.file_name().unwrap_or(p.as_os_str()).to_string_lossy();
-    Some(match &workspace.kind {
-        LapceWorkspaceType::Local => format!("{dir}"),
-        LapceWorkspaceType::RemoteSSH(ssh) => format!("{dir} [{ssh}]"),
-        LapceWorkspaceType::RemoteWSL => format!("{dir} [wsl]"),
-    })
+/// Add the items to the native menu, with the submenus nested in it. The labels
+/// mark the mnemonic of each item with a `&`, which the platform underlines and
+/// activates, and show the key bound to the command of the item. Opening a
+/// submenu, moving through it with the keyboard like
+/// [`lapce_data::menu::MenuNavigation`] and keeping it on the screen is left to
+/// the platform.
+fn build_menu(
+    mut menu: druid::Menu<LapceData>,
+    items: &[MenuKind],
+    data: &LapceTabData,
+) -> druid::Menu<LapceData> {
+    let mnemonics = Mnemonics::new(items);
+    for (index, i) in items.iter().enumerate() {
+        match i {
+            MenuKind::Item(i) => {
+                let mut item = druid::MenuItem::new(mnemonics.label(index));
+                if let Some(key) = data.keypress.command_hotkey(i.command.kind.str())
+                {
+                    item = item.dynamic_hotkey(move |_, _| Some(key.clone()))
+                }
+                item = item
+                    .command(Command::new(
+                        LAPCE_COMMAND,
+                        i.command.clone(),
+                        Target::Widget(data.id),
+                    ))
+                    .enabled(i.enabled);
+                menu = menu.entry(item);
+            }
+            MenuKind::Submenu { items, .. } => {
+                menu = menu.entry(build_menu(
+                    druid::Menu::new(mnemonics.label(index)),
+                    items,
+                    data,
+                ));
+            }
+            MenuKind::Header(_) => {
+                menu = menu.entry(
+                    druid::MenuItem::new(mnemonics.label(index)).enabled(false),
+                );
+            }
+            MenuKind::Separator => {
+                menu = menu.separator();
+            }
+        }
+    }
+    menu
 }
 
 impl LapceTab {
@@ -128,6 +180,7 @@ pub fn new(data: &mut LapceTabData) -> Self {
                 .hide_header()
                 .hide_gutter()
                 .padding((10.0, 5.0, 10.0, 5.0));
+        let peek = PeekContainer::new(&data.peek);
         let status = LapceStatus::new();
         let picker = FilePicker::new(data);
 
@@ -189,6 +242,14 @@ pub fn new(data: &mut LapceTabData) -> Self {
                             WidgetPod::new(new_problem_panel(&data.problem).boxed()),
                         );
                     }
+                    PanelKind::RenamePreview => {
+                        panel.insert_panel(
+                            *kind,
+                            WidgetPod::new(
+                                new_rename_preview_panel(&data.rename).boxed(),
+                            ),
+                        );
+                    }
                 }
             }
         }
@@ -201,6 +262,8 @@ pub fn new(data: &mut LapceTabData) -> Self {
             signature: WidgetPod::new(signature),
             hover: WidgetPod::new(hover.boxed()),
             rename: WidgetPod::new(rename.boxed()),
+            color_picker: WidgetPod::new(ColorPicker::new()),
+            peek: WidgetPod::new(peek),
             picker: WidgetPod::new(picker.boxed()),
             status: WidgetPod::new(status.boxed()),
             about: WidgetPod::new(about.boxed()),
@@ -210,6 +273,7 @@ pub fn new(data: &mut LapceTabData) -> Self {
             panel_right: WidgetPod::new(panel_right),
             panel_bottom: WidgetPod::new(panel_bottom),
             current_bar_hover: None,
+            resize_start_size: 0.0,
             width: 0.0,
             height: 0.0,
             status_height: 0.0,
@@ -224,135 +288,127 @@ fn update_split_point(
         data: &mut LapceTabData,
         mouse_pos: Point,
     ) {
-        if let Some(position) = self.current_bar_hover.as_ref() {
-            match position {
-                PanelResizePosition::Left => {
-                    let maximum = self.width - 100.0 - data.panel.size.right;
-                    Arc::make_mut(&mut data.panel).size.left =
-                        mouse_pos.x.round().max(180.0).min(maximum);
-                    if mouse_pos.x < 90.0 {
-                        if data
-                            .panel
-                            .is_container_shown(&PanelContainerPosition::Left)
-                        {
-                            ctx.submit_command(Command::new(
-                                LAPCE_COMMAND,
-                                LapceCommand {
-                                    kind: CommandKind::Workbench(
-                                        LapceWorkbenchCommand::TogglePanelLeftVisual,
-                                    ),
-                                    data: None,
-                                },
-                                Target::Widget(data.id),
-                            ));
-                        }
-                    } else if !data
-                        .panel
-                        .is_container_shown(&PanelContainerPosition::Left)
-                    {
-                        ctx.submit_command(Command::new(
-                            LAPCE_COMMAND,
-                            LapceCommand {
-                                kind: CommandKind::Workbench(
-                                    LapceWorkbenchCommand::TogglePanelLeftVisual,
-                                ),
-                                data: None,
-                            },
-                            Target::Widget(data.id),
-                        ));
-                    }
+        let (container, size, maximum) = match self.current_bar_hover.as_ref() {
+            Some(PanelResizePosition::Left) => (
+                PanelContainerPosition::Left,
+                mouse_pos.x.round(),
+                self.width - 100.0 - data.panel.size.right,
+            ),
+            Some(PanelResizePosition::Right) => (
+                PanelContainerPosition::Right,
+                self.width - mouse_pos.x.round(),
+                self.width - 100.0 - data.panel.size.left,
+            ),
+            Some(PanelResizePosition::Bottom) => {
+                let header_height = data.config.ui.header_height() as f64;
+                // The maximum position (from the bottom) that the bottom split
+                // is allowed to reach
+                let maximum = self.height
+                    - self.title_height
+                    - self.status_height
+                    - header_height
+                    - 1.0;
+                (
+                    PanelContainerPosition::Bottom,
+                    self.height - mouse_pos.y.round() - self.status_height,
+                    maximum,
+                )
+            }
+            Some(PanelResizePosition::LeftSplit) => {
+                let split = self.panel_left.widget().split_at(
+                    mouse_pos - self.panel_left.layout_rect().origin().to_vec2(),
+                );
+                Arc::make_mut(&mut data.panel)
+                    .size
+                    .set_split(&PanelContainerPosition::Left, split);
+                return;
+            }
+            Some(PanelResizePosition::RightSplit) => {
+                let split = self.panel_right.widget().split_at(
+                    mouse_pos - self.panel_right.layout_rect().origin().to_vec2(),
+                );
+                Arc::make_mut(&mut data.panel)
+                    .size
+                    .set_split(&PanelContainerPosition::Right, split);
+                return;
+            }
+            Some(PanelResizePosition::BottomSplit) => {
+                let split = self.panel_bottom.widget().split_at(
+                    mouse_pos - self.panel_bottom.layout_rect().origin().to_vec2(),
+                );
+                Arc::make_mut(&mut data.panel)
+                    .size
+                    .set_split(&PanelContainerPosition::Bottom, split);
+                return;
+            }
+            None => return,
+        };
+
+        // The container is hidden when it's dragged small enough, and shown
+        // again when it's dragged back
+        let shown = Arc::make_mut(&mut data.panel).drag_container_size(
+            &container,
+            size,
+            self.resize_start_size,
+            maximum,
+        );
+        if shown != data.panel.is_container_shown(&container) {
+            let command = match container {
+                PanelContainerPosition::Left => {
+                    LapceWorkbenchCommand::TogglePanelLeftVisual
                 }
-                PanelResizePosition::Right => {
-                    let maximum = self.width - 100.0 - data.panel.size.left;
-                    let right = self.width - mouse_pos.x.round();
-                    Arc::make_mut(&mut data.panel).size.right =
-                        right.max(180.0).min(maximum);
-                    if right < 90.0 {
-                        if data
-                            .panel
-                            .is_container_shown(&PanelContainerPosition::Right)
-                        {
-                            ctx.submit_command(Command::new(
-                                LAPCE_COMMAND,
-                                LapceCommand {
-                                    kind: CommandKind::Workbench(
-                                        LapceWorkbenchCommand::TogglePanelRightVisual,
-                                    ),
-                                    data: None,
-                                },
-                                Target::Widget(data.id),
-                            ));
-                        }
-                    } else if !data
-                        .panel
-                        .is_container_shown(&PanelContainerPosition::Right)
-                    {
-                        ctx.submit_command(Command::new(
-                            LAPCE_COMMAND,
-                            LapceCommand {
-                                kind: CommandKind::Workbench(
-                                    LapceWorkbenchCommand::TogglePanelRightVisual,
-                                ),
-                                data: None,
-                            },
-                            Target::Widget(data.id),
-                        ));
-                    }
+                PanelContainerPosition::Right => {
+                    LapceWorkbenchCommand::TogglePanelRightVisual
                 }
-                PanelResizePosition::LeftSplit => (),
-                PanelResizePosition::Bottom => {
-                    let bottom =
-                        self.height - mouse_pos.y.round() - self.status_height;
-
-                    let header_height = data.config.ui.header_height() as f64;
-                    // The maximum position (from the bottom) that the bottom split is allowed to reach
-                    let minimum = self.height
-                        - self.title_height
-                        - self.status_height
-                        - header_height
-                        - 1.0;
-
-                    Arc::make_mut(&mut data.panel).size.bottom =
-                        bottom.max(180.0).min(minimum);
-
-                    // Check if it should snap the bottom panel away, if you are too low
-                    if bottom < 90.0 {
-                        if data
-                            .panel
-                            .is_container_shown(&PanelContainerPosition::Bottom)
-                        {
-                            ctx.submit_command(Command::new(
-                                LAPCE_COMMAND,
-                                LapceCommand {
-                                    kind: CommandKind::Workbench(
-                                        LapceWorkbenchCommand::TogglePanelBottomVisual,
-                                    ),
-                                    data: None,
-                                },
-                                Target::Widget(data.id),
-                            ));
-                        }
-                    } else if !data
-                        .panel
-                        .is_container_shown(&PanelContainerPosition::Bottom)
-                    {
-                        ctx.submit_command(Command::new(
-                            LAPCE_COMMAND,
-                            LapceCommand {
-                                kind: CommandKind::Workbench(
-                                    LapceWorkbenchCommand::TogglePanelBottomVisual,
-                                ),
-                                data: None,
-                            },
-                            Target::Widget(data.id),
-                        ));
-                    }
+                PanelContainerPosition::Bottom => {
+                    LapceWorkbenchCommand::TogglePanelBottomVisual
                 }
-            }
+            };
+            ctx.submit_command(Command::new(
+                LAPCE_COMMAND,
+                LapceCommand {
+                    kind: CommandKind::Workbench(command),
+                    data: None,
+                },
+                Target::Widget(data.id),
+            ));
         }
     }
 
-    fn bar_hit_test(&self, mouse_pos: Point) -> Option<PanelResizePosition> {
+    fn bar_hit_test(
+        &self,
+        data: &LapceTabData,
+        mouse_pos: Point,
+    ) -> Option<PanelResizePosition> {
+        let splits = [
+            (
+                &self.panel_left,
+                PanelContainerPosition::Left,
+                PanelResizePosition::LeftSplit,
+            ),
+            (
+                &self.panel_right,
+                PanelContainerPosition::Right,
+                PanelResizePosition::RightSplit,
+            ),
+            (
+                &self.panel_bottom,
+                PanelContainerPosition::Bottom,
+                PanelResizePosition::BottomSplit,
+            ),
+        ];
+        for (container, position, resize) in splits {
+            if !data.panel.is_container_shown(&position) {
+                continue;
+            }
+            let origin = container.layout_rect().origin().to_vec2();
+            if let Some(rect) = container.widget().split_rect() {
+                if (rect + origin).inflate(1.0, 1.0).contains(mouse_pos) {
+                    return Some(resize);
+                }
+            }
+        }
+
         let rect = self.main_split.layout_rect();
         let left = rect.x0;
         let right = rect.x1;
@@ -479,30 +535,11 @@ fn handle_panel_drop(&mut self, _ctx: &mut EventCtx, data: &mut LapceTabData) {
                     continue;
                 }
 
-                let (_, from_position) = data.panel.panel_position(kind).unwrap();
-                if from_position == *p {
-                    return;
-                }
-
                 let panel = Arc::make_mut(&mut data.panel);
-                if let Some(order) = panel.order.get_mut(&from_position) {
-                    order.retain(|k| k != kind);
+                if panel.move_panel(kind, *p) {
+                    let _ = data.db.save_panel_orders(&panel.order);
                 }
 
-                let order = panel.order.entry(*p).or_insert_with(im::Vector::new);
-
-                order.push_back(*kind);
-
-                let style = panel.style.entry(*p).or_insert(PanelStyle {
-                    active: 0,
-                    shown: true,
-                    maximized: false,
-                });
-
-                style.active = order.len() - 1;
-                style.shown = true;
-                let _ = data.db.save_panel_orders(&panel.order);
-
                 return;
             }
         }
@@ -619,6 +656,51 @@ fn paint_drag(&self, ctx: &mut PaintCtx, data: &LapceTabData) {
                         ),
                     );
                 }
+                DragContent::ExplorerPaths(paths) => {
+                    let label = match paths.as_slice() {
+                        [path] => path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        _ => format!("{} items", paths.len()),
+                    };
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout(label)
+                        .font(
+                            data.config.ui.font_family(),
+                            data.config.ui.font_size() as f64,
+                        )
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    let height = data.config.ui.list_line_height() as f64;
+                    let rect = Size::new(text_layout.size().width + 20.0, height)
+                        .to_rect()
+                        .with_origin(self.mouse_pos - *offset);
+                    ctx.fill(
+                        rect,
+                        data.config.get_color_unchecked(
+                            LapceTheme::EDITOR_DRAG_DROP_BACKGROUND,
+                        ),
+                    );
+                    ctx.stroke(
+                        rect.inflate(0.5, 0.5),
+                        data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                        1.0,
+                    );
+                    ctx.draw_text(
+                        &text_layout,
+                        Point::new(
+                            rect.x0 + 10.0,
+                            rect.y0 + text_layout.y_offset(height),
+                        ),
+                    );
+                }
             }
         }
     }
@@ -637,7 +719,15 @@ fn handle_mouse_event(
             }
             Event::MouseDown(mouse) => {
                 if !ctx.is_handled() && mouse.button.is_left() {
-                    if let Some(position) = self.bar_hit_test(mouse.pos) {
+                    if let Some(position) = self.bar_hit_test(data, mouse.pos) {
+                        self.resize_start_size = match position {
+                            PanelResizePosition::Left => data.panel.size.left,
+                            PanelResizePosition::Right => data.panel.size.right,
+                            PanelResizePosition::Bottom => data.panel.size.bottom,
+                            PanelResizePosition::LeftSplit
+                            | PanelResizePosition::RightSplit
+                            | PanelResizePosition::BottomSplit => 0.0,
+                        };
                         self.current_bar_hover = Some(position);
                         ctx.set_active(true);
                         ctx.set_handled();
@@ -673,6 +763,11 @@ fn handle_command_event(
             Event::Command(cmd) if cmd.is(LAPCE_SAVE_FILE_AS) => {
                 ctx.set_handled();
                 let file = cmd.get_unchecked(LAPCE_SAVE_FILE_AS);
+                FilePickerData::remember_dir(
+                    &data.db,
+                    PickerKind::SaveAs,
+                    &file.path,
+                );
                 if let Some(info) = data.main_split.current_save_as.as_ref() {
                     ctx.submit_command(Command::new(
                         LAPCE_UI_COMMAND,
@@ -689,6 +784,11 @@ fn handle_command_event(
             Event::Command(cmd) if cmd.is(LAPCE_OPEN_FOLDER) => {
                 ctx.set_handled();
                 let file = cmd.get_unchecked(LAPCE_OPEN_FOLDER);
+                FilePickerData::remember_dir(
+                    &data.db,
+                    PickerKind::OpenFolder,
+                    &file.path,
+                );
                 let workspace = LapceWorkspace {
                     kind: LapceWorkspaceType::Local,
                     path: Some(file.path.clone()),
@@ -703,9 +803,44 @@ fn handle_command_event(
                     Target::Window(*data.window_id),
                 ));
             }
+            Event::Command(cmd) if cmd.is(LAPCE_IMPORT_KEYMAPS) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_IMPORT_KEYMAPS);
+                data.preview_keymap_import(ctx, &file.path);
+            }
+            Event::Command(cmd) if cmd.is(LAPCE_EXPORT_STATE) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_EXPORT_STATE);
+                data.transfer_state(ctx, &file.path, None);
+            }
+            Event::Command(cmd) if cmd.is(LAPCE_IMPORT_STATE) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_IMPORT_STATE);
+                data.transfer_state(ctx, &file.path, Some(ImportMode::Merge));
+            }
+            Event::Command(cmd) if cmd.is(LAPCE_IMPORT_STATE_REPLACE) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_IMPORT_STATE_REPLACE);
+                data.transfer_state(ctx, &file.path, Some(ImportMode::Replace));
+            }
+            Event::Command(cmd) if cmd.is(LAPCE_INSTALL_PLUGIN_FROM_FILE) => {
+                ctx.set_handled();
+                let file = cmd.get_unchecked(LAPCE_INSTALL_PLUGIN_FROM_FILE);
+                PluginData::run_command(
+                    data.id,
+                    data.proxy.clone(),
+                    PluginCommand::InstallFromFile(file.path.clone()),
+                    ctx.get_external_handle(),
+                );
+            }
             Event::Command(cmd) if cmd.is(LAPCE_OPEN_FILE) => {
                 ctx.set_handled();
                 let file = cmd.get_unchecked(LAPCE_OPEN_FILE);
+                FilePickerData::remember_dir(
+                    &data.db,
+                    PickerKind::OpenFile,
+                    &file.path,
+                );
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
                     LapceUICommand::OpenFile(file.path.clone(), false),
@@ -715,7 +850,7 @@ fn handle_command_event(
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
                 match command {
-                    LapceUICommand::UpdateStarted => {
+                    LapceUICommand::UpdateProgress(_) => {
                         ctx.set_handled();
                         ctx.request_layout();
                     }
@@ -730,37 +865,7 @@ fn handle_command_event(
                     LapceUICommand::ShowMenu(point, items) => {
                         ctx.set_handled();
 
-                        let mut menu = druid::Menu::new("");
-                        for i in items.iter() {
-                            match i {
-                                MenuKind::Item(i) => {
-                                    let mut item = druid::MenuItem::new(i.desc());
-                                    if let Some(key) = data
-                                        .keypress
-                                        .command_keymaps
-                                        .get(i.command.kind.str())
-                                        .and_then(|m| {
-                                            m.iter().find_map(KeyMap::hotkey)
-                                        })
-                                    {
-                                        item = item.dynamic_hotkey(move |_, _| {
-                                            Some(key.clone())
-                                        })
-                                    }
-                                    item = item
-                                        .command(Command::new(
-                                            LAPCE_COMMAND,
-                                            i.command.clone(),
-                                            Target::Widget(data.id),
-                                        ))
-                                        .enabled(i.enabled);
-                                    menu = menu.entry(item);
-                                }
-                                MenuKind::Separator => {
-                                    menu = menu.separator();
-                                }
-                            }
-                        }
+                        let menu = build_menu(druid::Menu::new(""), items, data);
                         ctx.show_context_menu::<LapceData>(menu, *point);
                     }
                     LapceUICommand::InitBufferContent(init) => {
@@ -809,16 +914,16 @@ fn handle_command_event(
                         }
                     }
                     LapceUICommand::UpdateSearch(pattern, new_cs) => {
+                        let search_id = Arc::make_mut(&mut data.search).new_search();
                         if pattern.is_empty() {
                             Arc::make_mut(&mut data.find).unset();
-                            Arc::make_mut(&mut data.search).matches =
-                                Arc::new(Default::default());
                         } else {
                             let find = Arc::make_mut(&mut data.find);
                             if let Some(cs) = new_cs {
                                 find.set_case_sensitive(*cs);
                             }
-                            find.set_find(pattern, false, false);
+                            let whole_words = find.whole_words;
+                            find.set_find(pattern, false, whole_words);
                             find.visual = true;
                             if data.focus_area == FocusArea::Panel(PanelKind::Search)
                                 && data.config.editor.move_focus_while_search
@@ -836,11 +941,11 @@ fn handle_command_event(
                                     ));
                                 }
                             }
-                            let pattern = pattern.to_string();
                             let event_sink = ctx.get_external_handle();
                             let tab_id = data.id;
                             data.proxy.proxy_rpc.global_search(
-                                pattern.clone(),
+                                search_id,
+                                pattern.to_string(),
                                 find.case_sensitive(),
                                 Box::new(move |result| {
                                     if let Ok(
@@ -851,10 +956,10 @@ fn handle_command_event(
                                     {
                                         let _ = event_sink.submit_command(
                                             LAPCE_UI_COMMAND,
-                                            LapceUICommand::GlobalSearchResult(
-                                                pattern,
-                                                Arc::new(matches),
-                                            ),
+                                            LapceUICommand::GlobalSearchResult {
+                                                search_id,
+                                                matches: Arc::new(matches),
+                                            },
                                             Target::Widget(tab_id),
                                         );
                                     }
@@ -865,15 +970,10 @@ fn handle_command_event(
                     LapceUICommand::OpenPluginInfo(volt) => {
                         data.main_split.open_plugin_info(ctx, volt);
                     }
-                    LapceUICommand::GlobalSearchResult(pattern, matches) => {
-                        let doc = data
-                            .main_split
-                            .local_docs
-                            .get(&LocalBufferKind::Search)
-                            .unwrap();
-                        if &doc.buffer().text().slice_to_cow(..) == pattern {
-                            Arc::make_mut(&mut data.search).matches =
-                                matches.clone();
+                    LapceUICommand::GlobalSearchResult { search_id, matches } => {
+                        if data.search.search_id == *search_id {
+                            Arc::make_mut(&mut data.search)
+                                .extend_matches(*search_id, matches);
                         }
                     }
                     LapceUICommand::LoadBufferHead {
@@ -906,6 +1006,17 @@ fn handle_command_event(
                             placeholder.clone(),
                         );
                     }
+                    LapceUICommand::ShowRenamePreview { new_name, edit } => {
+                        ctx.set_handled();
+                        Arc::make_mut(&mut data.rename).show_preview(
+                            ctx,
+                            &mut data.main_split,
+                            new_name.clone(),
+                            edit,
+                            &data.config,
+                        );
+                        data.show_panel(ctx, PanelKind::RenamePreview);
+                    }
                     LapceUICommand::UpdateTerminalTitle(term_id, title) => {
                         for (_, split) in
                             Arc::make_mut(&mut data.terminal).tabs.iter_mut()
@@ -921,12 +1032,15 @@ fn handle_command_event(
                         ctx.set_handled();
                     }
                     LapceUICommand::ProxyUpdateStatus(status) => {
-                        data.proxy_status = Arc::new(*status);
+                        data.update_proxy_status(ctx, *status);
                         ctx.set_handled();
                     }
                     LapceUICommand::HomeDir(path) => {
                         Arc::make_mut(&mut data.picker).init_home(path);
                         data.set_picker_pwd(path.clone());
+                        if data.picker.active {
+                            data.read_picker_pwd(ctx);
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::WorkspaceFileChange => {
@@ -947,6 +1061,24 @@ fn handle_command_event(
                             *plugin_id,
                         );
                     }
+                    LapceUICommand::UpdateCompletionDocumentation {
+                        session_id,
+                        plugin_id,
+                        label,
+                        documentation,
+                    } => {
+                        let completion = Arc::make_mut(&mut data.completion);
+                        completion.receive_documentation(
+                            *session_id,
+                            *plugin_id,
+                            label,
+                            documentation.to_owned(),
+                        );
+                    }
+                    LapceUICommand::SendCompletionRequest { request_id } => {
+                        let completion = Arc::make_mut(&mut data.completion);
+                        completion.send_pending(*request_id, &data.proxy);
+                    }
                     LapceUICommand::CancelCompletion { request_id } => {
                         if data.completion.request_id == *request_id {
                             let completion = Arc::make_mut(&mut data.completion);
@@ -962,24 +1094,28 @@ fn handle_command_event(
                         signature.receive(*request_id, resp.to_owned(), *plugin_id);
                     }
                     LapceUICommand::CloseTerminal(id) => {
-                        let terminal_panel = Arc::make_mut(&mut data.terminal);
-                        if let Some(terminal) = terminal_panel
-                            .active_terminal_split_mut()
-                            .unwrap()
-                            .terminals
-                            .get_mut(id)
+                        if let Some((split_id, widget_id)) = data
+                            .terminal
+                            .active_terminal_split()
+                            .and_then(|split| split.close_target(*id))
                         {
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
-                                LapceUICommand::SplitTerminalClose(
-                                    terminal.term_id,
-                                    terminal.widget_id,
-                                ),
-                                Target::Widget(terminal.split_id),
+                                LapceUICommand::SplitTerminalClose(*id, widget_id),
+                                Target::Widget(split_id),
                             ));
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::RenameTerminalTab(split_id, name) => {
+                        let terminal_panel = Arc::make_mut(&mut data.terminal);
+                        if let Some(split) = terminal_panel.tabs.get_mut(split_id) {
+                            let name = name.trim();
+                            split.name =
+                                (!name.is_empty()).then(|| name.to_string());
+                        }
+                        ctx.set_handled();
+                    }
                     LapceUICommand::LoadPluginLatest(info) => {
                         ctx.set_handled();
                         let plugin = Arc::make_mut(&mut data.plugin);
@@ -1028,6 +1164,9 @@ fn handle_command_event(
                             }
                         }
                     }
+                    LapceUICommand::VoltPermissionsRequested(volt, requested) => {
+                        data.request_volt_permissions(ctx, volt, requested.clone());
+                    }
                     LapceUICommand::VoltInstalling(volt, error) => {
                         let plugin = Arc::make_mut(&mut data.plugin);
 
@@ -1062,6 +1201,14 @@ fn handle_command_event(
                             );
                         }
                     }
+                    LapceUICommand::VoltDownloadProgress(progress) => {
+                        let plugin = Arc::make_mut(&mut data.plugin);
+                        if let Some(elem) =
+                            plugin.installing.get_mut(&progress.volt_id)
+                        {
+                            elem.set_progress(progress.fraction());
+                        }
+                    }
                     LapceUICommand::VoltRemoving(volt, error) => {
                         let plugin = Arc::make_mut(&mut data.plugin);
 
@@ -1115,6 +1262,11 @@ fn handle_command_event(
                                     plugin.disabled.iter().collect(),
                                 );
                             }
+                            if plugin.pinned.remove(&id).is_some() {
+                                let _ = data.db.save_pinned_volts(
+                                    plugin.pinned.iter().collect(),
+                                );
+                            }
                             if plugin.workspace_disabled.remove(&id) {
                                 let _ = data.db.save_disabled_volts(
                                     plugin.workspace_disabled.iter().collect(),
@@ -1162,6 +1314,31 @@ fn handle_command_event(
                             plugin.workspace_disabled.iter().collect(),
                         );
                     }
+                    LapceUICommand::RunPluginCommand(command) => {
+                        ctx.set_handled();
+                        PluginData::run_command(
+                            data.id,
+                            data.proxy.clone(),
+                            command.clone(),
+                            ctx.get_external_handle(),
+                        );
+                    }
+                    LapceUICommand::PinVolt(id, version) => {
+                        ctx.set_handled();
+                        let plugin = Arc::make_mut(&mut data.plugin);
+                        plugin.pinned.insert(id.clone(), version.clone());
+                        let _ = data
+                            .db
+                            .save_pinned_volts(plugin.pinned.iter().collect());
+                    }
+                    LapceUICommand::UnpinVolt(id) => {
+                        ctx.set_handled();
+                        let plugin = Arc::make_mut(&mut data.plugin);
+                        plugin.pinned.remove(id);
+                        let _ = data
+                            .db
+                            .save_pinned_volts(plugin.pinned.iter().collect());
+                    }
                     LapceUICommand::DisableVolt(volt) => {
                         let plugin = Arc::make_mut(&mut data.plugin);
                         plugin.disabled.insert(volt.id());
@@ -1198,12 +1375,34 @@ fn handle_command_event(
                                 (diff.path().clone(), (diff, checked))
                             })
                             .collect();
-
-                        for (_path, doc) in data.main_split.open_docs.iter() {
+                        source_control.hunks = diff.hunks.clone();
+                        let hunks = &source_control.hunks;
+                        source_control
+                            .expanded_files
+                            .retain(|path| hunks.contains_key(path));
+                        source_control.file_list_index = source_control
+                            .file_list_index
+                            .min(source_control.items().len().saturating_sub(1));
+
+                        for (_path, doc) in data.main_split.open_docs.iter_mut() {
                             doc.reload_history("head");
+                            // The blame changes with new commits
+                            Arc::make_mut(doc).clear_blame();
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::StageHunk { path, hunk } => {
+                        data.proxy
+                            .proxy_rpc
+                            .git_stage_hunk(path.clone(), hunk.clone());
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UnstageHunk { path, hunk } => {
+                        data.proxy
+                            .proxy_rpc
+                            .git_unstage_hunk(path.clone(), hunk.clone());
+                        ctx.set_handled();
+                    }
                     LapceUICommand::WorkDoneProgress(params) => {
                         match &params.value {
                             lsp_types::ProgressParamsValue::WorkDone(progress) => {
@@ -1234,6 +1433,8 @@ fn handle_command_event(
                                             let editor_data =
                                                 data.editor_view_content(*view_id);
                                             editor_data.doc.get_inlay_hints();
+                                            editor_data.doc.get_document_colors();
+                                            editor_data.doc.get_symbol_scopes();
                                         }
                                         for i in data
                                             .progresses
@@ -1289,27 +1490,19 @@ fn handle_command_event(
 
                         data.main_split.diagnostics.insert(path, diagnostics);
 
-                        let mut errors = 0;
-                        let mut warnings = 0;
-                        for (_, diagnostics) in data.main_split.diagnostics.iter() {
-                            for diagnostic in diagnostics.iter() {
-                                if let Some(severity) =
-                                    diagnostic.diagnostic.severity
-                                {
-                                    match severity {
-                                        DiagnosticSeverity::ERROR => errors += 1,
-                                        DiagnosticSeverity::WARNING => warnings += 1,
-                                        _ => (),
-                                    }
-                                }
-                            }
-                        }
-                        data.main_split.error_count = errors;
-                        data.main_split.warning_count = warnings;
+                        let counts =
+                            severity_counts(data.main_split.file_diagnostics());
+                        data.main_split.error_count = counts[0];
+                        data.main_split.warning_count = counts[1];
 
                         ctx.set_handled();
                     }
-                    LapceUICommand::DocumentSave { path, exit } => {
+                    LapceUICommand::DocumentSave {
+                        path,
+                        exit,
+                        cleanup,
+                    } => {
+                        data.main_split.clean_up_whitespace(path, cleanup);
                         data.main_split.document_save(ctx, path, *exit);
                         ctx.set_handled();
                     }
@@ -1318,9 +1511,10 @@ fn handle_command_event(
                         rev,
                         result,
                         exit,
+                        cleanup,
                     } => {
                         data.main_split.document_format_and_save(
-                            ctx, path, *rev, result, *exit,
+                            ctx, path, *rev, result, *exit, cleanup,
                         );
                         ctx.set_handled();
                     }
@@ -1340,8 +1534,7 @@ fn handle_command_event(
                     }
                     LapceUICommand::ShowAlert(content) => {
                         let alert = Arc::make_mut(&mut data.alert);
-                        alert.active = true;
-                        alert.content = content.to_owned();
+                        alert.push(content.to_owned());
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::Focus,
@@ -1354,9 +1547,15 @@ fn handle_command_event(
                         rev,
                         exit: exit_widget_id,
                     } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() == *rev {
-                            Arc::make_mut(doc).buffer_mut().set_pristine();
+                        // The files only loaded for a rename preview are closed
+                        // before their save is answered
+                        let saved = data
+                            .main_split
+                            .open_docs
+                            .get_mut(path)
+                            .filter(|doc| doc.rev() == *rev);
+                        if let Some(doc) = saved {
+                            Arc::make_mut(doc).set_saved();
                             if let Some(widget_id) = exit_widget_id {
                                 ctx.submit_command(Command::new(
                                     LAPCE_COMMAND,
@@ -1369,8 +1568,85 @@ fn handle_command_event(
                                     Target::Widget(*widget_id),
                                 ));
                             }
+                            data.save_local_history(path);
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::LocalHistorySnapshot { path } => {
+                        ctx.set_handled();
+                        let debounce = Duration::from_secs(
+                            data.config.history.local.debounce_secs,
+                        );
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            let elapsed = doc
+                                .last_edit
+                                .map(|time| time.elapsed())
+                                .unwrap_or(debounce);
+                            if elapsed < debounce {
+                                // Check again once the latest edit settled
+                                let event_sink = ctx.get_external_handle();
+                                let path = path.clone();
+                                let tab_id = data.id;
+                                thread::spawn(move || {
+                                    thread::sleep(debounce - elapsed);
+                                    let _ = event_sink.submit_command(
+                                        LAPCE_UI_COMMAND,
+                                        LapceUICommand::LocalHistorySnapshot {
+                                            path,
+                                        },
+                                        Target::Widget(tab_id),
+                                    );
+                                });
+                            } else {
+                                Arc::make_mut(doc).local_history_pending = false;
+                                data.save_local_history(path);
+                            }
                         }
+                    }
+                    LapceUICommand::PreviewLocalHistory {
+                        editor_id,
+                        path,
+                        time,
+                    } => {
+                        ctx.set_handled();
+                        if let (Ok(content), Some(doc)) = (
+                            data.db.get_local_history_content(path, *time),
+                            data.main_split.open_docs.get_mut(path),
+                        ) {
+                            Arc::make_mut(doc).load_history(
+                                LOCAL_HISTORY_VERSION,
+                                Rope::from(content),
+                            );
+                            data.main_split.jump_to_location(
+                                ctx,
+                                Some(*editor_id),
+                                true,
+                                EditorLocation {
+                                    path: path.clone(),
+                                    position: None::<usize>,
+                                    scroll_offset: None,
+                                    history: Some(LOCAL_HISTORY_VERSION.to_string()),
+                                },
+                                &data.config,
+                            );
+                        }
+                    }
+                    LapceUICommand::RestoreLocalHistory { path, time } => {
                         ctx.set_handled();
+                        if let Ok(content) =
+                            data.db.get_local_history_content(path, *time)
+                        {
+                            // The replaced content can be restored in turn
+                            data.save_local_history(path);
+                            if let Some(doc) = data.main_split.open_docs.get(path) {
+                                let len = doc.buffer().len();
+                                data.main_split.edit(
+                                    path,
+                                    &[(Selection::region(0, len), content.as_str())],
+                                    EditType::Other,
+                                );
+                            }
+                        }
                     }
                     LapceUICommand::UpdateSettingsFile { kind, key, value } => {
                         ctx.set_handled();
@@ -1406,6 +1682,11 @@ fn handle_command_event(
                         ));
                         ctx.set_handled();
                     }
+                    LapceUICommand::UpdateSettingsFilter(pattern) => {
+                        ctx.set_handled();
+                        let settings = Arc::make_mut(&mut data.settings);
+                        settings.filter_pattern = pattern.clone();
+                    }
                     LapceUICommand::UpdateKeymapsFilter(pattern) => {
                         ctx.set_handled();
                         let keypress = Arc::make_mut(&mut data.keypress);
@@ -1456,6 +1737,23 @@ fn handle_command_event(
                         );
                         ctx.set_handled();
                     }
+                    LapceUICommand::OpenFileLocation(file) => {
+                        data.main_split.open_file_location(ctx, file, &data.config);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::OpenUrlDocument(url) => {
+                        data.main_split.open_url_document(ctx, url.clone());
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UrlDocumentLoaded { url, content } => {
+                        data.main_split.show_url_document(
+                            ctx,
+                            url,
+                            content.clone(),
+                            &data.config,
+                        );
+                        ctx.set_handled();
+                    }
                     LapceUICommand::RevealInFileExplorer(path) => {
                         // TODO: replace with proper implementation from druid that
                         // highlights items in file explorer
@@ -1538,6 +1836,70 @@ fn handle_command_event(
                             .or_insert(false);
                         *state = !*state;
                     }
+                    LapceUICommand::SetProblemGrouping(grouping) => {
+                        Arc::make_mut(&mut data.problem).grouping = *grouping;
+                        let _ = data.db.save_problem_grouping(*grouping);
+                    }
+                    LapceUICommand::SetProblemMinSeverity(severity) => {
+                        Arc::make_mut(&mut data.problem).filter.min_severity =
+                            *severity;
+                    }
+                    LapceUICommand::ToggleProblemSource(source) => {
+                        let hidden_sources = &mut Arc::make_mut(&mut data.problem)
+                            .filter
+                            .hidden_sources;
+                        if !hidden_sources.remove(source) {
+                            hidden_sources.insert(source.to_owned());
+                        }
+                    }
+                    LapceUICommand::ShowProblemCodeActions {
+                        point,
+                        path,
+                        plugin_id,
+                        resp,
+                    } => {
+                        let items = code_action_menu(path, *plugin_id, resp);
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ShowMenu(*point, Arc::new(items)),
+                            Target::Widget(data.id),
+                        ));
+                    }
+                    LapceUICommand::ShowColorPicker {
+                        point,
+                        path,
+                        range,
+                        color,
+                    } => {
+                        Arc::make_mut(&mut data.color_picker).open(
+                            *point,
+                            path.clone(),
+                            *range,
+                            color,
+                        );
+                    }
+                    LapceUICommand::ShowColorPresentations {
+                        point,
+                        path,
+                        range,
+                        presentations,
+                    } => {
+                        let items =
+                            color_presentation_menu(path, *range, presentations);
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ShowMenu(*point, Arc::new(items)),
+                            Target::Widget(data.id),
+                        ));
+                    }
+                    LapceUICommand::ApplyWorkspaceEdit(edit, reply) => {
+                        data.main_split.apply_plugin_workspace_edit(
+                            ctx,
+                            edit.clone(),
+                            reply.clone(),
+                            &data.config,
+                        );
+                    }
                     LapceUICommand::JumpToLineLocation(editor_view_id, location) => {
                         data.main_split.jump_to_location(
                             ctx,
@@ -1587,6 +1949,17 @@ fn handle_command_event(
                         // ));
                         ctx.set_handled();
                     }
+                    LapceUICommand::RunMacroStep { name, step } => {
+                        data.run_macro_step(ctx, name, *step, env);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::TerminalRunText(text) => {
+                        let line_by_line =
+                            data.config.terminal.run_selection_line_by_line;
+                        data.run_text_in_terminal(ctx, text, line_by_line);
+                        ctx.request_paint();
+                        ctx.set_handled();
+                    }
                     LapceUICommand::GotoDefinition {
                         editor_view_id,
                         offset,
@@ -1607,13 +1980,55 @@ fn handle_command_event(
                         }
                         ctx.set_handled();
                     }
-                    LapceUICommand::UpdateInlayHints { path, rev, hints } => {
+                    LapceUICommand::ShowPeek {
+                        editor_view_id,
+                        offset,
+                        entries,
+                    } => {
+                        if let Some(editor) = data.main_split.active_editor() {
+                            if *editor_view_id == editor.view_id
+                                && *offset == editor.cursor.offset()
+                            {
+                                Arc::make_mut(&mut data.peek).show(
+                                    ctx,
+                                    &mut data.main_split,
+                                    *editor_view_id,
+                                    *offset,
+                                    entries.clone(),
+                                    &data.config,
+                                );
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateInlayHints {
+                        path,
+                        rev,
+                        lines,
+                        hints,
+                    } => {
                         if let Some(doc) = data.main_split.open_docs.get_mut(path) {
-                            if doc.rev() == *rev {
+                            // The hints of lines scrolled past since are dropped
+                            if doc.rev() == *rev && doc.inlay_hint_lines() == lines {
                                 Arc::make_mut(doc).set_inlay_hints(hints.clone());
                             }
                         }
                     }
+                    LapceUICommand::UpdateDocumentColors { path, rev, colors } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_document_colors(colors.clone());
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateSymbolScopes { path, rev, scopes } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).set_symbol_scopes(scopes.clone());
+                            }
+                        }
+                    }
                     LapceUICommand::CodeActionsError { path, rev, offset } => {
                         if let Some(doc) = data.main_split.open_docs.get_mut(path) {
                             if doc.rev() == *rev {
@@ -1636,6 +2051,11 @@ fn handle_command_event(
                             }
                         }
                     }
+                    LapceUICommand::UpdateBlame { path, rev, hunks } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).set_blame(*rev, hunks.clone());
+                        }
+                    }
                     LapceUICommand::PaletteReferences(offset, locations) => {
                         if let Some(editor) = data.main_split.active_editor() {
                             if *offset == editor.cursor.offset() {
@@ -1681,7 +2101,45 @@ fn handle_command_event(
                     LapceUICommand::OpenFileChanged { path, content } => {
                         let doc = data.main_split.open_docs.get_mut(path).unwrap();
                         let doc = Arc::make_mut(doc);
-                        doc.handle_file_changed(content.to_owned());
+                        if doc.handle_file_changed(
+                            content.to_owned(),
+                            data.config.document.auto_reload,
+                        ) {
+                            let alert = Arc::make_mut(&mut data.alert);
+                            alert.push_keyed(
+                                Some(path.to_string_lossy().into_owned()),
+                                disk_change_alert(data.id, path, false, None),
+                            );
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::Focus,
+                                Target::Widget(alert.widget_id),
+                            ));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::OpenFileDeleted { path } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).handle_file_deleted();
+                            let content = BufferContent::File(path.clone());
+                            let editor = data
+                                .main_split
+                                .editors
+                                .values()
+                                .find(|editor| editor.content == content)
+                                .map(|editor| editor.view_id);
+                            let alert = Arc::make_mut(&mut data.alert);
+                            alert.push_keyed(
+                                Some(path.to_string_lossy().into_owned()),
+                                disk_change_alert(data.id, path, true, editor),
+                            );
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::Focus,
+                                Target::Widget(alert.widget_id),
+                            ));
+                        }
+                        ctx.set_handled();
                     }
                     LapceUICommand::ReloadBuffer { path, rev, content } => {
                         let doc = data.main_split.open_docs.get_mut(path).unwrap();
@@ -1737,11 +2195,6 @@ fn handle_command_event(
                         ctx.set_handled();
                     }
                     LapceUICommand::Focus => {
-                        ctx.window().set_title(
-                            &workspace_title(&data.workspace)
-                                .map(|x| format!("{x} - Lapce"))
-                                .unwrap_or_else(|| String::from("Lapce")),
-                        );
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::Focus,
@@ -1797,6 +2250,64 @@ fn handle_command_event(
                         }
                         doc.trigger_syntax_change(None);
                     }
+                    LapceUICommand::ConvertLineEndings(line_ending) => {
+                        ctx.set_handled();
+                        if let Some(editor) =
+                            data.main_split.active_editor().cloned()
+                        {
+                            let doc =
+                                data.main_split.content_doc_mut(&editor.content);
+                            Arc::make_mut(doc).convert_line_endings(*line_ending);
+                        }
+                    }
+                    LapceUICommand::MovePanel { kind, position } => {
+                        ctx.set_handled();
+                        let panel = Arc::make_mut(&mut data.panel);
+                        if panel.move_panel(kind, *position) {
+                            let _ = data.db.save_panel_orders(&panel.order);
+                        }
+                    }
+                    LapceUICommand::SetSettingsProfile(profile) => {
+                        ctx.set_handled();
+                        let _ = data.db.save_workspace_profile(
+                            &data.workspace,
+                            profile.as_deref(),
+                        );
+                        Arc::make_mut(&mut data.config).active_profile =
+                            profile.clone();
+                        // The config is loaded again with the profile, like
+                        // when the settings files change
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ReloadConfig,
+                            Target::Auto,
+                        ));
+                    }
+                    LapceUICommand::ReopenWithEncoding(encoding) => {
+                        ctx.set_handled();
+                        if let Some(editor) = data.main_split.active_editor() {
+                            data.main_split
+                                .content_doc(&editor.content)
+                                .reopen_with_encoding(encoding);
+                        }
+                    }
+                    LapceUICommand::ReopenedWithEncoding {
+                        path,
+                        rev,
+                        content,
+                        encoding,
+                    } => {
+                        ctx.set_handled();
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            let doc = Arc::make_mut(doc);
+                            doc.set_encoding(encoding.clone());
+                            // The edits made since the request are kept, the file
+                            // is still saved with the new encoding
+                            if doc.rev() == *rev {
+                                doc.reload(content.clone(), true);
+                            }
+                        }
+                    }
                     LapceUICommand::UpdateHistoryChanges {
                         path,
                         rev,
@@ -1826,10 +2337,22 @@ fn handle_command_event(
                             .update_history_styles(history, highlights.to_owned());
                     }
                     LapceUICommand::UpdatePickerPwd(path) => {
-                        Arc::make_mut(&mut data.picker).pwd = path.clone();
+                        let picker = Arc::make_mut(&mut data.picker);
+                        if path.to_str() != Some(&picker.completion.input) {
+                            picker.completion = PickerCompletion::default();
+                        }
+                        picker.pwd = path.clone();
                         data.read_picker_pwd(ctx);
                         ctx.set_handled();
                     }
+                    LapceUICommand::CompletePickerPath => {
+                        data.complete_picker_path(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdatePickerCompletion(input, items) => {
+                        data.update_picker_completion(ctx, input, items);
+                        ctx.set_handled();
+                    }
                     LapceUICommand::UpdatePickerItems(path, items) => {
                         Arc::make_mut(&mut data.picker)
                             .root
@@ -1958,6 +2481,100 @@ fn handle_command_event(
                         );
                         ctx.set_handled();
                     }
+                    LapceUICommand::ExplorerCut { paths } => {
+                        let file_explorer = Arc::make_mut(&mut data.file_explorer);
+                        file_explorer.clipboard = Some(ExplorerClipboard {
+                            paths: paths.clone(),
+                            cut: true,
+                        });
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ExplorerCopy { paths } => {
+                        let file_explorer = Arc::make_mut(&mut data.file_explorer);
+                        file_explorer.clipboard = Some(ExplorerClipboard {
+                            paths: paths.clone(),
+                            cut: false,
+                        });
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ExplorerPaste { dir } => {
+                        let file_explorer = Arc::make_mut(&mut data.file_explorer);
+                        let operations = file_explorer.paste_operations(dir);
+                        if !operations.is_empty() {
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ExplorerFileOperations {
+                                    operations,
+                                },
+                                Target::Widget(data.id),
+                            ));
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ExplorerFileOperations { operations } => {
+                        let file_explorer = Arc::make_mut(&mut data.file_explorer);
+                        file_explorer.selection.clear();
+                        let explorer = data.file_explorer.clone();
+                        let tab_id = data.id;
+                        let moves = operations
+                            .iter()
+                            .filter_map(|operation| match operation {
+                                FileOperation::Move { from, to, .. } => {
+                                    Some((from.clone(), to.clone()))
+                                }
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>();
+                        data.proxy.proxy_rpc.file_operations(
+                            operations.clone(),
+                            Box::new(move |res| {
+                                match res {
+                                    Ok(ProxyResponse::FileOperationsResponse {
+                                        untrashed,
+                                    }) => {
+                                        if !moves.is_empty() {
+                                            let _ = explorer
+                                                .event_sink
+                                                .submit_command(
+                                                LAPCE_UI_COMMAND,
+                                                LapceUICommand::ExplorerPathsMoved {
+                                                    moves,
+                                                },
+                                                Target::Widget(tab_id),
+                                            );
+                                        }
+                                        if !untrashed.is_empty() {
+                                            let _ =
+                                                explorer.event_sink.submit_command(
+                                                    LAPCE_UI_COMMAND,
+                                                    LapceUICommand::ShowAlert(
+                                                        permanent_delete_alert(
+                                                            tab_id, untrashed,
+                                                        ),
+                                                    ),
+                                                    Target::Widget(tab_id),
+                                                );
+                                        }
+                                    }
+                                    Err(err) => {
+                                        // TODO: inform the user through a corner-notif
+                                        log::warn!(
+                                            "Failed to apply file operations: {:?}",
+                                            err
+                                        );
+                                    }
+                                    _ => {}
+                                }
+                                explorer.reload();
+                            }),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ExplorerPathsMoved { moves } => {
+                        data.main_split.move_paths(moves);
+                        Arc::make_mut(&mut data.file_explorer).selection.clear();
+                        ctx.set_handled();
+                    }
                     LapceUICommand::ExplorerNew {
                         list_index,
                         indent_level,
@@ -2019,6 +2636,21 @@ fn handle_command_event(
                         let mut clipboard = druid::Application::global().clipboard();
                         clipboard.put_string(target_string);
                     }
+                    LapceUICommand::RegisterPluginPaletteProvider {
+                        plugin_id,
+                        provider,
+                    } => {
+                        ctx.set_handled();
+                        Arc::make_mut(&mut data.palette)
+                            .plugin_providers
+                            .insert(*plugin_id, provider.clone());
+                    }
+                    LapceUICommand::PluginServerStopped(plugin_id) => {
+                        ctx.set_handled();
+                        Arc::make_mut(&mut data.palette)
+                            .plugin_providers
+                            .remove(plugin_id);
+                    }
                     LapceUICommand::NewMessage {
                         kind,
                         title,
@@ -2095,6 +2727,12 @@ fn event(
         if data.rename.active || event.should_propagate_to_hidden() {
             self.rename.event(ctx, event, data, env);
         }
+        if data.color_picker.active || event.should_propagate_to_hidden() {
+            self.color_picker.event(ctx, event, data, env);
+        }
+        if data.peek.active || event.should_propagate_to_hidden() {
+            self.peek.event(ctx, event, data, env);
+        }
 
         self.handle_mouse_event(ctx, event, data, env);
 
@@ -2121,6 +2759,21 @@ fn event(
             self.panel_bottom.event(ctx, event, data, env);
         }
 
+        if let Event::MouseUp(_) = event {
+            // The file explorer handled the drop if it was over it
+            if let Some((_, _, DragContent::ExplorerPaths(_))) = data.drag.as_ref() {
+                *Arc::make_mut(&mut data.drag) = None;
+            }
+        }
+
+        if data.color_picker.active {
+            if let Event::MouseDown(mouse_event) = &event {
+                if !self.color_picker.layout_rect().contains(mouse_event.pos) {
+                    Arc::make_mut(&mut data.color_picker).active = false;
+                }
+            }
+        }
+
         if data.hover.status != HoverStatus::Inactive {
             if let Event::MouseMove(mouse_event) = &event {
                 if !self.hover.layout_rect().contains(mouse_event.pos)
@@ -2153,7 +2806,7 @@ fn event(
                 } else if ctx.has_active() {
                     ctx.clear_cursor();
                 } else {
-                    match self.bar_hit_test(mouse.pos) {
+                    match self.bar_hit_test(data, mouse.pos) {
                         Some(position) => {
                             if self.current_bar_hover.as_ref() != Some(&position) {
                                 self.current_bar_hover = Some(position.clone());
@@ -2166,12 +2819,16 @@ fn event(
                                 PanelResizePosition::Right => {
                                     ctx.set_cursor(&druid::Cursor::ResizeLeftRight);
                                 }
-                                PanelResizePosition::LeftSplit => {
+                                PanelResizePosition::LeftSplit
+                                | PanelResizePosition::RightSplit => {
                                     ctx.set_cursor(&druid::Cursor::ResizeUpDown);
                                 }
                                 PanelResizePosition::Bottom => {
                                     ctx.set_cursor(&druid::Cursor::ResizeUpDown)
                                 }
+                                PanelResizePosition::BottomSplit => {
+                                    ctx.set_cursor(&druid::Cursor::ResizeLeftRight)
+                                }
                             }
                         }
                         None => {
@@ -2248,6 +2905,8 @@ fn lifecycle(
         self.signature.lifecycle(ctx, event, data, env);
         self.hover.lifecycle(ctx, event, data, env);
         self.rename.lifecycle(ctx, event, data, env);
+        self.color_picker.lifecycle(ctx, event, data, env);
+        self.peek.lifecycle(ctx, event, data, env);
         self.picker.lifecycle(ctx, event, data, env);
         self.about.lifecycle(ctx, event, data, env);
         self.alert.lifecycle(ctx, event, data, env);
@@ -2289,7 +2948,9 @@ fn update(
         if old_data.about.active != data.about.active {
             ctx.request_layout();
         }
-        if old_data.alert.active != data.alert.active {
+        if old_data.alert.active != data.alert.active
+            || old_data.alert.shown != data.alert.shown
+        {
             ctx.request_layout();
         }
 
@@ -2325,6 +2986,16 @@ fn update(
             ctx.request_layout();
         }
 
+        if old_data.color_picker.active != data.color_picker.active {
+            ctx.request_layout();
+        }
+
+        if old_data.peek.active != data.peek.active
+            || old_data.peek.offset != data.peek.offset
+        {
+            ctx.request_layout();
+        }
+
         if old_data.picker.active != data.picker.active {
             ctx.request_layout();
         }
@@ -2335,6 +3006,8 @@ fn update(
         self.signature.update(ctx, data, env);
         self.hover.update(ctx, data, env);
         self.rename.update(ctx, data, env);
+        self.color_picker.update(ctx, data, env);
+        self.peek.update(ctx, data, env);
         self.status.update(ctx, data, env);
         self.picker.update(ctx, data, env);
         self.about.update(ctx, data, env);
@@ -2489,7 +3162,17 @@ fn layout(
         }
 
         if data.hover.status == HoverStatus::Done {
-            self.hover.layout(ctx, bc, data, env);
+            let max_height =
+                data.hover_max_height(ctx.text(), self_size, &data.config);
+            self.hover.layout(
+                ctx,
+                &BoxConstraints::new(
+                    Size::ZERO,
+                    Size::new(self_size.width, max_height),
+                ),
+                data,
+                env,
+            );
             let hover_origin =
                 data.hover_origin(ctx.text(), self_size, &data.config);
             self.hover.set_origin(ctx, data, env, hover_origin);
@@ -2507,6 +3190,35 @@ fn layout(
             self.rename.set_origin(ctx, data, env, rename_origin);
         }
 
+        if data.color_picker.active {
+            let picker_size = self.color_picker.layout(
+                ctx,
+                &BoxConstraints::loose(self_size),
+                data,
+                env,
+            );
+            // At the clicked swatch, but kept within the tab
+            let point =
+                data.color_picker.point - data.window_origin.borrow().to_vec2();
+            let picker_origin = Point::new(
+                point.x.min(self_size.width - picker_size.width).max(0.0),
+                point.y.min(self_size.height - picker_size.height).max(0.0),
+            );
+            self.color_picker.set_origin(ctx, data, env, picker_origin);
+        }
+
+        if data.peek.active {
+            if let Some(rect) = data.peek_rect(ctx.text(), self_size, &data.config) {
+                self.peek.layout(
+                    ctx,
+                    &BoxConstraints::tight(rect.size()),
+                    data,
+                    env,
+                );
+                self.peek.set_origin(ctx, data, env, rect.origin());
+            }
+        }
+
         if data.picker.active {
             let picker_size = self.picker.layout(ctx, bc, data, env);
             self.picker.set_origin(
@@ -2603,12 +3315,33 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
                         )
                     }
                 }
-                PanelResizePosition::LeftSplit => {
-                    let rect = self.panel_left.layout_rect();
-                    (
-                        Point::new(rect.x1.round(), rect.y0),
-                        Point::new(rect.x1.round(), rect.y1),
-                    )
+                PanelResizePosition::LeftSplit
+                | PanelResizePosition::RightSplit
+                | PanelResizePosition::BottomSplit => {
+                    let container = match position {
+                        PanelResizePosition::LeftSplit => &self.panel_left,
+                        PanelResizePosition::RightSplit => &self.panel_right,
+                        _ => &self.panel_bottom,
+                    };
+                    let origin = container.layout_rect().origin().to_vec2();
+                    match container.widget().split_rect() {
+                        Some(rect) => {
+                            let rect = rect + origin;
+                            let center = rect.center();
+                            if matches!(position, PanelResizePosition::BottomSplit) {
+                                (
+                                    Point::new(center.x, rect.y0),
+                                    Point::new(center.x, rect.y1),
+                                )
+                            } else {
+                                (
+                                    Point::new(rect.x0, center.y),
+                                    Point::new(rect.x1, center.y),
+                                )
+                            }
+                        }
+                        None => (Point::ZERO, Point::ZERO),
+                    }
                 }
                 PanelResizePosition::Bottom => {
                     let rect = self.panel_bottom.layout_rect();
@@ -2637,6 +3370,25 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
         }
         self.title.paint(ctx, data, env);
         self.status.paint(ctx, data, env);
+        if data.peek.active {
+            let rect = self.peek.layout_rect();
+            let shadow_width = data.config.ui.drop_shadow_width() as f64;
+            if shadow_width > 0.0 {
+                ctx.blurred_rect(
+                    rect,
+                    shadow_width,
+                    data.config
+                        .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+                );
+            } else {
+                ctx.stroke(
+                    rect.inflate(0.5, 0.5),
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                    1.0,
+                );
+            }
+            self.peek.paint(ctx, data, env);
+        }
         if data.rename.active {
             let rect = self.rename.layout_rect();
             let shadow_width = data.config.ui.drop_shadow_width() as f64;
@@ -2661,6 +3413,30 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
             );
             self.rename.paint(ctx, data, env);
         }
+        if data.color_picker.active {
+            let rect = self.color_picker.layout_rect();
+            let shadow_width = data.config.ui.drop_shadow_width() as f64;
+            if shadow_width > 0.0 {
+                ctx.blurred_rect(
+                    rect,
+                    shadow_width,
+                    data.config
+                        .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+                );
+            } else {
+                ctx.stroke(
+                    rect.inflate(0.5, 0.5),
+                    data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                    1.0,
+                );
+            }
+            ctx.fill(
+                rect,
+                data.config
+                    .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+            );
+            self.color_picker.paint(ctx, data, env);
+        }
         self.completion.paint(ctx, data, env);
         self.signature.paint(ctx, data, env);
         self.hover.paint(ctx, data, env);
@@ -10,8 +10,9 @@
     piet::{PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder},
     widget::{Click, ControllerHost},
     BoxConstraints, Command, Cursor, Data, Env, Event, EventCtx, FontWeight,
-    LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect,
-    RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt, WidgetId, WidgetPod,
+    InternalLifeCycle, LayoutCtx, LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx,
+    Point, Rect, RenderContext, Size, Target, UpdateCtx, Widget, WidgetExt,
+    WidgetId, WidgetPod,
 };
 use lapce_core::{mode::Mode, register::Clipboard};
 use lapce_data::{
@@ -24,7 +25,11 @@
     document::SystemClipboard,
     panel::PanelKind,
     proxy::LapceProxy,
-    terminal::{EventProxy, LapceTerminalData, LapceTerminalViewData},
+    terminal::{
+        EventProxy, LapceTerminalData, LapceTerminalViewData, TerminalSplitData,
+    },
+    terminal_layout::TerminalLayout,
+    terminal_link::TerminalLink,
 };
 use lapce_rpc::terminal::TermId;
 use smallvec::SmallVec;
@@ -40,6 +45,53 @@
 
 pub type TermConfig = alacritty_terminal::config::Config;
 
+/// The split of a terminal tab, with the splits nested in it the way the
+/// terminals are laid out
+fn terminal_split_widget(tab: &TerminalSplitData) -> LapceSplit {
+    layout_split_widget(tab, &tab.layout)
+        .unwrap_or_else(|| LapceSplit::new(tab.split_id))
+}
+
+fn layout_split_widget(
+    tab: &TerminalSplitData,
+    layout: &TerminalLayout,
+) -> Option<LapceSplit> {
+    let (widget_id, direction, children) = match layout {
+        TerminalLayout::Split {
+            widget_id,
+            direction,
+            children,
+        } => (*widget_id, *direction, children),
+        TerminalLayout::Terminal(_) => return None,
+    };
+    let mut split = LapceSplit::new(widget_id).direction(direction);
+    for child in children {
+        match child {
+            TerminalLayout::Terminal(term_id) => {
+                if let Some(term_data) = tab.terminals.get(term_id) {
+                    split = split.with_flex_child(
+                        LapceTerminalView::new(term_data).boxed(),
+                        Some(term_data.widget_id),
+                        1.0,
+                        true,
+                    );
+                }
+            }
+            TerminalLayout::Split { widget_id, .. } => {
+                if let Some(child_split) = layout_split_widget(tab, child) {
+                    split = split.with_flex_child(
+                        child_split.boxed(),
+                        Some(*widget_id),
+                        1.0,
+                        true,
+                    );
+                }
+            }
+        }
+    }
+    Some(split)
+}
+
 /// This struct represents the main body of the terminal, i.e. the part
 /// where the shell is presented.
 pub struct TerminalPanel {
@@ -55,17 +107,7 @@ pub fn new(data: &LapceTabData) -> Self {
             .tabs
             .iter()
             .map(|(term_tab_id, tab)| {
-                let mut split = LapceSplit::new(tab.split_id);
-                for (_, term_data) in tab.terminals.iter() {
-                    let term = LapceTerminalView::new(term_data);
-                    split = split.with_flex_child(
-                        term.boxed(),
-                        Some(term_data.widget_id),
-                        1.0,
-                        true,
-                    );
-                }
-                (*term_tab_id, WidgetPod::new(split))
+                (*term_tab_id, WidgetPod::new(terminal_split_widget(tab)))
             })
             .collect();
         let header = WidgetPod::new(LapceTerminalPanelHeader::new());
@@ -243,17 +285,10 @@ fn update(
                     if !self.tabs.contains_key(tab_id) {
                         changed = true;
                         ctx.children_changed();
-                        let mut split = LapceSplit::new(tab.split_id);
-                        for (_, term_data) in tab.terminals.iter() {
-                            let term = LapceTerminalView::new(term_data);
-                            split = split.with_flex_child(
-                                term.boxed(),
-                                Some(term_data.widget_id),
-                                1.0,
-                                true,
-                            );
-                        }
-                        self.tabs.insert(*tab_id, WidgetPod::new(split));
+                        self.tabs.insert(
+                            *tab_id,
+                            WidgetPod::new(terminal_split_widget(tab)),
+                        );
                     }
                 }
                 self.tabs.retain(|tab_id, _| {
@@ -742,6 +777,20 @@ fn event(
             }
             Event::MouseDown(mouse_event) => {
                 if !self.icon.layout_rect().contains(mouse_event.pos) {
+                    if mouse_event.count == 2 {
+                        ctx.submit_command(Command::new(
+                            LAPCE_COMMAND,
+                            LapceCommand {
+                                kind: CommandKind::Workbench(
+                                    LapceWorkbenchCommand::RenameTerminalTab,
+                                ),
+                                data: Some(serde_json::json!(self
+                                    .split_id
+                                    .to_usize())),
+                            },
+                            Target::Auto,
+                        ));
+                    }
                     if let Some(i) = data
                         .terminal
                         .tabs_order
@@ -780,14 +829,12 @@ fn update(
             .terminal
             .tabs
             .get(&self.split_id)
-            .and_then(|t| t.active_terminal())
-            .map(|t| &t.title);
+            .and_then(|t| t.title());
         let new_title = data
             .terminal
             .tabs
             .get(&self.split_id)
-            .and_then(|t| t.active_terminal())
-            .map(|t| &t.title);
+            .and_then(|t| t.title());
         if old_title != new_title {
             ctx.request_layout();
         }
@@ -804,8 +851,8 @@ fn layout(
             .terminal
             .tabs
             .get(&self.split_id)
-            .and_then(|t| t.active_terminal())
-            .map(|t| t.title.clone())
+            .and_then(|t| t.title())
+            .map(|title| title.to_string())
         {
             Some(title) => title,
             None => return Size::new(0.0, bc.max().height),
@@ -911,6 +958,8 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
 }
 
 pub struct LapceTerminalView {
+    term_id: TermId,
+    split_id: WidgetId,
     header: WidgetPod<LapceTabData, LapceTerminalHeader>,
     terminal: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
 }
@@ -920,6 +969,8 @@ pub fn new(data: &LapceTerminalData) -> Self {
         let header = LapceTerminalHeader::new(data);
         let terminal = LapcePadding::new(10.0, LapceTerminal::new(data));
         Self {
+            term_id: data.term_id,
+            split_id: data.split_id,
             header: WidgetPod::new(header),
             terminal: WidgetPod::new(terminal.boxed()),
         }
@@ -945,9 +996,24 @@ fn lifecycle(
         data: &LapceTabData,
         env: &Env,
     ) {
-        if let LifeCycle::HotChanged(is_hot) = event {
-            self.header.widget_mut().view_is_hot = *is_hot;
-            ctx.request_paint();
+        match event {
+            LifeCycle::HotChanged(is_hot) => {
+                self.header.widget_mut().view_is_hot = *is_hot;
+                ctx.request_paint();
+            }
+            LifeCycle::Size(_)
+            | LifeCycle::Internal(InternalLifeCycle::ParentWindowOrigin) => {
+                if let Some(terminal) = data
+                    .terminal
+                    .tabs
+                    .get(&self.split_id)
+                    .and_then(|split| split.terminals.get(&self.term_id))
+                {
+                    *terminal.layout_rect.borrow_mut() =
+                        ctx.size().to_rect().with_origin(ctx.window_origin());
+                }
+            }
+            _ => {}
         }
         self.header.lifecycle(ctx, event, data, env);
         self.terminal.lifecycle(ctx, event, data, env);
@@ -1171,6 +1237,9 @@ struct LapceTerminal {
     width: f64,
     height: f64,
     proxy: Arc<LapceProxy>,
+    /// The link under the mouse, which is underlined, and the first row of the
+    /// line it's on
+    hovered_link: Option<(Line, TerminalLink)>,
 }
 
 impl Drop for LapceTerminal {
@@ -1188,6 +1257,7 @@ pub fn new(data: &LapceTerminalData) -> Self {
             proxy: data.proxy.clone(),
             width: 0.0,
             height: 0.0,
+            hovered_link: None,
         }
     }
 
@@ -1211,6 +1281,59 @@ pub fn request_focus(&self, ctx: &mut EventCtx, data: &mut LapceTabData) {
         }
     }
 
+    /// The link at the position in the terminal, and the line it's on, if the
+    /// modifier opening links is held or isn't needed
+    fn link_at(
+        &self,
+        term: &Term<EventProxy>,
+        mouse_event: &MouseEvent,
+        data: &LapceTabData,
+    ) -> Option<(Line, TerminalLink)> {
+        let modifier = if cfg!(target_os = "macos") {
+            mouse_event.mods.meta()
+        } else {
+            mouse_event.mods.ctrl()
+        };
+        if data.config.terminal.open_links_with_modifier && !modifier {
+            return None;
+        }
+
+        let row_size = self.height / term.screen_lines() as f64;
+        let col_size = self.width / term.columns() as f64;
+        let offset = term.grid().display_offset();
+        let line = Line((mouse_event.pos.y / row_size) as i32 - offset as i32);
+        if line < term.topmost_line() || line > term.bottommost_line() {
+            return None;
+        }
+
+        // A link can wrap over several rows, so it's looked for in all the rows
+        // of the line the shell wrote
+        let columns = term.columns();
+        let wraps = |line: Line| {
+            term.grid()[line][Column(columns - 1)]
+                .flags
+                .contains(Flags::WRAPLINE)
+        };
+        let mut first = line;
+        while first > term.topmost_line() && wraps(Line(first.0 - 1)) {
+            first.0 -= 1;
+        }
+        let mut last = line;
+        while last < term.bottommost_line() && wraps(last) {
+            last.0 += 1;
+        }
+        let text = (first.0..=last.0)
+            .flat_map(|line| {
+                let row = &term.grid()[Line(line)];
+                (0..columns).map(move |column| row[Column(column)].c)
+            })
+            .collect::<String>();
+        let column = (line.0 - first.0) as usize * columns
+            + (mouse_event.pos.x / col_size) as usize;
+        let link = TerminalLink::at(&text, column)?;
+        Some((first, link))
+    }
+
     fn select(
         &self,
         term: &mut Term<EventProxy>,
@@ -1289,6 +1412,19 @@ fn event(
                         }
                     }
                 } else if mouse_event.button.is_left() {
+                    if mouse_event.count == 1 {
+                        if let Some((_, link)) =
+                            self.link_at(term, mouse_event, data)
+                        {
+                            ctx.submit_command(Command::new(
+                                LAPCE_UI_COMMAND,
+                                terminal.link_command(&link.target, &data.workspace),
+                                Target::Widget(data.id),
+                            ));
+                            term.selection = None;
+                            return;
+                        }
+                    }
                     match mouse_event.count {
                         2 => self.select(term, mouse_event, SelectionType::Semantic),
                         _ => {
@@ -1301,14 +1437,24 @@ fn event(
                 }
             }
             Event::MouseMove(mouse_event) => {
+                let terminal = old_terminal_data.clone();
+                let term = &mut terminal.raw.lock().term;
                 if mouse_event.buttons.has_left() {
-                    let terminal = old_terminal_data.clone();
-                    let term = &mut terminal.raw.lock().term;
                     self.select(term, mouse_event, SelectionType::Simple);
                     ctx.request_paint();
+                } else {
+                    let hovered_link = self.link_at(term, mouse_event, data);
+                    if hovered_link.is_some() {
+                        ctx.set_cursor(&Cursor::Pointer);
+                    }
+                    if hovered_link != self.hovered_link {
+                        self.hovered_link = hovered_link;
+                        ctx.request_paint();
+                    }
                 }
             }
             Event::Wheel(wheel_event) => {
+                self.hovered_link = None;
                 old_terminal_data.wheel_scroll(wheel_event.wheel_delta.y);
                 ctx.request_paint();
             }
@@ -1326,6 +1472,18 @@ fn event(
                 ctx.set_handled();
                 data.keypress = keypress.clone();
             }
+            Event::Timer(token) => {
+                let mut keypress = data.keypress.clone();
+                if Arc::make_mut(&mut keypress).sequence_timer_fired(
+                    ctx,
+                    *token,
+                    &mut term_data,
+                    env,
+                ) {
+                    ctx.set_handled();
+                    data.keypress = keypress;
+                }
+            }
             Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
                 let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
                 if let LapceUICommand::Focus = command {
@@ -1351,8 +1509,14 @@ fn lifecycle(
         _data: &LapceTabData,
         _env: &Env,
     ) {
-        if let LifeCycle::FocusChanged(_) = event {
-            ctx.request_paint();
+        match event {
+            LifeCycle::FocusChanged(_) => ctx.request_paint(),
+            LifeCycle::HotChanged(false) => {
+                if self.hovered_link.take().is_some() {
+                    ctx.request_paint();
+                }
+            }
+            _ => {}
         }
     }
 
@@ -1557,6 +1721,26 @@ fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
                 );
             }
         }
+        if let Some((line, link)) = self.hovered_link.as_ref() {
+            for (row, start, end) in link.rows(term.columns()) {
+                let y = (line.0 as f64
+                    + row as f64
+                    + content.display_offset as f64
+                    + 1.0)
+                    * line_height
+                    - 1.0;
+                ctx.fill(
+                    Rect::new(
+                        start as f64 * char_width,
+                        y,
+                        end as f64 * char_width,
+                        y + 1.0,
+                    ),
+                    data.config
+                        .get_color_unchecked(LapceTheme::TERMINAL_FOREGROUND),
+                );
+            }
+        }
         if data.find.visual {
             if let Some(search_string) = data.find.search_string.as_ref() {
                 if let Ok(dfas) = RegexSearch::new(&regex::escape(search_string)) {
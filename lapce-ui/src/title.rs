@@ -9,7 +9,7 @@
     RenderContext, Size, Target, TimerToken, Widget, WidgetExt, WidgetId, WidgetPod,
     WindowConfig, WindowState,
 };
-use lapce_core::{command::FocusCommand, meta};
+use lapce_core::command::FocusCommand;
 use lapce_data::{
     command::{
         CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
@@ -117,7 +117,7 @@ fn update_content(
         }
 
         let padding = 15.0;
-        x = self.update_remote(data, size, padding, x);
+        x = self.update_remote(data, piet_text, size, padding, x);
         x = self.update_source_control(data, piet_text, size, padding, x);
 
         let mut region = Region::EMPTY;
@@ -154,8 +154,9 @@ fn update_content(
     fn update_remote(
         &mut self,
         data: &LapceTabData,
+        piet_text: &mut PietText,
         size: Size,
-        _padding: f64,
+        padding: f64,
         x: f64,
     ) -> f64 {
         let command_rect = Size::ZERO.to_rect().with_origin(Point::new(x, 0.0));
@@ -172,6 +173,9 @@ fn update_remote(
                     ProxyStatus::Connecting => data
                         .config
                         .get_color_unchecked(LapceTheme::LAPCE_REMOTE_CONNECTING),
+                    ProxyStatus::Reconnecting { .. } => data
+                        .config
+                        .get_color_unchecked(LapceTheme::LAPCE_REMOTE_CONNECTING),
                     ProxyStatus::Connected => data
                         .config
                         .get_color_unchecked(LapceTheme::LAPCE_REMOTE_CONNECTED),
@@ -196,7 +200,28 @@ fn update_remote(
             ),
             None,
         ));
-        let x = x + remote_rect.width();
+        let mut x = x + remote_rect.width();
+        if let ProxyStatus::Reconnecting { .. } = *data.proxy_status {
+            let text_layout = piet_text
+                .new_text_layout("Reconnecting…")
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let point = Point::new(
+                x + padding,
+                (size.height - text_layout.size().height) / 2.0,
+            );
+            x += padding + text_layout.size().width.round() + padding;
+            self.text_layouts.push((text_layout, point));
+        }
         let command_rect =
             command_rect.with_size(Size::new(x - command_rect.x0, size.height));
 
@@ -372,6 +397,7 @@ fn update_settings(
             .latest_release
             .as_ref()
             .as_ref()
+            .filter(|r| r.is_update())
             .map(|r| r.version.as_str());
         let menu_items = vec![
             MenuKind::Item(MenuItem {
@@ -407,14 +433,14 @@ fn update_settings(
             }),
             MenuKind::Separator,
             MenuKind::Item(MenuItem {
-                desc: Some(if data.update_in_progress && latest_version.is_some() {
-                    format!("Update in progress ({}) ", latest_version.unwrap())
-                } else if latest_version.is_some()
-                    && latest_version != Some(*meta::VERSION)
-                {
-                    format!("Restart to update ({})", latest_version.unwrap())
-                } else {
-                    "No update available".to_string()
+                desc: Some(match (data.update_status, latest_version) {
+                    (Some(status), Some(version)) => {
+                        format!("{} ({version})", status.desc())
+                    }
+                    (None, Some(version)) => {
+                        format!("Restart to update ({version})")
+                    }
+                    _ => "No update available".to_string(),
                 }),
                 command: LapceCommand {
                     kind: CommandKind::Workbench(
@@ -422,9 +448,7 @@ fn update_settings(
                     ),
                     data: None,
                 },
-                enabled: latest_version.is_some()
-                    && latest_version != Some(*meta::VERSION)
-                    && !data.update_in_progress,
+                enabled: latest_version.is_some() && data.update_status.is_none(),
             }),
             MenuKind::Separator,
             MenuKind::Item(MenuItem {
@@ -436,7 +460,7 @@ fn update_settings(
                 enabled: true,
             }),
         ];
-        if latest_version.is_some() && latest_version != Some(*meta::VERSION) {
+        if latest_version.is_some() {
             let text_layout = piet_text
                 .new_text_layout("1")
                 .font(data.config.ui.font_family(), 10.0)
@@ -11,6 +11,8 @@
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::{LapceConfig, LapceTheme},
     data::{LapceTabData, LapceTabLens, LapceWindowData, LapceWorkspace},
+    explorer::ExplorerFilter,
+    title::{TitleTemplate, TitleValues},
 };
 use lapce_rpc::plugin::VoltID;
 
@@ -31,6 +33,8 @@ pub struct LapceWindow {
     pub mouse_down_cmd: Option<(Rect, Command)>,
     #[cfg(not(target_os = "macos"))]
     pub holding_click_rect: Option<Rect>,
+    /// The title the window was last given
+    title: String,
 }
 
 impl LapceWindow {
@@ -62,6 +66,7 @@ pub fn new(data: &mut LapceWindowData) -> Self {
             mouse_down_cmd: None,
             #[cfg(not(target_os = "macos"))]
             holding_click_rect: None,
+            title: String::new(),
         }
     }
 
@@ -90,7 +95,7 @@ pub fn new_tab(
             data.db.clone(),
             data.keypress.clone(),
             data.latest_release.clone(),
-            data.update_in_progress,
+            data.update_status,
             data.log_file.clone(),
             Some(current_panels),
             data.panel_orders.clone(),
@@ -330,6 +335,7 @@ fn event(
                         data.config = Arc::new(LapceConfig::load(
                             &LapceWorkspace::default(),
                             &[],
+                            None,
                         ));
                         for (_, tab) in data.tabs.iter_mut() {
                             let mut disabled_volts: Vec<VoltID> =
@@ -342,16 +348,43 @@ fn event(
                                     .into_iter()
                                     .collect(),
                             );
+                            let old_theme = tab.config.color_theme.name.clone();
                             tab.config = Arc::new(LapceConfig::load(
                                 &tab.workspace.clone(),
                                 &disabled_volts,
+                                tab.config.active_profile.as_deref(),
                             ));
+                            if tab.config.color_theme.name != old_theme {
+                                Arc::make_mut(&mut tab.hover).cancel();
+                                Arc::make_mut(&mut tab.signature).cancel();
+                            }
+                            Arc::make_mut(&mut tab.file_explorer).set_filter(
+                                ExplorerFilter::new(&tab.config.explorer),
+                            );
+                            for (_, doc) in tab.main_split.open_docs.iter_mut() {
+                                if doc.loaded() {
+                                    Arc::make_mut(doc).detect_indent(&tab.config);
+                                }
+                            }
                             tab.proxy
                                 .proxy_rpc
                                 .update_plugin_configs(data.config.plugins.clone());
                         }
                         Arc::make_mut(&mut data.keypress)
                             .update_keymaps(&data.config);
+                        // The invalid settings are left out rather than failing
+                        // the whole reload, so the user is told about them
+                        if let Some(tab) = data.tabs.get(&*data.active_id) {
+                            if let Some(alert) =
+                                LapceConfig::settings_alert(&tab.workspace)
+                            {
+                                ctx.submit_command(Command::new(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::ShowAlert(alert),
+                                    Target::Widget(tab.id),
+                                ));
+                            }
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::ReloadWindow => {
@@ -359,6 +392,8 @@ fn event(
 
                         let workspace = (*tab.workspace).clone();
                         self.new_tab(ctx, data, workspace, true);
+                        // Reopened with the state which may have been imported
+                        data.db.resume_saving();
                         return;
                     }
                     LapceUICommand::SetWorkspace(workspace) => {
@@ -378,6 +413,10 @@ fn event(
                                 theme,
                                 true,
                             );
+                            // The hover and signature contents were colored
+                            // with the old theme, so they are requested again
+                            Arc::make_mut(&mut tab.hover).cancel();
+                            Arc::make_mut(&mut tab.signature).cancel();
                         }
                         ctx.set_handled();
                     }
@@ -568,6 +607,14 @@ fn update(
             tab.update(ctx, data, env);
         }
 
+        // Follows the active editor and whether it has unsaved changes
+        let title = TitleTemplate::parse(&data.config.core.window_title)
+            .render(&TitleValues::new(tab));
+        if title != self.title {
+            ctx.window().set_title(&title);
+            self.title = title;
+        }
+
         if !old_data.latest_release.same(&data.latest_release) {
             ctx.request_layout();
         }